@@ -36,6 +36,7 @@ detect_arch() {
   case "$(uname -m)" in
     x86_64|amd64)  echo "x86_64"  ;;
     arm64|aarch64) echo "aarch64" ;;
+    armv7l|armv7|armhf) echo "armv7" ;;
     *)
       err "Unsupported architecture: $(uname -m)"
       exit 1
@@ -43,6 +44,15 @@ detect_arch() {
   esac
 }
 
+# Alpine and other musl-based distros can't run glibc builds.
+detect_libc() {
+  if [ -f /etc/alpine-release ] || (ldd --version 2>&1 | grep -qi musl); then
+    echo "musl"
+  else
+    echo "gnu"
+  fi
+}
+
 detect_install_dir() {
   if [ -n "${RAYCLAW_INSTALL_DIR:-}" ]; then
     echo "$RAYCLAW_INSTALL_DIR"
@@ -97,7 +107,13 @@ extract_asset_url() {
 
   case "$os" in
     darwin) os_regex="apple-darwin|darwin" ;;
-    linux)  os_regex="unknown-linux-gnu|unknown-linux-musl|linux" ;;
+    linux)
+      if [ "$(detect_libc)" = "musl" ]; then
+        os_regex="unknown-linux-musl(eabihf)?"
+      else
+        os_regex="unknown-linux-gnu(eabihf)?"
+      fi
+      ;;
     *)
       err "Unsupported OS for release matching: $os"
       return 1
@@ -107,6 +123,7 @@ extract_asset_url() {
   case "$arch" in
     x86_64)  arch_regex="x86_64|amd64" ;;
     aarch64) arch_regex="aarch64|arm64" ;;
+    armv7)   arch_regex="armv7" ;;
     *)
       err "Unsupported architecture for release matching: $arch"
       return 1
@@ -116,7 +133,7 @@ extract_asset_url() {
   printf '%s\n' "$release_json" \
     | grep -Eo 'https://[^"]+' \
     | grep '/releases/download/' \
-    | grep -E "/${BIN_NAME}-v?[0-9]+\.[0-9]+\.[0-9]+-.*(apple-darwin|unknown-linux-gnu|unknown-linux-musl|pc-windows-msvc)\.(tar\.gz|zip)$" \
+    | grep -E "/${BIN_NAME}-v?[0-9]+\.[0-9]+\.[0-9]+-.*(apple-darwin|unknown-linux-gnu|unknown-linux-musl|unknown-linux-gnueabihf|unknown-linux-musleabihf|pc-windows-msvc)\.(tar\.gz|zip)$" \
     | grep -Ei "(${arch_regex}).*(${os_regex})|(${os_regex}).*(${arch_regex})" \
     | head -n1
 }
@@ -6,6 +6,7 @@
 //! MVP scope: Claude Code support only, stdio transport.
 
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -16,6 +17,8 @@ use tokio::sync::{Mutex, RwLock};
 use tokio::time::Instant;
 use tracing::{debug, error, info, warn};
 
+use crate::text::floor_char_boundary;
+
 // ---------------------------------------------------------------------------
 // Config types — loaded from <data_root>/acp.json
 // ---------------------------------------------------------------------------
@@ -36,6 +39,30 @@ fn default_idle_timeout_secs() -> u64 {
     600
 }
 
+fn default_health_check_interval_secs() -> u64 {
+    30
+}
+
+fn default_permission_timeout_secs() -> u64 {
+    120
+}
+
+fn default_max_queue_depth() -> usize {
+    5
+}
+
+fn default_spawn_retries() -> u32 {
+    2
+}
+
+fn default_spawn_backoff_ms() -> u64 {
+    1000
+}
+
+fn default_first_spawn_timeout_secs() -> u64 {
+    180
+}
+
 fn default_launch() -> String {
     "npx".to_string()
 }
@@ -51,15 +78,28 @@ pub struct AcpAgentConfig {
     #[serde(default = "default_mode")]
     pub mode: String,
 
-    /// Launch method: "npx" | "binary" | "uvx"
+    /// Launch method: "npx" | "binary" | "uvx" | "remote"
     #[serde(default = "default_launch")]
     pub launch: String,
 
     /// Executable or package name.
     /// npx: package spec (e.g. "@anthropic-ai/claude-code@latest")
     /// binary: absolute path to executable
+    /// remote: unused
+    #[serde(default)]
     pub command: String,
 
+    /// Endpoint of a `remote` agent already running elsewhere:
+    /// `ws://` / `wss://` (one JSON-RPC message per text frame) or
+    /// `tcp://host:port` (newline-delimited JSON-RPC)
+    #[serde(default)]
+    pub url: Option<String>,
+
+    /// Extra headers sent with the WebSocket handshake of a `remote` agent
+    /// (e.g. `Authorization`)
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+
     #[serde(default)]
     pub args: Vec<String>,
 
@@ -78,6 +118,16 @@ pub struct AcpAgentConfig {
     /// On non-Linux platforms, limits are logged and silently ignored.
     #[serde(default, alias = "resourceLimits")]
     pub resource_limits: Option<ResourceLimits>,
+
+    /// Rules answering this agent's permission requests before falling back
+    /// to `auto_approve` or interactive approval
+    #[serde(default, alias = "permissionPolicy")]
+    pub policy: Option<AcpPermissionPolicy>,
+
+    /// Model the agent runs, used to look up `model_prices` when the agent
+    /// reports tokens but no cost. Defaults to the agent ID.
+    #[serde(default)]
+    pub model: Option<String>,
 }
 
 /// Resource limits enforced via cgroups v2 on Linux.
@@ -112,10 +162,61 @@ pub struct AcpConfig {
     pub max_per_agent: usize,
 
     /// Idle timeout in seconds. Sessions with no prompt activity for this
-    /// duration are automatically reaped. 0 disables the reaper.
-    #[serde(default = "default_idle_timeout_secs", alias = "idleTimeoutSecs")]
+    /// duration are ended and their bound chats told. 0 disables the reaper.
+    /// Also accepted as `session_idle_ttl_secs`.
+    #[serde(
+        default = "default_idle_timeout_secs",
+        alias = "idleTimeoutSecs",
+        alias = "session_idle_ttl_secs",
+        alias = "sessionIdleTtlSecs"
+    )]
     pub idle_timeout_secs: u64,
 
+    /// How often (in seconds) the health monitor checks that agent processes
+    /// are still running. Dead agents mark their session `Crashed`.
+    /// 0 disables the monitor.
+    #[serde(
+        default = "default_health_check_interval_secs",
+        alias = "healthCheckIntervalSecs"
+    )]
+    pub health_check_interval_secs: u64,
+
+    /// Respawn crashed agents as soon as the health monitor notices them,
+    /// instead of waiting for the next prompt to do it.
+    #[serde(default, alias = "autoRespawn")]
+    pub auto_respawn: bool,
+
+    /// How long to wait for a chat user to answer a permission request when
+    /// auto-approve is off. Unanswered requests are cancelled.
+    #[serde(
+        default = "default_permission_timeout_secs",
+        alias = "permissionTimeoutSecs"
+    )]
+    pub permission_timeout_secs: u64,
+
+    /// How many prompts may wait for a session that is busy with another.
+    /// Prompts beyond this are rejected; 0 rejects every prompt sent while
+    /// the session is working.
+    #[serde(default = "default_max_queue_depth", alias = "maxQueueDepth")]
+    pub max_queue_depth: usize,
+
+    /// How many times to retry an agent that fails to start (handshake
+    /// timeout or crash on start). A missing binary is never retried.
+    #[serde(default = "default_spawn_retries", alias = "spawnRetries")]
+    pub spawn_retries: u32,
+
+    /// Delay before the first spawn retry; doubles with each further retry.
+    #[serde(default = "default_spawn_backoff_ms", alias = "spawnBackoffMs")]
+    pub spawn_backoff_ms: u64,
+
+    /// Handshake timeout for the first spawn of an npx/uvx agent, which may
+    /// have to download its package. Later spawns use the request timeout.
+    #[serde(
+        default = "default_first_spawn_timeout_secs",
+        alias = "firstSpawnTimeoutSecs"
+    )]
+    pub first_spawn_timeout_secs: u64,
+
     /// Configured agents, keyed by name (e.g. "claude", "opencode")
     #[serde(default, alias = "acpAgents")]
     pub agents: HashMap<String, AcpAgentConfig>,
@@ -135,6 +236,13 @@ impl Default for AcpConfig {
             max_sessions: default_max_sessions(),
             max_per_agent: default_max_per_agent(),
             idle_timeout_secs: default_idle_timeout_secs(),
+            health_check_interval_secs: default_health_check_interval_secs(),
+            auto_respawn: false,
+            permission_timeout_secs: default_permission_timeout_secs(),
+            max_queue_depth: default_max_queue_depth(),
+            spawn_retries: default_spawn_retries(),
+            spawn_backoff_ms: default_spawn_backoff_ms(),
+            first_spawn_timeout_secs: default_first_spawn_timeout_secs(),
             agents: HashMap::new(),
             acp_api_token: None,
         }
@@ -216,18 +324,461 @@ struct JsonRpcError {
 const DEFAULT_REQUEST_TIMEOUT_SECS: u64 = 30;
 const ACP_PROTOCOL_VERSION: u32 = 1;
 
-struct AcpConnectionInner {
-    stdin: tokio::process::ChildStdin,
-    stdout: BufReader<tokio::process::ChildStdout>,
-    _child: Child,
-    next_id: u64,
+/// Capacity of the broadcast channel carrying agent notifications.
+const AGENT_MESSAGE_BUFFER: usize = 1024;
+
+/// A message initiated by the agent: a notification (e.g. `session/update`),
+/// or — when `id` is set — a request such as `session/request_permission`
+/// that must be answered with [`AcpConnection::respond`].
+#[derive(Debug, Clone)]
+pub struct AcpAgentMessage {
+    pub id: Option<serde_json::Value>,
+    pub method: String,
+    pub params: Option<serde_json::Value>,
+}
+
+impl AcpAgentMessage {
+    /// The ACP session this message belongs to, if the agent included one.
+    pub fn session_id(&self) -> Option<&str> {
+        self.params
+            .as_ref()
+            .and_then(|p| p.get("sessionId"))
+            .and_then(|v| v.as_str())
+    }
+}
+
+/// In-flight requests keyed by JSON-RPC id, resolved by the reader task.
+type PendingRequests =
+    Arc<std::sync::Mutex<HashMap<u64, tokio::sync::oneshot::Sender<JsonRpcMessage>>>>;
+
+/// Running prompts that agent requests are delivered to, see [`RequestRoute`].
+type RequestRoutes = Arc<std::sync::Mutex<Vec<RequestRoute>>>;
+
+/// Where agent requests for one running prompt go. Unlike notifications,
+/// requests must each get an answer, so they travel over an unbounded
+/// channel rather than the broadcast, which drops messages when a
+/// subscriber falls behind.
+struct RequestRoute {
+    key: u64,
+    /// Session of the prompt; `None` takes requests for any session
+    session_id: Option<String>,
+    tx: tokio::sync::mpsc::UnboundedSender<AcpAgentMessage>,
+}
+
+/// Hand `msg` to the first running prompt whose session it belongs to.
+/// Gives the message back when there is none.
+fn route_request(routes: &RequestRoutes, msg: AcpAgentMessage) -> Result<(), AcpAgentMessage> {
+    let routes = routes.lock().unwrap();
+    let route = routes.iter().find(
+        |route| match (route.session_id.as_deref(), msg.session_id()) {
+            (Some(ours), Some(theirs)) => ours == theirs,
+            _ => true,
+        },
+    );
+    match route {
+        Some(route) => route.tx.send(msg).map_err(|e| e.0),
+        None => Err(msg),
+    }
+}
+
+/// Removes a prompt's [`RequestRoute`] when the prompt ends.
+struct RequestRouteGuard<'a> {
+    routes: &'a RequestRoutes,
+    key: u64,
+}
+
+impl Drop for RequestRouteGuard<'_> {
+    fn drop(&mut self) {
+        self.routes
+            .lock()
+            .unwrap()
+            .retain(|route| route.key != self.key);
+    }
 }
 
 /// A connection to a single ACP agent process over stdio JSON-RPC.
+///
+/// A background reader task owns the agent's stdout: responses are routed to
+/// the caller waiting on the matching request id, agent requests to the
+/// running prompt of their session, and everything else is broadcast to all
+/// subscribers. Requests can therefore be issued concurrently, e.g.
+/// `session/cancel` while a prompt is running.
 pub struct AcpConnection {
     agent_name: String,
-    inner: Mutex<AcpConnectionInner>,
+    transport: SharedTransport,
+    /// The agent process; `None` for remote agents
+    child: Mutex<Option<Child>>,
+    next_id: AtomicU64,
+    pending: PendingRequests,
+    /// Set by the reader task once the agent's stdout closes
+    closed: Arc<AtomicBool>,
+    agent_messages: tokio::sync::broadcast::Sender<AcpAgentMessage>,
+    request_routes: RequestRoutes,
+    reader: tokio::task::JoinHandle<()>,
     request_timeout: Duration,
+    /// Canonical session workspace; `fs/*` requests must stay inside it
+    workspace: Option<std::path::PathBuf>,
+    /// Client-side terminals created by the agent, keyed by terminal ID
+    terminals: std::sync::Mutex<HashMap<String, Arc<AcpTerminal>>>,
+    /// Set once the user answers "always" to a terminal permission request
+    terminals_always_allowed: AtomicBool,
+    /// Set once the user answers "always" to a file write permission request
+    writes_always_allowed: AtomicBool,
+    /// Per-agent permission rules, checked before `auto_approve`
+    policy: Option<AcpPermissionPolicy>,
+    /// Whether the agent advertised `promptCapabilities.image` at initialize
+    accepts_images: AtomicBool,
+    /// Whether the agent advertised `loadSession` at initialize
+    supports_load_session: AtomicBool,
+    /// Question from a `session/request_input` request, waiting for an answer
+    pending_question: std::sync::Mutex<Option<PendingQuestion>>,
+}
+
+/// An agent question and the channel its answer is delivered on.
+struct PendingQuestion {
+    text: String,
+    answer_tx: tokio::sync::oneshot::Sender<String>,
+}
+
+/// Outgoing half of an agent connection. Incoming messages are read as
+/// newline-delimited JSON by [`run_reader`], whatever the transport.
+#[async_trait::async_trait]
+pub trait AcpTransport: Send + Sync {
+    /// Send one serialized JSON-RPC message.
+    async fn send_frame(&self, frame: &str) -> std::io::Result<()>;
+
+    /// Close the connection (no-op for pipes, which close with the process).
+    async fn close(&self) {}
+}
+
+/// Writes frames as lines to an agent's stdin or a TCP stream.
+struct LineTransport<W> {
+    writer: Mutex<W>,
+}
+
+#[async_trait::async_trait]
+impl<W: tokio::io::AsyncWrite + Send + Unpin> AcpTransport for LineTransport<W> {
+    async fn send_frame(&self, frame: &str) -> std::io::Result<()> {
+        let mut writer = self.writer.lock().await;
+        writer.write_all(frame.as_bytes()).await?;
+        writer.write_all(b"\n").await?;
+        writer.flush().await
+    }
+
+    async fn close(&self) {
+        let _ = self.writer.lock().await.shutdown().await;
+    }
+}
+
+type WsStream =
+    tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>;
+
+/// Sends each frame as one WebSocket text message.
+struct WebSocketTransport {
+    sink: Mutex<futures_util::stream::SplitSink<WsStream, tokio_tungstenite::tungstenite::Message>>,
+}
+
+#[async_trait::async_trait]
+impl AcpTransport for WebSocketTransport {
+    async fn send_frame(&self, frame: &str) -> std::io::Result<()> {
+        use futures_util::SinkExt;
+        self.sink
+            .lock()
+            .await
+            .send(tokio_tungstenite::tungstenite::Message::Text(
+                frame.to_string(),
+            ))
+            .await
+            .map_err(std::io::Error::other)
+    }
+
+    async fn close(&self) {
+        use futures_util::SinkExt;
+        let _ = self.sink.lock().await.close().await;
+    }
+}
+
+/// The agent transport, shared with tasks that answer requests asynchronously.
+type SharedTransport = Arc<dyn AcpTransport>;
+
+/// Byte stream of newline-delimited JSON-RPC messages from the agent.
+type AgentReader = Box<dyn tokio::io::AsyncRead + Send + Unpin>;
+
+/// Serialize a JSON-RPC message and send it to the agent as one frame.
+async fn write_frame<T: Serialize>(
+    transport: &SharedTransport,
+    agent_name: &str,
+    message: &T,
+) -> Result<(), String> {
+    let json = serde_json::to_string(message).map_err(|e| e.to_string())?;
+    transport
+        .send_frame(&json)
+        .await
+        .map_err(|e| format!("ACP [{agent_name}] write error: {e}"))
+}
+
+/// Connect to a `remote` agent. Returns its transport and a reader of its
+/// messages; WebSocket text frames are turned into lines for [`run_reader`].
+async fn connect_remote(
+    agent_name: &str,
+    config: &AcpAgentConfig,
+    connect_timeout: Duration,
+) -> Result<(SharedTransport, AgentReader), AcpSpawnError> {
+    let url = config
+        .url
+        .as_deref()
+        .map(str::trim)
+        .filter(|u| !u.is_empty())
+        .ok_or_else(|| {
+            AcpSpawnError::Other(format!(
+                "ACP agent '{agent_name}': launch \"remote\" requires a url"
+            ))
+        })?;
+    let unreachable = |reason: String| AcpSpawnError::Unreachable {
+        agent: agent_name.to_string(),
+        url: url.to_string(),
+        reason,
+    };
+    info!("ACP: connecting to remote agent '{agent_name}' at {url}");
+
+    if let Some(addr) = url.strip_prefix("tcp://") {
+        let stream = tokio::time::timeout(connect_timeout, tokio::net::TcpStream::connect(addr))
+            .await
+            .map_err(|_| unreachable(format!("connect timed out after {connect_timeout:?}")))?
+            .map_err(|e| unreachable(e.to_string()))?;
+        let (read, write) = stream.into_split();
+        let transport = LineTransport {
+            writer: Mutex::new(write),
+        };
+        return Ok((Arc::new(transport), Box::new(read)));
+    }
+    if !(url.starts_with("ws://") || url.starts_with("wss://")) {
+        return Err(AcpSpawnError::Other(format!(
+            "ACP agent '{agent_name}': unsupported remote url '{url}' (expected ws://, wss:// or tcp://)"
+        )));
+    }
+
+    use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+    let mut request = url
+        .into_client_request()
+        .map_err(|e| AcpSpawnError::Other(format!("ACP agent '{agent_name}': {e}")))?;
+    for (name, value) in &config.headers {
+        let name = tokio_tungstenite::tungstenite::http::HeaderName::from_bytes(name.as_bytes());
+        let value = tokio_tungstenite::tungstenite::http::HeaderValue::from_str(value);
+        match (name, value) {
+            (Ok(name), Ok(value)) => {
+                request.headers_mut().insert(name, value);
+            }
+            _ => warn!("ACP agent '{agent_name}': skipping invalid header"),
+        }
+    }
+    let (ws, _) = tokio::time::timeout(connect_timeout, tokio_tungstenite::connect_async(request))
+        .await
+        .map_err(|_| unreachable(format!("connect timed out after {connect_timeout:?}")))?
+        .map_err(|e| unreachable(e.to_string()))?;
+
+    use futures_util::StreamExt;
+    let (sink, mut stream) = ws.split();
+    let (mut lines_tx, lines_rx) = tokio::io::duplex(64 * 1024);
+    let name = agent_name.to_string();
+    tokio::spawn(async move {
+        use tokio_tungstenite::tungstenite::Message as WsMessage;
+        while let Some(msg) = stream.next().await {
+            let frame = match msg {
+                Ok(WsMessage::Text(text)) => text.into_bytes(),
+                Ok(WsMessage::Binary(bytes)) => bytes,
+                Ok(WsMessage::Close(_)) => break,
+                Ok(_) => continue,
+                Err(e) => {
+                    warn!("ACP [{name}] websocket error: {e}");
+                    break;
+                }
+            };
+            if lines_tx.write_all(&frame).await.is_err() || lines_tx.write_all(b"\n").await.is_err()
+            {
+                break;
+            }
+        }
+        // Dropping the writer ends the reader, marking the connection closed
+    });
+    let transport = WebSocketTransport {
+        sink: Mutex::new(sink),
+    };
+    Ok((Arc::new(transport), Box::new(lines_rx)))
+}
+
+impl Drop for AcpConnection {
+    fn drop(&mut self) {
+        self.reader.abort();
+    }
+}
+
+/// Upper bound on text merged into one coalesced `agent_message_chunk`, so
+/// a very chatty agent still streams progress in reasonable pieces.
+const MAX_COALESCED_CHUNK_BYTES: usize = 16 * 1024;
+
+/// Parse one stdout line: a single JSON-RPC message or a batch (array) of them.
+fn parse_frame(line: &str) -> Option<Vec<JsonRpcMessage>> {
+    if line.starts_with('[') {
+        serde_json::from_str(line).ok()
+    } else {
+        serde_json::from_str(line).ok().map(|msg| vec![msg])
+    }
+}
+
+/// Mutable text of an `agent_message_chunk` notification, if `msg` is one.
+fn message_chunk_text(msg: &mut AcpAgentMessage) -> Option<&mut String> {
+    if msg.id.is_some() || msg.method != "session/update" {
+        return None;
+    }
+    let update = msg.params.as_mut()?.get_mut("update")?;
+    let kind = update
+        .get("sessionUpdate")
+        .or_else(|| update.get("type"))
+        .and_then(|t| t.as_str())?;
+    if kind != "agent_message_chunk" && kind != "AgentMessageChunk" {
+        return None;
+    }
+    match update.get_mut("content")?.get_mut("text")? {
+        serde_json::Value::String(text) => Some(text),
+        _ => None,
+    }
+}
+
+/// Append `next`'s chunk text to `held` if both are message chunks of the
+/// same session and the result stays under the coalescing limit.
+fn merge_chunk(held: &mut AcpAgentMessage, next: &mut AcpAgentMessage) -> bool {
+    if held.session_id() != next.session_id() {
+        return false;
+    }
+    let (Some(held_text), Some(next_text)) = (message_chunk_text(held), message_chunk_text(next))
+    else {
+        return false;
+    };
+    if held_text.len() + next_text.len() > MAX_COALESCED_CHUNK_BYTES {
+        return false;
+    }
+    held_text.push_str(next_text);
+    true
+}
+
+/// Read JSON-RPC lines from the agent until stdout closes, dispatching
+/// responses to their waiters, requests to the prompt they belong to, and
+/// broadcasting everything else (including requests no prompt takes).
+///
+/// Consecutive `agent_message_chunk` notifications that are already buffered
+/// are merged into one before broadcasting, so agents that emit thousands of
+/// tiny chunks don't cost a broadcast and an update parse per token. A held
+/// chunk is always flushed before any other message, keeping order intact.
+async fn run_reader(
+    agent_name: String,
+    stdout: AgentReader,
+    pending: PendingRequests,
+    closed: Arc<AtomicBool>,
+    agent_messages: tokio::sync::broadcast::Sender<AcpAgentMessage>,
+    request_routes: RequestRoutes,
+) {
+    let mut reader = BufReader::new(stdout);
+    let mut line = String::new();
+    let mut held_chunk: Option<AcpAgentMessage> = None;
+    // No subscribers just means nobody is listening right now
+    let broadcast = |msg: AcpAgentMessage| {
+        let _ = agent_messages.send(msg);
+    };
+    loop {
+        // Don't sit on a chunk while waiting for more output
+        if reader.buffer().is_empty() {
+            if let Some(chunk) = held_chunk.take() {
+                broadcast(chunk);
+            }
+        }
+
+        line.clear();
+        match reader.read_line(&mut line).await {
+            Ok(0) => break,
+            Err(e) => {
+                warn!("ACP [{agent_name}] read error: {e}");
+                break;
+            }
+            Ok(_) => {}
+        }
+
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+
+        let Some(frame) = parse_frame(trimmed) else {
+            debug!(
+                "ACP [{agent_name}] ignoring non-JSON line: {}",
+                &trimmed[..floor_char_boundary(trimmed, 200)]
+            );
+            continue;
+        };
+
+        for msg in frame {
+            if msg.is_response() {
+                if let Some(chunk) = held_chunk.take() {
+                    broadcast(chunk);
+                }
+                let waiter = msg
+                    .id
+                    .as_ref()
+                    .and_then(|v| v.as_u64())
+                    .and_then(|id| pending.lock().unwrap().remove(&id));
+                match waiter {
+                    Some(tx) => {
+                        let _ = tx.send(msg);
+                    }
+                    None => debug!(
+                        "ACP [{agent_name}] response for unknown request id {:?}",
+                        msg.id
+                    ),
+                }
+                continue;
+            }
+            if !(msg.is_notification() || msg.is_request()) {
+                continue;
+            }
+
+            let mut agent_msg = AcpAgentMessage {
+                id: msg.id,
+                method: msg.method.unwrap_or_default(),
+                params: msg.params,
+            };
+            if message_chunk_text(&mut agent_msg).is_some() {
+                if held_chunk
+                    .as_mut()
+                    .is_some_and(|held| merge_chunk(held, &mut agent_msg))
+                {
+                    continue;
+                }
+                if let Some(chunk) = held_chunk.replace(agent_msg) {
+                    broadcast(chunk);
+                }
+                continue;
+            }
+
+            if let Some(chunk) = held_chunk.take() {
+                broadcast(chunk);
+            }
+            if agent_msg.id.is_some() {
+                if let Err(unrouted) = route_request(&request_routes, agent_msg) {
+                    broadcast(unrouted);
+                }
+                continue;
+            }
+            broadcast(agent_msg);
+        }
+    }
+
+    if let Some(chunk) = held_chunk.take() {
+        broadcast(chunk);
+    }
+    debug!("ACP [{agent_name}] reader stopped");
+    // Dropping the waiters fails every in-flight request immediately
+    closed.store(true, Ordering::SeqCst);
+    pending.lock().unwrap().clear();
 }
 
 /// Build the OS command for spawning an agent process.
@@ -238,7 +789,7 @@ fn build_spawn_command(config: &AcpAgentConfig, workspace: Option<&str>) -> Comm
         _ => (&config.command, vec![]),
     };
 
-    let mut cmd = Command::new(program);
+    let mut cmd = Command::new(crate::platform::program(program));
     for arg in &base_args {
         cmd.arg(arg);
     }
@@ -367,6 +918,52 @@ fn cleanup_cgroup(cgroup_path: &str) {
     }
 }
 
+/// Why an ACP agent failed to start.
+#[derive(Debug, thiserror::Error)]
+pub enum AcpSpawnError {
+    /// The launcher or binary could not be executed at all.
+    #[error("ACP agent '{agent}': '{program}' not found or not executable: {reason}")]
+    BinaryMissing {
+        agent: String,
+        program: String,
+        reason: String,
+    },
+    /// The process started but did not answer `initialize` in time.
+    #[error("ACP agent '{agent}': no initialize response within {timeout:?}")]
+    HandshakeTimeout { agent: String, timeout: Duration },
+    /// The process exited (or closed stdout) during the handshake.
+    #[error("ACP agent '{agent}' exited during startup ({status})")]
+    CrashedOnStart { agent: String, status: String },
+    /// A remote agent could not be connected to.
+    #[error("ACP agent '{agent}': cannot connect to {url}: {reason}")]
+    Unreachable {
+        agent: String,
+        url: String,
+        reason: String,
+    },
+    #[error("{0}")]
+    Other(String),
+}
+
+impl AcpSpawnError {
+    /// Whether another attempt may succeed. A missing binary won't appear
+    /// by itself; timeouts, early exits and unreachable remotes are often
+    /// transient (npx downloads, registry hiccups, containers starting up).
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            AcpSpawnError::HandshakeTimeout { .. }
+                | AcpSpawnError::CrashedOnStart { .. }
+                | AcpSpawnError::Unreachable { .. }
+        )
+    }
+}
+
+/// Delay before retry number `attempt` (1-based): `base`, doubling each time.
+fn spawn_backoff(base: Duration, attempt: u32) -> Duration {
+    base.saturating_mul(1 << attempt.saturating_sub(1).min(10))
+}
+
 impl AcpConnection {
     /// Spawn an agent process and perform the ACP initialization handshake.
     pub async fn spawn(
@@ -375,17 +972,132 @@ impl AcpConnection {
         workspace: Option<&str>,
         request_timeout: Duration,
     ) -> Result<Self, String> {
-        let mut cmd = build_spawn_command(config, workspace);
+        Self::try_spawn(
+            agent_name,
+            config,
+            workspace,
+            request_timeout,
+            request_timeout,
+        )
+        .await
+        .map_err(|e| e.to_string())
+    }
 
-        info!(
-            "ACP: spawning agent '{agent_name}' ({} {})",
-            config.launch, config.command
-        );
+    /// Spawn an agent, allowing `handshake_timeout` for `initialize`, and
+    /// classify failures so callers can decide whether to retry.
+    pub async fn try_spawn(
+        agent_name: &str,
+        config: &AcpAgentConfig,
+        workspace: Option<&str>,
+        request_timeout: Duration,
+        handshake_timeout: Duration,
+    ) -> Result<Self, AcpSpawnError> {
+        let mut conn = if config.launch == "remote" {
+            let (transport, reader) = connect_remote(agent_name, config, handshake_timeout).await?;
+            Self::from_parts(agent_name, transport, reader, None, handshake_timeout)
+        } else {
+            let mut cmd = build_spawn_command(config, workspace);
 
-        let mut child = cmd
-            .spawn()
-            .map_err(|e| format!("Failed to spawn ACP agent '{agent_name}': {e}"))?;
+            info!(
+                "ACP: spawning agent '{agent_name}' ({} {})",
+                config.launch, config.command
+            );
+
+            let mut child = cmd.spawn().map_err(|e| AcpSpawnError::BinaryMissing {
+                agent: agent_name.to_string(),
+                program: cmd.as_std().get_program().to_string_lossy().to_string(),
+                reason: e.to_string(),
+            })?;
+
+            // Spawn a task to drain stderr to tracing::debug
+            if let Some(stderr) = child.stderr.take() {
+                let name = agent_name.to_string();
+                tokio::spawn(async move {
+                    let mut reader = BufReader::new(stderr);
+                    let mut line = String::new();
+                    loop {
+                        line.clear();
+                        match reader.read_line(&mut line).await {
+                            Ok(0) | Err(_) => break,
+                            Ok(_) => {
+                                let trimmed = line.trim();
+                                if !trimmed.is_empty() {
+                                    debug!("ACP [{name}] stderr: {trimmed}");
+                                }
+                            }
+                        }
+                    }
+                });
+            }
+            Self::from_child(agent_name, child, handshake_timeout).map_err(AcpSpawnError::Other)?
+        };
+        conn.workspace = workspace
+            .or(config.workspace.as_deref())
+            .and_then(|ws| crate::platform::canonicalize(ws).ok());
+        conn.policy = config.policy.clone();
+
+        // Perform initialization handshake
+        if let Err(e) = conn.initialize().await {
+            return Err(conn.classify_startup_failure(e, handshake_timeout).await);
+        }
+        conn.request_timeout = request_timeout;
+
+        Ok(conn)
+    }
+
+    /// Turn a failed handshake into an [`AcpSpawnError`], killing the
+    /// process if it is still hanging around.
+    async fn classify_startup_failure(&self, error: String, timeout: Duration) -> AcpSpawnError {
+        let agent = self.agent_name.clone();
+        let mut child = self.child.lock().await;
+        let Some(child) = child.as_mut() else {
+            self.transport.close().await;
+            if self.closed.load(Ordering::SeqCst) {
+                return AcpSpawnError::CrashedOnStart {
+                    agent,
+                    status: "remote closed the connection".to_string(),
+                };
+            }
+            return if error.contains("timed out") {
+                AcpSpawnError::HandshakeTimeout { agent, timeout }
+            } else {
+                AcpSpawnError::Other(error)
+            };
+        };
+        if let Ok(Some(status)) = child.try_wait() {
+            return AcpSpawnError::CrashedOnStart {
+                agent,
+                status: status.to_string(),
+            };
+        }
+        if self.closed.load(Ordering::SeqCst) {
+            // Closed stdout; give the exit status a moment to land
+            let status = match tokio::time::timeout(Duration::from_millis(500), child.wait()).await
+            {
+                Ok(Ok(status)) => status.to_string(),
+                _ => {
+                    let _ = child.kill().await;
+                    let _ = child.wait().await;
+                    "closed stdout".to_string()
+                }
+            };
+            return AcpSpawnError::CrashedOnStart { agent, status };
+        }
+        let _ = child.kill().await;
+        let _ = child.wait().await;
+        if error.contains("timed out") {
+            AcpSpawnError::HandshakeTimeout { agent, timeout }
+        } else {
+            AcpSpawnError::Other(error)
+        }
+    }
 
+    /// Wrap an already-spawned agent process and start the stdout reader task.
+    fn from_child(
+        agent_name: &str,
+        mut child: Child,
+        request_timeout: Duration,
+    ) -> Result<Self, String> {
         let stdin = child
             .stdin
             .take()
@@ -394,43 +1106,59 @@ impl AcpConnection {
             .stdout
             .take()
             .ok_or_else(|| format!("ACP agent '{agent_name}': failed to capture stdout"))?;
+        let transport = LineTransport {
+            writer: Mutex::new(stdin),
+        };
+        Ok(Self::from_parts(
+            agent_name,
+            Arc::new(transport),
+            Box::new(stdout),
+            Some(child),
+            request_timeout,
+        ))
+    }
 
-        // Spawn a task to drain stderr to tracing::debug
-        if let Some(stderr) = child.stderr.take() {
-            let name = agent_name.to_string();
-            tokio::spawn(async move {
-                let mut reader = BufReader::new(stderr);
-                let mut line = String::new();
-                loop {
-                    line.clear();
-                    match reader.read_line(&mut line).await {
-                        Ok(0) | Err(_) => break,
-                        Ok(_) => {
-                            let trimmed = line.trim();
-                            if !trimmed.is_empty() {
-                                debug!("ACP [{name}] stderr: {trimmed}");
-                            }
-                        }
-                    }
-                }
-            });
-        }
-
-        let conn = AcpConnection {
+    /// Wrap a connected transport and start the reader task.
+    fn from_parts(
+        agent_name: &str,
+        transport: SharedTransport,
+        reader: AgentReader,
+        child: Option<Child>,
+        request_timeout: Duration,
+    ) -> Self {
+        let pending: PendingRequests = Arc::new(std::sync::Mutex::new(HashMap::new()));
+        let closed = Arc::new(AtomicBool::new(false));
+        let (agent_messages, _) = tokio::sync::broadcast::channel(AGENT_MESSAGE_BUFFER);
+        let request_routes: RequestRoutes = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let reader = tokio::spawn(run_reader(
+            agent_name.to_string(),
+            reader,
+            pending.clone(),
+            closed.clone(),
+            agent_messages.clone(),
+            request_routes.clone(),
+        ));
+
+        AcpConnection {
             agent_name: agent_name.to_string(),
-            inner: Mutex::new(AcpConnectionInner {
-                stdin,
-                stdout: BufReader::new(stdout),
-                _child: child,
-                next_id: 1,
-            }),
+            transport,
+            child: Mutex::new(child),
+            next_id: AtomicU64::new(1),
+            pending,
+            closed,
+            agent_messages,
+            request_routes,
+            reader,
             request_timeout,
-        };
-
-        // Perform initialization handshake
-        conn.initialize().await?;
-
-        Ok(conn)
+            workspace: None,
+            terminals: std::sync::Mutex::new(HashMap::new()),
+            terminals_always_allowed: AtomicBool::new(false),
+            writes_always_allowed: AtomicBool::new(false),
+            policy: None,
+            accepts_images: AtomicBool::new(false),
+            supports_load_session: AtomicBool::new(false),
+            pending_question: std::sync::Mutex::new(None),
+        }
     }
 
     /// Send the `initialize` request and `notifications/initialized` notification.
@@ -439,10 +1167,10 @@ impl AcpConnection {
             "protocolVersion": ACP_PROTOCOL_VERSION,
             "clientCapabilities": {
                 "fs": {
-                    "readTextFile": false,
-                    "writeTextFile": false
+                    "readTextFile": true,
+                    "writeTextFile": true
                 },
-                "terminal": false
+                "terminal": true
             },
             "clientInfo": {
                 "name": "rayclaw",
@@ -466,9 +1194,20 @@ impl AcpConnection {
             .and_then(|v| v.get("name"))
             .and_then(|v| v.as_str())
             .unwrap_or("unknown");
+        let accepts_images = result
+            .pointer("/agentCapabilities/promptCapabilities/image")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        self.accepts_images.store(accepts_images, Ordering::Relaxed);
+        let load_session = result
+            .pointer("/agentCapabilities/loadSession")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        self.supports_load_session
+            .store(load_session, Ordering::Relaxed);
 
         info!(
-            "ACP [{}]: initialized (agent={server_name}, protocol={server_version})",
+            "ACP [{}]: initialized (agent={server_name}, protocol={server_version}, images={accepts_images}, load_session={load_session})",
             self.agent_name
         );
 
@@ -488,16 +1227,32 @@ impl AcpConnection {
         Ok(())
     }
 
-    /// Send a JSON-RPC request and wait for the matching response.
-    /// Notifications received while waiting are logged and discarded.
-    pub async fn send_request(
+    /// Subscribe to agent-initiated notifications, and requests no running
+    /// prompt takes. Only messages received after subscribing are delivered.
+    pub fn subscribe(&self) -> tokio::sync::broadcast::Receiver<AcpAgentMessage> {
+        self.agent_messages.subscribe()
+    }
+
+    /// Serialize a JSON-RPC message and write it to the agent's stdin.
+    async fn write_message<T: Serialize>(&self, message: &T) -> Result<(), String> {
+        write_frame(&self.transport, &self.agent_name, message).await
+    }
+
+    /// Register a pending request and send it. The returned receiver resolves
+    /// with the response, or errors if the agent exits first.
+    async fn start_request(
         &self,
         method: &str,
         params: Option<serde_json::Value>,
-    ) -> Result<serde_json::Value, String> {
-        let mut inner = self.inner.lock().await;
-        let id = inner.next_id;
-        inner.next_id += 1;
+    ) -> Result<(u64, tokio::sync::oneshot::Receiver<JsonRpcMessage>), String> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        self.pending.lock().unwrap().insert(id, tx);
+        // Checked after registering so a reader exiting concurrently can't strand us
+        if self.closed.load(Ordering::SeqCst) {
+            self.pending.lock().unwrap().remove(&id);
+            return Err(format!("ACP [{}] agent closed connection", self.agent_name));
+        }
 
         let request = JsonRpcRequest {
             jsonrpc: "2.0".to_string(),
@@ -505,89 +1260,42 @@ impl AcpConnection {
             method: method.to_string(),
             params,
         };
+        if let Err(e) = self.write_message(&request).await {
+            self.pending.lock().unwrap().remove(&id);
+            return Err(e);
+        }
+        Ok((id, rx))
+    }
 
-        let mut json = serde_json::to_string(&request).map_err(|e| e.to_string())?;
-        json.push('\n');
-
-        inner
-            .stdin
-            .write_all(json.as_bytes())
-            .await
-            .map_err(|e| format!("ACP [{}] write error: {e}", self.agent_name))?;
-        inner
-            .stdin
-            .flush()
-            .await
-            .map_err(|e| format!("ACP [{}] flush error: {e}", self.agent_name))?;
-
-        // Read lines until we get the matching response
-        let deadline = tokio::time::Instant::now() + self.request_timeout;
-        let mut line = String::new();
-
-        loop {
-            line.clear();
-            let read_result =
-                tokio::time::timeout_at(deadline, inner.stdout.read_line(&mut line)).await;
-
-            match read_result {
-                Err(_) => {
-                    return Err(format!(
-                        "ACP [{}] request '{}' timed out ({:?})",
-                        self.agent_name, method, self.request_timeout
-                    ));
-                }
-                Ok(Err(e)) => {
-                    return Err(format!("ACP [{}] read error: {e}", self.agent_name));
-                }
-                Ok(Ok(0)) => {
-                    return Err(format!("ACP [{}] agent closed connection", self.agent_name));
-                }
-                Ok(Ok(_)) => {}
-            }
+    /// Send a JSON-RPC request and wait for the matching response.
+    pub async fn send_request(
+        &self,
+        method: &str,
+        params: Option<serde_json::Value>,
+    ) -> Result<serde_json::Value, String> {
+        let (id, rx) = self.start_request(method, params).await?;
 
-            let trimmed = line.trim();
-            if trimmed.is_empty() {
-                continue;
+        let msg = match tokio::time::timeout(self.request_timeout, rx).await {
+            Err(_) => {
+                self.pending.lock().unwrap().remove(&id);
+                return Err(format!(
+                    "ACP [{}] request '{}' timed out ({:?})",
+                    self.agent_name, method, self.request_timeout
+                ));
             }
-
-            let msg: JsonRpcMessage = match serde_json::from_str(trimmed) {
-                Ok(m) => m,
-                Err(_) => {
-                    debug!(
-                        "ACP [{}] ignoring non-JSON line: {}",
-                        self.agent_name,
-                        &trimmed[..trimmed.len().min(200)]
-                    );
-                    continue;
-                }
-            };
-
-            if msg.is_notification() {
-                // Discard notifications during simple request/response
-                debug!(
-                    "ACP [{}] notification during '{}': {:?}",
-                    self.agent_name, method, msg.method
-                );
-                continue;
+            Ok(Err(_)) => {
+                return Err(format!("ACP [{}] agent closed connection", self.agent_name));
             }
+            Ok(Ok(msg)) => msg,
+        };
 
-            if msg.is_response() {
-                let matches = match &msg.id {
-                    Some(serde_json::Value::Number(n)) => n.as_u64() == Some(id),
-                    _ => true, // best effort
-                };
-                if !matches {
-                    continue;
-                }
-                if let Some(err) = msg.error {
-                    return Err(format!(
-                        "ACP [{}] error ({}): {}",
-                        self.agent_name, err.code, err.message
-                    ));
-                }
-                return Ok(msg.result.unwrap_or(serde_json::Value::Null));
-            }
+        if let Some(err) = msg.error {
+            return Err(format!(
+                "ACP [{}] error ({}): {}",
+                self.agent_name, err.code, err.message
+            ));
         }
+        Ok(msg.result.unwrap_or(serde_json::Value::Null))
     }
 
     /// Send a JSON-RPC notification (no response expected).
@@ -596,2291 +1304,5755 @@ impl AcpConnection {
         method: &str,
         params: Option<serde_json::Value>,
     ) -> Result<(), String> {
-        let mut inner = self.inner.lock().await;
         let request = JsonRpcRequest {
             jsonrpc: "2.0".to_string(),
             id: None,
             method: method.to_string(),
             params,
         };
-        let mut json = serde_json::to_string(&request).map_err(|e| e.to_string())?;
-        json.push('\n');
-        inner
-            .stdin
-            .write_all(json.as_bytes())
-            .await
-            .map_err(|e| format!("ACP [{}] write error: {e}", self.agent_name))?;
-        inner
-            .stdin
-            .flush()
-            .await
-            .map_err(|e| format!("ACP [{}] flush error: {e}", self.agent_name))?;
-        Ok(())
+        self.write_message(&request).await
+    }
+
+    /// Answer a request initiated by the agent (see [`AcpAgentMessage`]).
+    pub async fn respond(
+        &self,
+        id: &serde_json::Value,
+        result: serde_json::Value,
+    ) -> Result<(), String> {
+        self.write_message(&serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "result": result
+        }))
+        .await
+    }
+
+    /// Answer a request initiated by the agent with a JSON-RPC error.
+    pub async fn respond_error(
+        &self,
+        id: &serde_json::Value,
+        code: i64,
+        message: &str,
+    ) -> Result<(), String> {
+        self.write_message(&serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": code, "message": message }
+        }))
+        .await
     }
 
     /// Send `session/prompt` and collect the notification stream until the
-    /// response arrives. During execution, permission requests are auto-resolved
-    /// according to `auto_approve`. Returns `AcpPromptResult` with all
+    /// response arrives. During execution, permission requests are checked
+    /// against the agent's policy first; requests it doesn't settle are
+    /// approved when `auto_approve` is set, otherwise forwarded to
+    /// `permission_handler` (or cancelled if there is none). Returns `AcpPromptResult` with all
     /// collected messages, tool calls, and file changes.
+    ///
+    /// Agent messages tagged with a different `sessionId` are ignored, so
+    /// several prompts may run on one connection at the same time.
     pub async fn prompt_streaming(
         &self,
         params: serde_json::Value,
         auto_approve: bool,
         timeout: Duration,
         progress_tx: Option<&AcpProgressSender>,
+        permission_handler: Option<&AcpPermissionHandler>,
     ) -> Result<AcpPromptResult, String> {
         let started = std::time::Instant::now();
-        let mut inner = self.inner.lock().await;
-        let id = inner.next_id;
-        inner.next_id += 1;
-
-        let request = JsonRpcRequest {
-            jsonrpc: "2.0".to_string(),
-            id: Some(id),
-            method: "session/prompt".to_string(),
-            params: Some(params),
+        let session_id = params
+            .get("sessionId")
+            .and_then(|v| v.as_str())
+            .map(str::to_string);
+
+        // Subscribe before sending so no update or request for this prompt
+        // is missed
+        let mut agent_rx = self.subscribe();
+        let (request_tx, mut request_rx) = tokio::sync::mpsc::unbounded_channel();
+        let route_key = self.next_id.fetch_add(1, Ordering::SeqCst);
+        self.request_routes.lock().unwrap().push(RequestRoute {
+            key: route_key,
+            session_id: session_id.clone(),
+            tx: request_tx,
+        });
+        let _route = RequestRouteGuard {
+            routes: &self.request_routes,
+            key: route_key,
         };
-        let mut json = serde_json::to_string(&request).map_err(|e| e.to_string())?;
-        json.push('\n');
-
-        inner
-            .stdin
-            .write_all(json.as_bytes())
-            .await
-            .map_err(|e| format!("ACP [{}] write error: {e}", self.agent_name))?;
-        inner
-            .stdin
-            .flush()
-            .await
-            .map_err(|e| format!("ACP [{}] flush error: {e}", self.agent_name))?;
+        let (id, mut response_rx) = self.start_request("session/prompt", Some(params)).await?;
 
         let mut result = AcpPromptResult {
             messages: Vec::new(),
             tool_calls: Vec::new(),
             files_changed: Vec::new(),
+            plan: Vec::new(),
+            thoughts: Vec::new(),
+            usage: None,
             completed: false,
             duration_ms: 0,
             context_reset: false,
         };
         // Buffer for accumulating streamed message chunks
         let mut message_buffer = String::new();
+        // Same for thought chunks, one entry per uninterrupted run of thinking
+        let mut thought_buffer = String::new();
+        // File writes approved during this prompt, for `max_file_writes`
+        let mut writes = PromptWrites::default();
 
         let mut deadline = tokio::time::Instant::now() + timeout;
-        let mut line = String::new();
 
         loop {
-            line.clear();
-            let read_result =
-                tokio::time::timeout_at(deadline, inner.stdout.read_line(&mut line)).await;
+            tokio::select! {
+                // Agent messages first: the reader broadcasts updates before
+                // the final response, so this drains them before returning.
+                biased;
+
+                received = agent_rx.recv() => {
+                    let agent_msg = match received {
+                        Ok(m) => m,
+                        Err(tokio::sync::broadcast::error::RecvError::Lagged(n)) => {
+                            // The result would be missing part of the reply
+                            self.pending.lock().unwrap().remove(&id);
+                            return Err(format!(
+                                "ACP [{}] prompt fell behind and missed {n} agent message(s)",
+                                self.agent_name
+                            ));
+                        }
+                        Err(tokio::sync::broadcast::error::RecvError::Closed) => {
+                            return Err(format!(
+                                "ACP [{}] agent closed connection during prompt",
+                                self.agent_name
+                            ));
+                        }
+                    };
+                    if let (Some(ours), Some(theirs)) = (session_id.as_deref(), agent_msg.session_id()) {
+                        if ours != theirs {
+                            continue;
+                        }
+                    }
 
-            match read_result {
-                Err(_) => {
-                    result.duration_ms = started.elapsed().as_millis();
-                    return Err(format!(
-                        "ACP [{}] prompt timed out after {timeout:?}",
-                        self.agent_name
-                    ));
-                }
-                Ok(Err(e)) => {
-                    result.duration_ms = started.elapsed().as_millis();
-                    return Err(format!("ACP [{}] read error: {e}", self.agent_name));
-                }
-                Ok(Ok(0)) => {
-                    result.duration_ms = started.elapsed().as_millis();
-                    return Err(format!(
-                        "ACP [{}] agent closed connection during prompt",
-                        self.agent_name
-                    ));
-                }
-                Ok(Ok(_)) => {
-                    // Reset deadline on each received line (activity-based timeout)
+                    if agent_msg.id.is_some() {
+                        // Requests this prompt takes arrive on `request_rx`
+                        continue;
+                    } else if agent_msg.method == "session/update" {
+                        self.handle_session_update(
+                            agent_msg.params.as_ref(),
+                            &mut result,
+                            &mut message_buffer,
+                            &mut thought_buffer,
+                            progress_tx,
+                        );
+                    } else {
+                        debug!(
+                            "ACP [{}] unhandled notification: {}",
+                            self.agent_name, agent_msg.method
+                        );
+                    }
                     deadline = tokio::time::Instant::now() + timeout;
                 }
-            }
-
-            let trimmed = line.trim();
-            if trimmed.is_empty() {
-                continue;
-            }
 
-            let msg: JsonRpcMessage = match serde_json::from_str(trimmed) {
-                Ok(m) => m,
-                Err(_) => {
-                    debug!(
-                        "ACP [{}] ignoring non-JSON: {}",
-                        self.agent_name,
-                        &trimmed[..trimmed.len().min(200)]
-                    );
-                    continue;
+                Some(request) = request_rx.recv() => {
+                    if request.method.starts_with("fs/") {
+                        self.handle_fs_request(
+                            &request,
+                            &mut result,
+                            auto_approve,
+                            permission_handler,
+                            &mut writes,
+                        )
+                        .await;
+                    } else if request.method.starts_with("terminal/") {
+                        self.handle_terminal_request(
+                            &request,
+                            auto_approve,
+                            progress_tx,
+                            permission_handler,
+                            writes.count,
+                        )
+                        .await;
+                    } else {
+                        self.handle_agent_request(
+                            &request,
+                            auto_approve,
+                            progress_tx,
+                            permission_handler,
+                            &mut writes,
+                            timeout,
+                        )
+                        .await;
+                    }
+                    // Activity-based timeout; time spent waiting on a user to
+                    // answer a permission request doesn't count either.
+                    deadline = tokio::time::Instant::now() + timeout;
                 }
-            };
 
-            // Handle the final response to our session/prompt request
-            if msg.is_response() {
-                let matches = match &msg.id {
-                    Some(serde_json::Value::Number(n)) => n.as_u64() == Some(id),
-                    _ => true,
-                };
-                if !matches {
-                    continue;
-                }
-                if let Some(err) = msg.error {
+                response = &mut response_rx => {
                     result.duration_ms = started.elapsed().as_millis();
-                    return Err(format!(
-                        "ACP [{}] prompt error ({}): {}",
-                        self.agent_name, err.code, err.message
-                    ));
-                }
-
-                // Flush any remaining message buffer
-                if !message_buffer.is_empty() {
-                    result.messages.push(std::mem::take(&mut message_buffer));
-                }
-
-                // Extract stopReason from response if available
-                if let Some(res) = &msg.result {
-                    if let Some(reason) = res.get("stopReason").and_then(|v| v.as_str()) {
-                        debug!("ACP [{}] prompt stopReason: {reason}", self.agent_name);
+                    let msg = response.map_err(|_| {
+                        format!(
+                            "ACP [{}] agent closed connection during prompt",
+                            self.agent_name
+                        )
+                    })?;
+                    if let Some(err) = msg.error {
+                        return Err(format!(
+                            "ACP [{}] prompt error ({}): {}",
+                            self.agent_name, err.code, err.message
+                        ));
                     }
-                }
 
-                result.completed = true;
-                result.duration_ms = started.elapsed().as_millis();
-                return Ok(result);
-            }
+                    // Flush any remaining message buffer
+                    if !message_buffer.is_empty() {
+                        result.messages.push(std::mem::take(&mut message_buffer));
+                    }
+                    if !thought_buffer.is_empty() {
+                        result.thoughts.push(std::mem::take(&mut thought_buffer));
+                    }
 
-            // Handle requests from agent (e.g. session/request_permission)
-            if msg.is_request() {
-                let method = msg.method.as_deref().unwrap_or("");
-                let request_id = &msg.id;
-                info!(
-                    "ACP [{}] agent request: method={method} params={}",
-                    self.agent_name,
-                    msg.params
+                    if let Some(usage) = msg
+                        .result
                         .as_ref()
-                        .map(|p| {
-                            let s = p.to_string();
-                            s[..s.len().min(300)].to_string()
-                        })
-                        .unwrap_or_default()
-                );
+                        .and_then(|res| res.get("usage"))
+                        .and_then(AcpUsage::from_value)
+                    {
+                        result.usage = Some(usage);
+                    }
 
-                if method == "session/request_permission" {
-                    // Permission request: agent wants approval for a tool call
-                    let params = msg.params.as_ref();
-                    let options = params
-                        .and_then(|p| p.get("options"))
-                        .and_then(|o| o.as_array());
-                    // Find an "allow" option (prefer allow_always, then allow_once)
-                    let allow_option_id = options
-                        .and_then(|arr| {
-                            arr.iter()
-                                .find(|opt| {
-                                    opt.get("kind")
-                                        .and_then(|k| k.as_str())
-                                        .map(|k| k == "allow_always")
-                                        .unwrap_or(false)
-                                })
-                                .or_else(|| {
-                                    arr.iter().find(|opt| {
-                                        opt.get("kind")
-                                            .and_then(|k| k.as_str())
-                                            .map(|k| k.starts_with("allow"))
-                                            .unwrap_or(false)
-                                    })
-                                })
-                        })
-                        .and_then(|opt| opt.get("optionId"))
+                    // Extract stopReason from response if available
+                    if let Some(reason) = msg
+                        .result
+                        .as_ref()
+                        .and_then(|res| res.get("stopReason"))
                         .and_then(|v| v.as_str())
-                        .unwrap_or("allow");
-
-                    if auto_approve {
-                        // Send JSON-RPC response approving the permission
-                        let response = serde_json::json!({
-                            "jsonrpc": "2.0",
-                            "id": request_id,
-                            "result": {
-                                "outcome": {
-                                    "outcome": "selected",
-                                    "optionId": allow_option_id
-                                }
-                            }
-                        });
-                        let mut resp_json = serde_json::to_string(&response).unwrap_or_default();
-                        resp_json.push('\n');
-                        let _ = inner.stdin.write_all(resp_json.as_bytes()).await;
-                        let _ = inner.stdin.flush().await;
-                        info!(
-                            "ACP [{}] auto-approved permission (optionId={})",
-                            self.agent_name, allow_option_id
-                        );
-                    } else {
-                        // Reject by sending cancelled outcome
-                        let response = serde_json::json!({
-                            "jsonrpc": "2.0",
-                            "id": request_id,
-                            "result": {
-                                "outcome": {
-                                    "outcome": "cancelled"
-                                }
-                            }
-                        });
-                        let mut resp_json = serde_json::to_string(&response).unwrap_or_default();
-                        resp_json.push('\n');
-                        let _ = inner.stdin.write_all(resp_json.as_bytes()).await;
-                        let _ = inner.stdin.flush().await;
-                        debug!(
-                            "ACP [{}] rejected permission request (auto_approve=false)",
-                            self.agent_name
-                        );
+                    {
+                        debug!("ACP [{}] prompt stopReason: {reason}", self.agent_name);
+                        if reason == "cancelled" {
+                            return Ok(result);
+                        }
                     }
-                } else {
-                    debug!(
-                        "ACP [{}] unhandled agent request: {method}",
+
+                    result.completed = true;
+                    return Ok(result);
+                }
+
+                _ = tokio::time::sleep_until(deadline) => {
+                    self.pending.lock().unwrap().remove(&id);
+                    return Err(format!(
+                        "ACP [{}] prompt timed out after {timeout:?}",
                         self.agent_name
-                    );
+                    ));
                 }
-                continue;
             }
+        }
+    }
+
+    /// Handle a request from the agent (e.g. `session/request_permission`)
+    /// received during a prompt. A question (`session/request_input`) waits
+    /// up to `timeout` for an answer.
+    async fn handle_agent_request(
+        &self,
+        msg: &AcpAgentMessage,
+        auto_approve: bool,
+        progress_tx: Option<&AcpProgressSender>,
+        permission_handler: Option<&AcpPermissionHandler>,
+        writes: &mut PromptWrites,
+        timeout: Duration,
+    ) {
+        let Some(request_id) = msg.id.as_ref() else {
+            return;
+        };
+        info!(
+            "ACP [{}] agent request: method={} params={}",
+            self.agent_name,
+            msg.method,
+            msg.params
+                .as_ref()
+                .map(|p| {
+                    let s = p.to_string();
+                    s[..floor_char_boundary(&s, 300)].to_string()
+                })
+                .unwrap_or_default()
+        );
+
+        if msg.method == "session/request_input" {
+            self.handle_question(request_id, msg.params.as_ref(), progress_tx, timeout)
+                .await;
+            return;
+        }
+        if msg.method != "session/request_permission" {
+            debug!(
+                "ACP [{}] unhandled agent request: {}",
+                self.agent_name, msg.method
+            );
+            return;
+        }
 
-            // Handle notifications (session/update)
-            if msg.is_notification() {
-                let method = msg.method.as_deref().unwrap_or("");
-                let params = msg.params.as_ref();
+        // Permission request: agent wants approval for a tool call
+        let params = msg.params.as_ref();
+        let tool_call = params.and_then(|p| p.get("toolCall"));
+        let policy_request = PolicyRequest::from_tool_call(tool_call);
+        let verdict = match &self.policy {
+            Some(policy) => {
+                policy.evaluate(&policy_request, self.workspace.as_deref(), writes.count)
+            }
+            None => PolicyVerdict::Ask,
+        };
 
-                match method {
-                    "session/update" => {
-                        // Parse the update type from params.update.sessionUpdate or params.update.type
-                        let update = params.and_then(|p| p.get("update"));
-                        let update_type_raw = update
-                            .and_then(|u| u.get("sessionUpdate").or_else(|| u.get("type")))
+        let decision = match verdict {
+            PolicyVerdict::Deny(reason) => {
+                info!("ACP [{}] permission rejected: {reason}", self.agent_name);
+                AcpPermissionDecision::Reject
+            }
+            PolicyVerdict::Allow => AcpPermissionDecision::AllowOnce,
+            // With a policy, approve once so later calls are checked again
+            PolicyVerdict::Ask if auto_approve && self.policy.is_some() => {
+                AcpPermissionDecision::AllowOnce
+            }
+            PolicyVerdict::Ask if auto_approve => AcpPermissionDecision::AllowAlways,
+            PolicyVerdict::Ask => match permission_handler {
+                Some(handler) => {
+                    let request = AcpPermissionRequest {
+                        agent_name: self.agent_name.clone(),
+                        tool_name: tool_call
+                            .and_then(|t| t.get("title").or_else(|| t.get("kind")))
                             .and_then(|t| t.as_str())
-                            .unwrap_or("");
-                        // Normalize PascalCase to snake_case for matching
-                        let update_type: String = if update_type_raw.contains('_') {
-                            update_type_raw.to_string()
-                        } else {
-                            // AgentMessageChunk -> agent_message_chunk
-                            let mut result_str = String::new();
-                            for (i, c) in update_type_raw.chars().enumerate() {
-                                if c.is_uppercase() && i > 0 {
-                                    result_str.push('_');
-                                }
-                                result_str.push(c.to_lowercase().next().unwrap_or(c));
-                            }
-                            result_str
-                        };
-
-                        match update_type.as_str() {
-                            "agent_message_chunk" => {
-                                // Extract text from content block
-                                let text = update
-                                    .and_then(|u| u.get("content"))
-                                    .and_then(|c| c.get("text"))
-                                    .and_then(|t| t.as_str());
-                                if let Some(text) = text {
-                                    message_buffer.push_str(text);
-                                }
-                            }
-                            "agent_thought_chunk" => {
-                                // Agent thinking — log but don't include in output
-                                let text = update
-                                    .and_then(|u| u.get("content"))
-                                    .and_then(|c| c.get("text"))
-                                    .and_then(|t| t.as_str());
-                                if let Some(text) = text {
-                                    debug!(
-                                        "ACP [{}] thought: {}",
-                                        self.agent_name,
-                                        &text[..text.len().min(100)]
-                                    );
-                                    if let Some(tx) = progress_tx {
-                                        let _ = tx.send(AcpProgressEvent::Thinking {
-                                            text: text.to_string(),
-                                        });
-                                    }
-                                }
-                            }
-                            "tool_call" => {
-                                let title = update
-                                    .and_then(|u| u.get("title"))
-                                    .and_then(|t| t.as_str())
-                                    .unwrap_or("unknown")
-                                    .to_string();
-                                let raw_input = update
-                                    .and_then(|u| u.get("rawInput"))
-                                    .cloned()
-                                    .unwrap_or(serde_json::Value::Null);
-                                if let Some(tx) = progress_tx {
-                                    let _ = tx.send(AcpProgressEvent::ToolStart {
-                                        name: title.clone(),
-                                    });
-                                }
-                                result.tool_calls.push(ToolCallInfo {
-                                    name: title,
-                                    input: raw_input,
-                                });
-                                // Flush message buffer before tool calls
-                                if !message_buffer.is_empty() {
-                                    result.messages.push(std::mem::take(&mut message_buffer));
-                                }
-                            }
-                            "tool_call_update" => {
-                                let tool_id = update
-                                    .and_then(|u| u.get("toolCallId"))
-                                    .and_then(|t| t.as_str())
-                                    .unwrap_or("?");
-                                let status = update
-                                    .and_then(|u| u.get("status"))
-                                    .and_then(|s| s.as_str())
-                                    .unwrap_or("?");
-                                debug!(
-                                    "ACP [{}] tool update: id={tool_id} status={status}",
-                                    self.agent_name
-                                );
-                                if let Some(tx) = progress_tx {
-                                    let tool_name = update
-                                        .and_then(|u| u.get("title"))
-                                        .and_then(|t| t.as_str())
-                                        .unwrap_or(tool_id)
-                                        .to_string();
-                                    let _ = tx.send(AcpProgressEvent::ToolComplete {
-                                        name: tool_name,
-                                        status: status.to_string(),
-                                    });
-                                }
-                                // Capture rawOutput (e.g. command stdout)
-                                if let Some(raw) = update.and_then(|u| u.get("rawOutput")) {
-                                    let output_str = match raw {
-                                        serde_json::Value::String(s) => s.clone(),
-                                        other => other.to_string(),
-                                    };
-                                    if !output_str.is_empty() {
-                                        result.messages.push(output_str);
-                                    }
-                                }
-                                // Capture content blocks (terminal output, diffs, etc.)
-                                if let Some(content_arr) = update
-                                    .and_then(|u| u.get("content"))
-                                    .and_then(|c| c.as_array())
-                                {
-                                    for item in content_arr {
-                                        let content_type =
-                                            item.get("type").and_then(|t| t.as_str()).unwrap_or("");
-                                        if content_type == "content" {
-                                            // Inline text content
-                                            if let Some(text) = item
-                                                .get("content")
-                                                .and_then(|c| c.get("text"))
-                                                .and_then(|t| t.as_str())
-                                            {
-                                                if !text.is_empty() {
-                                                    result.messages.push(text.to_string());
-                                                }
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                            "plan" => {
-                                let entries = update
-                                    .and_then(|u| u.get("entries"))
-                                    .and_then(|e| e.as_array());
-                                if let Some(entries) = entries {
-                                    debug!(
-                                        "ACP [{}] plan update: {} entries",
-                                        self.agent_name,
-                                        entries.len()
-                                    );
-                                }
-                            }
-                            _ => {
-                                debug!(
-                                    "ACP [{}] unhandled session/update type: {update_type}",
-                                    self.agent_name
-                                );
-                            }
-                        }
-                    }
-                    _ => {
-                        debug!("ACP [{}] unhandled notification: {method}", self.agent_name);
-                    }
+                            .unwrap_or("unknown")
+                            .to_string(),
+                        raw_input: tool_call
+                            .and_then(|t| t.get("rawInput"))
+                            .cloned()
+                            .unwrap_or(serde_json::Value::Null),
+                    };
+                    handler(request).await
                 }
+                None => AcpPermissionDecision::Cancelled,
+            },
+        };
+        if policy_request.is_write()
+            && matches!(
+                decision,
+                AcpPermissionDecision::AllowOnce | AcpPermissionDecision::AllowAlways
+            )
+        {
+            writes.approve(&policy_request.paths, self.workspace.as_deref());
+            if decision == AcpPermissionDecision::AllowAlways {
+                self.writes_always_allowed.store(true, Ordering::SeqCst);
             }
         }
-    }
 
-    /// Check whether the agent child process is still running.
-    pub async fn is_alive(&self) -> bool {
-        let mut inner = self.inner.lock().await;
-        matches!(inner._child.try_wait(), Ok(None))
+        let outcome = permission_outcome(params, decision);
+        if let Err(e) = self
+            .respond(request_id, serde_json::json!({ "outcome": outcome }))
+            .await
+        {
+            warn!(
+                "ACP [{}] failed to answer permission request: {e}",
+                self.agent_name
+            );
+        } else {
+            info!(
+                "ACP [{}] permission request resolved: {decision:?}",
+                self.agent_name
+            );
+        }
     }
 
-    /// Get the child process ID.
-    pub async fn pid(&self) -> Option<u32> {
-        let inner = self.inner.lock().await;
-        inner._child.id()
+    /// Relay a question from the agent and answer it with the user's reply
+    /// (see [`answer_question`](Self::answer_question)), or report it
+    /// cancelled when none arrives within `timeout`.
+    async fn handle_question(
+        &self,
+        request_id: &serde_json::Value,
+        params: Option<&serde_json::Value>,
+        progress_tx: Option<&AcpProgressSender>,
+        timeout: Duration,
+    ) {
+        let text = params
+            .and_then(|p| {
+                ["question", "message", "prompt"]
+                    .iter()
+                    .find_map(|key| p.get(*key).and_then(|v| v.as_str()))
+            })
+            .unwrap_or("The agent needs more input.")
+            .to_string();
+        let (answer_tx, answer_rx) = tokio::sync::oneshot::channel();
+        // A newer question replaces (and thereby cancels) any stale one
+        *self.pending_question.lock().unwrap() = Some(PendingQuestion {
+            text: text.clone(),
+            answer_tx,
+        });
+        if let Some(tx) = progress_tx {
+            let _ = tx.send(AcpProgressEvent::Question { text });
+        }
+
+        let outcome = match tokio::time::timeout(timeout, answer_rx).await {
+            Ok(Ok(answer)) => serde_json::json!({"outcome": "answered", "text": answer}),
+            Ok(Err(_)) => serde_json::json!({"outcome": "cancelled"}),
+            Err(_) => {
+                self.pending_question.lock().unwrap().take();
+                warn!(
+                    "ACP [{}] question unanswered after {timeout:?}, cancelled",
+                    self.agent_name
+                );
+                serde_json::json!({"outcome": "cancelled"})
+            }
+        };
+        if let Err(e) = self
+            .respond(request_id, serde_json::json!({ "outcome": outcome }))
+            .await
+        {
+            warn!("ACP [{}] failed to answer question: {e}", self.agent_name);
+        }
     }
 
-    /// Gracefully shut down the agent process.
-    pub async fn shutdown(&self) -> Result<(), String> {
-        info!("ACP [{}]: shutting down", self.agent_name);
+    /// The question the agent is waiting on, if any.
+    pub fn pending_question(&self) -> Option<String> {
+        self.pending_question
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|q| q.text.clone())
+    }
 
-        // Try sending session/end (best effort)
-        let _ = self.send_request("shutdown", None).await;
+    /// Deliver `answer` to the question the agent is waiting on.
+    pub fn answer_question(&self, answer: &str) -> Result<(), String> {
+        let question = self
+            .pending_question
+            .lock()
+            .unwrap()
+            .take()
+            .ok_or_else(|| "The agent is not waiting for an answer.".to_string())?;
+        question
+            .answer_tx
+            .send(answer.to_string())
+            .map_err(|_| "The agent stopped waiting for an answer.".to_string())
+    }
 
-        // Kill the child process
-        let mut inner = self.inner.lock().await;
-        let _ = inner._child.kill().await;
-        info!("ACP [{}]: process terminated", self.agent_name);
-        Ok(())
+    /// Drop the pending question, answering it as cancelled.
+    fn cancel_question(&self) {
+        self.pending_question.lock().unwrap().take();
     }
-}
 
-// ---------------------------------------------------------------------------
-// Progress events (streamed during prompt execution)
-// ---------------------------------------------------------------------------
+    /// Answer an `fs/read_text_file` or `fs/write_text_file` request from the
+    /// agent. Written files are added to `result.files_changed`.
+    async fn handle_fs_request(
+        &self,
+        msg: &AcpAgentMessage,
+        result: &mut AcpPromptResult,
+        auto_approve: bool,
+        permission_handler: Option<&AcpPermissionHandler>,
+        writes: &mut PromptWrites,
+    ) {
+        let Some(request_id) = msg.id.as_ref() else {
+            return;
+        };
+        let sent = match self
+            .serve_fs_request(msg, auto_approve, permission_handler, writes)
+            .await
+        {
+            Ok((value, written)) => {
+                if let Some(changed) = written {
+                    if !result.files_changed.contains(&changed) {
+                        result.files_changed.push(changed);
+                    }
+                }
+                self.respond(request_id, value).await
+            }
+            Err(e) => {
+                warn!("ACP [{}] {} denied: {e}", self.agent_name, msg.method);
+                self.respond_error(request_id, -32000, &e).await
+            }
+        };
+        if let Err(e) = sent {
+            warn!(
+                "ACP [{}] failed to answer {}: {e}",
+                self.agent_name, msg.method
+            );
+        }
+    }
 
-/// Events emitted during ACP prompt execution for real-time progress reporting.
-#[derive(Debug, Clone)]
-pub enum AcpProgressEvent {
-    /// Agent started executing a tool
-    ToolStart { name: String },
-    /// Agent tool execution completed
-    ToolComplete { name: String, status: String },
-    /// Agent is thinking (extended thinking chunk)
-    Thinking { text: String },
-}
+    /// Perform an `fs/*` request inside the session workspace. Writes go
+    /// through the same approval as permission requests. Returns the
+    /// JSON-RPC result and, for writes, the workspace-relative path written.
+    async fn serve_fs_request(
+        &self,
+        msg: &AcpAgentMessage,
+        auto_approve: bool,
+        permission_handler: Option<&AcpPermissionHandler>,
+        writes: &mut PromptWrites,
+    ) -> Result<(serde_json::Value, Option<String>), String> {
+        let workspace = self
+            .workspace
+            .as_deref()
+            .ok_or("no workspace is set for this session")?;
+        let param = |key: &str| msg.params.as_ref().and_then(|p| p.get(key));
+        let path = param("path").and_then(|v| v.as_str()).unwrap_or_default();
+        let resolved = resolve_workspace_path(workspace, path)?;
+
+        match msg.method.as_str() {
+            "fs/read_text_file" => {
+                let line = param("line").and_then(|v| v.as_u64());
+                let limit = param("limit").and_then(|v| v.as_u64());
+                let content = read_text_file(&resolved, line, limit).await?;
+                Ok((serde_json::json!({ "content": content }), None))
+            }
+            "fs/write_text_file" => {
+                let content = param("content")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or_default();
+                let relative = resolved.strip_prefix(workspace).unwrap_or(&resolved);
+                self.approve_fs_write(
+                    &resolved,
+                    relative,
+                    auto_approve,
+                    permission_handler,
+                    writes,
+                )
+                .await?;
+                write_text_file(&resolved, content).await?;
+                Ok((
+                    serde_json::Value::Null,
+                    Some(relative.to_string_lossy().to_string()),
+                ))
+            }
+            other => Err(format!("unsupported method {other}")),
+        }
+    }
 
-/// Sender for streaming progress events during prompt execution.
-pub type AcpProgressSender = tokio::sync::mpsc::UnboundedSender<AcpProgressEvent>;
+    /// Check an `fs/write_text_file` request against the policy, then
+    /// `auto_approve` or the user. A write the agent already got approved
+    /// through `session/request_permission` isn't asked or counted again.
+    async fn approve_fs_write(
+        &self,
+        resolved: &std::path::Path,
+        relative: &std::path::Path,
+        auto_approve: bool,
+        permission_handler: Option<&AcpPermissionHandler>,
+        writes: &mut PromptWrites,
+    ) -> Result<(), String> {
+        if writes.take_approved(resolved) {
+            return Ok(());
+        }
+        let title = format!("Write {}", relative.display());
+        let policy_request = PolicyRequest {
+            kind: "edit",
+            title: &title,
+            paths: vec![resolved.to_string_lossy().to_string()],
+        };
+        let verdict = match &self.policy {
+            Some(policy) => {
+                policy.evaluate(&policy_request, self.workspace.as_deref(), writes.count)
+            }
+            None => PolicyVerdict::Ask,
+        };
+        if let PolicyVerdict::Deny(reason) = &verdict {
+            return Err(format!("write rejected: {reason}"));
+        }
 
-// ---------------------------------------------------------------------------
-// PTY connection — simple stdin/stdout subprocess for non-ACP CLI tools
-// ---------------------------------------------------------------------------
+        if verdict == PolicyVerdict::Ask
+            && !auto_approve
+            && !self.writes_always_allowed.load(Ordering::SeqCst)
+        {
+            let Some(handler) = permission_handler else {
+                return Err("file writes need approval and no one can approve them here".into());
+            };
+            let request = AcpPermissionRequest {
+                agent_name: self.agent_name.clone(),
+                tool_name: title.clone(),
+                raw_input: serde_json::json!({ "path": relative.to_string_lossy() }),
+            };
+            match handler(request).await {
+                AcpPermissionDecision::AllowOnce => {}
+                AcpPermissionDecision::AllowAlways => {
+                    self.writes_always_allowed.store(true, Ordering::SeqCst);
+                }
+                AcpPermissionDecision::Reject | AcpPermissionDecision::Cancelled => {
+                    return Err("write rejected".to_string());
+                }
+            }
+        }
+        writes.count += 1;
+        Ok(())
+    }
 
-struct PtyConnectionInner {
-    stdin: tokio::process::ChildStdin,
-    stdout: BufReader<tokio::process::ChildStdout>,
-    _child: Child,
-}
+    /// Answer a `terminal/*` request from the agent. Creating a terminal
+    /// follows the same approval policy as tool permissions; waiting for exit
+    /// is answered from a background task so the prompt keeps streaming.
+    async fn handle_terminal_request(
+        &self,
+        msg: &AcpAgentMessage,
+        auto_approve: bool,
+        progress_tx: Option<&AcpProgressSender>,
+        permission_handler: Option<&AcpPermissionHandler>,
+        approved_writes: usize,
+    ) {
+        let Some(request_id) = msg.id.clone() else {
+            return;
+        };
+        let param = |key: &str| msg.params.as_ref().and_then(|p| p.get(key));
+        let terminal = || {
+            let id = param("terminalId").and_then(|v| v.as_str()).unwrap_or("");
+            self.terminals
+                .lock()
+                .unwrap()
+                .get(id)
+                .cloned()
+                .ok_or_else(|| format!("unknown terminal '{id}'"))
+        };
 
-/// A simple subprocess connection that pipes prompts via stdin and reads
-/// stdout until a configurable end-of-response marker or timeout.
-/// Unlike `AcpConnection`, there is no JSON-RPC framing — input is sent
-/// as-is and output is collected line-by-line.
-pub struct PtyConnection {
-    agent_name: String,
-    inner: Mutex<PtyConnectionInner>,
-}
+        let outcome = match msg.method.as_str() {
+            "terminal/create" => self
+                .create_terminal(
+                    msg,
+                    auto_approve,
+                    progress_tx,
+                    permission_handler,
+                    approved_writes,
+                )
+                .await
+                .map(|id| serde_json::json!({ "terminalId": id })),
+            "terminal/output" => terminal().map(|t| t.output_json()),
+            "terminal/kill" => terminal().map(|t| {
+                t.kill();
+                serde_json::json!({})
+            }),
+            "terminal/release" => {
+                let id = param("terminalId").and_then(|v| v.as_str()).unwrap_or("");
+                match self.terminals.lock().unwrap().remove(id) {
+                    // Dropping the last handle kills a still-running command
+                    Some(_) => Ok(serde_json::json!({})),
+                    None => Err(format!("unknown terminal '{id}'")),
+                }
+            }
+            "terminal/wait_for_exit" => {
+                match terminal() {
+                    Ok(t) => {
+                        let transport = self.transport.clone();
+                        let agent_name = self.agent_name.clone();
+                        tokio::spawn(async move {
+                            let exit = t.wait().await;
+                            let response = serde_json::json!({
+                                "jsonrpc": "2.0",
+                                "id": request_id,
+                                "result": exit.to_json(),
+                            });
+                            if let Err(e) = write_frame(&transport, &agent_name, &response).await {
+                                warn!("ACP [{agent_name}] failed to answer terminal/wait_for_exit: {e}");
+                            }
+                        });
+                        return;
+                    }
+                    Err(e) => Err(e),
+                }
+            }
+            other => Err(format!("unsupported method {other}")),
+        };
 
-impl PtyConnection {
-    /// Spawn the agent process and capture stdin/stdout.
-    pub async fn spawn(
-        agent_name: &str,
-        config: &AcpAgentConfig,
-        workspace: Option<&str>,
-    ) -> Result<Self, String> {
-        let mut cmd = build_spawn_command(config, workspace);
+        let sent = match outcome {
+            Ok(value) => self.respond(&request_id, value).await,
+            Err(e) => {
+                warn!("ACP [{}] {} failed: {e}", self.agent_name, msg.method);
+                self.respond_error(&request_id, -32000, &e).await
+            }
+        };
+        if let Err(e) = sent {
+            warn!(
+                "ACP [{}] failed to answer {}: {e}",
+                self.agent_name, msg.method
+            );
+        }
+    }
+
+    /// Handle `terminal/create`: check approval, then start the command in
+    /// the session workspace. Returns the new terminal ID.
+    async fn create_terminal(
+        &self,
+        msg: &AcpAgentMessage,
+        auto_approve: bool,
+        progress_tx: Option<&AcpProgressSender>,
+        permission_handler: Option<&AcpPermissionHandler>,
+        approved_writes: usize,
+    ) -> Result<String, String> {
+        let params = msg.params.clone().unwrap_or(serde_json::Value::Null);
+        let command = params
+            .get("command")
+            .and_then(|v| v.as_str())
+            .filter(|c| !c.trim().is_empty())
+            .ok_or("missing command")?;
+        let args: Vec<String> = params
+            .get("args")
+            .and_then(|v| v.as_array())
+            .map(|a| {
+                a.iter()
+                    .filter_map(|v| v.as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let policy_request = PolicyRequest {
+            kind: "execute",
+            title: command,
+            paths: params
+                .get("cwd")
+                .and_then(|v| v.as_str())
+                .map(|cwd| vec![cwd.to_string()])
+                .unwrap_or_default(),
+        };
+        let verdict = match &self.policy {
+            Some(policy) => {
+                policy.evaluate(&policy_request, self.workspace.as_deref(), approved_writes)
+            }
+            None => PolicyVerdict::Ask,
+        };
+        if let PolicyVerdict::Deny(reason) = &verdict {
+            return Err(format!("terminal command rejected: {reason}"));
+        }
+
+        if verdict == PolicyVerdict::Ask
+            && !auto_approve
+            && !self.terminals_always_allowed.load(Ordering::SeqCst)
+        {
+            let Some(handler) = permission_handler else {
+                return Err(
+                    "terminal commands need approval and no one can approve them here".into(),
+                );
+            };
+            let request = AcpPermissionRequest {
+                agent_name: self.agent_name.clone(),
+                tool_name: format!("terminal: {command} {}", args.join(" "))
+                    .trim_end()
+                    .to_string(),
+                raw_input: params.clone(),
+            };
+            match handler(request).await {
+                AcpPermissionDecision::AllowOnce => {}
+                AcpPermissionDecision::AllowAlways => {
+                    self.terminals_always_allowed.store(true, Ordering::SeqCst);
+                }
+                AcpPermissionDecision::Reject | AcpPermissionDecision::Cancelled => {
+                    return Err("terminal command rejected".to_string());
+                }
+            }
+        }
 
+        let workspace = self
+            .workspace
+            .as_deref()
+            .ok_or("no workspace is set for this session")?;
+        let cwd = match params.get("cwd").and_then(|v| v.as_str()) {
+            Some(cwd) => resolve_workspace_path(workspace, cwd)?,
+            None => workspace.to_path_buf(),
+        };
+        if !cwd.is_dir() {
+            return Err(format!("'{}' is not a directory", cwd.display()));
+        }
+        let env: Vec<(String, String)> = params
+            .get("env")
+            .and_then(|v| v.as_array())
+            .map(|vars| {
+                vars.iter()
+                    .filter_map(|var| {
+                        Some((
+                            var.get("name")?.as_str()?.to_string(),
+                            var.get("value")?.as_str()?.to_string(),
+                        ))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        let output_limit = params
+            .get("outputByteLimit")
+            .and_then(|v| v.as_u64())
+            .map_or(DEFAULT_TERMINAL_OUTPUT_LIMIT, |l| l as usize);
+
+        let terminal_id = format!("term-{}", uuid::Uuid::new_v4());
+        let mut cmd = Command::new(crate::platform::program(command));
+        cmd.args(&args).envs(env).current_dir(&cwd);
+        let terminal =
+            AcpTerminal::spawn(cmd, output_limit, terminal_id.clone(), progress_tx.cloned())
+                .map_err(|e| format!("failed to start '{command}': {e}"))?;
         info!(
-            "ACP/PTY: spawning agent '{agent_name}' ({} {})",
-            config.launch, config.command
+            "ACP [{}] terminal {terminal_id} started: {command} {}",
+            self.agent_name,
+            args.join(" ")
         );
+        self.terminals
+            .lock()
+            .unwrap()
+            .insert(terminal_id.clone(), Arc::new(terminal));
+        Ok(terminal_id)
+    }
 
-        let mut child = cmd
-            .spawn()
-            .map_err(|e| format!("Failed to spawn PTY agent '{agent_name}': {e}"))?;
-
-        let stdin = child
-            .stdin
-            .take()
-            .ok_or_else(|| format!("PTY agent '{agent_name}': failed to capture stdin"))?;
-        let stdout = child
-            .stdout
-            .take()
-            .ok_or_else(|| format!("PTY agent '{agent_name}': failed to capture stdout"))?;
+    /// Add the files a tool call changed to `result.files_changed`, relative to
+    /// the session workspace when they are inside it.
+    fn record_changed_paths(&self, update: &serde_json::Value, result: &mut AcpPromptResult) {
+        for path in tool_call_changed_paths(update) {
+            let path = std::path::Path::new(&path);
+            let changed = self
+                .workspace
+                .as_deref()
+                .and_then(|ws| path.strip_prefix(ws).ok())
+                .unwrap_or(path)
+                .to_string_lossy()
+                .to_string();
+            if !result.files_changed.contains(&changed) {
+                result.files_changed.push(changed);
+            }
+        }
+    }
 
-        // Drain stderr to tracing::debug
-        if let Some(stderr) = child.stderr.take() {
-            let name = agent_name.to_string();
-            tokio::spawn(async move {
-                let mut reader = BufReader::new(stderr);
-                let mut line = String::new();
-                loop {
-                    line.clear();
-                    match reader.read_line(&mut line).await {
-                        Ok(0) | Err(_) => break,
-                        Ok(_) => {
-                            let trimmed = line.trim();
-                            if !trimmed.is_empty() {
-                                debug!("PTY [{name}] stderr: {trimmed}");
-                            }
+    /// Open an ACP-level session rooted at `workspace`. With `resume`, an
+    /// agent that supports `loadSession` reloads that session (and its
+    /// context) instead.
+    async fn open_session(&self, workspace: &str, resume: Option<&str>) -> OpenedSession {
+        let cwd = crate::platform::canonicalize(workspace)
+            .unwrap_or_else(|_| std::path::PathBuf::from(workspace));
+        if let Some(acp_sid) = resume {
+            if self.supports_load_session.load(Ordering::Relaxed) {
+                let params = serde_json::json!({
+                    "sessionId": acp_sid,
+                    "cwd": cwd.to_string_lossy(),
+                    "mcpServers": []
+                });
+                match self.send_request("session/load", Some(params)).await {
+                    Ok(result) => {
+                        return OpenedSession {
+                            acp_session_id: Some(acp_sid.to_string()),
+                            resumed: true,
+                            modes: AcpSessionModes::from_response(&result),
                         }
                     }
+                    Err(e) => warn!(
+                        "ACP [{}]: session/load failed ({e}), starting a new session",
+                        self.agent_name
+                    ),
                 }
-            });
+            }
+        }
+        match self
+            .send_request(
+                "session/new",
+                Some(serde_json::json!({
+                    "cwd": cwd.to_string_lossy(),
+                    "mcpServers": []
+                })),
+            )
+            .await
+        {
+            Ok(result) => OpenedSession {
+                acp_session_id: result
+                    .get("sessionId")
+                    .and_then(|v| v.as_str())
+                    .map(|s| s.to_string()),
+                resumed: false,
+                modes: AcpSessionModes::from_response(&result),
+            },
+            Err(e) => {
+                warn!(
+                    "ACP [{}]: session/new failed ({e}), continuing without ACP session ID",
+                    self.agent_name
+                );
+                OpenedSession::default()
+            }
         }
-
-        Ok(PtyConnection {
-            agent_name: agent_name.to_string(),
-            inner: Mutex::new(PtyConnectionInner {
-                stdin,
-                stdout: BufReader::new(stdout),
-                _child: child,
-            }),
-        })
     }
 
-    /// Send a prompt string to stdin and collect stdout until the process
-    /// stops producing output (no new line within `timeout`) or exits.
-    pub async fn prompt(
+    /// Fold one `session/update` notification into the prompt result and
+    /// forward progress events.
+    fn handle_session_update(
         &self,
-        message: &str,
-        timeout: Duration,
+        params: Option<&serde_json::Value>,
+        result: &mut AcpPromptResult,
+        message_buffer: &mut String,
+        thought_buffer: &mut String,
         progress_tx: Option<&AcpProgressSender>,
-    ) -> Result<AcpPromptResult, String> {
-        let start = std::time::Instant::now();
-        let mut inner = self.inner.lock().await;
-
-        // Write message + newline to stdin
-        let payload = format!("{message}\n");
-        inner
-            .stdin
-            .write_all(payload.as_bytes())
-            .await
-            .map_err(|e| format!("PTY [{}]: failed to write to stdin: {e}", self.agent_name))?;
-        inner
-            .stdin
-            .flush()
-            .await
-            .map_err(|e| format!("PTY [{}]: failed to flush stdin: {e}", self.agent_name))?;
-
-        // Read stdout lines until timeout with no new output, or process exits.
-        // Use a per-line read timeout — if no line arrives within the overall
-        // timeout, we consider the response complete.
-        let mut output_lines = Vec::new();
-        let line_timeout = Duration::from_secs(5).min(timeout);
+    ) {
+        // Parse the update type from params.update.sessionUpdate or params.update.type
+        let update = params.and_then(|p| p.get("update"));
+        let update_type_raw = update
+            .and_then(|u| u.get("sessionUpdate").or_else(|| u.get("type")))
+            .and_then(|t| t.as_str())
+            .unwrap_or("");
+        // Normalize PascalCase to snake_case for matching
+        let update_type: String = if update_type_raw.contains('_') {
+            update_type_raw.to_string()
+        } else {
+            // AgentMessageChunk -> agent_message_chunk
+            let mut result_str = String::new();
+            for (i, c) in update_type_raw.chars().enumerate() {
+                if c.is_uppercase() && i > 0 {
+                    result_str.push('_');
+                }
+                result_str.push(c.to_lowercase().next().unwrap_or(c));
+            }
+            result_str
+        };
+        if update_type != "agent_thought_chunk" && !thought_buffer.is_empty() {
+            result.thoughts.push(std::mem::take(thought_buffer));
+        }
 
-        loop {
-            let mut line = String::new();
-            match tokio::time::timeout(line_timeout, inner.stdout.read_line(&mut line)).await {
-                Ok(Ok(0)) => {
-                    // EOF — process exited
-                    break;
+        match update_type.as_str() {
+            "agent_message_chunk" => {
+                // Extract text from content block
+                let text = update
+                    .and_then(|u| u.get("content"))
+                    .and_then(|c| c.get("text"))
+                    .and_then(|t| t.as_str());
+                if let Some(text) = text {
+                    message_buffer.push_str(text);
+                    if let Some(tx) = progress_tx {
+                        let _ = tx.send(AcpProgressEvent::MessageChunk {
+                            text: text.to_string(),
+                        });
+                    }
                 }
-                Ok(Ok(_)) => {
-                    let trimmed = line.trim_end_matches('\n').trim_end_matches('\r');
-                    output_lines.push(trimmed.to_string());
+            }
+            "agent_thought_chunk" => {
+                // Kept apart from messages; callers opt in to showing it
+                let text = update
+                    .and_then(|u| u.get("content"))
+                    .and_then(|c| c.get("text"))
+                    .and_then(|t| t.as_str());
+                if let Some(text) = text {
+                    debug!(
+                        "ACP [{}] thought: {}",
+                        self.agent_name,
+                        &text[..text.len().min(100)]
+                    );
+                    thought_buffer.push_str(text);
                     if let Some(tx) = progress_tx {
                         let _ = tx.send(AcpProgressEvent::Thinking {
-                            text: trimmed.to_string(),
+                            text: text.to_string(),
                         });
                     }
                 }
-                Ok(Err(e)) => {
-                    // Read error — treat as done
-                    warn!("PTY [{}]: read error: {e}", self.agent_name);
-                    break;
+            }
+            "tool_call" => {
+                let title = update
+                    .and_then(|u| u.get("title"))
+                    .and_then(|t| t.as_str())
+                    .unwrap_or("unknown")
+                    .to_string();
+                let raw_input = update
+                    .and_then(|u| u.get("rawInput"))
+                    .cloned()
+                    .unwrap_or(serde_json::Value::Null);
+                if let Some(tx) = progress_tx {
+                    let _ = tx.send(AcpProgressEvent::ToolStart {
+                        name: title.clone(),
+                    });
                 }
-                Err(_) => {
-                    // Timeout waiting for next line — consider response complete
-                    break;
+                result.tool_calls.push(ToolCallInfo {
+                    name: title,
+                    input: raw_input,
+                });
+                if let Some(update) = update {
+                    self.record_changed_paths(update, result);
                 }
-            }
-
-            // Check overall timeout
-            if start.elapsed() >= timeout {
-                warn!(
-                    "PTY [{}]: overall timeout ({:?}) reached",
-                    self.agent_name, timeout
+                // Flush message buffer before tool calls
+                if !message_buffer.is_empty() {
+                    result.messages.push(std::mem::take(message_buffer));
+                }
+            }
+            "tool_call_update" => {
+                let tool_id = update
+                    .and_then(|u| u.get("toolCallId"))
+                    .and_then(|t| t.as_str())
+                    .unwrap_or("?");
+                let status = update
+                    .and_then(|u| u.get("status"))
+                    .and_then(|s| s.as_str())
+                    .unwrap_or("?");
+                debug!(
+                    "ACP [{}] tool update: id={tool_id} status={status}",
+                    self.agent_name
+                );
+                if let Some(tx) = progress_tx {
+                    let tool_name = update
+                        .and_then(|u| u.get("title"))
+                        .and_then(|t| t.as_str())
+                        .unwrap_or(tool_id)
+                        .to_string();
+                    let _ = tx.send(AcpProgressEvent::ToolComplete {
+                        name: tool_name,
+                        status: status.to_string(),
+                    });
+                }
+                // Capture rawOutput (e.g. command stdout)
+                if let Some(raw) = update.and_then(|u| u.get("rawOutput")) {
+                    let output_str = match raw {
+                        serde_json::Value::String(s) => s.clone(),
+                        other => other.to_string(),
+                    };
+                    if !output_str.is_empty() {
+                        result.messages.push(output_str);
+                    }
+                }
+                if let Some(update) = update {
+                    self.record_changed_paths(update, result);
+                }
+                // Capture content blocks (terminal output, diffs, etc.)
+                if let Some(content_arr) = update
+                    .and_then(|u| u.get("content"))
+                    .and_then(|c| c.as_array())
+                {
+                    for item in content_arr {
+                        let content_type = item.get("type").and_then(|t| t.as_str()).unwrap_or("");
+                        if content_type == "content" {
+                            // Inline text content
+                            if let Some(text) = item
+                                .get("content")
+                                .and_then(|c| c.get("text"))
+                                .and_then(|t| t.as_str())
+                            {
+                                if !text.is_empty() {
+                                    result.messages.push(text.to_string());
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            "plan" => {
+                let entries = update
+                    .and_then(|u| u.get("entries"))
+                    .and_then(|e| e.as_array());
+                if let Some(entries) = entries {
+                    debug!(
+                        "ACP [{}] plan update: {} entries",
+                        self.agent_name,
+                        entries.len()
+                    );
+                    let entries: Vec<AcpPlanEntry> = entries
+                        .iter()
+                        .filter_map(|e| {
+                            Some(AcpPlanEntry {
+                                content: e.get("content")?.as_str()?.to_string(),
+                                status: e
+                                    .get("status")
+                                    .and_then(|s| s.as_str())
+                                    .unwrap_or("pending")
+                                    .to_string(),
+                            })
+                        })
+                        .collect();
+                    // Each update replaces the whole plan
+                    result.plan = entries.clone();
+                    if let Some(tx) = progress_tx {
+                        let _ = tx.send(AcpProgressEvent::Plan { entries });
+                    }
+                }
+            }
+            "usage_update" => {
+                // The prompt response's own `usage`, if any, supersedes this
+                if let Some(usage) = update.and_then(AcpUsage::from_value) {
+                    debug!("ACP [{}] usage update: {usage:?}", self.agent_name);
+                    result.usage = Some(usage);
+                }
+            }
+            _ => {
+                debug!(
+                    "ACP [{}] unhandled session/update type: {update_type}",
+                    self.agent_name
                 );
-                break;
             }
         }
-
-        let duration_ms = start.elapsed().as_millis();
-        let text = output_lines.join("\n");
-        let messages = if text.is_empty() { vec![] } else { vec![text] };
-
-        Ok(AcpPromptResult {
-            completed: true,
-            messages,
-            tool_calls: vec![],
-            files_changed: vec![],
-            duration_ms,
-            context_reset: false,
-        })
     }
 
-    /// Check if the child process is still running.
+    /// Check whether the agent child process (or remote connection) is
+    /// still up.
     pub async fn is_alive(&self) -> bool {
-        let mut inner = self.inner.lock().await;
-        matches!(inner._child.try_wait(), Ok(None))
+        match self.child.lock().await.as_mut() {
+            Some(child) => matches!(child.try_wait(), Ok(None)),
+            None => !self.closed.load(Ordering::SeqCst),
+        }
     }
 
-    /// Get the child process ID.
+    /// Get the child process ID (`None` for remote agents).
     pub async fn pid(&self) -> Option<u32> {
-        let inner = self.inner.lock().await;
-        inner._child.id()
+        self.child.lock().await.as_ref().and_then(|c| c.id())
     }
 
-    /// Kill the child process.
+    /// Kill the agent process, or close the connection to a remote agent.
+    pub async fn kill(&self) {
+        match self.child.lock().await.as_mut() {
+            Some(child) => {
+                let _ = child.kill().await;
+            }
+            None => self.transport.close().await,
+        }
+    }
+
+    /// Gracefully shut down the agent process.
     pub async fn shutdown(&self) -> Result<(), String> {
-        info!("PTY [{}]: shutting down", self.agent_name);
-        let mut inner = self.inner.lock().await;
-        let _ = inner._child.kill().await;
-        info!("PTY [{}]: process terminated", self.agent_name);
+        info!("ACP [{}]: shutting down", self.agent_name);
+
+        // Try sending session/end (best effort)
+        let _ = self.send_request("shutdown", None).await;
+
+        // Kill the child process
+        self.kill().await;
+        info!("ACP [{}]: process terminated", self.agent_name);
         Ok(())
     }
 }
 
 // ---------------------------------------------------------------------------
-// ConnectionKind — dispatch to either ACP or PTY connection
+// Client filesystem capability (fs/read_text_file, fs/write_text_file)
 // ---------------------------------------------------------------------------
 
-/// Wraps either an ACP JSON-RPC connection or a simple PTY stdin/stdout pipe.
-pub enum ConnectionKind {
-    Acp(AcpConnection),
-    Pty(PtyConnection),
-}
+/// Largest file an agent may read through `fs/read_text_file`.
+const MAX_FS_READ_BYTES: u64 = 10 * 1024 * 1024;
 
-impl ConnectionKind {
-    pub async fn is_alive(&self) -> bool {
-        match self {
-            ConnectionKind::Acp(c) => c.is_alive().await,
-            ConnectionKind::Pty(c) => c.is_alive().await,
+/// Resolve a path requested by the agent against the session workspace,
+/// rejecting anything that escapes it (including through symlinks) or hits a
+/// sensitive path.
+fn resolve_workspace_path(
+    workspace: &std::path::Path,
+    requested: &str,
+) -> Result<std::path::PathBuf, String> {
+    use std::path::{Component, PathBuf};
+
+    if requested.is_empty() {
+        return Err("missing path".to_string());
+    }
+    let joined = workspace.join(requested);
+    let mut normalized = PathBuf::new();
+    for component in joined.components() {
+        match component {
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::CurDir => {}
+            other => normalized.push(other),
         }
     }
+    let outside = || format!("'{requested}' is outside the session workspace");
+    if !normalized.starts_with(workspace) {
+        return Err(outside());
+    }
 
-    pub async fn shutdown(&self) -> Result<(), String> {
-        match self {
-            ConnectionKind::Acp(c) => c.shutdown().await,
-            ConnectionKind::Pty(c) => c.shutdown().await,
-        }
+    // The closest existing ancestor must also resolve inside the workspace
+    let mut existing = normalized.as_path();
+    while !existing.exists() {
+        existing = existing.parent().ok_or_else(outside)?;
+    }
+    let real = crate::platform::canonicalize(existing).map_err(|e| e.to_string())?;
+    if !real.starts_with(workspace) {
+        return Err(outside());
     }
 
-    /// Returns true if this is an ACP connection (supports session/end, etc.).
-    pub fn is_acp(&self) -> bool {
-        matches!(self, ConnectionKind::Acp(_))
+    crate::tools::path_guard::check_path(&normalized.to_string_lossy())?;
+    Ok(normalized)
+}
+
+/// Read a text file, optionally starting at 1-based `line` and returning at
+/// most `limit` lines.
+async fn read_text_file(
+    path: &std::path::Path,
+    line: Option<u64>,
+    limit: Option<u64>,
+) -> Result<String, String> {
+    let meta = tokio::fs::metadata(path)
+        .await
+        .map_err(|e| format!("cannot read {}: {e}", path.display()))?;
+    if meta.len() > MAX_FS_READ_BYTES {
+        return Err(format!(
+            "{} is too large ({} bytes, max {MAX_FS_READ_BYTES})",
+            path.display(),
+            meta.len()
+        ));
+    }
+    let content = tokio::fs::read_to_string(path)
+        .await
+        .map_err(|e| format!("cannot read {}: {e}", path.display()))?;
+    if line.is_none() && limit.is_none() {
+        return Ok(content);
     }
+    let skip = line.unwrap_or(1).saturating_sub(1) as usize;
+    let take = limit.map_or(usize::MAX, |l| l as usize);
+    Ok(content
+        .split_inclusive('\n')
+        .skip(skip)
+        .take(take)
+        .collect())
+}
 
-    /// Get a reference to the inner ACP connection, if this is ACP mode.
-    pub fn as_acp(&self) -> Option<&AcpConnection> {
-        match self {
-            ConnectionKind::Acp(c) => Some(c),
-            ConnectionKind::Pty(_) => None,
-        }
+async fn write_text_file(path: &std::path::Path, content: &str) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .map_err(|e| format!("cannot create {}: {e}", parent.display()))?;
     }
+    tokio::fs::write(path, content)
+        .await
+        .map_err(|e| format!("cannot write {}: {e}", path.display()))
+}
 
-    /// Get the child process ID.
-    pub async fn pid(&self) -> Option<u32> {
-        match self {
-            ConnectionKind::Acp(c) => c.pid().await,
-            ConnectionKind::Pty(c) => c.pid().await,
+/// Paths a `tool_call` / `tool_call_update` reports as changed: the `path` of
+/// every `diff` content item, plus the target in `rawInput` for edit tools.
+/// Failed tool calls change nothing.
+fn tool_call_changed_paths(update: &serde_json::Value) -> Vec<String> {
+    if update.get("status").and_then(|s| s.as_str()) == Some("failed") {
+        return Vec::new();
+    }
+    let mut paths: Vec<String> = update
+        .get("content")
+        .and_then(|c| c.as_array())
+        .into_iter()
+        .flatten()
+        .filter(|item| item.get("type").and_then(|t| t.as_str()) == Some("diff"))
+        .filter_map(|item| item.get("path").and_then(|p| p.as_str()))
+        .map(str::to_string)
+        .collect();
+
+    let kind = update.get("kind").and_then(|k| k.as_str()).unwrap_or("");
+    let title = update
+        .get("title")
+        .and_then(|t| t.as_str())
+        .unwrap_or("")
+        .to_lowercase();
+    let is_edit = kind == "edit" || title.starts_with("edit") || title.starts_with("write");
+    if let Some(input) = update.get("rawInput").filter(|_| is_edit) {
+        for key in ["file_path", "path", "notebook_path"] {
+            if let Some(path) = input.get(key).and_then(|v| v.as_str()) {
+                paths.push(path.to_string());
+            }
         }
     }
+    paths.retain(|p| !p.is_empty());
+    paths
 }
 
 // ---------------------------------------------------------------------------
-// Session & prompt result types
+// Client terminal capability (terminal/create, output, wait_for_exit, ...)
 // ---------------------------------------------------------------------------
 
-/// Summary info returned after creating a session
-#[derive(Debug, Clone)]
-pub struct SessionInfo {
-    pub session_id: String,
-    pub agent_id: String,
-    pub workspace: String,
+/// Output kept per terminal when the agent doesn't set `outputByteLimit`.
+const DEFAULT_TERMINAL_OUTPUT_LIMIT: usize = 1024 * 1024;
+
+/// How a terminal command ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TerminalExit {
+    exit_code: Option<i32>,
+    signal: Option<String>,
 }
 
-/// Status of an ACP session
-#[derive(Debug, Clone, PartialEq)]
-pub enum SessionStatus {
-    Active,
-    Prompting,
-    Ended,
+impl TerminalExit {
+    fn from_status(status: std::process::ExitStatus) -> Self {
+        #[cfg(unix)]
+        let signal = std::os::unix::process::ExitStatusExt::signal(&status).map(|n| match n {
+            9 => "SIGKILL".to_string(),
+            15 => "SIGTERM".to_string(),
+            n => format!("SIG{n}"),
+        });
+        #[cfg(not(unix))]
+        let signal = None;
+        Self {
+            exit_code: status.code(),
+            signal,
+        }
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({ "exitCode": self.exit_code, "signal": self.signal })
+    }
 }
 
-/// Record of a single tool call made by the agent during prompt execution
-#[derive(Debug, Clone)]
-pub struct ToolCallInfo {
-    pub name: String,
-    pub input: serde_json::Value,
+/// Output retained for a terminal, trimmed from the front past `limit` bytes.
+#[derive(Debug, Default)]
+struct TerminalOutput {
+    text: String,
+    truncated: bool,
+    limit: usize,
 }
 
-/// Result of an ACP prompt execution
+impl TerminalOutput {
+    fn push(&mut self, chunk: &str) {
+        self.text.push_str(chunk);
+        if self.text.len() > self.limit {
+            let mut cut = self.text.len() - self.limit;
+            while !self.text.is_char_boundary(cut) {
+                cut += 1;
+            }
+            self.text.drain(..cut);
+            self.truncated = true;
+        }
+    }
+}
+
+/// A command started by the agent through `terminal/create`. Dropping the
+/// last handle kills the command if it is still running.
+struct AcpTerminal {
+    output: Arc<std::sync::Mutex<TerminalOutput>>,
+    exit: tokio::sync::watch::Receiver<Option<TerminalExit>>,
+    kill_tx: std::sync::Mutex<Option<tokio::sync::oneshot::Sender<()>>>,
+}
+
+impl AcpTerminal {
+    /// Start `cmd`, capturing stdout and stderr into one output buffer and
+    /// forwarding it as [`AcpProgressEvent::TerminalOutput`] events.
+    fn spawn(
+        mut cmd: Command,
+        output_limit: usize,
+        terminal_id: String,
+        progress_tx: Option<AcpProgressSender>,
+    ) -> std::io::Result<Self> {
+        use tokio::io::AsyncReadExt;
+
+        cmd.stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .kill_on_drop(true);
+        let mut child = cmd.spawn()?;
+
+        let output = Arc::new(std::sync::Mutex::new(TerminalOutput {
+            limit: output_limit,
+            ..Default::default()
+        }));
+        let streams: Vec<Box<dyn tokio::io::AsyncRead + Unpin + Send>> = [
+            child
+                .stdout
+                .take()
+                .map(|s| Box::new(s) as Box<dyn tokio::io::AsyncRead + Unpin + Send>),
+            child
+                .stderr
+                .take()
+                .map(|s| Box::new(s) as Box<dyn tokio::io::AsyncRead + Unpin + Send>),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        let mut readers = Vec::new();
+        for mut stream in streams {
+            let output = output.clone();
+            let progress_tx = progress_tx.clone();
+            let terminal_id = terminal_id.clone();
+            readers.push(tokio::spawn(async move {
+                let mut buf = [0u8; 4096];
+                loop {
+                    match stream.read(&mut buf).await {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => {
+                            let text = String::from_utf8_lossy(&buf[..n]).to_string();
+                            output.lock().unwrap().push(&text);
+                            if let Some(tx) = &progress_tx {
+                                let _ = tx.send(AcpProgressEvent::TerminalOutput {
+                                    terminal_id: terminal_id.clone(),
+                                    text,
+                                });
+                            }
+                        }
+                    }
+                }
+            }));
+        }
+
+        let (exit_tx, exit) = tokio::sync::watch::channel(None);
+        let (kill_tx, kill_rx) = tokio::sync::oneshot::channel::<()>();
+        tokio::spawn(async move {
+            let status = tokio::select! {
+                status = child.wait() => status,
+                // Fires on kill() and when the terminal is dropped
+                _ = kill_rx => {
+                    let _ = child.kill().await;
+                    child.wait().await
+                }
+            };
+            // Report exit only once all output has been captured
+            for reader in readers {
+                let _ = reader.await;
+            }
+            let exit = match status {
+                Ok(status) => TerminalExit::from_status(status),
+                Err(_) => TerminalExit {
+                    exit_code: None,
+                    signal: None,
+                },
+            };
+            let _ = exit_tx.send(Some(exit));
+        });
+
+        Ok(Self {
+            output,
+            exit,
+            kill_tx: std::sync::Mutex::new(Some(kill_tx)),
+        })
+    }
+
+    fn output_json(&self) -> serde_json::Value {
+        let output = self.output.lock().unwrap();
+        serde_json::json!({
+            "output": output.text,
+            "truncated": output.truncated,
+            "exitStatus": self.exit.borrow().as_ref().map(TerminalExit::to_json),
+        })
+    }
+
+    async fn wait(&self) -> TerminalExit {
+        let mut exit = self.exit.clone();
+        let done = exit
+            .wait_for(Option::is_some)
+            .await
+            .ok()
+            .and_then(|done| done.clone());
+        done.unwrap_or(TerminalExit {
+            exit_code: None,
+            signal: None,
+        })
+    }
+
+    fn kill(&self) {
+        if let Some(tx) = self.kill_tx.lock().unwrap().take() {
+            let _ = tx.send(());
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Progress events (streamed during prompt execution)
+// ---------------------------------------------------------------------------
+
+/// Events emitted during ACP prompt execution for real-time progress reporting.
 #[derive(Debug, Clone)]
-pub struct AcpPromptResult {
-    /// Text messages emitted by the agent
-    pub messages: Vec<String>,
-    /// Tool calls executed by the agent
-    pub tool_calls: Vec<ToolCallInfo>,
-    /// Files changed during execution
-    pub files_changed: Vec<String>,
-    /// Whether the prompt completed normally (vs timeout/cancel)
-    pub completed: bool,
-    /// Wall-clock execution time in milliseconds
-    pub duration_ms: u128,
-    /// True if the agent process had crashed and was restarted for this
-    /// prompt. Previous conversation context was lost.
-    pub context_reset: bool,
+pub enum AcpProgressEvent {
+    /// Agent started executing a tool
+    ToolStart { name: String },
+    /// Agent tool execution completed
+    ToolComplete { name: String, status: String },
+    /// Agent is thinking (extended thinking chunk)
+    Thinking { text: String },
+    /// Piece of the agent's reply text, in order
+    MessageChunk { text: String },
+    /// Agent published or revised its plan (full list each time)
+    Plan { entries: Vec<AcpPlanEntry> },
+    /// Output from a command the agent runs in a client-side terminal
+    TerminalOutput { terminal_id: String, text: String },
+    /// The session is busy; this prompt waits behind `position` others
+    Queued { position: usize },
+    /// The agent asked a question and is waiting for an answer
+    Question { text: String },
 }
 
+/// One step of an agent plan (`pending`, `in_progress` or `completed`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AcpPlanEntry {
+    pub content: String,
+    pub status: String,
+}
+
+/// Sender for streaming progress events during prompt execution.
+pub type AcpProgressSender = tokio::sync::mpsc::UnboundedSender<AcpProgressEvent>;
+
 // ---------------------------------------------------------------------------
-// Async Job types
+// Permission policy (per-agent rules checked before asking anyone)
 // ---------------------------------------------------------------------------
 
-/// Status of an async ACP job
-#[derive(Debug, Clone, PartialEq)]
-pub enum AcpJobStatus {
-    Running,
-    Completed,
-    Failed,
+/// Per-agent rules for permission requests. Deny rules always win. Requests
+/// matching an allow rule, touching only `allow_paths` and within the write
+/// budget are approved without asking. Everything else falls back to
+/// `auto_approve` or interactive approval.
+///
+/// Tool rules match the ACP tool kind (`read`, `edit`, `delete`, `move`,
+/// `search`, `execute`, `fetch`, ...) or the first word of the tool title.
+/// `bash`, `shell` and `terminal` are accepted as names for `execute`, and
+/// `write` for `edit`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AcpPermissionPolicy {
+    /// Tools approved without asking (e.g. `["read", "edit"]`)
+    #[serde(default, alias = "allowTools")]
+    pub allow_tools: Vec<String>,
+
+    /// Tools that are always rejected (e.g. `["bash"]`)
+    #[serde(default, alias = "denyTools")]
+    pub deny_tools: Vec<String>,
+
+    /// Glob patterns, relative to the workspace, that allowed tools may
+    /// touch. Empty means any path.
+    #[serde(default, alias = "allowPaths")]
+    pub allow_paths: Vec<String>,
+
+    /// File writes the policy approves per prompt; later writes are asked
+    #[serde(default, alias = "maxFileWrites")]
+    pub max_file_writes: Option<usize>,
 }
 
-/// An async ACP job that runs a prompt in the background.
-#[derive(Debug, Clone)]
-pub struct AcpJob {
-    pub id: String,
-    pub session_id: String,
-    pub agent_id: String,
-    pub status: AcpJobStatus,
-    pub result: Option<AcpPromptResult>,
-    pub error: Option<String>,
-    pub created_at: chrono::DateTime<chrono::Utc>,
-    pub completed_at: Option<chrono::DateTime<chrono::Utc>>,
+/// How the policy answers one request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PolicyVerdict {
+    Allow,
+    Deny(String),
+    /// Not settled by the policy
+    Ask,
 }
 
-/// Summary of a job (for listing/querying)
-#[derive(Debug, Clone)]
-pub struct AcpJobSummary {
-    pub id: String,
-    pub session_id: String,
-    pub agent_id: String,
-    pub status: AcpJobStatus,
-    pub created_at: String,
-    pub completed_at: Option<String>,
-    pub duration_ms: Option<u128>,
-    pub error: Option<String>,
+/// File writes approved during one prompt.
+#[derive(Debug, Default)]
+struct PromptWrites {
+    /// Approved writes, checked against `max_file_writes`
+    count: usize,
+    /// Files approved through `session/request_permission` that the agent
+    /// hasn't written with `fs/write_text_file` yet
+    approved: Vec<std::path::PathBuf>,
 }
 
-const MAX_JOBS: usize = 100;
-const JOB_TTL_SECS: u64 = 3600; // 1 hour
+impl PromptWrites {
+    fn approve(&mut self, paths: &[String], workspace: Option<&std::path::Path>) {
+        self.count += 1;
+        if let Some(workspace) = workspace {
+            self.approved.extend(
+                paths
+                    .iter()
+                    .filter_map(|p| resolve_workspace_path(workspace, p).ok()),
+            );
+        }
+    }
 
-/// Callback invoked when an async job completes. Receives (chat_id, message_text).
-/// Used to push results back to the user's chat via send_message.
-pub type JobCompletionCallback = Arc<
-    dyn Fn(i64, String) -> std::pin::Pin<Box<dyn std::future::Future<Output = ()> + Send>>
-        + Send
-        + Sync,
->;
+    /// Use up an earlier approval for writing `path`.
+    fn take_approved(&mut self, path: &std::path::Path) -> bool {
+        match self.approved.iter().position(|p| p == path) {
+            Some(i) => {
+                self.approved.swap_remove(i);
+                true
+            }
+            None => false,
+        }
+    }
+}
 
-/// An active ACP agent session with its connection
-pub struct AcpSession {
-    pub id: String,
-    pub agent_id: String,
-    pub workspace: String,
-    pub auto_approve: bool,
-    pub status: SessionStatus,
-    pub acp_session_id: Option<String>,
-    pub connection: ConnectionKind,
-    pub created_at: chrono::DateTime<chrono::Utc>,
-    /// Monotonic timestamp of last prompt activity (start or complete).
-    /// Used by the idle reaper to detect stuck/abandoned sessions.
-    pub last_activity: Instant,
-    /// Set to true when the agent process crashed and was restarted.
-    /// The next prompt result will include a context-loss notice, then
-    /// this flag is cleared.
-    pub session_reset: bool,
-    /// Path to the cgroup v2 directory, if resource limits were applied.
-    /// Cleaned up on session end.
-    pub cgroup_path: Option<String>,
-}
-
-// ---------------------------------------------------------------------------
-// AcpManager — global session lifecycle manager
-// ---------------------------------------------------------------------------
-
-pub struct AcpManager {
-    pub config: AcpConfig,
-    sessions: RwLock<HashMap<String, Mutex<AcpSession>>>,
-    /// Map chat_id → session_id for command-based ACP routing
-    chat_sessions: RwLock<HashMap<i64, String>>,
-    /// Per-agent active session count for enforcing max_per_agent
-    agent_session_counts: RwLock<HashMap<String, usize>>,
-    /// In-memory async job store
-    jobs: RwLock<HashMap<String, Mutex<AcpJob>>>,
+/// What a permission request wants to do, as seen by the policy.
+#[derive(Debug)]
+struct PolicyRequest<'a> {
+    kind: &'a str,
+    title: &'a str,
+    paths: Vec<String>,
 }
 
-impl AcpManager {
-    /// Create an AcpManager from a config file path.
-    /// Does NOT spawn any agents — they are created on demand via tools.
-    pub fn from_config_file(path: &str) -> Self {
-        Self::from_config(AcpConfig::from_file(path))
-    }
-
-    /// Create an AcpManager from an already-parsed config.
-    /// Does NOT spawn any agents — they are created on demand via tools.
-    pub fn from_config(config: AcpConfig) -> Self {
-        if !config.agents.is_empty() {
-            info!(
-                "ACP config loaded: {} agent(s) configured ({})",
-                config.agents.len(),
-                config.agents.keys().cloned().collect::<Vec<_>>().join(", ")
-            );
+impl<'a> PolicyRequest<'a> {
+    fn from_tool_call(tool_call: Option<&'a serde_json::Value>) -> Self {
+        let field = |key: &str| {
+            tool_call
+                .and_then(|t| t.get(key))
+                .and_then(|v| v.as_str())
+                .unwrap_or_default()
+        };
+        let mut paths: Vec<String> = tool_call
+            .and_then(|t| t.get("locations"))
+            .and_then(|l| l.as_array())
+            .map(|locations| {
+                locations
+                    .iter()
+                    .filter_map(|l| l.get("path").and_then(|p| p.as_str()))
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+        if let Some(input) = tool_call.and_then(|t| t.get("rawInput")) {
+            for key in ["file_path", "path", "notebook_path"] {
+                if let Some(path) = input.get(key).and_then(|v| v.as_str()) {
+                    if !paths.iter().any(|p| p == path) {
+                        paths.push(path.to_string());
+                    }
+                }
+            }
         }
-        AcpManager {
-            config,
-            sessions: RwLock::new(HashMap::new()),
-            chat_sessions: RwLock::new(HashMap::new()),
-            agent_session_counts: RwLock::new(HashMap::new()),
-            jobs: RwLock::new(HashMap::new()),
+        Self {
+            kind: field("kind"),
+            title: field("title"),
+            paths,
         }
     }
 
-    /// List configured agent names
-    pub fn available_agents(&self) -> Vec<String> {
-        self.config.agents.keys().cloned().collect()
+    fn is_write(&self) -> bool {
+        matches!(self.kind, "edit" | "delete" | "move")
     }
 
-    /// Check if a given agent name is configured
-    pub fn has_agent(&self, name: &str) -> bool {
-        self.config.agents.contains_key(name)
+    fn matches_rule(&self, rule: &str) -> bool {
+        let rule = rule.trim().to_lowercase();
+        let kind = match rule.as_str() {
+            "bash" | "shell" | "terminal" => "execute",
+            "write" => "edit",
+            other => other,
+        };
+        if !self.kind.is_empty() && self.kind.eq_ignore_ascii_case(kind) {
+            return true;
+        }
+        let first_word = self
+            .title
+            .trim_start_matches(['`', ' '])
+            .split(|c: char| c.is_whitespace() || c == '`')
+            .next()
+            .unwrap_or_default();
+        !first_word.is_empty() && first_word.eq_ignore_ascii_case(&rule)
     }
 
-    /// Get agent config by name
-    pub fn agent_config(&self, name: &str) -> Option<&AcpAgentConfig> {
-        self.config.agents.get(name)
+    fn label(&self) -> &str {
+        if self.title.is_empty() {
+            self.kind
+        } else {
+            self.title
+        }
     }
+}
 
-    /// Spawn a new agent process, perform ACP handshake, and create a session.
-    pub async fn new_session(
+impl AcpPermissionPolicy {
+    fn evaluate(
         &self,
-        agent_id: &str,
-        workspace: Option<&str>,
-        auto_approve: Option<bool>,
-    ) -> Result<SessionInfo, String> {
-        // Enforce process pool limits (before config lookup / spawn)
+        request: &PolicyRequest<'_>,
+        workspace: Option<&std::path::Path>,
+        approved_writes: usize,
+    ) -> PolicyVerdict {
+        if self.deny_tools.iter().any(|r| request.matches_rule(r)) {
+            return PolicyVerdict::Deny(format!("'{}' is denied by policy", request.label()));
+        }
+        if !self.allow_tools.iter().any(|r| request.matches_rule(r)) {
+            return PolicyVerdict::Ask;
+        }
+        if !request
+            .paths
+            .iter()
+            .all(|path| self.path_allowed(path, workspace))
         {
-            let sessions = self.sessions.read().await;
-            let total = sessions.len();
-            if total >= self.config.max_sessions {
-                return Err(format!(
-                    "ACP session limit reached ({total}/{}). End an existing session first.",
-                    self.config.max_sessions
-                ));
-            }
+            return PolicyVerdict::Ask;
         }
-
-        let agent_config = self
-            .config
-            .agents
-            .get(agent_id)
-            .ok_or_else(|| format!("ACP agent '{agent_id}' not configured"))?
-            .clone();
-
+        if request.is_write()
+            && self
+                .max_file_writes
+                .is_some_and(|max| approved_writes >= max)
         {
-            let counts = self.agent_session_counts.read().await;
-            let agent_count = counts.get(agent_id).copied().unwrap_or(0);
-            if agent_count >= self.config.max_per_agent {
-                return Err(format!(
-                    "ACP per-agent limit reached for '{agent_id}' ({agent_count}/{}). End an existing session first.",
-                    self.config.max_per_agent
-                ));
-            }
+            return PolicyVerdict::Ask;
         }
+        PolicyVerdict::Allow
+    }
 
-        let effective_auto_approve = auto_approve
-            .or(agent_config.auto_approve)
-            .unwrap_or(self.config.default_auto_approve);
-
-        let effective_workspace = workspace
-            .map(|s| s.to_string())
-            .or_else(|| agent_config.workspace.clone())
-            .unwrap_or_else(|| ".".to_string());
-
-        let is_pty_mode = agent_config.mode == "pty";
+    fn path_allowed(&self, path: &str, workspace: Option<&std::path::Path>) -> bool {
+        use std::path::{Component, Path};
 
-        let (connection, acp_session_id) = if is_pty_mode {
-            // PTY mode — simple stdin/stdout subprocess, no JSON-RPC
-            let pty_conn =
-                PtyConnection::spawn(agent_id, &agent_config, Some(&effective_workspace)).await?;
-            (ConnectionKind::Pty(pty_conn), None)
+        if self.allow_paths.is_empty() {
+            return true;
+        }
+        let path = Path::new(path);
+        let relative = if path.is_absolute() {
+            match workspace.and_then(|ws| path.strip_prefix(ws).ok()) {
+                Some(rel) => rel,
+                None => return false,
+            }
         } else {
-            // ACP mode — full JSON-RPC protocol
-            let request_timeout = Duration::from_secs(DEFAULT_REQUEST_TIMEOUT_SECS);
-            let acp_conn = AcpConnection::spawn(
-                agent_id,
-                &agent_config,
-                Some(&effective_workspace),
-                request_timeout,
-            )
-            .await?;
-
-            // Create an ACP-level session with workspace as cwd
-            let cwd = std::path::Path::new(&effective_workspace)
-                .canonicalize()
-                .unwrap_or_else(|_| std::path::PathBuf::from(&effective_workspace));
-            let acp_session_id = match acp_conn
-                .send_request(
-                    "session/new",
-                    Some(serde_json::json!({
-                        "cwd": cwd.to_string_lossy(),
-                        "mcpServers": []
-                    })),
-                )
-                .await
-            {
-                Ok(result) => result
-                    .get("sessionId")
-                    .and_then(|v| v.as_str())
-                    .map(|s| s.to_string()),
-                Err(e) => {
-                    warn!(
-                        "ACP [{}]: session/new failed ({e}), continuing without ACP session ID",
-                        agent_id
-                    );
-                    None
-                }
-            };
-            (ConnectionKind::Acp(acp_conn), acp_session_id)
+            path
         };
+        if relative
+            .components()
+            .any(|c| matches!(c, Component::ParentDir))
+        {
+            return false;
+        }
+        self.allow_paths.iter().any(|pattern| {
+            relative.starts_with(pattern.trim_end_matches('/'))
+                || glob::Pattern::new(pattern).is_ok_and(|p| p.matches_path(relative))
+        })
+    }
+}
 
-        let session_id = uuid::Uuid::new_v4().to_string();
+// ---------------------------------------------------------------------------
+// Interactive permissions (used when auto_approve is off)
+// ---------------------------------------------------------------------------
 
-        // Apply cgroup resource limits if configured
-        let cgroup_path = if let Some(ref limits) = agent_config.resource_limits {
-            if let Some(pid) = connection.pid().await {
-                apply_resource_limits(pid, &session_id, limits)
-            } else {
-                warn!("ACP [{agent_id}]: could not get child PID for cgroup setup");
-                None
-            }
-        } else {
-            None
-        };
+/// A `session/request_permission` call forwarded to a user for approval.
+#[derive(Debug, Clone)]
+pub struct AcpPermissionRequest {
+    pub agent_name: String,
+    /// Tool call title as reported by the agent (e.g. "Run `cargo test`")
+    pub tool_name: String,
+    pub raw_input: serde_json::Value,
+}
 
-        let info = SessionInfo {
-            session_id: session_id.clone(),
-            agent_id: agent_id.to_string(),
-            workspace: effective_workspace.clone(),
-        };
+/// A user's answer to an `AcpPermissionRequest`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AcpPermissionDecision {
+    AllowOnce,
+    /// Allow and let the agent remember the choice for similar calls
+    AllowAlways,
+    Reject,
+    /// No answer before the permission timeout
+    Cancelled,
+}
 
-        let session = AcpSession {
-            id: session_id.clone(),
-            agent_id: agent_id.to_string(),
-            workspace: effective_workspace,
-            auto_approve: effective_auto_approve,
-            status: SessionStatus::Active,
-            acp_session_id,
-            connection,
-            created_at: chrono::Utc::now(),
-            last_activity: Instant::now(),
-            session_reset: false,
-            cgroup_path,
-        };
+impl AcpPermissionDecision {
+    /// Parse a chat reply such as "approve", "always" or "#deny".
+    /// `@mention` tokens are ignored so group replies to the bot also match.
+    pub fn parse_reply(text: &str) -> Option<Self> {
+        let words: Vec<String> = text
+            .split_whitespace()
+            .filter(|w| !w.starts_with('@'))
+            .map(|w| {
+                w.trim_start_matches('#')
+                    .trim_end_matches(['.', '!'])
+                    .to_lowercase()
+            })
+            .collect();
+        let words: Vec<&str> = words.iter().map(String::as_str).collect();
+        match words.as_slice() {
+            ["approve" | "allow" | "yes" | "y"] => Some(Self::AllowOnce),
+            ["always"] | ["approve" | "allow", "always"] => Some(Self::AllowAlways),
+            ["deny" | "reject" | "no" | "n"] => Some(Self::Reject),
+            _ => None,
+        }
+    }
+}
 
-        self.sessions
-            .write()
-            .await
-            .insert(session_id, Mutex::new(session));
+/// A permission request waiting for its answer in a chat.
+struct PendingPermission {
+    /// Sender whose message started the prompt; `None` for background jobs
+    requester: Option<String>,
+    tx: tokio::sync::oneshot::Sender<AcpPermissionDecision>,
+}
 
-        // Increment per-agent session counter
-        *self
-            .agent_session_counts
-            .write()
-            .await
-            .entry(agent_id.to_string())
-            .or_insert(0) += 1;
+/// Whether a chat message asks to stop the running ACP prompt
+/// (`#stop` or `/acp stop`, optionally after an `@mention`).
+pub fn is_stop_command(text: &str) -> bool {
+    let words: Vec<String> = text
+        .split_whitespace()
+        .filter(|w| !w.starts_with('@'))
+        .map(str::to_lowercase)
+        .collect();
+    let words: Vec<&str> = words.iter().map(String::as_str).collect();
+    matches!(words.as_slice(), ["#stop"] | ["/acp", "stop"])
+}
 
-        info!(
-            "ACP session created: {} (agent={agent_id}, auto_approve={effective_auto_approve})",
-            info.session_id
-        );
-        Ok(info)
-    }
+/// Asks a user to approve an agent tool call and resolves to their decision.
+pub type AcpPermissionHandler = Arc<
+    dyn Fn(
+            AcpPermissionRequest,
+        )
+            -> std::pin::Pin<Box<dyn std::future::Future<Output = AcpPermissionDecision> + Send>>
+        + Send
+        + Sync,
+>;
 
-    /// Send a prompt to an existing session and wait for completion.
-    ///
-    /// If the agent process has crashed, this method attempts to respawn the
-    /// process and re-create the ACP session before sending the prompt. The
-    /// returned `AcpPromptResult.context_reset` will be `true` to indicate
-    /// that previous conversation context was lost.
-    pub async fn prompt(
-        &self,
-        session_id: &str,
-        message: &str,
-        timeout_secs: Option<u64>,
-        progress_tx: Option<&AcpProgressSender>,
-    ) -> Result<AcpPromptResult, String> {
-        let sessions = self.sessions.read().await;
-        let session_mutex = sessions
-            .get(session_id)
-            .ok_or_else(|| format!("ACP session '{session_id}' not found"))?;
+/// Pick the `optionId` of the first option whose `kind` is in `kinds`, then
+/// fall back to any option whose kind starts with `prefix`.
+fn find_permission_option<'a>(
+    options: &'a [serde_json::Value],
+    kinds: &[&str],
+    prefix: &str,
+) -> Option<&'a str> {
+    fn kind_of(opt: &serde_json::Value) -> Option<&str> {
+        opt.get("kind").and_then(|k| k.as_str())
+    }
+    kinds
+        .iter()
+        .find_map(|want| options.iter().find(|opt| kind_of(opt) == Some(*want)))
+        .or_else(|| {
+            options
+                .iter()
+                .find(|opt| kind_of(opt).is_some_and(|k| k.starts_with(prefix)))
+        })
+        .and_then(|opt| opt.get("optionId"))
+        .and_then(|v| v.as_str())
+}
 
-        let mut session = session_mutex.lock().await;
-        if session.status == SessionStatus::Ended {
-            return Err(format!("ACP session '{session_id}' has ended"));
+/// Build the `outcome` object answering a `session/request_permission` call.
+fn permission_outcome(
+    params: Option<&serde_json::Value>,
+    decision: AcpPermissionDecision,
+) -> serde_json::Value {
+    let options = params
+        .and_then(|p| p.get("options"))
+        .and_then(|o| o.as_array())
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    let option_id = match decision {
+        AcpPermissionDecision::AllowAlways => {
+            find_permission_option(options, &["allow_always", "allow_once"], "allow")
+                .or(Some("allow"))
         }
-
-        // --- Crash recovery: detect dead process and respawn ---------------
-        if !session.connection.is_alive().await {
-            warn!(
-                "ACP [{}]: agent process died, attempting restart (session={})",
-                session.agent_id, session_id
-            );
-            if let Err(e) = self.recover_session(&mut session).await {
-                session.status = SessionStatus::Ended;
-                return Err(format!(
-                    "ACP [{}]: agent process died and recovery failed: {e}",
-                    session.agent_id
-                ));
-            }
+        AcpPermissionDecision::AllowOnce => {
+            find_permission_option(options, &["allow_once"], "allow").or(Some("allow"))
         }
+        AcpPermissionDecision::Reject => {
+            find_permission_option(options, &["reject_once"], "reject")
+        }
+        AcpPermissionDecision::Cancelled => None,
+    };
+    match option_id {
+        Some(id) => serde_json::json!({"outcome": "selected", "optionId": id}),
+        None => serde_json::json!({"outcome": "cancelled"}),
+    }
+}
 
-        session.status = SessionStatus::Prompting;
-        session.last_activity = Instant::now();
+/// Chat message asking the user to answer a permission request.
+/// Whether a chat message is a permission prompt from
+/// `format_permission_prompt`, so channels can render it with buttons.
+pub fn is_permission_prompt(text: &str) -> bool {
+    text.strip_prefix("[ACP ")
+        .and_then(|rest| rest.split_once("] "))
+        .is_some_and(|(_, rest)| rest.starts_with("Permission requested: "))
+}
 
-        let timeout = Duration::from_secs(timeout_secs.unwrap_or(self.config.prompt_timeout_secs));
+fn format_permission_prompt(request: &AcpPermissionRequest, timeout: Duration) -> String {
+    const MAX_INPUT_CHARS: usize = 1500;
+    let mut input = match &request.raw_input {
+        serde_json::Value::Null => String::new(),
+        v => serde_json::to_string_pretty(v).unwrap_or_default(),
+    };
+    if input.chars().count() > MAX_INPUT_CHARS {
+        input = input.chars().take(MAX_INPUT_CHARS).collect::<String>() + "\n…";
+    }
+    let mut text = format!(
+        "[ACP {}] Permission requested: {}",
+        request.agent_name, request.tool_name
+    );
+    if !input.is_empty() {
+        text.push_str(&format!("\n{input}"));
+    }
+    text.push_str(&format!(
+        "\n\nReply \"approve\", \"always\" or \"deny\" within {}s.",
+        timeout.as_secs()
+    ));
+    text
+}
 
-        let result = match &session.connection {
-            ConnectionKind::Acp(conn) => {
-                let acp_sid = session
-                    .acp_session_id
-                    .as_deref()
-                    .ok_or_else(|| format!("ACP session '{session_id}' has no ACP session ID"))?;
-                let params = serde_json::json!({
-                    "sessionId": acp_sid,
-                    "prompt": [{"type": "text", "text": message}]
-                });
-                conn.prompt_streaming(params, session.auto_approve, timeout, progress_tx)
-                    .await
-            }
-            ConnectionKind::Pty(conn) => conn.prompt(message, timeout, progress_tx).await,
-        };
-
-        session.status = SessionStatus::Active;
-        session.last_activity = Instant::now();
-
-        // Consume the reset flag so it's only reported once.
-        let context_reset = session.session_reset;
-        session.session_reset = false;
-
-        match result {
-            Ok(mut r) => {
-                r.context_reset = context_reset;
-                info!(
-                    "ACP [{}] prompt completed in {}ms ({} messages, {} tool calls, {} files{})",
-                    session.agent_id,
-                    r.duration_ms,
-                    r.messages.len(),
-                    r.tool_calls.len(),
-                    r.files_changed.len(),
-                    if context_reset { ", context_reset" } else { "" }
-                );
-                Ok(r)
-            }
-            Err(e) => {
-                error!("ACP [{}] prompt failed: {e}", session.agent_id);
-                Err(e)
-            }
-        }
-    }
+// ---------------------------------------------------------------------------
+// PTY connection — simple stdin/stdout subprocess for non-ACP CLI tools
+// ---------------------------------------------------------------------------
 
-    /// Attempt to respawn the agent process and re-create a session,
-    /// replacing the dead connection in-place. Sets `session_reset = true`.
-    async fn recover_session(&self, session: &mut AcpSession) -> Result<(), String> {
-        let agent_config = self
-            .config
-            .agents
-            .get(&session.agent_id)
-            .ok_or_else(|| format!("Agent '{}' no longer configured", session.agent_id))?
-            .clone();
+struct PtyConnectionInner {
+    stdin: tokio::process::ChildStdin,
+    stdout: BufReader<tokio::process::ChildStdout>,
+    _child: Child,
+}
 
-        if agent_config.mode == "pty" {
-            // PTY mode — just respawn the process
-            let new_conn =
-                PtyConnection::spawn(&session.agent_id, &agent_config, Some(&session.workspace))
-                    .await?;
-            session.connection = ConnectionKind::Pty(new_conn);
-            session.acp_session_id = None;
-        } else {
-            // ACP mode — respawn + re-initialize + session/new
-            let request_timeout = Duration::from_secs(DEFAULT_REQUEST_TIMEOUT_SECS);
-            let new_connection = AcpConnection::spawn(
-                &session.agent_id,
-                &agent_config,
-                Some(&session.workspace),
-                request_timeout,
-            )
-            .await?;
-
-            let cwd = std::path::Path::new(&session.workspace)
-                .canonicalize()
-                .unwrap_or_else(|_| std::path::PathBuf::from(&session.workspace));
-            let new_acp_session_id = match new_connection
-                .send_request(
-                    "session/new",
-                    Some(serde_json::json!({
-                        "cwd": cwd.to_string_lossy(),
-                        "mcpServers": []
-                    })),
-                )
-                .await
-            {
-                Ok(result) => result
-                    .get("sessionId")
-                    .and_then(|v| v.as_str())
-                    .map(|s| s.to_string()),
-                Err(e) => {
-                    warn!(
-                        "ACP [{}]: session/new failed during recovery ({e}), continuing without ACP session ID",
-                        session.agent_id
-                    );
-                    None
-                }
-            };
-            session.connection = ConnectionKind::Acp(new_connection);
-            session.acp_session_id = new_acp_session_id;
-        }
+/// A simple subprocess connection that pipes prompts via stdin and reads
+/// stdout until a configurable end-of-response marker or timeout.
+/// Unlike `AcpConnection`, there is no JSON-RPC framing — input is sent
+/// as-is and output is collected line-by-line.
+pub struct PtyConnection {
+    agent_name: String,
+    inner: Mutex<PtyConnectionInner>,
+}
 
-        // Clean up old cgroup and set up new one if limits configured
-        if let Some(ref old_cg) = session.cgroup_path {
-            cleanup_cgroup(old_cg);
+impl PtyConnection {
+    /// Spawn the agent process and capture stdin/stdout.
+    pub async fn spawn(
+        agent_name: &str,
+        config: &AcpAgentConfig,
+        workspace: Option<&str>,
+    ) -> Result<Self, String> {
+        if config.launch == "remote" {
+            return Err(format!(
+                "ACP agent '{agent_name}': PTY mode needs a local process; remote agents must use ACP mode"
+            ));
         }
-        session.cgroup_path = if let Some(ref limits) = agent_config.resource_limits {
-            if let Some(pid) = session.connection.pid().await {
-                apply_resource_limits(pid, &session.id, limits)
-            } else {
-                None
-            }
-        } else {
-            None
-        };
-
-        session.session_reset = true;
-        session.last_activity = Instant::now();
+        let mut cmd = build_spawn_command(config, workspace);
 
         info!(
-            "ACP [{}]: process recovered successfully (session={})",
-            session.agent_id, session.id
+            "ACP/PTY: spawning agent '{agent_name}' ({} {})",
+            config.launch, config.command
         );
-        Ok(())
-    }
 
-    /// End a session and terminate the agent process.
-    pub async fn end_session(&self, session_id: &str) -> Result<(), String> {
-        let session_mutex = {
-            let mut sessions = self.sessions.write().await;
-            sessions
-                .remove(session_id)
-                .ok_or_else(|| format!("ACP session '{session_id}' not found"))?
-        };
+        let mut child = cmd
+            .spawn()
+            .map_err(|e| format!("Failed to spawn PTY agent '{agent_name}': {e}"))?;
 
-        let mut session = session_mutex.lock().await;
+        let stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| format!("PTY agent '{agent_name}': failed to capture stdin"))?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| format!("PTY agent '{agent_name}': failed to capture stdout"))?;
 
-        // Send session/end to agent (ACP mode only, best effort)
-        if let Some(acp_sid) = &session.acp_session_id {
-            if let Some(conn) = session.connection.as_acp() {
-                let _ = conn
-                    .send_request(
-                        "session/end",
-                        Some(serde_json::json!({"sessionId": acp_sid})),
-                    )
-                    .await;
-            }
+        // Drain stderr to tracing::debug
+        if let Some(stderr) = child.stderr.take() {
+            let name = agent_name.to_string();
+            tokio::spawn(async move {
+                let mut reader = BufReader::new(stderr);
+                let mut line = String::new();
+                loop {
+                    line.clear();
+                    match reader.read_line(&mut line).await {
+                        Ok(0) | Err(_) => break,
+                        Ok(_) => {
+                            let trimmed = line.trim();
+                            if !trimmed.is_empty() {
+                                debug!("PTY [{name}] stderr: {trimmed}");
+                            }
+                        }
+                    }
+                }
+            });
         }
 
-        session.connection.shutdown().await?;
-        session.status = SessionStatus::Ended;
+        Ok(PtyConnection {
+            agent_name: agent_name.to_string(),
+            inner: Mutex::new(PtyConnectionInner {
+                stdin,
+                stdout: BufReader::new(stdout),
+                _child: child,
+            }),
+        })
+    }
 
-        // Clean up cgroup if one was created
-        if let Some(ref cg_path) = session.cgroup_path {
-            cleanup_cgroup(cg_path);
-        }
+    /// Send a prompt string to stdin and collect stdout until the process
+    /// stops producing output (no new line within `timeout`) or exits.
+    pub async fn prompt(
+        &self,
+        message: &str,
+        timeout: Duration,
+        progress_tx: Option<&AcpProgressSender>,
+    ) -> Result<AcpPromptResult, String> {
+        let start = std::time::Instant::now();
+        let mut inner = self.inner.lock().await;
 
-        // Decrement per-agent session counter
-        {
-            let mut counts = self.agent_session_counts.write().await;
-            if let Some(count) = counts.get_mut(&session.agent_id) {
-                *count = count.saturating_sub(1);
-                if *count == 0 {
-                    counts.remove(&session.agent_id);
+        // Write message + newline to stdin
+        let payload = format!("{message}\n");
+        inner
+            .stdin
+            .write_all(payload.as_bytes())
+            .await
+            .map_err(|e| format!("PTY [{}]: failed to write to stdin: {e}", self.agent_name))?;
+        inner
+            .stdin
+            .flush()
+            .await
+            .map_err(|e| format!("PTY [{}]: failed to flush stdin: {e}", self.agent_name))?;
+
+        // Read stdout lines until timeout with no new output, or process exits.
+        // Use a per-line read timeout — if no line arrives within the overall
+        // timeout, we consider the response complete.
+        let mut output_lines = Vec::new();
+        let line_timeout = Duration::from_secs(5).min(timeout);
+
+        loop {
+            let mut line = String::new();
+            match tokio::time::timeout(line_timeout, inner.stdout.read_line(&mut line)).await {
+                Ok(Ok(0)) => {
+                    // EOF — process exited
+                    break;
+                }
+                Ok(Ok(_)) => {
+                    let trimmed = line.trim_end_matches('\n').trim_end_matches('\r');
+                    output_lines.push(trimmed.to_string());
+                    if let Some(tx) = progress_tx {
+                        let _ = tx.send(AcpProgressEvent::Thinking {
+                            text: trimmed.to_string(),
+                        });
+                    }
+                }
+                Ok(Err(e)) => {
+                    // Read error — treat as done
+                    warn!("PTY [{}]: read error: {e}", self.agent_name);
+                    break;
+                }
+                Err(_) => {
+                    // Timeout waiting for next line — consider response complete
+                    break;
                 }
             }
+
+            // Check overall timeout
+            if start.elapsed() >= timeout {
+                warn!(
+                    "PTY [{}]: overall timeout ({:?}) reached",
+                    self.agent_name, timeout
+                );
+                break;
+            }
         }
 
-        // Unbind any chats referencing this session
-        let mut chat_sessions = self.chat_sessions.write().await;
-        chat_sessions.retain(|_, sid| sid != session_id);
+        let duration_ms = start.elapsed().as_millis();
+        let text = output_lines.join("\n");
+        let messages = if text.is_empty() { vec![] } else { vec![text] };
 
-        info!("ACP session ended: {session_id}");
-        Ok(())
+        Ok(AcpPromptResult {
+            completed: true,
+            messages,
+            tool_calls: vec![],
+            files_changed: vec![],
+            plan: vec![],
+            thoughts: vec![],
+            usage: None,
+            duration_ms,
+            context_reset: false,
+        })
     }
 
-    /// List all active sessions.
-    pub async fn list_sessions(&self) -> Vec<SessionSummary> {
-        let sessions = self.sessions.read().await;
-        let mut summaries = Vec::new();
-        for (id, session_mutex) in sessions.iter() {
-            let session = session_mutex.lock().await;
-            summaries.push(SessionSummary {
-                session_id: id.clone(),
-                agent_id: session.agent_id.clone(),
-                workspace: session.workspace.clone(),
-                status: session.status.clone(),
-                created_at: session.created_at.to_rfc3339(),
-                idle_secs: session.last_activity.elapsed().as_secs(),
-            });
-        }
-        summaries
+    /// Check if the child process is still running.
+    pub async fn is_alive(&self) -> bool {
+        let mut inner = self.inner.lock().await;
+        matches!(inner._child.try_wait(), Ok(None))
     }
 
-    // -----------------------------------------------------------------------
-    // Chat-to-session binding (for command-based ACP)
-    // -----------------------------------------------------------------------
+    /// Get the child process ID.
+    pub async fn pid(&self) -> Option<u32> {
+        let inner = self.inner.lock().await;
+        inner._child.id()
+    }
 
-    /// Bind a chat to an ACP session. Messages in this chat will be routed
-    /// to the ACP agent instead of the LLM.
-    pub async fn bind_chat(&self, chat_id: i64, session_id: &str) {
-        self.chat_sessions
-            .write()
-            .await
-            .insert(chat_id, session_id.to_string());
-        debug!("ACP: bound chat {chat_id} to session {session_id}");
+    /// Kill the child process.
+    pub async fn shutdown(&self) -> Result<(), String> {
+        info!("PTY [{}]: shutting down", self.agent_name);
+        let mut inner = self.inner.lock().await;
+        let _ = inner._child.kill().await;
+        info!("PTY [{}]: process terminated", self.agent_name);
+        Ok(())
     }
+}
 
-    /// Unbind a chat from its ACP session.
-    pub async fn unbind_chat(&self, chat_id: i64) {
-        self.chat_sessions.write().await.remove(&chat_id);
-        debug!("ACP: unbound chat {chat_id}");
+// ---------------------------------------------------------------------------
+// ConnectionKind — dispatch to either ACP or PTY connection
+// ---------------------------------------------------------------------------
+
+/// Wraps either an ACP JSON-RPC connection or a simple PTY stdin/stdout pipe.
+pub enum ConnectionKind {
+    /// Shared so a running prompt can be cancelled without the session lock.
+    Acp(Arc<AcpConnection>),
+    Pty(Box<PtyConnection>),
+}
+
+impl ConnectionKind {
+    pub async fn is_alive(&self) -> bool {
+        match self {
+            ConnectionKind::Acp(c) => c.is_alive().await,
+            ConnectionKind::Pty(c) => c.is_alive().await,
+        }
     }
 
-    /// Get the session_id bound to a chat, if any.
-    pub async fn chat_session(&self, chat_id: i64) -> Option<String> {
-        self.chat_sessions.read().await.get(&chat_id).cloned()
+    pub async fn shutdown(&self) -> Result<(), String> {
+        match self {
+            ConnectionKind::Acp(c) => c.shutdown().await,
+            ConnectionKind::Pty(c) => c.shutdown().await,
+        }
     }
 
-    /// End the session bound to a chat and unbind it. Returns Ok if a session
-    /// existed and was ended, Err if no session was bound.
-    pub async fn end_chat_session(&self, chat_id: i64) -> Result<(), String> {
-        let session_id = self
-            .chat_sessions
-            .read()
-            .await
-            .get(&chat_id)
-            .cloned()
-            .ok_or_else(|| "No active ACP session in this chat".to_string())?;
+    /// Returns true if this is an ACP connection (supports session/end, etc.).
+    pub fn is_acp(&self) -> bool {
+        matches!(self, ConnectionKind::Acp(_))
+    }
 
-        self.end_session(&session_id).await?;
-        self.unbind_chat(chat_id).await;
-        Ok(())
+    /// Get a reference to the inner ACP connection, if this is ACP mode.
+    pub fn as_acp(&self) -> Option<&AcpConnection> {
+        match self {
+            ConnectionKind::Acp(c) => Some(c.as_ref()),
+            ConnectionKind::Pty(_) => None,
+        }
     }
 
-    // -----------------------------------------------------------------------
-    // Async job management
-    // -----------------------------------------------------------------------
+    /// Get the child process ID.
+    pub async fn pid(&self) -> Option<u32> {
+        match self {
+            ConnectionKind::Acp(c) => c.pid().await,
+            ConnectionKind::Pty(c) => c.pid().await,
+        }
+    }
+}
 
-    /// Submit an async job that runs a prompt in the background.
-    /// Returns the job ID immediately. The job runs in a background task and
-    /// optionally calls `on_complete` with the result when done.
-    pub async fn submit_job(
-        self: &Arc<Self>,
-        session_id: &str,
-        message: &str,
-        timeout_secs: Option<u64>,
-        chat_id: Option<i64>,
-        on_complete: Option<JobCompletionCallback>,
-    ) -> Result<String, String> {
-        // Validate session exists
-        {
-            let sessions = self.sessions.read().await;
-            let session_mutex = sessions
-                .get(session_id)
-                .ok_or_else(|| format!("ACP session '{session_id}' not found"))?;
-            let session = session_mutex.lock().await;
-            if session.status == SessionStatus::Ended {
-                return Err(format!("ACP session '{session_id}' has ended"));
+// ---------------------------------------------------------------------------
+// Prompt attachments
+// ---------------------------------------------------------------------------
+
+/// Extra content sent along with a prompt's text.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AcpAttachment {
+    /// Base64-encoded image, sent as an ACP `image` content block
+    Image { data: String, mime_type: String },
+    /// File on the local disk, sent as a `resource_link` the agent can read
+    File { path: String },
+}
+
+impl AcpAttachment {
+    /// The ACP content block for this attachment. Images fall back to a text
+    /// note when the agent did not advertise image support.
+    fn to_content_block(&self, accepts_images: bool) -> serde_json::Value {
+        match self {
+            AcpAttachment::Image { data, mime_type } if accepts_images => serde_json::json!({
+                "type": "image",
+                "data": data,
+                "mimeType": mime_type,
+            }),
+            AcpAttachment::Image { mime_type, .. } => serde_json::json!({
+                "type": "text",
+                "text": format!("[An image ({mime_type}) was attached, but this agent does not accept images]"),
+            }),
+            AcpAttachment::File { path } => {
+                let abs = crate::platform::canonicalize(path)
+                    .unwrap_or_else(|_| std::path::PathBuf::from(path));
+                let name = abs
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_else(|| path.clone());
+                serde_json::json!({
+                    "type": "resource_link",
+                    "uri": format!("file://{}", abs.to_string_lossy()),
+                    "name": name,
+                })
             }
         }
+    }
 
-        // Enforce job limit
-        self.cleanup_expired_jobs().await;
-        {
-            let jobs = self.jobs.read().await;
-            let running = jobs.values().count();
-            if running >= MAX_JOBS {
-                return Err(format!(
-                    "Job limit reached ({running}/{MAX_JOBS}). Wait for existing jobs to complete."
-                ));
+    /// Plain-text form for PTY agents, which only read stdin.
+    fn to_text(&self) -> String {
+        match self {
+            AcpAttachment::Image { mime_type, .. } => {
+                format!("[An image ({mime_type}) was attached but cannot be shown here]")
             }
+            AcpAttachment::File { path } => format!("[Attached file: {path}]"),
         }
+    }
+}
 
-        let job_id = uuid::Uuid::new_v4().to_string();
+/// Build the `prompt` array of a `session/prompt` request.
+fn prompt_content_blocks(
+    message: &str,
+    attachments: &[AcpAttachment],
+    accepts_images: bool,
+) -> Vec<serde_json::Value> {
+    let mut blocks = Vec::with_capacity(attachments.len() + 1);
+    if !message.is_empty() || attachments.is_empty() {
+        blocks.push(serde_json::json!({"type": "text", "text": message}));
+    }
+    blocks.extend(
+        attachments
+            .iter()
+            .map(|a| a.to_content_block(accepts_images)),
+    );
+    blocks
+}
 
-        // Look up agent_id for the job record
-        let agent_id = {
-            let sessions = self.sessions.read().await;
-            let session_mutex = sessions.get(session_id).unwrap();
-            let session = session_mutex.lock().await;
-            session.agent_id.clone()
-        };
+// ---------------------------------------------------------------------------
+// Session & prompt result types
+// ---------------------------------------------------------------------------
 
-        let job = AcpJob {
-            id: job_id.clone(),
-            session_id: session_id.to_string(),
-            agent_id: agent_id.clone(),
-            status: AcpJobStatus::Running,
-            result: None,
-            error: None,
-            created_at: chrono::Utc::now(),
-            completed_at: None,
-        };
+/// Summary info returned after creating a session
+#[derive(Debug, Clone)]
+pub struct SessionInfo {
+    pub session_id: String,
+    pub agent_id: String,
+    pub workspace: String,
+    /// Modes the agent offers for this session, if it reported any
+    pub modes: Option<AcpSessionModes>,
+}
 
-        self.jobs
-            .write()
-            .await
-            .insert(job_id.clone(), Mutex::new(job));
+/// Outcome of `AcpConnection::open_session`.
+#[derive(Debug, Default)]
+struct OpenedSession {
+    acp_session_id: Option<String>,
+    /// The previous session was reloaded via `session/load`
+    resumed: bool,
+    modes: Option<AcpSessionModes>,
+}
 
-        // Spawn background task
-        let manager = Arc::clone(self);
-        let sid = session_id.to_string();
-        let msg = message.to_string();
-        let jid = job_id.clone();
-        let agent_id_for_task = agent_id.clone();
+/// An operating mode an agent offers (e.g. Claude Code's `plan`,
+/// `acceptEdits`, `bypassPermissions`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AcpSessionMode {
+    pub id: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
 
-        tokio::spawn(async move {
-            let agent_id = agent_id_for_task;
-            let result = manager.prompt(&sid, &msg, timeout_secs, None).await;
-            let now = chrono::Utc::now();
+/// The `modes` block of a `session/new` or `session/load` response.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AcpSessionModes {
+    pub current_mode_id: String,
+    pub available: Vec<AcpSessionMode>,
+}
 
-            // Format notification text before updating job store
-            let notification = match &result {
-                Ok(r) => {
-                    let mut text = String::new();
-                    if r.context_reset {
-                        text.push_str("[Agent restarted — previous context lost]\n\n");
-                    }
-                    for m in &r.messages {
-                        if !m.is_empty() {
-                            if !text.is_empty() {
-                                text.push('\n');
-                            }
-                            text.push_str(m);
-                        }
-                    }
-                    if !r.tool_calls.is_empty() {
-                        if !text.is_empty() {
-                            text.push_str("\n\n");
-                        }
-                        text.push_str(&format!("[{} tool call(s)]", r.tool_calls.len()));
-                    }
-                    if text.is_empty() {
-                        text = "(Agent completed with no output)".to_string();
-                    }
-                    text
-                }
-                Err(e) => format!("ACP job failed: {e}"),
-            };
+impl AcpSessionModes {
+    fn from_response(result: &serde_json::Value) -> Option<Self> {
+        let modes = result.get("modes")?;
+        let available: Vec<AcpSessionMode> = modes
+            .get("availableModes")?
+            .as_array()?
+            .iter()
+            .filter_map(|m| {
+                let id = m.get("id")?.as_str()?.to_string();
+                Some(AcpSessionMode {
+                    name: m
+                        .get("name")
+                        .and_then(|v| v.as_str())
+                        .unwrap_or(&id)
+                        .to_string(),
+                    description: m
+                        .get("description")
+                        .and_then(|v| v.as_str())
+                        .map(str::to_string),
+                    id,
+                })
+            })
+            .collect();
+        if available.is_empty() {
+            return None;
+        }
+        let current_mode_id = modes
+            .get("currentModeId")
+            .and_then(|v| v.as_str())
+            .unwrap_or(&available[0].id)
+            .to_string();
+        Some(AcpSessionModes {
+            current_mode_id,
+            available,
+        })
+    }
 
-            // Update job record
-            {
-                let jobs = manager.jobs.read().await;
-                if let Some(job_mutex) = jobs.get(&jid) {
-                    let mut job = job_mutex.lock().await;
-                    match result {
-                        Ok(r) => {
-                            job.status = AcpJobStatus::Completed;
-                            job.result = Some(r);
-                        }
-                        Err(e) => {
-                            job.status = AcpJobStatus::Failed;
-                            job.error = Some(e);
-                        }
-                    }
-                    job.completed_at = Some(now);
-                }
-            }
+    /// Find a mode by ID or display name, ignoring case.
+    pub fn find(&self, mode: &str) -> Option<&AcpSessionMode> {
+        self.available
+            .iter()
+            .find(|m| m.id.eq_ignore_ascii_case(mode) || m.name.eq_ignore_ascii_case(mode))
+    }
+}
 
-            // Fire completion callback
-            if let (Some(cid), Some(cb)) = (chat_id, on_complete) {
+/// Status of an ACP session
+#[derive(Debug, Clone, PartialEq)]
+pub enum SessionStatus {
+    Active,
+    Prompting,
+    /// The agent process exited on its own. The next prompt (or the health
+    /// monitor, with `auto_respawn`) restarts it.
+    Crashed,
+    Ended,
+}
+
+/// Record of a single tool call made by the agent during prompt execution
+#[derive(Debug, Clone)]
+pub struct ToolCallInfo {
+    pub name: String,
+    pub input: serde_json::Value,
+}
+
+/// Token usage an agent reported for one prompt, or summed over a session.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct AcpUsage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub cached_read_tokens: u64,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub cached_write_tokens: u64,
+    /// Cost in USD as reported by the agent, or estimated from `model_prices`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cost_usd: Option<f64>,
+}
+
+fn is_zero(n: &u64) -> bool {
+    *n == 0
+}
+
+impl AcpUsage {
+    /// Parse a `usage` object from a `session/prompt` response or a
+    /// `usage_update` notification. Accepts camelCase and snake_case field
+    /// names; `None` when it carries neither token counts nor a cost.
+    pub fn from_value(value: &serde_json::Value) -> Option<Self> {
+        let tokens = |camel: &str, snake: &str| {
+            value
+                .get(camel)
+                .or_else(|| value.get(snake))
+                .and_then(|v| v.as_u64())
+        };
+        let input = tokens("inputTokens", "input_tokens");
+        let output = tokens("outputTokens", "output_tokens");
+        let cached_read = tokens("cachedReadTokens", "cache_read_input_tokens");
+        let cached_write = tokens("cachedWriteTokens", "cache_creation_input_tokens");
+        // `cost` is either a plain USD number or `{amount, currency}`
+        let cost = match value.get("cost").or_else(|| value.get("costUsd")) {
+            Some(serde_json::Value::Object(cost)) => cost
+                .get("currency")
+                .and_then(|c| c.as_str())
+                .is_none_or(|c| c.eq_ignore_ascii_case("USD"))
+                .then(|| cost.get("amount").and_then(|a| a.as_f64()))
+                .flatten(),
+            Some(cost) => cost.as_f64(),
+            None => value.get("cost_usd").and_then(|c| c.as_f64()),
+        };
+        if input.is_none() && output.is_none() && cost.is_none() {
+            return None;
+        }
+        Some(AcpUsage {
+            input_tokens: input.unwrap_or(0),
+            output_tokens: output.unwrap_or(0),
+            cached_read_tokens: cached_read.unwrap_or(0),
+            cached_write_tokens: cached_write.unwrap_or(0),
+            cost_usd: cost,
+        })
+    }
+
+    /// Add another prompt's usage to this running total.
+    pub fn add(&mut self, other: &AcpUsage) {
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.cached_read_tokens += other.cached_read_tokens;
+        self.cached_write_tokens += other.cached_write_tokens;
+        self.cost_usd = match (self.cost_usd, other.cost_usd) {
+            (Some(a), Some(b)) => Some(a + b),
+            (a, b) => a.or(b),
+        };
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == AcpUsage::default()
+    }
+}
+
+impl std::fmt::Display for AcpUsage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} in / {} out tokens",
+            self.input_tokens, self.output_tokens
+        )?;
+        if let Some(cost) = self.cost_usd {
+            write!(f, ", ${cost:.4}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Result of an ACP prompt execution
+#[derive(Debug, Clone)]
+pub struct AcpPromptResult {
+    /// Text messages emitted by the agent
+    pub messages: Vec<String>,
+    /// Tool calls executed by the agent
+    pub tool_calls: Vec<ToolCallInfo>,
+    /// Files changed during execution
+    pub files_changed: Vec<String>,
+    /// Latest plan published by the agent (empty if it never sent one)
+    pub plan: Vec<AcpPlanEntry>,
+    /// The agent's thinking, one entry per uninterrupted run of thought
+    /// chunks. Not part of `messages`; callers show it only on request.
+    pub thoughts: Vec<String>,
+    /// Token usage and cost, if the agent reported any
+    pub usage: Option<AcpUsage>,
+    /// Whether the prompt completed normally (vs timeout/cancel)
+    pub completed: bool,
+    /// Wall-clock execution time in milliseconds
+    pub duration_ms: u128,
+    /// True if the agent process had crashed and was restarted for this
+    /// prompt. Previous conversation context was lost.
+    pub context_reset: bool,
+}
+
+// ---------------------------------------------------------------------------
+// Async Job types
+// ---------------------------------------------------------------------------
+
+/// Status of an async ACP job
+#[derive(Debug, Clone, PartialEq)]
+pub enum AcpJobStatus {
+    Running,
+    Completed,
+    Failed,
+}
+
+/// An async ACP job that runs a prompt in the background.
+#[derive(Debug, Clone)]
+pub struct AcpJob {
+    pub id: String,
+    pub session_id: String,
+    pub agent_id: String,
+    pub status: AcpJobStatus,
+    pub result: Option<AcpPromptResult>,
+    pub error: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub completed_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Summary of a job (for listing/querying)
+#[derive(Debug, Clone)]
+pub struct AcpJobSummary {
+    pub id: String,
+    pub session_id: String,
+    pub agent_id: String,
+    pub status: AcpJobStatus,
+    pub created_at: String,
+    pub completed_at: Option<String>,
+    pub duration_ms: Option<u128>,
+    pub error: Option<String>,
+}
+
+const MAX_JOBS: usize = 100;
+const JOB_TTL_SECS: u64 = 3600; // 1 hour
+
+/// Callback invoked when an async job completes. Receives (chat_id, message_text).
+/// Used to push results back to the user's chat via send_message.
+pub type JobCompletionCallback = Arc<
+    dyn Fn(i64, String) -> std::pin::Pin<Box<dyn std::future::Future<Output = ()> + Send>>
+        + Send
+        + Sync,
+>;
+
+/// A prompt's place in its session's queue; leaving (on completion or
+/// error) lets the prompts behind it move up.
+struct PromptQueueSlot<'a> {
+    queues: &'a std::sync::Mutex<HashMap<String, usize>>,
+    session_id: String,
+    /// Prompts running or waiting ahead of this one
+    ahead: usize,
+}
+
+impl Drop for PromptQueueSlot<'_> {
+    fn drop(&mut self) {
+        let mut queues = self.queues.lock().unwrap();
+        if let Some(count) = queues.get_mut(&self.session_id) {
+            *count = count.saturating_sub(1);
+            if *count == 0 {
+                queues.remove(&self.session_id);
+            }
+        }
+    }
+}
+
+/// An active ACP agent session with its connection
+pub struct AcpSession {
+    pub id: String,
+    pub agent_id: String,
+    pub workspace: String,
+    pub auto_approve: bool,
+    pub status: SessionStatus,
+    pub acp_session_id: Option<String>,
+    pub connection: ConnectionKind,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    /// Monotonic timestamp of last prompt activity (start or complete).
+    /// Used by the idle reaper to detect stuck/abandoned sessions.
+    pub last_activity: Instant,
+    /// Set to true when the agent process crashed and was restarted.
+    /// The next prompt result will include a context-loss notice, then
+    /// this flag is cleared.
+    pub session_reset: bool,
+    /// Path to the cgroup v2 directory, if resource limits were applied.
+    /// Cleaned up on session end.
+    pub cgroup_path: Option<String>,
+    /// Modes the agent offers and the one currently active
+    pub modes: Option<AcpSessionModes>,
+    /// Token usage summed over every prompt in this session
+    pub usage: AcpUsage,
+}
+
+// ---------------------------------------------------------------------------
+// AcpManager — global session lifecycle manager
+// ---------------------------------------------------------------------------
+
+pub struct AcpManager {
+    pub config: AcpConfig,
+    sessions: RwLock<HashMap<String, Mutex<AcpSession>>>,
+    /// Map chat_id → session_id for command-based ACP routing
+    chat_sessions: RwLock<HashMap<i64, String>>,
+    /// Per-agent active session count for enforcing max_per_agent
+    agent_session_counts: RwLock<HashMap<String, usize>>,
+    /// In-memory async job store
+    jobs: RwLock<HashMap<String, Mutex<AcpJob>>>,
+    /// Map chat_id → permission request awaiting an answer
+    pending_permissions: Mutex<HashMap<i64, PendingPermission>>,
+    /// Map session_id → connection and ACP session ID of the prompt currently
+    /// running, so it can be cancelled while the prompt holds the session lock
+    active_prompts: RwLock<HashMap<String, (Arc<AcpConnection>, String)>>,
+    /// Map session_id → prompts running or waiting on it. The session mutex
+    /// is fair, so waiting prompts run in arrival order.
+    prompt_queues: std::sync::Mutex<HashMap<String, usize>>,
+    /// Map session_id → prompt history for `session_status`, kept outside the
+    /// session lock so it can be read while a prompt runs
+    session_activity: std::sync::Mutex<HashMap<String, SessionActivity>>,
+    /// Where finished prompt runs (`acp_runs`) and open sessions
+    /// (`acp_sessions`) are persisted, once attached
+    run_log: std::sync::OnceLock<Arc<crate::db::Database>>,
+    /// Delivers crash, restart and idle-timeout notices to chats bound to a
+    /// session, once attached
+    chat_notifier: std::sync::OnceLock<JobCompletionCallback>,
+    /// Prices for estimating the cost of usage agents report without one
+    model_prices: std::sync::OnceLock<Vec<crate::config::ModelPrice>>,
+    /// Agents that have started successfully at least once, so later spawns
+    /// skip the long first-spawn handshake timeout
+    started_agents: std::sync::Mutex<std::collections::HashSet<String>>,
+}
+
+impl AcpManager {
+    /// Create an AcpManager from a config file path.
+    /// Does NOT spawn any agents — they are created on demand via tools.
+    pub fn from_config_file(path: &str) -> Self {
+        Self::from_config(AcpConfig::from_file(path))
+    }
+
+    /// Create an AcpManager from an already-parsed config.
+    /// Does NOT spawn any agents — they are created on demand via tools.
+    pub fn from_config(config: AcpConfig) -> Self {
+        if !config.agents.is_empty() {
+            info!(
+                "ACP config loaded: {} agent(s) configured ({})",
+                config.agents.len(),
+                config.agents.keys().cloned().collect::<Vec<_>>().join(", ")
+            );
+        }
+        AcpManager {
+            config,
+            sessions: RwLock::new(HashMap::new()),
+            chat_sessions: RwLock::new(HashMap::new()),
+            agent_session_counts: RwLock::new(HashMap::new()),
+            jobs: RwLock::new(HashMap::new()),
+            pending_permissions: Mutex::new(HashMap::new()),
+            active_prompts: RwLock::new(HashMap::new()),
+            prompt_queues: std::sync::Mutex::new(HashMap::new()),
+            session_activity: std::sync::Mutex::new(HashMap::new()),
+            run_log: std::sync::OnceLock::new(),
+            chat_notifier: std::sync::OnceLock::new(),
+            model_prices: std::sync::OnceLock::new(),
+            started_agents: std::sync::Mutex::new(std::collections::HashSet::new()),
+        }
+    }
+
+    /// Spawn an ACP-mode agent, retrying transient startup failures with
+    /// exponential backoff. The first spawn of an npx/uvx agent gets
+    /// `first_spawn_timeout_secs` for its handshake.
+    async fn spawn_connection(
+        &self,
+        agent_id: &str,
+        agent_config: &AcpAgentConfig,
+        workspace: &str,
+    ) -> Result<AcpConnection, String> {
+        let request_timeout = Duration::from_secs(DEFAULT_REQUEST_TIMEOUT_SECS);
+        let downloads = matches!(agent_config.launch.as_str(), "npx" | "uvx");
+        let first = !self.started_agents.lock().unwrap().contains(agent_id);
+        let handshake_timeout = if downloads && first {
+            request_timeout.max(Duration::from_secs(self.config.first_spawn_timeout_secs))
+        } else {
+            request_timeout
+        };
+        let base_backoff = Duration::from_millis(self.config.spawn_backoff_ms);
+
+        let mut attempt = 0;
+        loop {
+            match AcpConnection::try_spawn(
+                agent_id,
+                agent_config,
+                Some(workspace),
+                request_timeout,
+                handshake_timeout,
+            )
+            .await
+            {
+                Ok(conn) => {
+                    self.started_agents
+                        .lock()
+                        .unwrap()
+                        .insert(agent_id.to_string());
+                    return Ok(conn);
+                }
+                Err(e) if e.is_retryable() && attempt < self.config.spawn_retries => {
+                    attempt += 1;
+                    let delay = spawn_backoff(base_backoff, attempt);
+                    warn!(
+                        "{e}; retrying in {delay:?} (attempt {attempt}/{})",
+                        self.config.spawn_retries
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                Err(e) if attempt > 0 => {
+                    return Err(format!("{e} (gave up after {} attempts)", attempt + 1))
+                }
+                Err(e) => return Err(e.to_string()),
+            }
+        }
+    }
+
+    /// List configured agent names
+    pub fn available_agents(&self) -> Vec<String> {
+        self.config.agents.keys().cloned().collect()
+    }
+
+    /// Check if a given agent name is configured
+    pub fn has_agent(&self, name: &str) -> bool {
+        self.config.agents.contains_key(name)
+    }
+
+    /// Get agent config by name
+    pub fn agent_config(&self, name: &str) -> Option<&AcpAgentConfig> {
+        self.config.agents.get(name)
+    }
+
+    /// Spawn a new agent process, perform ACP handshake, and create a session.
+    pub async fn new_session(
+        &self,
+        agent_id: &str,
+        workspace: Option<&str>,
+        auto_approve: Option<bool>,
+    ) -> Result<SessionInfo, String> {
+        // Enforce process pool limits (before config lookup / spawn)
+        {
+            let sessions = self.sessions.read().await;
+            let total = sessions.len();
+            if total >= self.config.max_sessions {
+                return Err(format!(
+                    "ACP session limit reached ({total}/{}). End an existing session first.",
+                    self.config.max_sessions
+                ));
+            }
+        }
+
+        let agent_config = self
+            .config
+            .agents
+            .get(agent_id)
+            .ok_or_else(|| format!("ACP agent '{agent_id}' not configured"))?
+            .clone();
+
+        {
+            let counts = self.agent_session_counts.read().await;
+            let agent_count = counts.get(agent_id).copied().unwrap_or(0);
+            if agent_count >= self.config.max_per_agent {
+                return Err(format!(
+                    "ACP per-agent limit reached for '{agent_id}' ({agent_count}/{}). End an existing session first.",
+                    self.config.max_per_agent
+                ));
+            }
+        }
+
+        let effective_auto_approve = auto_approve
+            .or(agent_config.auto_approve)
+            .unwrap_or(self.config.default_auto_approve);
+
+        let effective_workspace = workspace
+            .map(|s| s.to_string())
+            .or_else(|| agent_config.workspace.clone())
+            .unwrap_or_else(|| ".".to_string());
+
+        let is_pty_mode = agent_config.mode == "pty";
+
+        let (connection, opened) = if is_pty_mode {
+            // PTY mode — simple stdin/stdout subprocess, no JSON-RPC
+            let pty_conn =
+                PtyConnection::spawn(agent_id, &agent_config, Some(&effective_workspace)).await?;
+            (
+                ConnectionKind::Pty(Box::new(pty_conn)),
+                OpenedSession::default(),
+            )
+        } else {
+            // ACP mode — full JSON-RPC protocol
+            let acp_conn = self
+                .spawn_connection(agent_id, &agent_config, &effective_workspace)
+                .await?;
+
+            // Create an ACP-level session with workspace as cwd
+            let opened = acp_conn.open_session(&effective_workspace, None).await;
+            (ConnectionKind::Acp(Arc::new(acp_conn)), opened)
+        };
+
+        let session_id = uuid::Uuid::new_v4().to_string();
+
+        // Apply cgroup resource limits if configured
+        let cgroup_path = if let Some(ref limits) = agent_config.resource_limits {
+            if let Some(pid) = connection.pid().await {
+                apply_resource_limits(pid, &session_id, limits)
+            } else {
+                warn!("ACP [{agent_id}]: could not get child PID for cgroup setup");
+                None
+            }
+        } else {
+            None
+        };
+
+        let info = SessionInfo {
+            session_id: session_id.clone(),
+            agent_id: agent_id.to_string(),
+            workspace: effective_workspace.clone(),
+            modes: opened.modes.clone(),
+        };
+
+        let session = AcpSession {
+            id: session_id.clone(),
+            agent_id: agent_id.to_string(),
+            workspace: effective_workspace,
+            auto_approve: effective_auto_approve,
+            status: SessionStatus::Active,
+            acp_session_id: opened.acp_session_id,
+            connection,
+            created_at: chrono::Utc::now(),
+            last_activity: Instant::now(),
+            session_reset: false,
+            cgroup_path,
+            modes: opened.modes,
+            usage: AcpUsage::default(),
+        };
+
+        self.persist_session(&session).await;
+        self.sessions
+            .write()
+            .await
+            .insert(session_id, Mutex::new(session));
+
+        // Increment per-agent session counter
+        *self
+            .agent_session_counts
+            .write()
+            .await
+            .entry(agent_id.to_string())
+            .or_insert(0) += 1;
+
+        info!(
+            "ACP session created: {} (agent={agent_id}, auto_approve={effective_auto_approve})",
+            info.session_id
+        );
+        Ok(info)
+    }
+
+    /// Persist every finished prompt run to `db` from now on. Later calls are ignored.
+    pub fn set_run_log(&self, db: Arc<crate::db::Database>) {
+        let _ = self.run_log.set(db);
+    }
+
+    /// Database holding past prompt runs, if one was attached.
+    pub fn run_log(&self) -> Option<Arc<crate::db::Database>> {
+        self.run_log.get().cloned()
+    }
+
+    /// Estimate the cost of reported usage with `prices` (config
+    /// `model_prices`) from now on. Later calls are ignored.
+    pub fn set_model_prices(&self, prices: Vec<crate::config::ModelPrice>) {
+        let _ = self.model_prices.set(prices);
+    }
+
+    /// Model an agent's usage is priced and reported under.
+    fn agent_model(&self, agent_id: &str) -> String {
+        self.config
+            .agents
+            .get(agent_id)
+            .and_then(|a| a.model.clone())
+            .filter(|m| !m.trim().is_empty())
+            .unwrap_or_else(|| agent_id.to_string())
+    }
+
+    /// Fill in an estimated cost when the agent reported tokens but no cost.
+    fn price_usage(&self, agent_id: &str, usage: &mut AcpUsage) {
+        if usage.cost_usd.is_some() {
+            return;
+        }
+        let Some(prices) = self.model_prices.get() else {
+            return;
+        };
+        if let Some(price) = crate::config::find_model_price(prices, &self.agent_model(agent_id)) {
+            usage.cost_usd =
+                Some(price.cost_usd(usage.input_tokens as i64, usage.output_tokens as i64));
+        }
+    }
+
+    /// Send crash, restart and idle-timeout notices to bound chats via
+    /// `notify` from now on. Later calls are ignored.
+    pub fn set_chat_notifier(&self, notify: JobCompletionCallback) {
+        let _ = self.chat_notifier.set(notify);
+    }
+
+    /// Save a session's metadata so it can be restored after a restart.
+    async fn persist_session(&self, session: &AcpSession) {
+        let Some(db) = self.run_log() else {
+            return;
+        };
+        let record = crate::db::AcpSessionRecord {
+            session_id: session.id.clone(),
+            agent_id: session.agent_id.clone(),
+            workspace: session.workspace.clone(),
+            acp_session_id: session.acp_session_id.clone(),
+            auto_approve: session.auto_approve,
+            chat_ids: Vec::new(),
+            created_at: session.created_at.to_rfc3339(),
+        };
+        if let Err(e) = crate::db::call_blocking(db, move |db| db.save_acp_session(&record)).await {
+            warn!("ACP: failed to persist session {}: {e}", session.id);
+        }
+    }
+
+    /// Restart the sessions persisted before the last shutdown and rebind
+    /// their chats. Agents that support `loadSession` pick up their previous
+    /// context; others start fresh and the next prompt reports a context
+    /// reset. Sessions that can't be restarted are forgotten and their chats
+    /// told. Returns the number of restored sessions.
+    pub async fn restore_sessions(&self) -> usize {
+        let Some(db) = self.run_log() else {
+            return 0;
+        };
+        let records = match crate::db::call_blocking(db.clone(), |db| db.get_acp_sessions()).await {
+            Ok(records) => records,
+            Err(e) => {
+                warn!("ACP restore: failed to load sessions: {e}");
+                return 0;
+            }
+        };
+
+        let mut restored = 0;
+        for record in records {
+            if self.sessions.read().await.contains_key(&record.session_id) {
+                continue;
+            }
+            match self.restore_session(&record).await {
+                Ok(resumed) => {
+                    restored += 1;
+                    info!(
+                        "ACP restore: session {} (agent={}, resumed={resumed}, chats={:?})",
+                        record.session_id, record.agent_id, record.chat_ids
+                    );
+                }
+                Err(e) => {
+                    warn!(
+                        "ACP restore: dropping session {} (agent={}): {e}",
+                        record.session_id, record.agent_id
+                    );
+                    let id = record.session_id.clone();
+                    if let Err(e) =
+                        crate::db::call_blocking(db.clone(), move |db| db.delete_acp_session(&id))
+                            .await
+                    {
+                        warn!("ACP: failed to forget session {}: {e}", record.session_id);
+                    }
+                    if let Some(notify) = self.chat_notifier.get() {
+                        for chat_id in &record.chat_ids {
+                            notify(
+                                *chat_id,
+                                format!(
+                                    "[ACP {}] Session could not be restored after a restart: {e}. Use #new to start another.",
+                                    record.agent_id
+                                ),
+                            )
+                            .await;
+                        }
+                    }
+                }
+            }
+        }
+        if restored > 0 {
+            info!("ACP restore: {restored} session(s) restored");
+        }
+        restored
+    }
+
+    /// Respawn one persisted session. Returns whether the agent resumed its
+    /// previous ACP session.
+    async fn restore_session(&self, record: &crate::db::AcpSessionRecord) -> Result<bool, String> {
+        let agent_config = self
+            .config
+            .agents
+            .get(&record.agent_id)
+            .ok_or_else(|| format!("agent '{}' is no longer configured", record.agent_id))?
+            .clone();
+
+        let (connection, opened) = if agent_config.mode == "pty" {
+            let conn =
+                PtyConnection::spawn(&record.agent_id, &agent_config, Some(&record.workspace))
+                    .await?;
+            (
+                ConnectionKind::Pty(Box::new(conn)),
+                OpenedSession::default(),
+            )
+        } else {
+            let conn = self
+                .spawn_connection(&record.agent_id, &agent_config, &record.workspace)
+                .await?;
+            let opened = conn
+                .open_session(&record.workspace, record.acp_session_id.as_deref())
+                .await;
+            (ConnectionKind::Acp(Arc::new(conn)), opened)
+        };
+
+        let resumed = opened.resumed;
+        let cgroup_path = match (&agent_config.resource_limits, connection.pid().await) {
+            (Some(limits), Some(pid)) => apply_resource_limits(pid, &record.session_id, limits),
+            _ => None,
+        };
+        let session = AcpSession {
+            id: record.session_id.clone(),
+            agent_id: record.agent_id.clone(),
+            workspace: record.workspace.clone(),
+            auto_approve: record.auto_approve,
+            status: SessionStatus::Active,
+            acp_session_id: opened.acp_session_id,
+            connection,
+            created_at: chrono::DateTime::parse_from_rfc3339(&record.created_at)
+                .map(|t| t.with_timezone(&chrono::Utc))
+                .unwrap_or_else(|_| chrono::Utc::now()),
+            last_activity: Instant::now(),
+            // Without session/load the agent starts over
+            session_reset: !resumed,
+            cgroup_path,
+            modes: opened.modes,
+            usage: AcpUsage::default(),
+        };
+        self.persist_session(&session).await;
+        self.sessions
+            .write()
+            .await
+            .insert(record.session_id.clone(), Mutex::new(session));
+        *self
+            .agent_session_counts
+            .write()
+            .await
+            .entry(record.agent_id.clone())
+            .or_insert(0) += 1;
+        let mut chat_sessions = self.chat_sessions.write().await;
+        for chat_id in &record.chat_ids {
+            chat_sessions.insert(*chat_id, record.session_id.clone());
+        }
+        Ok(resumed)
+    }
+
+    /// Store a finished prompt run, attributed to the chat bound to the session.
+    async fn record_run(
+        &self,
+        session_id: &str,
+        agent_id: &str,
+        prompt: &str,
+        result: &AcpPromptResult,
+    ) {
+        let Some(db) = self.run_log() else {
+            return;
+        };
+        let chat_id = self
+            .chat_sessions
+            .read()
+            .await
+            .iter()
+            .find(|(_, sid)| sid.as_str() == session_id)
+            .map(|(chat_id, _)| *chat_id);
+        let run = crate::db::AcpRun {
+            id: 0,
+            session_id: session_id.to_string(),
+            chat_id,
+            agent_id: agent_id.to_string(),
+            prompt: prompt.to_string(),
+            messages: result.messages.clone(),
+            tool_calls: result
+                .tool_calls
+                .iter()
+                .map(|tc| serde_json::json!({"name": tc.name, "input": tc.input}))
+                .collect(),
+            files_changed: result.files_changed.clone(),
+            completed: result.completed,
+            context_reset: result.context_reset,
+            duration_ms: result.duration_ms as i64,
+            created_at: chrono::Utc::now().to_rfc3339(),
+        };
+        if let Err(e) = crate::db::call_blocking(db.clone(), move |db| db.log_acp_run(&run)).await {
+            warn!("ACP: failed to record run for session {session_id}: {e}");
+        }
+
+        // Token usage also goes to the LLM usage log so /usage covers agents
+        if let (Some(chat_id), Some(usage)) = (chat_id, result.usage.clone()) {
+            let model = self.agent_model(agent_id);
+            if let Err(e) = crate::db::call_blocking(db, move |db| {
+                db.log_llm_usage(
+                    chat_id,
+                    "acp",
+                    "acp",
+                    &model,
+                    usage.input_tokens as i64,
+                    usage.output_tokens as i64,
+                    "acp_prompt",
+                )
+            })
+            .await
+            {
+                warn!("ACP: failed to record usage for session {session_id}: {e}");
+            }
+        }
+    }
+
+    /// Send a prompt to an existing session and wait for completion.
+    ///
+    /// If the agent process has crashed, this method attempts to respawn the
+    /// process and re-create the ACP session before sending the prompt. The
+    /// returned `AcpPromptResult.context_reset` will be `true` to indicate
+    /// that previous conversation context was lost.
+    pub async fn prompt(
+        &self,
+        session_id: &str,
+        message: &str,
+        timeout_secs: Option<u64>,
+        progress_tx: Option<&AcpProgressSender>,
+    ) -> Result<AcpPromptResult, String> {
+        self.prompt_with_permissions(session_id, message, &[], timeout_secs, progress_tx, None)
+            .await
+    }
+
+    /// Like [`prompt`](Self::prompt), but sends `attachments` with the text,
+    /// and permission requests on sessions without auto-approve are forwarded
+    /// to `permission_handler` instead of being cancelled.
+    pub async fn prompt_with_permissions(
+        &self,
+        session_id: &str,
+        message: &str,
+        attachments: &[AcpAttachment],
+        timeout_secs: Option<u64>,
+        progress_tx: Option<&AcpProgressSender>,
+        permission_handler: Option<&AcpPermissionHandler>,
+    ) -> Result<AcpPromptResult, String> {
+        let sessions = self.sessions.read().await;
+        let session_mutex = sessions
+            .get(session_id)
+            .ok_or_else(|| format!("ACP session '{session_id}' not found"))?;
+
+        let slot = self.join_prompt_queue(session_id)?;
+        if slot.ahead > 0 {
+            info!(
+                "ACP session {session_id}: prompt queued behind {} other(s)",
+                slot.ahead
+            );
+            if let Some(tx) = progress_tx {
+                let _ = tx.send(AcpProgressEvent::Queued {
+                    position: slot.ahead,
+                });
+            }
+        }
+
+        let mut session = session_mutex.lock().await;
+        if session.status == SessionStatus::Ended {
+            return Err(format!("ACP session '{session_id}' has ended"));
+        }
+
+        // --- Crash recovery: detect dead process and respawn ---------------
+        if !session.connection.is_alive().await {
+            warn!(
+                "ACP [{}]: agent process died, attempting restart (session={})",
+                session.agent_id, session_id
+            );
+            if let Err(e) = self.recover_session(&mut session).await {
+                session.status = SessionStatus::Ended;
+                return Err(format!(
+                    "ACP [{}]: agent process died and recovery failed: {e}",
+                    session.agent_id
+                ));
+            }
+        }
+
+        session.status = SessionStatus::Prompting;
+        session.last_activity = Instant::now();
+        {
+            let mut activity = self.session_activity.lock().unwrap();
+            let entry = activity.entry(session_id.to_string()).or_default();
+            entry.agent_id = session.agent_id.clone();
+            entry.workspace = session.workspace.clone();
+            entry.last_prompt = Some(message.to_string());
+            entry.prompt_started = Some(Instant::now());
+        }
+
+        let timeout = Duration::from_secs(timeout_secs.unwrap_or(self.config.prompt_timeout_secs));
+
+        let result = match &session.connection {
+            ConnectionKind::Acp(conn) => {
+                let acp_sid = session
+                    .acp_session_id
+                    .as_deref()
+                    .ok_or_else(|| format!("ACP session '{session_id}' has no ACP session ID"))?;
+                let params = serde_json::json!({
+                    "sessionId": acp_sid,
+                    "prompt": prompt_content_blocks(
+                        message,
+                        attachments,
+                        conn.accepts_images.load(Ordering::Relaxed),
+                    )
+                });
+                self.active_prompts.write().await.insert(
+                    session_id.to_string(),
+                    (Arc::clone(conn), acp_sid.to_string()),
+                );
+                let result = conn
+                    .prompt_streaming(
+                        params,
+                        session.auto_approve,
+                        timeout,
+                        progress_tx,
+                        permission_handler,
+                    )
+                    .await;
+                self.active_prompts.write().await.remove(session_id);
+                result
+            }
+            ConnectionKind::Pty(conn) => {
+                let mut text = message.to_string();
+                for attachment in attachments {
+                    text.push('\n');
+                    text.push_str(&attachment.to_text());
+                }
+                conn.prompt(&text, timeout, progress_tx).await
+            }
+        };
+
+        session.status = SessionStatus::Active;
+        session.last_activity = Instant::now();
+
+        // Consume the reset flag so it's only reported once.
+        let context_reset = session.session_reset;
+        session.session_reset = false;
+
+        match result {
+            Ok(mut r) => {
+                r.context_reset = context_reset;
+                if let Some(usage) = r.usage.as_mut() {
+                    self.price_usage(&session.agent_id, usage);
+                    session.usage.add(usage);
+                }
+                if r.completed {
+                    info!(
+                        "ACP [{}] prompt completed in {}ms ({} messages, {} tool calls, {} files{})",
+                        session.agent_id,
+                        r.duration_ms,
+                        r.messages.len(),
+                        r.tool_calls.len(),
+                        r.files_changed.len(),
+                        if context_reset { ", context_reset" } else { "" }
+                    );
+                } else {
+                    info!(
+                        "ACP [{}] prompt cancelled (session={session_id})",
+                        session.agent_id
+                    );
+                }
+                self.record_run(session_id, &session.agent_id, message, &r)
+                    .await;
+                self.note_prompt_finished(session_id, Some(&r), &session.usage);
+                Ok(r)
+            }
+            Err(e) => {
+                error!("ACP [{}] prompt failed: {e}", session.agent_id);
+                self.note_prompt_finished(session_id, None, &session.usage);
+                Err(e)
+            }
+        }
+    }
+
+    /// Fold a finished (or failed) prompt into the session's activity.
+    fn note_prompt_finished(
+        &self,
+        session_id: &str,
+        result: Option<&AcpPromptResult>,
+        usage: &AcpUsage,
+    ) {
+        let mut activity = self.session_activity.lock().unwrap();
+        let Some(entry) = activity.get_mut(session_id) else {
+            return;
+        };
+        entry.prompt_started = None;
+        entry.usage = usage.clone();
+        if let Some(result) = result {
+            entry.prompts += 1;
+            entry.tool_calls += result.tool_calls.len();
+            for path in &result.files_changed {
+                if !entry.files_changed.contains(path) {
+                    entry.files_changed.push(path.clone());
+                }
+            }
+        }
+    }
+
+    /// Detailed state of one session: what it is doing now, what it was last
+    /// asked, and what its prompts have done so far. Does not wait for a
+    /// running prompt.
+    pub async fn session_status(&self, session_id: &str) -> Result<AcpSessionStatus, String> {
+        let sessions = self.sessions.read().await;
+        let session_mutex = sessions
+            .get(session_id)
+            .ok_or_else(|| format!("ACP session '{session_id}' not found"))?;
+        let activity = self
+            .session_activity
+            .lock()
+            .unwrap()
+            .get(session_id)
+            .cloned()
+            .unwrap_or_default();
+        let prompt_elapsed_secs = activity.prompt_started.map(|t| t.elapsed().as_secs());
+        let running = usize::from(prompt_elapsed_secs.is_some());
+        let mut status = AcpSessionStatus {
+            session_id: session_id.to_string(),
+            agent_id: activity.agent_id.clone(),
+            workspace: activity.workspace.clone(),
+            status: SessionStatus::Prompting,
+            created_at: None,
+            idle_secs: None,
+            last_prompt: activity.last_prompt,
+            prompt_elapsed_secs,
+            queued_prompts: self.queued_prompts(session_id).saturating_sub(running),
+            prompts_completed: activity.prompts,
+            tool_calls: activity.tool_calls,
+            files_changed: activity.files_changed,
+            usage: activity.usage,
+        };
+        // A running prompt holds the session lock; its activity entry
+        // already has what we need
+        let session = match session_mutex.try_lock() {
+            Ok(session) => session,
+            Err(_) if running == 1 => return Ok(status),
+            Err(_) => session_mutex.lock().await,
+        };
+        status.agent_id = session.agent_id.clone();
+        status.workspace = session.workspace.clone();
+        status.status = session.status.clone();
+        status.created_at = Some(session.created_at.to_rfc3339());
+        status.idle_secs = Some(session.last_activity.elapsed().as_secs());
+        status.usage = session.usage.clone();
+        Ok(status)
+    }
+
+    /// Take a place in a session's prompt queue. Fails when
+    /// `max_queue_depth` prompts are already waiting behind a running one.
+    fn join_prompt_queue(&self, session_id: &str) -> Result<PromptQueueSlot<'_>, String> {
+        let mut queues = self.prompt_queues.lock().unwrap();
+        let count = queues.entry(session_id.to_string()).or_insert(0);
+        let ahead = *count;
+        if ahead > self.config.max_queue_depth {
+            return Err(format!(
+                "The agent is busy and {} prompt(s) are already queued (max {}). Try again later, or #stop the current prompt.",
+                ahead - 1,
+                self.config.max_queue_depth
+            ));
+        }
+        *count += 1;
+        Ok(PromptQueueSlot {
+            queues: &self.prompt_queues,
+            session_id: session_id.to_string(),
+            ahead,
+        })
+    }
+
+    /// Number of prompts running or waiting on a session.
+    pub fn queued_prompts(&self, session_id: &str) -> usize {
+        self.prompt_queues
+            .lock()
+            .unwrap()
+            .get(session_id)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Attempt to respawn the agent process and re-create a session,
+    /// replacing the dead connection in-place. Sets `session_reset = true`.
+    async fn recover_session(&self, session: &mut AcpSession) -> Result<(), String> {
+        let agent_config = self
+            .config
+            .agents
+            .get(&session.agent_id)
+            .ok_or_else(|| format!("Agent '{}' no longer configured", session.agent_id))?
+            .clone();
+
+        if agent_config.mode == "pty" {
+            // PTY mode — just respawn the process
+            let new_conn =
+                PtyConnection::spawn(&session.agent_id, &agent_config, Some(&session.workspace))
+                    .await?;
+            session.connection = ConnectionKind::Pty(Box::new(new_conn));
+            session.acp_session_id = None;
+            session.modes = None;
+        } else {
+            // ACP mode — respawn + re-initialize + session/new
+            let new_connection = self
+                .spawn_connection(&session.agent_id, &agent_config, &session.workspace)
+                .await?;
+
+            let opened = new_connection.open_session(&session.workspace, None).await;
+            session.connection = ConnectionKind::Acp(Arc::new(new_connection));
+            session.acp_session_id = opened.acp_session_id;
+            session.modes = opened.modes;
+        }
+
+        // Clean up old cgroup and set up new one if limits configured
+        if let Some(ref old_cg) = session.cgroup_path {
+            cleanup_cgroup(old_cg);
+        }
+        session.cgroup_path = if let Some(ref limits) = agent_config.resource_limits {
+            if let Some(pid) = session.connection.pid().await {
+                apply_resource_limits(pid, &session.id, limits)
+            } else {
+                None
+            }
+        } else {
+            None
+        };
+
+        session.session_reset = true;
+        session.last_activity = Instant::now();
+        self.persist_session(session).await;
+
+        info!(
+            "ACP [{}]: process recovered successfully (session={})",
+            session.agent_id, session.id
+        );
+        Ok(())
+    }
+
+    /// End a session and terminate the agent process.
+    pub async fn end_session(&self, session_id: &str) -> Result<(), String> {
+        self.close_session(session_id).await?;
+        if let Some(db) = self.run_log() {
+            let id = session_id.to_string();
+            if let Err(e) = crate::db::call_blocking(db, move |db| db.delete_acp_session(&id)).await
+            {
+                warn!("ACP: failed to forget session {session_id}: {e}");
+            }
+        }
+        Ok(())
+    }
+
+    /// Terminate a session's agent process, keeping its persisted record.
+    async fn close_session(&self, session_id: &str) -> Result<(), String> {
+        let session_mutex = {
+            let mut sessions = self.sessions.write().await;
+            sessions
+                .remove(session_id)
+                .ok_or_else(|| format!("ACP session '{session_id}' not found"))?
+        };
+        self.session_activity.lock().unwrap().remove(session_id);
+
+        let mut session = session_mutex.lock().await;
+
+        // Send session/end to agent (ACP mode only, best effort)
+        if let Some(acp_sid) = &session.acp_session_id {
+            if let Some(conn) = session.connection.as_acp() {
+                let _ = conn
+                    .send_request(
+                        "session/end",
+                        Some(serde_json::json!({"sessionId": acp_sid})),
+                    )
+                    .await;
+            }
+        }
+
+        session.connection.shutdown().await?;
+        session.status = SessionStatus::Ended;
+
+        // Clean up cgroup if one was created
+        if let Some(ref cg_path) = session.cgroup_path {
+            cleanup_cgroup(cg_path);
+        }
+
+        // Decrement per-agent session counter
+        {
+            let mut counts = self.agent_session_counts.write().await;
+            if let Some(count) = counts.get_mut(&session.agent_id) {
+                *count = count.saturating_sub(1);
+                if *count == 0 {
+                    counts.remove(&session.agent_id);
+                }
+            }
+        }
+
+        // Unbind any chats referencing this session
+        let mut chat_sessions = self.chat_sessions.write().await;
+        chat_sessions.retain(|_, sid| sid != session_id);
+
+        info!("ACP session ended: {session_id}");
+        Ok(())
+    }
+
+    /// Ask the agent to stop the prompt currently running in a session by
+    /// sending `session/cancel`. The agent finishes the prompt with
+    /// `stopReason: "cancelled"`, so the prompt returns with `completed: false`.
+    /// Any permission request the prompt is waiting on in a bound chat is
+    /// cancelled as well.
+    pub async fn cancel_prompt(&self, session_id: &str) -> Result<(), String> {
+        let active = self.active_prompts.read().await.get(session_id).cloned();
+        let Some((conn, acp_sid)) = active else {
+            if self.sessions.read().await.contains_key(session_id) {
+                return Err(format!(
+                    "ACP session '{session_id}' has no cancellable prompt running"
+                ));
+            }
+            return Err(format!("ACP session '{session_id}' not found"));
+        };
+
+        conn.send_notification(
+            "session/cancel",
+            Some(serde_json::json!({"sessionId": acp_sid})),
+        )
+        .await?;
+        conn.cancel_question();
+
+        let chats: Vec<i64> = self
+            .chat_sessions
+            .read()
+            .await
+            .iter()
+            .filter(|(_, sid)| sid.as_str() == session_id)
+            .map(|(chat_id, _)| *chat_id)
+            .collect();
+        let mut pending = self.pending_permissions.lock().await;
+        for chat_id in chats {
+            if let Some(permission) = pending.remove(&chat_id) {
+                let _ = permission.tx.send(AcpPermissionDecision::Cancelled);
+            }
+        }
+
+        info!("ACP session {session_id}: prompt cancel requested");
+        Ok(())
+    }
+
+    /// The question a session's running prompt is waiting on, if any.
+    pub async fn pending_question(&self, session_id: &str) -> Option<String> {
+        let active = self.active_prompts.read().await.get(session_id).cloned();
+        active.and_then(|(conn, _)| conn.pending_question())
+    }
+
+    /// Answer the question a session's running prompt is waiting on.
+    pub async fn answer_question(&self, session_id: &str, answer: &str) -> Result<(), String> {
+        let active = self.active_prompts.read().await.get(session_id).cloned();
+        let Some((conn, _)) = active else {
+            if self.sessions.read().await.contains_key(session_id) {
+                return Err("The agent is not waiting for an answer.".to_string());
+            }
+            return Err(format!("ACP session '{session_id}' not found"));
+        };
+        conn.answer_question(answer)?;
+        info!("ACP session {session_id}: question answered");
+        Ok(())
+    }
+
+    /// Cancel the prompt running in the session bound to a chat.
+    pub async fn cancel_chat_prompt(&self, chat_id: i64) -> Result<(), String> {
+        let session_id = self
+            .chat_session(chat_id)
+            .await
+            .ok_or_else(|| "No active ACP session in this chat.".to_string())?;
+        self.cancel_prompt(&session_id).await
+    }
+
+    /// Switch a session to one of the modes its agent offers (by ID or name)
+    /// via `session/set_mode`. Returns the mode now active.
+    pub async fn set_mode(&self, session_id: &str, mode: &str) -> Result<AcpSessionMode, String> {
+        let sessions = self.sessions.read().await;
+        let session_mutex = sessions
+            .get(session_id)
+            .ok_or_else(|| format!("ACP session '{session_id}' not found"))?;
+        let Ok(mut session) = session_mutex.try_lock() else {
+            return Err(
+                "The agent is busy with a prompt; change the mode once it finishes.".to_string(),
+            );
+        };
+        let modes = session
+            .modes
+            .as_ref()
+            .ok_or_else(|| format!("Agent '{}' does not offer session modes", session.agent_id))?;
+        let target = modes.find(mode).cloned().ok_or_else(|| {
+            let ids: Vec<&str> = modes.available.iter().map(|m| m.id.as_str()).collect();
+            format!("Unknown mode '{mode}'. Available: {}", ids.join(", "))
+        })?;
+        let (Some(conn), Some(acp_sid)) = (session.connection.as_acp(), &session.acp_session_id)
+        else {
+            return Err("Session has no ACP connection to change modes on".to_string());
+        };
+        conn.send_request(
+            "session/set_mode",
+            Some(serde_json::json!({"sessionId": acp_sid, "modeId": target.id})),
+        )
+        .await?;
+
+        if let Some(modes) = session.modes.as_mut() {
+            modes.current_mode_id = target.id.clone();
+        }
+        session.last_activity = Instant::now();
+        info!("ACP session {session_id}: mode set to {}", target.id);
+        Ok(target)
+    }
+
+    /// Modes offered by a session's agent, with the current one.
+    pub async fn session_modes(&self, session_id: &str) -> Result<Option<AcpSessionModes>, String> {
+        let sessions = self.sessions.read().await;
+        let session_mutex = sessions
+            .get(session_id)
+            .ok_or_else(|| format!("ACP session '{session_id}' not found"))?;
+        let Ok(session) = session_mutex.try_lock() else {
+            return Err("The agent is busy with a prompt; try again once it finishes.".to_string());
+        };
+        Ok(session.modes.clone())
+    }
+
+    /// List all active sessions.
+    pub async fn list_sessions(&self) -> Vec<SessionSummary> {
+        let sessions = self.sessions.read().await;
+        let mut summaries = Vec::new();
+        for (id, session_mutex) in sessions.iter() {
+            let session = session_mutex.lock().await;
+            summaries.push(SessionSummary {
+                session_id: id.clone(),
+                agent_id: session.agent_id.clone(),
+                workspace: session.workspace.clone(),
+                status: session.status.clone(),
+                created_at: session.created_at.to_rfc3339(),
+                idle_secs: session.last_activity.elapsed().as_secs(),
+                usage: session.usage.clone(),
+            });
+        }
+        summaries
+    }
+
+    // -----------------------------------------------------------------------
+    // Chat-to-session binding (for command-based ACP)
+    // -----------------------------------------------------------------------
+
+    /// Bind a chat to an ACP session. Messages in this chat will be routed
+    /// to the ACP agent instead of the LLM.
+    pub async fn bind_chat(&self, chat_id: i64, session_id: &str) {
+        self.chat_sessions
+            .write()
+            .await
+            .insert(chat_id, session_id.to_string());
+        if let Some(db) = self.run_log() {
+            let id = session_id.to_string();
+            if let Err(e) =
+                crate::db::call_blocking(db, move |db| db.bind_acp_chat(chat_id, &id)).await
+            {
+                warn!("ACP: failed to persist binding of chat {chat_id}: {e}");
+            }
+        }
+        debug!("ACP: bound chat {chat_id} to session {session_id}");
+    }
+
+    /// Unbind a chat from its ACP session.
+    pub async fn unbind_chat(&self, chat_id: i64) {
+        self.chat_sessions.write().await.remove(&chat_id);
+        if let Some(db) = self.run_log() {
+            if let Err(e) =
+                crate::db::call_blocking(db, move |db| db.unbind_acp_chat(chat_id)).await
+            {
+                warn!("ACP: failed to persist unbinding of chat {chat_id}: {e}");
+            }
+        }
+        debug!("ACP: unbound chat {chat_id}");
+    }
+
+    /// Get the session_id bound to a chat, if any.
+    pub async fn chat_session(&self, chat_id: i64) -> Option<String> {
+        self.chat_sessions.read().await.get(&chat_id).cloned()
+    }
+
+    /// End the session bound to a chat and unbind it. Returns Ok if a session
+    /// existed and was ended, Err if no session was bound.
+    pub async fn end_chat_session(&self, chat_id: i64) -> Result<(), String> {
+        let session_id = self
+            .chat_sessions
+            .read()
+            .await
+            .get(&chat_id)
+            .cloned()
+            .ok_or_else(|| "No active ACP session in this chat".to_string())?;
+
+        self.end_session(&session_id).await?;
+        self.unbind_chat(chat_id).await;
+        Ok(())
+    }
+
+    // -----------------------------------------------------------------------
+    // Interactive permissions
+    // -----------------------------------------------------------------------
+
+    /// Build a permission handler that posts each request to `chat_id` via
+    /// `notify` and waits up to `permission_timeout_secs` for the reply of
+    /// `requester` (or a control chat), delivered through
+    /// [`resolve_permission`](Self::resolve_permission). With no requester,
+    /// only a control chat can answer.
+    pub fn chat_permission_handler(
+        self: &Arc<Self>,
+        chat_id: i64,
+        requester: Option<String>,
+        notify: JobCompletionCallback,
+    ) -> AcpPermissionHandler {
+        let manager = Arc::clone(self);
+        Arc::new(move |request| {
+            let manager = Arc::clone(&manager);
+            let requester = requester.clone();
+            let notify = notify.clone();
+            Box::pin(async move {
+                manager
+                    .ask_chat_permission(chat_id, requester, request, notify)
+                    .await
+            })
+        })
+    }
+
+    async fn ask_chat_permission(
+        &self,
+        chat_id: i64,
+        requester: Option<String>,
+        request: AcpPermissionRequest,
+        notify: JobCompletionCallback,
+    ) -> AcpPermissionDecision {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        // A newer request replaces (and thereby cancels) any stale one
+        self.pending_permissions
+            .lock()
+            .await
+            .insert(chat_id, PendingPermission { requester, tx });
+
+        let timeout = Duration::from_secs(self.config.permission_timeout_secs);
+        notify(chat_id, format_permission_prompt(&request, timeout)).await;
+
+        match tokio::time::timeout(timeout, rx).await {
+            Ok(Ok(decision)) => decision,
+            Ok(Err(_)) => AcpPermissionDecision::Cancelled,
+            Err(_) => {
+                self.pending_permissions.lock().await.remove(&chat_id);
+                warn!(
+                    "ACP [{}]: permission request in chat {chat_id} timed out",
+                    request.agent_name
+                );
+                notify(
+                    chat_id,
+                    format!(
+                        "[ACP {}] Permission request timed out, cancelled: {}",
+                        request.agent_name, request.tool_name
+                    ),
+                )
+                .await;
+                AcpPermissionDecision::Cancelled
+            }
+        }
+    }
+
+    /// Whether a permission request is waiting for an answer in this chat.
+    pub async fn has_pending_permission(&self, chat_id: i64) -> bool {
+        self.pending_permissions.lock().await.contains_key(&chat_id)
+    }
+
+    /// Answer the pending permission request in a chat with a reply from
+    /// `sender`; `control_chat` lets anyone in the chat answer. Returns the
+    /// decision if `text` was an approve/deny reply and a request was
+    /// waiting, an error if `sender` may not answer it (the request stays
+    /// pending), and `None` otherwise (the message should be handled
+    /// normally).
+    pub async fn resolve_permission(
+        &self,
+        chat_id: i64,
+        sender: &str,
+        control_chat: bool,
+        text: &str,
+    ) -> Option<Result<AcpPermissionDecision, String>> {
+        let decision = AcpPermissionDecision::parse_reply(text)?;
+        let mut pending = self.pending_permissions.lock().await;
+        let requester = pending.get(&chat_id)?.requester.as_deref();
+        if !control_chat && requester != Some(sender) {
+            return Some(Err(match requester {
+                Some(requester) => {
+                    format!(
+                        "Only {requester} or a control chat can answer this permission request."
+                    )
+                }
+                None => "Only a control chat can answer this permission request.".to_string(),
+            }));
+        }
+        let permission = pending.remove(&chat_id)?;
+        permission.tx.send(decision).ok()?;
+        Some(Ok(decision))
+    }
+
+    // -----------------------------------------------------------------------
+    // Async job management
+    // -----------------------------------------------------------------------
+
+    /// Submit an async job that runs a prompt in the background.
+    /// Returns the job ID immediately. The job runs in a background task and
+    /// optionally calls `on_complete` with the result when done.
+    pub async fn submit_job(
+        self: &Arc<Self>,
+        session_id: &str,
+        message: &str,
+        timeout_secs: Option<u64>,
+        chat_id: Option<i64>,
+        on_complete: Option<JobCompletionCallback>,
+    ) -> Result<String, String> {
+        // Validate session exists
+        {
+            let sessions = self.sessions.read().await;
+            let session_mutex = sessions
+                .get(session_id)
+                .ok_or_else(|| format!("ACP session '{session_id}' not found"))?;
+            let session = session_mutex.lock().await;
+            if session.status == SessionStatus::Ended {
+                return Err(format!("ACP session '{session_id}' has ended"));
+            }
+        }
+
+        // Enforce job limit
+        self.cleanup_expired_jobs().await;
+        {
+            let jobs = self.jobs.read().await;
+            let running = jobs.values().count();
+            if running >= MAX_JOBS {
+                return Err(format!(
+                    "Job limit reached ({running}/{MAX_JOBS}). Wait for existing jobs to complete."
+                ));
+            }
+        }
+
+        let job_id = uuid::Uuid::new_v4().to_string();
+
+        // Look up agent_id for the job record
+        let agent_id = {
+            let sessions = self.sessions.read().await;
+            let session_mutex = sessions.get(session_id).unwrap();
+            let session = session_mutex.lock().await;
+            session.agent_id.clone()
+        };
+
+        let job = AcpJob {
+            id: job_id.clone(),
+            session_id: session_id.to_string(),
+            agent_id: agent_id.clone(),
+            status: AcpJobStatus::Running,
+            result: None,
+            error: None,
+            created_at: chrono::Utc::now(),
+            completed_at: None,
+        };
+
+        self.jobs
+            .write()
+            .await
+            .insert(job_id.clone(), Mutex::new(job));
+
+        // Spawn background task
+        let manager = Arc::clone(self);
+        let sid = session_id.to_string();
+        let msg = message.to_string();
+        let jid = job_id.clone();
+        let agent_id_for_task = agent_id.clone();
+
+        tokio::spawn(async move {
+            let agent_id = agent_id_for_task;
+            // Route permission requests to the chat the job reports back to
+            let permission_handler = chat_id
+                .zip(on_complete.clone())
+                .map(|(cid, cb)| manager.chat_permission_handler(cid, None, cb));
+            let result = manager
+                .prompt_with_permissions(
+                    &sid,
+                    &msg,
+                    &[],
+                    timeout_secs,
+                    None,
+                    permission_handler.as_ref(),
+                )
+                .await;
+            let now = chrono::Utc::now();
+
+            // Format notification text before updating job store
+            let notification = match &result {
+                Ok(r) => {
+                    let mut text = String::new();
+                    if r.context_reset {
+                        text.push_str("[Agent restarted — previous context lost]\n\n");
+                    }
+                    for m in &r.messages {
+                        if !m.is_empty() {
+                            if !text.is_empty() {
+                                text.push('\n');
+                            }
+                            text.push_str(m);
+                        }
+                    }
+                    if !r.tool_calls.is_empty() {
+                        if !text.is_empty() {
+                            text.push_str("\n\n");
+                        }
+                        text.push_str(&format!("[{} tool call(s)]", r.tool_calls.len()));
+                    }
+                    if !r.completed {
+                        if !text.is_empty() {
+                            text.push_str("\n\n");
+                        }
+                        text.push_str("[Prompt cancelled]");
+                    }
+                    if text.is_empty() {
+                        text = "(Agent completed with no output)".to_string();
+                    }
+                    text
+                }
+                Err(e) => format!("ACP job failed: {e}"),
+            };
+
+            // Update job record
+            {
+                let jobs = manager.jobs.read().await;
+                if let Some(job_mutex) = jobs.get(&jid) {
+                    let mut job = job_mutex.lock().await;
+                    match result {
+                        Ok(r) => {
+                            job.status = AcpJobStatus::Completed;
+                            job.result = Some(r);
+                        }
+                        Err(e) => {
+                            job.status = AcpJobStatus::Failed;
+                            job.error = Some(e);
+                        }
+                    }
+                    job.completed_at = Some(now);
+                }
+            }
+
+            // Fire completion callback
+            if let (Some(cid), Some(cb)) = (chat_id, on_complete) {
                 let header = format!("[ACP job {jid} ({agent_id})]:\n");
                 cb(cid, format!("{header}{notification}")).await;
             }
 
-            info!("ACP job {jid} finished");
+            info!("ACP job {jid} finished");
+        });
+
+        info!("ACP job submitted: {job_id} (session={session_id}, agent={agent_id})");
+        Ok(job_id)
+    }
+
+    /// Get the status of a job by ID.
+    pub async fn job_status(&self, job_id: &str) -> Result<AcpJobSummary, String> {
+        let jobs = self.jobs.read().await;
+        let job_mutex = jobs
+            .get(job_id)
+            .ok_or_else(|| format!("ACP job '{job_id}' not found"))?;
+        let job = job_mutex.lock().await;
+        Ok(AcpJobSummary {
+            id: job.id.clone(),
+            session_id: job.session_id.clone(),
+            agent_id: job.agent_id.clone(),
+            status: job.status.clone(),
+            created_at: job.created_at.to_rfc3339(),
+            completed_at: job.completed_at.map(|t| t.to_rfc3339()),
+            duration_ms: job.result.as_ref().map(|r| r.duration_ms),
+            error: job.error.clone(),
+        })
+    }
+
+    /// Remove expired jobs (older than JOB_TTL_SECS).
+    async fn cleanup_expired_jobs(&self) {
+        let now = chrono::Utc::now();
+        let ttl = chrono::Duration::seconds(JOB_TTL_SECS as i64);
+
+        let expired: Vec<String> = {
+            let jobs = self.jobs.read().await;
+            jobs.iter()
+                .filter(|(_, jm)| {
+                    // Only non-blocking try_lock here; skip if locked
+                    if let Ok(j) = jm.try_lock() {
+                        j.status != AcpJobStatus::Running && (now - j.created_at) > ttl
+                    } else {
+                        false
+                    }
+                })
+                .map(|(id, _)| id.clone())
+                .collect()
+        };
+
+        if !expired.is_empty() {
+            let mut jobs = self.jobs.write().await;
+            for id in &expired {
+                jobs.remove(id);
+            }
+            debug!("ACP job cleanup: removed {} expired job(s)", expired.len());
+        }
+    }
+
+    /// Reap sessions that have been idle (no prompt activity) longer than
+    /// `idle_timeout_secs` and tell their bound chats. Busy sessions are
+    /// skipped — prompts have their own timeout. Returns the number of reaped
+    /// sessions.
+    pub async fn reap_idle_sessions(&self) -> usize {
+        // Also clean up expired jobs while we're here
+        self.cleanup_expired_jobs().await;
+
+        let idle_timeout = Duration::from_secs(self.config.idle_timeout_secs);
+        if idle_timeout.is_zero() {
+            return 0;
+        }
+
+        // Collect session IDs that exceed the idle threshold.
+        // We only need a read lock to scan; end_session takes its own write lock.
+        let mut to_reap: Vec<(String, String)> = Vec::new(); // (session_id, agent_id)
+        {
+            let sessions = self.sessions.read().await;
+            for (id, session_mutex) in sessions.iter() {
+                // A prompt holds the lock for its whole run — skip busy sessions
+                let Ok(session) = session_mutex.try_lock() else {
+                    continue;
+                };
+                if session.status == SessionStatus::Prompting {
+                    continue; // active work — skip
+                }
+                if session.last_activity.elapsed() >= idle_timeout {
+                    to_reap.push((id.clone(), session.agent_id.clone()));
+                }
+            }
+        }
+
+        let count = to_reap.len();
+        for (session_id, agent_id) in &to_reap {
+            warn!(
+                "ACP idle reaper: ending session {session_id} (agent={agent_id}, idle > {}s)",
+                self.config.idle_timeout_secs
+            );
+            // Before ending — that unbinds the chats
+            self.notify_bound_chats(
+                session_id,
+                &format!(
+                    "[ACP {agent_id}] Session ended after {}s without activity. Use #new to start another.",
+                    self.config.idle_timeout_secs
+                ),
+            )
+            .await;
+            if let Err(e) = self.end_session(session_id).await {
+                warn!("ACP idle reaper: failed to end session {session_id}: {e}");
+            }
+        }
+
+        if count > 0 {
+            info!("ACP idle reaper: reaped {count} session(s)");
+        }
+        count
+    }
+
+    /// Check that every idle session's agent process is still running.
+    /// Dead agents mark their session `Crashed` and the bound chats are told;
+    /// with `auto_respawn` the agent is restarted right away. Sessions that
+    /// are mid-prompt are skipped — a dead agent fails the prompt itself.
+    /// Returns the number of crashed sessions found.
+    pub async fn check_session_health(&self) -> usize {
+        let mut crashed: Vec<(String, String)> = Vec::new(); // (session_id, agent_id)
+        {
+            let sessions = self.sessions.read().await;
+            for (id, session_mutex) in sessions.iter() {
+                // A prompt holds the lock for its whole run — skip busy sessions
+                let Ok(mut session) = session_mutex.try_lock() else {
+                    continue;
+                };
+                if matches!(
+                    session.status,
+                    SessionStatus::Crashed | SessionStatus::Ended
+                ) {
+                    continue;
+                }
+                if session.connection.is_alive().await {
+                    continue;
+                }
+                warn!(
+                    "ACP health: agent process for session {id} exited (agent={})",
+                    session.agent_id
+                );
+                session.status = SessionStatus::Crashed;
+                crashed.push((id.clone(), session.agent_id.clone()));
+            }
+        }
+
+        for (session_id, agent_id) in &crashed {
+            let notice = if self.config.auto_respawn {
+                match self.respawn_crashed(session_id).await {
+                    Ok(()) => format!(
+                        "[ACP {agent_id}] Agent process crashed and was restarted — previous context lost."
+                    ),
+                    Err(e) => {
+                        warn!("ACP health: failed to respawn session {session_id}: {e}");
+                        format!(
+                            "[ACP {agent_id}] Agent process crashed and restart failed: {e}. \
+                             The next prompt will try again."
+                        )
+                    }
+                }
+            } else {
+                format!(
+                    "[ACP {agent_id}] Agent process crashed. It will be restarted on the next prompt \
+                     (previous context will be lost)."
+                )
+            };
+            self.notify_bound_chats(session_id, &notice).await;
+        }
+
+        if !crashed.is_empty() {
+            info!("ACP health: {} crashed session(s) found", crashed.len());
+        }
+        crashed.len()
+    }
+
+    /// Restart the agent of a `Crashed` session in place.
+    async fn respawn_crashed(&self, session_id: &str) -> Result<(), String> {
+        let sessions = self.sessions.read().await;
+        let session_mutex = sessions
+            .get(session_id)
+            .ok_or_else(|| format!("ACP session '{session_id}' not found"))?;
+        let mut session = session_mutex.lock().await;
+        if session.status != SessionStatus::Crashed {
+            // A prompt got there first and already recovered it
+            return Ok(());
+        }
+        self.recover_session(&mut session).await?;
+        session.status = SessionStatus::Active;
+        Ok(())
+    }
+
+    /// Post `text` to every chat bound to `session_id`, if a notifier is set.
+    async fn notify_bound_chats(&self, session_id: &str, text: &str) {
+        let Some(notify) = self.chat_notifier.get() else {
+            return;
+        };
+        let chats: Vec<i64> = self
+            .chat_sessions
+            .read()
+            .await
+            .iter()
+            .filter(|(_, sid)| sid.as_str() == session_id)
+            .map(|(chat_id, _)| *chat_id)
+            .collect();
+        for chat_id in chats {
+            notify(chat_id, text.to_string()).await;
+        }
+    }
+
+    /// Cleanup all sessions (called on process shutdown).
+    pub async fn cleanup(&self) {
+        let session_ids: Vec<String> = {
+            let sessions = self.sessions.read().await;
+            sessions.keys().cloned().collect()
+        };
+
+        // Persisted records stay, so sessions are restored on next start
+        for id in &session_ids {
+            if let Err(e) = self.close_session(id).await {
+                warn!("ACP cleanup: failed to end session {id}: {e}");
+            }
+        }
+
+        if !session_ids.is_empty() {
+            info!(
+                "ACP manager cleanup: terminated {} session(s)",
+                session_ids.len()
+            );
+        }
+    }
+}
+
+/// Spawn a background task that periodically reaps idle ACP sessions.
+/// The task runs every 60 seconds (or every `idle_timeout_secs`, if shorter)
+/// and terminates sessions that have been idle longer than
+/// `idle_timeout_secs`. Does nothing if the timeout is 0.
+pub fn spawn_idle_reaper(manager: Arc<AcpManager>) {
+    if manager.config.idle_timeout_secs == 0 {
+        info!("ACP idle reaper disabled (idle_timeout_secs=0)");
+        return;
+    }
+    let period = manager.config.idle_timeout_secs.min(60);
+    info!(
+        "ACP idle reaper started (checking every {period}s, timeout={}s)",
+        manager.config.idle_timeout_secs
+    );
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(period));
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        loop {
+            interval.tick().await;
+            manager.reap_idle_sessions().await;
+        }
+    });
+}
+
+/// Spawn a background task that periodically checks ACP agent liveness via
+/// [`AcpManager::check_session_health`]. Does nothing if the interval is 0.
+pub fn spawn_health_monitor(manager: Arc<AcpManager>) {
+    if manager.config.health_check_interval_secs == 0 {
+        info!("ACP health monitor disabled (health_check_interval_secs=0)");
+        return;
+    }
+    info!(
+        "ACP health monitor started (checking every {}s, auto_respawn={})",
+        manager.config.health_check_interval_secs, manager.config.auto_respawn
+    );
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(
+            manager.config.health_check_interval_secs,
+        ));
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        loop {
+            interval.tick().await;
+            manager.check_session_health().await;
+        }
+    });
+}
+
+/// What `AcpManager::session_status` reports for one session.
+#[derive(Debug, Clone)]
+pub struct AcpSessionStatus {
+    pub session_id: String,
+    pub agent_id: String,
+    pub workspace: String,
+    pub status: SessionStatus,
+    /// Unknown while a prompt holds the session
+    pub created_at: Option<String>,
+    /// Seconds since last prompt activity; unknown while a prompt runs
+    pub idle_secs: Option<u64>,
+    /// Text of the running or most recent prompt
+    pub last_prompt: Option<String>,
+    /// How long the running prompt has been going, if one is running
+    pub prompt_elapsed_secs: Option<u64>,
+    /// Prompts waiting behind the running one
+    pub queued_prompts: usize,
+    pub prompts_completed: usize,
+    /// Tool calls summed over finished prompts
+    pub tool_calls: usize,
+    /// Files changed by finished prompts, without duplicates
+    pub files_changed: Vec<String>,
+    pub usage: AcpUsage,
+}
+
+/// Prompt history of a session, see `AcpManager::session_activity`.
+#[derive(Debug, Clone, Default)]
+struct SessionActivity {
+    agent_id: String,
+    workspace: String,
+    last_prompt: Option<String>,
+    prompt_started: Option<Instant>,
+    prompts: usize,
+    tool_calls: usize,
+    files_changed: Vec<String>,
+    usage: AcpUsage,
+}
+
+/// Summary of an active session (for listing)
+#[derive(Debug, Clone)]
+pub struct SessionSummary {
+    pub session_id: String,
+    pub agent_id: String,
+    pub workspace: String,
+    pub status: SessionStatus,
+    pub created_at: String,
+    /// Seconds since last prompt activity
+    pub idle_secs: u64,
+    /// Token usage summed over the session's prompts
+    pub usage: AcpUsage,
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_config_defaults() {
+        let config = AcpConfig::default();
+        assert!(!config.default_auto_approve);
+        assert_eq!(config.prompt_timeout_secs, 300);
+        assert!(config.agents.is_empty());
+    }
+
+    #[test]
+    fn test_config_parse_full() {
+        let json = r#"{
+            "defaultAutoApprove": true,
+            "promptTimeoutSecs": 600,
+            "acpAgents": {
+                "claude": {
+                    "launch": "npx",
+                    "command": "@anthropic-ai/claude-code@latest",
+                    "args": ["--acp"],
+                    "env": {"ANTHROPIC_API_KEY": "sk-test"},
+                    "workspace": "/tmp/test",
+                    "auto_approve": true
+                }
+            }
+        }"#;
+
+        let config: AcpConfig = serde_json::from_str(json).unwrap();
+        assert!(config.default_auto_approve);
+        assert_eq!(config.prompt_timeout_secs, 600);
+        assert_eq!(config.agents.len(), 1);
+
+        let claude = config.agents.get("claude").unwrap();
+        assert_eq!(claude.launch, "npx");
+        assert_eq!(claude.command, "@anthropic-ai/claude-code@latest");
+        assert_eq!(claude.args, vec!["--acp"]);
+        assert_eq!(claude.workspace.as_deref(), Some("/tmp/test"));
+        assert_eq!(claude.auto_approve, Some(true));
+    }
+
+    #[test]
+    fn test_config_parse_minimal() {
+        let json = r#"{
+            "acpAgents": {
+                "claude": {
+                    "command": "@anthropic-ai/claude-code@latest"
+                }
+            }
+        }"#;
+
+        let config: AcpConfig = serde_json::from_str(json).unwrap();
+        assert!(!config.default_auto_approve);
+        assert_eq!(config.prompt_timeout_secs, 300);
+        assert_eq!(config.spawn_retries, 2);
+        assert_eq!(config.spawn_backoff_ms, 1000);
+        assert_eq!(config.first_spawn_timeout_secs, 180);
+
+        let claude = config.agents.get("claude").unwrap();
+        assert_eq!(claude.launch, "npx");
+        assert!(claude.args.is_empty());
+        assert!(claude.env.is_empty());
+        assert!(claude.workspace.is_none());
+        assert!(claude.auto_approve.is_none());
+    }
+
+    #[test]
+    fn test_config_parse_snake_case_aliases() {
+        let json = r#"{
+            "default_auto_approve": true,
+            "prompt_timeout_secs": 120,
+            "agents": {
+                "claude": {
+                    "command": "@anthropic-ai/claude-code@latest"
+                }
+            }
+        }"#;
+
+        let config: AcpConfig = serde_json::from_str(json).unwrap();
+        assert!(config.default_auto_approve);
+        assert_eq!(config.prompt_timeout_secs, 120);
+        assert_eq!(config.agents.len(), 1);
+    }
+
+    #[test]
+    fn test_missing_file_returns_default() {
+        let config = AcpConfig::from_file("/nonexistent/acp.json");
+        assert!(config.agents.is_empty());
+    }
+
+    #[test]
+    fn test_manager_from_config() {
+        let manager = AcpManager::from_config_file("/nonexistent/acp.json");
+        assert!(manager.available_agents().is_empty());
+        assert!(!manager.has_agent("claude"));
+    }
+
+    #[test]
+    fn test_build_spawn_command_npx() {
+        let config = AcpAgentConfig {
+            launch: "npx".to_string(),
+            command: "@anthropic-ai/claude-code@latest".to_string(),
+            args: vec!["--acp".to_string()],
+            env: HashMap::from([("FOO".to_string(), "bar".to_string())]),
+            workspace: Some("/tmp/ws".to_string()),
+            auto_approve: None,
+            mode: default_mode(),
+            resource_limits: None,
+            policy: None,
+            model: None,
+            url: None,
+            headers: HashMap::new(),
+        };
+
+        let cmd = build_spawn_command(&config, None);
+        let prog = cmd.as_std().get_program();
+        assert_eq!(prog, "npx");
+
+        let args: Vec<&std::ffi::OsStr> = cmd.as_std().get_args().collect();
+        assert_eq!(
+            args,
+            vec!["-y", "@anthropic-ai/claude-code@latest", "--acp"]
+        );
+    }
+
+    #[test]
+    fn test_build_spawn_command_binary() {
+        let config = AcpAgentConfig {
+            launch: "binary".to_string(),
+            command: "/usr/bin/opencode".to_string(),
+            args: vec!["acp".to_string()],
+            env: HashMap::new(),
+            workspace: None,
+            auto_approve: None,
+            mode: default_mode(),
+            resource_limits: None,
+            policy: None,
+            model: None,
+            url: None,
+            headers: HashMap::new(),
+        };
+
+        let cmd = build_spawn_command(&config, Some("/home/user/project"));
+        let prog = cmd.as_std().get_program();
+        assert_eq!(prog, "/usr/bin/opencode");
+
+        let args: Vec<&std::ffi::OsStr> = cmd.as_std().get_args().collect();
+        assert_eq!(args, vec!["acp"]);
+
+        assert_eq!(
+            cmd.as_std().get_current_dir(),
+            Some(std::path::Path::new("/home/user/project"))
+        );
+    }
+
+    #[test]
+    fn test_build_spawn_command_workspace_override() {
+        let config = AcpAgentConfig {
+            launch: "npx".to_string(),
+            command: "agent".to_string(),
+            args: vec![],
+            env: HashMap::new(),
+            workspace: Some("/default/ws".to_string()),
+            auto_approve: None,
+            mode: default_mode(),
+            resource_limits: None,
+            policy: None,
+            model: None,
+            url: None,
+            headers: HashMap::new(),
+        };
+
+        // Explicit workspace overrides config default
+        let cmd = build_spawn_command(&config, Some("/override/ws"));
+        assert_eq!(
+            cmd.as_std().get_current_dir(),
+            Some(std::path::Path::new("/override/ws"))
+        );
+
+        // Falls back to config default
+        let cmd2 = build_spawn_command(&config, None);
+        assert_eq!(
+            cmd2.as_std().get_current_dir(),
+            Some(std::path::Path::new("/default/ws"))
+        );
+    }
+
+    #[test]
+    fn test_jsonrpc_message_classification() {
+        // Response
+        let resp: JsonRpcMessage =
+            serde_json::from_str(r#"{"jsonrpc":"2.0","id":1,"result":{"ok":true}}"#).unwrap();
+        assert!(resp.is_response());
+        assert!(!resp.is_notification());
+
+        // Error response
+        let err: JsonRpcMessage = serde_json::from_str(
+            r#"{"jsonrpc":"2.0","id":2,"error":{"code":-1,"message":"fail"}}"#,
+        )
+        .unwrap();
+        assert!(err.is_response());
+        assert!(!err.is_notification());
+
+        // Notification
+        let notif: JsonRpcMessage = serde_json::from_str(
+            r#"{"jsonrpc":"2.0","method":"messages/create","params":{"text":"hi"}}"#,
+        )
+        .unwrap();
+        assert!(!notif.is_response());
+        assert!(notif.is_notification());
+    }
+
+    #[test]
+    fn test_prompt_result_default() {
+        let result = AcpPromptResult {
+            messages: vec!["hello".to_string()],
+            tool_calls: vec![ToolCallInfo {
+                name: "bash".to_string(),
+                input: serde_json::json!({"command": "ls"}),
+            }],
+            files_changed: vec!["foo.rs".to_string()],
+            plan: vec![],
+            thoughts: vec![],
+            usage: None,
+            completed: true,
+            duration_ms: 1234,
+            context_reset: false,
+        };
+
+        assert_eq!(result.messages.len(), 1);
+        assert_eq!(result.tool_calls.len(), 1);
+        assert_eq!(result.tool_calls[0].name, "bash");
+        assert!(result.completed);
+        assert_eq!(result.duration_ms, 1234);
+    }
+
+    #[tokio::test]
+    async fn test_manager_new_session_unknown_agent() {
+        let manager = AcpManager::from_config_file("/nonexistent/acp.json");
+        let result = manager.new_session("nonexistent", None, None).await;
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("not configured"));
+    }
+
+    #[tokio::test]
+    async fn test_manager_list_sessions_empty() {
+        let manager = AcpManager::from_config_file("/nonexistent/acp.json");
+        let sessions = manager.list_sessions().await;
+        assert!(sessions.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_manager_end_session_not_found() {
+        let manager = AcpManager::from_config_file("/nonexistent/acp.json");
+        let result = manager.end_session("nonexistent").await;
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("not found"));
+    }
+
+    #[tokio::test]
+    async fn test_manager_prompt_not_found() {
+        let manager = AcpManager::from_config_file("/nonexistent/acp.json");
+        let result = manager.prompt("nonexistent", "hello", None, None).await;
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("not found"));
+    }
+
+    // -----------------------------------------------------------------------
+    // Phase 7.1: Additional config parsing tests
+    // -----------------------------------------------------------------------
+
+    #[test]
+    fn test_config_parse_multiple_agents() {
+        let json = r#"{
+            "acpAgents": {
+                "claude": {
+                    "command": "@anthropic-ai/claude-code@latest",
+                    "workspace": "/tmp/claude"
+                },
+                "opencode": {
+                    "launch": "binary",
+                    "command": "/usr/bin/opencode",
+                    "args": ["acp"]
+                },
+                "gemini": {
+                    "launch": "npx",
+                    "command": "@google/gemini-cli@latest",
+                    "args": ["--experimental-acp"],
+                    "env": {"GEMINI_API_KEY": "test-key"}
+                }
+            }
+        }"#;
+
+        let config: AcpConfig = serde_json::from_str(json).unwrap();
+        assert_eq!(config.agents.len(), 3);
+
+        let claude = config.agents.get("claude").unwrap();
+        assert_eq!(claude.launch, "npx"); // default
+        assert_eq!(claude.workspace.as_deref(), Some("/tmp/claude"));
+
+        let opencode = config.agents.get("opencode").unwrap();
+        assert_eq!(opencode.launch, "binary");
+        assert_eq!(opencode.command, "/usr/bin/opencode");
+        assert_eq!(opencode.args, vec!["acp"]);
+        assert!(opencode.workspace.is_none());
+
+        let gemini = config.agents.get("gemini").unwrap();
+        assert_eq!(gemini.launch, "npx");
+        assert_eq!(gemini.env.get("GEMINI_API_KEY").unwrap(), "test-key");
+    }
+
+    #[test]
+    fn test_config_parse_invalid_json_returns_default() {
+        // AcpConfig::from_file should return defaults on parse failure.
+        // We can't easily test from_file with bad content without a temp file,
+        // but we can verify serde_json rejects garbage.
+        let result: Result<AcpConfig, _> = serde_json::from_str("NOT JSON");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_config_parse_empty_object() {
+        let config: AcpConfig = serde_json::from_str("{}").unwrap();
+        assert!(!config.default_auto_approve);
+        assert_eq!(config.prompt_timeout_secs, 300);
+        assert!(config.agents.is_empty());
+    }
+
+    #[test]
+    fn test_config_parse_agent_env_propagated() {
+        let config = AcpAgentConfig {
+            launch: "npx".to_string(),
+            command: "agent".to_string(),
+            args: vec![],
+            env: HashMap::from([
+                ("KEY1".to_string(), "val1".to_string()),
+                ("KEY2".to_string(), "val2".to_string()),
+            ]),
+            workspace: None,
+            auto_approve: None,
+            mode: default_mode(),
+            resource_limits: None,
+            policy: None,
+            model: None,
+            url: None,
+            headers: HashMap::new(),
+        };
+
+        let cmd = build_spawn_command(&config, None);
+        let envs: Vec<_> = cmd.as_std().get_envs().collect();
+        // Verify our env vars are set (they should be among the envs)
+        let has_key1 = envs.iter().any(|(k, v)| {
+            k == &std::ffi::OsStr::new("KEY1") && v == &Some(std::ffi::OsStr::new("val1"))
+        });
+        let has_key2 = envs.iter().any(|(k, v)| {
+            k == &std::ffi::OsStr::new("KEY2") && v == &Some(std::ffi::OsStr::new("val2"))
         });
+        assert!(has_key1, "KEY1 env var should be set");
+        assert!(has_key2, "KEY2 env var should be set");
+    }
 
-        info!("ACP job submitted: {job_id} (session={session_id}, agent={agent_id})");
-        Ok(job_id)
+    #[test]
+    fn test_build_spawn_command_uvx() {
+        let config = AcpAgentConfig {
+            launch: "uvx".to_string(),
+            command: "some-agent".to_string(),
+            args: vec!["--flag".to_string()],
+            env: HashMap::new(),
+            workspace: None,
+            auto_approve: None,
+            mode: default_mode(),
+            resource_limits: None,
+            policy: None,
+            model: None,
+            url: None,
+            headers: HashMap::new(),
+        };
+
+        let cmd = build_spawn_command(&config, None);
+        let prog = cmd.as_std().get_program();
+        assert_eq!(prog, "uvx");
+
+        let args: Vec<&std::ffi::OsStr> = cmd.as_std().get_args().collect();
+        assert_eq!(args, vec!["some-agent", "--flag"]);
     }
 
-    /// Get the status of a job by ID.
-    pub async fn job_status(&self, job_id: &str) -> Result<AcpJobSummary, String> {
-        let jobs = self.jobs.read().await;
-        let job_mutex = jobs
-            .get(job_id)
-            .ok_or_else(|| format!("ACP job '{job_id}' not found"))?;
-        let job = job_mutex.lock().await;
-        Ok(AcpJobSummary {
-            id: job.id.clone(),
-            session_id: job.session_id.clone(),
-            agent_id: job.agent_id.clone(),
-            status: job.status.clone(),
-            created_at: job.created_at.to_rfc3339(),
-            completed_at: job.completed_at.map(|t| t.to_rfc3339()),
-            duration_ms: job.result.as_ref().map(|r| r.duration_ms),
-            error: job.error.clone(),
-        })
+    #[test]
+    fn test_manager_from_config_direct() {
+        let config = AcpConfig {
+            default_auto_approve: true,
+            prompt_timeout_secs: 60,
+            agents: HashMap::from([(
+                "test".to_string(),
+                AcpAgentConfig {
+                    launch: "binary".to_string(),
+                    command: "/usr/bin/test".to_string(),
+                    args: vec![],
+                    env: HashMap::new(),
+                    workspace: None,
+                    auto_approve: None,
+                    mode: default_mode(),
+                    resource_limits: None,
+                    policy: None,
+                    model: None,
+                    url: None,
+                    headers: HashMap::new(),
+                },
+            )]),
+            ..AcpConfig::default()
+        };
+
+        let manager = AcpManager::from_config(config);
+        assert!(manager.has_agent("test"));
+        assert!(!manager.has_agent("other"));
+        assert_eq!(manager.available_agents(), vec!["test"]);
+        assert!(manager.config.default_auto_approve);
+        assert_eq!(manager.config.prompt_timeout_secs, 60);
+    }
+
+    #[test]
+    fn test_agent_config_method() {
+        let config = AcpConfig {
+            default_auto_approve: false,
+            prompt_timeout_secs: 300,
+            agents: HashMap::from([(
+                "claude".to_string(),
+                AcpAgentConfig {
+                    launch: "npx".to_string(),
+                    command: "@anthropic-ai/claude-code@latest".to_string(),
+                    args: vec!["--acp".to_string()],
+                    env: HashMap::new(),
+                    workspace: Some("/tmp/ws".to_string()),
+                    auto_approve: Some(true),
+                    mode: default_mode(),
+                    resource_limits: None,
+                    policy: None,
+                    model: None,
+                    url: None,
+                    headers: HashMap::new(),
+                },
+            )]),
+            ..AcpConfig::default()
+        };
+
+        let manager = AcpManager::from_config(config);
+        let agent_cfg = manager.agent_config("claude");
+        assert!(agent_cfg.is_some());
+        let cfg = agent_cfg.unwrap();
+        assert_eq!(cfg.command, "@anthropic-ai/claude-code@latest");
+        assert_eq!(cfg.workspace.as_deref(), Some("/tmp/ws"));
+        assert_eq!(cfg.auto_approve, Some(true));
+
+        assert!(manager.agent_config("nonexistent").is_none());
+    }
+
+    #[test]
+    fn test_session_status_equality() {
+        assert_eq!(SessionStatus::Active, SessionStatus::Active);
+        assert_eq!(SessionStatus::Prompting, SessionStatus::Prompting);
+        assert_eq!(SessionStatus::Ended, SessionStatus::Ended);
+        assert_ne!(SessionStatus::Active, SessionStatus::Ended);
+        assert_ne!(SessionStatus::Active, SessionStatus::Prompting);
+    }
+
+    #[test]
+    fn test_jsonrpc_request_serialization() {
+        let req = JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            id: Some(42),
+            method: "test/method".to_string(),
+            params: Some(serde_json::json!({"key": "value"})),
+        };
+        let json = serde_json::to_string(&req).unwrap();
+        assert!(json.contains("\"jsonrpc\":\"2.0\""));
+        assert!(json.contains("\"id\":42"));
+        assert!(json.contains("\"method\":\"test/method\""));
+        assert!(json.contains("\"key\":\"value\""));
     }
 
-    /// Remove expired jobs (older than JOB_TTL_SECS).
-    async fn cleanup_expired_jobs(&self) {
-        let now = chrono::Utc::now();
-        let ttl = chrono::Duration::seconds(JOB_TTL_SECS as i64);
-
-        let expired: Vec<String> = {
-            let jobs = self.jobs.read().await;
-            jobs.iter()
-                .filter(|(_, jm)| {
-                    // Only non-blocking try_lock here; skip if locked
-                    if let Ok(j) = jm.try_lock() {
-                        j.status != AcpJobStatus::Running && (now - j.created_at) > ttl
-                    } else {
-                        false
-                    }
-                })
-                .map(|(id, _)| id.clone())
-                .collect()
+    #[test]
+    fn test_jsonrpc_notification_serialization() {
+        let req = JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            id: None,
+            method: "notifications/initialized".to_string(),
+            params: None,
         };
+        let json = serde_json::to_string(&req).unwrap();
+        assert!(!json.contains("\"id\""), "Notification should not have id");
+    }
 
-        if !expired.is_empty() {
-            let mut jobs = self.jobs.write().await;
-            for id in &expired {
-                jobs.remove(id);
+    #[test]
+    fn test_jsonrpc_error_response_parsing() {
+        let json = r#"{
+            "jsonrpc": "2.0",
+            "id": 5,
+            "error": {
+                "code": -32601,
+                "message": "Method not found"
             }
-            debug!("ACP job cleanup: removed {} expired job(s)", expired.len());
-        }
+        }"#;
+        let msg: JsonRpcMessage = serde_json::from_str(json).unwrap();
+        assert!(msg.is_response());
+        assert!(msg.error.is_some());
+        let err = msg.error.unwrap();
+        assert_eq!(err.code, -32601);
+        assert_eq!(err.message, "Method not found");
     }
 
-    /// Reap sessions that have been idle (no prompt activity) longer than
-    /// `idle_timeout_secs`. Sessions in `Prompting` state are skipped — they
-    /// have their own per-prompt timeout. Returns the number of reaped sessions.
-    pub async fn reap_idle_sessions(&self) -> usize {
-        // Also clean up expired jobs while we're here
-        self.cleanup_expired_jobs().await;
-
-        let idle_timeout = Duration::from_secs(self.config.idle_timeout_secs);
-        if idle_timeout.is_zero() {
-            return 0;
-        }
+    // -----------------------------------------------------------------------
+    // Phase 0: Process pool limit tests
+    // -----------------------------------------------------------------------
 
-        // Collect session IDs that exceed the idle threshold.
-        // We only need a read lock to scan; end_session takes its own write lock.
-        let mut to_reap: Vec<(String, String)> = Vec::new(); // (session_id, agent_id)
-        {
-            let sessions = self.sessions.read().await;
-            for (id, session_mutex) in sessions.iter() {
-                let session = session_mutex.lock().await;
-                if session.status == SessionStatus::Prompting {
-                    continue; // active work — skip
-                }
-                if session.last_activity.elapsed() >= idle_timeout {
-                    to_reap.push((id.clone(), session.agent_id.clone()));
-                }
-            }
-        }
+    #[test]
+    fn test_config_pool_defaults() {
+        let config = AcpConfig::default();
+        assert_eq!(config.max_sessions, 20);
+        assert_eq!(config.max_per_agent, 10);
+    }
 
-        let count = to_reap.len();
-        for (session_id, agent_id) in &to_reap {
-            warn!(
-                "ACP idle reaper: ending session {session_id} (agent={agent_id}, idle > {}s)",
-                self.config.idle_timeout_secs
-            );
-            if let Err(e) = self.end_session(session_id).await {
-                warn!("ACP idle reaper: failed to end session {session_id}: {e}");
+    #[test]
+    fn test_config_pool_parse() {
+        let json = r#"{
+            "maxSessions": 5,
+            "maxPerAgent": 2,
+            "acpAgents": {
+                "claude": { "command": "claude" }
             }
-        }
+        }"#;
+        let config: AcpConfig = serde_json::from_str(json).unwrap();
+        assert_eq!(config.max_sessions, 5);
+        assert_eq!(config.max_per_agent, 2);
+    }
 
-        if count > 0 {
-            info!("ACP idle reaper: reaped {count} session(s)");
-        }
-        count
+    #[test]
+    fn test_config_pool_parse_snake_case() {
+        let json = r#"{
+            "max_sessions": 8,
+            "max_per_agent": 3
+        }"#;
+        let config: AcpConfig = serde_json::from_str(json).unwrap();
+        assert_eq!(config.max_sessions, 8);
+        assert_eq!(config.max_per_agent, 3);
     }
 
-    /// Cleanup all sessions (called on process shutdown).
-    pub async fn cleanup(&self) {
-        let session_ids: Vec<String> = {
-            let sessions = self.sessions.read().await;
-            sessions.keys().cloned().collect()
+    #[tokio::test]
+    async fn test_pool_total_limit_enforced() {
+        let config = AcpConfig {
+            max_sessions: 0, // zero capacity — any new_session should fail
+            max_per_agent: 10,
+            ..AcpConfig::default()
         };
+        let manager = AcpManager::from_config(config);
 
-        for id in &session_ids {
-            if let Err(e) = self.end_session(id).await {
-                warn!("ACP cleanup: failed to end session {id}: {e}");
-            }
-        }
-
-        if !session_ids.is_empty() {
-            info!(
-                "ACP manager cleanup: terminated {} session(s)",
-                session_ids.len()
-            );
-        }
+        // Total limit check fires before agent config lookup,
+        // so even a nonexistent agent triggers the pool error first.
+        let result = manager.new_session("claude", None, None).await;
+        assert!(result.is_err());
+        let err = result.unwrap_err();
+        assert!(
+            err.contains("session limit reached"),
+            "Expected pool limit error, got: {err}"
+        );
     }
-}
 
-/// Spawn a background task that periodically reaps idle ACP sessions.
-/// The task runs every 60 seconds and terminates sessions that have been
-/// idle longer than `idle_timeout_secs`. Does nothing if the timeout is 0.
-pub fn spawn_idle_reaper(manager: Arc<AcpManager>) {
-    if manager.config.idle_timeout_secs == 0 {
-        info!("ACP idle reaper disabled (idle_timeout_secs=0)");
-        return;
-    }
-    info!(
-        "ACP idle reaper started (checking every 60s, timeout={}s)",
-        manager.config.idle_timeout_secs
-    );
-    tokio::spawn(async move {
-        let mut interval = tokio::time::interval(Duration::from_secs(60));
-        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
-        loop {
-            interval.tick().await;
-            manager.reap_idle_sessions().await;
-        }
-    });
-}
+    #[tokio::test]
+    async fn test_pool_per_agent_limit_enforced() {
+        let config = AcpConfig {
+            max_sessions: 100,
+            max_per_agent: 1,
+            agents: HashMap::from([(
+                "claude".to_string(),
+                AcpAgentConfig {
+                    launch: "binary".to_string(),
+                    command: "/nonexistent/bin".to_string(),
+                    args: vec![],
+                    env: HashMap::new(),
+                    workspace: None,
+                    auto_approve: None,
+                    mode: default_mode(),
+                    resource_limits: None,
+                    policy: None,
+                    model: None,
+                    url: None,
+                    headers: HashMap::new(),
+                },
+            )]),
+            ..AcpConfig::default()
+        };
+        let manager = AcpManager::from_config(config);
 
-/// Summary of an active session (for listing)
-#[derive(Debug, Clone)]
-pub struct SessionSummary {
-    pub session_id: String,
-    pub agent_id: String,
-    pub workspace: String,
-    pub status: SessionStatus,
-    pub created_at: String,
-    /// Seconds since last prompt activity
-    pub idle_secs: u64,
-}
+        // Simulate 1 existing session for "claude"
+        manager
+            .agent_session_counts
+            .write()
+            .await
+            .insert("claude".to_string(), 1);
 
-// ---------------------------------------------------------------------------
-// Tests
-// ---------------------------------------------------------------------------
+        // Now new_session for "claude" should be rejected
+        let result = manager.new_session("claude", None, None).await;
+        assert!(result.is_err());
+        let err = result.unwrap_err();
+        assert!(
+            err.contains("per-agent limit reached"),
+            "Expected per-agent limit error, got: {err}"
+        );
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    // -----------------------------------------------------------------------
+    // Phase 1: Idle timeout / reaper tests
+    // -----------------------------------------------------------------------
 
     #[test]
-    fn test_empty_config_defaults() {
+    fn test_config_idle_timeout_default() {
         let config = AcpConfig::default();
-        assert!(!config.default_auto_approve);
-        assert_eq!(config.prompt_timeout_secs, 300);
-        assert!(config.agents.is_empty());
+        assert_eq!(config.idle_timeout_secs, 600);
     }
 
-    #[test]
-    fn test_config_parse_full() {
-        let json = r#"{
-            "defaultAutoApprove": true,
-            "promptTimeoutSecs": 600,
-            "acpAgents": {
-                "claude": {
-                    "launch": "npx",
-                    "command": "@anthropic-ai/claude-code@latest",
-                    "args": ["--acp"],
-                    "env": {"ANTHROPIC_API_KEY": "sk-test"},
-                    "workspace": "/tmp/test",
-                    "auto_approve": true
-                }
-            }
-        }"#;
-
+    #[test]
+    fn test_config_idle_timeout_parse_camel() {
+        let json = r#"{ "idleTimeoutSecs": 120 }"#;
         let config: AcpConfig = serde_json::from_str(json).unwrap();
-        assert!(config.default_auto_approve);
-        assert_eq!(config.prompt_timeout_secs, 600);
-        assert_eq!(config.agents.len(), 1);
-
-        let claude = config.agents.get("claude").unwrap();
-        assert_eq!(claude.launch, "npx");
-        assert_eq!(claude.command, "@anthropic-ai/claude-code@latest");
-        assert_eq!(claude.args, vec!["--acp"]);
-        assert_eq!(claude.workspace.as_deref(), Some("/tmp/test"));
-        assert_eq!(claude.auto_approve, Some(true));
+        assert_eq!(config.idle_timeout_secs, 120);
     }
 
     #[test]
-    fn test_config_parse_minimal() {
-        let json = r#"{
-            "acpAgents": {
-                "claude": {
-                    "command": "@anthropic-ai/claude-code@latest"
-                }
-            }
-        }"#;
-
+    fn test_config_idle_timeout_parse_snake() {
+        let json = r#"{ "idle_timeout_secs": 0 }"#;
         let config: AcpConfig = serde_json::from_str(json).unwrap();
-        assert!(!config.default_auto_approve);
-        assert_eq!(config.prompt_timeout_secs, 300);
+        assert_eq!(config.idle_timeout_secs, 0);
+    }
 
-        let claude = config.agents.get("claude").unwrap();
-        assert_eq!(claude.launch, "npx");
-        assert!(claude.args.is_empty());
-        assert!(claude.env.is_empty());
-        assert!(claude.workspace.is_none());
-        assert!(claude.auto_approve.is_none());
+    #[tokio::test]
+    async fn test_reap_idle_sessions_disabled_when_zero() {
+        let config = AcpConfig {
+            idle_timeout_secs: 0,
+            ..AcpConfig::default()
+        };
+        let manager = AcpManager::from_config(config);
+        let reaped = manager.reap_idle_sessions().await;
+        assert_eq!(reaped, 0);
     }
 
     #[test]
-    fn test_config_parse_snake_case_aliases() {
-        let json = r#"{
-            "default_auto_approve": true,
-            "prompt_timeout_secs": 120,
-            "agents": {
-                "claude": {
-                    "command": "@anthropic-ai/claude-code@latest"
-                }
-            }
-        }"#;
-
+    fn test_config_idle_timeout_parse_ttl_alias() {
+        let json = r#"{ "sessionIdleTtlSecs": 90 }"#;
         let config: AcpConfig = serde_json::from_str(json).unwrap();
-        assert!(config.default_auto_approve);
-        assert_eq!(config.prompt_timeout_secs, 120);
-        assert_eq!(config.agents.len(), 1);
+        assert_eq!(config.idle_timeout_secs, 90);
     }
 
-    #[test]
-    fn test_missing_file_returns_default() {
-        let config = AcpConfig::from_file("/nonexistent/acp.json");
-        assert!(config.agents.is_empty());
+    #[tokio::test]
+    async fn test_reap_idle_sessions_empty() {
+        let config = AcpConfig {
+            idle_timeout_secs: 1,
+            ..AcpConfig::default()
+        };
+        let manager = AcpManager::from_config(config);
+        let reaped = manager.reap_idle_sessions().await;
+        assert_eq!(reaped, 0);
     }
 
+    // -----------------------------------------------------------------------
+    // Phase 2: Crash recovery tests
+    // -----------------------------------------------------------------------
+
     #[test]
-    fn test_manager_from_config() {
-        let manager = AcpManager::from_config_file("/nonexistent/acp.json");
-        assert!(manager.available_agents().is_empty());
-        assert!(!manager.has_agent("claude"));
+    fn test_prompt_result_context_reset_default_false() {
+        let result = AcpPromptResult {
+            messages: vec![],
+            tool_calls: vec![],
+            files_changed: vec![],
+            plan: vec![],
+            thoughts: vec![],
+            usage: None,
+            completed: true,
+            duration_ms: 0,
+            context_reset: false,
+        };
+        assert!(!result.context_reset);
     }
 
     #[test]
-    fn test_build_spawn_command_npx() {
-        let config = AcpAgentConfig {
-            launch: "npx".to_string(),
-            command: "@anthropic-ai/claude-code@latest".to_string(),
-            args: vec!["--acp".to_string()],
-            env: HashMap::from([("FOO".to_string(), "bar".to_string())]),
-            workspace: Some("/tmp/ws".to_string()),
-            auto_approve: None,
-            mode: default_mode(),
-            resource_limits: None,
+    fn test_prompt_result_context_reset_true() {
+        let result = AcpPromptResult {
+            messages: vec!["recovered".to_string()],
+            tool_calls: vec![],
+            files_changed: vec![],
+            plan: vec![],
+            thoughts: vec![],
+            usage: None,
+            completed: true,
+            duration_ms: 100,
+            context_reset: true,
         };
+        assert!(result.context_reset);
+        assert_eq!(result.messages[0], "recovered");
+    }
 
-        let cmd = build_spawn_command(&config, None);
-        let prog = cmd.as_std().get_program();
-        assert_eq!(prog, "npx");
+    #[tokio::test]
+    async fn test_recover_session_agent_not_configured() {
+        // If agent config was removed after session creation, recovery should
+        // fail with a clear error.
+        let config = AcpConfig::default(); // no agents configured
+        let manager = AcpManager::from_config(config);
 
-        let args: Vec<&std::ffi::OsStr> = cmd.as_std().get_args().collect();
-        assert_eq!(
-            args,
-            vec!["-y", "@anthropic-ai/claude-code@latest", "--acp"]
-        );
+        // Build a minimal session struct to pass to recover_session.
+        // We can't build a real AcpConnection without a process, but
+        // recover_session only reads session fields before spawning.
+        // Since we can't construct AcpSession without AcpConnection,
+        // we test via prompt() on a nonexistent session instead.
+        let result = manager.prompt("nonexistent", "hello", None, None).await;
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("not found"));
     }
 
-    #[test]
-    fn test_build_spawn_command_binary() {
+    #[tokio::test]
+    async fn test_is_alive_after_spawn_and_kill() {
+        // Spawn a real process (sleep) and verify is_alive / kill behavior.
         let config = AcpAgentConfig {
             launch: "binary".to_string(),
-            command: "/usr/bin/opencode".to_string(),
-            args: vec!["acp".to_string()],
+            command: "sleep".to_string(),
+            args: vec!["60".to_string()],
             env: HashMap::new(),
             workspace: None,
             auto_approve: None,
             mode: default_mode(),
             resource_limits: None,
+            policy: None,
+            model: None,
+            url: None,
+            headers: HashMap::new(),
         };
 
-        let cmd = build_spawn_command(&config, Some("/home/user/project"));
-        let prog = cmd.as_std().get_program();
-        assert_eq!(prog, "/usr/bin/opencode");
+        let mut cmd = build_spawn_command(&config, Some("/tmp"));
+        let child = cmd.spawn();
+        if child.is_err() {
+            // Skip test if 'sleep' is not available
+            return;
+        }
+        let conn =
+            AcpConnection::from_child("test", child.unwrap(), Duration::from_secs(5)).unwrap();
 
-        let args: Vec<&std::ffi::OsStr> = cmd.as_std().get_args().collect();
-        assert_eq!(args, vec!["acp"]);
+        // Process should be alive
+        assert!(conn.is_alive().await);
 
-        assert_eq!(
-            cmd.as_std().get_current_dir(),
-            Some(std::path::Path::new("/home/user/project"))
-        );
+        // Kill it
+        {
+            let mut child = conn.child.lock().await;
+            let child = child.as_mut().unwrap();
+            let _ = child.kill().await;
+            let _ = child.wait().await;
+        }
+
+        // Process should be dead
+        assert!(!conn.is_alive().await);
     }
 
-    #[test]
-    fn test_build_spawn_command_workspace_override() {
-        let config = AcpAgentConfig {
-            launch: "npx".to_string(),
-            command: "agent".to_string(),
-            args: vec![],
+    fn sh_agent(script: &str) -> AcpAgentConfig {
+        AcpAgentConfig {
+            launch: "binary".to_string(),
+            command: "sh".to_string(),
+            args: vec!["-c".to_string(), script.to_string()],
             env: HashMap::new(),
-            workspace: Some("/default/ws".to_string()),
+            workspace: None,
             auto_approve: None,
             mode: default_mode(),
             resource_limits: None,
-        };
-
-        // Explicit workspace overrides config default
-        let cmd = build_spawn_command(&config, Some("/override/ws"));
-        assert_eq!(
-            cmd.as_std().get_current_dir(),
-            Some(std::path::Path::new("/override/ws"))
-        );
-
-        // Falls back to config default
-        let cmd2 = build_spawn_command(&config, None);
-        assert_eq!(
-            cmd2.as_std().get_current_dir(),
-            Some(std::path::Path::new("/default/ws"))
-        );
-    }
-
-    #[test]
-    fn test_jsonrpc_message_classification() {
-        // Response
-        let resp: JsonRpcMessage =
-            serde_json::from_str(r#"{"jsonrpc":"2.0","id":1,"result":{"ok":true}}"#).unwrap();
-        assert!(resp.is_response());
-        assert!(!resp.is_notification());
-
-        // Error response
-        let err: JsonRpcMessage = serde_json::from_str(
-            r#"{"jsonrpc":"2.0","id":2,"error":{"code":-1,"message":"fail"}}"#,
-        )
-        .unwrap();
-        assert!(err.is_response());
-        assert!(!err.is_notification());
-
-        // Notification
-        let notif: JsonRpcMessage = serde_json::from_str(
-            r#"{"jsonrpc":"2.0","method":"messages/create","params":{"text":"hi"}}"#,
-        )
-        .unwrap();
-        assert!(!notif.is_response());
-        assert!(notif.is_notification());
-    }
-
-    #[test]
-    fn test_prompt_result_default() {
-        let result = AcpPromptResult {
-            messages: vec!["hello".to_string()],
-            tool_calls: vec![ToolCallInfo {
-                name: "bash".to_string(),
-                input: serde_json::json!({"command": "ls"}),
-            }],
-            files_changed: vec!["foo.rs".to_string()],
-            completed: true,
-            duration_ms: 1234,
-            context_reset: false,
-        };
-
-        assert_eq!(result.messages.len(), 1);
-        assert_eq!(result.tool_calls.len(), 1);
-        assert_eq!(result.tool_calls[0].name, "bash");
-        assert!(result.completed);
-        assert_eq!(result.duration_ms, 1234);
-    }
-
-    #[tokio::test]
-    async fn test_manager_new_session_unknown_agent() {
-        let manager = AcpManager::from_config_file("/nonexistent/acp.json");
-        let result = manager.new_session("nonexistent", None, None).await;
-        assert!(result.is_err());
-        assert!(result.unwrap_err().contains("not configured"));
+            policy: None,
+            model: None,
+            url: None,
+            headers: HashMap::new(),
+        }
     }
 
-    #[tokio::test]
-    async fn test_manager_list_sessions_empty() {
-        let manager = AcpManager::from_config_file("/nonexistent/acp.json");
-        let sessions = manager.list_sessions().await;
-        assert!(sessions.is_empty());
+    /// A `remote` agent config pointing at `url`.
+    fn remote_agent(url: String) -> AcpAgentConfig {
+        AcpAgentConfig {
+            launch: "remote".to_string(),
+            url: Some(url),
+            ..sh_agent("")
+        }
     }
 
-    #[tokio::test]
-    async fn test_manager_end_session_not_found() {
-        let manager = AcpManager::from_config_file("/nonexistent/acp.json");
-        let result = manager.end_session("nonexistent").await;
-        assert!(result.is_err());
-        assert!(result.unwrap_err().contains("not found"));
+    fn initialize_reply(line: &str) -> String {
+        let id = serde_json::from_str::<serde_json::Value>(line).unwrap()["id"].clone();
+        serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "result": {"protocolVersion": 1, "agentInfo": {"name": "remote-fake"}}
+        })
+        .to_string()
     }
 
     #[tokio::test]
-    async fn test_manager_prompt_not_found() {
-        let manager = AcpManager::from_config_file("/nonexistent/acp.json");
-        let result = manager.prompt("nonexistent", "hello", None, None).await;
-        assert!(result.is_err());
-        assert!(result.unwrap_err().contains("not found"));
+    async fn test_remote_tcp_agent() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (read, mut write) = stream.into_split();
+            let mut lines = BufReader::new(read).lines();
+            let init = lines.next_line().await.unwrap().unwrap();
+            let reply = initialize_reply(&init) + "\n";
+            write.write_all(reply.as_bytes()).await.unwrap();
+            // Keep the connection open until the client goes away
+            while let Ok(Some(_)) = lines.next_line().await {}
+        });
+
+        let timeout = Duration::from_secs(5);
+        let config = remote_agent(format!("tcp://{addr}"));
+        let conn = AcpConnection::try_spawn("remote", &config, None, timeout, timeout)
+            .await
+            .unwrap();
+        assert!(conn.is_alive().await);
+        assert_eq!(conn.pid().await, None);
+        conn.kill().await;
     }
 
-    // -----------------------------------------------------------------------
-    // Phase 7.1: Additional config parsing tests
-    // -----------------------------------------------------------------------
+    #[tokio::test]
+    async fn test_remote_websocket_agent() {
+        use futures_util::{SinkExt, StreamExt};
+        use tokio_tungstenite::tungstenite::Message as WsMessage;
 
-    #[test]
-    fn test_config_parse_multiple_agents() {
-        let json = r#"{
-            "acpAgents": {
-                "claude": {
-                    "command": "@anthropic-ai/claude-code@latest",
-                    "workspace": "/tmp/claude"
-                },
-                "opencode": {
-                    "launch": "binary",
-                    "command": "/usr/bin/opencode",
-                    "args": ["acp"]
-                },
-                "gemini": {
-                    "launch": "npx",
-                    "command": "@google/gemini-cli@latest",
-                    "args": ["--experimental-acp"],
-                    "env": {"GEMINI_API_KEY": "test-key"}
-                }
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+            if let Some(Ok(WsMessage::Text(init))) = ws.next().await {
+                let reply = initialize_reply(&init);
+                ws.send(WsMessage::Text(reply)).await.unwrap();
             }
-        }"#;
-
-        let config: AcpConfig = serde_json::from_str(json).unwrap();
-        assert_eq!(config.agents.len(), 3);
+            // Close after the handshake: the client should notice
+            let _ = ws.close(None).await;
+        });
 
-        let claude = config.agents.get("claude").unwrap();
-        assert_eq!(claude.launch, "npx"); // default
-        assert_eq!(claude.workspace.as_deref(), Some("/tmp/claude"));
+        let timeout = Duration::from_secs(5);
+        let config = remote_agent(format!("ws://{addr}"));
+        let conn = AcpConnection::try_spawn("remote", &config, None, timeout, timeout)
+            .await
+            .unwrap();
+        for _ in 0..50 {
+            if !conn.is_alive().await {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(!conn.is_alive().await);
+    }
 
-        let opencode = config.agents.get("opencode").unwrap();
-        assert_eq!(opencode.launch, "binary");
-        assert_eq!(opencode.command, "/usr/bin/opencode");
-        assert_eq!(opencode.args, vec!["acp"]);
-        assert!(opencode.workspace.is_none());
+    #[tokio::test]
+    async fn test_remote_agent_unreachable() {
+        // Grab a free port, then close it so nothing listens there
+        let addr = {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            listener.local_addr().unwrap()
+        };
+        let short = Duration::from_millis(500);
+        let err = AcpConnection::try_spawn(
+            "remote",
+            &remote_agent(format!("tcp://{addr}")),
+            None,
+            short,
+            short,
+        )
+        .await
+        .err()
+        .unwrap();
+        assert!(matches!(err, AcpSpawnError::Unreachable { .. }), "{err}");
+        assert!(err.is_retryable());
 
-        let gemini = config.agents.get("gemini").unwrap();
-        assert_eq!(gemini.launch, "npx");
-        assert_eq!(gemini.env.get("GEMINI_API_KEY").unwrap(), "test-key");
+        let mut no_url = remote_agent(String::new());
+        no_url.url = None;
+        let err = AcpConnection::try_spawn("remote", &no_url, None, short, short)
+            .await
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("requires a url"), "{err}");
+        assert!(!err.is_retryable());
     }
 
     #[test]
-    fn test_config_parse_invalid_json_returns_default() {
-        // AcpConfig::from_file should return defaults on parse failure.
-        // We can't easily test from_file with bad content without a temp file,
-        // but we can verify serde_json rejects garbage.
-        let result: Result<AcpConfig, _> = serde_json::from_str("NOT JSON");
-        assert!(result.is_err());
+    fn test_spawn_backoff_doubles() {
+        let base = Duration::from_millis(100);
+        assert_eq!(spawn_backoff(base, 1), Duration::from_millis(100));
+        assert_eq!(spawn_backoff(base, 2), Duration::from_millis(200));
+        assert_eq!(spawn_backoff(base, 4), Duration::from_millis(800));
     }
 
-    #[test]
-    fn test_config_parse_empty_object() {
-        let config: AcpConfig = serde_json::from_str("{}").unwrap();
-        assert!(!config.default_auto_approve);
-        assert_eq!(config.prompt_timeout_secs, 300);
-        assert!(config.agents.is_empty());
-    }
+    #[tokio::test]
+    async fn test_try_spawn_classifies_failures() {
+        let short = Duration::from_millis(300);
+        let mut missing = sh_agent("");
+        missing.command = "/nonexistent/rayclaw-agent".to_string();
+        let err = AcpConnection::try_spawn("missing", &missing, Some("/tmp"), short, short)
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(err, AcpSpawnError::BinaryMissing { .. }), "{err}");
+        assert!(!err.is_retryable());
 
-    #[test]
-    fn test_config_parse_agent_env_propagated() {
-        let config = AcpAgentConfig {
-            launch: "npx".to_string(),
-            command: "agent".to_string(),
-            args: vec![],
-            env: HashMap::from([
-                ("KEY1".to_string(), "val1".to_string()),
-                ("KEY2".to_string(), "val2".to_string()),
-            ]),
-            workspace: None,
-            auto_approve: None,
-            mode: default_mode(),
-            resource_limits: None,
+        let err =
+            AcpConnection::try_spawn("crash", &sh_agent("exit 3"), Some("/tmp"), short, short)
+                .await
+                .err()
+                .unwrap();
+        let AcpSpawnError::CrashedOnStart { status, .. } = &err else {
+            panic!("expected crash, got {err}");
         };
+        assert!(status.contains('3'), "{status}");
+        assert!(err.is_retryable());
 
-        let cmd = build_spawn_command(&config, None);
-        let envs: Vec<_> = cmd.as_std().get_envs().collect();
-        // Verify our env vars are set (they should be among the envs)
-        let has_key1 = envs.iter().any(|(k, v)| {
-            k == &std::ffi::OsStr::new("KEY1") && v == &Some(std::ffi::OsStr::new("val1"))
-        });
-        let has_key2 = envs.iter().any(|(k, v)| {
-            k == &std::ffi::OsStr::new("KEY2") && v == &Some(std::ffi::OsStr::new("val2"))
-        });
-        assert!(has_key1, "KEY1 env var should be set");
-        assert!(has_key2, "KEY2 env var should be set");
+        let err =
+            AcpConnection::try_spawn("hang", &sh_agent("sleep 5"), Some("/tmp"), short, short)
+                .await
+                .err()
+                .unwrap();
+        assert!(
+            matches!(err, AcpSpawnError::HandshakeTimeout { .. }),
+            "{err}"
+        );
     }
 
-    #[test]
-    fn test_build_spawn_command_uvx() {
+    #[tokio::test]
+    async fn test_spawn_connection_retries_then_gives_up() {
+        let config = AcpConfig {
+            spawn_retries: 2,
+            spawn_backoff_ms: 10,
+            ..Default::default()
+        };
+        let manager = AcpManager::from_config(config);
+        let err = manager
+            .spawn_connection("crash", &sh_agent("exit 1"), "/tmp")
+            .await
+            .err()
+            .unwrap();
+        assert!(err.contains("gave up after 3 attempts"), "{err}");
+
+        let mut missing = sh_agent("");
+        missing.command = "/nonexistent/rayclaw-agent".to_string();
+        let err = manager
+            .spawn_connection("missing", &missing, "/tmp")
+            .await
+            .err()
+            .unwrap();
+        assert!(err.contains("not found"), "{err}");
+        assert!(!err.contains("gave up"), "{err}");
+    }
+
+    /// Spawn a shell-scripted fake agent wrapped in an `AcpConnection`.
+    fn scripted_connection(script: &str) -> Option<AcpConnection> {
         let config = AcpAgentConfig {
-            launch: "uvx".to_string(),
-            command: "some-agent".to_string(),
-            args: vec!["--flag".to_string()],
+            launch: "binary".to_string(),
+            command: "sh".to_string(),
+            args: vec!["-c".to_string(), script.to_string()],
             env: HashMap::new(),
             workspace: None,
             auto_approve: None,
             mode: default_mode(),
             resource_limits: None,
+            policy: None,
+            model: None,
+            url: None,
+            headers: HashMap::new(),
         };
-
-        let cmd = build_spawn_command(&config, None);
-        let prog = cmd.as_std().get_program();
-        assert_eq!(prog, "uvx");
-
-        let args: Vec<&std::ffi::OsStr> = cmd.as_std().get_args().collect();
-        assert_eq!(args, vec!["some-agent", "--flag"]);
-    }
-
-    #[test]
-    fn test_manager_from_config_direct() {
-        let config = AcpConfig {
-            default_auto_approve: true,
-            prompt_timeout_secs: 60,
-            agents: HashMap::from([(
-                "test".to_string(),
-                AcpAgentConfig {
-                    launch: "binary".to_string(),
-                    command: "/usr/bin/test".to_string(),
-                    args: vec![],
-                    env: HashMap::new(),
-                    workspace: None,
-                    auto_approve: None,
-                    mode: default_mode(),
-                    resource_limits: None,
-                },
-            )]),
-            ..AcpConfig::default()
-        };
-
-        let manager = AcpManager::from_config(config);
-        assert!(manager.has_agent("test"));
-        assert!(!manager.has_agent("other"));
-        assert_eq!(manager.available_agents(), vec!["test"]);
-        assert!(manager.config.default_auto_approve);
-        assert_eq!(manager.config.prompt_timeout_secs, 60);
+        let child = build_spawn_command(&config, Some("/tmp")).spawn().ok()?;
+        AcpConnection::from_child("scripted", child, Duration::from_secs(5)).ok()
     }
 
-    #[test]
-    fn test_agent_config_method() {
-        let config = AcpConfig {
-            default_auto_approve: false,
-            prompt_timeout_secs: 300,
-            agents: HashMap::from([(
-                "claude".to_string(),
-                AcpAgentConfig {
-                    launch: "npx".to_string(),
-                    command: "@anthropic-ai/claude-code@latest".to_string(),
-                    args: vec!["--acp".to_string()],
-                    env: HashMap::new(),
-                    workspace: Some("/tmp/ws".to_string()),
-                    auto_approve: Some(true),
-                    mode: default_mode(),
-                    resource_limits: None,
-                },
-            )]),
-            ..AcpConfig::default()
+    #[tokio::test]
+    async fn test_concurrent_requests_routed_by_id() {
+        // Reads two requests, emits a notification, then answers them in
+        // reverse order.
+        let script = r#"
+            read a; read b
+            ida=$(echo "$a" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+            idb=$(echo "$b" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+            echo '{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s1"}}'
+            echo "{\"jsonrpc\":\"2.0\",\"id\":$idb,\"result\":{\"id\":$idb}}"
+            echo "{\"jsonrpc\":\"2.0\",\"id\":$ida,\"result\":{\"id\":$ida}}"
+            sleep 5
+        "#;
+        let Some(conn) = scripted_connection(script) else {
+            return; // sh not available
         };
+        let mut notifications = conn.subscribe();
 
-        let manager = AcpManager::from_config(config);
-        let agent_cfg = manager.agent_config("claude");
-        assert!(agent_cfg.is_some());
-        let cfg = agent_cfg.unwrap();
-        assert_eq!(cfg.command, "@anthropic-ai/claude-code@latest");
-        assert_eq!(cfg.workspace.as_deref(), Some("/tmp/ws"));
-        assert_eq!(cfg.auto_approve, Some(true));
+        let (a, b) = tokio::join!(
+            conn.send_request("first", None),
+            conn.send_request("second", None)
+        );
+        // Each caller gets its own response despite the reversed order
+        assert_eq!(a.unwrap()["id"], 1);
+        assert_eq!(b.unwrap()["id"], 2);
 
-        assert!(manager.agent_config("nonexistent").is_none());
-    }
+        let note = notifications.recv().await.unwrap();
+        assert_eq!(note.method, "session/update");
+        assert_eq!(note.session_id(), Some("s1"));
+        assert!(note.id.is_none());
 
-    #[test]
-    fn test_session_status_equality() {
-        assert_eq!(SessionStatus::Active, SessionStatus::Active);
-        assert_eq!(SessionStatus::Prompting, SessionStatus::Prompting);
-        assert_eq!(SessionStatus::Ended, SessionStatus::Ended);
-        assert_ne!(SessionStatus::Active, SessionStatus::Ended);
-        assert_ne!(SessionStatus::Active, SessionStatus::Prompting);
+        conn.kill().await;
     }
 
-    #[test]
-    fn test_jsonrpc_request_serialization() {
-        let req = JsonRpcRequest {
-            jsonrpc: "2.0".to_string(),
-            id: Some(42),
-            method: "test/method".to_string(),
-            params: Some(serde_json::json!({"key": "value"})),
+    #[tokio::test]
+    async fn test_prompt_cancelled_by_session_cancel() {
+        // Streams one chunk, then ends the prompt only once session/cancel arrives.
+        let script = r#"
+            read p
+            id=$(echo "$p" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+            echo '{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s1","update":{"sessionUpdate":"agent_message_chunk","content":{"type":"text","text":"partial"}}}}'
+            read c
+            case "$c" in *session/cancel*)
+                echo "{\"jsonrpc\":\"2.0\",\"id\":$id,\"result\":{\"stopReason\":\"cancelled\"}}";;
+            esac
+            sleep 5
+        "#;
+        let Some(conn) = scripted_connection(script) else {
+            return;
         };
-        let json = serde_json::to_string(&req).unwrap();
-        assert!(json.contains("\"jsonrpc\":\"2.0\""));
-        assert!(json.contains("\"id\":42"));
-        assert!(json.contains("\"method\":\"test/method\""));
-        assert!(json.contains("\"key\":\"value\""));
+        let params = serde_json::json!({"sessionId": "s1", "prompt": []});
+        let (result, sent) = tokio::join!(
+            conn.prompt_streaming(params, true, Duration::from_secs(5), None, None),
+            async {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                conn.send_notification(
+                    "session/cancel",
+                    Some(serde_json::json!({"sessionId": "s1"})),
+                )
+                .await
+            }
+        );
+        sent.unwrap();
+        let result = result.unwrap();
+        assert!(!result.completed);
+        assert_eq!(result.messages, vec!["partial".to_string()]);
+
+        conn.kill().await;
     }
 
-    #[test]
-    fn test_jsonrpc_notification_serialization() {
-        let req = JsonRpcRequest {
-            jsonrpc: "2.0".to_string(),
-            id: None,
-            method: "notifications/initialized".to_string(),
-            params: None,
+    #[tokio::test]
+    async fn test_prompt_streams_message_and_plan_events() {
+        let script = r#"
+            read p
+            id=$(echo "$p" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+            echo '{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s1","update":{"sessionUpdate":"plan","entries":[{"content":"Read code","status":"completed","priority":"high"},{"content":"Fix bug","status":"in_progress","priority":"high"}]}}}'
+            echo '{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s1","update":{"sessionUpdate":"agent_message_chunk","content":{"type":"text","text":"Hel"}}}}'
+            echo '{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s1","update":{"sessionUpdate":"agent_message_chunk","content":{"type":"text","text":"lo"}}}}'
+            echo "{\"jsonrpc\":\"2.0\",\"id\":$id,\"result\":{\"stopReason\":\"end_turn\"}}"
+            sleep 5
+        "#;
+        let Some(conn) = scripted_connection(script) else {
+            return;
         };
-        let json = serde_json::to_string(&req).unwrap();
-        assert!(!json.contains("\"id\""), "Notification should not have id");
-    }
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let params = serde_json::json!({"sessionId": "s1", "prompt": []});
+        let result = conn
+            .prompt_streaming(params, true, Duration::from_secs(5), Some(&tx), None)
+            .await
+            .unwrap();
+        drop(tx);
+        assert_eq!(result.messages, vec!["Hello".to_string()]);
+        assert_eq!(result.plan.len(), 2);
+        assert_eq!(result.plan[0].status, "completed");
 
-    #[test]
-    fn test_jsonrpc_error_response_parsing() {
-        let json = r#"{
-            "jsonrpc": "2.0",
-            "id": 5,
-            "error": {
-                "code": -32601,
-                "message": "Method not found"
+        let mut events = vec![];
+        while let Some(e) = rx.recv().await {
+            events.push(e);
+        }
+        match &events[0] {
+            AcpProgressEvent::Plan { entries } => {
+                assert_eq!(entries.len(), 2);
+                assert_eq!(entries[1].content, "Fix bug");
+                assert_eq!(entries[1].status, "in_progress");
             }
-        }"#;
-        let msg: JsonRpcMessage = serde_json::from_str(json).unwrap();
-        assert!(msg.is_response());
-        assert!(msg.error.is_some());
-        let err = msg.error.unwrap();
-        assert_eq!(err.code, -32601);
-        assert_eq!(err.message, "Method not found");
+            other => panic!("expected plan, got {other:?}"),
+        }
+        // Chunks that arrive together may be coalesced into one event
+        let streamed: String = events[1..]
+            .iter()
+            .map(|e| match e {
+                AcpProgressEvent::MessageChunk { text } => text.as_str(),
+                other => panic!("expected message chunk, got {other:?}"),
+            })
+            .collect();
+        assert_eq!(streamed, "Hello");
+
+        conn.kill().await;
+    }
+
+    #[tokio::test]
+    async fn test_prompt_collects_usage() {
+        // A usage_update arrives first; the response's own usage supersedes it
+        let script = r#"
+            read p
+            id=$(echo "$p" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+            echo '{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s1","update":{"sessionUpdate":"usage_update","inputTokens":10,"outputTokens":1}}}'
+            echo "{\"jsonrpc\":\"2.0\",\"id\":$id,\"result\":{\"stopReason\":\"end_turn\",\"usage\":{\"inputTokens\":1200,\"outputTokens\":300,\"cachedReadTokens\":800}}}"
+            sleep 5
+        "#;
+        let Some(conn) = scripted_connection(script) else {
+            return;
+        };
+        let params = serde_json::json!({"sessionId": "s1", "prompt": []});
+        let result = conn
+            .prompt_streaming(params, true, Duration::from_secs(5), None, None)
+            .await
+            .unwrap();
+        let usage = result.usage.unwrap();
+        assert_eq!(usage.input_tokens, 1200);
+        assert_eq!(usage.output_tokens, 300);
+        assert_eq!(usage.cached_read_tokens, 800);
+        assert_eq!(usage.cost_usd, None);
+
+        conn.kill().await;
     }
 
-    // -----------------------------------------------------------------------
-    // Phase 0: Process pool limit tests
-    // -----------------------------------------------------------------------
+    #[test]
+    fn test_usage_parse_add_and_price() {
+        assert_eq!(AcpUsage::from_value(&serde_json::json!({"used": 5})), None);
+        let reported = AcpUsage::from_value(&serde_json::json!({
+            "input_tokens": 100,
+            "output_tokens": 50,
+            "cost": {"amount": 0.25, "currency": "USD"}
+        }))
+        .unwrap();
+        assert_eq!(reported.cost_usd, Some(0.25));
+        let eur = AcpUsage::from_value(&serde_json::json!({
+            "inputTokens": 1,
+            "cost": {"amount": 0.25, "currency": "EUR"}
+        }))
+        .unwrap();
+        assert_eq!(eur.cost_usd, None);
+
+        let mut config = AcpConfig::default();
+        config.agents.insert(
+            "claude".into(),
+            serde_json::from_value(serde_json::json!({
+                "command": "claude-code-acp",
+                "model": "claude-sonnet-4-5"
+            }))
+            .unwrap(),
+        );
+        let manager = AcpManager::from_config(config);
+        let mut usage = AcpUsage {
+            input_tokens: 1_000_000,
+            output_tokens: 100_000,
+            ..Default::default()
+        };
+        manager.price_usage("claude", &mut usage);
+        assert_eq!(usage.cost_usd, None);
+
+        manager.set_model_prices(vec![crate::config::ModelPrice {
+            model: "claude-sonnet-4-5".into(),
+            input_per_million_usd: 3.0,
+            output_per_million_usd: 15.0,
+            cache_read_per_million_usd: None,
+            cache_write_per_million_usd: None,
+        }]);
+        manager.price_usage("claude", &mut usage);
+        assert_eq!(usage.cost_usd, Some(4.5));
+        // An agent-reported cost is kept as is
+        let mut total = reported.clone();
+        manager.price_usage("claude", &mut total);
+        assert_eq!(total.cost_usd, Some(0.25));
+
+        total.add(&usage);
+        assert_eq!(total.input_tokens, 1_000_100);
+        assert_eq!(total.cost_usd, Some(4.75));
+        assert_eq!(total.to_string(), "1000100 in / 100050 out tokens, $4.7500");
+    }
 
     #[test]
-    fn test_config_pool_defaults() {
-        let config = AcpConfig::default();
-        assert_eq!(config.max_sessions, 20);
-        assert_eq!(config.max_per_agent, 10);
+    fn test_parse_frame_single_and_batch() {
+        let single =
+            parse_frame(r#"{"jsonrpc":"2.0","method":"session/update","params":{}}"#).unwrap();
+        assert_eq!(single.len(), 1);
+        assert!(single[0].is_notification());
+
+        let batch = parse_frame(
+            r#"[{"jsonrpc":"2.0","method":"session/update","params":{}},{"jsonrpc":"2.0","id":3,"result":{}}]"#,
+        )
+        .unwrap();
+        assert_eq!(batch.len(), 2);
+        assert!(batch[1].is_response());
+
+        assert!(parse_frame("not json").is_none());
+    }
+
+    fn chunk_message(session: &str, text: &str) -> AcpAgentMessage {
+        AcpAgentMessage {
+            id: None,
+            method: "session/update".to_string(),
+            params: Some(serde_json::json!({
+                "sessionId": session,
+                "update": {"sessionUpdate": "agent_message_chunk", "content": {"type": "text", "text": text}}
+            })),
+        }
     }
 
     #[test]
-    fn test_config_pool_parse() {
-        let json = r#"{
-            "maxSessions": 5,
-            "maxPerAgent": 2,
-            "acpAgents": {
-                "claude": { "command": "claude" }
-            }
-        }"#;
-        let config: AcpConfig = serde_json::from_str(json).unwrap();
-        assert_eq!(config.max_sessions, 5);
-        assert_eq!(config.max_per_agent, 2);
+    fn test_requests_routed_to_their_session() {
+        let routes: RequestRoutes = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let request = |session: &str| AcpAgentMessage {
+            id: Some(serde_json::json!(1)),
+            method: "session/request_permission".into(),
+            params: Some(serde_json::json!({"sessionId": session})),
+        };
+        assert!(route_request(&routes, request("s1")).is_err());
+
+        let (tx1, mut rx1) = tokio::sync::mpsc::unbounded_channel();
+        let (tx2, mut rx2) = tokio::sync::mpsc::unbounded_channel();
+        routes.lock().unwrap().extend([
+            RequestRoute {
+                key: 1,
+                session_id: Some("s1".into()),
+                tx: tx1,
+            },
+            RequestRoute {
+                key: 2,
+                session_id: Some("s2".into()),
+                tx: tx2,
+            },
+        ]);
+        route_request(&routes, request("s2")).unwrap();
+        assert!(rx1.try_recv().is_err());
+        assert_eq!(rx2.try_recv().unwrap().session_id(), Some("s2"));
+        assert!(route_request(&routes, request("s3")).is_err());
+
+        drop(RequestRouteGuard {
+            routes: &routes,
+            key: 2,
+        });
+        assert!(route_request(&routes, request("s2")).is_err());
     }
 
     #[test]
-    fn test_config_pool_parse_snake_case() {
-        let json = r#"{
-            "max_sessions": 8,
-            "max_per_agent": 3
-        }"#;
-        let config: AcpConfig = serde_json::from_str(json).unwrap();
-        assert_eq!(config.max_sessions, 8);
-        assert_eq!(config.max_per_agent, 3);
+    fn test_merge_chunk_rules() {
+        let mut held = chunk_message("s1", "Hel");
+        assert!(merge_chunk(&mut held, &mut chunk_message("s1", "lo")));
+        assert_eq!(message_chunk_text(&mut held).unwrap(), "Hello");
+
+        assert!(!merge_chunk(&mut held, &mut chunk_message("s2", "x")));
+        let mut thought = chunk_message("s1", "hmm");
+        thought.params.as_mut().unwrap()["update"]["sessionUpdate"] =
+            serde_json::json!("agent_thought_chunk");
+        assert!(!merge_chunk(&mut held, &mut thought));
+
+        let big = "x".repeat(MAX_COALESCED_CHUNK_BYTES);
+        assert!(!merge_chunk(&mut held, &mut chunk_message("s1", &big)));
     }
 
     #[tokio::test]
-    async fn test_pool_total_limit_enforced() {
-        let config = AcpConfig {
-            max_sessions: 0, // zero capacity — any new_session should fail
-            max_per_agent: 10,
-            ..AcpConfig::default()
+    async fn test_buffered_chunks_are_coalesced() {
+        // Five chunks and a batch holding one more chunk plus the response,
+        // all written at once so they arrive in a single read.
+        let script = r#"
+            read p
+            id=$(echo "$p" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+            c() { printf '{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s1","update":{"sessionUpdate":"agent_message_chunk","content":{"type":"text","text":"%s"}}}}' "$1"; }
+            out="$(c a)
+$(c b)
+$(c c)
+$(c d)
+$(c e)
+[$(c f),{\"jsonrpc\":\"2.0\",\"id\":$id,\"result\":{\"stopReason\":\"end_turn\"}}]"
+            printf '%s\n' "$out"
+            sleep 5
+        "#;
+        let Some(conn) = scripted_connection(script) else {
+            return;
         };
-        let manager = AcpManager::from_config(config);
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let params = serde_json::json!({"sessionId": "s1", "prompt": []});
+        let result = conn
+            .prompt_streaming(params, true, Duration::from_secs(5), Some(&tx), None)
+            .await
+            .unwrap();
+        drop(tx);
 
-        // Total limit check fires before agent config lookup,
-        // so even a nonexistent agent triggers the pool error first.
-        let result = manager.new_session("claude", None, None).await;
-        assert!(result.is_err());
-        let err = result.unwrap_err();
-        assert!(
-            err.contains("session limit reached"),
-            "Expected pool limit error, got: {err}"
-        );
+        assert!(result.completed);
+        assert_eq!(result.messages, vec!["abcdef".to_string()]);
+        let mut chunk_events = 0;
+        while let Some(event) = rx.recv().await {
+            if matches!(event, AcpProgressEvent::MessageChunk { .. }) {
+                chunk_events += 1;
+            }
+        }
+        assert_eq!(chunk_events, 1);
+
+        conn.kill().await;
     }
 
     #[tokio::test]
-    async fn test_pool_per_agent_limit_enforced() {
-        let config = AcpConfig {
-            max_sessions: 100,
-            max_per_agent: 1,
-            agents: HashMap::from([(
-                "claude".to_string(),
-                AcpAgentConfig {
-                    launch: "binary".to_string(),
-                    command: "/nonexistent/bin".to_string(),
-                    args: vec![],
-                    env: HashMap::new(),
-                    workspace: None,
-                    auto_approve: None,
-                    mode: default_mode(),
-                    resource_limits: None,
-                },
-            )]),
-            ..AcpConfig::default()
+    async fn test_prompt_collects_thoughts_apart_from_messages() {
+        let script = r#"
+            read p
+            id=$(echo "$p" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+            u() { printf '{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s1","update":{"sessionUpdate":"%s","content":{"type":"text","text":"%s"}}}}\n' "$1" "$2"; }
+            u agent_thought_chunk "plan "
+            u agent_thought_chunk "it"
+            u agent_message_chunk "done"
+            u agent_thought_chunk "check"
+            printf '{"jsonrpc":"2.0","id":%s,"result":{"stopReason":"end_turn"}}\n' "$id"
+            sleep 5
+        "#;
+        let Some(conn) = scripted_connection(script) else {
+            return;
         };
-        let manager = AcpManager::from_config(config);
-
-        // Simulate 1 existing session for "claude"
-        manager
-            .agent_session_counts
-            .write()
+        let params = serde_json::json!({"sessionId": "s1", "prompt": []});
+        let result = conn
+            .prompt_streaming(params, true, Duration::from_secs(5), None, None)
             .await
-            .insert("claude".to_string(), 1);
+            .unwrap();
 
-        // Now new_session for "claude" should be rejected
-        let result = manager.new_session("claude", None, None).await;
-        assert!(result.is_err());
-        let err = result.unwrap_err();
-        assert!(
-            err.contains("per-agent limit reached"),
-            "Expected per-agent limit error, got: {err}"
+        assert_eq!(result.messages, vec!["done".to_string()]);
+        assert_eq!(
+            result.thoughts,
+            vec!["plan it".to_string(), "check".to_string()]
         );
+
+        conn.kill().await;
     }
 
-    // -----------------------------------------------------------------------
-    // Phase 1: Idle timeout / reaper tests
-    // -----------------------------------------------------------------------
+    fn temp_workspace() -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("rayclaw_acp_fs_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::canonicalize(&dir).unwrap()
+    }
 
     #[test]
-    fn test_config_idle_timeout_default() {
-        let config = AcpConfig::default();
-        assert_eq!(config.idle_timeout_secs, 600);
+    fn test_tool_call_changed_paths() {
+        let update = serde_json::json!({
+            "sessionUpdate": "tool_call_update",
+            "status": "completed",
+            "content": [
+                {"type": "content", "content": {"type": "text", "text": "ok"}},
+                {"type": "diff", "path": "/ws/src/main.rs", "oldText": "a", "newText": "b"}
+            ]
+        });
+        assert_eq!(tool_call_changed_paths(&update), vec!["/ws/src/main.rs"]);
+
+        let edit = serde_json::json!({
+            "title": "Write README.md",
+            "rawInput": {"file_path": "/ws/README.md", "content": "hi"}
+        });
+        assert_eq!(tool_call_changed_paths(&edit), vec!["/ws/README.md"]);
+
+        // Reads and failed edits change nothing
+        let read = serde_json::json!({
+            "kind": "read",
+            "title": "Read src/lib.rs",
+            "rawInput": {"file_path": "/ws/src/lib.rs"}
+        });
+        assert!(tool_call_changed_paths(&read).is_empty());
+        let failed = serde_json::json!({
+            "kind": "edit",
+            "status": "failed",
+            "rawInput": {"file_path": "/ws/src/lib.rs"}
+        });
+        assert!(tool_call_changed_paths(&failed).is_empty());
     }
 
-    #[test]
-    fn test_config_idle_timeout_parse_camel() {
-        let json = r#"{ "idleTimeoutSecs": 120 }"#;
-        let config: AcpConfig = serde_json::from_str(json).unwrap();
-        assert_eq!(config.idle_timeout_secs, 120);
+    #[tokio::test]
+    async fn test_prompt_collects_files_changed_from_tool_calls() {
+        let ws = temp_workspace();
+        let script = format!(
+            r#"
+            read p
+            id=$(echo "$p" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+            echo '{{"jsonrpc":"2.0","method":"session/update","params":{{"sessionId":"s1","update":{{"sessionUpdate":"tool_call","toolCallId":"t1","title":"Edit","kind":"edit","rawInput":{{"file_path":"{ws}/src/a.rs"}}}}}}}}'
+            echo '{{"jsonrpc":"2.0","method":"session/update","params":{{"sessionId":"s1","update":{{"sessionUpdate":"tool_call_update","toolCallId":"t1","status":"completed","content":[{{"type":"diff","path":"{ws}/src/a.rs","newText":"x"}},{{"type":"diff","path":"/elsewhere/b.rs","newText":"y"}}]}}}}}}'
+            echo "{{\"jsonrpc\":\"2.0\",\"id\":$id,\"result\":{{\"stopReason\":\"end_turn\"}}}}"
+            sleep 5
+        "#,
+            ws = ws.display()
+        );
+        let Some(mut conn) = scripted_connection(&script) else {
+            return;
+        };
+        conn.workspace = Some(ws.clone());
+
+        let params = serde_json::json!({"sessionId": "s1", "prompt": []});
+        let result = conn
+            .prompt_streaming(params, true, Duration::from_secs(5), None, None)
+            .await
+            .unwrap();
+
+        assert!(result.completed);
+        assert_eq!(
+            result.files_changed,
+            vec!["src/a.rs".to_string(), "/elsewhere/b.rs".to_string()]
+        );
+        let _ = std::fs::remove_dir_all(&ws);
     }
 
     #[test]
-    fn test_config_idle_timeout_parse_snake() {
-        let json = r#"{ "idle_timeout_secs": 0 }"#;
-        let config: AcpConfig = serde_json::from_str(json).unwrap();
-        assert_eq!(config.idle_timeout_secs, 0);
+    fn test_resolve_workspace_path_sandbox() {
+        let ws = temp_workspace();
+        std::fs::create_dir_all(ws.join("src")).unwrap();
+
+        assert_eq!(
+            resolve_workspace_path(&ws, "src/main.rs").unwrap(),
+            ws.join("src/main.rs")
+        );
+        let absolute = ws.join("new/dir/file.txt");
+        assert_eq!(
+            resolve_workspace_path(&ws, absolute.to_str().unwrap()).unwrap(),
+            absolute
+        );
+        assert_eq!(
+            resolve_workspace_path(&ws, "src/../README.md").unwrap(),
+            ws.join("README.md")
+        );
+
+        assert!(resolve_workspace_path(&ws, "../outside.txt").is_err());
+        assert!(resolve_workspace_path(&ws, "/etc/passwd").is_err());
+        assert!(resolve_workspace_path(&ws, "").is_err());
+        assert!(resolve_workspace_path(&ws, ".env").is_err());
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(std::env::temp_dir(), ws.join("link")).unwrap();
+            let err = resolve_workspace_path(&ws, "link/escape.txt").unwrap_err();
+            assert!(err.contains("outside the session workspace"), "{err}");
+        }
+
+        let _ = std::fs::remove_dir_all(&ws);
     }
 
     #[tokio::test]
-    async fn test_reap_idle_sessions_disabled_when_zero() {
-        let config = AcpConfig {
-            idle_timeout_secs: 0,
-            ..AcpConfig::default()
-        };
-        let manager = AcpManager::from_config(config);
-        let reaped = manager.reap_idle_sessions().await;
-        assert_eq!(reaped, 0);
+    async fn test_read_text_file_line_window() {
+        let ws = temp_workspace();
+        let path = ws.join("lines.txt");
+        std::fs::write(&path, "one\ntwo\nthree\nfour\n").unwrap();
+
+        assert_eq!(
+            read_text_file(&path, None, None).await.unwrap(),
+            "one\ntwo\nthree\nfour\n"
+        );
+        assert_eq!(
+            read_text_file(&path, Some(2), Some(2)).await.unwrap(),
+            "two\nthree\n"
+        );
+        assert_eq!(
+            read_text_file(&path, Some(4), None).await.unwrap(),
+            "four\n"
+        );
+        assert!(read_text_file(&ws.join("missing"), None, None)
+            .await
+            .is_err());
+
+        let _ = std::fs::remove_dir_all(&ws);
     }
 
     #[tokio::test]
-    async fn test_reap_idle_sessions_empty() {
-        let config = AcpConfig {
-            idle_timeout_secs: 1,
-            ..AcpConfig::default()
+    async fn test_prompt_serves_fs_requests_in_workspace() {
+        // Writes a file, tries to escape the workspace, reads the file back,
+        // and reports the read result in a message chunk before finishing.
+        let script = r#"
+            read p
+            id=$(echo "$p" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+            echo '{"jsonrpc":"2.0","id":"w1","method":"fs/write_text_file","params":{"sessionId":"s1","path":"out/hello.txt","content":"hi there"}}'
+            read w
+            echo '{"jsonrpc":"2.0","id":"w2","method":"fs/write_text_file","params":{"sessionId":"s1","path":"../escape.txt","content":"x"}}'
+            read e
+            echo '{"jsonrpc":"2.0","id":"r1","method":"fs/read_text_file","params":{"sessionId":"s1","path":"out/hello.txt"}}'
+            read r
+            case "$e" in *error*) denied=yes;; *) denied=no;; esac
+            case "$r" in *"hi there"*) read_ok=yes;; *) read_ok=no;; esac
+            echo "{\"jsonrpc\":\"2.0\",\"method\":\"session/update\",\"params\":{\"sessionId\":\"s1\",\"update\":{\"sessionUpdate\":\"agent_message_chunk\",\"content\":{\"type\":\"text\",\"text\":\"denied=$denied read=$read_ok\"}}}}"
+            echo "{\"jsonrpc\":\"2.0\",\"id\":$id,\"result\":{\"stopReason\":\"end_turn\"}}"
+            sleep 5
+        "#;
+        let Some(mut conn) = scripted_connection(script) else {
+            return;
         };
-        let manager = AcpManager::from_config(config);
-        let reaped = manager.reap_idle_sessions().await;
-        assert_eq!(reaped, 0);
-    }
+        let ws = temp_workspace();
+        conn.workspace = Some(ws.clone());
 
-    // -----------------------------------------------------------------------
-    // Phase 2: Crash recovery tests
-    // -----------------------------------------------------------------------
+        let params = serde_json::json!({"sessionId": "s1", "prompt": []});
+        let result = conn
+            .prompt_streaming(params, true, Duration::from_secs(5), None, None)
+            .await
+            .unwrap();
 
-    #[test]
-    fn test_prompt_result_context_reset_default_false() {
-        let result = AcpPromptResult {
-            messages: vec![],
-            tool_calls: vec![],
-            files_changed: vec![],
-            completed: true,
-            duration_ms: 0,
-            context_reset: false,
-        };
-        assert!(!result.context_reset);
+        assert!(result.completed);
+        assert_eq!(result.files_changed, vec!["out/hello.txt".to_string()]);
+        assert_eq!(result.messages, vec!["denied=yes read=yes".to_string()]);
+        assert_eq!(
+            std::fs::read_to_string(ws.join("out/hello.txt")).unwrap(),
+            "hi there"
+        );
+        assert!(!ws.parent().unwrap().join("escape.txt").exists());
+
+        conn.kill().await;
+        let _ = std::fs::remove_dir_all(&ws);
     }
 
     #[test]
-    fn test_prompt_result_context_reset_true() {
-        let result = AcpPromptResult {
-            messages: vec!["recovered".to_string()],
-            tool_calls: vec![],
-            files_changed: vec![],
-            completed: true,
-            duration_ms: 100,
-            context_reset: true,
+    fn test_terminal_output_truncates_from_front() {
+        let mut out = TerminalOutput {
+            limit: 8,
+            ..Default::default()
         };
-        assert!(result.context_reset);
-        assert_eq!(result.messages[0], "recovered");
-    }
-
-    #[tokio::test]
-    async fn test_recover_session_agent_not_configured() {
-        // If agent config was removed after session creation, recovery should
-        // fail with a clear error.
-        let config = AcpConfig::default(); // no agents configured
-        let manager = AcpManager::from_config(config);
-
-        // Build a minimal session struct to pass to recover_session.
-        // We can't build a real AcpConnection without a process, but
-        // recover_session only reads session fields before spawning.
-        // Since we can't construct AcpSession without AcpConnection,
-        // we test via prompt() on a nonexistent session instead.
-        let result = manager.prompt("nonexistent", "hello", None, None).await;
-        assert!(result.is_err());
-        assert!(result.unwrap_err().contains("not found"));
+        out.push("abcd");
+        assert!(!out.truncated);
+        out.push("efghij");
+        assert_eq!(out.text, "cdefghij");
+        assert!(out.truncated);
+
+        // Never splits a multi-byte character
+        let mut out = TerminalOutput {
+            limit: 3,
+            ..Default::default()
+        };
+        out.push("éa");
+        out.push("b");
+        assert_eq!(out.text, "ab");
     }
 
     #[tokio::test]
-    async fn test_is_alive_after_spawn_and_kill() {
-        // Spawn a real process (sleep) and verify is_alive / kill behavior.
-        let config = AcpAgentConfig {
-            launch: "binary".to_string(),
-            command: "sleep".to_string(),
-            args: vec!["60".to_string()],
-            env: HashMap::new(),
-            workspace: None,
-            auto_approve: None,
-            mode: default_mode(),
-            resource_limits: None,
+    async fn test_prompt_runs_terminal_commands() {
+        // Creates a terminal, waits for it, reads its output, releases it,
+        // and reports what it saw in a message chunk.
+        let script = r#"
+            read p
+            id=$(echo "$p" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+            echo '{"jsonrpc":"2.0","id":"c1","method":"terminal/create","params":{"sessionId":"s1","command":"sh","args":["-c","echo hi; exit 3"]}}'
+            read c
+            tid=$(echo "$c" | sed -n 's/.*"terminalId":"\([^"]*\)".*/\1/p')
+            echo "{\"jsonrpc\":\"2.0\",\"id\":\"x1\",\"method\":\"terminal/wait_for_exit\",\"params\":{\"sessionId\":\"s1\",\"terminalId\":\"$tid\"}}"
+            read x
+            echo "{\"jsonrpc\":\"2.0\",\"id\":\"o1\",\"method\":\"terminal/output\",\"params\":{\"sessionId\":\"s1\",\"terminalId\":\"$tid\"}}"
+            read -r o
+            echo "{\"jsonrpc\":\"2.0\",\"id\":\"r1\",\"method\":\"terminal/release\",\"params\":{\"sessionId\":\"s1\",\"terminalId\":\"$tid\"}}"
+            read r
+            case "$x" in *'"exitCode":3'*) code=yes;; *) code=no;; esac
+            case "$o" in *'hi\n'*) out=yes;; *) out=no;; esac
+            echo "{\"jsonrpc\":\"2.0\",\"method\":\"session/update\",\"params\":{\"sessionId\":\"s1\",\"update\":{\"sessionUpdate\":\"agent_message_chunk\",\"content\":{\"type\":\"text\",\"text\":\"code=$code out=$out\"}}}}"
+            echo "{\"jsonrpc\":\"2.0\",\"id\":$id,\"result\":{\"stopReason\":\"end_turn\"}}"
+            sleep 5
+        "#;
+        let Some(mut conn) = scripted_connection(script) else {
+            return;
         };
+        let ws = temp_workspace();
+        conn.workspace = Some(ws.clone());
 
-        let mut cmd = build_spawn_command(&config, Some("/tmp"));
-        let child = cmd.spawn();
-        if child.is_err() {
-            // Skip test if 'sleep' is not available
-            return;
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let params = serde_json::json!({"sessionId": "s1", "prompt": []});
+        let result = conn
+            .prompt_streaming(params, true, Duration::from_secs(5), Some(&tx), None)
+            .await
+            .unwrap();
+        drop(tx);
+
+        assert!(result.completed);
+        assert_eq!(result.messages, vec!["code=yes out=yes".to_string()]);
+        assert!(conn.terminals.lock().unwrap().is_empty());
+        let mut streamed = String::new();
+        while let Ok(event) = rx.try_recv() {
+            if let AcpProgressEvent::TerminalOutput { text, .. } = event {
+                streamed.push_str(&text);
+            }
         }
-        let mut child = child.unwrap();
-        let stdin = child.stdin.take().unwrap();
-        let stdout = child.stdout.take().unwrap();
+        assert_eq!(streamed, "hi\n");
 
-        let conn = AcpConnection {
-            agent_name: "test".to_string(),
-            inner: Mutex::new(AcpConnectionInner {
-                stdin,
-                stdout: BufReader::new(stdout),
-                _child: child,
-                next_id: 1,
-            }),
-            request_timeout: Duration::from_secs(5),
+        conn.kill().await;
+        let _ = std::fs::remove_dir_all(&ws);
+    }
+
+    #[tokio::test]
+    async fn test_terminal_create_needs_approval() {
+        let script = r#"
+            read p
+            id=$(echo "$p" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+            echo '{"jsonrpc":"2.0","id":"c1","method":"terminal/create","params":{"sessionId":"s1","command":"touch","args":["ran"]}}'
+            read c
+            case "$c" in *error*) denied=yes;; *) denied=no;; esac
+            echo "{\"jsonrpc\":\"2.0\",\"method\":\"session/update\",\"params\":{\"sessionId\":\"s1\",\"update\":{\"sessionUpdate\":\"agent_message_chunk\",\"content\":{\"type\":\"text\",\"text\":\"denied=$denied\"}}}}"
+            echo "{\"jsonrpc\":\"2.0\",\"id\":$id,\"result\":{\"stopReason\":\"end_turn\"}}"
+            sleep 5
+        "#;
+        let Some(mut conn) = scripted_connection(script) else {
+            return;
         };
+        let ws = temp_workspace();
+        conn.workspace = Some(ws.clone());
 
-        // Process should be alive
-        assert!(conn.is_alive().await);
+        let params = serde_json::json!({"sessionId": "s1", "prompt": []});
+        let result = conn
+            .prompt_streaming(params, false, Duration::from_secs(5), None, None)
+            .await
+            .unwrap();
 
-        // Kill it
-        {
-            let mut inner = conn.inner.lock().await;
-            let _ = inner._child.kill().await;
-            let _ = inner._child.wait().await;
-        }
+        assert_eq!(result.messages, vec!["denied=yes".to_string()]);
+        assert!(!ws.join("ran").exists());
 
-        // Process should be dead
-        assert!(!conn.is_alive().await);
+        conn.kill().await;
+        let _ = std::fs::remove_dir_all(&ws);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_prompt_without_running_prompt() {
+        let manager = AcpManager::from_config(AcpConfig::default());
+        let err = manager.cancel_prompt("missing").await.unwrap_err();
+        assert!(err.contains("not found"));
+        let err = manager.cancel_chat_prompt(42).await.unwrap_err();
+        assert!(err.contains("No active ACP session"));
+    }
+
+    #[test]
+    fn test_is_stop_command() {
+        assert!(is_stop_command("#stop"));
+        assert!(is_stop_command("@bot  #STOP"));
+        assert!(is_stop_command("/acp stop"));
+        assert!(!is_stop_command("#stop the build after tests"));
+        assert!(!is_stop_command("stop"));
+    }
+
+    #[tokio::test]
+    async fn test_pending_request_fails_when_agent_exits() {
+        let Some(conn) = scripted_connection("read line; exit 0") else {
+            return;
+        };
+        let err = conn.send_request("anything", None).await.unwrap_err();
+        assert!(err.contains("closed connection"), "{err}");
+        // Later requests fail fast instead of waiting for the timeout
+        let err = conn.send_request("again", None).await.unwrap_err();
+        assert!(err.contains("closed connection") || err.contains("write error"));
     }
 
     // -----------------------------------------------------------------------
@@ -3043,6 +7215,10 @@ mod tests {
             workspace: None,
             auto_approve: None,
             resource_limits: None,
+            policy: None,
+            model: None,
+            url: None,
+            headers: HashMap::new(),
         };
 
         let conn = PtyConnection::spawn("test-cat", &config, Some("/tmp")).await;
@@ -3075,6 +7251,10 @@ mod tests {
             workspace: None,
             auto_approve: None,
             resource_limits: None,
+            policy: None,
+            model: None,
+            url: None,
+            headers: HashMap::new(),
         };
 
         let conn = PtyConnection::spawn("test-sleep", &config, Some("/tmp")).await;
@@ -3110,6 +7290,10 @@ mod tests {
             workspace: None,
             auto_approve: None,
             resource_limits: None,
+            policy: None,
+            model: None,
+            url: None,
+            headers: HashMap::new(),
         };
 
         let conn = PtyConnection::spawn("test-cat-progress", &config, Some("/tmp")).await;
@@ -3247,4 +7431,831 @@ mod tests {
         let config: AcpAgentConfig = serde_json::from_str(json).unwrap();
         assert!(config.resource_limits.is_some());
     }
+
+    #[test]
+    fn test_permission_reply_parsing() {
+        use AcpPermissionDecision::*;
+        assert_eq!(
+            AcpPermissionDecision::parse_reply("approve"),
+            Some(AllowOnce)
+        );
+        assert_eq!(
+            AcpPermissionDecision::parse_reply(" Yes! "),
+            Some(AllowOnce)
+        );
+        assert_eq!(
+            AcpPermissionDecision::parse_reply("#always"),
+            Some(AllowAlways)
+        );
+        assert_eq!(
+            AcpPermissionDecision::parse_reply("@rayclaw_bot approve always"),
+            Some(AllowAlways)
+        );
+        assert_eq!(AcpPermissionDecision::parse_reply("deny"), Some(Reject));
+        assert_eq!(AcpPermissionDecision::parse_reply("yes please do it"), None);
+        assert_eq!(AcpPermissionDecision::parse_reply(""), None);
+    }
+
+    #[test]
+    fn test_permission_outcome_selects_option() {
+        let params = serde_json::json!({
+            "options": [
+                {"optionId": "a1", "kind": "allow_once"},
+                {"optionId": "a2", "kind": "allow_always"},
+                {"optionId": "r1", "kind": "reject_once"}
+            ]
+        });
+        let id = |d| permission_outcome(Some(&params), d)["optionId"].clone();
+        assert_eq!(id(AcpPermissionDecision::AllowOnce), "a1");
+        assert_eq!(id(AcpPermissionDecision::AllowAlways), "a2");
+        assert_eq!(id(AcpPermissionDecision::Reject), "r1");
+        assert_eq!(
+            permission_outcome(Some(&params), AcpPermissionDecision::Cancelled)["outcome"],
+            "cancelled"
+        );
+        // Without a reject option, a denial is sent as a cancellation
+        assert_eq!(
+            permission_outcome(None, AcpPermissionDecision::Reject)["outcome"],
+            "cancelled"
+        );
+    }
+
+    #[test]
+    fn test_permission_policy_evaluate() {
+        let policy = AcpPermissionPolicy {
+            allow_tools: vec!["read".into(), "edit".into()],
+            deny_tools: vec!["bash".into()],
+            allow_paths: vec!["src/**".into(), "docs".into()],
+            max_file_writes: Some(1),
+        };
+        let ws = std::path::Path::new("/ws");
+        let call = |kind: &str, title: &str, path: &str| {
+            serde_json::json!({
+                "kind": kind,
+                "title": title,
+                "locations": [{"path": path}],
+            })
+        };
+        let verdict = |tool_call: &serde_json::Value, writes: usize| {
+            policy.evaluate(
+                &PolicyRequest::from_tool_call(Some(tool_call)),
+                Some(ws),
+                writes,
+            )
+        };
+
+        assert_eq!(
+            verdict(&call("read", "Read src/main.rs", "/ws/src/main.rs"), 0),
+            PolicyVerdict::Allow
+        );
+        assert_eq!(
+            verdict(&call("read", "Read", "docs/guide.md"), 0),
+            PolicyVerdict::Allow
+        );
+        assert!(matches!(
+            verdict(&call("execute", "`ls -la`", "/ws"), 0),
+            PolicyVerdict::Deny(_)
+        ));
+        // Outside allow_paths, or escaping through `..`
+        assert_eq!(
+            verdict(&call("edit", "Edit", "/ws/Cargo.toml"), 0),
+            PolicyVerdict::Ask
+        );
+        assert_eq!(
+            verdict(&call("read", "Read", "/ws/src/../.env"), 0),
+            PolicyVerdict::Ask
+        );
+        assert_eq!(
+            verdict(&call("read", "Read", "/etc/passwd"), 0),
+            PolicyVerdict::Ask
+        );
+        // Write budget
+        assert_eq!(
+            verdict(&call("edit", "Edit", "/ws/src/lib.rs"), 0),
+            PolicyVerdict::Allow
+        );
+        assert_eq!(
+            verdict(&call("edit", "Edit", "/ws/src/lib.rs"), 1),
+            PolicyVerdict::Ask
+        );
+        // Not mentioned by any rule
+        assert_eq!(
+            verdict(&call("fetch", "Fetch", "/ws/src/x"), 0),
+            PolicyVerdict::Ask
+        );
+    }
+
+    #[test]
+    fn test_config_parse_permission_policy() {
+        let json = r#"{
+            "acpAgents": {
+                "claude": {
+                    "command": "claude-agent-acp",
+                    "permissionPolicy": {
+                        "allowTools": ["read"],
+                        "denyTools": ["bash"],
+                        "allowPaths": ["src/**"],
+                        "maxFileWrites": 5
+                    }
+                }
+            }
+        }"#;
+        let config: AcpConfig = serde_json::from_str(json).unwrap();
+        let policy = config.agents["claude"].policy.as_ref().unwrap();
+        assert_eq!(policy.allow_tools, vec!["read"]);
+        assert_eq!(policy.deny_tools, vec!["bash"]);
+        assert_eq!(policy.allow_paths, vec!["src/**"]);
+        assert_eq!(policy.max_file_writes, Some(5));
+    }
+
+    #[tokio::test]
+    async fn test_prompt_applies_permission_policy() {
+        // Asks to run a command (denied by policy) and to read a file
+        // (allowed), then reports both answers in a message chunk.
+        let script = r#"
+            read p
+            id=$(echo "$p" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+            opts='"options":[{"optionId":"a1","kind":"allow_once"},{"optionId":"r1","kind":"reject_once"}]'
+            echo "{\"jsonrpc\":\"2.0\",\"id\":\"p1\",\"method\":\"session/request_permission\",\"params\":{\"sessionId\":\"s1\",\"toolCall\":{\"kind\":\"execute\",\"title\":\"rm -rf /\"},$opts}}"
+            read a
+            echo "{\"jsonrpc\":\"2.0\",\"id\":\"p2\",\"method\":\"session/request_permission\",\"params\":{\"sessionId\":\"s1\",\"toolCall\":{\"kind\":\"read\",\"title\":\"Read README.md\"},$opts}}"
+            read b
+            case "$a" in *r1*) first=rejected;; *) first=other;; esac
+            case "$b" in *a1*) second=allowed;; *) second=other;; esac
+            echo "{\"jsonrpc\":\"2.0\",\"method\":\"session/update\",\"params\":{\"sessionId\":\"s1\",\"update\":{\"sessionUpdate\":\"agent_message_chunk\",\"content\":{\"type\":\"text\",\"text\":\"$first $second\"}}}}"
+            echo "{\"jsonrpc\":\"2.0\",\"id\":$id,\"result\":{\"stopReason\":\"end_turn\"}}"
+            sleep 5
+        "#;
+        let Some(mut conn) = scripted_connection(script) else {
+            return;
+        };
+        conn.policy = Some(AcpPermissionPolicy {
+            allow_tools: vec!["read".into()],
+            deny_tools: vec!["bash".into()],
+            ..Default::default()
+        });
+
+        // No auto-approve and no one to ask: only the policy can answer
+        let params = serde_json::json!({"sessionId": "s1", "prompt": []});
+        let result = conn
+            .prompt_streaming(params, false, Duration::from_secs(5), None, None)
+            .await
+            .unwrap();
+        assert_eq!(result.messages, vec!["rejected allowed".to_string()]);
+
+        conn.kill().await;
+    }
+
+    #[tokio::test]
+    async fn test_prompt_fs_writes_follow_permission_policy() {
+        // Gets an edit of src/a.txt approved, writes it, then writes three
+        // more files and reports which writes succeeded.
+        let script = r#"
+            read p
+            id=$(echo "$p" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+            opts='"options":[{"optionId":"a1","kind":"allow_once"},{"optionId":"r1","kind":"reject_once"}]'
+            echo "{\"jsonrpc\":\"2.0\",\"id\":\"p1\",\"method\":\"session/request_permission\",\"params\":{\"sessionId\":\"s1\",\"toolCall\":{\"kind\":\"edit\",\"title\":\"Edit\",\"locations\":[{\"path\":\"src/a.txt\"}]},$opts}}"
+            read g
+            r=""
+            for f in src/a.txt src/b.txt src/c.txt notes.txt; do
+                echo "{\"jsonrpc\":\"2.0\",\"id\":\"w\",\"method\":\"fs/write_text_file\",\"params\":{\"sessionId\":\"s1\",\"path\":\"$f\",\"content\":\"x\"}}"
+                read w
+                case "$w" in *error*) r="$r err";; *) r="$r ok";; esac
+            done
+            echo "{\"jsonrpc\":\"2.0\",\"method\":\"session/update\",\"params\":{\"sessionId\":\"s1\",\"update\":{\"sessionUpdate\":\"agent_message_chunk\",\"content\":{\"type\":\"text\",\"text\":\"$r\"}}}}"
+            echo "{\"jsonrpc\":\"2.0\",\"id\":$id,\"result\":{\"stopReason\":\"end_turn\"}}"
+            sleep 5
+        "#;
+        let Some(mut conn) = scripted_connection(script) else {
+            return;
+        };
+        let ws = temp_workspace();
+        conn.workspace = Some(ws.clone());
+        conn.policy = Some(AcpPermissionPolicy {
+            allow_tools: vec!["edit".into()],
+            allow_paths: vec!["src/**".into()],
+            max_file_writes: Some(2),
+            ..Default::default()
+        });
+
+        // The approved edit and its write count once; the third write is
+        // over budget and notes.txt is outside allow_paths, with no one to ask
+        let params = serde_json::json!({"sessionId": "s1", "prompt": []});
+        let result = conn
+            .prompt_streaming(params, false, Duration::from_secs(5), None, None)
+            .await
+            .unwrap();
+        assert_eq!(result.messages, vec![" ok ok err err".to_string()]);
+        assert_eq!(result.files_changed, vec!["src/a.txt", "src/b.txt"]);
+        assert!(!ws.join("src/c.txt").exists());
+        assert!(!ws.join("notes.txt").exists());
+
+        conn.kill().await;
+        let _ = std::fs::remove_dir_all(&ws);
+    }
+
+    #[tokio::test]
+    async fn test_prompt_question_answered() {
+        // Asks a question, then echoes the answer back as a message chunk.
+        let script = r#"
+            read p
+            id=$(echo "$p" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+            echo '{"jsonrpc":"2.0","id":"q1","method":"session/request_input","params":{"sessionId":"s1","question":"Which DB?"}}'
+            read a
+            text=$(echo "$a" | sed -n 's/.*"text":"\([^"]*\)".*/\1/p')
+            echo "{\"jsonrpc\":\"2.0\",\"method\":\"session/update\",\"params\":{\"sessionId\":\"s1\",\"update\":{\"sessionUpdate\":\"agent_message_chunk\",\"content\":{\"type\":\"text\",\"text\":\"using $text\"}}}}"
+            echo "{\"jsonrpc\":\"2.0\",\"id\":$id,\"result\":{\"stopReason\":\"end_turn\"}}"
+            sleep 5
+        "#;
+        let Some(conn) = scripted_connection(script) else {
+            return;
+        };
+        assert!(conn.answer_question("early").is_err());
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let params = serde_json::json!({"sessionId": "s1", "prompt": []});
+        let answer = async {
+            while let Some(event) = rx.recv().await {
+                if let AcpProgressEvent::Question { text } = event {
+                    assert_eq!(text, "Which DB?");
+                    assert_eq!(conn.pending_question().as_deref(), Some("Which DB?"));
+                    conn.answer_question("postgres").unwrap();
+                    break;
+                }
+            }
+        };
+        let (result, ()) = tokio::join!(
+            conn.prompt_streaming(params, false, Duration::from_secs(5), Some(&tx), None),
+            answer
+        );
+        assert_eq!(result.unwrap().messages, vec!["using postgres".to_string()]);
+        assert_eq!(conn.pending_question(), None);
+
+        conn.kill().await;
+    }
+
+    fn recording_callback() -> (JobCompletionCallback, Arc<std::sync::Mutex<Vec<String>>>) {
+        let sent = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = sent.clone();
+        let cb: JobCompletionCallback = Arc::new(move |_chat_id, text| {
+            sink.lock().unwrap().push(text);
+            Box::pin(async {})
+        });
+        (cb, sent)
+    }
+
+    fn test_permission_request() -> AcpPermissionRequest {
+        AcpPermissionRequest {
+            agent_name: "claude".to_string(),
+            tool_name: "Run `rm -rf target`".to_string(),
+            raw_input: serde_json::json!({"command": "rm -rf target"}),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_chat_permission_handler_resolves_reply() {
+        let manager = Arc::new(AcpManager::from_config(AcpConfig::default()));
+        let (notify, sent) = recording_callback();
+        let handler = manager.chat_permission_handler(42, Some("alice".into()), notify);
+
+        let pending = tokio::spawn(handler(test_permission_request()));
+        while !manager.has_pending_permission(42).await {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(
+            manager
+                .resolve_permission(42, "alice", false, "hello")
+                .await,
+            None
+        );
+        assert_eq!(
+            manager
+                .resolve_permission(7, "alice", false, "approve")
+                .await,
+            None
+        );
+        assert_eq!(
+            manager
+                .resolve_permission(42, "alice", false, "always")
+                .await,
+            Some(Ok(AcpPermissionDecision::AllowAlways))
+        );
+        assert_eq!(pending.await.unwrap(), AcpPermissionDecision::AllowAlways);
+        assert!(!manager.has_pending_permission(42).await);
+
+        let sent = sent.lock().unwrap();
+        assert_eq!(sent.len(), 1);
+        assert!(sent[0].contains("Run `rm -rf target`"));
+        assert!(sent[0].contains("rm -rf target\""));
+        assert!(is_permission_prompt(&sent[0]));
+        assert!(!is_permission_prompt("[ACP claude] finished"));
+    }
+
+    #[tokio::test]
+    async fn test_chat_permission_answered_by_requester_or_control_chat() {
+        let manager = Arc::new(AcpManager::from_config(AcpConfig::default()));
+        let (notify, _sent) = recording_callback();
+        let handler = manager.chat_permission_handler(42, Some("alice".into()), notify.clone());
+        let pending = tokio::spawn(handler(test_permission_request()));
+        while !manager.has_pending_permission(42).await {
+            tokio::task::yield_now().await;
+        }
+        let refused = manager
+            .resolve_permission(42, "mallory", false, "approve")
+            .await;
+        assert!(matches!(refused, Some(Err(e)) if e.contains("alice")));
+        assert!(manager.has_pending_permission(42).await);
+        assert_eq!(
+            manager.resolve_permission(42, "bob", true, "deny").await,
+            Some(Ok(AcpPermissionDecision::Reject))
+        );
+        assert_eq!(pending.await.unwrap(), AcpPermissionDecision::Reject);
+
+        // Background jobs have no requester: only a control chat can answer
+        let handler = manager.chat_permission_handler(42, None, notify);
+        let pending = tokio::spawn(handler(test_permission_request()));
+        while !manager.has_pending_permission(42).await {
+            tokio::task::yield_now().await;
+        }
+        assert!(matches!(
+            manager
+                .resolve_permission(42, "alice", false, "approve")
+                .await,
+            Some(Err(_))
+        ));
+        assert_eq!(
+            manager
+                .resolve_permission(42, "alice", true, "approve")
+                .await,
+            Some(Ok(AcpPermissionDecision::AllowOnce))
+        );
+        assert_eq!(pending.await.unwrap(), AcpPermissionDecision::AllowOnce);
+    }
+
+    #[tokio::test]
+    async fn test_chat_permission_handler_times_out() {
+        let config = AcpConfig {
+            permission_timeout_secs: 0,
+            ..AcpConfig::default()
+        };
+        let manager = Arc::new(AcpManager::from_config(config));
+        let (notify, sent) = recording_callback();
+        let handler = manager.chat_permission_handler(42, None, notify);
+        assert_eq!(
+            handler(test_permission_request()).await,
+            AcpPermissionDecision::Cancelled
+        );
+        assert!(!manager.has_pending_permission(42).await);
+        assert!(sent.lock().unwrap()[1].contains("timed out"));
+    }
+
+    #[test]
+    fn test_prompt_content_blocks_with_attachments() {
+        assert_eq!(
+            prompt_content_blocks("hi", &[], false),
+            vec![serde_json::json!({"type": "text", "text": "hi"})]
+        );
+
+        let attachments = vec![
+            AcpAttachment::Image {
+                data: "aGk=".into(),
+                mime_type: "image/png".into(),
+            },
+            AcpAttachment::File {
+                path: "/nonexistent/dir/notes.md".into(),
+            },
+        ];
+        let blocks = prompt_content_blocks("", &attachments, true);
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(
+            blocks[0],
+            serde_json::json!({"type": "image", "data": "aGk=", "mimeType": "image/png"})
+        );
+        assert_eq!(blocks[1]["type"], "resource_link");
+        assert_eq!(blocks[1]["uri"], "file:///nonexistent/dir/notes.md");
+        assert_eq!(blocks[1]["name"], "notes.md");
+
+        // Agents without image support get a note instead
+        let blocks = prompt_content_blocks("look", &attachments[..1], false);
+        assert_eq!(blocks[1]["type"], "text");
+        assert!(blocks[1]["text"]
+            .as_str()
+            .unwrap()
+            .contains("does not accept images"));
+    }
+
+    fn health_test_manager(script: &str, auto_respawn: bool) -> AcpManager {
+        let mut agents = HashMap::new();
+        agents.insert(
+            "flaky".to_string(),
+            AcpAgentConfig {
+                launch: "binary".to_string(),
+                command: "sh".to_string(),
+                args: vec!["-c".to_string(), script.to_string()],
+                env: HashMap::new(),
+                workspace: None,
+                auto_approve: None,
+                mode: "pty".to_string(),
+                resource_limits: None,
+                policy: None,
+                model: None,
+                url: None,
+                headers: HashMap::new(),
+            },
+        );
+        AcpManager::from_config(AcpConfig {
+            agents,
+            auto_respawn,
+            ..AcpConfig::default()
+        })
+    }
+
+    async fn wait_for_exit(manager: &AcpManager, session_id: &str) {
+        for _ in 0..100 {
+            let sessions = manager.sessions.read().await;
+            let session = sessions[session_id].lock().await;
+            if !session.connection.is_alive().await {
+                return;
+            }
+            drop(session);
+            drop(sessions);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("agent did not exit");
+    }
+
+    #[test]
+    fn test_config_health_defaults_and_parse() {
+        let config = AcpConfig::default();
+        assert_eq!(config.health_check_interval_secs, 30);
+        assert!(!config.auto_respawn);
+
+        let json = r#"{"healthCheckIntervalSecs": 5, "autoRespawn": true}"#;
+        let config: AcpConfig = serde_json::from_str(json).unwrap();
+        assert_eq!(config.health_check_interval_secs, 5);
+        assert!(config.auto_respawn);
+    }
+
+    #[tokio::test]
+    async fn test_sessions_survive_restart() {
+        let dir = temp_workspace();
+        let db = Arc::new(crate::db::Database::new(dir.to_str().unwrap()).unwrap());
+
+        let manager = health_test_manager("sleep 60", false);
+        manager.set_run_log(db.clone());
+        let Ok(info) = manager.new_session("flaky", Some("/tmp"), None).await else {
+            return;
+        };
+        manager.bind_chat(42, &info.session_id).await;
+        // Shutdown keeps the record
+        manager.cleanup().await;
+
+        let manager = health_test_manager("sleep 60", false);
+        manager.set_run_log(db.clone());
+        assert_eq!(manager.restore_sessions().await, 1);
+        assert_eq!(
+            manager.chat_session(42).await,
+            Some(info.session_id.clone())
+        );
+        {
+            let sessions = manager.sessions.read().await;
+            let session = sessions[&info.session_id].lock().await;
+            // PTY agents can't resume, so the context was lost
+            assert!(session.session_reset);
+            assert!(session.connection.is_alive().await);
+        }
+
+        // Ending for real forgets it
+        manager.end_chat_session(42).await.unwrap();
+        assert!(db.get_acp_sessions().unwrap().is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_restore_uses_session_load() {
+        let dir = temp_workspace();
+        let db = Arc::new(crate::db::Database::new(dir.to_str().unwrap()).unwrap());
+        let record = |session_id: &str, agent_id: &str| crate::db::AcpSessionRecord {
+            session_id: session_id.into(),
+            agent_id: agent_id.into(),
+            workspace: "/tmp".into(),
+            acp_session_id: Some("agent-old".into()),
+            auto_approve: false,
+            chat_ids: vec![],
+            created_at: chrono::Utc::now().to_rfc3339(),
+        };
+        db.save_acp_session(&record("s1", "loader")).unwrap();
+        db.bind_acp_chat(7, "s1").unwrap();
+        db.save_acp_session(&record("s2", "removed")).unwrap();
+        db.bind_acp_chat(8, "s2").unwrap();
+
+        let script = r#"
+            read init
+            echo '{"jsonrpc":"2.0","id":1,"result":{"protocolVersion":1,"agentCapabilities":{"loadSession":true}}}'
+            read initialized
+            read req
+            id=$(echo "$req" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+            case "$req" in
+                *session/load*) echo "{\"jsonrpc\":\"2.0\",\"id\":$id,\"result\":null}" ;;
+                *) echo "{\"jsonrpc\":\"2.0\",\"id\":$id,\"result\":{\"sessionId\":\"agent-new\"}}" ;;
+            esac
+            while read req; do
+                id=$(echo "$req" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+                echo "{\"jsonrpc\":\"2.0\",\"id\":$id,\"result\":null}"
+            done
+        "#;
+        let mut agents = HashMap::new();
+        agents.insert(
+            "loader".to_string(),
+            AcpAgentConfig {
+                launch: "binary".to_string(),
+                command: "sh".to_string(),
+                args: vec!["-c".to_string(), script.to_string()],
+                env: HashMap::new(),
+                workspace: None,
+                auto_approve: None,
+                mode: default_mode(),
+                resource_limits: None,
+                policy: None,
+                model: None,
+                url: None,
+                headers: HashMap::new(),
+            },
+        );
+        let manager = AcpManager::from_config(AcpConfig {
+            agents,
+            ..AcpConfig::default()
+        });
+        manager.set_run_log(db.clone());
+        let (notify, sent) = recording_callback();
+        manager.set_chat_notifier(notify);
+
+        assert_eq!(manager.restore_sessions().await, 1);
+        assert_eq!(manager.chat_session(7).await.as_deref(), Some("s1"));
+        {
+            let sessions = manager.sessions.read().await;
+            let session = sessions["s1"].lock().await;
+            assert_eq!(session.acp_session_id.as_deref(), Some("agent-old"));
+            assert!(!session.session_reset);
+        }
+        // The unconfigured agent's session is dropped and its chat told
+        assert_eq!(manager.chat_session(8).await, None);
+        let remaining = db.get_acp_sessions().unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].session_id, "s1");
+        assert!(sent.lock().unwrap()[0].contains("could not be restored"));
+
+        manager.cleanup().await;
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_session_modes_and_set_mode() {
+        let script = r#"
+            read init
+            echo '{"jsonrpc":"2.0","id":1,"result":{"protocolVersion":1}}'
+            read initialized
+            read req
+            id=$(echo "$req" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+            echo "{\"jsonrpc\":\"2.0\",\"id\":$id,\"result\":{\"sessionId\":\"s1\",\"modes\":{\"currentModeId\":\"default\",\"availableModes\":[{\"id\":\"default\",\"name\":\"Default\"},{\"id\":\"plan\",\"name\":\"Plan Mode\",\"description\":\"Read-only planning\"}]}}}"
+            while read req; do
+                id=$(echo "$req" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+                echo "{\"jsonrpc\":\"2.0\",\"id\":$id,\"result\":null}"
+            done
+        "#;
+        let mut agents = HashMap::new();
+        agents.insert(
+            "moded".to_string(),
+            AcpAgentConfig {
+                launch: "binary".to_string(),
+                command: "sh".to_string(),
+                args: vec!["-c".to_string(), script.to_string()],
+                env: HashMap::new(),
+                workspace: None,
+                auto_approve: None,
+                mode: default_mode(),
+                resource_limits: None,
+                policy: None,
+                model: None,
+                url: None,
+                headers: HashMap::new(),
+            },
+        );
+        let manager = AcpManager::from_config(AcpConfig {
+            agents,
+            ..AcpConfig::default()
+        });
+        let Ok(info) = manager.new_session("moded", Some("/tmp"), None).await else {
+            return; // 'sh' unavailable
+        };
+        let modes = info.modes.expect("modes from session/new");
+        assert_eq!(modes.current_mode_id, "default");
+        assert_eq!(modes.available.len(), 2);
+        assert_eq!(
+            modes.available[1].description.as_deref(),
+            Some("Read-only planning")
+        );
+
+        let mode = manager
+            .set_mode(&info.session_id, "plan mode")
+            .await
+            .unwrap();
+        assert_eq!(mode.id, "plan");
+        let current = manager.session_modes(&info.session_id).await.unwrap();
+        assert_eq!(current.unwrap().current_mode_id, "plan");
+
+        let err = manager
+            .set_mode(&info.session_id, "yolo")
+            .await
+            .unwrap_err();
+        assert!(err.contains("Available: default, plan"), "{err}");
+        manager.cleanup().await;
+    }
+
+    fn slow_prompt_manager(max_queue_depth: usize) -> AcpManager {
+        let script = r#"
+            read init
+            echo '{"jsonrpc":"2.0","id":1,"result":{"protocolVersion":1}}'
+            read initialized
+            while read req; do
+                id=$(echo "$req" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+                case "$req" in
+                    *session/new*) echo "{\"jsonrpc\":\"2.0\",\"id\":$id,\"result\":{\"sessionId\":\"s1\"}}" ;;
+                    *session/prompt*) sleep 0.3; echo "{\"jsonrpc\":\"2.0\",\"id\":$id,\"result\":{\"stopReason\":\"end_turn\"}}" ;;
+                    *) echo "{\"jsonrpc\":\"2.0\",\"id\":$id,\"result\":null}" ;;
+                esac
+            done
+        "#;
+        let mut agents = HashMap::new();
+        agents.insert(
+            "slow".to_string(),
+            AcpAgentConfig {
+                launch: "binary".to_string(),
+                command: "sh".to_string(),
+                args: vec!["-c".to_string(), script.to_string()],
+                env: HashMap::new(),
+                workspace: None,
+                auto_approve: None,
+                mode: default_mode(),
+                resource_limits: None,
+                policy: None,
+                model: None,
+                url: None,
+                headers: HashMap::new(),
+            },
+        );
+        AcpManager::from_config(AcpConfig {
+            agents,
+            max_queue_depth,
+            ..AcpConfig::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn test_prompts_queue_behind_running_prompt() {
+        let manager = slow_prompt_manager(1);
+        let Ok(info) = manager.new_session("slow", Some("/tmp"), None).await else {
+            return; // 'sh' unavailable
+        };
+        let sid = info.session_id.as_str();
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let second = async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            manager.prompt(sid, "second", None, Some(&tx)).await
+        };
+        // Queue depth 1 is taken by "second"
+        let third = async {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            manager.prompt(sid, "third", None, None).await
+        };
+        let (first, second, third) =
+            tokio::join!(manager.prompt(sid, "first", None, None), second, third);
+        assert!(first.unwrap().completed);
+        assert!(second.unwrap().completed);
+        assert!(third.unwrap_err().contains("already queued"));
+        assert!(matches!(
+            rx.try_recv(),
+            Ok(AcpProgressEvent::Queued { position: 1 })
+        ));
+        assert_eq!(manager.queued_prompts(sid), 0);
+        manager.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn test_session_status_during_and_after_prompt() {
+        let manager = slow_prompt_manager(2);
+        let Ok(info) = manager.new_session("slow", Some("/tmp"), None).await else {
+            return; // 'sh' unavailable
+        };
+        let sid = info.session_id.as_str();
+        let idle = manager.session_status(sid).await.unwrap();
+        assert_eq!(idle.status, SessionStatus::Active);
+        assert!(idle.last_prompt.is_none());
+        assert!(idle.created_at.is_some());
+
+        let probe = async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            manager.session_status(sid).await.unwrap()
+        };
+        let (done, busy) = tokio::join!(manager.prompt(sid, "fix the build", None, None), probe);
+        assert!(done.unwrap().completed);
+        assert_eq!(busy.status, SessionStatus::Prompting);
+        assert_eq!(busy.last_prompt.as_deref(), Some("fix the build"));
+        assert!(busy.prompt_elapsed_secs.is_some());
+        assert_eq!(busy.queued_prompts, 0);
+        assert_eq!(busy.agent_id, "slow");
+
+        let after = manager.session_status(sid).await.unwrap();
+        assert_eq!(after.status, SessionStatus::Active);
+        assert_eq!(after.prompts_completed, 1);
+        assert!(after.prompt_elapsed_secs.is_none());
+        assert_eq!(after.last_prompt.as_deref(), Some("fix the build"));
+
+        manager.end_session(sid).await.unwrap();
+        assert!(manager.session_status(sid).await.is_err());
+        manager.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn test_health_check_marks_crashed_and_notifies_chat() {
+        let manager = health_test_manager("exit 0", false);
+        let (notify, sent) = recording_callback();
+        manager.set_chat_notifier(notify);
+        let Ok(info) = manager.new_session("flaky", Some("/tmp"), None).await else {
+            return; // 'sh' unavailable
+        };
+        manager.bind_chat(42, &info.session_id).await;
+        wait_for_exit(&manager, &info.session_id).await;
+
+        assert_eq!(manager.check_session_health().await, 1);
+        let sessions = manager.list_sessions().await;
+        assert_eq!(sessions[0].status, SessionStatus::Crashed);
+        assert!(sent.lock().unwrap()[0].contains("crashed"));
+
+        // Already crashed — not reported twice
+        assert_eq!(manager.check_session_health().await, 0);
+        assert_eq!(sent.lock().unwrap().len(), 1);
+        manager.cleanup().await;
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_reap_idle_session_notifies_chat() {
+        let mut manager = health_test_manager("sleep 60", false);
+        manager.config.idle_timeout_secs = 60;
+        let (notify, sent) = recording_callback();
+        manager.set_chat_notifier(notify);
+        let info = manager
+            .new_session("flaky", Some("/tmp"), None)
+            .await
+            .expect("spawn sh");
+        manager.bind_chat(42, &info.session_id).await;
+
+        // Fresh session stays
+        assert_eq!(manager.reap_idle_sessions().await, 0);
+        {
+            let sessions = manager.sessions.read().await;
+            let mut session = sessions[&info.session_id].lock().await;
+            session.last_activity = Instant::now() - Duration::from_secs(61);
+        }
+        assert_eq!(manager.reap_idle_sessions().await, 1);
+        assert!(manager.list_sessions().await.is_empty());
+        assert_eq!(manager.chat_session(42).await, None);
+        let sent = sent.lock().unwrap();
+        assert_eq!(sent.len(), 1);
+        assert!(sent[0].contains("without activity"), "{}", sent[0]);
+    }
+
+    #[tokio::test]
+    async fn test_health_check_auto_respawns() {
+        let workspace = temp_workspace();
+        // First run exits immediately, the respawned one stays up
+        let manager = health_test_manager(
+            "if [ -e started ]; then sleep 60; else touch started; fi",
+            true,
+        );
+        let (notify, sent) = recording_callback();
+        manager.set_chat_notifier(notify);
+        let Ok(info) = manager
+            .new_session("flaky", Some(workspace.to_str().unwrap()), None)
+            .await
+        else {
+            return;
+        };
+        manager.bind_chat(42, &info.session_id).await;
+        wait_for_exit(&manager, &info.session_id).await;
+
+        assert_eq!(manager.check_session_health().await, 1);
+        let sessions = manager.list_sessions().await;
+        assert_eq!(sessions[0].status, SessionStatus::Active);
+        assert!(sent.lock().unwrap()[0].contains("restarted"));
+        {
+            let sessions = manager.sessions.read().await;
+            let session = sessions[&info.session_id].lock().await;
+            assert!(session.session_reset);
+            assert!(session.connection.is_alive().await);
+        }
+        manager.cleanup().await;
+        let _ = std::fs::remove_dir_all(&workspace);
+    }
 }
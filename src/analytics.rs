@@ -0,0 +1,463 @@
+//! Per-chat conversation analytics for `/analytics` and `GET /api/analytics`:
+//! messages per day, most active participants, how long replies take, token
+//! spend and which tools ran. Days are counted in the configured timezone.
+//!
+//! Message stats come from the stored history, so they cover whatever the
+//! chat has stored; token and tool figures come from the usage logs.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime, TimeZone, Utc};
+use serde::Serialize;
+
+use crate::chart::{ChartKind, ChartSpec, Series};
+use crate::config::Config;
+use crate::db::{call_blocking, Database, StoredMessage};
+use crate::usage::fmt_int;
+
+pub const DEFAULT_DAYS: u32 = 7;
+pub const MAX_DAYS: u32 = 90;
+/// Messages read per report; beyond this the newest days are incomplete.
+const MAX_MESSAGES: usize = 20_000;
+const TOP_PARTICIPANTS: usize = 5;
+const TOP_TOOLS: usize = 8;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DailyCount {
+    /// Local date, `YYYY-MM-DD`
+    pub date: String,
+    pub user_messages: i64,
+    pub bot_messages: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ParticipantCount {
+    pub name: String,
+    pub messages: i64,
+}
+
+/// Time from the last user message to the bot reply that followed it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LatencyStats {
+    pub replies: usize,
+    pub median_secs: f64,
+    pub p90_secs: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TokenSpend {
+    pub requests: i64,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    pub total_tokens: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ToolCount {
+    pub name: String,
+    pub calls: i64,
+    pub errors: i64,
+    pub avg_duration_ms: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChatAnalytics {
+    pub chat_id: i64,
+    pub days: u32,
+    pub timezone: String,
+    /// Start of the window (local midnight), RFC 3339 in UTC
+    pub since: String,
+    /// Set when the message limit was hit and later days are undercounted
+    pub truncated: bool,
+    pub daily: Vec<DailyCount>,
+    pub participants: Vec<ParticipantCount>,
+    pub latency: Option<LatencyStats>,
+    pub tokens: TokenSpend,
+    pub tools: Vec<ToolCount>,
+}
+
+impl ChatAnalytics {
+    pub fn user_messages(&self) -> i64 {
+        self.daily.iter().map(|d| d.user_messages).sum()
+    }
+
+    pub fn bot_messages(&self) -> i64 {
+        self.daily.iter().map(|d| d.bot_messages).sum()
+    }
+}
+
+/// Parse a `days` argument: 1 to [`MAX_DAYS`].
+pub fn parse_days(s: &str) -> Result<u32, String> {
+    match s.trim().trim_end_matches('d').parse::<u32>() {
+        Ok(days) if (1..=MAX_DAYS).contains(&days) => Ok(days),
+        _ => Err(format!(
+            "'{s}' is not a valid number of days (1-{MAX_DAYS})"
+        )),
+    }
+}
+
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    let rank = ((sorted.len() - 1) as f64 * p).round() as usize;
+    sorted[rank.min(sorted.len() - 1)]
+}
+
+/// Daily counts, top participants and reply latency for messages in
+/// `days` local days starting at `start`. Messages are expected oldest first.
+fn summarize_messages<Tz: TimeZone>(
+    messages: &[StoredMessage],
+    tz: &Tz,
+    start: NaiveDate,
+    days: u32,
+) -> (Vec<DailyCount>, Vec<ParticipantCount>, Option<LatencyStats>) {
+    let mut daily: Vec<DailyCount> = (0..days)
+        .map(|i| DailyCount {
+            date: (start + Duration::days(i as i64)).to_string(),
+            user_messages: 0,
+            bot_messages: 0,
+        })
+        .collect();
+    let mut by_sender: HashMap<&str, i64> = HashMap::new();
+    let mut latencies = Vec::new();
+    let mut last_user_at: Option<DateTime<Utc>> = None;
+
+    for msg in messages {
+        let Ok(at) = DateTime::parse_from_rfc3339(&msg.timestamp) else {
+            continue;
+        };
+        let at = at.with_timezone(&Utc);
+        let day = (at.with_timezone(tz).date_naive() - start).num_days();
+        let Some(bucket) = usize::try_from(day).ok().and_then(|d| daily.get_mut(d)) else {
+            continue;
+        };
+        if msg.is_from_bot {
+            bucket.bot_messages += 1;
+            if let Some(asked) = last_user_at.take() {
+                latencies.push((at - asked).num_milliseconds().max(0) as f64 / 1000.0);
+            }
+        } else {
+            bucket.user_messages += 1;
+            *by_sender.entry(msg.sender_name.as_str()).or_default() += 1;
+            last_user_at = Some(at);
+        }
+    }
+
+    let mut participants: Vec<ParticipantCount> = by_sender
+        .into_iter()
+        .map(|(name, messages)| ParticipantCount {
+            name: name.to_string(),
+            messages,
+        })
+        .collect();
+    participants.sort_by(|a, b| b.messages.cmp(&a.messages).then(a.name.cmp(&b.name)));
+    participants.truncate(TOP_PARTICIPANTS);
+
+    let latency = if latencies.is_empty() {
+        None
+    } else {
+        latencies.sort_by(f64::total_cmp);
+        Some(LatencyStats {
+            replies: latencies.len(),
+            median_secs: percentile(&latencies, 0.5),
+            p90_secs: percentile(&latencies, 0.9),
+        })
+    };
+    (daily, participants, latency)
+}
+
+/// Gather analytics for the last `days` local days of a chat, today included.
+pub async fn build_chat_analytics(
+    db: Arc<Database>,
+    config: &Config,
+    chat_id: i64,
+    days: u32,
+) -> Result<ChatAnalytics, String> {
+    let days = days.clamp(1, MAX_DAYS);
+    let tz: chrono_tz::Tz = config.timezone.parse().unwrap_or(chrono_tz::Tz::UTC);
+    let now = Utc::now();
+    let start = now.with_timezone(&tz).date_naive() - Duration::days(days as i64 - 1);
+    let since = tz
+        .from_local_datetime(&start.and_time(NaiveTime::MIN))
+        .earliest()
+        .map(|t| t.with_timezone(&Utc))
+        .unwrap_or_else(|| now - Duration::days(days as i64))
+        .to_rfc3339();
+
+    let query_since = since.clone();
+    let (messages, usage, tools) = call_blocking(db, move |db| {
+        let messages = db.get_messages_since(chat_id, &query_since, MAX_MESSAGES)?;
+        let usage = db.get_llm_usage_summary_since(Some(chat_id), Some(&query_since))?;
+        let tools = db.get_tool_usage_by_name(chat_id, &query_since)?;
+        Ok((messages, usage, tools))
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let (daily, participants, latency) = summarize_messages(&messages, &tz, start, days);
+    Ok(ChatAnalytics {
+        chat_id,
+        days,
+        timezone: tz.name().to_string(),
+        since,
+        truncated: messages.len() >= MAX_MESSAGES,
+        daily,
+        participants,
+        latency,
+        tokens: TokenSpend {
+            requests: usage.requests,
+            input_tokens: usage.input_tokens,
+            output_tokens: usage.output_tokens,
+            total_tokens: usage.total_tokens,
+        },
+        tools: tools
+            .into_iter()
+            .map(|t| ToolCount {
+                name: t.tool_name,
+                calls: t.calls,
+                errors: t.errors,
+                avg_duration_ms: t.avg_duration_ms,
+            })
+            .collect(),
+    })
+}
+
+fn fmt_secs(secs: f64) -> String {
+    if secs < 60.0 {
+        format!("{secs:.1}s")
+    } else if secs < 3600.0 {
+        format!("{:.1}m", secs / 60.0)
+    } else {
+        format!("{:.1}h", secs / 3600.0)
+    }
+}
+
+pub fn format_report(a: &ChatAnalytics) -> String {
+    let plural = if a.days == 1 { "" } else { "s" };
+    let mut lines = vec![
+        format!(
+            "📈 Chat analytics — last {} day{plural} ({})",
+            a.days, a.timezone
+        ),
+        "".to_string(),
+        format!(
+            "💬 Messages: {} (user {} / bot {})",
+            fmt_int(a.user_messages() + a.bot_messages()),
+            fmt_int(a.user_messages()),
+            fmt_int(a.bot_messages())
+        ),
+    ];
+    for d in &a.daily {
+        lines.push(format!(
+            "    {}  user {:>4}  bot {:>4}",
+            d.date, d.user_messages, d.bot_messages
+        ));
+    }
+    if a.truncated {
+        lines.push(format!(
+            "    (only the first {} messages were counted)",
+            fmt_int(MAX_MESSAGES as i64)
+        ));
+    }
+
+    lines.push("".to_string());
+    lines.push("👥 Top participants".to_string());
+    if a.participants.is_empty() {
+        lines.push("    - (no data)".to_string());
+    }
+    for (i, p) in a.participants.iter().enumerate() {
+        lines.push(format!(
+            "    {}. {}  {}",
+            i + 1,
+            p.name,
+            fmt_int(p.messages)
+        ));
+    }
+
+    lines.push("".to_string());
+    lines.push(match &a.latency {
+        Some(l) => format!(
+            "⏱ Response time: median {}, p90 {} ({} replies)",
+            fmt_secs(l.median_secs),
+            fmt_secs(l.p90_secs),
+            fmt_int(l.replies as i64)
+        ),
+        None => "⏱ Response time: (no replies)".to_string(),
+    });
+    lines.push(format!(
+        "🧮 Tokens: {} (in {} / out {}) over {} requests",
+        fmt_int(a.tokens.total_tokens),
+        fmt_int(a.tokens.input_tokens),
+        fmt_int(a.tokens.output_tokens),
+        fmt_int(a.tokens.requests)
+    ));
+
+    lines.push("".to_string());
+    lines.push("🛠 Tools".to_string());
+    if a.tools.is_empty() {
+        lines.push("    - (no data)".to_string());
+    }
+    for (i, t) in a.tools.iter().take(TOP_TOOLS).enumerate() {
+        lines.push(format!(
+            "    {}. {}  calls={}  errors={}  avg={}",
+            i + 1,
+            t.name,
+            fmt_int(t.calls),
+            fmt_int(t.errors),
+            fmt_secs(t.avg_duration_ms as f64 / 1000.0)
+        ));
+    }
+    if a.tools.len() > TOP_TOOLS {
+        lines.push(format!("    ... and {} more", a.tools.len() - TOP_TOOLS));
+    }
+    lines.join("\n")
+}
+
+/// Bar chart of user and bot messages per day.
+pub fn messages_chart(a: &ChatAnalytics) -> ChartSpec {
+    ChartSpec {
+        kind: ChartKind::Bar,
+        title: Some(format!("Messages per day (last {} days)", a.days)),
+        x_label: None,
+        y_label: Some("messages".into()),
+        labels: a
+            .daily
+            .iter()
+            .map(|d| d.date.get(5..).unwrap_or(&d.date).to_string())
+            .collect(),
+        series: vec![
+            Series {
+                name: "user".into(),
+                values: a.daily.iter().map(|d| d.user_messages as f64).collect(),
+            },
+            Series {
+                name: "bot".into(),
+                values: a.daily.iter().map(|d| d.bot_messages as f64).collect(),
+            },
+        ],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn msg(sender: &str, is_from_bot: bool, timestamp: &str) -> StoredMessage {
+        StoredMessage {
+            id: uuid::Uuid::new_v4().to_string(),
+            chat_id: 1,
+            sender_name: sender.into(),
+            content: "hi".into(),
+            is_from_bot,
+            timestamp: timestamp.into(),
+            thread_id: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_days() {
+        assert_eq!(parse_days("30"), Ok(30));
+        assert_eq!(parse_days("7d"), Ok(7));
+        assert!(parse_days("0").is_err());
+        assert!(parse_days("91").is_err());
+        assert!(parse_days("week").is_err());
+    }
+
+    #[test]
+    fn test_summarize_messages() {
+        let tz: chrono_tz::Tz = "Europe/Berlin".parse().unwrap();
+        let start = NaiveDate::from_ymd_opt(2026, 3, 1).unwrap();
+        let messages = vec![
+            // 2026-02-28 23:30 UTC is already March 1st in Berlin
+            msg("alice", false, "2026-02-28T23:30:00+00:00"),
+            msg("bot", true, "2026-02-28T23:30:04+00:00"),
+            msg("bob", false, "2026-03-02T10:00:00+00:00"),
+            msg("alice", false, "2026-03-02T10:00:30+00:00"),
+            msg("bot", true, "2026-03-02T10:00:40+00:00"),
+            // scheduled output with no question before it
+            msg("bot", true, "2026-03-02T12:00:00+00:00"),
+            msg("alice", false, "not a timestamp"),
+            msg("carol", false, "2026-03-09T10:00:00+00:00"),
+        ];
+        let (daily, participants, latency) = summarize_messages(&messages, &tz, start, 3);
+
+        assert_eq!(daily.len(), 3);
+        assert_eq!(daily[0].date, "2026-03-01");
+        assert_eq!((daily[0].user_messages, daily[0].bot_messages), (1, 1));
+        assert_eq!((daily[1].user_messages, daily[1].bot_messages), (2, 2));
+        assert_eq!((daily[2].user_messages, daily[2].bot_messages), (0, 0));
+
+        assert_eq!(
+            participants,
+            vec![
+                ParticipantCount {
+                    name: "alice".into(),
+                    messages: 2
+                },
+                ParticipantCount {
+                    name: "bob".into(),
+                    messages: 1
+                },
+            ]
+        );
+
+        let latency = latency.unwrap();
+        assert_eq!(latency.replies, 2);
+        assert_eq!(latency.p90_secs, 10.0);
+        assert!(summarize_messages(&[], &tz, start, 3).2.is_none());
+    }
+
+    #[test]
+    fn test_format_report_and_chart() {
+        let analytics = ChatAnalytics {
+            chat_id: 1,
+            days: 2,
+            timezone: "UTC".into(),
+            since: "2026-03-01T00:00:00+00:00".into(),
+            truncated: false,
+            daily: vec![
+                DailyCount {
+                    date: "2026-03-01".into(),
+                    user_messages: 3,
+                    bot_messages: 2,
+                },
+                DailyCount {
+                    date: "2026-03-02".into(),
+                    user_messages: 1000,
+                    bot_messages: 0,
+                },
+            ],
+            participants: vec![ParticipantCount {
+                name: "alice".into(),
+                messages: 1003,
+            }],
+            latency: Some(LatencyStats {
+                replies: 2,
+                median_secs: 4.0,
+                p90_secs: 90.0,
+            }),
+            tokens: TokenSpend {
+                requests: 2,
+                input_tokens: 1500,
+                output_tokens: 500,
+                total_tokens: 2000,
+            },
+            tools: vec![ToolCount {
+                name: "bash".into(),
+                calls: 3,
+                errors: 1,
+                avg_duration_ms: 300,
+            }],
+        };
+        let report = format_report(&analytics);
+        assert!(report.contains("💬 Messages: 1,005 (user 1,003 / bot 2)"));
+        assert!(report.contains("1. alice  1,003"));
+        assert!(report.contains("median 4.0s, p90 1.5m (2 replies)"));
+        assert!(report.contains("🧮 Tokens: 2,000 (in 1,500 / out 500) over 2 requests"));
+        assert!(report.contains("1. bash  calls=3  errors=1  avg=0.3s"));
+
+        let chart = messages_chart(&analytics);
+        assert!(chart.validate().is_ok());
+        assert_eq!(chart.labels, vec!["03-01", "03-02"]);
+        assert_eq!(chart.series[1].values, vec![2.0, 0.0]);
+    }
+}
@@ -0,0 +1,352 @@
+//! Interactive approval of high-risk tool calls (`bash`, ACP prompts) in
+//! control chats on channels that can show buttons. The prompt goes to the
+//! chat and the tool call waits for an Approve/Deny button press, or a typed
+//! "approve"/"deny", until `tool_approval_timeout_secs` runs out.
+//!
+//! Channels recognise prompts with [`prompt_id`] and attach buttons whose
+//! callback data comes from [`callback_data`]; presses come back through
+//! [`ToolApprovals::resolve_callback`].
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::time::Duration;
+
+use tokio::sync::{oneshot, Mutex};
+use tracing::warn;
+
+const PROMPT_PREFIX: &str = "[Approval ";
+const CALLBACK_PREFIX: &str = "rayclaw_approval:";
+const MAX_INPUT_CHARS: usize = 1500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApprovalDecision {
+    Approve,
+    Deny,
+    /// No answer before the approval timeout
+    Expired,
+}
+
+impl ApprovalDecision {
+    /// Parse a chat reply such as "approve", "yes" or "#deny".
+    /// `@mention` tokens are ignored so group replies to the bot also match.
+    pub fn parse_reply(text: &str) -> Option<Self> {
+        let words: Vec<String> = text
+            .split_whitespace()
+            .filter(|w| !w.starts_with('@'))
+            .map(|w| {
+                w.trim_start_matches('#')
+                    .trim_end_matches(['.', '!'])
+                    .to_lowercase()
+            })
+            .collect();
+        let words: Vec<&str> = words.iter().map(String::as_str).collect();
+        match words.as_slice() {
+            ["approve" | "allow" | "yes" | "y"] => Some(Self::Approve),
+            ["deny" | "reject" | "no" | "n"] => Some(Self::Deny),
+            _ => None,
+        }
+    }
+
+    /// Status line channels put under an answered prompt.
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Approve => "Approved",
+            Self::Deny => "Denied",
+            Self::Expired => "Expired",
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Approve => "approve",
+            Self::Deny => "deny",
+            Self::Expired => "expired",
+        }
+    }
+}
+
+/// Button payload for answering approval `id`, e.g. `rayclaw_approval:3f9a2c1d:approve`.
+pub fn callback_data(id: &str, decision: ApprovalDecision) -> String {
+    format!("{CALLBACK_PREFIX}{id}:{}", decision.as_str())
+}
+
+/// Approval id and decision from a button payload made by [`callback_data`].
+pub fn parse_callback_data(data: &str) -> Option<(&str, ApprovalDecision)> {
+    let (id, decision) = data.strip_prefix(CALLBACK_PREFIX)?.split_once(':')?;
+    let decision = match decision {
+        "approve" => ApprovalDecision::Approve,
+        "deny" => ApprovalDecision::Deny,
+        _ => return None,
+    };
+    (!id.is_empty()).then_some((id, decision))
+}
+
+/// The approval id if `text` is a prompt made by [`ToolApprovals::request`].
+pub fn prompt_id(text: &str) -> Option<&str> {
+    let (id, rest) = text.strip_prefix(PROMPT_PREFIX)?.split_once("] ")?;
+    (!id.is_empty() && !id.contains(char::is_whitespace) && rest.starts_with("Run ")).then_some(id)
+}
+
+/// `prompt` as channels redraw it once a button is pressed: the buttons go,
+/// and a line says who answered. `decision` is `None` for a stale prompt.
+pub fn answered_prompt(prompt: &str, decision: Option<ApprovalDecision>, by: &str) -> String {
+    match decision {
+        Some(decision) => format!("{prompt}\n\n{} by {by}", decision.label()),
+        None => format!("{prompt}\n\n{}", ApprovalDecision::Expired.label()),
+    }
+}
+
+fn format_prompt(id: &str, tool_name: &str, input: &str, timeout: Duration) -> String {
+    let mut input = input.to_string();
+    if input.chars().count() > MAX_INPUT_CHARS {
+        input = input.chars().take(MAX_INPUT_CHARS).collect::<String>() + "\n…";
+    }
+    let mut text = format!("{PROMPT_PREFIX}{id}] Run high-risk tool `{tool_name}`?");
+    if !input.is_empty() {
+        text.push_str(&format!("\n{input}"));
+    }
+    text.push_str(&format!(
+        "\n\nTap Approve or Deny, or reply \"approve\" or \"deny\" within {}s.",
+        timeout.as_secs()
+    ));
+    text
+}
+
+struct PendingApproval {
+    id: String,
+    tx: oneshot::Sender<ApprovalDecision>,
+}
+
+/// High-risk tool calls waiting for an answer, at most one per chat.
+pub struct ToolApprovals {
+    timeout: Duration,
+    pending: Mutex<HashMap<i64, PendingApproval>>,
+}
+
+impl ToolApprovals {
+    pub fn new(timeout: Duration) -> Self {
+        ToolApprovals {
+            timeout,
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Post an approval prompt for `tool_name` to `chat_id` via `notify` and
+    /// wait for the answer. `input` is the (redacted) tool input shown in the
+    /// prompt. Fails only if the prompt could not be delivered.
+    pub async fn request<F, Fut>(
+        &self,
+        chat_id: i64,
+        tool_name: &str,
+        input: &str,
+        notify: F,
+    ) -> Result<ApprovalDecision, String>
+    where
+        F: FnOnce(String) -> Fut,
+        Fut: Future<Output = Result<(), String>>,
+    {
+        let id: String = uuid::Uuid::new_v4()
+            .simple()
+            .to_string()
+            .chars()
+            .take(8)
+            .collect();
+        let (tx, rx) = oneshot::channel();
+        // A newer request replaces (and thereby denies) any stale one
+        self.pending
+            .lock()
+            .await
+            .insert(chat_id, PendingApproval { id: id.clone(), tx });
+
+        if let Err(e) = notify(format_prompt(&id, tool_name, input, self.timeout)).await {
+            self.remove(chat_id, &id).await;
+            return Err(e);
+        }
+
+        match tokio::time::timeout(self.timeout, rx).await {
+            Ok(Ok(decision)) => Ok(decision),
+            Ok(Err(_)) => Ok(ApprovalDecision::Deny),
+            Err(_) => {
+                self.remove(chat_id, &id).await;
+                warn!("Approval {id} for tool '{tool_name}' in chat {chat_id} timed out");
+                Ok(ApprovalDecision::Expired)
+            }
+        }
+    }
+
+    async fn remove(&self, chat_id: i64, id: &str) {
+        let mut pending = self.pending.lock().await;
+        if pending.get(&chat_id).is_some_and(|p| p.id == id) {
+            pending.remove(&chat_id);
+        }
+    }
+
+    /// Whether a tool call is waiting for approval in this chat.
+    pub async fn has_pending(&self, chat_id: i64) -> bool {
+        self.pending.lock().await.contains_key(&chat_id)
+    }
+
+    /// Answer the pending approval in a chat with a typed reply. Returns the
+    /// decision if `text` was an approve/deny reply and a call was waiting,
+    /// `None` otherwise (the message should be handled normally).
+    pub async fn resolve_reply(&self, chat_id: i64, text: &str) -> Option<ApprovalDecision> {
+        let decision = ApprovalDecision::parse_reply(text)?;
+        let pending = self.pending.lock().await.remove(&chat_id)?;
+        pending.tx.send(decision).ok()?;
+        Some(decision)
+    }
+
+    /// Answer the approval a button press refers to. Returns the decision
+    /// if that approval was still waiting, `None` for stale or foreign buttons.
+    pub async fn resolve_callback(&self, data: &str) -> Option<ApprovalDecision> {
+        let (id, decision) = parse_callback_data(data)?;
+        let pending = {
+            let mut pending = self.pending.lock().await;
+            let chat_id = pending
+                .iter()
+                .find(|(_, p)| p.id == id)
+                .map(|(chat_id, _)| *chat_id)?;
+            pending.remove(&chat_id)?
+        };
+        pending.tx.send(decision).ok()?;
+        Some(decision)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_parse_reply() {
+        assert_eq!(
+            ApprovalDecision::parse_reply("Approve!"),
+            Some(ApprovalDecision::Approve)
+        );
+        assert_eq!(
+            ApprovalDecision::parse_reply("@rayclaw_bot #deny"),
+            Some(ApprovalDecision::Deny)
+        );
+        assert_eq!(ApprovalDecision::parse_reply("approve this please"), None);
+    }
+
+    #[test]
+    fn test_callback_data_round_trip() {
+        let data = callback_data("3f9a2c1d", ApprovalDecision::Approve);
+        assert_eq!(data, "rayclaw_approval:3f9a2c1d:approve");
+        assert_eq!(
+            parse_callback_data(&data),
+            Some(("3f9a2c1d", ApprovalDecision::Approve))
+        );
+        assert_eq!(
+            parse_callback_data("rayclaw_approval:3f9a2c1d:expired"),
+            None
+        );
+        assert_eq!(parse_callback_data("rayclaw_approval::deny"), None);
+        assert_eq!(parse_callback_data("other:3f9a2c1d:deny"), None);
+    }
+
+    #[test]
+    fn test_prompt_id() {
+        let prompt = format_prompt(
+            "3f9a2c1d",
+            "bash",
+            "{\"command\": \"ls\"}",
+            Duration::from_secs(300),
+        );
+        assert!(prompt.starts_with("[Approval 3f9a2c1d] Run high-risk tool `bash`?\n"));
+        assert!(prompt.ends_with("within 300s."));
+        assert_eq!(prompt_id(&prompt), Some("3f9a2c1d"));
+        assert_eq!(prompt_id("[Approval needed] for what?"), None);
+        assert!(
+            answered_prompt(&prompt, Some(ApprovalDecision::Deny), "ray")
+                .ends_with("\n\nDenied by ray")
+        );
+        assert!(answered_prompt(&prompt, None, "ray").ends_with("within 300s.\n\nExpired"));
+        assert_eq!(prompt_id("[ACP claude] Permission requested: Edit"), None);
+    }
+
+    #[tokio::test]
+    async fn test_request_resolved_by_button() {
+        let approvals = Arc::new(ToolApprovals::new(Duration::from_secs(5)));
+        let (sent_tx, sent_rx) = oneshot::channel();
+        let waiting = {
+            let approvals = approvals.clone();
+            tokio::spawn(async move {
+                approvals
+                    .request(7, "bash", "ls", |text| async move {
+                        let _ = sent_tx.send(text);
+                        Ok(())
+                    })
+                    .await
+            })
+        };
+        let prompt = sent_rx.await.unwrap();
+        let id = prompt_id(&prompt).unwrap();
+        assert!(approvals.has_pending(7).await);
+
+        assert_eq!(
+            approvals
+                .resolve_callback(&callback_data("0000", ApprovalDecision::Approve))
+                .await,
+            None
+        );
+        assert_eq!(
+            approvals
+                .resolve_callback(&callback_data(id, ApprovalDecision::Approve))
+                .await,
+            Some(ApprovalDecision::Approve)
+        );
+        assert_eq!(waiting.await.unwrap(), Ok(ApprovalDecision::Approve));
+        assert!(!approvals.has_pending(7).await);
+        // A second press on the same buttons finds nothing to answer
+        assert_eq!(
+            approvals
+                .resolve_callback(&callback_data(id, ApprovalDecision::Deny))
+                .await,
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_request_resolved_by_reply() {
+        let approvals = Arc::new(ToolApprovals::new(Duration::from_secs(5)));
+        let waiting = {
+            let approvals = approvals.clone();
+            tokio::spawn(
+                async move { approvals.request(7, "bash", "", |_| async { Ok(()) }).await },
+            )
+        };
+        while !approvals.has_pending(7).await {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(approvals.resolve_reply(8, "deny").await, None);
+        assert_eq!(approvals.resolve_reply(7, "what?").await, None);
+        assert_eq!(
+            approvals.resolve_reply(7, "no").await,
+            Some(ApprovalDecision::Deny)
+        );
+        assert_eq!(waiting.await.unwrap(), Ok(ApprovalDecision::Deny));
+    }
+
+    #[tokio::test]
+    async fn test_request_expires_and_delivery_failure() {
+        let approvals = ToolApprovals::new(Duration::from_millis(20));
+        assert_eq!(
+            approvals.request(7, "bash", "", |_| async { Ok(()) }).await,
+            Ok(ApprovalDecision::Expired)
+        );
+        assert!(!approvals.has_pending(7).await);
+
+        let failed = approvals
+            .request(7, "bash", "", |_| async { Err("chat gone".to_string()) })
+            .await;
+        assert_eq!(failed, Err("chat gone".to_string()));
+        assert!(!approvals.has_pending(7).await);
+    }
+}
@@ -0,0 +1,798 @@
+//! Credentials from AWS shared config profiles (`~/.aws/credentials` and
+//! `~/.aws/config`), resolved the way the AWS CLI and SDKs do:
+//!
+//! - `role_arn` with `source_profile` or `credential_source`: STS
+//!   AssumeRole, chaining through source profiles,
+//! - `sso_session` / `sso_start_url`: IAM Identity Center role credentials,
+//!   using (and refreshing) the token `aws sso login` cached,
+//! - `credential_process`: an external command printing credentials JSON,
+//! - `aws_access_key_id` / `aws_secret_access_key`: static keys.
+//!
+//! Temporary credentials carry their expiry; the Bedrock provider re-runs
+//! the profile shortly before it (see `AwsCredentials::refresh`).
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+
+use chrono::{DateTime, Utc};
+
+use crate::error::RayClawError;
+
+/// Longest chain of `source_profile` hops before giving up.
+const MAX_PROFILE_CHAIN: usize = 8;
+
+const DEFAULT_STS_REGION: &str = "us-east-1";
+
+/// Credentials a profile resolved to.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ProfileCredentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub session_token: Option<String>,
+    /// Set for temporary credentials (AssumeRole, SSO, most processes)
+    pub expiration: Option<DateTime<Utc>>,
+}
+
+/// Locations of the shared AWS files.
+pub(crate) struct AwsConfigFiles {
+    pub credentials: PathBuf,
+    pub config: PathBuf,
+    /// Token cache written by `aws sso login`
+    pub sso_cache: PathBuf,
+}
+
+impl AwsConfigFiles {
+    /// The standard locations, honouring `AWS_SHARED_CREDENTIALS_FILE` and
+    /// `AWS_CONFIG_FILE`.
+    pub fn locate() -> Self {
+        let aws_dir = crate::platform::home_dir().unwrap_or_default().join(".aws");
+        let env_path = |var: &str| {
+            std::env::var(var)
+                .ok()
+                .filter(|s| !s.trim().is_empty())
+                .map(PathBuf::from)
+        };
+        AwsConfigFiles {
+            credentials: env_path("AWS_SHARED_CREDENTIALS_FILE")
+                .unwrap_or_else(|| aws_dir.join("credentials")),
+            config: env_path("AWS_CONFIG_FILE").unwrap_or_else(|| aws_dir.join("config")),
+            sso_cache: aws_dir.join("sso").join("cache"),
+        }
+    }
+
+    /// Settings of `profile`: its `~/.aws/config` section overlaid with its
+    /// `~/.aws/credentials` section.
+    pub fn profile(&self, profile: &str) -> HashMap<String, String> {
+        let config_header = if profile == "default" {
+            "default".to_string()
+        } else {
+            format!("profile {profile}")
+        };
+        let mut settings = read_section(&self.config, &config_header);
+        settings.extend(read_section(&self.credentials, profile));
+        settings
+    }
+
+    fn sso_session(&self, name: &str) -> HashMap<String, String> {
+        read_section(&self.config, &format!("sso-session {name}"))
+    }
+}
+
+/// Key/value pairs of the `[header]` section of an INI file. Keys are
+/// lowercased; comments and blank lines are skipped.
+fn read_section(path: &Path, header: &str) -> HashMap<String, String> {
+    let Ok(content) = std::fs::read_to_string(path) else {
+        return HashMap::new();
+    };
+    let mut in_section = false;
+    let mut values = HashMap::new();
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            in_section = name.split_whitespace().collect::<Vec<_>>().join(" ") == header;
+            continue;
+        }
+        if in_section {
+            if let Some((key, value)) = line.split_once('=') {
+                values.insert(key.trim().to_lowercase(), value.trim().to_string());
+            }
+        }
+    }
+    values
+}
+
+fn setting<'a>(settings: &'a HashMap<String, String>, key: &str) -> Option<&'a str> {
+    settings
+        .get(key)
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty())
+}
+
+fn config_error(message: impl Into<String>) -> RayClawError {
+    RayClawError::Config(message.into())
+}
+
+/// Credentials for `profile`, or `None` when it has no credential settings
+/// (the caller then falls back to instance metadata).
+pub(crate) async fn resolve_profile(
+    files: &AwsConfigFiles,
+    profile: &str,
+) -> Result<Option<ProfileCredentials>, RayClawError> {
+    resolve_chain(files, profile, &mut Vec::new()).await
+}
+
+type ResolveFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<Option<ProfileCredentials>, RayClawError>> + Send + 'a>>;
+
+/// Boxed so a profile can recurse into its `source_profile`.
+fn resolve_chain<'a>(
+    files: &'a AwsConfigFiles,
+    profile: &'a str,
+    visited: &'a mut Vec<String>,
+) -> ResolveFuture<'a> {
+    Box::pin(async move {
+        if visited.iter().any(|p| p == profile) {
+            visited.push(profile.to_string());
+            return Err(config_error(format!(
+                "AWS profile chain loops: {}",
+                visited.join(" -> ")
+            )));
+        }
+        if visited.len() >= MAX_PROFILE_CHAIN {
+            return Err(config_error(format!(
+                "AWS profile chain from {} is longer than {MAX_PROFILE_CHAIN} profiles",
+                visited[0]
+            )));
+        }
+        visited.push(profile.to_string());
+        let settings = files.profile(profile);
+
+        if let Some(role_arn) = setting(&settings, "role_arn") {
+            if setting(&settings, "mfa_serial").is_some() {
+                return Err(config_error(format!(
+                    "AWS profile {profile} requires MFA to assume {role_arn}, which can't be \
+                     prompted for; use SSO or credential_process instead"
+                )));
+            }
+            let source = match (
+                setting(&settings, "source_profile"),
+                setting(&settings, "credential_source"),
+            ) {
+                // A profile may name itself to use its own static keys
+                (Some(source), _) if source == profile => {
+                    static_keys(&settings).ok_or_else(|| {
+                        config_error(format!(
+                            "AWS profile {profile} has no static keys to assume {role_arn} with"
+                        ))
+                    })?
+                }
+                (Some(source), _) => {
+                    resolve_chain(files, source, visited)
+                        .await?
+                        .ok_or_else(|| {
+                            config_error(format!(
+                                "AWS profile {source} (source_profile of {profile}) \
+                                 has no credentials"
+                            ))
+                        })?
+                }
+                (None, Some(source)) => credential_source(source).await?,
+                (None, None) => {
+                    return Err(config_error(format!(
+                        "AWS profile {profile} sets role_arn but neither source_profile nor \
+                         credential_source"
+                    )))
+                }
+            };
+            let region = setting(&settings, "region").unwrap_or(DEFAULT_STS_REGION);
+            let request = AssumeRoleRequest {
+                role_arn,
+                session_name: setting(&settings, "role_session_name")
+                    .map(str::to_string)
+                    .unwrap_or_else(|| format!("rayclaw-{}", Utc::now().timestamp())),
+                external_id: setting(&settings, "external_id"),
+                duration_seconds: setting(&settings, "duration_seconds")
+                    .and_then(|d| d.parse().ok()),
+            };
+            return assume_role(&source, region, &request).await.map(Some);
+        }
+
+        if setting(&settings, "sso_session").is_some()
+            || setting(&settings, "sso_start_url").is_some()
+        {
+            return sso_credentials(files, profile, &settings).await.map(Some);
+        }
+
+        if let Some(command) = setting(&settings, "credential_process") {
+            return credential_process(command).await.map(Some);
+        }
+
+        Ok(static_keys(&settings))
+    })
+}
+
+fn static_keys(settings: &HashMap<String, String>) -> Option<ProfileCredentials> {
+    Some(ProfileCredentials {
+        access_key_id: setting(settings, "aws_access_key_id")?.to_string(),
+        secret_access_key: setting(settings, "aws_secret_access_key")?.to_string(),
+        session_token: setting(settings, "aws_session_token").map(str::to_string),
+        expiration: None,
+    })
+}
+
+/// Source credentials named by a profile's `credential_source`.
+async fn credential_source(source: &str) -> Result<ProfileCredentials, RayClawError> {
+    match source {
+        "Environment" => {
+            let var = |name: &str| std::env::var(name).ok().filter(|v| !v.trim().is_empty());
+            match (var("AWS_ACCESS_KEY_ID"), var("AWS_SECRET_ACCESS_KEY")) {
+                (Some(access_key_id), Some(secret_access_key)) => Ok(ProfileCredentials {
+                    access_key_id,
+                    secret_access_key,
+                    session_token: var("AWS_SESSION_TOKEN"),
+                    expiration: None,
+                }),
+                _ => Err(config_error(
+                    "credential_source = Environment but AWS_ACCESS_KEY_ID/AWS_SECRET_ACCESS_KEY are not set",
+                )),
+            }
+        }
+        "Ec2InstanceMetadata" => {
+            let imds = crate::llm_bedrock::fetch_imds_credentials().await?;
+            Ok(ProfileCredentials {
+                access_key_id: imds.access_key_id,
+                secret_access_key: imds.secret_access_key,
+                session_token: Some(imds.session_token),
+                expiration: imds.expiration,
+            })
+        }
+        "EcsContainer" => ecs_container_credentials().await,
+        other => Err(config_error(format!(
+            "Unsupported credential_source {other:?} (expected Environment, Ec2InstanceMetadata or EcsContainer)"
+        ))),
+    }
+}
+
+/// Task role credentials from the ECS/EKS container credentials endpoint.
+async fn ecs_container_credentials() -> Result<ProfileCredentials, RayClawError> {
+    let url = match (
+        std::env::var("AWS_CONTAINER_CREDENTIALS_RELATIVE_URI"),
+        std::env::var("AWS_CONTAINER_CREDENTIALS_FULL_URI"),
+    ) {
+        (Ok(relative), _) => format!("http://169.254.170.2{relative}"),
+        (_, Ok(full)) => full,
+        _ => return Err(config_error(
+            "credential_source = EcsContainer but no AWS_CONTAINER_CREDENTIALS_* variable is set",
+        )),
+    };
+    let mut request = http_client()?.get(&url);
+    if let Ok(token) = std::env::var("AWS_CONTAINER_AUTHORIZATION_TOKEN") {
+        request = request.header("Authorization", token);
+    }
+    let body: serde_json::Value = async { request.send().await?.error_for_status()?.json().await }
+        .await
+        .map_err(|e: reqwest::Error| {
+            config_error(format!("Container credentials request failed: {e}"))
+        })?;
+    parse_process_output(&body)
+}
+
+fn http_client() -> Result<reqwest::Client, RayClawError> {
+    reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(10))
+        .build()
+        .map_err(|e| config_error(format!("AWS credentials HTTP client error: {e}")))
+}
+
+// ---------------------------------------------------------------------------
+// credential_process
+// ---------------------------------------------------------------------------
+
+async fn credential_process(command: &str) -> Result<ProfileCredentials, RayClawError> {
+    let spec = crate::tools::command_runner::shell_command(command);
+    let output = crate::tools::command_runner::build_command(&spec, None)
+        .output()
+        .await
+        .map_err(|e| config_error(format!("credential_process failed to start: {e}")))?;
+    if !output.status.success() {
+        return Err(config_error(format!(
+            "credential_process exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    let body: serde_json::Value = serde_json::from_slice(&output.stdout)
+        .map_err(|e| config_error(format!("credential_process printed invalid JSON: {e}")))?;
+    if body.get("Version").and_then(|v| v.as_i64()) != Some(1) {
+        return Err(config_error(
+            "credential_process output must have \"Version\": 1",
+        ));
+    }
+    parse_process_output(&body)
+}
+
+/// Credentials JSON in the `credential_process` / container endpoint shape
+/// (`AccessKeyId`, `SecretAccessKey`, `SessionToken` or `Token`,
+/// `Expiration`).
+fn parse_process_output(body: &serde_json::Value) -> Result<ProfileCredentials, RayClawError> {
+    let field = |name: &str| {
+        body.get(name)
+            .and_then(|v| v.as_str())
+            .filter(|v| !v.is_empty())
+            .map(str::to_string)
+    };
+    let (Some(access_key_id), Some(secret_access_key)) =
+        (field("AccessKeyId"), field("SecretAccessKey"))
+    else {
+        return Err(config_error(
+            "Credentials response is missing AccessKeyId or SecretAccessKey",
+        ));
+    };
+    Ok(ProfileCredentials {
+        access_key_id,
+        secret_access_key,
+        session_token: field("SessionToken").or_else(|| field("Token")),
+        expiration: field("Expiration").and_then(|e| parse_timestamp(&e)),
+    })
+}
+
+fn parse_timestamp(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value.trim())
+        .ok()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+// ---------------------------------------------------------------------------
+// STS AssumeRole
+// ---------------------------------------------------------------------------
+
+struct AssumeRoleRequest<'a> {
+    role_arn: &'a str,
+    session_name: String,
+    external_id: Option<&'a str>,
+    duration_seconds: Option<u32>,
+}
+
+fn sts_endpoint(region: &str) -> String {
+    let domain = if region.starts_with("cn-") {
+        "amazonaws.com.cn"
+    } else {
+        "amazonaws.com"
+    };
+    format!("https://sts.{region}.{domain}/")
+}
+
+fn assume_role_body(request: &AssumeRoleRequest) -> String {
+    let mut params = vec![
+        ("Action", "AssumeRole".to_string()),
+        ("Version", "2011-06-15".to_string()),
+        ("RoleArn", request.role_arn.to_string()),
+        ("RoleSessionName", request.session_name.clone()),
+    ];
+    if let Some(external_id) = request.external_id {
+        params.push(("ExternalId", external_id.to_string()));
+    }
+    if let Some(duration) = request.duration_seconds {
+        params.push(("DurationSeconds", duration.to_string()));
+    }
+    params
+        .iter()
+        .map(|(k, v)| format!("{k}={}", urlencoding::encode(v)))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+async fn assume_role(
+    source: &ProfileCredentials,
+    region: &str,
+    request: &AssumeRoleRequest<'_>,
+) -> Result<ProfileCredentials, RayClawError> {
+    let endpoint = sts_endpoint(region);
+    let url: reqwest::Url = endpoint
+        .parse()
+        .map_err(|e| config_error(format!("Invalid STS endpoint {endpoint}: {e}")))?;
+    let body = assume_role_body(request);
+    let headers = crate::llm_bedrock::sign_request(
+        "POST",
+        &url,
+        body.as_bytes(),
+        region,
+        "sts",
+        &source.access_key_id,
+        &source.secret_access_key,
+        source.session_token.as_deref(),
+        &Utc::now(),
+    );
+    let mut builder = http_client()?
+        .post(url)
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body(body);
+    for (key, value) in headers {
+        builder = builder.header(key, value);
+    }
+    let response = builder
+        .send()
+        .await
+        .map_err(|e| config_error(format!("STS AssumeRole request failed: {e}")))?;
+    let status = response.status();
+    let text = response.text().await.unwrap_or_default();
+    if !status.is_success() {
+        let message = xml_tag(&text, "Message").unwrap_or(text.trim());
+        return Err(config_error(format!(
+            "STS AssumeRole {} failed ({status}): {message}",
+            request.role_arn
+        )));
+    }
+    parse_assume_role_response(&text)
+}
+
+fn parse_assume_role_response(xml: &str) -> Result<ProfileCredentials, RayClawError> {
+    let credentials = xml_tag(xml, "Credentials")
+        .ok_or_else(|| config_error("STS AssumeRole response has no Credentials"))?;
+    let field = |tag: &str| {
+        xml_tag(credentials, tag)
+            .map(str::to_string)
+            .ok_or_else(|| config_error(format!("STS AssumeRole response is missing {tag}")))
+    };
+    Ok(ProfileCredentials {
+        access_key_id: field("AccessKeyId")?,
+        secret_access_key: field("SecretAccessKey")?,
+        session_token: Some(field("SessionToken")?),
+        expiration: xml_tag(credentials, "Expiration").and_then(parse_timestamp),
+    })
+}
+
+/// Text between the first `<tag>` and the following `</tag>`.
+fn xml_tag<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let open = format!("<{tag}>");
+    let close = format!("</{tag}>");
+    let start = xml.find(&open)? + open.len();
+    let end = start + xml[start..].find(&close)?;
+    Some(xml[start..end].trim())
+}
+
+// ---------------------------------------------------------------------------
+// IAM Identity Center (SSO)
+// ---------------------------------------------------------------------------
+
+/// The cached access token for `start_url`, as written by `aws sso login`.
+struct SsoToken {
+    path: PathBuf,
+    cache: serde_json::Value,
+    access_token: String,
+    expires_at: DateTime<Utc>,
+}
+
+/// The newest cached token for `start_url`. Cache files are named by a
+/// hash of the session name or start URL, so match on contents instead.
+fn find_sso_token(cache_dir: &Path, start_url: &str) -> Option<SsoToken> {
+    let entries = std::fs::read_dir(cache_dir).ok()?;
+    entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|path| {
+            let cache: serde_json::Value =
+                serde_json::from_str(&std::fs::read_to_string(&path).ok()?).ok()?;
+            if cache.get("startUrl")?.as_str()?.trim_end_matches('/')
+                != start_url.trim_end_matches('/')
+            {
+                return None;
+            }
+            Some(SsoToken {
+                access_token: cache.get("accessToken")?.as_str()?.to_string(),
+                expires_at: parse_timestamp(cache.get("expiresAt")?.as_str()?)?,
+                path,
+                cache,
+            })
+        })
+        .max_by_key(|token| token.expires_at)
+}
+
+async fn sso_credentials(
+    files: &AwsConfigFiles,
+    profile: &str,
+    settings: &HashMap<String, String>,
+) -> Result<ProfileCredentials, RayClawError> {
+    let session = setting(settings, "sso_session")
+        .map(|name| (name, files.sso_session(name)))
+        .unwrap_or_default();
+    let lookup = |key: &str| {
+        setting(&session.1, key)
+            .or_else(|| setting(settings, key))
+            .ok_or_else(|| config_error(format!("AWS SSO profile {profile} is missing {key}")))
+    };
+    let start_url = lookup("sso_start_url")?;
+    let sso_region = lookup("sso_region")?;
+    let account_id = lookup("sso_account_id")?;
+    let role_name = lookup("sso_role_name")?;
+
+    let login_hint = format!("run `aws sso login --profile {profile}`");
+    let mut token = find_sso_token(&files.sso_cache, start_url)
+        .ok_or_else(|| config_error(format!("No AWS SSO session for {start_url}; {login_hint}")))?;
+    if token.expires_at <= Utc::now() + chrono::Duration::minutes(1) {
+        refresh_sso_token(&mut token, sso_region)
+            .await
+            .map_err(|e| {
+                config_error(format!(
+                    "AWS SSO session for {start_url} expired ({e}); {login_hint}"
+                ))
+            })?;
+    }
+
+    let url = format!(
+        "https://portal.sso.{sso_region}.amazonaws.com/federation/credentials?role_name={}&account_id={}",
+        urlencoding::encode(role_name),
+        urlencoding::encode(account_id)
+    );
+    let response = http_client()?
+        .get(&url)
+        .header("x-amz-sso_bearer_token", &token.access_token)
+        .send()
+        .await
+        .map_err(|e| config_error(format!("AWS SSO GetRoleCredentials failed: {e}")))?;
+    let status = response.status();
+    let body: serde_json::Value = response.json().await.unwrap_or_default();
+    if !status.is_success() {
+        let message = body
+            .get("message")
+            .and_then(|m| m.as_str())
+            .unwrap_or("no details");
+        return Err(config_error(format!(
+            "AWS SSO GetRoleCredentials for {role_name} in {account_id} failed ({status}): {message}; {login_hint}"
+        )));
+    }
+    parse_sso_role_credentials(&body)
+}
+
+fn parse_sso_role_credentials(
+    body: &serde_json::Value,
+) -> Result<ProfileCredentials, RayClawError> {
+    let creds = body
+        .get("roleCredentials")
+        .ok_or_else(|| config_error("AWS SSO response has no roleCredentials"))?;
+    let field = |name: &str| {
+        creds
+            .get(name)
+            .and_then(|v| v.as_str())
+            .filter(|v| !v.is_empty())
+            .map(str::to_string)
+            .ok_or_else(|| config_error(format!("AWS SSO response is missing {name}")))
+    };
+    Ok(ProfileCredentials {
+        access_key_id: field("accessKeyId")?,
+        secret_access_key: field("secretAccessKey")?,
+        session_token: Some(field("sessionToken")?),
+        expiration: creds
+            .get("expiration")
+            .and_then(|v| v.as_i64())
+            .and_then(DateTime::from_timestamp_millis),
+    })
+}
+
+/// Exchange the cached refresh token for a new access token (sso-session
+/// profiles only) and write it back to the cache like the CLI does.
+async fn refresh_sso_token(token: &mut SsoToken, sso_region: &str) -> Result<(), RayClawError> {
+    let field = |name: &str| {
+        token
+            .cache
+            .get(name)
+            .and_then(|v| v.as_str())
+            .map(str::to_string)
+            .ok_or_else(|| config_error(format!("no {name} cached")))
+    };
+    let registration_expires = field("registrationExpiresAt")
+        .ok()
+        .and_then(|t| parse_timestamp(&t));
+    if registration_expires.is_some_and(|t| t <= Utc::now()) {
+        return Err(config_error("client registration expired"));
+    }
+    let request = serde_json::json!({
+        "clientId": field("clientId")?,
+        "clientSecret": field("clientSecret")?,
+        "grantType": "refresh_token",
+        "refreshToken": field("refreshToken")?,
+    });
+    let client = http_client()?;
+    let response: serde_json::Value = async {
+        client
+            .post(format!("https://oidc.{sso_region}.amazonaws.com/token"))
+            .json(&request)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+    }
+    .await
+    .map_err(|e: reqwest::Error| config_error(format!("token refresh failed: {e}")))?;
+
+    let access_token = response
+        .get("accessToken")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| config_error("token refresh returned no accessToken"))?;
+    let expires_in = response
+        .get("expiresIn")
+        .and_then(|v| v.as_i64())
+        .unwrap_or(3600);
+    let expires_at = Utc::now() + chrono::Duration::seconds(expires_in);
+
+    token.access_token = access_token.to_string();
+    token.expires_at = expires_at;
+    if let Some(cache) = token.cache.as_object_mut() {
+        cache.insert("accessToken".into(), access_token.into());
+        cache.insert(
+            "expiresAt".into(),
+            expires_at
+                .to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+                .into(),
+        );
+        if let Some(refresh) = response.get("refreshToken") {
+            cache.insert("refreshToken".into(), refresh.clone());
+        }
+    }
+    if let Ok(raw) = serde_json::to_string(&token.cache) {
+        let _ = std::fs::write(&token.path, raw);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files(config: &str, credentials: &str) -> (AwsConfigFiles, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("rayclaw_aws_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("sso")).unwrap();
+        std::fs::write(dir.join("config"), config).unwrap();
+        std::fs::write(dir.join("credentials"), credentials).unwrap();
+        (
+            AwsConfigFiles {
+                credentials: dir.join("credentials"),
+                config: dir.join("config"),
+                sso_cache: dir.join("sso"),
+            },
+            dir,
+        )
+    }
+
+    #[test]
+    fn test_profile_merges_config_and_credentials() {
+        let (files, dir) = files(
+            "[default]\nregion = eu-west-1\n\n[profile dev]\n# comment\nregion = us-west-2\nRole_Arn = arn:aws:iam::1:role/dev\n",
+            "[dev]\naws_access_key_id = AKIDDEV\naws_secret_access_key = secret\n",
+        );
+        let dev = files.profile("dev");
+        assert_eq!(dev["region"], "us-west-2");
+        assert_eq!(dev["role_arn"], "arn:aws:iam::1:role/dev");
+        assert_eq!(dev["aws_access_key_id"], "AKIDDEV");
+        assert_eq!(files.profile("default")["region"], "eu-west-1");
+        // `[dev]` in the config file is not the dev profile
+        assert!(files.profile("missing").is_empty());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_static_keys_and_missing_profile() {
+        let (files, dir) = files(
+            "",
+            "[default]\naws_access_key_id = AKIDTEST\naws_secret_access_key = SECRET\n",
+        );
+        let creds = resolve_profile(&files, "default").await.unwrap().unwrap();
+        assert_eq!(creds.access_key_id, "AKIDTEST");
+        assert!(creds.expiration.is_none());
+        assert!(resolve_profile(&files, "other").await.unwrap().is_none());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_role_chain_errors() {
+        let (files, dir) = files(
+            "[profile a]\nrole_arn = arn:aws:iam::1:role/a\nsource_profile = b\n\n\
+             [profile b]\nrole_arn = arn:aws:iam::1:role/b\nsource_profile = a\n\n\
+             [profile orphan]\nrole_arn = arn:aws:iam::1:role/o\n\n\
+             [profile mfa]\nrole_arn = arn:aws:iam::1:role/m\nsource_profile = a\nmfa_serial = arn:aws:iam::1:mfa/me\n",
+            "",
+        );
+        let err = resolve_profile(&files, "a").await.unwrap_err().to_string();
+        assert!(err.contains("a -> b -> a"), "{err}");
+        let err = resolve_profile(&files, "orphan")
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("neither source_profile nor credential_source"));
+        let err = resolve_profile(&files, "mfa")
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("MFA"));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_assume_role_request_and_response() {
+        let body = assume_role_body(&AssumeRoleRequest {
+            role_arn: "arn:aws:iam::123:role/x",
+            session_name: "rayclaw".into(),
+            external_id: Some("ext id"),
+            duration_seconds: Some(900),
+        });
+        assert_eq!(
+            body,
+            "Action=AssumeRole&Version=2011-06-15&RoleArn=arn%3Aaws%3Aiam%3A%3A123%3Arole%2Fx&RoleSessionName=rayclaw&ExternalId=ext%20id&DurationSeconds=900"
+        );
+        assert_eq!(
+            sts_endpoint("cn-north-1"),
+            "https://sts.cn-north-1.amazonaws.com.cn/"
+        );
+
+        let xml = "<AssumeRoleResponse><AssumeRoleResult><Credentials>\
+            <AccessKeyId>ASIAEXAMPLE</AccessKeyId><SecretAccessKey>sk</SecretAccessKey>\
+            <SessionToken>tok</SessionToken><Expiration>2026-10-16T12:00:00Z</Expiration>\
+            </Credentials></AssumeRoleResult></AssumeRoleResponse>";
+        let creds = parse_assume_role_response(xml).unwrap();
+        assert_eq!(creds.access_key_id, "ASIAEXAMPLE");
+        assert_eq!(creds.session_token.as_deref(), Some("tok"));
+        assert_eq!(
+            creds.expiration.unwrap().to_rfc3339(),
+            "2026-10-16T12:00:00+00:00"
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_credential_process() {
+        let (files, dir) = files(
+            "[profile proc]\ncredential_process = echo '{\"Version\": 1, \"AccessKeyId\": \"AKIDPROC\", \"SecretAccessKey\": \"s\", \"SessionToken\": \"t\", \"Expiration\": \"2030-01-01T00:00:00Z\"}'\n\n\
+             [profile bad]\ncredential_process = echo '{\"Version\": 2}'\n",
+            "",
+        );
+        let creds = resolve_profile(&files, "proc").await.unwrap().unwrap();
+        assert_eq!(creds.access_key_id, "AKIDPROC");
+        assert_eq!(creds.session_token.as_deref(), Some("t"));
+        assert!(creds.expiration.is_some());
+        assert!(resolve_profile(&files, "bad").await.is_err());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_sso_token_lookup_and_expiry() {
+        let (files, dir) = files(
+            "[profile sso]\nsso_session = corp\nsso_account_id = 123\nsso_role_name = Dev\n\n\
+             [sso-session corp]\nsso_start_url = https://corp.awsapps.com/start\nsso_region = us-east-1\n",
+            "",
+        );
+        std::fs::write(
+            files.sso_cache.join("abc.json"),
+            r#"{"startUrl": "https://corp.awsapps.com/start/", "accessToken": "old", "expiresAt": "2020-01-01T00:00:00Z"}"#,
+        )
+        .unwrap();
+        let token = find_sso_token(&files.sso_cache, "https://corp.awsapps.com/start").unwrap();
+        assert_eq!(token.access_token, "old");
+        assert!(find_sso_token(&files.sso_cache, "https://other.awsapps.com/start").is_none());
+
+        // Expired, and no refresh token cached: ask the user to log in again
+        let err = resolve_profile(&files, "sso")
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("aws sso login --profile sso"), "{err}");
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_parse_sso_role_credentials() {
+        let body = serde_json::json!({"roleCredentials": {
+            "accessKeyId": "ASIASSO", "secretAccessKey": "s", "sessionToken": "t",
+            "expiration": 1_790_000_000_000i64
+        }});
+        let creds = parse_sso_role_credentials(&body).unwrap();
+        assert_eq!(creds.access_key_id, "ASIASSO");
+        assert_eq!(creds.expiration.unwrap().timestamp(), 1_790_000_000);
+    }
+}
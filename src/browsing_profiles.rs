@@ -0,0 +1,527 @@
+//! Named browsing profiles: logins a chat has authorized the web tools to
+//! use. A profile lists the sites it belongs to and holds an optional
+//! username/password and a persistent cookie jar; `web_fetch` sends the
+//! cookies (and HTTP Basic credentials when challenged) and `browser` gets a
+//! separate browser profile with `{{username}}`/`{{password}}` placeholders.
+//!
+//! Passwords and cookie jars are sealed with AES-256-GCM before they reach
+//! the database. The key comes from `browsing_profiles_key`, or is generated
+//! on first use and kept next to the database.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::Engine;
+use reqwest_cookie_store::{CookieStore, CookieStoreMutex};
+use sha2::{Digest, Sha256};
+
+use crate::db::{call_blocking, BrowsingProfile, Database};
+
+const KEY_FILE: &str = "browsing_profiles.key";
+const NONCE_LEN: usize = 12;
+pub const MAX_PROFILE_NAME_CHARS: usize = 32;
+
+/// Encrypts profile secrets at rest.
+pub struct ProfileVault {
+    cipher: Aes256Gcm,
+}
+
+impl ProfileVault {
+    fn from_key(key: &[u8; 32]) -> Self {
+        ProfileVault {
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key)),
+        }
+    }
+
+    /// Any configured passphrase is hashed into the key.
+    fn from_passphrase(passphrase: &str) -> Self {
+        Self::from_key(&Sha256::digest(passphrase.as_bytes()).into())
+    }
+
+    /// Read the generated key from `path`, creating it (readable by the
+    /// owner only) if missing.
+    fn from_key_file(path: &Path) -> Result<Self, String> {
+        match std::fs::read(path) {
+            Ok(bytes) => {
+                let key: [u8; 32] = bytes
+                    .as_slice()
+                    .try_into()
+                    .map_err(|_| format!("{} is not a 32-byte key", path.display()))?;
+                return Ok(Self::from_key(&key));
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(format!("failed to read {}: {e}", path.display())),
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let key = Aes256Gcm::generate_key(OsRng);
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = match options.open(path) {
+            Ok(file) => file,
+            // Another task created it first
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                return Self::from_key_file(path)
+            }
+            Err(e) => return Err(format!("failed to create {}: {e}", path.display())),
+        };
+        file.write_all(&key)
+            .map_err(|e| format!("failed to write {}: {e}", path.display()))?;
+        Ok(Self::from_key(&key.into()))
+    }
+
+    /// base64 of the nonce followed by the ciphertext.
+    pub fn seal(&self, plaintext: &str) -> String {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, plaintext.as_bytes())
+            .expect("AES-GCM encryption failed");
+        let mut sealed = nonce.to_vec();
+        sealed.extend(ciphertext);
+        base64::engine::general_purpose::STANDARD.encode(sealed)
+    }
+
+    pub fn unseal(&self, sealed: &str) -> Result<String, String> {
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(sealed)
+            .map_err(|_| "stored secret is corrupt".to_string())?;
+        if bytes.len() < NONCE_LEN {
+            return Err("stored secret is corrupt".into());
+        }
+        let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+        let plaintext = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| {
+                "stored secret cannot be decrypted (was browsing_profiles_key changed?)".to_string()
+            })?;
+        String::from_utf8(plaintext).map_err(|_| "stored secret is corrupt".into())
+    }
+}
+
+/// Profile names are short slugs so they can name browser profile
+/// directories.
+pub fn validate_profile_name(name: &str) -> Result<(), String> {
+    if name.is_empty()
+        || name.chars().count() > MAX_PROFILE_NAME_CHARS
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+    {
+        return Err(format!(
+            "Invalid profile name '{name}': use up to {MAX_PROFILE_NAME_CHARS} lowercase letters, digits, '-' or '_'"
+        ));
+    }
+    Ok(())
+}
+
+/// Lowercase host from a domain or URL the user gave, without a leading
+/// `*.` or `.`.
+pub fn normalize_domain(domain: &str) -> Option<String> {
+    let domain = domain.trim();
+    let host = match reqwest::Url::parse(domain) {
+        Ok(url) if url.has_host() => url.host_str()?.to_string(),
+        _ => domain.split('/').next()?.to_string(),
+    };
+    let host = host
+        .trim_start_matches("*.")
+        .trim_start_matches('.')
+        .trim_end_matches('.')
+        .to_ascii_lowercase();
+    if host.is_empty() || host.contains(|c: char| c.is_whitespace() || c == '@' || c == ':') {
+        return None;
+    }
+    Some(host)
+}
+
+/// Whether `host` is one of `domains` or a subdomain of one.
+pub fn domain_allowed(domains: &[String], host: &str) -> bool {
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    domains.iter().any(|domain| {
+        host == *domain
+            || host
+                .strip_suffix(domain.as_str())
+                .is_some_and(|prefix| prefix.ends_with('.'))
+    })
+}
+
+fn not_found(name: &str) -> String {
+    format!("No browsing profile named '{name}' in this chat (see browsing_profile list)")
+}
+
+/// Profile storage for the web tools, sealing secrets on the way in.
+pub struct BrowsingProfiles {
+    db: Arc<Database>,
+    key_file: PathBuf,
+    configured_key: Option<String>,
+    vault: OnceLock<ProfileVault>,
+}
+
+impl BrowsingProfiles {
+    /// `configured_key` is `browsing_profiles_key`; without it the key file
+    /// lives in `runtime_data_dir`.
+    pub fn new(db: Arc<Database>, runtime_data_dir: &str, configured_key: Option<String>) -> Self {
+        BrowsingProfiles {
+            db,
+            key_file: PathBuf::from(runtime_data_dir).join(KEY_FILE),
+            configured_key,
+            vault: OnceLock::new(),
+        }
+    }
+
+    /// Opened on first use so deployments without profiles never create a
+    /// key file.
+    fn vault(&self) -> Result<&ProfileVault, String> {
+        if let Some(vault) = self.vault.get() {
+            return Ok(vault);
+        }
+        let vault = match &self.configured_key {
+            Some(key) => ProfileVault::from_passphrase(key),
+            None => ProfileVault::from_key_file(&self.key_file)?,
+        };
+        Ok(self.vault.get_or_init(|| vault))
+    }
+
+    pub async fn list(&self, chat_id: i64) -> Result<Vec<BrowsingProfile>, String> {
+        call_blocking(self.db.clone(), move |db| {
+            db.get_browsing_profiles_for_chat(chat_id)
+        })
+        .await
+        .map_err(|e| e.to_string())
+    }
+
+    pub async fn get(&self, chat_id: i64, name: &str) -> Result<BrowsingProfile, String> {
+        let lookup = name.to_string();
+        call_blocking(self.db.clone(), move |db| {
+            db.get_browsing_profile(chat_id, &lookup)
+        })
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| not_found(name))
+    }
+
+    /// Create or update a profile. `None` keeps the stored username or
+    /// password; an empty string removes it.
+    pub async fn save(
+        &self,
+        chat_id: i64,
+        name: &str,
+        domains: Option<Vec<String>>,
+        username: Option<&str>,
+        password: Option<&str>,
+    ) -> Result<BrowsingProfile, String> {
+        validate_profile_name(name)?;
+        let existing = self.get(chat_id, name).await.ok();
+        let domains = match domains {
+            Some(domains) => domains,
+            None => match &existing {
+                Some(profile) => profile.domains.clone(),
+                None => return Err("`domains` is required for a new profile".into()),
+            },
+        };
+        if domains.is_empty() {
+            return Err("A profile needs at least one domain".into());
+        }
+        let username = match username {
+            Some(u) => Some(u.trim().to_string()).filter(|u| !u.is_empty()),
+            None => existing.as_ref().and_then(|p| p.username.clone()),
+        };
+        let sealed_password = match password {
+            Some("") => None,
+            Some(password) => Some(self.vault()?.seal(password)),
+            None => existing.as_ref().and_then(|p| p.sealed_password.clone()),
+        };
+
+        let name = name.to_string();
+        call_blocking(self.db.clone(), move |db| {
+            db.save_browsing_profile(
+                chat_id,
+                &name,
+                &domains,
+                username.as_deref(),
+                sealed_password.as_deref(),
+            )?;
+            db.get_browsing_profile(chat_id, &name)
+        })
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Profile disappeared while saving".into())
+    }
+
+    pub async fn delete(&self, chat_id: i64, name: &str) -> Result<(), String> {
+        let lookup = name.to_string();
+        let deleted = call_blocking(self.db.clone(), move |db| {
+            db.delete_browsing_profile(chat_id, &lookup)
+        })
+        .await
+        .map_err(|e| e.to_string())?;
+        if deleted {
+            Ok(())
+        } else {
+            Err(not_found(name))
+        }
+    }
+
+    pub fn password(&self, profile: &BrowsingProfile) -> Result<Option<String>, String> {
+        match &profile.sealed_password {
+            Some(sealed) => self.vault()?.unseal(sealed).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// The profile's cookies, ready to hand to a reqwest client.
+    pub fn cookie_jar(&self, profile: &BrowsingProfile) -> Result<CookieStoreMutex, String> {
+        let store = match &profile.sealed_cookies {
+            Some(sealed) => {
+                let json = self.vault()?.unseal(sealed)?;
+                cookie_store::serde::json::load(json.as_bytes())
+                    .map_err(|e| format!("stored cookies are unreadable: {e}"))?
+            }
+            None => CookieStore::default(),
+        };
+        Ok(CookieStoreMutex::new(store))
+    }
+
+    /// Persist `jar` as the profile's cookies, including session cookies so
+    /// a login survives between fetches.
+    pub async fn store_cookie_jar(
+        &self,
+        profile: &BrowsingProfile,
+        jar: &CookieStoreMutex,
+    ) -> Result<(), String> {
+        let mut json = Vec::new();
+        {
+            let store = jar.lock().map_err(|_| "cookie jar lock poisoned")?;
+            cookie_store::serde::json::save_incl_expired_and_nonpersistent(&store, &mut json)
+                .map_err(|e| e.to_string())?;
+        }
+        let sealed = self
+            .vault()?
+            .seal(std::str::from_utf8(&json).map_err(|e| e.to_string())?);
+        let (chat_id, name) = (profile.chat_id, profile.name.clone());
+        call_blocking(self.db.clone(), move |db| {
+            db.set_browsing_profile_cookies(chat_id, &name, Some(&sealed))
+        })
+        .await
+        .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    pub async fn clear_cookies(&self, chat_id: i64, name: &str) -> Result<(), String> {
+        let lookup = name.to_string();
+        let updated = call_blocking(self.db.clone(), move |db| {
+            db.set_browsing_profile_cookies(chat_id, &lookup, None)
+        })
+        .await
+        .map_err(|e| e.to_string())?;
+        if updated {
+            Ok(())
+        } else {
+            Err(not_found(name))
+        }
+    }
+
+    /// Add cookies copied from a browser as a `Cookie` header value
+    /// (`name=value; name2=value2`) for `url`. Returns how many were added.
+    pub async fn import_cookies(
+        &self,
+        profile: &BrowsingProfile,
+        url: &str,
+        cookie_header: &str,
+    ) -> Result<usize, String> {
+        let url = reqwest::Url::parse(url).map_err(|e| format!("Invalid url: {e}"))?;
+        let host = url.host_str().unwrap_or_default();
+        if !domain_allowed(&profile.domains, host) {
+            return Err(format!(
+                "{host} is not one of profile '{}' domains ({})",
+                profile.name,
+                profile.domains.join(", ")
+            ));
+        }
+        let jar = self.cookie_jar(profile)?;
+        let mut added = 0;
+        {
+            let mut store = jar.lock().map_err(|_| "cookie jar lock poisoned")?;
+            for pair in cookie_header.split(';') {
+                let pair = pair.trim();
+                if pair.is_empty() {
+                    continue;
+                }
+                if !pair.contains('=') {
+                    return Err(format!("Expected name=value cookies, got '{pair}'"));
+                }
+                if store.parse(&format!("{pair}; Path=/"), &url).is_ok() {
+                    added += 1;
+                }
+            }
+        }
+        self.store_cookie_jar(profile, &jar).await?;
+        Ok(added)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_profiles(key: Option<&str>) -> (BrowsingProfiles, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("rayclaw_profiles_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let runtime = dir.join("runtime");
+        let db = Arc::new(Database::new(runtime.to_str().unwrap()).unwrap());
+        let profiles =
+            BrowsingProfiles::new(db, runtime.to_str().unwrap(), key.map(str::to_string));
+        (profiles, dir)
+    }
+
+    #[test]
+    fn test_vault_round_trip() {
+        let vault = ProfileVault::from_passphrase("correct horse");
+        let sealed = vault.seal("hunter2");
+        assert!(!sealed.contains("hunter2"));
+        assert_ne!(sealed, vault.seal("hunter2"));
+        assert_eq!(vault.unseal(&sealed).unwrap(), "hunter2");
+
+        let other = ProfileVault::from_passphrase("battery staple");
+        assert!(other.unseal(&sealed).unwrap_err().contains("decrypted"));
+        assert!(vault.unseal("not base64!").is_err());
+    }
+
+    #[test]
+    fn test_vault_key_file_is_created_once() {
+        let dir = std::env::temp_dir().join(format!("rayclaw_vault_{}", uuid::Uuid::new_v4()));
+        let path = dir.join(KEY_FILE);
+        let sealed = ProfileVault::from_key_file(&path).unwrap().seal("secret");
+        assert_eq!(std::fs::read(&path).unwrap().len(), 32);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        let reopened = ProfileVault::from_key_file(&path).unwrap();
+        assert_eq!(reopened.unseal(&sealed).unwrap(), "secret");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_validate_profile_name() {
+        assert!(validate_profile_name("work-sso_2").is_ok());
+        assert!(validate_profile_name("").is_err());
+        assert!(validate_profile_name("Work").is_err());
+        assert!(validate_profile_name("../etc").is_err());
+        assert!(validate_profile_name(&"a".repeat(33)).is_err());
+    }
+
+    #[test]
+    fn test_normalize_domain_and_matching() {
+        assert_eq!(
+            normalize_domain("https://Portal.Example.com/login").as_deref(),
+            Some("portal.example.com")
+        );
+        assert_eq!(
+            normalize_domain("*.example.com").as_deref(),
+            Some("example.com")
+        );
+        assert_eq!(
+            normalize_domain("example.com/path").as_deref(),
+            Some("example.com")
+        );
+        assert!(normalize_domain(" ").is_none());
+        assert!(normalize_domain("user@example.com").is_none());
+
+        let domains = vec!["example.com".to_string()];
+        assert!(domain_allowed(&domains, "example.com"));
+        assert!(domain_allowed(&domains, "WWW.example.com."));
+        assert!(!domain_allowed(&domains, "badexample.com"));
+        assert!(!domain_allowed(&domains, "example.com.evil.net"));
+    }
+
+    #[tokio::test]
+    async fn test_profile_secrets_are_sealed() {
+        let (profiles, dir) = test_profiles(Some("passphrase"));
+        let domains = Some(vec!["example.com".to_string()]);
+        assert!(profiles
+            .save(1, "work", None, Some("ann"), Some("pw"))
+            .await
+            .unwrap_err()
+            .contains("domains"));
+
+        let profile = profiles
+            .save(1, "work", domains, Some("ann"), Some("s3cret"))
+            .await
+            .unwrap();
+        let sealed = profile.sealed_password.clone().unwrap();
+        assert!(!sealed.contains("s3cret"));
+        assert_eq!(
+            profiles.password(&profile).unwrap().as_deref(),
+            Some("s3cret")
+        );
+        assert!(!dir.join("runtime").join(KEY_FILE).exists());
+
+        // Omitted fields are kept, empty ones cleared
+        let profile = profiles.save(1, "work", None, None, None).await.unwrap();
+        assert_eq!(profile.username.as_deref(), Some("ann"));
+        assert_eq!(profile.sealed_password.as_deref(), Some(sealed.as_str()));
+        let profile = profiles
+            .save(1, "work", None, Some(""), Some(""))
+            .await
+            .unwrap();
+        assert!(profile.username.is_none());
+        assert!(profiles.password(&profile).unwrap().is_none());
+
+        assert!(profiles.get(2, "work").await.is_err());
+        profiles.delete(1, "work").await.unwrap();
+        assert!(profiles.delete(1, "work").await.is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_cookie_jar_persists() {
+        let (profiles, dir) = test_profiles(None);
+        let profile = profiles
+            .save(1, "news", Some(vec!["example.com".to_string()]), None, None)
+            .await
+            .unwrap();
+        let url = reqwest::Url::parse("https://www.example.com/account").unwrap();
+
+        let err = profiles
+            .import_cookies(&profile, "https://other.org/", "sid=1")
+            .await
+            .unwrap_err();
+        assert!(err.contains("not one of"));
+        let added = profiles
+            .import_cookies(&profile, url.as_str(), "sid=abc; theme=dark")
+            .await
+            .unwrap();
+        assert_eq!(added, 2);
+        assert!(dir.join("runtime").join(KEY_FILE).exists());
+
+        let profile = profiles.get(1, "news").await.unwrap();
+        assert!(!profile.sealed_cookies.as_deref().unwrap().contains("abc"));
+        let jar = profiles.cookie_jar(&profile).unwrap();
+        let mut values: Vec<_> = jar
+            .lock()
+            .unwrap()
+            .get_request_values(&url)
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect();
+        values.sort();
+        assert_eq!(values, vec!["sid=abc", "theme=dark"]);
+
+        profiles.clear_cookies(1, "news").await.unwrap();
+        let profile = profiles.get(1, "news").await.unwrap();
+        assert!(profile.sealed_cookies.is_none());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,246 @@
+//! iCalendar (RFC 5545) feed of upcoming scheduled task runs, so bot
+//! automation shows up in calendar apps. Cron tasks are expanded into one
+//! event per run over a rolling window rather than translated to RRULEs,
+//! which can't express every cron expression.
+
+use std::str::FromStr;
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::db::ScheduledTask;
+
+/// How far ahead runs are listed.
+pub const FEED_HORIZON_DAYS: i64 = 30;
+/// Cap per task so a every-minute cron doesn't produce a huge feed.
+const MAX_RUNS_PER_TASK: usize = 100;
+/// Runs are instantaneous; give events a visible length in calendar views.
+const EVENT_MINUTES: i64 = 15;
+const SUMMARY_CHARS: usize = 60;
+
+/// Upcoming run times of `task` between `now` and `until`. Cron runs follow
+/// `tz`, the timezone the scheduler computes them in.
+pub fn upcoming_runs(
+    task: &ScheduledTask,
+    tz: chrono_tz::Tz,
+    now: DateTime<Utc>,
+    until: DateTime<Utc>,
+) -> Vec<DateTime<Utc>> {
+    if task.status != "active" {
+        return Vec::new();
+    }
+    let Ok(next) = DateTime::parse_from_rfc3339(&task.next_run) else {
+        return Vec::new();
+    };
+    let next = next.with_timezone(&Utc);
+    let mut runs = Vec::new();
+    // An overdue run goes out on the scheduler's next poll
+    let first = next.max(now);
+    if first <= until {
+        runs.push(first);
+    }
+    if task.schedule_type == "cron" {
+        if let Ok(schedule) = cron::Schedule::from_str(&task.schedule_value) {
+            runs.extend(
+                schedule
+                    .after(&first.with_timezone(&tz))
+                    .map(|t| t.with_timezone(&Utc))
+                    .take_while(|t| *t <= until)
+                    .take(MAX_RUNS_PER_TASK.saturating_sub(runs.len())),
+            );
+        }
+    }
+    runs
+}
+
+/// Render `tasks` as a VCALENDAR named `name`.
+pub fn render_ics(
+    name: &str,
+    tasks: &[ScheduledTask],
+    tz: chrono_tz::Tz,
+    now: DateTime<Utc>,
+) -> String {
+    let until = now + Duration::days(FEED_HORIZON_DAYS);
+    let stamp = format_utc(now);
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//RayClaw//Scheduled tasks//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+        format!("X-WR-CALNAME:{}", escape_text(name)),
+        // Hint for clients that honour it; most poll on their own schedule
+        "REFRESH-INTERVAL;VALUE=DURATION:PT1H".to_string(),
+        "X-PUBLISHED-TTL:PT1H".to_string(),
+    ];
+    for task in tasks {
+        let summary = task_summary(task);
+        let description = format!(
+            "Task #{} ({} {})\n\n{}",
+            task.id, task.schedule_type, task.schedule_value, task.prompt
+        );
+        for run in upcoming_runs(task, tz, now, until) {
+            lines.push("BEGIN:VEVENT".into());
+            lines.push(format!("UID:task-{}-{}@rayclaw", task.id, run.timestamp()));
+            lines.push(format!("DTSTAMP:{stamp}"));
+            lines.push(format!("DTSTART:{}", format_utc(run)));
+            lines.push(format!(
+                "DTEND:{}",
+                format_utc(run + Duration::minutes(EVENT_MINUTES))
+            ));
+            lines.push(format!("SUMMARY:{}", escape_text(&summary)));
+            lines.push(format!("DESCRIPTION:{}", escape_text(&description)));
+            lines.push("TRANSP:TRANSPARENT".into());
+            lines.push("END:VEVENT".into());
+        }
+    }
+    lines.push("END:VCALENDAR".into());
+
+    let mut out = String::new();
+    for line in lines {
+        out.push_str(&fold_line(&line));
+        out.push_str("\r\n");
+    }
+    out
+}
+
+fn task_summary(task: &ScheduledTask) -> String {
+    let first_line = task.prompt.lines().next().unwrap_or_default().trim();
+    let mut summary: String = first_line.chars().take(SUMMARY_CHARS).collect();
+    if first_line.chars().count() > SUMMARY_CHARS {
+        summary.push('…');
+    }
+    format!("🤖 {summary}")
+}
+
+fn format_utc(t: DateTime<Utc>) -> String {
+    t.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Escape a TEXT value (RFC 5545 §3.3.11).
+fn escape_text(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            ';' => out.push_str("\\;"),
+            ',' => out.push_str("\\,"),
+            '\n' => out.push_str("\\n"),
+            '\r' => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Fold a content line at 75 octets, never splitting a UTF-8 character.
+fn fold_line(line: &str) -> String {
+    const LIMIT: usize = 75;
+    let mut out = String::with_capacity(line.len() + line.len() / LIMIT * 3);
+    let mut width = 0;
+    for c in line.chars() {
+        let len = c.len_utf8();
+        if width + len > LIMIT {
+            out.push_str("\r\n ");
+            // The leading space counts toward the continuation line
+            width = 1;
+        }
+        out.push(c);
+        width += len;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(id: i64, schedule_type: &str, value: &str, next_run: &str) -> ScheduledTask {
+        ScheduledTask {
+            id,
+            chat_id: 1,
+            prompt: "Send the weekly report, with charts; thanks\nSecond line".into(),
+            schedule_type: schedule_type.into(),
+            schedule_value: value.into(),
+            next_run: next_run.into(),
+            last_run: None,
+            status: "active".into(),
+            created_at: "2026-01-01T00:00:00Z".into(),
+            thread_id: None,
+        }
+    }
+
+    fn at(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_upcoming_runs_cron_in_timezone() {
+        let now = at("2026-03-02T00:00:00Z");
+        let t = task(1, "cron", "0 0 9 * * Mon", "2026-03-02T08:00:00+00:00");
+        let runs = upcoming_runs(&t, chrono_tz::Europe::Berlin, now, now + Duration::days(15));
+        assert_eq!(
+            runs,
+            vec![
+                at("2026-03-02T08:00:00Z"),
+                at("2026-03-09T08:00:00Z"),
+                at("2026-03-16T08:00:00Z"),
+            ]
+        );
+
+        let every_minute = task(2, "cron", "0 * * * * *", "2026-03-02T00:01:00Z");
+        let runs = upcoming_runs(&every_minute, chrono_tz::UTC, now, now + Duration::days(1));
+        assert_eq!(runs.len(), MAX_RUNS_PER_TASK);
+    }
+
+    #[test]
+    fn test_upcoming_runs_once_and_inactive() {
+        let now = at("2026-03-02T00:00:00Z");
+        let until = now + Duration::days(30);
+        let once = task(1, "once", "2026-03-05T10:00:00Z", "2026-03-05T10:00:00Z");
+        assert_eq!(
+            upcoming_runs(&once, chrono_tz::UTC, now, until),
+            vec![at("2026-03-05T10:00:00Z")]
+        );
+        let overdue = task(2, "once", "2026-03-01T10:00:00Z", "2026-03-01T10:00:00Z");
+        assert_eq!(
+            upcoming_runs(&overdue, chrono_tz::UTC, now, until),
+            vec![now]
+        );
+        let far = task(3, "once", "2026-06-01T10:00:00Z", "2026-06-01T10:00:00Z");
+        assert!(upcoming_runs(&far, chrono_tz::UTC, now, until).is_empty());
+        let mut paused = once.clone();
+        paused.status = "paused".into();
+        assert!(upcoming_runs(&paused, chrono_tz::UTC, now, until).is_empty());
+    }
+
+    #[test]
+    fn test_render_ics() {
+        let now = at("2026-03-02T00:00:00Z");
+        let tasks = vec![task(
+            7,
+            "once",
+            "2026-03-05T10:00:00Z",
+            "2026-03-05T10:00:00Z",
+        )];
+        let ics = render_ics("Team chat, tasks", &tasks, chrono_tz::UTC, now);
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(ics.ends_with("END:VCALENDAR\r\n"));
+        assert!(ics.contains("X-WR-CALNAME:Team chat\\, tasks\r\n"));
+        assert!(ics.contains("UID:task-7-1772704800@rayclaw\r\n"));
+        assert!(ics.contains("DTSTART:20260305T100000Z\r\n"));
+        assert!(ics.contains("DTEND:20260305T101500Z\r\n"));
+        assert!(ics.contains("SUMMARY:🤖 Send the weekly report\\, with charts\\; thanks\r\n"));
+        for line in ics.split("\r\n") {
+            assert!(line.len() <= 75, "unfolded line: {line}");
+        }
+        let unfolded = ics.replace("\r\n ", "");
+        assert!(unfolded.contains("\\n\\nSend the weekly report"));
+    }
+
+    #[test]
+    fn test_fold_line_keeps_utf8_intact() {
+        let line = format!("SUMMARY:{}", "é".repeat(60));
+        let folded = fold_line(&line);
+        assert!(folded.split("\r\n").all(|l| l.len() <= 75));
+        assert_eq!(folded.replace("\r\n ", ""), line);
+    }
+}
@@ -1,7 +1,10 @@
+use std::future::Future;
 use std::sync::Arc;
 
+use tracing::warn;
+
 use crate::channel_adapter::ChannelRegistry;
-use crate::db::{call_blocking, Database, StoredMessage};
+use crate::db::{call_blocking, Database, Delivery, StoredMessage};
 use crate::tools::auth_context_from_input;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -91,6 +94,27 @@ pub async fn is_web_chat(registry: &ChannelRegistry, db: Arc<Database>, chat_id:
         .unwrap_or(false)
 }
 
+/// Whether an incoming message gets an agent reply. Direct messages and
+/// mentions normally do; chats in observer mode (`/observe`) only answer
+/// mentions, while their messages are still stored for search and memory.
+pub async fn should_respond(
+    db: Arc<Database>,
+    chat_id: i64,
+    is_direct: bool,
+    mentioned: bool,
+) -> bool {
+    if mentioned {
+        return true;
+    }
+    is_direct && !is_observer_chat(db, chat_id).await
+}
+
+pub async fn is_observer_chat(db: Arc<Database>, chat_id: i64) -> bool {
+    call_blocking(db, move |db| db.is_observer_chat(chat_id))
+        .await
+        .unwrap_or(false)
+}
+
 pub async fn enforce_channel_policy(
     registry: &ChannelRegistry,
     db: Arc<Database>,
@@ -116,22 +140,234 @@ pub async fn enforce_channel_policy(
     Ok(())
 }
 
+/// Send errors that retrying won't fix: the bot was blocked or removed, or
+/// the chat no longer exists.
+pub fn is_permanent_delivery_error(error: &str) -> bool {
+    const PERMANENT: &[&str] = &[
+        "forbidden",
+        "blocked",
+        "kicked",
+        "chat not found",
+        "deactivated",
+        "missing access",
+        "unknown channel",
+        "http 403",
+        "http 404",
+        "not_in_channel",
+        "channel_not_found",
+        "is_archived",
+        "account_inactive",
+        "out of the chat",
+        "external_chat_id",
+    ];
+    let error = error.to_lowercase();
+    PERMANENT.iter().any(|p| error.contains(p))
+}
+
+/// Largest uploaded file passed to the model as a document block; bigger
+/// files are only saved for tools to read.
+pub const MAX_LLM_DOCUMENT_BYTES: usize = 20 * 1024 * 1024;
+
+/// `(base64, media_type)` for an uploaded file the model can read directly
+/// as a document (currently PDFs), or `None` if it should only be saved.
+pub fn document_for_llm(bytes: &[u8], mime: Option<&str>) -> Option<(String, String)> {
+    let media_type = match mime {
+        Some(m) if crate::llm_types::is_document_media_type(m) => m,
+        // Mail clients and some phones send PDFs as octet-stream
+        _ if bytes.starts_with(b"%PDF-") => "application/pdf",
+        _ => return None,
+    };
+    if bytes.len() > MAX_LLM_DOCUMENT_BYTES {
+        return None;
+    }
+    Some((
+        crate::image_utils::base64_encode(bytes),
+        media_type.to_string(),
+    ))
+}
+
+/// The earlier message a user replied to (a Telegram reply, a Discord
+/// reference, a Slack thread parent).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplyContext {
+    pub sender: String,
+    pub text: String,
+}
+
+/// Longest excerpt of a replied-to message kept as context.
+const MAX_REPLY_CONTEXT_CHARS: usize = 500;
+
+/// Append the replied-to message to stored `content` as quoted context, so
+/// the agent sees it even when it has scrolled out of recent history. It goes
+/// after the content to keep leading `#` commands and `[image]`/`[document]`
+/// markers intact.
+pub fn with_reply_context(content: String, reply: Option<&ReplyContext>) -> String {
+    let Some(reply) = reply else {
+        return content;
+    };
+    let quoted = reply.text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if quoted.is_empty() {
+        return content;
+    }
+    let excerpt = match quoted.char_indices().nth(MAX_REPLY_CONTEXT_CHARS) {
+        Some((end, _)) => format!("{}…", &quoted[..end]),
+        None => quoted,
+    };
+    format!("{content}\n\n[in reply to {}: \"{excerpt}\"]", reply.sender)
+}
+
+/// Run `send` for an outbound message and record it in the `deliveries`
+/// table: `queued` first, then `sent` or `failed`. Bookkeeping errors are
+/// logged and never block the message itself.
+pub async fn send_tracked<F, Fut>(
+    db: Arc<Database>,
+    chat_id: i64,
+    channel: &str,
+    text: &str,
+    send: F,
+) -> Result<(), String>
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<(), String>>,
+{
+    let (channel_name, content) = (channel.to_string(), text.to_string());
+    let delivery_id = match call_blocking(db.clone(), move |d| {
+        d.create_delivery(chat_id, &channel_name, &content)
+    })
+    .await
+    {
+        Ok(id) => Some(id),
+        Err(e) => {
+            warn!("Failed to record delivery for chat {chat_id}: {e}");
+            None
+        }
+    };
+
+    let result = send().await;
+    if let Some(id) = delivery_id {
+        record_delivery_result(db, id, &result, None).await;
+    }
+    result
+}
+
+async fn record_delivery_result(
+    db: Arc<Database>,
+    delivery_id: i64,
+    result: &Result<(), String>,
+    edited_content: Option<String>,
+) {
+    let (status, error, retryable) = match result {
+        Ok(()) if edited_content.is_some() => ("edited", None, false),
+        Ok(()) => ("sent", None, false),
+        Err(e) => {
+            warn!("Delivery {delivery_id} failed: {e}");
+            ("failed", Some(e.clone()), !is_permanent_delivery_error(e))
+        }
+    };
+    if let Err(e) = call_blocking(db, move |d| {
+        d.record_delivery_attempt(
+            delivery_id,
+            status,
+            error.as_deref(),
+            retryable,
+            edited_content.as_deref(),
+        )
+    })
+    .await
+    {
+        warn!("Failed to update delivery {delivery_id}: {e}");
+    }
+}
+
+async fn resolve_external_chat_id(db: Arc<Database>, chat_id: i64) -> Result<String, String> {
+    Ok(call_blocking(db, move |d| d.get_chat_external_id(chat_id))
+        .await
+        .map_err(|e| format!("Failed to read external chat id for chat {chat_id}: {e}"))?
+        .unwrap_or_else(|| chat_id.to_string()))
+}
+
+/// Resend a failed delivery, optionally with corrected text. A resend with
+/// changed text is recorded as `edited` rather than `sent`.
+pub async fn retry_delivery(
+    registry: &ChannelRegistry,
+    db: Arc<Database>,
+    delivery_id: i64,
+    text: Option<String>,
+) -> Result<Delivery, String> {
+    let load = |db: Arc<Database>| async move {
+        call_blocking(db, move |d| d.get_delivery(delivery_id))
+            .await
+            .map_err(|e| format!("Failed to load delivery {delivery_id}: {e}"))?
+            .ok_or_else(|| format!("delivery {delivery_id} not found"))
+    };
+    let delivery = load(db.clone()).await?;
+    if delivery.status != "failed" {
+        return Err(format!(
+            "delivery {delivery_id} is {}; only failed deliveries can be retried",
+            delivery.status
+        ));
+    }
+
+    let adapter = registry
+        .get(&delivery.channel)
+        .ok_or_else(|| format!("No adapter registered for channel '{}'", delivery.channel))?;
+    let external_chat_id = resolve_external_chat_id(db.clone(), delivery.chat_id).await?;
+    let edited = text.filter(|t| !t.trim().is_empty() && *t != delivery.content);
+    let content = edited.as_deref().unwrap_or(&delivery.content);
+
+    let result = adapter.send_text(&external_chat_id, content).await;
+    record_delivery_result(db.clone(), delivery_id, &result, edited).await;
+    load(db).await
+}
+
+/// React to a message in `chat_id` through its channel adapter.
+pub async fn react_to_message(
+    registry: &ChannelRegistry,
+    db: Arc<Database>,
+    chat_id: i64,
+    message_id: &str,
+    emoji: &str,
+) -> Result<(), String> {
+    let routing = get_required_chat_routing(registry, db.clone(), chat_id).await?;
+    let adapter = registry.get(&routing.channel_name).ok_or_else(|| {
+        format!(
+            "No adapter registered for channel '{}'",
+            routing.channel_name
+        )
+    })?;
+    let external_chat_id = resolve_external_chat_id(db, chat_id).await?;
+    adapter.react(&external_chat_id, message_id, emoji).await
+}
+
 pub async fn deliver_and_store_bot_message(
     registry: &ChannelRegistry,
     db: Arc<Database>,
     bot_username: &str,
     chat_id: i64,
     text: &str,
+) -> Result<(), String> {
+    deliver_and_store_bot_message_in_thread(registry, db, bot_username, chat_id, None, text).await
+}
+
+/// Like `deliver_and_store_bot_message`, posting in forum topic `thread_id`
+/// of the chat.
+pub async fn deliver_and_store_bot_message_in_thread(
+    registry: &ChannelRegistry,
+    db: Arc<Database>,
+    bot_username: &str,
+    chat_id: i64,
+    thread_id: Option<i64>,
+    text: &str,
 ) -> Result<(), String> {
     let routing = get_required_chat_routing(registry, db.clone(), chat_id).await?;
-    let external_chat_id = call_blocking(db.clone(), move |d| d.get_chat_external_id(chat_id))
-        .await
-        .map_err(|e| format!("Failed to read external chat id for chat {chat_id}: {e}"))?
-        .unwrap_or_else(|| chat_id.to_string());
+    let external_chat_id = resolve_external_chat_id(db.clone(), chat_id).await?;
 
     if let Some(adapter) = registry.get(&routing.channel_name) {
         if !adapter.is_local_only() {
-            adapter.send_text(&external_chat_id, text).await?;
+            send_tracked(db.clone(), chat_id, &routing.channel_name, text, || {
+                adapter.send_text_in_thread(&external_chat_id, thread_id, text)
+            })
+            .await?;
         }
     } else {
         return Err(format!(
@@ -147,8 +383,50 @@ pub async fn deliver_and_store_bot_message(
         content: text.to_string(),
         is_from_bot: true,
         timestamp: chrono::Utc::now().to_rfc3339(),
+        thread_id,
     };
     call_blocking(db.clone(), move |d| d.store_message(&msg))
         .await
         .map_err(|e| format!("Failed to store sent message: {e}"))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_document_for_llm() {
+        let (data, media_type) = document_for_llm(b"%PDF-1.7", Some("application/pdf")).unwrap();
+        assert_eq!(
+            (data.as_str(), media_type.as_str()),
+            ("JVBERi0xLjc=", "application/pdf")
+        );
+        // Sniffed when the sender didn't label it
+        assert!(document_for_llm(b"%PDF-1.4", Some("application/octet-stream")).is_some());
+        assert!(document_for_llm(b"hello", Some("text/plain")).is_none());
+    }
+
+    #[test]
+    fn test_with_reply_context() {
+        assert_eq!(with_reply_context("hi".into(), None), "hi");
+        let reply = ReplyContext {
+            sender: "alice".into(),
+            text: "deploy\n  at  noon".into(),
+        };
+        assert_eq!(
+            with_reply_context("#end".into(), Some(&reply)),
+            "#end\n\n[in reply to alice: \"deploy at noon\"]"
+        );
+        let blank = ReplyContext {
+            sender: "bob".into(),
+            text: "  ".into(),
+        };
+        assert_eq!(with_reply_context("hi".into(), Some(&blank)), "hi");
+        let long = ReplyContext {
+            sender: "bob".into(),
+            text: "é".repeat(600),
+        };
+        let out = with_reply_context("hi".into(), Some(&long));
+        assert!(out.ends_with(&format!("{}…\"]", "é".repeat(500))));
+    }
+}
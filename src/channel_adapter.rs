@@ -6,9 +6,37 @@ use async_trait::async_trait;
 
 use crate::channel::ConversationKind;
 
+/// Markup understood by a channel when sending plain bot replies.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TextFormat {
+    #[default]
+    Plain,
+    /// CommonMark-style `**bold**` / `` `code` `` (Discord, Feishu cards, Web)
+    Markdown,
+    /// Slack mrkdwn: `*bold*` / `` `code` ``
+    SlackMrkdwn,
+}
+
+impl TextFormat {
+    pub fn bold(self, text: &str) -> String {
+        match self {
+            TextFormat::Plain => text.to_string(),
+            TextFormat::Markdown => format!("**{text}**"),
+            TextFormat::SlackMrkdwn => format!("*{text}*"),
+        }
+    }
+
+    pub fn code(self, text: &str) -> String {
+        match self {
+            TextFormat::Plain => text.to_string(),
+            TextFormat::Markdown | TextFormat::SlackMrkdwn => format!("`{text}`"),
+        }
+    }
+}
+
 #[async_trait]
 pub trait ChannelAdapter: Send + Sync {
-    /// Unique name: "telegram", "discord", "slack", "feishu", "weixin", "web"
+    /// Unique name: "telegram", "discord", "slack", "feishu", "weixin", "signal", "teams", "webhook", "web"
     fn name(&self) -> &str;
 
     /// DB chat_type strings this adapter handles + whether each is private/group.
@@ -24,9 +52,32 @@ pub trait ChannelAdapter: Send + Sync {
         true
     }
 
+    /// Whether `send_text` shows tool approval prompts (see `approvals`)
+    /// with Approve/Deny buttons. Without them, high-risk tools fall back to
+    /// the approval token. Default: false.
+    fn has_approval_buttons(&self) -> bool {
+        false
+    }
+
+    /// Markup used when rendering command replies. Default: plain text.
+    fn text_format(&self) -> TextFormat {
+        TextFormat::Plain
+    }
+
     /// Send text to external chat. Called by deliver_and_store_bot_message.
     async fn send_text(&self, external_chat_id: &str, text: &str) -> Result<(), String>;
 
+    /// Send text into forum topic `thread_id` of the chat (Telegram topics).
+    /// Default: ignore the topic and post in the chat.
+    async fn send_text_in_thread(
+        &self,
+        external_chat_id: &str,
+        _thread_id: Option<i64>,
+        text: &str,
+    ) -> Result<(), String> {
+        self.send_text(external_chat_id, text).await
+    }
+
     /// Send file attachment. Default: not supported.
     async fn send_attachment(
         &self,
@@ -36,6 +87,30 @@ pub trait ChannelAdapter: Send + Sync {
     ) -> Result<String, String> {
         Err(format!("attachments not supported for {}", self.name()))
     }
+
+    /// Send file attachment into forum topic `thread_id`. Default: ignore the
+    /// topic.
+    async fn send_attachment_in_thread(
+        &self,
+        external_chat_id: &str,
+        _thread_id: Option<i64>,
+        file_path: &Path,
+        caption: Option<&str>,
+    ) -> Result<String, String> {
+        self.send_attachment(external_chat_id, file_path, caption)
+            .await
+    }
+
+    /// React to a message with an emoji. `message_id` is the platform message
+    /// ID stored with the incoming message. Default: not supported.
+    async fn react(
+        &self,
+        _external_chat_id: &str,
+        _message_id: &str,
+        _emoji: &str,
+    ) -> Result<(), String> {
+        Err(format!("reactions not supported for {}", self.name()))
+    }
 }
 
 #[derive(Default)]
@@ -88,4 +163,11 @@ impl ChannelRegistry {
     pub fn has_any(&self) -> bool {
         !self.adapters.is_empty()
     }
+
+    /// Names of all registered channels, sorted.
+    pub fn channel_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.adapters.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
 }
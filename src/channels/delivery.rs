@@ -1,2 +1,290 @@
-// Delivery logic has been absorbed into individual ChannelAdapter implementations.
-// Text splitting is in src/text.rs.
+//! Streamed reply delivery for channels that can edit a message they sent.
+//!
+//! Once enough text has streamed in, a plain-text draft is posted and then
+//! edited in place at the pace of a [`StreamCadence`]. When the agent run
+//! finishes, [`DraftTarget::finish`] replaces the draft with the formatted
+//! final reply (splitting it as usual). Text splitting is in `src/text.rs`.
+
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tracing::warn;
+
+use crate::agent_engine::AgentEvent;
+use crate::channels::streaming::{StreamCadence, StreamingConfig};
+use crate::text::floor_char_boundary;
+
+/// Why a draft could not be posted or edited.
+#[derive(Debug)]
+pub enum DraftError {
+    /// The platform asked to wait this long before the next request
+    RateLimited(Duration),
+    Failed(String),
+}
+
+/// A chat where a reply draft can be posted and edited.
+#[async_trait]
+pub trait DraftTarget: Send + Sync {
+    /// Platform ID of the posted draft
+    type Id: Clone + Send + Sync;
+
+    /// Channel name, for logs
+    const CHANNEL: &'static str;
+    /// Shortest interval between edits the platform tolerates; raises a
+    /// lower `min_edit_interval_ms` from the config
+    const MIN_EDIT_INTERVAL_MS: u64;
+    /// Longest draft the platform accepts in one message
+    const MAX_DRAFT_LEN: usize;
+
+    async fn post(&self, text: &str) -> Result<Self::Id, DraftError>;
+    async fn edit(&self, id: &Self::Id, text: &str) -> Result<(), DraftError>;
+    async fn delete(&self, id: &Self::Id);
+
+    /// Send a final reply as new messages, split and formatted as usual.
+    async fn send(&self, text: &str) -> Result<(), String>;
+    /// The final reply split into messages, as [`Self::send`] splits it.
+    fn split(&self, text: &str) -> Vec<String>;
+
+    /// Replace the draft's text with a chunk of the final reply.
+    async fn edit_final(&self, id: &Self::Id, text: &str) -> Result<(), DraftError> {
+        self.edit(id, text).await
+    }
+
+    /// Deliver the final reply, replacing the streamed draft when there is one.
+    async fn finish(&self, draft: Option<Self::Id>, text: &str) -> Result<(), String> {
+        let Some(draft) = draft else {
+            return self.send(text).await;
+        };
+        let chunks = self.split(text);
+        let first = chunks.first().map(String::as_str).unwrap_or_default();
+        if self.edit_final(&draft, first).await.is_err() {
+            // The draft is unusable (deleted, too old, ...): start over
+            self.delete(&draft).await;
+            return self.send(text).await;
+        }
+        for chunk in chunks.iter().skip(1) {
+            self.send(chunk).await?;
+        }
+        Ok(())
+    }
+}
+
+/// What the event stream of one agent run left behind.
+pub struct StreamOutcome<Id> {
+    pub used_send_message_tool: bool,
+    pub superseded: bool,
+    /// Draft message holding the partial reply, if streaming posted one
+    pub draft: Option<Id>,
+}
+
+impl<Id> Default for StreamOutcome<Id> {
+    fn default() -> Self {
+        StreamOutcome {
+            used_send_message_tool: false,
+            superseded: false,
+            draft: None,
+        }
+    }
+}
+
+/// Consume agent events, keeping a draft of the streamed reply up to date.
+/// Drafts are plain text: partial Markdown rarely parses. With streaming
+/// disabled this only records what the final delivery needs to know.
+pub async fn stream_reply_draft<T: DraftTarget>(
+    target: T,
+    mut event_rx: UnboundedReceiver<AgentEvent>,
+    mut config: StreamingConfig,
+) -> StreamOutcome<T::Id> {
+    config.min_edit_interval_ms = config.min_edit_interval_ms.max(T::MIN_EDIT_INTERVAL_MS);
+    let mut cadence = StreamCadence::new(config);
+    let mut outcome = StreamOutcome::default();
+    while let Some(event) = event_rx.recv().await {
+        let text = match event {
+            AgentEvent::ToolStart { name } if name == "send_message" => {
+                outcome.used_send_message_tool = true;
+                continue;
+            }
+            AgentEvent::Superseded => {
+                outcome.superseded = true;
+                continue;
+            }
+            // Each model turn streams a fresh reply into the same draft
+            AgentEvent::Iteration { .. } => {
+                cadence.reset();
+                continue;
+            }
+            AgentEvent::TextDelta { delta } => match cadence.push(&delta, Instant::now()) {
+                Some(text) => {
+                    let end = floor_char_boundary(text, T::MAX_DRAFT_LEN.min(text.len()));
+                    text[..end].to_string()
+                }
+                None => continue,
+            },
+            _ => continue,
+        };
+
+        let result = match &outcome.draft {
+            Some(draft) => target.edit(draft, &text).await,
+            None => target.post(&text).await.map(|id| {
+                outcome.draft = Some(id);
+            }),
+        };
+        match result {
+            Ok(()) => {}
+            Err(DraftError::RateLimited(retry_after)) => {
+                warn!(
+                    "{}: draft edit rate-limited for {}s",
+                    T::CHANNEL,
+                    retry_after.as_secs()
+                );
+                cadence.rate_limited(retry_after, Instant::now());
+            }
+            Err(DraftError::Failed(e)) => {
+                warn!(
+                    "{}: draft update failed, sending final reply only: {e}",
+                    T::CHANNEL
+                );
+                cadence.stop();
+            }
+        }
+    }
+    outcome
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct RecordingTarget {
+        calls: Arc<Mutex<Vec<String>>>,
+        rate_limit_edits: bool,
+        fail_edits: bool,
+    }
+
+    #[async_trait]
+    impl DraftTarget for RecordingTarget {
+        type Id = u32;
+        const CHANNEL: &'static str = "test";
+        const MIN_EDIT_INTERVAL_MS: u64 = 0;
+        const MAX_DRAFT_LEN: usize = 8;
+
+        async fn post(&self, text: &str) -> Result<u32, DraftError> {
+            self.calls.lock().unwrap().push(format!("post {text}"));
+            Ok(7)
+        }
+
+        async fn edit(&self, id: &u32, text: &str) -> Result<(), DraftError> {
+            self.calls.lock().unwrap().push(format!("edit {id} {text}"));
+            if self.rate_limit_edits {
+                return Err(DraftError::RateLimited(Duration::from_secs(5)));
+            }
+            if self.fail_edits {
+                return Err(DraftError::Failed("gone".into()));
+            }
+            Ok(())
+        }
+
+        async fn delete(&self, id: &u32) {
+            self.calls.lock().unwrap().push(format!("delete {id}"));
+        }
+
+        async fn send(&self, text: &str) -> Result<(), String> {
+            self.calls.lock().unwrap().push(format!("send {text}"));
+            Ok(())
+        }
+
+        fn split(&self, text: &str) -> Vec<String> {
+            text.split(' ').map(str::to_string).collect()
+        }
+    }
+
+    fn streaming() -> StreamingConfig {
+        StreamingConfig {
+            enabled: true,
+            min_edit_interval_ms: 0,
+            min_chunk_chars: 2,
+            fallback_on_rate_limit: true,
+        }
+    }
+
+    async fn run(target: RecordingTarget, events: Vec<AgentEvent>) -> StreamOutcome<u32> {
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        for event in events {
+            tx.send(event).unwrap();
+        }
+        drop(tx);
+        stream_reply_draft(target, rx, streaming()).await
+    }
+
+    fn delta(text: &str) -> AgentEvent {
+        AgentEvent::TextDelta {
+            delta: text.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_draft_posted_then_edited() {
+        let target = RecordingTarget::default();
+        let outcome = run(
+            target.clone(),
+            vec![
+                delta("Hel"),
+                delta("lo"),
+                AgentEvent::ToolStart {
+                    name: "send_message".into(),
+                },
+                AgentEvent::Iteration { iteration: 2 },
+                delta("Second turn"),
+            ],
+        )
+        .await;
+        assert_eq!(outcome.draft, Some(7));
+        assert!(outcome.used_send_message_tool);
+        assert_eq!(
+            *target.calls.lock().unwrap(),
+            vec!["post Hel", "edit 7 Hello", "edit 7 Second t"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_finish_replaces_draft() {
+        let target = RecordingTarget::default();
+        target.finish(None, "a b").await.unwrap();
+        target.finish(Some(7), "a b").await.unwrap();
+        assert_eq!(
+            *target.calls.lock().unwrap(),
+            vec!["send a b", "edit 7 a", "send b"]
+        );
+
+        // A draft that can't be edited is replaced by a fresh reply
+        let target = RecordingTarget {
+            fail_edits: true,
+            ..Default::default()
+        };
+        target.finish(Some(7), "a b").await.unwrap();
+        assert_eq!(
+            *target.calls.lock().unwrap(),
+            vec!["edit 7 a", "delete 7", "send a b"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_falls_back_to_final_only() {
+        let target = RecordingTarget {
+            rate_limit_edits: true,
+            ..Default::default()
+        };
+        run(
+            target.clone(),
+            vec![delta("One"), delta(" two"), delta(" three")],
+        )
+        .await;
+        assert_eq!(
+            *target.calls.lock().unwrap(),
+            vec!["post One", "edit 7 One two"]
+        );
+    }
+}
@@ -5,30 +5,154 @@ use std::sync::Arc;
 use serde::Deserialize;
 use serde_json::json;
 use serenity::async_trait;
-use serenity::model::channel::Message as DiscordMessage;
+use serenity::builder::{CreateInteractionResponse, CreateInteractionResponseMessage, EditMessage};
+use serenity::http::Http;
+use serenity::model::application::Interaction;
+use serenity::model::channel::{Attachment, Message as DiscordMessage};
 use serenity::model::gateway::Ready;
-use serenity::model::id::ChannelId;
+use serenity::model::id::{ChannelId, MessageId};
 use serenity::prelude::*;
 use tracing::{error, info, warn};
 
-use crate::agent_engine::archive_conversation;
 use crate::agent_engine::process_with_agent_with_events;
 use crate::agent_engine::AgentEvent;
 use crate::agent_engine::AgentRequestContext;
-use crate::channel::ConversationKind;
-use crate::channel_adapter::ChannelAdapter;
+use crate::approvals::ApprovalDecision;
+use crate::channel::{ConversationKind, ReplyContext};
+use crate::channel_adapter::{ChannelAdapter, TextFormat};
+use crate::channels::delivery::{stream_reply_draft, DraftError, DraftTarget};
+use crate::channels::streaming::StreamingConfig;
+use crate::commands::handle_command;
 use crate::db::call_blocking;
 use crate::db::StoredMessage;
-use crate::llm_types::Message as LlmMessage;
 use crate::runtime::AppState;
 use crate::text::{floor_char_boundary, split_text};
-use crate::usage::build_usage_report;
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct DiscordChannelConfig {
     pub bot_token: String,
     #[serde(default)]
     pub allowed_channels: Vec<u64>,
+    #[serde(default)]
+    pub role_permissions: DiscordRolePermissions,
+    /// Edit a draft message while the reply is generated
+    #[serde(default)]
+    pub streaming: StreamingConfig,
+}
+
+/// Capabilities that can be limited to certain Discord roles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiscordCapability {
+    Acp,
+    Bash,
+    Schedule,
+}
+
+impl DiscordCapability {
+    const ALL: [DiscordCapability; 3] = [Self::Acp, Self::Bash, Self::Schedule];
+
+    /// Agent tools withheld from senders without the capability.
+    fn tools(self) -> &'static [&'static str] {
+        match self {
+            Self::Acp => &[
+                "acp_coding",
+                "acp_new_session",
+                "acp_prompt",
+                "acp_end_session",
+                "acp_cancel",
+                "acp_answer",
+                "acp_set_mode",
+                "acp_submit_job",
+            ],
+            // Sub-agents have their own bash tool
+            Self::Bash => &["bash", "sub_agent"],
+            Self::Schedule => &[
+                "schedule_task",
+                "pause_scheduled_task",
+                "resume_scheduled_task",
+                "cancel_scheduled_task",
+            ],
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Acp => "ACP coding sessions",
+            Self::Bash => "shell commands",
+            Self::Schedule => "scheduled tasks",
+        }
+    }
+}
+
+/// Role IDs allowed to use each capability in a server. An empty list leaves
+/// the capability open to everyone.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct DiscordRolePermissions {
+    #[serde(default)]
+    pub acp: Vec<u64>,
+    #[serde(default)]
+    pub bash: Vec<u64>,
+    #[serde(default)]
+    pub schedule: Vec<u64>,
+}
+
+impl DiscordRolePermissions {
+    fn roles_for(&self, capability: DiscordCapability) -> &[u64] {
+        match capability {
+            DiscordCapability::Acp => &self.acp,
+            DiscordCapability::Bash => &self.bash,
+            DiscordCapability::Schedule => &self.schedule,
+        }
+    }
+
+    /// Whether a sender holding `roles` may use `capability`. `roles` is
+    /// `None` in DMs, where restricted capabilities are always denied.
+    pub fn allows(&self, capability: DiscordCapability, roles: Option<&[u64]>) -> bool {
+        let required = self.roles_for(capability);
+        required.is_empty() || roles.is_some_and(|roles| roles.iter().any(|r| required.contains(r)))
+    }
+
+    /// Tools to withhold from an agent run for a sender holding `roles`.
+    pub fn denied_tools(&self, roles: Option<&[u64]>) -> Vec<&'static str> {
+        DiscordCapability::ALL
+            .into_iter()
+            .filter(|c| !self.allows(*c, roles))
+            .flat_map(|c| c.tools().iter().copied())
+            .collect()
+    }
+}
+
+/// Whether `text` starts or controls an ACP session (`#new`, `#end`,
+/// `#stop`, `/acp ...`), ignoring leading mentions.
+fn is_acp_control(text: &str) -> bool {
+    if crate::commands::parse_command(text).is_some_and(|c| c.spec.name == "acp") {
+        return true;
+    }
+    let first = text
+        .split_whitespace()
+        .find(|w| !w.starts_with('@') && !w.starts_with("<@"))
+        .unwrap_or("")
+        .to_lowercase();
+    matches!(first.as_str(), "#new" | "#end" | "#stop")
+}
+
+/// Action row with Approve/Deny buttons for a tool approval prompt.
+fn approval_buttons(id: &str) -> serde_json::Value {
+    let button = |label: &str, style: u8, decision: ApprovalDecision| {
+        json!({
+            "type": 2,
+            "style": style,
+            "label": label,
+            "custom_id": crate::approvals::callback_data(id, decision),
+        })
+    };
+    json!([{
+        "type": 1,
+        "components": [
+            button("Approve", 3, ApprovalDecision::Approve),
+            button("Deny", 4, ApprovalDecision::Deny),
+        ],
+    }])
 }
 
 pub struct DiscordAdapter {
@@ -98,10 +222,18 @@ impl ChannelAdapter for DiscordAdapter {
         "discord"
     }
 
+    fn text_format(&self) -> TextFormat {
+        TextFormat::Markdown
+    }
+
     fn chat_type_routes(&self) -> Vec<(&str, ConversationKind)> {
         vec![("discord", ConversationKind::Private)]
     }
 
+    fn has_approval_buttons(&self) -> bool {
+        true
+    }
+
     async fn send_text(&self, external_chat_id: &str, text: &str) -> Result<(), String> {
         let discord_chat_id = external_chat_id
             .parse::<u64>()
@@ -109,8 +241,13 @@ impl ChannelAdapter for DiscordAdapter {
 
         let url = format!("https://discord.com/api/v10/channels/{discord_chat_id}/messages");
 
-        for chunk in split_text(text, 2000) {
-            let body = json!({ "content": chunk });
+        let chunks = split_text(text, 2000);
+        let last = chunks.len().saturating_sub(1);
+        for (i, chunk) in chunks.into_iter().enumerate() {
+            let mut body = json!({ "content": chunk });
+            if let Some(id) = crate::approvals::prompt_id(text).filter(|_| i == last) {
+                body["components"] = approval_buttons(id);
+            }
             let resp = self
                 .http_client
                 .post(&url)
@@ -137,6 +274,45 @@ impl ChannelAdapter for DiscordAdapter {
         Ok(())
     }
 
+    async fn react(
+        &self,
+        external_chat_id: &str,
+        message_id: &str,
+        emoji: &str,
+    ) -> Result<(), String> {
+        let discord_chat_id = external_chat_id
+            .parse::<u64>()
+            .map_err(|_| format!("Invalid Discord external_chat_id '{}'", external_chat_id))?;
+        let message_id = message_id
+            .parse::<u64>()
+            .map_err(|_| format!("Invalid Discord message_id '{message_id}'"))?;
+        let url = format!(
+            "https://discord.com/api/v10/channels/{discord_chat_id}/messages/{message_id}/reactions/{}/@me",
+            urlencoding::encode(emoji)
+        );
+        let resp = self
+            .http_client
+            .put(&url)
+            .header(
+                reqwest::header::AUTHORIZATION,
+                format!("Bot {}", self.token),
+            )
+            .header(reqwest::header::CONTENT_LENGTH, "0")
+            .send()
+            .await
+            .map_err(|e| format_reqwest_error("Failed to add Discord reaction", &e))?;
+
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            return Err(format!(
+                "Failed to add Discord reaction: HTTP {status} {}",
+                body.chars().take(300).collect::<String>()
+            ));
+        }
+        Ok(())
+    }
+
     async fn send_attachment(
         &self,
         external_chat_id: &str,
@@ -194,6 +370,106 @@ impl ChannelAdapter for DiscordAdapter {
 
 struct Handler {
     app_state: Arc<AppState>,
+    role_permissions: DiscordRolePermissions,
+    streaming: StreamingConfig,
+}
+
+impl Handler {
+    /// Tell the sender they lack a capability.
+    async fn deny(&self, ctx: &Context, msg: &DiscordMessage, capability: DiscordCapability) {
+        info!(
+            "Discord: {} denied {} in channel {}",
+            msg.author.name,
+            capability.label(),
+            msg.channel_id
+        );
+        let reply = format!(
+            "You don't have a role that allows {} here.",
+            capability.label()
+        );
+        let _ = msg.channel_id.say(&ctx.http, reply).await;
+    }
+
+    /// Download an uploaded file and save it under
+    /// `working_dir/uploads/discord/<channel>/`. Returns a `[document]` note
+    /// (or a `[voice]` transcript for audio) for the message text, plus
+    /// `(base64, media_type)` for images and PDFs so the model can look at
+    /// them directly.
+    async fn read_attachment(
+        &self,
+        attachment: &Attachment,
+        external_channel_id: u64,
+    ) -> Result<(String, Option<String>, Option<(String, String)>), String> {
+        let config = &self.app_state.config;
+        let max_bytes = config
+            .max_document_size_mb
+            .saturating_mul(1024)
+            .saturating_mul(1024);
+        if u64::from(attachment.size) > max_bytes {
+            return Err(format!(
+                "Attachment is too large ({} bytes). Max allowed is {} MB.",
+                attachment.size, config.max_document_size_mb
+            ));
+        }
+        let bytes = attachment
+            .download()
+            .await
+            .map_err(|e| format!("Failed to download attachment: {e}"))?;
+
+        let safe_name = attachment
+            .filename
+            .chars()
+            .map(|c| match c {
+                'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '-' | '_' => c,
+                _ => '_',
+            })
+            .collect::<String>();
+        let dir = Path::new(&config.working_dir)
+            .join("uploads")
+            .join("discord")
+            .join(external_channel_id.to_string());
+        let ts = chrono::Utc::now().format("%Y%m%d-%H%M%S");
+        let path = dir.join(format!("{ts}-{safe_name}"));
+        let saved_path = match tokio::fs::create_dir_all(&dir).await {
+            Ok(()) => match tokio::fs::write(&path, &bytes).await {
+                Ok(()) => Some(path.display().to_string()),
+                Err(e) => {
+                    error!("Failed to save discord attachment {}: {e}", path.display());
+                    None
+                }
+            },
+            Err(e) => {
+                error!("Failed to create upload dir {}: {e}", dir.display());
+                None
+            }
+        };
+
+        let mime = attachment.content_type.as_deref();
+        // Voice messages arrive as audio/ogg attachments
+        if let Some(mime) = mime.filter(|m| m.starts_with("audio/")) {
+            if let Some(stt) = crate::transcribe::SttSettings::from_config(config) {
+                let note = crate::transcribe::voice_note_text(&stt, &bytes, mime).await;
+                return Ok((note, saved_path, None));
+            }
+        }
+        let note = format!(
+            "[document] filename={} bytes={} mime={}{}",
+            attachment.filename,
+            bytes.len(),
+            mime.unwrap_or("application/octet-stream"),
+            saved_path
+                .as_ref()
+                .map(|p| format!(" saved_path={p}"))
+                .unwrap_or_default(),
+        );
+        let llm_data = if mime.is_some_and(|m| m.starts_with("image/")) {
+            let opts = crate::image_utils::ImageOptions::from_config(config);
+            Some(crate::image_utils::encode_image_for_llm(bytes, opts).await)
+        } else {
+            crate::channel::document_for_llm(&bytes, mime)
+        };
+        Ok((note, saved_path, llm_data))
+    }
 }
 
 #[async_trait]
@@ -204,7 +480,7 @@ impl EventHandler for Handler {
             return;
         }
 
-        let text = msg.content.clone();
+        let mut text = msg.content.clone();
         let external_channel_id = msg.channel_id.get();
         let channel_id = {
             let external_chat_id = external_channel_id.to_string();
@@ -217,6 +493,12 @@ impl EventHandler for Handler {
             .unwrap_or(external_channel_id as i64)
         };
         let sender_name = msg.author.name.clone();
+        // Guild messages carry the sender's roles; DMs have none
+        let roles: Option<Vec<u64>> = msg
+            .member
+            .as_ref()
+            .map(|m| m.roles.iter().map(|r| r.get()).collect());
+        let roles = roles.as_deref();
 
         // Check allowed channels (empty = all)
         if !self.app_state.config.discord_allowed_channels.is_empty()
@@ -229,80 +511,36 @@ impl EventHandler for Handler {
             return;
         }
 
-        // Handle /reset command
-        if text.trim() == "/reset" {
-            let _ = call_blocking(self.app_state.db.clone(), move |db| {
-                db.clear_chat_context(channel_id)
-            })
-            .await;
-            let _ = msg
-                .channel_id
-                .say(&ctx.http, "Context cleared (session + chat history).")
-                .await;
-            return;
-        }
-
-        // Handle /skills command
-        if text.trim() == "/skills" {
-            let formatted = self.app_state.skills.list_skills_formatted();
-            let _ = msg.channel_id.say(&ctx.http, &formatted).await;
+        if is_acp_control(&text) && !self.role_permissions.allows(DiscordCapability::Acp, roles) {
+            self.deny(&ctx, &msg, DiscordCapability::Acp).await;
             return;
         }
 
-        // Handle /archive command
-        if text.trim() == "/archive" {
-            if let Ok(Some((json, _))) = call_blocking(self.app_state.db.clone(), move |db| {
-                db.load_session(channel_id)
-            })
-            .await
-            {
-                let messages: Vec<LlmMessage> = serde_json::from_str(&json).unwrap_or_default();
-                if messages.is_empty() {
-                    let _ = msg
-                        .channel_id
-                        .say(&ctx.http, "No session to archive.")
-                        .await;
-                } else {
-                    archive_conversation(
-                        &self.app_state.config.data_dir,
-                        "discord",
-                        channel_id,
-                        &messages,
-                    );
-                    let _ = msg
-                        .channel_id
-                        .say(&ctx.http, format!("Archived {} messages.", messages.len()))
-                        .await;
-                }
-            } else {
-                let _ = msg
-                    .channel_id
-                    .say(&ctx.http, "No session to archive.")
-                    .await;
-            }
+        if let Some(reply) = handle_command(&self.app_state, channel_id, "discord", &text).await {
+            let _ = msg.channel_id.say(&ctx.http, reply).await;
             return;
         }
 
-        // Handle /usage command
-        if text.trim() == "/usage" {
-            match build_usage_report(
-                self.app_state.db.clone(),
-                &self.app_state.config,
-                channel_id,
-            )
-            .await
-            {
-                Ok(text) => {
-                    let _ = msg.channel_id.say(&ctx.http, text).await;
+        // Only the first attachment is read, like a Telegram document
+        let mut image_data: Option<(String, String)> = None; // (base64, media_type)
+        let mut document_saved_path: Option<String> = None;
+        if let Some(attachment) = msg.attachments.first() {
+            match self.read_attachment(attachment, external_channel_id).await {
+                Ok((note, saved_path, llm_data)) => {
+                    text = if text.trim().is_empty() {
+                        note
+                    } else {
+                        format!("{}\n\n{note}", text.trim())
+                    };
+                    document_saved_path = saved_path;
+                    image_data = llm_data;
                 }
                 Err(e) => {
-                    let _ = msg
-                        .channel_id
-                        .say(&ctx.http, format!("Failed to query usage statistics: {e}"))
-                        .await;
+                    warn!("Discord: {e}");
+                    let _ = msg.channel_id.say(&ctx.http, e).await;
+                    return;
                 }
             }
-            return;
         }
 
         if text.is_empty() {
@@ -322,34 +560,68 @@ impl EventHandler for Handler {
         })
         .await;
 
+        let reply_context = msg
+            .referenced_message
+            .as_ref()
+            .map(|referenced| ReplyContext {
+                sender: referenced.author.name.clone(),
+                text: referenced.content.clone(),
+            });
         let stored = StoredMessage {
             id: msg.id.get().to_string(),
             chat_id: channel_id,
             sender_name: sender_name.clone(),
-            content: text.clone(),
+            content: crate::channel::with_reply_context(
+                stored_content(&text, image_data.as_ref(), document_saved_path.as_deref()),
+                reply_context.as_ref(),
+            ),
             is_from_bot: false,
             timestamp: chrono::Utc::now().to_rfc3339(),
+            thread_id: None,
         };
         let _ = call_blocking(self.app_state.db.clone(), move |db| {
             db.store_message(&stored)
         })
         .await;
+        if let Some(global_name) = msg.author.global_name.clone() {
+            if msg.guild_id.is_some() && global_name != sender_name {
+                let member = sender_name.clone();
+                let _ = call_blocking(self.app_state.db.clone(), move |db| {
+                    db.set_group_member_display_name(channel_id, &member, &global_name)
+                })
+                .await;
+            }
+        }
 
         // Determine if we should respond
-        let should_respond = if msg.guild_id.is_some() {
-            // In a guild: only respond to @mentions
-            let cache = &ctx.cache;
-            let bot_id = cache.current_user().id;
-            msg.mentions.iter().any(|u| u.id == bot_id)
-        } else {
-            // DM: respond to all messages
-            true
-        };
+        // In a guild: only respond to @mentions; DMs get every message
+        let bot_id = ctx.cache.current_user().id;
+        let should_respond = crate::channel::should_respond(
+            self.app_state.db.clone(),
+            channel_id,
+            msg.guild_id.is_none(),
+            msg.mentions.iter().any(|u| u.id == bot_id),
+        )
+        .await;
 
         if !should_respond {
             return;
         }
 
+        // A bound ACP session receives every message, so it needs the ACP role
+        if !self.role_permissions.allows(DiscordCapability::Acp, roles)
+            && self
+                .app_state
+                .acp_manager
+                .chat_session(channel_id)
+                .await
+                .is_some()
+        {
+            self.deny(&ctx, &msg, DiscordCapability::Acp).await;
+            return;
+        }
+        let denied_tools = self.role_permissions.denied_tools(roles);
+
         info!(
             "Discord message from {} in channel {}: {}",
             sender_name,
@@ -360,21 +632,36 @@ impl EventHandler for Handler {
         // Start typing indicator
         let typing = msg.channel_id.start_typing(&ctx.http);
 
-        let (event_tx, mut event_rx) = tokio::sync::mpsc::unbounded_channel::<AgentEvent>();
+        let (event_tx, event_rx) = tokio::sync::mpsc::unbounded_channel::<AgentEvent>();
+        let streaming = self
+            .streaming
+            .clone()
+            .for_provider(self.app_state.llm.capabilities().streaming);
+        let draft_target = DiscordDraft {
+            http: ctx.http.clone(),
+            channel_id: msg.channel_id,
+        };
+        let stream_task = tokio::spawn(stream_reply_draft(
+            draft_target.clone(),
+            event_rx,
+            streaming,
+        ));
         // Process with shared agent engine (reuses the same loop as Telegram)
         match process_with_agent_with_events(
             &self.app_state,
             AgentRequestContext {
                 caller_channel: "discord",
                 chat_id: channel_id,
+                thread_id: None,
                 chat_type: if msg.guild_id.is_some() {
                     "group"
                 } else {
                     "private"
                 },
+                denied_tools: &denied_tools,
             },
             None,
-            None,
+            image_data,
             Some(&event_tx),
         )
         .await
@@ -382,17 +669,25 @@ impl EventHandler for Handler {
             Ok(response) => {
                 drop(typing);
                 drop(event_tx);
-                let mut used_send_message_tool = false;
-                while let Some(event) = event_rx.recv().await {
-                    if let AgentEvent::ToolStart { name } = event {
-                        if name == "send_message" {
-                            used_send_message_tool = true;
-                        }
+                let stream = stream_task.await.unwrap_or_default();
+                if response.is_empty() {
+                    if let Some(draft) = &stream.draft {
+                        draft_target.delete(draft).await;
                     }
                 }
 
                 if !response.is_empty() {
-                    send_discord_response(&ctx, msg.channel_id, &response).await;
+                    let sent = crate::channel::send_tracked(
+                        self.app_state.db.clone(),
+                        channel_id,
+                        "discord",
+                        &response,
+                        || draft_target.finish(stream.draft, &response),
+                    )
+                    .await;
+                    if let Err(e) = sent {
+                        error!("Discord: failed to send response to channel {channel_id}: {e}");
+                    }
 
                     // Store bot response
                     let bot_msg = StoredMessage {
@@ -402,14 +697,15 @@ impl EventHandler for Handler {
                         content: response,
                         is_from_bot: true,
                         timestamp: chrono::Utc::now().to_rfc3339(),
+                        thread_id: None,
                     };
                     let _ = call_blocking(self.app_state.db.clone(), move |db| {
                         db.store_message(&bot_msg)
                     })
                     .await;
-                } else if !used_send_message_tool {
+                } else if !stream.used_send_message_tool && !stream.superseded {
                     let fallback = "I couldn't produce a visible reply after an automatic retry. Please try again.".to_string();
-                    send_discord_response(&ctx, msg.channel_id, &fallback).await;
+                    let _ = send_discord_response(&ctx.http, msg.channel_id, &fallback).await;
 
                     let bot_msg = StoredMessage {
                         id: uuid::Uuid::new_v4().to_string(),
@@ -418,6 +714,7 @@ impl EventHandler for Handler {
                         content: fallback,
                         is_from_bot: true,
                         timestamp: chrono::Utc::now().to_rfc3339(),
+                        thread_id: None,
                     };
                     let _ = call_blocking(self.app_state.db.clone(), move |db| {
                         db.store_message(&bot_msg)
@@ -427,24 +724,133 @@ impl EventHandler for Handler {
             }
             Err(e) => {
                 drop(typing);
+                drop(event_tx);
+                if let Some(draft) = stream_task.await.ok().and_then(|s| s.draft) {
+                    draft_target.delete(&draft).await;
+                }
                 error!("Error processing Discord message: {e}");
                 let _ = msg.channel_id.say(&ctx.http, format!("Error: {e}")).await;
             }
         }
     }
 
+    /// Approve/Deny button presses under tool approval prompts. The prompt
+    /// is redrawn with the outcome and without its buttons.
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        let Interaction::Component(component) = interaction else {
+            return;
+        };
+        let data = component.data.custom_id.as_str();
+        if crate::approvals::parse_callback_data(data).is_none() {
+            return;
+        }
+        let decision = self.app_state.tool_approvals.resolve_callback(data).await;
+        let text = crate::approvals::answered_prompt(
+            &component.message.content,
+            decision,
+            &component.user.name,
+        );
+        let response = CreateInteractionResponse::UpdateMessage(
+            CreateInteractionResponseMessage::new()
+                .content(text)
+                .components(Vec::new()),
+        );
+        if let Err(e) = component.create_response(&ctx.http, response).await {
+            warn!("Discord: failed to update approval prompt: {e}");
+        }
+    }
+
     async fn ready(&self, _ctx: Context, ready: Ready) {
         info!("Discord bot connected as {}", ready.user.name);
     }
 }
 
+/// Message text as stored in history, marked like Telegram's: `[image]` when
+/// an image goes to the model, else `[document] saved_path=...` for a file.
+fn stored_content(
+    text: &str,
+    image_data: Option<&(String, String)>,
+    document_saved_path: Option<&str>,
+) -> String {
+    let marker = match (image_data, document_saved_path) {
+        (Some((_, media_type)), _) if !crate::llm_types::is_document_media_type(media_type) => {
+            "[image]".to_string()
+        }
+        (_, Some(path)) => format!("[document] saved_path={path}"),
+        _ => return text.to_string(),
+    };
+    if text.trim().is_empty() {
+        marker
+    } else {
+        format!("{marker} {text}")
+    }
+}
+
+/// Streamed reply drafts, edited in place. serenity's HTTP client already
+/// waits out 429s, so errors here are real failures.
+#[derive(Clone)]
+struct DiscordDraft {
+    http: Arc<Http>,
+    channel_id: ChannelId,
+}
+
+#[async_trait]
+impl DraftTarget for DiscordDraft {
+    type Id = MessageId;
+    const CHANNEL: &'static str = "Discord";
+    // Message edits are limited to 5 per 5 seconds per channel
+    const MIN_EDIT_INTERVAL_MS: u64 = 1000;
+    const MAX_DRAFT_LEN: usize = 2000;
+
+    async fn post(&self, text: &str) -> Result<MessageId, DraftError> {
+        self.channel_id
+            .say(&self.http, text)
+            .await
+            .map(|sent| sent.id)
+            .map_err(|e| DraftError::Failed(e.to_string()))
+    }
+
+    async fn edit(&self, id: &MessageId, text: &str) -> Result<(), DraftError> {
+        self.channel_id
+            .edit_message(&self.http, *id, EditMessage::new().content(text))
+            .await
+            .map(|_| ())
+            .map_err(|e| DraftError::Failed(e.to_string()))
+    }
+
+    async fn delete(&self, id: &MessageId) {
+        let _ = self.channel_id.delete_message(&self.http, *id).await;
+    }
+
+    async fn send(&self, text: &str) -> Result<(), String> {
+        send_discord_response(&self.http, self.channel_id, text).await
+    }
+
+    fn split(&self, text: &str) -> Vec<String> {
+        split_text(text, 2000)
+    }
+}
+
 /// Split and send long messages (Discord limit is 2000 chars).
-async fn send_discord_response(ctx: &Context, channel_id: ChannelId, text: &str) {
+async fn send_discord_response(
+    http: &Http,
+    channel_id: ChannelId,
+    text: &str,
+) -> Result<(), String> {
     const MAX_LEN: usize = 2000;
+    let say = |chunk: &str| {
+        let chunk = chunk.to_string();
+        async move {
+            channel_id
+                .say(http, chunk)
+                .await
+                .map(|_| ())
+                .map_err(|e| format!("Failed to send Discord message: {e}"))
+        }
+    };
 
     if text.len() <= MAX_LEN {
-        let _ = channel_id.say(&ctx.http, text).await;
-        return;
+        return say(text).await;
     }
 
     let mut remaining = text;
@@ -456,14 +862,14 @@ async fn send_discord_response(ctx: &Context, channel_id: ChannelId, text: &str)
             remaining[..boundary].rfind('\n').unwrap_or(boundary)
         };
 
-        let chunk = &remaining[..chunk_len];
-        let _ = channel_id.say(&ctx.http, chunk).await;
+        say(&remaining[..chunk_len]).await?;
         remaining = &remaining[chunk_len..];
 
         if remaining.starts_with('\n') {
             remaining = &remaining[1..];
         }
     }
+    Ok(())
 }
 
 async fn run_discord_client(
@@ -471,7 +877,16 @@ async fn run_discord_client(
     token: &str,
     intents: GatewayIntents,
 ) -> Result<(), serenity::Error> {
-    let handler = Handler { app_state };
+    let (role_permissions, streaming) = app_state
+        .config
+        .channel_config::<DiscordChannelConfig>("discord")
+        .map(|c| (c.role_permissions, c.streaming))
+        .unwrap_or_default();
+    let handler = Handler {
+        app_state,
+        role_permissions,
+        streaming,
+    };
     let mut client = Client::builder(token, intents)
         .event_handler(handler)
         .await?;
@@ -506,3 +921,61 @@ pub async fn start_discord_bot(app_state: Arc<AppState>, token: &str) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stored_content_marks_attachments() {
+        let image = ("aGk=".to_string(), "image/png".to_string());
+        let pdf = ("JVBERi0=".to_string(), "application/pdf".to_string());
+        assert_eq!(stored_content("hi", None, None), "hi");
+        assert_eq!(
+            stored_content("", Some(&image), Some("/u/a.png")),
+            "[image]"
+        );
+        assert_eq!(
+            stored_content("read this", Some(&pdf), Some("/u/a.pdf")),
+            "[document] saved_path=/u/a.pdf read this"
+        );
+    }
+
+    #[test]
+    fn test_role_permissions() {
+        let perms: DiscordRolePermissions =
+            serde_yaml::from_str("acp: [10]\nbash: [10, 20]").unwrap();
+        assert!(perms.allows(DiscordCapability::Acp, Some(&[10])));
+        assert!(!perms.allows(DiscordCapability::Acp, Some(&[20])));
+        assert!(perms.allows(DiscordCapability::Bash, Some(&[20])));
+        // Unrestricted everywhere, restricted never in DMs
+        assert!(perms.allows(DiscordCapability::Schedule, None));
+        assert!(!perms.allows(DiscordCapability::Bash, None));
+
+        assert!(perms.denied_tools(Some(&[10])).is_empty());
+        let denied = perms.denied_tools(Some(&[20]));
+        assert!(denied.contains(&"acp_prompt"));
+        assert!(!denied.contains(&"bash"));
+        assert!(!denied.contains(&"schedule_task"));
+        assert!(perms.denied_tools(None).contains(&"sub_agent"));
+    }
+
+    #[test]
+    fn test_is_acp_control() {
+        assert!(is_acp_control("#new claude"));
+        assert!(is_acp_control("<@123> #STOP"));
+        assert!(is_acp_control("<@123> /acp end"));
+        assert!(!is_acp_control("#agents"));
+        assert!(!is_acp_control("let's add a #new feature"));
+    }
+
+    #[test]
+    fn test_approval_buttons() {
+        let rows = approval_buttons("3f9a2c1d");
+        let buttons = rows[0]["components"].as_array().unwrap();
+        assert_eq!(buttons.len(), 2);
+        assert_eq!(buttons[0]["custom_id"], "rayclaw_approval:3f9a2c1d:approve");
+        assert_eq!(buttons[1]["custom_id"], "rayclaw_approval:3f9a2c1d:deny");
+        assert_eq!(buttons[1]["style"], 4);
+    }
+}
@@ -9,16 +9,15 @@ use tokio::sync::RwLock;
 use tokio_tungstenite::tungstenite::Message as WsMessage;
 use tracing::{error, info, warn};
 
-use crate::agent_engine::archive_conversation;
 use crate::agent_engine::process_with_agent_with_events;
 use crate::agent_engine::AgentEvent;
 use crate::agent_engine::AgentRequestContext;
 use crate::channel::ConversationKind;
-use crate::channel_adapter::ChannelAdapter;
+use crate::channel_adapter::{ChannelAdapter, TextFormat};
+use crate::commands::handle_command;
 use crate::db::call_blocking;
 use crate::db::StoredMessage;
 use crate::image_utils;
-use crate::llm_types::Message as LlmMessage;
 use crate::runtime::AppState;
 
 type WsSink = Arc<
@@ -31,7 +30,6 @@ type WsSink = Arc<
         >,
     >,
 >;
-use crate::usage::build_usage_report;
 
 // ---------------------------------------------------------------------------
 // Config
@@ -124,7 +122,9 @@ const TEXT_EXTENSIONS: &[&str] = &[
 ];
 
 /// Extensions recognized as images.
-const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "bmp", "webp", "tiff", "svg"];
+const IMAGE_EXTENSIONS: &[&str] = &[
+    "png", "jpg", "jpeg", "gif", "bmp", "webp", "tiff", "svg", "heic", "heif",
+];
 
 fn is_text_extension(ext: &str) -> bool {
     TEXT_EXTENSIONS.contains(&ext.to_lowercase().as_str())
@@ -355,12 +355,39 @@ impl FeishuAdapter {
     }
 }
 
+/// Feishu reactions use named emoji types. Accepts a type name (`THUMBSUP`)
+/// or one of the common Unicode emoji.
+fn feishu_emoji_type(emoji: &str) -> Option<String> {
+    let emoji = emoji.trim();
+    if !emoji.is_empty() && emoji.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Some(emoji.to_string());
+    }
+    let name = match emoji.trim_end_matches('\u{fe0f}') {
+        "👍" => "THUMBSUP",
+        "✅" | "✔" => "DONE",
+        "👌" => "OK",
+        "👀" => "GLANCE",
+        "❤" => "HEART",
+        "👏" => "APPLAUSE",
+        "🔥" => "FIRE",
+        "😄" => "SMILE",
+        "🎉" => "PARTY",
+        "❌" => "CrossMark",
+        _ => return None,
+    };
+    Some(name.to_string())
+}
+
 #[async_trait::async_trait]
 impl ChannelAdapter for FeishuAdapter {
     fn name(&self) -> &str {
         "feishu"
     }
 
+    fn text_format(&self) -> TextFormat {
+        TextFormat::Markdown
+    }
+
     fn chat_type_routes(&self) -> Vec<(&str, ConversationKind)> {
         vec![
             ("feishu_group", ConversationKind::Group),
@@ -402,6 +429,45 @@ impl ChannelAdapter for FeishuAdapter {
         Ok(())
     }
 
+    async fn react(
+        &self,
+        _external_chat_id: &str,
+        message_id: &str,
+        emoji: &str,
+    ) -> Result<(), String> {
+        let emoji_type = feishu_emoji_type(emoji)
+            .ok_or_else(|| format!("No Feishu emoji type known for '{emoji}'"))?;
+        let token = self.ensure_token().await?;
+        let url = format!(
+            "{}/open-apis/im/v1/messages/{message_id}/reactions",
+            self.base_url
+        );
+        let body = serde_json::json!({ "reaction_type": { "emoji_type": emoji_type } });
+        let resp = self
+            .http_client
+            .post(&url)
+            .header(reqwest::header::AUTHORIZATION, format!("Bearer {token}"))
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to add Feishu reaction: {e}"))?;
+
+        let resp_json: serde_json::Value = resp
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse Feishu reaction response: {e}"))?;
+        let code = resp_json.get("code").and_then(|v| v.as_i64()).unwrap_or(-1);
+        if code != 0 {
+            let msg = resp_json
+                .get("msg")
+                .and_then(|v| v.as_str())
+                .unwrap_or("unknown");
+            return Err(format!("Feishu reaction error: code={code} msg={msg}"));
+        }
+        Ok(())
+    }
+
     async fn send_attachment(
         &self,
         external_chat_id: &str,
@@ -1458,31 +1524,33 @@ const DEDUP_TTL: Duration = Duration::from_secs(6 * 3600);
 
 /// Returns `true` if this message_id has already been seen (duplicate).
 /// Checks in-memory cache first, then falls back to the database
-/// (survives restarts). Also evicts expired cache entries on each call.
-async fn is_duplicate_message(message_id: &str, db: &std::sync::Arc<crate::db::Database>) -> bool {
+/// (survives restarts) and finally claims it across instances.
+/// Also evicts expired cache entries on each call.
+async fn is_duplicate_message(message_id: &str, app_state: &AppState) -> bool {
     if message_id.is_empty() {
         return false;
     }
-    let mut cache = DEDUP_CACHE.lock().await;
-    let now = Instant::now();
-    // Evict expired entries
-    cache.retain(|_, ts| now.duration_since(*ts) < DEDUP_TTL);
-    // Check in-memory cache first
-    if cache.contains_key(message_id) {
-        return true;
+    {
+        let mut cache = DEDUP_CACHE.lock().await;
+        let now = Instant::now();
+        // Evict expired entries
+        cache.retain(|_, ts| now.duration_since(*ts) < DEDUP_TTL);
+        // Check in-memory cache first; recording the ID here means a
+        // retransmission that arrives while we check below is dropped
+        if cache.insert(message_id.to_string(), now).is_some() {
+            return true;
+        }
     }
     // Check database (survives restarts)
     let mid = message_id.to_string();
-    let exists = call_blocking(db.clone(), move |db| db.message_exists(&mid))
+    let exists = call_blocking(app_state.db.clone(), move |db| db.message_exists(&mid))
         .await
         .unwrap_or(false);
     if exists {
-        // Warm the in-memory cache so subsequent retransmissions are fast
-        cache.insert(message_id.to_string(), now);
         return true;
     }
-    cache.insert(message_id.to_string(), now);
-    false
+    let claim_key = format!("feishu:message:{message_id}");
+    !app_state.coordinator.claim(&claim_key, DEDUP_TTL).await
 }
 
 // ---------------------------------------------------------------------------
@@ -1565,6 +1633,7 @@ async fn handle_file_message(
 
     let file_size = bytes.len();
     info!("Feishu file downloaded: name={file_name}, size={file_size}, ext={ext}");
+    let saved_path = save_upload(&app_state.config, chat_id_str, file_name, &bytes).await;
 
     // Three-path classification
     let text: String;
@@ -1572,9 +1641,8 @@ async fn handle_file_message(
 
     if is_image_extension(&ext) && file_size <= FILE_MAX_IMAGE_BYTES {
         // Path 1: Image file → base64 for LLM vision
-        let b64 = image_utils::base64_encode(&bytes);
-        let media = image_utils::guess_image_media_type(&bytes);
-        image_data = Some((b64, media));
+        let opts = image_utils::ImageOptions::from_config(&app_state.config);
+        image_data = Some(image_utils::encode_image_for_llm(bytes, opts).await);
         text = format!("Please analyze this image file: {file_name}");
     } else if (is_text_extension(&ext) || looks_like_text(&bytes))
         && file_size <= FILE_MAX_INLINE_BYTES
@@ -1595,6 +1663,11 @@ async fn handle_file_message(
             "The user sent a file that cannot be displayed inline.\n[FILE: {file_name} | size={size_human} | ext={ext}]"
         );
     }
+    // Keep the original on disk so tools can work with it
+    let text = match saved_path {
+        Some(path) => format!("[document] saved_path={path} {text}"),
+        None => text,
+    };
 
     // File messages in groups: @mention info isn't available in file content JSON,
     // so we treat file messages as always "mentioned" in DMs and not in groups.
@@ -1616,6 +1689,122 @@ async fn handle_file_message(
     .await;
 }
 
+/// Save a downloaded file under `working_dir/uploads/feishu/<chat>/`.
+/// Returns the saved path, or `None` if it could not be written.
+async fn save_upload(
+    config: &crate::config::Config,
+    chat_id_str: &str,
+    file_name: &str,
+    bytes: &[u8],
+) -> Option<String> {
+    let dir = std::path::Path::new(&config.working_dir)
+        .join("uploads")
+        .join("feishu")
+        .join(sanitize_filename(chat_id_str));
+    if let Err(e) = tokio::fs::create_dir_all(&dir).await {
+        error!("Feishu: failed to create upload dir {}: {e}", dir.display());
+        return None;
+    }
+    // No whitespace: the path is embedded in `saved_path=` markers
+    let safe_name = sanitize_filename(file_name).replace(char::is_whitespace, "_");
+    let ts = chrono::Utc::now().format("%Y%m%d-%H%M%S");
+    let path = dir.join(format!("{ts}-{safe_name}"));
+    match tokio::fs::write(&path, bytes).await {
+        Ok(()) => Some(path.display().to_string()),
+        Err(e) => {
+            error!("Feishu: failed to save upload {}: {e}", path.display());
+            None
+        }
+    }
+}
+
+/// Handle an "audio" message: download the voice clip and transcribe it when
+/// a speech-to-text backend is configured.
+#[allow(clippy::too_many_arguments)]
+async fn handle_audio_message(
+    app_state: &Arc<AppState>,
+    feishu_cfg: &FeishuChannelConfig,
+    base_url: &str,
+    bot_open_id: &str,
+    chat_id_str: &str,
+    sender_open_id: &str,
+    content_raw: &str,
+    message_id: &str,
+    is_dm: bool,
+) {
+    // Parse content: {"file_key":"...", "duration":1234}
+    let content: serde_json::Value = match serde_json::from_str(content_raw) {
+        Ok(v) => v,
+        Err(_) => return,
+    };
+    let file_key = content
+        .get("file_key")
+        .and_then(|v| v.as_str())
+        .unwrap_or("");
+    if file_key.is_empty() {
+        return;
+    }
+
+    let text = if let Some(stt) = crate::transcribe::SttSettings::from_config(&app_state.config) {
+        let http_client = reqwest::Client::new();
+        let bytes = match get_token(
+            &http_client,
+            base_url,
+            &feishu_cfg.app_id,
+            &feishu_cfg.app_secret,
+        )
+        .await
+        {
+            Ok(token) => {
+                download_feishu_resource(
+                    &http_client,
+                    base_url,
+                    &token,
+                    message_id,
+                    file_key,
+                    "file",
+                )
+                .await
+            }
+            Err(e) => Err(format!("failed to get token: {e}")),
+        };
+        match bytes {
+            Ok(bytes) => {
+                info!(
+                    "Feishu audio downloaded: key={file_key}, size={}",
+                    bytes.len()
+                );
+                // Feishu voice clips are Opus in an Ogg container
+                crate::transcribe::voice_note_text(&stt, &bytes, "audio/ogg").await
+            }
+            Err(e) => {
+                error!("Feishu: failed to download audio {file_key}: {e}");
+                return;
+            }
+        }
+    } else {
+        "[voice] [not transcribed: no speech-to-text backend configured]".to_string()
+    };
+
+    // Like file messages, audio carries no @mention info
+    let is_mentioned = false;
+
+    handle_feishu_message(
+        app_state.clone(),
+        feishu_cfg,
+        base_url,
+        bot_open_id,
+        chat_id_str,
+        sender_open_id,
+        &text,
+        is_dm,
+        is_mentioned,
+        message_id,
+        None,
+    )
+    .await;
+}
+
 /// Handle a Feishu event envelope. Dispatches im.message.receive_v1 events.
 async fn handle_feishu_event(
     app_state: Arc<AppState>,
@@ -1690,7 +1879,7 @@ async fn handle_feishu_event(
     info!("Feishu event: message_id={message_id}, chat_id={chat_id_str}");
 
     // Deduplicate: skip if this message_id was already processed (in-memory + DB)
-    if is_duplicate_message(message_id, &app_state.db).await {
+    if is_duplicate_message(message_id, &app_state).await {
         info!("Feishu: skipping duplicate message_id={message_id}");
         return;
     }
@@ -1703,7 +1892,8 @@ async fn handle_feishu_event(
 
     // --- File message handling (three-path strategy) ---
     // For "file" messages, download and classify: image → base64, text → inline, binary → metadata
-    if message_type == "file" {
+    // "media" (video) messages carry the same file_key/file_name content.
+    if message_type == "file" || message_type == "media" {
         handle_file_message(
             &app_state,
             feishu_cfg,
@@ -1719,6 +1909,22 @@ async fn handle_feishu_event(
         return;
     }
 
+    if message_type == "audio" {
+        handle_audio_message(
+            &app_state,
+            feishu_cfg,
+            base_url,
+            bot_open_id,
+            chat_id_str,
+            sender_open_id,
+            content_raw,
+            message_id,
+            is_dm,
+        )
+        .await;
+        return;
+    }
+
     let text = parse_message_content(content_raw, message_type);
 
     // --- Image handling (supports multiple images from post messages) ---
@@ -1756,9 +1962,10 @@ async fn handle_feishu_event(
                                 bytes.len()
                             );
                             if i == 0 {
-                                let b64 = image_utils::base64_encode(&bytes);
-                                let media = image_utils::guess_image_media_type(&bytes);
-                                image_data = Some((b64, media));
+                                let opts =
+                                    image_utils::ImageOptions::from_config(&app_state.config);
+                                image_data =
+                                    Some(image_utils::encode_image_for_llm(bytes, opts).await);
                             }
                             // TODO: when LLM supports multiple images, pass all of them
                         }
@@ -1794,21 +2001,18 @@ async fn handle_feishu_event(
         return;
     }
 
-    // Check if bot is mentioned in group messages
-    let is_mentioned = if !is_dm {
-        if let Some(mentions) = message.get("mentions").and_then(|v| v.as_array()) {
+    // Check if bot is mentioned (DMs need it only in observer mode)
+    let is_mentioned = message
+        .get("mentions")
+        .and_then(|v| v.as_array())
+        .is_some_and(|mentions| {
             mentions.iter().any(|m| {
                 m.pointer("/id/open_id")
                     .and_then(|v| v.as_str())
                     .map(|id| id == bot_open_id)
                     .unwrap_or(false)
             })
-        } else {
-            false
-        }
-    } else {
-        false
-    };
+        });
 
     handle_feishu_message(
         app_state,
@@ -1869,6 +2073,7 @@ async fn handle_feishu_message(
         content: text.to_string(),
         is_from_bot: false,
         timestamp: chrono::Utc::now().to_rfc3339(),
+        thread_id: None,
     };
     let _ = call_blocking(app_state.db.clone(), move |db| db.store_message(&stored)).await;
 
@@ -1889,88 +2094,15 @@ async fn handle_feishu_message(
         }
     };
 
-    let trimmed = text.trim();
-    if trimmed == "/reset" {
-        let _ = call_blocking(app_state.db.clone(), move |db| {
-            db.clear_chat_context(chat_id)
-        })
-        .await;
-        let _ = send_feishu_response(
-            &http_client,
-            base_url,
-            &token,
-            external_chat_id,
-            "Context cleared (session + chat history).",
-        )
-        .await;
-        return;
-    }
-    if trimmed == "/skills" {
-        let formatted = app_state.skills.list_skills_formatted();
-        let _ = send_feishu_response(&http_client, base_url, &token, external_chat_id, &formatted)
-            .await;
-        return;
-    }
-    if trimmed == "/archive" {
-        if let Ok(Some((json, _))) =
-            call_blocking(app_state.db.clone(), move |db| db.load_session(chat_id)).await
-        {
-            let messages: Vec<LlmMessage> = serde_json::from_str(&json).unwrap_or_default();
-            if messages.is_empty() {
-                let _ = send_feishu_response(
-                    &http_client,
-                    base_url,
-                    &token,
-                    external_chat_id,
-                    "No session to archive.",
-                )
-                .await;
-            } else {
-                archive_conversation(&app_state.config.data_dir, "feishu", chat_id, &messages);
-                let _ = send_feishu_response(
-                    &http_client,
-                    base_url,
-                    &token,
-                    external_chat_id,
-                    &format!("Archived {} messages.", messages.len()),
-                )
-                .await;
-            }
-        } else {
-            let _ = send_feishu_response(
-                &http_client,
-                base_url,
-                &token,
-                external_chat_id,
-                "No session to archive.",
-            )
-            .await;
-        }
-        return;
-    }
-    if trimmed == "/usage" {
-        match build_usage_report(app_state.db.clone(), &app_state.config, chat_id).await {
-            Ok(report) => {
-                let _ =
-                    send_feishu_response(&http_client, base_url, &token, external_chat_id, &report)
-                        .await;
-            }
-            Err(e) => {
-                let _ = send_feishu_response(
-                    &http_client,
-                    base_url,
-                    &token,
-                    external_chat_id,
-                    &format!("Failed to query usage statistics: {e}"),
-                )
-                .await;
-            }
-        }
+    if let Some(reply) = handle_command(&app_state, chat_id, "feishu", text).await {
+        let _ =
+            send_feishu_response(&http_client, base_url, &token, external_chat_id, &reply).await;
         return;
     }
 
     // Determine if we should respond
-    let should_respond = is_dm || is_mentioned;
+    let should_respond =
+        crate::channel::should_respond(app_state.db.clone(), chat_id, is_dm, is_mentioned).await;
     if !should_respond {
         return;
     }
@@ -1989,7 +2121,9 @@ async fn handle_feishu_message(
         AgentRequestContext {
             caller_channel: "feishu",
             chat_id,
+            thread_id: None,
             chat_type: if is_dm { "private" } else { "group" },
+            denied_tools: &[],
         },
         None,
         image_data,
@@ -2000,24 +2134,35 @@ async fn handle_feishu_message(
         Ok(response) => {
             drop(event_tx);
             let mut used_send_message_tool = false;
+            let mut superseded = false;
             while let Some(event) = event_rx.recv().await {
-                if let AgentEvent::ToolStart { name } = event {
-                    if name == "send_message" {
+                match event {
+                    AgentEvent::ToolStart { name } if name == "send_message" => {
                         used_send_message_tool = true;
                     }
+                    AgentEvent::Superseded => superseded = true,
+                    _ => {}
                 }
             }
 
             if !response.is_empty() {
-                if let Err(e) = send_feishu_response(
-                    &http_client,
-                    base_url,
-                    &token,
-                    external_chat_id,
+                let sent = crate::channel::send_tracked(
+                    app_state.db.clone(),
+                    chat_id,
+                    "feishu",
                     &response,
+                    || {
+                        send_feishu_response(
+                            &http_client,
+                            base_url,
+                            &token,
+                            external_chat_id,
+                            &response,
+                        )
+                    },
                 )
-                .await
-                {
+                .await;
+                if let Err(e) = sent {
                     error!("Feishu: failed to send response: {e}");
                 }
 
@@ -2028,10 +2173,11 @@ async fn handle_feishu_message(
                     content: response,
                     is_from_bot: true,
                     timestamp: chrono::Utc::now().to_rfc3339(),
+                    thread_id: None,
                 };
                 let _ =
                     call_blocking(app_state.db.clone(), move |db| db.store_message(&bot_msg)).await;
-            } else if !used_send_message_tool {
+            } else if !used_send_message_tool && !superseded {
                 let fallback =
                     "I couldn't produce a visible reply after an automatic retry. Please try again.";
                 let _ = send_feishu_response(
@@ -2050,6 +2196,7 @@ async fn handle_feishu_message(
                     content: fallback.to_string(),
                     is_from_bot: true,
                     timestamp: chrono::Utc::now().to_rfc3339(),
+                    thread_id: None,
                 };
                 let _ =
                     call_blocking(app_state.db.clone(), move |db| db.store_message(&bot_msg)).await;
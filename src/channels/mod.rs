@@ -1,13 +1,20 @@
 pub mod delivery;
+pub mod streaming;
 
 #[cfg(feature = "discord")]
 pub mod discord;
 #[cfg(feature = "feishu")]
 pub mod feishu;
+#[cfg(feature = "signal")]
+pub mod signal;
 #[cfg(feature = "slack")]
 pub mod slack;
+#[cfg(feature = "teams")]
+pub mod teams;
 #[cfg(feature = "telegram")]
 pub mod telegram;
+#[cfg(feature = "webhook")]
+pub mod webhook;
 #[cfg(feature = "weixin")]
 pub mod weixin;
 
@@ -16,9 +23,15 @@ pub mod weixin;
 pub use discord::DiscordAdapter;
 #[cfg(feature = "feishu")]
 pub use feishu::FeishuAdapter;
+#[cfg(feature = "signal")]
+pub use signal::SignalAdapter;
 #[cfg(feature = "slack")]
 pub use slack::SlackAdapter;
+#[cfg(feature = "teams")]
+pub use teams::TeamsAdapter;
 #[cfg(feature = "telegram")]
 pub use telegram::TelegramAdapter;
+#[cfg(feature = "webhook")]
+pub use webhook::WebhookAdapter;
 #[cfg(feature = "weixin")]
 pub use weixin::WeixinAdapter;
@@ -0,0 +1,839 @@
+//! Signal through a signal-cli daemon.
+//!
+//! signal-cli (`signal-cli -a +NUMBER daemon --tcp` or `--socket`) speaks
+//! newline-delimited JSON-RPC: incoming messages arrive as `receive`
+//! notifications and replies go out with `send`. A 1:1 conversation is the
+//! chat keyed by the sender's number (or UUID); a group is `group:<id>`.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use base64::Engine;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::oneshot;
+use tracing::{error, info, warn};
+
+use crate::agent_engine::process_with_agent_with_events;
+use crate::agent_engine::AgentEvent;
+use crate::agent_engine::AgentRequestContext;
+use crate::channel::ConversationKind;
+use crate::channel_adapter::ChannelAdapter;
+use crate::commands::handle_command;
+use crate::db::call_blocking;
+use crate::db::StoredMessage;
+use crate::runtime::AppState;
+use crate::text::split_text;
+
+const RPC_TIMEOUT: Duration = Duration::from_secs(30);
+const RECONNECT_MIN: Duration = Duration::from_secs(2);
+const RECONNECT_MAX: Duration = Duration::from_secs(60);
+/// Signal turns longer messages into a text attachment
+const MAX_MESSAGE_LEN: usize = 2000;
+/// Typing indicators expire after about 15 seconds
+const TYPING_INTERVAL: Duration = Duration::from_secs(10);
+/// External chat IDs of groups start with this; 1:1 chats are numbers or UUIDs
+const GROUP_PREFIX: &str = "group:";
+/// Stands in for each mention in a message's text
+const MENTION_PLACEHOLDER: char = '\u{FFFC}';
+
+fn default_tcp_address() -> String {
+    "127.0.0.1:7583".into()
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SignalChannelConfig {
+    /// Phone number of the bot's Signal account in E.164 form (`+15551234567`)
+    #[serde(default)]
+    pub account: String,
+    /// signal-cli's Unix socket (`daemon --socket`); used instead of
+    /// `tcp_address` when set
+    #[serde(default)]
+    pub socket_path: Option<String>,
+    /// `host:port` of signal-cli's JSON-RPC listener (`daemon --tcp`)
+    #[serde(default = "default_tcp_address")]
+    pub tcp_address: String,
+    /// Numbers or UUIDs of people who may message the bot; empty means anyone
+    #[serde(default)]
+    pub allowed_senders: Vec<String>,
+    /// Group IDs the bot answers in; empty means all
+    #[serde(default)]
+    pub allowed_groups: Vec<String>,
+}
+
+impl SignalChannelConfig {
+    pub fn is_configured(&self) -> bool {
+        !self.account.trim().is_empty()
+    }
+
+    fn socket_path(&self) -> Option<&str> {
+        self.socket_path.as_deref().filter(|p| !p.trim().is_empty())
+    }
+
+    fn endpoint(&self) -> &str {
+        self.socket_path().unwrap_or(&self.tcp_address)
+    }
+
+    fn is_allowed(&self, message: &IncomingMessage) -> bool {
+        let sender_ok = self.allowed_senders.is_empty()
+            || [&message.sender_number, &message.sender_uuid]
+                .into_iter()
+                .flatten()
+                .any(|id| self.allowed_senders.contains(id));
+        let group_ok = match &message.group_id {
+            Some(group) => self.allowed_groups.is_empty() || self.allowed_groups.contains(group),
+            None => true,
+        };
+        sender_ok && group_ok
+    }
+}
+
+// ---------------------------------------------------------------------------
+// JSON-RPC connection
+// ---------------------------------------------------------------------------
+
+type RpcReader = Box<dyn AsyncRead + Send + Unpin>;
+type RpcWriter = Box<dyn AsyncWrite + Send + Unpin>;
+type PendingCalls = HashMap<String, oneshot::Sender<Result<Value, String>>>;
+
+async fn connect(config: &SignalChannelConfig) -> Result<(RpcReader, RpcWriter), String> {
+    if let Some(path) = config.socket_path() {
+        #[cfg(unix)]
+        {
+            let stream = tokio::net::UnixStream::connect(path)
+                .await
+                .map_err(|e| e.to_string())?;
+            let (reader, writer) = stream.into_split();
+            return Ok((Box::new(reader), Box::new(writer)));
+        }
+        #[cfg(not(unix))]
+        return Err(format!(
+            "socket_path {path} needs Unix domain sockets; use tcp_address on this platform"
+        ));
+    }
+    let stream = tokio::net::TcpStream::connect(&config.tcp_address)
+        .await
+        .map_err(|e| e.to_string())?;
+    let (reader, writer) = stream.into_split();
+    Ok((Box::new(reader), Box::new(writer)))
+}
+
+/// Split a JSON-RPC response into its request ID and outcome; `None` for
+/// notifications.
+fn parse_response(message: &Value) -> Option<(String, Result<Value, String>)> {
+    if message.get("method").is_some() {
+        return None;
+    }
+    let id = match message.get("id")? {
+        Value::String(id) => id.clone(),
+        other => other.to_string(),
+    };
+    let outcome = match message.get("error") {
+        Some(error) => Err(error
+            .get("message")
+            .and_then(|v| v.as_str())
+            .map(str::to_string)
+            .unwrap_or_else(|| error.to_string())),
+        None => Ok(message.get("result").cloned().unwrap_or(Value::Null)),
+    };
+    Some((id, outcome))
+}
+
+/// Requests to signal-cli over the current connection, if any.
+#[derive(Default)]
+struct RpcClient {
+    writer: tokio::sync::Mutex<Option<RpcWriter>>,
+    pending: std::sync::Mutex<PendingCalls>,
+}
+
+impl RpcClient {
+    async fn call(&self, method: &str, params: Value) -> Result<Value, String> {
+        let id = uuid::Uuid::new_v4().to_string();
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert(id.clone(), tx);
+
+        let request = json!({
+            "jsonrpc": "2.0",
+            "method": method,
+            "params": params,
+            "id": id,
+        });
+        let written = match self.writer.lock().await.as_mut() {
+            Some(writer) => writer
+                .write_all(format!("{request}\n").as_bytes())
+                .await
+                .map_err(|e| e.to_string()),
+            None => Err("not connected to signal-cli".to_string()),
+        };
+        if let Err(e) = written {
+            self.pending.lock().unwrap().remove(&id);
+            return Err(format!("Signal {method}: {e}"));
+        }
+
+        match tokio::time::timeout(RPC_TIMEOUT, rx).await {
+            Ok(Ok(outcome)) => outcome.map_err(|e| format!("Signal {method}: {e}")),
+            Ok(Err(_)) => Err(format!(
+                "Signal {method}: connection to signal-cli was lost"
+            )),
+            Err(_) => {
+                self.pending.lock().unwrap().remove(&id);
+                Err(format!(
+                    "Signal {method}: no answer from signal-cli within {}s",
+                    RPC_TIMEOUT.as_secs()
+                ))
+            }
+        }
+    }
+
+    /// Hand a response to the call waiting for it. Returns false for
+    /// anything that isn't a response.
+    fn resolve(&self, message: &Value) -> bool {
+        let Some((id, outcome)) = parse_response(message) else {
+            return false;
+        };
+        if let Some(tx) = self.pending.lock().unwrap().remove(&id) {
+            let _ = tx.send(outcome);
+        }
+        true
+    }
+
+    async fn set_writer(&self, writer: Option<RpcWriter>) {
+        let disconnected = writer.is_none();
+        *self.writer.lock().await = writer;
+        if disconnected {
+            // Dropping the senders fails every call still waiting
+            self.pending.lock().unwrap().clear();
+        }
+    }
+}
+
+/// `send`/`sendTyping`/`sendReaction` parameters addressing a chat.
+fn target_params(account: &str, external_chat_id: &str) -> Value {
+    match external_chat_id.strip_prefix(GROUP_PREFIX) {
+        Some(group_id) => json!({ "account": account, "groupId": group_id }),
+        None => json!({ "account": account, "recipient": [external_chat_id] }),
+    }
+}
+
+/// A file as a data URI, which signal-cli accepts in place of a path on its
+/// own host.
+fn attachment_data_uri(path: &Path, bytes: &[u8]) -> String {
+    let mime = image::ImageFormat::from_path(path)
+        .map(|f| f.to_mime_type())
+        .unwrap_or("application/octet-stream");
+    let filename = path
+        .file_name()
+        .map(|n| n.to_string_lossy().replace([';', ','], "_"))
+        .unwrap_or_else(|| "attachment.bin".into());
+    format!(
+        "data:{mime};filename={filename};base64,{}",
+        base64::engine::general_purpose::STANDARD.encode(bytes)
+    )
+}
+
+// ---------------------------------------------------------------------------
+// Adapter
+// ---------------------------------------------------------------------------
+
+pub struct SignalAdapter {
+    config: SignalChannelConfig,
+    rpc: RpcClient,
+}
+
+impl SignalAdapter {
+    pub fn new(config: SignalChannelConfig) -> Self {
+        SignalAdapter {
+            config,
+            rpc: RpcClient::default(),
+        }
+    }
+
+    async fn send(&self, external_chat_id: &str, fields: Value) -> Result<Value, String> {
+        let mut params = target_params(&self.config.account, external_chat_id);
+        if let (Some(params), Value::Object(fields)) = (params.as_object_mut(), fields) {
+            params.extend(fields);
+        }
+        self.rpc.call("send", params).await
+    }
+
+    async fn send_typing(&self, external_chat_id: &str) {
+        let params = target_params(&self.config.account, external_chat_id);
+        if let Err(e) = self.rpc.call("sendTyping", params).await {
+            warn!("{e}");
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl ChannelAdapter for SignalAdapter {
+    fn name(&self) -> &str {
+        "signal"
+    }
+
+    fn chat_type_routes(&self) -> Vec<(&str, ConversationKind)> {
+        vec![
+            ("signal_dm", ConversationKind::Private),
+            ("signal_group", ConversationKind::Group),
+        ]
+    }
+
+    async fn send_text(&self, external_chat_id: &str, text: &str) -> Result<(), String> {
+        for chunk in split_text(text, MAX_MESSAGE_LEN) {
+            self.send(external_chat_id, json!({ "message": chunk }))
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn send_attachment(
+        &self,
+        external_chat_id: &str,
+        file_path: &Path,
+        caption: Option<&str>,
+    ) -> Result<String, String> {
+        let bytes = tokio::fs::read(file_path)
+            .await
+            .map_err(|e| format!("Failed to read attachment file: {e}"))?;
+        self.send(
+            external_chat_id,
+            json!({
+                "message": caption.unwrap_or_default(),
+                "attachments": [attachment_data_uri(file_path, &bytes)],
+            }),
+        )
+        .await?;
+        Ok(match caption {
+            Some(c) => format!("[attachment:{}] {}", file_path.display(), c),
+            None => format!("[attachment:{}]", file_path.display()),
+        })
+    }
+
+    /// `message_id` is `<author>:<timestamp>`, as stored for incoming messages.
+    async fn react(
+        &self,
+        external_chat_id: &str,
+        message_id: &str,
+        emoji: &str,
+    ) -> Result<(), String> {
+        let (author, timestamp) = message_id
+            .rsplit_once(':')
+            .and_then(|(author, ts)| Some((author, ts.parse::<i64>().ok()?)))
+            .ok_or_else(|| format!("Not a Signal message ID: {message_id}"))?;
+        let mut params = target_params(&self.config.account, external_chat_id);
+        params["emoji"] = json!(emoji);
+        params["targetAuthor"] = json!(author);
+        params["targetTimestamp"] = json!(timestamp);
+        self.rpc.call("sendReaction", params).await.map(|_| ())
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Inbound
+// ---------------------------------------------------------------------------
+
+/// A text message from a `receive` notification.
+#[derive(Debug, PartialEq)]
+struct IncomingMessage {
+    timestamp: i64,
+    sender_number: Option<String>,
+    sender_uuid: Option<String>,
+    sender_name: String,
+    group_id: Option<String>,
+    group_name: Option<String>,
+    text: String,
+    /// @mentions the bot or quotes one of its messages
+    addressed_to_bot: bool,
+}
+
+/// Replace mention placeholders with `@name`, dropping mentions of the bot.
+/// Returns the text and whether the bot was mentioned. Mention offsets count
+/// UTF-16 code units.
+fn render_mentions(text: &str, mentions: &[Value], account: &str) -> (String, bool) {
+    let by_offset: HashMap<u64, &Value> = mentions
+        .iter()
+        .filter_map(|m| Some((m.get("start")?.as_u64()?, m)))
+        .collect();
+    let mut out = String::with_capacity(text.len());
+    let mut mentioned = false;
+    let mut offset = 0u64;
+    for c in text.chars() {
+        match by_offset.get(&offset).filter(|_| c == MENTION_PLACEHOLDER) {
+            Some(mention) => {
+                let field = |key: &str| {
+                    mention
+                        .get(key)
+                        .and_then(|v| v.as_str())
+                        .filter(|v| !v.is_empty())
+                };
+                if field("number") == Some(account) {
+                    mentioned = true;
+                } else {
+                    out.push('@');
+                    out.push_str(
+                        field("name")
+                            .or_else(|| field("number"))
+                            .or_else(|| field("uuid"))
+                            .unwrap_or("someone"),
+                    );
+                }
+            }
+            None => out.push(c),
+        }
+        offset += c.len_utf16() as u64;
+    }
+    (out.trim().to_string(), mentioned)
+}
+
+impl IncomingMessage {
+    /// Parse the params of a `receive` notification for `account`. Receipts,
+    /// typing indicators, sync messages and messages without text give `None`.
+    fn from_receive(params: &Value, account: &str) -> Option<Self> {
+        // Daemons serving several accounts say which one received it
+        if params
+            .get("account")
+            .and_then(|v| v.as_str())
+            .is_some_and(|a| a != account)
+        {
+            return None;
+        }
+        let envelope = params.get("envelope")?;
+        let data = envelope.get("dataMessage")?;
+        let str_at = |value: &Value, pointer: &str| {
+            value
+                .pointer(pointer)
+                .and_then(|v| v.as_str())
+                .filter(|v| !v.is_empty())
+                .map(str::to_string)
+        };
+
+        let sender_number = str_at(envelope, "/sourceNumber");
+        let sender_uuid = str_at(envelope, "/sourceUuid");
+        if sender_number.as_deref() == Some(account) {
+            return None;
+        }
+        let mentions = data
+            .get("mentions")
+            .and_then(|v| v.as_array())
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let raw_text = str_at(data, "/message")?;
+        let (text, mentioned) = render_mentions(&raw_text, mentions, account);
+        if text.is_empty() {
+            return None;
+        }
+        let quoted_bot = [
+            str_at(data, "/quote/authorNumber"),
+            str_at(data, "/quote/author"),
+        ]
+        .iter()
+        .any(|author| author.as_deref() == Some(account));
+
+        Some(IncomingMessage {
+            timestamp: envelope
+                .get("timestamp")
+                .or_else(|| data.get("timestamp"))
+                .and_then(|v| v.as_i64())?,
+            sender_name: str_at(envelope, "/sourceName")
+                .or_else(|| sender_number.clone())
+                .or_else(|| sender_uuid.clone())?,
+            sender_number,
+            sender_uuid,
+            group_id: str_at(data, "/groupInfo/groupId"),
+            group_name: str_at(data, "/groupInfo/groupName"),
+            text,
+            addressed_to_bot: mentioned || quoted_bot,
+        })
+    }
+
+    fn sender_id(&self) -> &str {
+        self.sender_number
+            .as_deref()
+            .or(self.sender_uuid.as_deref())
+            .unwrap_or_default()
+    }
+
+    fn external_chat_id(&self) -> String {
+        match &self.group_id {
+            Some(group_id) => format!("{GROUP_PREFIX}{group_id}"),
+            None => self.sender_id().to_string(),
+        }
+    }
+
+    /// `<author>:<timestamp>`, which is what a reaction must point at.
+    fn message_id(&self) -> String {
+        format!("{}:{}", self.sender_id(), self.timestamp)
+    }
+}
+
+fn handle_line(app_state: &Arc<AppState>, adapter: &Arc<SignalAdapter>, line: &str) {
+    let message: Value = match serde_json::from_str(line) {
+        Ok(message) => message,
+        Err(e) => {
+            warn!("Signal: ignoring malformed line from signal-cli: {e}");
+            return;
+        }
+    };
+    if adapter.rpc.resolve(&message) {
+        return;
+    }
+    if message.get("method").and_then(|v| v.as_str()) != Some("receive") {
+        return;
+    }
+    let Some(incoming) = message
+        .get("params")
+        .and_then(|p| IncomingMessage::from_receive(p, &adapter.config.account))
+    else {
+        return;
+    };
+    if !adapter.config.is_allowed(&incoming) {
+        return;
+    }
+    tokio::spawn(handle_signal_message(
+        app_state.clone(),
+        adapter.clone(),
+        incoming,
+    ));
+}
+
+async fn handle_signal_message(
+    app_state: Arc<AppState>,
+    adapter: Arc<SignalAdapter>,
+    message: IncomingMessage,
+) {
+    let external_chat_id = message.external_chat_id();
+    let is_dm = message.group_id.is_none();
+    let chat_type = if is_dm { "signal_dm" } else { "signal_group" };
+    let title = match &message.group_name {
+        Some(name) => format!("signal-{name}"),
+        None if is_dm => format!("signal-{}", message.sender_name),
+        None => format!("signal-{external_chat_id}"),
+    };
+
+    let chat_id = call_blocking(app_state.db.clone(), {
+        let external_id = external_chat_id.clone();
+        let chat_type = chat_type.to_string();
+        move |db| db.resolve_or_create_chat_id("signal", &external_id, Some(&title), &chat_type)
+    })
+    .await
+    .unwrap_or(0);
+
+    if chat_id == 0 {
+        error!("Signal: failed to resolve chat ID for {external_chat_id}");
+        return;
+    }
+
+    let stored = StoredMessage {
+        id: message.message_id(),
+        chat_id,
+        sender_name: message.sender_name.clone(),
+        content: message.text.clone(),
+        is_from_bot: false,
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        thread_id: None,
+    };
+    let _ = call_blocking(app_state.db.clone(), move |db| db.store_message(&stored)).await;
+
+    if let Some(reply) = handle_command(&app_state, chat_id, "signal", &message.text).await {
+        let _ = adapter.send_text(&external_chat_id, &reply).await;
+        return;
+    }
+
+    let should_respond = crate::channel::should_respond(
+        app_state.db.clone(),
+        chat_id,
+        is_dm,
+        message.addressed_to_bot,
+    )
+    .await;
+    if !should_respond {
+        return;
+    }
+
+    info!(
+        "Signal message from {} in {}: {}",
+        message.sender_name,
+        external_chat_id,
+        message.text.chars().take(100).collect::<String>()
+    );
+
+    let typing_handle = tokio::spawn({
+        let adapter = adapter.clone();
+        let external_chat_id = external_chat_id.clone();
+        async move {
+            loop {
+                adapter.send_typing(&external_chat_id).await;
+                tokio::time::sleep(TYPING_INTERVAL).await;
+            }
+        }
+    });
+
+    let (event_tx, mut event_rx) = tokio::sync::mpsc::unbounded_channel::<AgentEvent>();
+    let result = process_with_agent_with_events(
+        &app_state,
+        AgentRequestContext {
+            caller_channel: "signal",
+            chat_id,
+            thread_id: None,
+            chat_type: if is_dm { "private" } else { "group" },
+            denied_tools: &[],
+        },
+        None,
+        None,
+        Some(&event_tx),
+    )
+    .await;
+    typing_handle.abort();
+
+    match result {
+        Ok(response) => {
+            drop(event_tx);
+            let mut used_send_message_tool = false;
+            let mut superseded = false;
+            while let Some(event) = event_rx.recv().await {
+                match event {
+                    AgentEvent::ToolStart { name } if name == "send_message" => {
+                        used_send_message_tool = true;
+                    }
+                    AgentEvent::Superseded => superseded = true,
+                    _ => {}
+                }
+            }
+
+            let reply = if !response.is_empty() {
+                let sent = crate::channel::send_tracked(
+                    app_state.db.clone(),
+                    chat_id,
+                    "signal",
+                    &response,
+                    || adapter.send_text(&external_chat_id, &response),
+                )
+                .await;
+                if let Err(e) = sent {
+                    error!("Signal: failed to send response to {external_chat_id}: {e}");
+                }
+                response
+            } else if !used_send_message_tool && !superseded {
+                let fallback = "I couldn't produce a visible reply after an automatic retry. Please try again.";
+                let _ = adapter.send_text(&external_chat_id, fallback).await;
+                fallback.to_string()
+            } else {
+                return;
+            };
+
+            let bot_msg = StoredMessage {
+                id: uuid::Uuid::new_v4().to_string(),
+                chat_id,
+                sender_name: app_state.config.bot_username.clone(),
+                content: reply,
+                is_from_bot: true,
+                timestamp: chrono::Utc::now().to_rfc3339(),
+                thread_id: None,
+            };
+            let _ = call_blocking(app_state.db.clone(), move |db| db.store_message(&bot_msg)).await;
+        }
+        Err(e) => {
+            error!("Error processing Signal message: {e}");
+            let _ = adapter
+                .send_text(&external_chat_id, &format!("Error: {e}"))
+                .await;
+        }
+    }
+}
+
+/// Stay connected to signal-cli, reconnecting with backoff, and dispatch
+/// what it sends.
+pub async fn start_signal_bot(app_state: Arc<AppState>, adapter: Arc<SignalAdapter>) {
+    let mut backoff = RECONNECT_MIN;
+    loop {
+        match connect(&adapter.config).await {
+            Ok((reader, writer)) => {
+                info!(
+                    "Signal: connected to signal-cli at {}",
+                    adapter.config.endpoint()
+                );
+                backoff = RECONNECT_MIN;
+                adapter.rpc.set_writer(Some(writer)).await;
+                let mut lines = BufReader::new(reader).lines();
+                loop {
+                    match lines.next_line().await {
+                        Ok(Some(line)) if line.trim().is_empty() => {}
+                        Ok(Some(line)) => handle_line(&app_state, &adapter, &line),
+                        Ok(None) => {
+                            warn!("Signal: signal-cli closed the connection");
+                            break;
+                        }
+                        Err(e) => {
+                            warn!("Signal: lost connection to signal-cli: {e}");
+                            break;
+                        }
+                    }
+                }
+                adapter.rpc.set_writer(None).await;
+            }
+            Err(e) => warn!(
+                "Signal: cannot reach signal-cli at {}: {e}",
+                adapter.config.endpoint()
+            ),
+        }
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(RECONNECT_MAX);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOT: &str = "+15550000000";
+
+    fn config(senders: &[&str], groups: &[&str]) -> SignalChannelConfig {
+        SignalChannelConfig {
+            account: BOT.into(),
+            socket_path: None,
+            tcp_address: default_tcp_address(),
+            allowed_senders: senders.iter().map(|s| s.to_string()).collect(),
+            allowed_groups: groups.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    fn receive(data: Value) -> Value {
+        json!({
+            "envelope": {
+                "source": "+15551112222",
+                "sourceNumber": "+15551112222",
+                "sourceUuid": "a1b2c3d4-0000-4000-8000-000000000001",
+                "sourceName": "Alice",
+                "sourceDevice": 1,
+                "timestamp": 1760000000123_i64,
+                "dataMessage": data,
+            }
+        })
+    }
+
+    #[test]
+    fn test_direct_message() {
+        let params = receive(json!({ "timestamp": 1760000000123_i64, "message": " hi there " }));
+        let message = IncomingMessage::from_receive(&params, BOT).unwrap();
+        assert_eq!(message.text, "hi there");
+        assert_eq!(message.sender_name, "Alice");
+        assert_eq!(message.external_chat_id(), "+15551112222");
+        assert_eq!(message.message_id(), "+15551112222:1760000000123");
+        assert!(!message.addressed_to_bot);
+
+        // Other accounts of a multi-account daemon, receipts and the bot's own messages
+        let mut other_account = params.clone();
+        other_account["account"] = json!("+15559999999");
+        assert!(IncomingMessage::from_receive(&other_account, BOT).is_none());
+        let receipt = json!({ "envelope": { "sourceNumber": "+15551112222", "timestamp": 1, "receiptMessage": {} } });
+        assert!(IncomingMessage::from_receive(&receipt, BOT).is_none());
+        let mut own = params;
+        own["envelope"]["sourceNumber"] = json!(BOT);
+        assert!(IncomingMessage::from_receive(&own, BOT).is_none());
+    }
+
+    #[test]
+    fn test_group_message_mentions() {
+        let params = receive(json!({
+            "message": "\u{FFFC} ask \u{FFFC} about 🎉 \u{FFFC}",
+            "groupInfo": { "groupId": "Z3JvdXA=", "groupName": "Family", "type": "DELIVER" },
+            "mentions": [
+                { "start": 0, "length": 1, "number": BOT, "uuid": "bot-uuid" },
+                { "start": 6, "length": 1, "name": "Bob", "number": "+15553334444" },
+                // After the emoji, which is two UTF-16 units
+                { "start": 17, "length": 1, "uuid": "c0ffee" },
+            ],
+        }));
+        let message = IncomingMessage::from_receive(&params, BOT).unwrap();
+        assert_eq!(message.text, "ask @Bob about 🎉 @c0ffee");
+        assert!(message.addressed_to_bot);
+        assert_eq!(message.external_chat_id(), "group:Z3JvdXA=");
+        assert_eq!(message.group_name.as_deref(), Some("Family"));
+
+        let reply = receive(json!({
+            "message": "thanks",
+            "groupInfo": { "groupId": "Z3JvdXA=" },
+            "quote": { "id": 1, "authorNumber": BOT, "text": "done" },
+        }));
+        assert!(
+            IncomingMessage::from_receive(&reply, BOT)
+                .unwrap()
+                .addressed_to_bot
+        );
+
+        let only_bot_mention = receive(json!({
+            "message": "\u{FFFC}",
+            "mentions": [{ "start": 0, "length": 1, "number": BOT }],
+        }));
+        assert!(IncomingMessage::from_receive(&only_bot_mention, BOT).is_none());
+    }
+
+    #[test]
+    fn test_allow_lists() {
+        let dm = IncomingMessage::from_receive(&receive(json!({ "message": "hi" })), BOT).unwrap();
+        let group = IncomingMessage::from_receive(
+            &receive(json!({ "message": "hi", "groupInfo": { "groupId": "g1" } })),
+            BOT,
+        )
+        .unwrap();
+
+        assert!(config(&[], &[]).is_allowed(&dm));
+        assert!(config(&["+15551112222"], &[]).is_allowed(&dm));
+        assert!(config(&["a1b2c3d4-0000-4000-8000-000000000001"], &[]).is_allowed(&dm));
+        assert!(!config(&["+15557777777"], &[]).is_allowed(&dm));
+        assert!(config(&[], &["g2"]).is_allowed(&dm));
+        assert!(config(&[], &["g1"]).is_allowed(&group));
+        assert!(!config(&[], &["g2"]).is_allowed(&group));
+        assert!(!config(&["+15557777777"], &["g1"]).is_allowed(&group));
+    }
+
+    #[test]
+    fn test_parse_response() {
+        assert_eq!(
+            parse_response(&json!({ "jsonrpc": "2.0", "id": "1", "result": { "timestamp": 5 } })),
+            Some(("1".into(), Ok(json!({ "timestamp": 5 }))))
+        );
+        assert_eq!(
+            parse_response(&json!({
+                "jsonrpc": "2.0",
+                "id": 7,
+                "error": { "code": -1, "message": "Invalid group id" }
+            })),
+            Some(("7".into(), Err("Invalid group id".into())))
+        );
+        assert_eq!(
+            parse_response(&json!({ "jsonrpc": "2.0", "method": "receive", "params": {} })),
+            None
+        );
+    }
+
+    #[test]
+    fn test_target_params_and_attachments() {
+        assert_eq!(
+            target_params(BOT, "+15551112222"),
+            json!({ "account": BOT, "recipient": ["+15551112222"] })
+        );
+        assert_eq!(
+            target_params(BOT, "group:Z3JvdXA="),
+            json!({ "account": BOT, "groupId": "Z3JvdXA=" })
+        );
+        assert_eq!(
+            attachment_data_uri(Path::new("/tmp/chart.png"), b"png"),
+            "data:image/png;filename=chart.png;base64,cG5n"
+        );
+        assert_eq!(
+            attachment_data_uri(Path::new("report;v2.csv"), b""),
+            "data:application/octet-stream;filename=report_v2.csv;base64,"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rpc_call_without_connection_fails() {
+        let rpc = RpcClient::default();
+        let err = rpc.call("send", json!({})).await.unwrap_err();
+        assert!(err.contains("not connected"), "{err}");
+        assert!(rpc.pending.lock().unwrap().is_empty());
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::path::Path;
 use std::sync::Arc;
 
@@ -6,39 +7,230 @@ use serde::Deserialize;
 use tokio_tungstenite::tungstenite::Message as WsMessage;
 use tracing::{error, info, warn};
 
-use crate::agent_engine::archive_conversation;
 use crate::agent_engine::process_with_agent_with_events;
 use crate::agent_engine::AgentEvent;
 use crate::agent_engine::AgentRequestContext;
-use crate::channel::ConversationKind;
-use crate::channel_adapter::ChannelAdapter;
+use crate::approvals::ApprovalDecision;
+use crate::channel::{ConversationKind, ReplyContext};
+use crate::channel_adapter::{ChannelAdapter, TextFormat};
+use crate::channels::delivery::{stream_reply_draft, DraftError, DraftTarget};
+use crate::channels::streaming::StreamingConfig;
+use crate::commands::handle_command;
 use crate::db::call_blocking;
 use crate::db::StoredMessage;
-use crate::llm_types::Message as LlmMessage;
 use crate::runtime::AppState;
 use crate::text::split_text;
-use crate::usage::build_usage_report;
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct SlackChannelConfig {
+    /// Bot token for a single-workspace install. With `workspaces`, used for
+    /// teams that have no entry of their own.
+    #[serde(default)]
     pub bot_token: String,
+    /// Socket Mode token. Shared by every workspace that doesn't set its own
+    /// (one app installed into several workspaces, or an org-wide app).
+    #[serde(default)]
     pub app_token: String,
     #[serde(default)]
     pub allowed_channels: Vec<String>,
+    /// Per-workspace installs. When set, chat IDs are qualified with the team
+    /// ID (`T123:C456`) so channels from different teams never collide.
+    #[serde(default)]
+    pub workspaces: Vec<SlackWorkspaceConfig>,
+    /// Edit a draft message while the reply is generated
+    #[serde(default)]
+    pub streaming: StreamingConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SlackWorkspaceConfig {
+    /// Team ID (`T...`), or the enterprise ID (`E...`) of an org-wide
+    /// Enterprise Grid install, which then covers every team in the org.
+    pub team_id: String,
+    pub bot_token: String,
+    /// Socket Mode token for this workspace; defaults to the top-level one.
+    #[serde(default)]
+    pub app_token: Option<String>,
+    /// Overrides the top-level `allowed_channels` for this workspace.
+    #[serde(default)]
+    pub allowed_channels: Vec<String>,
+}
+
+impl SlackChannelConfig {
+    /// Whether chat IDs are qualified with the Slack team ID.
+    pub fn is_multi_workspace(&self) -> bool {
+        !self.workspaces.is_empty()
+    }
+
+    /// Whether at least one install has both a bot and a Socket Mode token.
+    pub fn is_configured(&self) -> bool {
+        let has_app_token =
+            |token: Option<&str>| !token.unwrap_or(&self.app_token).trim().is_empty();
+        (!self.bot_token.trim().is_empty() && has_app_token(None))
+            || self
+                .workspaces
+                .iter()
+                .any(|w| !w.bot_token.trim().is_empty() && has_app_token(w.app_token.as_deref()))
+    }
+
+    /// The workspace entry serving a team: an exact team match first, then
+    /// an org-wide install for the team's enterprise.
+    fn workspace_for(
+        &self,
+        team_id: Option<&str>,
+        enterprise_id: Option<&str>,
+    ) -> Option<&SlackWorkspaceConfig> {
+        let find = |id: Option<&str>| {
+            id.filter(|id| !id.is_empty())
+                .and_then(|id| self.workspaces.iter().find(|w| w.team_id == id))
+        };
+        find(team_id).or_else(|| find(enterprise_id))
+    }
+
+    /// Bot token for a team, falling back to the top-level `bot_token`.
+    pub fn bot_token_for(
+        &self,
+        team_id: Option<&str>,
+        enterprise_id: Option<&str>,
+    ) -> Option<&str> {
+        self.workspace_for(team_id, enterprise_id)
+            .map(|w| w.bot_token.as_str())
+            .or(Some(self.bot_token.as_str()))
+            .filter(|t| !t.trim().is_empty())
+    }
+
+    /// Channel allow-list for a team; empty means every channel.
+    fn allowed_channels_for(
+        &self,
+        team_id: Option<&str>,
+        enterprise_id: Option<&str>,
+    ) -> &[String] {
+        match self.workspace_for(team_id, enterprise_id) {
+            Some(w) if !w.allowed_channels.is_empty() => &w.allowed_channels,
+            _ => &self.allowed_channels,
+        }
+    }
+
+    /// Distinct Socket Mode tokens, one connection each.
+    fn app_tokens(&self) -> Vec<String> {
+        let mut tokens: Vec<String> = Vec::new();
+        let top = (!self.bot_token.trim().is_empty()).then_some(self.app_token.as_str());
+        let per_workspace = self
+            .workspaces
+            .iter()
+            .map(|w| w.app_token.as_deref().unwrap_or(&self.app_token));
+        for token in top.into_iter().chain(per_workspace) {
+            if !token.trim().is_empty() && !tokens.iter().any(|t| t == token) {
+                tokens.push(token.to_string());
+            }
+        }
+        tokens
+    }
+
+    /// Distinct bot tokens across all installs.
+    fn bot_tokens(&self) -> Vec<String> {
+        let mut tokens: Vec<String> = Vec::new();
+        let all = std::iter::once(self.bot_token.as_str())
+            .chain(self.workspaces.iter().map(|w| w.bot_token.as_str()));
+        for token in all {
+            if !token.trim().is_empty() && !tokens.iter().any(|t| t == token) {
+                tokens.push(token.to_string());
+            }
+        }
+        tokens
+    }
+}
+
+/// External chat ID for a Slack channel: `TEAM:CHANNEL` in multi-workspace
+/// mode, the bare channel ID otherwise.
+pub fn external_chat_id(multi_workspace: bool, team_id: Option<&str>, channel: &str) -> String {
+    match team_id.filter(|t| multi_workspace && !t.is_empty()) {
+        Some(team) => format!("{team}:{channel}"),
+        None => channel.to_string(),
+    }
+}
+
+/// Split an external chat ID into its team (if qualified) and channel.
+/// Slack channel IDs never contain `:`.
+pub fn split_external_chat_id(external_chat_id: &str) -> (Option<&str>, &str) {
+    match external_chat_id.split_once(':') {
+        Some((team, channel)) => (Some(team), channel),
+        None => (None, external_chat_id),
+    }
+}
+
+/// Slack reactions are added by emoji name. Accepts a name (`eyes`,
+/// `:eyes:`) or one of the common Unicode emoji.
+fn slack_reaction_name(emoji: &str) -> Option<String> {
+    let emoji = emoji.trim();
+    let bare = emoji.trim_matches(':');
+    if !bare.is_empty()
+        && bare
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '+'))
+    {
+        return Some(bare.to_string());
+    }
+    let name = match emoji.trim_end_matches('\u{fe0f}') {
+        "👀" => "eyes",
+        "✅" => "white_check_mark",
+        "✔" => "heavy_check_mark",
+        "❌" => "x",
+        "👍" => "+1",
+        "👎" => "-1",
+        "👌" => "ok_hand",
+        "❤" => "heart",
+        "🎉" => "tada",
+        "🔥" => "fire",
+        "🚀" => "rocket",
+        "🙏" => "pray",
+        "👏" => "clap",
+        "🤔" => "thinking_face",
+        "⏳" => "hourglass_flowing_sand",
+        "⚠" => "warning",
+        "😄" => "smile",
+        _ => return None,
+    };
+    Some(name.to_string())
+}
+
+/// Enterprise ID of each team seen in events, so outbound messages to a team
+/// covered by an org-wide install find the org's token.
+static TEAM_ENTERPRISES: std::sync::LazyLock<std::sync::Mutex<HashMap<String, String>>> =
+    std::sync::LazyLock::new(|| std::sync::Mutex::new(HashMap::new()));
+
+fn known_enterprise(team_id: &str) -> Option<String> {
+    TEAM_ENTERPRISES.lock().unwrap().get(team_id).cloned()
 }
 
 pub struct SlackAdapter {
-    bot_token: String,
+    config: SlackChannelConfig,
     http_client: reqwest::Client,
 }
 
 impl SlackAdapter {
-    pub fn new(bot_token: String) -> Self {
+    pub fn new(config: SlackChannelConfig) -> Self {
         SlackAdapter {
-            bot_token,
+            config,
             http_client: reqwest::Client::new(),
         }
     }
+
+    /// Resolve an external chat ID to the bot token and bare channel ID.
+    fn route<'a>(&self, external_chat_id: &'a str) -> Result<(String, &'a str), String> {
+        let (team, channel) = split_external_chat_id(external_chat_id);
+        let enterprise = team.and_then(known_enterprise);
+        let token = self
+            .config
+            .bot_token_for(team, enterprise.as_deref())
+            .ok_or_else(|| {
+                format!(
+                    "No Slack bot token for workspace {}",
+                    team.unwrap_or("(default)")
+                )
+            })?;
+        Ok((token.to_string(), channel))
+    }
 }
 
 #[async_trait::async_trait]
@@ -47,6 +239,10 @@ impl ChannelAdapter for SlackAdapter {
         "slack"
     }
 
+    fn text_format(&self) -> TextFormat {
+        TextFormat::SlackMrkdwn
+    }
+
     fn chat_type_routes(&self) -> Vec<(&str, ConversationKind)> {
         vec![
             ("slack", ConversationKind::Group),
@@ -54,18 +250,28 @@ impl ChannelAdapter for SlackAdapter {
         ]
     }
 
+    fn has_approval_buttons(&self) -> bool {
+        true
+    }
+
     async fn send_text(&self, external_chat_id: &str, text: &str) -> Result<(), String> {
-        for chunk in split_text(text, 4000) {
-            let body = serde_json::json!({
-                "channel": external_chat_id,
+        let (bot_token, channel) = self.route(external_chat_id)?;
+        let chunks = split_text(text, 4000);
+        let last = chunks.len().saturating_sub(1);
+        for (i, chunk) in chunks.into_iter().enumerate() {
+            let mut body = serde_json::json!({
+                "channel": channel,
                 "text": chunk,
             });
+            if let Some(id) = crate::approvals::prompt_id(text).filter(|_| i == last) {
+                body["blocks"] = approval_blocks(&chunk, id);
+            }
             let resp = self
                 .http_client
                 .post("https://slack.com/api/chat.postMessage")
                 .header(
                     reqwest::header::AUTHORIZATION,
-                    format!("Bearer {}", self.bot_token),
+                    format!("Bearer {bot_token}"),
                 )
                 .header(reqwest::header::CONTENT_TYPE, "application/json")
                 .json(&body)
@@ -97,6 +303,50 @@ impl ChannelAdapter for SlackAdapter {
         Ok(())
     }
 
+    async fn react(
+        &self,
+        external_chat_id: &str,
+        message_id: &str,
+        emoji: &str,
+    ) -> Result<(), String> {
+        let name = slack_reaction_name(emoji)
+            .ok_or_else(|| format!("No Slack emoji name known for '{emoji}'"))?;
+        let (bot_token, channel) = self.route(external_chat_id)?;
+        let body = serde_json::json!({
+            "channel": channel,
+            "timestamp": message_id,
+            "name": name,
+        });
+        let resp = self
+            .http_client
+            .post("https://slack.com/api/reactions.add")
+            .header(
+                reqwest::header::AUTHORIZATION,
+                format!("Bearer {bot_token}"),
+            )
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to add Slack reaction: {e}"))?;
+
+        let resp_json: serde_json::Value = resp
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse Slack response: {e}"))?;
+        if resp_json.get("ok").and_then(|v| v.as_bool()) != Some(true) {
+            let err = resp_json
+                .get("error")
+                .and_then(|v| v.as_str())
+                .unwrap_or("unknown");
+            // Reacting twice is not worth failing over
+            if err != "already_reacted" {
+                return Err(format!("Slack API error: {err}"));
+            }
+        }
+        Ok(())
+    }
+
     async fn send_attachment(
         &self,
         external_chat_id: &str,
@@ -111,9 +361,10 @@ impl ChannelAdapter for SlackAdapter {
         let bytes = tokio::fs::read(file_path)
             .await
             .map_err(|e| format!("Failed to read attachment file: {e}"))?;
+        let (bot_token, channel) = self.route(external_chat_id)?;
 
         let form = reqwest::multipart::Form::new()
-            .text("channels", external_chat_id.to_string())
+            .text("channels", channel.to_string())
             .text("initial_comment", caption.unwrap_or_default().to_string())
             .part(
                 "file",
@@ -125,7 +376,7 @@ impl ChannelAdapter for SlackAdapter {
             .post("https://slack.com/api/files.upload")
             .header(
                 reqwest::header::AUTHORIZATION,
-                format!("Bearer {}", self.bot_token),
+                format!("Bearer {bot_token}"),
             )
             .multipart(form)
             .send()
@@ -224,6 +475,137 @@ async fn resolve_bot_user_id(bot_token: &str) -> Result<String, String> {
         .ok_or_else(|| "auth.test response missing user_id".to_string())
 }
 
+/// Fetch the message at `ts` in `channel` (e.g. a thread parent) via
+/// conversations.replies.
+async fn fetch_slack_message(
+    bot_token: &str,
+    channel: &str,
+    ts: &str,
+) -> Result<Option<ReplyContext>, String> {
+    let client = reqwest::Client::new();
+    let resp = client
+        .get("https://slack.com/api/conversations.replies")
+        .header(
+            reqwest::header::AUTHORIZATION,
+            format!("Bearer {bot_token}"),
+        )
+        .query(&[("channel", channel), ("ts", ts), ("limit", "1")])
+        .send()
+        .await
+        .map_err(|e| format!("conversations.replies failed: {e}"))?;
+
+    let body: serde_json::Value = resp
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse conversations.replies response: {e}"))?;
+
+    if body.get("ok").and_then(|v| v.as_bool()) != Some(true) {
+        let err = body
+            .get("error")
+            .and_then(|v| v.as_str())
+            .unwrap_or("unknown");
+        return Err(format!("conversations.replies failed: {err}"));
+    }
+
+    let Some(message) = body.pointer("/messages/0") else {
+        return Ok(None);
+    };
+    let sender = message
+        .get("user")
+        .or_else(|| message.get("username"))
+        .or_else(|| message.get("bot_id"))
+        .and_then(|v| v.as_str())
+        .unwrap_or("unknown")
+        .to_string();
+    let text = message
+        .get("text")
+        .and_then(|v| v.as_str())
+        .unwrap_or("")
+        .to_string();
+    Ok(Some(ReplyContext { sender, text }))
+}
+
+/// Blocks for a tool approval prompt: its text, then Approve/Deny buttons.
+fn approval_blocks(text: &str, id: &str) -> serde_json::Value {
+    let button = |label: &str, style: &str, decision: ApprovalDecision| {
+        serde_json::json!({
+            "type": "button",
+            "text": { "type": "plain_text", "text": label },
+            "style": style,
+            "action_id": format!("rayclaw_approval_{}", label.to_lowercase()),
+            "value": crate::approvals::callback_data(id, decision),
+        })
+    };
+    serde_json::json!([
+        { "type": "section", "text": { "type": "mrkdwn", "text": text } },
+        {
+            "type": "actions",
+            "elements": [
+                button("Approve", "primary", ApprovalDecision::Approve),
+                button("Deny", "danger", ApprovalDecision::Deny),
+            ],
+        },
+    ])
+}
+
+/// Approve/Deny button presses under tool approval prompts, delivered as
+/// `block_actions` payloads (the app needs Interactivity turned on). The
+/// prompt is redrawn with the outcome and without its buttons.
+async fn handle_slack_block_actions(
+    app_state: Arc<AppState>,
+    slack_cfg: Arc<SlackChannelConfig>,
+    payload: serde_json::Value,
+) {
+    let str_at = |pointer: &str| {
+        payload
+            .pointer(pointer)
+            .and_then(|v| v.as_str())
+            .filter(|v| !v.is_empty())
+    };
+    if str_at("/type") != Some("block_actions") {
+        return;
+    }
+    let Some(data) = str_at("/actions/0/value") else {
+        return;
+    };
+    if crate::approvals::parse_callback_data(data).is_none() {
+        return;
+    }
+    let decision = app_state.tool_approvals.resolve_callback(data).await;
+
+    let team_id = str_at("/team/id").or_else(|| str_at("/user/team_id"));
+    let (Some(channel), Some(ts)) = (
+        str_at("/channel/id"),
+        str_at("/container/message_ts").or_else(|| str_at("/message/ts")),
+    ) else {
+        return;
+    };
+    let Some(bot_token) = slack_cfg.bot_token_for(team_id, str_at("/enterprise/id")) else {
+        warn!(
+            "Slack: no bot token for workspace {}",
+            team_id.unwrap_or("?")
+        );
+        return;
+    };
+    let by = str_at("/user/username")
+        .or_else(|| str_at("/user/name"))
+        .unwrap_or("someone");
+    let text = crate::approvals::answered_prompt(
+        str_at("/message/text").unwrap_or_default(),
+        decision,
+        by,
+    );
+    let draft = SlackDraft {
+        client: reqwest::Client::new(),
+        bot_token: bot_token.to_string(),
+        channel: channel.to_string(),
+    };
+    let body = serde_json::json!({ "channel": channel, "ts": ts, "text": text, "blocks": [] });
+    if let Err(e) = draft.call("chat.update", body).await {
+        warn!("Slack: failed to update approval prompt {ts}: {e:?}");
+    }
+}
+
 /// Send a text response to a Slack channel, splitting at 4000 chars.
 async fn send_slack_response(bot_token: &str, channel: &str, text: &str) -> Result<(), String> {
     let client = reqwest::Client::new();
@@ -263,7 +645,106 @@ async fn send_slack_response(bot_token: &str, channel: &str, text: &str) -> Resu
     Ok(())
 }
 
-/// Start the Slack bot using Socket Mode.
+/// Streamed reply drafts, edited with chat.update.
+#[derive(Clone)]
+struct SlackDraft {
+    client: reqwest::Client,
+    bot_token: String,
+    channel: String,
+}
+
+impl SlackDraft {
+    async fn call(
+        &self,
+        method: &str,
+        body: serde_json::Value,
+    ) -> Result<serde_json::Value, DraftError> {
+        let resp = self
+            .client
+            .post(format!("https://slack.com/api/{method}"))
+            .header(
+                reqwest::header::AUTHORIZATION,
+                format!("Bearer {}", self.bot_token),
+            )
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| DraftError::Failed(format!("Slack {method} failed: {e}")))?;
+        if resp.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            let retry_after = resp
+                .headers()
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(1);
+            return Err(DraftError::RateLimited(std::time::Duration::from_secs(
+                retry_after,
+            )));
+        }
+        let json: serde_json::Value = resp
+            .json()
+            .await
+            .map_err(|e| DraftError::Failed(format!("Slack {method} response: {e}")))?;
+        if json.get("ok").and_then(|v| v.as_bool()) != Some(true) {
+            let err = json
+                .get("error")
+                .and_then(|v| v.as_str())
+                .unwrap_or("unknown");
+            return Err(DraftError::Failed(format!("Slack {method} error: {err}")));
+        }
+        Ok(json)
+    }
+}
+
+#[async_trait::async_trait]
+impl DraftTarget for SlackDraft {
+    /// Message `ts`
+    type Id = String;
+    const CHANNEL: &'static str = "Slack";
+    // chat.update is a Tier 3 method (~50 calls a minute)
+    const MIN_EDIT_INTERVAL_MS: u64 = 1500;
+    const MAX_DRAFT_LEN: usize = 4000;
+
+    async fn post(&self, text: &str) -> Result<String, DraftError> {
+        let json = self
+            .call(
+                "chat.postMessage",
+                serde_json::json!({ "channel": self.channel, "text": text }),
+            )
+            .await?;
+        json.get("ts")
+            .and_then(|v| v.as_str())
+            .map(str::to_string)
+            .ok_or_else(|| DraftError::Failed("Slack chat.postMessage returned no ts".into()))
+    }
+
+    async fn edit(&self, ts: &String, text: &str) -> Result<(), DraftError> {
+        self.call(
+            "chat.update",
+            serde_json::json!({ "channel": self.channel, "ts": ts, "text": text }),
+        )
+        .await
+        .map(|_| ())
+    }
+
+    async fn delete(&self, ts: &String) {
+        let body = serde_json::json!({ "channel": self.channel, "ts": ts });
+        if let Err(e) = self.call("chat.delete", body).await {
+            warn!("Slack: failed to delete reply draft {ts}: {e:?}");
+        }
+    }
+
+    async fn send(&self, text: &str) -> Result<(), String> {
+        send_slack_response(&self.bot_token, &self.channel, text).await
+    }
+
+    fn split(&self, text: &str) -> Vec<String> {
+        split_text(text, 4000)
+    }
+}
+
+/// Start the Slack bot using Socket Mode, with one connection per distinct
+/// app token.
 pub async fn start_slack_bot(app_state: Arc<AppState>) {
     let slack_cfg: SlackChannelConfig = match app_state.config.channel_config("slack") {
         Some(c) => c,
@@ -272,36 +753,56 @@ pub async fn start_slack_bot(app_state: Arc<AppState>) {
             return;
         }
     };
-    let app_token = slack_cfg.app_token;
-    let bot_token = slack_cfg.bot_token;
 
-    let bot_user_id = match resolve_bot_user_id(&bot_token).await {
-        Ok(id) => {
-            info!("Slack bot user ID: {id}");
-            id
-        }
-        Err(e) => {
-            error!("Failed to resolve Slack bot user ID: {e}");
-            return;
+    // Bot user IDs of every install, to skip our own messages and detect mentions
+    let mut bot_user_ids = HashMap::new();
+    for bot_token in slack_cfg.bot_tokens() {
+        match resolve_bot_user_id(&bot_token).await {
+            Ok(id) => {
+                info!("Slack bot user ID: {id}");
+                bot_user_ids.insert(bot_token, id);
+            }
+            Err(e) => error!("Failed to resolve Slack bot user ID: {e}"),
         }
-    };
+    }
+    if bot_user_ids.is_empty() {
+        return;
+    }
+    let bot_user_ids = Arc::new(bot_user_ids);
+    let slack_cfg = Arc::new(slack_cfg);
 
-    loop {
-        if let Err(e) =
-            run_socket_mode(app_state.clone(), &app_token, &bot_token, &bot_user_id).await
-        {
-            warn!("Slack Socket Mode disconnected: {e}");
-        }
-        info!("Slack: reconnecting in 5 seconds...");
-        tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+    let mut connections = Vec::new();
+    for app_token in slack_cfg.app_tokens() {
+        let app_state = app_state.clone();
+        let slack_cfg = slack_cfg.clone();
+        let bot_user_ids = bot_user_ids.clone();
+        connections.push(tokio::spawn(async move {
+            loop {
+                if let Err(e) = run_socket_mode(
+                    app_state.clone(),
+                    &app_token,
+                    slack_cfg.clone(),
+                    bot_user_ids.clone(),
+                )
+                .await
+                {
+                    warn!("Slack Socket Mode disconnected: {e}");
+                }
+                info!("Slack: reconnecting in 5 seconds...");
+                tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+            }
+        }));
+    }
+    for connection in connections {
+        let _ = connection.await;
     }
 }
 
 async fn run_socket_mode(
     app_state: Arc<AppState>,
     app_token: &str,
-    bot_token: &str,
-    bot_user_id: &str,
+    slack_cfg: Arc<SlackChannelConfig>,
+    bot_user_ids: Arc<HashMap<String, String>>,
 ) -> Result<(), String> {
     let ws_url = open_socket_mode_connection(app_token).await?;
     info!("Slack Socket Mode: connecting to WebSocket...");
@@ -342,7 +843,13 @@ async fn run_socket_mode(
 
                 let envelope_type = envelope.get("type").and_then(|v| v.as_str()).unwrap_or("");
 
-                if envelope_type == "events_api" {
+                if envelope_type == "interactive" {
+                    tokio::spawn(handle_slack_block_actions(
+                        app_state.clone(),
+                        slack_cfg.clone(),
+                        envelope["payload"].clone(),
+                    ));
+                } else if envelope_type == "events_api" {
                     let event_type = envelope
                         .pointer("/payload/event/type")
                         .and_then(|v| v.as_str())
@@ -362,10 +869,38 @@ async fn run_socket_mode(
                             .and_then(|v| v.as_str())
                             .unwrap_or("")
                             .to_string();
-                        if user == bot_user_id || user.is_empty() {
+                        if user.is_empty() || bot_user_ids.values().any(|id| *id == user) {
                             continue;
                         }
 
+                        let str_at = |pointer: &str| {
+                            envelope
+                                .pointer(pointer)
+                                .and_then(|v| v.as_str())
+                                .filter(|v| !v.is_empty())
+                                .map(str::to_string)
+                        };
+                        let team_id =
+                            str_at("/payload/team_id").or_else(|| str_at("/payload/event/team"));
+                        let enterprise_id = str_at("/payload/enterprise_id");
+                        if let (Some(team), Some(enterprise)) = (&team_id, &enterprise_id) {
+                            TEAM_ENTERPRISES
+                                .lock()
+                                .unwrap()
+                                .insert(team.clone(), enterprise.clone());
+                        }
+                        let Some(bot_token) = slack_cfg
+                            .bot_token_for(team_id.as_deref(), enterprise_id.as_deref())
+                            .map(str::to_string)
+                        else {
+                            warn!(
+                                "Slack: no bot token for workspace {}",
+                                team_id.as_deref().unwrap_or("?")
+                            );
+                            continue;
+                        };
+                        let bot_user_id = bot_user_ids.get(&bot_token).cloned().unwrap_or_default();
+
                         let channel = event
                             .get("channel")
                             .and_then(|v| v.as_str())
@@ -388,25 +923,44 @@ async fn run_socket_mode(
                             .and_then(|v| v.as_str())
                             .unwrap_or("")
                             .to_string();
+                        let thread_ts = event
+                            .get("thread_ts")
+                            .and_then(|v| v.as_str())
+                            .filter(|t| *t != ts)
+                            .map(str::to_string);
 
                         if channel.is_empty() || text_content.is_empty() {
                             continue;
                         }
 
+                        // Check allowed channels filter
+                        let allowed = slack_cfg
+                            .allowed_channels_for(team_id.as_deref(), enterprise_id.as_deref());
+                        if !allowed.is_empty() && !allowed.contains(&channel) {
+                            continue;
+                        }
+                        let external_id = external_chat_id(
+                            slack_cfg.is_multi_workspace(),
+                            team_id.as_deref(),
+                            &channel,
+                        );
+
                         let state = app_state.clone();
-                        let bot_token = bot_token.to_string();
-                        let bot_user_id = bot_user_id.to_string();
+                        let streaming = slack_cfg.streaming.clone();
                         tokio::spawn(async move {
                             handle_slack_message(
                                 state,
+                                streaming,
                                 &bot_token,
                                 &bot_user_id,
                                 &channel,
+                                &external_id,
                                 &user,
                                 &text_content,
                                 is_dm,
                                 is_app_mention,
                                 &ts,
+                                thread_ts.as_deref(),
                             )
                             .await;
                         });
@@ -431,43 +985,46 @@ async fn run_socket_mode(
 #[allow(clippy::too_many_arguments)]
 async fn handle_slack_message(
     app_state: Arc<AppState>,
+    streaming: StreamingConfig,
     bot_token: &str,
     bot_user_id: &str,
     channel: &str,
+    external_id: &str,
     user: &str,
     text: &str,
     is_dm: bool,
     is_app_mention: bool,
     ts: &str,
+    thread_ts: Option<&str>,
 ) {
     let chat_type = if is_dm { "slack_dm" } else { "slack" };
-    let title = format!("slack-{channel}");
+    let title = format!("slack-{external_id}");
 
     let chat_id = call_blocking(app_state.db.clone(), {
-        let channel = channel.to_string();
+        let external_id = external_id.to_string();
         let title = title.clone();
         let chat_type = chat_type.to_string();
-        move |db| db.resolve_or_create_chat_id("slack", &channel, Some(&title), &chat_type)
+        move |db| db.resolve_or_create_chat_id("slack", &external_id, Some(&title), &chat_type)
     })
     .await
     .unwrap_or(0);
 
     if chat_id == 0 {
-        error!("Slack: failed to resolve chat ID for channel {channel}");
+        error!("Slack: failed to resolve chat ID for channel {external_id}");
         return;
     }
 
-    // Check allowed channels filter
-    if let Some(slack_cfg) = app_state
-        .config
-        .channel_config::<SlackChannelConfig>("slack")
-    {
-        if !slack_cfg.allowed_channels.is_empty()
-            && !slack_cfg.allowed_channels.iter().any(|c| c == channel)
-        {
-            return;
-        }
-    }
+    // Replies in a thread carry the thread's parent as context
+    let reply_context = match thread_ts {
+        Some(parent_ts) => match fetch_slack_message(bot_token, channel, parent_ts).await {
+            Ok(parent) => parent,
+            Err(e) => {
+                warn!("Slack: failed to fetch thread parent {parent_ts}: {e}");
+                None
+            }
+        },
+        None => None,
+    };
 
     // Store incoming message
     let stored = StoredMessage {
@@ -478,73 +1035,27 @@ async fn handle_slack_message(
         },
         chat_id,
         sender_name: user.to_string(),
-        content: text.to_string(),
+        content: crate::channel::with_reply_context(text.to_string(), reply_context.as_ref()),
         is_from_bot: false,
         timestamp: chrono::Utc::now().to_rfc3339(),
+        thread_id: None,
     };
     let _ = call_blocking(app_state.db.clone(), move |db| db.store_message(&stored)).await;
 
-    // Handle slash commands
-    let trimmed = text.trim();
-    if trimmed == "/reset" {
-        let _ = call_blocking(app_state.db.clone(), move |db| {
-            db.clear_chat_context(chat_id)
-        })
-        .await;
-        let _ = send_slack_response(
-            bot_token,
-            channel,
-            "Context cleared (session + chat history).",
-        )
-        .await;
-        return;
-    }
-    if trimmed == "/skills" {
-        let formatted = app_state.skills.list_skills_formatted();
-        let _ = send_slack_response(bot_token, channel, &formatted).await;
-        return;
-    }
-    if trimmed == "/archive" {
-        if let Ok(Some((json, _))) =
-            call_blocking(app_state.db.clone(), move |db| db.load_session(chat_id)).await
-        {
-            let messages: Vec<LlmMessage> = serde_json::from_str(&json).unwrap_or_default();
-            if messages.is_empty() {
-                let _ = send_slack_response(bot_token, channel, "No session to archive.").await;
-            } else {
-                archive_conversation(&app_state.config.data_dir, "slack", chat_id, &messages);
-                let _ = send_slack_response(
-                    bot_token,
-                    channel,
-                    &format!("Archived {} messages.", messages.len()),
-                )
-                .await;
-            }
-        } else {
-            let _ = send_slack_response(bot_token, channel, "No session to archive.").await;
-        }
-        return;
-    }
-    if trimmed == "/usage" {
-        match build_usage_report(app_state.db.clone(), &app_state.config, chat_id).await {
-            Ok(report) => {
-                let _ = send_slack_response(bot_token, channel, &report).await;
-            }
-            Err(e) => {
-                let _ = send_slack_response(
-                    bot_token,
-                    channel,
-                    &format!("Failed to query usage statistics: {e}"),
-                )
-                .await;
-            }
-        }
+    if let Some(reply) = handle_command(&app_state, chat_id, "slack", text).await {
+        let _ = send_slack_response(bot_token, channel, &reply).await;
         return;
     }
 
     // Determine if we should respond
     let mention_tag = format!("<@{bot_user_id}>");
-    let should_respond = is_dm || is_app_mention || text.contains(&mention_tag);
+    let should_respond = crate::channel::should_respond(
+        app_state.db.clone(),
+        chat_id,
+        is_dm,
+        is_app_mention || text.contains(&mention_tag),
+    )
+    .await;
 
     if !should_respond {
         return;
@@ -553,18 +1064,31 @@ async fn handle_slack_message(
     info!(
         "Slack message from {} in {}: {}",
         user,
-        channel,
+        external_id,
         text.chars().take(100).collect::<String>()
     );
 
-    let (event_tx, mut event_rx) = tokio::sync::mpsc::unbounded_channel::<AgentEvent>();
+    let (event_tx, event_rx) = tokio::sync::mpsc::unbounded_channel::<AgentEvent>();
+    let streaming = streaming.for_provider(app_state.llm.capabilities().streaming);
+    let draft_target = SlackDraft {
+        client: reqwest::Client::new(),
+        bot_token: bot_token.to_string(),
+        channel: channel.to_string(),
+    };
+    let stream_task = tokio::spawn(stream_reply_draft(
+        draft_target.clone(),
+        event_rx,
+        streaming,
+    ));
 
     match process_with_agent_with_events(
         &app_state,
         AgentRequestContext {
             caller_channel: "slack",
             chat_id,
+            thread_id: None,
             chat_type: if is_dm { "private" } else { "group" },
+            denied_tools: &[],
         },
         None,
         None,
@@ -574,17 +1098,23 @@ async fn handle_slack_message(
     {
         Ok(response) => {
             drop(event_tx);
-            let mut used_send_message_tool = false;
-            while let Some(event) = event_rx.recv().await {
-                if let AgentEvent::ToolStart { name } = event {
-                    if name == "send_message" {
-                        used_send_message_tool = true;
-                    }
+            let stream = stream_task.await.unwrap_or_default();
+            if response.is_empty() {
+                if let Some(ts) = &stream.draft {
+                    draft_target.delete(ts).await;
                 }
             }
 
             if !response.is_empty() {
-                if let Err(e) = send_slack_response(bot_token, channel, &response).await {
+                let sent = crate::channel::send_tracked(
+                    app_state.db.clone(),
+                    chat_id,
+                    "slack",
+                    &response,
+                    || draft_target.finish(stream.draft.clone(), &response),
+                )
+                .await;
+                if let Err(e) = sent {
                     error!("Slack: failed to send response: {e}");
                 }
 
@@ -595,10 +1125,11 @@ async fn handle_slack_message(
                     content: response,
                     is_from_bot: true,
                     timestamp: chrono::Utc::now().to_rfc3339(),
+                    thread_id: None,
                 };
                 let _ =
                     call_blocking(app_state.db.clone(), move |db| db.store_message(&bot_msg)).await;
-            } else if !used_send_message_tool {
+            } else if !stream.used_send_message_tool && !stream.superseded {
                 let fallback = "I couldn't produce a visible reply after an automatic retry. Please try again.";
                 let _ = send_slack_response(bot_token, channel, fallback).await;
 
@@ -609,14 +1140,104 @@ async fn handle_slack_message(
                     content: fallback.to_string(),
                     is_from_bot: true,
                     timestamp: chrono::Utc::now().to_rfc3339(),
+                    thread_id: None,
                 };
                 let _ =
                     call_blocking(app_state.db.clone(), move |db| db.store_message(&bot_msg)).await;
             }
         }
         Err(e) => {
+            drop(event_tx);
+            if let Some(ts) = stream_task.await.ok().and_then(|s| s.draft) {
+                draft_target.delete(&ts).await;
+            }
             error!("Error processing Slack message: {e}");
             let _ = send_slack_response(bot_token, channel, &format!("Error: {e}")).await;
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn multi_config() -> SlackChannelConfig {
+        serde_yaml::from_str(
+            r#"
+bot_token: xoxb-default
+app_token: xapp-shared
+allowed_channels: [C1]
+workspaces:
+  - team_id: T1
+    bot_token: xoxb-t1
+  - team_id: E9
+    bot_token: xoxb-org
+    app_token: xapp-org
+    allowed_channels: [C9]
+"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_bot_token_resolution() {
+        let cfg = multi_config();
+        assert!(cfg.is_configured());
+        assert!(cfg.is_multi_workspace());
+        assert_eq!(cfg.bot_token_for(Some("T1"), None), Some("xoxb-t1"));
+        // Org-wide install matched through the team's enterprise
+        assert_eq!(cfg.bot_token_for(Some("T5"), Some("E9")), Some("xoxb-org"));
+        assert_eq!(cfg.bot_token_for(Some("T7"), None), Some("xoxb-default"));
+        assert_eq!(cfg.allowed_channels_for(Some("T5"), Some("E9")), ["C9"]);
+        assert_eq!(cfg.allowed_channels_for(Some("T1"), None), ["C1"]);
+        assert_eq!(cfg.app_tokens(), vec!["xapp-shared", "xapp-org"]);
+        assert_eq!(
+            cfg.bot_tokens(),
+            vec!["xoxb-default", "xoxb-t1", "xoxb-org"]
+        );
+
+        let legacy: SlackChannelConfig =
+            serde_yaml::from_str("bot_token: xoxb-a\napp_token: xapp-a").unwrap();
+        assert!(legacy.is_configured());
+        assert!(!legacy.is_multi_workspace());
+        assert_eq!(legacy.bot_token_for(Some("T1"), None), Some("xoxb-a"));
+
+        let no_app: SlackChannelConfig =
+            serde_yaml::from_str("workspaces: [{team_id: T1, bot_token: xoxb-t1}]").unwrap();
+        assert!(!no_app.is_configured());
+        assert_eq!(no_app.bot_token_for(Some("T2"), None), None);
+    }
+
+    #[test]
+    fn test_external_chat_id_round_trip() {
+        assert_eq!(external_chat_id(false, Some("T1"), "C1"), "C1");
+        assert_eq!(external_chat_id(true, Some("T1"), "C1"), "T1:C1");
+        assert_eq!(external_chat_id(true, None, "C1"), "C1");
+        assert_eq!(split_external_chat_id("T1:C1"), (Some("T1"), "C1"));
+        assert_eq!(split_external_chat_id("C1"), (None, "C1"));
+    }
+
+    #[test]
+    fn test_approval_blocks() {
+        let blocks = approval_blocks("[Approval 3f9a2c1d] Run high-risk tool `bash`?", "3f9a2c1d");
+        assert_eq!(blocks[0]["text"]["type"], "mrkdwn");
+        let buttons = blocks[1]["elements"].as_array().unwrap();
+        assert_eq!(buttons[0]["value"], "rayclaw_approval:3f9a2c1d:approve");
+        assert_eq!(buttons[0]["style"], "primary");
+        assert_eq!(buttons[1]["value"], "rayclaw_approval:3f9a2c1d:deny");
+        assert_ne!(buttons[0]["action_id"], buttons[1]["action_id"]);
+    }
+
+    #[test]
+    fn test_slack_reaction_name() {
+        assert_eq!(slack_reaction_name("👀").as_deref(), Some("eyes"));
+        assert_eq!(
+            slack_reaction_name("✅").as_deref(),
+            Some("white_check_mark")
+        );
+        assert_eq!(slack_reaction_name("❤️").as_deref(), Some("heart"));
+        assert_eq!(slack_reaction_name(":tada:").as_deref(), Some("tada"));
+        assert_eq!(slack_reaction_name("+1").as_deref(), Some("+1"));
+        assert_eq!(slack_reaction_name("🦀"), None);
+    }
+}
@@ -0,0 +1,213 @@
+//! Cadence control for streaming replies into chat platforms that can only
+//! "stream" by repeatedly editing a draft message.
+//!
+//! Each channel reads a [`StreamingConfig`] from its `channels.<name>.streaming`
+//! block and drives a [`StreamCadence`] with the agent's text deltas. The
+//! cadence decides when the draft is worth editing, so edits stay under the
+//! platform's rate limits.
+
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+fn default_min_edit_interval_ms() -> u64 {
+    1200
+}
+
+fn default_min_chunk_chars() -> usize {
+    40
+}
+
+fn default_fallback_on_rate_limit() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct StreamingConfig {
+    /// Edit a draft message as the reply is generated. Off by default.
+    #[serde(default)]
+    pub enabled: bool,
+    /// Minimum time between two edits of the draft.
+    #[serde(default = "default_min_edit_interval_ms")]
+    pub min_edit_interval_ms: u64,
+    /// Minimum number of new characters before the draft is edited again.
+    #[serde(default = "default_min_chunk_chars")]
+    pub min_chunk_chars: usize,
+    /// On a rate-limit response, stop editing and only send the final reply.
+    /// When false, editing pauses for the platform's retry-after and resumes.
+    #[serde(default = "default_fallback_on_rate_limit")]
+    pub fallback_on_rate_limit: bool,
+}
+
+impl Default for StreamingConfig {
+    fn default() -> Self {
+        StreamingConfig {
+            enabled: false,
+            min_edit_interval_ms: default_min_edit_interval_ms(),
+            min_chunk_chars: default_min_chunk_chars(),
+            fallback_on_rate_limit: default_fallback_on_rate_limit(),
+        }
+    }
+}
+
+impl StreamingConfig {
+    /// These settings for a reply from a provider: drafts only help when
+    /// the provider actually streams text.
+    pub fn for_provider(mut self, provider_streams: bool) -> Self {
+        self.enabled &= provider_streams;
+        self
+    }
+}
+
+/// Accumulates streamed text and decides when the draft should be edited.
+#[derive(Debug)]
+pub struct StreamCadence {
+    config: StreamingConfig,
+    text: String,
+    /// Length of `text` when the draft was last rendered
+    rendered_len: usize,
+    last_edit: Option<Instant>,
+    /// No edits before this instant (set by a retry-after)
+    paused_until: Option<Instant>,
+    stopped: bool,
+}
+
+impl StreamCadence {
+    pub fn new(config: StreamingConfig) -> Self {
+        let stopped = !config.enabled;
+        StreamCadence {
+            config,
+            text: String::new(),
+            rendered_len: 0,
+            last_edit: None,
+            paused_until: None,
+            stopped,
+        }
+    }
+
+    /// Whether drafts are still being edited for this reply.
+    pub fn is_active(&self) -> bool {
+        !self.stopped
+    }
+
+    /// Text streamed so far in the current iteration.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Append a delta. Returns the full text to render when an edit is due.
+    pub fn push(&mut self, delta: &str, now: Instant) -> Option<&str> {
+        self.text.push_str(delta);
+        if self.stopped || self.text.trim().is_empty() {
+            return None;
+        }
+        if self.paused_until.is_some_and(|until| now < until) {
+            return None;
+        }
+        if self.text.len() - self.rendered_len < self.config.min_chunk_chars {
+            return None;
+        }
+        let interval = Duration::from_millis(self.config.min_edit_interval_ms);
+        if self
+            .last_edit
+            .is_some_and(|last| now.duration_since(last) < interval)
+        {
+            return None;
+        }
+        self.rendered_len = self.text.len();
+        self.last_edit = Some(now);
+        self.paused_until = None;
+        Some(&self.text)
+    }
+
+    /// Start over for a new model turn; the draft is reused and overwritten.
+    pub fn reset(&mut self) {
+        self.text.clear();
+        self.rendered_len = 0;
+    }
+
+    /// The platform rejected an edit with a rate limit. Either stop streaming
+    /// (final-only fallback) or pause for `retry_after`.
+    pub fn rate_limited(&mut self, retry_after: Duration, now: Instant) {
+        if self.config.fallback_on_rate_limit {
+            self.stopped = true;
+        } else {
+            self.paused_until = Some(now + retry_after);
+        }
+    }
+
+    /// Stop editing for the rest of this reply (e.g. the draft failed).
+    pub fn stop(&mut self) {
+        self.stopped = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn enabled(fallback: bool) -> StreamingConfig {
+        StreamingConfig {
+            enabled: true,
+            min_edit_interval_ms: 1000,
+            min_chunk_chars: 5,
+            fallback_on_rate_limit: fallback,
+        }
+    }
+
+    #[test]
+    fn test_config_defaults() {
+        let config: StreamingConfig = serde_yaml::from_str("enabled: true").unwrap();
+        assert!(config.enabled);
+        assert_eq!(config.min_edit_interval_ms, 1200);
+        assert_eq!(config.min_chunk_chars, 40);
+        assert!(config.fallback_on_rate_limit);
+        assert!(!StreamCadence::new(StreamingConfig::default()).is_active());
+    }
+
+    #[test]
+    fn test_cadence_respects_interval_and_chunk_size() {
+        let mut cadence = StreamCadence::new(enabled(true));
+        let t0 = Instant::now();
+        // Too little text for a first draft
+        assert_eq!(cadence.push("Hi", t0), None);
+        assert_eq!(cadence.push(" there", t0), Some("Hi there"));
+        // Too soon
+        assert_eq!(cadence.push(", friend", t0), None);
+        // Enough of both
+        assert_eq!(
+            cadence.push("!", t0 + Duration::from_secs(2)),
+            Some("Hi there, friend!")
+        );
+        // Enough time, too little new text
+        assert_eq!(cadence.push("!", t0 + Duration::from_secs(4)), None);
+
+        cadence.reset();
+        assert_eq!(cadence.text(), "");
+        assert_eq!(
+            cadence.push("Next turn", t0 + Duration::from_secs(4)),
+            Some("Next turn")
+        );
+    }
+
+    #[test]
+    fn test_cadence_rate_limit_fallback_and_pause() {
+        let t0 = Instant::now();
+        let mut cadence = StreamCadence::new(enabled(true));
+        assert!(cadence.push("Hello", t0).is_some());
+        cadence.rate_limited(Duration::from_secs(3), t0);
+        assert!(!cadence.is_active());
+        assert_eq!(cadence.push(" world", t0 + Duration::from_secs(9)), None);
+        assert_eq!(cadence.text(), "Hello world");
+
+        let mut cadence = StreamCadence::new(enabled(false));
+        assert!(cadence.push("Hello", t0).is_some());
+        cadence.rate_limited(Duration::from_secs(3), t0);
+        assert!(cadence.is_active());
+        assert_eq!(cadence.push(" world", t0 + Duration::from_secs(2)), None);
+        assert_eq!(
+            cadence.push("!", t0 + Duration::from_secs(4)),
+            Some("Hello world!")
+        );
+    }
+}
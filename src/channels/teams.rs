@@ -0,0 +1,998 @@
+//! Microsoft Teams through the Bot Framework.
+//!
+//! Teams posts activities to `/api/teams/messages` on the web server, signed
+//! with a Bot Framework JWT; replies go to the Bot Connector REST API at the
+//! conversation's service URL. ACP permission prompts are sent as Adaptive
+//! Cards whose buttons submit the reply.
+
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, OnceLock};
+use std::time::{Duration, Instant};
+
+use base64::Engine;
+use serde::Deserialize;
+use tracing::{error, info, warn};
+
+use crate::agent_engine::process_with_agent_with_events;
+use crate::agent_engine::AgentEvent;
+use crate::agent_engine::AgentRequestContext;
+use crate::channel::ConversationKind;
+use crate::channel_adapter::{ChannelAdapter, TextFormat};
+use crate::commands::handle_command;
+use crate::db::call_blocking;
+use crate::db::StoredMessage;
+use crate::runtime::AppState;
+use crate::text::split_text;
+
+const OPENID_CONFIG_URL: &str = "https://login.botframework.com/v1/.well-known/openidconfiguration";
+const TOKEN_ISSUER: &str = "https://api.botframework.com";
+const CONNECTOR_SCOPE: &str = "https://api.botframework.com/.default";
+/// Clock skew tolerated on token lifetimes, as in the Bot Framework SDKs
+const CLOCK_SKEW_SECS: i64 = 300;
+const SIGNING_KEYS_TTL: Duration = Duration::from_secs(24 * 3600);
+/// Shortest interval between key refreshes triggered by an unknown key ID
+const SIGNING_KEYS_MIN_REFRESH: Duration = Duration::from_secs(300);
+/// Teams rejects messages over ~28 KB
+const MAX_MESSAGE_LEN: usize = 12000;
+/// Key of the Adaptive Card submit data carrying a permission reply
+const CARD_REPLY_KEY: &str = "rayclaw_reply";
+/// How long an activity stays claimed; the Bot Framework redelivers an
+/// activity that isn't acknowledged in time, possibly to another instance.
+const ACTIVITY_CLAIM_TTL: Duration = Duration::from_secs(6 * 3600);
+
+fn default_service_url() -> String {
+    "https://smba.trafficmanager.net/teams/".into()
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TeamsChannelConfig {
+    /// Microsoft App ID of the Azure Bot registration
+    #[serde(default)]
+    pub app_id: String,
+    /// Client secret of the app registration
+    #[serde(default)]
+    pub app_password: String,
+    /// Directory (tenant) ID of a single-tenant bot; unset for multi-tenant
+    #[serde(default)]
+    pub tenant_id: Option<String>,
+    /// Tenant IDs whose users may message the bot; empty means any
+    #[serde(default)]
+    pub allowed_tenants: Vec<String>,
+    /// Conversation IDs (`19:...@thread.tacv2`, `a:...`) the bot answers
+    /// in; empty means all
+    #[serde(default)]
+    pub allowed_conversations: Vec<String>,
+    /// Bot Connector endpoint for conversations Teams hasn't sent an
+    /// activity from since startup (e.g. scheduled messages after a restart)
+    #[serde(default = "default_service_url")]
+    pub service_url: String,
+}
+
+impl TeamsChannelConfig {
+    pub fn is_configured(&self) -> bool {
+        !self.app_id.trim().is_empty() && !self.app_password.trim().is_empty()
+    }
+
+    fn is_allowed(&self, tenant_id: Option<&str>, conversation_id: &str) -> bool {
+        let tenant_ok = self.allowed_tenants.is_empty()
+            || tenant_id.is_some_and(|t| self.allowed_tenants.iter().any(|a| a == t));
+        // Channel threads are `19:...@thread.tacv2;messageid=...`
+        let base_id = conversation_id.split(';').next().unwrap_or_default();
+        let conversation_ok = self.allowed_conversations.is_empty()
+            || self
+                .allowed_conversations
+                .iter()
+                .any(|a| a == conversation_id || a == base_id);
+        tenant_ok && conversation_ok
+    }
+}
+
+/// Why an incoming activity was refused.
+#[derive(Debug)]
+pub enum TeamsError {
+    NotConfigured,
+    Unauthorized(String),
+    Invalid(String),
+    /// Signing keys could not be fetched
+    Unavailable(String),
+}
+
+fn http_client() -> &'static reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        reqwest::Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()
+            .unwrap_or_default()
+    })
+}
+
+/// Service URL of each conversation seen since startup. Teams may move a
+/// conversation to another regional endpoint, so the latest one wins.
+static SERVICE_URLS: LazyLock<std::sync::Mutex<HashMap<String, String>>> =
+    LazyLock::new(|| std::sync::Mutex::new(HashMap::new()));
+
+fn service_url_for(config: &TeamsChannelConfig, conversation_id: &str) -> String {
+    SERVICE_URLS
+        .lock()
+        .unwrap()
+        .get(conversation_id)
+        .cloned()
+        .unwrap_or_else(|| config.service_url.clone())
+}
+
+// ---------------------------------------------------------------------------
+// Outbound: Bot Connector REST API
+// ---------------------------------------------------------------------------
+
+/// Connector access token and when it expires.
+static ACCESS_TOKEN: LazyLock<tokio::sync::Mutex<Option<(String, Instant)>>> =
+    LazyLock::new(|| tokio::sync::Mutex::new(None));
+
+/// Access token for the Bot Connector, from the client-credentials flow.
+async fn access_token(config: &TeamsChannelConfig) -> Result<String, String> {
+    let mut cached = ACCESS_TOKEN.lock().await;
+    if let Some((token, expires)) = cached.as_ref() {
+        if *expires > Instant::now() + Duration::from_secs(60) {
+            return Ok(token.clone());
+        }
+    }
+    let tenant = config
+        .tenant_id
+        .as_deref()
+        .filter(|t| !t.trim().is_empty())
+        .unwrap_or("botframework.com");
+    let resp = http_client()
+        .post(format!(
+            "https://login.microsoftonline.com/{tenant}/oauth2/v2.0/token"
+        ))
+        .form(&[
+            ("grant_type", "client_credentials"),
+            ("client_id", config.app_id.as_str()),
+            ("client_secret", config.app_password.as_str()),
+            ("scope", CONNECTOR_SCOPE),
+        ])
+        .send()
+        .await
+        .map_err(|e| format!("Teams token request failed: {e}"))?;
+    let status = resp.status();
+    let body: serde_json::Value = resp
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse Teams token response: {e}"))?;
+    let Some(token) = body.get("access_token").and_then(|v| v.as_str()) else {
+        let err = body
+            .get("error_description")
+            .or_else(|| body.get("error"))
+            .and_then(|v| v.as_str())
+            .unwrap_or("unknown");
+        return Err(format!("Teams token request failed: HTTP {status} {err}"));
+    };
+    let expires_in = body
+        .get("expires_in")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(3600);
+    *cached = Some((
+        token.to_string(),
+        Instant::now() + Duration::from_secs(expires_in),
+    ));
+    Ok(token.to_string())
+}
+
+/// Post an activity to a conversation.
+async fn send_activity(
+    config: &TeamsChannelConfig,
+    conversation_id: &str,
+    activity: &serde_json::Value,
+) -> Result<(), String> {
+    let service_url = service_url_for(config, conversation_id);
+    let mut url = reqwest::Url::parse(&service_url)
+        .map_err(|e| format!("Invalid Teams service URL {service_url}: {e}"))?;
+    url.path_segments_mut()
+        .map_err(|_| format!("Invalid Teams service URL {service_url}"))?
+        .pop_if_empty()
+        .extend(["v3", "conversations", conversation_id, "activities"]);
+    let token = access_token(config).await?;
+    let resp = http_client()
+        .post(url)
+        .bearer_auth(token)
+        .json(activity)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to send Teams message: {e}"))?;
+    if !resp.status().is_success() {
+        let status = resp.status();
+        if status == reqwest::StatusCode::UNAUTHORIZED {
+            *ACCESS_TOKEN.lock().await = None;
+        }
+        let body = resp.text().await.unwrap_or_default();
+        return Err(format!(
+            "Failed to send Teams message: HTTP {status} {}",
+            body.chars().take(300).collect::<String>()
+        ));
+    }
+    Ok(())
+}
+
+/// Adaptive Card for an ACP permission prompt, with one button per reply.
+fn permission_card(text: &str) -> serde_json::Value {
+    let action = |title: &str, reply: &str| {
+        serde_json::json!({
+            "type": "Action.Submit",
+            "title": title,
+            "data": { CARD_REPLY_KEY: reply },
+        })
+    };
+    serde_json::json!({
+        "contentType": "application/vnd.microsoft.card.adaptive",
+        "content": {
+            "type": "AdaptiveCard",
+            "$schema": "http://adaptivecards.io/schemas/adaptive-card.json",
+            "version": "1.4",
+            "body": [{ "type": "TextBlock", "text": text, "wrap": true }],
+            "actions": [
+                action("Approve", "approve"),
+                action("Always allow", "always"),
+                action("Deny", "deny"),
+            ],
+        },
+    })
+}
+
+async fn send_message(
+    config: &TeamsChannelConfig,
+    conversation_id: &str,
+    text: &str,
+) -> Result<(), String> {
+    if crate::acp::is_permission_prompt(text) {
+        let activity = serde_json::json!({
+            "type": "message",
+            "attachments": [permission_card(text)],
+        });
+        return send_activity(config, conversation_id, &activity).await;
+    }
+    for chunk in split_text(text, MAX_MESSAGE_LEN) {
+        let activity = serde_json::json!({
+            "type": "message",
+            "text": chunk,
+            "textFormat": "markdown",
+        });
+        send_activity(config, conversation_id, &activity).await?;
+    }
+    Ok(())
+}
+
+pub struct TeamsAdapter {
+    config: TeamsChannelConfig,
+}
+
+impl TeamsAdapter {
+    pub fn new(config: TeamsChannelConfig) -> Self {
+        TeamsAdapter { config }
+    }
+}
+
+#[async_trait::async_trait]
+impl ChannelAdapter for TeamsAdapter {
+    fn name(&self) -> &str {
+        "teams"
+    }
+
+    fn text_format(&self) -> TextFormat {
+        TextFormat::Markdown
+    }
+
+    fn chat_type_routes(&self) -> Vec<(&str, ConversationKind)> {
+        vec![
+            ("teams", ConversationKind::Group),
+            ("teams_dm", ConversationKind::Private),
+        ]
+    }
+
+    async fn send_text(&self, external_chat_id: &str, text: &str) -> Result<(), String> {
+        send_message(&self.config, external_chat_id, text).await
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Inbound: token validation
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Deserialize)]
+struct SigningKey {
+    kid: String,
+    #[serde(default)]
+    n: String,
+    #[serde(default)]
+    e: String,
+    /// Channels the key may sign for
+    #[serde(default)]
+    endorsements: Vec<String>,
+}
+
+/// What an incoming token has to match.
+struct TokenExpectations<'a> {
+    app_id: &'a str,
+    service_url: &'a str,
+    channel_id: &'a str,
+}
+
+fn decode_segment(segment: &str) -> Result<Vec<u8>, String> {
+    base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(segment.trim_end_matches('='))
+        .map_err(|_| "malformed token".to_string())
+}
+
+fn token_key_id(token: &str) -> Option<String> {
+    let header = decode_segment(token.split('.').next()?).ok()?;
+    let header: serde_json::Value = serde_json::from_slice(&header).ok()?;
+    header.get("kid")?.as_str().map(str::to_string)
+}
+
+/// Check a Bot Framework token's signature and claims. `now` is a Unix
+/// timestamp.
+fn verify_token(
+    token: &str,
+    keys: &[SigningKey],
+    expected: &TokenExpectations,
+    now: i64,
+) -> Result<(), String> {
+    let parts: Vec<&str> = token.split('.').collect();
+    let [header_b64, claims_b64, signature_b64] = parts[..] else {
+        return Err("malformed token".into());
+    };
+    let header: serde_json::Value = serde_json::from_slice(&decode_segment(header_b64)?)
+        .map_err(|_| "malformed token header")?;
+    if header.get("alg").and_then(|v| v.as_str()) != Some("RS256") {
+        return Err("unsupported token algorithm".into());
+    }
+    let kid = header.get("kid").and_then(|v| v.as_str()).unwrap_or("");
+    let key = keys
+        .iter()
+        .find(|k| k.kid == kid)
+        .ok_or_else(|| format!("unknown signing key '{kid}'"))?;
+    if !key.endorsements.is_empty() && !key.endorsements.iter().any(|e| e == expected.channel_id) {
+        return Err(format!(
+            "signing key is not endorsed for channel '{}'",
+            expected.channel_id
+        ));
+    }
+    let public_key = ring::signature::RsaPublicKeyComponents {
+        n: decode_segment(&key.n)?,
+        e: decode_segment(&key.e)?,
+    };
+    public_key
+        .verify(
+            &ring::signature::RSA_PKCS1_2048_8192_SHA256,
+            format!("{header_b64}.{claims_b64}").as_bytes(),
+            &decode_segment(signature_b64)?,
+        )
+        .map_err(|_| "invalid token signature")?;
+
+    let claims: serde_json::Value = serde_json::from_slice(&decode_segment(claims_b64)?)
+        .map_err(|_| "malformed token claims")?;
+    if claims.get("iss").and_then(|v| v.as_str()) != Some(TOKEN_ISSUER) {
+        return Err("unexpected token issuer".into());
+    }
+    let audience_ok = match claims.get("aud") {
+        Some(serde_json::Value::String(aud)) => aud == expected.app_id,
+        Some(serde_json::Value::Array(auds)) => {
+            auds.iter().any(|a| a.as_str() == Some(expected.app_id))
+        }
+        _ => false,
+    };
+    if !audience_ok {
+        return Err("token is for another app".into());
+    }
+    let exp = claims
+        .get("exp")
+        .and_then(|v| v.as_i64())
+        .ok_or("token has no expiry")?;
+    if now > exp + CLOCK_SKEW_SECS {
+        return Err("token expired".into());
+    }
+    if let Some(nbf) = claims.get("nbf").and_then(|v| v.as_i64()) {
+        if now + CLOCK_SKEW_SECS < nbf {
+            return Err("token not yet valid".into());
+        }
+    }
+    let claimed_service_url = claims
+        .get("serviceurl")
+        .or_else(|| claims.get("serviceUrl"))
+        .and_then(|v| v.as_str());
+    if let Some(claimed) = claimed_service_url {
+        if claimed.trim_end_matches('/') != expected.service_url.trim_end_matches('/') {
+            return Err("token service URL does not match the activity".into());
+        }
+    }
+    Ok(())
+}
+
+/// Signing keys published by the Bot Framework, and when they were fetched.
+type CachedKeys = Option<(Instant, Vec<SigningKey>)>;
+
+static SIGNING_KEYS: LazyLock<tokio::sync::Mutex<CachedKeys>> =
+    LazyLock::new(|| tokio::sync::Mutex::new(None));
+
+/// Cached signing keys, refreshed daily or when a token names a key ID we
+/// haven't seen (keys are rotated).
+async fn signing_keys(kid: &str) -> Result<Vec<SigningKey>, String> {
+    let mut cached = SIGNING_KEYS.lock().await;
+    if let Some((fetched, keys)) = cached.as_ref() {
+        let age = fetched.elapsed();
+        let known = keys.iter().any(|k| k.kid == kid);
+        if age < SIGNING_KEYS_TTL && (known || age < SIGNING_KEYS_MIN_REFRESH) {
+            return Ok(keys.clone());
+        }
+    }
+    let openid: serde_json::Value = http_client()
+        .get(OPENID_CONFIG_URL)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| format!("Failed to fetch Bot Framework OpenID configuration: {e}"))?
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse Bot Framework OpenID configuration: {e}"))?;
+    let jwks_uri = openid
+        .get("jwks_uri")
+        .and_then(|v| v.as_str())
+        .ok_or("Bot Framework OpenID configuration has no jwks_uri")?;
+    #[derive(Deserialize)]
+    struct KeySet {
+        keys: Vec<SigningKey>,
+    }
+    let key_set: KeySet = http_client()
+        .get(jwks_uri)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| format!("Failed to fetch Bot Framework signing keys: {e}"))?
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse Bot Framework signing keys: {e}"))?;
+    *cached = Some((Instant::now(), key_set.keys.clone()));
+    Ok(key_set.keys)
+}
+
+// ---------------------------------------------------------------------------
+// Inbound: activities
+// ---------------------------------------------------------------------------
+
+/// A message activity addressed to the bot.
+#[derive(Debug, PartialEq)]
+struct IncomingMessage {
+    id: String,
+    conversation_id: String,
+    tenant_id: Option<String>,
+    is_dm: bool,
+    sender: String,
+    text: String,
+    /// @mentions the bot, or answers one of its cards
+    addressed_to_bot: bool,
+}
+
+/// Remove `<at>Name</at>` mention markup, which Teams puts in the text.
+fn strip_mentions(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("<at>") {
+        out.push_str(&rest[..start]);
+        match rest[start..].find("</at>") {
+            Some(end) => rest = &rest[start + end + "</at>".len()..],
+            None => {
+                rest = "";
+            }
+        }
+    }
+    out.push_str(rest);
+    out.replace("&nbsp;", " ").trim().to_string()
+}
+
+impl IncomingMessage {
+    /// Parse a message activity; other activity types (conversation updates,
+    /// reactions, ...) and the bot's own messages give `None`.
+    fn from_activity(activity: &serde_json::Value) -> Option<Self> {
+        let str_at = |pointer: &str| {
+            activity
+                .pointer(pointer)
+                .and_then(|v| v.as_str())
+                .filter(|v| !v.is_empty())
+        };
+        if str_at("/type") != Some("message") {
+            return None;
+        }
+        let bot_id = str_at("/recipient/id").unwrap_or_default();
+        let sender_id = str_at("/from/id")?;
+        if sender_id == bot_id {
+            return None;
+        }
+        let conversation_id = str_at("/conversation/id")?.to_string();
+
+        let card_reply = str_at(&format!("/value/{CARD_REPLY_KEY}"));
+        let (text, addressed_to_bot) = match card_reply {
+            Some(reply) => (reply.to_string(), true),
+            None => {
+                let mentioned = activity
+                    .get("entities")
+                    .and_then(|v| v.as_array())
+                    .is_some_and(|entities| {
+                        entities.iter().any(|e| {
+                            e.get("type").and_then(|v| v.as_str()) == Some("mention")
+                                && e.pointer("/mentioned/id").and_then(|v| v.as_str())
+                                    == Some(bot_id)
+                        })
+                    });
+                (
+                    strip_mentions(str_at("/text").unwrap_or_default()),
+                    mentioned,
+                )
+            }
+        };
+        if text.is_empty() {
+            return None;
+        }
+
+        Some(IncomingMessage {
+            id: str_at("/id")
+                .map(str::to_string)
+                .unwrap_or_else(|| uuid::Uuid::new_v4().to_string()),
+            tenant_id: str_at("/conversation/tenantId")
+                .or_else(|| str_at("/channelData/tenant/id"))
+                .map(str::to_string),
+            is_dm: str_at("/conversation/conversationType") == Some("personal"),
+            sender: str_at("/from/name").unwrap_or(sender_id).to_string(),
+            conversation_id,
+            text,
+            addressed_to_bot,
+        })
+    }
+}
+
+/// Authenticate an activity posted to the messaging endpoint and, when it is
+/// a message for the bot, handle it in the background.
+pub async fn accept_activity(
+    app_state: Arc<AppState>,
+    authorization: Option<&str>,
+    body: &str,
+) -> Result<(), TeamsError> {
+    let config = app_state
+        .config
+        .channel_config::<TeamsChannelConfig>("teams")
+        .filter(TeamsChannelConfig::is_configured)
+        .ok_or(TeamsError::NotConfigured)?;
+    let token = authorization
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .ok_or_else(|| TeamsError::Unauthorized("missing bearer token".into()))?;
+    let activity: serde_json::Value = serde_json::from_str(body)
+        .map_err(|e| TeamsError::Invalid(format!("invalid activity: {e}")))?;
+    let service_url = activity
+        .get("serviceUrl")
+        .and_then(|v| v.as_str())
+        .unwrap_or_default();
+    let channel_id = activity
+        .get("channelId")
+        .and_then(|v| v.as_str())
+        .unwrap_or_default();
+
+    let kid =
+        token_key_id(token).ok_or_else(|| TeamsError::Unauthorized("malformed token".into()))?;
+    let keys = signing_keys(&kid).await.map_err(TeamsError::Unavailable)?;
+    let expected = TokenExpectations {
+        app_id: &config.app_id,
+        service_url,
+        channel_id,
+    };
+    verify_token(token, &keys, &expected, chrono::Utc::now().timestamp())
+        .map_err(TeamsError::Unauthorized)?;
+
+    let Some(message) = IncomingMessage::from_activity(&activity) else {
+        return Ok(());
+    };
+    if !config.is_allowed(message.tenant_id.as_deref(), &message.conversation_id) {
+        return Ok(());
+    }
+    if !service_url.is_empty() {
+        SERVICE_URLS
+            .lock()
+            .unwrap()
+            .insert(message.conversation_id.clone(), service_url.to_string());
+    }
+    let claim_key = format!("teams:activity:{}:{}", message.conversation_id, message.id);
+    if !app_state
+        .coordinator
+        .claim(&claim_key, ACTIVITY_CLAIM_TTL)
+        .await
+    {
+        return Ok(());
+    }
+    tokio::spawn(handle_teams_message(app_state, config, message));
+    Ok(())
+}
+
+async fn handle_teams_message(
+    app_state: Arc<AppState>,
+    config: TeamsChannelConfig,
+    message: IncomingMessage,
+) {
+    let conversation_id = message.conversation_id.as_str();
+    let chat_type = if message.is_dm { "teams_dm" } else { "teams" };
+    let title = format!("teams-{}", message.sender);
+
+    let chat_id = call_blocking(app_state.db.clone(), {
+        let external_id = conversation_id.to_string();
+        let title = if message.is_dm {
+            title
+        } else {
+            format!("teams-{conversation_id}")
+        };
+        let chat_type = chat_type.to_string();
+        move |db| db.resolve_or_create_chat_id("teams", &external_id, Some(&title), &chat_type)
+    })
+    .await
+    .unwrap_or(0);
+
+    if chat_id == 0 {
+        error!("Teams: failed to resolve chat ID for conversation {conversation_id}");
+        return;
+    }
+
+    let stored = StoredMessage {
+        id: message.id.clone(),
+        chat_id,
+        sender_name: message.sender.clone(),
+        content: message.text.clone(),
+        is_from_bot: false,
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        thread_id: None,
+    };
+    let _ = call_blocking(app_state.db.clone(), move |db| db.store_message(&stored)).await;
+
+    if let Some(reply) = handle_command(&app_state, chat_id, "teams", &message.text).await {
+        let _ = send_message(&config, conversation_id, &reply).await;
+        return;
+    }
+
+    let should_respond = crate::channel::should_respond(
+        app_state.db.clone(),
+        chat_id,
+        message.is_dm,
+        message.addressed_to_bot,
+    )
+    .await;
+    if !should_respond {
+        return;
+    }
+
+    info!(
+        "Teams message from {} in {}: {}",
+        message.sender,
+        conversation_id,
+        message.text.chars().take(100).collect::<String>()
+    );
+
+    let typing = serde_json::json!({ "type": "typing" });
+    if let Err(e) = send_activity(&config, conversation_id, &typing).await {
+        warn!("Teams: failed to send typing indicator: {e}");
+    }
+
+    let (event_tx, mut event_rx) = tokio::sync::mpsc::unbounded_channel::<AgentEvent>();
+    match process_with_agent_with_events(
+        &app_state,
+        AgentRequestContext {
+            caller_channel: "teams",
+            chat_id,
+            thread_id: None,
+            chat_type: if message.is_dm { "private" } else { "group" },
+            denied_tools: &[],
+        },
+        None,
+        None,
+        Some(&event_tx),
+    )
+    .await
+    {
+        Ok(response) => {
+            drop(event_tx);
+            let mut used_send_message_tool = false;
+            let mut superseded = false;
+            while let Some(event) = event_rx.recv().await {
+                match event {
+                    AgentEvent::ToolStart { name } if name == "send_message" => {
+                        used_send_message_tool = true;
+                    }
+                    AgentEvent::Superseded => superseded = true,
+                    _ => {}
+                }
+            }
+
+            let reply = if !response.is_empty() {
+                let sent = crate::channel::send_tracked(
+                    app_state.db.clone(),
+                    chat_id,
+                    "teams",
+                    &response,
+                    || send_message(&config, conversation_id, &response),
+                )
+                .await;
+                if let Err(e) = sent {
+                    error!("Teams: failed to send response: {e}");
+                }
+                response
+            } else if !used_send_message_tool && !superseded {
+                let fallback = "I couldn't produce a visible reply after an automatic retry. Please try again.";
+                let _ = send_message(&config, conversation_id, fallback).await;
+                fallback.to_string()
+            } else {
+                return;
+            };
+
+            let bot_msg = StoredMessage {
+                id: uuid::Uuid::new_v4().to_string(),
+                chat_id,
+                sender_name: app_state.config.bot_username.clone(),
+                content: reply,
+                is_from_bot: true,
+                timestamp: chrono::Utc::now().to_rfc3339(),
+                thread_id: None,
+            };
+            let _ = call_blocking(app_state.db.clone(), move |db| db.store_message(&bot_msg)).await;
+        }
+        Err(e) => {
+            error!("Error processing Teams message: {e}");
+            let _ = send_message(&config, conversation_id, &format!("Error: {e}")).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// RSA-2048 key used only to sign test tokens (PKCS#8 DER).
+    const TEST_KEY_PKCS8: &str = concat!(
+        "MIIEvwIBADANBgkqhkiG9w0BAQEFAASCBKkwggSlAgEAAoIBAQDDL2reZXJc5xwpZWw+2NjS1S4rMX2d0Yz13/En",
+        "GgT87r23llFo5A03z8zzoGt7J8Q+H7GKpec9CWt1OdBQyy6Erc6VG+N/mlfnxe/mntFyipO6ZhaT4fVdo35M88uW",
+        "81/AdlzXsBF0Zz69dPajiy9ozJhiypT/le48RRXunMWEors/8htSmMIouoYJjpgFekyEQEp5SLA0Ma64AeYDEPQ0",
+        "Go2Vt+xC71aul3bfCiRqcmedcw1o9igyy0hq+xm/3y5jQhoQRyHUY8dVIThlEm9S00L2Hy8trolDeNCKE6wEtPOF",
+        "qpBzm51m3vDb6D4RidFc/T1q3FBhSezk5Y5K++Q/AgMBAAECggEACetZ/To4nEIRuB5zm6OkX6qX196CNmGhtHxd",
+        "PBIVcmk+2QEM202W4mEI8GsU3nbNGvJVyoI96zTHi91hTQnWYywvruI/WiSU43sd6W5Lnj+VD5VqmYXG1RIFZ16O",
+        "2GAL9X/OauO2yPlwqQSafps8A8TW2OzX5gyCxZG/pWQjbmcXPPO163UDsptb/+BFf3Oj5MgHOY8mUDulE9N482S7",
+        "urJDBeGpxu+Qv3dRXD3vXtoPhXotyMx/N2CSvoBPHqikKsyhaqaAa9zNIY55zY5nqIZ2lhevIWZgc0peBCC6cIRH",
+        "nBOc2qiVdnXNemU+fIMQ3ghoWO8nhDHre3X/Xg9dWQKBgQDgvDcV1cf4ecRQ3Ed6unQL8xDPv0mwm5ssFesqfSvn",
+        "c0t9RDRf5uNSD6AKvKhD7WxNiz1H5avjsfWSobKSyOCNQdJomq0IyRAVQDhXBdPsWxA+gjyz2IXaJHO24vuldauW",
+        "2YPLrhJar4aLNVSG1qMv6eeGcpuWYxyR/4tlxuz53QKBgQDeVszk7k0cDFiPqES/wbSom3XazmmxsGNPnxRh3Wt9",
+        "O3PBtQ9rSwycdCoC+TEZQqrCh2cwkcPNYA+rvgLXcWEDGNvKyYb78lg/FRvnt+2avT6LByIrMFVSfv1f/Y28dZXz",
+        "PDDnjepIfi9XclDuTFJdDJrzW5khaIWmPQ/oCemqywKBgQCI5Bzpv/6+6+0ZFBS2PTzSyoxC/1HzsdUjVXaZuwUz",
+        "RpeFBjag9BMjuUDTSXd1aPm/gkEhulSprYzYdIZBwvqFEMMJ1K3x3Adf6m5nE7WzEwUiDlLLeeiOqAWUMU73ymVq",
+        "zbPqLW4grypVQXbra3KbjO7qhoUfZd/RTseqhzFyQQKBgQC+jrRTLW6HMImirjwmATX+ICd1Xd22fg/PyO7mJaif",
+        "j5nZln3dnlfzq5fqLnsHRF0oFna5aOThG4iy0EqNLc5QojiWZ/y56O37OvNMuRX6vmFhNL7zNxyZdN9iKuUUdc8L",
+        "xjW/76CxevzGPOLiKiWZPFl9Y68ts/CAFuuQGxba2wKBgQDYp8e0BiRc9vcpqTgIQ5LDSh9bZWuTtV/X98e0Y4TC",
+        "jqSxaIWWdAa+GXDAOZ3FMD7Kpu1/j4qMFugrip9laGsfRKg/BCLjeJgmuJOl6Zd6IKeFoIgOYCxBcuh9NZUVsgCa",
+        "ajCnWASaUXDc6QcqcKto+2ccOM9KDH7kgkDeYMe2ZA==",
+    );
+
+    fn b64url(bytes: &[u8]) -> String {
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+    }
+
+    fn test_key() -> (ring::signature::RsaKeyPair, SigningKey) {
+        let der = base64::engine::general_purpose::STANDARD
+            .decode(TEST_KEY_PKCS8)
+            .unwrap();
+        let pair = ring::signature::RsaKeyPair::from_pkcs8(&der).unwrap();
+        let components: ring::signature::RsaPublicKeyComponents<Vec<u8>> = pair.public().into();
+        let key = SigningKey {
+            kid: "key-1".into(),
+            n: b64url(&components.n),
+            e: b64url(&components.e),
+            endorsements: vec!["msteams".into()],
+        };
+        (pair, key)
+    }
+
+    fn sign(
+        pair: &ring::signature::RsaKeyPair,
+        header: &serde_json::Value,
+        claims: &serde_json::Value,
+    ) -> String {
+        let signed = format!(
+            "{}.{}",
+            b64url(header.to_string().as_bytes()),
+            b64url(claims.to_string().as_bytes())
+        );
+        let mut signature = vec![0; pair.public().modulus_len()];
+        pair.sign(
+            &ring::signature::RSA_PKCS1_SHA256,
+            &ring::rand::SystemRandom::new(),
+            signed.as_bytes(),
+            &mut signature,
+        )
+        .unwrap();
+        format!("{signed}.{}", b64url(&signature))
+    }
+
+    #[test]
+    fn test_verify_token() {
+        let (pair, key) = test_key();
+        let keys = [key];
+        let expected = TokenExpectations {
+            app_id: "app-123",
+            service_url: "https://smba.trafficmanager.net/emea/",
+            channel_id: "msteams",
+        };
+        let header = json!({"alg": "RS256", "kid": "key-1", "typ": "JWT"});
+        let claims = json!({
+            "iss": TOKEN_ISSUER,
+            "aud": "app-123",
+            "exp": 2_000_000_000,
+            "nbf": 1_000_000_000,
+            "serviceurl": "https://smba.trafficmanager.net/emea",
+        });
+        let token = sign(&pair, &header, &claims);
+        assert_eq!(token_key_id(&token).as_deref(), Some("key-1"));
+        assert_eq!(
+            verify_token(&token, &keys, &expected, 1_500_000_000),
+            Ok(())
+        );
+        // Within the tolerated clock skew
+        assert_eq!(
+            verify_token(&token, &keys, &expected, 2_000_000_000 + 60),
+            Ok(())
+        );
+        assert_eq!(
+            verify_token(&token, &keys, &expected, 2_000_000_000 + 600).unwrap_err(),
+            "token expired"
+        );
+
+        let mut tampered: Vec<&str> = token.split('.').collect();
+        let forged_claims = b64url(
+            json!({"iss": TOKEN_ISSUER, "aud": "other"})
+                .to_string()
+                .as_bytes(),
+        );
+        tampered[1] = &forged_claims;
+        assert_eq!(
+            verify_token(&tampered.join("."), &keys, &expected, 1_500_000_000).unwrap_err(),
+            "invalid token signature"
+        );
+
+        let other_app = sign(
+            &pair,
+            &header,
+            &json!({"iss": TOKEN_ISSUER, "aud": "app-999", "exp": 2_000_000_000}),
+        );
+        assert_eq!(
+            verify_token(&other_app, &keys, &expected, 1_500_000_000).unwrap_err(),
+            "token is for another app"
+        );
+        let wrong_issuer = sign(
+            &pair,
+            &header,
+            &json!({"iss": "https://evil.example", "aud": "app-123", "exp": 2_000_000_000}),
+        );
+        assert!(verify_token(&wrong_issuer, &keys, &expected, 1_500_000_000).is_err());
+
+        let other_endpoint = TokenExpectations {
+            service_url: "https://attacker.example/",
+            ..expected
+        };
+        assert!(verify_token(&token, &keys, &other_endpoint, 1_500_000_000).is_err());
+        let other_channel = TokenExpectations {
+            channel_id: "webchat",
+            ..other_endpoint
+        };
+        assert!(verify_token(&token, &keys, &other_channel, 1_500_000_000)
+            .unwrap_err()
+            .contains("not endorsed"));
+
+        let unsigned = sign(&pair, &json!({"alg": "none", "kid": "key-1"}), &claims);
+        assert_eq!(
+            verify_token(&unsigned, &keys, &expected, 1_500_000_000).unwrap_err(),
+            "unsupported token algorithm"
+        );
+        let unknown_key = sign(&pair, &json!({"alg": "RS256", "kid": "key-2"}), &claims);
+        assert!(verify_token(&unknown_key, &keys, &expected, 1_500_000_000)
+            .unwrap_err()
+            .contains("unknown signing key"));
+        assert!(verify_token("not-a-token", &keys, &expected, 1_500_000_000).is_err());
+    }
+
+    #[test]
+    fn test_incoming_message_from_activity() {
+        let activity = json!({
+            "type": "message",
+            "id": "1700000000000",
+            "channelId": "msteams",
+            "serviceUrl": "https://smba.trafficmanager.net/emea/",
+            "from": {"id": "29:user", "name": "Ann Lee"},
+            "recipient": {"id": "28:bot", "name": "RayClaw"},
+            "conversation": {
+                "id": "19:abc@thread.tacv2;messageid=1700000000000",
+                "conversationType": "channel",
+                "tenantId": "tenant-1"
+            },
+            "text": "<at>RayClaw</at>&nbsp;summarize the thread",
+            "entities": [{"type": "mention", "mentioned": {"id": "28:bot"}, "text": "<at>RayClaw</at>"}]
+        });
+        let message = IncomingMessage::from_activity(&activity).unwrap();
+        assert_eq!(
+            message,
+            IncomingMessage {
+                id: "1700000000000".into(),
+                conversation_id: "19:abc@thread.tacv2;messageid=1700000000000".into(),
+                tenant_id: Some("tenant-1".into()),
+                is_dm: false,
+                sender: "Ann Lee".into(),
+                text: "summarize the thread".into(),
+                addressed_to_bot: true,
+            }
+        );
+
+        // Card button press in a personal chat
+        let submit = json!({
+            "type": "message",
+            "from": {"id": "29:user"},
+            "recipient": {"id": "28:bot"},
+            "conversation": {"id": "a:personal", "conversationType": "personal"},
+            "channelData": {"tenant": {"id": "tenant-2"}},
+            "value": {"rayclaw_reply": "always"}
+        });
+        let message = IncomingMessage::from_activity(&submit).unwrap();
+        assert_eq!(message.text, "always");
+        assert!(message.is_dm && message.addressed_to_bot);
+        assert_eq!(message.tenant_id.as_deref(), Some("tenant-2"));
+
+        let update = json!({"type": "conversationUpdate", "from": {"id": "29:user"}, "conversation": {"id": "a:1"}});
+        assert_eq!(IncomingMessage::from_activity(&update), None);
+        let own = json!({"type": "message", "from": {"id": "28:bot"}, "recipient": {"id": "28:bot"}, "conversation": {"id": "a:1"}, "text": "hi"});
+        assert_eq!(IncomingMessage::from_activity(&own), None);
+    }
+
+    #[test]
+    fn test_allowed_tenants_and_conversations() {
+        let config: TeamsChannelConfig = serde_yaml::from_str(
+            "app_id: app\napp_password: secret\nallowed_tenants: [tenant-1]\nallowed_conversations: ['19:abc@thread.tacv2']",
+        )
+        .unwrap();
+        assert!(config.is_configured());
+        assert_eq!(config.service_url, default_service_url());
+        assert!(config.is_allowed(Some("tenant-1"), "19:abc@thread.tacv2;messageid=1"));
+        assert!(!config.is_allowed(Some("tenant-2"), "19:abc@thread.tacv2"));
+        assert!(!config.is_allowed(None, "19:abc@thread.tacv2"));
+        assert!(!config.is_allowed(Some("tenant-1"), "19:other@thread.tacv2"));
+
+        let open: TeamsChannelConfig =
+            serde_yaml::from_str("app_id: app\napp_password: ''").unwrap();
+        assert!(!open.is_configured());
+        assert!(open.is_allowed(None, "a:anyone"));
+    }
+
+    #[test]
+    fn test_permission_card_buttons() {
+        let card = permission_card("[ACP claude] Permission requested: Edit");
+        assert_eq!(
+            card["content"]["body"][0]["text"],
+            "[ACP claude] Permission requested: Edit"
+        );
+        let replies: Vec<&str> = card["content"]["actions"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|a| a["data"][CARD_REPLY_KEY].as_str().unwrap())
+            .collect();
+        assert_eq!(replies, ["approve", "always", "deny"]);
+        for reply in replies {
+            assert!(crate::acp::AcpPermissionDecision::parse_reply(reply).is_some());
+        }
+    }
+
+    #[test]
+    fn test_strip_mentions() {
+        assert_eq!(strip_mentions("<at>Bot</at> hello"), "hello");
+        assert_eq!(strip_mentions("hi <at>Bot</at>, ok"), "hi , ok");
+        assert_eq!(strip_mentions("<at>broken"), "");
+        assert_eq!(strip_mentions("plain"), "plain");
+    }
+}
@@ -1,24 +1,29 @@
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
 use async_trait::async_trait;
 use serde::Deserialize;
 use teloxide::prelude::*;
-use teloxide::types::{ChatAction, InputFile, ParseMode};
+use teloxide::types::{
+    ChatAction, InlineKeyboardButton, InlineKeyboardMarkup, InputFile, MessageId, ParseMode,
+    ReactionType, ThreadId,
+};
 use tracing::{error, info, warn};
 
-use crate::agent_engine::{
-    archive_conversation, process_with_agent_with_events, AgentEvent, AgentRequestContext,
-};
-use crate::channel::ConversationKind;
+use crate::agent_engine::{process_with_agent_with_events, AgentEvent, AgentRequestContext};
+use crate::approvals::ApprovalDecision;
+use crate::channel::{ConversationKind, ReplyContext};
 use crate::channel_adapter::ChannelAdapter;
+use crate::channels::delivery::{stream_reply_draft, DraftError, DraftTarget};
+use crate::channels::streaming::StreamingConfig;
+use crate::commands::{handle_command, parse_command};
 use crate::db::{call_blocking, StoredMessage};
-use crate::llm_types::Message;
+use crate::llm_types::is_document_media_type;
 #[cfg(test)]
 use crate::llm_types::{ContentBlock, ImageSource, MessageContent};
 use crate::runtime::AppState;
 use crate::text::floor_char_boundary;
-use crate::usage::build_usage_report;
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct TelegramChannelConfig {
@@ -27,6 +32,9 @@ pub struct TelegramChannelConfig {
     pub bot_username: String,
     #[serde(default)]
     pub allowed_groups: Vec<i64>,
+    /// Edit a draft message while the reply is generated
+    #[serde(default)]
+    pub streaming: StreamingConfig,
 }
 
 pub struct TelegramAdapter {
@@ -95,12 +103,30 @@ impl ChannelAdapter for TelegramAdapter {
         ]
     }
 
+    fn has_approval_buttons(&self) -> bool {
+        true
+    }
+
     async fn send_text(&self, external_chat_id: &str, text: &str) -> Result<(), String> {
-        let telegram_chat_id = external_chat_id
-            .parse::<i64>()
-            .map_err(|_| format!("Invalid Telegram external_chat_id '{}'", external_chat_id))?;
-        send_response(&self.bot, ChatId(telegram_chat_id), text).await;
-        Ok(())
+        self.send_text_in_thread(external_chat_id, None, text).await
+    }
+
+    async fn send_text_in_thread(
+        &self,
+        external_chat_id: &str,
+        thread_id: Option<i64>,
+        text: &str,
+    ) -> Result<(), String> {
+        let chat = parse_chat_id(external_chat_id)?;
+        let thread = thread_id.and_then(to_thread_id);
+        if let Some(id) = crate::approvals::prompt_id(text) {
+            message_in(&self.bot, chat, thread, text)
+                .reply_markup(approval_keyboard(id))
+                .await
+                .map_err(|e| format!("Failed to send Telegram approval prompt: {e}"))?;
+            return Ok(());
+        }
+        send_response(&self.bot, chat, thread, text).await
     }
 
     async fn send_attachment(
@@ -109,25 +135,37 @@ impl ChannelAdapter for TelegramAdapter {
         file_path: &Path,
         caption: Option<&str>,
     ) -> Result<String, String> {
-        let telegram_chat_id = external_chat_id
-            .parse::<i64>()
-            .map_err(|_| format!("Invalid Telegram external_chat_id '{}'", external_chat_id))?;
+        self.send_attachment_in_thread(external_chat_id, None, file_path, caption)
+            .await
+    }
+
+    async fn send_attachment_in_thread(
+        &self,
+        external_chat_id: &str,
+        thread_id: Option<i64>,
+        file_path: &Path,
+        caption: Option<&str>,
+    ) -> Result<String, String> {
+        let chat = parse_chat_id(external_chat_id)?;
+        let thread = thread_id.and_then(to_thread_id);
 
         let (caption_for_attachment, overflow_text) = Self::split_telegram_caption(caption);
 
         if Self::is_likely_image(file_path) {
-            let mut req = self
-                .bot
-                .send_photo(ChatId(telegram_chat_id), InputFile::file(file_path));
+            let mut req = self.bot.send_photo(chat, InputFile::file(file_path));
+            if let Some(thread) = thread {
+                req = req.message_thread_id(thread);
+            }
             if let Some(c) = &caption_for_attachment {
                 req = req.caption(c.clone());
             }
             req.await
                 .map_err(|e| format!("Failed to send Telegram photo: {e}"))?;
         } else {
-            let mut req = self
-                .bot
-                .send_document(ChatId(telegram_chat_id), InputFile::file(file_path));
+            let mut req = self.bot.send_document(chat, InputFile::file(file_path));
+            if let Some(thread) = thread {
+                req = req.message_thread_id(thread);
+            }
             if let Some(c) = &caption_for_attachment {
                 req = req.caption(c.clone());
             }
@@ -136,7 +174,7 @@ impl ChannelAdapter for TelegramAdapter {
         }
 
         if let Some(extra) = overflow_text {
-            send_response(&self.bot, ChatId(telegram_chat_id), &extra).await;
+            send_response(&self.bot, chat, thread, &extra).await?;
         }
 
         Ok(match caption {
@@ -144,6 +182,92 @@ impl ChannelAdapter for TelegramAdapter {
             None => format!("[attachment:{}]", file_path.display()),
         })
     }
+
+    async fn react(
+        &self,
+        external_chat_id: &str,
+        message_id: &str,
+        emoji: &str,
+    ) -> Result<(), String> {
+        let chat = parse_chat_id(external_chat_id)?;
+        let message_id = message_id
+            .parse::<i32>()
+            .map_err(|_| format!("Invalid Telegram message_id '{message_id}'"))?;
+        self.bot
+            .set_message_reaction(chat, MessageId(message_id))
+            .reaction(vec![ReactionType::Emoji {
+                emoji: telegram_reaction_emoji(emoji),
+            }])
+            .await
+            .map_err(|e| format!("Failed to set Telegram reaction: {e}"))?;
+        Ok(())
+    }
+}
+
+/// Telegram only accepts a fixed set of reaction emoji, without variation
+/// selectors; map common acknowledgements that aren't in it.
+fn telegram_reaction_emoji(emoji: &str) -> String {
+    let emoji: String = emoji.chars().filter(|c| *c != '\u{fe0f}').collect();
+    match emoji.as_str() {
+        "✅" | "✔" | "☑" => "👌".to_string(),
+        "⏳" | "⌛" => "👀".to_string(),
+        _ => emoji,
+    }
+}
+
+fn parse_chat_id(external_chat_id: &str) -> Result<ChatId, String> {
+    external_chat_id
+        .parse::<i64>()
+        .map(ChatId)
+        .map_err(|_| format!("Invalid Telegram external_chat_id '{}'", external_chat_id))
+}
+
+/// A stored `thread_id` as Telegram's `message_thread_id`.
+fn to_thread_id(thread_id: i64) -> Option<ThreadId> {
+    i32::try_from(thread_id)
+        .ok()
+        .map(|id| ThreadId(MessageId(id)))
+}
+
+/// The forum topic `msg` was posted in; `None` for General and for reply
+/// threads outside forums.
+fn message_topic(msg: &teloxide::types::Message) -> Option<ThreadId> {
+    msg.thread_id.filter(|_| msg.is_topic_message)
+}
+
+/// The topic's name, known when `msg` isn't a reply to another message:
+/// Telegram then reports the topic's creation as the replied-to message.
+fn topic_name(msg: &teloxide::types::Message) -> Option<String> {
+    let created = msg.reply_to_message()?.forum_topic_created()?;
+    Some(created.name.clone())
+}
+
+/// `send_message`, posted in `thread` when there is one.
+fn message_in(
+    bot: &Bot,
+    chat: ChatId,
+    thread: Option<ThreadId>,
+    text: impl Into<String>,
+) -> <Bot as Requester>::SendMessage {
+    let req = bot.send_message(chat, text);
+    match thread {
+        Some(thread) => req.message_thread_id(thread),
+        None => req,
+    }
+}
+
+/// Approve/Deny buttons under a tool approval prompt.
+fn approval_keyboard(id: &str) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new([[
+        InlineKeyboardButton::callback(
+            "Approve",
+            crate::approvals::callback_data(id, ApprovalDecision::Approve),
+        ),
+        InlineKeyboardButton::callback(
+            "Deny",
+            crate::approvals::callback_data(id, ApprovalDecision::Deny),
+        ),
+    ]])
 }
 
 /// Escape XML special characters in user-supplied content to prevent prompt injection.
@@ -166,9 +290,19 @@ fn format_user_message(sender_name: &str, content: &str) -> String {
 }
 
 pub async fn start_telegram_bot(state: Arc<AppState>, bot: Bot) -> anyhow::Result<()> {
-    let handler = Update::filter_message().endpoint(handle_message);
+    let handler = dptree::entry()
+        .branch(Update::filter_message().endpoint(handle_message))
+        .branch(Update::filter_callback_query().endpoint(handle_callback_query));
+    PARALLEL_CHAT_UPDATES.store(state.config.message_debounce_ms > 0, Ordering::Relaxed);
+    let streaming = state
+        .config
+        .channel_config::<TelegramChannelConfig>("telegram")
+        .map(|c| c.streaming)
+        .unwrap_or_default();
+    let _ = STREAMING.set(streaming);
 
     Dispatcher::builder(bot, handler)
+        .distribution_function(distribution_key)
         .default_handler(|_| async {})
         .dependencies(dptree::deps![state])
         .enable_ctrlc_handler()
@@ -178,6 +312,89 @@ pub async fn start_telegram_bot(state: Arc<AppState>, bot: Bot) -> anyhow::Resul
 
     Ok(())
 }
+
+/// Set when `message_debounce_ms` is on. Debouncing needs the next message
+/// stored while the previous one is still waiting, so per-chat serialization
+/// is left to the agent engine's chat lock instead.
+static PARALLEL_CHAT_UPDATES: AtomicBool = AtomicBool::new(false);
+
+/// Draft-edit cadence for streamed replies, read once at startup.
+static STREAMING: std::sync::OnceLock<StreamingConfig> = std::sync::OnceLock::new();
+
+/// Updates are handled one at a time per chat, except approval replies and
+/// button presses and `#stop`: they must reach the agent engine while that
+/// chat's agent run or ACP prompt is still waiting on them.
+fn distribution_key(update: &Update) -> Option<ChatId> {
+    if PARALLEL_CHAT_UPDATES.load(Ordering::Relaxed) {
+        return None;
+    }
+    match &update.kind {
+        teloxide::types::UpdateKind::Message(msg)
+            if msg.text().is_some_and(|text| {
+                crate::acp::AcpPermissionDecision::parse_reply(text).is_some()
+                    || ApprovalDecision::parse_reply(text).is_some()
+                    || crate::acp::is_stop_command(text)
+            }) =>
+        {
+            None
+        }
+        teloxide::types::UpdateKind::CallbackQuery(_) => None,
+        _ => update.chat().map(|chat| chat.id),
+    }
+}
+
+/// Approve/Deny button presses under tool approval prompts. The prompt is
+/// edited to show the outcome and lose its buttons.
+async fn handle_callback_query(
+    bot: Bot,
+    query: teloxide::types::CallbackQuery,
+    state: Arc<AppState>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let Some(data) = query.data.as_deref() else {
+        return Ok(());
+    };
+    if crate::approvals::parse_callback_data(data).is_none() {
+        return Ok(());
+    }
+    let decision = state.tool_approvals.resolve_callback(data).await;
+    let label = decision.unwrap_or(ApprovalDecision::Expired).label();
+    if let Err(e) = bot
+        .answer_callback_query(query.id.clone())
+        .text(label)
+        .await
+    {
+        warn!("Telegram: failed to answer approval button press: {e}");
+    }
+    if let Some(msg) = query.regular_message() {
+        let by = query
+            .from
+            .username
+            .as_deref()
+            .unwrap_or(&query.from.first_name);
+        let text = crate::approvals::answered_prompt(msg.text().unwrap_or_default(), decision, by);
+        if let Err(e) = bot.edit_message_text(msg.chat.id, msg.id, text).await {
+            warn!("Telegram: failed to update approval prompt: {e}");
+        }
+    }
+    Ok(())
+}
+
+/// The message `msg` replies to: the quoted part if the user picked one,
+/// otherwise its text or caption.
+fn telegram_reply_context(msg: &teloxide::types::Message) -> Option<ReplyContext> {
+    let replied = msg.reply_to_message()?;
+    let text = match msg.quote() {
+        Some(quote) => quote.text.clone(),
+        None => replied.text().or_else(|| replied.caption())?.to_string(),
+    };
+    let sender = replied
+        .from
+        .as_ref()
+        .map(|u| u.username.clone().unwrap_or_else(|| u.first_name.clone()))
+        .unwrap_or_else(|| "Unknown".into());
+    Some(ReplyContext { sender, text })
+}
+
 async fn handle_message(
     bot: Bot,
     msg: teloxide::types::Message,
@@ -200,43 +417,18 @@ async fn handle_message(
         }) => ("group", "telegram_channel"),
     };
     let chat_title = msg.chat.title().map(|t| t.to_string());
+    // Forum topics share the group's chat; the session and history are per topic
+    let thread = message_topic(&msg);
+    let thread_id = thread.map(|t| i64::from(t.0 .0));
+    let topic = thread.and_then(|_| topic_name(&msg));
 
     // Extract content: text, photo, or voice
     let mut text = msg.text().unwrap_or("").to_string();
     let mut image_data: Option<(String, String)> = None; // (base64, media_type)
     let mut document_saved_path: Option<String> = None;
 
-    // Handle /reset command — clear session
-    if text.trim() == "/reset" {
-        let external_chat_id = raw_chat_id.to_string();
-        let chat_title_for_lookup = chat_title.clone();
-        let chat_type_for_lookup = db_chat_type.to_string();
-        let chat_id = call_blocking(state.db.clone(), move |db| {
-            db.resolve_or_create_chat_id(
-                "telegram",
-                &external_chat_id,
-                chat_title_for_lookup.as_deref(),
-                &chat_type_for_lookup,
-            )
-        })
-        .await
-        .unwrap_or(raw_chat_id);
-        let _ = call_blocking(state.db.clone(), move |db| db.clear_chat_context(chat_id)).await;
-        let _ = bot
-            .send_message(msg.chat.id, "Context cleared (session + chat history).")
-            .await;
-        return Ok(());
-    }
-
-    // Handle /skills command — list available skills
-    if text.trim() == "/skills" {
-        let formatted = state.skills.list_skills_formatted();
-        let _ = bot.send_message(msg.chat.id, formatted).await;
-        return Ok(());
-    }
-
-    // Handle /archive command — archive current session to markdown
-    if text.trim() == "/archive" {
+    // Handle slash commands (/help, /status, /usage, ...)
+    if parse_command(&text).is_some() {
         let external_chat_id = raw_chat_id.to_string();
         let chat_title_for_lookup = chat_title.clone();
         let chat_type_for_lookup = db_chat_type.to_string();
@@ -250,58 +442,8 @@ async fn handle_message(
         })
         .await
         .unwrap_or(raw_chat_id);
-        if let Ok(Some((json, _))) =
-            call_blocking(state.db.clone(), move |db| db.load_session(chat_id)).await
-        {
-            let messages: Vec<Message> = serde_json::from_str(&json).unwrap_or_default();
-            if messages.is_empty() {
-                let _ = bot
-                    .send_message(msg.chat.id, "No session to archive.")
-                    .await;
-            } else {
-                archive_conversation(&state.config.data_dir, "telegram", chat_id, &messages);
-                let _ = bot
-                    .send_message(
-                        msg.chat.id,
-                        format!("Archived {} messages.", messages.len()),
-                    )
-                    .await;
-            }
-        } else {
-            let _ = bot
-                .send_message(msg.chat.id, "No session to archive.")
-                .await;
-        }
-        return Ok(());
-    }
-
-    // Handle /usage command — token usage summary
-    if text.trim() == "/usage" {
-        let external_chat_id = raw_chat_id.to_string();
-        let chat_title_for_lookup = chat_title.clone();
-        let chat_type_for_lookup = db_chat_type.to_string();
-        let chat_id = call_blocking(state.db.clone(), move |db| {
-            db.resolve_or_create_chat_id(
-                "telegram",
-                &external_chat_id,
-                chat_title_for_lookup.as_deref(),
-                &chat_type_for_lookup,
-            )
-        })
-        .await
-        .unwrap_or(raw_chat_id);
-        match build_usage_report(state.db.clone(), &state.config, chat_id).await {
-            Ok(response) => {
-                let _ = bot.send_message(msg.chat.id, response).await;
-            }
-            Err(e) => {
-                let _ = bot
-                    .send_message(
-                        msg.chat.id,
-                        format!("Failed to query usage statistics: {e}"),
-                    )
-                    .await;
-            }
+        if let Some(reply) = handle_command(&state, chat_id, "telegram", &text).await {
+            let _ = message_in(&bot, msg.chat.id, thread, reply).await;
         }
         return Ok(());
     }
@@ -311,9 +453,8 @@ async fn handle_message(
         if let Some(photo) = photos.last() {
             match download_telegram_file(&bot, &photo.file.id.0).await {
                 Ok(bytes) => {
-                    let base64 = base64_encode(&bytes);
-                    let media_type = guess_image_media_type(&bytes);
-                    image_data = Some((base64, media_type));
+                    let opts = crate::image_utils::ImageOptions::from_config(&state.config);
+                    image_data = Some(crate::image_utils::encode_image_for_llm(bytes, opts).await);
                 }
                 Err(e) => {
                     error!("Failed to download photo: {e}");
@@ -335,15 +476,16 @@ async fn handle_message(
             .saturating_mul(1024);
         let doc_bytes = u64::from(document.file.size);
         if doc_bytes > max_bytes {
-            let _ = bot
-                .send_message(
-                    msg.chat.id,
-                    format!(
-                        "Document is too large ({} bytes). Max allowed is {} MB.",
-                        doc_bytes, state.config.max_document_size_mb
-                    ),
-                )
-                .await;
+            let _ = message_in(
+                &bot,
+                msg.chat.id,
+                thread,
+                format!(
+                    "Document is too large ({} bytes). Max allowed is {} MB.",
+                    doc_bytes, state.config.max_document_size_mb
+                ),
+            )
+            .await;
             return Ok(());
         }
 
@@ -400,6 +542,21 @@ async fn handle_message(
                 } else {
                     text = format!("{}\n\n{}", text.trim(), file_note);
                 }
+
+                // Photos sent "as file" (e.g. full-resolution HEIC from phones)
+                let is_image_document = document
+                    .mime_type
+                    .as_ref()
+                    .map(|m| m.to_string().starts_with("image/"))
+                    .unwrap_or(false);
+                if is_image_document && image_data.is_none() {
+                    let opts = crate::image_utils::ImageOptions::from_config(&state.config);
+                    image_data = Some(crate::image_utils::encode_image_for_llm(bytes, opts).await);
+                } else if image_data.is_none() {
+                    // PDFs also go to the model as a document block
+                    let mime = document.mime_type.as_ref().map(|m| m.to_string());
+                    image_data = crate::channel::document_for_llm(&bytes, mime.as_deref());
+                }
             }
             Err(e) => {
                 error!("Failed to download document: {e}");
@@ -414,48 +571,39 @@ async fn handle_message(
         }
     }
 
-    // Handle voice messages
-    if let Some(voice) = msg.voice() {
-        if let Some(ref openai_key) = state.config.openai_api_key {
-            match download_telegram_file(&bot, &voice.file.id.0).await {
-                Ok(bytes) => {
-                    let sender_name = msg
-                        .from
-                        .as_ref()
-                        .map(|u| u.username.clone().unwrap_or_else(|| u.first_name.clone()))
-                        .unwrap_or_else(|| "Unknown".into());
-                    match crate::transcribe::transcribe_audio(openai_key, &bytes).await {
-                        Ok(transcription) => {
-                            text = format!(
-                                "[voice message from {}]: {}",
-                                sanitize_xml(&sender_name),
-                                sanitize_xml(&transcription)
-                            );
-                        }
-                        Err(e) => {
-                            error!("Whisper transcription failed: {e}");
-                            text = format!(
-                                "[voice message from {}]: [transcription failed: {e}]",
-                                sanitize_xml(&sender_name)
-                            );
-                        }
-                    }
-                }
-                Err(e) => {
-                    error!("Failed to download voice message: {e}");
-                }
-            }
-        } else {
-            let _ = bot
-                .send_message(
-                    msg.chat.id,
-                    "Voice messages not supported (no Whisper API key configured)",
-                )
-                .await;
+    // Handle voice notes and audio files
+    let audio_file = msg
+        .voice()
+        .map(|v| (&v.file.id.0, v.mime_type.as_ref()))
+        .or_else(|| msg.audio().map(|a| (&a.file.id.0, a.mime_type.as_ref())));
+    if let Some((file_id, mime)) = audio_file {
+        let Some(stt) = crate::transcribe::SttSettings::from_config(&state.config) else {
+            let _ = message_in(
+                &bot,
+                msg.chat.id,
+                thread,
+                "Voice messages not supported (no speech-to-text backend configured)",
+            )
+            .await;
             return Ok(());
+        };
+        match download_telegram_file(&bot, file_id).await {
+            Ok(bytes) => {
+                let mime = mime.map(|m| m.essence_str()).unwrap_or("audio/ogg");
+                let voice_text = crate::transcribe::voice_note_text(&stt, &bytes, mime).await;
+                text = sanitize_xml(&voice_text);
+            }
+            Err(e) => {
+                error!("Failed to download voice message: {e}");
+            }
         }
     }
 
+    // A PDF rides along in image_data but is stored like other documents
+    let has_image = image_data
+        .as_ref()
+        .is_some_and(|(_, media_type)| !is_document_media_type(media_type));
+
     // If no text/image/document content, nothing to process
     if text.trim().is_empty() && image_data.is_none() && document_saved_path.is_none() {
         return Ok(());
@@ -465,6 +613,7 @@ async fn handle_message(
         .as_ref()
         .map(|u| u.username.clone().unwrap_or_else(|| u.first_name.clone()))
         .unwrap_or_else(|| "Unknown".into());
+    let reply_context = telegram_reply_context(&msg);
 
     // Check group allowlist
     if (db_chat_type == "telegram_group" || db_chat_type == "telegram_supergroup")
@@ -491,7 +640,7 @@ async fn handle_message(
             db.upsert_chat(chat_id, chat_title_owned.as_deref(), &chat_type_owned)
         })
         .await;
-        let stored_content = if image_data.is_some() {
+        let stored_content = if has_image {
             format!(
                 "[image]{}",
                 if text.trim().is_empty() {
@@ -509,6 +658,8 @@ async fn handle_message(
         } else {
             text
         };
+        let stored_content =
+            crate::channel::with_reply_context(stored_content, reply_context.as_ref());
         let stored = StoredMessage {
             id: msg.id.0.to_string(),
             chat_id,
@@ -516,6 +667,7 @@ async fn handle_message(
             content: stored_content,
             is_from_bot: false,
             timestamp: chrono::Utc::now().to_rfc3339(),
+            thread_id,
         };
         let _ = call_blocking(state.db.clone(), move |db| db.store_message(&stored)).await;
         return Ok(());
@@ -542,8 +694,14 @@ async fn handle_message(
         db.upsert_chat(chat_id, chat_title_owned.as_deref(), &chat_type_owned)
     })
     .await;
+    if let (Some(thread_id), Some(topic)) = (thread_id, topic) {
+        let _ = call_blocking(state.db.clone(), move |db| {
+            db.set_chat_topic(chat_id, thread_id, &topic)
+        })
+        .await;
+    }
 
-    let stored_content = if image_data.is_some() {
+    let stored_content = if has_image {
         format!(
             "[image]{}",
             if text.trim().is_empty() {
@@ -561,6 +719,7 @@ async fn handle_message(
     } else {
         text.clone()
     };
+    let stored_content = crate::channel::with_reply_context(stored_content, reply_context.as_ref());
     let stored = StoredMessage {
         id: msg.id.0.to_string(),
         chat_id,
@@ -568,17 +727,39 @@ async fn handle_message(
         content: stored_content,
         is_from_bot: false,
         timestamp: chrono::Utc::now().to_rfc3339(),
+        thread_id,
     };
     let _ = call_blocking(state.db.clone(), move |db| db.store_message(&stored)).await;
+    // Telegram reports the sender's app language; tools such as wiki_lookup
+    // use it as the chat's default language
+    if let Some(locale) = msg.from.as_ref().and_then(|u| u.language_code.clone()) {
+        let _ = call_blocking(state.db.clone(), move |db| {
+            db.set_chat_locale(chat_id, &locale)
+        })
+        .await;
+    }
+    if runtime_chat_type != "private" {
+        // Handles are stored as-is; remember the full name for attribution
+        if let Some(full_name) = msg.from.as_ref().map(|u| u.full_name()) {
+            if full_name != sender_name {
+                let member = sender_name.clone();
+                let _ = call_blocking(state.db.clone(), move |db| {
+                    db.set_group_member_display_name(chat_id, &member, &full_name)
+                })
+                .await;
+            }
+        }
+    }
 
     // Determine if we should respond
-    let should_respond = match runtime_chat_type {
-        "private" => true,
-        _ => {
-            let bot_mention = format!("@{}", state.config.bot_username);
-            text.contains(&bot_mention)
-        }
-    };
+    let bot_mention = format!("@{}", state.config.bot_username);
+    let should_respond = crate::channel::should_respond(
+        state.db.clone(),
+        chat_id,
+        runtime_chat_type == "private",
+        text.contains(&bot_mention),
+    )
+    .await;
 
     if !should_respond {
         return Ok(());
@@ -596,21 +777,40 @@ async fn handle_message(
     let typing_bot = bot.clone();
     let typing_handle = tokio::spawn(async move {
         loop {
-            let _ = typing_bot
-                .send_chat_action(typing_chat_id, ChatAction::Typing)
-                .await;
+            let mut action = typing_bot.send_chat_action(typing_chat_id, ChatAction::Typing);
+            if let Some(thread) = thread {
+                action = action.message_thread_id(thread);
+            }
+            let _ = action.await;
             tokio::time::sleep(std::time::Duration::from_secs(4)).await;
         }
     });
 
     // Process through platform-agnostic agent engine.
-    let (event_tx, mut event_rx) = tokio::sync::mpsc::unbounded_channel::<AgentEvent>();
+    let (event_tx, event_rx) = tokio::sync::mpsc::unbounded_channel::<AgentEvent>();
+    let streaming = STREAMING
+        .get()
+        .cloned()
+        .unwrap_or_default()
+        .for_provider(state.llm.capabilities().streaming);
+    let draft_target = TelegramDraft {
+        bot: bot.clone(),
+        chat_id: msg.chat.id,
+        thread,
+    };
+    let stream_task = tokio::spawn(stream_reply_draft(
+        draft_target.clone(),
+        event_rx,
+        streaming,
+    ));
     match process_with_agent_with_events(
         &state,
         AgentRequestContext {
             caller_channel: "telegram",
             chat_id,
+            thread_id,
             chat_type: runtime_chat_type,
+            denied_tools: &[],
         },
         None,
         image_data,
@@ -621,17 +821,27 @@ async fn handle_message(
         Ok(response) => {
             typing_handle.abort();
             drop(event_tx);
-            let mut used_send_message_tool = false;
-            while let Some(event) = event_rx.recv().await {
-                if let AgentEvent::ToolStart { name } = event {
-                    if name == "send_message" {
-                        used_send_message_tool = true;
-                    }
+            let stream = stream_task.await.unwrap_or_default();
+            let used_send_message_tool = stream.used_send_message_tool;
+            let superseded = stream.superseded;
+            if response.is_empty() {
+                if let Some(draft) = &stream.draft {
+                    draft_target.delete(draft).await;
                 }
             }
 
             if !response.is_empty() {
-                send_response(&bot, msg.chat.id, &response).await;
+                let sent = crate::channel::send_tracked(
+                    state.db.clone(),
+                    chat_id,
+                    "telegram",
+                    &response,
+                    || draft_target.finish(stream.draft, &response),
+                )
+                .await;
+                if let Err(e) = sent {
+                    error!("Telegram: failed to send response to chat {chat_id}: {e}");
+                }
 
                 // Store bot response
                 let bot_msg = StoredMessage {
@@ -641,6 +851,7 @@ async fn handle_message(
                     content: response,
                     is_from_bot: true,
                     timestamp: chrono::Utc::now().to_rfc3339(),
+                    thread_id,
                 };
                 let _ = call_blocking(state.db.clone(), move |db| db.store_message(&bot_msg)).await;
             }
@@ -650,9 +861,11 @@ async fn handle_message(
                     "Agent returned empty final response for chat {}; likely delivered via send_message tool",
                     chat_id
                 );
+            } else if superseded {
+                // A later message in this chat carries the reply
             } else {
                 let fallback = "I couldn't produce a visible reply after an automatic retry. Please try again.".to_string();
-                send_response(&bot, msg.chat.id, &fallback).await;
+                let _ = send_response(&bot, msg.chat.id, thread, &fallback).await;
                 let bot_msg = StoredMessage {
                     id: uuid::Uuid::new_v4().to_string(),
                     chat_id,
@@ -660,14 +873,19 @@ async fn handle_message(
                     content: fallback,
                     is_from_bot: true,
                     timestamp: chrono::Utc::now().to_rfc3339(),
+                    thread_id,
                 };
                 let _ = call_blocking(state.db.clone(), move |db| db.store_message(&bot_msg)).await;
             }
         }
         Err(e) => {
             typing_handle.abort();
+            drop(event_tx);
+            if let Some(draft) = stream_task.await.ok().and_then(|s| s.draft) {
+                draft_target.delete(&draft).await;
+            }
             error!("Error processing message: {}", e);
-            let _ = bot.send_message(msg.chat.id, format!("Error: {e}")).await;
+            let _ = message_in(&bot, msg.chat.id, thread, format!("Error: {e}")).await;
         }
     }
 
@@ -686,14 +904,6 @@ async fn download_telegram_file(
     Ok(buf)
 }
 
-fn base64_encode(data: &[u8]) -> String {
-    crate::image_utils::base64_encode(data)
-}
-
-fn guess_image_media_type(data: &[u8]) -> String {
-    crate::image_utils::guess_image_media_type(data)
-}
-
 fn split_response_text(text: &str) -> Vec<String> {
     const MAX_LEN: usize = 4096;
 
@@ -856,23 +1066,101 @@ fn render_markdown_v2_safe(text: &str) -> String {
     out
 }
 
-async fn send_telegram_markdown_or_plain(bot: &Bot, chat_id: ChatId, text: &str) {
+async fn send_telegram_markdown_or_plain(
+    bot: &Bot,
+    chat_id: ChatId,
+    thread: Option<ThreadId>,
+    text: &str,
+) -> Result<(), String> {
     let markdown_text = render_markdown_v2_safe(text);
-    let markdown = bot
-        .send_message(chat_id, markdown_text)
+    let markdown = message_in(bot, chat_id, thread, markdown_text)
         .parse_mode(ParseMode::MarkdownV2)
         .await;
 
     if let Err(err) = markdown {
         warn!("Telegram MarkdownV2 send failed, falling back to plain text: {err}");
-        let _ = bot.send_message(chat_id, text).await;
+        message_in(bot, chat_id, thread, text)
+            .await
+            .map_err(|e| format!("Failed to send Telegram message: {e}"))?;
+    }
+    Ok(())
+}
+
+/// Streamed reply drafts, edited with editMessageText.
+#[derive(Clone)]
+struct TelegramDraft {
+    bot: Bot,
+    chat_id: ChatId,
+    thread: Option<ThreadId>,
+}
+
+fn draft_error(e: teloxide::RequestError) -> DraftError {
+    match e {
+        teloxide::RequestError::RetryAfter(secs) => DraftError::RateLimited(secs.duration()),
+        e => DraftError::Failed(e.to_string()),
+    }
+}
+
+#[async_trait]
+impl DraftTarget for TelegramDraft {
+    type Id = MessageId;
+    const CHANNEL: &'static str = "Telegram";
+    // Bots get about one message per second per chat
+    const MIN_EDIT_INTERVAL_MS: u64 = 1000;
+    // Below the 4096 limit; the final reply is split properly
+    const MAX_DRAFT_LEN: usize = 4000;
+
+    async fn post(&self, text: &str) -> Result<MessageId, DraftError> {
+        message_in(&self.bot, self.chat_id, self.thread, text)
+            .await
+            .map(|sent| sent.id)
+            .map_err(draft_error)
+    }
+
+    async fn edit(&self, id: &MessageId, text: &str) -> Result<(), DraftError> {
+        self.bot
+            .edit_message_text(self.chat_id, *id, text)
+            .await
+            .map(|_| ())
+            .map_err(draft_error)
+    }
+
+    async fn delete(&self, id: &MessageId) {
+        let _ = self.bot.delete_message(self.chat_id, *id).await;
+    }
+
+    async fn send(&self, text: &str) -> Result<(), String> {
+        send_response(&self.bot, self.chat_id, self.thread, text).await
+    }
+
+    fn split(&self, text: &str) -> Vec<String> {
+        split_response_text(text)
+    }
+
+    /// MarkdownV2 like any other reply, plain text if that doesn't parse.
+    async fn edit_final(&self, id: &MessageId, text: &str) -> Result<(), DraftError> {
+        let formatted = self
+            .bot
+            .edit_message_text(self.chat_id, *id, render_markdown_v2_safe(text))
+            .parse_mode(ParseMode::MarkdownV2)
+            .await;
+        match formatted {
+            Ok(_) => Ok(()),
+            Err(_) => self.edit(id, text).await,
+        }
     }
 }
 
-pub async fn send_response(bot: &Bot, chat_id: ChatId, text: &str) {
+pub async fn send_response(
+    bot: &Bot,
+    chat_id: ChatId,
+    thread: Option<ThreadId>,
+    text: &str,
+) -> Result<(), String> {
     for chunk in split_response_text(text) {
-        send_telegram_markdown_or_plain(bot, chat_id, &chunk).await;
+        send_telegram_markdown_or_plain(bot, chat_id, thread, &chunk).await?;
     }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -883,6 +1171,8 @@ mod tests {
         strip_thinking,
     };
     use crate::db::StoredMessage;
+    use crate::image_utils::{base64_encode, guess_image_media_type};
+    use crate::llm_types::Message;
 
     fn make_msg(id: &str, sender: &str, content: &str, is_bot: bool, ts: &str) -> StoredMessage {
         StoredMessage {
@@ -892,6 +1182,7 @@ mod tests {
             content: content.into(),
             is_from_bot: is_bot,
             timestamp: ts.into(),
+            thread_id: None,
         }
     }
 
@@ -1197,6 +1488,7 @@ mod tests {
                     id: "t1".into(),
                     name: "bash".into(),
                     input: serde_json::json!({"command": "ls"}),
+                    signature: None,
                 },
             ]),
         };
@@ -1297,6 +1589,32 @@ mod tests {
         assert!(prompt.contains("sub_agent"));
     }
 
+    #[test]
+    fn test_parse_chat_id_and_thread() {
+        assert_eq!(
+            parse_chat_id("-1001847508954").unwrap(),
+            ChatId(-1001847508954)
+        );
+        assert!(parse_chat_id("-1001847508954:4").is_err());
+        assert_eq!(to_thread_id(4), Some(ThreadId(MessageId(4))));
+        assert_eq!(to_thread_id(i64::MAX), None);
+    }
+
+    #[test]
+    fn test_message_topic_and_name() {
+        let json = r#"{"chat":{"id":-1001847508954,"is_forum":true,"title":"twest","type":"supergroup"},"date":1675229140,"from":{"first_name":"A","id":1253681278,"is_bot":false,"username":"alice"},"is_topic_message":true,"message_id":5,"message_thread_id":4,"reply_to_message":{"chat":{"id":-1001847508954,"is_forum":true,"title":"twest","type":"supergroup"},"date":1675229139,"forum_topic_created":{"icon_color":9367192,"name":"Releases"},"from":{"first_name":"A","id":1253681278,"is_bot":false,"username":"alice"},"is_topic_message":true,"message_id":4,"message_thread_id":4},"text":"blah"}"#;
+        let msg: teloxide::types::Message = serde_json::from_str(json).unwrap();
+        assert_eq!(message_topic(&msg), Some(ThreadId(MessageId(4))));
+        assert_eq!(topic_name(&msg).as_deref(), Some("Releases"));
+        assert!(telegram_reply_context(&msg).is_none());
+
+        // A reply thread in a group without topics is not a topic
+        let json = r#"{"chat":{"id":-1001,"title":"g","type":"supergroup"},"date":1675229140,"from":{"first_name":"A","id":1,"is_bot":false},"message_id":7,"message_thread_id":6,"text":"hi"}"#;
+        let msg: teloxide::types::Message = serde_json::from_str(json).unwrap();
+        assert_eq!(message_topic(&msg), None);
+        assert_eq!(topic_name(&msg), None);
+    }
+
     #[test]
     fn test_sanitize_xml() {
         assert_eq!(sanitize_xml("hello"), "hello");
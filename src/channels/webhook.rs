@@ -0,0 +1,467 @@
+//! Generic webhook channel for wiring RayClaw into other systems.
+//!
+//! Messages are POSTed to `/api/webhook/messages` as
+//! `{chat_id, text, sender?, metadata?, message_id?}` with the channel's
+//! bearer token. A caller-supplied `message_id` makes retries safe: it is
+//! claimed through the [`Coordinator`](crate::coordination::Coordinator), so
+//! the same message is only answered once, by one instance.
+//! Every bot message for a webhook chat is POSTed to `callback_url`, signed
+//! with HMAC-SHA256 over `"{timestamp}.{body}"`: the timestamp is in
+//! `X-RayClaw-Timestamp` and the signature in
+//! `X-RayClaw-Signature: sha256=<hex>`. Replies to a message carry its
+//! `in_reply_to` ID and `metadata`.
+
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+use tracing::{error, info};
+
+use crate::agent_engine::process_with_agent_with_events;
+use crate::agent_engine::AgentEvent;
+use crate::agent_engine::AgentRequestContext;
+use crate::channel::ConversationKind;
+use crate::channel_adapter::{ChannelAdapter, TextFormat};
+use crate::commands::handle_command;
+use crate::db::call_blocking;
+use crate::db::StoredMessage;
+use crate::runtime::AppState;
+use crate::web::token_matches;
+
+const MAX_CHAT_ID_LEN: usize = 128;
+/// How long a caller's `message_id` stays claimed
+const MESSAGE_CLAIM_TTL: Duration = Duration::from_secs(6 * 3600);
+pub const SIGNATURE_HEADER: &str = "X-RayClaw-Signature";
+pub const TIMESTAMP_HEADER: &str = "X-RayClaw-Timestamp";
+
+/// Validated in Config::post_deserialize.
+#[derive(Debug, Clone, Deserialize)]
+pub struct WebhookChannelConfig {
+    /// Bearer token callers send to the inbound endpoint
+    #[serde(default)]
+    pub token: String,
+    /// Where bot messages are POSTed
+    #[serde(default)]
+    pub callback_url: String,
+    /// HMAC key for the callback signature
+    #[serde(default)]
+    pub secret: String,
+}
+
+impl WebhookChannelConfig {
+    pub fn is_configured(&self) -> bool {
+        !self.token.trim().is_empty()
+            && !self.secret.trim().is_empty()
+            && !self.callback_url.trim().is_empty()
+    }
+}
+
+/// Why an inbound message was refused.
+#[derive(Debug)]
+pub enum WebhookError {
+    NotConfigured,
+    Unauthorized,
+    Invalid(String),
+    Internal(String),
+}
+
+#[derive(Debug)]
+pub struct Accepted {
+    pub chat_id: i64,
+    pub message_id: String,
+    /// The message_id was already claimed, so nothing was done
+    pub duplicate: bool,
+}
+
+/// A message posted to the inbound endpoint.
+#[derive(Debug, PartialEq)]
+pub struct InboundMessage {
+    /// The caller's conversation ID
+    pub chat_id: String,
+    pub text: String,
+    pub sender: String,
+    /// Echoed back with the reply
+    pub metadata: Option<serde_json::Value>,
+    /// The caller's ID for this message, used to drop retries
+    pub message_id: Option<String>,
+}
+
+impl InboundMessage {
+    pub fn from_json(value: &serde_json::Value) -> Result<Self, String> {
+        let chat_id = id_field(value, "chat_id")?.ok_or("chat_id must be a string or number")?;
+        let message_id = id_field(value, "message_id")?;
+        let text = value
+            .get("text")
+            .and_then(|v| v.as_str())
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+            .ok_or("text is required")?
+            .to_string();
+        let sender = value
+            .get("sender")
+            .and_then(|v| v.as_str())
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .unwrap_or("webhook")
+            .to_string();
+        let metadata = match value.get("metadata") {
+            None | Some(serde_json::Value::Null) => None,
+            Some(m @ serde_json::Value::Object(_)) => Some(m.clone()),
+            Some(_) => return Err("metadata must be an object".into()),
+        };
+        Ok(InboundMessage {
+            chat_id,
+            text,
+            sender,
+            metadata,
+            message_id,
+        })
+    }
+}
+
+/// An optional string or numeric ID field of 1-`MAX_CHAT_ID_LEN` printable
+/// characters.
+fn id_field(value: &serde_json::Value, name: &str) -> Result<Option<String>, String> {
+    let id = match value.get(name) {
+        None | Some(serde_json::Value::Null) => return Ok(None),
+        Some(serde_json::Value::String(s)) => s.trim().to_string(),
+        Some(serde_json::Value::Number(n)) => n.to_string(),
+        Some(_) => return Err(format!("{name} must be a string or number")),
+    };
+    if id.is_empty() || id.len() > MAX_CHAT_ID_LEN || id.chars().any(char::is_control) {
+        return Err(format!(
+            "{name} must be 1-{MAX_CHAT_ID_LEN} printable characters"
+        ));
+    }
+    Ok(Some(id))
+}
+
+/// `sha256=<hex>` signature of a callback body sent at `timestamp`.
+pub fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key size");
+    mac.update(format!("{timestamp}.{body}").as_bytes());
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+fn http_client() -> &'static reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        reqwest::Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()
+            .unwrap_or_default()
+    })
+}
+
+/// The message a bot message replies to.
+struct ReplyTo<'a> {
+    message_id: &'a str,
+    metadata: Option<&'a serde_json::Value>,
+}
+
+async fn post_callback(
+    config: &WebhookChannelConfig,
+    chat_id: &str,
+    text: &str,
+    reply_to: Option<&ReplyTo<'_>>,
+) -> Result<(), String> {
+    let mut payload = serde_json::json!({
+        "chat_id": chat_id,
+        "text": text,
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+    });
+    if let Some(reply_to) = reply_to {
+        payload["in_reply_to"] = reply_to.message_id.into();
+        if let Some(metadata) = reply_to.metadata {
+            payload["metadata"] = metadata.clone();
+        }
+    }
+    let body = payload.to_string();
+    let timestamp = chrono::Utc::now().timestamp();
+    let resp = http_client()
+        .post(&config.callback_url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(TIMESTAMP_HEADER, timestamp.to_string())
+        .header(SIGNATURE_HEADER, sign(&config.secret, timestamp, &body))
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to call webhook callback: {e}"))?;
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let body = resp.text().await.unwrap_or_default();
+        return Err(format!(
+            "Webhook callback failed: HTTP {status} {}",
+            body.chars().take(300).collect::<String>()
+        ));
+    }
+    Ok(())
+}
+
+pub struct WebhookAdapter {
+    config: WebhookChannelConfig,
+}
+
+impl WebhookAdapter {
+    pub fn new(config: WebhookChannelConfig) -> Self {
+        WebhookAdapter { config }
+    }
+}
+
+#[async_trait::async_trait]
+impl ChannelAdapter for WebhookAdapter {
+    fn name(&self) -> &str {
+        "webhook"
+    }
+
+    fn text_format(&self) -> TextFormat {
+        TextFormat::Markdown
+    }
+
+    fn chat_type_routes(&self) -> Vec<(&str, ConversationKind)> {
+        vec![("webhook", ConversationKind::Private)]
+    }
+
+    async fn send_text(&self, external_chat_id: &str, text: &str) -> Result<(), String> {
+        post_callback(&self.config, external_chat_id, text, None).await
+    }
+}
+
+/// Check the caller's token, store the message and answer it in the
+/// background.
+pub async fn accept(
+    app_state: Arc<AppState>,
+    token: Option<&str>,
+    body: &str,
+) -> Result<Accepted, WebhookError> {
+    let config = app_state
+        .config
+        .channel_config::<WebhookChannelConfig>("webhook")
+        .filter(WebhookChannelConfig::is_configured)
+        .ok_or(WebhookError::NotConfigured)?;
+    if !token_matches(token, config.token.trim()) {
+        return Err(WebhookError::Unauthorized);
+    }
+    let value: serde_json::Value =
+        serde_json::from_str(body).map_err(|e| WebhookError::Invalid(e.to_string()))?;
+    let message = InboundMessage::from_json(&value).map_err(WebhookError::Invalid)?;
+
+    let chat_id = call_blocking(app_state.db.clone(), {
+        let external_id = message.chat_id.clone();
+        move |db| {
+            let title = format!("webhook-{external_id}");
+            db.resolve_or_create_chat_id("webhook", &external_id, Some(&title), "webhook")
+        }
+    })
+    .await
+    .map_err(|e| WebhookError::Internal(e.to_string()))?;
+
+    let message_id = match &message.message_id {
+        Some(id) => {
+            let claim_key = format!("webhook:message:{}:{id}", message.chat_id);
+            if !app_state
+                .coordinator
+                .claim(&claim_key, MESSAGE_CLAIM_TTL)
+                .await
+            {
+                return Ok(Accepted {
+                    chat_id,
+                    message_id: id.clone(),
+                    duplicate: true,
+                });
+            }
+            id.clone()
+        }
+        None => uuid::Uuid::new_v4().to_string(),
+    };
+    let stored = StoredMessage {
+        id: message_id.clone(),
+        chat_id,
+        sender_name: message.sender.clone(),
+        content: message.text.clone(),
+        is_from_bot: false,
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        thread_id: None,
+    };
+    call_blocking(app_state.db.clone(), move |db| db.store_message(&stored))
+        .await
+        .map_err(|e| WebhookError::Internal(e.to_string()))?;
+
+    tokio::spawn(handle_webhook_message(
+        app_state,
+        config,
+        chat_id,
+        message,
+        message_id.clone(),
+    ));
+    Ok(Accepted {
+        chat_id,
+        message_id,
+        duplicate: false,
+    })
+}
+
+async fn handle_webhook_message(
+    app_state: Arc<AppState>,
+    config: WebhookChannelConfig,
+    chat_id: i64,
+    message: InboundMessage,
+    message_id: String,
+) {
+    let external_id = message.chat_id.as_str();
+    let reply_to = ReplyTo {
+        message_id: &message_id,
+        metadata: message.metadata.as_ref(),
+    };
+
+    if let Some(reply) = handle_command(&app_state, chat_id, "webhook", &message.text).await {
+        let _ = post_callback(&config, external_id, &reply, Some(&reply_to)).await;
+        return;
+    }
+    if !crate::channel::should_respond(app_state.db.clone(), chat_id, true, false).await {
+        return;
+    }
+
+    info!(
+        "Webhook message from {} in {}: {}",
+        message.sender,
+        external_id,
+        message.text.chars().take(100).collect::<String>()
+    );
+
+    let (event_tx, mut event_rx) = tokio::sync::mpsc::unbounded_channel::<AgentEvent>();
+    match process_with_agent_with_events(
+        &app_state,
+        AgentRequestContext {
+            caller_channel: "webhook",
+            chat_id,
+            thread_id: None,
+            chat_type: "private",
+            denied_tools: &[],
+        },
+        None,
+        None,
+        Some(&event_tx),
+    )
+    .await
+    {
+        Ok(response) => {
+            drop(event_tx);
+            let mut used_send_message_tool = false;
+            let mut superseded = false;
+            while let Some(event) = event_rx.recv().await {
+                match event {
+                    AgentEvent::ToolStart { name } if name == "send_message" => {
+                        used_send_message_tool = true;
+                    }
+                    AgentEvent::Superseded => superseded = true,
+                    _ => {}
+                }
+            }
+
+            let reply = if !response.is_empty() {
+                let sent = crate::channel::send_tracked(
+                    app_state.db.clone(),
+                    chat_id,
+                    "webhook",
+                    &response,
+                    || post_callback(&config, external_id, &response, Some(&reply_to)),
+                )
+                .await;
+                if let Err(e) = sent {
+                    error!("Webhook: failed to deliver response: {e}");
+                }
+                response
+            } else if !used_send_message_tool && !superseded {
+                let fallback = "I couldn't produce a visible reply after an automatic retry. Please try again.";
+                let _ = post_callback(&config, external_id, fallback, Some(&reply_to)).await;
+                fallback.to_string()
+            } else {
+                return;
+            };
+
+            let bot_msg = StoredMessage {
+                id: uuid::Uuid::new_v4().to_string(),
+                chat_id,
+                sender_name: app_state.config.bot_username.clone(),
+                content: reply,
+                is_from_bot: true,
+                timestamp: chrono::Utc::now().to_rfc3339(),
+                thread_id: None,
+            };
+            let _ = call_blocking(app_state.db.clone(), move |db| db.store_message(&bot_msg)).await;
+        }
+        Err(e) => {
+            error!("Error processing webhook message: {e}");
+            let _ = post_callback(
+                &config,
+                external_id,
+                &format!("Error: {e}"),
+                Some(&reply_to),
+            )
+            .await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_inbound_message_from_json() {
+        let message = InboundMessage::from_json(&json!({
+            "chat_id": " ticket-42 ",
+            "text": " Summarize the ticket ",
+            "sender": "helpdesk",
+            "metadata": {"ticket": 42},
+            "message_id": "m-1"
+        }))
+        .unwrap();
+        assert_eq!(
+            message,
+            InboundMessage {
+                chat_id: "ticket-42".into(),
+                text: "Summarize the ticket".into(),
+                sender: "helpdesk".into(),
+                metadata: Some(json!({"ticket": 42})),
+                message_id: Some("m-1".into()),
+            }
+        );
+
+        let numeric = InboundMessage::from_json(&json!({"chat_id": 7, "text": "hi"})).unwrap();
+        assert_eq!(numeric.chat_id, "7");
+        assert_eq!(numeric.sender, "webhook");
+        assert_eq!(numeric.metadata, None);
+        assert_eq!(numeric.message_id, None);
+
+        for bad in [
+            json!({"text": "hi"}),
+            json!({"chat_id": "", "text": "hi"}),
+            json!({"chat_id": "a\nb", "text": "hi"}),
+            json!({"chat_id": "x".repeat(MAX_CHAT_ID_LEN + 1), "text": "hi"}),
+            json!({"chat_id": "a", "text": "  "}),
+            json!({"chat_id": "a", "text": "hi", "metadata": [1]}),
+            json!({"chat_id": "a", "text": "hi", "message_id": ""}),
+            json!({"chat_id": "a", "text": "hi", "message_id": {"id": 1}}),
+        ] {
+            assert!(InboundMessage::from_json(&bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_sign_callback() {
+        assert_eq!(
+            sign("s3cret", 1_700_000_000, r#"{"text":"hi"}"#),
+            "sha256=a4abab2c9ec335a751cf8c3848e413a84a4e0eef17a9660d993911fafacadb66"
+        );
+        assert_ne!(
+            sign("s3cret", 1_700_000_001, r#"{"text":"hi"}"#),
+            sign("s3cret", 1_700_000_000, r#"{"text":"hi"}"#)
+        );
+    }
+}
@@ -5,17 +5,15 @@ use serde::Deserialize;
 use tokio::sync::RwLock;
 use tracing::{error, info, warn};
 
-use crate::agent_engine::archive_conversation;
 use crate::agent_engine::process_with_agent_with_events;
 use crate::agent_engine::AgentEvent;
 use crate::agent_engine::AgentRequestContext;
 use crate::channel::ConversationKind;
 use crate::channel_adapter::ChannelAdapter;
+use crate::commands::handle_command;
 use crate::db::call_blocking;
 use crate::db::StoredMessage;
-use crate::llm_types::Message as LlmMessage;
 use crate::runtime::AppState;
-use crate::usage::build_usage_report;
 
 // ---------------------------------------------------------------------------
 // Config
@@ -621,65 +619,17 @@ async fn handle_weixin_message(
         content: text.clone(),
         is_from_bot: false,
         timestamp: chrono::Utc::now().to_rfc3339(),
+        thread_id: None,
     };
     let _ = call_blocking(app_state.db.clone(), move |db| db.store_message(&stored)).await;
 
-    // Handle slash commands
-    let trimmed = text.trim();
-    if trimmed == "/reset" {
-        let _ = call_blocking(app_state.db.clone(), move |db| {
-            db.clear_chat_context(chat_id)
-        })
-        .await;
-        let _ = adapter
-            .send_text(&from_user_id, "Context cleared (session + chat history).")
-            .await;
-        return;
-    }
-    if trimmed == "/skills" {
-        let formatted = app_state.skills.list_skills_formatted();
-        let _ = adapter.send_text(&from_user_id, &formatted).await;
+    if let Some(reply) = handle_command(&app_state, chat_id, "weixin", &text).await {
+        let _ = adapter.send_text(&from_user_id, &reply).await;
         return;
     }
-    if trimmed == "/archive" {
-        if let Ok(Some((json, _))) =
-            call_blocking(app_state.db.clone(), move |db| db.load_session(chat_id)).await
-        {
-            let messages: Vec<LlmMessage> = serde_json::from_str(&json).unwrap_or_default();
-            if messages.is_empty() {
-                let _ = adapter
-                    .send_text(&from_user_id, "No session to archive.")
-                    .await;
-            } else {
-                archive_conversation(&app_state.config.data_dir, "weixin", chat_id, &messages);
-                let _ = adapter
-                    .send_text(
-                        &from_user_id,
-                        &format!("Archived {} messages.", messages.len()),
-                    )
-                    .await;
-            }
-        } else {
-            let _ = adapter
-                .send_text(&from_user_id, "No session to archive.")
-                .await;
-        }
-        return;
-    }
-    if trimmed == "/usage" {
-        match build_usage_report(app_state.db.clone(), &app_state.config, chat_id).await {
-            Ok(report) => {
-                let _ = adapter.send_text(&from_user_id, &report).await;
-            }
-            Err(e) => {
-                let _ = adapter
-                    .send_text(
-                        &from_user_id,
-                        &format!("Failed to query usage statistics: {e}"),
-                    )
-                    .await;
-            }
-        }
+
+    // Weixin chats are all direct and have no mentions, so observers stay silent
+    if !crate::channel::should_respond(app_state.db.clone(), chat_id, true, false).await {
         return;
     }
 
@@ -719,7 +669,9 @@ async fn handle_weixin_message(
         AgentRequestContext {
             caller_channel: "weixin",
             chat_id,
+            thread_id: None,
             chat_type: "private",
+            denied_tools: &[],
         },
         None,
         None,
@@ -743,7 +695,15 @@ async fn handle_weixin_message(
             typing_handle.abort();
 
             if !response.is_empty() && !used_send_message_tool {
-                if let Err(e) = adapter.send_text(&from_user_id, &response).await {
+                let sent = crate::channel::send_tracked(
+                    app_state.db.clone(),
+                    chat_id,
+                    "weixin",
+                    &response,
+                    || adapter.send_text(&from_user_id, &response),
+                )
+                .await;
+                if let Err(e) = sent {
                     error!("Weixin: failed to send response to {from_user_id}: {e}");
                 }
             }
@@ -757,6 +717,7 @@ async fn handle_weixin_message(
                     content: response,
                     is_from_bot: true,
                     timestamp: chrono::Utc::now().to_rfc3339(),
+                    thread_id: None,
                 };
                 let _ =
                     call_blocking(app_state.db.clone(), move |db| db.store_message(&bot_msg)).await;
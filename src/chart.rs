@@ -0,0 +1,343 @@
+//! Chart rendering for the `render_chart` tool.
+//!
+//! A [`ChartSpec`] (category labels plus one or more numeric series) is drawn
+//! with plotters into an RGB buffer and encoded as PNG. Text needs a TrueType
+//! font: the configured `chart_font_path` or a common system font. Without
+//! one, charts are still drawn but carry no title, axis labels or legend.
+
+use std::io::Cursor;
+use std::sync::OnceLock;
+
+use plotters::prelude::*;
+use plotters::style::{register_font, FontStyle};
+
+/// Largest chart the tool will render, per side.
+pub const MAX_CHART_DIMENSION: u32 = 2000;
+/// Most data points per series.
+pub const MAX_POINTS: usize = 1000;
+/// Most series per chart.
+pub const MAX_SERIES: usize = 12;
+
+/// Fonts tried when no `chart_font_path` is configured.
+const SYSTEM_FONT_PATHS: &[&str] = &[
+    "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf",
+    "/usr/share/fonts/dejavu/DejaVuSans.ttf",
+    "/usr/share/fonts/TTF/DejaVuSans.ttf",
+    "/usr/share/fonts/truetype/liberation/LiberationSans-Regular.ttf",
+    "/usr/share/fonts/liberation-sans/LiberationSans-Regular.ttf",
+    "/System/Library/Fonts/Supplemental/Arial.ttf",
+    "/Library/Fonts/Arial.ttf",
+    "C:\\Windows\\Fonts\\arial.ttf",
+];
+
+const FONT_FAMILY: &str = "sans-serif";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChartKind {
+    Line,
+    Bar,
+}
+
+impl ChartKind {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "" | "line" => Ok(ChartKind::Line),
+            "bar" => Ok(ChartKind::Bar),
+            other => Err(format!(
+                "Unsupported chart_type '{other}' (expected line or bar)"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Series {
+    pub name: String,
+    pub values: Vec<f64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ChartSpec {
+    pub kind: ChartKind,
+    pub title: Option<String>,
+    pub x_label: Option<String>,
+    pub y_label: Option<String>,
+    /// One label per data point, along the x axis
+    pub labels: Vec<String>,
+    pub series: Vec<Series>,
+}
+
+impl ChartSpec {
+    /// Check that the data is non-empty, bounded and rectangular.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.series.is_empty() {
+            return Err("Chart has no series".into());
+        }
+        if self.series.len() > MAX_SERIES {
+            return Err(format!("Too many series (max {MAX_SERIES})"));
+        }
+        if self.labels.is_empty() {
+            return Err("Chart has no data points".into());
+        }
+        if self.labels.len() > MAX_POINTS {
+            return Err(format!("Too many data points (max {MAX_POINTS})"));
+        }
+        for s in &self.series {
+            if s.values.len() != self.labels.len() {
+                return Err(format!(
+                    "Series '{}' has {} values but there are {} labels",
+                    s.name,
+                    s.values.len(),
+                    self.labels.len()
+                ));
+            }
+            if s.values.iter().any(|v| !v.is_finite()) {
+                return Err(format!("Series '{}' has a non-finite value", s.name));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parse CSV text into labels and series: the header row names the columns,
+/// the first column holds the labels and every other column is a series.
+/// Empty cells count as 0.
+pub fn parse_csv(text: &str) -> Result<(Vec<String>, Vec<Series>), String> {
+    let mut lines = text.lines().map(str::trim).filter(|l| !l.is_empty());
+    let header: Vec<&str> = lines
+        .next()
+        .ok_or("CSV is empty")?
+        .split(',')
+        .map(str::trim)
+        .collect();
+    if header.len() < 2 {
+        return Err("CSV needs a label column and at least one value column".into());
+    }
+    let mut labels = Vec::new();
+    let mut series: Vec<Series> = header[1..]
+        .iter()
+        .map(|name| Series {
+            name: name.to_string(),
+            values: Vec::new(),
+        })
+        .collect();
+    for (row, line) in lines.enumerate() {
+        let cells: Vec<&str> = line.split(',').map(str::trim).collect();
+        if cells.len() != header.len() {
+            return Err(format!(
+                "CSV row {} has {} cells, expected {}",
+                row + 2,
+                cells.len(),
+                header.len()
+            ));
+        }
+        labels.push(cells[0].to_string());
+        for (s, cell) in series.iter_mut().zip(&cells[1..]) {
+            let value = if cell.is_empty() {
+                0.0
+            } else {
+                cell.parse::<f64>()
+                    .map_err(|_| format!("CSV row {}: '{cell}' is not a number", row + 2))?
+            };
+            s.values.push(value);
+        }
+    }
+    Ok((labels, series))
+}
+
+/// Register a font for chart text once per process. Returns whether one is
+/// available.
+fn ensure_font(custom_path: Option<&str>) -> bool {
+    static FONT_READY: OnceLock<bool> = OnceLock::new();
+    *FONT_READY.get_or_init(|| {
+        let candidates = custom_path
+            .into_iter()
+            .chain(SYSTEM_FONT_PATHS.iter().copied());
+        for path in candidates {
+            let Ok(bytes) = std::fs::read(path) else {
+                continue;
+            };
+            // plotters keeps registered fonts for the life of the process
+            let bytes: &'static [u8] = Box::leak(bytes.into_boxed_slice());
+            if register_font(FONT_FAMILY, FontStyle::Normal, bytes).is_ok() {
+                tracing::debug!("Chart font loaded from {path}");
+                return true;
+            }
+        }
+        tracing::warn!("No font found for chart text; set chart_font_path to enable labels");
+        false
+    })
+}
+
+/// Render `spec` as a PNG of `width`x`height` pixels.
+pub fn render_png(
+    spec: &ChartSpec,
+    width: u32,
+    height: u32,
+    font_path: Option<&str>,
+) -> Result<Vec<u8>, String> {
+    spec.validate()?;
+    let has_font = ensure_font(font_path);
+    let mut buf = vec![0u8; width as usize * height as usize * 3];
+    draw(spec, &mut buf, width, height, has_font)
+        .map_err(|e| format!("Chart drawing failed: {e}"))?;
+
+    let img = image::RgbImage::from_raw(width, height, buf).ok_or("Chart buffer size mismatch")?;
+    let mut png = Vec::new();
+    img.write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)
+        .map_err(|e| format!("PNG encoding failed: {e}"))?;
+    Ok(png)
+}
+
+fn draw(
+    spec: &ChartSpec,
+    buf: &mut [u8],
+    width: u32,
+    height: u32,
+    has_font: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let root = BitMapBackend::with_buffer(buf, (width, height)).into_drawing_area();
+    root.fill(&WHITE)?;
+
+    let n = spec.labels.len();
+    let values = spec.series.iter().flat_map(|s| s.values.iter().copied());
+    let (lo, hi) = values.fold((0.0f64, 0.0f64), |(lo, hi), v| (lo.min(v), hi.max(v)));
+    let pad = ((hi - lo) * 0.08).max(f64::EPSILON);
+    let y_range = (if lo < 0.0 { lo - pad } else { 0.0 })..(hi + pad);
+    let x_range = -0.5f64..(n as f64 - 0.5);
+
+    let mut builder = ChartBuilder::on(&root);
+    builder.margin(16);
+    if has_font {
+        builder.x_label_area_size(40).y_label_area_size(60);
+        if let Some(title) = spec.title.as_deref().filter(|t| !t.trim().is_empty()) {
+            builder.caption(title, (FONT_FAMILY, 24));
+        }
+    }
+    let mut chart = builder.build_cartesian_2d(x_range, y_range)?;
+
+    let label_at = |x: &f64| {
+        let i = x.round();
+        if (x - i).abs() < 1e-6 && i >= 0.0 && (i as usize) < n {
+            spec.labels[i as usize].clone()
+        } else {
+            String::new()
+        }
+    };
+    let mut mesh = chart.configure_mesh();
+    mesh.disable_x_mesh();
+    if has_font {
+        mesh.x_labels(n.min(12))
+            .x_label_formatter(&label_at)
+            .label_style((FONT_FAMILY, 14));
+        if let Some(x) = spec.x_label.as_deref() {
+            mesh.x_desc(x);
+        }
+        if let Some(y) = spec.y_label.as_deref() {
+            mesh.y_desc(y);
+        }
+    } else {
+        mesh.x_labels(0).y_labels(0);
+    }
+    mesh.draw()?;
+
+    let bar_width = 0.8 / spec.series.len() as f64;
+    for (k, series) in spec.series.iter().enumerate() {
+        let color = Palette99::pick(k).to_rgba();
+        let points = series
+            .values
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (i as f64, *v));
+        let drawn = match spec.kind {
+            ChartKind::Line => chart.draw_series(LineSeries::new(points, color.stroke_width(2)))?,
+            ChartKind::Bar => chart.draw_series(points.map(|(x, v)| {
+                let x0 = x - 0.4 + k as f64 * bar_width;
+                Rectangle::new([(x0, 0.0), (x0 + bar_width, v)], color.filled())
+            }))?,
+        };
+        if has_font && !series.name.is_empty() {
+            drawn.label(series.name.clone()).legend(move |(x, y)| {
+                Rectangle::new([(x, y - 5), (x + 16, y + 5)], color.filled())
+            });
+        }
+    }
+    if has_font && spec.series.iter().any(|s| !s.name.is_empty()) {
+        chart
+            .configure_series_labels()
+            .background_style(WHITE.mix(0.8))
+            .border_style(BLACK)
+            .label_font((FONT_FAMILY, 14))
+            .draw()?;
+    }
+
+    root.present()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(kind: ChartKind) -> ChartSpec {
+        ChartSpec {
+            kind,
+            title: Some("Requests".into()),
+            x_label: Some("Day".into()),
+            y_label: None,
+            labels: vec!["Mon".into(), "Tue".into(), "Wed".into()],
+            series: vec![
+                Series {
+                    name: "api".into(),
+                    values: vec![3.0, 5.5, -1.0],
+                },
+                Series {
+                    name: "web".into(),
+                    values: vec![1.0, 2.0, 4.0],
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_parse_csv() {
+        let (labels, series) = parse_csv("day, api, web\nMon,3,1\n\nTue,5.5,\n").unwrap();
+        assert_eq!(labels, vec!["Mon", "Tue"]);
+        assert_eq!(series[0].name, "api");
+        assert_eq!(series[0].values, vec![3.0, 5.5]);
+        assert_eq!(series[1].values, vec![1.0, 0.0]);
+
+        assert!(parse_csv("day\nMon").is_err());
+        assert!(parse_csv("day,api\nMon,3,4").unwrap_err().contains("row 2"));
+        assert!(parse_csv("day,api\nMon,lots")
+            .unwrap_err()
+            .contains("not a number"));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(spec(ChartKind::Line).validate().is_ok());
+        let mut bad = spec(ChartKind::Line);
+        bad.series[1].values.pop();
+        assert!(bad.validate().unwrap_err().contains("'web' has 2 values"));
+        bad.series.clear();
+        assert!(bad.validate().is_err());
+        assert_eq!(ChartKind::parse("BAR"), Ok(ChartKind::Bar));
+        assert!(ChartKind::parse("pie").is_err());
+    }
+
+    #[test]
+    fn test_render_png() {
+        for kind in [ChartKind::Line, ChartKind::Bar] {
+            let png = render_png(&spec(kind), 320, 200, None).unwrap();
+            assert_eq!(&png[1..4], b"PNG");
+            let img = image::load_from_memory(&png).unwrap();
+            assert_eq!((img.width(), img.height()), (320, 200));
+
+            // Without a font the chart is drawn bare instead of failing
+            let mut buf = vec![0u8; 320 * 200 * 3];
+            draw(&spec(kind), &mut buf, 320, 200, false).unwrap();
+            assert!(buf.iter().any(|&b| b != 255));
+        }
+    }
+}
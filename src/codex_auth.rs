@@ -228,13 +228,13 @@ fn parse_toml_string_assignment(line: &str, key: &str) -> Option<String> {
 
 fn expand_tilde(input: &str) -> String {
     if let Some(rest) = input.strip_prefix("~/") {
-        if let Ok(home) = std::env::var("HOME") {
-            return format!("{home}/{rest}");
+        if let Some(home) = crate::platform::home_dir() {
+            return home.join(rest).to_string_lossy().to_string();
         }
     }
     if input == "~" {
-        if let Ok(home) = std::env::var("HOME") {
-            return home;
+        if let Some(home) = crate::platform::home_dir() {
+            return home.to_string_lossy().to_string();
         }
     }
     input.to_string()
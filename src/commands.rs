@@ -0,0 +1,1097 @@
+//! Chat slash commands (`/help`, `/status`, `/usage`, ...) shared by every
+//! channel adapter.
+//!
+//! Adapters store the incoming message and then call [`handle_command`]; if it
+//! returns a reply the message was a command and the agent is not run. Text
+//! that does not name a registered command falls through unchanged, so the
+//! agent still sees things like `/tmp/foo` or unknown slash words.
+
+use chrono::Utc;
+
+use crate::agent_engine::archive_conversation;
+use crate::analytics;
+use crate::channel_adapter::TextFormat;
+use crate::config::Config;
+use crate::db::{call_blocking, NotificationSettings};
+use crate::llm_types::Message;
+use crate::notifications;
+use crate::prompt_template::validate_standing_instructions;
+use crate::runtime::AppState;
+use crate::text::floor_char_boundary;
+use crate::tools::send_message::send_attachment_to_chat;
+use crate::usage::build_usage_report;
+use CommandPermission::{Anyone, ControlChat};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandPermission {
+    /// Any chat the bot is talking in
+    Anyone,
+    /// Only chats listed in `control_chat_ids`
+    ControlChat,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct CommandSpec {
+    pub name: &'static str,
+    /// Argument synopsis shown in help and usage errors
+    pub args: &'static str,
+    pub summary: &'static str,
+    pub permission: CommandPermission,
+    pub min_args: usize,
+    /// `None` takes free text (see [`ParsedCommand::rest`])
+    pub max_args: Option<usize>,
+}
+
+impl CommandSpec {
+    fn usage(&self) -> String {
+        if self.args.is_empty() {
+            format!("/{}", self.name)
+        } else {
+            format!("/{} {}", self.name, self.args)
+        }
+    }
+}
+
+const fn command(
+    name: &'static str,
+    args: &'static str,
+    summary: &'static str,
+    permission: CommandPermission,
+    min_args: usize,
+    max_args: Option<usize>,
+) -> CommandSpec {
+    CommandSpec {
+        name,
+        args,
+        summary,
+        permission,
+        min_args,
+        max_args,
+    }
+}
+
+/// All registered commands, in the order `/help` lists them.
+pub const COMMANDS: &[CommandSpec] = &[
+    command("help", "", "Show available commands", Anyone, 0, Some(0)),
+    command(
+        "status",
+        "",
+        "Show model, channels and this chat's session state",
+        Anyone,
+        0,
+        Some(0),
+    ),
+    command(
+        "reset",
+        "",
+        "Clear this chat's session and history",
+        Anyone,
+        0,
+        Some(0),
+    ),
+    command(
+        "archive",
+        "",
+        "Archive the current session to markdown",
+        Anyone,
+        0,
+        Some(0),
+    ),
+    command(
+        "usage",
+        "",
+        "Show token usage statistics",
+        Anyone,
+        0,
+        Some(0),
+    ),
+    command(
+        "analytics",
+        "[days] [chart]",
+        "Show this chat's activity, reply times and token/tool usage",
+        ControlChat,
+        0,
+        Some(2),
+    ),
+    command(
+        "tasks",
+        "[all]",
+        "List scheduled tasks (all chats: control chats only)",
+        Anyone,
+        0,
+        Some(1),
+    ),
+    command(
+        "instructions",
+        "[text|clear]",
+        "Show, set or clear standing instructions for every reply",
+        Anyone,
+        0,
+        None,
+    ),
+    command(
+        "model",
+        "[[provider] model|reset]",
+        "Show this chat's model; control chats can override or reset it",
+        Anyone,
+        0,
+        Some(2),
+    ),
+    command(
+        "calendar",
+        "[reset|off]",
+        "Show, rotate or revoke this chat's iCal feed of scheduled tasks",
+        Anyone,
+        0,
+        Some(1),
+    ),
+    command(
+        "observe",
+        "[on|off]",
+        "Observer mode: store messages but only reply when mentioned",
+        Anyone,
+        0,
+        Some(1),
+    ),
+    command(
+        "notify",
+        crate::notifications::NOTIFY_ARGS,
+        "Show or change this chat's notification settings",
+        Anyone,
+        0,
+        Some(2),
+    ),
+    command("skills", "", "List available skills", Anyone, 0, Some(0)),
+    command(
+        "tools",
+        "",
+        "List tools the agent can use",
+        Anyone,
+        0,
+        Some(0),
+    ),
+    command(
+        "acp",
+        "<stop|end|sessions|agents|mode [name]>",
+        "Control this chat's ACP coding agent session",
+        Anyone,
+        1,
+        Some(2),
+    ),
+    command(
+        "sessions",
+        "",
+        "List recently active chats",
+        ControlChat,
+        0,
+        Some(0),
+    ),
+    command(
+        "broadcast",
+        "<message>",
+        "Send a message to every chat",
+        ControlChat,
+        1,
+        None,
+    ),
+    command(
+        "checkconfig",
+        "",
+        "Validate the config file and list changes that need a restart",
+        ControlChat,
+        0,
+        Some(0),
+    ),
+];
+
+pub fn find_command(name: &str) -> Option<&'static CommandSpec> {
+    COMMANDS.iter().find(|c| c.name == name)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParsedCommand<'a> {
+    pub spec: &'static CommandSpec,
+    pub args: Vec<&'a str>,
+    /// Everything after the command name, for free-text arguments
+    pub rest: &'a str,
+}
+
+/// Parse `/name[@bot] args...`. Leading `@mention` / `<@U123>` tokens are
+/// skipped; returns `None` unless `name` is a registered command.
+pub fn parse_command(text: &str) -> Option<ParsedCommand<'_>> {
+    let mut text = text.trim();
+    while text.starts_with('@') || text.starts_with("<@") {
+        text = text
+            .split_once(char::is_whitespace)
+            .map(|(_, rest)| rest.trim_start())
+            .unwrap_or("");
+    }
+    let body = text.strip_prefix('/')?;
+    let (head, rest) = body
+        .split_once(char::is_whitespace)
+        .map(|(head, rest)| (head, rest.trim()))
+        .unwrap_or((body, ""));
+    // Telegram group commands carry the bot name: /status@my_bot
+    let name = head.split('@').next().unwrap_or(head).to_ascii_lowercase();
+    let spec = find_command(&name)?;
+    Some(ParsedCommand {
+        spec,
+        args: rest.split_whitespace().collect(),
+        rest,
+    })
+}
+
+struct CommandContext<'a> {
+    state: &'a AppState,
+    chat_id: i64,
+    channel: &'a str,
+    format: TextFormat,
+    is_control: bool,
+}
+
+impl CommandContext<'_> {
+    fn allowed(&self, spec: &CommandSpec) -> bool {
+        spec.permission == Anyone || self.is_control
+    }
+}
+
+/// Run `text` as a command in `chat_id` if it is one, returning the reply
+/// rendered for `channel`. `None` means the message is not a command, or
+/// the chat is in observer mode, where only `/observe` is answered.
+pub async fn handle_command(
+    state: &AppState,
+    chat_id: i64,
+    channel: &str,
+    text: &str,
+) -> Option<String> {
+    let parsed = parse_command(text)?;
+    if parsed.spec.name != "observe"
+        && crate::channel::is_observer_chat(state.db.clone(), chat_id).await
+    {
+        return None;
+    }
+    let ctx = CommandContext {
+        state,
+        chat_id,
+        channel,
+        format: state
+            .channel_registry
+            .get(channel)
+            .map(|a| a.text_format())
+            .unwrap_or_default(),
+        is_control: state.config.control_chat_ids.contains(&chat_id),
+    };
+    Some(run_command(&ctx, &parsed).await)
+}
+
+async fn run_command(ctx: &CommandContext<'_>, cmd: &ParsedCommand<'_>) -> String {
+    let spec = cmd.spec;
+    if !ctx.allowed(spec) {
+        return format!(
+            "{} is only available in control chats.",
+            ctx.format.code(&format!("/{}", spec.name))
+        );
+    }
+    let arity_ok =
+        cmd.args.len() >= spec.min_args && spec.max_args.is_none_or(|max| cmd.args.len() <= max);
+    if !arity_ok {
+        return format!("Usage: {}", ctx.format.code(&spec.usage()));
+    }
+
+    match spec.name {
+        "help" => help(ctx),
+        "status" => status(ctx).await,
+        "reset" => reset(ctx).await,
+        "archive" => archive(ctx).await,
+        "usage" => build_usage_report(ctx.state.db.clone(), &ctx.state.config, ctx.chat_id)
+            .await
+            .unwrap_or_else(|e| format!("Failed to query usage statistics: {e}")),
+        "analytics" => analytics(ctx, &cmd.args).await,
+        "tasks" => tasks(ctx, cmd.args.first().copied()).await,
+        "instructions" => instructions(ctx, cmd.rest).await,
+        "model" => model(ctx, &cmd.args).await,
+        "calendar" => calendar(ctx, cmd.args.first().copied()).await,
+        "observe" => observe(ctx, cmd.args.first().copied()).await,
+        "notify" => notify(ctx, &cmd.args).await,
+        "skills" => ctx.state.skills.list_skills_formatted(),
+        "tools" => tools(ctx),
+        "acp" => acp(ctx, cmd.args[0], cmd.args.get(1).copied()).await,
+        "sessions" => sessions(ctx).await,
+        "broadcast" => broadcast(ctx, cmd.rest).await,
+        "checkconfig" => check_config(ctx),
+        other => format!("Command /{other} is not implemented."),
+    }
+}
+
+fn help(ctx: &CommandContext<'_>) -> String {
+    let mut lines = vec![ctx.format.bold("Commands")];
+    for spec in COMMANDS.iter().filter(|s| ctx.allowed(s)) {
+        lines.push(format!(
+            "{} — {}",
+            ctx.format.code(&spec.usage()),
+            spec.summary
+        ));
+    }
+    if !ctx.state.acp_manager.config.agents.is_empty() {
+        lines.push(format!("ACP agent sessions: {}", ctx.format.code("#help")));
+    }
+    lines.join("\n")
+}
+
+async fn status(ctx: &CommandContext<'_>) -> String {
+    let state = ctx.state;
+    let chat_id = ctx.chat_id;
+    let session_messages =
+        call_blocking(state.db.clone(), move |db| db.load_session(chat_id, None))
+            .await
+            .ok()
+            .flatten()
+            .map(|(json, _)| {
+                serde_json::from_str::<Vec<Message>>(&json)
+                    .map(|m| m.len())
+                    .unwrap_or(0)
+            })
+            .unwrap_or(0);
+    let task_count = call_blocking(state.db.clone(), move |db| db.get_tasks_for_chat(chat_id))
+        .await
+        .map(|t| t.len())
+        .unwrap_or(0);
+    let acp = match state.acp_manager.chat_session(chat_id).await {
+        Some(session_id) => session_id,
+        None => "none".to_string(),
+    };
+    let queue = state.inbound_queue.stats();
+    let model_override = call_blocking(state.db.clone(), move |db| {
+        db.get_chat_model_override(chat_id)
+    })
+    .await
+    .ok()
+    .flatten();
+    let (model, provider) = match &model_override {
+        Some(o) => (
+            format!("{} [override]", o.model),
+            o.provider.as_deref().unwrap_or(&state.config.llm_provider),
+        ),
+        None => (
+            state.config.model.clone(),
+            state.config.llm_provider.as_str(),
+        ),
+    };
+
+    [
+        ctx.format.bold("Status"),
+        format!("Model: {model} ({provider})"),
+        format!(
+            "Channels: {}",
+            state.channel_registry.channel_names().join(", ")
+        ),
+        format!("Chat: {} ({})", chat_id, ctx.channel),
+        format!("Session: {session_messages} message(s) in context"),
+        format!("Scheduled tasks: {task_count}"),
+        format!("ACP session: {acp}"),
+        format!(
+            "Agent runs: {} running, {} waiting (limit {})",
+            queue.running,
+            queue.waiting,
+            if queue.limit == 0 {
+                "none".to_string()
+            } else {
+                queue.limit.to_string()
+            }
+        ),
+        format!(
+            "Control chat: {}",
+            if ctx.is_control { "yes" } else { "no" }
+        ),
+    ]
+    .join("\n")
+}
+
+async fn reset(ctx: &CommandContext<'_>) -> String {
+    let chat_id = ctx.chat_id;
+    match call_blocking(ctx.state.db.clone(), move |db| {
+        db.clear_chat_context(chat_id)
+    })
+    .await
+    {
+        Ok(_) => "Context cleared (session + chat history).".to_string(),
+        Err(e) => format!("Failed to clear context: {e}"),
+    }
+}
+
+async fn archive(ctx: &CommandContext<'_>) -> String {
+    let chat_id = ctx.chat_id;
+    let messages: Vec<Message> = match call_blocking(ctx.state.db.clone(), move |db| {
+        db.load_session(chat_id, None)
+    })
+    .await
+    {
+        Ok(Some((json, _))) => serde_json::from_str(&json).unwrap_or_default(),
+        _ => Vec::new(),
+    };
+    if messages.is_empty() {
+        return "No session to archive.".to_string();
+    }
+    archive_conversation(&ctx.state.config.data_dir, ctx.channel, chat_id, &messages);
+    format!("Archived {} messages.", messages.len())
+}
+
+fn truncate(text: &str, max: usize) -> String {
+    let text = text.replace('\n', " ");
+    if text.len() <= max {
+        text
+    } else {
+        format!("{}…", &text[..floor_char_boundary(&text, max)])
+    }
+}
+
+async fn analytics(ctx: &CommandContext<'_>, args: &[&str]) -> String {
+    let mut days = analytics::DEFAULT_DAYS;
+    let mut chart = false;
+    for arg in args {
+        if arg.eq_ignore_ascii_case("chart") {
+            chart = true;
+        } else {
+            match analytics::parse_days(arg) {
+                Ok(d) => days = d,
+                Err(e) => {
+                    return format!(
+                        "{e}. Usage: {}",
+                        ctx.format.code("/analytics [days] [chart]")
+                    )
+                }
+            }
+        }
+    }
+    let report = match analytics::build_chat_analytics(
+        ctx.state.db.clone(),
+        &ctx.state.config,
+        ctx.chat_id,
+        days,
+    )
+    .await
+    {
+        Ok(r) => r,
+        Err(e) => return format!("Failed to query analytics: {e}"),
+    };
+    let text = analytics::format_report(&report);
+    if !chart {
+        return text;
+    }
+    match send_analytics_chart(ctx, &report).await {
+        Ok(()) => text,
+        Err(e) => format!("{text}\n\n(Chart not sent: {e})"),
+    }
+}
+
+async fn send_analytics_chart(
+    ctx: &CommandContext<'_>,
+    report: &analytics::ChatAnalytics,
+) -> Result<(), String> {
+    let spec = analytics::messages_chart(report);
+    let font_path = ctx.state.config.chart_font_path.clone();
+    let png = tokio::task::spawn_blocking(move || {
+        crate::chart::render_png(&spec, 800, 480, font_path.as_deref())
+    })
+    .await
+    .map_err(|e| format!("chart rendering task failed: {e}"))??;
+    let path = std::env::temp_dir().join(format!(
+        "rayclaw-analytics-{}-{}.png",
+        ctx.chat_id,
+        uuid::Uuid::new_v4()
+    ));
+    std::fs::write(&path, &png).map_err(|e| format!("failed to write chart: {e}"))?;
+    let result = send_attachment_to_chat(
+        &ctx.state.channel_registry,
+        ctx.state.db.clone(),
+        &ctx.state.config.bot_username,
+        ctx.chat_id,
+        None,
+        &path,
+        Some("Messages per day"),
+    )
+    .await;
+    let _ = std::fs::remove_file(&path);
+    result
+}
+
+async fn tasks(ctx: &CommandContext<'_>, scope: Option<&str>) -> String {
+    let all = match scope {
+        None => false,
+        Some("all") if ctx.is_control => true,
+        Some("all") => return "Listing all tasks is only available in control chats.".into(),
+        Some(_) => return format!("Usage: {}", ctx.format.code("/tasks [all]")),
+    };
+    let chat_id = ctx.chat_id;
+    let result = call_blocking(ctx.state.db.clone(), move |db| {
+        if all {
+            db.get_all_tasks(None, None, 50, 0).map(|(tasks, _)| tasks)
+        } else {
+            db.get_tasks_for_chat(chat_id)
+        }
+    })
+    .await;
+    let tasks = match result {
+        Ok(tasks) => tasks,
+        Err(e) => return format!("Failed to list tasks: {e}"),
+    };
+    if tasks.is_empty() {
+        return "No scheduled tasks.".to_string();
+    }
+
+    let mut lines = vec![ctx.format.bold("Scheduled tasks")];
+    for task in &tasks {
+        let chat = if all {
+            format!(" chat={}", task.chat_id)
+        } else {
+            String::new()
+        };
+        lines.push(format!(
+            "#{} [{}]{chat} {} {} — next {}: {}",
+            task.id,
+            task.status,
+            task.schedule_type,
+            ctx.format.code(&task.schedule_value),
+            task.next_run,
+            truncate(&task.prompt, 80)
+        ));
+    }
+    lines.join("\n")
+}
+
+async fn instructions(ctx: &CommandContext<'_>, text: &str) -> String {
+    let chat_id = ctx.chat_id;
+    let text = text.trim();
+    if text.is_empty() {
+        return match call_blocking(ctx.state.db.clone(), move |db| {
+            db.get_standing_instructions(chat_id)
+        })
+        .await
+        {
+            Ok(Some(current)) => format!("{}\n{current}", ctx.format.bold("Standing instructions")),
+            Ok(None) => format!(
+                "No standing instructions. Set some with {}.",
+                ctx.format.code("/instructions <text>")
+            ),
+            Err(e) => format!("Failed to load standing instructions: {e}"),
+        };
+    }
+    if text.eq_ignore_ascii_case("clear") {
+        return match call_blocking(ctx.state.db.clone(), move |db| {
+            db.clear_standing_instructions(chat_id)
+        })
+        .await
+        {
+            Ok(true) => "Standing instructions cleared.".to_string(),
+            Ok(false) => "No standing instructions were set.".to_string(),
+            Err(e) => format!("Failed to clear standing instructions: {e}"),
+        };
+    }
+    let text = match validate_standing_instructions(text) {
+        Ok(t) => t,
+        Err(e) => return e,
+    };
+    match call_blocking(ctx.state.db.clone(), move |db| {
+        db.set_standing_instructions(chat_id, &text)
+    })
+    .await
+    {
+        Ok(()) => {
+            "Standing instructions saved; they apply to every reply in this chat.".to_string()
+        }
+        Err(e) => format!("Failed to save standing instructions: {e}"),
+    }
+}
+
+async fn model(ctx: &CommandContext<'_>, args: &[&str]) -> String {
+    use crate::tools::set_model::{describe_model, parse_model_override};
+
+    let config = &ctx.state.config;
+    let chat_id = ctx.chat_id;
+    if args.is_empty() {
+        return match call_blocking(ctx.state.db.clone(), move |db| {
+            db.get_chat_model_override(chat_id)
+        })
+        .await
+        {
+            Ok(Some(o)) => format!(
+                "{} {} (default: {})",
+                ctx.format.bold("Model:"),
+                ctx.format.code(&describe_model(config, Some(&o))),
+                ctx.format.code(&describe_model(config, None))
+            ),
+            Ok(None) => format!(
+                "{} {} (default)",
+                ctx.format.bold("Model:"),
+                ctx.format.code(&describe_model(config, None))
+            ),
+            Err(e) => format!("Failed to load model override: {e}"),
+        };
+    }
+    if !ctx.is_control {
+        return "Changing the model is only available in control chats.".into();
+    }
+    if args.len() == 1 && args[0].eq_ignore_ascii_case("reset") {
+        return match call_blocking(ctx.state.db.clone(), move |db| {
+            db.clear_chat_model_override(chat_id)
+        })
+        .await
+        {
+            Ok(true) => format!(
+                "Model override cleared; back on {}.",
+                ctx.format.code(&describe_model(config, None))
+            ),
+            Ok(false) => "No model override was set.".to_string(),
+            Err(e) => format!("Failed to clear model override: {e}"),
+        };
+    }
+    let (provider, model) = match args {
+        [model] => (None, *model),
+        [provider, model] => (Some(*provider), *model),
+        _ => {
+            return format!(
+                "Usage: {}",
+                ctx.format.code("/model [[provider] model|reset]")
+            )
+        }
+    };
+    let model_override = match parse_model_override(config, provider, model) {
+        Ok(o) => o,
+        Err(e) => return e,
+    };
+    let described = describe_model(config, Some(&model_override));
+    match call_blocking(ctx.state.db.clone(), move |db| {
+        db.set_chat_model_override(chat_id, &model_override)
+    })
+    .await
+    {
+        Ok(()) => format!(
+            "This chat now uses {} from the next message.",
+            ctx.format.code(&described)
+        ),
+        Err(e) => format!("Failed to save model override: {e}"),
+    }
+}
+
+async fn calendar(ctx: &CommandContext<'_>, arg: Option<&str>) -> String {
+    let config = &ctx.state.config;
+    if !config.web_enabled {
+        return "The calendar feed is served by the web server; enable web_enabled first."
+            .to_string();
+    }
+    let chat_id = ctx.chat_id;
+    let token = match arg.map(|a| a.to_ascii_lowercase()).as_deref() {
+        None => {
+            call_blocking(ctx.state.db.clone(), move |db| {
+                match db.get_calendar_feed_token(chat_id)? {
+                    Some(token) => Ok(token),
+                    None => db.create_calendar_feed_token(chat_id),
+                }
+            })
+            .await
+        }
+        Some("reset") => {
+            call_blocking(ctx.state.db.clone(), move |db| {
+                db.create_calendar_feed_token(chat_id)
+            })
+            .await
+        }
+        Some("off") => {
+            return match call_blocking(ctx.state.db.clone(), move |db| {
+                db.delete_calendar_feed(chat_id)
+            })
+            .await
+            {
+                Ok(true) => "Calendar feed revoked.".to_string(),
+                Ok(false) => "This chat has no calendar feed.".to_string(),
+                Err(e) => format!("Failed to revoke calendar feed: {e}"),
+            };
+        }
+        Some(other) => {
+            return format!(
+                "Unknown option '{other}'. Usage: {}",
+                ctx.format.code("/calendar [reset|off]")
+            )
+        }
+    };
+    let token = match token {
+        Ok(t) => t,
+        Err(e) => return format!("Failed to load calendar feed: {e}"),
+    };
+    let base = config
+        .web_public_url
+        .clone()
+        .unwrap_or_else(|| format!("http://{}:{}", config.web_host, config.web_port));
+    format!(
+        "{}
+{}
+Subscribe to this URL in your calendar app. Anyone with the link can see this chat's scheduled tasks; {} issues a new one, {} revokes it.",
+        ctx.format.bold("Calendar feed"),
+        ctx.format.code(&format!("{base}/api/tasks.ics?token={token}")),
+        ctx.format.code("/calendar reset"),
+        ctx.format.code("/calendar off"),
+    )
+}
+
+async fn observe(ctx: &CommandContext<'_>, arg: Option<&str>) -> String {
+    let chat_id = ctx.chat_id;
+    let enable = match arg.map(|a| a.to_ascii_lowercase()).as_deref() {
+        None => {
+            return match call_blocking(ctx.state.db.clone(), move |db| {
+                db.is_observer_chat(chat_id)
+            })
+            .await
+            {
+                Ok(true) => format!(
+                    "Observer mode is on: messages are stored, but I only reply when mentioned. {} turns it off.",
+                    ctx.format.code("/observe off")
+                ),
+                Ok(false) => format!(
+                    "Observer mode is off. {} keeps storing messages but stops replies unless I'm mentioned.",
+                    ctx.format.code("/observe on")
+                ),
+                Err(e) => format!("Failed to load observer mode: {e}"),
+            };
+        }
+        Some("on") => true,
+        Some("off") => false,
+        Some(_) => return format!("Usage: {}", ctx.format.code("/observe [on|off]")),
+    };
+    match call_blocking(ctx.state.db.clone(), move |db| {
+        db.set_observer_mode(chat_id, enable)
+    })
+    .await
+    {
+        Ok(_) if enable => {
+            "Observer mode on. I'll keep reading along and only reply when mentioned.".to_string()
+        }
+        Ok(_) => "Observer mode off.".to_string(),
+        Err(e) => format!("Failed to update observer mode: {e}"),
+    }
+}
+
+async fn notify(ctx: &CommandContext<'_>, args: &[&str]) -> String {
+    let chat_id = ctx.chat_id;
+    let mut settings = match call_blocking(ctx.state.db.clone(), move |db| {
+        db.get_notification_settings(chat_id)
+    })
+    .await
+    {
+        Ok(s) => s.unwrap_or_else(|| NotificationSettings::defaults(chat_id)),
+        Err(e) => return format!("Failed to load notification settings: {e}"),
+    };
+    let Some((key, value)) = args.split_first() else {
+        return format!(
+            "{}\n{}",
+            ctx.format.bold("Notifications"),
+            notifications::describe_settings(&settings)
+        );
+    };
+    let confirmation = match notifications::apply_setting(
+        &mut settings,
+        key,
+        value.first().copied(),
+        Utc::now(),
+    ) {
+        Ok(c) => c,
+        Err(e) => return e,
+    };
+    match call_blocking(ctx.state.db.clone(), move |db| {
+        db.save_notification_settings(&settings)
+    })
+    .await
+    {
+        Ok(()) => confirmation,
+        Err(e) => format!("Failed to save notification settings: {e}"),
+    }
+}
+
+fn tools(ctx: &CommandContext<'_>) -> String {
+    let mut names: Vec<&str> = ctx
+        .state
+        .tools
+        .definitions()
+        .iter()
+        .map(|d| d.name.as_str())
+        .collect();
+    names.sort_unstable();
+    format!(
+        "{}\n{}",
+        ctx.format.bold(&format!("Tools ({})", names.len())),
+        names.join(", ")
+    )
+}
+
+async fn acp(ctx: &CommandContext<'_>, action: &str, arg: Option<&str>) -> String {
+    let manager = &ctx.state.acp_manager;
+    match action {
+        "stop" => match manager.cancel_chat_prompt(ctx.chat_id).await {
+            Ok(()) => "Stopping the agent...".to_string(),
+            Err(e) => format!("Nothing to stop: {e}"),
+        },
+        "end" => match manager.end_chat_session(ctx.chat_id).await {
+            Ok(()) => "ACP session ended.".to_string(),
+            Err(e) => e,
+        },
+        "sessions" => {
+            let mut sessions = manager.list_sessions().await;
+            // Other chats' sessions are only listed in control chats
+            if !ctx.is_control {
+                let own = manager.chat_session(ctx.chat_id).await;
+                sessions.retain(|s| own.as_deref() == Some(s.session_id.as_str()));
+            }
+            if sessions.is_empty() {
+                return "No active ACP sessions.".to_string();
+            }
+            let mut lines = vec![ctx.format.bold("Active ACP sessions")];
+            lines.extend(sessions.iter().map(|s| {
+                let usage = if s.usage.is_empty() {
+                    String::new()
+                } else {
+                    format!(", usage: {}", s.usage)
+                };
+                format!(
+                    "- {} (agent={}, status={:?}, idle={}s{usage})",
+                    ctx.format.code(&s.session_id),
+                    s.agent_id,
+                    s.status,
+                    s.idle_secs
+                )
+            }));
+            lines.join("\n")
+        }
+        "mode" => {
+            let Some(session_id) = manager.chat_session(ctx.chat_id).await else {
+                return "No active ACP session in this chat.".to_string();
+            };
+            if let Some(mode) = arg {
+                return match manager.set_mode(&session_id, mode).await {
+                    Ok(mode) => format!("ACP mode set to {}.", ctx.format.bold(&mode.name)),
+                    Err(e) => e,
+                };
+            }
+            match manager.session_modes(&session_id).await {
+                Ok(Some(modes)) => {
+                    let mut lines = vec![ctx.format.bold("ACP modes")];
+                    lines.extend(modes.available.iter().map(|m| {
+                        let marker = if m.id == modes.current_mode_id {
+                            " (current)"
+                        } else {
+                            ""
+                        };
+                        match &m.description {
+                            Some(d) => format!("- {}{marker} — {d}", ctx.format.code(&m.id)),
+                            None => format!("- {}{marker}", ctx.format.code(&m.id)),
+                        }
+                    }));
+                    lines.join("\n")
+                }
+                Ok(None) => "This agent does not offer session modes.".to_string(),
+                Err(e) => e,
+            }
+        }
+        "agents" => {
+            let agents = manager.available_agents();
+            if agents.is_empty() {
+                "No ACP agents configured.".to_string()
+            } else {
+                format!("Available ACP agents: {}", agents.join(", "))
+            }
+        }
+        _ => format!(
+            "Usage: {}",
+            ctx.format
+                .code("/acp <stop|end|sessions|agents|mode [name]>")
+        ),
+    }
+}
+
+async fn sessions(ctx: &CommandContext<'_>) -> String {
+    let chats = match call_blocking(ctx.state.db.clone(), |db| db.get_recent_chats(20)).await {
+        Ok(chats) => chats,
+        Err(e) => return format!("Failed to list chats: {e}"),
+    };
+    if chats.is_empty() {
+        return "No chats yet.".to_string();
+    }
+    let mut lines = vec![ctx.format.bold("Recent chats")];
+    for chat in &chats {
+        lines.push(format!(
+            "- {} [{}] {} — last active {}",
+            chat.chat_id,
+            chat.chat_type,
+            chat.chat_title.as_deref().unwrap_or("(untitled)"),
+            chat.last_message_time
+        ));
+    }
+    lines.join("\n")
+}
+
+/// Upper bound on chats reached by one `/broadcast`.
+const MAX_BROADCAST_CHATS: usize = 500;
+
+async fn broadcast(ctx: &CommandContext<'_>, message: &str) -> String {
+    let state = ctx.state;
+    let chats = match call_blocking(state.db.clone(), |db| {
+        db.get_recent_chats(MAX_BROADCAST_CHATS)
+    })
+    .await
+    {
+        Ok(chats) => chats,
+        Err(e) => return format!("Failed to list chats: {e}"),
+    };
+
+    let (mut sent, mut failed) = (0usize, 0usize);
+    for chat in chats {
+        if chat.chat_id == ctx.chat_id {
+            continue;
+        }
+        // Local-only channels (web) have nobody to deliver to
+        let deliverable = state
+            .channel_registry
+            .resolve(&chat.chat_type)
+            .is_some_and(|(adapter, _)| !adapter.is_local_only());
+        if !deliverable {
+            continue;
+        }
+        match crate::channel::deliver_and_store_bot_message(
+            &state.channel_registry,
+            state.db.clone(),
+            &state.config.bot_username,
+            chat.chat_id,
+            message,
+        )
+        .await
+        {
+            Ok(()) => sent += 1,
+            Err(e) => {
+                tracing::warn!("Broadcast to chat {} failed: {e}", chat.chat_id);
+                failed += 1;
+            }
+        }
+    }
+    if failed == 0 {
+        format!("Broadcast sent to {sent} chat(s).")
+    } else {
+        format!("Broadcast sent to {sent} chat(s), {failed} failed (see logs).")
+    }
+}
+
+/// Top-level config keys whose values differ between two configs.
+fn changed_config_keys(running: &Config, on_disk: &Config) -> Vec<String> {
+    let (Ok(serde_yaml::Value::Mapping(a)), Ok(serde_yaml::Value::Mapping(b))) =
+        (serde_yaml::to_value(running), serde_yaml::to_value(on_disk))
+    else {
+        return Vec::new();
+    };
+    let mut keys: Vec<String> = a
+        .keys()
+        .chain(b.keys())
+        .filter(|k| a.get(*k) != b.get(*k))
+        .filter_map(|k| k.as_str().map(str::to_string))
+        .collect();
+    keys.sort_unstable();
+    keys.dedup();
+    keys
+}
+
+/// Validate the config file against the running config. Nothing is applied:
+/// the running config only changes on restart.
+fn check_config(ctx: &CommandContext<'_>) -> String {
+    let on_disk = match Config::load() {
+        Ok(config) => config,
+        Err(e) => return format!("Config file has errors, nothing changed: {e}"),
+    };
+    let note = "Skills and SOUL.md are re-read on every message and need no restart.";
+    let changed = changed_config_keys(&ctx.state.config, &on_disk);
+    if changed.is_empty() {
+        return format!("Config file is valid and matches the running config.\n{note}");
+    }
+    let keys = changed
+        .iter()
+        .map(|k| ctx.format.code(k))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("Config file is valid. Changed settings take effect after a restart: {keys}\n{note}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_command_basic_and_args() {
+        let cmd = parse_command("/usage").unwrap();
+        assert_eq!(cmd.spec.name, "usage");
+        assert!(cmd.args.is_empty());
+
+        let cmd = parse_command("  /broadcast  Server restarts at 5pm ").unwrap();
+        assert_eq!(cmd.spec.name, "broadcast");
+        assert_eq!(cmd.rest, "Server restarts at 5pm");
+        assert_eq!(cmd.args, vec!["Server", "restarts", "at", "5pm"]);
+    }
+
+    #[test]
+    fn test_parse_command_strips_bot_suffix_and_mentions() {
+        assert_eq!(parse_command("/Status@my_bot").unwrap().spec.name, "status");
+        assert_eq!(parse_command("@bot /tools").unwrap().spec.name, "tools");
+        assert_eq!(
+            parse_command("<@U123> /acp stop").unwrap().args,
+            vec!["stop"]
+        );
+    }
+
+    #[test]
+    fn test_parse_command_ignores_non_commands() {
+        assert!(parse_command("hello").is_none());
+        assert!(parse_command("/tmp/foo.txt").is_none());
+        assert!(parse_command("/unknown").is_none());
+        assert!(parse_command("#help").is_none());
+    }
+
+    #[test]
+    fn test_command_registry_is_consistent() {
+        let mut names: Vec<&str> = COMMANDS.iter().map(|c| c.name).collect();
+        let total = names.len();
+        names.sort_unstable();
+        names.dedup();
+        assert_eq!(names.len(), total, "command names must be unique");
+        for spec in COMMANDS {
+            assert!(spec.max_args.is_none_or(|max| max >= spec.min_args));
+            assert!(spec.usage().starts_with('/'));
+        }
+        for name in ["sessions", "broadcast", "checkconfig", "analytics"] {
+            assert_eq!(find_command(name).unwrap().permission, ControlChat);
+        }
+    }
+
+    #[test]
+    fn test_text_format_rendering() {
+        assert_eq!(TextFormat::Plain.bold("Status"), "Status");
+        assert_eq!(TextFormat::Markdown.bold("Status"), "**Status**");
+        assert_eq!(TextFormat::SlackMrkdwn.bold("Status"), "*Status*");
+        assert_eq!(TextFormat::Markdown.code("/help"), "`/help`");
+        assert_eq!(TextFormat::Plain.code("/help"), "/help");
+    }
+
+    #[test]
+    fn test_truncate_respects_char_boundaries() {
+        assert_eq!(truncate("short", 10), "short");
+        assert_eq!(truncate("line one\nline two", 100), "line one line two");
+        assert_eq!(truncate("日本語のテキスト", 7), "日本…");
+    }
+
+    #[test]
+    fn test_changed_config_keys() {
+        let running: Config =
+            serde_yaml::from_str("llm_provider: anthropic\napi_key: k\n").unwrap();
+        let mut on_disk = running.clone();
+        on_disk.model = "other-model".into();
+        on_disk.max_tokens += 1;
+        assert_eq!(
+            changed_config_keys(&running, &on_disk),
+            vec!["max_tokens".to_string(), "model".to_string()]
+        );
+        assert!(changed_config_keys(&running, &running).is_empty());
+    }
+}
@@ -36,6 +36,12 @@ fn default_max_history_messages() -> usize {
 fn default_max_document_size_mb() -> u64 {
     100
 }
+fn default_image_max_dimension() -> u32 {
+    1568
+}
+fn default_image_jpeg_quality() -> u8 {
+    85
+}
 fn default_memory_token_budget() -> usize {
     1500
 }
@@ -48,6 +54,21 @@ fn default_working_dir() -> String {
 fn default_working_dir_isolation() -> WorkingDirIsolation {
     WorkingDirIsolation::Chat
 }
+fn default_workspace_cleanup_interval_mins() -> u64 {
+    60
+}
+fn default_tts_model() -> String {
+    "tts-1".into()
+}
+fn default_tts_voice() -> String {
+    "alloy".into()
+}
+fn default_stt_backend() -> String {
+    "openai".into()
+}
+fn default_whisper_cpp_binary() -> String {
+    "whisper-cli".into()
+}
 fn default_timezone() -> String {
     "UTC".into()
 }
@@ -57,9 +78,63 @@ fn default_max_session_messages() -> usize {
 fn default_compact_keep_recent() -> usize {
     20
 }
+fn default_session_expiry_summary() -> bool {
+    true
+}
+fn default_digest_dedup_days() -> u64 {
+    14
+}
+fn default_llm_max_retries() -> u32 {
+    3
+}
+fn default_llm_retry_max_delay_secs() -> u64 {
+    60
+}
+fn default_cache_backend() -> String {
+    "memory".into()
+}
+
+/// Lowercase a `memory`/`redis` backend setting and check Redis is configured.
+fn normalize_backend(field: &str, value: &mut String, has_redis: bool) -> Result<(), RayClawError> {
+    *value = value.trim().to_lowercase();
+    match value.as_str() {
+        "" => *value = default_cache_backend(),
+        "memory" => {}
+        "redis" if !has_redis => {
+            return Err(RayClawError::Config(format!(
+                "{field}: redis requires coordination_redis_url"
+            )));
+        }
+        "redis" => {}
+        other => {
+            return Err(RayClawError::Config(format!(
+                "{field} must be memory or redis, got '{other}'"
+            )));
+        }
+    }
+    Ok(())
+}
+fn default_coordination_key_prefix() -> String {
+    "rayclaw".into()
+}
+fn default_thinking_budget_tokens() -> u32 {
+    4096
+}
+fn default_max_concurrent_agent_runs() -> usize {
+    8
+}
+fn default_outage_queue_size() -> usize {
+    50
+}
+fn default_outage_queue_ttl_mins() -> u64 {
+    60
+}
 fn default_control_chat_ids() -> Vec<i64> {
     Vec::new()
 }
+fn default_startup_preflight() -> bool {
+    true
+}
 fn default_web_enabled() -> bool {
     true
 }
@@ -100,6 +175,9 @@ fn default_soul_path() -> Option<String> {
 fn default_skip_tool_approval() -> bool {
     false
 }
+fn default_tool_approval_timeout_secs() -> u64 {
+    300
+}
 fn default_prompt_cache_ttl() -> String {
     "none".into()
 }
@@ -120,6 +198,439 @@ pub struct ModelPrice {
     pub model: String,
     pub input_per_million_usd: f64,
     pub output_per_million_usd: f64,
+    /// Prompt cache reads; unset bills them as regular input
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_read_per_million_usd: Option<f64>,
+    /// Prompt cache writes; unset bills them as regular input
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_write_per_million_usd: Option<f64>,
+}
+
+impl ModelPrice {
+    pub fn cost_usd(&self, input_tokens: i64, output_tokens: i64) -> f64 {
+        let in_tok = input_tokens.max(0) as f64;
+        let out_tok = output_tokens.max(0) as f64;
+        (in_tok / 1_000_000.0) * self.input_per_million_usd
+            + (out_tok / 1_000_000.0) * self.output_per_million_usd
+    }
+
+    /// Cost of one LLM call, pricing prompt cache traffic separately.
+    pub fn usage_cost_usd(&self, usage: &crate::llm_types::Usage) -> f64 {
+        let per_million = |tokens: u32, price: f64| f64::from(tokens) / 1_000_000.0 * price;
+        self.cost_usd(
+            i64::from(usage.input_tokens),
+            i64::from(usage.output_tokens),
+        ) + per_million(
+            usage.cache_read_input_tokens,
+            self.cache_read_per_million_usd
+                .unwrap_or(self.input_per_million_usd),
+        ) + per_million(
+            usage.cache_creation_input_tokens,
+            self.cache_write_per_million_usd
+                .unwrap_or(self.input_per_million_usd),
+        )
+    }
+}
+
+/// Context window / output size override for a model (see `model_limits`).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ModelLimit {
+    pub model: String,
+    #[serde(default)]
+    pub context_window: Option<u32>,
+    #[serde(default)]
+    pub max_output_tokens: Option<u32>,
+}
+
+/// Extended thinking / reasoning settings.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ThinkingConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Reasoning token budget for Anthropic and Claude on Bedrock (at least
+    /// 1024, below `max_tokens`)
+    #[serde(default = "default_thinking_budget_tokens")]
+    pub budget_tokens: u32,
+    /// `low`, `medium` or `high` for effort-based models (OpenAI o-series,
+    /// Amazon Nova). Unset derives it from `budget_tokens`.
+    #[serde(default)]
+    pub effort: Option<String>,
+}
+
+impl Default for ThinkingConfig {
+    fn default() -> Self {
+        ThinkingConfig {
+            enabled: false,
+            budget_tokens: default_thinking_budget_tokens(),
+            effort: None,
+        }
+    }
+}
+
+/// Smallest thinking budget Anthropic accepts.
+pub const MIN_THINKING_BUDGET_TOKENS: u32 = 1024;
+
+impl ThinkingConfig {
+    /// Reasoning effort for effort-based APIs.
+    pub fn effort(&self) -> &str {
+        match self.effort.as_deref() {
+            Some(effort) => effort,
+            None if self.budget_tokens < 4096 => "low",
+            None if self.budget_tokens < 16384 => "medium",
+            None => "high",
+        }
+    }
+}
+
+/// Sampling parameters sent with LLM requests. Unset fields keep the
+/// provider's defaults.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct InferenceParams {
+    /// 0-2; lower is more deterministic
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    /// Nucleus sampling cutoff, above 0 and at most 1
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f64>,
+    /// Sample from the k most likely tokens (not supported by OpenAI/Azure)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<u32>,
+    /// Stop generating at any of these strings (at most 4)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stop_sequences: Vec<String>,
+}
+
+/// Most stop sequences every provider accepts (OpenAI's limit).
+pub const MAX_STOP_SEQUENCES: usize = 4;
+
+impl InferenceParams {
+    /// These parameters with the ones set in `overrides` replacing them.
+    pub fn merged(&self, overrides: &InferenceParams) -> InferenceParams {
+        InferenceParams {
+            temperature: overrides.temperature.or(self.temperature),
+            top_p: overrides.top_p.or(self.top_p),
+            top_k: overrides.top_k.or(self.top_k),
+            stop_sequences: if overrides.stop_sequences.is_empty() {
+                self.stop_sequences.clone()
+            } else {
+                overrides.stop_sequences.clone()
+            },
+        }
+    }
+
+    fn validate(&mut self, field: &str) -> Result<(), RayClawError> {
+        if let Some(t) = self.temperature {
+            if !(0.0..=2.0).contains(&t) {
+                return Err(RayClawError::Config(format!(
+                    "{field}.temperature must be between 0 and 2, got {t}"
+                )));
+            }
+        }
+        if let Some(p) = self.top_p {
+            if !(p > 0.0 && p <= 1.0) {
+                return Err(RayClawError::Config(format!(
+                    "{field}.top_p must be above 0 and at most 1, got {p}"
+                )));
+            }
+        }
+        if self.top_k == Some(0) {
+            return Err(RayClawError::Config(format!(
+                "{field}.top_k must be at least 1"
+            )));
+        }
+        self.stop_sequences.retain(|s| !s.is_empty());
+        if self.stop_sequences.len() > MAX_STOP_SEQUENCES {
+            return Err(RayClawError::Config(format!(
+                "{field}.stop_sequences allows at most {MAX_STOP_SEQUENCES} entries"
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// OpenRouter settings, used when `llm_provider` is `openrouter`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct OpenRouterConfig {
+    /// Models tried in order when `model` is down, rate limited or refuses
+    /// the request (sent as OpenRouter's `models` list)
+    #[serde(default)]
+    pub fallback_models: Vec<String>,
+    /// Provider routing preferences (OpenRouter's `provider` object)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<OpenRouterProviderPreferences>,
+    /// Sent as `HTTP-Referer` to attribute requests to this app
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub app_url: Option<String>,
+    /// Sent as `X-Title` (default: RayClaw)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub app_title: Option<String>,
+}
+
+/// Providers behind the `news_search` tool.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct NewsSearchConfig {
+    /// `brave`, `bing` or `gdelt`. Unset: Brave, then Bing when their key
+    /// is set, then GDELT (free, no key)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    /// Brave Search API subscription token
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub brave_api_key: Option<String>,
+    /// Bing Search v7 subscription key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bing_api_key: Option<String>,
+}
+
+/// Backends of the `directions` and `place_search` tools.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct MapsConfig {
+    /// Google Maps Platform key (Directions and Places APIs). When set,
+    /// Google is used instead of OpenStreetMap
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub google_api_key: Option<String>,
+    /// Nominatim geocoder (default: https://nominatim.openstreetmap.org)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nominatim_url: Option<String>,
+    /// Self-hosted OSRM router, queried as `<url>/route/v1/<profile>/...`
+    /// (default: the FOSSGIS servers at routing.openstreetmap.de)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub osrm_url: Option<String>,
+}
+
+/// Parcel tracking services behind the `track_package` tool.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct PackageTrackingConfig {
+    /// `17track` or `aftership`. Unset: whichever has a key, 17TRACK first
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    /// 17TRACK API v2 token
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub track17_api_key: Option<String>,
+    /// AfterShip tracking API key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aftership_api_key: Option<String>,
+}
+
+/// Data sources of the `flight_status` tool.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct FlightStatusConfig {
+    /// aviationstack access key, for schedules, delays, gates and status
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aviationstack_api_key: Option<String>,
+    /// ADS-B Exchange v2 compatible API for live positions
+    /// (default: https://api.adsb.lol)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub adsb_url: Option<String>,
+}
+
+/// Proxy, user-agent and extra headers for the web tools' HTTP clients
+/// (see `outbound_http`).
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct OutboundHttpConfig {
+    /// `http://`, `https://`, `socks5://` or `socks5h://` proxy URL, with
+    /// optional `user:password@`. Unset: `HTTPS_PROXY`/`HTTP_PROXY` from the
+    /// environment
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+    /// Comma-separated hosts, domains or CIDRs that bypass `proxy`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub no_proxy: Option<String>,
+    /// Replaces the default `RayClaw/1.0` user-agent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<String>,
+    /// Headers added to requests by domain; a domain also matches its
+    /// subdomains
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub headers: HashMap<String, HashMap<String, String>>,
+    /// Per-client overrides, keyed by tool (`web_fetch`, `maps`, ...)
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub tools: HashMap<String, OutboundHttpOverride>,
+    /// Crawl politeness for page fetches; everything is off by default
+    #[serde(default)]
+    pub politeness: PolitenessConfig,
+}
+
+/// Limits for tools that fetch arbitrary pages (`web_fetch`, URL monitors,
+/// `screenshot_url`); see `politeness`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct PolitenessConfig {
+    /// Refuse pages that the site's robots.txt disallows for the
+    /// user-agent, and wait out its `Crawl-delay`
+    #[serde(default)]
+    pub respect_robots_txt: bool,
+    /// Minimum time between two requests to the same host; 0 disables
+    #[serde(default)]
+    pub min_host_interval_ms: u64,
+    /// Pages one agent run may fetch; 0 means no limit
+    #[serde(default)]
+    pub max_pages_per_run: u32,
+}
+
+/// Proxy and user-agent for one outbound HTTP client.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct OutboundHttpOverride {
+    /// Proxy URL, or `direct` to bypass the global proxy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<String>,
+}
+
+/// Trimmed, with empty strings as unset.
+fn trimmed(value: Option<String>) -> Option<String> {
+    value
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+fn validate_proxy_url(name: &str, proxy: &str) -> Result<(), RayClawError> {
+    let scheme_ok = reqwest::Url::parse(proxy).is_ok_and(|url| {
+        matches!(url.scheme(), "http" | "https" | "socks5" | "socks5h") && url.host().is_some()
+    });
+    if !scheme_ok {
+        return Err(RayClawError::Config(format!(
+            "{name} must be an http(s):// or socks5(h):// proxy URL, got '{proxy}'"
+        )));
+    }
+    Ok(())
+}
+
+/// Longer gaps would stall a tool call past its timeout.
+const MAX_HOST_INTERVAL_MS: u64 = 60_000;
+
+impl OutboundHttpConfig {
+    /// Trim values, lowercase header domains and reject unusable proxies,
+    /// headers and tool names.
+    fn normalize(&mut self) -> Result<(), RayClawError> {
+        self.proxy = trimmed(self.proxy.take());
+        self.no_proxy = trimmed(self.no_proxy.take());
+        self.user_agent = trimmed(self.user_agent.take());
+        if let Some(proxy) = &self.proxy {
+            validate_proxy_url("outbound_http.proxy", proxy)?;
+        }
+
+        let mut headers = HashMap::new();
+        for (domain, values) in std::mem::take(&mut self.headers) {
+            let domain = domain
+                .trim()
+                .trim_start_matches("*.")
+                .trim_start_matches('.')
+                .to_lowercase();
+            if domain.is_empty() {
+                return Err(RayClawError::Config(
+                    "outbound_http.headers needs a domain for each header set".into(),
+                ));
+            }
+            for (name, value) in &values {
+                let valid = reqwest::header::HeaderName::from_bytes(name.trim().as_bytes()).is_ok()
+                    && reqwest::header::HeaderValue::from_str(value.trim()).is_ok();
+                if !valid {
+                    return Err(RayClawError::Config(format!(
+                        "outbound_http.headers.{domain}: invalid header '{name}'"
+                    )));
+                }
+            }
+            let values: HashMap<String, String> = values
+                .into_iter()
+                .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+                .collect();
+            headers.insert(domain, values);
+        }
+        self.headers = headers;
+
+        if self.politeness.min_host_interval_ms > MAX_HOST_INTERVAL_MS {
+            return Err(RayClawError::Config(format!(
+                "outbound_http.politeness.min_host_interval_ms must be at most {MAX_HOST_INTERVAL_MS}"
+            )));
+        }
+
+        for (tool, settings) in &mut self.tools {
+            if !crate::outbound_http::SCOPES.contains(&tool.as_str()) {
+                return Err(RayClawError::Config(format!(
+                    "outbound_http.tools.{tool}: unknown tool (expected one of {})",
+                    crate::outbound_http::SCOPES.join(", ")
+                )));
+            }
+            settings.proxy = trimmed(settings.proxy.take());
+            settings.user_agent = trimmed(settings.user_agent.take());
+            match settings.proxy.as_deref() {
+                Some(proxy) if proxy.eq_ignore_ascii_case("direct") => {
+                    settings.proxy = Some("direct".into())
+                }
+                Some(proxy) => {
+                    validate_proxy_url(&format!("outbound_http.tools.{tool}.proxy"), proxy)?
+                }
+                None => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Which upstream providers OpenRouter may route to, and how.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct OpenRouterProviderPreferences {
+    /// Providers to try first, in order (e.g. `anthropic`, `together`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub order: Vec<String>,
+    /// Only route to these providers
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub only: Vec<String>,
+    /// Never route to these providers
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ignore: Vec<String>,
+    /// Whether providers outside `order` may serve the request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allow_fallbacks: Option<bool>,
+    /// `price`, `throughput` or `latency`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sort: Option<String>,
+    /// `allow` or `deny` providers that may store prompts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_collection: Option<String>,
+    /// Only route to providers supporting every request parameter (e.g. tools)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub require_parameters: Option<bool>,
+}
+
+/// Inbound email gateway (see `email_gateway`): a mail provider's inbound
+/// webhook posts forwarded emails to `/api/email/inbound`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EmailGatewayConfig {
+    /// Shared secret the webhook must send as a Bearer token or `?token=`
+    pub token: String,
+    #[serde(default)]
+    pub routes: Vec<EmailRoute>,
+}
+
+/// Maps a recipient address to the chat its emails are filed into.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EmailRoute {
+    /// Recipient address (case-insensitive), or `"*"` for any address
+    pub address: String,
+    pub chat_id: i64,
+    /// Accepted senders: full addresses or `@domain`. Empty accepts anyone.
+    #[serde(default)]
+    pub allowed_senders: Vec<String>,
+}
+
+/// Price entry for `model` (case-insensitive), falling back to a `"*"` entry.
+pub fn find_model_price<'a>(prices: &'a [ModelPrice], model: &str) -> Option<&'a ModelPrice> {
+    let needle = model.trim();
+    prices
+        .iter()
+        .find(|p| p.model.eq_ignore_ascii_case(needle))
+        .or_else(|| prices.iter().find(|p| p.model == "*"))
+}
+
+/// API key and base URL for a provider other than `llm_provider`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ProviderCredentials {
+    #[serde(default)]
+    pub api_key: String,
+    /// Defaults to the provider preset's URL
+    #[serde(default)]
+    pub llm_base_url: Option<String>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -133,8 +644,29 @@ pub struct Config {
     pub model: String,
     #[serde(default)]
     pub llm_base_url: Option<String>,
+    /// Credentials for other providers that per-chat model overrides may
+    /// switch to, keyed by provider ID
+    #[serde(default)]
+    pub provider_credentials: HashMap<String, ProviderCredentials>,
     #[serde(default = "default_max_tokens")]
     pub max_tokens: u32,
+    /// Retries per LLM request on rate limits (429), overload (500/502/503/529)
+    /// and network errors. Waits follow `Retry-After` when sent, else
+    /// exponential backoff, with jitter.
+    #[serde(default = "default_llm_max_retries")]
+    pub llm_max_retries: u32,
+    /// Longest single wait between retries; a longer `Retry-After` fails the request instead
+    #[serde(default = "default_llm_retry_max_delay_secs")]
+    pub llm_retry_max_delay_secs: u64,
+    /// Record every provider request/response as JSONL under
+    /// `<data_dir>/runtime/llm_traces/` for debugging (see `llm_trace`).
+    /// Can also be set via RAYCLAW_LLM_TRACE=true env var.
+    #[serde(default)]
+    pub llm_trace: bool,
+    /// Check the database, disk space, provider, channel tokens and clock
+    /// before `rayclaw start` brings up channels; failures abort startup.
+    #[serde(default = "default_startup_preflight")]
+    pub startup_preflight: bool,
     #[serde(default = "default_prompt_cache_ttl")]
     pub prompt_cache_ttl: String,
     #[serde(default = "default_max_tool_iterations")]
@@ -145,12 +677,44 @@ pub struct Config {
     pub max_document_size_mb: u64,
     #[serde(default = "default_memory_token_budget")]
     pub memory_token_budget: usize,
+    /// Images are downscaled so their longest edge fits this many pixels before
+    /// being sent to the LLM (0 = keep original size).
+    #[serde(default = "default_image_max_dimension")]
+    pub image_max_dimension: u32,
+    /// JPEG quality used when images are re-encoded (1-100).
+    #[serde(default = "default_image_jpeg_quality")]
+    pub image_jpeg_quality: u8,
+    /// TrueType font for `render_chart` text. When unset, common system
+    /// fonts are tried; without any, charts are drawn without labels.
+    #[serde(default)]
+    pub chart_font_path: Option<String>,
     #[serde(default = "default_max_session_messages")]
     pub max_session_messages: usize,
     #[serde(default = "default_compact_keep_recent")]
     pub compact_keep_recent: usize,
+    /// Hours a chat can sit idle before its session is retired and the next
+    /// message starts a new one (0 = sessions never expire).
+    #[serde(default)]
+    pub session_idle_expiry_hours: u64,
+    /// Open a new session after expiry with a summary of the old one.
+    #[serde(default = "default_session_expiry_summary")]
+    pub session_expiry_summary: bool,
+    /// Show the model's reasoning (native thinking blocks or `<think>` tags) in replies
     #[serde(default)]
     pub show_thinking: bool,
+    /// Extended thinking / reasoning tokens
+    #[serde(default)]
+    pub thinking: ThinkingConfig,
+    /// Sampling parameters (temperature, top_p, top_k, stop_sequences)
+    #[serde(default)]
+    pub inference: InferenceParams,
+    /// Per-chat sampling parameters, keyed by chat ID; set fields replace
+    /// the ones in `inference`
+    #[serde(default)]
+    pub chat_inference: HashMap<i64, InferenceParams>,
+    /// Model fallbacks, routing and attribution for `llm_provider: openrouter`
+    #[serde(default)]
+    pub openrouter: OpenRouterConfig,
 
     // --- Paths & environment ---
     #[serde(default = "default_data_dir")]
@@ -159,10 +723,59 @@ pub struct Config {
     pub working_dir: String,
     #[serde(default = "default_working_dir_isolation")]
     pub working_dir_isolation: WorkingDirIsolation,
+    /// Per-workspace size cap in MB (0 = unlimited). When exceeded, the
+    /// workspace cleaner deletes least-recently-used files first.
+    #[serde(default)]
+    pub workspace_quota_mb: u64,
+    /// Remove uploaded attachments and files under `tmp/` older than this (0 = keep).
+    #[serde(default)]
+    pub workspace_tmp_ttl_hours: u64,
+    #[serde(default = "default_workspace_cleanup_interval_mins")]
+    pub workspace_cleanup_interval_mins: u64,
     #[serde(default = "default_timezone")]
     pub timezone: String,
     #[serde(default = "default_control_chat_ids")]
     pub control_chat_ids: Vec<i64>,
+    /// Agent runs allowed at once across all chats; extra runs queue
+    /// round-robin per chat, control chats first. 0 = unlimited.
+    #[serde(default = "default_max_concurrent_agent_runs")]
+    pub max_concurrent_agent_runs: usize,
+    /// Chats whose messages are held while the LLM provider is down, to be
+    /// answered once it recovers. 0 = report the error instead.
+    #[serde(default = "default_outage_queue_size")]
+    pub outage_queue_size: usize,
+    /// Minutes a held message waits for the provider before it is dropped
+    #[serde(default = "default_outage_queue_ttl_mins")]
+    pub outage_queue_ttl_mins: u64,
+    /// Wait this long after a user message before starting the agent; messages
+    /// arriving meanwhile join the same run. 0 = start immediately.
+    #[serde(default)]
+    pub message_debounce_ms: u64,
+    /// Days a link sent in a scheduled task result stays known to the chat,
+    /// so recurring digests leave out items already sent. 0 = off.
+    #[serde(default = "default_digest_dedup_days")]
+    pub digest_dedup_days: u64,
+
+    // --- Multi-instance coordination ---
+    /// Redis shared by instances running against the same storage, e.g.
+    /// `redis://:password@redis:6379/0`. Each scheduled run and inbound
+    /// webhook message is claimed there first, so only one instance handles it.
+    #[serde(default)]
+    pub coordination_redis_url: Option<String>,
+    /// Prefix for coordination keys; instances sharing storage must use the same one
+    #[serde(default = "default_coordination_key_prefix")]
+    pub coordination_key_prefix: String,
+    /// Where shared runtime state (web API rate limits, response cache)
+    /// lives: `memory` (per instance) or `redis` (`coordination_redis_url`, shared)
+    #[serde(default = "default_cache_backend")]
+    pub cache_backend: String,
+    /// Where agent run slots (`max_concurrent_agent_runs`) are counted:
+    /// `memory` (per instance) or `redis` (across instances)
+    #[serde(default = "default_cache_backend")]
+    pub queue_backend: String,
+    /// How long `web_search` results are cached, in seconds (0 = off)
+    #[serde(default)]
+    pub response_cache_ttl_secs: u64,
 
     // --- Web UI ---
     #[serde(default = "default_web_enabled")]
@@ -173,6 +786,10 @@ pub struct Config {
     pub web_port: u16,
     #[serde(default)]
     pub web_auth_token: Option<String>,
+    /// Externally reachable base URL of the web server (e.g. behind a reverse
+    /// proxy), used in links the bot hands out. Defaults to web_host:web_port.
+    #[serde(default)]
+    pub web_public_url: Option<String>,
     #[serde(default = "default_web_max_inflight_per_session")]
     pub web_max_inflight_per_session: usize,
     #[serde(default = "default_web_max_requests_per_window")]
@@ -183,6 +800,9 @@ pub struct Config {
     pub web_run_history_limit: usize,
     #[serde(default = "default_web_session_idle_ttl_seconds")]
     pub web_session_idle_ttl_seconds: u64,
+    /// Turn forwarded emails into scheduled tasks or agent runs (needs the web server)
+    #[serde(default)]
+    pub email_gateway: Option<EmailGatewayConfig>,
 
     // --- Embedding ---
     #[serde(default)]
@@ -197,10 +817,29 @@ pub struct Config {
     pub embedding_dim: Option<usize>,
     #[serde(default)]
     pub openai_api_key: Option<String>,
+    /// OpenAI text-to-speech model used for web voice replies.
+    #[serde(default = "default_tts_model")]
+    pub tts_model: String,
+    /// OpenAI text-to-speech voice used for web voice replies.
+    #[serde(default = "default_tts_voice")]
+    pub tts_voice: String,
+    /// Speech-to-text for voice notes: "openai" (Whisper API, uses
+    /// `openai_api_key`) or "whisper_cpp" (local binary).
+    #[serde(default = "default_stt_backend")]
+    pub stt_backend: String,
+    /// whisper.cpp CLI, by name on PATH or as a path.
+    #[serde(default = "default_whisper_cpp_binary")]
+    pub whisper_cpp_binary: String,
+    /// ggml model file for whisper.cpp; required with `stt_backend: whisper_cpp`.
+    #[serde(default)]
+    pub whisper_cpp_model: Option<String>,
 
     // --- Pricing ---
     #[serde(default = "default_model_prices")]
     pub model_prices: Vec<ModelPrice>,
+    /// Context window / max output overrides on top of the built-in registry
+    #[serde(default)]
+    pub model_limits: Vec<ModelLimit>,
 
     // --- Reflector ---
     #[serde(default = "default_reflector_enabled")]
@@ -219,6 +858,28 @@ pub struct Config {
     pub aws_session_token: Option<String>,
     #[serde(default)]
     pub aws_profile: Option<String>,
+    /// Application inference profile ARN to invoke instead of `model`
+    #[serde(default)]
+    pub aws_bedrock_inference_profile: Option<String>,
+    /// Inference profile (cross-region ID or ARN) to invoke per foundation
+    /// model ID; takes precedence over `aws_bedrock_inference_profile`
+    #[serde(default)]
+    pub aws_bedrock_inference_profiles: HashMap<String, String>,
+    /// Converse `performanceConfig.latency`: "standard" or "optimized"
+    #[serde(default)]
+    pub aws_bedrock_latency: Option<String>,
+    /// Bedrock runtime endpoint override, e.g. a VPC interface endpoint
+    #[serde(default)]
+    pub aws_endpoint_url: Option<String>,
+    /// Use the FIPS 140-validated `bedrock-runtime-fips` endpoint
+    #[serde(default)]
+    pub aws_use_fips_endpoint: bool,
+
+    // --- Ollama ---
+    /// How long Ollama keeps the model loaded after a request: a duration
+    /// ("10m", "1h") or seconds, `-1` for indefinitely. Unset uses the server default.
+    #[serde(default)]
+    pub ollama_keep_alive: Option<String>,
 
     // --- Soul ---
     /// Path to a SOUL.md file that defines the bot's personality, voice, and values.
@@ -226,12 +887,60 @@ pub struct Config {
     #[serde(default = "default_soul_path")]
     pub soul_path: Option<String>,
 
+    /// Path to a system prompt template (see `prompt_template`) replacing the
+    /// built-in prompt layout. Per-chat override:
+    /// `<data_dir>/runtime/groups/<chat_id>/SYSTEM_PROMPT.md`.
+    #[serde(default)]
+    pub system_prompt_template: Option<String>,
+
     /// Skip the high-risk tool approval loop (e.g. for bash).
     /// Useful when running inside an isolated environment like Firecracker.
     /// Can also be set via RAYCLAW_SKIP_TOOL_APPROVAL=true env var.
     #[serde(default = "default_skip_tool_approval")]
     pub skip_tool_approval: bool,
 
+    /// How long a high-risk tool call waits for an Approve/Deny button
+    /// press (Telegram, Discord, Slack) before it is dropped.
+    #[serde(default = "default_tool_approval_timeout_secs")]
+    pub tool_approval_timeout_secs: u64,
+
+    /// Regexes for extra secrets to mask in tool output, on top of the
+    /// configured credentials and well-known key formats (see `redact`).
+    #[serde(default)]
+    pub secret_patterns: Vec<String>,
+
+    /// Providers and API keys for the `news_search` tool
+    #[serde(default)]
+    pub news_search: NewsSearchConfig,
+
+    /// Geocoding and routing backends for `directions` and `place_search`
+    #[serde(default)]
+    pub maps: MapsConfig,
+
+    /// Tracking services and API keys for `track_package`
+    #[serde(default)]
+    pub package_tracking: PackageTrackingConfig,
+
+    /// Schedule and live-position sources for `flight_status`
+    #[serde(default)]
+    pub flight_status: FlightStatusConfig,
+
+    /// Proxy, user-agent and per-domain headers for outbound web requests
+    #[serde(default)]
+    pub outbound_http: OutboundHttpConfig,
+
+    /// Passphrase that encrypts browsing profile passwords and cookies. Unset:
+    /// a random key generated in `<data_dir>/runtime/browsing_profiles.key`
+    #[serde(default)]
+    pub browsing_profiles_key: Option<String>,
+
+    /// Largest output (in estimated tokens) each tool may return, by tool
+    /// name. Longer output is cut and the rest saved for `read_tool_output`.
+    /// Overrides the built-in budgets (bash, browser, web_fetch,
+    /// acp_prompt); 0 removes a limit.
+    #[serde(default)]
+    pub tool_output_budgets: std::collections::HashMap<String, usize>,
+
     /// Override the skills directory path. When set, `skills_data_dir()` returns
     /// this value instead of computing `{data_dir}/skills`. Useful when `data_dir`
     /// is repointed (e.g. to a runtime subdirectory) but skills remain at the
@@ -341,8 +1050,10 @@ impl Config {
             self.model = match self.llm_provider.as_str() {
                 "anthropic" => "claude-sonnet-4-5-20250929".into(),
                 "bedrock" => "anthropic.claude-sonnet-4-5-v2".into(),
+                "gemini" => "gemini-2.5-flash".into(),
                 "ollama" => "llama3.2".into(),
                 "openai-codex" => "gpt-5.3-codex".into(),
+                "openrouter" => "openrouter/auto".into(),
                 _ => "gpt-5.2".into(),
             };
         }
@@ -352,23 +1063,127 @@ impl Config {
             .parse::<chrono_tz::Tz>()
             .map_err(|_| RayClawError::Config(format!("Invalid timezone: {}", self.timezone)))?;
 
+        if let Some(profile) = &self.aws_bedrock_inference_profile {
+            if profile.trim().is_empty() {
+                self.aws_bedrock_inference_profile = None;
+            }
+        }
+        self.aws_bedrock_inference_profiles =
+            std::mem::take(&mut self.aws_bedrock_inference_profiles)
+                .into_iter()
+                .map(|(model, profile)| (model.trim().to_string(), profile.trim().to_string()))
+                .filter(|(model, profile)| !model.is_empty() && !profile.is_empty())
+                .collect();
+        if let Some(latency) = &self.aws_bedrock_latency {
+            let latency = latency.trim().to_lowercase();
+            match latency.as_str() {
+                "" => self.aws_bedrock_latency = None,
+                "standard" | "optimized" => self.aws_bedrock_latency = Some(latency),
+                other => {
+                    return Err(RayClawError::Config(format!(
+                        "aws_bedrock_latency must be \"standard\" or \"optimized\", got \"{other}\""
+                    )))
+                }
+            }
+        }
+        if let Some(url) = &self.aws_endpoint_url {
+            let url = url.trim().trim_end_matches('/').to_string();
+            if url.is_empty() {
+                self.aws_endpoint_url = None;
+            } else if !url.starts_with("https://") && !url.starts_with("http://") {
+                return Err(RayClawError::Config(format!(
+                    "aws_endpoint_url must start with https:// or http://, got \"{url}\""
+                )));
+            } else {
+                self.aws_endpoint_url = Some(url);
+            }
+        }
+
+        if let Some(keep_alive) = &self.ollama_keep_alive {
+            let keep_alive = keep_alive.trim().to_string();
+            self.ollama_keep_alive = (!keep_alive.is_empty()).then_some(keep_alive);
+        }
+
         // Filter empty llm_base_url
         if let Some(ref url) = self.llm_base_url {
             if url.trim().is_empty() {
                 self.llm_base_url = None;
             }
         }
+        self.provider_credentials = std::mem::take(&mut self.provider_credentials)
+            .into_iter()
+            .map(|(provider, credentials)| (provider.trim().to_lowercase(), credentials))
+            .collect();
         if self.working_dir.trim().is_empty() {
             self.working_dir = default_working_dir();
         }
         if self.web_host.trim().is_empty() {
             self.web_host = default_web_host();
         }
+        if self.tts_model.trim().is_empty() {
+            self.tts_model = default_tts_model();
+        }
+        if self.tts_voice.trim().is_empty() {
+            self.tts_voice = default_tts_voice();
+        }
         if let Some(token) = &self.web_auth_token {
             if token.trim().is_empty() {
                 self.web_auth_token = None;
             }
         }
+        if let Some(url) = &self.web_public_url {
+            let url = url.trim().trim_end_matches('/');
+            self.web_public_url = if url.is_empty() {
+                None
+            } else {
+                Some(url.to_string())
+            };
+        }
+        if let Some(url) = &self.coordination_redis_url {
+            let url = url.trim();
+            if url.is_empty() {
+                self.coordination_redis_url = None;
+            } else {
+                if let Err(e) = redis::Client::open(url) {
+                    return Err(RayClawError::Config(format!(
+                        "coordination_redis_url is not a valid Redis URL: {e}"
+                    )));
+                }
+                self.coordination_redis_url = Some(url.to_string());
+            }
+        }
+        if self.coordination_key_prefix.trim().is_empty() {
+            self.coordination_key_prefix = default_coordination_key_prefix();
+        }
+        self.stt_backend = self.stt_backend.trim().to_lowercase();
+        match self.stt_backend.as_str() {
+            "" => self.stt_backend = default_stt_backend(),
+            "openai" => {}
+            "whisper_cpp" => {
+                if self.whisper_cpp_binary.trim().is_empty() {
+                    self.whisper_cpp_binary = default_whisper_cpp_binary();
+                }
+                let model = self
+                    .whisper_cpp_model
+                    .as_deref()
+                    .map(str::trim)
+                    .filter(|m| !m.is_empty());
+                let Some(model) = model else {
+                    return Err(RayClawError::Config(
+                        "stt_backend: whisper_cpp requires whisper_cpp_model".into(),
+                    ));
+                };
+                self.whisper_cpp_model = Some(model.to_string());
+            }
+            other => {
+                return Err(RayClawError::Config(format!(
+                    "stt_backend must be openai or whisper_cpp, got '{other}'"
+                )));
+            }
+        }
+        let has_redis = self.coordination_redis_url.is_some();
+        normalize_backend("cache_backend", &mut self.cache_backend, has_redis)?;
+        normalize_backend("queue_backend", &mut self.queue_backend, has_redis)?;
         if let Some(provider) = &self.embedding_provider {
             let p = provider.trim().to_lowercase();
             self.embedding_provider = if p.is_empty() { None } else { Some(p) };
@@ -392,6 +1207,146 @@ impl Config {
                 self.embedding_dim = None;
             }
         }
+        if let Some(effort) = &self.thinking.effort {
+            let effort = effort.trim().to_lowercase();
+            if !matches!(effort.as_str(), "" | "low" | "medium" | "high") {
+                return Err(RayClawError::Config(format!(
+                    "thinking.effort must be low, medium or high, got '{effort}'"
+                )));
+            }
+            self.thinking.effort = (!effort.is_empty()).then_some(effort);
+        }
+        if self.thinking.enabled {
+            self.thinking.budget_tokens =
+                self.thinking.budget_tokens.max(MIN_THINKING_BUDGET_TOKENS);
+            if self.thinking.budget_tokens >= self.max_tokens {
+                return Err(RayClawError::Config(format!(
+                    "thinking.budget_tokens ({}) must be below max_tokens ({})",
+                    self.thinking.budget_tokens, self.max_tokens
+                )));
+            }
+        }
+        self.inference.validate("inference")?;
+        for (chat_id, params) in &mut self.chat_inference {
+            params.validate(&format!("chat_inference.{chat_id}"))?;
+        }
+        self.openrouter.fallback_models = std::mem::take(&mut self.openrouter.fallback_models)
+            .into_iter()
+            .map(|m| m.trim().to_string())
+            .filter(|m| !m.is_empty() && *m != self.model)
+            .collect();
+        if let Some(prefs) = &mut self.openrouter.provider {
+            if let Some(sort) = &prefs.sort {
+                let sort = sort.trim().to_lowercase();
+                if !matches!(sort.as_str(), "price" | "throughput" | "latency") {
+                    return Err(RayClawError::Config(format!(
+                        "openrouter.provider.sort must be price, throughput or latency, got '{sort}'"
+                    )));
+                }
+                prefs.sort = Some(sort);
+            }
+            if let Some(mode) = &prefs.data_collection {
+                let mode = mode.trim().to_lowercase();
+                if !matches!(mode.as_str(), "allow" | "deny") {
+                    return Err(RayClawError::Config(format!(
+                        "openrouter.provider.data_collection must be allow or deny, got '{mode}'"
+                    )));
+                }
+                prefs.data_collection = Some(mode);
+            }
+        }
+        for key in [
+            &mut self.news_search.brave_api_key,
+            &mut self.news_search.bing_api_key,
+        ] {
+            *key = key
+                .take()
+                .map(|k| k.trim().to_string())
+                .filter(|k| !k.is_empty());
+        }
+        if let Some(provider) = &self.news_search.provider {
+            let provider = provider.trim().to_lowercase();
+            let key_missing = match provider.as_str() {
+                "brave" => self.news_search.brave_api_key.is_none(),
+                "bing" => self.news_search.bing_api_key.is_none(),
+                "gdelt" => false,
+                _ => {
+                    return Err(RayClawError::Config(format!(
+                        "news_search.provider must be brave, bing or gdelt, got '{provider}'"
+                    )))
+                }
+            };
+            if key_missing {
+                return Err(RayClawError::Config(format!(
+                    "news_search.provider is {provider} but news_search.{provider}_api_key is not set"
+                )));
+            }
+            self.news_search.provider = Some(provider);
+        }
+        for key in [
+            &mut self.maps.google_api_key,
+            &mut self.package_tracking.track17_api_key,
+            &mut self.package_tracking.aftership_api_key,
+            &mut self.flight_status.aviationstack_api_key,
+            &mut self.browsing_profiles_key,
+        ] {
+            *key = key
+                .take()
+                .map(|k| k.trim().to_string())
+                .filter(|k| !k.is_empty());
+        }
+        for (name, url) in [
+            ("maps.nominatim_url", &mut self.maps.nominatim_url),
+            ("maps.osrm_url", &mut self.maps.osrm_url),
+            ("flight_status.adsb_url", &mut self.flight_status.adsb_url),
+        ] {
+            *url = url
+                .take()
+                .map(|u| u.trim().trim_end_matches('/').to_string())
+                .filter(|u| !u.is_empty());
+            if let Some(u) = url {
+                if !(u.starts_with("http://") || u.starts_with("https://")) {
+                    return Err(RayClawError::Config(format!(
+                        "{name} must be an http(s) URL, got '{u}'"
+                    )));
+                }
+            }
+        }
+        self.outbound_http.normalize()?;
+        if let Some(provider) = &self.package_tracking.provider {
+            let provider = provider.trim().to_lowercase();
+            let key_missing = match provider.as_str() {
+                "17track" => self.package_tracking.track17_api_key.is_none(),
+                "aftership" => self.package_tracking.aftership_api_key.is_none(),
+                _ => {
+                    return Err(RayClawError::Config(format!(
+                        "package_tracking.provider must be 17track or aftership, got '{provider}'"
+                    )))
+                }
+            };
+            if key_missing {
+                let key = if provider == "17track" {
+                    "track17_api_key"
+                } else {
+                    "aftership_api_key"
+                };
+                return Err(RayClawError::Config(format!(
+                    "package_tracking.provider is {provider} but package_tracking.{key} is not set"
+                )));
+            }
+            self.package_tracking.provider = Some(provider);
+        }
+        if let Some(gateway) = &mut self.email_gateway {
+            gateway.token = gateway.token.trim().to_string();
+            if gateway.token.is_empty() {
+                return Err(RayClawError::Config(
+                    "email_gateway.token is required".into(),
+                ));
+            }
+            for route in &mut gateway.routes {
+                route.address = route.address.trim().to_lowercase();
+            }
+        }
         if self.web_enabled && !is_local_web_host(&self.web_host) && self.web_auth_token.is_none() {
             return Err(RayClawError::Config(
                 "web_auth_token is required when web_enabled=true and web_host is not local".into(),
@@ -418,6 +1373,12 @@ impl Config {
         if self.memory_token_budget == 0 {
             self.memory_token_budget = default_memory_token_budget();
         }
+        if self.image_jpeg_quality == 0 || self.image_jpeg_quality > 100 {
+            self.image_jpeg_quality = default_image_jpeg_quality();
+        }
+        if self.workspace_cleanup_interval_mins == 0 {
+            self.workspace_cleanup_interval_mins = default_workspace_cleanup_interval_mins();
+        }
         for price in &mut self.model_prices {
             price.model = price.model.trim().to_string();
             if price.model.is_empty() {
@@ -437,6 +1398,50 @@ impl Config {
                     price.model
                 )));
             }
+            for (field, value) in [
+                (
+                    "cache_read_per_million_usd",
+                    price.cache_read_per_million_usd,
+                ),
+                (
+                    "cache_write_per_million_usd",
+                    price.cache_write_per_million_usd,
+                ),
+            ] {
+                if value.is_some_and(|v| !(v.is_finite() && v >= 0.0)) {
+                    return Err(RayClawError::Config(format!(
+                        "model_prices[{}].{field} must be >= 0",
+                        price.model
+                    )));
+                }
+            }
+        }
+
+        for limit in &mut self.model_limits {
+            limit.model = limit.model.trim().to_string();
+            if limit.model.is_empty() {
+                return Err(RayClawError::Config(
+                    "model_limits entries must include non-empty model".into(),
+                ));
+            }
+            if limit.context_window == Some(0) || limit.max_output_tokens == Some(0) {
+                return Err(RayClawError::Config(format!(
+                    "model_limits[{}] sizes must be > 0",
+                    limit.model
+                )));
+            }
+        }
+
+        for pattern in &self.secret_patterns {
+            if let Err(e) = regex::Regex::new(pattern) {
+                return Err(RayClawError::Config(format!(
+                    "secret_patterns entry {pattern:?} is not a valid regex: {e}"
+                )));
+            }
+        }
+
+        if let Ok(val) = std::env::var("RAYCLAW_LLM_TRACE") {
+            self.llm_trace = matches!(val.as_str(), "1" | "true" | "yes");
         }
 
         // Allow env var override for skip_tool_approval
@@ -521,6 +1526,52 @@ impl Config {
             }
         }
 
+        if let Some(signal) = self.channels.get("signal") {
+            let account = signal
+                .get("account")
+                .and_then(|v| v.as_str())
+                .map(str::trim)
+                .unwrap_or_default();
+            let digits = account.strip_prefix('+').unwrap_or_default();
+            if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+                return Err(RayClawError::Config(format!(
+                    "channels.signal.account must be the bot's phone number in international form (+15551234567), got '{account}'"
+                )));
+            }
+        }
+        if self.channels.contains_key("teams") && !self.web_enabled {
+            return Err(RayClawError::Config(
+                "channels.teams requires web_enabled=true: Teams delivers messages to /api/teams/messages on the web server".into(),
+            ));
+        }
+        if let Some(webhook) = self.channels.get("webhook") {
+            if !self.web_enabled {
+                return Err(RayClawError::Config(
+                    "channels.webhook requires web_enabled=true: messages are posted to /api/webhook/messages on the web server".into(),
+                ));
+            }
+            let field = |key: &str| {
+                webhook
+                    .get(key)
+                    .and_then(|v| v.as_str())
+                    .map(str::trim)
+                    .unwrap_or_default()
+            };
+            for key in ["token", "secret"] {
+                if field(key).is_empty() {
+                    return Err(RayClawError::Config(format!(
+                        "channels.webhook.{key} is required"
+                    )));
+                }
+            }
+            let callback_url = field("callback_url");
+            if !(callback_url.starts_with("http://") || callback_url.starts_with("https://")) {
+                return Err(RayClawError::Config(format!(
+                    "channels.webhook.callback_url must be an http(s) URL, got '{callback_url}'"
+                )));
+            }
+        }
+
         // Validate required fields
         let has_telegram =
             !self.telegram_bot_token.trim().is_empty() || self.channels.contains_key("telegram");
@@ -533,11 +1584,19 @@ impl Config {
         let has_slack = self.channels.contains_key("slack");
         let has_feishu = self.channels.contains_key("feishu");
         let has_weixin = self.channels.contains_key("weixin");
+        let has_signal = self.channels.contains_key("signal");
         let has_web = self.web_enabled || self.channels.contains_key("web");
 
-        if !(has_telegram || has_discord || has_slack || has_feishu || has_weixin || has_web) {
+        if !(has_telegram
+            || has_discord
+            || has_slack
+            || has_feishu
+            || has_weixin
+            || has_signal
+            || has_web)
+        {
             return Err(RayClawError::Config(
-                "At least one channel must be enabled: telegram_bot_token, discord_bot_token, channels.slack, channels.feishu, channels.weixin, or web_enabled=true".into(),
+                "At least one channel must be enabled: telegram_bot_token, discord_bot_token, channels.slack, channels.feishu, channels.weixin, channels.signal, or web_enabled=true".into(),
             ));
         }
         self.validate_llm()?;
@@ -553,11 +1612,72 @@ impl Config {
     }
 
     pub fn model_price(&self, model: &str) -> Option<&ModelPrice> {
-        let needle = model.trim();
-        self.model_prices
-            .iter()
-            .find(|p| p.model.eq_ignore_ascii_case(needle))
-            .or_else(|| self.model_prices.iter().find(|p| p.model == "*"))
+        find_model_price(&self.model_prices, model)
+    }
+
+    /// Context window and output size of `model`, from `model_limits` or
+    /// the built-in registry.
+    pub fn model_limits_for(&self, model: &str) -> Option<crate::model_limits::ModelLimits> {
+        crate::model_limits::resolve(&self.model_limits, model)
+    }
+
+    /// This config with a per-chat provider/model override applied. A
+    /// different model drops the single `aws_bedrock_inference_profile`,
+    /// which belongs to the configured one. A different provider must be a
+    /// known preset and gets its API key and base URL from
+    /// `provider_credentials`; only providers that need no API key may go
+    /// without an entry. Errors say what is missing.
+    pub fn with_model_override(
+        &self,
+        provider: Option<&str>,
+        model: &str,
+    ) -> Result<Config, String> {
+        let mut config = self.clone();
+        if let Some(provider) = provider.filter(|p| !p.eq_ignore_ascii_case(&self.llm_provider)) {
+            let preset_base_url = crate::setup_wizard::provider_default_base_url(provider)
+                .ok_or_else(|| format!("Unknown provider '{provider}'."))?;
+            let credentials = self.provider_credentials.get(provider);
+            let api_key = match credentials.map(|c| c.api_key.trim()) {
+                Some(key) if !key.is_empty() => key.to_string(),
+                _ if provider_allows_empty_api_key(provider) => String::new(),
+                _ => {
+                    return Err(format!(
+                        "No credentials for provider '{provider}': add its api_key under provider_credentials."
+                    ))
+                }
+            };
+            let base_url = credentials
+                .and_then(|c| c.llm_base_url.as_deref())
+                .map(str::trim)
+                .filter(|u| !u.is_empty())
+                .map(str::to_string);
+            // Azure and custom presets have no usable default URL
+            if base_url.is_none() && matches!(provider, "azure" | "custom") {
+                return Err(format!(
+                    "Provider '{provider}' needs an llm_base_url under provider_credentials."
+                ));
+            }
+            config.llm_provider = provider.to_string();
+            config.api_key = api_key;
+            config.llm_base_url =
+                base_url.or_else(|| Some(preset_base_url.to_string()).filter(|u| !u.is_empty()));
+        }
+        if model != config.model {
+            config.aws_bedrock_inference_profile = None;
+        }
+        config.model = model.to_string();
+        Ok(config)
+    }
+
+    /// Bedrock model ID to invoke for `model`: its entry in
+    /// `aws_bedrock_inference_profiles`, else `aws_bedrock_inference_profile`,
+    /// else the model itself.
+    pub fn bedrock_invocation_id(&self) -> String {
+        self.aws_bedrock_inference_profiles
+            .get(&self.model)
+            .or(self.aws_bedrock_inference_profile.as_ref())
+            .cloned()
+            .unwrap_or_else(|| self.model.clone())
     }
 
     pub fn estimate_cost_usd(
@@ -566,12 +1686,9 @@ impl Config {
         input_tokens: i64,
         output_tokens: i64,
     ) -> Option<f64> {
-        let price = self.model_price(model)?;
-        let in_tok = input_tokens.max(0) as f64;
-        let out_tok = output_tokens.max(0) as f64;
         Some(
-            (in_tok / 1_000_000.0) * price.input_per_million_usd
-                + (out_tok / 1_000_000.0) * price.output_per_million_usd,
+            self.model_price(model)?
+                .cost_usd(input_tokens, output_tokens),
         )
     }
 
@@ -620,19 +1737,35 @@ mod tests {
             control_chat_ids: vec![],
             max_session_messages: 40,
             compact_keep_recent: 20,
+            session_idle_expiry_hours: 0,
+            session_expiry_summary: true,
             discord_bot_token: None,
             discord_allowed_channels: vec![],
             show_thinking: false,
+            thinking: ThinkingConfig::default(),
+            inference: InferenceParams::default(),
+            chat_inference: std::collections::HashMap::new(),
+            openrouter: OpenRouterConfig::default(),
+            secret_patterns: vec![],
+            news_search: NewsSearchConfig::default(),
+            maps: MapsConfig::default(),
+            package_tracking: PackageTrackingConfig::default(),
+            flight_status: FlightStatusConfig::default(),
+            outbound_http: OutboundHttpConfig::default(),
+            browsing_profiles_key: None,
+            tool_output_budgets: std::collections::HashMap::new(),
             web_enabled: true,
             web_host: "127.0.0.1".into(),
             web_port: 10961,
             web_auth_token: None,
+            web_public_url: None,
             web_max_inflight_per_session: 2,
             web_max_requests_per_window: 8,
             web_rate_window_seconds: 10,
             web_run_history_limit: 512,
             web_session_idle_ttl_seconds: 300,
             model_prices: vec![],
+            model_limits: vec![],
             embedding_provider: None,
             embedding_api_key: None,
             embedding_base_url: None,
@@ -645,8 +1778,43 @@ mod tests {
             aws_secret_access_key: None,
             aws_session_token: None,
             aws_profile: None,
+            aws_bedrock_inference_profile: None,
+            aws_bedrock_inference_profiles: HashMap::new(),
+            aws_bedrock_latency: None,
+            aws_endpoint_url: None,
+            aws_use_fips_endpoint: false,
+            ollama_keep_alive: None,
+            email_gateway: None,
             soul_path: None,
+            system_prompt_template: None,
             skip_tool_approval: false,
+            tool_approval_timeout_secs: 300,
+            workspace_quota_mb: 0,
+            workspace_tmp_ttl_hours: 0,
+            workspace_cleanup_interval_mins: 60,
+            image_max_dimension: 1568,
+            image_jpeg_quality: 85,
+            chart_font_path: None,
+            tts_model: "tts-1".into(),
+            tts_voice: "alloy".into(),
+            stt_backend: "openai".into(),
+            whisper_cpp_binary: "whisper-cli".into(),
+            whisper_cpp_model: None,
+            max_concurrent_agent_runs: 8,
+            outage_queue_size: 50,
+            outage_queue_ttl_mins: 60,
+            message_debounce_ms: 0,
+            digest_dedup_days: 14,
+            llm_max_retries: 3,
+            llm_retry_max_delay_secs: 60,
+            llm_trace: false,
+            startup_preflight: true,
+            coordination_redis_url: None,
+            coordination_key_prefix: "rayclaw".into(),
+            cache_backend: "memory".into(),
+            queue_backend: "memory".into(),
+            response_cache_ttl_secs: 0,
+            provider_credentials: HashMap::new(),
             skills_dir: None,
             channels: HashMap::new(),
         }
@@ -806,6 +1974,95 @@ mod tests {
         assert!(msg.contains("Invalid timezone"));
     }
 
+    #[test]
+    fn test_post_deserialize_bedrock_latency() {
+        let yaml = "telegram_bot_token: tok\nbot_username: bot\napi_key: key\naws_bedrock_latency: Optimized\naws_bedrock_inference_profile: \" \"\n";
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.post_deserialize().unwrap();
+        assert_eq!(config.aws_bedrock_latency.as_deref(), Some("optimized"));
+        assert_eq!(config.aws_bedrock_inference_profile, None);
+
+        let yaml =
+            "telegram_bot_token: tok\nbot_username: bot\napi_key: key\naws_bedrock_latency: fast\n";
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        let err = config.post_deserialize().unwrap_err();
+        assert!(err.to_string().contains("aws_bedrock_latency"));
+    }
+
+    #[test]
+    fn test_bedrock_invocation_id() {
+        let yaml = "api_key: key\nllm_provider: bedrock\nmodel: anthropic.claude-sonnet-4-5-v2\naws_bedrock_inference_profiles:\n  anthropic.claude-sonnet-4-5-v2: \" us.anthropic.claude-sonnet-4-5-v2 \"\n  anthropic.claude-opus-4-1: \"\"\n";
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.post_deserialize().unwrap();
+        assert_eq!(config.aws_bedrock_inference_profiles.len(), 1);
+        assert_eq!(
+            config.bedrock_invocation_id(),
+            "us.anthropic.claude-sonnet-4-5-v2"
+        );
+
+        config.aws_bedrock_inference_profile =
+            Some("arn:aws:bedrock:us-east-1:123456789012:application-inference-profile/abc".into());
+        assert_eq!(
+            config.bedrock_invocation_id(),
+            "us.anthropic.claude-sonnet-4-5-v2"
+        );
+        let haiku = config
+            .with_model_override(None, "anthropic.claude-3-5-haiku")
+            .unwrap();
+        assert_eq!(haiku.bedrock_invocation_id(), "anthropic.claude-3-5-haiku");
+        config.aws_bedrock_inference_profiles.clear();
+        assert!(config.bedrock_invocation_id().ends_with("/abc"));
+    }
+
+    #[test]
+    fn test_with_model_override_provider_credentials() {
+        let yaml = "api_key: anthropic-key\nllm_base_url: https://proxy.example/anthropic\nprovider_credentials:\n  deepseek:\n    api_key: ds-key\n  azure:\n    api_key: az-key\n";
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+
+        let same = config
+            .with_model_override(Some("anthropic"), "claude-opus-4-1")
+            .unwrap();
+        assert_eq!(same.api_key, "anthropic-key");
+        assert_eq!(
+            same.llm_base_url.as_deref(),
+            Some("https://proxy.example/anthropic")
+        );
+
+        let deepseek = config
+            .with_model_override(Some("deepseek"), "deepseek-chat")
+            .unwrap();
+        assert_eq!(deepseek.llm_provider, "deepseek");
+        assert_eq!(deepseek.api_key, "ds-key");
+        assert_eq!(
+            deepseek.llm_base_url.as_deref(),
+            Some("https://api.deepseek.com/v1")
+        );
+
+        // Never falls back to the default provider's key
+        let err = config
+            .with_model_override(Some("openai"), "gpt-5")
+            .unwrap_err();
+        assert!(
+            err.contains("No credentials for provider 'openai'"),
+            "{err}"
+        );
+        let err = config.with_model_override(Some("nope"), "x").unwrap_err();
+        assert!(err.contains("Unknown provider"), "{err}");
+        let err = config
+            .with_model_override(Some("azure"), "gpt-5")
+            .unwrap_err();
+        assert!(err.contains("llm_base_url"), "{err}");
+
+        let ollama = config
+            .with_model_override(Some("ollama"), "llama3")
+            .unwrap();
+        assert!(ollama.api_key.is_empty());
+        assert_eq!(
+            ollama.llm_base_url.as_deref(),
+            Some(crate::llm_ollama::DEFAULT_OLLAMA_BASE_URL)
+        );
+    }
+
     #[test]
     fn test_post_deserialize_missing_api_key() {
         let yaml = "telegram_bot_token: tok\nbot_username: bot\n";
@@ -1030,6 +2287,257 @@ mod tests {
         let mut config: Config = serde_yaml::from_str(yaml).unwrap();
         config.post_deserialize().unwrap();
         assert_eq!(config.model, "llama3.2");
+        assert!(config.ollama_keep_alive.is_none());
+    }
+
+    #[test]
+    fn test_post_deserialize_ollama_keep_alive_trimmed() {
+        let yaml = "telegram_bot_token: tok\nbot_username: bot\nllm_provider: ollama\nollama_keep_alive: ' 30m '\n";
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.post_deserialize().unwrap();
+        assert_eq!(config.ollama_keep_alive.as_deref(), Some("30m"));
+    }
+
+    #[test]
+    fn test_post_deserialize_inference() {
+        let yaml = "telegram_bot_token: tok\nbot_username: bot\napi_key: key\ninference:\n  temperature: 0.2\n  stop_sequences: ['END', '']\nchat_inference:\n  -100123:\n    temperature: 0\n    top_k: 40\n";
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.post_deserialize().unwrap();
+        assert_eq!(config.inference.stop_sequences, vec!["END"]);
+        let merged = config.inference.merged(&config.chat_inference[&-100123]);
+        assert_eq!(merged.temperature, Some(0.0));
+        assert_eq!(merged.top_k, Some(40));
+        assert_eq!(merged.stop_sequences, vec!["END"]);
+
+        for bad in [
+            "inference:\n  temperature: 2.5\n",
+            "inference:\n  top_p: 0\n",
+            "chat_inference:\n  1:\n    top_k: 0\n",
+            "inference:\n  stop_sequences: [a, b, c, d, e]\n",
+        ] {
+            let yaml = format!("telegram_bot_token: tok\nbot_username: bot\napi_key: key\n{bad}");
+            let mut config: Config = serde_yaml::from_str(&yaml).unwrap();
+            assert!(config.post_deserialize().is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_post_deserialize_news_search() {
+        let yaml = "telegram_bot_token: tok\nbot_username: bot\napi_key: key\nnews_search:\n  provider: Brave\n  brave_api_key: ' bsa-key '\n  bing_api_key: ''\n";
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.post_deserialize().unwrap();
+        assert_eq!(config.news_search.provider.as_deref(), Some("brave"));
+        assert_eq!(config.news_search.brave_api_key.as_deref(), Some("bsa-key"));
+        assert!(config.news_search.bing_api_key.is_none());
+
+        let yaml = "telegram_bot_token: tok\nbot_username: bot\napi_key: key\nnews_search:\n  provider: bing\n";
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        let err = config.post_deserialize().unwrap_err();
+        assert!(err.to_string().contains("bing_api_key"));
+    }
+
+    #[test]
+    fn test_post_deserialize_maps() {
+        let yaml = "telegram_bot_token: tok\nbot_username: bot\napi_key: key\nmaps:\n  google_api_key: ' '\n  osrm_url: 'http://osrm.local:5000/ '\n";
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.post_deserialize().unwrap();
+        assert!(config.maps.google_api_key.is_none());
+        assert_eq!(
+            config.maps.osrm_url.as_deref(),
+            Some("http://osrm.local:5000")
+        );
+        assert!(config.maps.nominatim_url.is_none());
+
+        let yaml = "telegram_bot_token: tok\nbot_username: bot\napi_key: key\nmaps:\n  nominatim_url: nominatim.local\n";
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        let err = config.post_deserialize().unwrap_err();
+        assert!(err.to_string().contains("maps.nominatim_url"));
+    }
+
+    #[test]
+    fn test_post_deserialize_outbound_http() {
+        let yaml = "telegram_bot_token: tok\nbot_username: bot\napi_key: key\noutbound_http:\n  proxy: ' socks5h://127.0.0.1:1080 '\n  user_agent: ''\n  headers:\n    '*.Example.com':\n      X-Api-Key: ' abc '\n  tools:\n    web_fetch:\n      proxy: DIRECT\n";
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.post_deserialize().unwrap();
+        let http = &config.outbound_http;
+        assert_eq!(http.proxy.as_deref(), Some("socks5h://127.0.0.1:1080"));
+        assert!(http.user_agent.is_none());
+        assert_eq!(http.headers["example.com"]["X-Api-Key"], "abc");
+        assert_eq!(http.tools["web_fetch"].proxy.as_deref(), Some("direct"));
+
+        for (extra, expected) in [
+            ("  proxy: ftp://proxy:21\n", "outbound_http.proxy"),
+            (
+                "  tools:\n    web_serach:\n      proxy: direct\n",
+                "outbound_http.tools.web_serach",
+            ),
+            (
+                "  headers:\n    example.com:\n      'Bad Header': x\n",
+                "invalid header",
+            ),
+            (
+                "  politeness:\n    min_host_interval_ms: 600000\n",
+                "min_host_interval_ms",
+            ),
+        ] {
+            let yaml = format!(
+                "telegram_bot_token: tok\nbot_username: bot\napi_key: key\noutbound_http:\n{extra}"
+            );
+            let mut config: Config = serde_yaml::from_str(&yaml).unwrap();
+            let err = config.post_deserialize().unwrap_err();
+            assert!(err.to_string().contains(expected), "{err}");
+        }
+    }
+
+    #[test]
+    fn test_post_deserialize_package_tracking() {
+        let yaml = "telegram_bot_token: tok\nbot_username: bot\napi_key: key\npackage_tracking:\n  provider: AfterShip\n  aftership_api_key: ' as-key '\n  track17_api_key: ''\n";
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.post_deserialize().unwrap();
+        assert_eq!(
+            config.package_tracking.provider.as_deref(),
+            Some("aftership")
+        );
+        assert_eq!(
+            config.package_tracking.aftership_api_key.as_deref(),
+            Some("as-key")
+        );
+        assert!(config.package_tracking.track17_api_key.is_none());
+
+        let yaml = "telegram_bot_token: tok\nbot_username: bot\napi_key: key\npackage_tracking:\n  provider: 17track\n";
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        let err = config.post_deserialize().unwrap_err();
+        assert!(err.to_string().contains("track17_api_key"));
+
+        let yaml = "telegram_bot_token: tok\nbot_username: bot\napi_key: key\nflight_status:\n  adsb_url: ftp://adsb\n";
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        let err = config.post_deserialize().unwrap_err();
+        assert!(err.to_string().contains("flight_status.adsb_url"));
+    }
+
+    #[test]
+    fn test_post_deserialize_openrouter() {
+        let yaml = "telegram_bot_token: tok\nbot_username: bot\napi_key: key\nllm_provider: openrouter\nmodel: anthropic/claude-sonnet-4.5\nopenrouter:\n  fallback_models: [' openai/gpt-5.2 ', '', anthropic/claude-sonnet-4.5]\n  provider:\n    sort: Price\n    data_collection: deny\n";
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.post_deserialize().unwrap();
+        assert_eq!(config.openrouter.fallback_models, vec!["openai/gpt-5.2"]);
+        let prefs = config.openrouter.provider.unwrap();
+        assert_eq!(prefs.sort.as_deref(), Some("price"));
+        assert_eq!(prefs.data_collection.as_deref(), Some("deny"));
+
+        let yaml =
+            "telegram_bot_token: tok\nbot_username: bot\napi_key: key\nllm_provider: openrouter\n";
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.post_deserialize().unwrap();
+        assert_eq!(config.model, "openrouter/auto");
+
+        let yaml = "telegram_bot_token: tok\nbot_username: bot\napi_key: key\nopenrouter:\n  provider:\n    sort: cheapest\n";
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        assert!(config.post_deserialize().is_err());
+    }
+
+    #[test]
+    fn test_post_deserialize_thinking() {
+        let yaml = "telegram_bot_token: tok\nbot_username: bot\napi_key: key\nthinking:\n  enabled: true\n  budget_tokens: 100\n  effort: ' High '\n";
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.post_deserialize().unwrap();
+        assert_eq!(config.thinking.budget_tokens, MIN_THINKING_BUDGET_TOKENS);
+        assert_eq!(config.thinking.effort(), "high");
+
+        let yaml = "telegram_bot_token: tok\nbot_username: bot\napi_key: key\nmax_tokens: 4096\nthinking:\n  enabled: true\n  budget_tokens: 8000\n";
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        let err = config.post_deserialize().unwrap_err();
+        assert!(err.to_string().contains("must be below max_tokens"));
+
+        let yaml = "telegram_bot_token: tok\nbot_username: bot\napi_key: key\nthinking:\n  effort: extreme\n";
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        assert!(config.post_deserialize().is_err());
+
+        let mut thinking = ThinkingConfig::default();
+        assert!(!thinking.enabled);
+        assert_eq!(thinking.effort(), "medium");
+        thinking.budget_tokens = 2048;
+        assert_eq!(thinking.effort(), "low");
+        thinking.budget_tokens = 32000;
+        assert_eq!(thinking.effort(), "high");
+    }
+
+    #[test]
+    fn test_post_deserialize_email_gateway() {
+        let yaml = "telegram_bot_token: tok\nbot_username: bot\napi_key: key\nemail_gateway:\n  token: ' secret '\n  routes:\n    - address: ' Tasks@Bot.Example '\n      chat_id: 5\n";
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.post_deserialize().unwrap();
+        let gateway = config.email_gateway.unwrap();
+        assert_eq!(gateway.token, "secret");
+        assert_eq!(gateway.routes[0].address, "tasks@bot.example");
+        assert!(gateway.routes[0].allowed_senders.is_empty());
+
+        let yaml = "telegram_bot_token: tok\nbot_username: bot\napi_key: key\nemail_gateway:\n  token: ''\n";
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        let err = config.post_deserialize().unwrap_err();
+        assert!(err.to_string().contains("email_gateway.token"));
+    }
+
+    #[test]
+    fn test_post_deserialize_signal_account() {
+        let yaml = "bot_username: bot\napi_key: key\nweb_enabled: false\nchannels:\n  signal:\n    account: '+15551234567'\n";
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.post_deserialize().unwrap();
+
+        for account in ["", "15551234567", "+1 555 123"] {
+            let yaml = format!(
+                "bot_username: bot\napi_key: key\nchannels:\n  signal:\n    account: '{account}'\n"
+            );
+            let mut config: Config = serde_yaml::from_str(&yaml).unwrap();
+            let err = config.post_deserialize().unwrap_err();
+            assert!(err.to_string().contains("channels.signal.account"), "{err}");
+        }
+    }
+
+    #[test]
+    fn test_post_deserialize_teams_requires_web() {
+        let yaml = "bot_username: bot\napi_key: key\nweb_enabled: false\ntelegram_bot_token: tok\nchannels:\n  teams:\n    app_id: app\n    app_password: secret\n";
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        let err = config.post_deserialize().unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("channels.teams requires web_enabled"));
+
+        let yaml = "bot_username: bot\napi_key: key\nchannels:\n  teams:\n    app_id: app\n    app_password: secret\n";
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.post_deserialize().unwrap();
+    }
+
+    #[test]
+    fn test_post_deserialize_webhook_channel() {
+        let base = "bot_username: bot\napi_key: key\nchannels:\n  webhook:\n    token: tok\n    secret: s3cret\n";
+        let mut config: Config = serde_yaml::from_str(&format!(
+            "{base}    callback_url: https://hooks.example/rayclaw\n"
+        ))
+        .unwrap();
+        config.post_deserialize().unwrap();
+
+        let mut config: Config =
+            serde_yaml::from_str(&format!("{base}    callback_url: hooks.example\n")).unwrap();
+        let err = config.post_deserialize().unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("callback_url must be an http(s) URL"));
+
+        let yaml = "bot_username: bot\napi_key: key\nchannels:\n  webhook:\n    token: tok\n    callback_url: https://hooks.example\n";
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        let err = config.post_deserialize().unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("channels.webhook.secret is required"));
+    }
+
+    #[test]
+    fn test_post_deserialize_invalid_secret_pattern() {
+        let yaml = "telegram_bot_token: tok\nbot_username: bot\napi_key: key\nsecret_patterns:\n  - 'tok_[0-9'\n";
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        let err = config.post_deserialize().unwrap_err();
+        assert!(err.to_string().contains("secret_patterns"));
     }
 
     #[test]
@@ -1061,10 +2569,82 @@ mod tests {
 
     #[test]
     fn test_post_deserialize_web_non_local_with_token_ok() {
-        let yaml = "telegram_bot_token: tok\nbot_username: bot\napi_key: key\nweb_enabled: true\nweb_host: 0.0.0.0\nweb_auth_token: token123\n";
+        let yaml = "telegram_bot_token: tok\nbot_username: bot\napi_key: key\nweb_enabled: true\nweb_host: 0.0.0.0\nweb_auth_token: token123\nweb_public_url: \" https://bot.example.com/ \"\n";
         let mut config: Config = serde_yaml::from_str(yaml).unwrap();
         config.post_deserialize().unwrap();
         assert_eq!(config.web_auth_token.as_deref(), Some("token123"));
+        assert_eq!(
+            config.web_public_url.as_deref(),
+            Some("https://bot.example.com")
+        );
+    }
+
+    #[test]
+    fn test_post_deserialize_coordination() {
+        let yaml = "telegram_bot_token: tok\nbot_username: bot\napi_key: key\ncoordination_redis_url: ' redis://:secret@redis:6379/1 '\ncoordination_key_prefix: ' '\n";
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.post_deserialize().unwrap();
+        assert_eq!(
+            config.coordination_redis_url.as_deref(),
+            Some("redis://:secret@redis:6379/1")
+        );
+        assert_eq!(config.coordination_key_prefix, "rayclaw");
+
+        let yaml = "telegram_bot_token: tok\nbot_username: bot\napi_key: key\ncoordination_redis_url: 'postgres://db/rayclaw'\n";
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        let err = config.post_deserialize().unwrap_err();
+        assert!(err.to_string().contains("coordination_redis_url"));
+
+        let yaml =
+            "telegram_bot_token: tok\nbot_username: bot\napi_key: key\ncache_backend: ' Redis '\n";
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        let err = config.post_deserialize().unwrap_err();
+        assert!(err.to_string().contains("requires coordination_redis_url"));
+
+        let yaml =
+            "telegram_bot_token: tok\nbot_username: bot\napi_key: key\ncache_backend: memcached\n";
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        assert!(config.post_deserialize().is_err());
+
+        let yaml = "telegram_bot_token: tok\nbot_username: bot\napi_key: key\ncoordination_redis_url: redis://redis\ncache_backend: ' Redis '\n";
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.post_deserialize().unwrap();
+        assert_eq!(config.cache_backend, "redis");
+        assert_eq!(config.queue_backend, "memory");
+
+        let yaml =
+            "telegram_bot_token: tok\nbot_username: bot\napi_key: key\nqueue_backend: redis\n";
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        let err = config.post_deserialize().unwrap_err();
+        assert!(err.to_string().contains("queue_backend: redis requires"));
+    }
+
+    #[test]
+    fn test_stt_backend_validation() {
+        let base = "telegram_bot_token: tok\nbot_username: bot\napi_key: key\n";
+        let mut config: Config = serde_yaml::from_str(base).unwrap();
+        config.post_deserialize().unwrap();
+        assert_eq!(config.stt_backend, "openai");
+
+        let yaml = format!("{base}stt_backend: whisper_cpp\n");
+        let mut config: Config = serde_yaml::from_str(&yaml).unwrap();
+        let err = config.post_deserialize().unwrap_err();
+        assert!(err.to_string().contains("requires whisper_cpp_model"));
+
+        let yaml = format!(
+            "{base}stt_backend: ' Whisper_CPP '\nwhisper_cpp_model: ' /models/ggml-base.en.bin '\n"
+        );
+        let mut config: Config = serde_yaml::from_str(&yaml).unwrap();
+        config.post_deserialize().unwrap();
+        assert_eq!(config.stt_backend, "whisper_cpp");
+        assert_eq!(
+            config.whisper_cpp_model.as_deref(),
+            Some("/models/ggml-base.en.bin")
+        );
+
+        let yaml = format!("{base}stt_backend: vosk\n");
+        let mut config: Config = serde_yaml::from_str(&yaml).unwrap();
+        assert!(config.post_deserialize().is_err());
     }
 
     #[test]
@@ -1084,6 +2664,19 @@ model_prices:
             .estimate_cost_usd("claude-sonnet-4-5-20250929", 1000, 2000)
             .unwrap();
         assert!((est - 0.033).abs() < 1e-9);
+
+        // Cache traffic falls back to the input price, or uses its own
+        let usage = crate::llm_types::Usage {
+            input_tokens: 1000,
+            output_tokens: 2000,
+            cache_read_input_tokens: 1_000_000,
+            cache_creation_input_tokens: 0,
+        };
+        let price = config.model_price("claude-sonnet-4-5-20250929").unwrap();
+        assert!((price.usage_cost_usd(&usage) - 3.033).abs() < 1e-9);
+        let mut price = price.clone();
+        price.cache_read_per_million_usd = Some(0.3);
+        assert!((price.usage_cost_usd(&usage) - 0.333).abs() < 1e-9);
     }
 
     #[test]
@@ -1104,6 +2697,29 @@ model_prices:
             .contains("model_prices entries must include non-empty model"));
     }
 
+    #[test]
+    fn test_model_limits_override_registry() {
+        let yaml = r#"
+telegram_bot_token: tok
+bot_username: bot
+api_key: key
+model: my-local-model
+model_limits:
+  - model: " my-local-model "
+    context_window: 32768
+"#;
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.post_deserialize().unwrap();
+        let limits = config.model_limits_for("my-local-model").unwrap();
+        assert_eq!(limits.context_window, Some(32768));
+        assert_eq!(limits.max_output_tokens, None);
+        assert!(config.model_limits_for("gpt-4o").is_some());
+
+        config.model_limits[0].max_output_tokens = Some(0);
+        let err = config.post_deserialize().unwrap_err();
+        assert!(err.to_string().contains("sizes must be > 0"));
+    }
+
     #[test]
     fn test_config_yaml_with_all_optional_fields() {
         let yaml = r#"
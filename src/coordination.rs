@@ -0,0 +1,406 @@
+//! Coordination between RayClaw instances that share storage (HA setups).
+//!
+//! Work that must happen once — a scheduled task run, an inbound Feishu,
+//! Teams or webhook message — is claimed under a key before it is handled.
+//! With `coordination_redis_url` set, a claim is a Redis `SET key NX PX ttl`,
+//! so only the first instance gets it; claims are never released and simply
+//! expire. Without it every claim succeeds, which is right for a single
+//! instance.
+//!
+//! If Redis can't be reached, or doesn't answer within two seconds, the
+//! claim is granted with a warning: running something twice is better than
+//! not running it at all.
+//!
+//! With `cache_backend: redis` the web API's per-session rate limits are
+//! kept in the same Redis ([`SharedRateLimiter`]), so a session can't get
+//! around them by reaching a different instance, and so is the response
+//! cache. With `queue_backend: redis`, `max_concurrent_agent_runs` caps
+//! agent runs across all instances ([`SharedRunSlots`]).
+//!
+//! Everything goes through one [`SharedRedis`] connection per URL.
+
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Duration;
+
+use tracing::warn;
+
+use crate::config::Config;
+use crate::error::RayClawError;
+
+/// How long a Redis command may take, connecting included, before it fails.
+/// Inbound handlers wait on claims, so it has to stay short.
+const REDIS_TIMEOUT: Duration = Duration::from_secs(2);
+
+static CONNECTIONS: LazyLock<Mutex<HashMap<String, SharedRedis>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// A multiplexed Redis connection shared by everything in the process that
+/// talks to the same URL. It connects on first use and again after an error,
+/// and every command is bounded by a two-second timeout.
+#[derive(Clone)]
+pub struct SharedRedis {
+    inner: Arc<SharedRedisInner>,
+}
+
+struct SharedRedisInner {
+    client: redis::Client,
+    conn: tokio::sync::Mutex<Option<redis::aio::MultiplexedConnection>>,
+}
+
+impl SharedRedis {
+    /// The process-wide connection for `url`.
+    pub fn for_url(url: &str) -> redis::RedisResult<Self> {
+        let mut connections = CONNECTIONS.lock().unwrap();
+        if let Some(shared) = connections.get(url) {
+            return Ok(shared.clone());
+        }
+        let shared = SharedRedis {
+            inner: Arc::new(SharedRedisInner {
+                client: redis::Client::open(url)?,
+                conn: tokio::sync::Mutex::new(None),
+            }),
+        };
+        connections.insert(url.to_string(), shared.clone());
+        Ok(shared)
+    }
+
+    /// Run `cmd`, failing after [`REDIS_TIMEOUT`]. A failed or timed-out
+    /// command drops the connection so the next one reconnects.
+    pub async fn query<T: redis::FromRedisValue>(&self, cmd: &redis::Cmd) -> redis::RedisResult<T> {
+        let result = tokio::time::timeout(REDIS_TIMEOUT, async {
+            let mut conn = self.connection().await?;
+            cmd.query_async::<T>(&mut conn).await
+        })
+        .await
+        .unwrap_or_else(|_| {
+            Err(
+                std::io::Error::new(std::io::ErrorKind::TimedOut, "Redis did not answer in time")
+                    .into(),
+            )
+        });
+        if let Err(e) = &result {
+            if e.is_io_error() || e.is_connection_dropped() || e.is_timeout() {
+                *self.inner.conn.lock().await = None;
+            }
+        }
+        result
+    }
+
+    async fn connection(&self) -> redis::RedisResult<redis::aio::MultiplexedConnection> {
+        let mut conn = self.inner.conn.lock().await;
+        if let Some(conn) = conn.as_ref() {
+            return Ok(conn.clone());
+        }
+        let fresh = self.inner.client.get_multiplexed_async_connection().await?;
+        *conn = Some(fresh.clone());
+        Ok(fresh)
+    }
+}
+
+pub struct Coordinator {
+    redis: Option<SharedRedis>,
+    key_prefix: String,
+    /// Stored as the claim value so a key shows which instance took it
+    instance_id: String,
+}
+
+impl Coordinator {
+    /// A coordinator that grants every claim.
+    pub fn local() -> Self {
+        Coordinator {
+            redis: None,
+            key_prefix: String::new(),
+            instance_id: instance_id(),
+        }
+    }
+
+    pub fn from_config(config: &Config) -> Result<Self, RayClawError> {
+        let Some(url) = config.coordination_redis_url.as_deref() else {
+            return Ok(Self::local());
+        };
+        let redis = SharedRedis::for_url(url).map_err(|e| {
+            RayClawError::Config(format!(
+                "coordination_redis_url is not a valid Redis URL: {e}"
+            ))
+        })?;
+        Ok(Coordinator {
+            redis: Some(redis),
+            key_prefix: config.coordination_key_prefix.clone(),
+            instance_id: instance_id(),
+        })
+    }
+
+    pub fn is_distributed(&self) -> bool {
+        self.redis.is_some()
+    }
+
+    fn full_key(&self, key: &str) -> String {
+        format!("{}:{key}", self.key_prefix)
+    }
+
+    /// Claim `key` for `ttl`. Returns false if another instance already has it.
+    pub async fn claim(&self, key: &str, ttl: Duration) -> bool {
+        let Some(redis) = &self.redis else {
+            return true;
+        };
+        let key = self.full_key(key);
+        let mut cmd = redis::cmd("SET");
+        cmd.arg(&key)
+            .arg(&self.instance_id)
+            .arg("NX")
+            .arg("PX")
+            .arg(ttl.as_millis().max(1) as u64);
+        match redis.query::<Option<String>>(&cmd).await {
+            Ok(reply) => reply.is_some(),
+            Err(e) => {
+                warn!("Coordination: claiming {key} failed, handling it here anyway: {e}");
+                true
+            }
+        }
+    }
+}
+
+/// Result of a shared rate-limit check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Admission {
+    Admitted,
+    TooManyInflight,
+    RateLimited,
+}
+
+/// In-flight count and sliding request window per key, checked and updated
+/// atomically. Times come from the Redis server so instance clocks don't matter.
+const BEGIN_SCRIPT: &str = r#"
+local t = redis.call('TIME')
+local now = tonumber(t[1]) * 1000 + math.floor(tonumber(t[2]) / 1000)
+local window = tonumber(ARGV[1])
+redis.call('ZREMRANGEBYSCORE', KEYS[2], '-inf', now - window)
+if tonumber(redis.call('GET', KEYS[1]) or '0') >= tonumber(ARGV[2]) then
+    return 1
+end
+if redis.call('ZCARD', KEYS[2]) >= tonumber(ARGV[3]) then
+    return 2
+end
+redis.call('INCR', KEYS[1])
+redis.call('PEXPIRE', KEYS[1], ARGV[4])
+redis.call('ZADD', KEYS[2], now, ARGV[5])
+redis.call('PEXPIRE', KEYS[2], window)
+return 0
+"#;
+
+const END_SCRIPT: &str = r#"
+if redis.call('DECR', KEYS[1]) <= 0 then
+    redis.call('DEL', KEYS[1])
+end
+return 0
+"#;
+
+/// An in-flight count outlives a crashed instance by at most this long.
+const INFLIGHT_TTL: Duration = Duration::from_secs(3600);
+
+/// Rate limits shared by every instance through Redis (`cache_backend: redis`).
+#[derive(Clone)]
+pub struct SharedRateLimiter {
+    redis: SharedRedis,
+    key_prefix: String,
+}
+
+impl SharedRateLimiter {
+    /// `None` unless `cache_backend` is `redis`.
+    pub fn from_config(config: &Config) -> Option<Self> {
+        if config.cache_backend != "redis" {
+            return None;
+        }
+        let url = config.coordination_redis_url.as_deref()?;
+        match SharedRedis::for_url(url) {
+            Ok(redis) => Some(SharedRateLimiter {
+                redis,
+                key_prefix: config.coordination_key_prefix.clone(),
+            }),
+            Err(e) => {
+                warn!("Shared rate limits disabled, invalid Redis URL: {e}");
+                None
+            }
+        }
+    }
+
+    fn keys(&self, key: &str) -> (String, String) {
+        (
+            format!("{}:inflight:{key}", self.key_prefix),
+            format!("{}:requests:{key}", self.key_prefix),
+        )
+    }
+
+    /// Start a request under `key` if it has fewer than `max_inflight`
+    /// running and fewer than `max_requests` started within `window`.
+    pub async fn begin(
+        &self,
+        key: &str,
+        max_inflight: usize,
+        max_requests: usize,
+        window: Duration,
+    ) -> redis::RedisResult<Admission> {
+        let (inflight_key, requests_key) = self.keys(key);
+        let mut cmd = redis::cmd("EVAL");
+        cmd.arg(BEGIN_SCRIPT)
+            .arg(2)
+            .arg(&inflight_key)
+            .arg(&requests_key)
+            .arg(window.as_millis().max(1) as u64)
+            .arg(max_inflight)
+            .arg(max_requests)
+            .arg(INFLIGHT_TTL.as_millis() as u64)
+            .arg(uuid::Uuid::new_v4().to_string());
+        let code: i64 = self.redis.query(&cmd).await?;
+        Ok(match code {
+            0 => Admission::Admitted,
+            1 => Admission::TooManyInflight,
+            _ => Admission::RateLimited,
+        })
+    }
+
+    /// Finish a request started with [`Self::begin`].
+    pub async fn end(&self, key: &str) -> redis::RedisResult<()> {
+        let (inflight_key, _) = self.keys(key);
+        let mut cmd = redis::cmd("EVAL");
+        cmd.arg(END_SCRIPT).arg(1).arg(&inflight_key);
+        self.redis.query(&cmd).await
+    }
+}
+
+/// Takes a run slot if fewer than ARGV[1] are held. Slots older than ARGV[2]
+/// ms belong to runs on an instance that died and are dropped first.
+const SLOT_ACQUIRE_SCRIPT: &str = r#"
+local t = redis.call('TIME')
+local now = tonumber(t[1]) * 1000 + math.floor(tonumber(t[2]) / 1000)
+redis.call('ZREMRANGEBYSCORE', KEYS[1], '-inf', now - tonumber(ARGV[2]))
+if redis.call('ZCARD', KEYS[1]) >= tonumber(ARGV[1]) then
+    return 0
+end
+redis.call('ZADD', KEYS[1], now, ARGV[3])
+redis.call('PEXPIRE', KEYS[1], ARGV[2])
+return 1
+"#;
+
+/// Agent run slots counted across instances (`queue_backend: redis`).
+#[derive(Clone)]
+pub struct SharedRunSlots {
+    redis: SharedRedis,
+    key: String,
+}
+
+impl SharedRunSlots {
+    /// `None` unless `queue_backend` is `redis`.
+    pub fn from_config(config: &Config) -> Option<Self> {
+        if config.queue_backend != "redis" {
+            return None;
+        }
+        let url = config.coordination_redis_url.as_deref()?;
+        match SharedRedis::for_url(url) {
+            Ok(redis) => Some(SharedRunSlots {
+                redis,
+                key: format!("{}:run_slots", config.coordination_key_prefix),
+            }),
+            Err(e) => {
+                warn!("Shared run slots disabled, invalid Redis URL: {e}");
+                None
+            }
+        }
+    }
+
+    /// Take a slot named `slot_id` if fewer than `limit` are taken.
+    pub async fn try_acquire(&self, slot_id: &str, limit: usize) -> redis::RedisResult<bool> {
+        let mut cmd = redis::cmd("EVAL");
+        cmd.arg(SLOT_ACQUIRE_SCRIPT)
+            .arg(1)
+            .arg(&self.key)
+            .arg(limit)
+            .arg(INFLIGHT_TTL.as_millis() as u64)
+            .arg(slot_id);
+        let taken: i64 = self.redis.query(&cmd).await?;
+        Ok(taken == 1)
+    }
+
+    pub async fn release(&self, slot_id: &str) -> redis::RedisResult<()> {
+        let mut cmd = redis::cmd("ZREM");
+        cmd.arg(&self.key).arg(slot_id);
+        self.redis.query(&cmd).await
+    }
+}
+
+fn instance_id() -> String {
+    let host = std::env::var("HOSTNAME").unwrap_or_else(|_| "rayclaw".into());
+    format!("{host}:{}", std::process::id())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_local_coordinator_grants_every_claim() {
+        let c = Coordinator::local();
+        assert!(!c.is_distributed());
+        assert!(c.claim("task:1", Duration::from_secs(60)).await);
+        assert!(c.claim("task:1", Duration::from_secs(60)).await);
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_redis_fails_open() {
+        let mut config: Config = serde_yaml::from_str(
+            "telegram_bot_token: tok\nbot_username: bot\napi_key: key\ncoordination_redis_url: redis://127.0.0.1:1/0\ncoordination_key_prefix: ha\n",
+        )
+        .unwrap();
+        config.post_deserialize().unwrap();
+        let c = Coordinator::from_config(&config).unwrap();
+        assert!(c.is_distributed());
+        assert_eq!(c.full_key("task:1"), "ha:task:1");
+        assert!(c.claim("task:1", Duration::from_secs(60)).await);
+    }
+
+    #[tokio::test]
+    async fn test_shared_rate_limiter_config() {
+        let yaml = "telegram_bot_token: tok\nbot_username: bot\napi_key: key\ncoordination_redis_url: redis://127.0.0.1:1/0\n";
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.post_deserialize().unwrap();
+        assert!(SharedRateLimiter::from_config(&config).is_none());
+
+        config.cache_backend = "redis".into();
+        let limiter = SharedRateLimiter::from_config(&config).unwrap();
+        assert_eq!(
+            limiter.keys("web:main"),
+            (
+                "rayclaw:inflight:web:main".to_string(),
+                "rayclaw:requests:web:main".to_string()
+            )
+        );
+        assert!(limiter
+            .begin("web:main", 1, 1, Duration::from_secs(10))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_shared_run_slots_config() {
+        let yaml = "telegram_bot_token: tok\nbot_username: bot\napi_key: key\ncoordination_redis_url: redis://127.0.0.1:1/0\ncoordination_key_prefix: ha\n";
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.post_deserialize().unwrap();
+        assert!(SharedRunSlots::from_config(&config).is_none());
+
+        config.queue_backend = "redis".into();
+        let slots = SharedRunSlots::from_config(&config).unwrap();
+        assert_eq!(slots.key, "ha:run_slots");
+        assert!(slots.try_acquire("run-1", 2).await.is_err());
+    }
+
+    #[test]
+    fn test_shared_redis_is_reused_per_url() {
+        let a = SharedRedis::for_url("redis://127.0.0.1:1/0").unwrap();
+        let b = SharedRedis::for_url("redis://127.0.0.1:1/0").unwrap();
+        let c = SharedRedis::for_url("redis://127.0.0.1:1/1").unwrap();
+        assert!(Arc::ptr_eq(&a.inner, &b.inner));
+        assert!(!Arc::ptr_eq(&a.inner, &c.inner));
+        assert!(SharedRedis::for_url("postgres://db").is_err());
+    }
+}
@@ -39,6 +39,8 @@ pub struct StoredMessage {
     pub content: String,
     pub is_from_bot: bool,
     pub timestamp: String,
+    /// Forum topic (Telegram `message_thread_id`) the message was posted in
+    pub thread_id: Option<i64>,
 }
 
 #[derive(Debug, Clone)]
@@ -72,6 +74,42 @@ pub struct LlmUsageSummary {
     pub last_request_at: Option<String>,
 }
 
+/// One LLM call to record in `llm_usage_logs`.
+#[derive(Debug, Clone, Default)]
+pub struct LlmUsageEntry {
+    pub chat_id: i64,
+    pub caller_channel: String,
+    pub provider: String,
+    pub model: String,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    pub cache_read_tokens: i64,
+    pub cache_write_tokens: i64,
+    /// `None` when `model_prices` has no entry for the model
+    pub cost_usd: Option<f64>,
+    pub request_kind: String,
+}
+
+/// Cache traffic and estimated cost over a period.
+#[derive(Debug, Clone, Default)]
+pub struct LlmCostSummary {
+    pub cache_read_tokens: i64,
+    pub cache_write_tokens: i64,
+    pub cost_usd: f64,
+    /// Calls without a price, left out of `cost_usd`
+    pub unpriced_requests: i64,
+}
+
+/// LLM usage for one UTC day.
+#[derive(Debug, Clone)]
+pub struct LlmDailyUsage {
+    /// `YYYY-MM-DD`
+    pub day: String,
+    pub requests: i64,
+    pub total_tokens: i64,
+    pub cost_usd: f64,
+}
+
 #[derive(Debug, Clone)]
 pub struct LlmModelUsageSummary {
     pub model: String,
@@ -81,6 +119,14 @@ pub struct LlmModelUsageSummary {
     pub total_tokens: i64,
 }
 
+#[derive(Debug, Clone)]
+pub struct ToolUsageSummary {
+    pub tool_name: String,
+    pub calls: i64,
+    pub errors: i64,
+    pub avg_duration_ms: i64,
+}
+
 #[derive(Debug, Clone)]
 pub struct Memory {
     pub id: i64,
@@ -140,7 +186,158 @@ pub struct MemoryInjectionLog {
     pub tokens_est: i64,
 }
 
-const SCHEMA_VERSION_CURRENT: i64 = 4;
+/// One ACP prompt run, as persisted in `acp_runs`.
+#[derive(Debug, Clone)]
+pub struct AcpRun {
+    pub id: i64,
+    pub session_id: String,
+    pub chat_id: Option<i64>,
+    pub agent_id: String,
+    pub prompt: String,
+    pub messages: Vec<String>,
+    /// `{"name", "input"}` objects, in call order
+    pub tool_calls: Vec<serde_json::Value>,
+    pub files_changed: Vec<String>,
+    pub completed: bool,
+    pub context_reset: bool,
+    pub duration_ms: i64,
+    pub created_at: String,
+}
+
+/// An open ACP session as persisted in `acp_sessions`, with the chats bound
+/// to it, so it can be restored after a restart.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AcpSessionRecord {
+    pub session_id: String,
+    pub agent_id: String,
+    pub workspace: String,
+    /// The agent's own session ID, used for `session/load`
+    pub acp_session_id: Option<String>,
+    pub auto_approve: bool,
+    pub chat_ids: Vec<i64>,
+    pub created_at: String,
+}
+
+/// One outbound bot message and how its delivery went, as persisted in
+/// `deliveries`. Status is `queued`, `sent`, `failed` or `edited`.
+#[derive(Debug, Clone)]
+pub struct Delivery {
+    pub id: i64,
+    pub chat_id: i64,
+    pub channel: String,
+    pub content: String,
+    pub status: String,
+    pub error: Option<String>,
+    /// Whether a failed delivery is worth retrying (false for blocked/kicked)
+    pub retryable: bool,
+    pub attempts: i64,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// What the bot knows about one participant of a chat, keyed by the
+/// `sender_name` their messages are stored under.
+#[derive(Debug, Clone)]
+pub struct GroupMember {
+    pub chat_id: i64,
+    pub member_name: String,
+    pub display_name: Option<String>,
+    pub role: Option<String>,
+    pub notes: Option<String>,
+    pub message_count: i64,
+    pub first_seen: String,
+    pub last_seen: String,
+}
+
+/// A webpage watched for changes on behalf of a chat, as persisted in
+/// `url_monitors`. Status is `active` or `paused`.
+#[derive(Debug, Clone)]
+pub struct UrlMonitor {
+    pub id: i64,
+    pub chat_id: i64,
+    pub url: String,
+    /// CSS selector narrowing the watched content; whole page when `None`
+    pub selector: Option<String>,
+    pub interval_mins: i64,
+    pub status: String,
+    /// Normalized text seen at the last successful check
+    pub last_content: Option<String>,
+    pub last_error: Option<String>,
+    pub last_checked: Option<String>,
+    pub last_changed: Option<String>,
+    pub next_check: String,
+    pub change_count: i64,
+    pub created_at: String,
+}
+
+/// A named login the web tools can browse with, as persisted in
+/// `browsing_profiles`. The password and cookie jar are stored sealed (see
+/// `browsing_profiles::ProfileVault`).
+#[derive(Debug, Clone)]
+pub struct BrowsingProfile {
+    pub chat_id: i64,
+    pub name: String,
+    /// Sites the profile's credentials and cookies may be sent to; a domain
+    /// also covers its subdomains
+    pub domains: Vec<String>,
+    pub username: Option<String>,
+    pub sealed_password: Option<String>,
+    pub sealed_cookies: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// A chat's model choice overriding the configured `llm_provider`/`model`,
+/// as persisted in `chat_model_overrides`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChatModelOverride {
+    /// `None` keeps the configured provider
+    pub provider: Option<String>,
+    pub model: String,
+}
+
+/// How a chat wants proactive messages (scheduled task results, monitor
+/// alerts) delivered, as persisted in `notification_settings`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NotificationSettings {
+    pub chat_id: i64,
+    /// Drop successful scheduled-task results (failures still arrive)
+    pub mute_task_results: bool,
+    /// `HH:MM` local time of the daily digest; set means digest-only mode
+    pub digest_time: Option<String>,
+    /// `HH:MM` local start/end of quiet hours; may wrap past midnight
+    pub quiet_start: Option<String>,
+    pub quiet_end: Option<String>,
+    /// Lowest severity delivered: `info`, `warning` or `error`
+    pub min_severity: String,
+    pub last_digest_at: Option<String>,
+}
+
+impl NotificationSettings {
+    pub fn defaults(chat_id: i64) -> Self {
+        NotificationSettings {
+            chat_id,
+            mute_task_results: false,
+            digest_time: None,
+            quiet_start: None,
+            quiet_end: None,
+            min_severity: "info".into(),
+            last_digest_at: None,
+        }
+    }
+}
+
+/// A proactive message held back by quiet hours or digest mode.
+#[derive(Debug, Clone)]
+pub struct PendingNotification {
+    pub id: i64,
+    pub chat_id: i64,
+    pub content: String,
+    pub severity: String,
+    pub created_at: String,
+}
+
+const SCHEMA_VERSION_CURRENT: i64 = 21;
 
 #[derive(Debug, Clone)]
 #[allow(dead_code)]
@@ -154,6 +351,8 @@ pub struct ScheduledTask {
     pub last_run: Option<String>,
     pub status: String, // "active", "paused", "completed", "cancelled"
     pub created_at: String,
+    /// Forum topic the task runs and reports in
+    pub thread_id: Option<i64>,
 }
 
 #[derive(Debug, Clone)]
@@ -403,12 +602,390 @@ fn apply_schema_migrations(conn: &Connection) -> Result<(), RayClawError> {
         set_schema_version(conn, 4)?;
         version = 4;
     }
+    if version < 5 {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS acp_runs (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                session_id TEXT NOT NULL,
+                chat_id INTEGER,
+                agent_id TEXT NOT NULL,
+                prompt TEXT NOT NULL,
+                messages_json TEXT NOT NULL,
+                tool_calls_json TEXT NOT NULL,
+                files_changed_json TEXT NOT NULL,
+                completed INTEGER NOT NULL DEFAULT 1,
+                context_reset INTEGER NOT NULL DEFAULT 0,
+                duration_ms INTEGER NOT NULL DEFAULT 0,
+                created_at TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_acp_runs_session_created
+                ON acp_runs(session_id, created_at);
+            CREATE INDEX IF NOT EXISTS idx_acp_runs_chat_created
+                ON acp_runs(chat_id, created_at);",
+        )?;
+        set_schema_version(conn, 5)?;
+        version = 5;
+    }
+    if version < 6 {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS deliveries (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                chat_id INTEGER NOT NULL,
+                channel TEXT NOT NULL,
+                content TEXT NOT NULL,
+                status TEXT NOT NULL,
+                error TEXT,
+                retryable INTEGER NOT NULL DEFAULT 0,
+                attempts INTEGER NOT NULL DEFAULT 0,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_deliveries_chat_created
+                ON deliveries(chat_id, created_at);
+            CREATE INDEX IF NOT EXISTS idx_deliveries_status
+                ON deliveries(status);",
+        )?;
+        set_schema_version(conn, 6)?;
+        version = 6;
+    }
+    if version < 7 {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS group_members (
+                chat_id INTEGER NOT NULL,
+                member_name TEXT NOT NULL,
+                display_name TEXT,
+                role TEXT,
+                notes TEXT,
+                message_count INTEGER NOT NULL DEFAULT 0,
+                first_seen TEXT NOT NULL,
+                last_seen TEXT NOT NULL,
+                PRIMARY KEY (chat_id, member_name)
+            );
+            CREATE INDEX IF NOT EXISTS idx_group_members_last_seen
+                ON group_members(chat_id, last_seen);
+            INSERT OR IGNORE INTO group_members
+                (chat_id, member_name, message_count, first_seen, last_seen)
+            SELECT chat_id, sender_name, COUNT(*), MIN(timestamp), MAX(timestamp)
+            FROM messages WHERE is_from_bot = 0
+            GROUP BY chat_id, sender_name;",
+        )?;
+        set_schema_version(conn, 7)?;
+        version = 7;
+    }
+    if version < 8 {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS acp_sessions (
+                session_id TEXT PRIMARY KEY,
+                agent_id TEXT NOT NULL,
+                workspace TEXT NOT NULL,
+                acp_session_id TEXT,
+                auto_approve INTEGER NOT NULL DEFAULT 0,
+                created_at TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS acp_chat_bindings (
+                chat_id INTEGER PRIMARY KEY,
+                session_id TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_acp_chat_bindings_session
+                ON acp_chat_bindings(session_id);",
+        )?;
+        set_schema_version(conn, 8)?;
+        version = 8;
+    }
+    if version < 9 {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS url_monitors (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                chat_id INTEGER NOT NULL,
+                url TEXT NOT NULL,
+                selector TEXT,
+                interval_mins INTEGER NOT NULL,
+                status TEXT NOT NULL DEFAULT 'active',
+                last_content TEXT,
+                last_error TEXT,
+                last_checked TEXT,
+                last_changed TEXT,
+                next_check TEXT NOT NULL,
+                change_count INTEGER NOT NULL DEFAULT 0,
+                created_at TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_url_monitors_chat
+                ON url_monitors(chat_id);
+            CREATE INDEX IF NOT EXISTS idx_url_monitors_due
+                ON url_monitors(status, next_check);",
+        )?;
+        set_schema_version(conn, 9)?;
+        version = 9;
+    }
+    if version < 10 {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS notification_settings (
+                chat_id INTEGER PRIMARY KEY,
+                mute_task_results INTEGER NOT NULL DEFAULT 0,
+                digest_time TEXT,
+                quiet_start TEXT,
+                quiet_end TEXT,
+                min_severity TEXT NOT NULL DEFAULT 'info',
+                last_digest_at TEXT,
+                updated_at TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS pending_notifications (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                chat_id INTEGER NOT NULL,
+                content TEXT NOT NULL,
+                severity TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_pending_notifications_chat
+                ON pending_notifications(chat_id, id);",
+        )?;
+        set_schema_version(conn, 10)?;
+        version = 10;
+    }
+    if version < 11 {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS standing_instructions (
+                chat_id INTEGER PRIMARY KEY,
+                instructions TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            );",
+        )?;
+        set_schema_version(conn, 11)?;
+        version = 11;
+    }
+    if version < 12 {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS calendar_feeds (
+                chat_id INTEGER PRIMARY KEY,
+                token TEXT NOT NULL UNIQUE,
+                created_at TEXT NOT NULL
+            );",
+        )?;
+        set_schema_version(conn, 12)?;
+        version = 12;
+    }
+    if version < 13 {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS chat_model_overrides (
+                chat_id INTEGER PRIMARY KEY,
+                provider TEXT,
+                model TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            );",
+        )?;
+        set_schema_version(conn, 13)?;
+        version = 13;
+    }
+    if version < 14 {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS observer_chats (
+                chat_id INTEGER PRIMARY KEY,
+                enabled_at TEXT NOT NULL
+            );",
+        )?;
+        set_schema_version(conn, 14)?;
+        version = 14;
+    }
+    if version < 15 {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS digest_seen_links (
+                chat_id INTEGER NOT NULL,
+                link_hash TEXT NOT NULL,
+                url TEXT NOT NULL,
+                task_id INTEGER NOT NULL,
+                first_seen_at TEXT NOT NULL,
+                PRIMARY KEY (chat_id, link_hash)
+            );
+            CREATE INDEX IF NOT EXISTS idx_digest_seen_links_seen
+                ON digest_seen_links(chat_id, first_seen_at);",
+        )?;
+        set_schema_version(conn, 15)?;
+        version = 15;
+    }
+    if version < 16 {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS tool_call_logs (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                chat_id INTEGER NOT NULL,
+                tool_name TEXT NOT NULL,
+                is_error INTEGER NOT NULL DEFAULT 0,
+                duration_ms INTEGER NOT NULL,
+                created_at TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_tool_call_logs_chat_created
+                ON tool_call_logs(chat_id, created_at);",
+        )?;
+        set_schema_version(conn, 16)?;
+        version = 16;
+    }
+    if version < 17 {
+        conn.execute_batch(
+            "ALTER TABLE llm_usage_logs ADD COLUMN cache_read_tokens INTEGER NOT NULL DEFAULT 0;
+            ALTER TABLE llm_usage_logs ADD COLUMN cache_write_tokens INTEGER NOT NULL DEFAULT 0;
+            ALTER TABLE llm_usage_logs ADD COLUMN cost_usd REAL;",
+        )?;
+        set_schema_version(conn, 17)?;
+        version = 17;
+    }
+    if version < 18 {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS chat_locales (
+                chat_id INTEGER PRIMARY KEY,
+                locale TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            );",
+        )?;
+        set_schema_version(conn, 18)?;
+        version = 18;
+    }
+    if version < 19 {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS browsing_profiles (
+                chat_id INTEGER NOT NULL,
+                name TEXT NOT NULL,
+                domains TEXT NOT NULL,
+                username TEXT,
+                sealed_password TEXT,
+                sealed_cookies TEXT,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL,
+                PRIMARY KEY (chat_id, name)
+            );",
+        )?;
+        set_schema_version(conn, 19)?;
+        version = 19;
+    }
+    if version < 20 {
+        // Forum topics: messages and sessions are per (chat, thread). Thread 0
+        // in `sessions` stands for messages outside any topic.
+        if !table_has_column(conn, "messages", "thread_id")? {
+            conn.execute("ALTER TABLE messages ADD COLUMN thread_id INTEGER", [])?;
+        }
+        conn.execute_batch(
+            "CREATE TABLE sessions_v20 (
+                chat_id INTEGER NOT NULL,
+                thread_id INTEGER NOT NULL DEFAULT 0,
+                messages_json TEXT NOT NULL,
+                updated_at TEXT NOT NULL,
+                PRIMARY KEY (chat_id, thread_id)
+            );
+            INSERT INTO sessions_v20 (chat_id, messages_json, updated_at)
+                SELECT chat_id, messages_json, updated_at FROM sessions;
+            DROP TABLE sessions;
+            ALTER TABLE sessions_v20 RENAME TO sessions;
+            CREATE TABLE IF NOT EXISTS chat_topics (
+                chat_id INTEGER NOT NULL,
+                thread_id INTEGER NOT NULL,
+                topic TEXT NOT NULL,
+                updated_at TEXT NOT NULL,
+                PRIMARY KEY (chat_id, thread_id)
+            );",
+        )?;
+        set_schema_version(conn, 20)?;
+        version = 20;
+    }
+    if version < 21 {
+        // Scheduled tasks run in the forum topic they were created from
+        if !table_has_column(conn, "scheduled_tasks", "thread_id")? {
+            conn.execute(
+                "ALTER TABLE scheduled_tasks ADD COLUMN thread_id INTEGER",
+                [],
+            )?;
+        }
+        set_schema_version(conn, 21)?;
+        version = 21;
+    }
     if version != SCHEMA_VERSION_CURRENT {
         set_schema_version(conn, SCHEMA_VERSION_CURRENT)?;
     }
     Ok(())
 }
 
+fn acp_run_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<AcpRun> {
+    fn json_column<T: serde::de::DeserializeOwned + Default>(raw: String) -> T {
+        serde_json::from_str(&raw).unwrap_or_default()
+    }
+    Ok(AcpRun {
+        id: row.get(0)?,
+        session_id: row.get(1)?,
+        chat_id: row.get(2)?,
+        agent_id: row.get(3)?,
+        prompt: row.get(4)?,
+        messages: json_column(row.get(5)?),
+        tool_calls: json_column(row.get(6)?),
+        files_changed: json_column(row.get(7)?),
+        completed: row.get::<_, i64>(8)? != 0,
+        context_reset: row.get::<_, i64>(9)? != 0,
+        duration_ms: row.get(10)?,
+        created_at: row.get(11)?,
+    })
+}
+
+fn delivery_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Delivery> {
+    Ok(Delivery {
+        id: row.get(0)?,
+        chat_id: row.get(1)?,
+        channel: row.get(2)?,
+        content: row.get(3)?,
+        status: row.get(4)?,
+        error: row.get(5)?,
+        retryable: row.get::<_, i64>(6)? != 0,
+        attempts: row.get(7)?,
+        created_at: row.get(8)?,
+        updated_at: row.get(9)?,
+    })
+}
+
+const URL_MONITOR_COLUMNS: &str = "id, chat_id, url, selector, interval_mins, status, last_content, last_error, last_checked, last_changed, next_check, change_count, created_at";
+
+fn url_monitor_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<UrlMonitor> {
+    Ok(UrlMonitor {
+        id: row.get(0)?,
+        chat_id: row.get(1)?,
+        url: row.get(2)?,
+        selector: row.get(3)?,
+        interval_mins: row.get(4)?,
+        status: row.get(5)?,
+        last_content: row.get(6)?,
+        last_error: row.get(7)?,
+        last_checked: row.get(8)?,
+        last_changed: row.get(9)?,
+        next_check: row.get(10)?,
+        change_count: row.get(11)?,
+        created_at: row.get(12)?,
+    })
+}
+
+const BROWSING_PROFILE_COLUMNS: &str =
+    "chat_id, name, domains, username, sealed_password, sealed_cookies, created_at, updated_at";
+
+fn browsing_profile_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<BrowsingProfile> {
+    let domains: String = row.get(2)?;
+    Ok(BrowsingProfile {
+        chat_id: row.get(0)?,
+        name: row.get(1)?,
+        domains: domains.split_whitespace().map(str::to_string).collect(),
+        username: row.get(3)?,
+        sealed_password: row.get(4)?,
+        sealed_cookies: row.get(5)?,
+        created_at: row.get(6)?,
+        updated_at: row.get(7)?,
+    })
+}
+
+fn group_member_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<GroupMember> {
+    Ok(GroupMember {
+        chat_id: row.get(0)?,
+        member_name: row.get(1)?,
+        display_name: row.get(2)?,
+        role: row.get(3)?,
+        notes: row.get(4)?,
+        message_count: row.get(5)?,
+        first_seen: row.get(6)?,
+        last_seen: row.get(7)?,
+    })
+}
+
 impl Database {
     fn lock_conn(&self) -> MutexGuard<'_, Connection> {
         match self.conn.lock() {
@@ -684,8 +1261,8 @@ impl Database {
     pub fn store_message(&self, msg: &StoredMessage) -> Result<(), RayClawError> {
         let conn = self.lock_conn();
         conn.execute(
-            "INSERT OR REPLACE INTO messages (id, chat_id, sender_name, content, is_from_bot, timestamp)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            "INSERT OR REPLACE INTO messages (id, chat_id, sender_name, content, is_from_bot, timestamp, thread_id)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
             params![
                 msg.id,
                 msg.chat_id,
@@ -693,11 +1270,49 @@ impl Database {
                 msg.content,
                 msg.is_from_bot as i32,
                 msg.timestamp,
+                msg.thread_id,
             ],
         )?;
+        if !msg.is_from_bot {
+            conn.execute(
+                "INSERT INTO group_members (chat_id, member_name, message_count, first_seen, last_seen)
+                 VALUES (?1, ?2, 1, ?3, ?3)
+                 ON CONFLICT(chat_id, member_name) DO UPDATE SET
+                    message_count = message_count + 1,
+                    last_seen = MAX(last_seen, excluded.last_seen)",
+                params![msg.chat_id, msg.sender_name, msg.timestamp],
+            )?;
+        }
         Ok(())
     }
 
+    /// Insert many messages in a single transaction, keeping any row that already
+    /// exists. Returns the number of newly inserted messages.
+    pub fn store_messages_bulk(&self, msgs: &[StoredMessage]) -> Result<usize, RayClawError> {
+        let conn = self.lock_conn();
+        let tx = conn.unchecked_transaction()?;
+        let mut inserted = 0;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT OR IGNORE INTO messages (id, chat_id, sender_name, content, is_from_bot, timestamp, thread_id)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            )?;
+            for msg in msgs {
+                inserted += stmt.execute(params![
+                    msg.id,
+                    msg.chat_id,
+                    msg.sender_name,
+                    msg.content,
+                    msg.is_from_bot as i32,
+                    msg.timestamp,
+                    msg.thread_id,
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(inserted)
+    }
+
     pub fn get_recent_messages(
         &self,
         chat_id: i64,
@@ -705,7 +1320,7 @@ impl Database {
     ) -> Result<Vec<StoredMessage>, RayClawError> {
         let conn = self.lock_conn();
         let mut stmt = conn.prepare(
-            "SELECT id, chat_id, sender_name, content, is_from_bot, timestamp
+            "SELECT id, chat_id, sender_name, content, is_from_bot, timestamp, thread_id
              FROM messages
              WHERE chat_id = ?1
              ORDER BY timestamp DESC
@@ -721,6 +1336,7 @@ impl Database {
                     content: row.get(3)?,
                     is_from_bot: row.get::<_, i32>(4)? != 0,
                     timestamp: row.get(5)?,
+                    thread_id: row.get(6)?,
                 })
             })?
             .collect::<Result<Vec<_>, _>>()?;
@@ -734,7 +1350,7 @@ impl Database {
     pub fn get_all_messages(&self, chat_id: i64) -> Result<Vec<StoredMessage>, RayClawError> {
         let conn = self.lock_conn();
         let mut stmt = conn.prepare(
-            "SELECT id, chat_id, sender_name, content, is_from_bot, timestamp
+            "SELECT id, chat_id, sender_name, content, is_from_bot, timestamp, thread_id
              FROM messages
              WHERE chat_id = ?1
              ORDER BY timestamp ASC",
@@ -748,6 +1364,7 @@ impl Database {
                     content: row.get(3)?,
                     is_from_bot: row.get::<_, i32>(4)? != 0,
                     timestamp: row.get(5)?,
+                    thread_id: row.get(6)?,
                 })
             })?
             .collect::<Result<Vec<_>, _>>()?;
@@ -839,6 +1456,20 @@ impl Database {
         }
     }
 
+    pub fn get_chat_title(&self, chat_id: i64) -> Result<Option<String>, RayClawError> {
+        let conn = self.lock_conn();
+        let result = conn.query_row(
+            "SELECT chat_title FROM chats WHERE chat_id = ?1",
+            params![chat_id],
+            |row| row.get::<_, Option<String>>(0),
+        );
+        match result {
+            Ok(v) => Ok(v),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
     pub fn get_chat_external_id(&self, chat_id: i64) -> Result<Option<String>, RayClawError> {
         let conn = self.lock_conn();
         let result = conn.query_row(
@@ -853,11 +1484,13 @@ impl Database {
         }
     }
 
-    /// Get messages since the bot's last response in this chat.
+    /// Get messages since the bot's last response in this chat (in forum
+    /// topic `thread_id`, or outside any topic for `None`).
     /// Falls back to `fallback_limit` most recent messages if bot never responded.
     pub fn get_messages_since_last_bot_response(
         &self,
         chat_id: i64,
+        thread_id: Option<i64>,
         max: usize,
         fallback: usize,
     ) -> Result<Vec<StoredMessage>, RayClawError> {
@@ -867,23 +1500,23 @@ impl Database {
         let last_bot_ts: Option<String> = conn
             .query_row(
                 "SELECT timestamp FROM messages
-                 WHERE chat_id = ?1 AND is_from_bot = 1
+                 WHERE chat_id = ?1 AND thread_id IS ?2 AND is_from_bot = 1
                  ORDER BY timestamp DESC LIMIT 1",
-                params![chat_id],
+                params![chat_id, thread_id],
                 |row| row.get(0),
             )
             .ok();
 
         let mut messages = if let Some(ts) = last_bot_ts {
             let mut stmt = conn.prepare(
-                "SELECT id, chat_id, sender_name, content, is_from_bot, timestamp
+                "SELECT id, chat_id, sender_name, content, is_from_bot, timestamp, thread_id
                  FROM messages
-                 WHERE chat_id = ?1 AND timestamp >= ?2
+                 WHERE chat_id = ?1 AND thread_id IS ?2 AND timestamp >= ?3
                  ORDER BY timestamp DESC
-                 LIMIT ?3",
+                 LIMIT ?4",
             )?;
             let rows = stmt
-                .query_map(params![chat_id, ts, max as i64], |row| {
+                .query_map(params![chat_id, thread_id, ts, max as i64], |row| {
                     Ok(StoredMessage {
                         id: row.get(0)?,
                         chat_id: row.get(1)?,
@@ -891,20 +1524,21 @@ impl Database {
                         content: row.get(3)?,
                         is_from_bot: row.get::<_, i32>(4)? != 0,
                         timestamp: row.get(5)?,
+                        thread_id: row.get(6)?,
                     })
                 })?
                 .collect::<Result<Vec<_>, _>>()?;
             rows
         } else {
             let mut stmt = conn.prepare(
-                "SELECT id, chat_id, sender_name, content, is_from_bot, timestamp
+                "SELECT id, chat_id, sender_name, content, is_from_bot, timestamp, thread_id
                  FROM messages
-                 WHERE chat_id = ?1
+                 WHERE chat_id = ?1 AND thread_id IS ?2
                  ORDER BY timestamp DESC
-                 LIMIT ?2",
+                 LIMIT ?3",
             )?;
             let rows = stmt
-                .query_map(params![chat_id, fallback as i64], |row| {
+                .query_map(params![chat_id, thread_id, fallback as i64], |row| {
                     Ok(StoredMessage {
                         id: row.get(0)?,
                         chat_id: row.get(1)?,
@@ -912,6 +1546,7 @@ impl Database {
                         content: row.get(3)?,
                         is_from_bot: row.get::<_, i32>(4)? != 0,
                         timestamp: row.get(5)?,
+                        thread_id: row.get(6)?,
                     })
                 })?
                 .collect::<Result<Vec<_>, _>>()?;
@@ -931,13 +1566,34 @@ impl Database {
         schedule_type: &str,
         schedule_value: &str,
         next_run: &str,
+    ) -> Result<i64, RayClawError> {
+        self.create_scheduled_task_in_thread(
+            chat_id,
+            None,
+            prompt,
+            schedule_type,
+            schedule_value,
+            next_run,
+        )
+    }
+
+    /// Like `create_scheduled_task`, for a task that runs in forum topic
+    /// `thread_id` of the chat.
+    pub fn create_scheduled_task_in_thread(
+        &self,
+        chat_id: i64,
+        thread_id: Option<i64>,
+        prompt: &str,
+        schedule_type: &str,
+        schedule_value: &str,
+        next_run: &str,
     ) -> Result<i64, RayClawError> {
         let conn = self.lock_conn();
         let now = chrono::Utc::now().to_rfc3339();
         conn.execute(
-            "INSERT INTO scheduled_tasks (chat_id, prompt, schedule_type, schedule_value, next_run, status, created_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, 'active', ?6)",
-            params![chat_id, prompt, schedule_type, schedule_value, next_run, now],
+            "INSERT INTO scheduled_tasks (chat_id, prompt, schedule_type, schedule_value, next_run, status, created_at, thread_id)
+             VALUES (?1, ?2, ?3, ?4, ?5, 'active', ?6, ?7)",
+            params![chat_id, prompt, schedule_type, schedule_value, next_run, now, thread_id],
         )?;
         Ok(conn.last_insert_rowid())
     }
@@ -945,7 +1601,7 @@ impl Database {
     pub fn get_due_tasks(&self, now: &str) -> Result<Vec<ScheduledTask>, RayClawError> {
         let conn = self.lock_conn();
         let mut stmt = conn.prepare(
-            "SELECT id, chat_id, prompt, schedule_type, schedule_value, next_run, last_run, status, created_at
+            "SELECT id, chat_id, prompt, schedule_type, schedule_value, next_run, last_run, status, created_at, thread_id
              FROM scheduled_tasks
              WHERE status = 'active' AND next_run <= ?1",
         )?;
@@ -961,6 +1617,7 @@ impl Database {
                     last_run: row.get(6)?,
                     status: row.get(7)?,
                     created_at: row.get(8)?,
+                    thread_id: row.get(9)?,
                 })
             })?
             .collect::<Result<Vec<_>, _>>()?;
@@ -970,7 +1627,7 @@ impl Database {
     pub fn get_tasks_for_chat(&self, chat_id: i64) -> Result<Vec<ScheduledTask>, RayClawError> {
         let conn = self.lock_conn();
         let mut stmt = conn.prepare(
-            "SELECT id, chat_id, prompt, schedule_type, schedule_value, next_run, last_run, status, created_at
+            "SELECT id, chat_id, prompt, schedule_type, schedule_value, next_run, last_run, status, created_at, thread_id
              FROM scheduled_tasks
              WHERE chat_id = ?1 AND status IN ('active', 'paused')
              ORDER BY id",
@@ -987,6 +1644,7 @@ impl Database {
                     last_run: row.get(6)?,
                     status: row.get(7)?,
                     created_at: row.get(8)?,
+                    thread_id: row.get(9)?,
                 })
             })?
             .collect::<Result<Vec<_>, _>>()?;
@@ -996,7 +1654,7 @@ impl Database {
     pub fn get_task_by_id(&self, task_id: i64) -> Result<Option<ScheduledTask>, RayClawError> {
         let conn = self.lock_conn();
         let result = conn.query_row(
-            "SELECT id, chat_id, prompt, schedule_type, schedule_value, next_run, last_run, status, created_at
+            "SELECT id, chat_id, prompt, schedule_type, schedule_value, next_run, last_run, status, created_at, thread_id
              FROM scheduled_tasks
              WHERE id = ?1",
             params![task_id],
@@ -1011,6 +1669,7 @@ impl Database {
                     last_run: row.get(6)?,
                     status: row.get(7)?,
                     created_at: row.get(8)?,
+                    thread_id: row.get(9)?,
                 })
             },
         );
@@ -1137,7 +1796,7 @@ impl Database {
         let conn = self.lock_conn();
 
         let mut sql = String::from(
-            "SELECT id, chat_id, prompt, schedule_type, schedule_value, next_run, last_run, status, created_at
+            "SELECT id, chat_id, prompt, schedule_type, schedule_value, next_run, last_run, status, created_at, thread_id
              FROM scheduled_tasks WHERE 1=1",
         );
         let mut count_sql = String::from("SELECT COUNT(*) FROM scheduled_tasks WHERE 1=1");
@@ -1191,6 +1850,7 @@ impl Database {
                     last_run: row.get(6)?,
                     status: row.get(7)?,
                     created_at: row.get(8)?,
+                    thread_id: row.get(9)?,
                 })
             })?
             .collect::<Result<Vec<_>, _>>()?;
@@ -1355,25 +2015,36 @@ impl Database {
 
     // --- Sessions ---
 
-    pub fn save_session(&self, chat_id: i64, messages_json: &str) -> Result<(), RayClawError> {
+    /// Sessions are kept per forum topic; `thread_id` `None` is the chat's
+    /// session outside any topic.
+    pub fn save_session(
+        &self,
+        chat_id: i64,
+        thread_id: Option<i64>,
+        messages_json: &str,
+    ) -> Result<(), RayClawError> {
         let conn = self.lock_conn();
         let now = chrono::Utc::now().to_rfc3339();
         conn.execute(
-            "INSERT INTO sessions (chat_id, messages_json, updated_at)
-             VALUES (?1, ?2, ?3)
-             ON CONFLICT(chat_id) DO UPDATE SET
-                messages_json = ?2,
-                updated_at = ?3",
-            params![chat_id, messages_json, now],
+            "INSERT INTO sessions (chat_id, thread_id, messages_json, updated_at)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(chat_id, thread_id) DO UPDATE SET
+                messages_json = ?3,
+                updated_at = ?4",
+            params![chat_id, thread_id.unwrap_or(0), messages_json, now],
         )?;
         Ok(())
     }
 
-    pub fn load_session(&self, chat_id: i64) -> Result<Option<(String, String)>, RayClawError> {
+    pub fn load_session(
+        &self,
+        chat_id: i64,
+        thread_id: Option<i64>,
+    ) -> Result<Option<(String, String)>, RayClawError> {
         let conn = self.lock_conn();
         let result = conn.query_row(
-            "SELECT messages_json, updated_at FROM sessions WHERE chat_id = ?1",
-            params![chat_id],
+            "SELECT messages_json, updated_at FROM sessions WHERE chat_id = ?1 AND thread_id = ?2",
+            params![chat_id, thread_id.unwrap_or(0)],
             |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
         );
         match result {
@@ -1435,26 +2106,90 @@ impl Database {
             params![chat_id],
         )?;
         affected += tx.execute("DELETE FROM memories WHERE chat_id = ?1", params![chat_id])?;
-        affected += tx.execute("DELETE FROM chats WHERE chat_id = ?1", params![chat_id])?;
-
-        tx.commit()?;
-        Ok(affected > 0)
-    }
-
-    pub fn get_new_user_messages_since(
-        &self,
-        chat_id: i64,
-        since: &str,
-    ) -> Result<Vec<StoredMessage>, RayClawError> {
-        let conn = self.lock_conn();
-        let mut stmt = conn.prepare(
-            "SELECT id, chat_id, sender_name, content, is_from_bot, timestamp
-             FROM messages
-             WHERE chat_id = ?1 AND timestamp > ?2 AND is_from_bot = 0
-             ORDER BY timestamp ASC",
+        affected += tx.execute("DELETE FROM acp_runs WHERE chat_id = ?1", params![chat_id])?;
+        affected += tx.execute(
+            "DELETE FROM acp_chat_bindings WHERE chat_id = ?1",
+            params![chat_id],
         )?;
-        let messages = stmt
-            .query_map(params![chat_id, since], |row| {
+        affected += tx.execute(
+            "DELETE FROM deliveries WHERE chat_id = ?1",
+            params![chat_id],
+        )?;
+        affected += tx.execute(
+            "DELETE FROM group_members WHERE chat_id = ?1",
+            params![chat_id],
+        )?;
+        affected += tx.execute(
+            "DELETE FROM url_monitors WHERE chat_id = ?1",
+            params![chat_id],
+        )?;
+        affected += tx.execute(
+            "DELETE FROM notification_settings WHERE chat_id = ?1",
+            params![chat_id],
+        )?;
+        affected += tx.execute(
+            "DELETE FROM pending_notifications WHERE chat_id = ?1",
+            params![chat_id],
+        )?;
+        affected += tx.execute(
+            "DELETE FROM standing_instructions WHERE chat_id = ?1",
+            params![chat_id],
+        )?;
+        affected += tx.execute(
+            "DELETE FROM calendar_feeds WHERE chat_id = ?1",
+            params![chat_id],
+        )?;
+        affected += tx.execute(
+            "DELETE FROM chat_model_overrides WHERE chat_id = ?1",
+            params![chat_id],
+        )?;
+        affected += tx.execute(
+            "DELETE FROM observer_chats WHERE chat_id = ?1",
+            params![chat_id],
+        )?;
+        affected += tx.execute(
+            "DELETE FROM digest_seen_links WHERE chat_id = ?1",
+            params![chat_id],
+        )?;
+        affected += tx.execute(
+            "DELETE FROM tool_call_logs WHERE chat_id = ?1",
+            params![chat_id],
+        )?;
+        affected += tx.execute(
+            "DELETE FROM chat_locales WHERE chat_id = ?1",
+            params![chat_id],
+        )?;
+        affected += tx.execute(
+            "DELETE FROM chat_topics WHERE chat_id = ?1",
+            params![chat_id],
+        )?;
+        affected += tx.execute(
+            "DELETE FROM browsing_profiles WHERE chat_id = ?1",
+            params![chat_id],
+        )?;
+        affected += tx.execute("DELETE FROM chats WHERE chat_id = ?1", params![chat_id])?;
+
+        tx.commit()?;
+        Ok(affected > 0)
+    }
+
+    /// User messages in forum topic `thread_id` (`None`: outside any topic)
+    /// newer than `since`.
+    pub fn get_new_user_messages_since(
+        &self,
+        chat_id: i64,
+        thread_id: Option<i64>,
+        since: &str,
+    ) -> Result<Vec<StoredMessage>, RayClawError> {
+        let conn = self.lock_conn();
+        let mut stmt = conn.prepare(
+            "SELECT id, chat_id, sender_name, content, is_from_bot, timestamp, thread_id
+             FROM messages
+             WHERE chat_id = ?1 AND thread_id IS ?2 AND timestamp > ?3 AND is_from_bot = 0
+             ORDER BY timestamp ASC",
+        )?;
+        let messages = stmt
+            .query_map(params![chat_id, thread_id, since], |row| {
                 Ok(StoredMessage {
                     id: row.get(0)?,
                     chat_id: row.get(1)?,
@@ -1462,6 +2197,7 @@ impl Database {
                     content: row.get(3)?,
                     is_from_bot: row.get::<_, i32>(4)? != 0,
                     timestamp: row.get(5)?,
+                    thread_id: row.get(6)?,
                 })
             })?
             .collect::<Result<Vec<_>, _>>()?;
@@ -1476,7 +2212,7 @@ impl Database {
     ) -> Result<Vec<StoredMessage>, RayClawError> {
         let conn = self.lock_conn();
         let mut stmt = conn.prepare(
-            "SELECT id, chat_id, sender_name, content, is_from_bot, timestamp
+            "SELECT id, chat_id, sender_name, content, is_from_bot, timestamp, thread_id
              FROM messages
              WHERE chat_id = ?1 AND timestamp > ?2
              ORDER BY timestamp ASC
@@ -1491,6 +2227,7 @@ impl Database {
                     content: row.get(3)?,
                     is_from_bot: row.get::<_, i32>(4)? != 0,
                     timestamp: row.get(5)?,
+                    thread_id: row.get(6)?,
                 })
             })?
             .collect::<Result<Vec<_>, _>>()?;
@@ -1540,28 +2277,156 @@ impl Database {
         output_tokens: i64,
         request_kind: &str,
     ) -> Result<i64, RayClawError> {
+        self.record_llm_usage(&LlmUsageEntry {
+            chat_id,
+            caller_channel: caller_channel.to_string(),
+            provider: provider.to_string(),
+            model: model.to_string(),
+            input_tokens,
+            output_tokens,
+            request_kind: request_kind.to_string(),
+            ..LlmUsageEntry::default()
+        })
+    }
+
+    /// Record one LLM call. `total_tokens` counts cache reads and writes too.
+    pub fn record_llm_usage(&self, entry: &LlmUsageEntry) -> Result<i64, RayClawError> {
         let conn = self.lock_conn();
         let now = chrono::Utc::now().to_rfc3339();
-        let total_tokens = input_tokens.saturating_add(output_tokens);
+        let total_tokens = entry
+            .input_tokens
+            .saturating_add(entry.output_tokens)
+            .saturating_add(entry.cache_read_tokens)
+            .saturating_add(entry.cache_write_tokens);
         conn.execute(
             "INSERT INTO llm_usage_logs
-                (chat_id, caller_channel, provider, model, input_tokens, output_tokens, total_tokens, request_kind, created_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                (chat_id, caller_channel, provider, model, input_tokens, output_tokens, total_tokens,
+                 cache_read_tokens, cache_write_tokens, cost_usd, request_kind, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
             params![
-                chat_id,
-                caller_channel,
-                provider,
-                model,
-                input_tokens,
-                output_tokens,
+                entry.chat_id,
+                entry.caller_channel,
+                entry.provider,
+                entry.model,
+                entry.input_tokens,
+                entry.output_tokens,
                 total_tokens,
-                request_kind,
+                entry.cache_read_tokens,
+                entry.cache_write_tokens,
+                entry.cost_usd,
+                entry.request_kind,
                 now,
             ],
         )?;
         Ok(conn.last_insert_rowid())
     }
 
+    /// Cache traffic and cost since `since` (all time when `None`), for one
+    /// chat or all chats.
+    pub fn get_llm_cost_summary(
+        &self,
+        chat_id: Option<i64>,
+        since: Option<&str>,
+    ) -> Result<LlmCostSummary, RayClawError> {
+        let conn = self.lock_conn();
+        let summary = conn.query_row(
+            "SELECT
+                COALESCE(SUM(cache_read_tokens), 0),
+                COALESCE(SUM(cache_write_tokens), 0),
+                COALESCE(SUM(cost_usd), 0),
+                COALESCE(SUM(cost_usd IS NULL), 0)
+             FROM llm_usage_logs
+             WHERE (?1 IS NULL OR chat_id = ?1) AND (?2 IS NULL OR created_at >= ?2)",
+            params![chat_id, since],
+            |row| {
+                Ok(LlmCostSummary {
+                    cache_read_tokens: row.get(0)?,
+                    cache_write_tokens: row.get(1)?,
+                    cost_usd: row.get(2)?,
+                    unpriced_requests: row.get(3)?,
+                })
+            },
+        )?;
+        Ok(summary)
+    }
+
+    /// Usage per UTC day since `since`, oldest first.
+    pub fn get_llm_usage_by_day(
+        &self,
+        chat_id: Option<i64>,
+        since: &str,
+    ) -> Result<Vec<LlmDailyUsage>, RayClawError> {
+        let conn = self.lock_conn();
+        let mut stmt = conn.prepare(
+            "SELECT substr(created_at, 1, 10) AS day, COUNT(*), COALESCE(SUM(total_tokens), 0),
+                    COALESCE(SUM(cost_usd), 0)
+             FROM llm_usage_logs
+             WHERE (?1 IS NULL OR chat_id = ?1) AND created_at >= ?2
+             GROUP BY day
+             ORDER BY day ASC",
+        )?;
+        let rows = stmt
+            .query_map(params![chat_id, since], |row| {
+                Ok(LlmDailyUsage {
+                    day: row.get(0)?,
+                    requests: row.get(1)?,
+                    total_tokens: row.get(2)?,
+                    cost_usd: row.get(3)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    pub fn log_tool_call(
+        &self,
+        chat_id: i64,
+        tool_name: &str,
+        is_error: bool,
+        duration_ms: i64,
+    ) -> Result<(), RayClawError> {
+        let conn = self.lock_conn();
+        conn.execute(
+            "INSERT INTO tool_call_logs (chat_id, tool_name, is_error, duration_ms, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                chat_id,
+                tool_name,
+                is_error as i32,
+                duration_ms,
+                chrono::Utc::now().to_rfc3339()
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Tool calls in a chat since `since`, most used first.
+    pub fn get_tool_usage_by_name(
+        &self,
+        chat_id: i64,
+        since: &str,
+    ) -> Result<Vec<ToolUsageSummary>, RayClawError> {
+        let conn = self.lock_conn();
+        let mut stmt = conn.prepare(
+            "SELECT tool_name, COUNT(*), COALESCE(SUM(is_error), 0), CAST(AVG(duration_ms) AS INTEGER)
+             FROM tool_call_logs
+             WHERE chat_id = ?1 AND created_at >= ?2
+             GROUP BY tool_name
+             ORDER BY COUNT(*) DESC, tool_name ASC",
+        )?;
+        let rows = stmt
+            .query_map(params![chat_id, since], |row| {
+                Ok(ToolUsageSummary {
+                    tool_name: row.get(0)?,
+                    calls: row.get(1)?,
+                    errors: row.get(2)?,
+                    avg_duration_ms: row.get(3)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
     pub fn get_llm_usage_summary(
         &self,
         chat_id: Option<i64>,
@@ -2538,123 +3403,1004 @@ impl Database {
         rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
     }
 
-    pub fn get_memory_injection_logs(
+    pub fn log_acp_run(&self, run: &AcpRun) -> Result<i64, RayClawError> {
+        let conn = self.lock_conn();
+        conn.execute(
+            "INSERT INTO acp_runs (
+                session_id, chat_id, agent_id, prompt, messages_json, tool_calls_json, files_changed_json, completed, context_reset, duration_ms, created_at
+             ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                run.session_id,
+                run.chat_id,
+                run.agent_id,
+                run.prompt,
+                serde_json::to_string(&run.messages)?,
+                serde_json::to_string(&run.tool_calls)?,
+                serde_json::to_string(&run.files_changed)?,
+                if run.completed { 1 } else { 0 },
+                if run.context_reset { 1 } else { 0 },
+                run.duration_ms,
+                run.created_at
+            ],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// Most recent ACP runs first, optionally narrowed to a chat and/or session.
+    pub fn get_acp_runs(
         &self,
         chat_id: Option<i64>,
-        since: Option<&str>,
+        session_id: Option<&str>,
         limit: usize,
         offset: usize,
-    ) -> Result<Vec<MemoryInjectionLog>, RayClawError> {
+    ) -> Result<Vec<AcpRun>, RayClawError> {
         let conn = self.lock_conn();
-        let mut query = String::from(
-            "SELECT id, chat_id, created_at, retrieval_method, candidate_count, selected_count, omitted_count, tokens_est
-             FROM memory_injection_logs",
-        );
-        let mut where_parts: Vec<&str> = Vec::new();
-        if chat_id.is_some() {
-            where_parts.push("chat_id = ?1");
-        }
-        if since.is_some() {
-            where_parts.push(if chat_id.is_some() {
-                "unixepoch(created_at) >= unixepoch(?2)"
-            } else {
-                "unixepoch(created_at) >= unixepoch(?1)"
-            });
-        }
-        if !where_parts.is_empty() {
-            query.push_str(" WHERE ");
-            query.push_str(&where_parts.join(" AND "));
-        }
-        query.push_str(" ORDER BY unixepoch(created_at) ASC LIMIT ");
-        query.push_str(&limit.max(1).to_string());
-        query.push_str(" OFFSET ");
-        query.push_str(&offset.to_string());
-
-        let mut stmt = conn.prepare(&query)?;
-        let mapper = |row: &rusqlite::Row<'_>| {
-            Ok(MemoryInjectionLog {
-                id: row.get(0)?,
-                chat_id: row.get(1)?,
-                created_at: row.get(2)?,
-                retrieval_method: row.get(3)?,
-                candidate_count: row.get(4)?,
-                selected_count: row.get(5)?,
-                omitted_count: row.get(6)?,
-                tokens_est: row.get(7)?,
-            })
-        };
-        let rows = match (chat_id, since) {
-            (Some(cid), Some(ts)) => stmt.query_map(params![cid, ts], mapper)?,
-            (Some(cid), None) => stmt.query_map(params![cid], mapper)?,
-            (None, Some(ts)) => stmt.query_map(params![ts], mapper)?,
-            (None, None) => stmt.query_map([], mapper)?,
-        };
+        let mut stmt = conn.prepare(
+            "SELECT id, session_id, chat_id, agent_id, prompt, messages_json, tool_calls_json, files_changed_json, completed, context_reset, duration_ms, created_at
+             FROM acp_runs
+             WHERE (?1 IS NULL OR chat_id = ?1) AND (?2 IS NULL OR session_id = ?2)
+             ORDER BY id DESC
+             LIMIT ?3 OFFSET ?4",
+        )?;
+        let rows = stmt.query_map(
+            params![chat_id, session_id, limit.max(1) as i64, offset as i64],
+            acp_run_from_row,
+        )?;
         rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    pub fn get_acp_run(&self, id: i64) -> Result<Option<AcpRun>, RayClawError> {
+        let conn = self.lock_conn();
+        conn.query_row(
+            "SELECT id, session_id, chat_id, agent_id, prompt, messages_json, tool_calls_json, files_changed_json, completed, context_reset, duration_ms, created_at
+             FROM acp_runs WHERE id = ?1",
+            params![id],
+            acp_run_from_row,
+        )
+        .optional()
+        .map_err(Into::into)
+    }
 
-    fn test_db() -> (Database, std::path::PathBuf) {
-        let dir = std::env::temp_dir().join(format!("rayclaw_test_{}", uuid::Uuid::new_v4()));
-        let db = Database::new(dir.to_str().unwrap()).unwrap();
-        (db, dir)
+    /// Insert or update an ACP session's metadata. Chat bindings are kept.
+    pub fn save_acp_session(&self, record: &AcpSessionRecord) -> Result<(), RayClawError> {
+        let conn = self.lock_conn();
+        conn.execute(
+            "INSERT INTO acp_sessions (session_id, agent_id, workspace, acp_session_id, auto_approve, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+             ON CONFLICT(session_id) DO UPDATE SET
+                agent_id = excluded.agent_id,
+                workspace = excluded.workspace,
+                acp_session_id = excluded.acp_session_id,
+                auto_approve = excluded.auto_approve",
+            params![
+                record.session_id,
+                record.agent_id,
+                record.workspace,
+                record.acp_session_id,
+                if record.auto_approve { 1 } else { 0 },
+                record.created_at
+            ],
+        )?;
+        Ok(())
     }
 
-    fn cleanup(dir: &std::path::Path) {
-        let _ = std::fs::remove_dir_all(dir);
+    /// Forget an ACP session and its chat bindings.
+    pub fn delete_acp_session(&self, session_id: &str) -> Result<(), RayClawError> {
+        let mut conn = self.lock_conn();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "DELETE FROM acp_chat_bindings WHERE session_id = ?1",
+            params![session_id],
+        )?;
+        tx.execute(
+            "DELETE FROM acp_sessions WHERE session_id = ?1",
+            params![session_id],
+        )?;
+        tx.commit()?;
+        Ok(())
     }
 
-    #[test]
-    fn test_new_database_creates_tables() {
-        let (db, dir) = test_db();
-        // Verify we can do basic operations without errors
-        let msgs = db.get_recent_messages(1, 10).unwrap();
-        assert!(msgs.is_empty());
-        let tasks = db.get_due_tasks("2099-01-01T00:00:00Z").unwrap();
-        assert!(tasks.is_empty());
-        cleanup(&dir);
+    /// Bind `chat_id` to an ACP session, replacing any previous binding.
+    pub fn bind_acp_chat(&self, chat_id: i64, session_id: &str) -> Result<(), RayClawError> {
+        let conn = self.lock_conn();
+        conn.execute(
+            "INSERT OR REPLACE INTO acp_chat_bindings (chat_id, session_id) VALUES (?1, ?2)",
+            params![chat_id, session_id],
+        )?;
+        Ok(())
     }
 
-    #[test]
-    fn test_schema_version_is_tracked() {
-        let (db, dir) = test_db();
-        let conn = db.lock_conn();
-        let version: String = conn
-            .query_row(
-                "SELECT value FROM db_meta WHERE key = 'schema_version'",
-                [],
-                |row| row.get(0),
-            )
-            .unwrap();
-        assert_eq!(version, SCHEMA_VERSION_CURRENT.to_string());
-        drop(conn);
-        cleanup(&dir);
+    pub fn unbind_acp_chat(&self, chat_id: i64) -> Result<(), RayClawError> {
+        let conn = self.lock_conn();
+        conn.execute(
+            "DELETE FROM acp_chat_bindings WHERE chat_id = ?1",
+            params![chat_id],
+        )?;
+        Ok(())
     }
 
-    #[test]
-    fn test_legacy_schema_is_upgraded_to_current_version() {
-        let dir =
-            std::env::temp_dir().join(format!("rayclaw_legacy_upgrade_{}", uuid::Uuid::new_v4()));
-        std::fs::create_dir_all(&dir).unwrap();
-        let db_path = dir.join("rayclaw.db");
-        let conn = Connection::open(&db_path).unwrap();
-        conn.execute_batch(
-            "PRAGMA journal_mode=WAL;
-             CREATE TABLE chats (
-                chat_id INTEGER PRIMARY KEY,
-                chat_title TEXT,
-                chat_type TEXT NOT NULL DEFAULT 'private',
-                last_message_time TEXT NOT NULL
-             );
-             CREATE TABLE messages (
-                id TEXT NOT NULL,
-                chat_id INTEGER NOT NULL,
-                sender_name TEXT NOT NULL,
-                content TEXT NOT NULL,
-                is_from_bot INTEGER NOT NULL DEFAULT 0,
+    /// All persisted ACP sessions, oldest first, with their bound chats.
+    pub fn get_acp_sessions(&self) -> Result<Vec<AcpSessionRecord>, RayClawError> {
+        let conn = self.lock_conn();
+        let mut stmt = conn.prepare(
+            "SELECT session_id, agent_id, workspace, acp_session_id, auto_approve, created_at
+             FROM acp_sessions ORDER BY created_at, session_id",
+        )?;
+        let mut records = stmt
+            .query_map([], |row| {
+                Ok(AcpSessionRecord {
+                    session_id: row.get(0)?,
+                    agent_id: row.get(1)?,
+                    workspace: row.get(2)?,
+                    acp_session_id: row.get(3)?,
+                    auto_approve: row.get::<_, i64>(4)? != 0,
+                    chat_ids: Vec::new(),
+                    created_at: row.get(5)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut stmt = conn.prepare(
+            "SELECT chat_id FROM acp_chat_bindings WHERE session_id = ?1 ORDER BY chat_id",
+        )?;
+        for record in &mut records {
+            record.chat_ids = stmt
+                .query_map(params![record.session_id], |row| row.get(0))?
+                .collect::<Result<Vec<_>, _>>()?;
+        }
+        Ok(records)
+    }
+
+    /// Remember how a member is shown on their channel (e.g. a full name for
+    /// a handle or user ID). Does nothing for members not seen yet.
+    pub fn set_group_member_display_name(
+        &self,
+        chat_id: i64,
+        member_name: &str,
+        display_name: &str,
+    ) -> Result<bool, RayClawError> {
+        let conn = self.lock_conn();
+        let rows = conn.execute(
+            "UPDATE group_members SET display_name = ?3
+             WHERE chat_id = ?1 AND member_name = ?2",
+            params![chat_id, member_name, display_name],
+        )?;
+        Ok(rows > 0)
+    }
+
+    /// Update the role and/or notes learned about a member. `None` leaves a
+    /// field unchanged; an empty string clears it.
+    pub fn update_group_member_profile(
+        &self,
+        chat_id: i64,
+        member_name: &str,
+        role: Option<&str>,
+        notes: Option<&str>,
+    ) -> Result<bool, RayClawError> {
+        let conn = self.lock_conn();
+        let rows = conn.execute(
+            "UPDATE group_members
+             SET role = CASE WHEN ?3 IS NULL THEN role ELSE NULLIF(?3, '') END,
+                 notes = CASE WHEN ?4 IS NULL THEN notes ELSE NULLIF(?4, '') END
+             WHERE chat_id = ?1 AND member_name = ?2",
+            params![chat_id, member_name, role, notes],
+        )?;
+        Ok(rows > 0)
+    }
+
+    /// Members of a chat, most recently active first.
+    pub fn get_group_members(
+        &self,
+        chat_id: i64,
+        limit: usize,
+    ) -> Result<Vec<GroupMember>, RayClawError> {
+        let conn = self.lock_conn();
+        let mut stmt = conn.prepare(
+            "SELECT chat_id, member_name, display_name, role, notes, message_count, first_seen, last_seen
+             FROM group_members
+             WHERE chat_id = ?1
+             ORDER BY last_seen DESC
+             LIMIT ?2",
+        )?;
+        let rows = stmt.query_map(params![chat_id, limit as i64], group_member_from_row)?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+    }
+
+    /// Record an outbound message as `queued` before it is handed to its channel.
+    pub fn create_delivery(
+        &self,
+        chat_id: i64,
+        channel: &str,
+        content: &str,
+    ) -> Result<i64, RayClawError> {
+        let conn = self.lock_conn();
+        let now = chrono::Utc::now().to_rfc3339();
+        conn.execute(
+            "INSERT INTO deliveries (chat_id, channel, content, status, created_at, updated_at)
+             VALUES (?1, ?2, ?3, 'queued', ?4, ?4)",
+            params![chat_id, channel, content, now],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// Record the outcome of one send attempt. `content` replaces the stored
+    /// text when the message was changed before resending.
+    pub fn record_delivery_attempt(
+        &self,
+        id: i64,
+        status: &str,
+        error: Option<&str>,
+        retryable: bool,
+        content: Option<&str>,
+    ) -> Result<bool, RayClawError> {
+        let conn = self.lock_conn();
+        let rows = conn.execute(
+            "UPDATE deliveries
+             SET status = ?2, error = ?3, retryable = ?4, attempts = attempts + 1,
+                 content = COALESCE(?5, content), updated_at = ?6
+             WHERE id = ?1",
+            params![
+                id,
+                status,
+                error,
+                if retryable { 1 } else { 0 },
+                content,
+                chrono::Utc::now().to_rfc3339()
+            ],
+        )?;
+        Ok(rows > 0)
+    }
+
+    /// Most recent deliveries first, optionally narrowed to a chat and/or status.
+    pub fn get_deliveries(
+        &self,
+        chat_id: Option<i64>,
+        status: Option<&str>,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<Delivery>, RayClawError> {
+        let conn = self.lock_conn();
+        let mut stmt = conn.prepare(
+            "SELECT id, chat_id, channel, content, status, error, retryable, attempts, created_at, updated_at
+             FROM deliveries
+             WHERE (?1 IS NULL OR chat_id = ?1) AND (?2 IS NULL OR status = ?2)
+             ORDER BY id DESC
+             LIMIT ?3 OFFSET ?4",
+        )?;
+        let rows = stmt.query_map(
+            params![chat_id, status, limit.max(1) as i64, offset as i64],
+            delivery_from_row,
+        )?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+    }
+
+    pub fn get_delivery(&self, id: i64) -> Result<Option<Delivery>, RayClawError> {
+        let conn = self.lock_conn();
+        conn.query_row(
+            "SELECT id, chat_id, channel, content, status, error, retryable, attempts, created_at, updated_at
+             FROM deliveries WHERE id = ?1",
+            params![id],
+            delivery_from_row,
+        )
+        .optional()
+        .map_err(Into::into)
+    }
+
+    /// Start watching `url` for a chat. `baseline` is the content the first
+    /// check is compared against.
+    pub fn create_url_monitor(
+        &self,
+        chat_id: i64,
+        url: &str,
+        selector: Option<&str>,
+        interval_mins: i64,
+        baseline: &str,
+        next_check: &str,
+    ) -> Result<i64, RayClawError> {
+        let conn = self.lock_conn();
+        let now = chrono::Utc::now().to_rfc3339();
+        conn.execute(
+            "INSERT INTO url_monitors
+                (chat_id, url, selector, interval_mins, status, last_content, last_checked, next_check, created_at)
+             VALUES (?1, ?2, ?3, ?4, 'active', ?5, ?6, ?7, ?6)",
+            params![chat_id, url, selector, interval_mins, baseline, now, next_check],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    pub fn get_url_monitor(&self, id: i64) -> Result<Option<UrlMonitor>, RayClawError> {
+        let conn = self.lock_conn();
+        conn.query_row(
+            &format!("SELECT {URL_MONITOR_COLUMNS} FROM url_monitors WHERE id = ?1"),
+            params![id],
+            url_monitor_from_row,
+        )
+        .optional()
+        .map_err(Into::into)
+    }
+
+    pub fn get_url_monitors_for_chat(&self, chat_id: i64) -> Result<Vec<UrlMonitor>, RayClawError> {
+        let conn = self.lock_conn();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {URL_MONITOR_COLUMNS} FROM url_monitors WHERE chat_id = ?1 ORDER BY id"
+        ))?;
+        let rows = stmt.query_map(params![chat_id], url_monitor_from_row)?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+    }
+
+    /// Active monitors whose next check is at or before `now`.
+    pub fn get_due_url_monitors(&self, now: &str) -> Result<Vec<UrlMonitor>, RayClawError> {
+        let conn = self.lock_conn();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {URL_MONITOR_COLUMNS} FROM url_monitors
+             WHERE status = 'active' AND next_check <= ?1
+             ORDER BY next_check"
+        ))?;
+        let rows = stmt.query_map(params![now], url_monitor_from_row)?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+    }
+
+    /// Record one check. With `content`, the check succeeded and the stored
+    /// content is replaced (bumping the change count when `changed`); with
+    /// `error`, the previous content is kept as the baseline.
+    pub fn record_url_monitor_check(
+        &self,
+        id: i64,
+        checked_at: &str,
+        next_check: &str,
+        content: Option<&str>,
+        changed: bool,
+        error: Option<&str>,
+    ) -> Result<bool, RayClawError> {
+        let conn = self.lock_conn();
+        let rows = conn.execute(
+            "UPDATE url_monitors
+             SET last_checked = ?2, next_check = ?3,
+                 last_content = COALESCE(?4, last_content),
+                 last_changed = CASE WHEN ?5 THEN ?2 ELSE last_changed END,
+                 change_count = change_count + ?5,
+                 last_error = ?6
+             WHERE id = ?1",
+            params![
+                id,
+                checked_at,
+                next_check,
+                content,
+                if changed { 1 } else { 0 },
+                error
+            ],
+        )?;
+        Ok(rows > 0)
+    }
+
+    pub fn update_url_monitor_status(&self, id: i64, status: &str) -> Result<bool, RayClawError> {
+        let conn = self.lock_conn();
+        let rows = conn.execute(
+            "UPDATE url_monitors SET status = ?2 WHERE id = ?1",
+            params![id, status],
+        )?;
+        Ok(rows > 0)
+    }
+
+    pub fn delete_url_monitor(&self, id: i64) -> Result<bool, RayClawError> {
+        let conn = self.lock_conn();
+        let rows = conn.execute("DELETE FROM url_monitors WHERE id = ?1", params![id])?;
+        Ok(rows > 0)
+    }
+
+    pub fn get_notification_settings(
+        &self,
+        chat_id: i64,
+    ) -> Result<Option<NotificationSettings>, RayClawError> {
+        let conn = self.lock_conn();
+        conn.query_row(
+            "SELECT chat_id, mute_task_results, digest_time, quiet_start, quiet_end, min_severity, last_digest_at
+             FROM notification_settings WHERE chat_id = ?1",
+            params![chat_id],
+            |row| {
+                Ok(NotificationSettings {
+                    chat_id: row.get(0)?,
+                    mute_task_results: row.get::<_, i64>(1)? != 0,
+                    digest_time: row.get(2)?,
+                    quiet_start: row.get(3)?,
+                    quiet_end: row.get(4)?,
+                    min_severity: row.get(5)?,
+                    last_digest_at: row.get(6)?,
+                })
+            },
+        )
+        .optional()
+        .map_err(Into::into)
+    }
+
+    pub fn save_notification_settings(
+        &self,
+        settings: &NotificationSettings,
+    ) -> Result<(), RayClawError> {
+        let conn = self.lock_conn();
+        conn.execute(
+            "INSERT INTO notification_settings
+                (chat_id, mute_task_results, digest_time, quiet_start, quiet_end, min_severity, last_digest_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+             ON CONFLICT(chat_id) DO UPDATE SET
+                mute_task_results = excluded.mute_task_results,
+                digest_time = excluded.digest_time,
+                quiet_start = excluded.quiet_start,
+                quiet_end = excluded.quiet_end,
+                min_severity = excluded.min_severity,
+                last_digest_at = excluded.last_digest_at,
+                updated_at = excluded.updated_at",
+            params![
+                settings.chat_id,
+                if settings.mute_task_results { 1 } else { 0 },
+                settings.digest_time,
+                settings.quiet_start,
+                settings.quiet_end,
+                settings.min_severity,
+                settings.last_digest_at,
+                chrono::Utc::now().to_rfc3339()
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn queue_notification(
+        &self,
+        chat_id: i64,
+        content: &str,
+        severity: &str,
+    ) -> Result<i64, RayClawError> {
+        let conn = self.lock_conn();
+        conn.execute(
+            "INSERT INTO pending_notifications (chat_id, content, severity, created_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![chat_id, content, severity, chrono::Utc::now().to_rfc3339()],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    pub fn get_chats_with_pending_notifications(&self) -> Result<Vec<i64>, RayClawError> {
+        let conn = self.lock_conn();
+        let mut stmt =
+            conn.prepare("SELECT DISTINCT chat_id FROM pending_notifications ORDER BY chat_id")?;
+        let rows = stmt.query_map([], |row| row.get(0))?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+    }
+
+    /// Remove and return a chat's held notifications, oldest first.
+    pub fn take_pending_notifications(
+        &self,
+        chat_id: i64,
+    ) -> Result<Vec<PendingNotification>, RayClawError> {
+        let conn = self.lock_conn();
+        let tx = conn.unchecked_transaction()?;
+        let pending = {
+            let mut stmt = tx.prepare(
+                "SELECT id, chat_id, content, severity, created_at
+                 FROM pending_notifications WHERE chat_id = ?1 ORDER BY id",
+            )?;
+            let rows = stmt.query_map(params![chat_id], |row| {
+                Ok(PendingNotification {
+                    id: row.get(0)?,
+                    chat_id: row.get(1)?,
+                    content: row.get(2)?,
+                    severity: row.get(3)?,
+                    created_at: row.get(4)?,
+                })
+            })?;
+            rows.collect::<Result<Vec<_>, _>>()?
+        };
+        tx.execute(
+            "DELETE FROM pending_notifications WHERE chat_id = ?1",
+            params![chat_id],
+        )?;
+        tx.commit()?;
+        Ok(pending)
+    }
+
+    pub fn get_standing_instructions(&self, chat_id: i64) -> Result<Option<String>, RayClawError> {
+        let conn = self.lock_conn();
+        let result = conn.query_row(
+            "SELECT instructions FROM standing_instructions WHERE chat_id = ?1",
+            params![chat_id],
+            |row| row.get(0),
+        );
+        match result {
+            Ok(instructions) => Ok(Some(instructions)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn set_standing_instructions(
+        &self,
+        chat_id: i64,
+        instructions: &str,
+    ) -> Result<(), RayClawError> {
+        let conn = self.lock_conn();
+        let now = chrono::Utc::now().to_rfc3339();
+        conn.execute(
+            "INSERT INTO standing_instructions (chat_id, instructions, updated_at)
+             VALUES (?1, ?2, ?3)
+             ON CONFLICT(chat_id) DO UPDATE SET
+                instructions = ?2,
+                updated_at = ?3",
+            params![chat_id, instructions, now],
+        )?;
+        Ok(())
+    }
+
+    /// Returns false when the chat had no standing instructions.
+    pub fn clear_standing_instructions(&self, chat_id: i64) -> Result<bool, RayClawError> {
+        let conn = self.lock_conn();
+        let rows = conn.execute(
+            "DELETE FROM standing_instructions WHERE chat_id = ?1",
+            params![chat_id],
+        )?;
+        Ok(rows > 0)
+    }
+
+    /// Language tag last reported by the chat's platform (e.g. "de", "pt-BR").
+    pub fn get_chat_locale(&self, chat_id: i64) -> Result<Option<String>, RayClawError> {
+        let conn = self.lock_conn();
+        let result = conn.query_row(
+            "SELECT locale FROM chat_locales WHERE chat_id = ?1",
+            params![chat_id],
+            |row| row.get(0),
+        );
+        match result {
+            Ok(locale) => Ok(Some(locale)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn set_chat_locale(&self, chat_id: i64, locale: &str) -> Result<(), RayClawError> {
+        let conn = self.lock_conn();
+        let now = chrono::Utc::now().to_rfc3339();
+        conn.execute(
+            "INSERT INTO chat_locales (chat_id, locale, updated_at)
+             VALUES (?1, ?2, ?3)
+             ON CONFLICT(chat_id) DO UPDATE SET
+                locale = ?2,
+                updated_at = ?3
+             WHERE locale != ?2",
+            params![chat_id, locale, now],
+        )?;
+        Ok(())
+    }
+
+    /// Name of forum topic `thread_id` of a chat.
+    pub fn get_chat_topic(
+        &self,
+        chat_id: i64,
+        thread_id: i64,
+    ) -> Result<Option<String>, RayClawError> {
+        let conn = self.lock_conn();
+        let result = conn.query_row(
+            "SELECT topic FROM chat_topics WHERE chat_id = ?1 AND thread_id = ?2",
+            params![chat_id, thread_id],
+            |row| row.get(0),
+        );
+        match result {
+            Ok(topic) => Ok(Some(topic)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn set_chat_topic(
+        &self,
+        chat_id: i64,
+        thread_id: i64,
+        topic: &str,
+    ) -> Result<(), RayClawError> {
+        let conn = self.lock_conn();
+        let now = chrono::Utc::now().to_rfc3339();
+        conn.execute(
+            "INSERT INTO chat_topics (chat_id, thread_id, topic, updated_at)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(chat_id, thread_id) DO UPDATE SET
+                topic = ?3,
+                updated_at = ?4
+             WHERE topic != ?3",
+            params![chat_id, thread_id, topic, now],
+        )?;
+        Ok(())
+    }
+
+    /// Create or replace a chat's browsing profile. The cookie jar of an
+    /// existing profile is kept.
+    pub fn save_browsing_profile(
+        &self,
+        chat_id: i64,
+        name: &str,
+        domains: &[String],
+        username: Option<&str>,
+        sealed_password: Option<&str>,
+    ) -> Result<(), RayClawError> {
+        let conn = self.lock_conn();
+        let now = chrono::Utc::now().to_rfc3339();
+        conn.execute(
+            "INSERT INTO browsing_profiles
+                (chat_id, name, domains, username, sealed_password, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6)
+             ON CONFLICT(chat_id, name) DO UPDATE SET
+                domains = ?3,
+                username = ?4,
+                sealed_password = ?5,
+                updated_at = ?6",
+            params![
+                chat_id,
+                name,
+                domains.join(" "),
+                username,
+                sealed_password,
+                now
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_browsing_profile(
+        &self,
+        chat_id: i64,
+        name: &str,
+    ) -> Result<Option<BrowsingProfile>, RayClawError> {
+        let conn = self.lock_conn();
+        conn.query_row(
+            &format!(
+                "SELECT {BROWSING_PROFILE_COLUMNS} FROM browsing_profiles
+                 WHERE chat_id = ?1 AND name = ?2"
+            ),
+            params![chat_id, name],
+            browsing_profile_from_row,
+        )
+        .optional()
+        .map_err(Into::into)
+    }
+
+    pub fn get_browsing_profiles_for_chat(
+        &self,
+        chat_id: i64,
+    ) -> Result<Vec<BrowsingProfile>, RayClawError> {
+        let conn = self.lock_conn();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {BROWSING_PROFILE_COLUMNS} FROM browsing_profiles
+             WHERE chat_id = ?1 ORDER BY name"
+        ))?;
+        let rows = stmt.query_map(params![chat_id], browsing_profile_from_row)?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+    }
+
+    /// Replace a profile's cookie jar; `None` empties it. Returns false when
+    /// the profile does not exist.
+    pub fn set_browsing_profile_cookies(
+        &self,
+        chat_id: i64,
+        name: &str,
+        sealed_cookies: Option<&str>,
+    ) -> Result<bool, RayClawError> {
+        let conn = self.lock_conn();
+        let now = chrono::Utc::now().to_rfc3339();
+        let rows = conn.execute(
+            "UPDATE browsing_profiles SET sealed_cookies = ?3, updated_at = ?4
+             WHERE chat_id = ?1 AND name = ?2",
+            params![chat_id, name, sealed_cookies, now],
+        )?;
+        Ok(rows > 0)
+    }
+
+    pub fn delete_browsing_profile(&self, chat_id: i64, name: &str) -> Result<bool, RayClawError> {
+        let conn = self.lock_conn();
+        let rows = conn.execute(
+            "DELETE FROM browsing_profiles WHERE chat_id = ?1 AND name = ?2",
+            params![chat_id, name],
+        )?;
+        Ok(rows > 0)
+    }
+
+    pub fn get_calendar_feed_token(&self, chat_id: i64) -> Result<Option<String>, RayClawError> {
+        let conn = self.lock_conn();
+        let result = conn.query_row(
+            "SELECT token FROM calendar_feeds WHERE chat_id = ?1",
+            params![chat_id],
+            |row| row.get(0),
+        );
+        match result {
+            Ok(token) => Ok(Some(token)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Issue a new feed token for the chat, replacing any previous one.
+    pub fn create_calendar_feed_token(&self, chat_id: i64) -> Result<String, RayClawError> {
+        let conn = self.lock_conn();
+        let token = uuid::Uuid::new_v4().simple().to_string();
+        let now = chrono::Utc::now().to_rfc3339();
+        conn.execute(
+            "INSERT INTO calendar_feeds (chat_id, token, created_at)
+             VALUES (?1, ?2, ?3)
+             ON CONFLICT(chat_id) DO UPDATE SET
+                token = ?2,
+                created_at = ?3",
+            params![chat_id, token, now],
+        )?;
+        Ok(token)
+    }
+
+    /// Returns false when the chat had no feed.
+    pub fn delete_calendar_feed(&self, chat_id: i64) -> Result<bool, RayClawError> {
+        let conn = self.lock_conn();
+        let rows = conn.execute(
+            "DELETE FROM calendar_feeds WHERE chat_id = ?1",
+            params![chat_id],
+        )?;
+        Ok(rows > 0)
+    }
+
+    pub fn get_chat_for_calendar_token(&self, token: &str) -> Result<Option<i64>, RayClawError> {
+        let conn = self.lock_conn();
+        let result = conn.query_row(
+            "SELECT chat_id FROM calendar_feeds WHERE token = ?1",
+            params![token],
+            |row| row.get(0),
+        );
+        match result {
+            Ok(chat_id) => Ok(Some(chat_id)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn get_chat_model_override(
+        &self,
+        chat_id: i64,
+    ) -> Result<Option<ChatModelOverride>, RayClawError> {
+        let conn = self.lock_conn();
+        let result = conn.query_row(
+            "SELECT provider, model FROM chat_model_overrides WHERE chat_id = ?1",
+            params![chat_id],
+            |row| {
+                Ok(ChatModelOverride {
+                    provider: row.get(0)?,
+                    model: row.get(1)?,
+                })
+            },
+        );
+        match result {
+            Ok(o) => Ok(Some(o)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn set_chat_model_override(
+        &self,
+        chat_id: i64,
+        model_override: &ChatModelOverride,
+    ) -> Result<(), RayClawError> {
+        let conn = self.lock_conn();
+        let now = chrono::Utc::now().to_rfc3339();
+        conn.execute(
+            "INSERT INTO chat_model_overrides (chat_id, provider, model, updated_at)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(chat_id) DO UPDATE SET
+                provider = ?2,
+                model = ?3,
+                updated_at = ?4",
+            params![chat_id, model_override.provider, model_override.model, now],
+        )?;
+        Ok(())
+    }
+
+    /// Returns false when the chat had no override.
+    pub fn clear_chat_model_override(&self, chat_id: i64) -> Result<bool, RayClawError> {
+        let conn = self.lock_conn();
+        let rows = conn.execute(
+            "DELETE FROM chat_model_overrides WHERE chat_id = ?1",
+            params![chat_id],
+        )?;
+        Ok(rows > 0)
+    }
+
+    pub fn is_observer_chat(&self, chat_id: i64) -> Result<bool, RayClawError> {
+        let conn = self.lock_conn();
+        let count: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM observer_chats WHERE chat_id = ?1",
+            params![chat_id],
+            |row| row.get(0),
+        )?;
+        Ok(count > 0)
+    }
+
+    /// Turn observer mode on or off for a chat. Returns false when it was
+    /// already in the requested state.
+    pub fn set_observer_mode(&self, chat_id: i64, enabled: bool) -> Result<bool, RayClawError> {
+        let conn = self.lock_conn();
+        let rows = if enabled {
+            conn.execute(
+                "INSERT OR IGNORE INTO observer_chats (chat_id, enabled_at) VALUES (?1, ?2)",
+                params![chat_id, chrono::Utc::now().to_rfc3339()],
+            )?
+        } else {
+            conn.execute(
+                "DELETE FROM observer_chats WHERE chat_id = ?1",
+                params![chat_id],
+            )?
+        };
+        Ok(rows > 0)
+    }
+
+    /// Remember `(hash, url)` links sent to a chat by a scheduled task.
+    /// Returns how many were new.
+    pub fn record_digest_links(
+        &self,
+        chat_id: i64,
+        task_id: i64,
+        links: &[(String, String)],
+    ) -> Result<usize, RayClawError> {
+        let mut conn = self.lock_conn();
+        let tx = conn.transaction()?;
+        let now = chrono::Utc::now().to_rfc3339();
+        let mut inserted = 0;
+        for (hash, url) in links {
+            inserted += tx.execute(
+                "INSERT OR IGNORE INTO digest_seen_links (chat_id, link_hash, url, task_id, first_seen_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![chat_id, hash, url, task_id, now],
+            )?;
+        }
+        tx.commit()?;
+        Ok(inserted)
+    }
+
+    /// Links first sent to the chat at or after `since`, newest first.
+    pub fn get_digest_links_since(
+        &self,
+        chat_id: i64,
+        since: &str,
+        limit: usize,
+    ) -> Result<Vec<String>, RayClawError> {
+        let conn = self.lock_conn();
+        let mut stmt = conn.prepare(
+            "SELECT url FROM digest_seen_links
+             WHERE chat_id = ?1 AND first_seen_at >= ?2
+             ORDER BY first_seen_at DESC, rowid DESC
+             LIMIT ?3",
+        )?;
+        let rows = stmt
+            .query_map(params![chat_id, since, limit as i64], |row| row.get(0))?
+            .collect::<Result<Vec<String>, _>>()?;
+        Ok(rows)
+    }
+
+    pub fn prune_digest_links(&self, before: &str) -> Result<usize, RayClawError> {
+        let conn = self.lock_conn();
+        let rows = conn.execute(
+            "DELETE FROM digest_seen_links WHERE first_seen_at < ?1",
+            params![before],
+        )?;
+        Ok(rows)
+    }
+
+    pub fn get_memory_injection_logs(
+        &self,
+        chat_id: Option<i64>,
+        since: Option<&str>,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<MemoryInjectionLog>, RayClawError> {
+        let conn = self.lock_conn();
+        let mut query = String::from(
+            "SELECT id, chat_id, created_at, retrieval_method, candidate_count, selected_count, omitted_count, tokens_est
+             FROM memory_injection_logs",
+        );
+        let mut where_parts: Vec<&str> = Vec::new();
+        if chat_id.is_some() {
+            where_parts.push("chat_id = ?1");
+        }
+        if since.is_some() {
+            where_parts.push(if chat_id.is_some() {
+                "unixepoch(created_at) >= unixepoch(?2)"
+            } else {
+                "unixepoch(created_at) >= unixepoch(?1)"
+            });
+        }
+        if !where_parts.is_empty() {
+            query.push_str(" WHERE ");
+            query.push_str(&where_parts.join(" AND "));
+        }
+        query.push_str(" ORDER BY unixepoch(created_at) ASC LIMIT ");
+        query.push_str(&limit.max(1).to_string());
+        query.push_str(" OFFSET ");
+        query.push_str(&offset.to_string());
+
+        let mut stmt = conn.prepare(&query)?;
+        let mapper = |row: &rusqlite::Row<'_>| {
+            Ok(MemoryInjectionLog {
+                id: row.get(0)?,
+                chat_id: row.get(1)?,
+                created_at: row.get(2)?,
+                retrieval_method: row.get(3)?,
+                candidate_count: row.get(4)?,
+                selected_count: row.get(5)?,
+                omitted_count: row.get(6)?,
+                tokens_est: row.get(7)?,
+            })
+        };
+        let rows = match (chat_id, since) {
+            (Some(cid), Some(ts)) => stmt.query_map(params![cid, ts], mapper)?,
+            (Some(cid), None) => stmt.query_map(params![cid], mapper)?,
+            (None, Some(ts)) => stmt.query_map(params![ts], mapper)?,
+            (None, None) => stmt.query_map([], mapper)?,
+        };
+        rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_db() -> (Database, std::path::PathBuf) {
+        let dir = std::env::temp_dir().join(format!("rayclaw_test_{}", uuid::Uuid::new_v4()));
+        let db = Database::new(dir.to_str().unwrap()).unwrap();
+        (db, dir)
+    }
+
+    fn cleanup(dir: &std::path::Path) {
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_new_database_creates_tables() {
+        let (db, dir) = test_db();
+        // Verify we can do basic operations without errors
+        let msgs = db.get_recent_messages(1, 10).unwrap();
+        assert!(msgs.is_empty());
+        let tasks = db.get_due_tasks("2099-01-01T00:00:00Z").unwrap();
+        assert!(tasks.is_empty());
+        cleanup(&dir);
+    }
+
+    #[test]
+    fn test_schema_version_is_tracked() {
+        let (db, dir) = test_db();
+        let conn = db.lock_conn();
+        let version: String = conn
+            .query_row(
+                "SELECT value FROM db_meta WHERE key = 'schema_version'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(version, SCHEMA_VERSION_CURRENT.to_string());
+        drop(conn);
+        cleanup(&dir);
+    }
+
+    #[test]
+    fn test_legacy_schema_is_upgraded_to_current_version() {
+        let dir =
+            std::env::temp_dir().join(format!("rayclaw_legacy_upgrade_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db_path = dir.join("rayclaw.db");
+        let conn = Connection::open(&db_path).unwrap();
+        conn.execute_batch(
+            "PRAGMA journal_mode=WAL;
+             CREATE TABLE chats (
+                chat_id INTEGER PRIMARY KEY,
+                chat_title TEXT,
+                chat_type TEXT NOT NULL DEFAULT 'private',
+                last_message_time TEXT NOT NULL
+             );
+             CREATE TABLE messages (
+                id TEXT NOT NULL,
+                chat_id INTEGER NOT NULL,
+                sender_name TEXT NOT NULL,
+                content TEXT NOT NULL,
+                is_from_bot INTEGER NOT NULL DEFAULT 0,
                 timestamp TEXT NOT NULL,
                 PRIMARY KEY (id, chat_id)
              );
@@ -2684,842 +4430,1602 @@ mod tests {
              );",
         )
         .unwrap();
-        drop(conn);
+        drop(conn);
+
+        let db = Database::new(dir.to_str().unwrap()).unwrap();
+        let conn = db.lock_conn();
+        let version: String = conn
+            .query_row(
+                "SELECT value FROM db_meta WHERE key = 'schema_version'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(version, SCHEMA_VERSION_CURRENT.to_string());
+
+        let has_confidence = table_has_column(&conn, "memories", "confidence").unwrap();
+        let has_source = table_has_column(&conn, "memories", "source").unwrap();
+        let has_last_seen = table_has_column(&conn, "memories", "last_seen_at").unwrap();
+        let has_archived = table_has_column(&conn, "memories", "is_archived").unwrap();
+        assert!(has_confidence && has_source && has_last_seen && has_archived);
+
+        let supersede_table_exists: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name='memory_supersede_edges'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(supersede_table_exists, 1);
+        drop(conn);
+        cleanup(&dir);
+    }
+
+    #[test]
+    fn test_upsert_chat_insert_and_update() {
+        let (db, dir) = test_db();
+        db.upsert_chat(100, Some("Test Chat"), "group").unwrap();
+        // Update title
+        db.upsert_chat(100, Some("New Title"), "group").unwrap();
+        // Insert without title
+        db.upsert_chat(200, None, "private").unwrap();
+        cleanup(&dir);
+    }
+
+    #[test]
+    fn test_store_and_retrieve_message() {
+        let (db, dir) = test_db();
+        let msg = StoredMessage {
+            id: "msg1".into(),
+            chat_id: 100,
+            sender_name: "alice".into(),
+            content: "hello".into(),
+            is_from_bot: false,
+            timestamp: "2024-01-01T00:00:00Z".into(),
+            thread_id: None,
+        };
+        db.store_message(&msg).unwrap();
+
+        let messages = db.get_recent_messages(100, 10).unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].id, "msg1");
+        assert_eq!(messages[0].sender_name, "alice");
+        assert_eq!(messages[0].content, "hello");
+        assert!(!messages[0].is_from_bot);
+        cleanup(&dir);
+    }
+
+    #[test]
+    fn test_store_message_upsert() {
+        let (db, dir) = test_db();
+        let msg = StoredMessage {
+            id: "msg1".into(),
+            chat_id: 100,
+            sender_name: "alice".into(),
+            content: "original".into(),
+            is_from_bot: false,
+            timestamp: "2024-01-01T00:00:00Z".into(),
+            thread_id: None,
+        };
+        db.store_message(&msg).unwrap();
+
+        // Store same id again with different content (INSERT OR REPLACE)
+        let msg2 = StoredMessage {
+            id: "msg1".into(),
+            chat_id: 100,
+            sender_name: "alice".into(),
+            content: "updated".into(),
+            is_from_bot: false,
+            timestamp: "2024-01-01T00:00:01Z".into(),
+            thread_id: None,
+        };
+        db.store_message(&msg2).unwrap();
+
+        let messages = db.get_recent_messages(100, 10).unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].content, "updated");
+        cleanup(&dir);
+    }
+
+    #[test]
+    fn test_store_messages_bulk_skips_existing() {
+        let (db, dir) = test_db();
+        let make = |id: &str, content: &str| StoredMessage {
+            id: id.into(),
+            chat_id: 100,
+            sender_name: "alice".into(),
+            content: content.into(),
+            is_from_bot: false,
+            timestamp: format!("2024-01-01T00:00:0{id}Z"),
+            thread_id: None,
+        };
+        db.store_message(&make("1", "live")).unwrap();
+
+        let inserted = db
+            .store_messages_bulk(&[make("1", "imported"), make("2", "b"), make("3", "c")])
+            .unwrap();
+        assert_eq!(inserted, 2);
+
+        let messages = db.get_recent_messages(100, 10).unwrap();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[0].content, "live");
+
+        // Re-running the same import is a no-op
+        let inserted = db.store_messages_bulk(&[make("2", "b")]).unwrap();
+        assert_eq!(inserted, 0);
+        cleanup(&dir);
+    }
+
+    #[test]
+    fn test_get_recent_messages_ordering_and_limit() {
+        let (db, dir) = test_db();
+        for i in 0..5 {
+            let msg = StoredMessage {
+                id: format!("msg{i}"),
+                chat_id: 100,
+                sender_name: "alice".into(),
+                content: format!("message {i}"),
+                is_from_bot: false,
+                timestamp: format!("2024-01-01T00:00:0{i}Z"),
+                thread_id: None,
+            };
+            db.store_message(&msg).unwrap();
+        }
+
+        // Limit to 3 - should get the 3 most recent, but reversed to oldest-first
+        let messages = db.get_recent_messages(100, 3).unwrap();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[0].content, "message 2"); // oldest of the 3 most recent
+        assert_eq!(messages[1].content, "message 3");
+        assert_eq!(messages[2].content, "message 4"); // most recent
+
+        // Different chat_id should be empty
+        let messages = db.get_recent_messages(200, 10).unwrap();
+        assert!(messages.is_empty());
+        cleanup(&dir);
+    }
+
+    #[test]
+    fn test_get_messages_since_last_bot_response_with_bot_msg() {
+        let (db, dir) = test_db();
+
+        // User message 1
+        db.store_message(&StoredMessage {
+            id: "m1".into(),
+            chat_id: 100,
+            sender_name: "alice".into(),
+            content: "hi".into(),
+            is_from_bot: false,
+            timestamp: "2024-01-01T00:00:01Z".into(),
+            thread_id: None,
+        })
+        .unwrap();
+
+        // Bot response
+        db.store_message(&StoredMessage {
+            id: "m2".into(),
+            chat_id: 100,
+            sender_name: "bot".into(),
+            content: "hello!".into(),
+            is_from_bot: true,
+            timestamp: "2024-01-01T00:00:02Z".into(),
+            thread_id: None,
+        })
+        .unwrap();
+
+        // User message 2 (after bot response)
+        db.store_message(&StoredMessage {
+            id: "m3".into(),
+            chat_id: 100,
+            sender_name: "alice".into(),
+            content: "how are you?".into(),
+            is_from_bot: false,
+            timestamp: "2024-01-01T00:00:03Z".into(),
+            thread_id: None,
+        })
+        .unwrap();
+
+        // User message 3
+        db.store_message(&StoredMessage {
+            id: "m4".into(),
+            chat_id: 100,
+            sender_name: "bob".into(),
+            content: "me too".into(),
+            is_from_bot: false,
+            timestamp: "2024-01-01T00:00:04Z".into(),
+            thread_id: None,
+        })
+        .unwrap();
+
+        let messages = db
+            .get_messages_since_last_bot_response(100, None, 50, 10)
+            .unwrap();
+        // Should include the bot message and everything after it
+        assert!(messages.len() >= 2);
+        // First should be the bot msg or after it
+        assert_eq!(messages[0].id, "m2"); // the bot message (timestamp >= bot's timestamp)
+        assert_eq!(messages[1].id, "m3");
+        assert_eq!(messages[2].id, "m4");
+        cleanup(&dir);
+    }
+
+    #[test]
+    fn test_get_messages_since_last_bot_response_no_bot_msg() {
+        let (db, dir) = test_db();
+
+        for i in 0..5 {
+            db.store_message(&StoredMessage {
+                id: format!("m{i}"),
+                chat_id: 100,
+                sender_name: "alice".into(),
+                content: format!("msg {i}"),
+                is_from_bot: false,
+                timestamp: format!("2024-01-01T00:00:0{i}Z"),
+                thread_id: None,
+            })
+            .unwrap();
+        }
+
+        // Fallback to last 3
+        let messages = db
+            .get_messages_since_last_bot_response(100, None, 50, 3)
+            .unwrap();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[0].content, "msg 2");
+        assert_eq!(messages[2].content, "msg 4");
+        cleanup(&dir);
+    }
+
+    #[test]
+    fn test_create_and_get_scheduled_task() {
+        let (db, dir) = test_db();
+        let id = db
+            .create_scheduled_task(
+                100,
+                "say hello",
+                "cron",
+                "0 */5 * * * *",
+                "2024-06-01T00:05:00Z",
+            )
+            .unwrap();
+        assert!(id > 0);
+
+        let tasks = db.get_tasks_for_chat(100).unwrap();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].prompt, "say hello");
+        assert_eq!(tasks[0].schedule_type, "cron");
+        assert_eq!(tasks[0].status, "active");
+        cleanup(&dir);
+    }
+
+    #[test]
+    fn test_get_due_tasks() {
+        let (db, dir) = test_db();
+        db.create_scheduled_task(100, "task1", "cron", "0 * * * * *", "2024-01-01T00:00:00Z")
+            .unwrap();
+        db.create_scheduled_task(
+            100,
+            "task2",
+            "once",
+            "2099-12-31T00:00:00Z",
+            "2099-12-31T00:00:00Z",
+        )
+        .unwrap();
 
-        let db = Database::new(dir.to_str().unwrap()).unwrap();
-        let conn = db.lock_conn();
-        let version: String = conn
-            .query_row(
-                "SELECT value FROM db_meta WHERE key = 'schema_version'",
-                [],
-                |row| row.get(0),
+        // Only task1 is due
+        let due = db.get_due_tasks("2024-06-01T00:00:00Z").unwrap();
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].prompt, "task1");
+
+        // Both are due in the far future
+        let due = db.get_due_tasks("2100-01-01T00:00:00Z").unwrap();
+        assert_eq!(due.len(), 2);
+        cleanup(&dir);
+    }
+
+    #[test]
+    fn test_get_tasks_for_chat_filters_status() {
+        let (db, dir) = test_db();
+        let id1 = db
+            .create_scheduled_task(
+                100,
+                "active task",
+                "cron",
+                "0 * * * * *",
+                "2024-01-01T00:00:00Z",
             )
             .unwrap();
-        assert_eq!(version, SCHEMA_VERSION_CURRENT.to_string());
+        let id2 = db
+            .create_scheduled_task(
+                100,
+                "to cancel",
+                "once",
+                "2024-01-01T00:00:00Z",
+                "2024-01-01T00:00:00Z",
+            )
+            .unwrap();
+        db.update_task_status(id2, "cancelled").unwrap();
 
-        let has_confidence = table_has_column(&conn, "memories", "confidence").unwrap();
-        let has_source = table_has_column(&conn, "memories", "source").unwrap();
-        let has_last_seen = table_has_column(&conn, "memories", "last_seen_at").unwrap();
-        let has_archived = table_has_column(&conn, "memories", "is_archived").unwrap();
-        assert!(has_confidence && has_source && has_last_seen && has_archived);
+        // Only active/paused tasks should be returned
+        let tasks = db.get_tasks_for_chat(100).unwrap();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].id, id1);
 
-        let supersede_table_exists: i64 = conn
-            .query_row(
-                "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name='memory_supersede_edges'",
-                [],
-                |row| row.get(0),
+        // Pause the active one
+        db.update_task_status(id1, "paused").unwrap();
+        let tasks = db.get_tasks_for_chat(100).unwrap();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].status, "paused");
+        cleanup(&dir);
+    }
+
+    #[test]
+    fn test_update_task_status() {
+        let (db, dir) = test_db();
+        let id = db
+            .create_scheduled_task(100, "test", "cron", "0 * * * * *", "2024-01-01T00:00:00Z")
+            .unwrap();
+
+        assert!(db.update_task_status(id, "paused").unwrap());
+        assert!(db.update_task_status(id, "active").unwrap());
+        assert!(db.update_task_status(id, "cancelled").unwrap());
+
+        // Non-existent task
+        assert!(!db.update_task_status(9999, "paused").unwrap());
+        cleanup(&dir);
+    }
+
+    #[test]
+    fn test_update_task_after_run_cron() {
+        let (db, dir) = test_db();
+        let id = db
+            .create_scheduled_task(100, "test", "cron", "0 * * * * *", "2024-01-01T00:00:00Z")
+            .unwrap();
+
+        db.update_task_after_run(id, "2024-01-01T00:01:00Z", Some("2024-01-01T00:02:00Z"))
+            .unwrap();
+
+        let tasks = db.get_tasks_for_chat(100).unwrap();
+        assert_eq!(tasks[0].last_run.as_deref(), Some("2024-01-01T00:01:00Z"));
+        assert_eq!(tasks[0].next_run, "2024-01-01T00:02:00Z");
+        assert_eq!(tasks[0].status, "active");
+        cleanup(&dir);
+    }
+
+    #[test]
+    fn test_update_task_after_run_one_shot() {
+        let (db, dir) = test_db();
+        let id = db
+            .create_scheduled_task(
+                100,
+                "test",
+                "once",
+                "2024-01-01T00:00:00Z",
+                "2024-01-01T00:00:00Z",
+            )
+            .unwrap();
+
+        // One-shot: no next_run, should mark as completed
+        db.update_task_after_run(id, "2024-01-01T00:00:00Z", None)
+            .unwrap();
+
+        // Should not appear in active/paused list
+        let tasks = db.get_tasks_for_chat(100).unwrap();
+        assert!(tasks.is_empty());
+        cleanup(&dir);
+    }
+
+    #[test]
+    fn test_delete_task() {
+        let (db, dir) = test_db();
+        let id = db
+            .create_scheduled_task(100, "test", "cron", "0 * * * * *", "2024-01-01T00:00:00Z")
+            .unwrap();
+
+        assert!(db.delete_task(id).unwrap());
+        assert!(!db.delete_task(id).unwrap()); // already deleted
+
+        let tasks = db.get_tasks_for_chat(100).unwrap();
+        assert!(tasks.is_empty());
+        cleanup(&dir);
+    }
+
+    #[test]
+    fn test_get_all_messages() {
+        let (db, dir) = test_db();
+        for i in 0..5 {
+            db.store_message(&StoredMessage {
+                id: format!("msg{i}"),
+                chat_id: 100,
+                sender_name: "alice".into(),
+                content: format!("message {i}"),
+                is_from_bot: false,
+                timestamp: format!("2024-01-01T00:00:0{i}Z"),
+                thread_id: None,
+            })
+            .unwrap();
+        }
+
+        let messages = db.get_all_messages(100).unwrap();
+        assert_eq!(messages.len(), 5);
+        assert_eq!(messages[0].content, "message 0");
+        assert_eq!(messages[4].content, "message 4");
+
+        // Different chat should be empty
+        assert!(db.get_all_messages(200).unwrap().is_empty());
+        cleanup(&dir);
+    }
+
+    #[test]
+    fn test_log_task_run() {
+        let (db, dir) = test_db();
+        let task_id = db
+            .create_scheduled_task(100, "test", "cron", "0 * * * * *", "2024-01-01T00:00:00Z")
+            .unwrap();
+
+        let log_id = db
+            .log_task_run(
+                task_id,
+                100,
+                "2024-01-01T00:00:00Z",
+                "2024-01-01T00:00:05Z",
+                5000,
+                true,
+                Some("Success"),
+            )
+            .unwrap();
+        assert!(log_id > 0);
+
+        let logs = db.get_task_run_logs(task_id, 10).unwrap();
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].task_id, task_id);
+        assert_eq!(logs[0].duration_ms, 5000);
+        assert!(logs[0].success);
+        assert_eq!(logs[0].result_summary.as_deref(), Some("Success"));
+        cleanup(&dir);
+    }
+
+    #[test]
+    fn test_get_task_run_logs_ordering_and_limit() {
+        let (db, dir) = test_db();
+        let task_id = db
+            .create_scheduled_task(100, "test", "cron", "0 * * * * *", "2024-01-01T00:00:00Z")
+            .unwrap();
+
+        for i in 0..5 {
+            db.log_task_run(
+                task_id,
+                100,
+                &format!("2024-01-01T00:0{i}:00Z"),
+                &format!("2024-01-01T00:0{i}:05Z"),
+                5000,
+                true,
+                Some(&format!("Run {i}")),
             )
             .unwrap();
-        assert_eq!(supersede_table_exists, 1);
-        drop(conn);
+        }
+
+        // Limit to 3, most recent first
+        let logs = db.get_task_run_logs(task_id, 3).unwrap();
+        assert_eq!(logs.len(), 3);
+        assert_eq!(logs[0].result_summary.as_deref(), Some("Run 4")); // most recent
+        assert_eq!(logs[2].result_summary.as_deref(), Some("Run 2"));
         cleanup(&dir);
     }
 
     #[test]
-    fn test_upsert_chat_insert_and_update() {
+    fn test_save_and_load_session() {
         let (db, dir) = test_db();
-        db.upsert_chat(100, Some("Test Chat"), "group").unwrap();
-        // Update title
-        db.upsert_chat(100, Some("New Title"), "group").unwrap();
-        // Insert without title
-        db.upsert_chat(200, None, "private").unwrap();
+        let json = r#"[{"role":"user","content":"hello"}]"#;
+        db.save_session(100, None, json).unwrap();
+
+        let result = db.load_session(100, None).unwrap();
+        assert!(result.is_some());
+        let (loaded_json, updated_at) = result.unwrap();
+        assert_eq!(loaded_json, json);
+        assert!(!updated_at.is_empty());
+
+        // Upsert: save again with different data
+        let json2 = r#"[{"role":"user","content":"hello"},{"role":"assistant","content":"hi"}]"#;
+        db.save_session(100, None, json2).unwrap();
+        let (loaded_json2, _) = db.load_session(100, None).unwrap().unwrap();
+        assert_eq!(loaded_json2, json2);
+
         cleanup(&dir);
     }
 
     #[test]
-    fn test_store_and_retrieve_message() {
+    fn test_load_session_nonexistent() {
         let (db, dir) = test_db();
-        let msg = StoredMessage {
-            id: "msg1".into(),
-            chat_id: 100,
-            sender_name: "alice".into(),
-            content: "hello".into(),
-            is_from_bot: false,
-            timestamp: "2024-01-01T00:00:00Z".into(),
-        };
-        db.store_message(&msg).unwrap();
-
-        let messages = db.get_recent_messages(100, 10).unwrap();
-        assert_eq!(messages.len(), 1);
-        assert_eq!(messages[0].id, "msg1");
-        assert_eq!(messages[0].sender_name, "alice");
-        assert_eq!(messages[0].content, "hello");
-        assert!(!messages[0].is_from_bot);
+        let result = db.load_session(999, None).unwrap();
+        assert!(result.is_none());
         cleanup(&dir);
     }
 
     #[test]
-    fn test_store_message_upsert() {
+    fn test_delete_session() {
         let (db, dir) = test_db();
-        let msg = StoredMessage {
-            id: "msg1".into(),
-            chat_id: 100,
-            sender_name: "alice".into(),
-            content: "original".into(),
-            is_from_bot: false,
-            timestamp: "2024-01-01T00:00:00Z".into(),
-        };
-        db.store_message(&msg).unwrap();
-
-        // Store same id again with different content (INSERT OR REPLACE)
-        let msg2 = StoredMessage {
-            id: "msg1".into(),
-            chat_id: 100,
-            sender_name: "alice".into(),
-            content: "updated".into(),
-            is_from_bot: false,
-            timestamp: "2024-01-01T00:00:01Z".into(),
-        };
-        db.store_message(&msg2).unwrap();
-
-        let messages = db.get_recent_messages(100, 10).unwrap();
-        assert_eq!(messages.len(), 1);
-        assert_eq!(messages[0].content, "updated");
+        db.save_session(100, None, "[]").unwrap();
+        assert!(db.delete_session(100).unwrap());
+        assert!(db.load_session(100, None).unwrap().is_none());
+        // Delete again returns false
+        assert!(!db.delete_session(100).unwrap());
         cleanup(&dir);
     }
 
     #[test]
-    fn test_get_recent_messages_ordering_and_limit() {
+    fn test_clear_chat_context_removes_session_and_messages_only() {
         let (db, dir) = test_db();
-        for i in 0..5 {
-            let msg = StoredMessage {
-                id: format!("msg{i}"),
-                chat_id: 100,
-                sender_name: "alice".into(),
-                content: format!("message {i}"),
-                is_from_bot: false,
-                timestamp: format!("2024-01-01T00:00:0{i}Z"),
-            };
-            db.store_message(&msg).unwrap();
-        }
+        db.upsert_chat(100, Some("chat-100"), "private").unwrap();
+        db.save_session(100, None, r#"[{"role":"user","content":"hi"}]"#)
+            .unwrap();
+        db.store_message(&StoredMessage {
+            id: "m1".into(),
+            chat_id: 100,
+            sender_name: "alice".into(),
+            content: "hello".into(),
+            is_from_bot: false,
+            timestamp: "2024-01-01T00:00:01Z".into(),
+            thread_id: None,
+        })
+        .unwrap();
+        db.insert_memory(Some(100), "User likes Rust", "PROFILE")
+            .unwrap();
 
-        // Limit to 3 - should get the 3 most recent, but reversed to oldest-first
-        let messages = db.get_recent_messages(100, 3).unwrap();
-        assert_eq!(messages.len(), 3);
-        assert_eq!(messages[0].content, "message 2"); // oldest of the 3 most recent
-        assert_eq!(messages[1].content, "message 3");
-        assert_eq!(messages[2].content, "message 4"); // most recent
+        assert!(db.clear_chat_context(100).unwrap());
+        assert!(db.load_session(100, None).unwrap().is_none());
+        assert!(db.get_recent_messages(100, 10).unwrap().is_empty());
+        assert!(!db.search_memories(100, "Rust", 10).unwrap().is_empty());
+        assert!(db.get_chat_type(100).unwrap().is_some());
 
-        // Different chat_id should be empty
-        let messages = db.get_recent_messages(200, 10).unwrap();
-        assert!(messages.is_empty());
         cleanup(&dir);
     }
 
     #[test]
-    fn test_get_messages_since_last_bot_response_with_bot_msg() {
+    fn test_get_new_user_messages_since() {
         let (db, dir) = test_db();
 
-        // User message 1
+        // Messages before the cutoff
         db.store_message(&StoredMessage {
             id: "m1".into(),
             chat_id: 100,
             sender_name: "alice".into(),
-            content: "hi".into(),
+            content: "old msg".into(),
             is_from_bot: false,
             timestamp: "2024-01-01T00:00:01Z".into(),
+            thread_id: None,
         })
         .unwrap();
 
-        // Bot response
+        // Bot message at the cutoff
         db.store_message(&StoredMessage {
             id: "m2".into(),
             chat_id: 100,
             sender_name: "bot".into(),
-            content: "hello!".into(),
+            content: "response".into(),
             is_from_bot: true,
             timestamp: "2024-01-01T00:00:02Z".into(),
+            thread_id: None,
         })
         .unwrap();
 
-        // User message 2 (after bot response)
+        // User messages after cutoff
         db.store_message(&StoredMessage {
             id: "m3".into(),
             chat_id: 100,
             sender_name: "alice".into(),
-            content: "how are you?".into(),
+            content: "new msg 1".into(),
             is_from_bot: false,
             timestamp: "2024-01-01T00:00:03Z".into(),
+            thread_id: None,
         })
         .unwrap();
 
-        // User message 3
         db.store_message(&StoredMessage {
             id: "m4".into(),
             chat_id: 100,
             sender_name: "bob".into(),
-            content: "me too".into(),
+            content: "new msg 2".into(),
             is_from_bot: false,
             timestamp: "2024-01-01T00:00:04Z".into(),
+            thread_id: None,
         })
         .unwrap();
 
-        let messages = db
-            .get_messages_since_last_bot_response(100, 50, 10)
-            .unwrap();
-        // Should include the bot message and everything after it
-        assert!(messages.len() >= 2);
-        // First should be the bot msg or after it
-        assert_eq!(messages[0].id, "m2"); // the bot message (timestamp >= bot's timestamp)
-        assert_eq!(messages[1].id, "m3");
-        assert_eq!(messages[2].id, "m4");
-        cleanup(&dir);
-    }
-
-    #[test]
-    fn test_get_messages_since_last_bot_response_no_bot_msg() {
-        let (db, dir) = test_db();
-
-        for i in 0..5 {
-            db.store_message(&StoredMessage {
-                id: format!("m{i}"),
-                chat_id: 100,
-                sender_name: "alice".into(),
-                content: format!("msg {i}"),
-                is_from_bot: false,
-                timestamp: format!("2024-01-01T00:00:0{i}Z"),
-            })
-            .unwrap();
-        }
-
-        // Fallback to last 3
-        let messages = db.get_messages_since_last_bot_response(100, 50, 3).unwrap();
-        assert_eq!(messages.len(), 3);
-        assert_eq!(messages[0].content, "msg 2");
-        assert_eq!(messages[2].content, "msg 4");
-        cleanup(&dir);
-    }
-
-    #[test]
-    fn test_create_and_get_scheduled_task() {
-        let (db, dir) = test_db();
-        let id = db
-            .create_scheduled_task(
-                100,
-                "say hello",
-                "cron",
-                "0 */5 * * * *",
-                "2024-06-01T00:05:00Z",
-            )
-            .unwrap();
-        assert!(id > 0);
-
-        let tasks = db.get_tasks_for_chat(100).unwrap();
-        assert_eq!(tasks.len(), 1);
-        assert_eq!(tasks[0].prompt, "say hello");
-        assert_eq!(tasks[0].schedule_type, "cron");
-        assert_eq!(tasks[0].status, "active");
-        cleanup(&dir);
-    }
-
-    #[test]
-    fn test_get_due_tasks() {
-        let (db, dir) = test_db();
-        db.create_scheduled_task(100, "task1", "cron", "0 * * * * *", "2024-01-01T00:00:00Z")
-            .unwrap();
-        db.create_scheduled_task(
-            100,
-            "task2",
-            "once",
-            "2099-12-31T00:00:00Z",
-            "2099-12-31T00:00:00Z",
-        )
-        .unwrap();
-
-        // Only task1 is due
-        let due = db.get_due_tasks("2024-06-01T00:00:00Z").unwrap();
-        assert_eq!(due.len(), 1);
-        assert_eq!(due[0].prompt, "task1");
-
-        // Both are due in the far future
-        let due = db.get_due_tasks("2100-01-01T00:00:00Z").unwrap();
-        assert_eq!(due.len(), 2);
-        cleanup(&dir);
-    }
-
-    #[test]
-    fn test_get_tasks_for_chat_filters_status() {
-        let (db, dir) = test_db();
-        let id1 = db
-            .create_scheduled_task(
-                100,
-                "active task",
-                "cron",
-                "0 * * * * *",
-                "2024-01-01T00:00:00Z",
-            )
-            .unwrap();
-        let id2 = db
-            .create_scheduled_task(
-                100,
-                "to cancel",
-                "once",
-                "2024-01-01T00:00:00Z",
-                "2024-01-01T00:00:00Z",
-            )
-            .unwrap();
-        db.update_task_status(id2, "cancelled").unwrap();
-
-        // Only active/paused tasks should be returned
-        let tasks = db.get_tasks_for_chat(100).unwrap();
-        assert_eq!(tasks.len(), 1);
-        assert_eq!(tasks[0].id, id1);
+        // Bot message after cutoff (should be excluded - only non-bot)
+        db.store_message(&StoredMessage {
+            id: "m5".into(),
+            chat_id: 100,
+            sender_name: "bot".into(),
+            content: "bot again".into(),
+            is_from_bot: true,
+            timestamp: "2024-01-01T00:00:05Z".into(),
+            thread_id: None,
+        })
+        .unwrap();
+
+        let msgs = db
+            .get_new_user_messages_since(100, None, "2024-01-01T00:00:02Z")
+            .unwrap();
+        assert_eq!(msgs.len(), 2);
+        assert_eq!(msgs[0].content, "new msg 1");
+        assert_eq!(msgs[1].content, "new msg 2");
 
-        // Pause the active one
-        db.update_task_status(id1, "paused").unwrap();
-        let tasks = db.get_tasks_for_chat(100).unwrap();
-        assert_eq!(tasks.len(), 1);
-        assert_eq!(tasks[0].status, "paused");
         cleanup(&dir);
     }
 
     #[test]
-    fn test_update_task_status() {
+    fn test_get_messages_since_includes_user_and_bot() {
         let (db, dir) = test_db();
-        let id = db
-            .create_scheduled_task(100, "test", "cron", "0 * * * * *", "2024-01-01T00:00:00Z")
-            .unwrap();
+        db.store_message(&StoredMessage {
+            id: "m1".into(),
+            chat_id: 100,
+            sender_name: "alice".into(),
+            content: "old".into(),
+            is_from_bot: false,
+            timestamp: "2024-01-01T00:00:01Z".into(),
+            thread_id: None,
+        })
+        .unwrap();
+        db.store_message(&StoredMessage {
+            id: "m2".into(),
+            chat_id: 100,
+            sender_name: "bot".into(),
+            content: "bot".into(),
+            is_from_bot: true,
+            timestamp: "2024-01-01T00:00:02Z".into(),
+            thread_id: None,
+        })
+        .unwrap();
+        db.store_message(&StoredMessage {
+            id: "m3".into(),
+            chat_id: 100,
+            sender_name: "alice".into(),
+            content: "new".into(),
+            is_from_bot: false,
+            timestamp: "2024-01-01T00:00:03Z".into(),
+            thread_id: None,
+        })
+        .unwrap();
 
-        assert!(db.update_task_status(id, "paused").unwrap());
-        assert!(db.update_task_status(id, "active").unwrap());
-        assert!(db.update_task_status(id, "cancelled").unwrap());
+        let msgs = db
+            .get_messages_since(100, "2024-01-01T00:00:01Z", 10)
+            .unwrap();
+        assert_eq!(msgs.len(), 2);
+        assert_eq!(msgs[0].id, "m2");
+        assert_eq!(msgs[1].id, "m3");
 
-        // Non-existent task
-        assert!(!db.update_task_status(9999, "paused").unwrap());
         cleanup(&dir);
     }
 
     #[test]
-    fn test_update_task_after_run_cron() {
+    fn test_reflector_cursor_roundtrip() {
         let (db, dir) = test_db();
-        let id = db
-            .create_scheduled_task(100, "test", "cron", "0 * * * * *", "2024-01-01T00:00:00Z")
+        assert!(db.get_reflector_cursor(100).unwrap().is_none());
+
+        db.set_reflector_cursor(100, "2024-01-01T00:00:03Z")
             .unwrap();
+        assert_eq!(
+            db.get_reflector_cursor(100).unwrap().as_deref(),
+            Some("2024-01-01T00:00:03Z")
+        );
 
-        db.update_task_after_run(id, "2024-01-01T00:01:00Z", Some("2024-01-01T00:02:00Z"))
+        db.set_reflector_cursor(100, "2024-01-01T00:00:05Z")
             .unwrap();
+        assert_eq!(
+            db.get_reflector_cursor(100).unwrap().as_deref(),
+            Some("2024-01-01T00:00:05Z")
+        );
 
-        let tasks = db.get_tasks_for_chat(100).unwrap();
-        assert_eq!(tasks[0].last_run.as_deref(), Some("2024-01-01T00:01:00Z"));
-        assert_eq!(tasks[0].next_run, "2024-01-01T00:02:00Z");
-        assert_eq!(tasks[0].status, "active");
         cleanup(&dir);
     }
 
     #[test]
-    fn test_update_task_after_run_one_shot() {
+    fn test_resolve_or_create_chat_id_channel_scoped() {
         let (db, dir) = test_db();
-        let id = db
-            .create_scheduled_task(
-                100,
-                "test",
-                "once",
-                "2024-01-01T00:00:00Z",
-                "2024-01-01T00:00:00Z",
+
+        let tg = db
+            .resolve_or_create_chat_id(
+                "telegram",
+                "12345",
+                Some("telegram-12345"),
+                "telegram_private",
+            )
+            .unwrap();
+        let tg_again = db
+            .resolve_or_create_chat_id(
+                "telegram",
+                "12345",
+                Some("telegram-12345"),
+                "telegram_private",
             )
             .unwrap();
+        assert_eq!(tg, tg_again);
 
-        // One-shot: no next_run, should mark as completed
-        db.update_task_after_run(id, "2024-01-01T00:00:00Z", None)
+        let discord = db
+            .resolve_or_create_chat_id("discord", "12345", Some("discord-12345"), "discord")
             .unwrap();
+        assert_ne!(tg, discord);
+        assert_eq!(
+            db.get_chat_external_id(discord).unwrap().as_deref(),
+            Some("12345")
+        );
 
-        // Should not appear in active/paused list
-        let tasks = db.get_tasks_for_chat(100).unwrap();
-        assert!(tasks.is_empty());
         cleanup(&dir);
     }
 
     #[test]
-    fn test_delete_task() {
-        let (db, dir) = test_db();
-        let id = db
-            .create_scheduled_task(100, "test", "cron", "0 * * * * *", "2024-01-01T00:00:00Z")
-            .unwrap();
+    fn test_migration_backfills_chat_identity_columns() {
+        let dir = std::env::temp_dir().join(format!(
+            "rayclaw_migration_chat_identity_{}",
+            uuid::Uuid::new_v4()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db_path = dir.join("rayclaw.db");
+        let conn = Connection::open(&db_path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE chats (
+                chat_id INTEGER PRIMARY KEY,
+                chat_title TEXT,
+                chat_type TEXT NOT NULL DEFAULT 'private',
+                last_message_time TEXT NOT NULL
+            );
+            INSERT INTO chats(chat_id, chat_title, chat_type, last_message_time)
+            VALUES (100, 'legacy tg', 'telegram_private', '2026-01-01T00:00:00Z');",
+        )
+        .unwrap();
+        drop(conn);
 
-        assert!(db.delete_task(id).unwrap());
-        assert!(!db.delete_task(id).unwrap()); // already deleted
+        let db = Database::new(dir.to_str().unwrap()).unwrap();
+        let conn = db.lock_conn();
+        let (channel, external): (Option<String>, Option<String>) = conn
+            .query_row(
+                "SELECT channel, external_chat_id FROM chats WHERE chat_id = 100",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(channel.as_deref(), Some("telegram"));
+        assert_eq!(external.as_deref(), Some("100"));
+        drop(conn);
 
-        let tasks = db.get_tasks_for_chat(100).unwrap();
-        assert!(tasks.is_empty());
         cleanup(&dir);
     }
 
     #[test]
-    fn test_get_all_messages() {
-        let (db, dir) = test_db();
-        for i in 0..5 {
-            db.store_message(&StoredMessage {
-                id: format!("msg{i}"),
-                chat_id: 100,
-                sender_name: "alice".into(),
-                content: format!("message {i}"),
-                is_from_bot: false,
-                timestamp: format!("2024-01-01T00:00:0{i}Z"),
-            })
-            .unwrap();
-        }
+    fn test_migration_backfills_memory_identity_columns() {
+        let dir = std::env::temp_dir().join(format!(
+            "rayclaw_migration_memory_identity_{}",
+            uuid::Uuid::new_v4()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db_path = dir.join("rayclaw.db");
+        let conn = Connection::open(&db_path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE chats (
+                chat_id INTEGER PRIMARY KEY,
+                chat_title TEXT,
+                chat_type TEXT NOT NULL DEFAULT 'private',
+                last_message_time TEXT NOT NULL
+            );
+            INSERT INTO chats(chat_id, chat_title, chat_type, last_message_time)
+            VALUES (200, 'legacy discord', 'discord', '2026-01-01T00:00:00Z');
 
-        let messages = db.get_all_messages(100).unwrap();
-        assert_eq!(messages.len(), 5);
-        assert_eq!(messages[0].content, "message 0");
-        assert_eq!(messages[4].content, "message 4");
+            CREATE TABLE memories (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                chat_id INTEGER,
+                content TEXT NOT NULL,
+                category TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL,
+                embedding_model TEXT
+            );
+            INSERT INTO memories(chat_id, content, category, created_at, updated_at, embedding_model)
+            VALUES (200, 'legacy memory', 'KNOWLEDGE', '2026-01-01T00:00:00Z', '2026-01-01T00:00:00Z', NULL);",
+        )
+        .unwrap();
+        drop(conn);
+
+        let db = Database::new(dir.to_str().unwrap()).unwrap();
+        let conn = db.lock_conn();
+        let (chat_channel, external): (Option<String>, Option<String>) = conn
+            .query_row(
+                "SELECT chat_channel, external_chat_id FROM memories WHERE chat_id = 200",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(chat_channel.as_deref(), Some("discord"));
+        assert_eq!(external.as_deref(), Some("200"));
+        drop(conn);
 
-        // Different chat should be empty
-        assert!(db.get_all_messages(200).unwrap().is_empty());
         cleanup(&dir);
     }
 
     #[test]
-    fn test_log_task_run() {
+    fn test_log_llm_usage_and_summary() {
         let (db, dir) = test_db();
-        let task_id = db
-            .create_scheduled_task(100, "test", "cron", "0 * * * * *", "2024-01-01T00:00:00Z")
+        db.log_llm_usage(
+            100,
+            "telegram",
+            "anthropic",
+            "claude-test",
+            10,
+            5,
+            "agent_loop",
+        )
+        .unwrap();
+        db.log_llm_usage(
+            100,
+            "telegram",
+            "anthropic",
+            "claude-test",
+            20,
+            8,
+            "agent_loop",
+        )
+        .unwrap();
+        db.log_llm_usage(200, "discord", "openai", "gpt-test", 30, 7, "agent_loop")
             .unwrap();
 
-        let log_id = db
-            .log_task_run(
-                task_id,
-                100,
-                "2024-01-01T00:00:00Z",
-                "2024-01-01T00:00:05Z",
-                5000,
-                true,
-                Some("Success"),
-            )
-            .unwrap();
-        assert!(log_id > 0);
+        let chat_100 = db.get_llm_usage_summary(Some(100)).unwrap();
+        assert_eq!(chat_100.requests, 2);
+        assert_eq!(chat_100.input_tokens, 30);
+        assert_eq!(chat_100.output_tokens, 13);
+        assert_eq!(chat_100.total_tokens, 43);
+        assert!(chat_100.last_request_at.is_some());
+
+        let all = db.get_llm_usage_summary(None).unwrap();
+        assert_eq!(all.requests, 3);
+        assert_eq!(all.input_tokens, 60);
+        assert_eq!(all.output_tokens, 20);
+        assert_eq!(all.total_tokens, 80);
+        assert!(all.last_request_at.is_some());
 
-        let logs = db.get_task_run_logs(task_id, 10).unwrap();
-        assert_eq!(logs.len(), 1);
-        assert_eq!(logs[0].task_id, task_id);
-        assert_eq!(logs[0].duration_ms, 5000);
-        assert!(logs[0].success);
-        assert_eq!(logs[0].result_summary.as_deref(), Some("Success"));
         cleanup(&dir);
     }
 
     #[test]
-    fn test_get_task_run_logs_ordering_and_limit() {
+    fn test_record_llm_usage_cost_and_daily() {
         let (db, dir) = test_db();
-        let task_id = db
-            .create_scheduled_task(100, "test", "cron", "0 * * * * *", "2024-01-01T00:00:00Z")
+        let entry = LlmUsageEntry {
+            chat_id: 100,
+            caller_channel: "telegram".into(),
+            provider: "anthropic".into(),
+            model: "claude-test".into(),
+            input_tokens: 10,
+            output_tokens: 5,
+            cache_read_tokens: 1000,
+            cache_write_tokens: 200,
+            cost_usd: Some(0.25),
+            request_kind: "agent_loop".into(),
+        };
+        db.record_llm_usage(&entry).unwrap();
+        db.record_llm_usage(&LlmUsageEntry {
+            cost_usd: Some(0.5),
+            ..entry.clone()
+        })
+        .unwrap();
+        db.record_llm_usage(&LlmUsageEntry {
+            cost_usd: None,
+            ..entry.clone()
+        })
+        .unwrap();
+        db.record_llm_usage(&LlmUsageEntry {
+            chat_id: 200,
+            ..entry
+        })
+        .unwrap();
+
+        let summary = db.get_llm_usage_summary(Some(100)).unwrap();
+        assert_eq!(summary.total_tokens, 3 * 1215);
+        let cost = db.get_llm_cost_summary(Some(100), None).unwrap();
+        assert_eq!(cost.cache_read_tokens, 3000);
+        assert_eq!(cost.cache_write_tokens, 600);
+        assert!((cost.cost_usd - 0.75).abs() < 1e-9);
+        assert_eq!(cost.unpriced_requests, 1);
+        let all = db.get_llm_cost_summary(None, None).unwrap();
+        assert!((all.cost_usd - 1.0).abs() < 1e-9);
+        let future = db
+            .get_llm_cost_summary(Some(100), Some("2100-01-01T00:00:00Z"))
             .unwrap();
+        assert_eq!(future.cost_usd, 0.0);
 
-        for i in 0..5 {
-            db.log_task_run(
-                task_id,
-                100,
-                &format!("2024-01-01T00:0{i}:00Z"),
-                &format!("2024-01-01T00:0{i}:05Z"),
-                5000,
-                true,
-                Some(&format!("Run {i}")),
-            )
+        let days = db
+            .get_llm_usage_by_day(Some(100), "2000-01-01T00:00:00Z")
             .unwrap();
-        }
+        assert_eq!(days.len(), 1);
+        assert_eq!(days[0].requests, 3);
+        assert_eq!(
+            days[0].day,
+            chrono::Utc::now().format("%Y-%m-%d").to_string()
+        );
 
-        // Limit to 3, most recent first
-        let logs = db.get_task_run_logs(task_id, 3).unwrap();
-        assert_eq!(logs.len(), 3);
-        assert_eq!(logs[0].result_summary.as_deref(), Some("Run 4")); // most recent
-        assert_eq!(logs[2].result_summary.as_deref(), Some("Run 2"));
         cleanup(&dir);
     }
 
     #[test]
-    fn test_save_and_load_session() {
+    fn test_delete_chat_data_cleans_llm_usage() {
         let (db, dir) = test_db();
-        let json = r#"[{"role":"user","content":"hello"}]"#;
-        db.save_session(100, json).unwrap();
+        db.upsert_chat(100, Some("chat-100"), "private").unwrap();
+        db.log_llm_usage(
+            100,
+            "telegram",
+            "anthropic",
+            "claude-test",
+            11,
+            9,
+            "agent_loop",
+        )
+        .unwrap();
+        db.log_llm_usage(
+            200,
+            "telegram",
+            "anthropic",
+            "claude-test",
+            3,
+            4,
+            "agent_loop",
+        )
+        .unwrap();
 
-        let result = db.load_session(100).unwrap();
-        assert!(result.is_some());
-        let (loaded_json, updated_at) = result.unwrap();
-        assert_eq!(loaded_json, json);
-        assert!(!updated_at.is_empty());
+        assert!(db.delete_chat_data(100).unwrap());
 
-        // Upsert: save again with different data
-        let json2 = r#"[{"role":"user","content":"hello"},{"role":"assistant","content":"hi"}]"#;
-        db.save_session(100, json2).unwrap();
-        let (loaded_json2, _) = db.load_session(100).unwrap().unwrap();
-        assert_eq!(loaded_json2, json2);
+        let chat_100 = db.get_llm_usage_summary(Some(100)).unwrap();
+        assert_eq!(chat_100.requests, 0);
+        let chat_200 = db.get_llm_usage_summary(Some(200)).unwrap();
+        assert_eq!(chat_200.requests, 1);
 
         cleanup(&dir);
     }
 
     #[test]
-    fn test_load_session_nonexistent() {
+    fn test_acp_sessions_round_trip() {
         let (db, dir) = test_db();
-        let result = db.load_session(999).unwrap();
-        assert!(result.is_none());
+        let mut record = AcpSessionRecord {
+            session_id: "s1".into(),
+            agent_id: "claude".into(),
+            workspace: "/work".into(),
+            acp_session_id: Some("agent-1".into()),
+            auto_approve: true,
+            chat_ids: vec![],
+            created_at: "2026-01-01T00:00:00Z".into(),
+        };
+        db.save_acp_session(&record).unwrap();
+        db.bind_acp_chat(7, "s1").unwrap();
+        db.bind_acp_chat(3, "s1").unwrap();
+
+        record.acp_session_id = Some("agent-2".into());
+        db.save_acp_session(&record).unwrap();
+        let sessions = db.get_acp_sessions().unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].acp_session_id.as_deref(), Some("agent-2"));
+        assert_eq!(sessions[0].chat_ids, vec![3, 7]);
+
+        db.unbind_acp_chat(3).unwrap();
+        assert_eq!(db.get_acp_sessions().unwrap()[0].chat_ids, vec![7]);
+        db.delete_acp_session("s1").unwrap();
+        assert!(db.get_acp_sessions().unwrap().is_empty());
         cleanup(&dir);
     }
 
     #[test]
-    fn test_delete_session() {
+    fn test_acp_runs_round_trip_and_filters() {
         let (db, dir) = test_db();
-        db.save_session(100, "[]").unwrap();
-        assert!(db.delete_session(100).unwrap());
-        assert!(db.load_session(100).unwrap().is_none());
-        // Delete again returns false
-        assert!(!db.delete_session(100).unwrap());
+        let run = |session_id: &str, chat_id: Option<i64>, prompt: &str| AcpRun {
+            id: 0,
+            session_id: session_id.to_string(),
+            chat_id,
+            agent_id: "claude".to_string(),
+            prompt: prompt.to_string(),
+            messages: vec!["done".to_string()],
+            tool_calls: vec![serde_json::json!({"name": "bash", "input": {"cmd": "ls"}})],
+            files_changed: vec!["src/main.rs".to_string()],
+            completed: true,
+            context_reset: false,
+            duration_ms: 1200,
+            created_at: chrono::Utc::now().to_rfc3339(),
+        };
+        let first = db.log_acp_run(&run("s1", Some(100), "fix it")).unwrap();
+        db.log_acp_run(&run("s1", Some(100), "test it")).unwrap();
+        db.log_acp_run(&run("s2", None, "other")).unwrap();
+
+        let all = db.get_acp_runs(None, None, 10, 0).unwrap();
+        assert_eq!(all.len(), 3);
+        assert_eq!(all[0].prompt, "other");
+
+        let chat = db.get_acp_runs(Some(100), None, 10, 0).unwrap();
+        assert_eq!(chat.len(), 2);
+        assert_eq!(chat[0].prompt, "test it");
+        assert_eq!(db.get_acp_runs(None, Some("s2"), 10, 0).unwrap().len(), 1);
+        assert_eq!(db.get_acp_runs(Some(100), None, 1, 1).unwrap()[0].id, first);
+
+        let stored = db.get_acp_run(first).unwrap().unwrap();
+        assert_eq!(stored.messages, vec!["done".to_string()]);
+        assert_eq!(stored.tool_calls[0]["name"], "bash");
+        assert_eq!(stored.files_changed, vec!["src/main.rs".to_string()]);
+        assert!(db.get_acp_run(9999).unwrap().is_none());
+
+        db.upsert_chat(100, Some("chat-100"), "private").unwrap();
+        assert!(db.delete_chat_data(100).unwrap());
+        assert_eq!(db.get_acp_runs(None, None, 10, 0).unwrap().len(), 1);
+
         cleanup(&dir);
     }
 
     #[test]
-    fn test_clear_chat_context_removes_session_and_messages_only() {
+    fn test_group_members_tracked_from_messages() {
         let (db, dir) = test_db();
-        db.upsert_chat(100, Some("chat-100"), "private").unwrap();
-        db.save_session(100, r#"[{"role":"user","content":"hi"}]"#)
-            .unwrap();
-        db.store_message(&StoredMessage {
-            id: "m1".into(),
+        let msg = |id: &str, sender: &str, is_from_bot: bool, ts: &str| StoredMessage {
+            id: id.into(),
             chat_id: 100,
-            sender_name: "alice".into(),
-            content: "hello".into(),
-            is_from_bot: false,
-            timestamp: "2024-01-01T00:00:01Z".into(),
-        })
-        .unwrap();
-        db.insert_memory(Some(100), "User likes Rust", "PROFILE")
+            sender_name: sender.into(),
+            content: "hi".into(),
+            is_from_bot,
+            timestamp: ts.into(),
+            thread_id: None,
+        };
+        db.store_message(&msg("1", "alice", false, "2024-01-01T00:00:01Z"))
+            .unwrap();
+        db.store_message(&msg("2", "bob", false, "2024-01-01T00:00:02Z"))
+            .unwrap();
+        db.store_message(&msg("3", "alice", false, "2024-01-01T00:00:03Z"))
+            .unwrap();
+        db.store_message(&msg("4", "bot", true, "2024-01-01T00:00:04Z"))
             .unwrap();
 
-        assert!(db.clear_chat_context(100).unwrap());
-        assert!(db.load_session(100).unwrap().is_none());
-        assert!(db.get_recent_messages(100, 10).unwrap().is_empty());
-        assert!(!db.search_memories(100, "Rust", 10).unwrap().is_empty());
-        assert!(db.get_chat_type(100).unwrap().is_some());
+        let members = db.get_group_members(100, 10).unwrap();
+        assert_eq!(members.len(), 2);
+        assert_eq!(members[0].member_name, "alice");
+        assert_eq!(members[0].message_count, 2);
+        assert_eq!(members[0].first_seen, "2024-01-01T00:00:01Z");
+
+        assert!(db
+            .set_group_member_display_name(100, "alice", "Alice Smith")
+            .unwrap());
+        assert!(db
+            .update_group_member_profile(100, "alice", Some("admin"), Some("runs CI"))
+            .unwrap());
+        assert!(db
+            .update_group_member_profile(100, "alice", None, Some(""))
+            .unwrap());
+        assert!(!db
+            .update_group_member_profile(100, "carol", Some("guest"), None)
+            .unwrap());
+        let alice = &db.get_group_members(100, 10).unwrap()[0];
+        assert_eq!(alice.display_name.as_deref(), Some("Alice Smith"));
+        assert_eq!(alice.role.as_deref(), Some("admin"));
+        assert!(alice.notes.is_none());
 
         cleanup(&dir);
     }
 
     #[test]
-    fn test_get_new_user_messages_since() {
+    fn test_delivery_lifecycle() {
         let (db, dir) = test_db();
+        let id = db.create_delivery(100, "telegram", "hello").unwrap();
+        let queued = db.get_delivery(id).unwrap().unwrap();
+        assert_eq!(queued.status, "queued");
+        assert_eq!(queued.attempts, 0);
 
-        // Messages before the cutoff
-        db.store_message(&StoredMessage {
-            id: "m1".into(),
-            chat_id: 100,
-            sender_name: "alice".into(),
-            content: "old msg".into(),
-            is_from_bot: false,
-            timestamp: "2024-01-01T00:00:01Z".into(),
-        })
-        .unwrap();
+        db.record_delivery_attempt(id, "failed", Some("timed out"), true, None)
+            .unwrap();
+        db.record_delivery_attempt(id, "edited", None, false, Some("hello again"))
+            .unwrap();
+        let edited = db.get_delivery(id).unwrap().unwrap();
+        assert_eq!(edited.status, "edited");
+        assert_eq!(edited.content, "hello again");
+        assert_eq!(edited.attempts, 2);
+        assert!(edited.error.is_none());
+
+        let other = db.create_delivery(200, "slack", "hi").unwrap();
+        db.record_delivery_attempt(other, "failed", Some("not_in_channel"), false, None)
+            .unwrap();
+        assert_eq!(db.get_deliveries(None, None, 10, 0).unwrap()[0].id, other);
+        assert_eq!(db.get_deliveries(Some(100), None, 10, 0).unwrap().len(), 1);
+        let failed = db.get_deliveries(None, Some("failed"), 10, 0).unwrap();
+        assert_eq!(failed.len(), 1);
+        assert!(!failed[0].retryable);
+        assert!(!db
+            .record_delivery_attempt(9999, "sent", None, false, None)
+            .unwrap());
 
-        // Bot message at the cutoff
-        db.store_message(&StoredMessage {
-            id: "m2".into(),
-            chat_id: 100,
-            sender_name: "bot".into(),
-            content: "response".into(),
-            is_from_bot: true,
-            timestamp: "2024-01-01T00:00:02Z".into(),
-        })
-        .unwrap();
+        db.upsert_chat(100, Some("chat-100"), "private").unwrap();
+        assert!(db.delete_chat_data(100).unwrap());
+        assert!(db.get_delivery(id).unwrap().is_none());
 
-        // User messages after cutoff
-        db.store_message(&StoredMessage {
-            id: "m3".into(),
-            chat_id: 100,
-            sender_name: "alice".into(),
-            content: "new msg 1".into(),
-            is_from_bot: false,
-            timestamp: "2024-01-01T00:00:03Z".into(),
-        })
-        .unwrap();
+        cleanup(&dir);
+    }
 
-        db.store_message(&StoredMessage {
-            id: "m4".into(),
-            chat_id: 100,
-            sender_name: "bob".into(),
-            content: "new msg 2".into(),
-            is_from_bot: false,
-            timestamp: "2024-01-01T00:00:04Z".into(),
-        })
-        .unwrap();
+    #[test]
+    fn test_url_monitor_lifecycle() {
+        let (db, dir) = test_db();
+        let id = db
+            .create_url_monitor(
+                100,
+                "https://example.com",
+                Some("#price"),
+                60,
+                "$10",
+                "2024-01-01T01:00:00+00:00",
+            )
+            .unwrap();
+        let created = db.get_url_monitor(id).unwrap().unwrap();
+        assert_eq!(created.status, "active");
+        assert_eq!(created.selector.as_deref(), Some("#price"));
+        assert_eq!(created.last_content.as_deref(), Some("$10"));
+        assert_eq!(created.change_count, 0);
 
-        // Bot message after cutoff (should be excluded - only non-bot)
-        db.store_message(&StoredMessage {
-            id: "m5".into(),
-            chat_id: 100,
-            sender_name: "bot".into(),
-            content: "bot again".into(),
-            is_from_bot: true,
-            timestamp: "2024-01-01T00:00:05Z".into(),
-        })
+        assert!(db
+            .get_due_url_monitors("2024-01-01T00:30:00+00:00")
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            db.get_due_url_monitors("2024-01-01T01:00:00+00:00")
+                .unwrap()
+                .len(),
+            1
+        );
+
+        // A failed check keeps the baseline
+        db.record_url_monitor_check(
+            id,
+            "2024-01-01T01:00:00+00:00",
+            "2024-01-01T02:00:00+00:00",
+            None,
+            false,
+            Some("HTTP 503"),
+        )
+        .unwrap();
+        let failed = db.get_url_monitor(id).unwrap().unwrap();
+        assert_eq!(failed.last_content.as_deref(), Some("$10"));
+        assert_eq!(failed.last_error.as_deref(), Some("HTTP 503"));
+        assert_eq!(failed.next_check, "2024-01-01T02:00:00+00:00");
+
+        db.record_url_monitor_check(
+            id,
+            "2024-01-01T02:00:00+00:00",
+            "2024-01-01T03:00:00+00:00",
+            Some("$12"),
+            true,
+            None,
+        )
         .unwrap();
+        let changed = db.get_url_monitor(id).unwrap().unwrap();
+        assert_eq!(changed.last_content.as_deref(), Some("$12"));
+        assert_eq!(
+            changed.last_changed.as_deref(),
+            Some("2024-01-01T02:00:00+00:00")
+        );
+        assert_eq!(changed.change_count, 1);
+        assert!(changed.last_error.is_none());
 
-        let msgs = db
-            .get_new_user_messages_since(100, "2024-01-01T00:00:02Z")
-            .unwrap();
-        assert_eq!(msgs.len(), 2);
-        assert_eq!(msgs[0].content, "new msg 1");
-        assert_eq!(msgs[1].content, "new msg 2");
+        assert!(db.update_url_monitor_status(id, "paused").unwrap());
+        assert!(db
+            .get_due_url_monitors("2099-01-01T00:00:00+00:00")
+            .unwrap()
+            .is_empty());
+        assert_eq!(db.get_url_monitors_for_chat(100).unwrap().len(), 1);
+        assert!(db.get_url_monitors_for_chat(200).unwrap().is_empty());
+
+        assert!(db.delete_url_monitor(id).unwrap());
+        assert!(!db.delete_url_monitor(id).unwrap());
+        assert!(db.get_url_monitor(id).unwrap().is_none());
 
         cleanup(&dir);
     }
 
     #[test]
-    fn test_get_messages_since_includes_user_and_bot() {
+    fn test_notification_settings_and_pending_queue() {
         let (db, dir) = test_db();
-        db.store_message(&StoredMessage {
-            id: "m1".into(),
-            chat_id: 100,
-            sender_name: "alice".into(),
-            content: "old".into(),
-            is_from_bot: false,
-            timestamp: "2024-01-01T00:00:01Z".into(),
-        })
-        .unwrap();
-        db.store_message(&StoredMessage {
-            id: "m2".into(),
-            chat_id: 100,
-            sender_name: "bot".into(),
-            content: "bot".into(),
-            is_from_bot: true,
-            timestamp: "2024-01-01T00:00:02Z".into(),
-        })
-        .unwrap();
-        db.store_message(&StoredMessage {
-            id: "m3".into(),
-            chat_id: 100,
-            sender_name: "alice".into(),
-            content: "new".into(),
-            is_from_bot: false,
-            timestamp: "2024-01-01T00:00:03Z".into(),
-        })
-        .unwrap();
+        assert!(db.get_notification_settings(100).unwrap().is_none());
+
+        let mut settings = NotificationSettings::defaults(100);
+        settings.mute_task_results = true;
+        settings.quiet_start = Some("22:00".into());
+        settings.quiet_end = Some("07:00".into());
+        db.save_notification_settings(&settings).unwrap();
+        assert_eq!(
+            db.get_notification_settings(100).unwrap(),
+            Some(settings.clone())
+        );
+
+        settings.min_severity = "warning".into();
+        settings.quiet_start = None;
+        settings.quiet_end = None;
+        db.save_notification_settings(&settings).unwrap();
+        assert_eq!(db.get_notification_settings(100).unwrap(), Some(settings));
+
+        db.queue_notification(100, "first", "info").unwrap();
+        db.queue_notification(200, "other chat", "warning").unwrap();
+        db.queue_notification(100, "second", "error").unwrap();
+        assert_eq!(
+            db.get_chats_with_pending_notifications().unwrap(),
+            vec![100, 200]
+        );
+        let taken = db.take_pending_notifications(100).unwrap();
+        assert_eq!(
+            taken.iter().map(|n| n.content.as_str()).collect::<Vec<_>>(),
+            vec!["first", "second"]
+        );
+        assert!(db.take_pending_notifications(100).unwrap().is_empty());
+        assert_eq!(
+            db.get_chats_with_pending_notifications().unwrap(),
+            vec![200]
+        );
+
+        db.upsert_chat(100, Some("chat-100"), "private").unwrap();
+        assert!(db.delete_chat_data(100).unwrap());
+        assert!(db.get_notification_settings(100).unwrap().is_none());
+
+        cleanup(&dir);
+    }
+
+    #[test]
+    fn test_chat_locale() {
+        let (db, dir) = test_db();
+        assert!(db.get_chat_locale(100).unwrap().is_none());
 
-        let msgs = db
-            .get_messages_since(100, "2024-01-01T00:00:01Z", 10)
-            .unwrap();
-        assert_eq!(msgs.len(), 2);
-        assert_eq!(msgs[0].id, "m2");
-        assert_eq!(msgs[1].id, "m3");
+        db.set_chat_locale(100, "de").unwrap();
+        db.set_chat_locale(100, "pt-BR").unwrap();
+        assert_eq!(db.get_chat_locale(100).unwrap().as_deref(), Some("pt-BR"));
+        assert!(db.get_chat_locale(200).unwrap().is_none());
 
+        db.upsert_chat(100, Some("chat-100"), "private").unwrap();
+        assert!(db.delete_chat_data(100).unwrap());
+        assert!(db.get_chat_locale(100).unwrap().is_none());
         cleanup(&dir);
     }
 
     #[test]
-    fn test_reflector_cursor_roundtrip() {
+    fn test_chat_topic() {
         let (db, dir) = test_db();
-        assert!(db.get_reflector_cursor(100).unwrap().is_none());
+        assert!(db.get_chat_topic(100, 4).unwrap().is_none());
 
-        db.set_reflector_cursor(100, "2024-01-01T00:00:03Z")
-            .unwrap();
+        db.set_chat_topic(100, 4, "Releases").unwrap();
+        db.set_chat_topic(100, 4, "Release planning").unwrap();
+        db.set_chat_topic(100, 9, "Support").unwrap();
         assert_eq!(
-            db.get_reflector_cursor(100).unwrap().as_deref(),
-            Some("2024-01-01T00:00:03Z")
+            db.get_chat_topic(100, 4).unwrap().as_deref(),
+            Some("Release planning")
         );
-
-        db.set_reflector_cursor(100, "2024-01-01T00:00:05Z")
-            .unwrap();
         assert_eq!(
-            db.get_reflector_cursor(100).unwrap().as_deref(),
-            Some("2024-01-01T00:00:05Z")
+            db.get_chat_topic(100, 9).unwrap().as_deref(),
+            Some("Support")
         );
+        assert!(db.get_chat_topic(200, 4).unwrap().is_none());
 
+        db.upsert_chat(100, Some("chat-100"), "telegram_supergroup")
+            .unwrap();
+        assert!(db.delete_chat_data(100).unwrap());
+        assert!(db.get_chat_topic(100, 4).unwrap().is_none());
         cleanup(&dir);
     }
 
     #[test]
-    fn test_resolve_or_create_chat_id_channel_scoped() {
+    fn test_sessions_and_history_per_thread() {
         let (db, dir) = test_db();
-
-        let tg = db
-            .resolve_or_create_chat_id(
-                "telegram",
-                "12345",
-                Some("telegram-12345"),
-                "telegram_private",
-            )
+        db.save_session(100, None, "[1]").unwrap();
+        db.save_session(100, Some(4), "[2]").unwrap();
+        assert_eq!(db.load_session(100, None).unwrap().unwrap().0, "[1]");
+        assert_eq!(db.load_session(100, Some(4)).unwrap().unwrap().0, "[2]");
+        assert!(db.load_session(100, Some(9)).unwrap().is_none());
+
+        let msg = |id: &str, thread_id: Option<i64>, is_from_bot: bool, ts: &str| StoredMessage {
+            id: id.into(),
+            chat_id: 100,
+            sender_name: if is_from_bot { "bot" } else { "alice" }.into(),
+            content: id.into(),
+            is_from_bot,
+            timestamp: ts.into(),
+            thread_id,
+        };
+        db.store_message(&msg("general", None, false, "2024-01-01T00:00:01Z"))
             .unwrap();
-        let tg_again = db
-            .resolve_or_create_chat_id(
-                "telegram",
-                "12345",
-                Some("telegram-12345"),
-                "telegram_private",
-            )
+        db.store_message(&msg("topic-q", Some(4), false, "2024-01-01T00:00:02Z"))
             .unwrap();
-        assert_eq!(tg, tg_again);
-
-        let discord = db
-            .resolve_or_create_chat_id("discord", "12345", Some("discord-12345"), "discord")
+        db.store_message(&msg("topic-a", Some(4), true, "2024-01-01T00:00:03Z"))
             .unwrap();
-        assert_ne!(tg, discord);
+        db.store_message(&msg("topic-q2", Some(4), false, "2024-01-01T00:00:04Z"))
+            .unwrap();
+
+        let ids = |msgs: Vec<StoredMessage>| msgs.into_iter().map(|m| m.id).collect::<Vec<_>>();
         assert_eq!(
-            db.get_chat_external_id(discord).unwrap().as_deref(),
-            Some("12345")
+            ids(db
+                .get_messages_since_last_bot_response(100, Some(4), 50, 10)
+                .unwrap()),
+            vec!["topic-a", "topic-q2"]
+        );
+        assert_eq!(
+            ids(db
+                .get_messages_since_last_bot_response(100, None, 50, 10)
+                .unwrap()),
+            vec!["general"]
+        );
+        assert_eq!(
+            ids(db
+                .get_new_user_messages_since(100, Some(4), "2024-01-01T00:00:00Z")
+                .unwrap()),
+            vec!["topic-q", "topic-q2"]
+        );
+        // The chat's history keeps every topic
+        assert_eq!(db.get_recent_messages(100, 10).unwrap().len(), 4);
+        assert_eq!(
+            db.get_recent_messages(100, 10).unwrap()[1].thread_id,
+            Some(4)
         );
 
+        assert!(db.clear_chat_context(100).unwrap());
+        assert!(db.load_session(100, Some(4)).unwrap().is_none());
         cleanup(&dir);
     }
 
     #[test]
-    fn test_migration_backfills_chat_identity_columns() {
+    fn test_migration_keys_sessions_by_thread() {
         let dir = std::env::temp_dir().join(format!(
-            "rayclaw_migration_chat_identity_{}",
+            "rayclaw_migration_sessions_{}",
             uuid::Uuid::new_v4()
         ));
         std::fs::create_dir_all(&dir).unwrap();
-        let db_path = dir.join("rayclaw.db");
-        let conn = Connection::open(&db_path).unwrap();
+        let conn = Connection::open(dir.join("rayclaw.db")).unwrap();
         conn.execute_batch(
-            "CREATE TABLE chats (
+            "CREATE TABLE messages (
+                id TEXT NOT NULL,
+                chat_id INTEGER NOT NULL,
+                sender_name TEXT NOT NULL,
+                content TEXT NOT NULL,
+                is_from_bot INTEGER NOT NULL DEFAULT 0,
+                timestamp TEXT NOT NULL,
+                PRIMARY KEY (id, chat_id)
+            );
+            CREATE TABLE sessions (
                 chat_id INTEGER PRIMARY KEY,
-                chat_title TEXT,
-                chat_type TEXT NOT NULL DEFAULT 'private',
-                last_message_time TEXT NOT NULL
+                messages_json TEXT NOT NULL,
+                updated_at TEXT NOT NULL
             );
-            INSERT INTO chats(chat_id, chat_title, chat_type, last_message_time)
-            VALUES (100, 'legacy tg', 'telegram_private', '2026-01-01T00:00:00Z');",
+            INSERT INTO sessions VALUES (100, '[1]', '2026-01-01T00:00:00Z');
+            CREATE TABLE db_meta (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+            INSERT INTO db_meta VALUES ('schema_version', '19');",
         )
         .unwrap();
         drop(conn);
 
         let db = Database::new(dir.to_str().unwrap()).unwrap();
-        let conn = db.lock_conn();
-        let (channel, external): (Option<String>, Option<String>) = conn
-            .query_row(
-                "SELECT channel, external_chat_id FROM chats WHERE chat_id = 100",
-                [],
-                |row| Ok((row.get(0)?, row.get(1)?)),
-            )
+        assert_eq!(db.load_session(100, None).unwrap().unwrap().0, "[1]");
+        db.save_session(100, Some(4), "[2]").unwrap();
+        assert_eq!(db.load_session(100, None).unwrap().unwrap().0, "[1]");
+        assert!(table_has_column(&db.lock_conn(), "messages", "thread_id").unwrap());
+        cleanup(&dir);
+    }
+
+    #[test]
+    fn test_browsing_profiles() {
+        let (db, dir) = test_db();
+        assert!(db.get_browsing_profile(100, "work").unwrap().is_none());
+        assert!(!db
+            .set_browsing_profile_cookies(100, "work", Some("jar"))
+            .unwrap());
+
+        let domains = vec!["example.com".to_string(), "intra.example.org".to_string()];
+        db.save_browsing_profile(100, "work", &domains, Some("ann"), Some("sealed-pw"))
+            .unwrap();
+        assert!(db
+            .set_browsing_profile_cookies(100, "work", Some("sealed-jar"))
+            .unwrap());
+        // Saving again replaces the login but keeps the cookies
+        db.save_browsing_profile(100, "work", &domains[..1], Some("bob"), None)
+            .unwrap();
+        db.save_browsing_profile(100, "news", &domains[..1], None, None)
             .unwrap();
-        assert_eq!(channel.as_deref(), Some("telegram"));
-        assert_eq!(external.as_deref(), Some("100"));
-        drop(conn);
 
+        let profile = db.get_browsing_profile(100, "work").unwrap().unwrap();
+        assert_eq!(profile.domains, vec!["example.com"]);
+        assert_eq!(profile.username.as_deref(), Some("bob"));
+        assert!(profile.sealed_password.is_none());
+        assert_eq!(profile.sealed_cookies.as_deref(), Some("sealed-jar"));
+        let names: Vec<_> = db
+            .get_browsing_profiles_for_chat(100)
+            .unwrap()
+            .into_iter()
+            .map(|p| p.name)
+            .collect();
+        assert_eq!(names, vec!["news", "work"]);
+        assert!(db.get_browsing_profiles_for_chat(200).unwrap().is_empty());
+
+        assert!(db.delete_browsing_profile(100, "news").unwrap());
+        assert!(!db.delete_browsing_profile(100, "news").unwrap());
+        db.upsert_chat(100, Some("chat-100"), "private").unwrap();
+        assert!(db.delete_chat_data(100).unwrap());
+        assert!(db.get_browsing_profile(100, "work").unwrap().is_none());
         cleanup(&dir);
     }
 
     #[test]
-    fn test_migration_backfills_memory_identity_columns() {
-        let dir = std::env::temp_dir().join(format!(
-            "rayclaw_migration_memory_identity_{}",
-            uuid::Uuid::new_v4()
-        ));
-        std::fs::create_dir_all(&dir).unwrap();
-        let db_path = dir.join("rayclaw.db");
-        let conn = Connection::open(&db_path).unwrap();
-        conn.execute_batch(
-            "CREATE TABLE chats (
-                chat_id INTEGER PRIMARY KEY,
-                chat_title TEXT,
-                chat_type TEXT NOT NULL DEFAULT 'private',
-                last_message_time TEXT NOT NULL
-            );
-            INSERT INTO chats(chat_id, chat_title, chat_type, last_message_time)
-            VALUES (200, 'legacy discord', 'discord', '2026-01-01T00:00:00Z');
+    fn test_standing_instructions() {
+        let (db, dir) = test_db();
+        assert!(db.get_standing_instructions(100).unwrap().is_none());
+        assert!(!db.clear_standing_instructions(100).unwrap());
 
-            CREATE TABLE memories (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                chat_id INTEGER,
-                content TEXT NOT NULL,
-                category TEXT NOT NULL,
-                created_at TEXT NOT NULL,
-                updated_at TEXT NOT NULL,
-                embedding_model TEXT
-            );
-            INSERT INTO memories(chat_id, content, category, created_at, updated_at, embedding_model)
-            VALUES (200, 'legacy memory', 'KNOWLEDGE', '2026-01-01T00:00:00Z', '2026-01-01T00:00:00Z', NULL);",
-        )
-        .unwrap();
-        drop(conn);
+        db.set_standing_instructions(100, "Answer in Spanish.")
+            .unwrap();
+        db.set_standing_instructions(100, "Answer in Spanish, under 100 words.")
+            .unwrap();
+        assert_eq!(
+            db.get_standing_instructions(100).unwrap().as_deref(),
+            Some("Answer in Spanish, under 100 words.")
+        );
+        assert!(db.get_standing_instructions(200).unwrap().is_none());
 
-        let db = Database::new(dir.to_str().unwrap()).unwrap();
-        let conn = db.lock_conn();
-        let (chat_channel, external): (Option<String>, Option<String>) = conn
-            .query_row(
-                "SELECT chat_channel, external_chat_id FROM memories WHERE chat_id = 200",
-                [],
-                |row| Ok((row.get(0)?, row.get(1)?)),
-            )
+        assert!(db.clear_standing_instructions(100).unwrap());
+        assert!(db.get_standing_instructions(100).unwrap().is_none());
+
+        db.set_standing_instructions(100, "Be brief.").unwrap();
+        db.upsert_chat(100, Some("chat-100"), "private").unwrap();
+        assert!(db.delete_chat_data(100).unwrap());
+        assert!(db.get_standing_instructions(100).unwrap().is_none());
+
+        cleanup(&dir);
+    }
+
+    #[test]
+    fn test_tool_usage_by_name() {
+        let (db, dir) = test_db();
+        db.log_tool_call(100, "bash", false, 120).unwrap();
+        db.log_tool_call(100, "bash", true, 80).unwrap();
+        db.log_tool_call(100, "web_search", false, 900).unwrap();
+        db.log_tool_call(200, "bash", false, 10).unwrap();
+
+        let rows = db
+            .get_tool_usage_by_name(100, "2000-01-01T00:00:00Z")
             .unwrap();
-        assert_eq!(chat_channel.as_deref(), Some("discord"));
-        assert_eq!(external.as_deref(), Some("200"));
-        drop(conn);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].tool_name, "bash");
+        assert_eq!(rows[0].calls, 2);
+        assert_eq!(rows[0].errors, 1);
+        assert_eq!(rows[0].avg_duration_ms, 100);
+        assert_eq!(rows[1].tool_name, "web_search");
+        assert!(db
+            .get_tool_usage_by_name(100, "2999-01-01T00:00:00Z")
+            .unwrap()
+            .is_empty());
+
+        db.upsert_chat(100, Some("chat-100"), "private").unwrap();
+        assert!(db.delete_chat_data(100).unwrap());
+        assert!(db
+            .get_tool_usage_by_name(100, "2000-01-01T00:00:00Z")
+            .unwrap()
+            .is_empty());
 
         cleanup(&dir);
     }
 
     #[test]
-    fn test_log_llm_usage_and_summary() {
+    fn test_digest_seen_links() {
         let (db, dir) = test_db();
-        db.log_llm_usage(
-            100,
-            "telegram",
-            "anthropic",
-            "claude-test",
-            10,
-            5,
-            "agent_loop",
-        )
-        .unwrap();
-        db.log_llm_usage(
-            100,
-            "telegram",
-            "anthropic",
-            "claude-test",
-            20,
-            8,
-            "agent_loop",
-        )
-        .unwrap();
-        db.log_llm_usage(200, "discord", "openai", "gpt-test", 30, 7, "agent_loop")
+        let links = vec![
+            ("h1".to_string(), "https://a.com/1".to_string()),
+            ("h2".to_string(), "https://b.com/2".to_string()),
+        ];
+        assert_eq!(db.record_digest_links(100, 7, &links).unwrap(), 2);
+        let more = vec![
+            ("h2".to_string(), "https://b.com/2".to_string()),
+            ("h3".to_string(), "https://c.com/3".to_string()),
+        ];
+        assert_eq!(db.record_digest_links(100, 7, &more).unwrap(), 1);
+        assert_eq!(db.record_digest_links(200, 8, &more).unwrap(), 2);
+
+        let seen = db
+            .get_digest_links_since(100, "2000-01-01T00:00:00Z", 10)
             .unwrap();
+        assert_eq!(seen.len(), 3);
+        assert_eq!(seen[0], "https://c.com/3");
+        assert_eq!(
+            db.get_digest_links_since(100, "2000-01-01T00:00:00Z", 1)
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(db
+            .get_digest_links_since(100, "2999-01-01T00:00:00Z", 10)
+            .unwrap()
+            .is_empty());
 
-        let chat_100 = db.get_llm_usage_summary(Some(100)).unwrap();
-        assert_eq!(chat_100.requests, 2);
-        assert_eq!(chat_100.input_tokens, 30);
-        assert_eq!(chat_100.output_tokens, 13);
-        assert_eq!(chat_100.total_tokens, 43);
-        assert!(chat_100.last_request_at.is_some());
+        assert_eq!(db.prune_digest_links("2999-01-01T00:00:00Z").unwrap(), 5);
+        assert!(db
+            .get_digest_links_since(200, "2000-01-01T00:00:00Z", 10)
+            .unwrap()
+            .is_empty());
 
-        let all = db.get_llm_usage_summary(None).unwrap();
-        assert_eq!(all.requests, 3);
-        assert_eq!(all.input_tokens, 60);
-        assert_eq!(all.output_tokens, 20);
-        assert_eq!(all.total_tokens, 80);
-        assert!(all.last_request_at.is_some());
+        db.record_digest_links(100, 7, &links).unwrap();
+        db.upsert_chat(100, Some("chat-100"), "private").unwrap();
+        assert!(db.delete_chat_data(100).unwrap());
+        assert!(db
+            .get_digest_links_since(100, "2000-01-01T00:00:00Z", 10)
+            .unwrap()
+            .is_empty());
 
         cleanup(&dir);
     }
 
     #[test]
-    fn test_delete_chat_data_cleans_llm_usage() {
+    fn test_observer_mode() {
         let (db, dir) = test_db();
+        assert!(!db.is_observer_chat(100).unwrap());
+        assert!(!db.set_observer_mode(100, false).unwrap());
+
+        assert!(db.set_observer_mode(100, true).unwrap());
+        assert!(!db.set_observer_mode(100, true).unwrap());
+        assert!(db.is_observer_chat(100).unwrap());
+        assert!(!db.is_observer_chat(200).unwrap());
+
+        assert!(db.set_observer_mode(100, false).unwrap());
+        assert!(!db.is_observer_chat(100).unwrap());
+
+        db.set_observer_mode(100, true).unwrap();
         db.upsert_chat(100, Some("chat-100"), "private").unwrap();
-        db.log_llm_usage(
-            100,
-            "telegram",
-            "anthropic",
-            "claude-test",
-            11,
-            9,
-            "agent_loop",
-        )
-        .unwrap();
-        db.log_llm_usage(
-            200,
-            "telegram",
-            "anthropic",
-            "claude-test",
-            3,
-            4,
-            "agent_loop",
-        )
-        .unwrap();
+        assert!(db.delete_chat_data(100).unwrap());
+        assert!(!db.is_observer_chat(100).unwrap());
+
+        cleanup(&dir);
+    }
+
+    #[test]
+    fn test_chat_model_overrides() {
+        let (db, dir) = test_db();
+        assert!(db.get_chat_model_override(100).unwrap().is_none());
+        assert!(!db.clear_chat_model_override(100).unwrap());
+
+        let opus = ChatModelOverride {
+            provider: Some("anthropic".into()),
+            model: "claude-opus-4-1".into(),
+        };
+        db.set_chat_model_override(100, &opus).unwrap();
+        assert_eq!(db.get_chat_model_override(100).unwrap(), Some(opus));
+
+        let mini = ChatModelOverride {
+            provider: None,
+            model: "gpt-5-mini".into(),
+        };
+        db.set_chat_model_override(100, &mini).unwrap();
+        assert_eq!(db.get_chat_model_override(100).unwrap(), Some(mini.clone()));
+        assert!(db.get_chat_model_override(200).unwrap().is_none());
+
+        assert!(db.clear_chat_model_override(100).unwrap());
+        assert!(db.get_chat_model_override(100).unwrap().is_none());
 
+        db.set_chat_model_override(100, &mini).unwrap();
+        db.upsert_chat(100, Some("chat-100"), "private").unwrap();
         assert!(db.delete_chat_data(100).unwrap());
+        assert!(db.get_chat_model_override(100).unwrap().is_none());
 
-        let chat_100 = db.get_llm_usage_summary(Some(100)).unwrap();
-        assert_eq!(chat_100.requests, 0);
-        let chat_200 = db.get_llm_usage_summary(Some(200)).unwrap();
-        assert_eq!(chat_200.requests, 1);
+        cleanup(&dir);
+    }
+
+    #[test]
+    fn test_calendar_feed_tokens() {
+        let (db, dir) = test_db();
+        assert!(db.get_calendar_feed_token(100).unwrap().is_none());
+        assert!(!db.delete_calendar_feed(100).unwrap());
+
+        let first = db.create_calendar_feed_token(100).unwrap();
+        assert_eq!(
+            db.get_calendar_feed_token(100).unwrap().as_deref(),
+            Some(first.as_str())
+        );
+        assert_eq!(db.get_chat_for_calendar_token(&first).unwrap(), Some(100));
+
+        let second = db.create_calendar_feed_token(100).unwrap();
+        assert_ne!(first, second);
+        assert!(db.get_chat_for_calendar_token(&first).unwrap().is_none());
+        assert_eq!(db.get_chat_for_calendar_token(&second).unwrap(), Some(100));
+
+        assert!(db.delete_calendar_feed(100).unwrap());
+        assert!(db.get_chat_for_calendar_token(&second).unwrap().is_none());
+
+        db.create_calendar_feed_token(100).unwrap();
+        db.upsert_chat(100, Some("chat-100"), "private").unwrap();
+        assert!(db.delete_chat_data(100).unwrap());
+        assert!(db.get_calendar_feed_token(100).unwrap().is_none());
 
         cleanup(&dir);
     }
@@ -3640,6 +6146,7 @@ mod tests {
             content: "hello".into(),
             is_from_bot: false,
             timestamp: "2024-06-01T00:00:01Z".into(),
+            thread_id: None,
         })
         .unwrap();
         db.store_message(&StoredMessage {
@@ -3649,6 +6156,7 @@ mod tests {
             content: "hi".into(),
             is_from_bot: false,
             timestamp: "2024-06-01T00:00:02Z".into(),
+            thread_id: None,
         })
         .unwrap();
         // Bot message should not count
@@ -3659,6 +6167,7 @@ mod tests {
             content: "bot msg".into(),
             is_from_bot: true,
             timestamp: "2024-06-01T00:00:03Z".into(),
+            thread_id: None,
         })
         .unwrap();
 
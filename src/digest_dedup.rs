@@ -0,0 +1,184 @@
+//! Duplicate detection for recurring digests: links in scheduled task
+//! results are remembered per chat (as hashes of a normalized URL), and the
+//! next runs are told which ones the chat already received, so a daily news
+//! summary doesn't repeat yesterday's stories.
+
+use std::sync::OnceLock;
+
+use chrono::{Duration, Utc};
+use regex::Regex;
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use crate::db::{call_blocking, ScheduledTask};
+use crate::runtime::AppState;
+
+/// Links listed in the prompt; older ones are still skipped by the model
+/// only if it happens to recall them, so keep the list to recent items.
+const MAX_PROMPT_LINKS: usize = 100;
+/// Steers scheduled runs (typically news digests) to dated, attributed
+/// articles instead of general web results.
+const NEWS_SOURCE_NOTE: &str = "[Sources]\nFor news and current events, use news_search (dated articles with their source) rather than web_search.";
+/// Query parameters that only track where a click came from.
+const TRACKING_PARAMS: &[&str] = &["fbclid", "gclid", "mc_cid", "mc_eid", "ref", "ref_src"];
+
+fn url_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r#"https?://[^\s<>()\[\]{}"'`|]+"#).unwrap())
+}
+
+/// Canonical form of a link for comparison: no fragment, tracking
+/// parameters, `www.` or trailing slash. `None` if it doesn't parse.
+pub fn normalize_link(link: &str) -> Option<String> {
+    let mut url = reqwest::Url::parse(link).ok()?;
+    url.set_fragment(None);
+    let host = url.host_str()?.to_ascii_lowercase();
+    if let Some(bare) = host.strip_prefix("www.") {
+        url.set_host(Some(bare)).ok()?;
+    }
+    let query: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(k, _)| {
+            let k = k.to_ascii_lowercase();
+            !k.starts_with("utm_") && !TRACKING_PARAMS.contains(&k.as_str())
+        })
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect();
+    if query.is_empty() {
+        url.set_query(None);
+    } else {
+        url.query_pairs_mut().clear().extend_pairs(query);
+    }
+    let path = url.path().trim_end_matches('/').to_string();
+    url.set_path(if path.is_empty() { "/" } else { &path });
+    let _ = url.set_scheme("https");
+    Some(url.to_string())
+}
+
+/// Distinct normalized links in `text`, in order of appearance.
+pub fn extract_links(text: &str) -> Vec<String> {
+    let mut links: Vec<String> = Vec::new();
+    for m in url_regex().find_iter(text) {
+        let raw = m
+            .as_str()
+            .trim_end_matches(['.', ',', ';', ':', '!', '?', '*', '_', '~']);
+        if let Some(link) = normalize_link(raw) {
+            if !links.contains(&link) {
+                links.push(link);
+            }
+        }
+    }
+    links
+}
+
+pub fn link_hash(normalized: &str) -> String {
+    hex::encode(Sha256::digest(normalized.as_bytes()))
+}
+
+/// `prompt` followed by the links the chat was already sent.
+pub fn prompt_with_seen_links(prompt: &str, seen: &[String]) -> String {
+    if seen.is_empty() {
+        return prompt.to_string();
+    }
+    let mut out = format!(
+        "{prompt}\n\n[Already sent]\nEarlier scheduled results in this chat already included these links. \
+         Leave them out unless there is a substantial new development, and say so briefly if nothing new is left:"
+    );
+    for link in seen {
+        out.push_str("\n- ");
+        out.push_str(link);
+    }
+    out
+}
+
+/// The task prompt for this run, with the news source note and recently
+/// sent links appended.
+pub async fn prompt_for_task(state: &AppState, task: &ScheduledTask) -> String {
+    let prompt = format!("{}\n\n{NEWS_SOURCE_NOTE}", task.prompt);
+    let days = state.config.digest_dedup_days;
+    if days == 0 {
+        return prompt;
+    }
+    let chat_id = task.chat_id;
+    let since = (Utc::now() - Duration::days(days as i64)).to_rfc3339();
+    match call_blocking(state.db.clone(), move |db| {
+        db.get_digest_links_since(chat_id, &since, MAX_PROMPT_LINKS)
+    })
+    .await
+    {
+        Ok(seen) => prompt_with_seen_links(&prompt, &seen),
+        Err(e) => {
+            warn!("Digest dedup: failed to load links for chat {chat_id}: {e}");
+            prompt
+        }
+    }
+}
+
+/// Remember the links in a task result and forget ones past the window.
+pub async fn record_result(state: &AppState, task: &ScheduledTask, result: &str) {
+    let days = state.config.digest_dedup_days;
+    if days == 0 {
+        return;
+    }
+    let items: Vec<(String, String)> = extract_links(result)
+        .into_iter()
+        .map(|link| (link_hash(&link), link))
+        .collect();
+    let (chat_id, task_id) = (task.chat_id, task.id);
+    let before = (Utc::now() - Duration::days(days as i64)).to_rfc3339();
+    if let Err(e) = call_blocking(state.db.clone(), move |db| {
+        db.prune_digest_links(&before)?;
+        db.record_digest_links(chat_id, task_id, &items)
+    })
+    .await
+    {
+        warn!("Digest dedup: failed to record links for task #{task_id}: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_link() {
+        assert_eq!(
+            normalize_link("http://WWW.Example.com/news/story/?utm_source=x&id=5&fbclid=abc#top")
+                .as_deref(),
+            Some("https://example.com/news/story?id=5")
+        );
+        assert_eq!(
+            normalize_link("https://example.com").as_deref(),
+            Some("https://example.com/")
+        );
+        assert_eq!(normalize_link("not a url"), None);
+    }
+
+    #[test]
+    fn test_extract_links() {
+        let text = "Top stories:\n\
+            1. [Rust 2.0](https://blog.rust-lang.org/2.0/) - big news.\n\
+            2. See https://example.com/a?utm_medium=rss, and **https://example.com/a**\n\
+            3. <https://news.ycombinator.com/item?id=1>";
+        assert_eq!(
+            extract_links(text),
+            vec![
+                "https://blog.rust-lang.org/2.0",
+                "https://example.com/a",
+                "https://news.ycombinator.com/item?id=1",
+            ]
+        );
+        assert!(extract_links("nothing here").is_empty());
+    }
+
+    #[test]
+    fn test_prompt_with_seen_links() {
+        assert_eq!(prompt_with_seen_links("Daily news", &[]), "Daily news");
+        let prompt = prompt_with_seen_links(
+            "Daily news",
+            &["https://a.com/1".into(), "https://b.com/2".into()],
+        );
+        assert!(prompt.starts_with("Daily news\n\n[Already sent]\n"));
+        assert!(prompt.ends_with("\n- https://a.com/1\n- https://b.com/2"));
+    }
+}
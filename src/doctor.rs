@@ -14,7 +14,7 @@ pub enum CheckStatus {
 }
 
 impl CheckStatus {
-    fn as_label(self) -> &'static str {
+    pub(crate) fn as_label(self) -> &'static str {
         match self {
             CheckStatus::Pass => "PASS",
             CheckStatus::Warn => "WARN",
@@ -22,7 +22,7 @@ impl CheckStatus {
         }
     }
 
-    fn as_emoji(self) -> &'static str {
+    pub(crate) fn as_emoji(self) -> &'static str {
         match self {
             CheckStatus::Pass => "✅",
             CheckStatus::Warn => "⚠️",
@@ -167,7 +167,7 @@ fn check_config(report: &mut DoctorReport) {
 
 fn check_path(report: &mut DoctorReport) {
     let target = if cfg!(target_os = "windows") {
-        user_home_dir().map(|h| h.join(".local").join("bin"))
+        crate::platform::home_dir().map(|h| h.join(".local").join("bin"))
     } else {
         None
     };
@@ -526,14 +526,6 @@ fn is_wsl() -> bool {
     false
 }
 
-fn user_home_dir() -> Option<PathBuf> {
-    if cfg!(target_os = "windows") {
-        std::env::var_os("USERPROFILE").map(PathBuf::from)
-    } else {
-        std::env::var_os("HOME").map(PathBuf::from)
-    }
-}
-
 fn path_contains(dir: &Path) -> bool {
     let Ok(path_var) = std::env::var("PATH") else {
         return false;
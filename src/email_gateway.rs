@@ -0,0 +1,424 @@
+//! Inbound email gateway: a mail provider's inbound webhook posts forwarded
+//! emails here, and each one becomes a task in the chat its recipient address
+//! is routed to. The subject may carry a schedule directive; without one the
+//! email runs as soon as the scheduler next polls.
+//!
+//! Subject directives (case-insensitive, anywhere in the subject):
+//! - `[cron 0 0 9 * * Mon]` — recurring task (5-field expressions get a `0`
+//!   seconds field prepended)
+//! - `[at 2026-11-02 09:00]` — one-time task, in the configured timezone
+//!   unless the timestamp is RFC 3339 with its own offset
+
+use std::sync::Arc;
+
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use tracing::info;
+
+use crate::config::{EmailGatewayConfig, EmailRoute};
+use crate::db::call_blocking;
+use crate::runtime::AppState;
+use crate::text::floor_char_boundary;
+use crate::tools::schedule::compute_next_run;
+
+/// Longest email body passed on to the agent.
+const MAX_BODY_BYTES: usize = 8_000;
+
+/// Field names used by common inbound-mail webhooks (Mailgun, Postmark,
+/// SendGrid, Cloudflare Email Workers), matched case-insensitively.
+const FROM_FIELDS: &[&str] = &["from", "sender"];
+const TO_FIELDS: &[&str] = &["to", "recipient", "originalrecipient"];
+const SUBJECT_FIELDS: &[&str] = &["subject"];
+const TEXT_FIELDS: &[&str] = &["stripped-text", "body-plain", "textbody", "text"];
+const HTML_FIELDS: &[&str] = &["body-html", "htmlbody", "html"];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct InboundEmail {
+    /// Bare, lowercased sender address
+    pub from: String,
+    /// Bare, lowercased recipient addresses
+    pub to: Vec<String>,
+    pub subject: String,
+    pub text: String,
+}
+
+impl InboundEmail {
+    /// Build from a JSON webhook payload.
+    pub fn from_json(value: &serde_json::Value) -> Result<Self, String> {
+        let obj = value
+            .as_object()
+            .ok_or_else(|| "expected a JSON object".to_string())?;
+        Self::from_fields(|names| {
+            obj.iter()
+                .find(|(k, _)| names.iter().any(|n| k.eq_ignore_ascii_case(n)))
+                .and_then(|(_, v)| match v {
+                    serde_json::Value::String(s) => Some(s.clone()),
+                    // Some providers send recipients as a list
+                    serde_json::Value::Array(items) => Some(
+                        items
+                            .iter()
+                            .filter_map(|i| i.as_str())
+                            .collect::<Vec<_>>()
+                            .join(", "),
+                    ),
+                    _ => None,
+                })
+        })
+    }
+
+    /// Build from an `application/x-www-form-urlencoded` webhook payload.
+    pub fn from_form(body: &str) -> Result<Self, String> {
+        let pairs: Vec<(String, String)> = body
+            .split('&')
+            .filter(|p| !p.is_empty())
+            .map(|pair| {
+                let (k, v) = pair.split_once('=').unwrap_or((pair, ""));
+                (form_decode(k), form_decode(v))
+            })
+            .collect();
+        Self::from_fields(|names| {
+            pairs
+                .iter()
+                .find(|(k, _)| names.iter().any(|n| k.eq_ignore_ascii_case(n)))
+                .map(|(_, v)| v.clone())
+        })
+    }
+
+    fn from_fields(get: impl Fn(&[&str]) -> Option<String>) -> Result<Self, String> {
+        let from = get(FROM_FIELDS)
+            .and_then(|raw| extract_address(&raw))
+            .ok_or_else(|| "missing sender address".to_string())?;
+        let to = get(TO_FIELDS)
+            .map(|raw| split_addresses(&raw))
+            .unwrap_or_default();
+        if to.is_empty() {
+            return Err("missing recipient address".into());
+        }
+        let subject = get(SUBJECT_FIELDS).unwrap_or_default().trim().to_string();
+        let text = get(TEXT_FIELDS)
+            .filter(|t| !t.trim().is_empty())
+            .or_else(|| get(HTML_FIELDS).map(|h| crate::tools::web_html::html_to_text(&h)))
+            .unwrap_or_default()
+            .trim()
+            .to_string();
+        Ok(InboundEmail {
+            from,
+            to,
+            subject,
+            text,
+        })
+    }
+}
+
+fn form_decode(s: &str) -> String {
+    let s = s.replace('+', " ");
+    urlencoding::decode(&s).map(|c| c.into_owned()).unwrap_or(s)
+}
+
+/// Bare lowercased address from `Name <addr>` or `addr`.
+pub fn extract_address(raw: &str) -> Option<String> {
+    let raw = raw.trim();
+    let addr = match (raw.rfind('<'), raw.rfind('>')) {
+        (Some(start), Some(end)) if start < end => &raw[start + 1..end],
+        _ => raw,
+    };
+    let addr = addr.trim().trim_matches('"').to_lowercase();
+    (addr.contains('@') && !addr.contains(char::is_whitespace)).then_some(addr)
+}
+
+/// Addresses in a comma-separated recipient header.
+pub fn split_addresses(raw: &str) -> Vec<String> {
+    raw.split(',').filter_map(extract_address).collect()
+}
+
+/// The route for the first recipient with an exact match, else a `"*"` route.
+pub fn find_route<'a>(config: &'a EmailGatewayConfig, to: &[String]) -> Option<&'a EmailRoute> {
+    to.iter()
+        .find_map(|addr| config.routes.iter().find(|r| r.address == *addr))
+        .or_else(|| config.routes.iter().find(|r| r.address == "*"))
+}
+
+pub fn sender_allowed(route: &EmailRoute, from: &str) -> bool {
+    route.allowed_senders.is_empty()
+        || route.allowed_senders.iter().any(|allowed| {
+            let allowed = allowed.trim().to_lowercase();
+            if allowed.starts_with('@') {
+                from.ends_with(&allowed)
+            } else {
+                from == allowed
+            }
+        })
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum EmailSchedule {
+    Now,
+    Cron(String),
+    Once(DateTime<Utc>),
+}
+
+/// Pull a schedule directive out of `subject`, returning it with the
+/// remaining subject text.
+pub fn parse_subject(subject: &str, tz_name: &str) -> Result<(EmailSchedule, String), String> {
+    let lower = subject.to_ascii_lowercase();
+    let Some(start) = lower.find("[cron ").or_else(|| lower.find("[at ")) else {
+        return Ok((EmailSchedule::Now, subject.trim().to_string()));
+    };
+    let end = subject[start..]
+        .find(']')
+        .map(|e| start + e)
+        .ok_or_else(|| "unterminated schedule directive in subject".to_string())?;
+    let directive = &subject[start + 1..end];
+    let rest = format!("{} {}", &subject[..start], &subject[end + 1..])
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let (kind, value) = directive.split_once(' ').unwrap_or((directive, ""));
+    let value = value.trim();
+    let schedule = if kind.eq_ignore_ascii_case("cron") {
+        let expr = if value.split_whitespace().count() == 5 {
+            format!("0 {value}")
+        } else {
+            value.to_string()
+        };
+        compute_next_run(&expr, tz_name)?;
+        EmailSchedule::Cron(expr)
+    } else {
+        EmailSchedule::Once(parse_at(value, tz_name)?)
+    };
+    Ok((schedule, rest))
+}
+
+fn parse_at(value: &str, tz_name: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Ok(dt.with_timezone(&Utc));
+    }
+    let tz: chrono_tz::Tz = tz_name
+        .parse()
+        .map_err(|_| format!("Invalid timezone: {tz_name}"))?;
+    let naive = ["%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M:%S"]
+        .iter()
+        .find_map(|fmt| NaiveDateTime::parse_from_str(value, fmt).ok())
+        .ok_or_else(|| format!("Invalid time '{value}': use YYYY-MM-DD HH:MM"))?;
+    tz.from_local_datetime(&naive)
+        .earliest()
+        .map(|dt| dt.with_timezone(&Utc))
+        .ok_or_else(|| format!("'{value}' does not exist in {tz_name}"))
+}
+
+/// The task prompt for an email: its subject as a heading, then the body.
+pub fn build_prompt(from: &str, subject: &str, text: &str) -> String {
+    let mut body = text.trim();
+    let truncated = body.len() > MAX_BODY_BYTES;
+    if truncated {
+        body = &body[..floor_char_boundary(body, MAX_BODY_BYTES)];
+    }
+    let mut prompt = format!("Email from {from}");
+    if !subject.is_empty() {
+        prompt.push_str(&format!(": {subject}"));
+    }
+    if !body.is_empty() {
+        prompt.push_str("\n\n");
+        prompt.push_str(body);
+        if truncated {
+            prompt.push_str("\n\n[email truncated]");
+        }
+    }
+    prompt
+}
+
+#[derive(Debug, PartialEq)]
+pub enum GatewayError {
+    NoRoute,
+    SenderNotAllowed,
+    Invalid(String),
+    Internal(String),
+}
+
+#[derive(Debug)]
+pub struct Accepted {
+    pub chat_id: i64,
+    pub task_id: i64,
+    pub next_run: String,
+    pub recurring: bool,
+}
+
+/// Route `email` and file it as a scheduled task.
+pub async fn accept(
+    state: &Arc<AppState>,
+    config: &EmailGatewayConfig,
+    email: InboundEmail,
+) -> Result<Accepted, GatewayError> {
+    let route = find_route(config, &email.to).ok_or(GatewayError::NoRoute)?;
+    if !sender_allowed(route, &email.from) {
+        return Err(GatewayError::SenderNotAllowed);
+    }
+    let (schedule, subject) =
+        parse_subject(&email.subject, &state.config.timezone).map_err(GatewayError::Invalid)?;
+    if subject.is_empty() && email.text.is_empty() {
+        return Err(GatewayError::Invalid("email has no subject or body".into()));
+    }
+    let prompt = build_prompt(&email.from, &subject, &email.text);
+
+    let (schedule_type, schedule_value, next_run) = match schedule {
+        EmailSchedule::Now => {
+            let now = Utc::now().to_rfc3339();
+            ("once", now.clone(), now)
+        }
+        EmailSchedule::Cron(expr) => {
+            let next =
+                compute_next_run(&expr, &state.config.timezone).map_err(GatewayError::Invalid)?;
+            ("cron", expr, next)
+        }
+        EmailSchedule::Once(at) => ("once", at.to_rfc3339(), at.to_rfc3339()),
+    };
+
+    let chat_id = route.chat_id;
+    let next_run_owned = next_run.clone();
+    let task_id = call_blocking(state.db.clone(), move |db| {
+        db.create_scheduled_task(
+            chat_id,
+            &prompt,
+            schedule_type,
+            &schedule_value,
+            &next_run_owned,
+        )
+    })
+    .await
+    .map_err(|e| GatewayError::Internal(e.to_string()))?;
+
+    info!(
+        "Email gateway: task #{task_id} for chat {chat_id} from {}",
+        email.from
+    );
+    Ok(Accepted {
+        chat_id,
+        task_id,
+        next_run,
+        recurring: schedule_type == "cron",
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gateway(routes: Vec<EmailRoute>) -> EmailGatewayConfig {
+        EmailGatewayConfig {
+            token: "t".into(),
+            routes,
+        }
+    }
+
+    fn route(address: &str, chat_id: i64, allowed: &[&str]) -> EmailRoute {
+        EmailRoute {
+            address: address.into(),
+            chat_id,
+            allowed_senders: allowed.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_extract_address() {
+        assert_eq!(
+            extract_address("Alice Example <Alice@Example.com>").as_deref(),
+            Some("alice@example.com")
+        );
+        assert_eq!(extract_address(" bob@x.org ").as_deref(), Some("bob@x.org"));
+        assert_eq!(extract_address("no address"), None);
+        assert_eq!(
+            split_addresses("a@x.com, \"B\" <b@y.com>, junk"),
+            vec!["a@x.com", "b@y.com"]
+        );
+    }
+
+    #[test]
+    fn test_from_json_and_form() {
+        let email = InboundEmail::from_json(&serde_json::json!({
+            "From": "Alice <alice@example.com>",
+            "To": ["tasks@bot.example"],
+            "Subject": "Buy milk",
+            "TextBody": "",
+            "HtmlBody": "<p>Two <b>litres</b></p>"
+        }))
+        .unwrap();
+        assert_eq!(email.from, "alice@example.com");
+        assert_eq!(email.to, vec!["tasks@bot.example"]);
+        assert_eq!(email.text, "Two litres");
+
+        let email = InboundEmail::from_form(
+            "sender=bob%40x.org&recipient=tasks%40bot.example&subject=Hello+there&body-plain=Line+1%0ALine+2",
+        )
+        .unwrap();
+        assert_eq!(email.from, "bob@x.org");
+        assert_eq!(email.subject, "Hello there");
+        assert_eq!(email.text, "Line 1\nLine 2");
+
+        assert!(InboundEmail::from_form("subject=x&to=a%40b.c").is_err());
+        assert!(InboundEmail::from_json(&serde_json::json!({"from": "a@b.c"})).is_err());
+    }
+
+    #[test]
+    fn test_find_route_and_senders() {
+        let cfg = gateway(vec![
+            route("*", 1, &[]),
+            route("work@bot.example", 2, &["@corp.com", "me@home.net"]),
+        ]);
+        let to = vec!["other@x.com".to_string(), "work@bot.example".to_string()];
+        let r = find_route(&cfg, &to).unwrap();
+        assert_eq!(r.chat_id, 2);
+        assert!(sender_allowed(r, "ann@corp.com"));
+        assert!(sender_allowed(r, "me@home.net"));
+        assert!(!sender_allowed(r, "ann@evilcorp.com.attacker"));
+        assert!(!sender_allowed(r, "you@home.net"));
+
+        let r = find_route(&cfg, &["x@y.z".to_string()]).unwrap();
+        assert_eq!(r.chat_id, 1);
+        assert!(sender_allowed(r, "anyone@anywhere.org"));
+        assert!(find_route(&gateway(vec![route("a@b.c", 1, &[])]), &to).is_none());
+    }
+
+    #[test]
+    fn test_parse_subject() {
+        assert_eq!(
+            parse_subject("  Plain subject ", "UTC").unwrap(),
+            (EmailSchedule::Now, "Plain subject".into())
+        );
+        assert_eq!(
+            parse_subject("Standup notes [CRON 0 9 * * Mon-Fri] please", "UTC").unwrap(),
+            (
+                EmailSchedule::Cron("0 0 9 * * Mon-Fri".into()),
+                "Standup notes please".into()
+            )
+        );
+        let (schedule, rest) =
+            parse_subject("[at 2026-11-02 09:00] Call Bob", "Europe/Berlin").unwrap();
+        assert_eq!(rest, "Call Bob");
+        assert_eq!(
+            schedule,
+            EmailSchedule::Once("2026-11-02T08:00:00Z".parse().unwrap())
+        );
+        let (schedule, _) = parse_subject("[at 2026-11-02T09:00:00-05:00] x", "UTC").unwrap();
+        assert_eq!(
+            schedule,
+            EmailSchedule::Once("2026-11-02T14:00:00Z".parse().unwrap())
+        );
+
+        assert!(parse_subject("[cron not a cron] x", "UTC").is_err());
+        assert!(parse_subject("[at tomorrow] x", "UTC").is_err());
+        assert!(parse_subject("[at 2026-11-02 09:00 x", "UTC").is_err());
+    }
+
+    #[test]
+    fn test_build_prompt() {
+        assert_eq!(
+            build_prompt("a@b.c", "Buy milk", " Two litres "),
+            "Email from a@b.c: Buy milk\n\nTwo litres"
+        );
+        assert_eq!(build_prompt("a@b.c", "", ""), "Email from a@b.c");
+        let long = "é".repeat(MAX_BODY_BYTES);
+        let prompt = build_prompt("a@b.c", "s", &long);
+        assert!(prompt.ends_with("[email truncated]"));
+        assert!(prompt.len() < MAX_BODY_BYTES + 100);
+    }
+}
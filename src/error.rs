@@ -9,6 +9,14 @@ pub enum RayClawError {
     #[error("Rate limited, retry after backoff")]
     RateLimited,
 
+    /// The provider's moderation rejected the request
+    #[error("Request flagged by moderation: {0}")]
+    Moderation(String),
+
+    /// No upstream model could serve the request (routers like OpenRouter)
+    #[error("Model provider unavailable: {0}")]
+    ProviderUnavailable(String),
+
     #[error("Database error: {0}")]
     Database(#[from] rusqlite::Error),
 
@@ -1,5 +1,11 @@
 //! Shared image utility functions used by multiple channel adapters (Telegram, Feishu, etc.)
 
+use std::io::Cursor;
+
+use image::{DynamicImage, GenericImageView, ImageDecoder, ImageFormat};
+
+use crate::config::Config;
+
 /// Base64-encode raw image bytes.
 pub fn base64_encode(data: &[u8]) -> String {
     use base64::Engine;
@@ -16,7 +22,288 @@ pub fn guess_image_media_type(data: &[u8]) -> String {
         "image/gif".into()
     } else if data.starts_with(b"RIFF") && data.len() >= 12 && &data[8..12] == b"WEBP" {
         "image/webp".into()
+    } else if is_heic(data) {
+        "image/heic".into()
     } else {
         "image/jpeg".into() // default fallback
     }
 }
+
+/// HEIC/HEIF containers are ISO-BMFF files with an `ftyp` box and a HEIF brand.
+pub fn is_heic(data: &[u8]) -> bool {
+    if data.len() < 12 || &data[4..8] != b"ftyp" {
+        return false;
+    }
+    matches!(
+        &data[8..12],
+        b"heic" | b"heix" | b"hevc" | b"hevx" | b"heim" | b"heis" | b"mif1" | b"msf1"
+    )
+}
+
+/// Provider payload limits hover around 5 MB per image; stay under it.
+const MAX_IMAGE_BYTES: usize = 4 * 1024 * 1024;
+
+/// How images are normalized before being sent to an LLM.
+#[derive(Debug, Clone, Copy)]
+pub struct ImageOptions {
+    /// Longest edge in pixels (0 = never resize).
+    pub max_dimension: u32,
+    /// JPEG quality used when re-encoding (1-100).
+    pub jpeg_quality: u8,
+}
+
+impl ImageOptions {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            max_dimension: config.image_max_dimension,
+            jpeg_quality: config.image_jpeg_quality,
+        }
+    }
+}
+
+impl Default for ImageOptions {
+    fn default() -> Self {
+        Self {
+            max_dimension: 1568,
+            jpeg_quality: 85,
+        }
+    }
+}
+
+/// Convert HEIC to JPEG with whichever converter is installed
+/// (`heif-convert` from libheif, ImageMagick, or macOS `sips`).
+fn convert_heic_to_jpeg(data: &[u8], quality: u8) -> Option<Vec<u8>> {
+    let dir = std::env::temp_dir().join(format!("rayclaw-heic-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).ok()?;
+    let input = dir.join("input.heic");
+    let output = dir.join("output.jpg");
+    let result = (|| {
+        std::fs::write(&input, data).ok()?;
+        let quality = quality.to_string();
+        let input_s = input.to_string_lossy().to_string();
+        let output_s = output.to_string_lossy().to_string();
+        let (q, i, o) = (quality.as_str(), input_s.as_str(), output_s.as_str());
+        let candidates: [(&str, Vec<&str>); 3] = [
+            ("heif-convert", vec!["-q", q, i, o]),
+            ("magick", vec![i, "-quality", q, o]),
+            ("sips", vec!["-s", "format", "jpeg", i, "--out", o]),
+        ];
+        for (program, args) in candidates {
+            let ok = std::process::Command::new(program)
+                .args(&args)
+                .stdout(std::process::Stdio::null())
+                .stderr(std::process::Stdio::null())
+                .status()
+                .map(|s| s.success())
+                .unwrap_or(false);
+            if ok {
+                if let Ok(bytes) = std::fs::read(&output) {
+                    return Some(bytes);
+                }
+            }
+        }
+        None
+    })();
+    let _ = std::fs::remove_dir_all(&dir);
+    result
+}
+
+fn encode_jpeg(img: &DynamicImage, quality: u8) -> Option<Vec<u8>> {
+    let mut buf = Vec::new();
+    let mut encoder =
+        image::codecs::jpeg::JpegEncoder::new_with_quality(&mut buf, quality.clamp(1, 100));
+    encoder.encode_image(&img.to_rgb8()).ok()?;
+    Some(buf)
+}
+
+fn encode_png(img: &DynamicImage) -> Option<Vec<u8>> {
+    let mut buf = Vec::new();
+    img.write_to(&mut Cursor::new(&mut buf), ImageFormat::Png)
+        .ok()?;
+    Some(buf)
+}
+
+/// Normalize an image for LLM submission: HEIC is converted to JPEG, images whose
+/// longest edge exceeds `max_dimension` are downscaled (after applying their EXIF
+/// orientation), and oversized payloads are re-encoded as JPEG. Returns `(bytes, media_type)`; on any decode failure the
+/// original bytes are returned unchanged.
+pub fn prepare_image_for_llm(data: &[u8], opts: &ImageOptions) -> (Vec<u8>, String) {
+    let converted = if is_heic(data) {
+        match convert_heic_to_jpeg(data, opts.jpeg_quality) {
+            Some(jpeg) => Some(jpeg),
+            None => {
+                tracing::warn!(
+                    "HEIC image received but no converter (heif-convert, magick, sips) is available"
+                );
+                return (data.to_vec(), guess_image_media_type(data));
+            }
+        }
+    } else {
+        None
+    };
+    let source: &[u8] = converted.as_deref().unwrap_or(data);
+    let media = guess_image_media_type(source);
+
+    let needs_resize_check = opts.max_dimension > 0 || source.len() > MAX_IMAGE_BYTES;
+    if !needs_resize_check {
+        return (source.to_vec(), media);
+    }
+    let img = match decode_oriented(source) {
+        Ok(img) => img,
+        Err(e) => {
+            tracing::debug!("Image decode failed, sending original bytes: {e}");
+            return (source.to_vec(), media);
+        }
+    };
+
+    let (w, h) = img.dimensions();
+    let too_large = opts.max_dimension > 0 && w.max(h) > opts.max_dimension;
+    if !too_large && source.len() <= MAX_IMAGE_BYTES {
+        return (source.to_vec(), media);
+    }
+    let img = if too_large {
+        img.resize(
+            opts.max_dimension,
+            opts.max_dimension,
+            image::imageops::FilterType::Lanczos3,
+        )
+    } else {
+        img
+    };
+
+    // Keep transparency for PNGs that stay small enough; everything else becomes JPEG.
+    if media == "image/png" && img.color().has_alpha() {
+        if let Some(png) = encode_png(&img).filter(|b| b.len() <= MAX_IMAGE_BYTES) {
+            return (png, "image/png".into());
+        }
+    }
+    match encode_jpeg(&img, opts.jpeg_quality) {
+        Some(jpeg) => (jpeg, "image/jpeg".into()),
+        None => (source.to_vec(), media),
+    }
+}
+
+/// Decode an image with its EXIF orientation applied, so rotated phone photos
+/// stay upright once re-encoded (which drops the EXIF tag).
+fn decode_oriented(data: &[u8]) -> image::ImageResult<DynamicImage> {
+    let mut decoder = image::ImageReader::new(Cursor::new(data))
+        .with_guessed_format()?
+        .into_decoder()?;
+    let orientation = decoder
+        .orientation()
+        .unwrap_or(image::metadata::Orientation::NoTransforms);
+    let mut img = DynamicImage::from_decoder(decoder)?;
+    img.apply_orientation(orientation);
+    Ok(img)
+}
+
+/// Prepare an image off the async runtime and return `(base64, media_type)`.
+pub async fn encode_image_for_llm(data: Vec<u8>, opts: ImageOptions) -> (String, String) {
+    let original = data.clone();
+    match tokio::task::spawn_blocking(move || {
+        let (bytes, media) = prepare_image_for_llm(&data, &opts);
+        (base64_encode(&bytes), media)
+    })
+    .await
+    {
+        Ok(encoded) => encoded,
+        Err(e) => {
+            tracing::warn!("Image preparation task failed, sending original: {e}");
+            (base64_encode(&original), guess_image_media_type(&original))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png_bytes(w: u32, h: u32, alpha: bool) -> Vec<u8> {
+        let img = if alpha {
+            DynamicImage::new_rgba8(w, h)
+        } else {
+            DynamicImage::new_rgb8(w, h)
+        };
+        encode_png(&img).unwrap()
+    }
+
+    #[test]
+    fn test_is_heic() {
+        let mut data = vec![0, 0, 0, 24];
+        data.extend_from_slice(b"ftypheic");
+        assert!(is_heic(&data));
+        assert_eq!(guess_image_media_type(&data), "image/heic");
+        assert!(!is_heic(b"\x89PNG\r\n\x1a\n0000"));
+    }
+
+    #[test]
+    fn test_prepare_image_small_png_unchanged() {
+        let data = png_bytes(100, 50, false);
+        let (out, media) = prepare_image_for_llm(&data, &ImageOptions::default());
+        assert_eq!(out, data);
+        assert_eq!(media, "image/png");
+    }
+
+    #[test]
+    fn test_prepare_image_downscales_to_jpeg() {
+        let data = png_bytes(2000, 1000, false);
+        let opts = ImageOptions {
+            max_dimension: 500,
+            jpeg_quality: 80,
+        };
+        let (out, media) = prepare_image_for_llm(&data, &opts);
+        assert_eq!(media, "image/jpeg");
+        let img = image::load_from_memory(&out).unwrap();
+        assert_eq!(img.dimensions(), (500, 250));
+    }
+
+    #[test]
+    fn test_prepare_image_keeps_png_alpha() {
+        let data = png_bytes(1000, 1000, true);
+        let opts = ImageOptions {
+            max_dimension: 200,
+            jpeg_quality: 80,
+        };
+        let (out, media) = prepare_image_for_llm(&data, &opts);
+        assert_eq!(media, "image/png");
+        assert_eq!(
+            image::load_from_memory(&out).unwrap().dimensions(),
+            (200, 200)
+        );
+    }
+
+    #[test]
+    fn test_prepare_image_applies_exif_orientation() {
+        use image::ImageEncoder;
+        // Big-endian TIFF header with one IFD entry: Orientation = 6 (rotate 90° CW)
+        let exif = vec![
+            b'M', b'M', 0, 42, 0, 0, 0, 8, 0, 1, 0x01, 0x12, 0, 3, 0, 0, 0, 1, 0, 6, 0, 0, 0, 0, 0,
+            0,
+        ];
+        let img = DynamicImage::new_rgb8(2000, 1000);
+        let mut data = Vec::new();
+        let mut encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(&mut data, 80);
+        encoder.set_exif_metadata(exif).unwrap();
+        encoder
+            .write_image(img.as_bytes(), 2000, 1000, image::ExtendedColorType::Rgb8)
+            .unwrap();
+
+        let opts = ImageOptions {
+            max_dimension: 500,
+            jpeg_quality: 80,
+        };
+        let (out, _) = prepare_image_for_llm(&data, &opts);
+        assert_eq!(
+            image::load_from_memory(&out).unwrap().dimensions(),
+            (250, 500)
+        );
+    }
+
+    #[test]
+    fn test_prepare_image_undecodable_passthrough() {
+        let data = vec![0xFF, 0xD8, 0x00, 0x01];
+        let (out, media) = prepare_image_for_llm(&data, &ImageOptions::default());
+        assert_eq!(out, data);
+        assert_eq!(media, "image/jpeg");
+    }
+}
@@ -0,0 +1,745 @@
+//! Bulk import of historical chat exports into the messages table.
+//!
+//! Supported formats:
+//! - `telegram-export`: Telegram Desktop JSON export (`result.json`, single chat or full account)
+//! - `discord-export`: DiscordChatExporter JSON export (one file per channel)
+//! - `slack-export`: Slack workspace export (unzipped directory with `channels.json`)
+//!
+//! Message IDs are kept in the same shape the live adapters use, so importing
+//! the same export twice (or importing history the bot already saw) is a no-op.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use chrono::{DateTime, TimeZone, Utc};
+use serde_json::Value;
+
+use crate::config::Config;
+use crate::db::{Database, StoredMessage};
+use crate::error::RayClawError;
+use crate::memory_quality;
+
+#[derive(Debug, Clone)]
+pub struct ImportedMessage {
+    pub id: String,
+    pub sender_name: String,
+    pub content: String,
+    pub is_from_bot: bool,
+    pub timestamp: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ImportedChat {
+    pub channel: &'static str,
+    pub external_chat_id: String,
+    pub title: Option<String>,
+    pub chat_type: String,
+    pub messages: Vec<ImportedMessage>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ImportStats {
+    pub chats: usize,
+    pub messages_seen: usize,
+    pub messages_inserted: usize,
+    /// `(id, content)` of each memory written
+    pub memories_inserted: Vec<(i64, String)>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ImportOptions {
+    /// Extract explicit "remember ..." statements into structured memory.
+    pub memory: bool,
+    pub dry_run: bool,
+}
+
+fn print_usage() {
+    println!(
+        "Usage: rayclaw import <format> <path> [--bot-name NAME] [--memory] [--dry-run]
+
+Formats:
+  telegram-export   Telegram Desktop JSON export (result.json)
+  discord-export    DiscordChatExporter JSON export
+  slack-export      Unzipped Slack workspace export directory
+
+Options:
+  --bot-name NAME   Sender name to treat as the bot (default: bot_username from config)
+  --memory          Save explicit \"remember ...\" messages as memories (embedded
+                    when sqlite-vec and an embedding provider are configured)
+  --dry-run         Parse and report without writing to the database"
+    );
+}
+
+pub async fn run_cli(args: &[String]) -> anyhow::Result<()> {
+    if args.is_empty() || args.iter().any(|a| a == "--help" || a == "-h") {
+        print_usage();
+        return Ok(());
+    }
+    let format = args[0].as_str();
+    let Some(path) = args.get(1) else {
+        print_usage();
+        anyhow::bail!("missing export path");
+    };
+    let rest = &args[2..];
+    let options = ImportOptions {
+        memory: rest.iter().any(|a| a == "--memory"),
+        dry_run: rest.iter().any(|a| a == "--dry-run"),
+    };
+    let bot_name_arg = rest
+        .windows(2)
+        .find(|w| w[0] == "--bot-name")
+        .map(|w| w[1].clone());
+
+    let config = Config::load()?;
+    let bot_name = bot_name_arg.unwrap_or_else(|| config.bot_username.clone());
+
+    let path = Path::new(path);
+    let chats = match format {
+        "telegram-export" => parse_telegram_export(&read_json(path)?, &bot_name)?,
+        "discord-export" => parse_discord_export(&read_json(path)?)?,
+        "slack-export" => parse_slack_export(path)?,
+        other => {
+            print_usage();
+            anyhow::bail!("unknown import format: {other}");
+        }
+    };
+
+    if options.dry_run {
+        for chat in &chats {
+            println!(
+                "{} {} ({}): {} messages",
+                chat.channel,
+                chat.external_chat_id,
+                chat.title.as_deref().unwrap_or("untitled"),
+                chat.messages.len()
+            );
+        }
+        println!("Dry run: nothing written.");
+        return Ok(());
+    }
+
+    let db = Database::new(&config.runtime_data_dir())?;
+    let stats = import_chats(&db, &chats, &options)?;
+    let embedded = embed_memories(&config, &db, &stats.memories_inserted).await;
+    println!(
+        "Imported {} of {} messages across {} chat(s){}",
+        stats.messages_inserted,
+        stats.messages_seen,
+        stats.chats,
+        if options.memory {
+            format!(
+                ", {} memories ({embedded} embedded)",
+                stats.memories_inserted.len()
+            )
+        } else {
+            String::new()
+        }
+    );
+    Ok(())
+}
+
+/// Add imported memories to the vector index. Returns how many were
+/// embedded; memories that fail are left for the reflector's backfill.
+#[cfg(feature = "sqlite-vec")]
+async fn embed_memories(config: &Config, db: &Database, memories: &[(i64, String)]) -> usize {
+    if memories.is_empty() {
+        return 0;
+    }
+    let Some(provider) = crate::embedding::create_provider(config) else {
+        return 0;
+    };
+    if let Err(e) = db.prepare_vector_index(provider.dimension()) {
+        eprintln!("Failed to initialize sqlite-vec index: {e}");
+        return 0;
+    }
+    let mut embedded = 0;
+    for (id, content) in memories {
+        let embedding = match provider.embed(content).await {
+            Ok(embedding) => embedding,
+            Err(e) => {
+                eprintln!("Failed to embed memory #{id}: {e}");
+                continue;
+            }
+        };
+        let stored = db
+            .upsert_memory_vec(*id, &embedding)
+            .and_then(|_| db.update_memory_embedding_model(*id, provider.model()));
+        match stored {
+            Ok(_) => embedded += 1,
+            Err(e) => eprintln!("Failed to store the embedding of memory #{id}: {e}"),
+        }
+    }
+    embedded
+}
+
+#[cfg(not(feature = "sqlite-vec"))]
+async fn embed_memories(_config: &Config, _db: &Database, _memories: &[(i64, String)]) -> usize {
+    0
+}
+
+fn read_json(path: &Path) -> Result<Value, RayClawError> {
+    let raw = std::fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&raw)?)
+}
+
+/// Write parsed chats to the database. Chats are resolved through the same
+/// `(channel, external_chat_id)` identity the live adapters use.
+pub fn import_chats(
+    db: &Database,
+    chats: &[ImportedChat],
+    options: &ImportOptions,
+) -> Result<ImportStats, RayClawError> {
+    let mut stats = ImportStats::default();
+    for chat in chats {
+        if chat.messages.is_empty() {
+            continue;
+        }
+        let chat_id = db.resolve_or_create_chat_id(
+            chat.channel,
+            &chat.external_chat_id,
+            chat.title.as_deref(),
+            &chat.chat_type,
+        )?;
+        let stored: Vec<StoredMessage> = chat
+            .messages
+            .iter()
+            .map(|m| StoredMessage {
+                id: m.id.clone(),
+                chat_id,
+                sender_name: m.sender_name.clone(),
+                content: m.content.clone(),
+                is_from_bot: m.is_from_bot,
+                timestamp: m.timestamp.clone(),
+                thread_id: None,
+            })
+            .collect();
+        stats.chats += 1;
+        stats.messages_seen += stored.len();
+        stats.messages_inserted += db.store_messages_bulk(&stored)?;
+
+        if options.memory {
+            stats
+                .memories_inserted
+                .extend(import_explicit_memories(db, chat_id, &chat.messages)?);
+        }
+    }
+    Ok(stats)
+}
+
+fn import_explicit_memories(
+    db: &Database,
+    chat_id: i64,
+    messages: &[ImportedMessage],
+) -> Result<Vec<(i64, String)>, RayClawError> {
+    let mut existing: Vec<String> = db
+        .get_all_memories_for_chat(Some(chat_id))?
+        .into_iter()
+        .filter(|m| !m.is_archived)
+        .map(|m| m.content)
+        .collect();
+    let mut inserted = Vec::new();
+    for msg in messages.iter().filter(|m| !m.is_from_bot) {
+        let Some(content) = memory_quality::extract_explicit_memory_command(&msg.content) else {
+            continue;
+        };
+        if !memory_quality::memory_quality_ok(&content)
+            || existing.iter().any(|e| e.eq_ignore_ascii_case(&content))
+        {
+            continue;
+        }
+        let id =
+            db.insert_memory_with_metadata(Some(chat_id), &content, "KNOWLEDGE", "import", 0.80)?;
+        existing.push(content.clone());
+        inserted.push((id, content));
+    }
+    Ok(inserted)
+}
+
+fn unix_to_rfc3339(secs: f64) -> Option<String> {
+    let whole = secs.trunc() as i64;
+    let nanos = ((secs - secs.trunc()) * 1e9) as u32;
+    Utc.timestamp_opt(whole, nanos)
+        .single()
+        .map(|dt| dt.to_rfc3339())
+}
+
+// --- Telegram ---
+
+/// Flatten Telegram's `text` field, which is either a string or an array of
+/// plain strings and `{type, text}` entity objects.
+fn telegram_text(value: Option<&Value>) -> String {
+    match value {
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Array(parts)) => parts
+            .iter()
+            .map(|p| match p {
+                Value::String(s) => s.as_str(),
+                other => other.get("text").and_then(|t| t.as_str()).unwrap_or(""),
+            })
+            .collect(),
+        _ => String::new(),
+    }
+}
+
+/// Map a Telegram export chat type + bare id onto the Bot API chat id and the
+/// db chat type the live adapter records.
+fn telegram_chat_identity(kind: &str, id: i64) -> (String, &'static str) {
+    match kind {
+        "private_group" => (format!("-{id}"), "telegram_group"),
+        "private_supergroup" | "public_supergroup" => (format!("-100{id}"), "telegram_supergroup"),
+        "private_channel" | "public_channel" => (format!("-100{id}"), "telegram_channel"),
+        _ => (id.to_string(), "telegram_private"),
+    }
+}
+
+fn parse_telegram_chat(chat: &Value, bot_name: &str) -> Option<ImportedChat> {
+    let id = chat.get("id").and_then(|v| v.as_i64())?;
+    let kind = chat
+        .get("type")
+        .and_then(|v| v.as_str())
+        .unwrap_or("personal_chat");
+    let (external_chat_id, chat_type) = telegram_chat_identity(kind, id);
+    let title = chat
+        .get("name")
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string());
+
+    let messages = chat
+        .get("messages")
+        .and_then(|v| v.as_array())
+        .map(|msgs| {
+            msgs.iter()
+                .filter(|m| m.get("type").and_then(|t| t.as_str()) == Some("message"))
+                .filter_map(|m| {
+                    let msg_id = m.get("id").and_then(|v| v.as_i64())?;
+                    let mut content = telegram_text(m.get("text"));
+                    let media = if m.get("photo").is_some() {
+                        Some("[image]".to_string())
+                    } else if let Some(media_type) = m.get("media_type").and_then(|v| v.as_str()) {
+                        Some(format!("[{media_type}]"))
+                    } else {
+                        m.get("file_name")
+                            .and_then(|v| v.as_str())
+                            .map(|name| format!("[document] {name}"))
+                    };
+                    if let Some(media) = media {
+                        content = if content.trim().is_empty() {
+                            media
+                        } else {
+                            format!("{media} {content}")
+                        };
+                    }
+                    if content.trim().is_empty() {
+                        return None;
+                    }
+                    let timestamp = m
+                        .get("date_unixtime")
+                        .and_then(|v| v.as_str())
+                        .and_then(|s| s.parse::<f64>().ok())
+                        .and_then(unix_to_rfc3339)
+                        .or_else(|| {
+                            let raw = m.get("date").and_then(|v| v.as_str())?;
+                            chrono::NaiveDateTime::parse_from_str(raw, "%Y-%m-%dT%H:%M:%S")
+                                .ok()
+                                .map(|dt| dt.and_utc().to_rfc3339())
+                        })?;
+                    let sender_name = m
+                        .get("from")
+                        .and_then(|v| v.as_str())
+                        .unwrap_or("unknown")
+                        .to_string();
+                    let is_from_bot = sender_name.eq_ignore_ascii_case(bot_name)
+                        || sender_name.eq_ignore_ascii_case(&format!("@{bot_name}"));
+                    Some(ImportedMessage {
+                        id: msg_id.to_string(),
+                        sender_name,
+                        content,
+                        is_from_bot,
+                        timestamp,
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Some(ImportedChat {
+        channel: "telegram",
+        external_chat_id,
+        title,
+        chat_type: chat_type.to_string(),
+        messages,
+    })
+}
+
+/// Parse a Telegram Desktop export. Accepts both a single-chat `result.json`
+/// and a full account export (`chats.list[]`).
+pub fn parse_telegram_export(
+    root: &Value,
+    bot_name: &str,
+) -> Result<Vec<ImportedChat>, RayClawError> {
+    if let Some(list) = root
+        .get("chats")
+        .and_then(|c| c.get("list"))
+        .and_then(|l| l.as_array())
+    {
+        return Ok(list
+            .iter()
+            .filter_map(|chat| parse_telegram_chat(chat, bot_name))
+            .collect());
+    }
+    if root.get("messages").is_some() {
+        return Ok(parse_telegram_chat(root, bot_name).into_iter().collect());
+    }
+    Err(RayClawError::Config(
+        "not a Telegram export: expected `messages` or `chats.list`".into(),
+    ))
+}
+
+// --- Discord ---
+
+/// Parse a DiscordChatExporter JSON file (one channel per file).
+pub fn parse_discord_export(root: &Value) -> Result<Vec<ImportedChat>, RayClawError> {
+    let channel = root
+        .get("channel")
+        .ok_or_else(|| RayClawError::Config("not a Discord export: missing `channel`".into()))?;
+    let channel_id = channel
+        .get("id")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| RayClawError::Config("Discord export channel has no id".into()))?
+        .to_string();
+
+    let messages = root
+        .get("messages")
+        .and_then(|v| v.as_array())
+        .map(|msgs| {
+            msgs.iter()
+                .filter_map(|m| {
+                    let id = m.get("id").and_then(|v| v.as_str())?.to_string();
+                    let mut content = m
+                        .get("content")
+                        .and_then(|v| v.as_str())
+                        .unwrap_or("")
+                        .to_string();
+                    if let Some(attachments) = m.get("attachments").and_then(|v| v.as_array()) {
+                        for a in attachments {
+                            if let Some(name) = a.get("fileName").and_then(|v| v.as_str()) {
+                                if !content.is_empty() {
+                                    content.push('\n');
+                                }
+                                content.push_str(&format!("[attachment] {name}"));
+                            }
+                        }
+                    }
+                    if content.trim().is_empty() {
+                        return None;
+                    }
+                    let timestamp = m
+                        .get("timestamp")
+                        .and_then(|v| v.as_str())
+                        .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+                        .map(|dt| dt.with_timezone(&Utc).to_rfc3339())?;
+                    let author = m.get("author");
+                    let sender_name = author
+                        .and_then(|a| a.get("name"))
+                        .and_then(|v| v.as_str())
+                        .unwrap_or("unknown")
+                        .to_string();
+                    let is_from_bot = author
+                        .and_then(|a| a.get("isBot"))
+                        .and_then(|v| v.as_bool())
+                        .unwrap_or(false);
+                    Some(ImportedMessage {
+                        id,
+                        sender_name,
+                        content,
+                        is_from_bot,
+                        timestamp,
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Ok(vec![ImportedChat {
+        channel: "discord",
+        title: Some(format!("discord-{channel_id}")),
+        external_chat_id: channel_id,
+        chat_type: "discord".to_string(),
+        messages,
+    }])
+}
+
+// --- Slack ---
+
+fn slack_user_names(dir: &Path) -> HashMap<String, String> {
+    let Ok(users) = read_json(&dir.join("users.json")) else {
+        return HashMap::new();
+    };
+    users
+        .as_array()
+        .map(|list| {
+            list.iter()
+                .filter_map(|u| {
+                    let id = u.get("id")?.as_str()?.to_string();
+                    let name = u
+                        .get("real_name")
+                        .and_then(|v| v.as_str())
+                        .filter(|s| !s.is_empty())
+                        .or_else(|| u.get("name").and_then(|v| v.as_str()))?
+                        .to_string();
+                    Some((id, name))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn parse_slack_messages(
+    day: &Value,
+    users: &HashMap<String, String>,
+    out: &mut Vec<ImportedMessage>,
+) {
+    let Some(msgs) = day.as_array() else {
+        return;
+    };
+    for m in msgs {
+        let subtype = m.get("subtype").and_then(|v| v.as_str()).unwrap_or("");
+        if matches!(subtype, "channel_join" | "channel_leave" | "channel_topic") {
+            continue;
+        }
+        let Some(ts) = m.get("ts").and_then(|v| v.as_str()) else {
+            continue;
+        };
+        let text = m.get("text").and_then(|v| v.as_str()).unwrap_or("");
+        if text.trim().is_empty() {
+            continue;
+        }
+        let Some(timestamp) = ts.parse::<f64>().ok().and_then(unix_to_rfc3339) else {
+            continue;
+        };
+        let is_from_bot = m.get("bot_id").is_some() || subtype == "bot_message";
+        let sender_name = m
+            .get("user")
+            .and_then(|v| v.as_str())
+            .map(|u| users.get(u).cloned().unwrap_or_else(|| u.to_string()))
+            .or_else(|| {
+                m.get("username")
+                    .and_then(|v| v.as_str())
+                    .map(|s| s.to_string())
+            })
+            .unwrap_or_else(|| "unknown".to_string());
+        out.push(ImportedMessage {
+            id: ts.to_string(),
+            sender_name,
+            content: text.to_string(),
+            is_from_bot,
+            timestamp,
+        });
+    }
+}
+
+/// Parse an unzipped Slack workspace export. Each channel listed in
+/// `channels.json` (and `dms.json` when present) has a directory of per-day
+/// JSON files named after the channel (or its id, for DMs).
+pub fn parse_slack_export(dir: &Path) -> Result<Vec<ImportedChat>, RayClawError> {
+    if !dir.join("channels.json").exists() {
+        return Err(RayClawError::Config(format!(
+            "not a Slack export: {} has no channels.json",
+            dir.display()
+        )));
+    }
+    let users = slack_user_names(dir);
+    let mut chats = Vec::new();
+    for (listing, is_dm) in [("channels.json", false), ("dms.json", true)] {
+        let Ok(list) = read_json(&dir.join(listing)) else {
+            continue;
+        };
+        for channel in list.as_array().into_iter().flatten() {
+            let Some(id) = channel.get("id").and_then(|v| v.as_str()) else {
+                continue;
+            };
+            let folder = channel.get("name").and_then(|v| v.as_str()).unwrap_or(id);
+            let channel_dir = dir.join(folder);
+            let Ok(entries) = std::fs::read_dir(&channel_dir) else {
+                continue;
+            };
+            let mut day_files: Vec<_> = entries
+                .flatten()
+                .map(|e| e.path())
+                .filter(|p| p.extension().and_then(|e| e.to_str()) == Some("json"))
+                .collect();
+            day_files.sort();
+
+            let mut messages = Vec::new();
+            for file in day_files {
+                if let Ok(day) = read_json(&file) {
+                    parse_slack_messages(&day, &users, &mut messages);
+                }
+            }
+            chats.push(ImportedChat {
+                channel: "slack",
+                external_chat_id: id.to_string(),
+                title: Some(format!("slack-{id}")),
+                chat_type: (if is_dm { "slack_dm" } else { "slack" }).to_string(),
+                messages,
+            });
+        }
+    }
+    Ok(chats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn temp_dir() -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("rayclaw_import_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_parse_telegram_single_chat() {
+        let export = json!({
+            "name": "Team",
+            "type": "private_supergroup",
+            "id": 1234,
+            "messages": [
+                {"id": 1, "type": "service", "date_unixtime": "1700000000", "action": "create_group"},
+                {"id": 2, "type": "message", "date_unixtime": "1700000001", "from": "Alice",
+                 "text": ["see ", {"type": "link", "text": "https://example.com"}]},
+                {"id": 3, "type": "message", "date": "2023-11-14T22:13:22", "from": "rayclaw_bot",
+                 "text": "hi"},
+                {"id": 4, "type": "message", "date_unixtime": "1700000003", "from": "Bob",
+                 "photo": "photos/1.jpg", "text": ""}
+            ]
+        });
+        let chats = parse_telegram_export(&export, "rayclaw_bot").unwrap();
+        assert_eq!(chats.len(), 1);
+        let chat = &chats[0];
+        assert_eq!(chat.external_chat_id, "-1001234");
+        assert_eq!(chat.chat_type, "telegram_supergroup");
+        assert_eq!(chat.messages.len(), 3);
+        assert_eq!(chat.messages[0].content, "see https://example.com");
+        assert!(chat.messages[1].is_from_bot);
+        assert_eq!(chat.messages[2].content, "[image]");
+    }
+
+    #[test]
+    fn test_parse_telegram_full_export() {
+        let export = json!({
+            "chats": {"list": [
+                {"id": 42, "type": "personal_chat", "name": "Alice", "messages": []},
+                {"id": 7, "type": "private_group", "name": "G", "messages": []}
+            ]}
+        });
+        let chats = parse_telegram_export(&export, "bot").unwrap();
+        assert_eq!(chats[0].external_chat_id, "42");
+        assert_eq!(chats[0].chat_type, "telegram_private");
+        assert_eq!(chats[1].external_chat_id, "-7");
+        assert!(parse_telegram_export(&json!({"foo": 1}), "bot").is_err());
+    }
+
+    #[test]
+    fn test_parse_discord_export() {
+        let export = json!({
+            "guild": {"id": "1", "name": "Guild"},
+            "channel": {"id": "555", "name": "general"},
+            "messages": [
+                {"id": "10", "timestamp": "2024-01-01T10:00:00+02:00", "content": "hello",
+                 "author": {"name": "alice", "isBot": false}},
+                {"id": "11", "timestamp": "2024-01-01T10:01:00+00:00", "content": "",
+                 "author": {"name": "rayclaw", "isBot": true},
+                 "attachments": [{"fileName": "a.png"}]}
+            ]
+        });
+        let chats = parse_discord_export(&export).unwrap();
+        assert_eq!(chats[0].external_chat_id, "555");
+        assert_eq!(chats[0].messages[0].timestamp, "2024-01-01T08:00:00+00:00");
+        assert_eq!(chats[0].messages[1].content, "[attachment] a.png");
+        assert!(chats[0].messages[1].is_from_bot);
+    }
+
+    #[test]
+    fn test_parse_slack_export() {
+        let dir = temp_dir();
+        std::fs::write(
+            dir.join("channels.json"),
+            json!([{"id": "C1", "name": "general"}]).to_string(),
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("users.json"),
+            json!([{"id": "U1", "name": "alice", "real_name": "Alice A"}]).to_string(),
+        )
+        .unwrap();
+        std::fs::create_dir_all(dir.join("general")).unwrap();
+        std::fs::write(
+            dir.join("general").join("2024-01-01.json"),
+            json!([
+                {"type": "message", "subtype": "channel_join", "user": "U1", "text": "joined", "ts": "1704067200.000100"},
+                {"type": "message", "user": "U1", "text": "deploy at 5", "ts": "1704067201.000200"},
+                {"type": "message", "bot_id": "B1", "username": "rayclaw", "text": "ok", "ts": "1704067202.000300"}
+            ])
+            .to_string(),
+        )
+        .unwrap();
+
+        let chats = parse_slack_export(&dir).unwrap();
+        assert_eq!(chats.len(), 1);
+        assert_eq!(chats[0].external_chat_id, "C1");
+        assert_eq!(chats[0].messages.len(), 2);
+        assert_eq!(chats[0].messages[0].sender_name, "Alice A");
+        assert_eq!(chats[0].messages[0].id, "1704067201.000200");
+        assert!(chats[0].messages[1].is_from_bot);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_import_chats_is_idempotent_and_extracts_memories() {
+        let dir = temp_dir();
+        let db = Database::new(dir.to_str().unwrap()).unwrap();
+        let chats = vec![ImportedChat {
+            channel: "telegram",
+            external_chat_id: "42".into(),
+            title: Some("Alice".into()),
+            chat_type: "telegram_private".into(),
+            messages: vec![
+                ImportedMessage {
+                    id: "1".into(),
+                    sender_name: "alice".into(),
+                    content: "Remember that the staging db runs on port 5433".into(),
+                    is_from_bot: false,
+                    timestamp: "2024-01-01T00:00:00+00:00".into(),
+                },
+                ImportedMessage {
+                    id: "2".into(),
+                    sender_name: "bot".into(),
+                    content: "Noted.".into(),
+                    is_from_bot: true,
+                    timestamp: "2024-01-01T00:00:01+00:00".into(),
+                },
+            ],
+        }];
+        let options = ImportOptions {
+            memory: true,
+            dry_run: false,
+        };
+
+        let first = import_chats(&db, &chats, &options).unwrap();
+        assert_eq!(first.messages_inserted, 2);
+        assert_eq!(first.memories_inserted.len(), 1);
+
+        let second = import_chats(&db, &chats, &options).unwrap();
+        assert_eq!(second.messages_seen, 2);
+        assert_eq!(second.messages_inserted, 0);
+        assert!(second.memories_inserted.is_empty());
+
+        let chat_id = db
+            .resolve_or_create_chat_id("telegram", "42", None, "telegram_private")
+            .unwrap();
+        assert_eq!(db.get_all_messages(chat_id).unwrap().len(), 2);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,368 @@
+//! Admission queue for agent runs across chats.
+//!
+//! Every agent run takes a permit from the shared [`InboundQueue`] before it
+//! calls the LLM. At most `max_concurrent_agent_runs` permits are out at once;
+//! when none are free, waiting chats are served round-robin (one run per chat
+//! per turn), with control chats served before everyone else. A burst of
+//! messages in one busy group therefore cannot starve other chats.
+//!
+//! Ordering *within* a chat is still handled by the per-chat lock in
+//! `agent_engine`; this queue only decides which chat goes next.
+//!
+//! With `queue_backend: redis` an admitted run also takes one of the
+//! `max_concurrent_agent_runs` slots shared by all instances
+//! ([`SharedRunSlots`]), polling until one frees up. Fairness across
+//! instances is best-effort; if Redis fails the run goes ahead.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::sync::oneshot;
+
+use crate::coordination::SharedRunSlots;
+
+/// How often a run waiting for a shared slot asks Redis again
+const SHARED_SLOT_POLL: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InboundPriority {
+    /// Control chats (`control_chat_ids`)
+    High,
+    Normal,
+}
+
+impl InboundPriority {
+    fn index(self) -> usize {
+        match self {
+            InboundPriority::High => 0,
+            InboundPriority::Normal => 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InboundQueueStats {
+    pub running: usize,
+    pub waiting: usize,
+    /// 0 = unlimited
+    pub limit: usize,
+}
+
+#[derive(Default)]
+struct QueueState {
+    running: usize,
+    /// Waiters per chat, oldest first
+    waiting: HashMap<i64, VecDeque<oneshot::Sender<InboundPermit>>>,
+    /// Round-robin order of chats with waiters, one ring per priority
+    rings: [VecDeque<i64>; 2],
+}
+
+struct Shared {
+    limit: usize,
+    state: Mutex<QueueState>,
+    slots: Option<SharedRunSlots>,
+}
+
+#[derive(Clone)]
+pub struct InboundQueue {
+    shared: Arc<Shared>,
+}
+
+/// Held for the duration of an agent run; dropping it admits the next waiter.
+pub struct InboundPermit {
+    shared: Option<Arc<Shared>>,
+    /// ID of the shared run slot this run holds
+    slot_id: Option<String>,
+}
+
+impl InboundPermit {
+    fn new(shared: Option<Arc<Shared>>) -> Self {
+        InboundPermit {
+            shared,
+            slot_id: None,
+        }
+    }
+}
+
+impl Drop for InboundPermit {
+    fn drop(&mut self) {
+        if let (Some(shared), Some(slot_id)) = (&self.shared, self.slot_id.take()) {
+            // Without a runtime the slot simply expires
+            if let (Some(slots), Ok(handle)) =
+                (shared.slots.clone(), tokio::runtime::Handle::try_current())
+            {
+                handle.spawn(async move {
+                    if let Err(e) = slots.release(&slot_id).await {
+                        tracing::warn!("Failed to release shared run slot {slot_id}: {e}");
+                    }
+                });
+            }
+        }
+        if let Some(shared) = self.shared.take() {
+            let mut state = shared.state.lock().unwrap();
+            state.running = state.running.saturating_sub(1);
+            dispatch(&shared, &mut state);
+        }
+    }
+}
+
+/// Hand out permits to waiting chats while capacity remains.
+fn dispatch(shared: &Arc<Shared>, state: &mut QueueState) {
+    while shared.limit == 0 || state.running < shared.limit {
+        let Some((ring, chat_id)) = (0..state.rings.len())
+            .find_map(|ring| state.rings[ring].pop_front().map(|chat| (ring, chat)))
+        else {
+            return;
+        };
+        let Some(queue) = state.waiting.get_mut(&chat_id) else {
+            continue;
+        };
+        let waiter = queue.pop_front();
+        if queue.is_empty() {
+            state.waiting.remove(&chat_id);
+        } else {
+            // Back of the ring: other chats get a turn before this one again
+            state.rings[ring].push_back(chat_id);
+        }
+        let Some(waiter) = waiter else {
+            continue;
+        };
+
+        state.running += 1;
+        let permit = InboundPermit::new(Some(Arc::clone(shared)));
+        if let Err(mut unclaimed) = waiter.send(permit) {
+            // The waiting request was dropped; release without re-locking
+            unclaimed.shared = None;
+            state.running -= 1;
+        }
+    }
+}
+
+impl InboundQueue {
+    /// `limit` of 0 admits every run immediately.
+    pub fn new(limit: usize) -> Self {
+        Self::with_shared_slots(limit, None)
+    }
+
+    /// Like [`Self::new`], but with `slots` the limit also holds across
+    /// every instance using them.
+    pub fn with_shared_slots(limit: usize, slots: Option<SharedRunSlots>) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                limit,
+                state: Mutex::new(QueueState::default()),
+                slots,
+            }),
+        }
+    }
+
+    /// Wait for a run slot for `chat_id`.
+    pub async fn acquire(&self, chat_id: i64, priority: InboundPriority) -> InboundPermit {
+        let mut permit = self.acquire_local(chat_id, priority).await;
+        if let (Some(slots), true) = (&self.shared.slots, self.shared.limit > 0) {
+            permit.slot_id = acquire_shared_slot(slots, self.shared.limit, chat_id).await;
+        }
+        permit
+    }
+
+    async fn acquire_local(&self, chat_id: i64, priority: InboundPriority) -> InboundPermit {
+        let rx = {
+            let mut state = self.shared.state.lock().unwrap();
+            let nobody_waiting = state.rings.iter().all(VecDeque::is_empty);
+            if nobody_waiting && (self.shared.limit == 0 || state.running < self.shared.limit) {
+                state.running += 1;
+                return InboundPermit::new(Some(Arc::clone(&self.shared)));
+            }
+
+            let (tx, rx) = oneshot::channel();
+            let queue = state.waiting.entry(chat_id).or_default();
+            queue.push_back(tx);
+            if queue.len() == 1 {
+                state.rings[priority.index()].push_back(chat_id);
+            }
+            tracing::info!(
+                "Inbound queue full ({} running), chat {chat_id} waiting",
+                state.running
+            );
+            rx
+        };
+
+        match rx.await {
+            Ok(permit) => permit,
+            // Waiters are only dropped unsent if the queue itself goes away;
+            // fall back to an untracked permit rather than failing the run.
+            Err(_) => InboundPermit::new(None),
+        }
+    }
+
+    pub fn stats(&self) -> InboundQueueStats {
+        let state = self.shared.state.lock().unwrap();
+        InboundQueueStats {
+            running: state.running,
+            waiting: state.waiting.values().map(VecDeque::len).sum(),
+            limit: self.shared.limit,
+        }
+    }
+}
+
+/// Wait for one of the `limit` shared run slots. Returns the slot's ID, or
+/// `None` if Redis failed and the run goes ahead without one.
+async fn acquire_shared_slot(slots: &SharedRunSlots, limit: usize, chat_id: i64) -> Option<String> {
+    let slot_id = uuid::Uuid::new_v4().to_string();
+    let mut logged = false;
+    loop {
+        match slots.try_acquire(&slot_id, limit).await {
+            Ok(true) => return Some(slot_id),
+            Ok(false) => {
+                if !logged {
+                    tracing::info!("All shared run slots taken, chat {chat_id} waiting");
+                    logged = true;
+                }
+                tokio::time::sleep(SHARED_SLOT_POLL).await;
+            }
+            Err(e) => {
+                tracing::warn!("Shared run slots unavailable, running chat {chat_id} anyway: {e}");
+                return None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// Spawn an acquire that reports `label` once admitted and holds its
+    /// permit until told to release it.
+    fn spawn_waiter(
+        queue: &InboundQueue,
+        chat_id: i64,
+        priority: InboundPriority,
+        label: &'static str,
+        admitted: tokio::sync::mpsc::UnboundedSender<&'static str>,
+    ) -> oneshot::Sender<()> {
+        let (release_tx, release_rx) = oneshot::channel::<()>();
+        let queue = queue.clone();
+        tokio::spawn(async move {
+            let _permit = queue.acquire(chat_id, priority).await;
+            let _ = admitted.send(label);
+            let _ = release_rx.await;
+        });
+        release_tx
+    }
+
+    async fn settle() {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+
+    #[tokio::test]
+    async fn test_limit_caps_concurrent_permits() {
+        let queue = InboundQueue::new(2);
+        let a = queue.acquire(1, InboundPriority::Normal).await;
+        let _b = queue.acquire(2, InboundPriority::Normal).await;
+        assert_eq!(queue.stats().running, 2);
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let _release = spawn_waiter(&queue, 3, InboundPriority::Normal, "c", tx);
+        settle().await;
+        assert!(rx.try_recv().is_err());
+        assert_eq!(queue.stats().waiting, 1);
+
+        drop(a);
+        assert_eq!(rx.recv().await, Some("c"));
+        assert_eq!(queue.stats().running, 2);
+        assert_eq!(queue.stats().waiting, 0);
+    }
+
+    #[tokio::test]
+    async fn test_round_robin_across_chats() {
+        let queue = InboundQueue::new(1);
+        let first = queue.acquire(0, InboundPriority::Normal).await;
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+
+        // Chat 1 bursts two runs before chat 2 sends one
+        let mut releases = HashMap::new();
+        for (chat, label) in [(1, "a1"), (1, "a2"), (2, "b1")] {
+            let release = spawn_waiter(&queue, chat, InboundPriority::Normal, label, tx.clone());
+            releases.insert(label, release);
+            settle().await;
+        }
+
+        drop(first);
+        let mut order = Vec::new();
+        for _ in 0..3 {
+            let label = rx.recv().await.unwrap();
+            order.push(label);
+            releases.remove(label);
+        }
+        assert_eq!(order, vec!["a1", "b1", "a2"]);
+    }
+
+    #[tokio::test]
+    async fn test_control_chat_priority() {
+        let queue = InboundQueue::new(1);
+        let first = queue.acquire(0, InboundPriority::Normal).await;
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let _normal = spawn_waiter(&queue, 1, InboundPriority::Normal, "normal", tx.clone());
+        settle().await;
+        let _control = spawn_waiter(&queue, 2, InboundPriority::High, "control", tx);
+        settle().await;
+
+        drop(first);
+        assert_eq!(rx.recv().await, Some("control"));
+    }
+
+    #[tokio::test]
+    async fn test_abandoned_waiter_is_skipped() {
+        let queue = InboundQueue::new(1);
+        let first = queue.acquire(0, InboundPriority::Normal).await;
+
+        let abandoned = {
+            let queue = queue.clone();
+            tokio::spawn(async move {
+                let _permit = queue.acquire(1, InboundPriority::Normal).await;
+            })
+        };
+        settle().await;
+        abandoned.abort();
+        settle().await;
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let _release = spawn_waiter(&queue, 2, InboundPriority::Normal, "live", tx);
+        settle().await;
+
+        drop(first);
+        assert_eq!(rx.recv().await, Some("live"));
+        assert_eq!(queue.stats().running, 1);
+    }
+
+    #[tokio::test]
+    async fn test_unlimited_never_waits() {
+        let queue = InboundQueue::new(0);
+        let permits: Vec<_> = futures_util::future::join_all(
+            (0..10).map(|chat| queue.acquire(chat, InboundPriority::Normal)),
+        )
+        .await;
+        assert_eq!(queue.stats().running, 10);
+        drop(permits);
+        assert_eq!(queue.stats().running, 0);
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_shared_slots_admit_runs() {
+        let mut config: crate::config::Config = serde_yaml::from_str(
+            "telegram_bot_token: tok\nbot_username: bot\napi_key: key\ncoordination_redis_url: redis://127.0.0.1:1/0\nqueue_backend: redis\n",
+        )
+        .unwrap();
+        config.post_deserialize().unwrap();
+        let queue = InboundQueue::with_shared_slots(1, SharedRunSlots::from_config(&config));
+        assert!(queue.shared.slots.is_some());
+
+        let permit = queue.acquire(1, InboundPriority::Normal).await;
+        assert!(permit.slot_id.is_none());
+        assert_eq!(queue.stats().running, 1);
+    }
+}
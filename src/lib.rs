@@ -1,24 +1,50 @@
 pub mod acp;
 pub mod agent_engine;
+pub mod analytics;
+pub mod approvals;
+pub mod aws_profile;
+pub mod browsing_profiles;
 pub mod builtin_skills;
+pub mod calendar;
 pub mod channel;
 pub mod channel_adapter;
 pub mod channels;
+pub mod chart;
 pub mod codex_auth;
+pub mod commands;
 pub mod config;
+pub mod coordination;
 pub mod db;
+pub mod digest_dedup;
 pub mod doctor;
+pub mod email_gateway;
 pub mod embedding;
 pub mod error;
 pub mod gateway;
 pub mod image_utils;
+pub mod import;
+pub mod inbound_queue;
 pub mod llm;
 pub mod llm_bedrock;
+pub mod llm_gemini;
+pub mod llm_ollama;
+pub mod llm_openrouter;
+pub mod llm_trace;
 pub mod llm_types;
 pub mod logging;
 pub mod mcp;
 pub mod memory;
 pub mod memory_quality;
+pub mod model_limits;
+pub mod notifications;
+pub mod outage;
+pub mod outbound_http;
+pub mod platform;
+pub mod politeness;
+pub mod preflight;
+pub mod prompt_template;
+pub mod redact;
+pub mod response_cache;
 pub mod runtime;
 pub mod scheduler;
 pub mod sdk;
@@ -27,10 +53,13 @@ pub mod skills;
 pub(crate) mod text;
 pub mod tools;
 pub mod transcribe;
+pub mod tts;
 pub mod update;
+pub mod url_monitor;
 pub mod usage;
 #[cfg(feature = "web")]
 pub mod web;
+pub mod workspace;
 #[cfg(feature = "discord")]
 pub use channels::discord;
 #[cfg(feature = "telegram")]
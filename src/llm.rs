@@ -6,19 +6,21 @@ use tokio::sync::mpsc::UnboundedSender;
 use tracing::warn;
 
 use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use crate::codex_auth::{
     codex_config_default_openai_base_url, is_openai_codex_provider,
     refresh_openai_codex_auth_if_needed, resolve_openai_codex_auth,
 };
-use crate::config::Config;
 #[cfg(test)]
 use crate::config::WorkingDirIsolation;
+use crate::config::{Config, InferenceParams};
 use crate::error::RayClawError;
 use crate::llm_types::{
     ContentBlock, ImageSource, Message, MessageContent, MessagesResponse, ResponseContentBlock,
     ToolDefinition, Usage,
 };
+use crate::model_limits::ModelLimits;
 
 /// Convert a `MessageContent` into a `Vec<ContentBlock>`, wrapping plain text
 /// in a single `Text` block.
@@ -122,14 +124,59 @@ pub(crate) fn sanitize_messages(messages: Vec<Message>) -> Vec<Message> {
     merged
 }
 
+/// Drop thinking blocks from history, for requests made without extended
+/// thinking (or to providers that don't take them back). Messages left
+/// empty are removed.
+pub(crate) fn strip_thinking_blocks(messages: Vec<Message>) -> Vec<Message> {
+    messages
+        .into_iter()
+        .filter_map(|msg| match msg.content {
+            MessageContent::Blocks(blocks) => {
+                let blocks: Vec<ContentBlock> = blocks
+                    .into_iter()
+                    .filter(|b| {
+                        !matches!(
+                            b,
+                            ContentBlock::Thinking { .. } | ContentBlock::RedactedThinking { .. }
+                        )
+                    })
+                    .collect();
+                (!blocks.is_empty()).then_some(Message {
+                    role: msg.role,
+                    content: MessageContent::Blocks(blocks),
+                })
+            }
+            other => Some(Message {
+                role: msg.role,
+                content: other,
+            }),
+        })
+        .collect()
+}
+
+/// Drop Gemini thought signatures from tool calls in history, for the
+/// Anthropic API, which rejects unknown fields on `tool_use` blocks.
+fn strip_tool_use_signatures(mut messages: Vec<Message>) -> Vec<Message> {
+    for msg in &mut messages {
+        if let MessageContent::Blocks(blocks) = &mut msg.content {
+            for block in blocks {
+                if let ContentBlock::ToolUse { signature, .. } = block {
+                    *signature = None;
+                }
+            }
+        }
+    }
+    messages
+}
+
 #[derive(Default)]
-struct SseEventParser {
+pub(crate) struct SseEventParser {
     pending: String,
     data_lines: Vec<String>,
 }
 
 impl SseEventParser {
-    fn push_chunk(&mut self, chunk: &str) -> Vec<String> {
+    pub(crate) fn push_chunk(&mut self, chunk: &str) -> Vec<String> {
         self.pending.push_str(chunk);
         let mut events = Vec::new();
 
@@ -147,7 +194,7 @@ impl SseEventParser {
         events
     }
 
-    fn finish(&mut self) -> Vec<String> {
+    pub(crate) fn finish(&mut self) -> Vec<String> {
         let mut events = Vec::new();
         if !self.pending.is_empty() {
             let mut line = std::mem::take(&mut self.pending);
@@ -200,8 +247,175 @@ impl SseEventParser {
 // Provider trait
 // ---------------------------------------------------------------------------
 
+/// What a provider can accept for the configured model. The agent engine
+/// consults this to degrade gracefully (e.g. drop images with a notice)
+/// instead of sending content the API rejects with an opaque 400.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProviderCapabilities {
+    /// Image content blocks in user messages
+    pub vision: bool,
+    /// Document (PDF) content blocks in user messages
+    pub documents: bool,
+    /// Tool definitions and tool_use / tool_result turns
+    pub tools: bool,
+    /// Text arrives incrementally from `send_message_stream`
+    pub streaming: bool,
+    /// Requests carry prompt-cache markers
+    pub prompt_caching: bool,
+    /// Context window in tokens, when known
+    pub max_context_tokens: Option<u32>,
+    /// Output tokens requested per response, when known
+    pub max_output_tokens: Option<u32>,
+}
+
+impl ProviderCapabilities {
+    /// Fill in the context window and output cap from a model's limits.
+    /// Output is capped by `max_tokens`, the configured request size.
+    pub fn with_limits(self, limits: ModelLimits, max_tokens: u32) -> Self {
+        ProviderCapabilities {
+            max_context_tokens: limits.context_window,
+            max_output_tokens: Some(
+                limits
+                    .max_output_tokens
+                    .map_or(max_tokens, |cap| cap.min(max_tokens)),
+            ),
+            ..self
+        }
+    }
+}
+
+impl Default for ProviderCapabilities {
+    fn default() -> Self {
+        ProviderCapabilities {
+            vision: true,
+            documents: false,
+            tools: true,
+            streaming: true,
+            prompt_caching: false,
+            max_context_tokens: None,
+            max_output_tokens: None,
+        }
+    }
+}
+
+/// Substrings of text-only model names served over OpenAI-compatible APIs.
+const TEXT_ONLY_MODEL_MARKERS: &[&str] = &[
+    "deepseek",
+    "o1-mini",
+    "o3-mini",
+    "gpt-3.5",
+    "codestral",
+    "qwq",
+];
+
+/// Whether an OpenAI-compatible model accepts image input. Unknown models
+/// are assumed to, so only known text-only models are degraded.
+fn openai_model_supports_vision(model: &str) -> bool {
+    let model = model.to_lowercase();
+    !TEXT_ONLY_MODEL_MARKERS
+        .iter()
+        .any(|marker| model.contains(marker))
+}
+
+/// Tokens charged per image regardless of its encoded size (a ~1.15
+/// megapixel image on Anthropic's scale).
+const IMAGE_TOKEN_ESTIMATE: usize = 1_600;
+/// Tokens charged per document; a PDF costs roughly 2k per page (text plus
+/// the page image), so this assumes a handful of pages.
+const DOCUMENT_TOKEN_ESTIMATE: usize = 10_000;
+/// Per-message overhead for role markers and block framing.
+const MESSAGE_TOKEN_OVERHEAD: usize = 4;
+
+/// Offline token estimate for `text`, close to what BPE tokenizers
+/// (tiktoken, Claude's) produce: about 4 bytes of ASCII per token, and a
+/// token per non-ASCII character (CJK, emoji).
+pub fn estimate_text_tokens(text: &str) -> usize {
+    let (ascii, other) = text.chars().fold((0usize, 0usize), |(ascii, other), c| {
+        if c.is_ascii() {
+            (ascii + 1, other)
+        } else {
+            (ascii, other + 1)
+        }
+    });
+    ascii.div_ceil(4) + other
+}
+
+/// Offline token estimate for a whole request. Images and documents count
+/// a flat [`IMAGE_TOKEN_ESTIMATE`] / [`DOCUMENT_TOKEN_ESTIMATE`] rather than
+/// their base64 size.
+pub fn estimate_request_tokens(
+    system: &str,
+    messages: &[Message],
+    tools: Option<&[ToolDefinition]>,
+) -> usize {
+    let block_tokens = |block: &ContentBlock| match block {
+        ContentBlock::Text { text } => estimate_text_tokens(text),
+        ContentBlock::Image { .. } => IMAGE_TOKEN_ESTIMATE,
+        ContentBlock::Document { .. } => DOCUMENT_TOKEN_ESTIMATE,
+        ContentBlock::ToolUse { name, input, .. } => {
+            estimate_text_tokens(name) + estimate_text_tokens(&input.to_string())
+        }
+        ContentBlock::ToolResult { content, .. } => estimate_text_tokens(content),
+        ContentBlock::Thinking { thinking, .. } => estimate_text_tokens(thinking),
+        ContentBlock::RedactedThinking { data } => estimate_text_tokens(data),
+    };
+    let messages: usize = messages
+        .iter()
+        .map(|m| {
+            MESSAGE_TOKEN_OVERHEAD
+                + match &m.content {
+                    MessageContent::Text(text) => estimate_text_tokens(text),
+                    MessageContent::Blocks(blocks) => blocks.iter().map(block_tokens).sum(),
+                }
+        })
+        .sum();
+    let tools: usize = tools
+        .unwrap_or_default()
+        .iter()
+        .map(|t| {
+            estimate_text_tokens(&t.name)
+                + estimate_text_tokens(&t.description)
+                + estimate_text_tokens(&t.input_schema.to_string())
+        })
+        .sum();
+    estimate_text_tokens(system) + messages + tools
+}
+
 #[async_trait]
 pub trait LlmProvider: Send + Sync {
+    /// Capabilities of this provider for the configured model.
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities::default()
+    }
+
+    /// The URL requests are sent to, probed by the startup preflight.
+    fn endpoint(&self) -> Option<String> {
+        None
+    }
+
+    /// Input tokens the request would use. Providers without a counting
+    /// endpoint estimate offline; see [`estimate_request_tokens`].
+    async fn count_tokens(
+        &self,
+        system: &str,
+        messages: &[Message],
+        tools: Option<&[ToolDefinition]>,
+    ) -> Result<usize, RayClawError> {
+        Ok(estimate_request_tokens(system, messages, tools))
+    }
+
+    /// The provider-native request body a send would post for these inputs,
+    /// for the debug recorder (see `llm_trace`). `None` if not available.
+    fn request_body(
+        &self,
+        _system: &str,
+        _messages: &[Message],
+        _tools: Option<&[ToolDefinition]>,
+        _stream: bool,
+    ) -> Option<serde_json::Value> {
+        None
+    }
+
     async fn send_message(
         &self,
         system: &str,
@@ -229,13 +443,205 @@ pub trait LlmProvider: Send + Sync {
 }
 
 pub fn create_provider(config: &Config) -> Box<dyn LlmProvider> {
-    match config.llm_provider.trim().to_lowercase().as_str() {
+    try_create_provider(config).expect("Failed to initialize Bedrock provider")
+}
+
+/// Like `create_provider`, but reports setup failures (e.g. missing AWS
+/// credentials) instead of panicking; used for per-chat overrides.
+pub fn try_create_provider(config: &Config) -> Result<Box<dyn LlmProvider>, RayClawError> {
+    let provider: Box<dyn LlmProvider> = match config.llm_provider.trim().to_lowercase().as_str() {
         "anthropic" => Box::new(AnthropicProvider::new(config)),
-        "bedrock" => Box::new(
-            crate::llm_bedrock::BedrockProvider::new(config)
-                .expect("Failed to initialize Bedrock provider"),
-        ),
+        "bedrock" => Box::new(crate::llm_bedrock::BedrockProvider::new(config)?),
+        "gemini" => Box::new(crate::llm_gemini::GeminiProvider::new(config)),
+        "ollama" => Box::new(crate::llm_ollama::OllamaProvider::new(config)),
+        "openrouter" => Box::new(crate::llm_openrouter::OpenRouterProvider::new(config)),
         _ => Box::new(OpenAiProvider::new(config)),
+    };
+    if config.llm_trace {
+        return Ok(Box::new(crate::llm_trace::TracedProvider::new(
+            provider, config,
+        )));
+    }
+    Ok(provider)
+}
+
+// ---------------------------------------------------------------------------
+// Retries shared by every provider
+// ---------------------------------------------------------------------------
+
+/// How provider requests are retried; see `llm_max_retries`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    /// Longest single wait; a longer `Retry-After` ends retrying
+    pub max_delay: std::time::Duration,
+}
+
+impl RetryPolicy {
+    pub fn from_config(config: &Config) -> Self {
+        RetryPolicy {
+            max_retries: config.llm_max_retries,
+            max_delay: std::time::Duration::from_secs(config.llm_retry_max_delay_secs),
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 3,
+            max_delay: std::time::Duration::from_secs(60),
+        }
+    }
+}
+
+/// Rate limited (429) or overloaded (5xx, Anthropic's 529).
+fn is_retryable_status(status: reqwest::StatusCode) -> bool {
+    matches!(status.as_u16(), 429 | 500 | 502 | 503 | 529)
+}
+
+/// The request never got a response: refused, reset or timed out.
+fn is_transient_error(e: &reqwest::Error) -> bool {
+    e.is_connect() || e.is_timeout() || (e.is_request() && !e.is_builder())
+}
+
+/// Whether `e` means the provider is unreachable or overloaded, as opposed to
+/// rejecting this particular request. Checked after retries have run out.
+pub fn is_provider_unavailable(e: &RayClawError) -> bool {
+    match e {
+        RayClawError::RateLimited | RayClawError::ProviderUnavailable(_) => true,
+        RayClawError::Http(e) => is_transient_error(e),
+        RayClawError::LlmApi(message) => {
+            // Anthropic error types, then "... HTTP 503 ..." from every provider
+            ["overloaded_error", "rate_limit_error", "api_error:"]
+                .iter()
+                .any(|kind| message.starts_with(kind))
+                || message
+                    .split("HTTP ")
+                    .skip(1)
+                    .filter_map(|rest| rest.get(..3)?.parse::<u16>().ok())
+                    .filter_map(|code| reqwest::StatusCode::from_u16(code).ok())
+                    .any(is_retryable_status)
+        }
+        _ => false,
+    }
+}
+
+/// Phrases providers use when a request doesn't fit the model's context
+/// window (Anthropic, OpenAI and compatibles, Bedrock, Gemini, Ollama).
+const CONTEXT_LENGTH_MARKERS: &[&str] = &[
+    "prompt is too long",
+    "context_length_exceeded",
+    "maximum context length",
+    "context window",
+    "input is too long",
+    "too many input tokens",
+    "input token count",
+    "exceeds the context",
+];
+
+/// Whether `e` is the provider rejecting a request for not fitting the
+/// model's context window.
+pub fn is_context_length_error(e: &RayClawError) -> bool {
+    let RayClawError::LlmApi(message) = e else {
+        return false;
+    };
+    let message = message.to_lowercase();
+    CONTEXT_LENGTH_MARKERS
+        .iter()
+        .any(|marker| message.contains(marker))
+}
+
+/// Wait asked for by `retry-after-ms` (OpenAI, Anthropic) or `Retry-After`
+/// (seconds or an HTTP date).
+fn parse_retry_after(
+    headers: &reqwest::header::HeaderMap,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Option<std::time::Duration> {
+    let header = |name: &str| headers.get(name)?.to_str().ok().map(str::trim);
+    if let Some(ms) = header("retry-after-ms").and_then(|v| v.parse::<f64>().ok()) {
+        if ms.is_finite() && ms >= 0.0 {
+            return Some(std::time::Duration::from_secs_f64(ms / 1000.0));
+        }
+    }
+    let value = header("retry-after")?;
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(std::time::Duration::from_secs(secs));
+    }
+    let at = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        (at.with_timezone(&chrono::Utc) - now)
+            .to_std()
+            .unwrap_or_default(),
+    )
+}
+
+/// Wait before retry `attempt` (1-based), given `jitter` in [0, 1). A
+/// server-requested wait is kept and stretched by up to 20%; otherwise the
+/// backoff is 2^attempt seconds, capped at `max_delay` and randomized down to
+/// half. `None` when the server asks for longer than `max_delay`.
+fn retry_delay(
+    policy: RetryPolicy,
+    attempt: u32,
+    retry_after: Option<std::time::Duration>,
+    jitter: f64,
+) -> Option<std::time::Duration> {
+    match retry_after {
+        Some(wait) if wait > policy.max_delay => None,
+        Some(wait) => Some(wait.mul_f64(1.0 + 0.2 * jitter)),
+        None => {
+            let backoff =
+                std::time::Duration::from_secs(1u64 << attempt.min(16)).min(policy.max_delay);
+            Some(backoff.mul_f64(0.5 + 0.5 * jitter))
+        }
+    }
+}
+
+fn jitter() -> f64 {
+    (uuid::Uuid::new_v4().as_u128() % 1_000_000) as f64 / 1_000_000.0
+}
+
+/// Send the request from `build` (called again for every attempt, so
+/// signatures stay fresh), retrying per `policy`. Returns the first response
+/// that is not retryable, or the last one when retries run out; callers
+/// handle error statuses as before. For streaming requests only opening the
+/// stream is retried.
+pub(crate) async fn send_with_retry<F>(
+    policy: RetryPolicy,
+    label: &str,
+    mut build: F,
+) -> Result<reqwest::Response, RayClawError>
+where
+    F: FnMut() -> Result<reqwest::RequestBuilder, RayClawError>,
+{
+    let mut attempt = 0u32;
+    loop {
+        let result = build()?.send().await;
+        let (reason, retry_after) = match &result {
+            Ok(response) if is_retryable_status(response.status()) => (
+                format!("HTTP {}", response.status()),
+                parse_retry_after(response.headers(), chrono::Utc::now()),
+            ),
+            Err(e) if is_transient_error(e) => (e.to_string(), None),
+            _ => return Ok(result?),
+        };
+        if attempt >= policy.max_retries {
+            return Ok(result?);
+        }
+        attempt += 1;
+        let Some(delay) = retry_delay(policy, attempt, retry_after, jitter()) else {
+            warn!(
+                "{label}: {reason}, server asks to retry after {:?}, longer than llm_retry_max_delay_secs",
+                retry_after.unwrap_or_default()
+            );
+            return Ok(result?);
+        };
+        warn!(
+            "{label}: {reason}, retrying in {delay:?} (attempt {attempt}/{})",
+            policy.max_retries
+        );
+        drop(result);
+        tokio::time::sleep(delay).await;
     }
 }
 
@@ -250,17 +656,29 @@ pub struct AnthropicProvider {
     max_tokens: u32,
     base_url: String,
     prompt_cache_ttl: String,
+    limits: ModelLimits,
+    /// Extended thinking budget, when enabled
+    thinking_budget: Option<u32>,
+    inference: InferenceParams,
+    retry: RetryPolicy,
 }
 
 impl AnthropicProvider {
     pub fn new(config: &Config) -> Self {
         AnthropicProvider {
+            retry: RetryPolicy::from_config(config),
             http: reqwest::Client::new(),
             api_key: config.api_key.clone(),
             model: config.model.clone(),
             max_tokens: config.max_tokens,
             base_url: resolve_anthropic_messages_url(config.llm_base_url.as_deref().unwrap_or("")),
             prompt_cache_ttl: config.prompt_cache_ttl.clone(),
+            limits: config.model_limits_for(&config.model).unwrap_or_default(),
+            thinking_budget: config
+                .thinking
+                .enabled
+                .then_some(config.thinking.budget_tokens),
+            inference: config.inference.clone(),
         }
     }
 
@@ -316,6 +734,27 @@ impl AnthropicProvider {
             }
         }
 
+        if let Some(budget) = self.thinking_budget {
+            body["thinking"] = json!({"type": "enabled", "budget_tokens": budget});
+        }
+
+        let params = &self.inference;
+        // Extended thinking doesn't allow changing temperature or top_k
+        if self.thinking_budget.is_none() {
+            if let Some(temperature) = params.temperature {
+                body["temperature"] = json!(temperature);
+            }
+            if let Some(top_k) = params.top_k {
+                body["top_k"] = json!(top_k);
+            }
+        }
+        if let Some(top_p) = params.top_p {
+            body["top_p"] = json!(top_p);
+        }
+        if !params.stop_sequences.is_empty() {
+            body["stop_sequences"] = json!(params.stop_sequences);
+        }
+
         if let Some(s) = stream {
             body["stream"] = json!(s);
         }
@@ -323,28 +762,48 @@ impl AnthropicProvider {
         body
     }
 
-    async fn send_message_stream_single_pass(
-        &self,
-        system: &str,
-        messages: &[Message],
-        tools: Option<&[ToolDefinition]>,
-        text_tx: Option<&UnboundedSender<String>>,
-    ) -> Result<MessagesResponse, RayClawError> {
-        let body = self.build_request_body(system, messages, tools, Some(true));
+    fn request(&self) -> reqwest::RequestBuilder {
+        self.request_to(&self.base_url)
+    }
 
-        let mut req = self
+    fn request_to(&self, url: &str) -> reqwest::RequestBuilder {
+        let req = self
             .http
-            .post(&self.base_url)
+            .post(url)
             .header("x-api-key", &self.api_key)
             .header("anthropic-version", "2023-06-01")
             .header("content-type", "application/json");
 
         // Add prompt caching beta header if enabled
         if self.prompt_cache_ttl != "none" {
-            req = req.header("anthropic-beta", "prompt-caching-2024-07-31");
+            req.header("anthropic-beta", "prompt-caching-2024-07-31")
+        } else {
+            req
         }
+    }
 
-        let response = req.json(&body).send().await?;
+    /// Sanitize history for a request. Thinking blocks from earlier turns
+    /// are only sent back while thinking is enabled.
+    fn prepare_messages(&self, messages: Vec<Message>) -> Vec<Message> {
+        let messages = strip_tool_use_signatures(messages);
+        if self.thinking_budget.is_some() {
+            sanitize_messages(messages)
+        } else {
+            sanitize_messages(strip_thinking_blocks(messages))
+        }
+    }
+
+    async fn send_message_stream_single_pass(
+        &self,
+        system: &str,
+        messages: &[Message],
+        tools: Option<&[ToolDefinition]>,
+        text_tx: Option<&UnboundedSender<String>>,
+    ) -> Result<MessagesResponse, RayClawError> {
+        let body = self.build_request_body(system, messages, tools, Some(true));
+
+        let response =
+            send_with_retry(self.retry, "Anthropic", || Ok(self.request().json(&body))).await?;
 
         let status = response.status();
         if !status.is_success() {
@@ -366,6 +825,8 @@ impl AnthropicProvider {
             std::collections::HashMap::new();
         let mut tool_blocks: std::collections::HashMap<usize, StreamToolUseBlock> =
             std::collections::HashMap::new();
+        let mut thinking_blocks: std::collections::HashMap<usize, ResponseContentBlock> =
+            std::collections::HashMap::new();
         let mut ordered_indexes: Vec<usize> = Vec::new();
 
         'outer: while let Some(chunk_res) = byte_stream.next().await {
@@ -384,6 +845,7 @@ impl AnthropicProvider {
                     &mut usage,
                     &mut text_blocks,
                     &mut tool_blocks,
+                    &mut thinking_blocks,
                     &mut ordered_indexes,
                 );
             }
@@ -399,6 +861,7 @@ impl AnthropicProvider {
                 &mut usage,
                 &mut text_blocks,
                 &mut tool_blocks,
+                &mut thinking_blocks,
                 &mut ordered_indexes,
             );
         }
@@ -407,6 +870,7 @@ impl AnthropicProvider {
             ordered_indexes,
             text_blocks,
             tool_blocks,
+            thinking_blocks,
             stop_reason,
             usage,
         ))
@@ -438,12 +902,20 @@ fn usage_from_json(v: &serde_json::Value) -> Option<Usage> {
         .and_then(|n| n.as_u64())
         .or_else(|| v.get("completion_tokens").and_then(|n| n.as_u64()))
         .unwrap_or(0);
+    let count = |key: &str| {
+        v.get(key)
+            .and_then(|n| n.as_u64())
+            .map_or(0, |n| u32::try_from(n).unwrap_or(u32::MAX))
+    };
     Some(Usage {
         input_tokens: u32::try_from(input).unwrap_or(u32::MAX),
         output_tokens: u32::try_from(output).unwrap_or(u32::MAX),
+        cache_creation_input_tokens: count("cache_creation_input_tokens"),
+        cache_read_input_tokens: count("cache_read_input_tokens"),
     })
 }
 
+#[allow(clippy::too_many_arguments)]
 fn process_anthropic_stream_event(
     data: &str,
     text_tx: Option<&UnboundedSender<String>>,
@@ -451,6 +923,7 @@ fn process_anthropic_stream_event(
     usage: &mut Option<Usage>,
     text_blocks: &mut std::collections::HashMap<usize, String>,
     tool_blocks: &mut std::collections::HashMap<usize, StreamToolUseBlock>,
+    thinking_blocks: &mut std::collections::HashMap<usize, ResponseContentBlock>,
     ordered_indexes: &mut Vec<usize>,
 ) {
     let Ok(v) = serde_json::from_str::<serde_json::Value>(data) else {
@@ -506,6 +979,24 @@ fn process_anthropic_stream_event(
                                 },
                             );
                         }
+                        Some("thinking") => {
+                            thinking_blocks.insert(
+                                index,
+                                ResponseContentBlock::Thinking {
+                                    thinking: String::new(),
+                                    signature: None,
+                                },
+                            );
+                        }
+                        Some("redacted_thinking") => {
+                            let data = block
+                                .get("data")
+                                .and_then(|d| d.as_str())
+                                .unwrap_or_default()
+                                .to_string();
+                            thinking_blocks
+                                .insert(index, ResponseContentBlock::RedactedThinking { data });
+                        }
                         _ => {}
                     }
                 }
@@ -552,6 +1043,25 @@ fn process_anthropic_stream_event(
                             .push_str(piece);
                     }
                 }
+                Some("thinking_delta") => {
+                    let piece = delta
+                        .get("thinking")
+                        .and_then(|t| t.as_str())
+                        .unwrap_or_default();
+                    if let Some(ResponseContentBlock::Thinking { thinking, .. }) =
+                        thinking_blocks.get_mut(&index)
+                    {
+                        thinking.push_str(piece);
+                    }
+                }
+                Some("signature_delta") => {
+                    let piece = delta.get("signature").and_then(|t| t.as_str());
+                    if let Some(ResponseContentBlock::Thinking { signature, .. }) =
+                        thinking_blocks.get_mut(&index)
+                    {
+                        *signature = piece.map(str::to_string);
+                    }
+                }
                 _ => {}
             }
         }
@@ -589,81 +1099,200 @@ fn process_anthropic_stream_event(
     }
 }
 
-fn process_openai_stream_event(
-    data: &str,
-    text_tx: Option<&UnboundedSender<String>>,
-    text: &mut String,
-    stop_reason: &mut Option<String>,
-    usage: &mut Option<Usage>,
-    tool_calls: &mut std::collections::BTreeMap<usize, StreamToolUseBlock>,
-) {
-    let Ok(v) = serde_json::from_str::<serde_json::Value>(data) else {
-        return;
-    };
+/// A chat completions stream assembled from its SSE chunks.
+#[derive(Default)]
+struct OaiStreamState {
+    text: String,
+    reasoning: String,
+    finish_reason: Option<String>,
+    usage: Option<Usage>,
+    /// Tool calls by slot, in the order they started
+    tool_calls: std::collections::BTreeMap<usize, StreamToolUseBlock>,
+    /// Slot for each wire `index`. A new call id on an index already in use
+    /// (servers that send each call whole at index 0) opens a new slot.
+    tool_slots: std::collections::HashMap<usize, usize>,
+}
 
-    if usage.is_none() {
-        *usage = v.get("usage").and_then(usage_from_json);
-    }
+impl OaiStreamState {
+    /// Apply one `data:` payload, forwarding text deltas to `text_tx`. An
+    /// error reported inside the stream comes back as its raw payload.
+    fn push(
+        &mut self,
+        data: &str,
+        text_tx: Option<&UnboundedSender<String>>,
+    ) -> Result<(), String> {
+        let Ok(v) = serde_json::from_str::<serde_json::Value>(data) else {
+            return Ok(());
+        };
+        if v.get("error").is_some() && v.get("choices").is_none() {
+            return Err(data.to_string());
+        }
 
-    let Some(choice) = v
-        .get("choices")
-        .and_then(|c| c.as_array())
-        .and_then(|arr| arr.first())
-    else {
-        return;
-    };
+        // Sent with the last chunk (or a final one without choices);
+        // `null` on the others
+        if let Some(u) = v
+            .get("usage")
+            .and_then(|u| serde_json::from_value::<OaiUsage>(u.clone()).ok())
+        {
+            self.usage = Some(oai_usage(u));
+        }
+
+        let Some(choice) = v
+            .get("choices")
+            .and_then(|c| c.as_array())
+            .and_then(|arr| arr.first())
+        else {
+            return Ok(());
+        };
+
+        if let Some(reason) = choice.get("finish_reason").and_then(|r| r.as_str()) {
+            self.finish_reason = Some(reason.to_string());
+        }
+
+        let Some(delta) = choice.get("delta") else {
+            return Ok(());
+        };
 
-    if let Some(reason) = choice.get("finish_reason").and_then(|r| r.as_str()) {
-        *stop_reason = Some(reason.to_string());
+        if let Some(piece) = delta
+            .get("reasoning_content")
+            .or_else(|| delta.get("reasoning"))
+            .and_then(|t| t.as_str())
+        {
+            self.reasoning.push_str(piece);
+        }
+
+        if let Some(piece) = delta.get("content").and_then(|t| t.as_str()) {
+            if !piece.is_empty() {
+                self.text.push_str(piece);
+                if let Some(tx) = text_tx {
+                    let _ = tx.send(piece.to_string());
+                }
+            }
+        }
+
+        if let Some(tc_arr) = delta.get("tool_calls").and_then(|v| v.as_array()) {
+            for (position, tc) in tc_arr.iter().enumerate() {
+                self.push_tool_call_delta(position, tc);
+            }
+        }
+        Ok(())
     }
 
-    let Some(delta) = choice.get("delta") else {
-        return;
-    };
+    fn push_tool_call_delta(&mut self, position: usize, tc: &serde_json::Value) {
+        // Some servers leave out `index`; fall back to the position in the delta
+        let index = tc
+            .get("index")
+            .and_then(|i| i.as_u64())
+            .and_then(|i| usize::try_from(i).ok())
+            .unwrap_or(position);
+        let id = tc
+            .get("id")
+            .and_then(|v| v.as_str())
+            .filter(|id| !id.is_empty());
+        let next_slot = self.tool_calls.len();
+        let mut slot = *self.tool_slots.entry(index).or_insert(next_slot);
+        if let (Some(id), Some(existing)) = (id, self.tool_calls.get(&slot)) {
+            if !existing.id.is_empty() && existing.id != id {
+                slot = next_slot;
+                self.tool_slots.insert(index, slot);
+            }
+        }
 
-    if let Some(piece) = delta.get("content").and_then(|t| t.as_str()) {
-        if !piece.is_empty() {
-            text.push_str(piece);
-            if let Some(tx) = text_tx {
-                let _ = tx.send(piece.to_string());
+        let entry = self.tool_calls.entry(slot).or_default();
+        if let Some(id) = id {
+            entry.id = id.to_string();
+        }
+        if let Some(function) = tc.get("function") {
+            if let Some(name) = function
+                .get("name")
+                .and_then(|v| v.as_str())
+                .filter(|n| !n.is_empty())
+            {
+                entry.name = name.to_string();
+            }
+            match function.get("arguments") {
+                Some(serde_json::Value::String(args)) => entry.input_json.push_str(args),
+                // Whole arguments as an object rather than a JSON string
+                Some(args @ serde_json::Value::Object(_)) => entry.input_json = args.to_string(),
+                _ => {}
             }
         }
     }
 
-    if let Some(tc_arr) = delta.get("tool_calls").and_then(|v| v.as_array()) {
-        for tc in tc_arr {
-            let Some(index) = tc
-                .get("index")
-                .and_then(|i| i.as_u64())
-                .and_then(|i| usize::try_from(i).ok())
-            else {
+    fn into_response(self) -> MessagesResponse {
+        let mut content = Vec::new();
+        if !self.reasoning.is_empty() {
+            content.push(ResponseContentBlock::Thinking {
+                thinking: self.reasoning,
+                signature: None,
+            });
+        }
+        if !self.text.is_empty() {
+            content.push(ResponseContentBlock::Text { text: self.text });
+        }
+        let mut has_tool_calls = false;
+        for tool in self.tool_calls.into_values() {
+            if tool.name.is_empty() {
                 continue;
-            };
-            let entry = tool_calls.entry(index).or_default();
-            if let Some(id) = tc.get("id").and_then(|v| v.as_str()) {
-                entry.id = id.to_string();
-            }
-            if let Some(function) = tc.get("function") {
-                if let Some(name) = function.get("name").and_then(|v| v.as_str()) {
-                    entry.name = name.to_string();
-                }
-                if let Some(args) = function.get("arguments").and_then(|v| v.as_str()) {
-                    entry.input_json.push_str(args);
-                }
             }
+            has_tool_calls = true;
+            let id = if tool.id.is_empty() {
+                format!("call_{}", uuid::Uuid::new_v4().simple())
+            } else {
+                tool.id
+            };
+            content.push(ResponseContentBlock::ToolUse {
+                id,
+                name: tool.name,
+                input: parse_tool_input(&tool.input_json),
+                signature: None,
+            });
+        }
+        if content.is_empty() {
+            content.push(ResponseContentBlock::Text {
+                text: String::new(),
+            });
+        }
+
+        MessagesResponse {
+            content,
+            stop_reason: oai_stop_reason(self.finish_reason, has_tool_calls),
+            usage: self.usage,
         }
     }
 }
 
 pub(crate) fn normalize_stop_reason(reason: Option<String>) -> Option<String> {
     match reason.as_deref() {
-        Some("tool_use") | Some("tool_calls") => Some("tool_use".into()),
+        Some("tool_use") | Some("tool_calls") | Some("function_call") => Some("tool_use".into()),
         Some("max_tokens") | Some("length") => Some("max_tokens".into()),
         Some("stop") | Some("end_turn") | None => Some("end_turn".into()),
         Some(other) => Some(other.to_string()),
     }
 }
 
+/// Stop reason for an OpenAI-compatible `finish_reason`. Some servers
+/// (llama.cpp, older vLLM tool parsers) end a turn that calls tools with
+/// "stop"; those calls still have to run.
+fn oai_stop_reason(finish_reason: Option<String>, has_tool_calls: bool) -> Option<String> {
+    let reason = normalize_stop_reason(finish_reason);
+    match reason.as_deref() {
+        Some("end_turn") if has_tool_calls => Some("tool_use".into()),
+        _ => reason,
+    }
+}
+
+/// `prompt_tokens` includes the cached part.
+fn oai_usage(u: OaiUsage) -> Usage {
+    let cached = u.prompt_tokens_details.map_or(0, |d| d.cached_tokens);
+    Usage {
+        input_tokens: u.prompt_tokens.saturating_sub(cached),
+        output_tokens: u.completion_tokens,
+        cache_read_input_tokens: cached,
+        ..Usage::default()
+    }
+}
+
 pub(crate) fn parse_tool_input(input_json: &str) -> serde_json::Value {
     let trimmed = input_json.trim();
     if trimmed.is_empty() {
@@ -676,11 +1305,15 @@ fn build_stream_response(
     ordered_indexes: Vec<usize>,
     text_blocks: std::collections::HashMap<usize, String>,
     tool_blocks: std::collections::HashMap<usize, StreamToolUseBlock>,
+    mut thinking_blocks: std::collections::HashMap<usize, ResponseContentBlock>,
     stop_reason: Option<String>,
     usage: Option<Usage>,
 ) -> MessagesResponse {
     let mut content = Vec::new();
     for index in ordered_indexes {
+        if let Some(block) = thinking_blocks.remove(&index) {
+            content.push(block);
+        }
         if let Some(text) = text_blocks.get(&index) {
             if !text.is_empty() {
                 content.push(ResponseContentBlock::Text { text: text.clone() });
@@ -691,6 +1324,7 @@ fn build_stream_response(
                 id: tool.id.clone(),
                 name: tool.name.clone(),
                 input: parse_tool_input(&tool.input_json),
+                signature: None,
             });
         }
     }
@@ -722,64 +1356,94 @@ struct AnthropicApiErrorDetail {
 
 #[async_trait]
 impl LlmProvider for AnthropicProvider {
+    fn endpoint(&self) -> Option<String> {
+        Some(self.base_url.clone())
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            documents: true,
+            prompt_caching: self.prompt_cache_ttl != "none",
+            ..ProviderCapabilities::default()
+        }
+        .with_limits(self.limits, self.max_tokens)
+    }
+
+    fn request_body(
+        &self,
+        system: &str,
+        messages: &[Message],
+        tools: Option<&[ToolDefinition]>,
+        stream: bool,
+    ) -> Option<serde_json::Value> {
+        let messages = self.prepare_messages(messages.to_vec());
+        Some(self.build_request_body(system, &messages, tools, stream.then_some(true)))
+    }
+
+    async fn count_tokens(
+        &self,
+        system: &str,
+        messages: &[Message],
+        tools: Option<&[ToolDefinition]>,
+    ) -> Result<usize, RayClawError> {
+        let messages = self.prepare_messages(messages.to_vec());
+        let mut body = self.build_request_body(system, &messages, tools, None);
+        if let Some(fields) = body.as_object_mut() {
+            fields.remove("max_tokens");
+        }
+        let url = format!("{}/count_tokens", self.base_url);
+        let response = send_with_retry(self.retry, "Anthropic count_tokens", || {
+            Ok(self.request_to(&url).json(&body))
+        })
+        .await?;
+        let status = response.status();
+        let text = response.text().await.unwrap_or_default();
+        if !status.is_success() {
+            return Err(RayClawError::LlmApi(format!(
+                "count_tokens HTTP {status}: {text}"
+            )));
+        }
+        serde_json::from_str::<serde_json::Value>(&text)?
+            .get("input_tokens")
+            .and_then(|n| n.as_u64())
+            .map(|n| n as usize)
+            .ok_or_else(|| {
+                RayClawError::LlmApi(format!(
+                    "count_tokens response without input_tokens: {text}"
+                ))
+            })
+    }
+
     async fn send_message(
         &self,
         system: &str,
         messages: Vec<Message>,
         tools: Option<Vec<ToolDefinition>>,
     ) -> Result<MessagesResponse, RayClawError> {
-        let messages = sanitize_messages(messages);
+        let messages = self.prepare_messages(messages);
 
         let body = self.build_request_body(system, &messages, tools.as_deref(), None);
 
-        let mut retries = 0u32;
-        let max_retries = 3;
-
-        loop {
-            let mut req = self
-                .http
-                .post(&self.base_url)
-                .header("x-api-key", &self.api_key)
-                .header("anthropic-version", "2023-06-01")
-                .header("content-type", "application/json");
-
-            // Add prompt caching beta header if enabled
-            if self.prompt_cache_ttl != "none" {
-                req = req.header("anthropic-beta", "prompt-caching-2024-07-31");
-            }
-
-            let response = req.json(&body).send().await?;
-
-            let status = response.status();
-
-            if status.is_success() {
-                let body = response.text().await?;
-                let parsed: MessagesResponse = serde_json::from_str(&body).map_err(|e| {
-                    RayClawError::LlmApi(format!("Failed to parse response: {e}\nBody: {body}"))
-                })?;
-                return Ok(parsed);
-            }
+        let response =
+            send_with_retry(self.retry, "Anthropic", || Ok(self.request().json(&body))).await?;
+        let status = response.status();
 
-            if status.as_u16() == 429 && retries < max_retries {
-                retries += 1;
-                let delay = std::time::Duration::from_secs(2u64.pow(retries));
-                warn!(
-                    "Rate limited, retrying in {:?} (attempt {retries}/{max_retries})",
-                    delay
-                );
-                tokio::time::sleep(delay).await;
-                continue;
-            }
+        if status.is_success() {
+            let body = response.text().await?;
+            let parsed: MessagesResponse = serde_json::from_str(&body).map_err(|e| {
+                RayClawError::LlmApi(format!("Failed to parse response: {e}\nBody: {body}"))
+            })?;
+            return Ok(parsed);
+        }
 
-            let body = response.text().await.unwrap_or_default();
-            if let Ok(api_err) = serde_json::from_str::<AnthropicApiError>(&body) {
-                return Err(RayClawError::LlmApi(format!(
-                    "{}: {}",
-                    api_err.error.error_type, api_err.error.message
-                )));
-            }
-            return Err(RayClawError::LlmApi(format!("HTTP {status}: {body}")));
+        let body = response.text().await.unwrap_or_default();
+        if let Ok(api_err) = serde_json::from_str::<AnthropicApiError>(&body) {
+            return Err(RayClawError::LlmApi(format!(
+                "{}: {}",
+                api_err.error.error_type, api_err.error.message
+            )));
         }
+        Err(RayClawError::LlmApi(format!("HTTP {status}: {body}")))
     }
 
     async fn send_message_stream(
@@ -789,7 +1453,7 @@ impl LlmProvider for AnthropicProvider {
         tools: Option<Vec<ToolDefinition>>,
         text_tx: Option<&UnboundedSender<String>>,
     ) -> Result<MessagesResponse, RayClawError> {
-        let messages = sanitize_messages(messages);
+        let messages = self.prepare_messages(messages);
 
         self.send_message_stream_single_pass(system, &messages, tools.as_deref(), text_tx)
             .await
@@ -797,7 +1461,7 @@ impl LlmProvider for AnthropicProvider {
 }
 
 // ---------------------------------------------------------------------------
-// OpenAI-compatible provider  (OpenAI, OpenRouter, DeepSeek, Groq, Ollama …)
+// OpenAI-compatible provider  (OpenAI, OpenRouter, DeepSeek, Groq …)
 // ---------------------------------------------------------------------------
 
 pub struct OpenAiProvider {
@@ -809,6 +1473,41 @@ pub struct OpenAiProvider {
     is_openai_codex: bool,
     chat_url: String,
     responses_url: String,
+    limits: ModelLimits,
+    /// `reasoning_effort` to request, when thinking is enabled
+    reasoning_effort: Option<String>,
+    inference: InferenceParams,
+    /// Whether the endpoint takes `top_k` (OpenAI and Azure reject it)
+    accepts_top_k: bool,
+    retry: RetryPolicy,
+    extras: OaiCompatExtras,
+    /// Cleared once the endpoint rejects `stream_options` (some servers
+    /// validate strictly); later streams then go without usage.
+    stream_usage: AtomicBool,
+}
+
+/// Provider-specific additions to OpenAI-compatible chat requests (see
+/// `llm_openrouter`).
+#[derive(Default)]
+pub(crate) struct OaiCompatExtras {
+    /// Name used in retry logs
+    pub label: Option<&'static str>,
+    pub headers: Vec<(&'static str, String)>,
+    /// Merged into every chat completions body
+    pub body: serde_json::Map<String, serde_json::Value>,
+    /// Turns an error response into a `RayClawError`; defaults to the
+    /// OpenAI error shape
+    pub map_error: Option<fn(reqwest::StatusCode, &str) -> RayClawError>,
+}
+
+/// OpenAI reasoning models (o-series, gpt-5), which take
+/// `max_completion_tokens` instead of `max_tokens`.
+fn openai_is_reasoning_model(model: &str) -> bool {
+    let model = model.to_lowercase();
+    let name = model.rsplit('/').next().unwrap_or(&model);
+    ["o1", "o3", "o4", "gpt-5"]
+        .iter()
+        .any(|prefix| name.starts_with(prefix))
 }
 
 fn resolve_openai_compat_base(provider: &str, configured_base: &str) -> String {
@@ -829,6 +1528,10 @@ fn resolve_openai_compat_base(provider: &str, configured_base: &str) -> String {
 
 impl OpenAiProvider {
     pub fn new(config: &Config) -> Self {
+        Self::with_extras(config, OaiCompatExtras::default())
+    }
+
+    pub(crate) fn with_extras(config: &Config, extras: OaiCompatExtras) -> Self {
         let is_openai_codex = is_openai_codex_provider(&config.llm_provider);
         let configured_base = config.llm_base_url.as_deref().unwrap_or("");
         let base = resolve_openai_compat_base(&config.llm_provider, configured_base);
@@ -843,19 +1546,118 @@ impl OpenAiProvider {
                 }
             }
         } else {
-            (config.api_key.clone(), None)
-        };
+            (config.api_key.clone(), None)
+        };
+
+        OpenAiProvider {
+            retry: RetryPolicy::from_config(config),
+            http: reqwest::Client::new(),
+            api_key,
+            codex_account_id,
+            model: config.model.clone(),
+            max_tokens: config.max_tokens,
+            is_openai_codex,
+            chat_url: format!("{}/chat/completions", base.trim_end_matches('/')),
+            responses_url: format!("{}/responses", base.trim_end_matches('/')),
+            limits: config.model_limits_for(&config.model).unwrap_or_default(),
+            reasoning_effort: config
+                .thinking
+                .enabled
+                .then(|| config.thinking.effort().to_string()),
+            inference: config.inference.clone(),
+            accepts_top_k: !matches!(config.llm_provider.as_str(), "openai" | "azure")
+                && !is_openai_codex,
+            extras,
+            stream_usage: AtomicBool::new(true),
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        self.extras.label.unwrap_or("OpenAI")
+    }
+
+    fn chat_request(&self, body: &serde_json::Value) -> reqwest::RequestBuilder {
+        let mut req = self
+            .http
+            .post(&self.chat_url)
+            .header("Content-Type", "application/json")
+            .json(body);
+        for (name, value) in &self.extras.headers {
+            req = req.header(*name, value);
+        }
+        if self.api_key.trim().is_empty() {
+            req
+        } else {
+            req.header("Authorization", format!("Bearer {}", self.api_key))
+        }
+    }
+
+    /// Full chat completions body: [`Self::chat_body`] plus tools and streaming.
+    fn chat_request_body(
+        &self,
+        system: &str,
+        messages: &[Message],
+        tools: Option<&[ToolDefinition]>,
+        stream: bool,
+    ) -> serde_json::Value {
+        let mut body = self.chat_body(system, messages);
+        if stream {
+            body["stream"] = json!(true);
+            if self.stream_usage.load(Ordering::Relaxed) {
+                body["stream_options"] = json!({ "include_usage": true });
+            }
+        }
+        if let Some(tool_defs) = tools {
+            if !tool_defs.is_empty() {
+                body["tools"] = json!(translate_tools_to_oai(tool_defs));
+            }
+        }
+        body
+    }
+
+    /// Chat completions body with the token limit and reasoning parameters
+    /// the model expects.
+    fn chat_body(&self, system: &str, messages: &[Message]) -> serde_json::Value {
+        let mut body = json!({
+            "model": self.model,
+            "messages": translate_messages_to_oai(system, messages),
+        });
+        let params = &self.inference;
+        if openai_is_reasoning_model(&self.model) {
+            // Reasoning models reject sampling parameters and stop sequences
+            body["max_completion_tokens"] = json!(self.max_tokens);
+        } else {
+            body["max_tokens"] = json!(self.max_tokens);
+            if let Some(temperature) = params.temperature {
+                body["temperature"] = json!(temperature);
+            }
+            if let Some(top_p) = params.top_p {
+                body["top_p"] = json!(top_p);
+            }
+            if !params.stop_sequences.is_empty() {
+                body["stop"] = json!(params.stop_sequences);
+            }
+        }
+        if let (Some(top_k), true) = (params.top_k, self.accepts_top_k) {
+            body["top_k"] = json!(top_k);
+        }
+        if let Some(effort) = &self.reasoning_effort {
+            body["reasoning_effort"] = json!(effort);
+        }
+        for (key, value) in &self.extras.body {
+            body[key] = value.clone();
+        }
+        body
+    }
 
-        OpenAiProvider {
-            http: reqwest::Client::new(),
-            api_key,
-            codex_account_id,
-            model: config.model.clone(),
-            max_tokens: config.max_tokens,
-            is_openai_codex,
-            chat_url: format!("{}/chat/completions", base.trim_end_matches('/')),
-            responses_url: format!("{}/responses", base.trim_end_matches('/')),
+    fn api_error(&self, status: reqwest::StatusCode, text: &str) -> RayClawError {
+        if let Some(map_error) = self.extras.map_error {
+            return map_error(status, text);
         }
+        if let Ok(err) = serde_json::from_str::<OaiErrorResponse>(text) {
+            return RayClawError::LlmApi(err.error.message);
+        }
+        RayClawError::LlmApi(format!("HTTP {status}: {text}"))
     }
 }
 
@@ -877,6 +1679,12 @@ struct OaiChoice {
 struct OaiMessage {
     content: Option<String>,
     tool_calls: Option<Vec<OaiToolCall>>,
+    /// Reasoning text (DeepSeek, vLLM)
+    #[serde(default)]
+    reasoning_content: Option<String>,
+    /// Reasoning text (OpenRouter)
+    #[serde(default)]
+    reasoning: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -895,6 +1703,14 @@ struct OaiFunction {
 struct OaiUsage {
     prompt_tokens: u32,
     completion_tokens: u32,
+    #[serde(default)]
+    prompt_tokens_details: Option<OaiPromptTokensDetails>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OaiPromptTokensDetails {
+    #[serde(default)]
+    cached_tokens: u32,
 }
 
 #[derive(Debug, Deserialize)]
@@ -948,6 +1764,38 @@ enum OaiResponsesOutputContentPart {
 
 #[async_trait]
 impl LlmProvider for OpenAiProvider {
+    fn endpoint(&self) -> Option<String> {
+        Some(if self.is_openai_codex {
+            self.responses_url.clone()
+        } else {
+            self.chat_url.clone()
+        })
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            vision: openai_model_supports_vision(&self.model),
+            // The Codex responses endpoint is called without streaming
+            streaming: !self.is_openai_codex,
+            ..ProviderCapabilities::default()
+        }
+        .with_limits(self.limits, self.max_tokens)
+    }
+
+    fn request_body(
+        &self,
+        system: &str,
+        messages: &[Message],
+        tools: Option<&[ToolDefinition]>,
+        stream: bool,
+    ) -> Option<serde_json::Value> {
+        Some(if self.is_openai_codex {
+            self.codex_body(system, messages, tools)
+        } else {
+            self.chat_request_body(system, messages, tools, stream)
+        })
+    }
+
     async fn send_message(
         &self,
         system: &str,
@@ -958,63 +1806,32 @@ impl LlmProvider for OpenAiProvider {
             return self.send_codex_message(system, messages, tools).await;
         }
 
-        let oai_messages = translate_messages_to_oai(system, &messages);
+        let body = self.chat_request_body(system, &messages, tools.as_deref(), false);
 
-        let mut body = json!({
-            "model": self.model,
-            "max_tokens": self.max_tokens,
-            "messages": oai_messages,
-        });
+        let response =
+            send_with_retry(self.retry, self.label(), || Ok(self.chat_request(&body))).await?;
+        let status = response.status();
 
-        if let Some(ref tool_defs) = tools {
-            if !tool_defs.is_empty() {
-                body["tools"] = json!(translate_tools_to_oai(tool_defs));
-            }
+        if status.is_success() {
+            let text = response.text().await?;
+            let oai: OaiResponse = match serde_json::from_str(&text) {
+                Ok(oai) => oai,
+                // Routers can answer 200 with an error from the upstream model
+                Err(_) if serde_json::from_str::<OaiErrorResponse>(&text).is_ok() => {
+                    return Err(self.api_error(status, &text));
+                }
+                Err(e) => {
+                    return Err(RayClawError::LlmApi(format!(
+                        "Failed to parse {} response: {e}\nBody: {text}",
+                        self.label()
+                    )));
+                }
+            };
+            return Ok(translate_oai_response(oai));
         }
 
-        let mut retries = 0u32;
-        let max_retries = 3;
-
-        loop {
-            let mut req = self
-                .http
-                .post(&self.chat_url)
-                .header("Content-Type", "application/json")
-                .json(&body);
-            if !self.api_key.trim().is_empty() {
-                req = req.header("Authorization", format!("Bearer {}", self.api_key));
-            }
-            let response = req.send().await?;
-
-            let status = response.status();
-
-            if status.is_success() {
-                let text = response.text().await?;
-                let oai: OaiResponse = serde_json::from_str(&text).map_err(|e| {
-                    RayClawError::LlmApi(format!(
-                        "Failed to parse OpenAI response: {e}\nBody: {text}"
-                    ))
-                })?;
-                return Ok(translate_oai_response(oai));
-            }
-
-            if status.as_u16() == 429 && retries < max_retries {
-                retries += 1;
-                let delay = std::time::Duration::from_secs(2u64.pow(retries));
-                warn!(
-                    "Rate limited, retrying in {:?} (attempt {retries}/{max_retries})",
-                    delay
-                );
-                tokio::time::sleep(delay).await;
-                continue;
-            }
-
-            let text = response.text().await.unwrap_or_default();
-            if let Ok(err) = serde_json::from_str::<OaiErrorResponse>(&text) {
-                return Err(RayClawError::LlmApi(err.error.message));
-            }
-            return Err(RayClawError::LlmApi(format!("HTTP {status}: {text}")));
-        }
+        let text = response.text().await.unwrap_or_default();
+        Err(self.api_error(status, &text))
     }
 
     async fn send_message_stream(
@@ -1043,118 +1860,82 @@ impl LlmProvider for OpenAiProvider {
             return Ok(response);
         }
 
-        let oai_messages = translate_messages_to_oai(system, &messages);
-
-        let mut body = json!({
-            "model": self.model,
-            "max_tokens": self.max_tokens,
-            "messages": oai_messages,
-            "stream": true,
-        });
-
-        if let Some(ref tool_defs) = tools {
-            if !tool_defs.is_empty() {
-                body["tools"] = json!(translate_tools_to_oai(tool_defs));
+        let response = loop {
+            let body = self.chat_request_body(system, &messages, tools.as_deref(), true);
+            let response =
+                send_with_retry(self.retry, self.label(), || Ok(self.chat_request(&body))).await?;
+            let status = response.status();
+            if status.is_success() {
+                break response;
             }
-        }
-
-        let mut req = self
-            .http
-            .post(&self.chat_url)
-            .header("Content-Type", "application/json")
-            .json(&body);
-        if !self.api_key.trim().is_empty() {
-            req = req.header("Authorization", format!("Bearer {}", self.api_key));
-        }
-        let response = req.send().await?;
-        let status = response.status();
-        if !status.is_success() {
             let text = response.text().await.unwrap_or_default();
-            if let Ok(err) = serde_json::from_str::<OaiErrorResponse>(&text) {
-                return Err(RayClawError::LlmApi(err.error.message));
+            if status.is_client_error()
+                && body.get("stream_options").is_some()
+                && text.contains("stream_options")
+            {
+                warn!(
+                    "{} rejected stream_options; streaming without token usage",
+                    self.label()
+                );
+                self.stream_usage.store(false, Ordering::Relaxed);
+                continue;
             }
-            return Err(RayClawError::LlmApi(format!("HTTP {status}: {text}")));
-        }
+            return Err(self.api_error(status, &text));
+        };
+        let status = response.status();
 
         let mut byte_stream = response.bytes_stream();
         let mut sse = SseEventParser::default();
-        let mut text = String::new();
-        let mut stop_reason: Option<String> = None;
-        let mut usage: Option<Usage> = None;
-        let mut tool_calls: std::collections::BTreeMap<usize, StreamToolUseBlock> =
-            std::collections::BTreeMap::new();
+        let mut state = OaiStreamState::default();
 
         'outer: while let Some(chunk_res) = byte_stream.next().await {
             let chunk = match chunk_res {
                 Ok(c) => c,
-                Err(_) => break,
+                // A connection dropped after the finish_reason lost nothing
+                Err(_) if state.finish_reason.is_some() => break,
+                Err(e) => {
+                    return Err(RayClawError::LlmApi(format!(
+                        "{} stream interrupted: {e}",
+                        self.label()
+                    )));
+                }
             };
             for data in sse.push_chunk(&String::from_utf8_lossy(&chunk)) {
                 if data == "[DONE]" {
                     break 'outer;
                 }
-                process_openai_stream_event(
-                    &data,
-                    text_tx,
-                    &mut text,
-                    &mut stop_reason,
-                    &mut usage,
-                    &mut tool_calls,
-                );
+                state
+                    .push(&data, text_tx)
+                    .map_err(|error| self.api_error(status, &error))?;
             }
         }
         for data in sse.finish() {
             if data == "[DONE]" {
                 break;
             }
-            process_openai_stream_event(
-                &data,
-                text_tx,
-                &mut text,
-                &mut stop_reason,
-                &mut usage,
-                &mut tool_calls,
-            );
-        }
-
-        let mut content = Vec::new();
-        if !text.is_empty() {
-            content.push(ResponseContentBlock::Text { text });
-        }
-        for (_index, tool) in tool_calls {
-            content.push(ResponseContentBlock::ToolUse {
-                id: tool.id,
-                name: tool.name,
-                input: parse_tool_input(&tool.input_json),
-            });
-        }
-        if content.is_empty() {
-            content.push(ResponseContentBlock::Text {
-                text: String::new(),
-            });
+            state
+                .push(&data, text_tx)
+                .map_err(|error| self.api_error(status, &error))?;
         }
 
-        Ok(MessagesResponse {
-            content,
-            stop_reason: normalize_stop_reason(stop_reason),
-            usage,
-        })
+        Ok(state.into_response())
     }
 }
 
 impl OpenAiProvider {
-    async fn send_codex_message(
+    /// Request body for the Codex responses endpoint (always streamed).
+    fn codex_body(
         &self,
         system: &str,
-        messages: Vec<Message>,
-        tools: Option<Vec<ToolDefinition>>,
-    ) -> Result<MessagesResponse, RayClawError> {
+        messages: &[Message],
+        tools: Option<&[ToolDefinition]>,
+    ) -> serde_json::Value {
         let instructions = if system.trim().is_empty() {
             "You are a helpful assistant."
         } else {
             system
         };
-        let mut input = translate_messages_to_oai_responses_input(&messages);
+        let mut input = translate_messages_to_oai_responses_input(messages);
         if input.is_empty() {
             input.push(json!({
                 "type": "message",
@@ -1169,17 +1950,28 @@ impl OpenAiProvider {
             "store": false,
             "stream": true,
         });
-        if let Some(ref tool_defs) = tools {
+        if let Some(effort) = &self.reasoning_effort {
+            body["reasoning"] = json!({ "effort": effort });
+        }
+        if let Some(tool_defs) = tools {
             if !tool_defs.is_empty() {
                 body["tools"] = json!(translate_tools_to_oai_responses(tool_defs));
                 body["tool_choice"] = json!("auto");
             }
         }
 
-        let mut retries = 0u32;
-        let max_retries = 3;
+        body
+    }
+
+    async fn send_codex_message(
+        &self,
+        system: &str,
+        messages: Vec<Message>,
+        tools: Option<Vec<ToolDefinition>>,
+    ) -> Result<MessagesResponse, RayClawError> {
+        let body = self.codex_body(system, &messages, tools.as_deref());
 
-        loop {
+        let response = send_with_retry(self.retry, "OpenAI Codex", || {
             let mut req = self
                 .http
                 .post(&self.responses_url)
@@ -1193,32 +1985,22 @@ impl OpenAiProvider {
                     req = req.header("ChatGPT-Account-ID", account_id);
                 }
             }
-            let response = req.send().await?;
-            let status = response.status();
-
-            if status.is_success() {
-                let text = response.text().await?;
-                let parsed = parse_openai_codex_response_payload(&text)?;
-                return Ok(translate_oai_responses_response(parsed));
-            }
+            Ok(req)
+        })
+        .await?;
+        let status = response.status();
 
-            if status.as_u16() == 429 && retries < max_retries {
-                retries += 1;
-                let delay = std::time::Duration::from_secs(2u64.pow(retries));
-                warn!(
-                    "Rate limited, retrying in {:?} (attempt {retries}/{max_retries})",
-                    delay
-                );
-                tokio::time::sleep(delay).await;
-                continue;
-            }
+        if status.is_success() {
+            let text = response.text().await?;
+            let parsed = parse_openai_codex_response_payload(&text)?;
+            return Ok(translate_oai_responses_response(parsed));
+        }
 
-            let text = response.text().await.unwrap_or_default();
-            if let Ok(err) = serde_json::from_str::<OaiErrorResponse>(&text) {
-                return Err(RayClawError::LlmApi(err.error.message));
-            }
-            return Err(RayClawError::LlmApi(format!("HTTP {status}: {text}")));
+        let text = response.text().await.unwrap_or_default();
+        if let Ok(err) = serde_json::from_str::<OaiErrorResponse>(&text) {
+            return Err(RayClawError::LlmApi(err.error.message));
         }
+        Err(RayClawError::LlmApi(format!("HTTP {status}: {text}")))
     }
 }
 
@@ -1311,7 +2093,9 @@ fn translate_messages_to_oai(system: &str, messages: &[Message]) -> Vec<serde_js
                     let tool_calls: Vec<serde_json::Value> = blocks
                         .iter()
                         .filter_map(|b| match b {
-                            ContentBlock::ToolUse { id, name, input } => Some(json!({
+                            ContentBlock::ToolUse {
+                                id, name, input, ..
+                            } => Some(json!({
                                 "id": id,
                                 "type": "function",
                                 "function": {
@@ -1362,6 +2146,29 @@ fn translate_messages_to_oai(system: &str, messages: &[Message]) -> Vec<serde_js
                                 }));
                             }
                         }
+                        // Tool messages are text-only; images the tools
+                        // returned follow in a user message
+                        let image_parts: Vec<serde_json::Value> = blocks
+                            .iter()
+                            .filter_map(|b| match b {
+                                ContentBlock::Image {
+                                    source:
+                                        ImageSource {
+                                            media_type, data, ..
+                                        },
+                                } => Some(json!({
+                                    "type": "image_url",
+                                    "image_url": {"url": format!("data:{media_type};base64,{data}")},
+                                })),
+                                ContentBlock::Text { text } => {
+                                    Some(json!({"type": "text", "text": text}))
+                                }
+                                _ => None,
+                            })
+                            .collect();
+                        if !image_parts.is_empty() {
+                            out.push(json!({"role": "user", "content": image_parts}));
+                        }
                     } else {
                         // Images + text → multipart content array
                         let has_images = blocks
@@ -1485,7 +2292,10 @@ fn translate_messages_to_oai_responses_input(messages: &[Message]) -> Vec<serde_
                     }
 
                     for block in blocks {
-                        if let ContentBlock::ToolUse { id, name, input } = block {
+                        if let ContentBlock::ToolUse {
+                            id, name, input, ..
+                        } = block
+                        {
                             out.push(json!({
                                 "type": "function_call",
                                 "call_id": id,
@@ -1521,6 +2331,35 @@ fn translate_messages_to_oai_responses_input(messages: &[Message]) -> Vec<serde_
                                 }));
                             }
                         }
+                        let image_parts: Vec<serde_json::Value> = blocks
+                            .iter()
+                            .filter_map(|b| match b {
+                                ContentBlock::Image {
+                                    source:
+                                        ImageSource {
+                                            media_type, data, ..
+                                        },
+                                } => Some(json!({
+                                    "type": "input_image",
+                                    "source": {
+                                        "type": "base64",
+                                        "media_type": media_type,
+                                        "data": data,
+                                    }
+                                })),
+                                ContentBlock::Text { text } => {
+                                    Some(json!({"type": "input_text", "text": text}))
+                                }
+                                _ => None,
+                            })
+                            .collect();
+                        if !image_parts.is_empty() {
+                            out.push(json!({
+                                "type": "message",
+                                "role": "user",
+                                "content": image_parts,
+                            }));
+                        }
                     } else {
                         let has_images = blocks
                             .iter()
@@ -1609,6 +2448,7 @@ fn translate_oai_responses_response(resp: OaiResponsesResponse) -> MessagesRespo
                     id: call_id,
                     name,
                     input: parsed_args,
+                    signature: None,
                 });
                 saw_tool_use = true;
             }
@@ -1632,6 +2472,7 @@ fn translate_oai_responses_response(resp: OaiResponsesResponse) -> MessagesRespo
         usage: resp.usage.map(|usage| Usage {
             input_tokens: usage.input_tokens,
             output_tokens: usage.output_tokens,
+            ..Usage::default()
         }),
     }
 }
@@ -1652,64 +2493,320 @@ fn translate_oai_response(oai: OaiResponse) -> MessagesResponse {
 
     let mut content = Vec::new();
 
+    if let Some(thinking) = choice
+        .message
+        .reasoning_content
+        .or(choice.message.reasoning)
+        .filter(|r| !r.is_empty())
+    {
+        content.push(ResponseContentBlock::Thinking {
+            thinking,
+            signature: None,
+        });
+    }
+
     if let Some(text) = choice.message.content {
         if !text.is_empty() {
             content.push(ResponseContentBlock::Text { text });
         }
     }
 
-    if let Some(tool_calls) = choice.message.tool_calls {
-        for tc in tool_calls {
-            let input: serde_json::Value =
-                serde_json::from_str(&tc.function.arguments).unwrap_or_default();
-            content.push(ResponseContentBlock::ToolUse {
-                id: tc.id,
-                name: tc.function.name,
-                input,
-            });
-        }
+    let has_tool_calls = choice
+        .message
+        .tool_calls
+        .as_ref()
+        .is_some_and(|calls| !calls.is_empty());
+    if let Some(tool_calls) = choice.message.tool_calls {
+        for tc in tool_calls {
+            let input: serde_json::Value =
+                serde_json::from_str(&tc.function.arguments).unwrap_or_default();
+            content.push(ResponseContentBlock::ToolUse {
+                id: tc.id,
+                name: tc.function.name,
+                input,
+                signature: None,
+            });
+        }
+    }
+
+    if content.is_empty() {
+        content.push(ResponseContentBlock::Text {
+            text: String::new(),
+        });
+    }
+
+    MessagesResponse {
+        content,
+        stop_reason: oai_stop_reason(choice.finish_reason, has_tool_calls),
+        usage: oai.usage.map(oai_usage),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+    use std::sync::{Mutex, OnceLock};
+    use std::time::Duration;
+
+    fn env_lock() -> std::sync::MutexGuard<'static, ()> {
+        static ENV_LOCK: OnceLock<Mutex<()>> = OnceLock::new();
+        ENV_LOCK
+            .get_or_init(|| Mutex::new(()))
+            .lock()
+            .expect("env lock poisoned")
+    }
+
+    #[test]
+    fn test_estimate_request_tokens() {
+        assert_eq!(estimate_text_tokens("abcdefgh"), 2);
+        assert_eq!(estimate_text_tokens("你好"), 2);
+        let image = Message {
+            role: "user".into(),
+            content: MessageContent::Blocks(vec![ContentBlock::Image {
+                source: ImageSource {
+                    source_type: "base64".into(),
+                    media_type: "image/png".into(),
+                    data: "A".repeat(1_000_000),
+                },
+            }]),
+        };
+        // Images count a flat amount, not their base64 size
+        assert_eq!(
+            estimate_request_tokens("", &[image], None),
+            IMAGE_TOKEN_ESTIMATE + MESSAGE_TOKEN_OVERHEAD
+        );
+        let tool = ToolDefinition {
+            name: "bash".into(),
+            description: "Run a command".into(),
+            input_schema: json!({}),
+        };
+        assert!(estimate_request_tokens("sys", &[], Some(&[tool])) > estimate_text_tokens("sys"));
+    }
+
+    #[test]
+    fn test_is_context_length_error() {
+        let api = |m: &str| RayClawError::LlmApi(m.into());
+        assert!(is_context_length_error(&api(
+            "invalid_request_error: prompt is too long: 210000 tokens > 200000 maximum"
+        )));
+        assert!(is_context_length_error(&api(
+            "HTTP 400 Bad Request: {\"error\":{\"code\":\"context_length_exceeded\"}}"
+        )));
+        assert!(is_context_length_error(&api(
+            "Bedrock Converse HTTP 400: ValidationException: Input is too long for requested model."
+        )));
+        assert!(is_context_length_error(&api(
+            "Gemini generateContent HTTP 400: The input token count (1200000) exceeds the maximum number of tokens allowed (1048576)."
+        )));
+        assert!(!is_context_length_error(&api(
+            "HTTP 503 Service Unavailable"
+        )));
+        assert!(!is_context_length_error(&RayClawError::Config(
+            "prompt is too long".into()
+        )));
+    }
+
+    #[test]
+    fn test_is_provider_unavailable() {
+        let api = |m: &str| RayClawError::LlmApi(m.into());
+        assert!(is_provider_unavailable(&api(
+            "HTTP 503 Service Unavailable: down"
+        )));
+        assert!(is_provider_unavailable(&api(
+            "Bedrock Converse HTTP 429 Too Many Requests: {}"
+        )));
+        assert!(is_provider_unavailable(&api(
+            "overloaded_error: Overloaded"
+        )));
+        assert!(is_provider_unavailable(&RayClawError::RateLimited));
+        assert!(!is_provider_unavailable(&api("HTTP 400 Bad Request: bad")));
+        assert!(!is_provider_unavailable(&api(
+            "invalid_request_error: prompt is too long"
+        )));
+        assert!(!is_provider_unavailable(&RayClawError::Config("x".into())));
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        let now = chrono::DateTime::parse_from_rfc3339("2015-10-21T07:28:00Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        let headers = |pairs: &[(&'static str, &'static str)]| {
+            let mut map = reqwest::header::HeaderMap::new();
+            for (k, v) in pairs {
+                map.insert(*k, reqwest::header::HeaderValue::from_static(v));
+            }
+            map
+        };
+        assert_eq!(
+            parse_retry_after(&headers(&[("retry-after", "7")]), now),
+            Some(Duration::from_secs(7))
+        );
+        assert_eq!(
+            parse_retry_after(
+                &headers(&[("retry-after", "Wed, 21 Oct 2015 07:28:30 GMT")]),
+                now
+            ),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_retry_after(
+                &headers(&[("retry-after", "Wed, 21 Oct 2015 07:00:00 GMT")]),
+                now
+            ),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(
+            parse_retry_after(
+                &headers(&[("retry-after-ms", "1500"), ("retry-after", "2")]),
+                now
+            ),
+            Some(Duration::from_millis(1500))
+        );
+        assert_eq!(
+            parse_retry_after(&headers(&[("retry-after", "soon")]), now),
+            None
+        );
+        assert_eq!(parse_retry_after(&headers(&[]), now), None);
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        let policy = RetryPolicy {
+            max_retries: 5,
+            max_delay: Duration::from_secs(10),
+        };
+        assert_eq!(
+            retry_delay(policy, 1, None, 0.0),
+            Some(Duration::from_secs(1))
+        );
+        assert_eq!(
+            retry_delay(policy, 2, None, 0.999),
+            Some(Duration::from_secs(4).mul_f64(0.9995))
+        );
+        // backoff is capped at max_delay
+        assert_eq!(
+            retry_delay(policy, 8, None, 0.0),
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(
+            retry_delay(policy, 1, Some(Duration::from_secs(5)), 0.5),
+            Some(Duration::from_secs(5).mul_f64(1.1))
+        );
+        assert_eq!(
+            retry_delay(policy, 1, Some(Duration::from_secs(11)), 0.0),
+            None
+        );
+        let j = jitter();
+        assert!((0.0..1.0).contains(&j));
     }
 
-    if content.is_empty() {
-        content.push(ResponseContentBlock::Text {
-            text: String::new(),
+    /// Serve `responses` (status line + extra headers) to successive
+    /// connections and count the requests.
+    fn spawn_scripted_server(
+        responses: Vec<&'static str>,
+    ) -> (String, std::thread::JoinHandle<usize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let mut served = 0;
+            for head in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                stream
+                    .set_read_timeout(Some(Duration::from_secs(2)))
+                    .unwrap();
+                let mut buf = [0u8; 8192];
+                let _ = stream.read(&mut buf);
+                let body = "{}";
+                let response = format!(
+                    "{head}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                let _ = stream.write_all(response.as_bytes());
+                served += 1;
+            }
+            served
         });
+        (url, server)
     }
 
-    let stop_reason = match choice.finish_reason.as_deref() {
-        Some("tool_calls") => Some("tool_use".into()),
-        Some("length") => Some("max_tokens".into()),
-        _ => Some("end_turn".into()),
-    };
+    #[tokio::test]
+    async fn test_send_with_retry_retries_overload_then_succeeds() {
+        let (url, server) = spawn_scripted_server(vec![
+            "HTTP/1.1 529 Overloaded\r\nRetry-After: 0",
+            "HTTP/1.1 503 Service Unavailable\r\nretry-after-ms: 10",
+            "HTTP/1.1 200 OK",
+        ]);
+        let http = reqwest::Client::new();
+        let response = send_with_retry(RetryPolicy::default(), "test", || Ok(http.post(&url)))
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(server.join().unwrap(), 3);
+    }
 
-    let usage = oai.usage.map(|u| Usage {
-        input_tokens: u.prompt_tokens,
-        output_tokens: u.completion_tokens,
-    });
+    #[tokio::test]
+    async fn test_send_with_retry_gives_up() {
+        // Non-retryable status: returned as is
+        let (url, server) = spawn_scripted_server(vec!["HTTP/1.1 400 Bad Request"]);
+        let http = reqwest::Client::new();
+        let response = send_with_retry(RetryPolicy::default(), "test", || Ok(http.post(&url)))
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 400);
+        assert_eq!(server.join().unwrap(), 1);
+
+        // Budget exhausted: the last retryable response is returned
+        let (url, server) = spawn_scripted_server(vec![
+            "HTTP/1.1 429 Too Many Requests\r\nRetry-After: 0",
+            "HTTP/1.1 429 Too Many Requests\r\nRetry-After: 0",
+        ]);
+        let policy = RetryPolicy {
+            max_retries: 1,
+            ..RetryPolicy::default()
+        };
+        let response = send_with_retry(policy, "test", || Ok(http.post(&url)))
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 429);
+        assert_eq!(server.join().unwrap(), 2);
 
-    MessagesResponse {
-        content,
-        stop_reason,
-        usage,
+        // Retry-After beyond max_delay: no retry
+        let (url, server) =
+            spawn_scripted_server(vec!["HTTP/1.1 429 Too Many Requests\r\nRetry-After: 3600"]);
+        let response = send_with_retry(RetryPolicy::default(), "test", || Ok(http.post(&url)))
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 429);
+        assert_eq!(server.join().unwrap(), 1);
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use serde_json::json;
-    use std::io::{Read, Write};
-    use std::net::TcpListener;
-    use std::sync::mpsc;
-    use std::sync::{Mutex, OnceLock};
-    use std::time::Duration;
 
-    fn env_lock() -> std::sync::MutexGuard<'static, ()> {
-        static ENV_LOCK: OnceLock<Mutex<()>> = OnceLock::new();
-        ENV_LOCK
-            .get_or_init(|| Mutex::new(()))
-            .lock()
-            .expect("env lock poisoned")
+    #[tokio::test]
+    async fn test_send_with_retry_network_errors() {
+        // Nothing listens on the port: connection refused on every attempt
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let url = format!("http://127.0.0.1:{port}");
+        let http = reqwest::Client::new();
+        let mut attempts = 0;
+        let policy = RetryPolicy {
+            max_retries: 1,
+            max_delay: Duration::from_millis(1),
+        };
+        let result = send_with_retry(policy, "test", || {
+            attempts += 1;
+            Ok(http.post(&url))
+        })
+        .await;
+        assert!(matches!(result, Err(RayClawError::Http(_))));
+        assert_eq!(attempts, 2);
     }
 
     // -----------------------------------------------------------------------
@@ -1764,6 +2861,7 @@ mod tests {
                     id: "t1".into(),
                     name: "bash".into(),
                     input: json!({"command": "ls"}),
+                    signature: None,
                 },
             ]),
         }];
@@ -1786,6 +2884,7 @@ mod tests {
                     id: "t1".into(),
                     name: "glob".into(),
                     input: json!({}),
+                    signature: None,
                 }]),
             },
             Message {
@@ -1805,6 +2904,52 @@ mod tests {
         assert_eq!(out[1]["content"], "file1.rs\nfile2.rs");
     }
 
+    #[test]
+    fn test_translate_messages_tool_result_images_follow() {
+        let msgs = vec![
+            Message {
+                role: "assistant".into(),
+                content: MessageContent::Blocks(vec![ContentBlock::ToolUse {
+                    id: "t1".into(),
+                    name: "browser".into(),
+                    input: json!({}),
+                    signature: None,
+                }]),
+            },
+            Message {
+                role: "user".into(),
+                content: MessageContent::Blocks(vec![
+                    ContentBlock::ToolResult {
+                        tool_use_id: "t1".into(),
+                        content: "Screenshot saved".into(),
+                        is_error: None,
+                    },
+                    ContentBlock::Image {
+                        source: ImageSource {
+                            source_type: "base64".into(),
+                            media_type: "image/png".into(),
+                            data: "iVBOR".into(),
+                        },
+                    },
+                ]),
+            },
+        ];
+        let out = translate_messages_to_oai("", &msgs);
+        assert_eq!(out.len(), 3);
+        assert_eq!(out[1]["role"], "tool");
+        assert_eq!(out[1]["content"], "Screenshot saved");
+        assert_eq!(out[2]["role"], "user");
+        assert_eq!(
+            out[2]["content"][0]["image_url"]["url"],
+            "data:image/png;base64,iVBOR"
+        );
+
+        let out = translate_messages_to_oai_responses_input(&msgs);
+        assert_eq!(out[1]["type"], "function_call_output");
+        assert_eq!(out[2]["role"], "user");
+        assert_eq!(out[2]["content"][0]["type"], "input_image");
+    }
+
     #[test]
     fn test_translate_messages_tool_result_error() {
         let msgs = vec![
@@ -1814,6 +2959,7 @@ mod tests {
                     id: "t1".into(),
                     name: "glob".into(),
                     input: json!({}),
+                    signature: None,
                 }]),
             },
             Message {
@@ -1917,6 +3063,8 @@ mod tests {
         let oai = OaiResponse {
             choices: vec![OaiChoice {
                 message: OaiMessage {
+                    reasoning_content: None,
+                    reasoning: None,
                     content: Some("Hello!".into()),
                     tool_calls: None,
                 },
@@ -1925,6 +3073,7 @@ mod tests {
             usage: Some(OaiUsage {
                 prompt_tokens: 10,
                 completion_tokens: 5,
+                prompt_tokens_details: None,
             }),
         };
         let resp = translate_oai_response(oai);
@@ -1939,11 +3088,322 @@ mod tests {
         assert_eq!(usage.output_tokens, 5);
     }
 
+    #[test]
+    fn test_translate_oai_response_cached_tokens() {
+        let oai = OaiResponse {
+            choices: vec![OaiChoice {
+                message: OaiMessage {
+                    reasoning_content: None,
+                    reasoning: None,
+                    content: Some("Hello!".into()),
+                    tool_calls: None,
+                },
+                finish_reason: Some("stop".into()),
+            }],
+            usage: Some(OaiUsage {
+                prompt_tokens: 10,
+                completion_tokens: 5,
+                prompt_tokens_details: Some(OaiPromptTokensDetails { cached_tokens: 4 }),
+            }),
+        };
+        let usage = translate_oai_response(oai).usage.unwrap();
+        // Cached prompt tokens are split out of the input count
+        assert_eq!(usage.input_tokens, 6);
+        assert_eq!(usage.cache_read_input_tokens, 4);
+        assert_eq!(usage.output_tokens, 5);
+    }
+
+    #[test]
+    fn test_translate_oai_response_reasoning() {
+        let oai = OaiResponse {
+            choices: vec![OaiChoice {
+                message: OaiMessage {
+                    reasoning_content: None,
+                    reasoning: Some("2 + 2 is 4".into()),
+                    content: Some("4".into()),
+                    tool_calls: None,
+                },
+                finish_reason: Some("stop".into()),
+            }],
+            usage: None,
+        };
+        let resp = translate_oai_response(oai);
+        assert_eq!(resp.content.len(), 2);
+        match &resp.content[0] {
+            ResponseContentBlock::Thinking {
+                thinking,
+                signature,
+            } => {
+                assert_eq!(thinking, "2 + 2 is 4");
+                assert!(signature.is_none());
+            }
+            _ => panic!("Expected Thinking"),
+        }
+
+        let mut state = OaiStreamState::default();
+        for data in [
+            r#"{"choices":[{"delta":{"reasoning_content":"Let me "}}]}"#,
+            r#"{"choices":[{"delta":{"reasoning_content":"think."}}]}"#,
+            r#"{"choices":[{"delta":{"content":"Done"},"finish_reason":"stop"}]}"#,
+        ] {
+            state.push(data, None).unwrap();
+        }
+        assert_eq!(state.reasoning, "Let me think.");
+        assert_eq!(state.text, "Done");
+    }
+
+    #[test]
+    fn test_oai_stream_tool_call_deltas() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut state = OaiStreamState::default();
+        for data in [
+            r#"{"choices":[{"delta":{"role":"assistant","content":"Checking."}}]}"#,
+            r#"{"choices":[{"delta":{"tool_calls":[{"index":0,"id":"call_a","type":"function","function":{"name":"bash","arguments":""}}]}}]}"#,
+            r#"{"choices":[{"delta":{"tool_calls":[{"index":0,"function":{"arguments":"{\"command\":"}}]}}]}"#,
+            r#"{"choices":[{"delta":{"tool_calls":[{"index":1,"id":"call_b","function":{"name":"read_file","arguments":"{\"path\":\"a.txt\"}"}}]}}]}"#,
+            r#"{"choices":[{"delta":{"tool_calls":[{"index":0,"function":{"arguments":"\"ls\"}"}}]}}]}"#,
+            r#"{"choices":[{"delta":{},"finish_reason":"tool_calls"}],"usage":null}"#,
+            r#"{"choices":[],"usage":{"prompt_tokens":12,"completion_tokens":7,"prompt_tokens_details":{"cached_tokens":2}}}"#,
+        ] {
+            state.push(data, Some(&tx)).unwrap();
+        }
+        assert_eq!(rx.try_recv().unwrap(), "Checking.");
+
+        let response = state.into_response();
+        assert_eq!(response.stop_reason.as_deref(), Some("tool_use"));
+        let usage = response.usage.unwrap();
+        assert_eq!((usage.input_tokens, usage.output_tokens), (10, 7));
+        assert_eq!(usage.cache_read_input_tokens, 2);
+        let calls: Vec<_> = response
+            .content
+            .iter()
+            .filter_map(|block| match block {
+                ResponseContentBlock::ToolUse {
+                    id, name, input, ..
+                } => Some((id, name, input)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(
+            (calls[0].0.as_str(), calls[0].1.as_str()),
+            ("call_a", "bash")
+        );
+        assert_eq!(calls[0].2, &json!({"command": "ls"}));
+        assert_eq!(calls[1].2, &json!({"path": "a.txt"}));
+    }
+
+    #[test]
+    fn test_oai_stream_whole_tool_calls_without_index() {
+        // Calls sent whole without an index, then one without an id,
+        // finished with "stop"
+        let mut state = OaiStreamState::default();
+        for data in [
+            r#"{"choices":[{"delta":{"tool_calls":[{"id":"c1","function":{"name":"bash","arguments":{"command":"ls"}}}]}}]}"#,
+            r#"{"choices":[{"delta":{"tool_calls":[{"id":"c2","function":{"name":"bash","arguments":"{\"command\":\"pwd\"}"}}]}}]}"#,
+            r#"{"choices":[{"delta":{"tool_calls":[{"index":1,"function":{"name":"glob","arguments":"{}"}}]}}]}"#,
+            r#"{"choices":[{"delta":{},"finish_reason":"stop"}]}"#,
+        ] {
+            state.push(data, None).unwrap();
+        }
+        let response = state.into_response();
+        assert_eq!(response.stop_reason.as_deref(), Some("tool_use"));
+        let calls: Vec<_> = response
+            .content
+            .iter()
+            .filter_map(|block| match block {
+                ResponseContentBlock::ToolUse { id, input, .. } => Some((id.clone(), input)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(calls.len(), 3);
+        assert_eq!(calls[0], ("c1".to_string(), &json!({"command": "ls"})));
+        assert_eq!(calls[1], ("c2".to_string(), &json!({"command": "pwd"})));
+        // No id from the server: one is made up
+        assert!(calls[2].0.starts_with("call_"));
+    }
+
+    #[test]
+    fn test_oai_stream_error_event() {
+        let mut state = OaiStreamState::default();
+        state
+            .push(r#"{"choices":[{"delta":{"content":"Hel"}}]}"#, None)
+            .unwrap();
+        let err = state
+            .push(r#"{"error":{"message":"model crashed","code":500}}"#, None)
+            .unwrap_err();
+        assert!(err.contains("model crashed"));
+    }
+
+    #[test]
+    fn test_oai_stop_reason() {
+        assert_eq!(
+            oai_stop_reason(Some("stop".into()), true).as_deref(),
+            Some("tool_use")
+        );
+        assert_eq!(oai_stop_reason(None, true).as_deref(), Some("tool_use"));
+        assert_eq!(
+            oai_stop_reason(Some("stop".into()), false).as_deref(),
+            Some("end_turn")
+        );
+        // Cut off mid-call: the arguments are incomplete
+        assert_eq!(
+            oai_stop_reason(Some("length".into()), true).as_deref(),
+            Some("max_tokens")
+        );
+        assert_eq!(
+            oai_stop_reason(Some("function_call".into()), false).as_deref(),
+            Some("tool_use")
+        );
+        assert_eq!(
+            oai_stop_reason(Some("content_filter".into()), false).as_deref(),
+            Some("content_filter")
+        );
+    }
+
+    /// Serve one canned response per connection and return the request bodies.
+    fn spawn_body_server(responses: Vec<String>) -> (String, std::thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let mut requests = Vec::new();
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                stream
+                    .set_read_timeout(Some(Duration::from_millis(500)))
+                    .unwrap();
+                let mut req = Vec::new();
+                let mut buf = [0u8; 8192];
+                while let Ok(n) = stream.read(&mut buf) {
+                    if n == 0 {
+                        break;
+                    }
+                    req.extend_from_slice(&buf[..n]);
+                    let text = String::from_utf8_lossy(&req);
+                    if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                        let len = head
+                            .lines()
+                            .find_map(|l| {
+                                l.to_ascii_lowercase()
+                                    .strip_prefix("content-length:")
+                                    .map(|v| v.trim().parse::<usize>().unwrap_or(0))
+                            })
+                            .unwrap_or(0);
+                        if body.len() >= len {
+                            break;
+                        }
+                    }
+                }
+                let text = String::from_utf8_lossy(&req).to_string();
+                requests.push(
+                    text.split_once("\r\n\r\n")
+                        .map(|(_, b)| b.to_string())
+                        .unwrap_or_default(),
+                );
+                let _ = stream.write_all(response.as_bytes());
+            }
+            requests
+        });
+        (url, server)
+    }
+
+    #[tokio::test]
+    async fn test_openai_compat_stream_falls_back_without_stream_options() {
+        let rejected =
+            r#"{"error":{"message":"Unrecognized request argument supplied: stream_options"}}"#;
+        let sse = concat!(
+            "data: {\"choices\":[{\"delta\":{\"content\":\"Hi\"}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"tool_calls\":[{\"index\":0,\"id\":\"c1\",\"function\":{\"name\":\"bash\",\"arguments\":\"{}\"}}]}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{},\"finish_reason\":\"tool_calls\"}]}\n\n",
+            "data: [DONE]\n\n",
+        );
+        let (url, server) = spawn_body_server(vec![
+            format!(
+                "HTTP/1.1 400 Bad Request\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{rejected}",
+                rejected.len()
+            ),
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{sse}",
+                sse.len()
+            ),
+        ]);
+        let mut config: Config = serde_yaml::from_str("api_key: key\n").unwrap();
+        config.llm_provider = "custom".into();
+        config.model = "qwen2.5".into();
+        config.llm_base_url = Some(url);
+        let provider = OpenAiProvider::new(&config);
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let response = provider
+            .send_message_stream("sys", vec![], None, Some(&tx))
+            .await
+            .unwrap();
+
+        assert_eq!(rx.try_recv().unwrap(), "Hi");
+        assert_eq!(response.stop_reason.as_deref(), Some("tool_use"));
+        assert!(matches!(
+            &response.content[1],
+            ResponseContentBlock::ToolUse { name, .. } if name == "bash"
+        ));
+        let requests = server.join().unwrap();
+        assert!(requests[0].contains("\"stream_options\""));
+        assert!(!requests[1].contains("\"stream_options\""));
+        assert!(!provider.stream_usage.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_openai_chat_body_reasoning_params() {
+        let mut config: Config = serde_yaml::from_str("api_key: key\n").unwrap();
+        config.llm_provider = "openai".into();
+        config.model = "o3-mini".into();
+        let body = OpenAiProvider::new(&config).chat_body("sys", &[]);
+        assert_eq!(body["max_completion_tokens"], 8192);
+        assert!(body.get("max_tokens").is_none());
+        assert!(body.get("reasoning_effort").is_none());
+
+        config.model = "openrouter/gpt-4o".into();
+        config.thinking.enabled = true;
+        config.thinking.effort = Some("high".into());
+        let body = OpenAiProvider::new(&config).chat_body("sys", &[]);
+        assert_eq!(body["max_tokens"], 8192);
+        assert_eq!(body["reasoning_effort"], "high");
+        assert!(openai_is_reasoning_model("openai/gpt-5.2"));
+        assert!(!openai_is_reasoning_model("gpt-4o"));
+    }
+
+    #[test]
+    fn test_openai_chat_body_inference_params() {
+        let mut config: Config = serde_yaml::from_str("api_key: key\n").unwrap();
+        config.llm_provider = "openai".into();
+        config.model = "gpt-4o".into();
+        config.inference = InferenceParams {
+            temperature: Some(0.3),
+            top_p: None,
+            top_k: Some(40),
+            stop_sequences: vec!["###".into()],
+        };
+        let body = OpenAiProvider::new(&config).chat_body("sys", &[]);
+        assert_eq!(body["temperature"], 0.3);
+        assert_eq!(body["stop"], json!(["###"]));
+        assert!(body.get("top_p").is_none());
+        assert!(body.get("top_k").is_none());
+
+        config.llm_provider = "deepseek".into();
+        let body = OpenAiProvider::new(&config).chat_body("sys", &[]);
+        assert_eq!(body["top_k"], 40);
+
+        config.model = "o3-mini".into();
+        let body = OpenAiProvider::new(&config).chat_body("sys", &[]);
+        assert!(body.get("temperature").is_none());
+        assert!(body.get("stop").is_none());
+    }
+
     #[test]
     fn test_translate_oai_response_tool_calls() {
         let oai = OaiResponse {
             choices: vec![OaiChoice {
                 message: OaiMessage {
+                    reasoning_content: None,
+                    reasoning: None,
                     content: None,
                     tool_calls: Some(vec![OaiToolCall {
                         id: "call_1".into(),
@@ -1960,7 +3420,9 @@ mod tests {
         let resp = translate_oai_response(oai);
         assert_eq!(resp.stop_reason.as_deref(), Some("tool_use"));
         match &resp.content[0] {
-            ResponseContentBlock::ToolUse { id, name, input } => {
+            ResponseContentBlock::ToolUse {
+                id, name, input, ..
+            } => {
                 assert_eq!(id, "call_1");
                 assert_eq!(name, "bash");
                 assert_eq!(input["command"], "ls");
@@ -1988,6 +3450,8 @@ mod tests {
         let oai = OaiResponse {
             choices: vec![OaiChoice {
                 message: OaiMessage {
+                    reasoning_content: None,
+                    reasoning: None,
                     content: Some("partial".into()),
                     tool_calls: None,
                 },
@@ -2004,6 +3468,8 @@ mod tests {
         let oai = OaiResponse {
             choices: vec![OaiChoice {
                 message: OaiMessage {
+                    reasoning_content: None,
+                    reasoning: None,
                     content: Some("thinking...".into()),
                     tool_calls: Some(vec![OaiToolCall {
                         id: "c1".into(),
@@ -2060,12 +3526,15 @@ mod tests {
             vec![0],
             std::collections::HashMap::new(),
             tool_blocks,
+            std::collections::HashMap::new(),
             Some("tool_use".into()),
             None,
         );
         assert_eq!(resp.stop_reason.as_deref(), Some("tool_use"));
         match &resp.content[0] {
-            ResponseContentBlock::ToolUse { id, name, input } => {
+            ResponseContentBlock::ToolUse {
+                id, name, input, ..
+            } => {
                 assert_eq!(id, "call_1");
                 assert_eq!(name, "bash");
                 assert_eq!(input["command"], "ls");
@@ -2103,19 +3572,35 @@ mod tests {
             control_chat_ids: vec![],
             max_session_messages: 40,
             compact_keep_recent: 20,
+            session_idle_expiry_hours: 0,
+            session_expiry_summary: true,
             discord_bot_token: None,
             discord_allowed_channels: vec![],
             show_thinking: false,
+            thinking: crate::config::ThinkingConfig::default(),
+            inference: crate::config::InferenceParams::default(),
+            chat_inference: std::collections::HashMap::new(),
+            openrouter: crate::config::OpenRouterConfig::default(),
+            secret_patterns: vec![],
+            news_search: crate::config::NewsSearchConfig::default(),
+            maps: crate::config::MapsConfig::default(),
+            package_tracking: crate::config::PackageTrackingConfig::default(),
+            flight_status: crate::config::FlightStatusConfig::default(),
+            outbound_http: crate::config::OutboundHttpConfig::default(),
+            browsing_profiles_key: None,
+            tool_output_budgets: std::collections::HashMap::new(),
             web_enabled: false,
             web_host: "127.0.0.1".into(),
             web_port: 3900,
             web_auth_token: None,
+            web_public_url: None,
             web_max_inflight_per_session: 2,
             web_max_requests_per_window: 8,
             web_rate_window_seconds: 10,
             web_run_history_limit: 512,
             web_session_idle_ttl_seconds: 300,
             model_prices: vec![],
+            model_limits: vec![],
             embedding_provider: None,
             embedding_api_key: None,
             embedding_base_url: None,
@@ -2128,8 +3613,43 @@ mod tests {
             aws_secret_access_key: None,
             aws_session_token: None,
             aws_profile: None,
+            aws_bedrock_inference_profile: None,
+            aws_bedrock_inference_profiles: std::collections::HashMap::new(),
+            aws_bedrock_latency: None,
+            aws_endpoint_url: None,
+            aws_use_fips_endpoint: false,
+            ollama_keep_alive: None,
+            email_gateway: None,
             soul_path: None,
+            system_prompt_template: None,
             skip_tool_approval: false,
+            tool_approval_timeout_secs: 300,
+            workspace_quota_mb: 0,
+            workspace_tmp_ttl_hours: 0,
+            workspace_cleanup_interval_mins: 60,
+            image_max_dimension: 1568,
+            image_jpeg_quality: 85,
+            chart_font_path: None,
+            tts_model: "tts-1".into(),
+            tts_voice: "alloy".into(),
+            stt_backend: "openai".into(),
+            whisper_cpp_binary: "whisper-cli".into(),
+            whisper_cpp_model: None,
+            max_concurrent_agent_runs: 8,
+            outage_queue_size: 50,
+            outage_queue_ttl_mins: 60,
+            message_debounce_ms: 0,
+            digest_dedup_days: 14,
+            llm_max_retries: 3,
+            llm_retry_max_delay_secs: 60,
+            llm_trace: false,
+            startup_preflight: true,
+            coordination_redis_url: None,
+            coordination_key_prefix: "rayclaw".into(),
+            cache_backend: "memory".into(),
+            queue_backend: "memory".into(),
+            response_cache_ttl_secs: 0,
+            provider_credentials: std::collections::HashMap::new(),
             skills_dir: None,
             channels: std::collections::HashMap::new(),
         };
@@ -2161,19 +3681,35 @@ mod tests {
             control_chat_ids: vec![],
             max_session_messages: 40,
             compact_keep_recent: 20,
+            session_idle_expiry_hours: 0,
+            session_expiry_summary: true,
             discord_bot_token: None,
             discord_allowed_channels: vec![],
             show_thinking: false,
+            thinking: crate::config::ThinkingConfig::default(),
+            inference: crate::config::InferenceParams::default(),
+            chat_inference: std::collections::HashMap::new(),
+            openrouter: crate::config::OpenRouterConfig::default(),
+            secret_patterns: vec![],
+            news_search: crate::config::NewsSearchConfig::default(),
+            maps: crate::config::MapsConfig::default(),
+            package_tracking: crate::config::PackageTrackingConfig::default(),
+            flight_status: crate::config::FlightStatusConfig::default(),
+            outbound_http: crate::config::OutboundHttpConfig::default(),
+            browsing_profiles_key: None,
+            tool_output_budgets: std::collections::HashMap::new(),
             web_enabled: false,
             web_host: "127.0.0.1".into(),
             web_port: 3900,
             web_auth_token: None,
+            web_public_url: None,
             web_max_inflight_per_session: 2,
             web_max_requests_per_window: 8,
             web_rate_window_seconds: 10,
             web_run_history_limit: 512,
             web_session_idle_ttl_seconds: 300,
             model_prices: vec![],
+            model_limits: vec![],
             embedding_provider: None,
             embedding_api_key: None,
             embedding_base_url: None,
@@ -2186,8 +3722,43 @@ mod tests {
             aws_secret_access_key: None,
             aws_session_token: None,
             aws_profile: None,
+            aws_bedrock_inference_profile: None,
+            aws_bedrock_inference_profiles: std::collections::HashMap::new(),
+            aws_bedrock_latency: None,
+            aws_endpoint_url: None,
+            aws_use_fips_endpoint: false,
+            ollama_keep_alive: None,
+            email_gateway: None,
             soul_path: None,
+            system_prompt_template: None,
             skip_tool_approval: false,
+            tool_approval_timeout_secs: 300,
+            workspace_quota_mb: 0,
+            workspace_tmp_ttl_hours: 0,
+            workspace_cleanup_interval_mins: 60,
+            image_max_dimension: 1568,
+            image_jpeg_quality: 85,
+            chart_font_path: None,
+            tts_model: "tts-1".into(),
+            tts_voice: "alloy".into(),
+            stt_backend: "openai".into(),
+            whisper_cpp_binary: "whisper-cli".into(),
+            whisper_cpp_model: None,
+            max_concurrent_agent_runs: 8,
+            outage_queue_size: 50,
+            outage_queue_ttl_mins: 60,
+            message_debounce_ms: 0,
+            digest_dedup_days: 14,
+            llm_max_retries: 3,
+            llm_retry_max_delay_secs: 60,
+            llm_trace: false,
+            startup_preflight: true,
+            coordination_redis_url: None,
+            coordination_key_prefix: "rayclaw".into(),
+            cache_backend: "memory".into(),
+            queue_backend: "memory".into(),
+            response_cache_ttl_secs: 0,
+            provider_credentials: std::collections::HashMap::new(),
             skills_dir: None,
             channels: std::collections::HashMap::new(),
         };
@@ -2284,19 +3855,35 @@ mod tests {
             control_chat_ids: vec![],
             max_session_messages: 40,
             compact_keep_recent: 20,
+            session_idle_expiry_hours: 0,
+            session_expiry_summary: true,
             discord_bot_token: None,
             discord_allowed_channels: vec![],
             show_thinking: false,
+            thinking: crate::config::ThinkingConfig::default(),
+            inference: crate::config::InferenceParams::default(),
+            chat_inference: std::collections::HashMap::new(),
+            openrouter: crate::config::OpenRouterConfig::default(),
+            secret_patterns: vec![],
+            news_search: crate::config::NewsSearchConfig::default(),
+            maps: crate::config::MapsConfig::default(),
+            package_tracking: crate::config::PackageTrackingConfig::default(),
+            flight_status: crate::config::FlightStatusConfig::default(),
+            outbound_http: crate::config::OutboundHttpConfig::default(),
+            browsing_profiles_key: None,
+            tool_output_budgets: std::collections::HashMap::new(),
             web_enabled: false,
             web_host: "127.0.0.1".into(),
             web_port: 3900,
             web_auth_token: None,
+            web_public_url: None,
             web_max_inflight_per_session: 2,
             web_max_requests_per_window: 8,
             web_rate_window_seconds: 10,
             web_run_history_limit: 512,
             web_session_idle_ttl_seconds: 300,
             model_prices: vec![],
+            model_limits: vec![],
             embedding_provider: None,
             embedding_api_key: None,
             embedding_base_url: None,
@@ -2309,8 +3896,43 @@ mod tests {
             aws_secret_access_key: None,
             aws_session_token: None,
             aws_profile: None,
+            aws_bedrock_inference_profile: None,
+            aws_bedrock_inference_profiles: std::collections::HashMap::new(),
+            aws_bedrock_latency: None,
+            aws_endpoint_url: None,
+            aws_use_fips_endpoint: false,
+            ollama_keep_alive: None,
+            email_gateway: None,
             soul_path: None,
+            system_prompt_template: None,
             skip_tool_approval: false,
+            tool_approval_timeout_secs: 300,
+            workspace_quota_mb: 0,
+            workspace_tmp_ttl_hours: 0,
+            workspace_cleanup_interval_mins: 60,
+            image_max_dimension: 1568,
+            image_jpeg_quality: 85,
+            chart_font_path: None,
+            tts_model: "tts-1".into(),
+            tts_voice: "alloy".into(),
+            stt_backend: "openai".into(),
+            whisper_cpp_binary: "whisper-cli".into(),
+            whisper_cpp_model: None,
+            max_concurrent_agent_runs: 8,
+            outage_queue_size: 50,
+            outage_queue_ttl_mins: 60,
+            message_debounce_ms: 0,
+            digest_dedup_days: 14,
+            llm_max_retries: 3,
+            llm_retry_max_delay_secs: 60,
+            llm_trace: false,
+            startup_preflight: true,
+            coordination_redis_url: None,
+            coordination_key_prefix: "rayclaw".into(),
+            cache_backend: "memory".into(),
+            queue_backend: "memory".into(),
+            response_cache_ttl_secs: 0,
+            provider_credentials: std::collections::HashMap::new(),
             skills_dir: None,
             channels: std::collections::HashMap::new(),
         };
@@ -2446,19 +4068,35 @@ mod tests {
             control_chat_ids: vec![],
             max_session_messages: 40,
             compact_keep_recent: 20,
+            session_idle_expiry_hours: 0,
+            session_expiry_summary: true,
             discord_bot_token: None,
             discord_allowed_channels: vec![],
             show_thinking: false,
+            thinking: crate::config::ThinkingConfig::default(),
+            inference: crate::config::InferenceParams::default(),
+            chat_inference: std::collections::HashMap::new(),
+            openrouter: crate::config::OpenRouterConfig::default(),
+            secret_patterns: vec![],
+            news_search: crate::config::NewsSearchConfig::default(),
+            maps: crate::config::MapsConfig::default(),
+            package_tracking: crate::config::PackageTrackingConfig::default(),
+            flight_status: crate::config::FlightStatusConfig::default(),
+            outbound_http: crate::config::OutboundHttpConfig::default(),
+            browsing_profiles_key: None,
+            tool_output_budgets: std::collections::HashMap::new(),
             web_enabled: false,
             web_host: "127.0.0.1".into(),
             web_port: 3900,
             web_auth_token: None,
+            web_public_url: None,
             web_max_inflight_per_session: 2,
             web_max_requests_per_window: 8,
             web_rate_window_seconds: 10,
             web_run_history_limit: 512,
             web_session_idle_ttl_seconds: 300,
             model_prices: vec![],
+            model_limits: vec![],
             embedding_provider: None,
             embedding_api_key: None,
             embedding_base_url: None,
@@ -2471,8 +4109,43 @@ mod tests {
             aws_secret_access_key: None,
             aws_session_token: None,
             aws_profile: None,
+            aws_bedrock_inference_profile: None,
+            aws_bedrock_inference_profiles: std::collections::HashMap::new(),
+            aws_bedrock_latency: None,
+            aws_endpoint_url: None,
+            aws_use_fips_endpoint: false,
+            ollama_keep_alive: None,
+            email_gateway: None,
             soul_path: None,
+            system_prompt_template: None,
             skip_tool_approval: false,
+            tool_approval_timeout_secs: 300,
+            workspace_quota_mb: 0,
+            workspace_tmp_ttl_hours: 0,
+            workspace_cleanup_interval_mins: 60,
+            image_max_dimension: 1568,
+            image_jpeg_quality: 85,
+            chart_font_path: None,
+            tts_model: "tts-1".into(),
+            tts_voice: "alloy".into(),
+            stt_backend: "openai".into(),
+            whisper_cpp_binary: "whisper-cli".into(),
+            whisper_cpp_model: None,
+            max_concurrent_agent_runs: 8,
+            outage_queue_size: 50,
+            outage_queue_ttl_mins: 60,
+            message_debounce_ms: 0,
+            digest_dedup_days: 14,
+            llm_max_retries: 3,
+            llm_retry_max_delay_secs: 60,
+            llm_trace: false,
+            startup_preflight: true,
+            coordination_redis_url: None,
+            coordination_key_prefix: "rayclaw".into(),
+            cache_backend: "memory".into(),
+            queue_backend: "memory".into(),
+            response_cache_ttl_secs: 0,
+            provider_credentials: std::collections::HashMap::new(),
             skills_dir: None,
             channels: std::collections::HashMap::new(),
         };
@@ -2546,6 +4219,7 @@ mod tests {
                     id: "t1".into(),
                     name: "bash".into(),
                     input: json!({}),
+                    signature: None,
                 }]),
             },
             Message {
@@ -2579,6 +4253,23 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_strip_tool_use_signatures() {
+        let messages = vec![Message {
+            role: "assistant".into(),
+            content: MessageContent::Blocks(vec![ContentBlock::ToolUse {
+                id: "t1".into(),
+                name: "bash".into(),
+                input: json!({}),
+                signature: Some("sig".into()),
+            }]),
+        }];
+        let out = strip_tool_use_signatures(messages);
+        let json = serde_json::to_value(&out[0].content).unwrap();
+        assert_eq!(json[0]["id"], "t1");
+        assert!(json[0].get("signature").is_none());
+    }
+
     #[test]
     fn test_sanitize_messages_drops_empty_user_message() {
         // User message with only orphaned tool_results → dropped entirely
@@ -2804,15 +4495,43 @@ data: [DONE]
 
     fn make_anthropic_provider(cache_ttl: &str) -> AnthropicProvider {
         AnthropicProvider {
+            retry: RetryPolicy::default(),
             http: reqwest::Client::new(),
             api_key: "test-key".into(),
             model: "claude-sonnet-4-5-20250929".into(),
             max_tokens: 4096,
             base_url: "https://api.anthropic.com/v1/messages".into(),
             prompt_cache_ttl: cache_ttl.into(),
+            limits: crate::model_limits::builtin_limits("claude-sonnet-4-5-20250929")
+                .unwrap_or_default(),
+            thinking_budget: None,
+            inference: InferenceParams::default(),
         }
     }
 
+    #[test]
+    fn test_build_request_body_inference_params() {
+        let mut provider = make_anthropic_provider("none");
+        provider.inference = InferenceParams {
+            temperature: Some(0.0),
+            top_p: Some(0.9),
+            top_k: Some(20),
+            stop_sequences: vec!["END".into()],
+        };
+        let body = provider.build_request_body("sys", &[], None, None);
+        assert_eq!(body["temperature"], 0.0);
+        assert_eq!(body["top_p"], 0.9);
+        assert_eq!(body["top_k"], 20);
+        assert_eq!(body["stop_sequences"], json!(["END"]));
+
+        // Extended thinking fixes temperature and top_k
+        provider.thinking_budget = Some(2048);
+        let body = provider.build_request_body("sys", &[], None, None);
+        assert!(body.get("temperature").is_none());
+        assert!(body.get("top_k").is_none());
+        assert_eq!(body["top_p"], 0.9);
+    }
+
     fn sample_tools() -> Vec<ToolDefinition> {
         vec![
             ToolDefinition {
@@ -2922,4 +4641,103 @@ data: [DONE]
         // Only last tool should have cache_control
         assert_eq!(tools_arr[1]["cache_control"]["type"], "ephemeral");
     }
+
+    #[test]
+    fn test_anthropic_thinking_request_and_stream() {
+        let mut provider = make_anthropic_provider("none");
+        let history = vec![
+            Message {
+                role: "user".into(),
+                content: MessageContent::Text("hi".into()),
+            },
+            Message {
+                role: "assistant".into(),
+                content: MessageContent::Blocks(vec![ContentBlock::Thinking {
+                    thinking: "hmm".into(),
+                    signature: Some("sig".into()),
+                }]),
+            },
+            Message {
+                role: "user".into(),
+                content: MessageContent::Text("again".into()),
+            },
+        ];
+        let body = provider.build_request_body("sys", &history, None, None);
+        assert!(body.get("thinking").is_none());
+        // Thinking off: the thinking-only assistant turn is dropped and the
+        // user turns merge
+        assert_eq!(provider.prepare_messages(history.clone()).len(), 1);
+
+        provider.thinking_budget = Some(2048);
+        let body = provider.build_request_body("sys", &history, None, None);
+        assert_eq!(body["thinking"]["type"], "enabled");
+        assert_eq!(body["thinking"]["budget_tokens"], 2048);
+        assert_eq!(provider.prepare_messages(history).len(), 3);
+
+        let mut stop_reason = None;
+        let mut usage = None;
+        let mut text_blocks = std::collections::HashMap::new();
+        let mut tool_blocks = std::collections::HashMap::new();
+        let mut thinking_blocks = std::collections::HashMap::new();
+        let mut ordered = Vec::new();
+        for data in [
+            r#"{"type":"content_block_start","index":0,"content_block":{"type":"thinking","thinking":""}}"#,
+            r#"{"type":"content_block_delta","index":0,"delta":{"type":"thinking_delta","thinking":"Plan "}}"#,
+            r#"{"type":"content_block_delta","index":0,"delta":{"type":"thinking_delta","thinking":"it."}}"#,
+            r#"{"type":"content_block_delta","index":0,"delta":{"type":"signature_delta","signature":"EqQB"}}"#,
+            r#"{"type":"content_block_start","index":1,"content_block":{"type":"redacted_thinking","data":"xyz"}}"#,
+            r#"{"type":"content_block_start","index":2,"content_block":{"type":"text","text":""}}"#,
+            r#"{"type":"content_block_delta","index":2,"delta":{"type":"text_delta","text":"Hi"}}"#,
+        ] {
+            process_anthropic_stream_event(
+                data,
+                None,
+                &mut stop_reason,
+                &mut usage,
+                &mut text_blocks,
+                &mut tool_blocks,
+                &mut thinking_blocks,
+                &mut ordered,
+            );
+        }
+        let resp = build_stream_response(
+            ordered,
+            text_blocks,
+            tool_blocks,
+            thinking_blocks,
+            Some("end_turn".into()),
+            None,
+        );
+        assert_eq!(resp.content.len(), 3);
+        match &resp.content[0] {
+            ResponseContentBlock::Thinking {
+                thinking,
+                signature,
+            } => {
+                assert_eq!(thinking, "Plan it.");
+                assert_eq!(signature.as_deref(), Some("EqQB"));
+            }
+            _ => panic!("Expected Thinking"),
+        }
+        assert!(matches!(
+            &resp.content[1],
+            ResponseContentBlock::RedactedThinking { data } if data == "xyz"
+        ));
+    }
+
+    #[test]
+    fn test_provider_capabilities() {
+        let anthropic = make_anthropic_provider("none");
+        let caps = anthropic.capabilities();
+        assert!(caps.vision && caps.tools && caps.streaming && !caps.prompt_caching);
+        assert_eq!(caps.max_context_tokens, Some(200_000));
+        // Capped by the configured max_tokens
+        assert_eq!(caps.max_output_tokens, Some(4096));
+        assert!(make_anthropic_provider("5m").capabilities().prompt_caching);
+
+        assert!(openai_model_supports_vision("gpt-4o"));
+        assert!(openai_model_supports_vision("some-unknown-model"));
+        assert!(!openai_model_supports_vision("deepseek-chat"));
+        assert!(!openai_model_supports_vision("o3-mini"));
+    }
 }
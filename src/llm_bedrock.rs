@@ -8,34 +8,80 @@
 // Authentication: AWS SigV4 signing
 // ---------------------------------------------------------------------------
 
+use std::sync::atomic::{AtomicI64, Ordering};
+
 use async_trait::async_trait;
 use futures_util::StreamExt;
 use hmac::{Hmac, Mac};
+use num_bigint::BigUint;
 use sha2::{Digest, Sha256};
 use tokio::sync::mpsc::UnboundedSender;
-use tracing::warn;
+use tracing::{info, warn};
 
-use crate::config::Config;
+use crate::aws_profile::AwsConfigFiles;
+use crate::config::{Config, InferenceParams, ThinkingConfig};
 use crate::error::RayClawError;
-use crate::llm::{normalize_stop_reason, sanitize_messages, LlmProvider};
+use crate::llm::{
+    normalize_stop_reason, sanitize_messages, send_with_retry, strip_thinking_blocks, LlmProvider,
+    ProviderCapabilities, RetryPolicy,
+};
 use crate::llm_types::{
     ContentBlock, Message, MessageContent, MessagesResponse, ResponseContentBlock, ToolDefinition,
     Usage,
 };
+use crate::model_limits::ModelLimits;
 
 // ---------------------------------------------------------------------------
 // AWS Credentials
 // ---------------------------------------------------------------------------
 
+#[derive(Clone)]
 pub(crate) struct AwsCredentials {
     pub access_key_id: String,
     pub secret_access_key: String,
     pub session_token: Option<String>,
     pub region: String,
+    /// When temporary credentials (instance role, AssumeRole, SSO,
+    /// credential_process) expire
+    pub expiration: Option<chrono::DateTime<chrono::Utc>>,
+    /// Where they came from, re-queried by [`AwsCredentials::refresh`]
+    pub source: CredentialSource,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum CredentialSource {
+    /// Keys from config or the environment; never refreshed
+    Static,
+    /// A shared config profile
+    Profile(String),
+    /// The EC2 instance role, via IMDSv2
+    InstanceMetadata,
 }
 
+/// Temporary credentials are refreshed this long before they expire.
+const CREDENTIAL_REFRESH_WINDOW_SECS: i64 = 5 * 60;
+
 impl AwsCredentials {
+    /// Blocking wrapper around [`AwsCredentials::load`] for provider
+    /// construction, which is synchronous but may run inside the runtime.
     pub fn resolve(config: &Config) -> Result<Self, RayClawError> {
+        std::thread::scope(|scope| {
+            scope
+                .spawn(|| {
+                    tokio::runtime::Builder::new_current_thread()
+                        .enable_all()
+                        .build()
+                        .map_err(|e| {
+                            RayClawError::Config(format!("AWS credential runtime error: {e}"))
+                        })?
+                        .block_on(Self::load(config))
+                })
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+        })
+    }
+
+    pub async fn load(config: &Config) -> Result<Self, RayClawError> {
         let access_key = config
             .aws_access_key_id
             .clone()
@@ -66,92 +112,112 @@ impl AwsCredentials {
             .or_else(|| std::env::var("AWS_DEFAULT_REGION").ok())
             .filter(|s| !s.trim().is_empty());
 
-        // If no explicit credentials, try ~/.aws/credentials
-        let (access_key, secret_key, session_token, region) =
-            if let (Some(ak), Some(sk)) = (access_key.clone(), secret_key.clone()) {
-                (ak, sk, session_token, region)
-            } else {
-                let profile_name = config
-                    .aws_profile
-                    .clone()
-                    .filter(|s| !s.trim().is_empty())
-                    .or_else(|| std::env::var("AWS_PROFILE").ok())
-                    .filter(|s| !s.trim().is_empty())
-                    .unwrap_or_else(|| "default".into());
-
-                let (file_ak, file_sk, file_token) = parse_aws_credentials_file(&profile_name);
-                let ak = access_key.or(file_ak);
-                let sk = secret_key.or(file_sk);
-                let token = session_token.or(file_token);
-
-                // Also check ~/.aws/config for region
-                let region = region.or_else(|| parse_aws_config_region(&profile_name));
+        if let (Some(ak), Some(sk)) = (access_key, secret_key) {
+            return Ok(AwsCredentials {
+                access_key_id: ak,
+                secret_access_key: sk,
+                session_token,
+                region: region.unwrap_or_else(|| "us-east-1".into()),
+                expiration: None,
+                source: CredentialSource::Static,
+            });
+        }
 
-                (
-                    ak.unwrap_or_default(),
-                    sk.unwrap_or_default(),
-                    token,
-                    region,
-                )
-            };
+        // No explicit keys: the shared config profile (static keys, SSO,
+        // credential_process or an assumed role)
+        let profile_name = config
+            .aws_profile
+            .clone()
+            .filter(|s| !s.trim().is_empty())
+            .or_else(|| std::env::var("AWS_PROFILE").ok())
+            .filter(|s| !s.trim().is_empty())
+            .unwrap_or_else(|| "default".into());
+        let region = region.or_else(|| parse_aws_config_region(&profile_name));
 
-        // Last resort: EC2 Instance Metadata Service (IMDSv2)
-        let (access_key, secret_key, session_token, region) = if !access_key.is_empty()
-            && !secret_key.is_empty()
+        if let Some(creds) =
+            crate::aws_profile::resolve_profile(&AwsConfigFiles::locate(), &profile_name).await?
         {
-            (access_key, secret_key, session_token, region)
-        } else {
-            match fetch_imds_credentials() {
-                Ok((ak, sk, token, imds_region)) => {
-                    let region = region.or(imds_region);
-                    (ak, sk, Some(token), region)
-                }
-                Err(_) => {
-                    return Err(RayClawError::Config(
-                            "AWS credentials not found. Set aws_access_key_id/aws_secret_access_key in config, \
-                             AWS_ACCESS_KEY_ID/AWS_SECRET_ACCESS_KEY env vars, \
-                             configure ~/.aws/credentials, \
-                             or attach an IAM role to your EC2 instance"
-                                .into(),
-                        ));
-                }
-            }
-        };
+            return Ok(AwsCredentials {
+                access_key_id: creds.access_key_id,
+                secret_access_key: creds.secret_access_key,
+                session_token: creds.session_token,
+                region: region.unwrap_or_else(|| "us-east-1".into()),
+                expiration: creds.expiration,
+                source: CredentialSource::Profile(profile_name),
+            });
+        }
 
-        let region = region.unwrap_or_else(|| "us-east-1".into());
+        // Last resort: EC2 Instance Metadata Service (IMDSv2)
+        match fetch_imds_credentials().await {
+            Ok(imds) => Ok(AwsCredentials {
+                access_key_id: imds.access_key_id,
+                secret_access_key: imds.secret_access_key,
+                session_token: Some(imds.session_token),
+                region: region.or(imds.region).unwrap_or_else(|| "us-east-1".into()),
+                expiration: imds.expiration,
+                source: CredentialSource::InstanceMetadata,
+            }),
+            Err(_) => Err(RayClawError::Config(
+                "AWS credentials not found. Set aws_access_key_id/aws_secret_access_key in config, \
+                 AWS_ACCESS_KEY_ID/AWS_SECRET_ACCESS_KEY env vars, \
+                 configure a profile in ~/.aws/credentials or ~/.aws/config (keys, SSO, \
+                 credential_process or role_arn), \
+                 or attach an IAM role to your EC2 instance"
+                    .into(),
+            )),
+        }
+    }
 
+    /// Whether these are temporary credentials due for a refresh.
+    pub fn needs_refresh(&self, now: chrono::DateTime<chrono::Utc>) -> bool {
+        self.expiration.is_some_and(|expires| {
+            expires - now < chrono::Duration::seconds(CREDENTIAL_REFRESH_WINDOW_SECS)
+        })
+    }
+
+    /// Fresh credentials from the same source, keeping the region (which
+    /// may have been moved to a model ARN's region).
+    pub async fn refresh(&self) -> Result<Self, RayClawError> {
+        let (access_key_id, secret_access_key, session_token, expiration) = match &self.source {
+            CredentialSource::Static => return Ok(self.clone()),
+            CredentialSource::Profile(profile) => {
+                let creds = crate::aws_profile::resolve_profile(&AwsConfigFiles::locate(), profile)
+                    .await?
+                    .ok_or_else(|| {
+                        RayClawError::Config(format!(
+                            "AWS profile {profile} no longer has credentials"
+                        ))
+                    })?;
+                (
+                    creds.access_key_id,
+                    creds.secret_access_key,
+                    creds.session_token,
+                    creds.expiration,
+                )
+            }
+            CredentialSource::InstanceMetadata => {
+                let imds = fetch_imds_credentials().await?;
+                (
+                    imds.access_key_id,
+                    imds.secret_access_key,
+                    Some(imds.session_token),
+                    imds.expiration,
+                )
+            }
+        };
         Ok(AwsCredentials {
-            access_key_id: access_key,
-            secret_access_key: secret_key,
+            access_key_id,
+            secret_access_key,
             session_token,
-            region,
+            region: self.region.clone(),
+            expiration,
+            source: self.source.clone(),
         })
     }
 }
 
-fn parse_aws_credentials_file(profile: &str) -> (Option<String>, Option<String>, Option<String>) {
-    let path = dirs_or_home().join(".aws").join("credentials");
-    parse_ini_profile(
-        &path,
-        profile,
-        &[
-            "aws_access_key_id",
-            "aws_secret_access_key",
-            "aws_session_token",
-        ],
-    )
-    .map(|vals| {
-        (
-            vals.get("aws_access_key_id").cloned(),
-            vals.get("aws_secret_access_key").cloned(),
-            vals.get("aws_session_token").cloned(),
-        )
-    })
-    .unwrap_or((None, None, None))
-}
-
 fn parse_aws_config_region(profile: &str) -> Option<String> {
-    let path = dirs_or_home().join(".aws").join("config");
+    let path = AwsConfigFiles::locate().config;
     // In ~/.aws/config, profiles are [profile name] except [default]
     let section = if profile == "default" {
         "default".to_string()
@@ -161,10 +227,17 @@ fn parse_aws_config_region(profile: &str) -> Option<String> {
     parse_ini_profile(&path, &section, &["region"]).and_then(|vals| vals.get("region").cloned())
 }
 
+pub(crate) struct ImdsCredentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub session_token: String,
+    pub expiration: Option<chrono::DateTime<chrono::Utc>>,
+    pub region: Option<String>,
+}
+
 /// Fetch temporary credentials from EC2 Instance Metadata Service (IMDSv2).
-/// Returns (access_key, secret_key, session_token, optional_region).
-fn fetch_imds_credentials() -> Result<(String, String, String, Option<String>), RayClawError> {
-    let client = reqwest::blocking::Client::builder()
+pub(crate) async fn fetch_imds_credentials() -> Result<ImdsCredentials, RayClawError> {
+    let client = reqwest::Client::builder()
         .timeout(std::time::Duration::from_secs(2))
         .build()
         .map_err(|e| RayClawError::Config(format!("IMDS HTTP client error: {e}")))?;
@@ -174,7 +247,10 @@ fn fetch_imds_credentials() -> Result<(String, String, String, Option<String>),
         .put("http://169.254.169.254/latest/api/token")
         .header("X-aws-ec2-metadata-token-ttl-seconds", "21600")
         .send()
-        .and_then(|r| r.text())
+        .await
+        .map_err(|e| RayClawError::Config(format!("IMDS token request failed: {e}")))?
+        .text()
+        .await
         .map_err(|e| RayClawError::Config(format!("IMDS token request failed: {e}")))?;
 
     // Get the IAM role name
@@ -182,7 +258,10 @@ fn fetch_imds_credentials() -> Result<(String, String, String, Option<String>),
         .get("http://169.254.169.254/latest/meta-data/iam/security-credentials/")
         .header("X-aws-ec2-metadata-token", &token)
         .send()
-        .and_then(|r| r.text())
+        .await
+        .map_err(|e| RayClawError::Config(format!("IMDS role lookup failed: {e}")))?
+        .text()
+        .await
         .map_err(|e| RayClawError::Config(format!("IMDS role lookup failed: {e}")))?;
     let role = role.trim().to_string();
     if role.is_empty() {
@@ -198,7 +277,10 @@ fn fetch_imds_credentials() -> Result<(String, String, String, Option<String>),
         .get(&creds_url)
         .header("X-aws-ec2-metadata-token", &token)
         .send()
-        .and_then(|r| r.json())
+        .await
+        .map_err(|e| RayClawError::Config(format!("IMDS credentials fetch failed: {e}")))?
+        .json()
+        .await
         .map_err(|e| RayClawError::Config(format!("IMDS credentials fetch failed: {e}")))?;
 
     let ak = creds_json["AccessKeyId"].as_str().unwrap_or("").to_string();
@@ -207,6 +289,10 @@ fn fetch_imds_credentials() -> Result<(String, String, String, Option<String>),
         .unwrap_or("")
         .to_string();
     let session_token = creds_json["Token"].as_str().unwrap_or("").to_string();
+    let expiration = creds_json["Expiration"]
+        .as_str()
+        .and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok())
+        .map(|t| t.with_timezone(&chrono::Utc));
 
     if ak.is_empty() || sk.is_empty() {
         return Err(RayClawError::Config(
@@ -215,22 +301,28 @@ fn fetch_imds_credentials() -> Result<(String, String, String, Option<String>),
     }
 
     // Try to get region from IMDS placement data
-    let region = client
+    let region = match client
         .get("http://169.254.169.254/latest/meta-data/placement/region")
         .header("X-aws-ec2-metadata-token", &token)
         .send()
-        .and_then(|r| r.text())
-        .ok()
-        .map(|r| r.trim().to_string())
-        .filter(|r| !r.is_empty());
-
-    Ok((ak, sk, session_token, region))
-}
+        .await
+    {
+        Ok(response) => response
+            .text()
+            .await
+            .ok()
+            .map(|r| r.trim().to_string())
+            .filter(|r| !r.is_empty()),
+        Err(_) => None,
+    };
 
-fn dirs_or_home() -> std::path::PathBuf {
-    std::env::var("HOME")
-        .map(std::path::PathBuf::from)
-        .unwrap_or_else(|_| std::path::PathBuf::from("/root"))
+    Ok(ImdsCredentials {
+        access_key_id: ak,
+        secret_access_key: sk,
+        session_token,
+        expiration,
+        region,
+    })
 }
 
 fn parse_ini_profile(
@@ -292,9 +384,56 @@ fn sigv4_signing_key(secret: &str, date: &str, region: &str, service: &str) -> V
     hmac_sha256(&k_service, b"aws4_request")
 }
 
+/// SigV4 canonical URI for services other than S3: every path segment is
+/// URI-encoded again, so an already-encoded model ID or ARN (`%3A`) is
+/// signed as `%253A`.
+fn canonical_uri(path: &str) -> String {
+    path.split('/')
+        .map(|segment| urlencoding::encode(segment).into_owned())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// SigV4 canonical request for `url` and its signed header list. `host`
+/// and `x-amz-date` are always signed, plus the `extra` (lowercase) headers.
+fn canonical_request(
+    method: &str,
+    url: &reqwest::Url,
+    body: &[u8],
+    amz_date: &str,
+    extra: &[(&str, &str)],
+) -> (String, String) {
+    // A non-default port (e.g. a local endpoint override) is part of the Host header
+    let host = match url.port() {
+        Some(port) => format!("{}:{port}", url.host_str().unwrap_or("")),
+        None => url.host_str().unwrap_or("").to_string(),
+    };
+    let path = canonical_uri(url.path());
+    let payload_hash = sha256_hex(body);
+
+    // Canonical headers must be sorted by name
+    let mut headers = vec![("host", host.as_str()), ("x-amz-date", amz_date)];
+    headers.extend_from_slice(extra);
+    headers.sort();
+    let canonical_headers: String = headers
+        .iter()
+        .map(|(name, value)| format!("{name}:{value}\n"))
+        .collect();
+    let signed_headers = headers
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>()
+        .join(";");
+
+    (
+        format!("{method}\n{path}\n\n{canonical_headers}\n{signed_headers}\n{payload_hash}"),
+        signed_headers,
+    )
+}
+
 /// Sign a request and return the headers to add (Authorization, X-Amz-Date, optionally X-Amz-Security-Token).
 #[allow(clippy::too_many_arguments)]
-fn sign_request(
+pub(crate) fn sign_request(
     method: &str,
     url: &reqwest::Url,
     body: &[u8],
@@ -308,63 +447,234 @@ fn sign_request(
     let date_stamp = now.format("%Y%m%d").to_string();
     let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
 
-    let host = url.host_str().unwrap_or("");
-    let path = url.path();
+    let extra: Vec<(&str, &str)> = session_token
+        .map(|token| ("x-amz-security-token", token))
+        .into_iter()
+        .collect();
+    let (canonical_request, signed_headers) =
+        canonical_request(method, url, body, &amz_date, &extra);
 
-    let payload_hash = sha256_hex(body);
+    let credential_scope = format!("{date_stamp}/{region}/{service}/aws4_request");
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{amz_date}\n{credential_scope}\n{}",
+        sha256_hex(canonical_request.as_bytes())
+    );
 
-    // Canonical headers (must be sorted)
-    let mut signed_headers_list = vec!["host", "x-amz-date"];
-    let mut canonical_headers = format!("host:{host}\nx-amz-date:{amz_date}\n");
+    let signing_key = sigv4_signing_key(secret_key, &date_stamp, region, service);
+    let signature = hex::encode(hmac_sha256(&signing_key, string_to_sign.as_bytes()));
 
+    let authorization = format!(
+        "AWS4-HMAC-SHA256 Credential={access_key}/{credential_scope}, SignedHeaders={signed_headers}, Signature={signature}"
+    );
+
+    let mut headers = vec![
+        ("Authorization".into(), authorization),
+        ("X-Amz-Date".into(), amz_date),
+    ];
     if let Some(token) = session_token {
-        signed_headers_list.push("x-amz-security-token");
-        canonical_headers.push_str(&format!("x-amz-security-token:{token}\n"));
-    }
-    // Ensure sorted order
-    signed_headers_list.sort();
-    let signed_headers = signed_headers_list.join(";");
-
-    // Rebuild canonical headers in sorted order
-    let mut sorted_headers = String::new();
-    for h in &signed_headers_list {
-        match *h {
-            "host" => sorted_headers.push_str(&format!("host:{host}\n")),
-            "x-amz-date" => sorted_headers.push_str(&format!("x-amz-date:{amz_date}\n")),
-            "x-amz-security-token" => {
-                sorted_headers.push_str(&format!(
-                    "x-amz-security-token:{}\n",
-                    session_token.unwrap_or("")
-                ));
-            }
-            _ => {}
+        headers.push(("X-Amz-Security-Token".into(), token.to_string()));
+    }
+    headers
+}
+
+// ---------------------------------------------------------------------------
+// AWS SigV4a Signing
+// ---------------------------------------------------------------------------
+//
+// SigV4a signs for a set of regions with an ECDSA P-256 key derived from
+// the secret key, instead of an HMAC key scoped to one region.
+
+const SIGV4A_ALGORITHM: &str = "AWS4-ECDSA-P256-SHA256";
+
+/// NIST P-256 field prime, group order and base point.
+struct P256 {
+    p: BigUint,
+    n: BigUint,
+    gx: BigUint,
+    gy: BigUint,
+}
+
+fn p256() -> &'static P256 {
+    static CURVE: std::sync::OnceLock<P256> = std::sync::OnceLock::new();
+    CURVE.get_or_init(|| {
+        let hex = |s: &str| BigUint::parse_bytes(s.as_bytes(), 16).expect("P-256 constant");
+        P256 {
+            p: hex("ffffffff00000001000000000000000000000000ffffffffffffffffffffffff"),
+            n: hex("ffffffff00000000ffffffffffffffffbce6faada7179e84f3b9cac2fc632551"),
+            gx: hex("6b17d1f2e12c4247f8bce6e563a440f277037d812deb33a0f4a13945d898c296"),
+            gy: hex("4fe342e2fe1a7f9b8ee7eb4a7c0f9e162bce33576b315ececbb6406837bf51f5"),
+        }
+    })
+}
+
+/// `d`·G on P-256 as an uncompressed SEC 1 point (`04 || x || y`). Uses
+/// Jacobian coordinates with a single inversion at the end. Not constant
+/// time, which is acceptable for deriving our own signing key.
+fn p256_public_key(d: &BigUint) -> Option<Vec<u8>> {
+    let c = p256();
+    let p = &c.p;
+    let sub = |a: &BigUint, b: &BigUint| (a + p - b) % p;
+    let mul = |a: &BigUint, b: &BigUint| (a * b) % p;
+
+    // (X, Y, Z) with x = X/Z², y = Y/Z³; Z = 0 is the point at infinity
+    let double = |(x, y, z): &(BigUint, BigUint, BigUint)| {
+        if *z == BigUint::ZERO || *y == BigUint::ZERO {
+            return (BigUint::from(1u8), BigUint::from(1u8), BigUint::ZERO);
         }
+        let delta = mul(z, z);
+        let gamma = mul(y, y);
+        let beta = mul(x, &gamma);
+        // a = -3: alpha = 3(x - delta)(x + delta)
+        let alpha = mul(&(BigUint::from(3u8) * sub(x, &delta)), &((x + &delta) % p));
+        let x3 = sub(&mul(&alpha, &alpha), &((BigUint::from(8u8) * &beta) % p));
+        let yz = (y + z) % p;
+        let z3 = sub(&sub(&mul(&yz, &yz), &gamma), &delta);
+        let y3 = sub(
+            &mul(&alpha, &sub(&((BigUint::from(4u8) * &beta) % p), &x3)),
+            &((BigUint::from(8u8) * mul(&gamma, &gamma)) % p),
+        );
+        (x3, y3, z3)
+    };
+    // Adds the base point G (affine)
+    let add_g = |pt: &(BigUint, BigUint, BigUint)| {
+        let (x1, y1, z1) = pt;
+        if *z1 == BigUint::ZERO {
+            return (c.gx.clone(), c.gy.clone(), BigUint::from(1u8));
+        }
+        let z1z1 = mul(z1, z1);
+        let h = sub(&mul(&c.gx, &z1z1), x1);
+        let r = sub(&mul(&c.gy, &mul(z1, &z1z1)), y1);
+        if h == BigUint::ZERO {
+            return if r == BigUint::ZERO {
+                double(pt)
+            } else {
+                (BigUint::from(1u8), BigUint::from(1u8), BigUint::ZERO)
+            };
+        }
+        let hh = mul(&h, &h);
+        let hhh = mul(&h, &hh);
+        let v = mul(x1, &hh);
+        let x3 = sub(&sub(&mul(&r, &r), &hhh), &((BigUint::from(2u8) * &v) % p));
+        let y3 = sub(&mul(&r, &sub(&v, &x3)), &mul(y1, &hhh));
+        let z3 = mul(z1, &h);
+        (x3, y3, z3)
+    };
+
+    let mut acc = (BigUint::from(1u8), BigUint::from(1u8), BigUint::ZERO);
+    for i in (0..d.bits()).rev() {
+        acc = double(&acc);
+        if d.bit(i) {
+            acc = add_g(&acc);
+        }
+    }
+    let (x, y, z) = acc;
+    if z == BigUint::ZERO {
+        return None;
     }
+    let z_inv = z.modpow(&(p - 2u8), p);
+    let z_inv2 = mul(&z_inv, &z_inv);
+    let mut point = vec![4u8];
+    point.extend(to_32_bytes(&mul(&x, &z_inv2)));
+    point.extend(to_32_bytes(&mul(&y, &mul(&z_inv2, &z_inv))));
+    Some(point)
+}
 
-    let canonical_request =
-        format!("{method}\n{path}\n\n{sorted_headers}\n{signed_headers}\n{payload_hash}");
+fn to_32_bytes(n: &BigUint) -> [u8; 32] {
+    let bytes = n.to_bytes_be();
+    let mut out = [0u8; 32];
+    out[32 - bytes.len()..].copy_from_slice(&bytes);
+    out
+}
 
-    let credential_scope = format!("{date_stamp}/{region}/{service}/aws4_request");
+/// SigV4a private key for an access key pair: the first HMAC-SHA256 output
+/// (over a counter-based KDF context) that is at most n - 2, plus one.
+fn sigv4a_private_key(access_key: &str, secret_key: &str) -> Result<[u8; 32], RayClawError> {
+    let n_minus_two = &p256().n - 2u8;
+    let input_key = format!("AWS4A{secret_key}");
+    for counter in 1u8..=254 {
+        let mut context = vec![0, 0, 0, 1];
+        context.extend_from_slice(SIGV4A_ALGORITHM.as_bytes());
+        context.push(0);
+        context.extend_from_slice(access_key.as_bytes());
+        context.push(counter);
+        // Output length in bits
+        context.extend_from_slice(&256u32.to_be_bytes());
+        let k0 = BigUint::from_bytes_be(&hmac_sha256(input_key.as_bytes(), &context));
+        if k0 <= n_minus_two {
+            return Ok(to_32_bytes(&(k0 + 1u8)));
+        }
+    }
+    Err(RayClawError::Config(
+        "Bedrock: failed to derive a SigV4a signing key".into(),
+    ))
+}
+
+fn sigv4a_key_pair(
+    access_key: &str,
+    secret_key: &str,
+) -> Result<ring::signature::EcdsaKeyPair, RayClawError> {
+    let private_key = sigv4a_private_key(access_key, secret_key)?;
+    let public_key = p256_public_key(&BigUint::from_bytes_be(&private_key))
+        .ok_or_else(|| RayClawError::Config("Bedrock: invalid SigV4a signing key".into()))?;
+    ring::signature::EcdsaKeyPair::from_private_key_and_public_key(
+        &ring::signature::ECDSA_P256_SHA256_ASN1_SIGNING,
+        &private_key,
+        &public_key,
+        &ring::rand::SystemRandom::new(),
+    )
+    .map_err(|e| RayClawError::Config(format!("Bedrock: invalid SigV4a signing key: {e}")))
+}
+
+/// Sign a request with SigV4a for `region_set` (comma-separated regions or
+/// `*`) and return the headers to add.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn sign_request_v4a(
+    method: &str,
+    url: &reqwest::Url,
+    body: &[u8],
+    region_set: &str,
+    service: &str,
+    access_key: &str,
+    secret_key: &str,
+    session_token: Option<&str>,
+    now: &chrono::DateTime<chrono::Utc>,
+) -> Result<Vec<(String, String)>, RayClawError> {
+    let date_stamp = now.format("%Y%m%d").to_string();
+    let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+
+    let mut extra = vec![("x-amz-region-set", region_set)];
+    if let Some(token) = session_token {
+        extra.push(("x-amz-security-token", token));
+    }
+    let (canonical_request, signed_headers) =
+        canonical_request(method, url, body, &amz_date, &extra);
+
+    // Unlike SigV4 the scope has no region; the region set is a signed header
+    let credential_scope = format!("{date_stamp}/{service}/aws4_request");
     let string_to_sign = format!(
-        "AWS4-HMAC-SHA256\n{amz_date}\n{credential_scope}\n{}",
+        "{SIGV4A_ALGORITHM}\n{amz_date}\n{credential_scope}\n{}",
         sha256_hex(canonical_request.as_bytes())
     );
 
-    let signing_key = sigv4_signing_key(secret_key, &date_stamp, region, service);
-    let signature = hex::encode(hmac_sha256(&signing_key, string_to_sign.as_bytes()));
+    let key_pair = sigv4a_key_pair(access_key, secret_key)?;
+    let signature = key_pair
+        .sign(&ring::rand::SystemRandom::new(), string_to_sign.as_bytes())
+        .map_err(|e| RayClawError::LlmApi(format!("SigV4a signing failed: {e}")))?;
 
     let authorization = format!(
-        "AWS4-HMAC-SHA256 Credential={access_key}/{credential_scope}, SignedHeaders={signed_headers}, Signature={signature}"
+        "{SIGV4A_ALGORITHM} Credential={access_key}/{credential_scope}, SignedHeaders={signed_headers}, Signature={}",
+        hex::encode(signature.as_ref())
     );
 
     let mut headers = vec![
         ("Authorization".into(), authorization),
         ("X-Amz-Date".into(), amz_date),
+        ("X-Amz-Region-Set".into(), region_set.to_string()),
     ];
     if let Some(token) = session_token {
         headers.push(("X-Amz-Security-Token".into(), token.to_string()));
     }
-    headers
+    Ok(headers)
 }
 
 // ---------------------------------------------------------------------------
@@ -570,7 +880,9 @@ fn translate_messages_to_bedrock(messages: &[Message]) -> Vec<serde_json::Value>
                             ContentBlock::Text { text } => {
                                 Some(serde_json::json!({ "text": text }))
                             }
-                            ContentBlock::ToolUse { id, name, input } => Some(serde_json::json!({
+                            ContentBlock::ToolUse {
+                                id, name, input, ..
+                            } => Some(serde_json::json!({
                                 "toolUse": {
                                     "toolUseId": id,
                                     "name": name,
@@ -602,6 +914,23 @@ fn translate_messages_to_bedrock(messages: &[Message]) -> Vec<serde_json::Value>
                                     }
                                 }))
                             }
+                            // Claude needs signed reasoning back with tool results
+                            ContentBlock::Thinking {
+                                thinking,
+                                signature,
+                            } => signature.as_ref().map(|signature| {
+                                serde_json::json!({
+                                    "reasoningContent": {
+                                        "reasoningText": {
+                                            "text": thinking,
+                                            "signature": signature,
+                                        }
+                                    }
+                                })
+                            }),
+                            ContentBlock::RedactedThinking { data } => Some(serde_json::json!({
+                                "reasoningContent": { "redactedContent": data }
+                            })),
                             ContentBlock::Image { source } => {
                                 let format = mime_to_bedrock_format(&source.media_type);
                                 Some(serde_json::json!({
@@ -613,6 +942,26 @@ fn translate_messages_to_bedrock(messages: &[Message]) -> Vec<serde_json::Value>
                                     }
                                 }))
                             }
+                            // Converse rejects the whole request over an oversized document
+                            ContentBlock::Document { source, title }
+                                if source.data.len() / 4 * 3 > BEDROCK_MAX_DOCUMENT_BYTES =>
+                            {
+                                Some(serde_json::json!({
+                                    "text": format!(
+                                        "[document {} omitted: larger than Bedrock's 4.5 MB limit]",
+                                        title.as_deref().unwrap_or("attachment")
+                                    )
+                                }))
+                            }
+                            ContentBlock::Document { source, title } => Some(serde_json::json!({
+                                "document": {
+                                    "format": mime_to_bedrock_document_format(&source.media_type),
+                                    "name": bedrock_document_name(title.as_deref()),
+                                    "source": {
+                                        "bytes": source.data,
+                                    }
+                                }
+                            })),
                         })
                         .collect();
                     if filtered.is_empty() {
@@ -647,6 +996,17 @@ fn translate_tools_to_bedrock(tools: &[ToolDefinition]) -> serde_json::Value {
     serde_json::json!({ "tools": tool_specs })
 }
 
+/// Converse `usage`; `inputTokens` excludes prompt cache reads and writes.
+fn translate_usage(u: &serde_json::Value) -> Usage {
+    let count = |key: &str| u.get(key).and_then(|v| v.as_u64()).unwrap_or(0) as u32;
+    Usage {
+        input_tokens: count("inputTokens"),
+        output_tokens: count("outputTokens"),
+        cache_creation_input_tokens: count("cacheWriteInputTokens"),
+        cache_read_input_tokens: count("cacheReadInputTokens"),
+    }
+}
+
 fn translate_bedrock_response(body: &serde_json::Value) -> MessagesResponse {
     let mut content = Vec::new();
 
@@ -658,6 +1018,8 @@ fn translate_bedrock_response(body: &serde_json::Value) -> MessagesResponse {
                         content.push(ResponseContentBlock::Text {
                             text: text.to_string(),
                         });
+                    } else if let Some(reasoning) = block.get("reasoningContent") {
+                        content.extend(translate_bedrock_reasoning(reasoning));
                     } else if let Some(tool_use) = block.get("toolUse") {
                         let id = tool_use
                             .get("toolUseId")
@@ -673,7 +1035,12 @@ fn translate_bedrock_response(body: &serde_json::Value) -> MessagesResponse {
                             .get("input")
                             .cloned()
                             .unwrap_or(serde_json::Value::Object(Default::default()));
-                        content.push(ResponseContentBlock::ToolUse { id, name, input });
+                        content.push(ResponseContentBlock::ToolUse {
+                            id,
+                            name,
+                            input,
+                            signature: None,
+                        });
                     }
                 }
             }
@@ -685,10 +1052,7 @@ fn translate_bedrock_response(body: &serde_json::Value) -> MessagesResponse {
         .and_then(|v| v.as_str())
         .map(|s| s.to_string());
 
-    let usage = body.get("usage").map(|u| Usage {
-        input_tokens: u.get("inputTokens").and_then(|v| v.as_u64()).unwrap_or(0) as u32,
-        output_tokens: u.get("outputTokens").and_then(|v| v.as_u64()).unwrap_or(0) as u32,
-    });
+    let usage = body.get("usage").map(translate_usage);
 
     MessagesResponse {
         content,
@@ -697,6 +1061,69 @@ fn translate_bedrock_response(body: &serde_json::Value) -> MessagesResponse {
     }
 }
 
+/// Fold a streamed `reasoningContent` delta into the block being built.
+fn merge_reasoning_delta(current: &mut Option<ResponseContentBlock>, delta: &serde_json::Value) {
+    if let Some(data) = delta.get("redactedContent").and_then(|d| d.as_str()) {
+        *current = Some(ResponseContentBlock::RedactedThinking {
+            data: data.to_string(),
+        });
+        return;
+    }
+    let block = current.get_or_insert_with(|| ResponseContentBlock::Thinking {
+        thinking: String::new(),
+        signature: None,
+    });
+    if let ResponseContentBlock::Thinking {
+        thinking,
+        signature,
+    } = block
+    {
+        if let Some(text) = delta.get("text").and_then(|t| t.as_str()) {
+            thinking.push_str(text);
+        }
+        if let Some(sig) = delta.get("signature").and_then(|s| s.as_str()) {
+            *signature = Some(sig.to_string());
+        }
+    }
+}
+
+fn translate_bedrock_reasoning(reasoning: &serde_json::Value) -> Option<ResponseContentBlock> {
+    if let Some(data) = reasoning.get("redactedContent").and_then(|d| d.as_str()) {
+        return Some(ResponseContentBlock::RedactedThinking {
+            data: data.to_string(),
+        });
+    }
+    let text = reasoning.get("reasoningText")?;
+    Some(ResponseContentBlock::Thinking {
+        thinking: text
+            .get("text")
+            .and_then(|t| t.as_str())
+            .unwrap_or_default()
+            .to_string(),
+        signature: text
+            .get("signature")
+            .and_then(|s| s.as_str())
+            .map(str::to_string),
+    })
+}
+
+/// `additionalModelRequestFields` enabling reasoning for `model`: Claude
+/// takes a token budget, other reasoning models (Amazon Nova) an effort.
+fn bedrock_reasoning_fields(model: &str, thinking: &ThinkingConfig) -> Option<serde_json::Value> {
+    if !thinking.enabled {
+        return None;
+    }
+    Some(if model.to_lowercase().contains("claude") {
+        serde_json::json!({
+            "thinking": { "type": "enabled", "budget_tokens": thinking.budget_tokens }
+        })
+    } else {
+        serde_json::json!({
+            "reasoningConfig": { "type": "enabled", "maxReasoningEffort": thinking.effort() }
+        })
+    })
+}
+
 fn mime_to_bedrock_format(mime: &str) -> &str {
     match mime {
         "image/png" => "png",
@@ -706,43 +1133,283 @@ fn mime_to_bedrock_format(mime: &str) -> &str {
     }
 }
 
+/// Converse limit on a single document's size.
+const BEDROCK_MAX_DOCUMENT_BYTES: usize = 4_500_000;
+
+fn mime_to_bedrock_document_format(mime: &str) -> &str {
+    match mime {
+        "text/csv" => "csv",
+        "text/html" => "html",
+        "text/markdown" => "md",
+        "text/plain" => "txt",
+        _ => "pdf",
+    }
+}
+
+/// Converse document names may only hold alphanumerics, single spaces,
+/// hyphens, parentheses and square brackets.
+fn bedrock_document_name(title: Option<&str>) -> String {
+    let stem = title
+        .map(|t| t.rsplit_once('.').map_or(t, |(stem, _)| stem))
+        .unwrap_or_default();
+    let cleaned: String = stem
+        .chars()
+        .map(|c| match c {
+            c if c.is_ascii_alphanumeric() => c,
+            '-' | '(' | ')' | '[' | ']' => c,
+            _ => ' ',
+        })
+        .collect();
+    let name = cleaned.split_whitespace().collect::<Vec<_>>().join(" ");
+    if name.is_empty() {
+        "document".into()
+    } else {
+        name
+    }
+}
+
 // ---------------------------------------------------------------------------
 // BedrockProvider
 // ---------------------------------------------------------------------------
 
 pub struct BedrockProvider {
     http: reqwest::Client,
-    credentials: AwsCredentials,
-    model: String,
+    /// Refreshed in place when temporary credentials near expiry
+    credentials: std::sync::RwLock<AwsCredentials>,
+    /// Serializes refreshes so concurrent requests share one
+    refresh_lock: tokio::sync::Mutex<()>,
+    /// Seconds AWS's clock is ahead of ours, learned from a clock-skew
+    /// rejection and added to every later signature's timestamp
+    clock_offset_secs: AtomicI64,
+    /// Model ID used in the Converse URL: `model`, or an application
+    /// inference profile ARN when one is configured
+    model_id: String,
     max_tokens: u32,
     prompt_cache_ttl: String,
+    latency: Option<String>,
+    /// Base URL of the Bedrock runtime API, without a trailing slash
+    endpoint: String,
+    capabilities: ProviderCapabilities,
+    /// `additionalModelRequestFields` that turn on reasoning, when enabled
+    reasoning_fields: Option<serde_json::Value>,
+    inference: InferenceParams,
+    /// Claude takes `top_k` as an additional field; other models don't
+    accepts_top_k: bool,
+    /// Regions requests are signed for with SigV4a; SigV4 for the
+    /// credentials' region when unset
+    sigv4a_region_set: Option<String>,
+    retry: RetryPolicy,
+}
+
+/// Substrings of Bedrock model IDs that accept image input via Converse.
+const BEDROCK_VISION_MODEL_MARKERS: &[&str] = &[
+    "claude",
+    "nova-pro",
+    "nova-lite",
+    "nova-premier",
+    "llama3-2-11b",
+    "llama3-2-90b",
+    "llama4",
+    "pixtral",
+];
+
+/// Substrings of Bedrock model IDs that accept document blocks via Converse.
+const BEDROCK_DOCUMENT_MODEL_MARKERS: &[&str] =
+    &["claude", "nova", "llama", "mistral", "command-r"];
+
+/// Capabilities of `model` (the foundation model ID, even when invoked
+/// through an inference profile) under the Converse API.
+fn bedrock_capabilities(
+    model: &str,
+    limits: ModelLimits,
+    prompt_cache_ttl: &str,
+    max_tokens: u32,
+) -> ProviderCapabilities {
+    let model = model.to_lowercase();
+    ProviderCapabilities {
+        vision: BEDROCK_VISION_MODEL_MARKERS
+            .iter()
+            .any(|marker| model.contains(marker)),
+        documents: BEDROCK_DOCUMENT_MODEL_MARKERS
+            .iter()
+            .any(|marker| model.contains(marker)),
+        // Titan text models don't support Converse tool use
+        tools: !model.contains("titan"),
+        streaming: true,
+        prompt_caching: prompt_cache_ttl != "none"
+            && (model.contains("claude") || model.contains("nova")),
+        ..ProviderCapabilities::default()
+    }
+    .with_limits(limits, max_tokens)
+}
+
+/// Bedrock runtime base URL for `region`. An explicit override (config
+/// `aws_endpoint_url` or `AWS_ENDPOINT_URL_BEDROCK_RUNTIME`) wins; otherwise
+/// the regional endpoint is used, FIPS if requested. GovCloud regions
+/// (`us-gov-*`) share the `amazonaws.com` domain, China regions do not.
+fn bedrock_endpoint(region: &str, endpoint_url: Option<&str>, use_fips: bool) -> String {
+    if let Some(url) = endpoint_url.map(str::trim).filter(|u| !u.is_empty()) {
+        return url.trim_end_matches('/').to_string();
+    }
+    let host = if use_fips {
+        "bedrock-runtime-fips"
+    } else {
+        "bedrock-runtime"
+    };
+    let domain = if region.starts_with("cn-") {
+        "amazonaws.com.cn"
+    } else {
+        "amazonaws.com"
+    };
+    format!("https://{host}.{region}.{domain}")
+}
+
+/// SigV4a region set from `AWS_SIGV4A_SIGNING_REGION_SET` or the
+/// profile's `sigv4a_signing_region_set`.
+fn sigv4a_region_set(config: &Config) -> Option<String> {
+    if let Some(set) = std::env::var("AWS_SIGV4A_SIGNING_REGION_SET")
+        .ok()
+        .filter(|s| !s.trim().is_empty())
+    {
+        return Some(set);
+    }
+    let profile = config
+        .aws_profile
+        .clone()
+        .filter(|s| !s.trim().is_empty())
+        .or_else(|| std::env::var("AWS_PROFILE").ok())
+        .filter(|s| !s.trim().is_empty())
+        .unwrap_or_else(|| "default".into());
+    AwsConfigFiles::locate()
+        .profile(&profile)
+        .remove("sigv4a_signing_region_set")
+        .filter(|s| !s.trim().is_empty())
+}
+
+/// A region set as sent in `X-Amz-Region-Set`: regions (or `*`) joined by
+/// commas without spaces. `None` when empty.
+fn normalize_region_set(region_set: Option<&str>) -> Option<String> {
+    let regions: Vec<&str> = region_set?
+        .split(',')
+        .map(str::trim)
+        .filter(|r| !r.is_empty())
+        .collect();
+    (!regions.is_empty()).then(|| regions.join(","))
+}
+
+/// Region of a Bedrock ARN (`arn:aws:bedrock:eu-west-1:123456789012:...`).
+/// Models and inference profiles must be invoked, and signed, there.
+fn arn_region(model_id: &str) -> Option<&str> {
+    let mut parts = model_id.strip_prefix("arn:")?.splitn(5, ':');
+    let _partition = parts.next()?;
+    if parts.next()? != "bedrock" {
+        return None;
+    }
+    parts.next().filter(|region| !region.is_empty())
+}
+
+/// Region prefixes a cross-region inference profile ID (`us.anthropic...`)
+/// can be invoked from. `None` when the ID has no known geography prefix;
+/// `global.` profiles work from any region.
+fn cross_region_source_prefixes(model_id: &str) -> Option<&'static [&'static str]> {
+    let (geo, _) = model_id.split_once('.')?;
+    Some(match geo {
+        "global" => &[""],
+        "us" => &["us-east-", "us-west-"],
+        "us-gov" => &["us-gov-"],
+        "eu" => &["eu-"],
+        "apac" => &["ap-"],
+        "jp" => &["ap-northeast-"],
+        "au" => &["ap-southeast-"],
+        "ca" => &["ca-"],
+        _ => return None,
+    })
 }
 
 impl BedrockProvider {
     pub fn new(config: &Config) -> Result<Self, RayClawError> {
-        let credentials = AwsCredentials::resolve(config)?;
+        let mut credentials = AwsCredentials::resolve(config)?;
+        let model_id = config.bedrock_invocation_id();
+        if let Some(region) = arn_region(&model_id) {
+            if region != credentials.region {
+                info!(
+                    "Bedrock: invoking {model_id} in its ARN region {region} instead of {}",
+                    credentials.region
+                );
+                credentials.region = region.to_string();
+            }
+        } else if let Some(prefixes) = cross_region_source_prefixes(&model_id) {
+            if !prefixes.iter().any(|p| credentials.region.starts_with(p)) {
+                warn!(
+                    "Bedrock: cross-region inference profile {model_id} is not available from region {}; set aws_region to a region in its geography",
+                    credentials.region
+                );
+            }
+        }
+        let sigv4a_region_set = normalize_region_set(sigv4a_region_set(config).as_deref());
+        if let Some(set) = &sigv4a_region_set {
+            info!("Bedrock: signing requests with SigV4a for region set {set}");
+        }
+        let endpoint_url = config
+            .aws_endpoint_url
+            .clone()
+            .or_else(|| std::env::var("AWS_ENDPOINT_URL_BEDROCK_RUNTIME").ok());
+        let endpoint = bedrock_endpoint(
+            &credentials.region,
+            endpoint_url.as_deref(),
+            config.aws_use_fips_endpoint,
+        );
         Ok(BedrockProvider {
+            retry: RetryPolicy::from_config(config),
             http: reqwest::Client::new(),
-            credentials,
-            model: config.model.clone(),
+            credentials: std::sync::RwLock::new(credentials),
+            refresh_lock: tokio::sync::Mutex::new(()),
+            clock_offset_secs: AtomicI64::new(0),
+            model_id,
             max_tokens: config.max_tokens,
             prompt_cache_ttl: config.prompt_cache_ttl.clone(),
+            latency: config.aws_bedrock_latency.clone(),
+            endpoint,
+            capabilities: bedrock_capabilities(
+                &config.model,
+                config.model_limits_for(&config.model).unwrap_or_default(),
+                &config.prompt_cache_ttl,
+                config.max_tokens,
+            ),
+            reasoning_fields: bedrock_reasoning_fields(&config.model, &config.thinking),
+            inference: config.inference.clone(),
+            accepts_top_k: config.model.to_lowercase().contains("claude"),
+            sigv4a_region_set,
         })
     }
 
+    /// Sanitize history for a request. Claude's signed reasoning is sent
+    /// back while thinking is on; other models don't accept it.
+    fn prepare_messages(&self, messages: Vec<Message>) -> Vec<Message> {
+        let replay = self
+            .reasoning_fields
+            .as_ref()
+            .is_some_and(|f| f.get("thinking").is_some());
+        if replay {
+            sanitize_messages(messages)
+        } else {
+            sanitize_messages(strip_thinking_blocks(messages))
+        }
+    }
+
     fn converse_url(&self) -> String {
         format!(
-            "https://bedrock-runtime.{}.amazonaws.com/model/{}/converse",
-            self.credentials.region,
-            urlencoding::encode(&self.model)
+            "{}/model/{}/converse",
+            self.endpoint,
+            urlencoding::encode(&self.model_id)
         )
     }
 
     fn converse_stream_url(&self) -> String {
         format!(
-            "https://bedrock-runtime.{}.amazonaws.com/model/{}/converse-stream",
-            self.credentials.region,
-            urlencoding::encode(&self.model)
+            "{}/model/{}/converse-stream",
+            self.endpoint,
+            urlencoding::encode(&self.model_id)
         )
     }
 
@@ -761,6 +1428,35 @@ impl BedrockProvider {
             },
         });
 
+        if let Some(latency) = &self.latency {
+            body["performanceConfig"] = serde_json::json!({ "latency": latency });
+        }
+
+        if let Some(fields) = &self.reasoning_fields {
+            body["additionalModelRequestFields"] = fields.clone();
+        }
+
+        let params = &self.inference;
+        // Claude's extended thinking doesn't allow changing temperature or top_k
+        let thinking = self
+            .reasoning_fields
+            .as_ref()
+            .is_some_and(|f| f.get("thinking").is_some());
+        if !thinking {
+            if let Some(temperature) = params.temperature {
+                body["inferenceConfig"]["temperature"] = serde_json::json!(temperature);
+            }
+            if let (Some(top_k), true) = (params.top_k, self.accepts_top_k) {
+                body["additionalModelRequestFields"]["top_k"] = serde_json::json!(top_k);
+            }
+        }
+        if let Some(top_p) = params.top_p {
+            body["inferenceConfig"]["topP"] = serde_json::json!(top_p);
+        }
+        if !params.stop_sequences.is_empty() {
+            body["inferenceConfig"]["stopSequences"] = serde_json::json!(params.stop_sequences);
+        }
+
         if !system.is_empty() {
             if use_cache {
                 // Add system prompt with cache point
@@ -795,8 +1491,46 @@ impl BedrockProvider {
         body
     }
 
+    /// Credentials to sign the next request with, refreshing temporary
+    /// ones that are about to expire.
+    async fn signing_credentials(&self) -> AwsCredentials {
+        let current = || {
+            self.credentials
+                .read()
+                .unwrap_or_else(|e| e.into_inner())
+                .clone()
+        };
+        if !current().needs_refresh(chrono::Utc::now()) {
+            return current();
+        }
+        let _guard = self.refresh_lock.lock().await;
+        // Another request may have refreshed while we waited
+        let stale = current();
+        if !stale.needs_refresh(chrono::Utc::now()) {
+            return stale;
+        }
+        match stale.refresh().await {
+            Ok(fresh) => {
+                info!(
+                    "Bedrock: refreshed AWS credentials (expire {})",
+                    fresh
+                        .expiration
+                        .map(|t| t.to_rfc3339())
+                        .unwrap_or_else(|| "never".into())
+                );
+                *self.credentials.write().unwrap_or_else(|e| e.into_inner()) = fresh.clone();
+                fresh
+            }
+            Err(e) => {
+                warn!("Bedrock: failed to refresh AWS credentials: {e}");
+                current()
+            }
+        }
+    }
+
     fn sign_and_build_request(
         &self,
+        credentials: &AwsCredentials,
         url_str: &str,
         body_bytes: &[u8],
     ) -> Result<reqwest::RequestBuilder, RayClawError> {
@@ -804,18 +1538,32 @@ impl BedrockProvider {
             .parse()
             .map_err(|e| RayClawError::LlmApi(format!("Invalid URL: {e}")))?;
 
-        let now = chrono::Utc::now();
-        let auth_headers = sign_request(
-            "POST",
-            &url,
-            body_bytes,
-            &self.credentials.region,
-            "bedrock",
-            &self.credentials.access_key_id,
-            &self.credentials.secret_access_key,
-            self.credentials.session_token.as_deref(),
-            &now,
-        );
+        let offset = self.clock_offset_secs.load(Ordering::Relaxed);
+        let now = chrono::Utc::now() + chrono::Duration::seconds(offset);
+        let auth_headers = match &self.sigv4a_region_set {
+            Some(region_set) => sign_request_v4a(
+                "POST",
+                &url,
+                body_bytes,
+                region_set,
+                "bedrock",
+                &credentials.access_key_id,
+                &credentials.secret_access_key,
+                credentials.session_token.as_deref(),
+                &now,
+            )?,
+            None => sign_request(
+                "POST",
+                &url,
+                body_bytes,
+                &credentials.region,
+                "bedrock",
+                &credentials.access_key_id,
+                &credentials.secret_access_key,
+                credentials.session_token.as_deref(),
+                &now,
+            ),
+        };
 
         let mut builder = self
             .http
@@ -830,51 +1578,128 @@ impl BedrockProvider {
 
         Ok(builder)
     }
+
+    /// Sign and send a request. If AWS rejects the signature because this
+    /// host's clock has drifted, the offset to AWS time is taken from the
+    /// response's `Date` header and the request is re-signed once with it.
+    /// Other 400/403 errors are returned as `Bedrock {operation} HTTP ...`.
+    async fn send_signed(
+        &self,
+        operation: &str,
+        url: &str,
+        body_bytes: &[u8],
+    ) -> Result<reqwest::Response, RayClawError> {
+        let credentials = self.signing_credentials().await;
+        let mut resigned = false;
+        loop {
+            let response = send_with_retry(self.retry, "Bedrock", || {
+                self.sign_and_build_request(&credentials, url, body_bytes)
+            })
+            .await?;
+            let status = response.status();
+            if resigned
+                || !(status == reqwest::StatusCode::BAD_REQUEST
+                    || status == reqwest::StatusCode::FORBIDDEN)
+            {
+                return Ok(response);
+            }
+            let header = |name: &str| {
+                response
+                    .headers()
+                    .get(name)
+                    .and_then(|v| v.to_str().ok())
+                    .map(str::to_string)
+            };
+            let error_type = header("x-amzn-errortype");
+            let date = header("date");
+            let err_body = response.text().await.unwrap_or_default();
+            let offset = date
+                .as_deref()
+                .and_then(|d| clock_offset_from_date(d, chrono::Utc::now()));
+            match offset {
+                Some(offset) if is_clock_skew_error(error_type.as_deref(), &err_body) => {
+                    warn!(
+                        "Bedrock: signature rejected for clock skew (AWS time is {offset:+}s from local), re-signing"
+                    );
+                    self.clock_offset_secs.store(offset, Ordering::Relaxed);
+                    resigned = true;
+                }
+                _ => {
+                    return Err(RayClawError::LlmApi(format!(
+                        "Bedrock {operation} HTTP {status}: {err_body}"
+                    )));
+                }
+            }
+        }
+    }
+}
+
+/// Whether AWS rejected a request for its signing time:
+/// `RequestTimeTooSkewed`, or `InvalidSignatureException` for a signature
+/// that has expired or is not yet valid.
+fn is_clock_skew_error(error_type: Option<&str>, body: &str) -> bool {
+    let error_type = error_type.unwrap_or("");
+    if error_type.starts_with("RequestTimeTooSkewed") || body.contains("RequestTimeTooSkewed") {
+        return true;
+    }
+    let invalid_signature = error_type.starts_with("InvalidSignatureException")
+        || body.contains("InvalidSignatureException");
+    invalid_signature
+        && (body.contains("Signature expired") || body.contains("Signature not yet current"))
+}
+
+/// Seconds the server's clock is ahead of `local`, from an HTTP `Date`
+/// header (negative when the server is behind).
+fn clock_offset_from_date(date: &str, local: chrono::DateTime<chrono::Utc>) -> Option<i64> {
+    let server = chrono::DateTime::parse_from_rfc2822(date).ok()?;
+    Some((server.with_timezone(&chrono::Utc) - local).num_seconds())
 }
 
 #[async_trait]
 impl LlmProvider for BedrockProvider {
+    fn endpoint(&self) -> Option<String> {
+        Some(self.endpoint.clone())
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        self.capabilities
+    }
+
+    fn request_body(
+        &self,
+        system: &str,
+        messages: &[Message],
+        tools: Option<&[ToolDefinition]>,
+        _stream: bool,
+    ) -> Option<serde_json::Value> {
+        let messages = self.prepare_messages(messages.to_vec());
+        Some(self.build_request_body(system, &messages, tools))
+    }
+
     async fn send_message(
         &self,
         system: &str,
         messages: Vec<Message>,
         tools: Option<Vec<ToolDefinition>>,
     ) -> Result<MessagesResponse, RayClawError> {
-        let messages = sanitize_messages(messages);
+        let messages = self.prepare_messages(messages);
         let body = self.build_request_body(system, &messages, tools.as_deref());
         let body_bytes = serde_json::to_vec(&body)
             .map_err(|e| RayClawError::LlmApi(format!("Failed to serialize request: {e}")))?;
 
         let url = self.converse_url();
-        let mut retries = 0u32;
-        let max_retries = 3;
-
-        loop {
-            let request = self.sign_and_build_request(&url, &body_bytes)?;
-            let response = request.send().await?;
-            let status = response.status();
-
-            if status.is_success() {
-                let response_body: serde_json::Value = response.json().await?;
-                return Ok(translate_bedrock_response(&response_body));
-            }
-
-            if status.as_u16() == 429 && retries < max_retries {
-                retries += 1;
-                let delay = std::time::Duration::from_secs(2u64.pow(retries));
-                warn!(
-                    "Bedrock rate limited, retrying in {:?} (attempt {retries}/{max_retries})",
-                    delay
-                );
-                tokio::time::sleep(delay).await;
-                continue;
-            }
+        let response = self.send_signed("Converse", &url, &body_bytes).await?;
+        let status = response.status();
 
-            let err_body = response.text().await.unwrap_or_default();
-            return Err(RayClawError::LlmApi(format!(
-                "Bedrock Converse HTTP {status}: {err_body}"
-            )));
+        if status.is_success() {
+            let response_body: serde_json::Value = response.json().await?;
+            return Ok(translate_bedrock_response(&response_body));
         }
+
+        let err_body = response.text().await.unwrap_or_default();
+        Err(RayClawError::LlmApi(format!(
+            "Bedrock Converse HTTP {status}: {err_body}"
+        )))
     }
 
     async fn send_message_stream(
@@ -884,40 +1709,15 @@ impl LlmProvider for BedrockProvider {
         tools: Option<Vec<ToolDefinition>>,
         text_tx: Option<&UnboundedSender<String>>,
     ) -> Result<MessagesResponse, RayClawError> {
-        let messages = sanitize_messages(messages);
+        let messages = self.prepare_messages(messages);
         let body = self.build_request_body(system, &messages, tools.as_deref());
         let body_bytes = serde_json::to_vec(&body)
             .map_err(|e| RayClawError::LlmApi(format!("Failed to serialize request: {e}")))?;
 
         let url = self.converse_stream_url();
-        let url_parsed: reqwest::Url = url
-            .parse()
-            .map_err(|e| RayClawError::LlmApi(format!("Invalid URL: {e}")))?;
-
-        let now = chrono::Utc::now();
-        let auth_headers = sign_request(
-            "POST",
-            &url_parsed,
-            &body_bytes,
-            &self.credentials.region,
-            "bedrock",
-            &self.credentials.access_key_id,
-            &self.credentials.secret_access_key,
-            self.credentials.session_token.as_deref(),
-            &now,
-        );
-
-        let mut builder = self
-            .http
-            .post(&url)
-            .header("Content-Type", "application/json")
-            .body(body_bytes);
-
-        for (key, value) in auth_headers {
-            builder = builder.header(&key, &value);
-        }
-
-        let response = builder.send().await?;
+        let response = self
+            .send_signed("ConverseStream", &url, &body_bytes)
+            .await?;
         let status = response.status();
 
         if !status.is_success() {
@@ -937,6 +1737,7 @@ impl LlmProvider for BedrockProvider {
         let mut current_tool_name = String::new();
         let mut current_tool_input_json = String::new();
         let mut in_tool_use = false;
+        let mut current_reasoning: Option<ResponseContentBlock> = None;
         let mut stop_reason: Option<String> = None;
         let mut usage: Option<Usage> = None;
 
@@ -985,16 +1786,22 @@ impl LlmProvider for BedrockProvider {
                             {
                                 current_tool_input_json.push_str(json_chunk);
                             }
+                            if let Some(reasoning) = delta.get("reasoningContent") {
+                                merge_reasoning_delta(&mut current_reasoning, reasoning);
+                            }
                         }
                     }
                     "contentBlockStop" => {
-                        if in_tool_use {
+                        if let Some(block) = current_reasoning.take() {
+                            content_blocks.push(block);
+                        } else if in_tool_use {
                             let input = serde_json::from_str(&current_tool_input_json)
                                 .unwrap_or(serde_json::Value::Object(Default::default()));
                             content_blocks.push(ResponseContentBlock::ToolUse {
                                 id: std::mem::take(&mut current_tool_id),
                                 name: std::mem::take(&mut current_tool_name),
                                 input,
+                                signature: None,
                             });
                             current_tool_input_json.clear();
                             in_tool_use = false;
@@ -1011,14 +1818,7 @@ impl LlmProvider for BedrockProvider {
                             .map(|s| s.to_string());
                     }
                     "metadata" => {
-                        usage = payload.get("usage").map(|u| Usage {
-                            input_tokens: u.get("inputTokens").and_then(|v| v.as_u64()).unwrap_or(0)
-                                as u32,
-                            output_tokens: u
-                                .get("outputTokens")
-                                .and_then(|v| v.as_u64())
-                                .unwrap_or(0) as u32,
-                        });
+                        usage = payload.get("usage").map(translate_usage);
                     }
                     _ => {}
                 }
@@ -1151,6 +1951,7 @@ mod tests {
                 id: "tool-1".into(),
                 name: "bash".into(),
                 input: serde_json::json!({"command": "ls"}),
+                signature: None,
             }]),
         }];
         let result = translate_messages_to_bedrock(&messages);
@@ -1176,6 +1977,35 @@ mod tests {
         assert_eq!(tool_result["status"], "success");
     }
 
+    #[test]
+    fn test_translate_messages_document() {
+        let document = |data: String| ContentBlock::Document {
+            source: crate::llm_types::DocumentSource {
+                source_type: "base64".into(),
+                media_type: "application/pdf".into(),
+                data,
+            },
+            title: Some("Q3 report_final.v2.pdf".into()),
+        };
+        let messages = vec![Message {
+            role: "user".into(),
+            content: MessageContent::Blocks(vec![
+                document("JVBERi0=".into()),
+                document("A".repeat(7_000_000)),
+            ]),
+        }];
+        let result = translate_messages_to_bedrock(&messages);
+        let doc = &result[0]["content"][0]["document"];
+        assert_eq!(doc["format"], "pdf");
+        assert_eq!(doc["name"], "Q3 report final v2");
+        assert_eq!(doc["source"]["bytes"], "JVBERi0=");
+        assert!(result[0]["content"][1]["text"]
+            .as_str()
+            .unwrap()
+            .contains("4.5 MB"));
+        assert_eq!(bedrock_document_name(Some("  .pdf")), "document");
+    }
+
     #[test]
     fn test_translate_tools_to_bedrock() {
         let tools = vec![ToolDefinition {
@@ -1216,6 +2046,71 @@ mod tests {
         assert_eq!(resp.usage.as_ref().unwrap().input_tokens, 10);
     }
 
+    #[test]
+    fn test_bedrock_reasoning() {
+        let mut thinking = ThinkingConfig::default();
+        assert!(bedrock_reasoning_fields("anthropic.claude-sonnet-4-5-v2", &thinking).is_none());
+        thinking.enabled = true;
+        let fields = bedrock_reasoning_fields("anthropic.claude-sonnet-4-5-v2", &thinking).unwrap();
+        assert_eq!(fields["thinking"]["budget_tokens"], 4096);
+        let fields = bedrock_reasoning_fields("amazon.nova-2-lite-v1:0", &thinking).unwrap();
+        assert_eq!(fields["reasoningConfig"]["maxReasoningEffort"], "medium");
+
+        let resp = translate_bedrock_response(&serde_json::json!({
+            "output": {"message": {"role": "assistant", "content": [
+                {"reasoningContent": {"reasoningText": {"text": "Think", "signature": "sig"}}},
+                {"reasoningContent": {"redactedContent": "b64"}},
+                {"text": "Answer"}
+            ]}},
+            "stopReason": "end_turn"
+        }));
+        assert_eq!(resp.content.len(), 3);
+        let history: Vec<ContentBlock> = resp
+            .content
+            .iter()
+            .filter_map(ResponseContentBlock::to_content_block)
+            .collect();
+        let translated = translate_messages_to_bedrock(&[Message {
+            role: "assistant".into(),
+            content: MessageContent::Blocks(history),
+        }]);
+        let content = translated[0]["content"].as_array().unwrap();
+        assert_eq!(
+            content[0]["reasoningContent"]["reasoningText"]["signature"],
+            "sig"
+        );
+        assert_eq!(content[1]["reasoningContent"]["redactedContent"], "b64");
+        assert_eq!(content[2]["text"], "Answer");
+
+        let mut current = None;
+        for delta in [
+            serde_json::json!({"text": "Step "}),
+            serde_json::json!({"text": "one"}),
+            serde_json::json!({"signature": "s2"}),
+        ] {
+            merge_reasoning_delta(&mut current, &delta);
+        }
+        match current {
+            Some(ResponseContentBlock::Thinking {
+                thinking,
+                signature,
+            }) => {
+                assert_eq!(thinking, "Step one");
+                assert_eq!(signature.as_deref(), Some("s2"));
+            }
+            other => panic!("Expected Thinking, got {other:?}"),
+        }
+
+        let mut provider = make_bedrock_provider("none");
+        provider.reasoning_fields =
+            bedrock_reasoning_fields("anthropic.claude-sonnet-4-5-v2", &thinking);
+        let body = provider.build_request_body("sys", &[], None);
+        assert_eq!(
+            body["additionalModelRequestFields"]["thinking"]["type"],
+            "enabled"
+        );
+    }
+
     #[test]
     fn test_translate_bedrock_response_tool_use() {
         let body = serde_json::json!({
@@ -1235,7 +2130,10 @@ mod tests {
             "usage": { "inputTokens": 20, "outputTokens": 15 }
         });
         let resp = translate_bedrock_response(&body);
-        if let ResponseContentBlock::ToolUse { id, name, input } = &resp.content[0] {
+        if let ResponseContentBlock::ToolUse {
+            id, name, input, ..
+        } = &resp.content[0]
+        {
             assert_eq!(id, "t1");
             assert_eq!(name, "bash");
             assert_eq!(input["command"], "ls");
@@ -1315,6 +2213,7 @@ mod tests {
             web_host: "127.0.0.1".into(),
             web_port: 3000,
             web_auth_token: None,
+            web_public_url: None,
             web_max_inflight_per_session: 5,
             web_max_requests_per_window: 30,
             web_rate_window_seconds: 60,
@@ -1324,7 +2223,21 @@ mod tests {
             memory_token_budget: 1500,
             max_session_messages: 50,
             compact_keep_recent: 10,
+            session_idle_expiry_hours: 0,
+            session_expiry_summary: true,
             show_thinking: false,
+            thinking: crate::config::ThinkingConfig::default(),
+            inference: crate::config::InferenceParams::default(),
+            chat_inference: std::collections::HashMap::new(),
+            openrouter: crate::config::OpenRouterConfig::default(),
+            secret_patterns: vec![],
+            news_search: crate::config::NewsSearchConfig::default(),
+            maps: crate::config::MapsConfig::default(),
+            package_tracking: crate::config::PackageTrackingConfig::default(),
+            flight_status: crate::config::FlightStatusConfig::default(),
+            outbound_http: crate::config::OutboundHttpConfig::default(),
+            browsing_profiles_key: None,
+            tool_output_budgets: std::collections::HashMap::new(),
             data_dir: "./rayclaw.data".into(),
             working_dir: "./tmp".into(),
             working_dir_isolation: crate::config::WorkingDirIsolation::Chat,
@@ -1338,13 +2251,49 @@ mod tests {
             reflector_enabled: true,
             reflector_interval_mins: 15,
             model_prices: vec![],
+            model_limits: vec![],
             aws_region: Some("us-west-2".into()),
             aws_access_key_id: Some("AKID_TEST".into()),
             aws_secret_access_key: Some("SECRET_TEST".into()),
             aws_session_token: None,
             aws_profile: None,
+            aws_bedrock_inference_profile: None,
+            aws_bedrock_inference_profiles: std::collections::HashMap::new(),
+            aws_bedrock_latency: None,
+            aws_endpoint_url: None,
+            aws_use_fips_endpoint: false,
+            ollama_keep_alive: None,
+            email_gateway: None,
             soul_path: None,
+            system_prompt_template: None,
             skip_tool_approval: false,
+            tool_approval_timeout_secs: 300,
+            workspace_quota_mb: 0,
+            workspace_tmp_ttl_hours: 0,
+            workspace_cleanup_interval_mins: 60,
+            image_max_dimension: 1568,
+            image_jpeg_quality: 85,
+            chart_font_path: None,
+            tts_model: "tts-1".into(),
+            tts_voice: "alloy".into(),
+            stt_backend: "openai".into(),
+            whisper_cpp_binary: "whisper-cli".into(),
+            whisper_cpp_model: None,
+            max_concurrent_agent_runs: 8,
+            outage_queue_size: 50,
+            outage_queue_ttl_mins: 60,
+            message_debounce_ms: 0,
+            digest_dedup_days: 14,
+            llm_max_retries: 3,
+            llm_retry_max_delay_secs: 60,
+            llm_trace: false,
+            startup_preflight: true,
+            coordination_redis_url: None,
+            coordination_key_prefix: "rayclaw".into(),
+            cache_backend: "memory".into(),
+            queue_backend: "memory".into(),
+            response_cache_ttl_secs: 0,
+            provider_credentials: std::collections::HashMap::new(),
             skills_dir: None,
             channels: std::collections::HashMap::new(),
         };
@@ -1360,25 +2309,474 @@ mod tests {
         assert!(creds.session_token.is_none());
     }
 
+    #[test]
+    fn test_credentials_need_refresh_near_expiry() {
+        let now = chrono::Utc::now();
+        let mut creds = AwsCredentials {
+            access_key_id: "AKID".into(),
+            secret_access_key: "SECRET".into(),
+            session_token: Some("token".into()),
+            region: "us-east-1".into(),
+            expiration: None,
+            source: CredentialSource::Profile("sso".into()),
+        };
+        assert!(!creds.needs_refresh(now));
+        creds.expiration = Some(now + chrono::Duration::hours(1));
+        assert!(!creds.needs_refresh(now));
+        creds.expiration = Some(now + chrono::Duration::minutes(2));
+        assert!(creds.needs_refresh(now));
+    }
+
+    #[tokio::test]
+    async fn test_static_credentials_refresh_unchanged() {
+        let creds = AwsCredentials {
+            access_key_id: "AKID".into(),
+            secret_access_key: "SECRET".into(),
+            session_token: None,
+            region: "eu-west-1".into(),
+            expiration: None,
+            source: CredentialSource::Static,
+        };
+        let refreshed = creds.refresh().await.unwrap();
+        assert_eq!(refreshed.access_key_id, "AKID");
+        assert_eq!(refreshed.region, "eu-west-1");
+        assert_eq!(refreshed.source, CredentialSource::Static);
+    }
+
     // -----------------------------------------------------------------------
     // Bedrock prompt caching
     // -----------------------------------------------------------------------
 
     fn make_bedrock_provider(cache_ttl: &str) -> BedrockProvider {
         BedrockProvider {
+            retry: RetryPolicy::default(),
             http: reqwest::Client::new(),
-            credentials: AwsCredentials {
+            credentials: std::sync::RwLock::new(AwsCredentials {
                 access_key_id: "AKID".into(),
                 secret_access_key: "SECRET".into(),
                 session_token: None,
                 region: "us-east-1".into(),
-            },
-            model: "anthropic.claude-sonnet-4-5-v2".into(),
+                expiration: None,
+                source: CredentialSource::Static,
+            }),
+            refresh_lock: tokio::sync::Mutex::new(()),
+            clock_offset_secs: AtomicI64::new(0),
+            model_id: "anthropic.claude-sonnet-4-5-v2".into(),
             max_tokens: 4096,
             prompt_cache_ttl: cache_ttl.into(),
+            latency: None,
+            endpoint: bedrock_endpoint("us-east-1", None, false),
+            capabilities: bedrock_capabilities(
+                "anthropic.claude-sonnet-4-5-v2",
+                ModelLimits::default(),
+                cache_ttl,
+                4096,
+            ),
+            reasoning_fields: None,
+            inference: InferenceParams::default(),
+            accepts_top_k: true,
+            sigv4a_region_set: None,
         }
     }
 
+    #[test]
+    fn test_build_request_body_bedrock_inference_params() {
+        let mut provider = make_bedrock_provider("none");
+        provider.inference = InferenceParams {
+            temperature: Some(0.1),
+            top_p: Some(0.5),
+            top_k: Some(10),
+            stop_sequences: vec!["END".into()],
+        };
+        let body = provider.build_request_body("sys", &[], None);
+        assert_eq!(body["inferenceConfig"]["maxTokens"], 4096);
+        assert_eq!(body["inferenceConfig"]["temperature"], 0.1);
+        assert_eq!(body["inferenceConfig"]["topP"], 0.5);
+        assert_eq!(
+            body["inferenceConfig"]["stopSequences"],
+            serde_json::json!(["END"])
+        );
+        assert_eq!(body["additionalModelRequestFields"]["top_k"], 10);
+
+        provider.accepts_top_k = false;
+        let body = provider.build_request_body("sys", &[], None);
+        assert!(body.get("additionalModelRequestFields").is_none());
+    }
+
+    #[test]
+    fn test_inference_profile_and_latency() {
+        let mut provider = make_bedrock_provider("none");
+        let msgs = vec![Message {
+            role: "user".into(),
+            content: MessageContent::Text("hi".into()),
+        }];
+        let body = provider.build_request_body("", &msgs, None);
+        assert!(body.get("performanceConfig").is_none());
+
+        provider.latency = Some("optimized".into());
+        provider.model_id =
+            "arn:aws:bedrock:us-east-1:123456789012:application-inference-profile/abc123".into();
+        let body = provider.build_request_body("", &msgs, None);
+        assert_eq!(body["performanceConfig"]["latency"], "optimized");
+        assert_eq!(
+            provider.converse_url(),
+            "https://bedrock-runtime.us-east-1.amazonaws.com/model/arn%3Aaws%3Abedrock%3Aus-east-1%3A123456789012%3Aapplication-inference-profile%2Fabc123/converse"
+        );
+    }
+
+    #[test]
+    fn test_arn_and_cross_region_ids() {
+        assert_eq!(
+            arn_region("arn:aws:bedrock:eu-west-1:123456789012:application-inference-profile/abc"),
+            Some("eu-west-1")
+        );
+        assert_eq!(
+            arn_region("arn:aws-us-gov:bedrock:us-gov-west-1:123456789012:inference-profile/us-gov.anthropic.claude-3-5-sonnet"),
+            Some("us-gov-west-1")
+        );
+        assert_eq!(
+            arn_region("arn:aws:bedrock:ap-northeast-1::foundation-model/anthropic.claude-3-haiku"),
+            Some("ap-northeast-1")
+        );
+        assert_eq!(arn_region("arn:aws:s3:::bucket"), None);
+        assert_eq!(arn_region("us.anthropic.claude-sonnet-4-5-v2"), None);
+
+        let us = cross_region_source_prefixes("us.anthropic.claude-sonnet-4-5-v2").unwrap();
+        assert!(us.iter().any(|p| "us-west-2".starts_with(p)));
+        assert!(!us.iter().any(|p| "us-gov-west-1".starts_with(p)));
+        assert!(!us.iter().any(|p| "eu-central-1".starts_with(p)));
+        let global = cross_region_source_prefixes("global.anthropic.claude-sonnet-4-5").unwrap();
+        assert!(global.iter().any(|p| "sa-east-1".starts_with(p)));
+        assert!(cross_region_source_prefixes("anthropic.claude-3-haiku").is_none());
+        assert!(cross_region_source_prefixes("meta.llama3").is_none());
+    }
+
+    #[test]
+    fn test_new_signs_in_arn_region() {
+        let yaml = "api_key: key\nllm_provider: bedrock\naws_region: us-east-1\naws_access_key_id: AKID\naws_secret_access_key: SECRET\nmodel: arn:aws:bedrock:eu-central-1:123456789012:application-inference-profile/abc\n";
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        let provider = BedrockProvider::new(&config).unwrap();
+        assert_eq!(provider.credentials.read().unwrap().region, "eu-central-1");
+        assert_eq!(
+            provider.converse_url(),
+            "https://bedrock-runtime.eu-central-1.amazonaws.com/model/arn%3Aaws%3Abedrock%3Aeu-central-1%3A123456789012%3Aapplication-inference-profile%2Fabc/converse"
+        );
+
+        // A per-model profile keeps the foundation ID for capabilities
+        let yaml = "api_key: key\nllm_provider: bedrock\naws_region: us-west-2\naws_access_key_id: AKID\naws_secret_access_key: SECRET\nmodel: anthropic.claude-sonnet-4-5-v2\naws_bedrock_inference_profiles:\n  anthropic.claude-sonnet-4-5-v2: us.anthropic.claude-sonnet-4-5-v2\n";
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        let provider = BedrockProvider::new(&config).unwrap();
+        assert_eq!(provider.credentials.read().unwrap().region, "us-west-2");
+        assert_eq!(provider.model_id, "us.anthropic.claude-sonnet-4-5-v2");
+        assert!(provider.capabilities.vision);
+    }
+
+    #[test]
+    fn test_is_clock_skew_error() {
+        assert!(is_clock_skew_error(
+            Some("InvalidSignatureException:http://internal.amazon.com/coral/"),
+            r#"{"message":"Signature expired: 20261016T120000Z is now earlier than 20261016T121500Z (20261016T122000Z - 5 min.)"}"#
+        ));
+        assert!(is_clock_skew_error(
+            None,
+            r#"{"__type":"InvalidSignatureException","message":"Signature not yet current: 20261016T130000Z is still later than 20261016T120500Z (20261016T120000Z + 5 min.)"}"#
+        ));
+        assert!(is_clock_skew_error(Some("RequestTimeTooSkewed"), ""));
+        // A wrong secret key is not a clock problem
+        assert!(!is_clock_skew_error(
+            Some("InvalidSignatureException"),
+            r#"{"message":"The request signature we calculated does not match the signature you provided."}"#
+        ));
+        assert!(!is_clock_skew_error(Some("ValidationException"), "{}"));
+    }
+
+    #[test]
+    fn test_clock_offset_from_date() {
+        let local = chrono::DateTime::parse_from_rfc3339("2026-10-16T12:00:00Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        assert_eq!(
+            clock_offset_from_date("Fri, 16 Oct 2026 12:10:00 GMT", local),
+            Some(600)
+        );
+        assert_eq!(
+            clock_offset_from_date("Fri, 16 Oct 2026 11:59:30 GMT", local),
+            Some(-30)
+        );
+        assert_eq!(clock_offset_from_date("yesterday", local), None);
+    }
+
+    #[tokio::test]
+    async fn test_send_signed_resigns_after_clock_skew() {
+        use std::io::{Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let aws_now = chrono::Utc::now() + chrono::Duration::hours(1);
+        let server = std::thread::spawn(move || {
+            let responses = [
+                (
+                    "403 Forbidden",
+                    format!(
+                        "x-amzn-ErrorType: InvalidSignatureException\r\nDate: {}\r\n",
+                        aws_now.format("%a, %d %b %Y %H:%M:%S GMT")
+                    ),
+                    r#"{"message":"Signature expired: 20261016T120000Z is now earlier than 20261016T125500Z (20261016T130000Z - 5 min.)"}"#,
+                ),
+                (
+                    "200 OK",
+                    String::new(),
+                    r#"{"output":{"message":{"role":"assistant","content":[{"text":"hi"}]}},"stopReason":"end_turn"}"#,
+                ),
+            ];
+            let mut amz_dates = Vec::new();
+            for (status, headers, body) in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                stream
+                    .set_read_timeout(Some(std::time::Duration::from_secs(2)))
+                    .unwrap();
+                let mut buf = [0u8; 16384];
+                let n = stream.read(&mut buf).unwrap_or(0);
+                let request = String::from_utf8_lossy(&buf[..n]).to_lowercase();
+                amz_dates.push(
+                    request
+                        .lines()
+                        .find_map(|l| l.strip_prefix("x-amz-date: "))
+                        .unwrap_or("")
+                        .to_uppercase(),
+                );
+                let response = format!(
+                    "HTTP/1.1 {status}\r\n{headers}Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                let _ = stream.write_all(response.as_bytes());
+            }
+            amz_dates
+        });
+
+        let yaml = format!(
+            "api_key: key\nllm_provider: bedrock\naws_region: us-east-1\naws_access_key_id: AKID\naws_secret_access_key: SECRET\nmodel: anthropic.claude-3-haiku\naws_endpoint_url: {endpoint}\n"
+        );
+        let config: Config = serde_yaml::from_str(&yaml).unwrap();
+        let provider = BedrockProvider::new(&config).unwrap();
+        let response = provider
+            .send_message(
+                "",
+                vec![Message {
+                    role: "user".into(),
+                    content: MessageContent::Text("hello".into()),
+                }],
+                None,
+            )
+            .await
+            .unwrap();
+        assert!(matches!(
+            &response.content[0],
+            ResponseContentBlock::Text { text } if text == "hi"
+        ));
+
+        let amz_dates = server.join().unwrap();
+        let parse = |d: &str| {
+            chrono::NaiveDateTime::parse_from_str(d, "%Y%m%dT%H%M%SZ")
+                .unwrap()
+                .and_utc()
+        };
+        // The retry is signed about an hour ahead, at AWS time
+        let shift = parse(&amz_dates[1]) - parse(&amz_dates[0]);
+        assert!((shift.num_seconds() - 3600).abs() <= 5, "shift {shift}");
+        let offset = provider.clock_offset_secs.load(Ordering::Relaxed);
+        assert!((offset - 3600).abs() <= 5, "offset {offset}");
+    }
+
+    #[test]
+    fn test_bedrock_endpoint_variants() {
+        assert_eq!(
+            bedrock_endpoint("us-gov-west-1", None, true),
+            "https://bedrock-runtime-fips.us-gov-west-1.amazonaws.com"
+        );
+        assert_eq!(
+            bedrock_endpoint("cn-north-1", None, false),
+            "https://bedrock-runtime.cn-north-1.amazonaws.com.cn"
+        );
+        assert_eq!(
+            bedrock_endpoint(
+                "us-east-1",
+                Some("https://vpce-0abc.bedrock-runtime.us-east-1.vpce.amazonaws.com/"),
+                true
+            ),
+            "https://vpce-0abc.bedrock-runtime.us-east-1.vpce.amazonaws.com"
+        );
+
+        let mut provider = make_bedrock_provider("none");
+        provider.endpoint = bedrock_endpoint("us-east-1", Some("http://localhost:4566"), false);
+        assert_eq!(
+            provider.converse_stream_url(),
+            "http://localhost:4566/model/anthropic.claude-sonnet-4-5-v2/converse-stream"
+        );
+    }
+
+    #[test]
+    fn test_normalize_region_set() {
+        assert_eq!(normalize_region_set(None), None);
+        assert_eq!(normalize_region_set(Some(" , ")), None);
+        assert_eq!(
+            normalize_region_set(Some("us-east-1, us-west-2")).as_deref(),
+            Some("us-east-1,us-west-2")
+        );
+        assert_eq!(normalize_region_set(Some("*")).as_deref(), Some("*"));
+    }
+
+    #[test]
+    fn test_sigv4a_signing_key() {
+        // Derivation is deterministic and depends on both halves of the key
+        let key = sigv4a_private_key("AKID", "SECRET").unwrap();
+        assert_eq!(key, sigv4a_private_key("AKID", "SECRET").unwrap());
+        assert_ne!(key, sigv4a_private_key("AKID2", "SECRET").unwrap());
+        assert_ne!(key, sigv4a_private_key("AKID", "SECRET2").unwrap());
+        // ring checks the derived public key matches the private key
+        assert!(sigv4a_key_pair("AKID", "SECRET").is_ok());
+        // 1·G is the base point itself
+        let g = p256_public_key(&BigUint::from(1u8)).unwrap();
+        assert_eq!(
+            hex::encode(&g[1..33]),
+            "6b17d1f2e12c4247f8bce6e563a440f277037d812deb33a0f4a13945d898c296"
+        );
+    }
+
+    #[test]
+    fn test_sign_request_v4a_verifies() {
+        let url: reqwest::Url = "https://bedrock-runtime.us-east-1.amazonaws.com/model/m/converse"
+            .parse()
+            .unwrap();
+        let now = chrono::DateTime::parse_from_rfc3339("2025-01-15T12:00:00Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        let headers = sign_request_v4a(
+            "POST",
+            &url,
+            b"{}",
+            "us-east-1,us-west-2",
+            "bedrock",
+            "AKID",
+            "SECRET",
+            Some("token"),
+            &now,
+        )
+        .unwrap();
+        let header = |name: &str| {
+            headers
+                .iter()
+                .find(|(k, _)| k == name)
+                .map(|(_, v)| v.clone())
+                .unwrap()
+        };
+        assert_eq!(header("X-Amz-Region-Set"), "us-east-1,us-west-2");
+        assert_eq!(header("X-Amz-Security-Token"), "token");
+        let auth = header("Authorization");
+        let prefix = "AWS4-ECDSA-P256-SHA256 Credential=AKID/20250115/bedrock/aws4_request, \
+                      SignedHeaders=host;x-amz-date;x-amz-region-set;x-amz-security-token, Signature=";
+        assert!(auth.starts_with(prefix), "{auth}");
+
+        let (canonical, _) = canonical_request(
+            "POST",
+            &url,
+            b"{}",
+            "20250115T120000Z",
+            &[
+                ("x-amz-region-set", "us-east-1,us-west-2"),
+                ("x-amz-security-token", "token"),
+            ],
+        );
+        let string_to_sign = format!(
+            "AWS4-ECDSA-P256-SHA256\n20250115T120000Z\n20250115/bedrock/aws4_request\n{}",
+            sha256_hex(canonical.as_bytes())
+        );
+        let private_key = sigv4a_private_key("AKID", "SECRET").unwrap();
+        let public_key = p256_public_key(&BigUint::from_bytes_be(&private_key)).unwrap();
+        let signature = hex::decode(&auth[prefix.len()..]).unwrap();
+        ring::signature::UnparsedPublicKey::new(
+            &ring::signature::ECDSA_P256_SHA256_ASN1,
+            public_key,
+        )
+        .verify(string_to_sign.as_bytes(), &signature)
+        .unwrap();
+    }
+
+    #[test]
+    fn test_sign_request_includes_non_default_port_in_host() {
+        let url: reqwest::Url = "http://localhost:4566/model/test/converse".parse().unwrap();
+        let now = chrono::Utc::now();
+        let with_port = sign_request(
+            "POST",
+            &url,
+            b"{}",
+            "us-east-1",
+            "bedrock",
+            "AKID",
+            "SECRET",
+            None,
+            &now,
+        );
+        let url: reqwest::Url = "http://localhost/model/test/converse".parse().unwrap();
+        let without_port = sign_request(
+            "POST",
+            &url,
+            b"{}",
+            "us-east-1",
+            "bedrock",
+            "AKID",
+            "SECRET",
+            None,
+            &now,
+        );
+        assert_ne!(with_port[0].1, without_port[0].1);
+    }
+
+    #[test]
+    fn test_bedrock_capabilities_by_model() {
+        let model = "us.anthropic.claude-sonnet-4-5-20250929-v1:0";
+        let limits = crate::model_limits::builtin_limits(model).unwrap();
+        let claude = bedrock_capabilities(model, limits, "5m", 8192);
+        assert!(claude.vision && claude.tools && claude.prompt_caching);
+        assert_eq!(claude.max_context_tokens, Some(200_000));
+        assert_eq!(claude.max_output_tokens, Some(8192));
+        assert!(
+            !bedrock_capabilities("anthropic.claude-v2", ModelLimits::default(), "none", 8192)
+                .prompt_caching
+        );
+
+        let llama = bedrock_capabilities(
+            "meta.llama3-1-70b-instruct-v1:0",
+            ModelLimits::default(),
+            "5m",
+            4096,
+        );
+        assert!(!llama.vision && llama.tools && !llama.prompt_caching);
+        assert_eq!(llama.max_context_tokens, None);
+        assert_eq!(llama.max_output_tokens, Some(4096));
+
+        let titan = bedrock_capabilities(
+            "amazon.titan-text-premier-v1:0",
+            ModelLimits::default(),
+            "none",
+            4096,
+        );
+        assert!(!titan.vision && !titan.tools && !titan.documents);
+        assert!(claude.documents && llama.documents);
+    }
+
+    #[test]
+    fn test_canonical_uri_double_encodes_segments() {
+        assert_eq!(
+            canonical_uri("/model/anthropic.claude-v2%3A1/converse"),
+            "/model/anthropic.claude-v2%253A1/converse"
+        );
+        assert_eq!(
+            canonical_uri("/model/plain-id/converse"),
+            "/model/plain-id/converse"
+        );
+    }
+
     fn sample_tools() -> Vec<ToolDefinition> {
         vec![
             ToolDefinition {
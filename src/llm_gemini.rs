@@ -0,0 +1,1007 @@
+// ---------------------------------------------------------------------------
+// Google Gemini API provider (native, NOT the OpenAI-compatible endpoint)
+//
+// Endpoints:
+//   POST /models/{model}:generateContent
+//   POST /models/{model}:streamGenerateContent?alt=sse
+//
+// Authentication: `x-goog-api-key` header
+// ---------------------------------------------------------------------------
+
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use futures_util::StreamExt;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::config::{Config, InferenceParams};
+use crate::error::RayClawError;
+use crate::llm::{
+    normalize_stop_reason, sanitize_messages, send_with_retry, LlmProvider, ProviderCapabilities,
+    RetryPolicy, SseEventParser,
+};
+use crate::llm_types::{
+    ContentBlock, Message, MessageContent, MessagesResponse, ResponseContentBlock, ToolDefinition,
+    Usage,
+};
+
+const DEFAULT_GEMINI_BASE_URL: &str = "https://generativelanguage.googleapis.com/v1beta";
+
+/// JSON Schema keywords the Gemini `Schema` object accepts. Anything else
+/// (`additionalProperties`, `$schema`, `const`, ...) is rejected with a 400.
+const GEMINI_SCHEMA_KEYS: &[&str] = &[
+    "type",
+    "format",
+    "title",
+    "description",
+    "nullable",
+    "enum",
+    "items",
+    "minItems",
+    "maxItems",
+    "properties",
+    "required",
+    "minProperties",
+    "maxProperties",
+    "minLength",
+    "maxLength",
+    "pattern",
+    "minimum",
+    "maximum",
+    "anyOf",
+    "default",
+    "example",
+    "propertyOrdering",
+];
+
+// ---------------------------------------------------------------------------
+// Message translation: internal types ↔ Gemini generateContent format
+// ---------------------------------------------------------------------------
+
+fn translate_messages_to_gemini(messages: &[Message]) -> Vec<serde_json::Value> {
+    // functionResponse parts are matched by function name, not call ID, so
+    // remember which tool each tool_use ID belongs to.
+    let tool_names: HashMap<&str, &str> = messages
+        .iter()
+        .filter_map(|msg| match &msg.content {
+            MessageContent::Blocks(blocks) => Some(blocks),
+            MessageContent::Text(_) => None,
+        })
+        .flatten()
+        .filter_map(|block| match block {
+            ContentBlock::ToolUse { id, name, .. } => Some((id.as_str(), name.as_str())),
+            _ => None,
+        })
+        .collect();
+
+    messages
+        .iter()
+        .filter_map(|msg| {
+            let parts = match &msg.content {
+                MessageContent::Text(text) => {
+                    if text.trim().is_empty() {
+                        return None;
+                    }
+                    vec![serde_json::json!({ "text": text })]
+                }
+                MessageContent::Blocks(blocks) => {
+                    let filtered: Vec<_> = blocks
+                        .iter()
+                        .filter_map(|block| match block {
+                            ContentBlock::Text { text } if text.trim().is_empty() => None,
+                            ContentBlock::Text { text } => {
+                                Some(serde_json::json!({ "text": text }))
+                            }
+                            // Thinking blocks come from other providers; Gemini's
+                            // thought signatures travel on the function calls
+                            ContentBlock::Thinking { .. }
+                            | ContentBlock::RedactedThinking { .. } => None,
+                            ContentBlock::Image { source } => Some(serde_json::json!({
+                                "inlineData": {
+                                    "mimeType": source.media_type,
+                                    "data": source.data,
+                                }
+                            })),
+                            // Gemini reads PDFs from inline data like images
+                            ContentBlock::Document { source, .. } => Some(serde_json::json!({
+                                "inlineData": {
+                                    "mimeType": source.media_type,
+                                    "data": source.data,
+                                }
+                            })),
+                            ContentBlock::ToolUse {
+                                id,
+                                name,
+                                input,
+                                signature,
+                            } => {
+                                let mut part = serde_json::json!({
+                                    "functionCall": {
+                                        "id": id,
+                                        "name": name,
+                                        "args": input,
+                                    }
+                                });
+                                // Gemini 3 rejects function calls sent back
+                                // without the signature it returned them with
+                                if let Some(signature) = signature {
+                                    part["thoughtSignature"] = serde_json::json!(signature);
+                                }
+                                Some(part)
+                            }
+                            ContentBlock::ToolResult {
+                                tool_use_id,
+                                content,
+                                is_error,
+                            } => {
+                                let name = tool_names
+                                    .get(tool_use_id.as_str())
+                                    .copied()
+                                    .unwrap_or("unknown_tool");
+                                Some(serde_json::json!({
+                                    "functionResponse": {
+                                        "id": tool_use_id,
+                                        "name": name,
+                                        "response": function_response_payload(
+                                            content,
+                                            is_error.unwrap_or(false),
+                                        ),
+                                    }
+                                }))
+                            }
+                        })
+                        .collect();
+                    if filtered.is_empty() {
+                        return None;
+                    }
+                    filtered
+                }
+            };
+            let role = if msg.role == "assistant" {
+                "model"
+            } else {
+                "user"
+            };
+            Some(serde_json::json!({
+                "role": role,
+                "parts": parts,
+            }))
+        })
+        .collect()
+}
+
+/// `functionResponse.response` must be a JSON object. Object results pass
+/// through; anything else is wrapped under `result` (or `error`).
+fn function_response_payload(content: &str, is_error: bool) -> serde_json::Value {
+    let key = if is_error { "error" } else { "result" };
+    match serde_json::from_str::<serde_json::Value>(content) {
+        Ok(serde_json::Value::Object(map)) if !is_error => serde_json::Value::Object(map),
+        Ok(value) if !value.is_string() => serde_json::json!({ key: value }),
+        _ => serde_json::json!({ key: content }),
+    }
+}
+
+fn translate_tools_to_gemini(tools: &[ToolDefinition]) -> serde_json::Value {
+    let declarations: Vec<serde_json::Value> = tools
+        .iter()
+        .map(|t| {
+            serde_json::json!({
+                "name": t.name,
+                "description": t.description,
+                "parameters": sanitize_schema(&t.input_schema),
+            })
+        })
+        .collect();
+    serde_json::json!([{ "functionDeclarations": declarations }])
+}
+
+/// Reduce a JSON Schema to the OpenAPI subset Gemini understands: unknown
+/// keywords are dropped and `"type": [T, "null"]` becomes `nullable`.
+fn sanitize_schema(schema: &serde_json::Value) -> serde_json::Value {
+    let Some(obj) = schema.as_object() else {
+        return schema.clone();
+    };
+    let mut out = serde_json::Map::new();
+    for (key, value) in obj {
+        if !GEMINI_SCHEMA_KEYS.contains(&key.as_str()) {
+            continue;
+        }
+        let value = match key.as_str() {
+            "type" => match value.as_array() {
+                Some(types) => {
+                    if types.iter().any(|t| t == "null") {
+                        out.insert("nullable".into(), serde_json::Value::Bool(true));
+                    }
+                    types
+                        .iter()
+                        .find(|t| *t != "null")
+                        .cloned()
+                        .unwrap_or_else(|| serde_json::json!("string"))
+                }
+                None => value.clone(),
+            },
+            "properties" => match value.as_object() {
+                Some(props) => serde_json::Value::Object(
+                    props
+                        .iter()
+                        .map(|(name, prop)| (name.clone(), sanitize_schema(prop)))
+                        .collect(),
+                ),
+                None => value.clone(),
+            },
+            "items" => sanitize_schema(value),
+            "anyOf" => match value.as_array() {
+                Some(variants) => {
+                    serde_json::Value::Array(variants.iter().map(sanitize_schema).collect())
+                }
+                None => value.clone(),
+            },
+            _ => value.clone(),
+        };
+        out.insert(key.clone(), value);
+    }
+    serde_json::Value::Object(out)
+}
+
+/// Map a Gemini `finishReason` onto the internal stop reasons.
+fn map_finish_reason(reason: Option<&str>, has_tool_calls: bool) -> Option<String> {
+    if has_tool_calls {
+        return Some("tool_use".into());
+    }
+    let mapped = match reason {
+        Some("STOP") | None => None,
+        Some("MAX_TOKENS") => Some("max_tokens".to_string()),
+        Some(other) => Some(other.to_lowercase()),
+    };
+    normalize_stop_reason(mapped)
+}
+
+fn translate_usage(body: &serde_json::Value) -> Option<Usage> {
+    body.get("usageMetadata").map(|u| {
+        let count = |key: &str| u.get(key).and_then(|v| v.as_u64()).unwrap_or(0) as u32;
+        // promptTokenCount includes the cached part
+        let cached = count("cachedContentTokenCount");
+        Usage {
+            input_tokens: count("promptTokenCount").saturating_sub(cached),
+            // Thinking tokens are billed as output
+            output_tokens: count("candidatesTokenCount") + count("thoughtsTokenCount"),
+            cache_read_input_tokens: cached,
+            ..Usage::default()
+        }
+    })
+}
+
+/// Pull the function call out of a part holding `functionCall`, keeping the
+/// part's `thoughtSignature`. Gemini only returns call IDs on some models,
+/// so one is generated when missing.
+fn translate_function_call(
+    part: &serde_json::Value,
+    call: &serde_json::Value,
+) -> ResponseContentBlock {
+    let id = call
+        .get("id")
+        .and_then(|v| v.as_str())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string())
+        .unwrap_or_else(|| format!("call_{}", uuid::Uuid::new_v4().simple()));
+    let name = call
+        .get("name")
+        .and_then(|v| v.as_str())
+        .unwrap_or("")
+        .to_string();
+    let input = call
+        .get("args")
+        .cloned()
+        .unwrap_or(serde_json::Value::Object(Default::default()));
+    let signature = part
+        .get("thoughtSignature")
+        .and_then(|v| v.as_str())
+        .map(str::to_string);
+    ResponseContentBlock::ToolUse {
+        id,
+        name,
+        input,
+        signature,
+    }
+}
+
+/// Content parts of the first candidate, excluding thought summaries.
+fn candidate_parts(body: &serde_json::Value) -> impl Iterator<Item = &serde_json::Value> {
+    body.get("candidates")
+        .and_then(|c| c.get(0))
+        .and_then(|c| c.get("content"))
+        .and_then(|c| c.get("parts"))
+        .and_then(|p| p.as_array())
+        .into_iter()
+        .flatten()
+        .filter(|part| {
+            !part
+                .get("thought")
+                .and_then(|t| t.as_bool())
+                .unwrap_or(false)
+        })
+}
+
+fn finish_reason(body: &serde_json::Value) -> Option<&str> {
+    body.get("candidates")
+        .and_then(|c| c.get(0))
+        .and_then(|c| c.get("finishReason"))
+        .and_then(|v| v.as_str())
+}
+
+/// A prompt rejected outright comes back with no candidates and a
+/// `promptFeedback.blockReason`.
+fn check_prompt_blocked(body: &serde_json::Value) -> Result<(), RayClawError> {
+    let has_candidates = body
+        .get("candidates")
+        .and_then(|c| c.as_array())
+        .is_some_and(|c| !c.is_empty());
+    if has_candidates {
+        return Ok(());
+    }
+    match body
+        .get("promptFeedback")
+        .and_then(|f| f.get("blockReason"))
+        .and_then(|v| v.as_str())
+    {
+        Some(reason) => Err(RayClawError::LlmApi(format!(
+            "Gemini blocked the prompt: {reason}"
+        ))),
+        None => Ok(()),
+    }
+}
+
+fn translate_gemini_response(body: &serde_json::Value) -> Result<MessagesResponse, RayClawError> {
+    check_prompt_blocked(body)?;
+
+    let mut content = Vec::new();
+    for part in candidate_parts(body) {
+        if let Some(text) = part.get("text").and_then(|t| t.as_str()) {
+            if let Some(ResponseContentBlock::Text { text: prev }) = content.last_mut() {
+                prev.push_str(text);
+            } else {
+                content.push(ResponseContentBlock::Text {
+                    text: text.to_string(),
+                });
+            }
+        } else if let Some(call) = part.get("functionCall") {
+            content.push(translate_function_call(part, call));
+        }
+    }
+
+    let has_tool_calls = content
+        .iter()
+        .any(|b| matches!(b, ResponseContentBlock::ToolUse { .. }));
+
+    Ok(MessagesResponse {
+        stop_reason: map_finish_reason(finish_reason(body), has_tool_calls),
+        usage: translate_usage(body),
+        content,
+    })
+}
+
+// ---------------------------------------------------------------------------
+// GeminiProvider
+// ---------------------------------------------------------------------------
+
+pub struct GeminiProvider {
+    http: reqwest::Client,
+    api_key: String,
+    model: String,
+    max_tokens: u32,
+    inference: InferenceParams,
+    /// Base URL of the Gemini API (including the version), without a trailing slash
+    base_url: String,
+    capabilities: ProviderCapabilities,
+    retry: RetryPolicy,
+}
+
+/// Gemini API base URL. `llm_base_url` overrides the public endpoint; a
+/// trailing `/openai` (the OpenAI-compatible preset) is stripped.
+fn resolve_gemini_base_url(configured: Option<&str>) -> String {
+    let base = configured
+        .map(str::trim)
+        .filter(|u| !u.is_empty())
+        .unwrap_or(DEFAULT_GEMINI_BASE_URL)
+        .trim_end_matches('/');
+    base.strip_suffix("/openai").unwrap_or(base).to_string()
+}
+
+impl GeminiProvider {
+    pub fn new(config: &Config) -> Self {
+        GeminiProvider {
+            retry: RetryPolicy::from_config(config),
+            http: reqwest::Client::new(),
+            api_key: config.api_key.clone(),
+            model: config.model.clone(),
+            max_tokens: config.max_tokens,
+            inference: config.inference.clone(),
+            base_url: resolve_gemini_base_url(config.llm_base_url.as_deref()),
+            capabilities: ProviderCapabilities {
+                vision: true,
+                documents: true,
+                tools: true,
+                streaming: true,
+                ..ProviderCapabilities::default()
+            }
+            .with_limits(
+                config.model_limits_for(&config.model).unwrap_or_default(),
+                config.max_tokens,
+            ),
+        }
+    }
+
+    fn generate_url(&self) -> String {
+        format!(
+            "{}/models/{}:generateContent",
+            self.base_url,
+            urlencoding::encode(&self.model)
+        )
+    }
+
+    fn stream_url(&self) -> String {
+        format!(
+            "{}/models/{}:streamGenerateContent?alt=sse",
+            self.base_url,
+            urlencoding::encode(&self.model)
+        )
+    }
+
+    fn build_request_body(
+        &self,
+        system: &str,
+        messages: &[Message],
+        tools: Option<&[ToolDefinition]>,
+    ) -> serde_json::Value {
+        let mut body = serde_json::json!({
+            "contents": translate_messages_to_gemini(messages),
+            "generationConfig": {
+                "maxOutputTokens": self.max_tokens,
+            },
+        });
+        let params = &self.inference;
+        let generation = &mut body["generationConfig"];
+        if let Some(temperature) = params.temperature {
+            generation["temperature"] = serde_json::json!(temperature);
+        }
+        if let Some(top_p) = params.top_p {
+            generation["topP"] = serde_json::json!(top_p);
+        }
+        if let Some(top_k) = params.top_k {
+            generation["topK"] = serde_json::json!(top_k);
+        }
+        if !params.stop_sequences.is_empty() {
+            generation["stopSequences"] = serde_json::json!(params.stop_sequences);
+        }
+
+        if !system.is_empty() {
+            body["systemInstruction"] = serde_json::json!({ "parts": [{ "text": system }] });
+        }
+
+        if let Some(tools) = tools {
+            if !tools.is_empty() {
+                body["tools"] = translate_tools_to_gemini(tools);
+            }
+        }
+
+        body
+    }
+
+    /// POST `body` to `url`, retrying per the configured retry policy.
+    async fn post(
+        &self,
+        url: &str,
+        body: &serde_json::Value,
+        label: &str,
+    ) -> Result<reqwest::Response, RayClawError> {
+        let response = send_with_retry(self.retry, "Gemini", || {
+            Ok(self
+                .http
+                .post(url)
+                .header("x-goog-api-key", &self.api_key)
+                .header("Content-Type", "application/json")
+                .json(body))
+        })
+        .await?;
+        let status = response.status();
+
+        if status.is_success() {
+            return Ok(response);
+        }
+
+        let err_body = response.text().await.unwrap_or_default();
+        Err(RayClawError::LlmApi(format!(
+            "Gemini {label} HTTP {status}: {err_body}"
+        )))
+    }
+}
+
+/// Accumulates `streamGenerateContent` chunks into a single response.
+#[derive(Default)]
+struct GeminiStreamState {
+    content: Vec<ResponseContentBlock>,
+    finish_reason: Option<String>,
+    usage: Option<Usage>,
+    blocked: Option<RayClawError>,
+}
+
+impl GeminiStreamState {
+    fn push_event(&mut self, data: &str, text_tx: Option<&UnboundedSender<String>>) {
+        let Ok(chunk) = serde_json::from_str::<serde_json::Value>(data) else {
+            return;
+        };
+        if let Err(e) = check_prompt_blocked(&chunk) {
+            self.blocked = Some(e);
+            return;
+        }
+
+        for part in candidate_parts(&chunk) {
+            if let Some(text) = part.get("text").and_then(|t| t.as_str()) {
+                if text.is_empty() {
+                    continue;
+                }
+                if let Some(tx) = text_tx {
+                    let _ = tx.send(text.to_string());
+                }
+                if let Some(ResponseContentBlock::Text { text: prev }) = self.content.last_mut() {
+                    prev.push_str(text);
+                } else {
+                    self.content.push(ResponseContentBlock::Text {
+                        text: text.to_string(),
+                    });
+                }
+            } else if let Some(call) = part.get("functionCall") {
+                // Function calls arrive whole, never split across chunks
+                self.content.push(translate_function_call(part, call));
+            }
+        }
+
+        if let Some(reason) = finish_reason(&chunk) {
+            self.finish_reason = Some(reason.to_string());
+        }
+        if let Some(usage) = translate_usage(&chunk) {
+            self.usage = Some(usage);
+        }
+    }
+
+    fn finish(self) -> Result<MessagesResponse, RayClawError> {
+        if let Some(err) = self.blocked {
+            if self.content.is_empty() {
+                return Err(err);
+            }
+        }
+        let has_tool_calls = self
+            .content
+            .iter()
+            .any(|b| matches!(b, ResponseContentBlock::ToolUse { .. }));
+        Ok(MessagesResponse {
+            stop_reason: map_finish_reason(self.finish_reason.as_deref(), has_tool_calls),
+            usage: self.usage,
+            content: self.content,
+        })
+    }
+}
+
+#[async_trait]
+impl LlmProvider for GeminiProvider {
+    fn endpoint(&self) -> Option<String> {
+        Some(self.base_url.clone())
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        self.capabilities
+    }
+
+    fn request_body(
+        &self,
+        system: &str,
+        messages: &[Message],
+        tools: Option<&[ToolDefinition]>,
+        _stream: bool,
+    ) -> Option<serde_json::Value> {
+        let messages = sanitize_messages(messages.to_vec());
+        Some(self.build_request_body(system, &messages, tools))
+    }
+
+    async fn send_message(
+        &self,
+        system: &str,
+        messages: Vec<Message>,
+        tools: Option<Vec<ToolDefinition>>,
+    ) -> Result<MessagesResponse, RayClawError> {
+        let messages = sanitize_messages(messages);
+        let body = self.build_request_body(system, &messages, tools.as_deref());
+
+        let response = self
+            .post(&self.generate_url(), &body, "generateContent")
+            .await?;
+        let response_body: serde_json::Value = response.json().await?;
+        translate_gemini_response(&response_body)
+    }
+
+    async fn send_message_stream(
+        &self,
+        system: &str,
+        messages: Vec<Message>,
+        tools: Option<Vec<ToolDefinition>>,
+        text_tx: Option<&UnboundedSender<String>>,
+    ) -> Result<MessagesResponse, RayClawError> {
+        let messages = sanitize_messages(messages);
+        let body = self.build_request_body(system, &messages, tools.as_deref());
+
+        let response = self
+            .post(&self.stream_url(), &body, "streamGenerateContent")
+            .await?;
+
+        let mut sse = SseEventParser::default();
+        let mut state = GeminiStreamState::default();
+        let mut stream = response.bytes_stream();
+
+        while let Some(chunk_result) = stream.next().await {
+            let chunk = chunk_result?;
+            for data in sse.push_chunk(&String::from_utf8_lossy(&chunk)) {
+                state.push_event(&data, text_tx);
+            }
+        }
+        for data in sse.finish() {
+            state.push_event(&data, text_tx);
+        }
+
+        state.finish()
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm_types::ImageSource;
+
+    fn blocks(role: &str, blocks: Vec<ContentBlock>) -> Message {
+        Message {
+            role: role.into(),
+            content: MessageContent::Blocks(blocks),
+        }
+    }
+
+    #[test]
+    fn test_translate_messages_roles_and_parts() {
+        let messages = vec![
+            Message {
+                role: "user".into(),
+                content: MessageContent::Text("hello".into()),
+            },
+            Message {
+                role: "assistant".into(),
+                content: MessageContent::Text("hi there".into()),
+            },
+            Message {
+                role: "user".into(),
+                content: MessageContent::Text("   ".into()),
+            },
+        ];
+        let out = translate_messages_to_gemini(&messages);
+        assert_eq!(out.len(), 2);
+        assert_eq!(out[0]["role"], "user");
+        assert_eq!(out[0]["parts"][0]["text"], "hello");
+        assert_eq!(out[1]["role"], "model");
+        assert_eq!(out[1]["parts"][0]["text"], "hi there");
+    }
+
+    #[test]
+    fn test_translate_image_block() {
+        let messages = vec![blocks(
+            "user",
+            vec![
+                ContentBlock::Image {
+                    source: ImageSource {
+                        source_type: "base64".into(),
+                        media_type: "image/png".into(),
+                        data: "iVBORw0KGgo=".into(),
+                    },
+                },
+                ContentBlock::Text {
+                    text: "what is this?".into(),
+                },
+            ],
+        )];
+        let out = translate_messages_to_gemini(&messages);
+        let parts = out[0]["parts"].as_array().unwrap();
+        assert_eq!(parts[0]["inlineData"]["mimeType"], "image/png");
+        assert_eq!(parts[0]["inlineData"]["data"], "iVBORw0KGgo=");
+        assert_eq!(parts[1]["text"], "what is this?");
+    }
+
+    #[test]
+    fn test_translate_tool_round_trip_uses_function_name() {
+        let messages = vec![
+            blocks(
+                "assistant",
+                vec![ContentBlock::ToolUse {
+                    id: "call_1".into(),
+                    name: "read_file".into(),
+                    input: serde_json::json!({"path": "a.txt"}),
+                    signature: None,
+                }],
+            ),
+            blocks(
+                "user",
+                vec![
+                    ContentBlock::ToolResult {
+                        tool_use_id: "call_1".into(),
+                        content: "file contents".into(),
+                        is_error: None,
+                    },
+                    ContentBlock::ToolResult {
+                        tool_use_id: "call_2".into(),
+                        content: "{\"ok\":true}".into(),
+                        is_error: None,
+                    },
+                ],
+            ),
+        ];
+        let out = translate_messages_to_gemini(&messages);
+        assert_eq!(out[0]["role"], "model");
+        assert_eq!(out[0]["parts"][0]["functionCall"]["name"], "read_file");
+        assert_eq!(out[0]["parts"][0]["functionCall"]["args"]["path"], "a.txt");
+
+        let response = &out[1]["parts"][0]["functionResponse"];
+        assert_eq!(response["name"], "read_file");
+        assert_eq!(response["id"], "call_1");
+        assert_eq!(response["response"]["result"], "file contents");
+        assert_eq!(
+            out[1]["parts"][1]["functionResponse"]["response"]["ok"],
+            true
+        );
+    }
+
+    #[test]
+    fn test_function_response_payload() {
+        assert_eq!(
+            function_response_payload("{\"a\":1}", false),
+            serde_json::json!({"a": 1})
+        );
+        assert_eq!(
+            function_response_payload("[1,2]", false),
+            serde_json::json!({"result": [1, 2]})
+        );
+        assert_eq!(
+            function_response_payload("boom", true),
+            serde_json::json!({"error": "boom"})
+        );
+        assert_eq!(
+            function_response_payload("{\"code\":2}", true),
+            serde_json::json!({"error": {"code": 2}})
+        );
+    }
+
+    #[test]
+    fn test_translate_tools_sanitizes_schema() {
+        let tools = vec![ToolDefinition {
+            name: "bash".into(),
+            description: "Run a command".into(),
+            input_schema: serde_json::json!({
+                "$schema": "http://json-schema.org/draft-07/schema#",
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {
+                    "command": {"type": "string", "description": "cmd"},
+                    "timeout": {"type": ["integer", "null"]},
+                    "tags": {
+                        "type": "array",
+                        "items": {"type": "string", "additionalProperties": false}
+                    }
+                },
+                "required": ["command"]
+            }),
+        }];
+        let out = translate_tools_to_gemini(&tools);
+        let decl = &out[0]["functionDeclarations"][0];
+        assert_eq!(decl["name"], "bash");
+        assert_eq!(decl["description"], "Run a command");
+        let params = &decl["parameters"];
+        assert!(params.get("$schema").is_none());
+        assert!(params.get("additionalProperties").is_none());
+        assert_eq!(params["required"][0], "command");
+        assert_eq!(params["properties"]["timeout"]["type"], "integer");
+        assert_eq!(params["properties"]["timeout"]["nullable"], true);
+        assert!(params["properties"]["tags"]["items"]
+            .get("additionalProperties")
+            .is_none());
+    }
+
+    #[test]
+    fn test_translate_response_text_and_usage() {
+        let body = serde_json::json!({
+            "candidates": [{
+                "content": {
+                    "role": "model",
+                    "parts": [
+                        {"text": "planning...", "thought": true},
+                        {"text": "Hello, "},
+                        {"text": "world"}
+                    ]
+                },
+                "finishReason": "STOP"
+            }],
+            "usageMetadata": {
+                "promptTokenCount": 12,
+                "candidatesTokenCount": 5,
+                "thoughtsTokenCount": 3
+            }
+        });
+        let resp = translate_gemini_response(&body).unwrap();
+        assert_eq!(resp.content.len(), 1);
+        match &resp.content[0] {
+            ResponseContentBlock::Text { text } => assert_eq!(text, "Hello, world"),
+            other => panic!("unexpected block: {other:?}"),
+        }
+        assert_eq!(resp.stop_reason.as_deref(), Some("end_turn"));
+        let usage = resp.usage.unwrap();
+        assert_eq!(usage.input_tokens, 12);
+        assert_eq!(usage.output_tokens, 8);
+    }
+
+    #[test]
+    fn test_translate_response_function_call() {
+        let body = serde_json::json!({
+            "candidates": [{
+                "content": {
+                    "role": "model",
+                    "parts": [
+                        {"text": "Let me check."},
+                        {"functionCall": {"name": "glob", "args": {"pattern": "*.rs"}}}
+                    ]
+                },
+                "finishReason": "STOP"
+            }]
+        });
+        let resp = translate_gemini_response(&body).unwrap();
+        assert_eq!(resp.stop_reason.as_deref(), Some("tool_use"));
+        match &resp.content[1] {
+            ResponseContentBlock::ToolUse {
+                id, name, input, ..
+            } => {
+                assert!(id.starts_with("call_"));
+                assert_eq!(name, "glob");
+                assert_eq!(input["pattern"], "*.rs");
+            }
+            other => panic!("unexpected block: {other:?}"),
+        }
+        assert!(resp.usage.is_none());
+    }
+
+    #[test]
+    fn test_function_call_thought_signature_round_trip() {
+        let body = serde_json::json!({
+            "candidates": [{
+                "content": {
+                    "role": "model",
+                    "parts": [{
+                        "functionCall": {"id": "fc-1", "name": "bash", "args": {"command": "ls"}},
+                        "thoughtSignature": "c2lnbmVk"
+                    }]
+                },
+                "finishReason": "STOP"
+            }]
+        });
+        let resp = translate_gemini_response(&body).unwrap();
+        let block = resp.content[0].to_content_block().unwrap();
+        assert!(matches!(
+            &block,
+            ContentBlock::ToolUse { signature: Some(s), .. } if s == "c2lnbmVk"
+        ));
+
+        let out = translate_messages_to_gemini(&[blocks("assistant", vec![block])]);
+        let part = &out[0]["parts"][0];
+        assert_eq!(part["functionCall"]["id"], "fc-1");
+        assert_eq!(part["thoughtSignature"], "c2lnbmVk");
+    }
+
+    #[test]
+    fn test_translate_response_max_tokens_and_blocked() {
+        let body = serde_json::json!({
+            "candidates": [{
+                "content": {"role": "model", "parts": [{"text": "trunc"}]},
+                "finishReason": "MAX_TOKENS"
+            }]
+        });
+        let resp = translate_gemini_response(&body).unwrap();
+        assert_eq!(resp.stop_reason.as_deref(), Some("max_tokens"));
+
+        let blocked = serde_json::json!({
+            "promptFeedback": {"blockReason": "SAFETY"}
+        });
+        let err = translate_gemini_response(&blocked).unwrap_err();
+        assert!(err.to_string().contains("SAFETY"));
+    }
+
+    #[test]
+    fn test_stream_state_accumulates_chunks() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut state = GeminiStreamState::default();
+        state.push_event(
+            r#"{"candidates":[{"content":{"role":"model","parts":[{"text":"Hel"}]}}]}"#,
+            Some(&tx),
+        );
+        state.push_event(
+            r#"{"candidates":[{"content":{"role":"model","parts":[{"text":"lo"}]}}]}"#,
+            Some(&tx),
+        );
+        state.push_event(
+            r#"{"candidates":[{"content":{"role":"model","parts":[{"functionCall":{"id":"fc-1","name":"bash","args":{"command":"ls"}}}]},"finishReason":"STOP"}],"usageMetadata":{"promptTokenCount":7,"candidatesTokenCount":4}}"#,
+            Some(&tx),
+        );
+        let resp = state.finish().unwrap();
+
+        assert_eq!(rx.try_recv().unwrap(), "Hel");
+        assert_eq!(rx.try_recv().unwrap(), "lo");
+        assert!(rx.try_recv().is_err());
+        assert_eq!(resp.content.len(), 2);
+        match &resp.content[0] {
+            ResponseContentBlock::Text { text } => assert_eq!(text, "Hello"),
+            other => panic!("unexpected block: {other:?}"),
+        }
+        match &resp.content[1] {
+            ResponseContentBlock::ToolUse { id, name, .. } => {
+                assert_eq!(id, "fc-1");
+                assert_eq!(name, "bash");
+            }
+            other => panic!("unexpected block: {other:?}"),
+        }
+        assert_eq!(resp.stop_reason.as_deref(), Some("tool_use"));
+        assert_eq!(resp.usage.unwrap().input_tokens, 7);
+    }
+
+    #[test]
+    fn test_resolve_gemini_base_url() {
+        assert_eq!(resolve_gemini_base_url(None), DEFAULT_GEMINI_BASE_URL);
+        assert_eq!(resolve_gemini_base_url(Some("  ")), DEFAULT_GEMINI_BASE_URL);
+        assert_eq!(
+            resolve_gemini_base_url(Some(
+                "https://generativelanguage.googleapis.com/v1beta/openai/"
+            )),
+            DEFAULT_GEMINI_BASE_URL
+        );
+        assert_eq!(
+            resolve_gemini_base_url(Some("http://localhost:8080/v1/")),
+            "http://localhost:8080/v1"
+        );
+    }
+
+    #[test]
+    fn test_build_request_body() {
+        let provider = GeminiProvider {
+            retry: RetryPolicy::default(),
+            http: reqwest::Client::new(),
+            api_key: "key".into(),
+            model: "gemini-2.5-flash".into(),
+            max_tokens: 1024,
+            inference: InferenceParams {
+                temperature: Some(0.0),
+                top_k: Some(8),
+                ..InferenceParams::default()
+            },
+            base_url: DEFAULT_GEMINI_BASE_URL.into(),
+            capabilities: ProviderCapabilities::default(),
+        };
+        let messages = vec![Message {
+            role: "user".into(),
+            content: MessageContent::Text("hi".into()),
+        }];
+        let body = provider.build_request_body("be brief", &messages, Some(&[]));
+        assert_eq!(body["systemInstruction"]["parts"][0]["text"], "be brief");
+        assert_eq!(body["generationConfig"]["maxOutputTokens"], 1024);
+        assert_eq!(body["generationConfig"]["temperature"], 0.0);
+        assert_eq!(body["generationConfig"]["topK"], 8);
+        assert!(body["generationConfig"].get("topP").is_none());
+        assert!(body.get("tools").is_none());
+        assert_eq!(
+            provider.stream_url(),
+            "https://generativelanguage.googleapis.com/v1beta/models/gemini-2.5-flash:streamGenerateContent?alt=sse"
+        );
+    }
+}
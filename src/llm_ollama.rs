@@ -0,0 +1,761 @@
+// ---------------------------------------------------------------------------
+// Ollama provider (native API, NOT the OpenAI-compatible /v1 endpoint)
+//
+// Endpoints:
+//   POST /api/chat   (streaming responses are newline-delimited JSON)
+//   GET  /api/tags   (locally installed models)
+//
+// Authentication: none
+// ---------------------------------------------------------------------------
+
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use futures_util::StreamExt;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::config::{Config, InferenceParams};
+use crate::error::RayClawError;
+use crate::llm::{
+    normalize_stop_reason, sanitize_messages, send_with_retry, LlmProvider, ProviderCapabilities,
+    RetryPolicy,
+};
+use crate::llm_types::{
+    ContentBlock, Message, MessageContent, MessagesResponse, ResponseContentBlock, ToolDefinition,
+    Usage,
+};
+
+pub const DEFAULT_OLLAMA_BASE_URL: &str = "http://127.0.0.1:11434";
+
+/// Ollama server root. `llm_base_url` overrides the local default; a
+/// trailing `/v1` (the OpenAI-compatible preset) or `/api` is stripped.
+pub fn resolve_ollama_base_url(configured: Option<&str>) -> String {
+    let base = configured
+        .map(str::trim)
+        .filter(|u| !u.is_empty())
+        .unwrap_or(DEFAULT_OLLAMA_BASE_URL)
+        .trim_end_matches('/');
+    base.strip_suffix("/v1")
+        .or_else(|| base.strip_suffix("/api"))
+        .unwrap_or(base)
+        .to_string()
+}
+
+// ---------------------------------------------------------------------------
+// Local model discovery
+// ---------------------------------------------------------------------------
+
+/// Model names from an `/api/tags` response, e.g. `llama3.2:latest`.
+pub fn parse_model_names(body: &serde_json::Value) -> Vec<String> {
+    body.get("models")
+        .and_then(|m| m.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|m| {
+            m.get("name")
+                .or_else(|| m.get("model"))
+                .and_then(|n| n.as_str())
+                .map(|n| n.to_string())
+        })
+        .collect()
+}
+
+/// Whether `model` is among `installed`. A name without a tag refers to
+/// `:latest`, as it does for `ollama run`.
+pub fn model_is_installed(installed: &[String], model: &str) -> bool {
+    let model = model.trim();
+    installed.iter().any(|name| {
+        name == model || (!model.contains(':') && name.strip_suffix(":latest") == Some(model))
+    })
+}
+
+/// List the models installed on the Ollama server at `base_url`.
+pub async fn list_models(
+    http: &reqwest::Client,
+    base_url: &str,
+) -> Result<Vec<String>, RayClawError> {
+    let url = format!("{}/api/tags", resolve_ollama_base_url(Some(base_url)));
+    let response = http.get(&url).send().await?;
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(RayClawError::LlmApi(format!(
+            "Ollama /api/tags HTTP {status}: {body}"
+        )));
+    }
+    let body: serde_json::Value = response.json().await?;
+    Ok(parse_model_names(&body))
+}
+
+// ---------------------------------------------------------------------------
+// Message translation: internal types ↔ Ollama /api/chat format
+// ---------------------------------------------------------------------------
+
+fn translate_messages_to_ollama(system: &str, messages: &[Message]) -> Vec<serde_json::Value> {
+    // Tool results are matched to calls by function name (`tool_name`), so
+    // remember which tool each tool_use ID belongs to.
+    let tool_names: HashMap<&str, &str> = messages
+        .iter()
+        .filter_map(|msg| match &msg.content {
+            MessageContent::Blocks(blocks) => Some(blocks),
+            MessageContent::Text(_) => None,
+        })
+        .flatten()
+        .filter_map(|block| match block {
+            ContentBlock::ToolUse { id, name, .. } => Some((id.as_str(), name.as_str())),
+            _ => None,
+        })
+        .collect();
+
+    let mut out = Vec::new();
+    if !system.is_empty() {
+        out.push(serde_json::json!({ "role": "system", "content": system }));
+    }
+
+    for msg in messages {
+        let blocks = match &msg.content {
+            MessageContent::Text(text) => {
+                if !text.trim().is_empty() {
+                    out.push(serde_json::json!({ "role": msg.role, "content": text }));
+                }
+                continue;
+            }
+            MessageContent::Blocks(blocks) => blocks,
+        };
+
+        let mut texts = Vec::new();
+        let mut images = Vec::new();
+        let mut tool_calls = Vec::new();
+        for block in blocks {
+            match block {
+                ContentBlock::Text { text } if !text.trim().is_empty() => texts.push(text.as_str()),
+                ContentBlock::Text { .. }
+                | ContentBlock::Document { .. }
+                | ContentBlock::Thinking { .. }
+                | ContentBlock::RedactedThinking { .. } => {}
+                ContentBlock::Image { source } => images.push(source.data.clone()),
+                ContentBlock::ToolUse { name, input, .. } => {
+                    tool_calls.push(serde_json::json!({
+                        "function": { "name": name, "arguments": input }
+                    }));
+                }
+                // Tool results are separate `tool` messages, emitted in
+                // order ahead of any text sent alongside them.
+                ContentBlock::ToolResult {
+                    tool_use_id,
+                    content,
+                    is_error,
+                } => {
+                    let content = if is_error.unwrap_or(false) {
+                        format!("Error: {content}")
+                    } else {
+                        content.clone()
+                    };
+                    out.push(serde_json::json!({
+                        "role": "tool",
+                        "tool_name": tool_names
+                            .get(tool_use_id.as_str())
+                            .copied()
+                            .unwrap_or("unknown_tool"),
+                        "content": content,
+                    }));
+                }
+            }
+        }
+
+        if texts.is_empty() && images.is_empty() && tool_calls.is_empty() {
+            continue;
+        }
+        let mut message = serde_json::json!({
+            "role": msg.role,
+            "content": texts.join("\n"),
+        });
+        if !images.is_empty() {
+            message["images"] = serde_json::json!(images);
+        }
+        if !tool_calls.is_empty() {
+            message["tool_calls"] = serde_json::json!(tool_calls);
+        }
+        out.push(message);
+    }
+    out
+}
+
+fn translate_tools_to_ollama(tools: &[ToolDefinition]) -> serde_json::Value {
+    serde_json::Value::Array(
+        tools
+            .iter()
+            .map(|t| {
+                serde_json::json!({
+                    "type": "function",
+                    "function": {
+                        "name": t.name,
+                        "description": t.description,
+                        "parameters": t.input_schema,
+                    }
+                })
+            })
+            .collect(),
+    )
+}
+
+/// `keep_alive` accepts a duration string ("10m", "1h") or a number of
+/// seconds, where a negative value keeps the model loaded indefinitely.
+fn keep_alive_value(keep_alive: &str) -> serde_json::Value {
+    match keep_alive.trim().parse::<i64>() {
+        Ok(secs) => serde_json::json!(secs),
+        Err(_) => serde_json::json!(keep_alive.trim()),
+    }
+}
+
+/// Ollama does not return call IDs, so one is generated for each call.
+fn translate_tool_call(call: &serde_json::Value) -> ResponseContentBlock {
+    let function = call.get("function").unwrap_or(call);
+    let id = call
+        .get("id")
+        .and_then(|v| v.as_str())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string())
+        .unwrap_or_else(|| format!("call_{}", uuid::Uuid::new_v4().simple()));
+    let name = function
+        .get("name")
+        .and_then(|v| v.as_str())
+        .unwrap_or("")
+        .to_string();
+    // Some models return arguments as a JSON string instead of an object
+    let input = match function.get("arguments") {
+        Some(serde_json::Value::String(s)) => crate::llm::parse_tool_input(s),
+        Some(args) => args.clone(),
+        None => serde_json::json!({}),
+    };
+    ResponseContentBlock::ToolUse {
+        id,
+        name,
+        input,
+        signature: None,
+    }
+}
+
+fn map_done_reason(reason: Option<&str>, has_tool_calls: bool) -> Option<String> {
+    if has_tool_calls {
+        return Some("tool_use".into());
+    }
+    normalize_stop_reason(reason.filter(|r| !r.is_empty()).map(|r| r.to_string()))
+}
+
+fn translate_usage(body: &serde_json::Value) -> Option<Usage> {
+    let count = |key: &str| body.get(key).and_then(|v| v.as_u64());
+    match (count("prompt_eval_count"), count("eval_count")) {
+        (None, None) => None,
+        (input, output) => Some(Usage {
+            input_tokens: input.unwrap_or(0) as u32,
+            output_tokens: output.unwrap_or(0) as u32,
+            ..Usage::default()
+        }),
+    }
+}
+
+/// Accumulates `/api/chat` output into a single response. A non-streaming
+/// reply is simply one chunk with `done: true`.
+#[derive(Default)]
+struct OllamaChatState {
+    content: Vec<ResponseContentBlock>,
+    done_reason: Option<String>,
+    usage: Option<Usage>,
+    error: Option<String>,
+}
+
+impl OllamaChatState {
+    fn push_chunk(&mut self, chunk: &serde_json::Value, text_tx: Option<&UnboundedSender<String>>) {
+        if let Some(err) = chunk.get("error").and_then(|e| e.as_str()) {
+            self.error = Some(err.to_string());
+            return;
+        }
+        if let Some(message) = chunk.get("message") {
+            if let Some(text) = message.get("content").and_then(|t| t.as_str()) {
+                if !text.is_empty() {
+                    if let Some(tx) = text_tx {
+                        let _ = tx.send(text.to_string());
+                    }
+                    if let Some(ResponseContentBlock::Text { text: prev }) = self.content.last_mut()
+                    {
+                        prev.push_str(text);
+                    } else {
+                        self.content.push(ResponseContentBlock::Text {
+                            text: text.to_string(),
+                        });
+                    }
+                }
+            }
+            // Tool calls arrive whole, never split across chunks
+            for call in message
+                .get("tool_calls")
+                .and_then(|c| c.as_array())
+                .into_iter()
+                .flatten()
+            {
+                self.content.push(translate_tool_call(call));
+            }
+        }
+        if chunk.get("done").and_then(|d| d.as_bool()) == Some(true) {
+            self.done_reason = chunk
+                .get("done_reason")
+                .and_then(|r| r.as_str())
+                .map(|r| r.to_string());
+            self.usage = translate_usage(chunk);
+        }
+    }
+
+    fn finish(self) -> Result<MessagesResponse, RayClawError> {
+        if let Some(err) = self.error {
+            return Err(RayClawError::LlmApi(format!("Ollama error: {err}")));
+        }
+        let has_tool_calls = self
+            .content
+            .iter()
+            .any(|b| matches!(b, ResponseContentBlock::ToolUse { .. }));
+        Ok(MessagesResponse {
+            stop_reason: map_done_reason(self.done_reason.as_deref(), has_tool_calls),
+            usage: self.usage,
+            content: self.content,
+        })
+    }
+}
+
+/// Splits a byte stream into complete NDJSON lines.
+#[derive(Default)]
+struct NdjsonLines {
+    buffer: String,
+}
+
+impl NdjsonLines {
+    fn push(&mut self, chunk: &str) -> Vec<String> {
+        self.buffer.push_str(chunk);
+        let mut lines = Vec::new();
+        while let Some(pos) = self.buffer.find('\n') {
+            let line = self.buffer[..pos].trim().to_string();
+            self.buffer.drain(..=pos);
+            if !line.is_empty() {
+                lines.push(line);
+            }
+        }
+        lines
+    }
+
+    fn finish(self) -> Option<String> {
+        let rest = self.buffer.trim();
+        (!rest.is_empty()).then(|| rest.to_string())
+    }
+}
+
+// ---------------------------------------------------------------------------
+// OllamaProvider
+// ---------------------------------------------------------------------------
+
+pub struct OllamaProvider {
+    http: reqwest::Client,
+    model: String,
+    max_tokens: u32,
+    /// Server root without a trailing slash, e.g. `http://127.0.0.1:11434`
+    base_url: String,
+    keep_alive: Option<String>,
+    inference: InferenceParams,
+    capabilities: ProviderCapabilities,
+    retry: RetryPolicy,
+}
+
+impl OllamaProvider {
+    pub fn new(config: &Config) -> Self {
+        OllamaProvider {
+            retry: RetryPolicy::from_config(config),
+            http: reqwest::Client::new(),
+            model: config.model.clone(),
+            max_tokens: config.max_tokens,
+            base_url: resolve_ollama_base_url(config.llm_base_url.as_deref()),
+            keep_alive: config.ollama_keep_alive.clone(),
+            inference: config.inference.clone(),
+            capabilities: ProviderCapabilities {
+                vision: true,
+                tools: true,
+                streaming: true,
+                ..ProviderCapabilities::default()
+            }
+            .with_limits(
+                config.model_limits_for(&config.model).unwrap_or_default(),
+                config.max_tokens,
+            ),
+        }
+    }
+
+    fn build_request_body(
+        &self,
+        system: &str,
+        messages: &[Message],
+        tools: Option<&[ToolDefinition]>,
+        stream: bool,
+    ) -> serde_json::Value {
+        let mut body = serde_json::json!({
+            "model": self.model,
+            "messages": translate_messages_to_ollama(system, messages),
+            "stream": stream,
+            "options": { "num_predict": self.max_tokens },
+        });
+        let params = &self.inference;
+        let options = &mut body["options"];
+        if let Some(temperature) = params.temperature {
+            options["temperature"] = serde_json::json!(temperature);
+        }
+        if let Some(top_p) = params.top_p {
+            options["top_p"] = serde_json::json!(top_p);
+        }
+        if let Some(top_k) = params.top_k {
+            options["top_k"] = serde_json::json!(top_k);
+        }
+        if !params.stop_sequences.is_empty() {
+            options["stop"] = serde_json::json!(params.stop_sequences);
+        }
+        if let Some(keep_alive) = &self.keep_alive {
+            body["keep_alive"] = keep_alive_value(keep_alive);
+        }
+        if let Some(tools) = tools {
+            if !tools.is_empty() {
+                body["tools"] = translate_tools_to_ollama(tools);
+            }
+        }
+        body
+    }
+
+    async fn post_chat(&self, body: &serde_json::Value) -> Result<reqwest::Response, RayClawError> {
+        let url = format!("{}/api/chat", self.base_url);
+        let response =
+            send_with_retry(self.retry, "Ollama", || Ok(self.http.post(&url).json(body))).await?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+
+        let err_body = response.text().await.unwrap_or_default();
+        let detail = serde_json::from_str::<serde_json::Value>(&err_body)
+            .ok()
+            .and_then(|v| v.get("error").and_then(|e| e.as_str()).map(String::from))
+            .unwrap_or(err_body);
+        let hint = if status.as_u16() == 404 {
+            format!(" (run `ollama pull {}`)", self.model)
+        } else {
+            String::new()
+        };
+        Err(RayClawError::LlmApi(format!(
+            "Ollama /api/chat HTTP {status}: {detail}{hint}"
+        )))
+    }
+}
+
+#[async_trait]
+impl LlmProvider for OllamaProvider {
+    fn endpoint(&self) -> Option<String> {
+        Some(self.base_url.clone())
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        self.capabilities
+    }
+
+    fn request_body(
+        &self,
+        system: &str,
+        messages: &[Message],
+        tools: Option<&[ToolDefinition]>,
+        stream: bool,
+    ) -> Option<serde_json::Value> {
+        let messages = sanitize_messages(messages.to_vec());
+        Some(self.build_request_body(system, &messages, tools, stream))
+    }
+
+    async fn send_message(
+        &self,
+        system: &str,
+        messages: Vec<Message>,
+        tools: Option<Vec<ToolDefinition>>,
+    ) -> Result<MessagesResponse, RayClawError> {
+        let messages = sanitize_messages(messages);
+        let body = self.build_request_body(system, &messages, tools.as_deref(), false);
+
+        let response = self.post_chat(&body).await?;
+        let response_body: serde_json::Value = response.json().await?;
+        let mut state = OllamaChatState::default();
+        state.push_chunk(&response_body, None);
+        state.finish()
+    }
+
+    async fn send_message_stream(
+        &self,
+        system: &str,
+        messages: Vec<Message>,
+        tools: Option<Vec<ToolDefinition>>,
+        text_tx: Option<&UnboundedSender<String>>,
+    ) -> Result<MessagesResponse, RayClawError> {
+        let messages = sanitize_messages(messages);
+        let body = self.build_request_body(system, &messages, tools.as_deref(), true);
+
+        let response = self.post_chat(&body).await?;
+
+        let mut lines = NdjsonLines::default();
+        let mut state = OllamaChatState::default();
+        let mut stream = response.bytes_stream();
+
+        while let Some(chunk_result) = stream.next().await {
+            let chunk = chunk_result?;
+            for line in lines.push(&String::from_utf8_lossy(&chunk)) {
+                if let Ok(value) = serde_json::from_str(&line) {
+                    state.push_chunk(&value, text_tx);
+                }
+            }
+        }
+        if let Some(line) = lines.finish() {
+            if let Ok(value) = serde_json::from_str(&line) {
+                state.push_chunk(&value, text_tx);
+            }
+        }
+
+        state.finish()
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm_types::ImageSource;
+    use serde_json::json;
+
+    fn blocks(role: &str, blocks: Vec<ContentBlock>) -> Message {
+        Message {
+            role: role.into(),
+            content: MessageContent::Blocks(blocks),
+        }
+    }
+
+    #[test]
+    fn test_resolve_ollama_base_url() {
+        assert_eq!(resolve_ollama_base_url(None), "http://127.0.0.1:11434");
+        assert_eq!(
+            resolve_ollama_base_url(Some("  ")),
+            "http://127.0.0.1:11434"
+        );
+        assert_eq!(
+            resolve_ollama_base_url(Some("http://127.0.0.1:11434/v1/")),
+            "http://127.0.0.1:11434"
+        );
+        assert_eq!(
+            resolve_ollama_base_url(Some("http://gpu-box:11434/api")),
+            "http://gpu-box:11434"
+        );
+    }
+
+    #[test]
+    fn test_parse_model_names_and_installed() {
+        let body = json!({
+            "models": [
+                {"name": "llama3.2:latest", "model": "llama3.2:latest"},
+                {"name": "qwen2.5-coder:7b"},
+                {"model": "mistral:latest"}
+            ]
+        });
+        let names = parse_model_names(&body);
+        assert_eq!(
+            names,
+            vec!["llama3.2:latest", "qwen2.5-coder:7b", "mistral:latest"]
+        );
+        assert!(model_is_installed(&names, "llama3.2"));
+        assert!(model_is_installed(&names, "llama3.2:latest"));
+        assert!(model_is_installed(&names, "qwen2.5-coder:7b"));
+        assert!(!model_is_installed(&names, "qwen2.5-coder"));
+        assert!(!model_is_installed(&names, "llama3.2:1b"));
+        assert!(parse_model_names(&json!({})).is_empty());
+    }
+
+    #[test]
+    fn test_translate_messages_system_text_and_images() {
+        let messages = vec![
+            Message {
+                role: "user".into(),
+                content: MessageContent::Text("hello".into()),
+            },
+            blocks(
+                "user",
+                vec![
+                    ContentBlock::Image {
+                        source: ImageSource {
+                            source_type: "base64".into(),
+                            media_type: "image/png".into(),
+                            data: "iVBORw0KGgo=".into(),
+                        },
+                    },
+                    ContentBlock::Text {
+                        text: "what is this?".into(),
+                    },
+                ],
+            ),
+        ];
+        let out = translate_messages_to_ollama("be brief", &messages);
+        assert_eq!(out.len(), 3);
+        assert_eq!(out[0], json!({"role": "system", "content": "be brief"}));
+        assert_eq!(out[1]["content"], "hello");
+        assert_eq!(out[2]["content"], "what is this?");
+        assert_eq!(out[2]["images"][0], "iVBORw0KGgo=");
+    }
+
+    #[test]
+    fn test_translate_tool_round_trip() {
+        let messages = vec![
+            blocks(
+                "assistant",
+                vec![
+                    ContentBlock::Text {
+                        text: "Checking.".into(),
+                    },
+                    ContentBlock::ToolUse {
+                        id: "call_1".into(),
+                        name: "read_file".into(),
+                        input: json!({"path": "a.txt"}),
+                        signature: None,
+                    },
+                ],
+            ),
+            blocks(
+                "user",
+                vec![
+                    ContentBlock::ToolResult {
+                        tool_use_id: "call_1".into(),
+                        content: "not found".into(),
+                        is_error: Some(true),
+                    },
+                    ContentBlock::Text {
+                        text: "and then?".into(),
+                    },
+                ],
+            ),
+        ];
+        let out = translate_messages_to_ollama("", &messages);
+        assert_eq!(out.len(), 3);
+        assert_eq!(out[0]["role"], "assistant");
+        assert_eq!(out[0]["content"], "Checking.");
+        assert_eq!(out[0]["tool_calls"][0]["function"]["name"], "read_file");
+        assert_eq!(
+            out[0]["tool_calls"][0]["function"]["arguments"]["path"],
+            "a.txt"
+        );
+        assert_eq!(
+            out[1],
+            json!({"role": "tool", "tool_name": "read_file", "content": "Error: not found"})
+        );
+        assert_eq!(out[2]["role"], "user");
+        assert_eq!(out[2]["content"], "and then?");
+    }
+
+    #[test]
+    fn test_translate_tools() {
+        let tools = vec![ToolDefinition {
+            name: "bash".into(),
+            description: "Run a command".into(),
+            input_schema: json!({"type": "object", "properties": {"command": {"type": "string"}}}),
+        }];
+        let out = translate_tools_to_ollama(&tools);
+        assert_eq!(out[0]["type"], "function");
+        assert_eq!(out[0]["function"]["name"], "bash");
+        assert_eq!(
+            out[0]["function"]["parameters"]["properties"]["command"]["type"],
+            "string"
+        );
+    }
+
+    #[test]
+    fn test_keep_alive_value() {
+        assert_eq!(keep_alive_value("10m"), json!("10m"));
+        assert_eq!(keep_alive_value("-1"), json!(-1));
+        assert_eq!(keep_alive_value(" 300 "), json!(300));
+    }
+
+    #[test]
+    fn test_chat_response_with_tool_call() {
+        let body = json!({
+            "model": "llama3.2",
+            "message": {
+                "role": "assistant",
+                "content": "",
+                "tool_calls": [
+                    {"function": {"name": "get_weather", "arguments": {"city": "Paris"}}},
+                    {"function": {"name": "bash", "arguments": "{\"command\":\"ls\"}"}}
+                ]
+            },
+            "done": true,
+            "done_reason": "stop",
+            "prompt_eval_count": 42,
+            "eval_count": 7
+        });
+        let mut state = OllamaChatState::default();
+        state.push_chunk(&body, None);
+        let resp = state.finish().unwrap();
+        assert_eq!(resp.stop_reason.as_deref(), Some("tool_use"));
+        assert_eq!(resp.content.len(), 2);
+        match &resp.content[0] {
+            ResponseContentBlock::ToolUse {
+                id, name, input, ..
+            } => {
+                assert!(id.starts_with("call_"));
+                assert_eq!(name, "get_weather");
+                assert_eq!(input["city"], "Paris");
+            }
+            other => panic!("expected tool use, got {other:?}"),
+        }
+        match &resp.content[1] {
+            ResponseContentBlock::ToolUse { input, .. } => assert_eq!(input["command"], "ls"),
+            other => panic!("expected tool use, got {other:?}"),
+        }
+        let usage = resp.usage.unwrap();
+        assert_eq!(usage.input_tokens, 42);
+        assert_eq!(usage.output_tokens, 7);
+    }
+
+    #[test]
+    fn test_streamed_chunks_accumulate() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut lines = NdjsonLines::default();
+        let mut state = OllamaChatState::default();
+        let raw = concat!(
+            "{\"message\":{\"role\":\"assistant\",\"content\":\"Hel\"},\"done\":false}\n",
+            "{\"message\":{\"role\":\"assistant\",\"content\":\"lo\"},\"done\":false}\n",
+            "{\"message\":{\"role\":\"assistant\",\"content\":\"\"},\"done\":true,",
+            "\"done_reason\":\"length\",\"prompt_eval_count\":3,\"eval_count\":2}"
+        );
+        // Split mid-line to exercise buffering
+        let (a, b) = raw.split_at(30);
+        let mut collected: Vec<String> = lines.push(a);
+        collected.extend(lines.push(b));
+        collected.extend(lines.finish());
+        for line in &collected {
+            state.push_chunk(&serde_json::from_str(line).unwrap(), Some(&tx));
+        }
+        let resp = state.finish().unwrap();
+        assert_eq!(collected.len(), 3);
+        assert_eq!(rx.try_recv().unwrap(), "Hel");
+        assert_eq!(rx.try_recv().unwrap(), "lo");
+        assert_eq!(resp.stop_reason.as_deref(), Some("max_tokens"));
+        match &resp.content[0] {
+            ResponseContentBlock::Text { text } => assert_eq!(text, "Hello"),
+            other => panic!("expected text, got {other:?}"),
+        }
+        assert_eq!(resp.usage.unwrap().output_tokens, 2);
+    }
+
+    #[test]
+    fn test_error_chunk_fails() {
+        let mut state = OllamaChatState::default();
+        state.push_chunk(&json!({"error": "model 'nope' not found"}), None);
+        let err = state.finish().unwrap_err().to_string();
+        assert!(err.contains("not found"));
+    }
+}
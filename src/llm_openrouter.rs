@@ -0,0 +1,261 @@
+// ---------------------------------------------------------------------------
+// OpenRouter provider (OpenAI-compatible chat completions plus routing)
+//
+// Endpoint:
+//   POST {base}/chat/completions   (base defaults to https://openrouter.ai/api/v1)
+//
+// On top of the OpenAI request this sends the attribution headers, the
+// `models` fallback list and `provider` routing preferences, and maps
+// OpenRouter's error payloads (moderation, upstream down) to distinct errors.
+// ---------------------------------------------------------------------------
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::json;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::config::Config;
+use crate::error::RayClawError;
+use crate::llm::{LlmProvider, OaiCompatExtras, OpenAiProvider, ProviderCapabilities};
+use crate::llm_types::{Message, MessagesResponse, ToolDefinition};
+
+pub const DEFAULT_OPENROUTER_BASE_URL: &str = "https://openrouter.ai/api/v1";
+const DEFAULT_APP_URL: &str = "https://github.com/rayclaw/rayclaw";
+const DEFAULT_APP_TITLE: &str = "RayClaw";
+
+pub struct OpenRouterProvider {
+    inner: OpenAiProvider,
+}
+
+impl OpenRouterProvider {
+    pub fn new(config: &Config) -> Self {
+        let mut config = config.clone();
+        if config
+            .llm_base_url
+            .as_deref()
+            .is_none_or(|url| url.trim().is_empty())
+        {
+            config.llm_base_url = Some(DEFAULT_OPENROUTER_BASE_URL.into());
+        }
+        let extras = OaiCompatExtras {
+            label: Some("OpenRouter"),
+            headers: attribution_headers(&config),
+            body: routing_body(&config),
+            map_error: Some(map_openrouter_error),
+        };
+        OpenRouterProvider {
+            inner: OpenAiProvider::with_extras(&config, extras),
+        }
+    }
+}
+
+fn attribution_headers(config: &Config) -> Vec<(&'static str, String)> {
+    let non_empty = |v: &Option<String>| {
+        v.as_deref()
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(str::to_string)
+    };
+    vec![
+        (
+            "HTTP-Referer",
+            non_empty(&config.openrouter.app_url).unwrap_or_else(|| DEFAULT_APP_URL.into()),
+        ),
+        (
+            "X-Title",
+            non_empty(&config.openrouter.app_title).unwrap_or_else(|| DEFAULT_APP_TITLE.into()),
+        ),
+    ]
+}
+
+/// `models` (the primary model, then fallbacks) and `provider` preferences.
+fn routing_body(config: &Config) -> serde_json::Map<String, serde_json::Value> {
+    let mut body = serde_json::Map::new();
+    let fallbacks = &config.openrouter.fallback_models;
+    if !fallbacks.is_empty() {
+        let models: Vec<&str> = std::iter::once(config.model.as_str())
+            .chain(fallbacks.iter().map(String::as_str))
+            .collect();
+        body.insert("models".into(), json!(models));
+    }
+    if let Some(prefs) = &config.openrouter.provider {
+        let prefs = json!(prefs);
+        if prefs.as_object().is_some_and(|p| !p.is_empty()) {
+            body.insert("provider".into(), prefs);
+        }
+    }
+    body
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenRouterErrorResponse {
+    error: OpenRouterErrorDetail,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenRouterErrorDetail {
+    #[serde(default)]
+    code: Option<serde_json::Value>,
+    #[serde(default)]
+    message: String,
+    #[serde(default)]
+    metadata: Option<OpenRouterErrorMetadata>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct OpenRouterErrorMetadata {
+    /// Moderation categories the input was flagged for
+    #[serde(default)]
+    reasons: Vec<String>,
+    /// Upstream provider that failed
+    #[serde(default)]
+    provider_name: Option<String>,
+}
+
+/// OpenRouter reports the upstream status as `error.code`, which can differ
+/// from the HTTP status (errors from the model arrive with 200).
+fn map_openrouter_error(status: reqwest::StatusCode, text: &str) -> RayClawError {
+    let Ok(parsed) = serde_json::from_str::<OpenRouterErrorResponse>(text) else {
+        return RayClawError::LlmApi(format!("HTTP {status}: {text}"));
+    };
+    let err = parsed.error;
+    let code = err
+        .code
+        .as_ref()
+        .and_then(|c| c.as_u64().or_else(|| c.as_str()?.parse().ok()))
+        .and_then(|c| u16::try_from(c).ok())
+        .unwrap_or(status.as_u16());
+    let metadata = err.metadata.unwrap_or_default();
+    let message = if err.message.is_empty() {
+        format!("HTTP {code}")
+    } else {
+        err.message
+    };
+
+    match code {
+        403 if !metadata.reasons.is_empty() => RayClawError::Moderation(format!(
+            "{message} (flagged: {})",
+            metadata.reasons.join(", ")
+        )),
+        403 if message.to_lowercase().contains("moderation") => RayClawError::Moderation(message),
+        429 => RayClawError::RateLimited,
+        502 | 503 => RayClawError::ProviderUnavailable(match metadata.provider_name {
+            Some(provider) => format!("{message} (provider: {provider})"),
+            None => message,
+        }),
+        // Keep the status visible so retry and outage checks can see it
+        _ => RayClawError::LlmApi(format!("HTTP {code}: {message}")),
+    }
+}
+
+#[async_trait]
+impl LlmProvider for OpenRouterProvider {
+    fn endpoint(&self) -> Option<String> {
+        self.inner.endpoint()
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        self.inner.capabilities()
+    }
+
+    fn request_body(
+        &self,
+        system: &str,
+        messages: &[Message],
+        tools: Option<&[ToolDefinition]>,
+        stream: bool,
+    ) -> Option<serde_json::Value> {
+        self.inner.request_body(system, messages, tools, stream)
+    }
+
+    async fn send_message(
+        &self,
+        system: &str,
+        messages: Vec<Message>,
+        tools: Option<Vec<ToolDefinition>>,
+    ) -> Result<MessagesResponse, RayClawError> {
+        self.inner.send_message(system, messages, tools).await
+    }
+
+    async fn send_message_stream(
+        &self,
+        system: &str,
+        messages: Vec<Message>,
+        tools: Option<Vec<ToolDefinition>>,
+        text_tx: Option<&UnboundedSender<String>>,
+    ) -> Result<MessagesResponse, RayClawError> {
+        self.inner
+            .send_message_stream(system, messages, tools, text_tx)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::OpenRouterProviderPreferences;
+    use reqwest::StatusCode;
+
+    fn config() -> Config {
+        let yaml = "telegram_bot_token: tok\nbot_username: bot\napi_key: key\nllm_provider: openrouter\nmodel: anthropic/claude-sonnet-4.5\n";
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.post_deserialize().unwrap();
+        config
+    }
+
+    #[test]
+    fn test_routing_body() {
+        let mut config = config();
+        assert!(routing_body(&config).is_empty());
+
+        config.openrouter.fallback_models = vec!["openai/gpt-5.2".into()];
+        config.openrouter.provider = Some(OpenRouterProviderPreferences {
+            order: vec!["anthropic".into()],
+            allow_fallbacks: Some(false),
+            ..OpenRouterProviderPreferences::default()
+        });
+        let body = routing_body(&config);
+        assert_eq!(
+            body["models"],
+            json!(["anthropic/claude-sonnet-4.5", "openai/gpt-5.2"])
+        );
+        assert_eq!(
+            body["provider"],
+            json!({"order": ["anthropic"], "allow_fallbacks": false})
+        );
+    }
+
+    #[test]
+    fn test_attribution_headers() {
+        let mut config = config();
+        assert_eq!(attribution_headers(&config)[1].1, "RayClaw");
+        config.openrouter.app_title = Some(" My Bot ".into());
+        assert_eq!(attribution_headers(&config)[1].1, "My Bot");
+    }
+
+    #[test]
+    fn test_map_openrouter_error() {
+        let moderation = r#"{"error":{"code":403,"message":"Input was flagged","metadata":{"reasons":["harassment"],"flagged_input":"..."}}}"#;
+        let e = map_openrouter_error(StatusCode::FORBIDDEN, moderation);
+        assert!(matches!(e, RayClawError::Moderation(ref m) if m.contains("harassment")));
+
+        let down = r#"{"error":{"code":502,"message":"Provider returned error","metadata":{"provider_name":"Together"}}}"#;
+        let e = map_openrouter_error(StatusCode::OK, down);
+        assert!(matches!(e, RayClawError::ProviderUnavailable(ref m) if m.contains("Together")));
+        assert!(crate::llm::is_provider_unavailable(&e));
+
+        let limited = r#"{"error":{"code":"429","message":"Rate limit exceeded"}}"#;
+        let e = map_openrouter_error(StatusCode::TOO_MANY_REQUESTS, limited);
+        assert!(matches!(e, RayClawError::RateLimited));
+
+        let credits = r#"{"error":{"code":402,"message":"Insufficient credits"}}"#;
+        let e = map_openrouter_error(StatusCode::PAYMENT_REQUIRED, credits);
+        assert_eq!(
+            e.to_string(),
+            "LLM API error: HTTP 402: Insufficient credits"
+        );
+
+        let e = map_openrouter_error(StatusCode::BAD_GATEWAY, "upstream connect error");
+        assert!(crate::llm::is_provider_unavailable(&e));
+    }
+}
@@ -0,0 +1,392 @@
+//! Opt-in recorder of LLM traffic, for debugging provider translation.
+//!
+//! With `llm_trace: true` (or `RAYCLAW_LLM_TRACE=true`) every provider call
+//! appends one JSON line to `<data_dir>/runtime/llm_traces/<YYYY-MM-DD>.jsonl`:
+//! the request body in the provider's own format (as `send_message` builds
+//! it) and the response as the agent sees it, or the error. Credentials are
+//! masked with [`SecretRedactor`] and large base64 payloads (images,
+//! documents) are replaced by their length.
+//!
+//! `rayclaw traces` lists recent records and pretty-prints one by id.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::mpsc::UnboundedSender;
+use tracing::warn;
+
+use crate::config::Config;
+use crate::error::RayClawError;
+use crate::llm::{LlmProvider, ProviderCapabilities};
+use crate::llm_types::{Message, MessagesResponse, ToolDefinition};
+use crate::redact::SecretRedactor;
+
+/// Strings longer than this without whitespace are treated as binary payloads.
+const MAX_INLINE_BASE64_CHARS: usize = 1_024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TraceRecord {
+    pub id: String,
+    pub timestamp: String,
+    pub provider: String,
+    pub model: String,
+    pub stream: bool,
+    pub duration_ms: u64,
+    /// Provider-native request body; null if the provider can't rebuild it
+    pub request: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+pub fn traces_dir(config: &Config) -> PathBuf {
+    Path::new(&config.runtime_data_dir()).join("llm_traces")
+}
+
+/// Wraps a provider and records each call it makes.
+pub struct TracedProvider {
+    inner: Box<dyn LlmProvider>,
+    provider: String,
+    model: String,
+    dir: PathBuf,
+    redactor: SecretRedactor,
+    /// Serializes appends so concurrent calls don't interleave lines
+    write_lock: Mutex<()>,
+}
+
+impl TracedProvider {
+    pub fn new(inner: Box<dyn LlmProvider>, config: &Config) -> Self {
+        TracedProvider {
+            inner,
+            provider: config.llm_provider.clone(),
+            model: config.model.clone(),
+            dir: traces_dir(config),
+            redactor: SecretRedactor::from_config(config),
+            write_lock: Mutex::new(()),
+        }
+    }
+
+    fn record(
+        &self,
+        request: Option<Value>,
+        stream: bool,
+        started: Instant,
+        result: &Result<MessagesResponse, RayClawError>,
+    ) {
+        let now = chrono::Utc::now();
+        let mut record = TraceRecord {
+            id: uuid::Uuid::new_v4().simple().to_string()[..12].to_string(),
+            timestamp: now.to_rfc3339(),
+            provider: self.provider.clone(),
+            model: self.model.clone(),
+            stream,
+            duration_ms: started.elapsed().as_millis() as u64,
+            request: request.unwrap_or(Value::Null),
+            response: None,
+            error: None,
+        };
+        match result {
+            Ok(response) => record.response = serde_json::to_value(response).ok(),
+            Err(e) => record.error = Some(e.to_string()),
+        }
+        elide_base64(&mut record.request);
+
+        let line = match serde_json::to_string(&record) {
+            Ok(line) => self.redactor.redact(&line).into_owned(),
+            Err(e) => {
+                warn!("LLM trace: failed to serialize record: {e}");
+                return;
+            }
+        };
+        let path = self.dir.join(format!("{}.jsonl", now.format("%Y-%m-%d")));
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        let written = std::fs::create_dir_all(&self.dir).and_then(|_| {
+            let mut file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)?;
+            writeln!(file, "{line}")
+        });
+        if let Err(e) = written {
+            warn!("LLM trace: failed to write {}: {e}", path.display());
+        }
+    }
+}
+
+#[async_trait]
+impl LlmProvider for TracedProvider {
+    fn endpoint(&self) -> Option<String> {
+        self.inner.endpoint()
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        self.inner.capabilities()
+    }
+
+    async fn count_tokens(
+        &self,
+        system: &str,
+        messages: &[Message],
+        tools: Option<&[ToolDefinition]>,
+    ) -> Result<usize, RayClawError> {
+        self.inner.count_tokens(system, messages, tools).await
+    }
+
+    fn request_body(
+        &self,
+        system: &str,
+        messages: &[Message],
+        tools: Option<&[ToolDefinition]>,
+        stream: bool,
+    ) -> Option<Value> {
+        self.inner.request_body(system, messages, tools, stream)
+    }
+
+    async fn send_message(
+        &self,
+        system: &str,
+        messages: Vec<Message>,
+        tools: Option<Vec<ToolDefinition>>,
+    ) -> Result<MessagesResponse, RayClawError> {
+        let request = self
+            .inner
+            .request_body(system, &messages, tools.as_deref(), false);
+        let started = Instant::now();
+        let result = self.inner.send_message(system, messages, tools).await;
+        self.record(request, false, started, &result);
+        result
+    }
+
+    async fn send_message_stream(
+        &self,
+        system: &str,
+        messages: Vec<Message>,
+        tools: Option<Vec<ToolDefinition>>,
+        text_tx: Option<&UnboundedSender<String>>,
+    ) -> Result<MessagesResponse, RayClawError> {
+        let request = self
+            .inner
+            .request_body(system, &messages, tools.as_deref(), true);
+        let started = Instant::now();
+        let result = self
+            .inner
+            .send_message_stream(system, messages, tools, text_tx)
+            .await;
+        self.record(request, true, started, &result);
+        result
+    }
+}
+
+/// Replace long base64-looking strings with a `[base64: N chars]` marker.
+fn elide_base64(value: &mut Value) {
+    match value {
+        Value::String(s)
+            if s.len() > MAX_INLINE_BASE64_CHARS && !s.contains(char::is_whitespace) =>
+        {
+            *s = format!("[base64: {} chars]", s.len());
+        }
+        Value::Array(items) => items.iter_mut().for_each(elide_base64),
+        Value::Object(map) => map.values_mut().for_each(elide_base64),
+        _ => {}
+    }
+}
+
+/// Records in `dir`, oldest first.
+pub fn load_records(dir: &Path) -> std::io::Result<Vec<TraceRecord>> {
+    let mut files: Vec<PathBuf> = match std::fs::read_dir(dir) {
+        Ok(entries) => entries
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| p.extension().is_some_and(|ext| ext == "jsonl"))
+            .collect(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    files.sort();
+    let mut records = Vec::new();
+    for file in files {
+        let raw = std::fs::read_to_string(&file)?;
+        records.extend(
+            raw.lines()
+                .filter_map(|line| serde_json::from_str::<TraceRecord>(line).ok()),
+        );
+    }
+    Ok(records)
+}
+
+/// One-line summary: id, time, provider/model, duration and outcome.
+fn summarize(record: &TraceRecord) -> String {
+    let messages = record
+        .request
+        .get("messages")
+        .or_else(|| record.request.get("contents"))
+        .or_else(|| record.request.get("input"))
+        .and_then(Value::as_array)
+        .map_or(0, Vec::len);
+    let outcome = match (&record.error, &record.response) {
+        (Some(e), _) => format!("error: {}", e.chars().take(80).collect::<String>()),
+        (None, Some(response)) => response
+            .get("stop_reason")
+            .and_then(Value::as_str)
+            .unwrap_or("ok")
+            .to_string(),
+        (None, None) => "ok".into(),
+    };
+    format!(
+        "{}  {}  {}/{}  {}ms  {} msg(s){}  {}",
+        record.id,
+        record.timestamp.get(..19).unwrap_or(&record.timestamp),
+        record.provider,
+        record.model,
+        record.duration_ms,
+        messages,
+        if record.stream { " stream" } else { "" },
+        outcome
+    )
+}
+
+fn print_usage() {
+    println!(
+        "Usage: rayclaw traces [list] [--limit N]
+       rayclaw traces show <id>
+
+Records are written while `llm_trace: true` (or RAYCLAW_LLM_TRACE=true) is set.
+
+Commands:
+  list        Summarize the most recent records (default: 20)
+  show <id>   Pretty-print one record (request body and response)"
+    );
+}
+
+pub fn run_cli(args: &[String]) -> anyhow::Result<()> {
+    if args.iter().any(|a| a == "--help" || a == "-h") {
+        print_usage();
+        return Ok(());
+    }
+    let config = Config::load()?;
+    let dir = traces_dir(&config);
+    let records = load_records(&dir)?;
+
+    match args.first().map(String::as_str) {
+        None | Some("list") | Some("--limit") => {
+            let limit = args
+                .windows(2)
+                .find(|w| w[0] == "--limit")
+                .and_then(|w| w[1].parse::<usize>().ok())
+                .unwrap_or(20);
+            if records.is_empty() {
+                println!(
+                    "No LLM traces in {}. Enable them with `llm_trace: true`.",
+                    dir.display()
+                );
+                return Ok(());
+            }
+            let skip = records.len().saturating_sub(limit);
+            for record in &records[skip..] {
+                println!("{}", summarize(record));
+            }
+            Ok(())
+        }
+        Some("show") => {
+            let Some(id) = args.get(1) else {
+                print_usage();
+                anyhow::bail!("missing trace id");
+            };
+            let record = records
+                .iter()
+                .rev()
+                .find(|r| r.id.starts_with(id.as_str()))
+                .ok_or_else(|| anyhow::anyhow!("no trace with id {id}"))?;
+            println!("{}", serde_json::to_string_pretty(record)?);
+            Ok(())
+        }
+        Some(other) => {
+            print_usage();
+            anyhow::bail!("unknown traces command: {other}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm_types::ResponseContentBlock;
+
+    struct EchoProvider;
+
+    #[async_trait]
+    impl LlmProvider for EchoProvider {
+        fn request_body(
+            &self,
+            system: &str,
+            messages: &[Message],
+            _tools: Option<&[ToolDefinition]>,
+            _stream: bool,
+        ) -> Option<Value> {
+            Some(serde_json::json!({
+                "system": system,
+                "messages": messages,
+                "image": "A".repeat(4_000),
+            }))
+        }
+
+        async fn send_message(
+            &self,
+            _system: &str,
+            _messages: Vec<Message>,
+            _tools: Option<Vec<ToolDefinition>>,
+        ) -> Result<MessagesResponse, RayClawError> {
+            Ok(MessagesResponse {
+                content: vec![ResponseContentBlock::Text { text: "hi".into() }],
+                stop_reason: Some("end_turn".into()),
+                usage: None,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_traced_provider_records_redacted_pairs() {
+        let dir = std::env::temp_dir().join(format!("rayclaw_trace_{}", uuid::Uuid::new_v4()));
+        let yaml = format!(
+            "telegram_bot_token: tok\nbot_username: bot\napi_key: sk-test-trace-secret\ndata_dir: {}\nllm_trace: true\n",
+            dir.display()
+        );
+        let mut config: Config = serde_yaml::from_str(&yaml).unwrap();
+        config.post_deserialize().unwrap();
+
+        let provider = TracedProvider::new(Box::new(EchoProvider), &config);
+        let messages = vec![Message {
+            role: "user".into(),
+            content: crate::llm_types::MessageContent::Text(
+                "my key is sk-test-trace-secret".into(),
+            ),
+        }];
+        provider
+            .send_message_stream("sys", messages, None, None)
+            .await
+            .unwrap();
+
+        let records = load_records(&traces_dir(&config)).unwrap();
+        assert_eq!(records.len(), 1);
+        let record = &records[0];
+        assert!(record.stream);
+        assert_eq!(
+            record.request["messages"][0]["content"],
+            "my key is [REDACTED:api_key]"
+        );
+        assert_eq!(record.request["image"], "[base64: 4000 chars]");
+        assert_eq!(
+            record.response.as_ref().unwrap()["content"][0]["text"],
+            "hi"
+        );
+        assert!(summarize(record).contains("1 msg(s) stream  end_turn"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
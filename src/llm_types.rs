@@ -15,6 +15,23 @@ pub struct ImageSource {
     pub data: String,
 }
 
+/// A file attached for the model to read, e.g. a PDF (`application/pdf`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocumentSource {
+    #[serde(rename = "type")]
+    pub source_type: String,
+    pub media_type: String,
+    pub data: String,
+}
+
+/// Attachment media types sent to the model as document blocks rather than
+/// images. Other files are only saved and described in the message text.
+pub const DOCUMENT_MEDIA_TYPES: &[&str] = &["application/pdf"];
+
+pub fn is_document_media_type(media_type: &str) -> bool {
+    DOCUMENT_MEDIA_TYPES.contains(&media_type)
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum ContentBlock {
@@ -22,11 +39,22 @@ pub enum ContentBlock {
     Text { text: String },
     #[serde(rename = "image")]
     Image { source: ImageSource },
+    #[serde(rename = "document")]
+    Document {
+        source: DocumentSource,
+        /// Original file name, shown to the model
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        title: Option<String>,
+    },
     #[serde(rename = "tool_use")]
     ToolUse {
         id: String,
         name: String,
         input: serde_json::Value,
+        /// Gemini's `thoughtSignature` for this call, which Gemini 3 requires
+        /// back with it; other providers drop it.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        signature: Option<String>,
     },
     #[serde(rename = "tool_result")]
     ToolResult {
@@ -35,6 +63,17 @@ pub enum ContentBlock {
         #[serde(skip_serializing_if = "Option::is_none")]
         is_error: Option<bool>,
     },
+    /// Extended thinking from an earlier assistant turn. Kept in history
+    /// during tool use because Anthropic (and Claude on Bedrock) require the
+    /// signed block to be sent back; other providers drop it.
+    #[serde(rename = "thinking")]
+    Thinking {
+        thinking: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        signature: Option<String>,
+    },
+    #[serde(rename = "redacted_thinking")]
+    RedactedThinking { data: String },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -62,7 +101,7 @@ pub struct MessagesRequest {
     pub stream: Option<bool>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[allow(dead_code)]
 pub struct MessagesResponse {
     pub content: Vec<ResponseContentBlock>,
@@ -70,7 +109,7 @@ pub struct MessagesResponse {
     pub usage: Option<Usage>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum ResponseContentBlock {
     #[serde(rename = "text")]
@@ -80,14 +119,65 @@ pub enum ResponseContentBlock {
         id: String,
         name: String,
         input: serde_json::Value,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        signature: Option<String>,
     },
+    /// Reasoning the model did before answering (extended thinking).
+    #[serde(rename = "thinking")]
+    Thinking {
+        thinking: String,
+        #[serde(default)]
+        signature: Option<String>,
+    },
+    /// Thinking the provider encrypted for safety reasons; only replayable.
+    #[serde(rename = "redacted_thinking")]
+    RedactedThinking { data: String },
 }
 
-#[derive(Debug, Deserialize)]
+impl ResponseContentBlock {
+    /// The history block for this response block, if it must be replayed.
+    pub fn to_content_block(&self) -> Option<ContentBlock> {
+        match self {
+            // Anthropic rejects blank text blocks
+            ResponseContentBlock::Text { text } if text.trim().is_empty() => None,
+            ResponseContentBlock::Text { text } => Some(ContentBlock::Text { text: text.clone() }),
+            ResponseContentBlock::ToolUse {
+                id,
+                name,
+                input,
+                signature,
+            } => Some(ContentBlock::ToolUse {
+                id: id.clone(),
+                name: name.clone(),
+                input: input.clone(),
+                signature: signature.clone(),
+            }),
+            ResponseContentBlock::Thinking {
+                thinking,
+                signature,
+            } => Some(ContentBlock::Thinking {
+                thinking: thinking.clone(),
+                signature: signature.clone(),
+            }),
+            ResponseContentBlock::RedactedThinking { data } => {
+                Some(ContentBlock::RedactedThinking { data: data.clone() })
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
 #[allow(dead_code)]
 pub struct Usage {
+    /// Input tokens billed at the full rate (excludes cache reads/writes)
     pub input_tokens: u32,
     pub output_tokens: u32,
+    /// Input tokens written to the provider's prompt cache
+    #[serde(default)]
+    pub cache_creation_input_tokens: u32,
+    /// Input tokens served from the provider's prompt cache
+    #[serde(default)]
+    pub cache_read_input_tokens: u32,
 }
 
 #[cfg(test)]
@@ -105,12 +195,36 @@ mod tests {
         assert_eq!(json["text"], "hello");
     }
 
+    #[test]
+    fn test_content_block_document_serialization() {
+        let block = ContentBlock::Document {
+            source: DocumentSource {
+                source_type: "base64".into(),
+                media_type: "application/pdf".into(),
+                data: "JVBERi0=".into(),
+            },
+            title: Some("report.pdf".into()),
+        };
+        let json = serde_json::to_value(&block).unwrap();
+        assert_eq!(
+            json,
+            json!({
+                "type": "document",
+                "source": {"type": "base64", "media_type": "application/pdf", "data": "JVBERi0="},
+                "title": "report.pdf"
+            })
+        );
+        assert!(is_document_media_type("application/pdf"));
+        assert!(!is_document_media_type("image/png"));
+    }
+
     #[test]
     fn test_content_block_tool_use_serialization() {
         let block = ContentBlock::ToolUse {
             id: "id_123".into(),
             name: "bash".into(),
             input: json!({"command": "ls"}),
+            signature: None,
         };
         let json = serde_json::to_value(&block).unwrap();
         assert_eq!(json["type"], "tool_use");
@@ -201,7 +315,9 @@ mod tests {
         });
         let block: ResponseContentBlock = serde_json::from_value(json).unwrap();
         match block {
-            ResponseContentBlock::ToolUse { id, name, input } => {
+            ResponseContentBlock::ToolUse {
+                id, name, input, ..
+            } => {
                 assert_eq!(id, "tu_abc");
                 assert_eq!(name, "bash");
                 assert_eq!(input["command"], "echo hi");
@@ -210,6 +326,41 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_thinking_blocks_roundtrip() {
+        let resp: MessagesResponse = serde_json::from_value(json!({
+            "content": [
+                {"type": "thinking", "thinking": "Let me check.", "signature": "sig=="},
+                {"type": "redacted_thinking", "data": "opaque"},
+                {"type": "text", "text": "  "},
+                {"type": "tool_use", "id": "tu_1", "name": "bash", "input": {}}
+            ],
+            "stop_reason": "tool_use",
+            "usage": null
+        }))
+        .unwrap();
+        let history: Vec<ContentBlock> = resp
+            .content
+            .iter()
+            .filter_map(ResponseContentBlock::to_content_block)
+            .collect();
+        assert_eq!(history.len(), 3);
+        let json = serde_json::to_value(&history).unwrap();
+        assert_eq!(json[0]["type"], "thinking");
+        assert_eq!(json[0]["signature"], "sig==");
+        assert_eq!(json[1]["type"], "redacted_thinking");
+        assert_eq!(json[2]["type"], "tool_use");
+
+        let unsigned = ContentBlock::Thinking {
+            thinking: "hmm".into(),
+            signature: None,
+        };
+        assert!(serde_json::to_value(&unsigned)
+            .unwrap()
+            .get("signature")
+            .is_none());
+    }
+
     #[test]
     fn test_messages_request_serialization() {
         let req = MessagesRequest {
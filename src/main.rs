@@ -1,8 +1,8 @@
 use rayclaw::config::Config;
 use rayclaw::error::RayClawError;
 use rayclaw::{
-    acp, builtin_skills, db, doctor, gateway, logging, mcp, memory, runtime, setup_wizard, skills,
-    update,
+    acp, builtin_skills, db, doctor, gateway, import, llm_trace, logging, mcp, memory, preflight,
+    runtime, setup_wizard, skills, update,
 };
 use std::path::Path;
 use tracing::info;
@@ -35,6 +35,8 @@ Commands:
                   --base-url   API base URL (default: https://ilinkai.weixin.qq.com)
                   --data-dir   Data directory for credentials (default: ./rayclaw.data)
   doctor        Run preflight environment checks
+  import        Import chat history exports (telegram-export / discord-export / slack-export)
+  traces        List and show recorded LLM requests (see llm_trace)
   gateway       Service lifecycle (install / start / stop / status / logs)
   update        Check for updates and self-update the binary
   version       Print version and exit
@@ -188,6 +190,14 @@ async fn main() -> anyhow::Result<()> {
             doctor::run_cli(&args[2..])?;
             return Ok(());
         }
+        Some("import") => {
+            import::run_cli(&args[2..]).await?;
+            return Ok(());
+        }
+        Some("traces") => {
+            llm_trace::run_cli(&args[2..])?;
+            return Ok(());
+        }
         Some("update") => {
             update::run_update(&args[2..]).await?;
             return Ok(());
@@ -237,6 +247,10 @@ async fn main() -> anyhow::Result<()> {
         logging::init_console_logging();
     }
 
+    if config.startup_preflight {
+        preflight::run(&config).await?;
+    }
+
     let db = db::Database::new(&runtime_data_dir)?;
     info!("Database initialized");
 
@@ -153,7 +153,7 @@ pub struct McpServer {
 }
 
 fn spawn_stdio_inner(spec: &McpStdioSpawnSpec, server_name: &str) -> Result<McpStdioInner, String> {
-    let mut cmd = Command::new(&spec.command);
+    let mut cmd = Command::new(crate::platform::program(&spec.command));
     cmd.args(&spec.args);
     cmd.envs(&spec.env);
     cmd.stdin(std::process::Stdio::piped());
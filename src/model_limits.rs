@@ -0,0 +1,163 @@
+//! Context window and output sizes of known models.
+//!
+//! Providers look up the configured model here to report
+//! [`ProviderCapabilities`](crate::llm::ProviderCapabilities), which the agent
+//! engine uses to decide when to compact. Entries in the config's
+//! `model_limits` list override (or add to) the built-in registry.
+
+use crate::config::ModelLimit;
+
+/// Limits of one model, in tokens. `None` when unknown.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ModelLimits {
+    pub context_window: Option<u32>,
+    pub max_output_tokens: Option<u32>,
+}
+
+/// Built-in limits, matched by model-name prefix. A prefix matches at the
+/// start of the name or after a `.`, `/` or `:` separator, so Bedrock IDs
+/// (`us.anthropic.claude-...`) and OpenRouter names (`openai/gpt-4o`) resolve
+/// too. The longest matching prefix wins.
+const BUILTIN_LIMITS: &[(&str, u32, u32)] = &[
+    // Anthropic
+    ("claude", 200_000, 8_192),
+    ("claude-3-haiku", 200_000, 4_096),
+    ("claude-3-opus", 200_000, 4_096),
+    ("claude-3-5-sonnet", 200_000, 8_192),
+    ("claude-3-5-haiku", 200_000, 8_192),
+    ("claude-3-7-sonnet", 200_000, 64_000),
+    ("claude-sonnet-4", 200_000, 64_000),
+    ("claude-haiku-4", 200_000, 64_000),
+    ("claude-opus-4", 200_000, 32_000),
+    // OpenAI
+    ("gpt-3.5-turbo", 16_385, 4_096),
+    ("gpt-4-turbo", 128_000, 4_096),
+    ("gpt-4o", 128_000, 16_384),
+    ("gpt-4.1", 1_047_576, 32_768),
+    ("gpt-5", 400_000, 128_000),
+    ("o1", 200_000, 100_000),
+    ("o3", 200_000, 100_000),
+    ("o4-mini", 200_000, 100_000),
+    // Google
+    ("gemini-1.5-pro", 2_097_152, 8_192),
+    ("gemini-1.5-flash", 1_048_576, 8_192),
+    ("gemini-2.0", 1_048_576, 8_192),
+    ("gemini-2.5", 1_048_576, 65_536),
+    // Others
+    ("deepseek-chat", 128_000, 8_192),
+    ("deepseek-reasoner", 128_000, 65_536),
+    ("mistral-large", 128_000, 8_192),
+    ("grok-4", 256_000, 128_000),
+    ("nova-pro", 300_000, 10_000),
+    ("nova-lite", 300_000, 10_000),
+    ("nova-micro", 128_000, 10_000),
+];
+
+/// Whether `prefix` starts `model` or one of its separator-delimited parts.
+fn matches_prefix(model: &str, prefix: &str) -> bool {
+    model
+        .match_indices(prefix)
+        .any(|(i, _)| i == 0 || matches!(model.as_bytes()[i - 1], b'.' | b'/' | b':'))
+}
+
+/// Limits from the built-in registry.
+pub fn builtin_limits(model: &str) -> Option<ModelLimits> {
+    let model = model.trim().to_lowercase();
+    BUILTIN_LIMITS
+        .iter()
+        .filter(|(prefix, _, _)| matches_prefix(&model, prefix))
+        .max_by_key(|(prefix, _, _)| prefix.len())
+        .map(|&(_, context_window, max_output_tokens)| ModelLimits {
+            context_window: Some(context_window),
+            max_output_tokens: Some(max_output_tokens),
+        })
+}
+
+/// Limits for `model`: a config entry naming it (case-insensitive, or `"*"`)
+/// overrides the built-in values it sets; fields it leaves out fall back to
+/// the registry. `None` when neither knows the model.
+pub fn resolve(overrides: &[ModelLimit], model: &str) -> Option<ModelLimits> {
+    let needle = model.trim();
+    let configured = overrides
+        .iter()
+        .find(|l| l.model.eq_ignore_ascii_case(needle))
+        .or_else(|| overrides.iter().find(|l| l.model == "*"));
+    let builtin = builtin_limits(model);
+    let Some(configured) = configured else {
+        return builtin;
+    };
+    let builtin = builtin.unwrap_or_default();
+    Some(ModelLimits {
+        context_window: configured.context_window.or(builtin.context_window),
+        max_output_tokens: configured.max_output_tokens.or(builtin.max_output_tokens),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_limits_longest_prefix_wins() {
+        let sonnet = builtin_limits("claude-sonnet-4-5-20250929").unwrap();
+        assert_eq!(sonnet.context_window, Some(200_000));
+        assert_eq!(sonnet.max_output_tokens, Some(64_000));
+        assert_eq!(
+            builtin_limits("claude-3-5-haiku-latest")
+                .unwrap()
+                .max_output_tokens,
+            Some(8_192)
+        );
+        assert_eq!(
+            builtin_limits("gpt-4o-mini").unwrap().context_window,
+            Some(128_000)
+        );
+        assert_eq!(builtin_limits("llama3.2"), None);
+    }
+
+    #[test]
+    fn test_builtin_limits_match_after_separators() {
+        assert_eq!(
+            builtin_limits("us.anthropic.claude-opus-4-1-20250805-v1:0")
+                .unwrap()
+                .max_output_tokens,
+            Some(32_000)
+        );
+        assert_eq!(
+            builtin_limits("openai/o3-mini").unwrap().context_window,
+            Some(200_000)
+        );
+        // "o1" inside a word is not a model prefix
+        assert_eq!(builtin_limits("foo1-model"), None);
+    }
+
+    #[test]
+    fn test_resolve_overrides_builtin_fields() {
+        let overrides = vec![
+            ModelLimit {
+                model: "Claude-Sonnet-4-5".into(),
+                context_window: Some(1_000_000),
+                max_output_tokens: None,
+            },
+            ModelLimit {
+                model: "my-local-model".into(),
+                context_window: Some(32_768),
+                max_output_tokens: Some(4_096),
+            },
+        ];
+        let sonnet = resolve(&overrides, "claude-sonnet-4-5").unwrap();
+        assert_eq!(sonnet.context_window, Some(1_000_000));
+        assert_eq!(sonnet.max_output_tokens, Some(64_000));
+        assert_eq!(
+            resolve(&overrides, "my-local-model")
+                .unwrap()
+                .context_window,
+            Some(32_768)
+        );
+        assert_eq!(
+            resolve(&[], "gpt-4.1").unwrap().context_window,
+            Some(1_047_576)
+        );
+        assert_eq!(resolve(&overrides, "unknown"), None);
+    }
+}
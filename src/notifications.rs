@@ -0,0 +1,539 @@
+//! Per-chat routing of proactive messages (scheduled task results, URL
+//! monitor alerts). Before such a message is pushed, the chat's
+//! [`NotificationSettings`] decide whether it goes out now, waits for the
+//! end of quiet hours / the next digest, or is dropped.
+
+use std::sync::Arc;
+
+use chrono::{DateTime, NaiveTime, TimeZone, Utc};
+use tracing::{error, info};
+
+use crate::channel::{deliver_and_store_bot_message, deliver_and_store_bot_message_in_thread};
+use crate::db::{call_blocking, NotificationSettings};
+use crate::runtime::AppState;
+
+/// Argument synopsis of the `/notify` command.
+pub const NOTIFY_ARGS: &str =
+    "[mute on|off | digest off|HH:MM | quiet off|HH:MM-HH:MM | level info|warning|error | reset]";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "info" => Some(Severity::Info),
+            "warning" | "warn" => Some(Severity::Warning),
+            "error" => Some(Severity::Error),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+}
+
+/// What produced a proactive message; fixes its severity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotificationKind {
+    /// Output of a scheduled task that ran successfully
+    TaskResult,
+    TaskFailure,
+    /// A watched webpage changed
+    UrlChange,
+    /// A watched webpage could not be checked
+    UrlMonitorError,
+}
+
+impl NotificationKind {
+    pub fn severity(self) -> Severity {
+        match self {
+            NotificationKind::TaskResult => Severity::Info,
+            NotificationKind::UrlChange | NotificationKind::UrlMonitorError => Severity::Warning,
+            NotificationKind::TaskFailure => Severity::Error,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Route {
+    Deliver,
+    /// Queue until quiet hours end or the next digest
+    Hold,
+    Drop,
+}
+
+pub fn parse_hhmm(s: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(s.trim(), "%H:%M")
+        .map_err(|_| format!("Invalid time '{s}': use 24-hour HH:MM"))
+}
+
+/// Whether `now` (local time) falls in the chat's quiet hours. A window
+/// whose start is after its end wraps past midnight (22:00-07:00).
+pub fn in_quiet_hours(settings: &NotificationSettings, now: NaiveTime) -> bool {
+    let (Some(start), Some(end)) = (
+        settings
+            .quiet_start
+            .as_deref()
+            .and_then(|s| parse_hhmm(s).ok()),
+        settings
+            .quiet_end
+            .as_deref()
+            .and_then(|s| parse_hhmm(s).ok()),
+    ) else {
+        return false;
+    };
+    if start <= end {
+        start <= now && now < end
+    } else {
+        now >= start || now < end
+    }
+}
+
+pub fn route<Tz: TimeZone>(
+    settings: &NotificationSettings,
+    kind: NotificationKind,
+    now: &DateTime<Tz>,
+) -> Route {
+    if kind == NotificationKind::TaskResult && settings.mute_task_results {
+        return Route::Drop;
+    }
+    let threshold = Severity::parse(&settings.min_severity).unwrap_or(Severity::Info);
+    if kind.severity() < threshold {
+        return Route::Drop;
+    }
+    if settings.digest_time.is_some() || in_quiet_hours(settings, now.time()) {
+        return Route::Hold;
+    }
+    Route::Deliver
+}
+
+/// Whether today's digest time has passed without a digest being sent.
+pub fn digest_due<Tz: TimeZone>(settings: &NotificationSettings, now: &DateTime<Tz>) -> bool {
+    let Some(at) = settings
+        .digest_time
+        .as_deref()
+        .and_then(|s| parse_hhmm(s).ok())
+    else {
+        return false;
+    };
+    let Some(today_digest) = now
+        .timezone()
+        .from_local_datetime(&now.date_naive().and_time(at))
+        .earliest()
+    else {
+        return false;
+    };
+    if *now < today_digest {
+        return false;
+    }
+    match settings
+        .last_digest_at
+        .as_deref()
+        .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+    {
+        Some(last) => last < today_digest,
+        None => true,
+    }
+}
+
+fn chat_timezone(state: &AppState) -> chrono_tz::Tz {
+    state.config.timezone.parse().unwrap_or(chrono_tz::Tz::UTC)
+}
+
+async fn load_settings(state: &AppState, chat_id: i64) -> NotificationSettings {
+    call_blocking(state.db.clone(), move |db| {
+        db.get_notification_settings(chat_id)
+    })
+    .await
+    .ok()
+    .flatten()
+    .unwrap_or_else(|| NotificationSettings::defaults(chat_id))
+}
+
+/// Push a proactive message to `chat_id`, honoring its notification
+/// settings. Held messages go out later via [`flush_pending`].
+pub async fn notify_chat(
+    state: &AppState,
+    chat_id: i64,
+    text: &str,
+    kind: NotificationKind,
+) -> Result<(), String> {
+    notify_chat_in_thread(state, chat_id, None, text, kind).await
+}
+
+/// Like [`notify_chat`], delivering in forum topic `thread_id` of the chat.
+/// Held messages still go out in the chat's digest.
+pub async fn notify_chat_in_thread(
+    state: &AppState,
+    chat_id: i64,
+    thread_id: Option<i64>,
+    text: &str,
+    kind: NotificationKind,
+) -> Result<(), String> {
+    let mut settings = load_settings(state, chat_id).await;
+    let now = Utc::now().with_timezone(&chat_timezone(state));
+    let route = route(&settings, kind, &now);
+    if route == Route::Hold && digest_due(&settings, &now) {
+        // Today's digest time passed with nothing to send; without this the
+        // flusher would treat the digest as still due and send right away.
+        let chats = call_blocking(state.db.clone(), |db| {
+            db.get_chats_with_pending_notifications()
+        })
+        .await
+        .unwrap_or_default();
+        if !chats.contains(&chat_id) {
+            settings.last_digest_at = Some(Utc::now().to_rfc3339());
+            let _ = call_blocking(state.db.clone(), move |db| {
+                db.save_notification_settings(&settings)
+            })
+            .await;
+        }
+    }
+    match route {
+        Route::Deliver => {
+            deliver_and_store_bot_message_in_thread(
+                &state.channel_registry,
+                state.db.clone(),
+                &state.config.bot_username,
+                chat_id,
+                thread_id,
+                text,
+            )
+            .await
+        }
+        Route::Hold => {
+            let content = text.to_string();
+            let severity = kind.severity().as_str();
+            call_blocking(state.db.clone(), move |db| {
+                db.queue_notification(chat_id, &content, severity)
+            })
+            .await
+            .map(|_| ())
+            .map_err(|e| format!("Failed to queue notification: {e}"))
+        }
+        Route::Drop => {
+            info!("Notification for chat {chat_id} dropped by its settings ({kind:?})");
+            Ok(())
+        }
+    }
+}
+
+/// Deliver held notifications for chats whose quiet hours are over and
+/// whose digest (if in digest mode) is due, one combined message per chat.
+pub async fn flush_pending(state: &Arc<AppState>) {
+    let chats = match call_blocking(state.db.clone(), |db| {
+        db.get_chats_with_pending_notifications()
+    })
+    .await
+    {
+        Ok(chats) => chats,
+        Err(e) => {
+            error!("Notifications: failed to query pending notifications: {e}");
+            return;
+        }
+    };
+
+    let tz = chat_timezone(state);
+    for chat_id in chats {
+        let mut settings = load_settings(state, chat_id).await;
+        let now = Utc::now().with_timezone(&tz);
+        if in_quiet_hours(&settings, now.time()) {
+            continue;
+        }
+        let digest = settings.digest_time.is_some();
+        if digest && !digest_due(&settings, &now) {
+            continue;
+        }
+
+        let pending = match call_blocking(state.db.clone(), move |db| {
+            db.take_pending_notifications(chat_id)
+        })
+        .await
+        {
+            Ok(p) if !p.is_empty() => p,
+            Ok(_) => continue,
+            Err(e) => {
+                error!("Notifications: failed to load pending for chat {chat_id}: {e}");
+                continue;
+            }
+        };
+
+        let header = if digest {
+            format!("📬 Digest: {} notification(s)", pending.len())
+        } else {
+            format!(
+                "📬 {} notification(s) held during quiet hours",
+                pending.len()
+            )
+        };
+        let body = pending
+            .iter()
+            .map(|n| {
+                let at = DateTime::parse_from_rfc3339(&n.created_at)
+                    .map(|t| t.with_timezone(&tz).format("%H:%M").to_string())
+                    .unwrap_or_default();
+                format!("[{at}] {}", n.content)
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        if let Err(e) = deliver_and_store_bot_message(
+            &state.channel_registry,
+            state.db.clone(),
+            &state.config.bot_username,
+            chat_id,
+            &format!("{header}\n\n{body}"),
+        )
+        .await
+        {
+            error!("Notifications: failed to deliver held messages to chat {chat_id}: {e}");
+        }
+
+        if digest {
+            settings.last_digest_at = Some(Utc::now().to_rfc3339());
+            let _ = call_blocking(state.db.clone(), move |db| {
+                db.save_notification_settings(&settings)
+            })
+            .await;
+        }
+    }
+}
+
+/// Apply one `/notify` change to `settings`, returning a confirmation.
+pub fn apply_setting(
+    settings: &mut NotificationSettings,
+    key: &str,
+    value: Option<&str>,
+    now: DateTime<Utc>,
+) -> Result<String, String> {
+    let usage = || format!("Usage: /notify {NOTIFY_ARGS}");
+    match (key.to_ascii_lowercase().as_str(), value) {
+        ("reset", None) => {
+            *settings = NotificationSettings::defaults(settings.chat_id);
+            Ok("Notification settings reset to defaults.".into())
+        }
+        ("mute", Some("on")) => {
+            settings.mute_task_results = true;
+            Ok("Scheduled task results muted (failures still arrive).".into())
+        }
+        ("mute", Some("off")) => {
+            settings.mute_task_results = false;
+            Ok("Scheduled task results unmuted.".into())
+        }
+        ("digest", Some("off")) => {
+            settings.digest_time = None;
+            Ok("Digest mode off: notifications are delivered as they happen.".into())
+        }
+        ("digest", Some(at)) => {
+            let at = parse_hhmm(at)?;
+            settings.digest_time = Some(at.format("%H:%M").to_string());
+            // The first digest is the next occurrence, not one right away
+            settings.last_digest_at = Some(now.to_rfc3339());
+            Ok(format!(
+                "Digest mode on: notifications are collected and sent daily at {}.",
+                at.format("%H:%M")
+            ))
+        }
+        ("quiet", Some("off")) => {
+            settings.quiet_start = None;
+            settings.quiet_end = None;
+            Ok("Quiet hours off.".into())
+        }
+        ("quiet", Some(range)) => {
+            let (start, end) = range.split_once('-').ok_or_else(usage)?;
+            let (start, end) = (parse_hhmm(start)?, parse_hhmm(end)?);
+            if start == end {
+                return Err("Quiet hours must start and end at different times.".into());
+            }
+            settings.quiet_start = Some(start.format("%H:%M").to_string());
+            settings.quiet_end = Some(end.format("%H:%M").to_string());
+            Ok(format!(
+                "Quiet hours set to {}-{}; notifications are held until they end.",
+                start.format("%H:%M"),
+                end.format("%H:%M")
+            ))
+        }
+        ("level", Some(level)) => {
+            let level = Severity::parse(level).ok_or_else(usage)?;
+            settings.min_severity = level.as_str().to_string();
+            Ok(format!(
+                "Only {} and above notifications are delivered.",
+                level.as_str()
+            ))
+        }
+        _ => Err(usage()),
+    }
+}
+
+/// One line per setting, for `/notify` without arguments.
+pub fn describe_settings(settings: &NotificationSettings) -> String {
+    let quiet = match (&settings.quiet_start, &settings.quiet_end) {
+        (Some(start), Some(end)) => format!("{start}-{end}"),
+        _ => "off".into(),
+    };
+    [
+        format!(
+            "Task results: {}",
+            if settings.mute_task_results {
+                "muted"
+            } else {
+                "on"
+            }
+        ),
+        format!(
+            "Digest: {}",
+            settings
+                .digest_time
+                .as_deref()
+                .map(|t| format!("daily at {t}"))
+                .unwrap_or_else(|| "off".into())
+        ),
+        format!("Quiet hours: {quiet}"),
+        format!("Minimum level: {}", settings.min_severity),
+    ]
+    .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(hhmm: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(&format!("2024-05-01T{hhmm}:00Z"))
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn settings() -> NotificationSettings {
+        NotificationSettings::defaults(1)
+    }
+
+    #[test]
+    fn test_default_settings_deliver_everything() {
+        let s = settings();
+        for kind in [
+            NotificationKind::TaskResult,
+            NotificationKind::TaskFailure,
+            NotificationKind::UrlChange,
+            NotificationKind::UrlMonitorError,
+        ] {
+            assert_eq!(route(&s, kind, &at("12:00")), Route::Deliver);
+        }
+    }
+
+    #[test]
+    fn test_mute_and_level_drop() {
+        let mut s = settings();
+        s.mute_task_results = true;
+        assert_eq!(
+            route(&s, NotificationKind::TaskResult, &at("12:00")),
+            Route::Drop
+        );
+        assert_eq!(
+            route(&s, NotificationKind::TaskFailure, &at("12:00")),
+            Route::Deliver
+        );
+
+        let mut s = settings();
+        s.min_severity = "error".into();
+        assert_eq!(
+            route(&s, NotificationKind::UrlChange, &at("12:00")),
+            Route::Drop
+        );
+        assert_eq!(
+            route(&s, NotificationKind::TaskFailure, &at("12:00")),
+            Route::Deliver
+        );
+    }
+
+    #[test]
+    fn test_quiet_hours_wrap_midnight() {
+        let mut s = settings();
+        s.quiet_start = Some("22:00".into());
+        s.quiet_end = Some("07:00".into());
+        assert!(in_quiet_hours(&s, parse_hhmm("23:30").unwrap()));
+        assert!(in_quiet_hours(&s, parse_hhmm("06:59").unwrap()));
+        assert!(!in_quiet_hours(&s, parse_hhmm("07:00").unwrap()));
+        assert!(!in_quiet_hours(&s, parse_hhmm("12:00").unwrap()));
+        assert_eq!(
+            route(&s, NotificationKind::TaskFailure, &at("23:00")),
+            Route::Hold
+        );
+
+        s.quiet_start = Some("13:00".into());
+        s.quiet_end = Some("14:00".into());
+        assert!(in_quiet_hours(&s, parse_hhmm("13:30").unwrap()));
+        assert!(!in_quiet_hours(&s, parse_hhmm("14:30").unwrap()));
+    }
+
+    #[test]
+    fn test_digest_mode_holds_until_due() {
+        let mut s = settings();
+        s.digest_time = Some("09:00".into());
+        s.last_digest_at = Some(at("08:00").to_rfc3339());
+        assert_eq!(
+            route(&s, NotificationKind::UrlChange, &at("10:00")),
+            Route::Hold
+        );
+        assert!(!digest_due(&s, &at("08:59")));
+        assert!(digest_due(&s, &at("09:00")));
+
+        s.last_digest_at = Some(at("09:00").to_rfc3339());
+        assert!(!digest_due(&s, &at("18:00")));
+
+        s.digest_time = None;
+        assert!(!digest_due(&s, &at("18:00")));
+    }
+
+    #[test]
+    fn test_digest_due_in_chat_timezone() {
+        let mut s = settings();
+        s.digest_time = Some("09:00".into());
+        // 09:00 in Tokyo is 00:00 UTC
+        let tokyo = at("00:30").with_timezone(&chrono_tz::Asia::Tokyo);
+        assert!(digest_due(&s, &tokyo));
+        let before = at("23:30").with_timezone(&chrono_tz::Asia::Tokyo);
+        assert!(!digest_due(&s, &before));
+    }
+
+    #[test]
+    fn test_apply_setting() {
+        let now = at("10:00");
+        let mut s = settings();
+        apply_setting(&mut s, "mute", Some("on"), now).unwrap();
+        assert!(s.mute_task_results);
+        apply_setting(&mut s, "digest", Some("8:30"), now).unwrap();
+        assert_eq!(s.digest_time.as_deref(), Some("08:30"));
+        assert_eq!(s.last_digest_at, Some(now.to_rfc3339()));
+        apply_setting(&mut s, "quiet", Some("22:00-07:00"), now).unwrap();
+        assert_eq!(s.quiet_start.as_deref(), Some("22:00"));
+        assert_eq!(s.quiet_end.as_deref(), Some("07:00"));
+        apply_setting(&mut s, "level", Some("WARN"), now).unwrap();
+        assert_eq!(s.min_severity, "warning");
+
+        let text = describe_settings(&s);
+        assert!(text.contains("Task results: muted"));
+        assert!(text.contains("Digest: daily at 08:30"));
+        assert!(text.contains("Quiet hours: 22:00-07:00"));
+
+        assert!(apply_setting(&mut s, "quiet", Some("22:00"), now).is_err());
+        assert!(apply_setting(&mut s, "quiet", Some("07:00-07:00"), now).is_err());
+        assert!(apply_setting(&mut s, "digest", Some("25:00"), now)
+            .unwrap_err()
+            .contains("HH:MM"));
+        assert!(apply_setting(&mut s, "level", Some("loud"), now).is_err());
+        assert!(apply_setting(&mut s, "bogus", None, now).is_err());
+
+        apply_setting(&mut s, "reset", None, now).unwrap();
+        assert_eq!(s, settings());
+    }
+}
@@ -0,0 +1,379 @@
+//! Degraded mode while the LLM provider is down.
+//!
+//! When a user message can't be answered because the shared provider is
+//! unreachable or overloaded (after the usual retries), the chat gets a short
+//! status reply and the request is parked here. While the provider is marked
+//! down, new messages skip the LLM call and are parked right away. A
+//! background task probes the provider and, once it answers again, replays
+//! the parked requests, telling each chat its message is now being handled.
+//!
+//! The queue holds at most one entry per chat (a replay answers every message
+//! the chat sent meanwhile), is bounded by `outage_queue_size`, and entries
+//! older than `outage_queue_ttl_mins` are dropped instead of replayed.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tracing::{info, warn};
+
+use crate::agent_engine::AgentRequestContext;
+use crate::db::call_blocking;
+use crate::llm_types::{Message, MessageContent};
+use crate::runtime::AppState;
+
+/// How often the provider is probed while marked down.
+const PROBE_INTERVAL: Duration = Duration::from_secs(30);
+
+const RECOVERED_NOTICE: &str = "The AI provider is back. Working on your earlier message now.";
+
+#[derive(Debug, Clone)]
+pub struct QueuedRequest {
+    pub caller_channel: String,
+    pub chat_id: i64,
+    /// Forum topic the message came from
+    pub thread_id: Option<i64>,
+    pub chat_type: String,
+    /// Tools withheld from the original run, withheld from the replay too
+    pub denied_tools: Vec<String>,
+    pub image_data: Option<(String, String)>,
+    pub queued_at: chrono::DateTime<chrono::Utc>,
+    expires_at: Instant,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Enqueued {
+    /// Parked at this 1-based position
+    Queued(usize),
+    /// The chat already had an entry, which will answer this message too
+    AlreadyQueued(usize),
+    /// The queue is full (or disabled)
+    Full,
+}
+
+#[derive(Default)]
+struct OutageState {
+    down_since: Option<Instant>,
+    pending: VecDeque<QueuedRequest>,
+}
+
+pub struct OutageQueue {
+    capacity: usize,
+    ttl: Duration,
+    state: Mutex<OutageState>,
+}
+
+impl OutageQueue {
+    /// `capacity` of 0 disables queueing; outages then surface as errors.
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            capacity,
+            ttl,
+            state: Mutex::new(OutageState::default()),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    pub fn is_down(&self) -> bool {
+        self.state.lock().unwrap().down_since.is_some()
+    }
+
+    /// Minutes a parked request waits before it is dropped.
+    pub fn ttl_mins(&self) -> u64 {
+        self.ttl.as_secs().div_ceil(60)
+    }
+
+    /// Park the request of `context`, marking the provider down.
+    pub fn enqueue(
+        &self,
+        context: AgentRequestContext<'_>,
+        image_data: Option<(String, String)>,
+    ) -> Enqueued {
+        self.enqueue_at(context, image_data, Instant::now())
+    }
+
+    fn enqueue_at(
+        &self,
+        context: AgentRequestContext<'_>,
+        image_data: Option<(String, String)>,
+        now: Instant,
+    ) -> Enqueued {
+        let AgentRequestContext {
+            caller_channel,
+            chat_id,
+            thread_id,
+            chat_type,
+            denied_tools,
+        } = context;
+        let mut state = self.state.lock().unwrap();
+        if state.down_since.is_none() {
+            warn!("LLM provider unavailable; queueing user messages until it recovers");
+            state.down_since = Some(now);
+        }
+        state.pending.retain(|r| r.expires_at > now);
+        if let Some(pos) = state
+            .pending
+            .iter()
+            .position(|r| r.chat_id == chat_id && r.thread_id == thread_id)
+        {
+            let entry = &mut state.pending[pos];
+            // The replay answers this message too, so it gets the
+            // restrictions of both
+            for tool in denied_tools {
+                if !entry.denied_tools.iter().any(|t| t == tool) {
+                    entry.denied_tools.push(tool.to_string());
+                }
+            }
+            if image_data.is_some() {
+                entry.image_data = image_data;
+            }
+            return Enqueued::AlreadyQueued(pos + 1);
+        }
+        if state.pending.len() >= self.capacity {
+            return Enqueued::Full;
+        }
+        state.pending.push_back(QueuedRequest {
+            caller_channel: caller_channel.to_string(),
+            chat_id,
+            thread_id,
+            chat_type: chat_type.to_string(),
+            denied_tools: denied_tools.iter().map(|t| t.to_string()).collect(),
+            image_data,
+            queued_at: chrono::Utc::now(),
+            expires_at: now + self.ttl,
+        });
+        Enqueued::Queued(state.pending.len())
+    }
+
+    /// Mark the provider as recovered and hand back the unexpired requests,
+    /// oldest first.
+    pub fn recover(&self) -> Vec<QueuedRequest> {
+        self.recover_at(Instant::now())
+    }
+
+    fn recover_at(&self, now: Instant) -> Vec<QueuedRequest> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(since) = state.down_since.take() {
+            info!(
+                "LLM provider recovered after {}s; replaying {} queued request(s)",
+                now.saturating_duration_since(since).as_secs(),
+                state.pending.len()
+            );
+        }
+        state
+            .pending
+            .drain(..)
+            .filter(|r| r.expires_at > now)
+            .collect()
+    }
+}
+
+/// Status reply for a user message that hit an outage.
+pub fn status_message(outcome: Enqueued, ttl_mins: u64) -> String {
+    match outcome {
+        Enqueued::Queued(1) | Enqueued::AlreadyQueued(1) => format!(
+            "The AI provider is unavailable right now. I've queued your message and will answer it automatically once it's back (if that's within {ttl_mins} min)."
+        ),
+        Enqueued::Queued(pos) | Enqueued::AlreadyQueued(pos) => format!(
+            "The AI provider is unavailable right now. Your message is #{pos} in the queue and will be answered automatically once it's back (if that's within {ttl_mins} min)."
+        ),
+        Enqueued::Full => "The AI provider is unavailable right now and too many messages are already waiting. Please try again later.".to_string(),
+    }
+}
+
+pub fn spawn_recovery_worker(state: Arc<AppState>) {
+    if !state.outage.enabled() {
+        return;
+    }
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(PROBE_INTERVAL).await;
+            if !state.outage.is_down() || !probe(&state).await {
+                continue;
+            }
+            for request in state.outage.recover() {
+                replay(&state, request).await;
+            }
+        }
+    });
+}
+
+/// A minimal request to check whether the shared provider answers again.
+async fn probe(state: &AppState) -> bool {
+    let messages = vec![Message {
+        role: "user".into(),
+        content: MessageContent::Text("ping".into()),
+    }];
+    match state
+        .llm
+        .send_message("Reply with OK.", messages, None)
+        .await
+    {
+        Ok(_) => true,
+        Err(e) => {
+            info!("LLM provider still unavailable: {e}");
+            false
+        }
+    }
+}
+
+pub(crate) async fn replay(state: &AppState, request: QueuedRequest) {
+    let chat_id = request.chat_id;
+    let thread_id = request.thread_id;
+    // A session saved after the request was parked means the chat has
+    // already been answered (e.g. the user wrote again after recovery).
+    let answered = call_blocking(state.db.clone(), move |db| {
+        db.load_session(chat_id, thread_id)
+    })
+    .await
+    .ok()
+    .flatten()
+    .and_then(|(_, updated_at)| chrono::DateTime::parse_from_rfc3339(&updated_at).ok())
+    .is_some_and(|updated_at| updated_at > request.queued_at);
+    if answered {
+        return;
+    }
+
+    let deliver = |text: String| async move {
+        if let Err(e) = crate::channel::deliver_and_store_bot_message_in_thread(
+            &state.channel_registry,
+            state.db.clone(),
+            &state.config.bot_username,
+            chat_id,
+            thread_id,
+            &text,
+        )
+        .await
+        {
+            warn!("Outage replay: failed to deliver to chat {chat_id}: {e}");
+        }
+    };
+
+    deliver(RECOVERED_NOTICE.to_string()).await;
+    let denied_tools: Vec<&str> = request.denied_tools.iter().map(String::as_str).collect();
+    let result = crate::agent_engine::process_queued_request(
+        state,
+        AgentRequestContext {
+            caller_channel: &request.caller_channel,
+            chat_id,
+            thread_id,
+            chat_type: &request.chat_type,
+            denied_tools: &denied_tools,
+        },
+        request.image_data,
+    )
+    .await;
+    match result {
+        Ok(response) if !response.is_empty() => deliver(response).await,
+        Ok(_) => {}
+        Err(e) => deliver(format!("Error: {e}")).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue(capacity: usize) -> OutageQueue {
+        OutageQueue::new(capacity, Duration::from_secs(600))
+    }
+
+    fn context<'a>(
+        caller_channel: &'a str,
+        chat_id: i64,
+        thread_id: Option<i64>,
+        chat_type: &'a str,
+        denied_tools: &'a [&'a str],
+    ) -> AgentRequestContext<'a> {
+        AgentRequestContext {
+            caller_channel,
+            chat_id,
+            thread_id,
+            chat_type,
+            denied_tools,
+        }
+    }
+
+    #[test]
+    fn test_enqueue_marks_down_and_dedupes_per_chat() {
+        let q = queue(2);
+        assert!(!q.is_down());
+        let now = Instant::now();
+        assert_eq!(
+            q.enqueue_at(context("telegram", 1, None, "private", &[]), None, now),
+            Enqueued::Queued(1)
+        );
+        assert!(q.is_down());
+        assert_eq!(
+            q.enqueue_at(context("telegram", 2, None, "group", &[]), None, now),
+            Enqueued::Queued(2)
+        );
+        assert_eq!(
+            q.enqueue_at(context("telegram", 1, None, "private", &[]), None, now),
+            Enqueued::AlreadyQueued(1)
+        );
+        // Another forum topic of the same chat waits on its own
+        assert_eq!(
+            q.enqueue_at(context("telegram", 2, Some(7), "group", &[]), None, now),
+            Enqueued::Full
+        );
+
+        let replayed = q.recover_at(now);
+        assert!(!q.is_down());
+        assert_eq!(
+            replayed.iter().map(|r| r.chat_id).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        assert!(q.recover_at(now).is_empty());
+    }
+
+    #[test]
+    fn test_denied_tools_kept_and_merged() {
+        let q = queue(2);
+        let now = Instant::now();
+        q.enqueue_at(context("teams", 1, None, "group", &["bash"]), None, now);
+        let denied = ["bash", "write_file"];
+        q.enqueue_at(context("teams", 1, None, "group", &denied), None, now);
+        let replayed = q.recover_at(now);
+        assert_eq!(replayed[0].denied_tools, vec!["bash", "write_file"]);
+    }
+
+    #[test]
+    fn test_expired_requests_are_dropped() {
+        let q = queue(1);
+        let now = Instant::now();
+        q.enqueue_at(context("telegram", 1, None, "private", &[]), None, now);
+        // Expired entries free their slot for newcomers
+        let later = now + Duration::from_secs(601);
+        assert_eq!(
+            q.enqueue_at(context("telegram", 2, None, "private", &[]), None, later),
+            Enqueued::Queued(1)
+        );
+        let replayed = q.recover_at(later + Duration::from_secs(601));
+        assert!(replayed.is_empty());
+    }
+
+    #[test]
+    fn test_disabled_queue_is_always_full() {
+        let q = queue(0);
+        assert!(!q.enabled());
+        assert_eq!(
+            q.enqueue_at(
+                context("telegram", 1, None, "private", &[]),
+                None,
+                Instant::now()
+            ),
+            Enqueued::Full
+        );
+    }
+
+    #[test]
+    fn test_status_message() {
+        assert!(status_message(Enqueued::Queued(1), 60).contains("within 60 min"));
+        assert!(status_message(Enqueued::AlreadyQueued(3), 60).contains("#3"));
+        assert!(status_message(Enqueued::Full, 60).contains("try again later"));
+    }
+}
@@ -0,0 +1,269 @@
+//! HTTP clients for the web-facing tools, with the deployment's proxy,
+//! user-agent and per-domain headers from `outbound_http` applied.
+//!
+//! Each tool keeps its own lazily built client (see the `http_client()`
+//! functions); they are built from [`client_builder`] with the tool's scope
+//! name, so settings must be installed with [`configure`] at startup, before
+//! the first request.
+
+use std::sync::OnceLock;
+
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Method, RequestBuilder};
+use tracing::warn;
+
+use crate::config::OutboundHttpConfig;
+
+/// Clients that can be given their own proxy and user-agent under
+/// `outbound_http.tools`.
+pub const SCOPES: &[&str] = &[
+    "web_fetch",
+    "web_search",
+    "news_search",
+    "wiki_lookup",
+    "maps",
+    "package_tracking",
+    "flight_status",
+    "url_monitor",
+];
+
+static SETTINGS: OnceLock<OutboundHttpConfig> = OnceLock::new();
+
+/// Install the `outbound_http` settings. Only the first call takes effect.
+pub fn configure(config: &OutboundHttpConfig) {
+    if SETTINGS.set(config.clone()).is_err() {
+        warn!("outbound_http settings already in use; ignoring reconfiguration");
+    }
+}
+
+pub(crate) fn settings() -> &'static OutboundHttpConfig {
+    SETTINGS.get_or_init(OutboundHttpConfig::default)
+}
+
+#[derive(Debug, PartialEq)]
+enum ProxyChoice<'a> {
+    /// reqwest's default: proxies from the environment
+    System,
+    Direct,
+    Url(&'a str),
+}
+
+fn proxy_for<'a>(settings: &'a OutboundHttpConfig, scope: &str) -> ProxyChoice<'a> {
+    let proxy = settings
+        .tools
+        .get(scope)
+        .and_then(|t| t.proxy.as_deref())
+        .or(settings.proxy.as_deref());
+    match proxy {
+        None => ProxyChoice::System,
+        Some("direct") => ProxyChoice::Direct,
+        Some(url) => ProxyChoice::Url(url),
+    }
+}
+
+fn user_agent_for<'a>(
+    settings: &'a OutboundHttpConfig,
+    scope: &str,
+    default_user_agent: &'a str,
+) -> &'a str {
+    settings
+        .tools
+        .get(scope)
+        .and_then(|t| t.user_agent.as_deref())
+        .or(settings.user_agent.as_deref())
+        .unwrap_or(default_user_agent)
+}
+
+/// The user-agent a client for `scope` sends.
+pub fn user_agent(scope: &str, default_user_agent: &'static str) -> &'static str {
+    user_agent_for(settings(), scope, default_user_agent)
+}
+
+/// Configured headers for `host`. Sets for broader domains come first so a
+/// more specific domain's value wins.
+fn headers_for(settings: &OutboundHttpConfig, host: &str) -> HeaderMap {
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    let mut matching: Vec<_> = settings
+        .headers
+        .iter()
+        .filter(|(domain, _)| {
+            host == **domain
+                || host
+                    .strip_suffix(domain.as_str())
+                    .is_some_and(|prefix| prefix.ends_with('.'))
+        })
+        .collect();
+    matching.sort_by_key(|(domain, _)| domain.len());
+    let mut headers = HeaderMap::new();
+    for (_, values) in matching {
+        for (name, value) in values {
+            // Validated in Config::post_deserialize
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(value),
+            ) {
+                headers.insert(name, value);
+            }
+        }
+    }
+    headers
+}
+
+/// A client builder for `scope` with its proxy and user-agent applied.
+pub fn client_builder(scope: &str, default_user_agent: &str) -> reqwest::ClientBuilder {
+    let settings = settings();
+    let builder =
+        reqwest::Client::builder().user_agent(user_agent_for(settings, scope, default_user_agent));
+    match proxy_for(settings, scope) {
+        ProxyChoice::System => builder,
+        ProxyChoice::Direct => builder.no_proxy(),
+        ProxyChoice::Url(url) => match reqwest::Proxy::all(url) {
+            Ok(proxy) => builder.proxy(
+                proxy.no_proxy(
+                    settings
+                        .no_proxy
+                        .as_deref()
+                        .and_then(reqwest::NoProxy::from_string),
+                ),
+            ),
+            Err(e) => {
+                warn!("{scope}: ignoring unusable proxy: {e}");
+                builder
+            }
+        },
+    }
+}
+
+/// A `reqwest::Client` that adds the configured per-domain headers to each
+/// request.
+pub struct OutboundClient {
+    client: reqwest::Client,
+}
+
+impl From<reqwest::Client> for OutboundClient {
+    fn from(client: reqwest::Client) -> Self {
+        OutboundClient { client }
+    }
+}
+
+impl OutboundClient {
+    pub fn get(&self, url: impl AsRef<str>) -> RequestBuilder {
+        self.request(Method::GET, url)
+    }
+
+    pub fn post(&self, url: impl AsRef<str>) -> RequestBuilder {
+        self.request(Method::POST, url)
+    }
+
+    pub fn request(&self, method: Method, url: impl AsRef<str>) -> RequestBuilder {
+        let url = url.as_ref();
+        let request = self.client.request(method, url);
+        let host = reqwest::Url::parse(url)
+            .ok()
+            .and_then(|u| u.host_str().map(str::to_string));
+        match host {
+            Some(host) => {
+                let headers = headers_for(settings(), &host);
+                if headers.is_empty() {
+                    request
+                } else {
+                    request.headers(headers)
+                }
+            }
+            None => request,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::OutboundHttpOverride;
+    use std::collections::HashMap;
+
+    fn sample() -> OutboundHttpConfig {
+        OutboundHttpConfig {
+            proxy: Some("socks5h://127.0.0.1:1080".into()),
+            no_proxy: None,
+            user_agent: Some("Acme/2.0".into()),
+            headers: HashMap::from([
+                (
+                    "example.com".to_string(),
+                    HashMap::from([
+                        ("X-Team".to_string(), "all".to_string()),
+                        ("Authorization".to_string(), "Bearer outer".to_string()),
+                    ]),
+                ),
+                (
+                    "api.example.com".to_string(),
+                    HashMap::from([("Authorization".to_string(), "Bearer inner".to_string())]),
+                ),
+            ]),
+            tools: HashMap::from([
+                (
+                    "web_fetch".to_string(),
+                    OutboundHttpOverride {
+                        proxy: Some("http://residential:8080".into()),
+                        user_agent: Some("Mozilla/5.0".into()),
+                    },
+                ),
+                (
+                    "maps".to_string(),
+                    OutboundHttpOverride {
+                        proxy: Some("direct".into()),
+                        user_agent: None,
+                    },
+                ),
+            ]),
+            politeness: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_proxy_and_user_agent_precedence() {
+        let settings = sample();
+        assert_eq!(
+            proxy_for(&settings, "web_fetch"),
+            ProxyChoice::Url("http://residential:8080")
+        );
+        assert_eq!(proxy_for(&settings, "maps"), ProxyChoice::Direct);
+        assert_eq!(
+            proxy_for(&settings, "web_search"),
+            ProxyChoice::Url("socks5h://127.0.0.1:1080")
+        );
+        assert_eq!(
+            proxy_for(&OutboundHttpConfig::default(), "web_search"),
+            ProxyChoice::System
+        );
+
+        assert_eq!(
+            user_agent_for(&settings, "web_fetch", "RayClaw/1.0"),
+            "Mozilla/5.0"
+        );
+        assert_eq!(user_agent_for(&settings, "maps", "RayClaw/1.0"), "Acme/2.0");
+        assert_eq!(
+            user_agent_for(&OutboundHttpConfig::default(), "maps", "RayClaw/1.0"),
+            "RayClaw/1.0"
+        );
+    }
+
+    #[test]
+    fn test_headers_for_domain() {
+        let settings = sample();
+        let headers = headers_for(&settings, "api.example.com");
+        assert_eq!(headers["authorization"], "Bearer inner");
+        assert_eq!(headers["x-team"], "all");
+
+        let headers = headers_for(&settings, "WWW.Example.com.");
+        assert_eq!(headers["authorization"], "Bearer outer");
+        assert!(headers_for(&settings, "notexample.com").is_empty());
+        assert!(headers_for(&settings, "example.org").is_empty());
+    }
+
+    #[test]
+    fn test_client_builder_accepts_socks_proxy() {
+        // Building fails when the socks feature is missing
+        let proxy = reqwest::Proxy::all("socks5h://127.0.0.1:1080").unwrap();
+        assert!(reqwest::Client::builder().proxy(proxy).build().is_ok());
+    }
+}
@@ -0,0 +1,125 @@
+//! Differences between Unix and Windows that several modules need: where
+//! the home directory is, how canonical paths look, and which file a bare
+//! program name like `npx` launches.
+
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+/// The user's home directory. On Windows `USERPROFILE` comes first, then
+/// `HOME` (set by Git Bash and MSYS) and `HOMEDRIVE` + `HOMEPATH`.
+pub fn home_dir() -> Option<PathBuf> {
+    home_dir_from(|key| std::env::var_os(key), cfg!(target_os = "windows"))
+}
+
+fn home_dir_from(env: impl Fn(&str) -> Option<OsString>, windows: bool) -> Option<PathBuf> {
+    let var = |key: &str| env(key).filter(|v| !v.is_empty());
+    if !windows {
+        return var("HOME").map(PathBuf::from);
+    }
+    if let Some(home) = var("USERPROFILE").or_else(|| var("HOME")) {
+        return Some(PathBuf::from(home));
+    }
+    let (drive, path) = (var("HOMEDRIVE")?, var("HOMEPATH")?);
+    let mut home = drive;
+    home.push(path);
+    Some(PathBuf::from(home))
+}
+
+/// `std::fs::canonicalize`, except that on Windows the result is a plain
+/// `C:\...` or `\\server\share\...` path rather than the `\\?\` verbatim form,
+/// so it compares equal to paths agents and users type and is accepted by
+/// programs that don't understand verbatim paths.
+pub fn canonicalize(path: impl AsRef<Path>) -> std::io::Result<PathBuf> {
+    let path = std::fs::canonicalize(path)?;
+    if !cfg!(target_os = "windows") {
+        return Ok(path);
+    }
+    Ok(path
+        .to_str()
+        .and_then(strip_verbatim_prefix)
+        .map(PathBuf::from)
+        .unwrap_or(path))
+}
+
+/// `\\?\C:\dir` -> `C:\dir` and `\\?\UNC\server\share` -> `\\server\share`;
+/// `None` for other paths, including verbatim paths without a drive or share.
+fn strip_verbatim_prefix(path: &str) -> Option<String> {
+    let rest = path.strip_prefix(r"\\?\")?;
+    if let Some(unc) = rest.strip_prefix(r"UNC\") {
+        return Some(format!(r"\\{unc}"));
+    }
+    let bytes = rest.as_bytes();
+    let has_drive = bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':';
+    has_drive.then(|| rest.to_string())
+}
+
+/// The program to spawn for `program`. npm installs `npx`, `npm`, `pnpm`
+/// and `yarn` on Windows as `.cmd` shims, which `Command` doesn't find by
+/// bare name; `uv` tools are plain `.exe` files.
+pub fn program(program: &str) -> String {
+    program_for(program, cfg!(target_os = "windows"))
+}
+
+fn program_for(program: &str, windows: bool) -> String {
+    if !windows {
+        return program.to_string();
+    }
+    match program {
+        "npx" | "npm" | "pnpm" | "yarn" => format!("{program}.cmd"),
+        "uvx" | "uv" => format!("{program}.exe"),
+        _ => program.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<OsString> {
+        let vars: HashMap<String, OsString> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), OsString::from(v)))
+            .collect();
+        move |key| vars.get(key).cloned()
+    }
+
+    #[test]
+    fn test_home_dir_from() {
+        let unix = env(&[("HOME", "/home/ray"), ("USERPROFILE", r"C:\Users\ray")]);
+        assert_eq!(home_dir_from(&unix, false), Some("/home/ray".into()));
+        assert_eq!(home_dir_from(&unix, true), Some(r"C:\Users\ray".into()));
+
+        let git_bash = env(&[("HOME", r"C:\Users\ray"), ("USERPROFILE", "")]);
+        assert_eq!(home_dir_from(&git_bash, true), Some(r"C:\Users\ray".into()));
+
+        let drive = env(&[("HOMEDRIVE", "D:"), ("HOMEPATH", r"\Users\ray")]);
+        assert_eq!(home_dir_from(&drive, true), Some(r"D:\Users\ray".into()));
+        assert_eq!(home_dir_from(&drive, false), None);
+        assert_eq!(home_dir_from(env(&[]), true), None);
+    }
+
+    #[test]
+    fn test_strip_verbatim_prefix() {
+        assert_eq!(
+            strip_verbatim_prefix(r"\\?\C:\rayclaw\tmp").as_deref(),
+            Some(r"C:\rayclaw\tmp")
+        );
+        assert_eq!(
+            strip_verbatim_prefix(r"\\?\UNC\nas\share\ws").as_deref(),
+            Some(r"\\nas\share\ws")
+        );
+        assert_eq!(strip_verbatim_prefix(r"\\?\Volume{abc}\ws"), None);
+        assert_eq!(strip_verbatim_prefix(r"C:\rayclaw"), None);
+        assert_eq!(strip_verbatim_prefix("/home/ray"), None);
+    }
+
+    #[test]
+    fn test_program_for() {
+        assert_eq!(program_for("npx", false), "npx");
+        assert_eq!(program_for("npx", true), "npx.cmd");
+        assert_eq!(program_for("uvx", true), "uvx.exe");
+        assert_eq!(program_for("my-agent", true), "my-agent");
+        assert_eq!(program_for(r"C:\tools\npx.cmd", true), r"C:\tools\npx.cmd");
+    }
+}
@@ -0,0 +1,467 @@
+//! Opt-in crawl politeness for tools that fetch arbitrary pages, configured
+//! under `outbound_http.politeness`: robots.txt rules (RFC 9309, plus
+//! `Crawl-delay`), a minimum interval between requests to one host, and a
+//! page budget per agent run.
+//!
+//! Tools call [`before_request`] before each page fetch; the agent engine
+//! calls [`end_run`] when a run finishes.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::outbound_http::{self, OutboundClient};
+
+const DEFAULT_USER_AGENT: &str = "RayClaw/1.0";
+/// RFC 9309: a cached robots.txt should not be used for more than a day
+const ROBOTS_TTL: Duration = Duration::from_secs(24 * 3600);
+const ROBOTS_ERROR_TTL: Duration = Duration::from_secs(10 * 60);
+/// RFC 9309 requires parsing at least the first 500 KiB
+const MAX_ROBOTS_BYTES: usize = 500 * 1024;
+const MAX_CRAWL_DELAY: Duration = Duration::from_secs(30);
+/// Page counts left by runs that failed expire after this
+const RUN_PAGES_TTL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, PartialEq)]
+struct RobotsRule {
+    allow: bool,
+    pattern: String,
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct RobotsGroup {
+    /// Lowercase product tokens, or `*`
+    agents: Vec<String>,
+    rules: Vec<RobotsRule>,
+    crawl_delay: Option<Duration>,
+}
+
+/// What a site's robots.txt permits.
+#[derive(Debug, PartialEq)]
+enum Robots {
+    /// No robots.txt (4xx)
+    AllowAll,
+    /// Server or network error: RFC 9309 says to assume complete disallow
+    Unreachable,
+    Groups(Vec<RobotsGroup>),
+}
+
+impl Robots {
+    fn parse(body: &str) -> Robots {
+        let mut groups: Vec<RobotsGroup> = Vec::new();
+        // Consecutive user-agent lines share one group
+        let mut reading_agents = false;
+        for line in body.lines() {
+            let line = line.split('#').next().unwrap_or_default();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let key = key.trim().to_ascii_lowercase();
+            let value = value.trim();
+            match key.as_str() {
+                "user-agent" => {
+                    if !reading_agents {
+                        groups.push(RobotsGroup::default());
+                        reading_agents = true;
+                    }
+                    if let Some(group) = groups.last_mut() {
+                        group.agents.push(value.to_ascii_lowercase());
+                    }
+                }
+                "allow" | "disallow" => {
+                    reading_agents = false;
+                    if let Some(group) = groups.last_mut().filter(|_| !value.is_empty()) {
+                        group.rules.push(RobotsRule {
+                            allow: key == "allow",
+                            pattern: value.to_string(),
+                        });
+                    }
+                }
+                "crawl-delay" => {
+                    reading_agents = false;
+                    if let Some(group) = groups.last_mut() {
+                        group.crawl_delay = value
+                            .parse::<f64>()
+                            .ok()
+                            .filter(|secs| secs.is_finite() && *secs >= 0.0)
+                            .map(Duration::from_secs_f64);
+                    }
+                }
+                _ => {}
+            }
+        }
+        Robots::Groups(groups)
+    }
+
+    /// The groups for `token`, or the `*` groups when none names it.
+    fn groups_for(&self, token: &str) -> Vec<&RobotsGroup> {
+        let Robots::Groups(groups) = self else {
+            return Vec::new();
+        };
+        let named = |agent: &str| agent.split('/').next().unwrap_or_default() == token;
+        let matching: Vec<&RobotsGroup> = groups
+            .iter()
+            .filter(|g| g.agents.iter().any(|a| named(a)))
+            .collect();
+        if !matching.is_empty() {
+            return matching;
+        }
+        groups
+            .iter()
+            .filter(|g| g.agents.iter().any(|a| a == "*"))
+            .collect()
+    }
+
+    /// Whether `token` may fetch `path` (path plus query). The longest
+    /// matching rule decides; on a tie, allow wins.
+    fn is_allowed(&self, token: &str, path: &str) -> bool {
+        match self {
+            Robots::AllowAll => return true,
+            Robots::Unreachable => return false,
+            Robots::Groups(_) => {}
+        }
+        if path == "/robots.txt" {
+            return true;
+        }
+        let mut best: Option<(usize, bool)> = None;
+        for rule in self.groups_for(token).iter().flat_map(|g| &g.rules) {
+            if !pattern_matches(&rule.pattern, path) {
+                continue;
+            }
+            let len = rule.pattern.len();
+            best = match best {
+                Some((best_len, allow)) if best_len > len || (best_len == len && allow) => {
+                    Some((best_len, allow))
+                }
+                _ => Some((len, rule.allow)),
+            };
+        }
+        best.is_none_or(|(_, allow)| allow)
+    }
+
+    fn crawl_delay(&self, token: &str) -> Option<Duration> {
+        self.groups_for(token)
+            .iter()
+            .filter_map(|g| g.crawl_delay)
+            .max()
+    }
+}
+
+/// Match a robots.txt path pattern: `*` matches any run of characters and a
+/// trailing `$` anchors the end.
+fn pattern_matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(p) => (p, true),
+        None => (pattern, false),
+    };
+    let parts: Vec<&str> = pattern.split('*').collect();
+    let Some(mut rest) = path.strip_prefix(parts[0]) else {
+        return false;
+    };
+    if parts.len() == 1 {
+        return !anchored || rest.is_empty();
+    }
+    let last = parts.len() - 1;
+    for (i, part) in parts.iter().enumerate().skip(1) {
+        if i == last && anchored {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(pos) => rest = &rest[pos + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+/// The product token robots.txt groups are matched against:
+/// `Mozilla/5.0 (...)` -> `mozilla`, `RayClaw/1.0` -> `rayclaw`.
+fn product_token(user_agent: &str) -> String {
+    user_agent
+        .split(|c: char| c == '/' || c.is_whitespace())
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase()
+}
+
+fn robots_client() -> &'static OutboundClient {
+    static CLIENT: OnceLock<OutboundClient> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        outbound_http::client_builder("robots_txt", DEFAULT_USER_AGENT)
+            .timeout(Duration::from_secs(10))
+            .redirect(reqwest::redirect::Policy::limited(5))
+            .build()
+            .expect("failed to build HTTP client")
+            .into()
+    })
+}
+
+async fn fetch_robots(origin: &str) -> Robots {
+    let resp = match robots_client()
+        .get(format!("{origin}/robots.txt"))
+        .send()
+        .await
+    {
+        Ok(resp) => resp,
+        Err(_) => return Robots::Unreachable,
+    };
+    let status = resp.status();
+    if status.is_success() {
+        match resp.bytes().await {
+            Ok(bytes) => {
+                let body = &bytes[..bytes.len().min(MAX_ROBOTS_BYTES)];
+                Robots::parse(&String::from_utf8_lossy(body))
+            }
+            Err(_) => Robots::Unreachable,
+        }
+    } else if status.is_client_error() && status != reqwest::StatusCode::TOO_MANY_REQUESTS {
+        Robots::AllowAll
+    } else {
+        Robots::Unreachable
+    }
+}
+
+/// robots.txt by origin, with when each entry expires.
+type RobotsCache = HashMap<String, (Instant, Arc<Robots>)>;
+
+fn robots_cache() -> &'static Mutex<RobotsCache> {
+    static CACHE: OnceLock<Mutex<RobotsCache>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+async fn robots_for(origin: &str) -> Arc<Robots> {
+    {
+        let mut cache = robots_cache().lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        cache.retain(|_, (expires, _)| *expires > now);
+        if let Some((_, robots)) = cache.get(origin) {
+            return robots.clone();
+        }
+    }
+    let robots = Arc::new(fetch_robots(origin).await);
+    let ttl = if *robots == Robots::Unreachable {
+        ROBOTS_ERROR_TTL
+    } else {
+        ROBOTS_TTL
+    };
+    robots_cache()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(origin.to_string(), (Instant::now() + ttl, robots.clone()));
+    robots
+}
+
+/// Earliest time of the next request to each host.
+fn host_slots() -> &'static Mutex<HashMap<String, Instant>> {
+    static SLOTS: OnceLock<Mutex<HashMap<String, Instant>>> = OnceLock::new();
+    SLOTS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Book the next request slot for `host` and return how long to wait for
+/// it. Concurrent callers queue up one interval apart.
+fn reserve_slot(
+    slots: &mut HashMap<String, Instant>,
+    host: &str,
+    interval: Duration,
+    now: Instant,
+) -> Duration {
+    slots.retain(|_, next| *next > now);
+    let start = slots.get(host).copied().unwrap_or(now);
+    slots.insert(host.to_string(), start + interval);
+    start - now
+}
+
+struct RunPages {
+    count: u32,
+    touched: Instant,
+}
+
+fn run_pages() -> &'static Mutex<HashMap<i64, RunPages>> {
+    static PAGES: OnceLock<Mutex<HashMap<i64, RunPages>>> = OnceLock::new();
+    PAGES.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Count one page against the chat's current run.
+fn take_page(
+    runs: &mut HashMap<i64, RunPages>,
+    chat_id: i64,
+    max_pages: u32,
+    now: Instant,
+) -> Result<(), String> {
+    runs.retain(|_, run| now.duration_since(run.touched) < RUN_PAGES_TTL);
+    let run = runs.entry(chat_id).or_insert(RunPages {
+        count: 0,
+        touched: now,
+    });
+    run.touched = now;
+    if run.count >= max_pages {
+        return Err(format!(
+            "Page limit reached: this run already fetched {max_pages} page(s) \
+             (outbound_http.politeness.max_pages_per_run). Work with what you have."
+        ));
+    }
+    run.count += 1;
+    Ok(())
+}
+
+/// Forget the pages fetched for `chat_id` once its agent run finishes.
+pub fn end_run(chat_id: i64) {
+    run_pages()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(&chat_id);
+}
+
+/// Apply the politeness settings to a fetch of `url` by the client for
+/// `scope`: refuse it when robots.txt disallows it or the run is out of
+/// pages, and wait for the host's next free slot. `chat_id` is the chat
+/// whose agent run fetches the page, if any.
+pub async fn before_request(scope: &str, url: &str, chat_id: Option<i64>) -> Result<(), String> {
+    let settings = &outbound_http::settings().politeness;
+    let Ok(parsed) = reqwest::Url::parse(url) else {
+        // The fetch itself reports the bad URL
+        return Ok(());
+    };
+    let Some(host) = parsed.host_str().map(str::to_ascii_lowercase) else {
+        return Ok(());
+    };
+
+    let mut interval = Duration::from_millis(settings.min_host_interval_ms);
+    if settings.respect_robots_txt {
+        let origin = parsed.origin().ascii_serialization();
+        let robots = robots_for(&origin).await;
+        let token = product_token(outbound_http::user_agent(scope, DEFAULT_USER_AGENT));
+        let path = match parsed.query() {
+            Some(query) => format!("{}?{query}", parsed.path()),
+            None => parsed.path().to_string(),
+        };
+        if *robots == Robots::Unreachable {
+            return Err(format!(
+                "robots.txt of {host} could not be fetched, so the site is treated as off-limits for now"
+            ));
+        }
+        if !robots.is_allowed(&token, &path) {
+            return Err(format!("robots.txt of {host} disallows {path}"));
+        }
+        if let Some(delay) = robots.crawl_delay(&token) {
+            interval = interval.max(delay.min(MAX_CRAWL_DELAY));
+        }
+    }
+
+    if let (Some(chat_id), max_pages @ 1..) = (chat_id, settings.max_pages_per_run) {
+        let mut runs = run_pages().lock().unwrap_or_else(|e| e.into_inner());
+        take_page(&mut runs, chat_id, max_pages, Instant::now())?;
+    }
+
+    if !interval.is_zero() {
+        let wait = {
+            let mut slots = host_slots().lock().unwrap_or_else(|e| e.into_inner());
+            reserve_slot(&mut slots, &host, interval, Instant::now())
+        };
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROBOTS_TXT: &str = "\
+# Example
+User-agent: *
+Disallow: /private/
+Allow: /private/press
+Disallow: /*.pdf$
+Crawl-delay: 2
+
+User-agent: RayClaw
+User-agent: otherbot
+Disallow: /search
+Allow: /search/about
+
+Sitemap: https://example.com/sitemap.xml
+";
+
+    #[test]
+    fn test_robots_rules() {
+        let robots = Robots::parse(ROBOTS_TXT);
+        // Generic group
+        assert!(robots.is_allowed("mozilla", "/"));
+        assert!(!robots.is_allowed("mozilla", "/private/notes"));
+        assert!(robots.is_allowed("mozilla", "/private/press/2024"));
+        assert!(!robots.is_allowed("mozilla", "/files/report.pdf"));
+        assert!(robots.is_allowed("mozilla", "/files/report.pdf?page=2"));
+        assert_eq!(robots.crawl_delay("mozilla"), Some(Duration::from_secs(2)));
+
+        // A group naming the bot replaces the generic one
+        assert!(!robots.is_allowed("rayclaw", "/search?q=rust"));
+        assert!(robots.is_allowed("rayclaw", "/search/about"));
+        assert!(robots.is_allowed("rayclaw", "/private/notes"));
+        assert_eq!(robots.crawl_delay("rayclaw"), None);
+
+        assert!(Robots::AllowAll.is_allowed("rayclaw", "/anything"));
+        assert!(!Robots::Unreachable.is_allowed("rayclaw", "/"));
+        assert!(Robots::parse("User-agent: *\nDisallow: /\n").is_allowed("rayclaw", "/robots.txt"));
+        assert!(Robots::parse("User-agent: *\nDisallow:\n").is_allowed("rayclaw", "/"));
+    }
+
+    #[test]
+    fn test_pattern_matches() {
+        assert!(pattern_matches("/", "/anything"));
+        assert!(pattern_matches("/fish", "/fish.html"));
+        assert!(!pattern_matches("/fish", "/Fish"));
+        assert!(pattern_matches("/fish*.php", "/fish/salmon.php?x=1"));
+        assert!(pattern_matches("/*.php$", "/a/b.php"));
+        assert!(!pattern_matches("/*.php$", "/a/b.php?x=1"));
+        assert!(pattern_matches("/exact$", "/exact"));
+        assert!(!pattern_matches("/exact$", "/exactly"));
+    }
+
+    #[test]
+    fn test_product_token() {
+        assert_eq!(product_token("RayClaw/1.0"), "rayclaw");
+        assert_eq!(product_token("Mozilla/5.0 (X11; Linux x86_64)"), "mozilla");
+        assert_eq!(product_token("AcmeBot"), "acmebot");
+    }
+
+    #[test]
+    fn test_reserve_slot_spaces_requests() {
+        let mut slots = HashMap::new();
+        let now = Instant::now();
+        let interval = Duration::from_secs(1);
+        assert_eq!(
+            reserve_slot(&mut slots, "example.com", interval, now),
+            Duration::ZERO
+        );
+        assert_eq!(
+            reserve_slot(&mut slots, "example.com", interval, now),
+            Duration::from_secs(1)
+        );
+        assert_eq!(
+            reserve_slot(&mut slots, "example.org", interval, now),
+            Duration::ZERO
+        );
+        let later = now + Duration::from_secs(5);
+        assert_eq!(
+            reserve_slot(&mut slots, "example.com", interval, later),
+            Duration::ZERO
+        );
+    }
+
+    #[test]
+    fn test_take_page_budget() {
+        let mut runs = HashMap::new();
+        let now = Instant::now();
+        assert!(take_page(&mut runs, 1, 2, now).is_ok());
+        assert!(take_page(&mut runs, 1, 2, now).is_ok());
+        let err = take_page(&mut runs, 1, 2, now).unwrap_err();
+        assert!(err.contains("max_pages_per_run"));
+        // Other chats have their own budget
+        assert!(take_page(&mut runs, 2, 2, now).is_ok());
+        // Stale runs are forgotten
+        assert!(take_page(&mut runs, 1, 2, now + RUN_PAGES_TTL).is_ok());
+    }
+}
@@ -0,0 +1,490 @@
+//! Startup preflight for `rayclaw start`: checks the things a running bot
+//! depends on (writable database, free disk, LLM provider, channel tokens,
+//! clock skew for SigV4) and prints a status table, so a broken setup fails
+//! at startup instead of mid-conversation. Disabled by `startup_preflight`.
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+
+use crate::config::Config;
+use crate::doctor::{CheckStatus, DoctorCheck};
+
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+/// Below this the database and logs can fill the disk within hours.
+const MIN_FREE_BYTES: u64 = 100 * 1024 * 1024;
+const LOW_FREE_BYTES: u64 = 1024 * 1024 * 1024;
+/// AWS rejects SigV4 signatures more than 5 minutes off its clock; the
+/// Bedrock provider then re-signs with AWS time, so skew only warns.
+const MAX_SIGV4_SKEW_SECS: i64 = 5 * 60;
+const WARN_SKEW_SECS: i64 = 60;
+
+/// Run the checks, print the table, and fail if any check failed.
+pub async fn run(config: &Config) -> anyhow::Result<()> {
+    let checks = run_checks(config).await;
+    print_table(&checks);
+    let failed: Vec<&str> = checks
+        .iter()
+        .filter(|c| c.status == CheckStatus::Fail)
+        .map(|c| c.title.as_str())
+        .collect();
+    if failed.is_empty() {
+        return Ok(());
+    }
+    anyhow::bail!(
+        "preflight failed ({}); fix the issues above or set startup_preflight: false to skip these checks",
+        failed.join(", ")
+    )
+}
+
+async fn run_checks(config: &Config) -> Vec<DoctorCheck> {
+    let runtime_dir = config.runtime_data_dir();
+    let mut checks = vec![
+        check_database(Path::new(&runtime_dir)),
+        check_disk_space(Path::new(&runtime_dir)).await,
+    ];
+    let client = reqwest::Client::builder()
+        .timeout(HTTP_TIMEOUT)
+        .build()
+        .unwrap_or_default();
+    let (provider, channels) = tokio::join!(
+        check_provider(&client, config),
+        check_channels(&client, config)
+    );
+    checks.extend(provider);
+    checks.extend(channels);
+    checks
+}
+
+fn check(
+    id: &str,
+    title: &str,
+    status: CheckStatus,
+    detail: impl Into<String>,
+    fix: Option<&str>,
+) -> DoctorCheck {
+    DoctorCheck {
+        id: id.to_string(),
+        title: title.to_string(),
+        status,
+        detail: detail.into(),
+        fix: fix.map(str::to_string),
+    }
+}
+
+fn print_table(checks: &[DoctorCheck]) {
+    eprintln!("Preflight");
+    for c in checks {
+        eprintln!(
+            "  {} {:<4}  {:<16} {}",
+            c.status.as_emoji(),
+            c.status.as_label(),
+            c.title,
+            c.detail
+        );
+        if c.status != CheckStatus::Pass {
+            if let Some(fix) = &c.fix {
+                eprintln!("              fix: {fix}");
+            }
+        }
+    }
+    eprintln!();
+}
+
+// ---------------------------------------------------------------------------
+// Local checks
+// ---------------------------------------------------------------------------
+
+fn check_database(runtime_dir: &Path) -> DoctorCheck {
+    let fix = Some("Fix the directory's permissions or point data_dir at a writable location.");
+    let fail = |detail: String| check("db", "Database", CheckStatus::Fail, detail, fix);
+    if let Err(e) = std::fs::create_dir_all(runtime_dir) {
+        return fail(format!("cannot create {}: {e}", runtime_dir.display()));
+    }
+    let probe = runtime_dir.join(format!(".preflight-{}", uuid::Uuid::new_v4()));
+    if let Err(e) = std::fs::write(&probe, b"ok") {
+        return fail(format!("{} is not writable: {e}", runtime_dir.display()));
+    }
+    let _ = std::fs::remove_file(&probe);
+    let db_path = runtime_dir.join("rayclaw.db");
+    if db_path.exists() {
+        if let Err(e) = std::fs::OpenOptions::new().write(true).open(&db_path) {
+            return fail(format!("{} is not writable: {e}", db_path.display()));
+        }
+    }
+    check(
+        "db",
+        "Database",
+        CheckStatus::Pass,
+        format!("{} is writable", runtime_dir.display()),
+        None,
+    )
+}
+
+async fn check_disk_space(runtime_dir: &Path) -> DoctorCheck {
+    let Some(free) = free_bytes(runtime_dir).await else {
+        return check(
+            "disk",
+            "Disk space",
+            CheckStatus::Warn,
+            "could not determine free space",
+            None,
+        );
+    };
+    let status = disk_space_status(free);
+    check(
+        "disk",
+        "Disk space",
+        status,
+        format!("{} free", format_bytes(free)),
+        Some("Free up space on the data_dir volume or move data_dir to a larger disk."),
+    )
+}
+
+fn disk_space_status(free: u64) -> CheckStatus {
+    if free < MIN_FREE_BYTES {
+        CheckStatus::Fail
+    } else if free < LOW_FREE_BYTES {
+        CheckStatus::Warn
+    } else {
+        CheckStatus::Pass
+    }
+}
+
+/// Free space on the filesystem holding `dir`, from POSIX `df`. `None` on
+/// Windows and wherever `df` is unavailable.
+async fn free_bytes(dir: &Path) -> Option<u64> {
+    if cfg!(windows) {
+        return None;
+    }
+    let output = tokio::process::Command::new("df")
+        .arg("-Pk")
+        .arg(dir)
+        .output()
+        .await
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse_df_available(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// The "Available" column (1K blocks) of `df -Pk` output, in bytes.
+fn parse_df_available(output: &str) -> Option<u64> {
+    let line = output.lines().nth(1)?;
+    let kb: u64 = line.split_whitespace().nth(3)?.parse().ok()?;
+    Some(kb * 1024)
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const GB: f64 = 1024.0 * 1024.0 * 1024.0;
+    const MB: f64 = 1024.0 * 1024.0;
+    let b = bytes as f64;
+    if b >= GB {
+        format!("{:.1} GB", b / GB)
+    } else {
+        format!("{:.0} MB", b / MB)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Provider and clock
+// ---------------------------------------------------------------------------
+
+async fn check_provider(client: &reqwest::Client, config: &Config) -> Vec<DoctorCheck> {
+    let provider = match crate::llm::try_create_provider(config) {
+        Ok(provider) => provider,
+        Err(e) => {
+            return vec![check(
+                "provider",
+                "LLM provider",
+                CheckStatus::Fail,
+                format!("{}: {e}", config.llm_provider),
+                Some("Check the provider credentials in rayclaw.config.yaml (`rayclaw setup --provider`)."),
+            )];
+        }
+    };
+    let Some(endpoint) = provider.endpoint() else {
+        return Vec::new();
+    };
+
+    // Any HTTP response proves the endpoint is reachable; the request is
+    // unauthenticated, so 401/404 are expected.
+    let started = Instant::now();
+    let resp = match client.get(&endpoint).send().await {
+        Ok(resp) => resp,
+        Err(e) => {
+            return vec![check(
+                "provider",
+                "LLM provider",
+                CheckStatus::Fail,
+                format!("cannot reach {endpoint}: {e}"),
+                Some("Check network access, proxy settings (HTTPS_PROXY) and llm_base_url."),
+            )];
+        }
+    };
+    let mut checks = vec![check(
+        "provider",
+        "LLM provider",
+        CheckStatus::Pass,
+        format!(
+            "{} reachable ({} ms)",
+            config.llm_provider,
+            started.elapsed().as_millis()
+        ),
+        None,
+    )];
+
+    if config.llm_provider.trim().eq_ignore_ascii_case("bedrock") {
+        let server_time = resp
+            .headers()
+            .get(reqwest::header::DATE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| DateTime::parse_from_rfc2822(v).ok())
+            .map(|t| t.with_timezone(&Utc));
+        checks.push(match server_time {
+            Some(server_time) => clock_skew_check(Utc::now(), server_time),
+            None => check(
+                "clock",
+                "Clock skew",
+                CheckStatus::Warn,
+                "AWS sent no Date header to compare against",
+                None,
+            ),
+        });
+    }
+    checks
+}
+
+/// Compare the local clock with AWS's. SigV4 signatures are only valid
+/// within a few minutes of AWS time.
+fn clock_skew_check(local: DateTime<Utc>, server: DateTime<Utc>) -> DoctorCheck {
+    let skew = (local - server).num_seconds();
+    let status = if skew.abs() > WARN_SKEW_SECS {
+        CheckStatus::Warn
+    } else {
+        CheckStatus::Pass
+    };
+    let direction = if skew >= 0 { "ahead of" } else { "behind" };
+    let mut detail = format!("local clock is {}s {direction} AWS", skew.abs());
+    if skew.abs() > MAX_SIGV4_SKEW_SECS {
+        detail.push_str("; requests will be re-signed with AWS time");
+    }
+    check(
+        "clock",
+        "Clock skew",
+        status,
+        detail,
+        Some("Sync the system clock (e.g. enable NTP with `timedatectl set-ntp true`)."),
+    )
+}
+
+// ---------------------------------------------------------------------------
+// Channel tokens
+// ---------------------------------------------------------------------------
+
+#[allow(unused_mut, unused_variables)]
+async fn check_channels(client: &reqwest::Client, config: &Config) -> Vec<DoctorCheck> {
+    let mut checks = Vec::new();
+
+    #[cfg(feature = "telegram")]
+    if let Some(tg) =
+        config.channel_config::<crate::channels::telegram::TelegramChannelConfig>("telegram")
+    {
+        if !tg.bot_token.trim().is_empty() {
+            let url = format!("https://api.telegram.org/bot{}/getMe", tg.bot_token);
+            // The URL carries the token, so errors are reported without it
+            let result = client
+                .get(url)
+                .send()
+                .await
+                .map_err(|e| e.without_url().to_string());
+            checks.push(token_check("telegram", "Telegram", result).await);
+        }
+    }
+
+    #[cfg(feature = "discord")]
+    if let Some(dc) =
+        config.channel_config::<crate::channels::discord::DiscordChannelConfig>("discord")
+    {
+        if !dc.bot_token.trim().is_empty() {
+            let result = client
+                .get("https://discord.com/api/v10/users/@me")
+                .header(
+                    reqwest::header::AUTHORIZATION,
+                    format!("Bot {}", dc.bot_token),
+                )
+                .send()
+                .await
+                .map_err(|e| e.to_string());
+            checks.push(token_check("discord", "Discord", result).await);
+        }
+    }
+
+    #[cfg(feature = "slack")]
+    if let Some(slack) =
+        config.channel_config::<crate::channels::slack::SlackChannelConfig>("slack")
+    {
+        let tokens = std::iter::once(slack.bot_token.as_str())
+            .chain(slack.workspaces.iter().map(|w| w.bot_token.as_str()))
+            .filter(|t| !t.trim().is_empty());
+        for token in tokens {
+            let result = client
+                .post("https://slack.com/api/auth.test")
+                .bearer_auth(token)
+                .send()
+                .await
+                .map_err(|e| e.to_string());
+            checks.push(token_check("slack", "Slack", result).await);
+        }
+    }
+
+    checks
+}
+
+/// Classify a token verification call. A rejected token fails; an
+/// unreachable API only warns, since the channel retries on its own.
+#[cfg(any(feature = "telegram", feature = "discord", feature = "slack"))]
+async fn token_check(
+    id: &str,
+    title: &str,
+    result: Result<reqwest::Response, String>,
+) -> DoctorCheck {
+    let fix = Some("Replace the bot token in rayclaw.config.yaml (`rayclaw setup --channels`).");
+    let resp = match result {
+        Ok(resp) => resp,
+        Err(e) => {
+            return check(
+                &format!("channel.{id}"),
+                title,
+                CheckStatus::Warn,
+                format!("could not verify token: {e}"),
+                None,
+            );
+        }
+    };
+    let status = resp.status();
+    let body: serde_json::Value = resp.json().await.unwrap_or_default();
+    let (check_status, detail) = token_status(status.as_u16(), &body);
+    check(&format!("channel.{id}"), title, check_status, detail, fix)
+}
+
+/// Telegram and Slack answer with `{"ok": ...}` (Slack always with HTTP
+/// 200); Discord with plain HTTP statuses.
+#[cfg(any(feature = "telegram", feature = "discord", feature = "slack"))]
+fn token_status(http_status: u16, body: &serde_json::Value) -> (CheckStatus, String) {
+    if http_status == 401 || http_status == 403 || http_status == 404 {
+        return (
+            CheckStatus::Fail,
+            format!("token rejected (HTTP {http_status})"),
+        );
+    }
+    if body.get("ok").and_then(|v| v.as_bool()) == Some(false) {
+        let error = body
+            .get("error")
+            .or_else(|| body.get("description"))
+            .and_then(|v| v.as_str())
+            .unwrap_or("unknown error");
+        return (CheckStatus::Fail, format!("token rejected ({error})"));
+    }
+    if !(200..300).contains(&http_status) {
+        return (
+            CheckStatus::Warn,
+            format!("could not verify token (HTTP {http_status})"),
+        );
+    }
+    let name = body
+        .pointer("/result/username")
+        .or_else(|| body.get("username"))
+        .or_else(|| body.get("user"))
+        .and_then(|v| v.as_str());
+    match name {
+        Some(name) => (CheckStatus::Pass, format!("token valid ({name})")),
+        None => (CheckStatus::Pass, "token valid".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_database() {
+        let dir = std::env::temp_dir().join(format!("rayclaw_preflight_{}", uuid::Uuid::new_v4()));
+        let runtime = dir.join("runtime");
+        let c = check_database(&runtime);
+        assert_eq!(c.status, CheckStatus::Pass, "{}", c.detail);
+        assert!(runtime.is_dir());
+        assert_eq!(std::fs::read_dir(&runtime).unwrap().count(), 0);
+
+        // A file where the directory should be
+        let blocked = dir.join("blocked");
+        std::fs::write(&blocked, b"").unwrap();
+        assert_eq!(check_database(&blocked).status, CheckStatus::Fail);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_disk_space() {
+        assert_eq!(disk_space_status(50 * 1024 * 1024), CheckStatus::Fail);
+        assert_eq!(disk_space_status(500 * 1024 * 1024), CheckStatus::Warn);
+        assert_eq!(
+            disk_space_status(20 * 1024 * 1024 * 1024),
+            CheckStatus::Pass
+        );
+        let df = "Filesystem     1024-blocks      Used Available Capacity Mounted on\n\
+                  /dev/sda1        102400000  51200000  51200000      50% /\n";
+        assert_eq!(parse_df_available(df), Some(51_200_000 * 1024));
+        assert_eq!(parse_df_available("Filesystem\n"), None);
+        assert_eq!(format_bytes(51_200_000 * 1024), "48.8 GB");
+        assert_eq!(format_bytes(300 * 1024 * 1024), "300 MB");
+    }
+
+    #[test]
+    fn test_clock_skew_check() {
+        let aws = DateTime::parse_from_rfc2822("Fri, 16 Oct 2026 12:00:00 GMT")
+            .unwrap()
+            .with_timezone(&Utc);
+        let at = |secs| aws + chrono::Duration::seconds(secs);
+        assert_eq!(clock_skew_check(at(3), aws).status, CheckStatus::Pass);
+        assert_eq!(clock_skew_check(at(-120), aws).status, CheckStatus::Warn);
+        let c = clock_skew_check(at(400), aws);
+        assert_eq!(c.status, CheckStatus::Warn);
+        assert_eq!(
+            c.detail,
+            "local clock is 400s ahead of AWS; requests will be re-signed with AWS time"
+        );
+        assert_eq!(
+            clock_skew_check(at(-90), aws).detail,
+            "local clock is 90s behind AWS"
+        );
+    }
+
+    #[cfg(any(feature = "telegram", feature = "discord", feature = "slack"))]
+    #[test]
+    fn test_token_status() {
+        let telegram = serde_json::json!({"ok": true, "result": {"username": "ray_bot"}});
+        assert_eq!(
+            token_status(200, &telegram),
+            (CheckStatus::Pass, "token valid (ray_bot)".into())
+        );
+        let telegram_bad = serde_json::json!({"ok": false, "description": "Unauthorized"});
+        assert_eq!(token_status(401, &telegram_bad).0, CheckStatus::Fail);
+
+        let slack_bad = serde_json::json!({"ok": false, "error": "invalid_auth"});
+        assert_eq!(
+            token_status(200, &slack_bad),
+            (CheckStatus::Fail, "token rejected (invalid_auth)".into())
+        );
+        let slack = serde_json::json!({"ok": true, "user": "rayclaw"});
+        assert_eq!(token_status(200, &slack).1, "token valid (rayclaw)");
+
+        let discord = serde_json::json!({"id": "1", "username": "RayClaw"});
+        assert_eq!(token_status(200, &discord).1, "token valid (RayClaw)");
+        assert_eq!(
+            token_status(503, &serde_json::Value::Null).0,
+            CheckStatus::Warn
+        );
+    }
+}
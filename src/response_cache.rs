@@ -0,0 +1,159 @@
+//! Short-lived cache for answers from external services, so repeated
+//! lookups (the same `web_search` from several chats) don't hit the
+//! service — and its rate limits — again.
+//!
+//! Entries live for `response_cache_ttl_secs` (0 turns the cache off). With
+//! `cache_backend: memory` each instance keeps its own entries; with
+//! `cache_backend: redis` they are shared through `coordination_redis_url`.
+//! A Redis failure is a cache miss.
+
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use crate::config::Config;
+use crate::coordination::SharedRedis;
+
+/// Entries kept in memory before the oldest are dropped
+const MAX_MEMORY_ENTRIES: usize = 512;
+
+static MEMORY: LazyLock<Mutex<HashMap<String, (Instant, String)>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Clone)]
+pub struct ResponseCache {
+    ttl: Duration,
+    redis: Option<(SharedRedis, String)>,
+}
+
+impl ResponseCache {
+    /// A cache that never stores anything.
+    pub fn disabled() -> Self {
+        ResponseCache {
+            ttl: Duration::ZERO,
+            redis: None,
+        }
+    }
+
+    pub fn from_config(config: &Config) -> Self {
+        let ttl = Duration::from_secs(config.response_cache_ttl_secs);
+        let redis = match (
+            config.cache_backend.as_str(),
+            config.coordination_redis_url.as_deref(),
+        ) {
+            ("redis", Some(url)) => match SharedRedis::for_url(url) {
+                Ok(redis) => Some((redis, config.coordination_key_prefix.clone())),
+                Err(e) => {
+                    warn!("Response cache kept in memory, invalid Redis URL: {e}");
+                    None
+                }
+            },
+            _ => None,
+        };
+        ResponseCache { ttl, redis }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.ttl.is_zero()
+    }
+
+    pub async fn get(&self, key: &str) -> Option<String> {
+        if !self.is_enabled() {
+            return None;
+        }
+        if let Some((redis, prefix)) = &self.redis {
+            let mut cmd = redis::cmd("GET");
+            cmd.arg(redis_key(prefix, key));
+            return match redis.query::<Option<String>>(&cmd).await {
+                Ok(value) => value,
+                Err(e) => {
+                    warn!("Response cache read failed: {e}");
+                    None
+                }
+            };
+        }
+        let mut memory = MEMORY.lock().unwrap();
+        match memory.get(key) {
+            Some((stored, value)) if stored.elapsed() < self.ttl => Some(value.clone()),
+            Some(_) => {
+                memory.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    pub async fn put(&self, key: &str, value: &str) {
+        if !self.is_enabled() {
+            return;
+        }
+        if let Some((redis, prefix)) = &self.redis {
+            let mut cmd = redis::cmd("SET");
+            cmd.arg(redis_key(prefix, key))
+                .arg(value)
+                .arg("PX")
+                .arg(self.ttl.as_millis() as u64);
+            if let Err(e) = redis.query::<()>(&cmd).await {
+                warn!("Response cache write failed: {e}");
+            }
+            return;
+        }
+        let mut memory = MEMORY.lock().unwrap();
+        let ttl = self.ttl;
+        memory.retain(|_, (stored, _)| stored.elapsed() < ttl);
+        if memory.len() >= MAX_MEMORY_ENTRIES {
+            if let Some(oldest) = memory
+                .iter()
+                .min_by_key(|(_, (stored, _))| *stored)
+                .map(|(k, _)| k.clone())
+            {
+                memory.remove(&oldest);
+            }
+        }
+        memory.insert(key.to_string(), (Instant::now(), value.to_string()));
+    }
+}
+
+/// Keys can be long free text, so Redis gets a hash of them.
+fn redis_key(prefix: &str, key: &str) -> String {
+    format!("{prefix}:response:{}", hex::encode(Sha256::digest(key)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_memory_cache_round_trip() {
+        let cache = ResponseCache {
+            ttl: Duration::from_secs(60),
+            redis: None,
+        };
+        assert_eq!(cache.get("test:round-trip").await, None);
+        cache.put("test:round-trip", "value").await;
+        assert_eq!(cache.get("test:round-trip").await.as_deref(), Some("value"));
+
+        let disabled = ResponseCache::disabled();
+        disabled.put("test:disabled", "value").await;
+        assert_eq!(disabled.get("test:disabled").await, None);
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_redis_is_a_miss() {
+        let yaml = "telegram_bot_token: tok\nbot_username: bot\napi_key: key\ncoordination_redis_url: redis://127.0.0.1:1/0\ncache_backend: redis\nresponse_cache_ttl_secs: 60\n";
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.post_deserialize().unwrap();
+        let cache = ResponseCache::from_config(&config);
+        assert!(cache.is_enabled());
+        assert!(cache.redis.is_some());
+        cache.put("test:redis", "value").await;
+        assert_eq!(cache.get("test:redis").await, None);
+        assert_eq!(
+            redis_key("rayclaw", "q"),
+            format!("rayclaw:response:{}", hex::encode(Sha256::digest("q")))
+        );
+    }
+}
@@ -9,6 +9,7 @@ use tracing::warn;
 
 /// Wait for any termination signal: SIGTERM, SIGHUP, or Ctrl-C.
 /// Returns a human-readable label of which signal was received.
+#[cfg(unix)]
 async fn shutdown_signal() -> &'static str {
     use tokio::signal::unix::{signal, SignalKind};
 
@@ -22,6 +23,24 @@ async fn shutdown_signal() -> &'static str {
     }
 }
 
+/// Wait for Ctrl-C, Ctrl-Break, the console window closing, or system
+/// shutdown. Returns a human-readable label of which event was received.
+#[cfg(windows)]
+async fn shutdown_signal() -> &'static str {
+    use tokio::signal::windows::{ctrl_break, ctrl_close, ctrl_shutdown};
+
+    let mut ctrl_break = ctrl_break().expect("failed to register Ctrl-Break handler");
+    let mut ctrl_close = ctrl_close().expect("failed to register console close handler");
+    let mut ctrl_shutdown = ctrl_shutdown().expect("failed to register shutdown handler");
+
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => "Ctrl-C",
+        _ = ctrl_break.recv() => "Ctrl-Break",
+        _ = ctrl_close.recv() => "console close",
+        _ = ctrl_shutdown.recv() => "system shutdown",
+    }
+}
+
 use crate::channel_adapter::ChannelRegistry;
 #[cfg(feature = "telegram")]
 use crate::channels::telegram::TelegramChannelConfig;
@@ -29,10 +48,16 @@ use crate::channels::telegram::TelegramChannelConfig;
 use crate::channels::DiscordAdapter;
 #[cfg(feature = "feishu")]
 use crate::channels::FeishuAdapter;
+#[cfg(feature = "signal")]
+use crate::channels::SignalAdapter;
 #[cfg(feature = "slack")]
 use crate::channels::SlackAdapter;
+#[cfg(feature = "teams")]
+use crate::channels::TeamsAdapter;
 #[cfg(feature = "telegram")]
 use crate::channels::TelegramAdapter;
+#[cfg(feature = "webhook")]
+use crate::channels::WebhookAdapter;
 #[cfg(feature = "weixin")]
 use crate::channels::WeixinAdapter;
 use crate::config::Config;
@@ -49,6 +74,12 @@ use crate::web::WebAdapter;
 /// When a second request arrives for a chat_id that is already processing,
 /// it waits for the first to finish before starting.
 pub type ChatLocks = Mutex<HashMap<i64, Arc<Mutex<()>>>>;
+/// Latest debounce generation per chat and forum topic; see
+/// `message_debounce_ms`.
+pub type ChatDebounce = Mutex<HashMap<(i64, Option<i64>), u64>>;
+/// Providers built for per-chat model overrides, keyed by (provider, model,
+/// chat ID for chats with their own `chat_inference` parameters).
+pub type ChatLlms = Mutex<HashMap<(String, String, Option<i64>), Arc<dyn LlmProvider>>>;
 
 pub struct AppState {
     pub config: Config,
@@ -60,8 +91,21 @@ pub struct AppState {
     pub embedding: Option<Arc<dyn EmbeddingProvider>>,
     pub tools: ToolRegistry,
     pub acp_manager: Arc<crate::acp::AcpManager>,
+    /// High-risk tool calls waiting for an Approve/Deny button press.
+    pub tool_approvals: crate::approvals::ToolApprovals,
     /// Per-chat concurrency lock: ensures only one agent loop runs per chat_id at a time.
     pub chat_locks: ChatLocks,
+    /// Bumped on every debounced user message so older runs can step aside.
+    pub chat_debounce: ChatDebounce,
+    /// Providers for chats whose `/model` override or sampling parameters
+    /// differ from the config.
+    pub chat_llms: ChatLlms,
+    /// Caps agent runs across chats, admitting waiters round-robin per chat.
+    pub inbound_queue: crate::inbound_queue::InboundQueue,
+    /// User messages held while the LLM provider is down.
+    pub outage: crate::outage::OutageQueue,
+    /// Claims scheduled runs and webhook messages across instances.
+    pub coordinator: crate::coordination::Coordinator,
 }
 
 /// Build an `AppState` without starting any channels, schedulers, or signal handlers.
@@ -79,6 +123,10 @@ pub async fn create_app_state(
 ) -> anyhow::Result<Arc<AppState>> {
     let llm = crate::llm::create_provider(&config);
     let embedding = crate::embedding::create_provider(&config);
+    let coordinator = crate::coordination::Coordinator::from_config(&config)?;
+    if coordinator.is_distributed() {
+        info!("Coordinating scheduled tasks and webhook messages through Redis");
+    }
     #[cfg(feature = "sqlite-vec")]
     {
         let dim = embedding
@@ -102,17 +150,23 @@ pub async fn create_app_state(
     }
 
     let acp_manager = Arc::new(acp_manager);
+    acp_manager.set_run_log(db.clone());
+    acp_manager.set_model_prices(config.model_prices.clone());
 
     // Build completion callback for async ACP jobs — delivers results to the
-    // originating chat via the channel adapter.
+    // originating chat via the channel adapter. ACP output is redacted like
+    // tool output since it goes straight to the chat.
+    let redactor = tools.redactor().clone();
     let job_callback: Option<crate::acp::JobCompletionCallback> = if !use_sdk_tools {
         let cb_registry = channel_registry.clone();
         let cb_db = db.clone();
         let cb_bot = config.bot_username.clone();
+        let cb_redactor = redactor.clone();
         Some(std::sync::Arc::new(move |chat_id: i64, text: String| {
             let reg = cb_registry.clone();
             let db = cb_db.clone();
             let bot = cb_bot.clone();
+            let text = cb_redactor.redact(&text).into_owned();
             Box::pin(async move {
                 if let Err(e) =
                     crate::channel::deliver_and_store_bot_message(&reg, db, &bot, chat_id, &text)
@@ -125,16 +179,21 @@ pub async fn create_app_state(
     } else {
         None
     };
+    if let Some(ref cb) = job_callback {
+        acp_manager.set_chat_notifier(cb.clone());
+    }
 
     // Build notification callback for ACP tools (send status messages to chats).
     let notify_fn: Option<crate::tools::acp::NotifyFn> = if !use_sdk_tools {
         let n_registry = channel_registry.clone();
         let n_db = db.clone();
         let n_bot = config.bot_username.clone();
+        let n_redactor = redactor.clone();
         Some(std::sync::Arc::new(move |chat_id: i64, text: String| {
             let reg = n_registry.clone();
             let db = n_db.clone();
             let bot = n_bot.clone();
+            let text = n_redactor.redact(&text).into_owned();
             Box::pin(async move {
                 if let Err(e) =
                     crate::channel::deliver_and_store_bot_message(&reg, db, &bot, chat_id, &text)
@@ -157,6 +216,18 @@ pub async fn create_app_state(
         tools.add_tool(tool);
     }
 
+    let tool_approvals = crate::approvals::ToolApprovals::new(std::time::Duration::from_secs(
+        config.tool_approval_timeout_secs,
+    ));
+    let inbound_queue = crate::inbound_queue::InboundQueue::with_shared_slots(
+        config.max_concurrent_agent_runs,
+        crate::coordination::SharedRunSlots::from_config(&config),
+    );
+    let outage = crate::outage::OutageQueue::new(
+        config.outage_queue_size,
+        std::time::Duration::from_secs(config.outage_queue_ttl_mins * 60),
+    );
+
     Ok(Arc::new(AppState {
         config,
         channel_registry,
@@ -167,7 +238,13 @@ pub async fn create_app_state(
         embedding,
         tools,
         acp_manager,
+        tool_approvals,
         chat_locks: Mutex::new(HashMap::new()),
+        chat_debounce: Mutex::new(HashMap::new()),
+        chat_llms: Mutex::new(HashMap::new()),
+        inbound_queue,
+        outage,
+        coordinator,
     }))
 }
 
@@ -180,6 +257,7 @@ pub async fn run(
     acp_manager: crate::acp::AcpManager,
 ) -> anyhow::Result<()> {
     let db = Arc::new(db);
+    crate::outbound_http::configure(&config.outbound_http);
 
     // Clear stale TODO.json files from previous runs to prevent
     // in_progress tasks from being blindly resumed after a restart.
@@ -233,9 +311,9 @@ pub async fn run(
     if let Some(slack_cfg) =
         config.channel_config::<crate::channels::slack::SlackChannelConfig>("slack")
     {
-        if !slack_cfg.bot_token.trim().is_empty() && !slack_cfg.app_token.trim().is_empty() {
+        if slack_cfg.is_configured() {
             has_slack = true;
-            registry.register(Arc::new(SlackAdapter::new(slack_cfg.bot_token)));
+            registry.register(Arc::new(SlackAdapter::new(slack_cfg)));
         }
     }
 
@@ -267,6 +345,40 @@ pub async fn run(
         }
     }
 
+    #[cfg(feature = "signal")]
+    let mut signal_adapter: Option<Arc<SignalAdapter>> = None;
+    #[cfg(feature = "signal")]
+    if let Some(signal_cfg) =
+        config.channel_config::<crate::channels::signal::SignalChannelConfig>("signal")
+    {
+        if signal_cfg.is_configured() {
+            let adapter = Arc::new(SignalAdapter::new(signal_cfg));
+            registry.register(adapter.clone());
+            signal_adapter = Some(adapter);
+        }
+    }
+
+    // Activities arrive on the web server (see web::api_teams_messages)
+    #[cfg(feature = "teams")]
+    if let Some(teams_cfg) =
+        config.channel_config::<crate::channels::teams::TeamsChannelConfig>("teams")
+    {
+        if teams_cfg.is_configured() && config.web_enabled {
+            info!("Teams: receiving activities at /api/teams/messages");
+            registry.register(Arc::new(TeamsAdapter::new(teams_cfg)));
+        }
+    }
+
+    #[cfg(feature = "webhook")]
+    if let Some(webhook_cfg) =
+        config.channel_config::<crate::channels::webhook::WebhookChannelConfig>("webhook")
+    {
+        if webhook_cfg.is_configured() && config.web_enabled {
+            info!("Webhook channel: receiving messages at /api/webhook/messages");
+            registry.register(Arc::new(WebhookAdapter::new(webhook_cfg)));
+        }
+    }
+
     #[cfg(feature = "web")]
     if config.web_enabled {
         registry.register(Arc::new(WebAdapter));
@@ -287,8 +399,19 @@ pub async fn run(
     .await?;
 
     crate::scheduler::spawn_scheduler(state.clone());
+    crate::scheduler::spawn_url_monitor(state.clone());
+    crate::scheduler::spawn_notification_flusher(state.clone());
     crate::scheduler::spawn_reflector(state.clone());
+    crate::scheduler::spawn_workspace_cleaner(state.clone());
+    crate::outage::spawn_recovery_worker(state.clone());
     crate::acp::spawn_idle_reaper(state.acp_manager.clone());
+    crate::acp::spawn_health_monitor(state.acp_manager.clone());
+    {
+        let acp_manager = state.acp_manager.clone();
+        tokio::spawn(async move {
+            acp_manager.restore_sessions().await;
+        });
+    }
 
     #[cfg(feature = "discord")]
     if let Some(ref token) = discord_token {
@@ -327,6 +450,17 @@ pub async fn run(
         });
     }
 
+    #[cfg(feature = "signal")]
+    let has_signal = signal_adapter.is_some();
+    #[cfg(feature = "signal")]
+    if let Some(adapter) = signal_adapter {
+        let signal_state = state.clone();
+        info!("Starting Signal bot (signal-cli JSON-RPC)");
+        tokio::spawn(async move {
+            crate::channels::signal::start_signal_bot(signal_state, adapter).await;
+        });
+    }
+
     #[cfg(feature = "web")]
     if state.config.web_enabled {
         let web_state = state.clone();
@@ -363,6 +497,10 @@ pub async fn run(
         {
             active = active || has_weixin;
         }
+        #[cfg(feature = "signal")]
+        {
+            active = active || has_signal;
+        }
         active
     };
 
@@ -6,15 +6,18 @@ use tracing::{error, info};
 
 use crate::agent_engine::process_with_agent;
 use crate::agent_engine::AgentRequestContext;
-use crate::channel::{
-    deliver_and_store_bot_message, get_chat_routing, ChatRouting, ConversationKind,
-};
+use crate::channel::{get_chat_routing, ChatRouting, ConversationKind};
 use crate::db::call_blocking;
 use crate::llm_types::{Message, MessageContent, ResponseContentBlock};
+use crate::notifications::{notify_chat_in_thread, NotificationKind};
 use crate::runtime::AppState;
 use crate::text::floor_char_boundary;
 use crate::{db::Memory, memory_quality};
 
+/// How long a run stays claimed. A run that outlives it may be started again
+/// by another instance; one that crashes is retried after it.
+const TASK_CLAIM_TTL: std::time::Duration = std::time::Duration::from_secs(3600);
+
 pub fn spawn_scheduler(state: Arc<AppState>) {
     tokio::spawn(async move {
         info!("Scheduler started");
@@ -36,6 +39,15 @@ async fn run_due_tasks(state: &Arc<AppState>) {
     };
 
     for task in tasks {
+        // Another instance sharing this database may be due to run it too
+        let claim_key = format!("task:{}:{}", task.id, task.next_run);
+        if !state.coordinator.claim(&claim_key, TASK_CLAIM_TTL).await {
+            info!(
+                "Scheduler: task #{} already claimed by another instance",
+                task.id
+            );
+            continue;
+        }
         info!(
             "Scheduler: executing task #{} for chat {}",
             task.id, task.chat_id
@@ -53,26 +65,30 @@ async fn run_due_tasks(state: &Arc<AppState>) {
             });
 
         // Run agent loop with the task prompt
+        let prompt = crate::digest_dedup::prompt_for_task(state, &task).await;
         let (success, result_summary) = match process_with_agent(
             state,
             AgentRequestContext {
                 caller_channel: &routing.channel_name,
                 chat_id: task.chat_id,
+                thread_id: task.thread_id,
                 chat_type: routing.conversation.as_agent_chat_type(),
+                denied_tools: &[],
             },
-            Some(&task.prompt),
+            Some(&prompt),
             None,
         )
         .await
         {
             Ok(response) => {
+                crate::digest_dedup::record_result(state, &task, &response).await;
                 if !response.is_empty() {
-                    let _ = deliver_and_store_bot_message(
-                        &state.channel_registry,
-                        state.db.clone(),
-                        &state.config.bot_username,
+                    let _ = notify_chat_in_thread(
+                        state,
                         task.chat_id,
+                        task.thread_id,
                         &response,
+                        NotificationKind::TaskResult,
                     )
                     .await;
                 }
@@ -86,12 +102,12 @@ async fn run_due_tasks(state: &Arc<AppState>) {
             Err(e) => {
                 error!("Scheduler: task #{} failed: {e}", task.id);
                 let err_text = format!("Scheduled task #{} failed: {e}", task.id);
-                let _ = deliver_and_store_bot_message(
-                    &state.channel_registry,
-                    state.db.clone(),
-                    &state.config.bot_username,
+                let _ = notify_chat_in_thread(
+                    state,
                     task.chat_id,
+                    task.thread_id,
                     &err_text,
+                    NotificationKind::TaskFailure,
                 )
                 .await;
                 (false, Some(format!("Error: {e}")))
@@ -275,6 +291,26 @@ async fn backfill_embeddings(state: &Arc<AppState>) {
     }
 }
 
+pub fn spawn_url_monitor(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        info!("URL monitor started");
+        loop {
+            tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+            crate::url_monitor::run_due_monitors(&state).await;
+        }
+    });
+}
+
+pub fn spawn_notification_flusher(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        info!("Notification flusher started");
+        loop {
+            tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+            crate::notifications::flush_pending(&state).await;
+        }
+    });
+}
+
 pub fn spawn_reflector(state: Arc<AppState>) {
     if !state.config.reflector_enabled {
         info!("Reflector disabled by config");
@@ -293,6 +329,41 @@ pub fn spawn_reflector(state: Arc<AppState>) {
     });
 }
 
+pub fn spawn_workspace_cleaner(state: Arc<AppState>) {
+    let quota_mb = state.config.workspace_quota_mb;
+    let ttl_hours = state.config.workspace_tmp_ttl_hours;
+    if quota_mb == 0 && ttl_hours == 0 {
+        return;
+    }
+    let interval_secs = state.config.workspace_cleanup_interval_mins * 60;
+    tokio::spawn(async move {
+        info!(
+            "Workspace cleaner started (interval: {}min, quota: {}MB, tmp ttl: {}h)",
+            state.config.workspace_cleanup_interval_mins, quota_mb, ttl_hours
+        );
+        let working_dir = std::path::PathBuf::from(&state.config.working_dir);
+        let tmp_ttl = (ttl_hours > 0).then(|| std::time::Duration::from_secs(ttl_hours * 3600));
+        let max_bytes = quota_mb * 1024 * 1024;
+        loop {
+            let dir = working_dir.clone();
+            let result = tokio::task::spawn_blocking(move || {
+                crate::workspace::clean_all_workspaces(&dir, tmp_ttl, max_bytes)
+            })
+            .await;
+            match result {
+                Ok(report) if report.files_removed > 0 => info!(
+                    "Workspace cleaner: removed {} file(s), freed {}",
+                    report.files_removed,
+                    crate::workspace::format_bytes(report.bytes_freed)
+                ),
+                Ok(_) => {}
+                Err(e) => error!("Workspace cleaner: task failed: {e}"),
+            }
+            tokio::time::sleep(std::time::Duration::from_secs(interval_secs)).await;
+        }
+    });
+}
+
 async fn run_reflector(state: &Arc<AppState>) {
     #[cfg(feature = "sqlite-vec")]
     backfill_embeddings(state).await;
@@ -53,6 +53,7 @@ impl RayClawAgent {
     /// handlers are started.
     pub async fn new(mut config: Config) -> Result<Self, RayClawError> {
         config.validate_for_sdk()?;
+        crate::outbound_http::configure(&config.outbound_http);
 
         let data_root_dir = config.data_root_dir();
         let runtime_data_dir = config.runtime_data_dir();
@@ -126,7 +127,9 @@ impl RayClawAgent {
         let context = AgentRequestContext {
             caller_channel: "sdk",
             chat_id,
+            thread_id: None,
             chat_type: "private",
+            denied_tools: &[],
         };
         self.store_user_message(chat_id, user_text);
         agent_engine::process_with_agent(&self.state, context, Some(user_text), None)
@@ -144,7 +147,9 @@ impl RayClawAgent {
         let context = AgentRequestContext {
             caller_channel: "sdk",
             chat_id,
+            thread_id: None,
             chat_type: "private",
+            denied_tools: &[],
         };
         self.store_user_message(chat_id, user_text);
         agent_engine::process_with_agent_with_events(
@@ -187,6 +192,7 @@ impl RayClawAgent {
             content: text.to_string(),
             is_from_bot: false,
             timestamp: chrono::Utc::now().to_rfc3339(),
+            thread_id: None,
         };
         let _ = self.state.db.store_message(&msg);
     }
@@ -102,6 +102,80 @@ const DYNAMIC_CHANNELS: &[DynamicChannelDef] = &[
             },
         ],
     },
+    DynamicChannelDef {
+        name: "signal",
+        presence_keys: &["account"],
+        fields: &[
+            ChannelFieldDef {
+                yaml_key: "account",
+                label: "Signal number registered with signal-cli (+15551234567)",
+                default: "",
+                secret: false,
+                required: true,
+            },
+            ChannelFieldDef {
+                yaml_key: "tcp_address",
+                label: "signal-cli JSON-RPC address (daemon --tcp)",
+                default: "127.0.0.1:7583",
+                secret: false,
+                required: false,
+            },
+        ],
+    },
+    DynamicChannelDef {
+        name: "teams",
+        presence_keys: &["app_id", "app_password"],
+        fields: &[
+            ChannelFieldDef {
+                yaml_key: "app_id",
+                label: "Microsoft App ID of the Azure Bot",
+                default: "",
+                secret: false,
+                required: true,
+            },
+            ChannelFieldDef {
+                yaml_key: "app_password",
+                label: "Microsoft App password (client secret)",
+                default: "",
+                secret: true,
+                required: true,
+            },
+            ChannelFieldDef {
+                yaml_key: "tenant_id",
+                label: "Tenant ID (single-tenant bots only, leave empty otherwise)",
+                default: "",
+                secret: false,
+                required: false,
+            },
+        ],
+    },
+    DynamicChannelDef {
+        name: "webhook",
+        presence_keys: &["token", "callback_url"],
+        fields: &[
+            ChannelFieldDef {
+                yaml_key: "token",
+                label: "Webhook token (callers send it as a Bearer token)",
+                default: "",
+                secret: true,
+                required: true,
+            },
+            ChannelFieldDef {
+                yaml_key: "callback_url",
+                label: "Callback URL that receives the bot's replies",
+                default: "",
+                secret: false,
+                required: true,
+            },
+            ChannelFieldDef {
+                yaml_key: "secret",
+                label: "HMAC secret for signing callbacks",
+                default: "",
+                secret: true,
+                required: true,
+            },
+        ],
+    },
 ];
 
 fn dynamic_field_key(channel: &str, yaml_key: &str) -> String {
@@ -117,6 +191,8 @@ enum ProviderProtocol {
     Anthropic,
     OpenAiCompat,
     Bedrock,
+    Gemini,
+    Ollama,
 }
 
 #[derive(Clone, Copy)]
@@ -164,8 +240,8 @@ const PROVIDER_PRESETS: &[ProviderPreset] = &[
     ProviderPreset {
         id: "ollama",
         label: "Ollama (local)",
-        protocol: ProviderProtocol::OpenAiCompat,
-        default_base_url: "http://127.0.0.1:11434/v1",
+        protocol: ProviderProtocol::Ollama,
+        default_base_url: crate::llm_ollama::DEFAULT_OLLAMA_BASE_URL,
         models: &["llama3.2", "qwen2.5-coder:7b", "mistral"],
     },
     ProviderPreset {
@@ -175,6 +251,13 @@ const PROVIDER_PRESETS: &[ProviderPreset] = &[
         default_base_url: "https://generativelanguage.googleapis.com/v1beta/openai",
         models: &["gemini-2.5-pro", "gemini-2.5-flash"],
     },
+    ProviderPreset {
+        id: "gemini",
+        label: "Google Gemini (native API)",
+        protocol: ProviderProtocol::Gemini,
+        default_base_url: "",
+        models: &["gemini-2.5-flash", "gemini-2.5-pro"],
+    },
     ProviderPreset {
         id: "alibaba",
         label: "Alibaba Cloud (Qwen / DashScope)",
@@ -288,6 +371,12 @@ fn find_provider_preset(provider: &str) -> Option<&'static ProviderPreset> {
         .find(|p| p.id.eq_ignore_ascii_case(provider))
 }
 
+/// Default base URL of a provider preset (empty when the provider's client
+/// has its own), or `None` for a provider with no preset.
+pub(crate) fn provider_default_base_url(provider: &str) -> Option<&'static str> {
+    find_provider_preset(provider).map(|p| p.default_base_url)
+}
+
 fn provider_protocol(provider: &str) -> ProviderProtocol {
     find_provider_preset(provider)
         .map(|p| p.protocol)
@@ -591,11 +680,34 @@ fn step_provider(
 
     // Model selection
     let mut model_options: Vec<String> = preset.models.iter().map(|m| (*m).to_string()).collect();
+    if preset.protocol == ProviderProtocol::Ollama {
+        let base = existing
+            .get("LLM_BASE_URL")
+            .map(String::as_str)
+            .unwrap_or(preset.default_base_url);
+        match detect_ollama_models(base) {
+            Ok(models) if !models.is_empty() => {
+                println!(
+                    "  {} Found {} local model(s) on {base}",
+                    style("✓").green(),
+                    models.len()
+                );
+                model_options = models;
+            }
+            Ok(_) => println!(
+                "  {} Ollama has no local models yet. Run `ollama pull <model>` first.",
+                style("!").yellow().bold()
+            ),
+            Err(e) => println!(
+                "  {} Could not reach Ollama on {base} ({e}); showing suggested models.",
+                style("!").yellow().bold()
+            ),
+        }
+    }
     model_options.push("Custom...".to_string());
 
     let existing_model = existing.get("LLM_MODEL").cloned().unwrap_or_default();
-    let model_default_idx = preset
-        .models
+    let model_default_idx = model_options
         .iter()
         .position(|m| *m == existing_model)
         .unwrap_or(0);
@@ -815,6 +927,9 @@ fn step_channels(
                 "slack" => "Slack",
                 "feishu" => "Feishu / Lark",
                 "weixin" => "WeChat",
+                "signal" => "Signal (signal-cli)",
+                "teams" => "Microsoft Teams",
+                "webhook" => "Webhook (generic HTTP)",
                 _ => o,
             };
             name.to_string()
@@ -953,6 +1068,24 @@ fn step_channels(
     }
 }
 
+/// Models installed on the Ollama server at `base_url` (`/api/tags`).
+fn detect_ollama_models(base_url: &str) -> Result<Vec<String>, String> {
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(3))
+        .build()
+        .map_err(|e| e.to_string())?;
+    let base = crate::llm_ollama::resolve_ollama_base_url(Some(base_url));
+    let resp = client
+        .get(format!("{base}/api/tags"))
+        .send()
+        .map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("HTTP {}", resp.status()));
+    }
+    let body: serde_json::Value = resp.json().map_err(|e| e.to_string())?;
+    Ok(crate::llm_ollama::parse_model_names(&body))
+}
+
 fn test_telegram_token(token: &str) -> Result<String, String> {
     let client = reqwest::blocking::Client::builder()
         .timeout(Duration::from_secs(10))
@@ -1394,6 +1527,63 @@ fn perform_online_validation(
         return Ok(checks);
     }
 
+    if protocol == ProviderProtocol::Gemini {
+        let base = if base_url.is_empty() {
+            "https://generativelanguage.googleapis.com/v1beta"
+        } else {
+            base_url.trim_end_matches('/')
+        };
+        let body = serde_json::json!({
+            "contents": [{"role": "user", "parts": [{"text": "hi"}]}],
+            "generationConfig": {"maxOutputTokens": 1}
+        });
+        let resp = client
+            .post(format!("{base}/models/{model}:generateContent"))
+            .header("x-goog-api-key", api_key)
+            .header("content-type", "application/json")
+            .body(body.to_string())
+            .send()?;
+        let status = resp.status();
+        if !status.is_success() {
+            let text = resp.text().unwrap_or_default();
+            let detail = serde_json::from_str::<serde_json::Value>(&text)
+                .ok()
+                .and_then(|v| {
+                    v.get("error")
+                        .and_then(|e| e.get("message"))
+                        .and_then(|m| m.as_str())
+                        .map(|s| s.to_string())
+                })
+                .unwrap_or_else(|| format!("HTTP {status}"));
+            return Err(RayClawError::Config(format!(
+                "LLM validation failed: {detail}"
+            )));
+        }
+        checks.push(format!("LLM OK (gemini, model={model})"));
+        return Ok(checks);
+    }
+
+    if protocol == ProviderProtocol::Ollama {
+        let base = crate::llm_ollama::resolve_ollama_base_url(Some(base_url));
+        let installed = detect_ollama_models(&base).map_err(|e| {
+            RayClawError::Config(format!(
+                "LLM validation failed: Ollama not reachable at {base} ({e})"
+            ))
+        })?;
+        if !crate::llm_ollama::model_is_installed(&installed, &model) {
+            let available = if installed.is_empty() {
+                "none".to_string()
+            } else {
+                installed.join(", ")
+            };
+            return Err(RayClawError::Config(format!(
+                "LLM validation failed: model '{model}' is not installed in Ollama (available: {available}). Run `ollama pull {model}`."
+            )));
+        }
+        checks.push(format!("LLM OK (ollama, model={model})"));
+        return Ok(checks);
+    }
+
     if protocol == ProviderProtocol::Anthropic {
         let mut base = if base_url.is_empty() {
             "https://api.anthropic.com".to_string()
@@ -2157,6 +2347,7 @@ mod tests {
         assert!(find_provider_preset("anthropic").is_some());
         assert!(find_provider_preset("ANTHROPIC").is_some());
         assert!(find_provider_preset("nonexistent").is_none());
+        assert!(provider_protocol("ollama") == ProviderProtocol::Ollama);
     }
 
     #[test]
@@ -2166,6 +2357,7 @@ mod tests {
             "claude-sonnet-4-5-20250929"
         );
         assert_eq!(default_model_for_provider("ollama"), "llama3.2");
+        assert_eq!(default_model_for_provider("gemini"), "gemini-2.5-flash");
         assert_eq!(default_model_for_provider("unknown"), "gpt-5.2");
     }
 
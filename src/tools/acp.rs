@@ -8,6 +8,8 @@ use async_trait::async_trait;
 use serde_json::json;
 
 use super::{auth_context_from_input, schema_object, Tool, ToolResult};
+use crate::db::{call_blocking, AcpRun};
+use crate::text::floor_char_boundary;
 
 /// Callback type for sending a notification message to a chat.
 pub type NotifyFn =
@@ -33,9 +35,14 @@ pub fn make_acp_tools_with_callback(
         Box::new(AcpNewSessionTool::new(manager.clone(), notify)),
         Box::new(AcpPromptTool::new(manager.clone())),
         Box::new(AcpEndSessionTool::new(manager.clone())),
+        Box::new(AcpCancelTool::new(manager.clone())),
+        Box::new(AcpAnswerTool::new(manager.clone())),
+        Box::new(AcpSetModeTool::new(manager.clone())),
         Box::new(AcpListSessionsTool::new(manager.clone())),
+        Box::new(AcpStatusTool::new(manager.clone())),
         Box::new(AcpSubmitJobTool::new(manager.clone(), on_job_complete)),
-        Box::new(AcpJobStatusTool::new(manager)),
+        Box::new(AcpJobStatusTool::new(manager.clone())),
+        Box::new(AcpHistoryTool::new(manager)),
     ];
     tools
 }
@@ -106,6 +113,10 @@ impl Tool for AcpCodingTool {
                     "timeout_secs": {
                         "type": "integer",
                         "description": "Max seconds to wait (sync mode only). Default: 300"
+                    },
+                    "include_thoughts": {
+                        "type": "boolean",
+                        "description": "Include the agent's thinking in a separate thoughts field (sync mode only). Default: false"
                     }
                 }),
                 &["message"],
@@ -129,6 +140,10 @@ impl Tool for AcpCodingTool {
             .and_then(|v| v.as_bool())
             .unwrap_or(false);
         let timeout_secs = input.get("timeout_secs").and_then(|v| v.as_u64());
+        let include_thoughts = input
+            .get("include_thoughts")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
 
         let chat_id = auth_context_from_input(&input).map(|ctx| ctx.caller_chat_id);
 
@@ -253,6 +268,15 @@ impl Tool for AcpCodingTool {
                         "files_changed": result.files_changed,
                         "duration_ms": result.duration_ms,
                     });
+                    if !result.plan.is_empty() {
+                        output["plan"] = json!(result.plan);
+                    }
+                    if include_thoughts {
+                        output["thoughts"] = json!(result.thoughts);
+                    }
+                    if let Some(usage) = &result.usage {
+                        output["usage"] = json!(usage);
+                    }
                     if result.context_reset {
                         output["context_reset"] = json!(true);
                         output["context_reset_notice"] = json!(
@@ -356,15 +380,17 @@ impl Tool for AcpNewSessionTool {
                     .await;
                 }
 
-                ToolResult::success(
-                    json!({
-                        "session_id": info.session_id,
-                        "agent": info.agent_id,
-                        "workspace": info.workspace,
-                        "status": "active"
-                    })
-                    .to_string(),
-                )
+                let mut output = json!({
+                    "session_id": info.session_id,
+                    "agent": info.agent_id,
+                    "workspace": info.workspace,
+                    "status": "active"
+                });
+                if let Some(modes) = &info.modes {
+                    output["current_mode"] = json!(modes.current_mode_id);
+                    output["available_modes"] = json!(modes.available);
+                }
+                ToolResult::success(output.to_string())
             }
             Err(e) => ToolResult::error(format!("Failed to create ACP session: {e}"))
                 .with_error_type("acp_error"),
@@ -412,6 +438,10 @@ impl Tool for AcpPromptTool {
                     "timeout_secs": {
                         "type": "integer",
                         "description": "Max seconds to wait for completion. Defaults to config value (300s)."
+                    },
+                    "include_thoughts": {
+                        "type": "boolean",
+                        "description": "Include the agent's thinking in a separate thoughts field. Default: false"
                     }
                 }),
                 &["session_id", "message"],
@@ -431,6 +461,10 @@ impl Tool for AcpPromptTool {
         };
 
         let timeout_secs = input.get("timeout_secs").and_then(|v| v.as_u64());
+        let include_thoughts = input
+            .get("include_thoughts")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
 
         match self
             .manager
@@ -456,6 +490,15 @@ impl Tool for AcpPromptTool {
                     "files_changed": result.files_changed,
                     "duration_ms": result.duration_ms,
                 });
+                if !result.plan.is_empty() {
+                    output["plan"] = json!(result.plan);
+                }
+                if include_thoughts {
+                    output["thoughts"] = json!(result.thoughts);
+                }
+                if let Some(usage) = &result.usage {
+                    output["usage"] = json!(usage);
+                }
                 if result.context_reset {
                     output["context_reset"] = json!(true);
                     output["context_reset_notice"] =
@@ -529,6 +572,200 @@ impl Tool for AcpEndSessionTool {
     }
 }
 
+// ---------------------------------------------------------------------------
+// acp_cancel
+// ---------------------------------------------------------------------------
+
+struct AcpCancelTool {
+    manager: Arc<AcpManager>,
+}
+
+impl AcpCancelTool {
+    fn new(manager: Arc<AcpManager>) -> Self {
+        Self { manager }
+    }
+}
+
+#[async_trait]
+impl Tool for AcpCancelTool {
+    fn name(&self) -> &str {
+        "acp_cancel"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "acp_cancel".into(),
+            description: "Cancel the prompt an ACP agent session is currently working on \
+                (e.g. a job submitted with acp_submit_job that is taking too long). \
+                The session stays open and can be prompted again."
+                .into(),
+            input_schema: schema_object(
+                json!({
+                    "session_id": {
+                        "type": "string",
+                        "description": "Session ID returned by acp_new_session"
+                    }
+                }),
+                &["session_id"],
+            ),
+        }
+    }
+
+    async fn execute(&self, input: serde_json::Value) -> ToolResult {
+        let session_id = match input.get("session_id").and_then(|v| v.as_str()) {
+            Some(s) => s,
+            None => return ToolResult::error("Missing required parameter: session_id".into()),
+        };
+
+        match self.manager.cancel_prompt(session_id).await {
+            Ok(()) => ToolResult::success(
+                json!({
+                    "status": "cancel_requested",
+                    "session_id": session_id,
+                })
+                .to_string(),
+            ),
+            Err(e) => ToolResult::error(format!("Failed to cancel ACP prompt: {e}"))
+                .with_error_type("acp_error"),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// acp_answer
+// ---------------------------------------------------------------------------
+
+struct AcpAnswerTool {
+    manager: Arc<AcpManager>,
+}
+
+impl AcpAnswerTool {
+    fn new(manager: Arc<AcpManager>) -> Self {
+        Self { manager }
+    }
+}
+
+#[async_trait]
+impl Tool for AcpAnswerTool {
+    fn name(&self) -> &str {
+        "acp_answer"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "acp_answer".into(),
+            description: "Answer a question an ACP agent asked mid-prompt (e.g. \"which \
+                database should I use?\"). The agent's prompt stays blocked until it gets an \
+                answer or the prompt times out."
+                .into(),
+            input_schema: schema_object(
+                json!({
+                    "session_id": {
+                        "type": "string",
+                        "description": "Session ID returned by acp_new_session"
+                    },
+                    "answer": {
+                        "type": "string",
+                        "description": "The answer to send to the agent"
+                    }
+                }),
+                &["session_id", "answer"],
+            ),
+        }
+    }
+
+    async fn execute(&self, input: serde_json::Value) -> ToolResult {
+        let session_id = match input.get("session_id").and_then(|v| v.as_str()) {
+            Some(s) => s,
+            None => return ToolResult::error("Missing required parameter: session_id".into()),
+        };
+        let answer = match input.get("answer").and_then(|v| v.as_str()) {
+            Some(a) => a,
+            None => return ToolResult::error("Missing required parameter: answer".into()),
+        };
+
+        match self.manager.answer_question(session_id, answer).await {
+            Ok(()) => ToolResult::success(
+                json!({
+                    "status": "answered",
+                    "session_id": session_id,
+                })
+                .to_string(),
+            ),
+            Err(e) => ToolResult::error(format!("Failed to answer ACP agent: {e}"))
+                .with_error_type("acp_error"),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// acp_set_mode
+// ---------------------------------------------------------------------------
+
+struct AcpSetModeTool {
+    manager: Arc<AcpManager>,
+}
+
+impl AcpSetModeTool {
+    fn new(manager: Arc<AcpManager>) -> Self {
+        Self { manager }
+    }
+}
+
+#[async_trait]
+impl Tool for AcpSetModeTool {
+    fn name(&self) -> &str {
+        "acp_set_mode"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "acp_set_mode".into(),
+            description: "Switch an ACP agent session to another operating mode (e.g. Claude \
+                Code's plan, acceptEdits or bypassPermissions). The modes an agent offers are \
+                listed in acp_new_session's available_modes."
+                .into(),
+            input_schema: schema_object(
+                json!({
+                    "session_id": {
+                        "type": "string",
+                        "description": "Session ID returned by acp_new_session"
+                    },
+                    "mode": {
+                        "type": "string",
+                        "description": "Mode ID or name to switch to"
+                    }
+                }),
+                &["session_id", "mode"],
+            ),
+        }
+    }
+
+    async fn execute(&self, input: serde_json::Value) -> ToolResult {
+        let session_id = match input.get("session_id").and_then(|v| v.as_str()) {
+            Some(s) => s,
+            None => return ToolResult::error("Missing required parameter: session_id".into()),
+        };
+        let mode = match input.get("mode").and_then(|v| v.as_str()) {
+            Some(m) => m,
+            None => return ToolResult::error("Missing required parameter: mode".into()),
+        };
+
+        match self.manager.set_mode(session_id, mode).await {
+            Ok(mode) => ToolResult::success(
+                json!({
+                    "session_id": session_id,
+                    "mode": mode.id,
+                    "name": mode.name,
+                })
+                .to_string(),
+            ),
+            Err(e) => ToolResult::error(format!("Failed to set ACP mode: {e}"))
+                .with_error_type("acp_error"),
+        }
+    }
+}
+
 // ---------------------------------------------------------------------------
 // acp_list_sessions
 // ---------------------------------------------------------------------------
@@ -572,6 +809,7 @@ impl Tool for AcpListSessionsTool {
                     "status": format!("{:?}", s.status),
                     "created_at": s.created_at,
                     "idle_secs": s.idle_secs,
+                    "usage": s.usage,
                 })
             })
             .collect();
@@ -588,6 +826,77 @@ impl Tool for AcpListSessionsTool {
     }
 }
 
+// ---------------------------------------------------------------------------
+// acp_status
+// ---------------------------------------------------------------------------
+
+struct AcpStatusTool {
+    manager: Arc<AcpManager>,
+}
+
+impl AcpStatusTool {
+    fn new(manager: Arc<AcpManager>) -> Self {
+        Self { manager }
+    }
+}
+
+#[async_trait]
+impl Tool for AcpStatusTool {
+    fn name(&self) -> &str {
+        "acp_status"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "acp_status".into(),
+            description: "Detailed status of one ACP session: whether it is working, the last \
+                prompt it was given, how long the current prompt has been running, prompts \
+                queued behind it, and the tool calls, changed files and token usage of its \
+                finished prompts. Use it to check on long-running delegated work without \
+                waiting for it."
+                .into(),
+            input_schema: schema_object(
+                json!({
+                    "session_id": {
+                        "type": "string",
+                        "description": "Session ID from acp_list_sessions or acp_new_session"
+                    }
+                }),
+                &["session_id"],
+            ),
+        }
+    }
+
+    async fn execute(&self, input: serde_json::Value) -> ToolResult {
+        let session_id = match input.get("session_id").and_then(|v| v.as_str()) {
+            Some(s) => s,
+            None => return ToolResult::error("Missing required parameter: session_id".into()),
+        };
+
+        match self.manager.session_status(session_id).await {
+            Ok(s) => ToolResult::success(
+                json!({
+                    "session_id": s.session_id,
+                    "agent": s.agent_id,
+                    "workspace": s.workspace,
+                    "status": format!("{:?}", s.status),
+                    "created_at": s.created_at,
+                    "idle_secs": s.idle_secs,
+                    "last_prompt": s.last_prompt,
+                    "prompt_elapsed_secs": s.prompt_elapsed_secs,
+                    "queued_prompts": s.queued_prompts,
+                    "prompts_completed": s.prompts_completed,
+                    "tool_calls": s.tool_calls,
+                    "files_changed": s.files_changed,
+                    "usage": s.usage,
+                })
+                .to_string(),
+            ),
+            Err(e) => ToolResult::error(e).with_error_type("acp_error"),
+        }
+    }
+}
+
 // ---------------------------------------------------------------------------
 // acp_submit_job
 // ---------------------------------------------------------------------------
@@ -750,6 +1059,155 @@ impl Tool for AcpJobStatusTool {
     }
 }
 
+// ---------------------------------------------------------------------------
+// acp_history
+// ---------------------------------------------------------------------------
+
+const HISTORY_PREVIEW_CHARS: usize = 300;
+
+fn preview(text: &str) -> String {
+    if text.len() <= HISTORY_PREVIEW_CHARS {
+        text.to_string()
+    } else {
+        format!(
+            "{}...",
+            &text[..floor_char_boundary(text, HISTORY_PREVIEW_CHARS)]
+        )
+    }
+}
+
+fn run_summary(run: &AcpRun) -> serde_json::Value {
+    json!({
+        "run_id": run.id,
+        "session_id": run.session_id,
+        "chat_id": run.chat_id,
+        "agent": run.agent_id,
+        "created_at": run.created_at,
+        "completed": run.completed,
+        "duration_ms": run.duration_ms,
+        "prompt": preview(&run.prompt),
+        "tool_call_count": run.tool_calls.len(),
+        "files_changed": run.files_changed,
+        "last_message": run.messages.last().map(|m| preview(m)),
+    })
+}
+
+struct AcpHistoryTool {
+    manager: Arc<AcpManager>,
+}
+
+impl AcpHistoryTool {
+    fn new(manager: Arc<AcpManager>) -> Self {
+        Self { manager }
+    }
+}
+
+#[async_trait]
+impl Tool for AcpHistoryTool {
+    fn name(&self) -> &str {
+        "acp_history"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "acp_history".into(),
+            description: "Browse past ACP agent runs (prompt, messages, tool calls, files \
+                changed, duration). Without run_id, lists recent runs newest first; with \
+                run_id, returns that run in full. Only runs from this chat are visible \
+                unless called from a control chat."
+                .into(),
+            input_schema: schema_object(
+                json!({
+                    "run_id": {
+                        "type": "integer",
+                        "description": "Return the full record of this run"
+                    },
+                    "session_id": {
+                        "type": "string",
+                        "description": "Only list runs from this ACP session"
+                    },
+                    "limit": {
+                        "type": "integer",
+                        "description": "Max runs to list (default: 10, max: 50)"
+                    },
+                    "offset": {
+                        "type": "integer",
+                        "description": "Runs to skip, for paging (default: 0)"
+                    }
+                }),
+                &[],
+            ),
+        }
+    }
+
+    async fn execute(&self, input: serde_json::Value) -> ToolResult {
+        let Some(db) = self.manager.run_log() else {
+            return ToolResult::error("ACP run history is not available".into())
+                .with_error_type("acp_error");
+        };
+        // Non-control chats only see runs attributed to themselves
+        let chat_scope = auth_context_from_input(&input)
+            .filter(|auth| !auth.is_control_chat())
+            .map(|auth| auth.caller_chat_id);
+
+        if let Some(run_id) = input.get("run_id").and_then(|v| v.as_i64()) {
+            let run = match call_blocking(db, move |db| db.get_acp_run(run_id)).await {
+                Ok(run) => run,
+                Err(e) => return ToolResult::error(format!("Failed to load ACP run: {e}")),
+            };
+            return match run {
+                Some(run) if chat_scope.is_none() || run.chat_id == chat_scope => {
+                    ToolResult::success(
+                        json!({
+                            "run_id": run.id,
+                            "session_id": run.session_id,
+                            "chat_id": run.chat_id,
+                            "agent": run.agent_id,
+                            "created_at": run.created_at,
+                            "completed": run.completed,
+                            "context_reset": run.context_reset,
+                            "duration_ms": run.duration_ms,
+                            "prompt": run.prompt,
+                            "messages": run.messages,
+                            "tool_calls": run.tool_calls,
+                            "files_changed": run.files_changed,
+                        })
+                        .to_string(),
+                    )
+                }
+                _ => ToolResult::error(format!("ACP run {run_id} not found"))
+                    .with_error_type("acp_error"),
+            };
+        }
+
+        let session_id = input
+            .get("session_id")
+            .and_then(|v| v.as_str())
+            .map(str::to_string);
+        let limit = input
+            .get("limit")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(10)
+            .clamp(1, 50) as usize;
+        let offset = input.get("offset").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+
+        match call_blocking(db, move |db| {
+            db.get_acp_runs(chat_scope, session_id.as_deref(), limit, offset)
+        })
+        .await
+        {
+            Ok(runs) => ToolResult::success(
+                json!({
+                    "count": runs.len(),
+                    "runs": runs.iter().map(run_summary).collect::<Vec<_>>(),
+                })
+                .to_string(),
+            ),
+            Err(e) => ToolResult::error(format!("Failed to list ACP runs: {e}")),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -763,12 +1221,12 @@ mod tests {
         let manager = test_manager();
         let tools = make_acp_tools(manager);
         let names: Vec<&str> = tools.iter().map(|t| t.name()).collect();
-        assert_eq!(names.len(), 7);
+        assert_eq!(names.len(), 12);
 
         let mut sorted = names.clone();
         sorted.sort();
         sorted.dedup();
-        assert_eq!(sorted.len(), 7, "Tool names must be unique");
+        assert_eq!(sorted.len(), 12, "Tool names must be unique");
     }
 
     #[test]
@@ -798,9 +1256,14 @@ mod tests {
             "acp_new_session",
             "acp_prompt",
             "acp_end_session",
+            "acp_cancel",
+            "acp_answer",
+            "acp_set_mode",
             "acp_list_sessions",
+            "acp_status",
             "acp_submit_job",
             "acp_job_status",
+            "acp_history",
         ];
         let names: Vec<&str> = tools.iter().map(|t| t.name()).collect();
         assert_eq!(names, expected);
@@ -860,6 +1323,65 @@ mod tests {
         assert!(result.content.contains("not found"));
     }
 
+    #[tokio::test]
+    async fn test_cancel_session_not_found() {
+        let manager = test_manager();
+        let tool = AcpCancelTool::new(manager);
+        let result = tool.execute(json!({"session_id": "nonexistent"})).await;
+        assert!(result.is_error);
+        assert!(result.content.contains("not found"));
+    }
+
+    #[tokio::test]
+    async fn test_history_scoped_to_caller_chat() {
+        let dir =
+            std::env::temp_dir().join(format!("rayclaw_acp_history_{}", uuid::Uuid::new_v4()));
+        let db = Arc::new(crate::db::Database::new(dir.to_str().unwrap()).unwrap());
+        let run = |chat_id: i64, prompt: &str| AcpRun {
+            id: 0,
+            session_id: "s1".to_string(),
+            chat_id: Some(chat_id),
+            agent_id: "claude".to_string(),
+            prompt: prompt.to_string(),
+            messages: vec!["ok".to_string()],
+            tool_calls: vec![],
+            files_changed: vec![],
+            completed: true,
+            context_reset: false,
+            duration_ms: 10,
+            created_at: chrono::Utc::now().to_rfc3339(),
+        };
+        db.log_acp_run(&run(1, "mine")).unwrap();
+        let other = db.log_acp_run(&run(2, "theirs")).unwrap();
+
+        let manager = test_manager();
+        let tool = AcpHistoryTool::new(manager.clone());
+        let result = tool.execute(json!({})).await;
+        assert!(result.is_error);
+        assert!(result.content.contains("not available"));
+
+        manager.set_run_log(db);
+        let auth = |chat_id: i64| json!({"caller_channel": "telegram", "caller_chat_id": chat_id, "control_chat_ids": [99]});
+        let result = tool.execute(json!({"__rayclaw_auth": auth(1)})).await;
+        let parsed: serde_json::Value = serde_json::from_str(&result.content).unwrap();
+        assert_eq!(parsed["count"], 1);
+        assert_eq!(parsed["runs"][0]["prompt"], "mine");
+
+        let result = tool
+            .execute(json!({"run_id": other, "__rayclaw_auth": auth(1)}))
+            .await;
+        assert!(result.is_error);
+
+        let result = tool
+            .execute(json!({"run_id": other, "__rayclaw_auth": auth(99)}))
+            .await;
+        assert!(!result.is_error);
+        let parsed: serde_json::Value = serde_json::from_str(&result.content).unwrap();
+        assert_eq!(parsed["messages"][0], "ok");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
     #[tokio::test]
     async fn test_list_sessions_empty() {
         let manager = test_manager();
@@ -872,6 +1394,16 @@ mod tests {
         assert!(parsed["available_agents"].as_array().unwrap().is_empty());
     }
 
+    #[tokio::test]
+    async fn test_status_unknown_session() {
+        let manager = test_manager();
+        let tool = AcpStatusTool::new(manager);
+        let result = tool.execute(json!({"session_id": "nope"})).await;
+        assert!(result.is_error);
+        assert!(result.content.contains("not found"));
+        assert!(tool.execute(json!({})).await.is_error);
+    }
+
     // -----------------------------------------------------------------------
     // Phase 7.2: Additional schema validation tests
     // -----------------------------------------------------------------------
@@ -0,0 +1,461 @@
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use polars::prelude::*;
+use polars::sql::SQLContext;
+use regex::Regex;
+use serde_json::json;
+use tracing::info;
+
+use crate::config::WorkingDirIsolation;
+use crate::llm_types::ToolDefinition;
+use crate::text::floor_char_boundary;
+
+use super::{schema_object, Tool, ToolResult};
+
+/// Name of the table a loaded file is queried as.
+const TABLE_NAME: &str = "data";
+const DEFAULT_MAX_ROWS: usize = 50;
+const MAX_ROWS_LIMIT: usize = 500;
+/// Largest file the tool will load.
+const MAX_FILE_BYTES: u64 = 50 * 1024 * 1024;
+/// Result rows counted before the total is reported as "more than".
+const MAX_COUNTED_ROWS: usize = 10_000;
+/// Rows read to infer CSV column types.
+const INFER_SCHEMA_ROWS: usize = 10_000;
+/// Longest a query may run before the tool gives up on it.
+const QUERY_TIMEOUT: Duration = Duration::from_secs(60);
+/// Longest cell shown in the Markdown output.
+const MAX_CELL_CHARS: usize = 200;
+
+/// Scans a CSV/TSV or Parquet file with polars and runs a SQL query over it.
+pub struct AnalyzeTableTool {
+    working_dir: PathBuf,
+    working_dir_isolation: WorkingDirIsolation,
+}
+
+impl AnalyzeTableTool {
+    pub fn new(working_dir: &str) -> Self {
+        Self::new_with_isolation(working_dir, WorkingDirIsolation::Shared)
+    }
+
+    pub fn new_with_isolation(
+        working_dir: &str,
+        working_dir_isolation: WorkingDirIsolation,
+    ) -> Self {
+        Self {
+            working_dir: PathBuf::from(working_dir),
+            working_dir_isolation,
+        }
+    }
+}
+
+/// Lazily scan a file by extension (`.parquet`, `.tsv`/`.tab`, or CSV
+/// otherwise).
+fn scan_file(path: &Path) -> Result<LazyFrame, String> {
+    let size = std::fs::metadata(path)
+        .map_err(|e| format!("Failed to read file: {e}"))?
+        .len();
+    if size > MAX_FILE_BYTES {
+        return Err(format!(
+            "File is too large ({size} bytes, max {MAX_FILE_BYTES})"
+        ));
+    }
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_ascii_lowercase();
+    let csv = |separator: u8| {
+        LazyCsvReader::new(path)
+            .with_separator(separator)
+            .with_has_header(true)
+            .with_missing_is_null(true)
+            .with_infer_schema_length(Some(INFER_SCHEMA_ROWS))
+            .finish()
+    };
+    match ext.as_str() {
+        "parquet" => LazyFrame::scan_parquet(path, ScanArgsParquet::default()),
+        "tsv" | "tab" => csv(b'\t'),
+        _ => csv(b','),
+    }
+    .map_err(|e| format!("Failed to load file: {e}"))
+}
+
+/// Reject queries that could reach other files or blow up in size: polars
+/// SQL's `read_csv(...)`-style table functions scan any path they are
+/// given, and joins (only self-joins here) can grow quadratically.
+fn check_query(query: &str) -> Result<(), String> {
+    static TABLE_FN: OnceLock<Regex> = OnceLock::new();
+    static JOIN: OnceLock<Regex> = OnceLock::new();
+    let table_fn =
+        TABLE_FN.get_or_init(|| Regex::new(r"(?i)\bread_(csv|parquet|ipc|json)\b").unwrap());
+    if table_fn.is_match(query) {
+        return Err(format!(
+            "Table functions are not allowed; query table `{TABLE_NAME}`"
+        ));
+    }
+    let join = JOIN.get_or_init(|| Regex::new(r"(?i)\bjoin\b").unwrap());
+    if join.is_match(query) {
+        return Err("Joins are not supported".into());
+    }
+    Ok(())
+}
+
+/// Run `query` over `data` in a fresh SQL context. Only SELECT-style
+/// queries are accepted: anything that leaves the context with other
+/// tables than `data` (CREATE TABLE, DROP, ...) is refused.
+fn plan_query(data: LazyFrame, query: &str) -> Result<LazyFrame, String> {
+    check_query(query)?;
+    let mut ctx = SQLContext::new();
+    ctx.register(TABLE_NAME, data);
+    let plan = ctx
+        .execute(query)
+        .map_err(|e| format!("Query error: {e}"))?;
+    if ctx.get_tables() != [TABLE_NAME] {
+        return Err("Only SELECT (or WITH ... SELECT) queries are allowed".into());
+    }
+    Ok(plan)
+}
+
+fn format_cell(value: &AnyValue) -> String {
+    let text = match value {
+        AnyValue::Null => String::new(),
+        AnyValue::String(s) => s.to_string(),
+        AnyValue::StringOwned(s) => s.to_string(),
+        AnyValue::Float32(f) => f.to_string(),
+        AnyValue::Float64(f) => f.to_string(),
+        AnyValue::Binary(b) => format!("<{} bytes>", b.len()),
+        AnyValue::BinaryOwned(b) => format!("<{} bytes>", b.len()),
+        other => other.to_string(),
+    };
+    let mut text = text.replace('|', "\\|").replace(['\r', '\n'], " ");
+    if text.chars().count() > MAX_CELL_CHARS {
+        text.truncate(floor_char_boundary(&text, MAX_CELL_CHARS));
+        text.push('…');
+    }
+    text
+}
+
+/// Run `plan` and render up to `max_rows` rows as a Markdown table. At most
+/// `MAX_COUNTED_ROWS` result rows are computed, to count the rest.
+fn run_query(plan: LazyFrame, max_rows: usize) -> Result<String, String> {
+    let df = plan
+        .limit((MAX_COUNTED_ROWS + 1) as IdxSize)
+        .collect()
+        .map_err(|e| format!("Query error: {e}"))?;
+    let columns = df.get_columns();
+    let mut out = format!(
+        "| {} |\n|{}\n",
+        columns
+            .iter()
+            .map(|c| format_cell(&AnyValue::String(c.name().as_str())))
+            .collect::<Vec<_>>()
+            .join(" | "),
+        " --- |".repeat(columns.len())
+    );
+    let total = df.height();
+    for i in 0..total.min(max_rows) {
+        let cells = columns
+            .iter()
+            .map(|c| c.get(i).map(|v| format_cell(&v)))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Query error: {e}"))?;
+        out.push_str(&format!("| {} |\n", cells.join(" | ")));
+    }
+    if total > MAX_COUNTED_ROWS {
+        out.push_str(&format!(
+            "\n({max_rows} of more than {MAX_COUNTED_ROWS} rows shown)"
+        ));
+    } else if total > max_rows {
+        out.push_str(&format!("\n({max_rows} of {total} rows shown)"));
+    } else {
+        out.push_str(&format!("\n({total} rows)"));
+    }
+    Ok(out)
+}
+
+/// Row count and column types of the loaded file.
+fn describe(data: &LazyFrame) -> Result<String, String> {
+    let schema = data
+        .clone()
+        .collect_schema()
+        .map_err(|e| format!("Failed to load file: {e}"))?;
+    let row_count = data
+        .clone()
+        .select([len()])
+        .collect()
+        .and_then(|df| df.get_columns()[0].get(0).map(|v| v.extract::<u64>()))
+        .map_err(|e| format!("Failed to load file: {e}"))?
+        .unwrap_or(0);
+    let columns = schema
+        .iter()
+        .map(|(name, dtype)| format!("{name} ({dtype})"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    Ok(format!(
+        "Table `{TABLE_NAME}`: {row_count} rows; columns: {columns}"
+    ))
+}
+
+fn analyze(path: &Path, query: Option<&str>, max_rows: usize) -> Result<String, String> {
+    let data = scan_file(path)?;
+    let summary = describe(&data)?;
+    let plan = match query {
+        Some(query) => plan_query(data, query)?,
+        None => data,
+    };
+    let result = run_query(plan, max_rows)?;
+    Ok(format!("{summary}\n\n{result}"))
+}
+
+#[async_trait]
+impl Tool for AnalyzeTableTool {
+    fn name(&self) -> &str {
+        "analyze_table"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "analyze_table".into(),
+            description: "Load a CSV, TSV or Parquet file as a table named `data` and run a \
+                SQL query over it with polars (WHERE, GROUP BY, aggregates, ORDER BY, \
+                window functions; no joins). Returns the column types and the result as a \
+                Markdown table. Without a query, shows the first rows."
+                .into(),
+            input_schema: schema_object(
+                json!({
+                    "path": {
+                        "type": "string",
+                        "description": "The CSV/TSV/Parquet file to load"
+                    },
+                    "query": {
+                        "type": "string",
+                        "description": "SELECT query over table `data`, e.g. \"SELECT region, sum(revenue) FROM data GROUP BY region ORDER BY 2 DESC\""
+                    },
+                    "max_rows": {
+                        "type": "integer",
+                        "description": "Maximum result rows to return (default: 50, max: 500)"
+                    }
+                }),
+                &["path"],
+            ),
+        }
+    }
+
+    async fn execute(&self, input: serde_json::Value) -> ToolResult {
+        let path = match input.get("path").and_then(|v| v.as_str()) {
+            Some(p) => p,
+            None => return ToolResult::error("Missing 'path' parameter".into()),
+        };
+        let working_dir =
+            super::resolve_tool_working_dir(&self.working_dir, self.working_dir_isolation, &input);
+        let resolved_path = super::resolve_tool_path(&working_dir, path);
+        if let Err(msg) = super::path_guard::check_path(&resolved_path.to_string_lossy()) {
+            return ToolResult::error(msg);
+        }
+        let query = input
+            .get("query")
+            .and_then(|v| v.as_str())
+            .map(str::trim)
+            .filter(|q| !q.is_empty())
+            .map(str::to_string);
+        let max_rows = input
+            .get("max_rows")
+            .and_then(|v| v.as_u64())
+            .map_or(DEFAULT_MAX_ROWS, |n| (n as usize).clamp(1, MAX_ROWS_LIMIT));
+
+        info!("Analyzing table: {}", resolved_path.display());
+        // polars can't be interrupted, so a query past the deadline is left
+        // to finish on the blocking pool while the agent moves on
+        let task = tokio::task::spawn_blocking(move || {
+            analyze(&resolved_path, query.as_deref(), max_rows)
+        });
+        match tokio::time::timeout(QUERY_TIMEOUT, task).await {
+            Ok(Ok(Ok(output))) => ToolResult::success(output),
+            Ok(Ok(Err(e))) => ToolResult::error(e),
+            Ok(Err(e)) => ToolResult::error(format!("Table analysis failed: {e}")),
+            Err(_) => ToolResult::error(format!(
+                "Table analysis timed out after {}s",
+                QUERY_TIMEOUT.as_secs()
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_file(name: &str, content: &str) -> (PathBuf, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("rayclaw_table_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join(name);
+        std::fs::write(&file, content).unwrap();
+        (dir, file)
+    }
+
+    #[test]
+    fn test_scan_csv_infers_types() {
+        let (dir, file) = temp_file(
+            "t.csv",
+            "name,qty,price\nA,1,2.5\nB,,3\n\"C, Inc.\",2,\"say \"\"hi\"\"\"",
+        );
+        let out = analyze(
+            &file,
+            Some("SELECT name, qty FROM data WHERE qty IS NULL"),
+            5,
+        )
+        .unwrap();
+        assert!(out.contains("3 rows; columns: name (str), qty (i64), price (str)"));
+        assert!(out.contains("| B |  |"));
+        assert!(out.contains("(1 rows)"));
+        let out = analyze(&file, Some("SELECT name, price FROM data WHERE qty = 2"), 5).unwrap();
+        assert!(out.contains("| C, Inc. | say \"hi\" |"));
+
+        let (dir2, tsv) = temp_file("t.tsv", "a\tb\n1\tx\n");
+        assert!(analyze(&tsv, None, 5)
+            .unwrap()
+            .contains("columns: a (i64), b (str)"));
+        let _ = std::fs::remove_dir_all(&dir);
+        let _ = std::fs::remove_dir_all(&dir2);
+    }
+
+    #[test]
+    fn test_analyze_csv_aggregation() {
+        let (dir, file) = temp_file(
+            "sales.csv",
+            "region,revenue\nnorth,10\nsouth,5\nnorth,7\nwest,1\n",
+        );
+        let out = analyze(
+            &file,
+            Some("SELECT region, sum(revenue) AS total FROM data GROUP BY region ORDER BY total DESC"),
+            2,
+        )
+        .unwrap();
+        assert!(out.contains("4 rows; columns: region (str), revenue (i64)"));
+        assert!(out.contains("| region | total |"));
+        assert!(out.contains("| north | 17 |"));
+        assert!(!out.contains("| west |"));
+        assert!(out.contains("(2 of 3 rows shown)"));
+
+        let out = analyze(&file, None, 50).unwrap();
+        assert!(out.contains("(4 rows)"));
+
+        assert!(analyze(&file, Some("DELETE FROM data"), 5).is_err());
+        assert!(analyze(&file, Some("DROP TABLE data"), 5)
+            .unwrap_err()
+            .contains("Only SELECT"));
+        assert!(
+            analyze(&file, Some("CREATE TABLE x AS SELECT * FROM data"), 5)
+                .unwrap_err()
+                .contains("Only SELECT")
+        );
+        assert!(analyze(
+            &file,
+            Some("-- regions\nSELECT DISTINCT region FROM data"),
+            5
+        )
+        .is_ok_and(|out| out.contains("(3 rows)")));
+        assert!(analyze(&file, Some("SELECT 1; SELECT 2"), 5).is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_query_cannot_read_other_files() {
+        let (dir, file) = temp_file("t.csv", "a\n1\n");
+        let secret = dir.join("secret.csv");
+        std::fs::write(&secret, "token\nhunter2\n").unwrap();
+        for query in [
+            format!("SELECT * FROM read_csv('{}')", secret.display()),
+            format!("SELECT * FROM \"READ_CSV\"('{}')", secret.display()),
+            format!(
+                "SELECT a FROM data WHERE a IN (SELECT * FROM read_parquet('{}'))",
+                secret.display()
+            ),
+        ] {
+            let err = analyze(&file, Some(&query), 5).unwrap_err();
+            assert!(err.contains("Table functions are not allowed"), "{err}");
+        }
+        assert!(
+            analyze(&file, Some("SELECT * FROM data a CROSS JOIN data b"), 5)
+                .unwrap_err()
+                .contains("Joins are not supported")
+        );
+        assert!(analyze(
+            &file,
+            Some("WITH RECURSIVE r AS (SELECT 1) SELECT * FROM r"),
+            5
+        )
+        .is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_result_count_capped() {
+        let rows: String = (0..MAX_COUNTED_ROWS + 5)
+            .map(|i| format!("{i}\n"))
+            .collect();
+        let (dir, file) = temp_file("big.csv", &format!("n\n{rows}"));
+        let out = analyze(&file, Some("SELECT n FROM data WHERE n >= 3"), 2).unwrap();
+        assert!(out.contains("| 3 |"));
+        assert!(out.contains(&format!("(2 of more than {MAX_COUNTED_ROWS} rows shown)")));
+        let out = analyze(&file, Some("SELECT n FROM data WHERE n >= 10000"), 2).unwrap();
+        assert!(out.contains("(2 of 5 rows shown)"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_analyze_parquet() {
+        use parquet::data_type::{ByteArray, ByteArrayType, DoubleType};
+        use parquet::file::properties::WriterProperties;
+        use parquet::file::writer::SerializedFileWriter;
+        use parquet::schema::parser::parse_message_type;
+        use std::sync::Arc;
+
+        let dir = std::env::temp_dir().join(format!("rayclaw_table_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("t.parquet");
+        let schema = Arc::new(
+            parse_message_type("message t { required binary name (UTF8); optional double x; }")
+                .unwrap(),
+        );
+        let mut writer = SerializedFileWriter::new(
+            std::fs::File::create(&file).unwrap(),
+            schema,
+            Arc::new(WriterProperties::builder().build()),
+        )
+        .unwrap();
+        let mut group = writer.next_row_group().unwrap();
+        let mut col = group.next_column().unwrap().unwrap();
+        col.typed::<ByteArrayType>()
+            .write_batch(&[ByteArray::from("a"), ByteArray::from("b")], None, None)
+            .unwrap();
+        col.close().unwrap();
+        let mut col = group.next_column().unwrap().unwrap();
+        col.typed::<DoubleType>()
+            .write_batch(&[1.5], Some(&[1, 0]), None)
+            .unwrap();
+        col.close().unwrap();
+        group.close().unwrap();
+        writer.close().unwrap();
+
+        let out = analyze(&file, Some("SELECT name, x FROM data ORDER BY name"), 10).unwrap();
+        assert!(out.contains("columns: name (str), x (f64)"));
+        assert!(out.contains("| a | 1.5 |"));
+        assert!(out.contains("| b |  |"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_analyze_table_tool_missing_file() {
+        let tool = AnalyzeTableTool::new("/tmp");
+        let result = tool
+            .execute(json!({"path": "/tmp/rayclaw-no-such-table.csv"}))
+            .await;
+        assert!(result.is_error);
+        assert!(result.content.contains("Failed to read file"));
+    }
+}
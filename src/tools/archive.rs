@@ -0,0 +1,849 @@
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+use async_trait::async_trait;
+use serde_json::json;
+use tracing::info;
+
+use super::{schema_object, Tool, ToolResult};
+use crate::config::WorkingDirIsolation;
+use crate::llm_types::ToolDefinition;
+use crate::workspace::format_bytes;
+
+/// Most entries an archive may hold, for extraction and creation alike.
+const MAX_ENTRIES: usize = 10_000;
+/// Most bytes written when extracting, or read when creating, an archive.
+const MAX_TOTAL_BYTES: u64 = 512 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ArchiveFormat {
+    Zip,
+    TarGz,
+}
+
+impl ArchiveFormat {
+    fn from_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?.to_ascii_lowercase();
+        if name.ends_with(".zip") {
+            Some(ArchiveFormat::Zip)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(ArchiveFormat::TarGz)
+        } else {
+            None
+        }
+    }
+
+    /// File name without the archive extension.
+    fn stem(self, file_name: &str) -> &str {
+        let lower = file_name.to_ascii_lowercase();
+        let ext_len = match self {
+            ArchiveFormat::Zip => 4,
+            ArchiveFormat::TarGz if lower.ends_with(".tgz") => 4,
+            ArchiveFormat::TarGz => 7,
+        };
+        &file_name[..file_name.len().saturating_sub(ext_len)]
+    }
+}
+
+/// Resolve `path` against `workspace` and make sure it stays inside it,
+/// following any symlinks in the part of the path that already exists.
+fn confine(workspace: &Path, path: &str) -> Result<PathBuf, String> {
+    let root = crate::platform::canonicalize(workspace)
+        .map_err(|e| format!("Workspace unavailable: {e}"))?;
+    let joined = super::resolve_tool_path(&root, path);
+    let mut normalized = PathBuf::new();
+    for component in joined.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    return Err(format!("Path '{path}' is outside the workspace"));
+                }
+            }
+            other => normalized.push(other),
+        }
+    }
+    // Canonicalize the deepest existing ancestor so symlinks can't escape
+    let mut existing = normalized.as_path();
+    let mut rest = Vec::new();
+    while !existing.exists() {
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                rest.push(name.to_os_string());
+                existing = parent;
+            }
+            _ => break,
+        }
+    }
+    let mut resolved = crate::platform::canonicalize(existing).unwrap_or_else(|_| existing.into());
+    resolved.extend(rest.iter().rev());
+    if !resolved.starts_with(&root) {
+        return Err(format!("Path '{path}' is outside the workspace"));
+    }
+    super::path_guard::check_path(&resolved.to_string_lossy())?;
+    Ok(resolved)
+}
+
+/// Turn an archive entry name into a relative path, rejecting absolute
+/// paths and `..` components.
+fn safe_entry_path(name: &str) -> Option<PathBuf> {
+    let mut out = PathBuf::new();
+    for component in Path::new(&name.replace('\\', "/")).components() {
+        match component {
+            Component::Normal(part) => out.push(part),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    (!out.as_os_str().is_empty()).then_some(out)
+}
+
+/// Totals and skipped entries for an extraction.
+#[derive(Debug, Default)]
+struct ExtractStats {
+    files: usize,
+    dirs: usize,
+    bytes: u64,
+    skipped: Vec<String>,
+}
+
+impl ExtractStats {
+    fn check_entry_count(&self) -> Result<(), String> {
+        if self.files + self.dirs + self.skipped.len() >= MAX_ENTRIES {
+            return Err(format!("Archive has more than {MAX_ENTRIES} entries"));
+        }
+        Ok(())
+    }
+}
+
+/// Create the directories of `relative` below `dest` one level at a time.
+/// A directory that already exists as a symlink is only followed when it
+/// resolves inside `dest`, so no directory is created outside it.
+fn create_confined_dirs(dest: &Path, relative: &Path) -> Result<(), String> {
+    let mut dir = dest.to_path_buf();
+    for part in relative.components() {
+        dir.push(part);
+        match dir.symlink_metadata() {
+            Ok(meta) if meta.is_dir() => {}
+            Ok(meta) if meta.file_type().is_symlink() => {
+                let real = crate::platform::canonicalize(&dir).map_err(|e| e.to_string())?;
+                if !real.starts_with(dest) || !real.is_dir() {
+                    return Err(format!(
+                        "Entry '{}' would be written outside the destination",
+                        relative.display()
+                    ));
+                }
+            }
+            Ok(_) => {
+                return Err(format!(
+                    "Failed to create directory: '{}' is not a directory",
+                    dir.strip_prefix(dest).unwrap_or(&dir).display()
+                ))
+            }
+            Err(_) => {
+                std::fs::create_dir(&dir).map_err(|e| format!("Failed to create directory: {e}"))?
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Write one file entry below `dest`, enforcing the size budget on the bytes
+/// actually decompressed rather than the sizes the archive claims.
+fn write_entry(
+    dest: &Path,
+    relative: &Path,
+    reader: &mut dyn Read,
+    overwrite: bool,
+    stats: &mut ExtractStats,
+) -> Result<(), String> {
+    let target = dest.join(relative);
+    if let Some(parent) = relative.parent() {
+        create_confined_dirs(dest, parent)?;
+    }
+    if let Ok(meta) = target.symlink_metadata() {
+        if !overwrite {
+            return Err(format!(
+                "'{}' already exists (set overwrite=true to replace)",
+                relative.display()
+            ));
+        }
+        if meta.is_dir() {
+            return Err(format!(
+                "'{}' is a directory and can't be replaced",
+                relative.display()
+            ));
+        }
+        // Replace the entry itself: writing through a symlink would land
+        // wherever it points
+        std::fs::remove_file(&target)
+            .map_err(|e| format!("Failed to replace {}: {e}", relative.display()))?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&target)
+        .map_err(|e| format!("Failed to create {}: {e}", relative.display()))?;
+    let budget = MAX_TOTAL_BYTES - stats.bytes;
+    let written = std::io::copy(&mut reader.take(budget + 1), &mut file)
+        .map_err(|e| format!("Failed to extract {}: {e}", relative.display()))?;
+    if written > budget {
+        drop(file);
+        let _ = std::fs::remove_file(&target);
+        return Err(format!(
+            "Archive expands to more than {}",
+            format_bytes(MAX_TOTAL_BYTES)
+        ));
+    }
+    stats.bytes += written;
+    stats.files += 1;
+    Ok(())
+}
+
+fn make_dir(dest: &Path, relative: &Path, stats: &mut ExtractStats) -> Result<(), String> {
+    create_confined_dirs(dest, relative)?;
+    stats.dirs += 1;
+    Ok(())
+}
+
+fn extract_zip(archive: &Path, dest: &Path, overwrite: bool) -> Result<ExtractStats, String> {
+    let file = File::open(archive).map_err(|e| format!("Failed to open archive: {e}"))?;
+    let mut zip = zip::ZipArchive::new(file).map_err(|e| format!("Invalid zip archive: {e}"))?;
+    if zip.len() > MAX_ENTRIES {
+        return Err(format!("Archive has more than {MAX_ENTRIES} entries"));
+    }
+    let mut stats = ExtractStats::default();
+    for i in 0..zip.len() {
+        let mut entry = zip
+            .by_index(i)
+            .map_err(|e| format!("Invalid zip entry: {e}"))?;
+        let name = entry.name().to_string();
+        let Some(relative) = safe_entry_path(&name) else {
+            stats.skipped.push(name);
+            continue;
+        };
+        if entry.is_dir() {
+            make_dir(dest, &relative, &mut stats)?;
+        } else if entry.is_file() {
+            write_entry(dest, &relative, &mut entry, overwrite, &mut stats)?;
+        } else {
+            // Symlinks could point anywhere once extracted
+            stats.skipped.push(name);
+        }
+    }
+    Ok(stats)
+}
+
+fn extract_tar_gz(archive: &Path, dest: &Path, overwrite: bool) -> Result<ExtractStats, String> {
+    let file = File::open(archive).map_err(|e| format!("Failed to open archive: {e}"))?;
+    let mut tar = tar::Archive::new(flate2::read::GzDecoder::new(file));
+    let mut stats = ExtractStats::default();
+    let entries = tar
+        .entries()
+        .map_err(|e| format!("Invalid tar.gz archive: {e}"))?;
+    for entry in entries {
+        stats.check_entry_count()?;
+        let mut entry = entry.map_err(|e| format!("Invalid tar entry: {e}"))?;
+        let name = String::from_utf8_lossy(&entry.path_bytes()).into_owned();
+        let kind = entry.header().entry_type();
+        let Some(relative) = safe_entry_path(&name) else {
+            stats.skipped.push(name);
+            continue;
+        };
+        if kind.is_dir() {
+            make_dir(dest, &relative, &mut stats)?;
+        } else if kind.is_file() {
+            write_entry(dest, &relative, &mut entry, overwrite, &mut stats)?;
+        } else if kind.is_pax_global_extensions() || kind.is_pax_local_extensions() {
+            continue;
+        } else {
+            // Links, devices and FIFOs are never recreated
+            stats.skipped.push(name);
+        }
+    }
+    Ok(stats)
+}
+
+/// Collect the files to archive: each source path, recursing into
+/// directories. Names are relative to the source's parent directory.
+fn collect_sources(
+    sources: &[PathBuf],
+    output: &Path,
+) -> Result<Vec<(PathBuf, String, bool)>, String> {
+    let mut entries = Vec::new();
+    let mut total = 0u64;
+    let mut stack: Vec<(PathBuf, String)> = sources
+        .iter()
+        .map(|p| {
+            let name = p
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            (p.clone(), name)
+        })
+        .collect();
+    while let Some((path, name)) = stack.pop() {
+        if path == output {
+            continue;
+        }
+        let meta = std::fs::symlink_metadata(&path)
+            .map_err(|e| format!("Cannot read '{}': {e}", path.display()))?;
+        if meta.file_type().is_symlink() {
+            continue;
+        }
+        if entries.len() >= MAX_ENTRIES {
+            return Err(format!("More than {MAX_ENTRIES} files to archive"));
+        }
+        if meta.is_dir() {
+            let mut children = std::fs::read_dir(&path)
+                .map_err(|e| format!("Cannot read '{}': {e}", path.display()))?
+                .filter_map(Result::ok)
+                .map(|c| c.file_name())
+                .collect::<Vec<_>>();
+            // Reverse so entries come out in name order after popping
+            children.sort_by(|a, b| b.cmp(a));
+            for child in children {
+                let child_name = format!("{name}/{}", child.to_string_lossy());
+                stack.push((path.join(&child), child_name));
+            }
+            entries.push((path, name, true));
+        } else {
+            total += meta.len();
+            if total > MAX_TOTAL_BYTES {
+                return Err(format!(
+                    "Files to archive exceed {}",
+                    format_bytes(MAX_TOTAL_BYTES)
+                ));
+            }
+            entries.push((path, name, false));
+        }
+    }
+    Ok(entries)
+}
+
+fn write_zip(output: &Path, entries: &[(PathBuf, String, bool)]) -> Result<(), String> {
+    let file = File::create(output).map_err(|e| format!("Failed to create archive: {e}"))?;
+    let mut zip = zip::ZipWriter::new(file);
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated);
+    for (path, name, is_dir) in entries {
+        if *is_dir {
+            zip.add_directory(name.as_str(), options)
+                .map_err(|e| format!("Failed to add {name}: {e}"))?;
+            continue;
+        }
+        zip.start_file(name.as_str(), options)
+            .map_err(|e| format!("Failed to add {name}: {e}"))?;
+        let mut source = File::open(path).map_err(|e| format!("Failed to read {name}: {e}"))?;
+        std::io::copy(&mut source, &mut zip).map_err(|e| format!("Failed to add {name}: {e}"))?;
+    }
+    zip.finish()
+        .map_err(|e| format!("Failed to finish archive: {e}"))?;
+    Ok(())
+}
+
+fn write_tar_gz(output: &Path, entries: &[(PathBuf, String, bool)]) -> Result<(), String> {
+    let file = File::create(output).map_err(|e| format!("Failed to create archive: {e}"))?;
+    let encoder = flate2::write::GzEncoder::new(file, flate2::Compression::default());
+    let mut tar = tar::Builder::new(encoder);
+    for (path, name, is_dir) in entries {
+        let result = if *is_dir {
+            tar.append_dir(name, path)
+        } else {
+            tar.append_path_with_name(path, name)
+        };
+        result.map_err(|e| format!("Failed to add {name}: {e}"))?;
+    }
+    let mut encoder = tar
+        .into_inner()
+        .map_err(|e| format!("Failed to finish archive: {e}"))?;
+    encoder
+        .flush()
+        .and_then(|_| encoder.try_finish())
+        .map_err(|e| format!("Failed to finish archive: {e}"))?;
+    Ok(())
+}
+
+fn string_array(input: &serde_json::Value, key: &str) -> Vec<String> {
+    input
+        .get(key)
+        .and_then(|v| v.as_array())
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(|v| v.as_str())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// ---------------------------------------------------------------------------
+// extract_archive
+// ---------------------------------------------------------------------------
+
+pub struct ExtractArchiveTool {
+    working_dir: PathBuf,
+    working_dir_isolation: WorkingDirIsolation,
+}
+
+impl ExtractArchiveTool {
+    pub fn new(working_dir: &str, working_dir_isolation: WorkingDirIsolation) -> Self {
+        Self {
+            working_dir: PathBuf::from(working_dir),
+            working_dir_isolation,
+        }
+    }
+}
+
+#[async_trait]
+impl Tool for ExtractArchiveTool {
+    fn name(&self) -> &str {
+        "extract_archive"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "extract_archive".into(),
+            description: format!(
+                "Extract a .zip or .tar.gz/.tgz archive inside the working directory (e.g. a \
+                 zip the user uploaded). Entries with absolute or '..' paths and links are \
+                 skipped. At most {MAX_ENTRIES} entries and {} of content.",
+                format_bytes(MAX_TOTAL_BYTES)
+            ),
+            input_schema: schema_object(
+                json!({
+                    "path": {
+                        "type": "string",
+                        "description": "The archive to extract"
+                    },
+                    "destination": {
+                        "type": "string",
+                        "description": "Directory to extract into (default: a folder named after the archive, next to it)"
+                    },
+                    "overwrite": {
+                        "type": "boolean",
+                        "description": "Replace files that already exist (default: false)"
+                    }
+                }),
+                &["path"],
+            ),
+        }
+    }
+
+    async fn execute(&self, input: serde_json::Value) -> ToolResult {
+        let path = match input.get("path").and_then(|v| v.as_str()) {
+            Some(p) => p,
+            None => return ToolResult::error("Missing 'path' parameter".into()),
+        };
+        let workspace =
+            super::resolve_tool_working_dir(&self.working_dir, self.working_dir_isolation, &input);
+        let archive = match confine(&workspace, path) {
+            Ok(p) => p,
+            Err(e) => return ToolResult::error(e),
+        };
+        let Some(format) = ArchiveFormat::from_path(&archive) else {
+            return ToolResult::error(
+                "Unsupported archive type (expected .zip, .tar.gz or .tgz)".into(),
+            );
+        };
+        let destination = match input.get("destination").and_then(|v| v.as_str()) {
+            Some(d) => d.to_string(),
+            None => {
+                let file_name = archive
+                    .file_name()
+                    .map(|n| n.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                let stem = format.stem(&file_name);
+                archive
+                    .with_file_name(if stem.is_empty() { "extracted" } else { stem })
+                    .to_string_lossy()
+                    .into_owned()
+            }
+        };
+        let dest = match confine(&workspace, &destination) {
+            Ok(p) => p,
+            Err(e) => return ToolResult::error(e),
+        };
+        let overwrite = input
+            .get("overwrite")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+
+        info!("Extracting {} into {}", archive.display(), dest.display());
+        let target = dest.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            std::fs::create_dir_all(&target)
+                .map_err(|e| format!("Failed to create destination: {e}"))?;
+            let target = crate::platform::canonicalize(&target).map_err(|e| e.to_string())?;
+            match format {
+                ArchiveFormat::Zip => extract_zip(&archive, &target, overwrite),
+                ArchiveFormat::TarGz => extract_tar_gz(&archive, &target, overwrite),
+            }
+        })
+        .await;
+        match result {
+            Ok(Ok(stats)) => {
+                let mut out = format!(
+                    "Extracted {} file(s) and {} folder(s), {}, into {}",
+                    stats.files,
+                    stats.dirs,
+                    format_bytes(stats.bytes),
+                    dest.display()
+                );
+                if !stats.skipped.is_empty() {
+                    out.push_str(&format!(
+                        "\nSkipped {} unsafe or unsupported entr{}: {}",
+                        stats.skipped.len(),
+                        if stats.skipped.len() == 1 { "y" } else { "ies" },
+                        stats.skipped.join(", ")
+                    ));
+                }
+                ToolResult::success(out).with_produced_file(dest.display().to_string())
+            }
+            Ok(Err(e)) => ToolResult::error(e),
+            Err(e) => ToolResult::error(format!("Extraction failed: {e}")),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// create_archive
+// ---------------------------------------------------------------------------
+
+pub struct CreateArchiveTool {
+    working_dir: PathBuf,
+    working_dir_isolation: WorkingDirIsolation,
+}
+
+impl CreateArchiveTool {
+    pub fn new(working_dir: &str, working_dir_isolation: WorkingDirIsolation) -> Self {
+        Self {
+            working_dir: PathBuf::from(working_dir),
+            working_dir_isolation,
+        }
+    }
+}
+
+#[async_trait]
+impl Tool for CreateArchiveTool {
+    fn name(&self) -> &str {
+        "create_archive"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "create_archive".into(),
+            description: format!(
+                "Pack files and folders from the working directory into a .zip or .tar.gz \
+                 archive (format from the output name), e.g. to send results back with \
+                 send_message. Symlinks are left out. At most {MAX_ENTRIES} entries and {} of \
+                 input.",
+                format_bytes(MAX_TOTAL_BYTES)
+            ),
+            input_schema: schema_object(
+                json!({
+                    "paths": {
+                        "type": "array",
+                        "items": {"type": "string"},
+                        "description": "Files or folders to include; folders are added recursively under their own name"
+                    },
+                    "output": {
+                        "type": "string",
+                        "description": "Archive to write, ending in .zip, .tar.gz or .tgz"
+                    },
+                    "overwrite": {
+                        "type": "boolean",
+                        "description": "Replace the output if it exists (default: false)"
+                    }
+                }),
+                &["paths", "output"],
+            ),
+        }
+    }
+
+    async fn execute(&self, input: serde_json::Value) -> ToolResult {
+        let paths = string_array(&input, "paths");
+        if paths.is_empty() {
+            return ToolResult::error("Missing 'paths' parameter".into());
+        }
+        let output = match input.get("output").and_then(|v| v.as_str()) {
+            Some(o) => o,
+            None => return ToolResult::error("Missing 'output' parameter".into()),
+        };
+        let workspace =
+            super::resolve_tool_working_dir(&self.working_dir, self.working_dir_isolation, &input);
+        let output = match confine(&workspace, output) {
+            Ok(p) => p,
+            Err(e) => return ToolResult::error(e),
+        };
+        let Some(format) = ArchiveFormat::from_path(&output) else {
+            return ToolResult::error("Output must end in .zip, .tar.gz or .tgz".into());
+        };
+        let overwrite = input
+            .get("overwrite")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        if output.exists() && !overwrite {
+            return ToolResult::error(format!(
+                "{} already exists (set overwrite=true to replace)",
+                output.display()
+            ));
+        }
+        let mut sources = Vec::new();
+        for path in &paths {
+            match confine(&workspace, path) {
+                Ok(p) if p.exists() => sources.push(p),
+                Ok(_) => return ToolResult::error(format!("'{path}' does not exist")),
+                Err(e) => return ToolResult::error(e),
+            }
+        }
+
+        info!("Creating archive {}", output.display());
+        let target = output.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            let entries = collect_sources(&sources, &target)?;
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)
+                    .map_err(|e| format!("Failed to create directory: {e}"))?;
+            }
+            let written = match format {
+                ArchiveFormat::Zip => write_zip(&target, &entries),
+                ArchiveFormat::TarGz => write_tar_gz(&target, &entries),
+            };
+            if written.is_err() {
+                let _ = std::fs::remove_file(&target);
+            }
+            written?;
+            let files = entries.iter().filter(|(_, _, is_dir)| !is_dir).count();
+            let size = std::fs::metadata(&target).map(|m| m.len()).unwrap_or(0);
+            Ok::<_, String>((files, size))
+        })
+        .await;
+        match result {
+            Ok(Ok((files, size))) => ToolResult::success(format!(
+                "Created {} with {files} file(s), {}",
+                output.display(),
+                format_bytes(size)
+            ))
+            .with_produced_file(output.display().to_string()),
+            Ok(Err(e)) => ToolResult::error(e),
+            Err(e) => ToolResult::error(format!("Archive creation failed: {e}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_workspace() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rayclaw_archive_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("shared")).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_archive_format_from_path() {
+        assert_eq!(
+            ArchiveFormat::from_path(Path::new("a/B.ZIP")),
+            Some(ArchiveFormat::Zip)
+        );
+        assert_eq!(
+            ArchiveFormat::from_path(Path::new("x.tar.gz")),
+            Some(ArchiveFormat::TarGz)
+        );
+        assert_eq!(ArchiveFormat::from_path(Path::new("x.tar")), None);
+        assert_eq!(ArchiveFormat::TarGz.stem("proj.tar.gz"), "proj");
+        assert_eq!(ArchiveFormat::TarGz.stem("proj.tgz"), "proj");
+        assert_eq!(ArchiveFormat::Zip.stem("up.zip"), "up");
+    }
+
+    #[test]
+    fn test_safe_entry_path() {
+        assert_eq!(
+            safe_entry_path("./src/main.rs"),
+            Some(PathBuf::from("src/main.rs"))
+        );
+        assert_eq!(safe_entry_path("a\\b.txt"), Some(PathBuf::from("a/b.txt")));
+        assert_eq!(safe_entry_path("../evil"), None);
+        assert_eq!(safe_entry_path("a/../../evil"), None);
+        assert_eq!(safe_entry_path("/etc/passwd"), None);
+        assert_eq!(safe_entry_path("."), None);
+    }
+
+    #[test]
+    fn test_confine_rejects_escapes() {
+        let root = temp_workspace();
+        let ws = root.join("shared");
+        assert!(confine(&ws, "a/b.zip").is_ok());
+        assert!(confine(&ws, "../outside.zip").is_err());
+        assert!(confine(&ws, "/tmp/elsewhere.zip").is_err());
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(&root, ws.join("link")).unwrap();
+            assert!(confine(&ws, "link/x.zip").is_err());
+        }
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test]
+    async fn test_zip_roundtrip() {
+        let root = temp_workspace();
+        let ws = root.join("shared");
+        std::fs::create_dir_all(ws.join("proj/src")).unwrap();
+        std::fs::write(ws.join("proj/README.md"), "hello").unwrap();
+        std::fs::write(ws.join("proj/src/lib.rs"), "fn main() {}").unwrap();
+        let dir = root.to_str().unwrap();
+
+        let create = CreateArchiveTool::new(dir, WorkingDirIsolation::Shared);
+        let result = create
+            .execute(json!({"paths": ["proj"], "output": "out/proj.zip"}))
+            .await;
+        assert!(!result.is_error, "{}", result.content);
+        assert!(result.content.contains("2 file(s)"));
+        let again = create
+            .execute(json!({"paths": ["proj"], "output": "out/proj.zip"}))
+            .await;
+        assert!(again.is_error);
+
+        let extract = ExtractArchiveTool::new(dir, WorkingDirIsolation::Shared);
+        let result = extract.execute(json!({"path": "out/proj.zip"})).await;
+        assert!(!result.is_error, "{}", result.content);
+        assert_eq!(
+            std::fs::read_to_string(ws.join("out/proj/proj/src/lib.rs")).unwrap(),
+            "fn main() {}"
+        );
+        let again = extract.execute(json!({"path": "out/proj.zip"})).await;
+        assert!(again.content.contains("already exists"));
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test]
+    async fn test_tar_gz_roundtrip() {
+        let root = temp_workspace();
+        let ws = root.join("shared");
+        std::fs::write(ws.join("notes.txt"), "n").unwrap();
+        let dir = root.to_str().unwrap();
+
+        let create = CreateArchiveTool::new(dir, WorkingDirIsolation::Shared);
+        let result = create
+            .execute(json!({"paths": ["notes.txt"], "output": "n.tgz"}))
+            .await;
+        assert!(!result.is_error, "{}", result.content);
+        let extract = ExtractArchiveTool::new(dir, WorkingDirIsolation::Shared);
+        let result = extract
+            .execute(json!({"path": "n.tgz", "destination": "unpacked"}))
+            .await;
+        assert!(!result.is_error, "{}", result.content);
+        assert_eq!(
+            std::fs::read_to_string(ws.join("unpacked/notes.txt")).unwrap(),
+            "n"
+        );
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_extract_zip_skips_traversal_entries() {
+        let root = temp_workspace();
+        let archive = root.join("evil.zip");
+        let mut zip = zip::ZipWriter::new(File::create(&archive).unwrap());
+        let options = zip::write::SimpleFileOptions::default();
+        zip.start_file("../escape.txt", options).unwrap();
+        zip.write_all(b"x").unwrap();
+        zip.start_file("ok.txt", options).unwrap();
+        zip.write_all(b"ok").unwrap();
+        zip.finish().unwrap();
+
+        let dest = root.join("dest");
+        std::fs::create_dir_all(&dest).unwrap();
+        let dest = dest.canonicalize().unwrap();
+        let stats = extract_zip(&archive, &dest, false).unwrap();
+        assert_eq!(stats.files, 1);
+        assert_eq!(stats.skipped, vec!["../escape.txt".to_string()]);
+        assert!(!root.join("escape.txt").exists());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_extract_tar_gz_skips_symlinks() {
+        let root = temp_workspace();
+        let archive = root.join("links.tar.gz");
+        let encoder = flate2::write::GzEncoder::new(
+            File::create(&archive).unwrap(),
+            flate2::Compression::default(),
+        );
+        let mut tar = tar::Builder::new(encoder);
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Symlink);
+        header.set_size(0);
+        tar.append_link(&mut header, "passwd", "/etc/passwd")
+            .unwrap();
+        tar.into_inner().unwrap().finish().unwrap();
+
+        let dest = root.join("dest");
+        std::fs::create_dir_all(&dest).unwrap();
+        let stats = extract_tar_gz(&archive, &dest.canonicalize().unwrap(), false).unwrap();
+        assert_eq!(stats.files, 0);
+        assert_eq!(stats.skipped, vec!["passwd".to_string()]);
+        assert!(dest.join("passwd").symlink_metadata().is_err());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_extract_does_not_follow_existing_symlinks() {
+        let root = temp_workspace();
+        let outside = root.join("outside");
+        std::fs::create_dir_all(&outside).unwrap();
+        std::fs::write(outside.join("secret.txt"), "keep").unwrap();
+        let dest = root.join("dest");
+        std::fs::create_dir_all(&dest).unwrap();
+        let dest = dest.canonicalize().unwrap();
+        std::os::unix::fs::symlink(outside.join("secret.txt"), dest.join("file.txt")).unwrap();
+        std::os::unix::fs::symlink(&outside, dest.join("linkdir")).unwrap();
+
+        let archive = root.join("a.zip");
+        let mut zip = zip::ZipWriter::new(File::create(&archive).unwrap());
+        let options = zip::write::SimpleFileOptions::default();
+        zip.start_file("file.txt", options).unwrap();
+        zip.write_all(b"new").unwrap();
+        zip.finish().unwrap();
+
+        // Overwriting replaces the symlink rather than writing through it
+        let stats = extract_zip(&archive, &dest, true).unwrap();
+        assert_eq!(stats.files, 1);
+        assert_eq!(
+            std::fs::read_to_string(outside.join("secret.txt")).unwrap(),
+            "keep"
+        );
+        assert!(!dest
+            .join("file.txt")
+            .symlink_metadata()
+            .unwrap()
+            .is_symlink());
+        assert_eq!(
+            std::fs::read_to_string(dest.join("file.txt")).unwrap(),
+            "new"
+        );
+
+        // Neither files nor directories are created through a symlinked dir
+        for name in ["linkdir/x.txt", "linkdir/sub/"] {
+            let archive = root.join("b.zip");
+            let mut zip = zip::ZipWriter::new(File::create(&archive).unwrap());
+            if let Some(dir) = name.strip_suffix('/') {
+                zip.add_directory(dir, options).unwrap();
+            } else {
+                zip.start_file(name, options).unwrap();
+                zip.write_all(b"x").unwrap();
+            }
+            zip.finish().unwrap();
+            let err = extract_zip(&archive, &dest, true).unwrap_err();
+            assert!(err.contains("outside the destination"), "{err}");
+        }
+        assert!(!outside.join("x.txt").exists());
+        assert!(!outside.join("sub").exists());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
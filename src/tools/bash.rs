@@ -5,7 +5,6 @@ use tracing::info;
 
 use crate::config::WorkingDirIsolation;
 use crate::llm_types::ToolDefinition;
-use crate::text::floor_char_boundary;
 use crate::tools::command_runner::{build_command, shell_command};
 
 use super::{schema_object, Tool, ToolResult};
@@ -106,13 +105,6 @@ impl Tool for BashTool {
                     result_text = format!("Command completed with exit code {exit_code}");
                 }
 
-                // Truncate very long output
-                if result_text.len() > 30000 {
-                    let cutoff = floor_char_boundary(&result_text, 30000);
-                    result_text.truncate(cutoff);
-                    result_text.push_str("\n... (output truncated)");
-                }
-
                 if exit_code == 0 {
                     ToolResult::success(result_text).with_status_code(exit_code)
                 } else {
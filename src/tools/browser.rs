@@ -1,19 +1,26 @@
 use std::path::PathBuf;
+use std::sync::Arc;
 
 use async_trait::async_trait;
 use serde_json::json;
 use tracing::info;
 
+use crate::browsing_profiles::{domain_allowed, BrowsingProfiles};
+use crate::db::BrowsingProfile;
+use crate::image_utils::{encode_image_for_llm, ImageOptions};
 use crate::llm_types::ToolDefinition;
-use crate::text::floor_char_boundary;
 use crate::tools::command_runner::agent_browser_program;
 
 use super::{auth_context_from_input, schema_object, Tool, ToolResult};
 
 pub struct BrowserTool {
     data_dir: PathBuf,
+    profiles: Arc<BrowsingProfiles>,
 }
 
+const USERNAME_PLACEHOLDER: &str = "{{username}}";
+const PASSWORD_PLACEHOLDER: &str = "{{password}}";
+
 fn split_browser_command(command: &str) -> Result<Vec<String>, String> {
     let mut args = Vec::new();
     let mut current = String::new();
@@ -69,26 +76,116 @@ fn split_browser_command(command: &str) -> Result<Vec<String>, String> {
     Ok(args)
 }
 
+/// File a `screenshot <path>` command writes to, if the command is one.
+fn screenshot_path(command_args: &[String]) -> Option<&str> {
+    if command_args.first().map(String::as_str) != Some("screenshot") {
+        return None;
+    }
+    command_args[1..]
+        .iter()
+        .map(String::as_str)
+        .find(|arg| !arg.starts_with('-'))
+}
+
+/// Fill in the profile's login in `args`, refusing placeholders the profile
+/// cannot satisfy.
+fn substitute_login(
+    args: &mut [String],
+    profile: Option<&BrowsingProfile>,
+    password: Option<&str>,
+) -> Result<(), String> {
+    for arg in args.iter_mut() {
+        for (placeholder, value) in [
+            (
+                USERNAME_PLACEHOLDER,
+                profile.and_then(|p| p.username.as_deref()),
+            ),
+            (PASSWORD_PLACEHOLDER, password),
+        ] {
+            if !arg.contains(placeholder) {
+                continue;
+            }
+            let Some(profile) = profile else {
+                return Err(format!("{placeholder} needs a browsing profile"));
+            };
+            let Some(value) = value else {
+                return Err(format!(
+                    "Browsing profile '{}' has no {} saved",
+                    profile.name,
+                    placeholder.trim_matches(['{', '}'])
+                ));
+            };
+            *arg = arg.replace(placeholder, value);
+        }
+    }
+    Ok(())
+}
+
 impl BrowserTool {
-    pub fn new(data_dir: &str) -> Self {
+    pub fn new(data_dir: &str, profiles: Arc<BrowsingProfiles>) -> Self {
         BrowserTool {
             data_dir: PathBuf::from(data_dir).join("groups"),
+            profiles,
         }
     }
 
-    fn profile_path(&self, chat_id: i64) -> PathBuf {
-        self.data_dir
-            .join(chat_id.to_string())
-            .join("browser-profile")
+    /// Browser profiles for named browsing profiles are kept apart from the
+    /// chat's default one.
+    fn profile_path(&self, chat_id: i64, profile: Option<&str>) -> PathBuf {
+        let chat_dir = self.data_dir.join(chat_id.to_string());
+        match profile {
+            Some(name) => chat_dir.join("browser-profiles").join(name),
+            None => chat_dir.join("browser-profile"),
+        }
     }
 
-    fn session_name_for_chat(chat_id: i64) -> String {
+    fn session_name_for_chat(chat_id: i64, profile: Option<&str>) -> String {
         let normalized = if chat_id < 0 {
             format!("neg{}", chat_id.unsigned_abs())
         } else {
             chat_id.to_string()
         };
-        format!("rayclaw-chat-{normalized}")
+        match profile {
+            Some(name) => format!("rayclaw-chat-{normalized}-{name}"),
+            None => format!("rayclaw-chat-{normalized}"),
+        }
+    }
+
+    /// Passwords are only typed into pages on the profile's domains.
+    async fn check_current_page(
+        program: &str,
+        session_args: &[String],
+        profile: &BrowsingProfile,
+    ) -> Result<(), String> {
+        let output = tokio::time::timeout(
+            std::time::Duration::from_secs(15),
+            tokio::process::Command::new(program)
+                .args(session_args)
+                .args(["get", "url"])
+                .output(),
+        )
+        .await
+        .map_err(|_| "Timed out reading the current page URL".to_string())?
+        .map_err(|e| format!("Failed to execute agent-browser: {e}"))?;
+        let url = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        let host = reqwest::Url::parse(&url)
+            .ok()
+            .and_then(|u| u.host_str().map(str::to_string))
+            .unwrap_or_default();
+        if domain_allowed(&profile.domains, &host) {
+            Ok(())
+        } else {
+            Err(format!(
+                "Refusing to enter the password of '{}' on {}: open a page on {} first",
+                profile.name,
+                if url.is_empty() {
+                    "an unknown page"
+                } else {
+                    &url
+                },
+                profile.domains.join(", ")
+            ))
+        }
     }
 }
 
@@ -101,7 +198,7 @@ impl Tool for BrowserTool {
     fn definition(&self) -> ToolDefinition {
         ToolDefinition {
             name: "browser".into(),
-            description: "Headless browser automation via agent-browser CLI. Browser state (cookies, localStorage, login sessions) persists across calls and across conversations.\n\n\
+            description: "Headless browser automation via agent-browser CLI. Browser state (cookies, localStorage, login sessions) persists across calls and across conversations. Pass a browsing profile (see browsing_profile) to use its own browser state and type its login with `{{username}}` and `{{password}}` (e.g. `fill @e3 \"{{password}}\"`); the password is only entered on the profile's domains.\n\n\
                 ## Basic workflow\n\
                 1. `open <url>` — navigate to a URL\n\
                 2. `snapshot -i` — get interactive elements with refs (@e1, @e2, ...)\n\
@@ -115,7 +212,7 @@ impl Tool for BrowserTool {
                 **Data extraction**: get text/html/value/attr/title/url/count/box <sel>\n\
                 **State checks**: is visible/enabled/checked <sel>\n\
                 **Snapshot**: snapshot (-i for interactive only, -c for compact)\n\
-                **Screenshot/PDF**: screenshot [path] (--full for full page), pdf <path>\n\
+                **Screenshot/PDF**: screenshot [path] (--full for full page; with a path the image is shown to you), pdf <path>\n\
                 **JavaScript**: eval <js>\n\
                 **Cookies**: cookies, cookies set <name> <val>, cookies clear\n\
                 **Storage**: storage local [key], storage local set <k> <v>, storage local clear (same for session)\n\
@@ -133,6 +230,10 @@ impl Tool for BrowserTool {
                         "type": "string",
                         "description": "The agent-browser command to run (e.g. `open https://example.com`, `snapshot -i`, `fill @e2 \"hello\"`)"
                     },
+                    "profile": {
+                        "type": "string",
+                        "description": "Browsing profile to browse as"
+                    },
                     "timeout_secs": {
                         "type": "integer",
                         "description": "Timeout in seconds (default: 30)"
@@ -156,19 +257,33 @@ impl Tool for BrowserTool {
 
         let auth = auth_context_from_input(&input);
 
+        let profile = match input.get("profile").and_then(|v| v.as_str()) {
+            Some(name) => {
+                let Some(auth) = auth.as_ref() else {
+                    return ToolResult::error("Browsing profiles need a chat context".into());
+                };
+                match self.profiles.get(auth.caller_chat_id, name).await {
+                    Ok(profile) => Some(profile),
+                    Err(e) => return ToolResult::error(e),
+                }
+            }
+            None => None,
+        };
+        let profile_name = profile.as_ref().map(|p| p.name.as_str());
+
         let session_name = auth
             .as_ref()
-            .map(|auth| Self::session_name_for_chat(auth.caller_chat_id))
+            .map(|auth| Self::session_name_for_chat(auth.caller_chat_id, profile_name))
             .unwrap_or_else(|| "rayclaw".to_string());
 
         let mut args = vec!["--session".to_string(), session_name];
         if let Some(auth) = auth.as_ref() {
-            let path = self.profile_path(auth.caller_chat_id);
+            let path = self.profile_path(auth.caller_chat_id, profile_name);
             args.push("--profile".to_string());
             args.push(path.to_string_lossy().to_string());
         }
 
-        let command_args = match split_browser_command(command) {
+        let mut command_args = match split_browser_command(command) {
             Ok(parts) if !parts.is_empty() => parts,
             Ok(_) => return ToolResult::error("Empty browser command".into()),
             Err(e) => {
@@ -177,9 +292,30 @@ impl Tool for BrowserTool {
                 ));
             }
         };
+        let program = agent_browser_program();
+        let mut password = None;
+        if command_args
+            .iter()
+            .any(|a| a.contains(PASSWORD_PLACEHOLDER))
+        {
+            if let Some(profile) = &profile {
+                password = match self.profiles.password(profile) {
+                    Ok(password) => password,
+                    Err(e) => return ToolResult::error(e),
+                };
+                if password.is_some() {
+                    if let Err(e) = Self::check_current_page(&program, &args, profile).await {
+                        return ToolResult::error(e);
+                    }
+                }
+            }
+        }
+        if let Err(e) = substitute_login(&mut command_args, profile.as_ref(), password.as_deref()) {
+            return ToolResult::error(e);
+        }
+        let screenshot = screenshot_path(&command_args).map(PathBuf::from);
         args.extend(command_args);
 
-        let program = agent_browser_program();
         info!("Executing browser command via '{}'", program);
 
         let result = tokio::time::timeout(
@@ -208,16 +344,20 @@ impl Tool for BrowserTool {
                 if result_text.is_empty() {
                     result_text = format!("Command completed with exit code {exit_code}");
                 }
-
-                // Truncate very long output
-                if result_text.len() > 30000 {
-                    let cutoff = floor_char_boundary(&result_text, 30000);
-                    result_text.truncate(cutoff);
-                    result_text.push_str("\n... (output truncated)");
+                if let Some(password) = password.as_deref().filter(|p| !p.is_empty()) {
+                    result_text = result_text.replace(password, "[REDACTED]");
                 }
 
                 if exit_code == 0 {
-                    ToolResult::success(result_text).with_status_code(exit_code)
+                    let result = ToolResult::success(result_text).with_status_code(exit_code);
+                    match screenshot.map(std::fs::read) {
+                        Some(Ok(bytes)) => {
+                            let (data, media_type) =
+                                encode_image_for_llm(bytes, ImageOptions::default()).await;
+                            result.with_image(media_type, data)
+                        }
+                        _ => result,
+                    }
                 } else {
                     ToolResult::error(format!("Exit code {exit_code}\n{result_text}"))
                         .with_status_code(exit_code)
@@ -237,14 +377,33 @@ impl Tool for BrowserTool {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::db::Database;
     use serde_json::json;
 
+    fn test_tool() -> (BrowserTool, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("rayclaw_browser_{}", uuid::Uuid::new_v4()));
+        let db = Arc::new(Database::new(dir.to_str().unwrap()).unwrap());
+        let profiles = BrowsingProfiles::new(db, dir.to_str().unwrap(), None);
+        (BrowserTool::new("/tmp/test-data", Arc::new(profiles)), dir)
+    }
+
     #[test]
     fn test_split_browser_command() {
         let args = split_browser_command("fill @e2 \"hello world\"").unwrap();
         assert_eq!(args, vec!["fill", "@e2", "hello world"]);
     }
 
+    #[test]
+    fn test_screenshot_path() {
+        let args = |cmd: &str| split_browser_command(cmd).unwrap();
+        assert_eq!(
+            screenshot_path(&args("screenshot --full /tmp/page.png")),
+            Some("/tmp/page.png")
+        );
+        assert_eq!(screenshot_path(&args("screenshot --full")), None);
+        assert_eq!(screenshot_path(&args("pdf /tmp/page.pdf")), None);
+    }
+
     #[test]
     fn test_split_browser_command_unclosed_quote() {
         let err = split_browser_command("open \"https://example.com").unwrap_err();
@@ -253,7 +412,7 @@ mod tests {
 
     #[test]
     fn test_browser_tool_name_and_definition() {
-        let tool = BrowserTool::new("/tmp/test-data");
+        let (tool, dir) = test_tool();
         assert_eq!(tool.name(), "browser");
         let def = tool.definition();
         assert_eq!(def.name, "browser");
@@ -263,35 +422,91 @@ mod tests {
         assert!(def.description.contains("pdf"));
         assert!(def.input_schema["properties"]["command"].is_object());
         assert!(def.input_schema["properties"]["timeout_secs"].is_object());
+        assert!(def.input_schema["properties"]["profile"].is_object());
+        let _ = std::fs::remove_dir_all(&dir);
     }
 
     #[test]
     fn test_browser_profile_path() {
-        let tool = BrowserTool::new("/tmp/test-data");
-        let path = tool.profile_path(12345);
+        let (tool, dir) = test_tool();
+        let path = tool.profile_path(12345, None);
         assert_eq!(
             path,
             PathBuf::from("/tmp/test-data/groups/12345/browser-profile")
         );
+        assert_eq!(
+            tool.profile_path(12345, Some("work")),
+            PathBuf::from("/tmp/test-data/groups/12345/browser-profiles/work")
+        );
+        let _ = std::fs::remove_dir_all(&dir);
     }
 
     #[test]
     fn test_browser_session_name_for_chat() {
         assert_eq!(
-            BrowserTool::session_name_for_chat(12345),
+            BrowserTool::session_name_for_chat(12345, None),
             "rayclaw-chat-12345"
         );
         assert_eq!(
-            BrowserTool::session_name_for_chat(-100987),
+            BrowserTool::session_name_for_chat(-100987, None),
             "rayclaw-chat-neg100987"
         );
+        assert_eq!(
+            BrowserTool::session_name_for_chat(12345, Some("work")),
+            "rayclaw-chat-12345-work"
+        );
+    }
+
+    #[test]
+    fn test_substitute_login() {
+        let profile = BrowsingProfile {
+            chat_id: 1,
+            name: "work".into(),
+            domains: vec!["example.com".into()],
+            username: Some("ann".into()),
+            sealed_password: None,
+            sealed_cookies: None,
+            created_at: String::new(),
+            updated_at: String::new(),
+        };
+        let mut args = split_browser_command("fill @e2 \"{{username}}\"").unwrap();
+        substitute_login(&mut args, Some(&profile), None).unwrap();
+        assert_eq!(args, vec!["fill", "@e2", "ann"]);
+
+        let mut args = split_browser_command("fill @e3 {{password}}").unwrap();
+        let err = substitute_login(&mut args, Some(&profile), None).unwrap_err();
+        assert_eq!(err, "Browsing profile 'work' has no password saved");
+        substitute_login(&mut args, Some(&profile), Some("pw")).unwrap();
+        assert_eq!(args, vec!["fill", "@e3", "pw"]);
+
+        let mut args = split_browser_command("fill @e3 {{password}}").unwrap();
+        let err = substitute_login(&mut args, None, None).unwrap_err();
+        assert!(err.contains("needs a browsing profile"));
+
+        let mut args = split_browser_command("open https://example.com").unwrap();
+        substitute_login(&mut args, None, None).unwrap();
     }
 
     #[tokio::test]
     async fn test_browser_missing_command() {
-        let tool = BrowserTool::new("/tmp/test-data");
+        let (tool, dir) = test_tool();
         let result = tool.execute(json!({})).await;
         assert!(result.is_error);
         assert!(result.content.contains("Missing 'command'"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_browser_unknown_profile() {
+        let (tool, dir) = test_tool();
+        let result = tool
+            .execute(json!({
+                "command": "open https://example.com",
+                "profile": "work",
+                "__rayclaw_auth": {"caller_chat_id": 100, "control_chat_ids": []}
+            }))
+            .await;
+        assert!(result.content.contains("No browsing profile named 'work'"));
+        let _ = std::fs::remove_dir_all(&dir);
     }
 }
@@ -0,0 +1,288 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde_json::json;
+
+use super::{authorize_chat_access, schema_object, Tool, ToolResult};
+use crate::browsing_profiles::{normalize_domain, BrowsingProfiles};
+use crate::db::BrowsingProfile;
+use crate::llm_types::ToolDefinition;
+
+pub struct BrowsingProfileTool {
+    profiles: Arc<BrowsingProfiles>,
+}
+
+impl BrowsingProfileTool {
+    pub fn new(profiles: Arc<BrowsingProfiles>) -> Self {
+        BrowsingProfileTool { profiles }
+    }
+}
+
+fn describe(profile: &BrowsingProfile) -> String {
+    let mut line = format!("{} — {}", profile.name, profile.domains.join(", "));
+    if let Some(username) = &profile.username {
+        line.push_str(&format!("; user {username}"));
+    }
+    if profile.sealed_password.is_some() {
+        line.push_str("; password saved");
+    }
+    if profile.sealed_cookies.is_some() {
+        line.push_str("; cookies saved");
+    }
+    line
+}
+
+fn parse_domains(value: &serde_json::Value) -> Result<Vec<String>, String> {
+    let items: Vec<&str> = match value {
+        serde_json::Value::String(s) => s.split([',', ' ']).collect(),
+        serde_json::Value::Array(items) => items.iter().filter_map(|v| v.as_str()).collect(),
+        _ => return Err("domains must be a list of domains".into()),
+    };
+    let mut domains = Vec::new();
+    for item in items.into_iter().filter(|d| !d.trim().is_empty()) {
+        let domain = normalize_domain(item).ok_or_else(|| format!("Invalid domain '{item}'"))?;
+        if !domains.contains(&domain) {
+            domains.push(domain);
+        }
+    }
+    Ok(domains)
+}
+
+#[async_trait]
+impl Tool for BrowsingProfileTool {
+    fn name(&self) -> &str {
+        "browsing_profile"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "browsing_profile".into(),
+            description: "Manage this chat's browsing profiles: named logins for sites the user \
+                has authorized you to use. Pass `profile` to web_fetch to send a profile's \
+                cookies (and its username/password when a site asks for HTTP Basic auth), or to \
+                browser to use a separate browser with `{{username}}`/`{{password}}` \
+                placeholders for login forms. Credentials and cookies only go to the profile's \
+                domains. Passwords and cookies are stored encrypted and are never shown again. \
+                Only save credentials the user gave you for this purpose, and suggest they \
+                delete the message that contained the password."
+                .into(),
+            input_schema: schema_object(
+                json!({
+                    "chat_id": {
+                        "type": "integer",
+                        "description": "The chat ID"
+                    },
+                    "action": {
+                        "type": "string",
+                        "enum": ["list", "save", "delete", "clear_cookies", "import_cookies"],
+                        "description": "list (default); save creates or updates a profile; import_cookies adds cookies the user copied from their browser"
+                    },
+                    "name": {
+                        "type": "string",
+                        "description": "Profile name: lowercase letters, digits, '-' or '_' (e.g. \"work\")"
+                    },
+                    "domains": {
+                        "type": "array",
+                        "items": {"type": "string"},
+                        "description": "For save: sites the profile may be used on; each covers its subdomains. Required for a new profile"
+                    },
+                    "username": {
+                        "type": "string",
+                        "description": "For save: login name; empty string removes it"
+                    },
+                    "password": {
+                        "type": "string",
+                        "description": "For save: password; empty string removes it"
+                    },
+                    "url": {
+                        "type": "string",
+                        "description": "For import_cookies: the page the cookies came from"
+                    },
+                    "cookies": {
+                        "type": "string",
+                        "description": "For import_cookies: a Cookie header value, e.g. \"sid=abc; theme=dark\""
+                    }
+                }),
+                &["chat_id"],
+            ),
+        }
+    }
+
+    async fn execute(&self, input: serde_json::Value) -> ToolResult {
+        let chat_id = match input.get("chat_id").and_then(|v| v.as_i64()) {
+            Some(id) => id,
+            None => return ToolResult::error("Missing required parameter: chat_id".into()),
+        };
+        if let Err(e) = authorize_chat_access(&input, chat_id) {
+            return ToolResult::error(e);
+        }
+        let action = input
+            .get("action")
+            .and_then(|v| v.as_str())
+            .unwrap_or("list");
+        if action == "list" {
+            return match self.profiles.list(chat_id).await {
+                Ok(profiles) if profiles.is_empty() => {
+                    ToolResult::success("No browsing profiles saved.".into())
+                }
+                Ok(profiles) => ToolResult::success(
+                    profiles.iter().map(describe).collect::<Vec<_>>().join("\n"),
+                ),
+                Err(e) => ToolResult::error(format!("Failed to list browsing profiles: {e}")),
+            };
+        }
+
+        let name = match input.get("name").and_then(|v| v.as_str()) {
+            Some(n) => n.trim(),
+            None => {
+                return ToolResult::error(format!("Missing required parameter for {action}: name"))
+            }
+        };
+        match action {
+            "save" => {
+                let domains = match input.get("domains").map(parse_domains).transpose() {
+                    Ok(domains) => domains,
+                    Err(e) => return ToolResult::error(e),
+                };
+                let username = input.get("username").and_then(|v| v.as_str());
+                let password = input.get("password").and_then(|v| v.as_str());
+                match self
+                    .profiles
+                    .save(chat_id, name, domains, username, password)
+                    .await
+                {
+                    Ok(profile) => ToolResult::success(format!("Saved {}", describe(&profile))),
+                    Err(e) => ToolResult::error(e),
+                }
+            }
+            "delete" => match self.profiles.delete(chat_id, name).await {
+                Ok(()) => ToolResult::success(format!("Deleted browsing profile '{name}'.")),
+                Err(e) => ToolResult::error(e),
+            },
+            "clear_cookies" => match self.profiles.clear_cookies(chat_id, name).await {
+                Ok(()) => ToolResult::success(format!("Cleared the cookies of '{name}'.")),
+                Err(e) => ToolResult::error(e),
+            },
+            "import_cookies" => {
+                let (Some(url), Some(cookies)) = (
+                    input.get("url").and_then(|v| v.as_str()),
+                    input.get("cookies").and_then(|v| v.as_str()),
+                ) else {
+                    return ToolResult::error(
+                        "Missing required parameters for import_cookies: url and cookies".into(),
+                    );
+                };
+                let profile = match self.profiles.get(chat_id, name).await {
+                    Ok(profile) => profile,
+                    Err(e) => return ToolResult::error(e),
+                };
+                match self.profiles.import_cookies(&profile, url, cookies).await {
+                    Ok(count) => {
+                        ToolResult::success(format!("Imported {count} cookie(s) into '{name}'."))
+                    }
+                    Err(e) => ToolResult::error(e),
+                }
+            }
+            other => ToolResult::error(format!(
+                "Unknown action '{other}': use list, save, delete, clear_cookies or import_cookies"
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+
+    fn test_profiles() -> (Arc<BrowsingProfiles>, std::path::PathBuf) {
+        let dir = std::env::temp_dir().join(format!("rayclaw_bprofile_{}", uuid::Uuid::new_v4()));
+        let db = Arc::new(Database::new(dir.to_str().unwrap()).unwrap());
+        let profiles = BrowsingProfiles::new(db, dir.to_str().unwrap(), None);
+        (Arc::new(profiles), dir)
+    }
+
+    #[test]
+    fn test_parse_domains() {
+        assert_eq!(
+            parse_domains(&json!([
+                "https://Example.com/login",
+                "*.corp.net",
+                "example.com"
+            ]))
+            .unwrap(),
+            vec!["example.com", "corp.net"]
+        );
+        assert_eq!(
+            parse_domains(&json!("a.com, b.org")).unwrap(),
+            vec!["a.com", "b.org"]
+        );
+        assert!(parse_domains(&json!(["user@evil.com"])).is_err());
+        assert!(parse_domains(&json!(5)).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_browsing_profile_tool() {
+        let (profiles, dir) = test_profiles();
+        let tool = BrowsingProfileTool::new(profiles.clone());
+
+        let result = tool.execute(json!({"chat_id": 100})).await;
+        assert_eq!(result.content, "No browsing profiles saved.");
+
+        let result = tool
+            .execute(json!({
+                "chat_id": 100,
+                "action": "save",
+                "name": "work",
+                "domains": ["intranet.example.com"],
+                "username": "ann",
+                "password": "hunter22"
+            }))
+            .await;
+        assert!(!result.is_error, "{}", result.content);
+        assert!(!result.content.contains("hunter22"));
+        let result = tool
+            .execute(json!({
+                "chat_id": 100,
+                "action": "import_cookies",
+                "name": "work",
+                "url": "https://intranet.example.com/",
+                "cookies": "sid=abc"
+            }))
+            .await;
+        assert_eq!(result.content, "Imported 1 cookie(s) into 'work'.");
+
+        let result = tool
+            .execute(json!({"chat_id": 100, "action": "list"}))
+            .await;
+        assert_eq!(
+            result.content,
+            "work — intranet.example.com; user ann; password saved; cookies saved"
+        );
+
+        let result = tool
+            .execute(
+                json!({"chat_id": 100, "action": "save", "name": "Bad Name", "domains": ["a.com"]}),
+            )
+            .await;
+        assert!(result.content.contains("Invalid profile name"));
+
+        let denied = tool
+            .execute(json!({
+                "chat_id": 100,
+                "action": "delete",
+                "name": "work",
+                "__rayclaw_auth": {"caller_chat_id": 200, "control_chat_ids": []}
+            }))
+            .await;
+        assert!(denied.content.contains("Permission denied"));
+
+        let result = tool
+            .execute(json!({"chat_id": 100, "action": "delete", "name": "work"}))
+            .await;
+        assert!(!result.is_error);
+        assert!(profiles.list(100).await.unwrap().is_empty());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,161 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde_json::json;
+
+use super::{auth_context_from_input, authorize_chat_access, schema_object, Tool, ToolResult};
+use crate::db::{call_blocking, Database, Delivery};
+use crate::llm_types::ToolDefinition;
+
+const DELIVERY_STATUSES: &[&str] = &["queued", "sent", "failed", "edited"];
+
+pub struct DeliveryStatusTool {
+    db: Arc<Database>,
+}
+
+impl DeliveryStatusTool {
+    pub fn new(db: Arc<Database>) -> Self {
+        DeliveryStatusTool { db }
+    }
+}
+
+fn delivery_summary(d: &Delivery) -> serde_json::Value {
+    let preview: String = d.content.chars().take(120).collect();
+    json!({
+        "delivery_id": d.id,
+        "chat_id": d.chat_id,
+        "channel": d.channel,
+        "status": d.status,
+        "error": d.error,
+        "retryable": d.retryable,
+        "attempts": d.attempts,
+        "updated_at": d.updated_at,
+        "preview": preview,
+    })
+}
+
+#[async_trait]
+impl Tool for DeliveryStatusTool {
+    fn name(&self) -> &str {
+        "delivery_status"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "delivery_status".into(),
+            description: "Check whether recent outbound messages reached their chat. Each \
+                delivery is queued, sent, failed or edited; failures include the channel \
+                error and whether a retry could help (blocked or removed bots can't be \
+                retried). Defaults to the current chat."
+                .into(),
+            input_schema: schema_object(
+                json!({
+                    "chat_id": {
+                        "type": "integer",
+                        "description": "Chat to inspect (default: the current chat)"
+                    },
+                    "status": {
+                        "type": "string",
+                        "enum": DELIVERY_STATUSES,
+                        "description": "Only list deliveries with this status"
+                    },
+                    "limit": {
+                        "type": "integer",
+                        "description": "Max deliveries to list (default: 10, max: 50)"
+                    }
+                }),
+                &[],
+            ),
+        }
+    }
+
+    async fn execute(&self, input: serde_json::Value) -> ToolResult {
+        let chat_id = match input.get("chat_id").and_then(|v| v.as_i64()) {
+            Some(id) => {
+                if let Err(e) = authorize_chat_access(&input, id) {
+                    return ToolResult::error(e);
+                }
+                Some(id)
+            }
+            None => auth_context_from_input(&input).map(|auth| auth.caller_chat_id),
+        };
+        let status = input
+            .get("status")
+            .and_then(|v| v.as_str())
+            .map(str::to_string);
+        if let Some(s) = &status {
+            if !DELIVERY_STATUSES.contains(&s.as_str()) {
+                return ToolResult::error(format!(
+                    "Invalid status '{s}'. Use one of: {}",
+                    DELIVERY_STATUSES.join(", ")
+                ));
+            }
+        }
+        let limit = input
+            .get("limit")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(10)
+            .clamp(1, 50) as usize;
+
+        match call_blocking(self.db.clone(), move |db| {
+            db.get_deliveries(chat_id, status.as_deref(), limit, 0)
+        })
+        .await
+        {
+            Ok(deliveries) => ToolResult::success(
+                json!({
+                    "count": deliveries.len(),
+                    "deliveries": deliveries.iter().map(delivery_summary).collect::<Vec<_>>(),
+                })
+                .to_string(),
+            ),
+            Err(e) => ToolResult::error(format!("Failed to load deliveries: {e}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_db() -> (Arc<Database>, std::path::PathBuf) {
+        let dir = std::env::temp_dir().join(format!("rayclaw_delivery_{}", uuid::Uuid::new_v4()));
+        let db = Arc::new(Database::new(dir.to_str().unwrap()).unwrap());
+        (db, dir)
+    }
+
+    #[tokio::test]
+    async fn test_delivery_status_scoped_to_caller_chat() {
+        let (db, dir) = test_db();
+        let own = db.create_delivery(100, "telegram", "hi").unwrap();
+        db.record_delivery_attempt(
+            own,
+            "failed",
+            Some("Forbidden: bot was blocked by the user"),
+            false,
+            None,
+        )
+        .unwrap();
+        db.create_delivery(200, "telegram", "other chat").unwrap();
+        let tool = DeliveryStatusTool::new(db);
+        let auth = json!({"caller_chat_id": 100, "control_chat_ids": []});
+
+        let result = tool.execute(json!({"__rayclaw_auth": auth})).await;
+        assert!(!result.is_error, "{}", result.content);
+        let body: serde_json::Value = serde_json::from_str(&result.content).unwrap();
+        assert_eq!(body["count"], 1);
+        assert_eq!(body["deliveries"][0]["status"], "failed");
+        assert_eq!(body["deliveries"][0]["retryable"], false);
+
+        let denied = tool
+            .execute(json!({"chat_id": 200, "__rayclaw_auth": auth}))
+            .await;
+        assert!(denied.is_error);
+        assert!(denied.content.contains("Permission denied"));
+
+        let bad = tool.execute(json!({"status": "lost"})).await;
+        assert!(bad.is_error);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -105,6 +105,7 @@ impl Tool for EditFileTool {
         match tokio::fs::write(&resolved_path, new_content).await {
             Ok(()) => {
                 ToolResult::success(format!("Successfully edited {}", resolved_path.display()))
+                    .with_produced_file(resolved_path.display().to_string())
             }
             Err(e) => ToolResult::error(format!("Failed to write file: {e}")),
         }
@@ -104,7 +104,8 @@ impl Tool for ExportChatTool {
                 "Exported {} messages to {}",
                 messages.len(),
                 path.display()
-            )),
+            ))
+            .with_produced_file(path.display().to_string()),
             Err(e) => ToolResult::error(format!("Failed to write file: {e}")),
         }
     }
@@ -145,6 +146,7 @@ mod tests {
             content: "hello".into(),
             is_from_bot: false,
             timestamp: "2024-01-01T00:00:01Z".into(),
+            thread_id: None,
         })
         .unwrap();
         db.store_message(&StoredMessage {
@@ -154,6 +156,7 @@ mod tests {
             content: "hi there!".into(),
             is_from_bot: true,
             timestamp: "2024-01-01T00:00:02Z".into(),
+            thread_id: None,
         })
         .unwrap();
 
@@ -183,6 +186,7 @@ mod tests {
             content: "hello".into(),
             is_from_bot: false,
             timestamp: "2024-01-01T00:00:01Z".into(),
+            thread_id: None,
         })
         .unwrap();
 
@@ -211,6 +215,7 @@ mod tests {
             content: "hello".into(),
             is_from_bot: false,
             timestamp: "2024-01-01T00:00:01Z".into(),
+            thread_id: None,
         })
         .unwrap();
         let out_path = dir.join("control_export.md");
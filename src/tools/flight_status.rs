@@ -0,0 +1,542 @@
+//! `flight_status`: schedule, delay, gate and live position of a flight.
+//!
+//! Schedules come from aviationstack (needs `flight_status.aviationstack_api_key`).
+//! Live positions come from an ADS-B Exchange v2 compatible API (adsb.lol by
+//! default, free), which knows aircraft by ICAO callsign (`DLH400`) rather
+//! than the IATA flight number printed on tickets (`LH400`); aviationstack
+//! provides the mapping.
+
+use std::sync::OnceLock;
+
+use async_trait::async_trait;
+use regex::Regex;
+use serde_json::json;
+use tracing::warn;
+
+use super::{fetch_json, schema_object, Tool, ToolResult};
+use crate::config::{Config, FlightStatusConfig};
+use crate::llm_types::ToolDefinition;
+use crate::outbound_http::{self, OutboundClient};
+
+const AVIATIONSTACK_API: &str = "https://api.aviationstack.com/v1";
+const DEFAULT_ADSB_URL: &str = "https://api.adsb.lol";
+
+fn http_client() -> &'static OutboundClient {
+    static CLIENT: OnceLock<OutboundClient> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        outbound_http::client_builder(
+            "flight_status",
+            "RayClaw/1.0 (https://github.com/rayclaw/rayclaw)",
+        )
+        .timeout(std::time::Duration::from_secs(15))
+        .build()
+        .expect("failed to build HTTP client")
+        .into()
+    })
+}
+
+/// A flight designator as typed by the user.
+#[derive(Debug, Clone, PartialEq)]
+enum FlightNumber {
+    /// Two-character airline code, e.g. `LH400`
+    Iata(String),
+    /// Three-letter airline code, e.g. `DLH400`; also the ADS-B callsign
+    Icao(String),
+}
+
+impl FlightNumber {
+    fn parse(value: &str) -> Option<Self> {
+        static IATA: OnceLock<Regex> = OnceLock::new();
+        static ICAO: OnceLock<Regex> = OnceLock::new();
+        let iata = IATA
+            .get_or_init(|| Regex::new(r"^([A-Z][A-Z0-9]|[0-9][A-Z])0*(\d{1,4}[A-Z]?)$").unwrap());
+        let icao = ICAO.get_or_init(|| Regex::new(r"^[A-Z]{3}\d{1,4}[A-Z]{0,2}$").unwrap());
+        let value: String = value
+            .chars()
+            .filter(|c| !c.is_whitespace() && *c != '-')
+            .collect::<String>()
+            .to_uppercase();
+        if let Some(caps) = iata.captures(&value) {
+            // Leading zeros are not part of IATA flight numbers (LH0400 = LH400)
+            return Some(FlightNumber::Iata(format!("{}{}", &caps[1], &caps[2])));
+        }
+        icao.is_match(&value).then_some(FlightNumber::Icao(value))
+    }
+
+    fn as_str(&self) -> &str {
+        match self {
+            FlightNumber::Iata(s) | FlightNumber::Icao(s) => s,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct FlightLeg {
+    airport: String,
+    iata: String,
+    terminal: Option<String>,
+    gate: Option<String>,
+    scheduled: Option<String>,
+    estimated: Option<String>,
+    actual: Option<String>,
+    delay_min: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct FlightInfo {
+    iata: Option<String>,
+    icao: Option<String>,
+    airline: Option<String>,
+    date: String,
+    status: String,
+    departure: FlightLeg,
+    arrival: FlightLeg,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct LivePosition {
+    callsign: String,
+    lat: f64,
+    lon: f64,
+    /// Feet, or None on the ground
+    altitude_ft: Option<i64>,
+    ground_speed_kt: Option<f64>,
+    track: Option<f64>,
+    aircraft_type: Option<String>,
+    registration: Option<String>,
+}
+
+fn str_field<'a>(value: &'a serde_json::Value, pointer: &str) -> &'a str {
+    value
+        .pointer(pointer)
+        .and_then(|v| v.as_str())
+        .unwrap_or("")
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+/// aviationstack timestamps are airport-local times with a `+00:00` suffix;
+/// drop the offset rather than convert.
+fn local_time(value: &str) -> Option<String> {
+    let value = value.trim();
+    let time = value.get(..16)?;
+    chrono::NaiveDateTime::parse_from_str(time, "%Y-%m-%dT%H:%M")
+        .ok()
+        .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+}
+
+fn parse_leg(leg: &serde_json::Value) -> FlightLeg {
+    FlightLeg {
+        airport: str_field(leg, "/airport").trim().to_string(),
+        iata: str_field(leg, "/iata").trim().to_string(),
+        terminal: non_empty(str_field(leg, "/terminal")),
+        gate: non_empty(str_field(leg, "/gate")),
+        scheduled: local_time(str_field(leg, "/scheduled")),
+        estimated: local_time(str_field(leg, "/estimated")),
+        actual: local_time(str_field(leg, "/actual")),
+        delay_min: leg["delay"].as_i64(),
+    }
+}
+
+fn parse_aviationstack(body: &serde_json::Value) -> Result<Vec<FlightInfo>, String> {
+    if let Some(error) = body.get("error") {
+        return Err(non_empty(str_field(error, "/message"))
+            .or_else(|| non_empty(str_field(error, "/code")))
+            .unwrap_or_else(|| "unknown aviationstack error".into()));
+    }
+    let flights = body["data"].as_array().cloned().unwrap_or_default();
+    Ok(flights
+        .iter()
+        .map(|f| FlightInfo {
+            iata: non_empty(str_field(f, "/flight/iata")),
+            icao: non_empty(str_field(f, "/flight/icao")),
+            airline: non_empty(str_field(f, "/airline/name")),
+            date: str_field(f, "/flight_date").to_string(),
+            status: non_empty(str_field(f, "/flight_status")).unwrap_or_else(|| "unknown".into()),
+            departure: parse_leg(&f["departure"]),
+            arrival: parse_leg(&f["arrival"]),
+        })
+        .collect())
+}
+
+/// The flight on `date` if given, else the one departing closest to `today`
+/// (aviationstack returns a flight number's recent and upcoming legs).
+fn pick_flight(flights: Vec<FlightInfo>, date: Option<&str>, today: &str) -> Option<FlightInfo> {
+    if let Some(date) = date {
+        return flights.into_iter().find(|f| f.date == date);
+    }
+    let day_distance = |f: &FlightInfo| match (
+        chrono::NaiveDate::parse_from_str(&f.date, "%Y-%m-%d"),
+        chrono::NaiveDate::parse_from_str(today, "%Y-%m-%d"),
+    ) {
+        (Ok(d), Ok(t)) => (d - t).num_days().abs(),
+        _ => i64::MAX,
+    };
+    flights.into_iter().min_by_key(|f| {
+        // Among equally close days, a flight in the air wins
+        (day_distance(f), f.status != "active")
+    })
+}
+
+/// First aircraft of an ADS-B Exchange v2 style `/v2/callsign` response.
+fn parse_adsb(body: &serde_json::Value) -> Option<LivePosition> {
+    let ac = body["ac"].as_array()?.first()?;
+    let altitude_ft = match &ac["alt_baro"] {
+        serde_json::Value::Number(n) => n.as_f64().map(|a| a.round() as i64),
+        // "ground"
+        _ => None,
+    };
+    Some(LivePosition {
+        callsign: str_field(ac, "/flight").trim().to_string(),
+        lat: ac["lat"].as_f64()?,
+        lon: ac["lon"].as_f64()?,
+        altitude_ft,
+        ground_speed_kt: ac["gs"].as_f64(),
+        track: ac["track"].as_f64(),
+        aircraft_type: non_empty(str_field(ac, "/t")),
+        registration: non_empty(str_field(ac, "/r")),
+    })
+}
+
+fn format_leg(label: &str, leg: &FlightLeg) -> String {
+    let mut place = if leg.iata.is_empty() || leg.airport.contains(&leg.iata) {
+        leg.airport.clone()
+    } else {
+        format!("{} ({})", leg.airport, leg.iata)
+    };
+    if let Some(terminal) = &leg.terminal {
+        place.push_str(&format!(", terminal {terminal}"));
+    }
+    if let Some(gate) = &leg.gate {
+        place.push_str(&format!(", gate {gate}"));
+    }
+    let mut times = Vec::new();
+    for (kind, time) in [
+        ("scheduled", &leg.scheduled),
+        ("estimated", &leg.estimated),
+        ("actual", &leg.actual),
+    ] {
+        if let Some(time) = time {
+            times.push(format!("{kind} {time}"));
+        }
+    }
+    let mut output = format!("{label}: {place}\n");
+    if !times.is_empty() {
+        output.push_str(&format!("  {}", times.join(", ")));
+        match leg.delay_min {
+            Some(delay) if delay > 0 => output.push_str(&format!(" (delayed {delay} min)")),
+            _ => {}
+        }
+        output.push('\n');
+    }
+    output
+}
+
+fn format_flight(flight: &FlightInfo) -> String {
+    let designators: Vec<&str> = [&flight.iata, &flight.icao]
+        .into_iter()
+        .flatten()
+        .map(String::as_str)
+        .collect();
+    let airline = match &flight.airline {
+        Some(airline) => format!(" ({airline})"),
+        None => String::new(),
+    };
+    format!(
+        "Flight {}{airline}, {}: {}\n{}{}(times are local to each airport)\n",
+        designators.join(" / "),
+        flight.date,
+        flight.status,
+        format_leg("Departure", &flight.departure),
+        format_leg("Arrival", &flight.arrival)
+    )
+}
+
+fn format_live(live: &LivePosition) -> String {
+    let mut details = Vec::new();
+    details.push(match live.altitude_ft {
+        Some(ft) => format!("{ft} ft"),
+        None => "on the ground".to_string(),
+    });
+    if let Some(gs) = live.ground_speed_kt {
+        details.push(format!("{gs:.0} kt"));
+    }
+    if let Some(track) = live.track {
+        details.push(format!("heading {track:.0}°"));
+    }
+    let aircraft: Vec<&str> = [&live.aircraft_type, &live.registration]
+        .into_iter()
+        .flatten()
+        .map(String::as_str)
+        .collect();
+    let aircraft = if aircraft.is_empty() {
+        String::new()
+    } else {
+        format!(" ({})", aircraft.join(", "))
+    };
+    format!(
+        "Live (ADS-B, {}): {} at {:.4}, {:.4}{aircraft}\n",
+        live.callsign,
+        details.join(", "),
+        live.lat,
+        live.lon
+    )
+}
+
+pub struct FlightStatusTool {
+    config: FlightStatusConfig,
+}
+
+impl FlightStatusTool {
+    pub fn new(config: &Config) -> Self {
+        FlightStatusTool {
+            config: config.flight_status.clone(),
+        }
+    }
+
+    async fn schedule(
+        &self,
+        key: &str,
+        flight: &FlightNumber,
+        date: Option<&str>,
+    ) -> Result<Option<FlightInfo>, String> {
+        let param = match flight {
+            FlightNumber::Iata(_) => "flight_iata",
+            FlightNumber::Icao(_) => "flight_icao",
+        };
+        let mut request = http_client()
+            .get(format!("{AVIATIONSTACK_API}/flights"))
+            .query(&[("access_key", key), (param, flight.as_str())]);
+        if let Some(date) = date {
+            request = request.query(&[("flight_date", date)]);
+        }
+        let flights = parse_aviationstack(&fetch_json(request).await?)?;
+        let today = chrono::Utc::now().format("%Y-%m-%d").to_string();
+        Ok(pick_flight(flights, date, &today))
+    }
+
+    async fn live_position(&self, callsign: &str) -> Result<Option<LivePosition>, String> {
+        let base = self.config.adsb_url.as_deref().unwrap_or(DEFAULT_ADSB_URL);
+        let body = fetch_json(http_client().get(format!("{base}/v2/callsign/{callsign}"))).await?;
+        Ok(parse_adsb(&body))
+    }
+}
+
+#[async_trait]
+impl Tool for FlightStatusTool {
+    fn name(&self) -> &str {
+        "flight_status"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "flight_status".into(),
+            description: "Check a flight's status: scheduled, estimated and actual departure and arrival times, delays, terminals and gates, plus the aircraft's live position and altitude while airborne. Accepts the IATA flight number from the ticket (e.g. LH400) or the ICAO callsign (e.g. DLH400).".into(),
+            input_schema: schema_object(
+                json!({
+                    "flight": {
+                        "type": "string",
+                        "description": "Flight number such as \"LH400\", \"BA 117\" or ICAO callsign \"DLH400\""
+                    },
+                    "date": {
+                        "type": "string",
+                        "description": "Departure date (YYYY-MM-DD) when the flight number operates daily; default: the nearest flight"
+                    }
+                }),
+                &["flight"],
+            ),
+        }
+    }
+
+    async fn execute(&self, input: serde_json::Value) -> ToolResult {
+        let raw = match input.get("flight").and_then(|v| v.as_str()) {
+            Some(f) if !f.trim().is_empty() => f.trim(),
+            _ => return ToolResult::error("Missing required parameter: flight".into()),
+        };
+        let Some(flight) = FlightNumber::parse(raw) else {
+            return ToolResult::error(format!(
+                "Invalid flight number: {raw} (expected e.g. LH400 or DLH400)"
+            ));
+        };
+        let date = match input.get("date").and_then(|v| v.as_str()) {
+            None => None,
+            Some(date) => match chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+                Ok(_) => Some(date),
+                Err(_) => {
+                    return ToolResult::error(format!("Invalid date: {date} (expected YYYY-MM-DD)"))
+                }
+            },
+        };
+
+        let mut output = String::new();
+        let mut sources = Vec::new();
+        let mut callsign = match &flight {
+            FlightNumber::Icao(callsign) => Some(callsign.clone()),
+            FlightNumber::Iata(_) => None,
+        };
+        let mut status = None;
+        if let Some(key) = self.config.aviationstack_api_key.as_deref() {
+            match self.schedule(key, &flight, date).await {
+                Ok(Some(info)) => {
+                    output.push_str(&format_flight(&info));
+                    sources.push("aviationstack");
+                    callsign = info.icao.clone().or(callsign);
+                    status = Some(info.status.clone());
+                }
+                Ok(None) => output.push_str(&format!(
+                    "No schedule found for {}{}.\n",
+                    flight.as_str(),
+                    date.map(|d| format!(" on {d}")).unwrap_or_default()
+                )),
+                Err(e) => {
+                    warn!("flight_status: aviationstack failed: {e}");
+                    output.push_str(&format!("Schedule lookup failed: {e}\n"));
+                }
+            }
+        }
+
+        // Landed, cancelled or future flights have no position worth showing
+        let airborne_possible = matches!(status.as_deref(), None | Some("active"));
+        match callsign {
+            Some(callsign) if airborne_possible && date.is_none() => {
+                match self.live_position(&callsign).await {
+                    Ok(Some(live)) => {
+                        output.push_str(&format_live(&live));
+                        sources.push("ADS-B");
+                    }
+                    Ok(None) => output.push_str(&format!(
+                        "No live ADS-B position for {callsign}; the aircraft is not currently transmitting (not airborne yet, landed, or out of receiver range).\n"
+                    )),
+                    Err(e) => {
+                        warn!("flight_status: ADS-B lookup for {callsign} failed: {e}");
+                        output.push_str(&format!("Live position lookup failed: {e}\n"));
+                    }
+                }
+            }
+            None if self.config.aviationstack_api_key.is_none() => {
+                return ToolResult::error(format!(
+                    "Without flight_status.aviationstack_api_key only live positions by ICAO callsign are available; retry with the airline's ICAO code instead of {} (e.g. DLH400 for LH400)",
+                    flight.as_str()
+                ));
+            }
+            _ => {}
+        }
+
+        if sources.is_empty() {
+            return ToolResult::error(output.trim_end().to_string());
+        }
+        output.push_str(&format!("\nSources: {}\n", sources.join(", ")));
+        ToolResult::success(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flight(date: &str, status: &str) -> FlightInfo {
+        FlightInfo {
+            iata: Some("LH400".into()),
+            icao: Some("DLH400".into()),
+            airline: Some("Lufthansa".into()),
+            date: date.into(),
+            status: status.into(),
+            departure: FlightLeg::default(),
+            arrival: FlightLeg::default(),
+        }
+    }
+
+    #[test]
+    fn test_parse_flight_number() {
+        assert_eq!(
+            FlightNumber::parse("lh 400"),
+            Some(FlightNumber::Iata("LH400".into()))
+        );
+        assert_eq!(
+            FlightNumber::parse("U2-0123"),
+            Some(FlightNumber::Iata("U2123".into()))
+        );
+        assert_eq!(
+            FlightNumber::parse("DLH400"),
+            Some(FlightNumber::Icao("DLH400".into()))
+        );
+        assert_eq!(FlightNumber::parse("hello"), None);
+        assert_eq!(FlightNumber::parse("DLH/400"), None);
+    }
+
+    #[test]
+    fn test_pick_flight() {
+        let flights = vec![
+            flight("2026-10-15", "landed"),
+            flight("2026-10-16", "scheduled"),
+            flight("2026-10-17", "scheduled"),
+        ];
+        assert_eq!(
+            pick_flight(flights.clone(), None, "2026-10-16")
+                .unwrap()
+                .date,
+            "2026-10-16"
+        );
+        assert_eq!(
+            pick_flight(flights.clone(), Some("2026-10-17"), "2026-10-16")
+                .unwrap()
+                .date,
+            "2026-10-17"
+        );
+        assert!(pick_flight(flights, Some("2026-11-01"), "2026-10-16").is_none());
+    }
+
+    #[test]
+    fn test_parse_aviationstack() {
+        let body = json!({"data": [{
+            "flight_date": "2026-10-16",
+            "flight_status": "active",
+            "departure": {"airport": "Frankfurt International Airport", "iata": "FRA",
+                "terminal": "1", "gate": "Z25", "delay": 21,
+                "scheduled": "2026-10-16T10:10:00+00:00", "estimated": "2026-10-16T10:10:00+00:00",
+                "actual": "2026-10-16T10:31:00+00:00"},
+            "arrival": {"airport": "John F. Kennedy International", "iata": "JFK",
+                "terminal": "1", "gate": null, "delay": null,
+                "scheduled": "2026-10-16T12:45:00+00:00", "estimated": null, "actual": null},
+            "airline": {"name": "Lufthansa"},
+            "flight": {"number": "400", "iata": "LH400", "icao": "DLH400"}
+        }]});
+        let flights = parse_aviationstack(&body).unwrap();
+        assert_eq!(
+            format_flight(&flights[0]),
+            "Flight LH400 / DLH400 (Lufthansa), 2026-10-16: active\n\
+             Departure: Frankfurt International Airport (FRA), terminal 1, gate Z25\n  \
+             scheduled 2026-10-16 10:10, estimated 2026-10-16 10:10, actual 2026-10-16 10:31 (delayed 21 min)\n\
+             Arrival: John F. Kennedy International (JFK), terminal 1\n  \
+             scheduled 2026-10-16 12:45\n\
+             (times are local to each airport)\n"
+        );
+
+        let error = json!({"error": {"code": "invalid_access_key", "message": "You have not supplied a valid API Access Key."}});
+        assert!(parse_aviationstack(&error)
+            .unwrap_err()
+            .contains("valid API Access Key"));
+    }
+
+    #[test]
+    fn test_parse_adsb() {
+        let body = json!({"ac": [{
+            "hex": "3c65a1", "flight": "DLH400  ", "r": "D-AIXA", "t": "A359",
+            "alt_baro": 37000, "gs": 478.3, "track": 285.4,
+            "lat": 51.23456, "lon": -30.12346
+        }], "msg": "No error"});
+        let live = parse_adsb(&body).unwrap();
+        assert_eq!(
+            format_live(&live),
+            "Live (ADS-B, DLH400): 37000 ft, 478 kt, heading 285° at 51.2346, -30.1235 (A359, D-AIXA)\n"
+        );
+
+        let ground =
+            json!({"ac": [{"flight": "DLH400", "alt_baro": "ground", "lat": 50.0, "lon": 8.5}]});
+        assert_eq!(parse_adsb(&ground).unwrap().altitude_ft, None);
+        assert!(parse_adsb(&json!({"ac": [], "msg": "No error"})).is_none());
+    }
+}
@@ -1,6 +1,6 @@
 use async_trait::async_trait;
 use serde_json::json;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use tracing::info;
 
 use crate::config::WorkingDirIsolation;
@@ -72,7 +72,7 @@ impl Tool for GlobTool {
 
         info!("Glob: {} in {}", pattern, resolved_base.display());
 
-        let full_pattern = if pattern.starts_with('/') {
+        let full_pattern = if pattern.starts_with('/') || Path::new(pattern).is_absolute() {
             pattern.to_string()
         } else {
             format!("{}/{}", resolved_base.display(), pattern)
@@ -96,6 +96,8 @@ impl Tool for GlobTool {
                         matches.push(format!("... and {} more files", count - 500));
                     }
                     ToolResult::success(matches.join("\n"))
+                        .with_truncated(count > 500)
+                        .with_suggested_next_tools(&["read_file", "grep"])
                 }
             }
             Err(e) => ToolResult::error(format!("Invalid glob pattern: {e}")),
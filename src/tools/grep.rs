@@ -97,11 +97,14 @@ impl Tool for GrepTool {
         if results.is_empty() {
             ToolResult::success("No matches found.".into())
         } else {
-            if results.len() > 500 {
+            let truncated = results.len() > 500;
+            if truncated {
                 results.truncate(500);
                 results.push("... (results truncated)".into());
             }
             ToolResult::success(results.join("\n"))
+                .with_truncated(truncated)
+                .with_suggested_next_tools(&["read_file"])
         }
     }
 }
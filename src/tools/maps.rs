@@ -0,0 +1,975 @@
+//! `directions` and `place_search`: routes with distance and travel time, and
+//! places of a kind near a location.
+//!
+//! By default places are geocoded with Nominatim and routes come from OSRM,
+//! both on OpenStreetMap data and free to use. With `maps.google_api_key` set
+//! the Google Directions, Geocoding and Places APIs are used instead, which
+//! adds live traffic and public transit.
+
+use std::sync::OnceLock;
+
+use async_trait::async_trait;
+use serde_json::json;
+
+use super::{schema_object, Tool, ToolResult};
+use crate::config::{Config, MapsConfig};
+use crate::llm_types::ToolDefinition;
+use crate::outbound_http::{self, OutboundClient};
+
+const DEFAULT_NOMINATIM_URL: &str = "https://nominatim.openstreetmap.org";
+const GOOGLE_MAPS_API: &str = "https://maps.googleapis.com/maps/api";
+const DEFAULT_RADIUS_KM: f64 = 5.0;
+const MAX_RADIUS_KM: f64 = 50.0;
+const DEFAULT_MAX_RESULTS: usize = 5;
+const MAX_RESULTS: usize = 10;
+const MAX_STEPS: usize = 30;
+
+fn http_client() -> &'static OutboundClient {
+    static CLIENT: OnceLock<OutboundClient> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        // Nominatim's usage policy requires an identifying User-Agent
+        outbound_http::client_builder("maps", "RayClaw/1.0 (https://github.com/rayclaw/rayclaw)")
+            .timeout(std::time::Duration::from_secs(20))
+            .build()
+            .expect("failed to build HTTP client")
+            .into()
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TravelMode {
+    Driving,
+    Walking,
+    Cycling,
+    Transit,
+}
+
+impl TravelMode {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "driving" => Some(TravelMode::Driving),
+            "walking" => Some(TravelMode::Walking),
+            "cycling" => Some(TravelMode::Cycling),
+            "transit" => Some(TravelMode::Transit),
+            _ => None,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            TravelMode::Driving => "Driving",
+            TravelMode::Walking => "Walking",
+            TravelMode::Cycling => "Cycling",
+            TravelMode::Transit => "Transit",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Place {
+    name: String,
+    address: String,
+    lat: f64,
+    lon: f64,
+    rating: Option<f64>,
+    open_now: Option<bool>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Route {
+    distance_m: f64,
+    duration_s: f64,
+    /// Google only, for driving
+    traffic_duration_s: Option<f64>,
+    steps: Vec<String>,
+}
+
+/// `"52.52, 13.40"` as latitude and longitude.
+fn parse_coordinates(value: &str) -> Option<(f64, f64)> {
+    let (lat, lon) = value.split_once(',')?;
+    let lat: f64 = lat.trim().parse().ok()?;
+    let lon: f64 = lon.trim().parse().ok()?;
+    ((-90.0..=90.0).contains(&lat) && (-180.0..=180.0).contains(&lon)).then_some((lat, lon))
+}
+
+/// Great-circle distance in meters.
+fn haversine_m(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    const EARTH_RADIUS_M: f64 = 6_371_000.0;
+    let (phi1, phi2) = (lat1.to_radians(), lat2.to_radians());
+    let d_phi = (lat2 - lat1).to_radians();
+    let d_lambda = (lon2 - lon1).to_radians();
+    let a = (d_phi / 2.0).sin().powi(2) + phi1.cos() * phi2.cos() * (d_lambda / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_M * a.sqrt().asin()
+}
+
+fn format_distance(meters: f64) -> String {
+    if meters < 1_000.0 {
+        format!("{} m", ((meters / 10.0).round() * 10.0) as u64)
+    } else {
+        format!("{:.1} km", meters / 1_000.0)
+    }
+}
+
+fn format_duration(seconds: f64) -> String {
+    let minutes = (seconds / 60.0).round().max(1.0) as u64;
+    match (minutes / 60, minutes % 60) {
+        (0, m) => format!("{m} min"),
+        (h, 0) => format!("{h} h"),
+        (h, m) => format!("{h} h {m} min"),
+    }
+}
+
+fn str_field<'a>(value: &'a serde_json::Value, pointer: &str) -> &'a str {
+    value
+        .pointer(pointer)
+        .and_then(|v| v.as_str())
+        .unwrap_or("")
+}
+
+/// Nominatim `format=jsonv2` results.
+fn parse_nominatim(body: &serde_json::Value) -> Vec<Place> {
+    let results = body.as_array().cloned().unwrap_or_default();
+    results
+        .iter()
+        .filter_map(|r| {
+            // Coordinates come as strings
+            let lat = str_field(r, "/lat").parse().ok()?;
+            let lon = str_field(r, "/lon").parse().ok()?;
+            let address = str_field(r, "/display_name").to_string();
+            let name = match str_field(r, "/name") {
+                "" => address.split(',').next().unwrap_or_default().to_string(),
+                name => name.to_string(),
+            };
+            Some(Place {
+                name,
+                address,
+                lat,
+                lon,
+                rating: None,
+                open_now: None,
+            })
+        })
+        .collect()
+}
+
+/// Google Geocoding or Places Text Search results.
+fn parse_google_places(body: &serde_json::Value) -> Vec<Place> {
+    let results = body["results"].as_array().cloned().unwrap_or_default();
+    results
+        .iter()
+        .filter_map(|r| {
+            let lat = r.pointer("/geometry/location/lat")?.as_f64()?;
+            let lon = r.pointer("/geometry/location/lng")?.as_f64()?;
+            let address = str_field(r, "/formatted_address").to_string();
+            let name = match str_field(r, "/name") {
+                "" => address.clone(),
+                name => name.to_string(),
+            };
+            Some(Place {
+                name,
+                address,
+                lat,
+                lon,
+                rating: r["rating"].as_f64(),
+                open_now: r
+                    .pointer("/opening_hours/open_now")
+                    .and_then(|v| v.as_bool()),
+            })
+        })
+        .collect()
+}
+
+/// Instruction text for one OSRM route step.
+fn describe_osrm_step(step: &serde_json::Value) -> Option<String> {
+    let kind = str_field(step, "/maneuver/type");
+    let modifier = str_field(step, "/maneuver/modifier");
+    let name = str_field(step, "/name");
+    let onto = if name.is_empty() {
+        String::new()
+    } else {
+        format!(" onto {name}")
+    };
+    let action = match kind {
+        "depart" => {
+            return Some(if name.is_empty() {
+                "Depart".to_string()
+            } else {
+                format!("Head out on {name}")
+            })
+        }
+        "arrive" => return Some("Arrive at the destination".to_string()),
+        "notification" => return None,
+        "roundabout" | "rotary" => match step.pointer("/maneuver/exit").and_then(|v| v.as_u64()) {
+            Some(exit) => format!("At the roundabout take exit {exit}"),
+            None => "Go through the roundabout".to_string(),
+        },
+        "turn" | "roundabout turn" | "end of road" => format!("Turn {modifier}"),
+        "merge" => format!("Merge {modifier}"),
+        "on ramp" => format!("Take the ramp {modifier}"),
+        "off ramp" => format!("Take the exit {modifier}"),
+        "fork" => format!("Keep {modifier} at the fork"),
+        _ if modifier.is_empty() || modifier == "straight" => "Continue".to_string(),
+        _ => format!("Continue {modifier}"),
+    };
+    Some(format!("{}{onto}", action.trim_end()))
+}
+
+/// First route of an OSRM `route` response; None for `NoRoute`.
+fn parse_osrm(body: &serde_json::Value) -> Result<Option<Route>, String> {
+    match body["code"].as_str() {
+        Some("Ok") => {}
+        Some("NoRoute") => return Ok(None),
+        code => {
+            let message = body["message"].as_str().or(code).unwrap_or("no code");
+            return Err(format!("OSRM error: {message}"));
+        }
+    }
+    let Some(route) = body.pointer("/routes/0") else {
+        return Ok(None);
+    };
+    let steps = route["legs"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .flat_map(|leg| leg["steps"].as_array().cloned().unwrap_or_default())
+        .filter_map(|step| {
+            let text = describe_osrm_step(&step)?;
+            match step["distance"].as_f64() {
+                Some(d) if d > 0.0 => Some(format!("{text} ({})", format_distance(d))),
+                _ => Some(text),
+            }
+        })
+        .collect();
+    Ok(Some(Route {
+        distance_m: route["distance"].as_f64().unwrap_or(0.0),
+        duration_s: route["duration"].as_f64().unwrap_or(0.0),
+        traffic_duration_s: None,
+        steps,
+    }))
+}
+
+fn strip_html(html: &str) -> String {
+    static TAG: OnceLock<regex::Regex> = OnceLock::new();
+    let tag = TAG.get_or_init(|| regex::Regex::new(r"<[^>]+>").unwrap());
+    // Google puts follow-up notes ("Destination will be on the right") in <div>s
+    let text = html.replace("<div", " <div");
+    let text = tag.replace_all(&text, "");
+    text.replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Google's status field, mapping ZERO_RESULTS/NOT_FOUND to Ok(false).
+fn google_status(body: &serde_json::Value) -> Result<bool, String> {
+    match body["status"].as_str() {
+        Some("OK") => Ok(true),
+        Some("ZERO_RESULTS") | Some("NOT_FOUND") => Ok(false),
+        status => {
+            let status = status.unwrap_or("no status");
+            Err(match body["error_message"].as_str() {
+                Some(message) => format!("Google Maps {status}: {message}"),
+                None => format!("Google Maps {status}"),
+            })
+        }
+    }
+}
+
+/// First route of a Google Directions response, with resolved endpoints.
+fn parse_google_directions(
+    body: &serde_json::Value,
+) -> Result<Option<(Route, String, String)>, String> {
+    if !google_status(body)? {
+        return Ok(None);
+    }
+    let Some(leg) = body.pointer("/routes/0/legs/0") else {
+        return Ok(None);
+    };
+    let steps = leg["steps"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|step| {
+            let text = strip_html(str_field(step, "/html_instructions"));
+            match step.pointer("/distance/value").and_then(|v| v.as_f64()) {
+                Some(d) if d > 0.0 => format!("{text} ({})", format_distance(d)),
+                _ => text,
+            }
+        })
+        .collect();
+    let route = Route {
+        distance_m: leg
+            .pointer("/distance/value")
+            .and_then(|v| v.as_f64())
+            .unwrap_or(0.0),
+        duration_s: leg
+            .pointer("/duration/value")
+            .and_then(|v| v.as_f64())
+            .unwrap_or(0.0),
+        traffic_duration_s: leg
+            .pointer("/duration_in_traffic/value")
+            .and_then(|v| v.as_f64()),
+        steps,
+    };
+    Ok(Some((
+        route,
+        str_field(leg, "/start_address").to_string(),
+        str_field(leg, "/end_address").to_string(),
+    )))
+}
+
+fn format_route(
+    route: &Route,
+    mode: TravelMode,
+    from: &str,
+    to: &str,
+    provider: &str,
+    with_steps: bool,
+) -> String {
+    let mut output = format!(
+        "{} directions ({provider}):\nFrom: {from}\nTo: {to}\nDistance: {}\nDuration: {}",
+        mode.label(),
+        format_distance(route.distance_m),
+        format_duration(route.duration_s)
+    );
+    if let Some(traffic) = route.traffic_duration_s {
+        output.push_str(&format!(
+            " (about {} in current traffic)",
+            format_duration(traffic)
+        ));
+    }
+    output.push('\n');
+    if with_steps && !route.steps.is_empty() {
+        output.push_str("\nSteps:\n");
+        for (i, step) in route.steps.iter().take(MAX_STEPS).enumerate() {
+            output.push_str(&format!("{}. {step}\n", i + 1));
+        }
+        if route.steps.len() > MAX_STEPS {
+            output.push_str(&format!(
+                "... {} more steps\n",
+                route.steps.len() - MAX_STEPS
+            ));
+        }
+    }
+    output
+}
+
+fn format_places(
+    places: &[Place],
+    query: &str,
+    center: Option<&Place>,
+    provider: &str,
+    google: bool,
+) -> String {
+    let mut output = match center {
+        Some(center) => format!(
+            "Places matching \"{query}\" near {} ({provider}):\n\n",
+            center.address
+        ),
+        None => format!("Places matching \"{query}\" ({provider}):\n\n"),
+    };
+    for (i, place) in places.iter().enumerate() {
+        let mut details = Vec::new();
+        if let Some(center) = center {
+            details.push(format!(
+                "{} away",
+                format_distance(haversine_m(center.lat, center.lon, place.lat, place.lon))
+            ));
+        }
+        if let Some(rating) = place.rating {
+            details.push(format!("rated {rating:.1}"));
+        }
+        match place.open_now {
+            Some(true) => details.push("open now".into()),
+            Some(false) => details.push("closed now".into()),
+            None => {}
+        }
+        let details = if details.is_empty() {
+            String::new()
+        } else {
+            format!(" ({})", details.join(", "))
+        };
+        let link = if google {
+            format!(
+                "https://www.google.com/maps/search/?api=1&query={:.6},{:.6}",
+                place.lat, place.lon
+            )
+        } else {
+            format!(
+                "https://www.openstreetmap.org/?mlat={:.6}&mlon={:.6}#map=18/{:.6}/{:.6}",
+                place.lat, place.lon, place.lat, place.lon
+            )
+        };
+        output.push_str(&format!("{}. {}{details}\n", i + 1, place.name));
+        if place.address != place.name {
+            output.push_str(&format!("   {}\n", place.address));
+        }
+        output.push_str(&format!("   {link}\n\n"));
+    }
+    output
+}
+
+async fn fetch_json(request: reqwest::RequestBuilder) -> Result<serde_json::Value, String> {
+    let response = request.send().await.map_err(|e| e.to_string())?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(format!("HTTP {status}"));
+    }
+    response.json().await.map_err(|e| e.to_string())
+}
+
+/// Shared geocoding and routing client for both tools.
+#[derive(Clone)]
+struct Maps {
+    config: MapsConfig,
+}
+
+impl Maps {
+    fn provider(&self) -> &'static str {
+        if self.config.google_api_key.is_some() {
+            "Google Maps"
+        } else {
+            "OpenStreetMap"
+        }
+    }
+
+    fn google_key(&self) -> Option<&str> {
+        self.config.google_api_key.as_deref()
+    }
+
+    fn osrm_route_url(&self, mode: TravelMode) -> String {
+        let (server, profile) = match mode {
+            TravelMode::Walking => ("routed-foot", "walking"),
+            TravelMode::Cycling => ("routed-bike", "cycling"),
+            TravelMode::Driving | TravelMode::Transit => ("routed-car", "driving"),
+        };
+        match &self.config.osrm_url {
+            Some(url) => format!("{url}/route/v1/{profile}"),
+            // The FOSSGIS servers run one OSRM instance per profile
+            None => format!("https://routing.openstreetmap.de/{server}/route/v1/driving"),
+        }
+    }
+
+    /// Places matching `query`, optionally within `radius_km` of a center.
+    async fn search(
+        &self,
+        query: &str,
+        limit: usize,
+        near: Option<(&Place, f64)>,
+    ) -> Result<Vec<Place>, String> {
+        if let Some(key) = self.google_key() {
+            let endpoint = if near.is_some() {
+                "place/textsearch"
+            } else {
+                "geocode"
+            };
+            let mut request = http_client()
+                .get(format!("{GOOGLE_MAPS_API}/{endpoint}/json"))
+                .query(&[("key", key)]);
+            request = match near {
+                Some((center, radius_km)) => request.query(&[
+                    ("query", query.to_string()),
+                    ("location", format!("{},{}", center.lat, center.lon)),
+                    ("radius", ((radius_km * 1_000.0) as u64).to_string()),
+                ]),
+                None => request.query(&[("address", query)]),
+            };
+            let body = fetch_json(request).await?;
+            if !google_status(&body)? {
+                return Ok(Vec::new());
+            }
+            let mut places = parse_google_places(&body);
+            places.truncate(limit);
+            return Ok(places);
+        }
+
+        let base = self
+            .config
+            .nominatim_url
+            .as_deref()
+            .unwrap_or(DEFAULT_NOMINATIM_URL);
+        let mut request = http_client().get(format!("{base}/search")).query(&[
+            ("q", query),
+            ("format", "jsonv2"),
+            ("limit", &limit.to_string()),
+        ]);
+        if let Some((center, radius_km)) = near {
+            let d_lat = radius_km / 111.0;
+            let d_lon = radius_km / (111.0 * center.lat.to_radians().cos().max(0.01));
+            let viewbox = format!(
+                "{},{},{},{}",
+                center.lon - d_lon,
+                center.lat + d_lat,
+                center.lon + d_lon,
+                center.lat - d_lat
+            );
+            request = request.query(&[("viewbox", viewbox.as_str()), ("bounded", "1")]);
+        }
+        Ok(parse_nominatim(&fetch_json(request).await?))
+    }
+
+    async fn geocode(&self, location: &str) -> Result<Option<Place>, String> {
+        if let Some((lat, lon)) = parse_coordinates(location) {
+            return Ok(Some(Place {
+                name: location.to_string(),
+                address: location.to_string(),
+                lat,
+                lon,
+                rating: None,
+                open_now: None,
+            }));
+        }
+        Ok(self.search(location, 1, None).await?.into_iter().next())
+    }
+}
+
+pub struct DirectionsTool {
+    maps: Maps,
+}
+
+impl DirectionsTool {
+    pub fn new(config: &Config) -> Self {
+        DirectionsTool {
+            maps: Maps {
+                config: config.maps.clone(),
+            },
+        }
+    }
+
+    async fn route(
+        &self,
+        origin: &str,
+        destination: &str,
+        mode: TravelMode,
+        with_steps: bool,
+    ) -> Result<String, String> {
+        let provider = self.maps.provider();
+        if let Some(key) = self.maps.google_key() {
+            let mode_param = match mode {
+                TravelMode::Driving => "driving",
+                TravelMode::Walking => "walking",
+                TravelMode::Cycling => "bicycling",
+                TravelMode::Transit => "transit",
+            };
+            let body = fetch_json(
+                http_client()
+                    .get(format!("{GOOGLE_MAPS_API}/directions/json"))
+                    .query(&[
+                        ("origin", origin),
+                        ("destination", destination),
+                        ("mode", mode_param),
+                        // Needed for duration_in_traffic and transit schedules
+                        ("departure_time", "now"),
+                        ("key", key),
+                    ]),
+            )
+            .await?;
+            return Ok(match parse_google_directions(&body)? {
+                Some((route, from, to)) => {
+                    format_route(&route, mode, &from, &to, provider, with_steps)
+                }
+                None => format!(
+                    "No {} route found from {origin} to {destination}.",
+                    mode.label().to_lowercase()
+                ),
+            });
+        }
+
+        if mode == TravelMode::Transit {
+            return Err(
+                "transit directions need maps.google_api_key; OpenStreetMap routing supports driving, walking and cycling"
+                    .into(),
+            );
+        }
+        let Some(from) = self.maps.geocode(origin).await? else {
+            return Err(format!("could not find the origin: {origin}"));
+        };
+        let Some(to) = self.maps.geocode(destination).await? else {
+            return Err(format!("could not find the destination: {destination}"));
+        };
+        let url = format!(
+            "{}/{:.6},{:.6};{:.6},{:.6}",
+            self.maps.osrm_route_url(mode),
+            from.lon,
+            from.lat,
+            to.lon,
+            to.lat
+        );
+        let request = http_client().get(url).query(&[
+            ("overview", "false"),
+            ("steps", if with_steps { "true" } else { "false" }),
+        ]);
+        Ok(match parse_osrm(&fetch_json(request).await?)? {
+            Some(route) => format_route(
+                &route,
+                mode,
+                &from.address,
+                &to.address,
+                "OpenStreetMap/OSRM",
+                with_steps,
+            ),
+            None => format!(
+                "No {} route found from {} to {}.",
+                mode.label().to_lowercase(),
+                from.address,
+                to.address
+            ),
+        })
+    }
+}
+
+#[async_trait]
+impl Tool for DirectionsTool {
+    fn name(&self) -> &str {
+        "directions"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "directions".into(),
+            description: "Get a route between two places with its distance and travel time, optionally with turn-by-turn steps. Use it for questions like \"how long does it take to drive to X\". Origin and destination can be addresses, place names or \"lat,lon\".".into(),
+            input_schema: schema_object(
+                json!({
+                    "origin": {
+                        "type": "string",
+                        "description": "Starting point: address, place name or \"lat,lon\""
+                    },
+                    "destination": {
+                        "type": "string",
+                        "description": "End point: address, place name or \"lat,lon\""
+                    },
+                    "mode": {
+                        "type": "string",
+                        "enum": ["driving", "walking", "cycling", "transit"],
+                        "description": "Travel mode (default driving). transit needs a Google Maps key"
+                    },
+                    "steps": {
+                        "type": "boolean",
+                        "description": "Include turn-by-turn steps (default false)"
+                    }
+                }),
+                &["origin", "destination"],
+            ),
+        }
+    }
+
+    async fn execute(&self, input: serde_json::Value) -> ToolResult {
+        let origin = match input.get("origin").and_then(|v| v.as_str()) {
+            Some(o) if !o.trim().is_empty() => o.trim(),
+            _ => return ToolResult::error("Missing required parameter: origin".into()),
+        };
+        let destination = match input.get("destination").and_then(|v| v.as_str()) {
+            Some(d) if !d.trim().is_empty() => d.trim(),
+            _ => return ToolResult::error("Missing required parameter: destination".into()),
+        };
+        let mode = match input.get("mode").and_then(|v| v.as_str()) {
+            None => TravelMode::Driving,
+            Some(value) => match TravelMode::parse(value) {
+                Some(mode) => mode,
+                None => {
+                    return ToolResult::error(format!(
+                        "Invalid mode: {value} (expected driving, walking, cycling or transit)"
+                    ))
+                }
+            },
+        };
+        let with_steps = input
+            .get("steps")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+
+        match self.route(origin, destination, mode, with_steps).await {
+            Ok(output) => ToolResult::success(output),
+            Err(e) => ToolResult::error(format!("Directions failed: {e}")),
+        }
+    }
+}
+
+pub struct PlaceSearchTool {
+    maps: Maps,
+}
+
+impl PlaceSearchTool {
+    pub fn new(config: &Config) -> Self {
+        PlaceSearchTool {
+            maps: Maps {
+                config: config.maps.clone(),
+            },
+        }
+    }
+}
+
+#[async_trait]
+impl Tool for PlaceSearchTool {
+    fn name(&self) -> &str {
+        "place_search"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "place_search".into(),
+            description: "Find places such as shops, pharmacies, restaurants or landmarks, optionally near a location, nearest first. Returns names, addresses, distance and a map link. Use directions afterwards for travel time.".into(),
+            input_schema: schema_object(
+                json!({
+                    "query": {
+                        "type": "string",
+                        "description": "What to find, e.g. \"pharmacy\", \"Italian restaurant\" or a specific place name"
+                    },
+                    "near": {
+                        "type": "string",
+                        "description": "Address, place name or \"lat,lon\" to search around"
+                    },
+                    "radius_km": {
+                        "type": "number",
+                        "minimum": 0.1,
+                        "maximum": MAX_RADIUS_KM,
+                        "description": format!("Search radius around `near` in km (default {DEFAULT_RADIUS_KM})")
+                    },
+                    "max_results": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "maximum": MAX_RESULTS,
+                        "description": format!("Number of places to return (default {DEFAULT_MAX_RESULTS})")
+                    }
+                }),
+                &["query"],
+            ),
+        }
+    }
+
+    async fn execute(&self, input: serde_json::Value) -> ToolResult {
+        let query = match input.get("query").and_then(|v| v.as_str()) {
+            Some(q) if !q.trim().is_empty() => q.trim(),
+            _ => return ToolResult::error("Missing required parameter: query".into()),
+        };
+        let near = input
+            .get("near")
+            .and_then(|v| v.as_str())
+            .map(str::trim)
+            .filter(|n| !n.is_empty());
+        let radius_km = input
+            .get("radius_km")
+            .and_then(|v| v.as_f64())
+            .unwrap_or(DEFAULT_RADIUS_KM)
+            .clamp(0.1, MAX_RADIUS_KM);
+        let count = input
+            .get("max_results")
+            .and_then(|v| v.as_u64())
+            .map_or(DEFAULT_MAX_RESULTS, |n| n as usize)
+            .clamp(1, MAX_RESULTS);
+
+        let center = match near {
+            None => None,
+            Some(near) => match self.maps.geocode(near).await {
+                Ok(Some(center)) => Some(center),
+                Ok(None) => {
+                    return ToolResult::error(format!("Could not find the location: {near}"))
+                }
+                Err(e) => return ToolResult::error(format!("Place search failed: {e}")),
+            },
+        };
+        let mut places = match self
+            .maps
+            .search(query, count, center.as_ref().map(|c| (c, radius_km)))
+            .await
+        {
+            Ok(places) => places,
+            Err(e) => return ToolResult::error(format!("Place search failed: {e}")),
+        };
+        if places.is_empty() {
+            return ToolResult::success(match &center {
+                Some(center) => format!(
+                    "No places matching \"{query}\" within {} of {}.",
+                    format_distance(radius_km * 1_000.0),
+                    center.address
+                ),
+                None => format!("No places matching \"{query}\"."),
+            });
+        }
+        if let Some(center) = &center {
+            places.sort_by(|a, b| {
+                let da = haversine_m(center.lat, center.lon, a.lat, a.lon);
+                let db = haversine_m(center.lat, center.lon, b.lat, b.lon);
+                da.total_cmp(&db)
+            });
+        }
+        ToolResult::success(format_places(
+            &places,
+            query,
+            center.as_ref(),
+            self.maps.provider(),
+            self.maps.google_key().is_some(),
+        ))
+        .with_suggested_next_tools(&["directions"])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn maps(osrm_url: Option<&str>) -> Maps {
+        Maps {
+            config: MapsConfig {
+                osrm_url: osrm_url.map(str::to_string),
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn test_format_distance_and_duration() {
+        assert_eq!(format_distance(347.0), "350 m");
+        assert_eq!(format_distance(12_345.0), "12.3 km");
+        assert_eq!(format_duration(20.0), "1 min");
+        assert_eq!(format_duration(1_080.0), "18 min");
+        assert_eq!(format_duration(3_600.0), "1 h");
+        assert_eq!(format_duration(4_530.0), "1 h 16 min");
+    }
+
+    #[test]
+    fn test_haversine() {
+        // Berlin Hbf to Brandenburg Gate, roughly 1.1 km
+        let d = haversine_m(52.5251, 13.3694, 52.5163, 13.3777);
+        assert!((1_000.0..1_300.0).contains(&d), "{d}");
+        assert_eq!(haversine_m(10.0, 10.0, 10.0, 10.0), 0.0);
+    }
+
+    #[test]
+    fn test_parse_coordinates() {
+        assert_eq!(parse_coordinates("52.52, 13.405"), Some((52.52, 13.405)));
+        assert_eq!(parse_coordinates("-33.9,151.2"), Some((-33.9, 151.2)));
+        assert_eq!(parse_coordinates("95.0, 13.4"), None);
+        assert_eq!(parse_coordinates("Main St, Springfield"), None);
+    }
+
+    #[test]
+    fn test_osrm_route_url() {
+        assert_eq!(
+            maps(None).osrm_route_url(TravelMode::Walking),
+            "https://routing.openstreetmap.de/routed-foot/route/v1/driving"
+        );
+        assert_eq!(
+            maps(Some("http://osrm.local:5000")).osrm_route_url(TravelMode::Cycling),
+            "http://osrm.local:5000/route/v1/cycling"
+        );
+    }
+
+    #[test]
+    fn test_parse_nominatim() {
+        let body = json!([
+            {"lat": "52.5163", "lon": "13.3777", "name": "Brandenburger Tor",
+             "display_name": "Brandenburger Tor, Pariser Platz, Berlin, Deutschland"},
+            {"lat": "52.52", "lon": "13.40", "name": "",
+             "display_name": "Apotheke am Markt, Berlin"},
+            {"lat": "bad", "lon": "13.40", "display_name": "Broken"}
+        ]);
+        let places = parse_nominatim(&body);
+        assert_eq!(places.len(), 2);
+        assert_eq!(places[0].name, "Brandenburger Tor");
+        assert_eq!(places[0].lat, 52.5163);
+        assert_eq!(places[1].name, "Apotheke am Markt");
+    }
+
+    #[test]
+    fn test_parse_osrm() {
+        let body = json!({
+            "code": "Ok",
+            "routes": [{
+                "distance": 12345.6,
+                "duration": 1080.0,
+                "legs": [{"steps": [
+                    {"distance": 200.0, "name": "Main Street", "maneuver": {"type": "depart"}},
+                    {"distance": 1500.0, "name": "Oak Avenue",
+                     "maneuver": {"type": "turn", "modifier": "left"}},
+                    {"distance": 800.0, "name": "",
+                     "maneuver": {"type": "roundabout", "modifier": "right", "exit": 2}},
+                    {"distance": 0.0, "name": "", "maneuver": {"type": "arrive"}}
+                ]}]
+            }]
+        });
+        let route = parse_osrm(&body).unwrap().unwrap();
+        assert_eq!(
+            route.steps,
+            vec![
+                "Head out on Main Street (200 m)",
+                "Turn left onto Oak Avenue (1.5 km)",
+                "At the roundabout take exit 2 (800 m)",
+                "Arrive at the destination",
+            ]
+        );
+        assert_eq!(
+            format_route(&route, TravelMode::Driving, "A", "B", "OSRM", false),
+            "Driving directions (OSRM):\nFrom: A\nTo: B\nDistance: 12.3 km\nDuration: 18 min\n"
+        );
+
+        assert_eq!(parse_osrm(&json!({"code": "NoRoute"})).unwrap(), None);
+        assert!(
+            parse_osrm(&json!({"code": "InvalidQuery", "message": "bad"}))
+                .unwrap_err()
+                .contains("bad")
+        );
+    }
+
+    #[test]
+    fn test_parse_google_directions() {
+        let body = json!({
+            "status": "OK",
+            "routes": [{"legs": [{
+                "start_address": "Berlin, Germany",
+                "end_address": "Potsdam, Germany",
+                "distance": {"value": 35200},
+                "duration": {"value": 2400},
+                "duration_in_traffic": {"value": 3000},
+                "steps": [{
+                    "html_instructions": "Turn <b>right</b> onto <b>A100</b><div style=\"font-size:0.9em\">Toll road</div>",
+                    "distance": {"value": 5000}
+                }]
+            }]}]
+        });
+        let (route, from, to) = parse_google_directions(&body).unwrap().unwrap();
+        assert_eq!(
+            (from.as_str(), to.as_str()),
+            ("Berlin, Germany", "Potsdam, Germany")
+        );
+        assert_eq!(route.steps, vec!["Turn right onto A100 Toll road (5.0 km)"]);
+        let output = format_route(&route, TravelMode::Driving, &from, &to, "Google Maps", true);
+        assert!(output.contains("Duration: 40 min (about 50 min in current traffic)"));
+        assert!(output.contains("Steps:\n1. Turn right onto A100"));
+
+        let denied = json!({"status": "REQUEST_DENIED", "error_message": "API key invalid"});
+        assert!(parse_google_directions(&denied)
+            .unwrap_err()
+            .contains("API key invalid"));
+        assert!(parse_google_directions(&json!({"status": "ZERO_RESULTS"}))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_parse_google_places_and_format() {
+        let body = json!({"status": "OK", "results": [{
+            "name": "City Pharmacy",
+            "formatted_address": "1 Main St, Springfield",
+            "geometry": {"location": {"lat": 40.001, "lng": -75.0}},
+            "rating": 4.46,
+            "opening_hours": {"open_now": true}
+        }]});
+        let places = parse_google_places(&body);
+        assert_eq!(places.len(), 1);
+        let center = Place {
+            name: "Center".into(),
+            address: "Springfield".into(),
+            lat: 40.0,
+            lon: -75.0,
+            rating: None,
+            open_now: None,
+        };
+        let output = format_places(&places, "pharmacy", Some(&center), "Google Maps", true);
+        assert_eq!(
+            output,
+            "Places matching \"pharmacy\" near Springfield (Google Maps):\n\n1. City Pharmacy (110 m away, rated 4.5, open now)\n   1 Main St, Springfield\n   https://www.google.com/maps/search/?api=1&query=40.001000,-75.000000\n\n"
+        );
+    }
+}
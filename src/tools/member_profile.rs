@@ -0,0 +1,163 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde_json::json;
+
+use super::{authorize_chat_access, schema_object, Tool, ToolResult};
+use crate::db::{call_blocking, Database};
+use crate::llm_types::ToolDefinition;
+
+/// Longest notes kept per member; profiles are shown in every group prompt.
+const MAX_NOTES_CHARS: usize = 500;
+
+pub struct UpdateMemberProfileTool {
+    db: Arc<Database>,
+}
+
+impl UpdateMemberProfileTool {
+    pub fn new(db: Arc<Database>) -> Self {
+        UpdateMemberProfileTool { db }
+    }
+}
+
+#[async_trait]
+impl Tool for UpdateMemberProfileTool {
+    fn name(&self) -> &str {
+        "update_member_profile"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "update_member_profile".into(),
+            description: "Record what you learned about a group member: their role (e.g. \
+                maintainer, designer) and brief notes (preferences, responsibilities). \
+                Profiles are shown in the group's member list on every turn. Pass an empty \
+                string to clear a field."
+                .into(),
+            input_schema: schema_object(
+                json!({
+                    "chat_id": {
+                        "type": "integer",
+                        "description": "The group chat ID"
+                    },
+                    "member": {
+                        "type": "string",
+                        "description": "The member's sender name, as in the `sender` attribute (the part in parentheses if a display name is shown)"
+                    },
+                    "role": {
+                        "type": "string",
+                        "description": "Short role in the group"
+                    },
+                    "notes": {
+                        "type": "string",
+                        "description": "Brief notes; replaces the existing notes (max 500 characters)"
+                    }
+                }),
+                &["chat_id", "member"],
+            ),
+        }
+    }
+
+    async fn execute(&self, input: serde_json::Value) -> ToolResult {
+        let chat_id = match input.get("chat_id").and_then(|v| v.as_i64()) {
+            Some(id) => id,
+            None => return ToolResult::error("Missing required parameter: chat_id".into()),
+        };
+        if let Err(e) = authorize_chat_access(&input, chat_id) {
+            return ToolResult::error(e);
+        }
+        let member = match input.get("member").and_then(|v| v.as_str()) {
+            Some(m) if !m.trim().is_empty() => m.trim().to_string(),
+            _ => return ToolResult::error("Missing required parameter: member".into()),
+        };
+        let role = input
+            .get("role")
+            .and_then(|v| v.as_str())
+            .map(|r| r.trim().to_string());
+        let notes = input
+            .get("notes")
+            .and_then(|v| v.as_str())
+            .map(|n| n.trim().to_string());
+        if role.is_none() && notes.is_none() {
+            return ToolResult::error("Provide role and/or notes to update".into());
+        }
+        if notes
+            .as_ref()
+            .is_some_and(|n| n.chars().count() > MAX_NOTES_CHARS)
+        {
+            return ToolResult::error(format!(
+                "notes are too long (max {MAX_NOTES_CHARS} characters); keep them brief"
+            ));
+        }
+
+        let member_for_db = member.clone();
+        match call_blocking(self.db.clone(), move |db| {
+            db.update_group_member_profile(
+                chat_id,
+                &member_for_db,
+                role.as_deref(),
+                notes.as_deref(),
+            )
+        })
+        .await
+        {
+            Ok(true) => ToolResult::success(format!("Updated profile for {member}.")),
+            Ok(false) => ToolResult::error(format!(
+                "No member '{member}' has posted in chat {chat_id}."
+            )),
+            Err(e) => ToolResult::error(format!("Failed to update member profile: {e}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::StoredMessage;
+
+    fn test_db() -> (Arc<Database>, std::path::PathBuf) {
+        let dir = std::env::temp_dir().join(format!("rayclaw_members_{}", uuid::Uuid::new_v4()));
+        let db = Arc::new(Database::new(dir.to_str().unwrap()).unwrap());
+        (db, dir)
+    }
+
+    #[tokio::test]
+    async fn test_update_member_profile() {
+        let (db, dir) = test_db();
+        db.store_message(&StoredMessage {
+            id: "m1".into(),
+            chat_id: 100,
+            sender_name: "alice".into(),
+            content: "hello".into(),
+            is_from_bot: false,
+            timestamp: "2024-01-01T00:00:01Z".into(),
+            thread_id: None,
+        })
+        .unwrap();
+        let tool = UpdateMemberProfileTool::new(db.clone());
+
+        let result = tool
+            .execute(json!({"chat_id": 100, "member": "alice", "role": "maintainer"}))
+            .await;
+        assert!(!result.is_error, "{}", result.content);
+        let members = db.get_group_members(100, 10).unwrap();
+        assert_eq!(members[0].role.as_deref(), Some("maintainer"));
+
+        let unknown = tool
+            .execute(json!({"chat_id": 100, "member": "mallory", "notes": "x"}))
+            .await;
+        assert!(unknown.is_error);
+
+        let denied = tool
+            .execute(json!({
+                "chat_id": 100,
+                "member": "alice",
+                "role": "owner",
+                "__rayclaw_auth": {"caller_chat_id": 200, "control_chat_ids": []}
+            }))
+            .await;
+        assert!(denied.content.contains("Permission denied"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,426 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde_json::json;
+
+use super::{authorize_chat_access, schema_object, Tool, ToolResult};
+use crate::channel::enforce_channel_policy;
+use crate::channel_adapter::ChannelRegistry;
+use crate::db::{call_blocking, Database};
+use crate::llm_types::ToolDefinition;
+use crate::url_monitor::{
+    describe_target, fetch_monitored_content, next_check_after, parse_selector,
+    DEFAULT_INTERVAL_MINS, MAX_INTERVAL_MINS, MIN_INTERVAL_MINS,
+};
+
+const MAX_MONITORS_PER_CHAT: usize = 20;
+
+fn validate_monitor_url(url: &str) -> Result<String, String> {
+    let parsed = reqwest::Url::parse(url.trim()).map_err(|e| format!("Invalid URL: {e}"))?;
+    match parsed.scheme() {
+        "http" | "https" => Ok(parsed.to_string()),
+        other => Err(format!(
+            "Unsupported URL scheme '{other}': only http and https are allowed"
+        )),
+    }
+}
+
+// --- monitor_url ---
+
+pub struct MonitorUrlTool {
+    registry: Arc<ChannelRegistry>,
+    db: Arc<Database>,
+}
+
+impl MonitorUrlTool {
+    pub fn new(registry: Arc<ChannelRegistry>, db: Arc<Database>) -> Self {
+        MonitorUrlTool { registry, db }
+    }
+}
+
+#[async_trait]
+impl Tool for MonitorUrlTool {
+    fn name(&self) -> &str {
+        "monitor_url"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "monitor_url".into(),
+            description: "Watch a webpage for changes. The page is fetched now to record a baseline, then re-checked in the background every interval; when its visible text (or the part matched by a CSS selector) changes, the chat gets a message listing removed and added lines. Use a selector (e.g. '.price', '#status') to ignore unrelated parts of the page.".into(),
+            input_schema: schema_object(
+                json!({
+                    "chat_id": {
+                        "type": "integer",
+                        "description": "The chat ID to notify on changes"
+                    },
+                    "url": {
+                        "type": "string",
+                        "description": "The http(s) URL to watch"
+                    },
+                    "selector": {
+                        "type": "string",
+                        "description": "Optional CSS selector; only text inside matching elements is compared"
+                    },
+                    "interval_minutes": {
+                        "type": "integer",
+                        "description": "Minutes between checks (5-10080, default: 60)"
+                    }
+                }),
+                &["chat_id", "url"],
+            ),
+        }
+    }
+
+    async fn execute(&self, input: serde_json::Value) -> ToolResult {
+        let chat_id = match input.get("chat_id").and_then(|v| v.as_i64()) {
+            Some(id) => id,
+            None => return ToolResult::error("Missing required parameter: chat_id".into()),
+        };
+        if let Err(e) = authorize_chat_access(&input, chat_id) {
+            return ToolResult::error(e);
+        }
+        if let Err(e) =
+            enforce_channel_policy(&self.registry, self.db.clone(), &input, chat_id).await
+        {
+            return ToolResult::error(e);
+        }
+        let url = match input.get("url").and_then(|v| v.as_str()) {
+            Some(u) => match validate_monitor_url(u) {
+                Ok(u) => u,
+                Err(e) => return ToolResult::error(e),
+            },
+            None => return ToolResult::error("Missing required parameter: url".into()),
+        };
+        let selector = input
+            .get("selector")
+            .and_then(|v| v.as_str())
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(str::to_string);
+        if let Some(selector) = &selector {
+            if let Err(e) = parse_selector(selector) {
+                return ToolResult::error(e);
+            }
+        }
+        let interval_mins = input
+            .get("interval_minutes")
+            .and_then(|v| v.as_i64())
+            .unwrap_or(DEFAULT_INTERVAL_MINS);
+        if !(MIN_INTERVAL_MINS..=MAX_INTERVAL_MINS).contains(&interval_mins) {
+            return ToolResult::error(format!(
+                "interval_minutes must be between {MIN_INTERVAL_MINS} and {MAX_INTERVAL_MINS}"
+            ));
+        }
+
+        match call_blocking(self.db.clone(), move |db| {
+            db.get_url_monitors_for_chat(chat_id)
+        })
+        .await
+        {
+            Ok(existing) if existing.len() >= MAX_MONITORS_PER_CHAT => {
+                return ToolResult::error(format!(
+                    "This chat already has {MAX_MONITORS_PER_CHAT} monitors; remove one first."
+                ));
+            }
+            Ok(_) => {}
+            Err(e) => return ToolResult::error(format!("Failed to load monitors: {e}")),
+        }
+
+        let baseline = match fetch_monitored_content(&url, selector.as_deref()).await {
+            Ok(text) => text,
+            Err(e) => {
+                return ToolResult::error(format!("Could not fetch a baseline for {url}: {e}"))
+            }
+        };
+
+        let next_check = next_check_after(chrono::Utc::now(), interval_mins);
+        let target = describe_target(&url, selector.as_deref());
+        let baseline_len = baseline.chars().count();
+        let next_check_owned = next_check.clone();
+        match call_blocking(self.db.clone(), move |db| {
+            db.create_url_monitor(
+                chat_id,
+                &url,
+                selector.as_deref(),
+                interval_mins,
+                &baseline,
+                &next_check_owned,
+            )
+        })
+        .await
+        {
+            Ok(id) => ToolResult::success(format!(
+                "Monitor #{id} watching {target} every {interval_mins} min (baseline: {baseline_len} chars). Next check: {next_check}"
+            )),
+            Err(e) => ToolResult::error(format!("Failed to create monitor: {e}")),
+        }
+    }
+}
+
+// --- list_url_monitors ---
+
+pub struct ListUrlMonitorsTool {
+    registry: Arc<ChannelRegistry>,
+    db: Arc<Database>,
+}
+
+impl ListUrlMonitorsTool {
+    pub fn new(registry: Arc<ChannelRegistry>, db: Arc<Database>) -> Self {
+        ListUrlMonitorsTool { registry, db }
+    }
+}
+
+#[async_trait]
+impl Tool for ListUrlMonitorsTool {
+    fn name(&self) -> &str {
+        "list_url_monitors"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "list_url_monitors".into(),
+            description: "List the webpages watched for changes in a chat, with their last change, next check and any error.".into(),
+            input_schema: schema_object(
+                json!({
+                    "chat_id": {
+                        "type": "integer",
+                        "description": "The chat ID to list monitors for"
+                    }
+                }),
+                &["chat_id"],
+            ),
+        }
+    }
+
+    async fn execute(&self, input: serde_json::Value) -> ToolResult {
+        let chat_id = match input.get("chat_id").and_then(|v| v.as_i64()) {
+            Some(id) => id,
+            None => return ToolResult::error("Missing required parameter: chat_id".into()),
+        };
+        if let Err(e) = authorize_chat_access(&input, chat_id) {
+            return ToolResult::error(e);
+        }
+        if let Err(e) =
+            enforce_channel_policy(&self.registry, self.db.clone(), &input, chat_id).await
+        {
+            return ToolResult::error(e);
+        }
+
+        match call_blocking(self.db.clone(), move |db| {
+            db.get_url_monitors_for_chat(chat_id)
+        })
+        .await
+        {
+            Ok(monitors) => {
+                if monitors.is_empty() {
+                    return ToolResult::success("No URL monitors found for this chat.".into());
+                }
+                let mut output = String::new();
+                for m in &monitors {
+                    output.push_str(&format!(
+                        "#{} [{}] {} | every {} min | changes: {} | last changed: {} | next: {}",
+                        m.id,
+                        m.status,
+                        describe_target(&m.url, m.selector.as_deref()),
+                        m.interval_mins,
+                        m.change_count,
+                        m.last_changed.as_deref().unwrap_or("never"),
+                        m.next_check
+                    ));
+                    if let Some(err) = &m.last_error {
+                        output.push_str(&format!(" | error: {err}"));
+                    }
+                    output.push('\n');
+                }
+                ToolResult::success(output)
+            }
+            Err(e) => ToolResult::error(format!("Failed to list monitors: {e}")),
+        }
+    }
+}
+
+// --- remove_url_monitor ---
+
+pub struct RemoveUrlMonitorTool {
+    registry: Arc<ChannelRegistry>,
+    db: Arc<Database>,
+}
+
+impl RemoveUrlMonitorTool {
+    pub fn new(registry: Arc<ChannelRegistry>, db: Arc<Database>) -> Self {
+        RemoveUrlMonitorTool { registry, db }
+    }
+}
+
+#[async_trait]
+impl Tool for RemoveUrlMonitorTool {
+    fn name(&self) -> &str {
+        "remove_url_monitor"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "remove_url_monitor".into(),
+            description: "Stop watching a webpage: delete the monitor, or pause/resume it to keep its history.".into(),
+            input_schema: schema_object(
+                json!({
+                    "monitor_id": {
+                        "type": "integer",
+                        "description": "The monitor ID"
+                    },
+                    "action": {
+                        "type": "string",
+                        "enum": ["delete", "pause", "resume"],
+                        "description": "What to do with the monitor (default: delete)"
+                    }
+                }),
+                &["monitor_id"],
+            ),
+        }
+    }
+
+    async fn execute(&self, input: serde_json::Value) -> ToolResult {
+        let monitor_id = match input.get("monitor_id").and_then(|v| v.as_i64()) {
+            Some(id) => id,
+            None => return ToolResult::error("Missing required parameter: monitor_id".into()),
+        };
+        let action = input
+            .get("action")
+            .and_then(|v| v.as_str())
+            .unwrap_or("delete")
+            .to_string();
+        if !matches!(action.as_str(), "delete" | "pause" | "resume") {
+            return ToolResult::error("action must be 'delete', 'pause' or 'resume'".into());
+        }
+        let monitor =
+            match call_blocking(self.db.clone(), move |db| db.get_url_monitor(monitor_id)).await {
+                Ok(Some(m)) => m,
+                Ok(None) => return ToolResult::error(format!("Monitor #{monitor_id} not found.")),
+                Err(e) => return ToolResult::error(format!("Failed to load monitor: {e}")),
+            };
+        if let Err(e) = authorize_chat_access(&input, monitor.chat_id) {
+            return ToolResult::error(e);
+        }
+        if let Err(e) =
+            enforce_channel_policy(&self.registry, self.db.clone(), &input, monitor.chat_id).await
+        {
+            return ToolResult::error(e);
+        }
+
+        let action_for_db = action.clone();
+        let result = call_blocking(self.db.clone(), move |db| match action_for_db.as_str() {
+            "pause" => db.update_url_monitor_status(monitor_id, "paused"),
+            "resume" => db.update_url_monitor_status(monitor_id, "active"),
+            _ => db.delete_url_monitor(monitor_id),
+        })
+        .await;
+        let done = match action.as_str() {
+            "pause" => "paused",
+            "resume" => "resumed",
+            _ => "removed",
+        };
+        match result {
+            Ok(true) => ToolResult::success(format!("Monitor #{monitor_id} {done}.")),
+            Ok(false) => ToolResult::error(format!("Monitor #{monitor_id} not found.")),
+            Err(e) => ToolResult::error(format!("Failed to update monitor: {e}")),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "web"))]
+mod tests {
+    use super::*;
+    use crate::web::WebAdapter;
+
+    fn test_registry() -> Arc<ChannelRegistry> {
+        let mut registry = ChannelRegistry::new();
+        registry.register(Arc::new(WebAdapter));
+        Arc::new(registry)
+    }
+
+    fn test_db() -> (Arc<Database>, std::path::PathBuf) {
+        let dir = std::env::temp_dir().join(format!("rayclaw_monitor_{}", uuid::Uuid::new_v4()));
+        let db = Arc::new(Database::new(dir.to_str().unwrap()).unwrap());
+        (db, dir)
+    }
+
+    fn cleanup(dir: &std::path::Path) {
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_validate_monitor_url() {
+        assert_eq!(
+            validate_monitor_url("https://example.com").unwrap(),
+            "https://example.com/"
+        );
+        assert!(validate_monitor_url("ftp://example.com").is_err());
+        assert!(validate_monitor_url("not a url").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_monitor_url_rejects_bad_input() {
+        let (db, dir) = test_db();
+        let tool = MonitorUrlTool::new(test_registry(), db);
+
+        let result = tool
+            .execute(json!({"chat_id": 1, "url": "file:///etc/passwd"}))
+            .await;
+        assert!(result.is_error);
+        assert!(result.content.contains("only http and https"));
+
+        let result = tool
+            .execute(json!({"chat_id": 1, "url": "https://example.com", "selector": "div["}))
+            .await;
+        assert!(result.is_error);
+        assert!(result.content.contains("Invalid CSS selector"));
+
+        let result = tool
+            .execute(json!({"chat_id": 1, "url": "https://example.com", "interval_minutes": 1}))
+            .await;
+        assert!(result.is_error);
+        assert!(result.content.contains("interval_minutes"));
+
+        cleanup(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_list_and_remove_monitors() {
+        let (db, dir) = test_db();
+        let registry = test_registry();
+        let id = db
+            .create_url_monitor(
+                42,
+                "https://example.com/",
+                Some(".price"),
+                30,
+                "$10",
+                "2099-01-01T00:00:00+00:00",
+            )
+            .unwrap();
+
+        let list = ListUrlMonitorsTool::new(registry.clone(), db.clone());
+        let result = list.execute(json!({"chat_id": 42})).await;
+        assert!(!result.is_error);
+        assert!(result
+            .content
+            .contains(&format!("#{id} [active] https://example.com/ (.price)")));
+
+        let remove = RemoveUrlMonitorTool::new(registry.clone(), db.clone());
+        let result = remove
+            .execute(json!({"monitor_id": id, "action": "pause"}))
+            .await;
+        assert!(!result.is_error, "{}", result.content);
+        assert_eq!(db.get_url_monitor(id).unwrap().unwrap().status, "paused");
+
+        let result = remove.execute(json!({"monitor_id": id})).await;
+        assert!(result.content.contains("removed"));
+        assert!(db.get_url_monitor(id).unwrap().is_none());
+
+        let result = list.execute(json!({"chat_id": 42})).await;
+        assert!(result.content.contains("No URL monitors"));
+
+        cleanup(&dir);
+    }
+}
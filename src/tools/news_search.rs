@@ -0,0 +1,474 @@
+//! `news_search`: recent articles from a news vertical rather than general
+//! web results, each with its source and publication date.
+//!
+//! Providers are Brave News and Bing News (both need an API key) and the
+//! GDELT DOC API (free, covers the last three months). Unless
+//! `news_search.provider` picks one, keyed providers are tried first and
+//! GDELT is the fallback.
+
+use std::sync::OnceLock;
+
+use async_trait::async_trait;
+use serde_json::json;
+use tracing::warn;
+
+use super::{schema_object, Tool, ToolResult};
+use crate::config::{Config, NewsSearchConfig};
+use crate::llm_types::ToolDefinition;
+use crate::outbound_http::{self, OutboundClient};
+
+const DEFAULT_MAX_RESULTS: usize = 10;
+const MAX_RESULTS: usize = 20;
+
+fn http_client() -> &'static OutboundClient {
+    static CLIENT: OnceLock<OutboundClient> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        outbound_http::client_builder("news_search", "RayClaw/1.0")
+            .timeout(std::time::Duration::from_secs(15))
+            .build()
+            .expect("failed to build HTTP client")
+            .into()
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TimeRange {
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+impl TimeRange {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "day" => Some(TimeRange::Day),
+            "week" => Some(TimeRange::Week),
+            "month" => Some(TimeRange::Month),
+            "year" => Some(TimeRange::Year),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum NewsProvider {
+    Brave,
+    Bing,
+    Gdelt,
+}
+
+impl NewsProvider {
+    fn name(self) -> &'static str {
+        match self {
+            NewsProvider::Brave => "Brave News",
+            NewsProvider::Bing => "Bing News",
+            NewsProvider::Gdelt => "GDELT",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct NewsItem {
+    title: String,
+    url: String,
+    source: String,
+    /// `YYYY-MM-DD`
+    published: Option<String>,
+    summary: String,
+}
+
+pub struct NewsSearchTool {
+    config: NewsSearchConfig,
+}
+
+impl NewsSearchTool {
+    pub fn new(config: &Config) -> Self {
+        NewsSearchTool {
+            config: config.news_search.clone(),
+        }
+    }
+
+    /// Providers to try, in order.
+    fn providers(&self) -> Vec<NewsProvider> {
+        match self.config.provider.as_deref() {
+            Some("brave") => vec![NewsProvider::Brave],
+            Some("bing") => vec![NewsProvider::Bing],
+            Some("gdelt") => vec![NewsProvider::Gdelt],
+            _ => {
+                let mut providers = Vec::new();
+                if self.config.brave_api_key.is_some() {
+                    providers.push(NewsProvider::Brave);
+                }
+                if self.config.bing_api_key.is_some() {
+                    providers.push(NewsProvider::Bing);
+                }
+                providers.push(NewsProvider::Gdelt);
+                providers
+            }
+        }
+    }
+
+    async fn search(
+        &self,
+        provider: NewsProvider,
+        query: &str,
+        range: Option<TimeRange>,
+        count: usize,
+    ) -> Result<Vec<NewsItem>, String> {
+        let key = |key: &Option<String>| key.clone().unwrap_or_default();
+        match provider {
+            NewsProvider::Brave => {
+                let mut request = http_client()
+                    .get("https://api.search.brave.com/res/v1/news/search")
+                    .header("X-Subscription-Token", key(&self.config.brave_api_key))
+                    .header("Accept", "application/json")
+                    .query(&[("q", query), ("count", &count.to_string())]);
+                if let Some(range) = range {
+                    let freshness = match range {
+                        TimeRange::Day => "pd",
+                        TimeRange::Week => "pw",
+                        TimeRange::Month => "pm",
+                        TimeRange::Year => "py",
+                    };
+                    request = request.query(&[("freshness", freshness)]);
+                }
+                Ok(parse_brave(&fetch_json(request).await?))
+            }
+            NewsProvider::Bing => {
+                let mut request = http_client()
+                    .get("https://api.bing.microsoft.com/v7.0/news/search")
+                    .header("Ocp-Apim-Subscription-Key", key(&self.config.bing_api_key))
+                    .query(&[
+                        ("q", query),
+                        ("count", &count.to_string()),
+                        ("sortBy", "Date"),
+                        ("textFormat", "Raw"),
+                    ]);
+                // Bing has no yearly freshness; its default window is wider
+                let freshness = match range {
+                    Some(TimeRange::Day) => Some("Day"),
+                    Some(TimeRange::Week) => Some("Week"),
+                    Some(TimeRange::Month) => Some("Month"),
+                    Some(TimeRange::Year) | None => None,
+                };
+                if let Some(freshness) = freshness {
+                    request = request.query(&[("freshness", freshness)]);
+                }
+                Ok(parse_bing(&fetch_json(request).await?))
+            }
+            NewsProvider::Gdelt => {
+                // GDELT only keeps the last three months
+                let timespan = match range {
+                    Some(TimeRange::Day) => "1d",
+                    Some(TimeRange::Week) => "1w",
+                    Some(TimeRange::Month) => "1m",
+                    Some(TimeRange::Year) | None => "3m",
+                };
+                let request = http_client()
+                    .get("https://api.gdeltproject.org/api/v2/doc/doc")
+                    .query(&[
+                        ("query", query),
+                        ("mode", "ArtList"),
+                        ("format", "json"),
+                        ("sort", "DateDesc"),
+                        ("timespan", timespan),
+                        ("maxrecords", &count.to_string()),
+                    ]);
+                Ok(parse_gdelt(&fetch_json(request).await?))
+            }
+        }
+    }
+}
+
+async fn fetch_json(request: reqwest::RequestBuilder) -> Result<serde_json::Value, String> {
+    let response = request.send().await.map_err(|e| e.to_string())?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(format!("HTTP {status}"));
+    }
+    let text = response.text().await.map_err(|e| e.to_string())?;
+    // GDELT answers query errors with a plain-text message and 200
+    serde_json::from_str(&text).map_err(|_| {
+        let message = crate::text::floor_char_boundary(&text, 200);
+        format!("unexpected response: {}", text[..message].trim())
+    })
+}
+
+/// `YYYY-MM-DD` from the timestamp formats the providers use
+/// (RFC 3339-ish, or GDELT's `20241120T101500Z`).
+fn news_date(value: &str) -> Option<String> {
+    let value = value.trim();
+    if let Some(date) = value
+        .get(..10)
+        .and_then(|d| chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+    {
+        return Some(date.to_string());
+    }
+    value
+        .get(..8)
+        .and_then(|d| chrono::NaiveDate::parse_from_str(d, "%Y%m%d").ok())
+        .map(|date| date.to_string())
+}
+
+fn str_field<'a>(value: &'a serde_json::Value, pointer: &str) -> &'a str {
+    value
+        .pointer(pointer)
+        .and_then(|v| v.as_str())
+        .unwrap_or("")
+}
+
+fn parse_brave(body: &serde_json::Value) -> Vec<NewsItem> {
+    let results = body["results"].as_array().cloned().unwrap_or_default();
+    results
+        .iter()
+        .filter_map(|r| {
+            let url = str_field(r, "/url");
+            let title = str_field(r, "/title");
+            if url.is_empty() || title.is_empty() {
+                return None;
+            }
+            let source = match str_field(r, "/meta_url/hostname") {
+                "" => str_field(r, "/source"),
+                host => host,
+            };
+            Some(NewsItem {
+                title: title.to_string(),
+                url: url.to_string(),
+                source: source.trim_start_matches("www.").to_string(),
+                published: news_date(str_field(r, "/page_age")),
+                summary: str_field(r, "/description").to_string(),
+            })
+        })
+        .collect()
+}
+
+fn parse_bing(body: &serde_json::Value) -> Vec<NewsItem> {
+    let results = body["value"].as_array().cloned().unwrap_or_default();
+    results
+        .iter()
+        .filter_map(|r| {
+            let url = str_field(r, "/url");
+            let title = str_field(r, "/name");
+            if url.is_empty() || title.is_empty() {
+                return None;
+            }
+            Some(NewsItem {
+                title: title.to_string(),
+                url: url.to_string(),
+                source: str_field(r, "/provider/0/name").to_string(),
+                published: news_date(str_field(r, "/datePublished")),
+                summary: str_field(r, "/description").to_string(),
+            })
+        })
+        .collect()
+}
+
+fn parse_gdelt(body: &serde_json::Value) -> Vec<NewsItem> {
+    let articles = body["articles"].as_array().cloned().unwrap_or_default();
+    articles
+        .iter()
+        .filter_map(|a| {
+            let url = str_field(a, "/url");
+            let title = str_field(a, "/title");
+            if url.is_empty() || title.is_empty() {
+                return None;
+            }
+            Some(NewsItem {
+                title: title.to_string(),
+                url: url.to_string(),
+                source: str_field(a, "/domain").to_string(),
+                published: news_date(str_field(a, "/seendate")),
+                summary: String::new(),
+            })
+        })
+        .collect()
+}
+
+fn format_news(items: &[NewsItem], provider: NewsProvider) -> String {
+    let mut output = format!("News results from {}:\n\n", provider.name());
+    for (i, item) in items.iter().enumerate() {
+        let mut byline = vec![];
+        if !item.source.is_empty() {
+            byline.push(item.source.as_str());
+        }
+        if let Some(date) = &item.published {
+            byline.push(date.as_str());
+        }
+        let byline = if byline.is_empty() {
+            String::new()
+        } else {
+            format!(" ({})", byline.join(", "))
+        };
+        output.push_str(&format!(
+            "{}. {}{}\n   {}\n",
+            i + 1,
+            item.title,
+            byline,
+            item.url
+        ));
+        if !item.summary.is_empty() {
+            output.push_str(&format!("   {}\n", item.summary));
+        }
+        output.push('\n');
+    }
+    output
+}
+
+#[async_trait]
+impl Tool for NewsSearchTool {
+    fn name(&self) -> &str {
+        "news_search"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "news_search".into(),
+            description: "Search recent news articles. Returns headlines with their source, publication date and URL, newest first. Prefer this over web_search for news, current events and scheduled news digests.".into(),
+            input_schema: schema_object(
+                json!({
+                    "query": {
+                        "type": "string",
+                        "description": "Topic, keywords or names to find news about"
+                    },
+                    "time_range": {
+                        "type": "string",
+                        "enum": ["day", "week", "month", "year"],
+                        "description": "Only return articles published within this period"
+                    },
+                    "max_results": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "maximum": MAX_RESULTS,
+                        "description": format!("Number of articles to return (default {DEFAULT_MAX_RESULTS})")
+                    }
+                }),
+                &["query"],
+            ),
+        }
+    }
+
+    async fn execute(&self, input: serde_json::Value) -> ToolResult {
+        let query = match input.get("query").and_then(|v| v.as_str()) {
+            Some(q) if !q.trim().is_empty() => q.trim(),
+            _ => return ToolResult::error("Missing required parameter: query".into()),
+        };
+        let range = match input.get("time_range").and_then(|v| v.as_str()) {
+            None => None,
+            Some(value) => match TimeRange::parse(value) {
+                Some(range) => Some(range),
+                None => {
+                    return ToolResult::error(format!(
+                        "Invalid time_range: {value} (expected day, week, month or year)"
+                    ))
+                }
+            },
+        };
+        let count = input
+            .get("max_results")
+            .and_then(|v| v.as_u64())
+            .map_or(DEFAULT_MAX_RESULTS, |n| n as usize)
+            .clamp(1, MAX_RESULTS);
+
+        let mut failures = Vec::new();
+        for provider in self.providers() {
+            match self.search(provider, query, range, count).await {
+                Ok(items) if items.is_empty() => {
+                    return ToolResult::success(format!(
+                        "No news found for \"{query}\" ({}).",
+                        provider.name()
+                    ))
+                }
+                Ok(mut items) => {
+                    items.truncate(count);
+                    return ToolResult::success(format_news(&items, provider))
+                        .with_suggested_next_tools(&["web_fetch"]);
+                }
+                Err(e) => {
+                    warn!("news_search: {} failed: {e}", provider.name());
+                    failures.push(format!("{}: {e}", provider.name()));
+                }
+            }
+        }
+        ToolResult::error(format!("News search failed: {}", failures.join("; ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tool(provider: Option<&str>, brave: bool, bing: bool) -> NewsSearchTool {
+        NewsSearchTool {
+            config: NewsSearchConfig {
+                provider: provider.map(str::to_string),
+                brave_api_key: brave.then(|| "brave-key".to_string()),
+                bing_api_key: bing.then(|| "bing-key".to_string()),
+            },
+        }
+    }
+
+    #[test]
+    fn test_provider_order() {
+        assert_eq!(
+            tool(None, false, false).providers(),
+            vec![NewsProvider::Gdelt]
+        );
+        assert_eq!(
+            tool(None, true, true).providers(),
+            vec![NewsProvider::Brave, NewsProvider::Bing, NewsProvider::Gdelt]
+        );
+        assert_eq!(
+            tool(Some("bing"), true, true).providers(),
+            vec![NewsProvider::Bing]
+        );
+    }
+
+    #[test]
+    fn test_parse_provider_responses() {
+        let brave = json!({"results": [
+            {"title": "Rust 1.83 released", "url": "https://blog.rust-lang.org/2024/11/28/Rust-1.83.0.html",
+             "description": "The Rust team is happy to announce...", "page_age": "2024-11-28T00:00:00",
+             "meta_url": {"hostname": "www.blog.rust-lang.org"}},
+            {"title": "", "url": "https://example.com"}
+        ]});
+        let bing = json!({"value": [
+            {"name": "Markets rally", "url": "https://news.example/markets",
+             "description": "Stocks rose.", "datePublished": "2024-11-20T14:05:00.0000000Z",
+             "provider": [{"name": "Example News"}]}
+        ]});
+        let gdelt = json!({"articles": [
+            {"url": "https://paper.example/a", "title": "Election results", "seendate": "20241106T031500Z",
+             "domain": "paper.example", "language": "English"}
+        ]});
+
+        let items = parse_brave(&brave);
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].source, "blog.rust-lang.org");
+        assert_eq!(items[0].published.as_deref(), Some("2024-11-28"));
+        let items = parse_bing(&bing);
+        assert_eq!(items[0].source, "Example News");
+        assert_eq!(items[0].published.as_deref(), Some("2024-11-20"));
+        let items = parse_gdelt(&gdelt);
+        assert_eq!(items[0].source, "paper.example");
+        assert_eq!(items[0].published.as_deref(), Some("2024-11-06"));
+
+        assert_eq!(
+            format_news(&items, NewsProvider::Gdelt),
+            "News results from GDELT:\n\n1. Election results (paper.example, 2024-11-06)\n   https://paper.example/a\n\n"
+        );
+        assert!(parse_gdelt(&json!({})).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_news_search_rejects_bad_input() {
+        let tool = tool(None, false, false);
+        let result = tool.execute(json!({"query": " "})).await;
+        assert!(result.is_error);
+        let result = tool
+            .execute(json!({"query": "rust", "time_range": "decade"}))
+            .await;
+        assert!(result.is_error);
+        assert!(result.content.contains("Invalid time_range"));
+    }
+}
@@ -0,0 +1,539 @@
+//! `track_package`: status and checkpoint history of a parcel from its
+//! tracking number, through the 17TRACK or AfterShip APIs.
+//!
+//! Both services detect the carrier from the number and need a number to be
+//! registered before they report on it, so a first lookup can come back
+//! without events while the carrier is queried.
+
+use std::sync::OnceLock;
+
+use async_trait::async_trait;
+use serde_json::json;
+use tracing::warn;
+
+use super::{fetch_json, schema_object, Tool, ToolResult};
+use crate::config::{Config, PackageTrackingConfig};
+use crate::llm_types::ToolDefinition;
+use crate::outbound_http::{self, OutboundClient};
+
+const TRACK17_API: &str = "https://api.17track.net/track/v2.2";
+const AFTERSHIP_API: &str = "https://api.aftership.com/tracking/2024-04";
+/// 17TRACK's rejection code for numbers registered earlier
+const TRACK17_ALREADY_REGISTERED: i64 = -18019901;
+/// AfterShip's meta code for trackings created earlier
+const AFTERSHIP_ALREADY_EXISTS: i64 = 4003;
+const MAX_EVENTS: usize = 10;
+
+fn http_client() -> &'static OutboundClient {
+    static CLIENT: OnceLock<OutboundClient> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        outbound_http::client_builder("package_tracking", "RayClaw/1.0")
+            .timeout(std::time::Duration::from_secs(20))
+            .build()
+            .expect("failed to build HTTP client")
+            .into()
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TrackingProvider {
+    Track17,
+    AfterShip,
+}
+
+impl TrackingProvider {
+    fn name(self) -> &'static str {
+        match self {
+            TrackingProvider::Track17 => "17TRACK",
+            TrackingProvider::AfterShip => "AfterShip",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct TrackingEvent {
+    time: String,
+    location: String,
+    description: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Shipment {
+    number: String,
+    carrier: Option<String>,
+    /// Human-readable, e.g. "In transit"
+    status: String,
+    estimated_delivery: Option<String>,
+    /// Newest first
+    events: Vec<TrackingEvent>,
+}
+
+/// `InTransit` / `OutForDelivery` -> `In transit` / `Out for delivery`.
+fn humanize_status(status: &str) -> String {
+    let mut output = String::new();
+    for (i, c) in status.chars().enumerate() {
+        if i > 0 && c.is_ascii_uppercase() {
+            output.push(' ');
+            output.push(c.to_ascii_lowercase());
+        } else {
+            output.push(c);
+        }
+    }
+    output
+}
+
+/// `YYYY-MM-DD HH:MM` in the event's own timezone; other formats unchanged.
+fn event_time(value: &str) -> String {
+    match chrono::DateTime::parse_from_rfc3339(value) {
+        Ok(time) => time.format("%Y-%m-%d %H:%M").to_string(),
+        Err(_) => value.replace('T', " "),
+    }
+}
+
+fn str_field<'a>(value: &'a serde_json::Value, pointer: &str) -> &'a str {
+    value
+        .pointer(pointer)
+        .and_then(|v| v.as_str())
+        .unwrap_or("")
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+/// Tracking numbers are letters, digits and the odd dash.
+fn valid_tracking_number(number: &str) -> bool {
+    (5..=40).contains(&number.len())
+        && number
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+/// Rejection message from a 17TRACK response, unless it is `ignored_code`.
+fn track17_rejection(body: &serde_json::Value, ignored_code: Option<i64>) -> Option<String> {
+    let error = body.pointer("/data/rejected/0/error")?;
+    if error["code"].as_i64() == ignored_code && ignored_code.is_some() {
+        return None;
+    }
+    Some(non_empty(str_field(error, "/message")).unwrap_or_else(|| error["code"].to_string()))
+}
+
+/// First accepted entry of a 17TRACK `gettrackinfo` response.
+fn parse_track17(body: &serde_json::Value) -> Option<Shipment> {
+    let accepted = body.pointer("/data/accepted/0")?;
+    let info = &accepted["track_info"];
+    let provider = info.pointer("/tracking/providers/0");
+    let events = provider
+        .and_then(|p| p["events"].as_array())
+        .into_iter()
+        .flatten()
+        .map(|e| TrackingEvent {
+            time: event_time(str_field(e, "/time_iso")),
+            location: str_field(e, "/location").trim().to_string(),
+            description: str_field(e, "/description").trim().to_string(),
+        })
+        .collect();
+    let estimated_delivery = non_empty(str_field(info, "/time_metrics/estimated_delivery_date/to"))
+        .or_else(|| {
+            non_empty(str_field(
+                info,
+                "/time_metrics/estimated_delivery_date/from",
+            ))
+        })
+        .map(|date| event_time(&date));
+    Some(Shipment {
+        number: str_field(accepted, "/number").to_string(),
+        carrier: provider.and_then(|p| non_empty(str_field(p, "/provider/name"))),
+        status: humanize_status(
+            non_empty(str_field(info, "/latest_status/status"))
+                .as_deref()
+                .unwrap_or("NotFound"),
+        ),
+        estimated_delivery,
+        events,
+    })
+}
+
+/// AfterShip tracking object, from a create (`data`) or list
+/// (`data.trackings[0]`) response.
+fn parse_aftership(body: &serde_json::Value) -> Option<Shipment> {
+    let tracking = match body.pointer("/data/trackings") {
+        Some(list) => list.get(0)?,
+        None => body.pointer("/data/tracking").or(body.get("data"))?,
+    };
+    let number = non_empty(str_field(tracking, "/tracking_number"))?;
+    let mut events: Vec<TrackingEvent> = tracking["checkpoints"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|c| {
+            let location = non_empty(str_field(c, "/location")).unwrap_or_else(|| {
+                [str_field(c, "/city"), str_field(c, "/country_region")]
+                    .iter()
+                    .filter(|part| !part.is_empty())
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            });
+            TrackingEvent {
+                time: event_time(str_field(c, "/checkpoint_time")),
+                location,
+                description: str_field(c, "/message").trim().to_string(),
+            }
+        })
+        .collect();
+    // Checkpoints are listed oldest first
+    events.reverse();
+    let status = match non_empty(str_field(tracking, "/subtag_message")) {
+        Some(message) => message,
+        None => humanize_status(
+            non_empty(str_field(tracking, "/tag"))
+                .as_deref()
+                .unwrap_or("Pending"),
+        ),
+    };
+    Some(Shipment {
+        number,
+        carrier: non_empty(str_field(tracking, "/slug")),
+        status,
+        estimated_delivery: non_empty(str_field(
+            tracking,
+            "/courier_estimated_delivery_date/estimated_delivery_date",
+        ))
+        .or_else(|| non_empty(str_field(tracking, "/expected_delivery"))),
+        events,
+    })
+}
+
+fn format_shipment(shipment: &Shipment, provider: TrackingProvider) -> String {
+    let carrier = match &shipment.carrier {
+        Some(carrier) => format!(" ({carrier})"),
+        None => String::new(),
+    };
+    let mut output = format!(
+        "Package {}{carrier}: {}\n",
+        shipment.number, shipment.status
+    );
+    if let Some(date) = &shipment.estimated_delivery {
+        output.push_str(&format!("Estimated delivery: {date}\n"));
+    }
+    if shipment.events.is_empty() {
+        output.push_str(
+            "No tracking events yet; the carrier may not have scanned the parcel, or the number was only just registered. Try again later.\n",
+        );
+    } else {
+        output.push_str("\nLatest events:\n");
+        for event in shipment.events.iter().take(MAX_EVENTS) {
+            let location = if event.location.is_empty() {
+                String::new()
+            } else {
+                format!(" {}:", event.location)
+            };
+            output.push_str(&format!(
+                "- {}{location} {}\n",
+                event.time, event.description
+            ));
+        }
+        if shipment.events.len() > MAX_EVENTS {
+            output.push_str(&format!(
+                "... {} earlier events\n",
+                shipment.events.len() - MAX_EVENTS
+            ));
+        }
+    }
+    output.push_str(&format!("\nSource: {}\n", provider.name()));
+    output
+}
+
+pub struct TrackPackageTool {
+    config: PackageTrackingConfig,
+}
+
+impl TrackPackageTool {
+    pub fn new(config: &Config) -> Self {
+        TrackPackageTool {
+            config: config.package_tracking.clone(),
+        }
+    }
+
+    /// Providers to try, in order.
+    fn providers(&self) -> Vec<TrackingProvider> {
+        match self.config.provider.as_deref() {
+            Some("17track") => vec![TrackingProvider::Track17],
+            Some("aftership") => vec![TrackingProvider::AfterShip],
+            _ => {
+                let mut providers = Vec::new();
+                if self.config.track17_api_key.is_some() {
+                    providers.push(TrackingProvider::Track17);
+                }
+                if self.config.aftership_api_key.is_some() {
+                    providers.push(TrackingProvider::AfterShip);
+                }
+                providers
+            }
+        }
+    }
+
+    async fn track(
+        &self,
+        provider: TrackingProvider,
+        number: &str,
+        carrier: Option<&str>,
+    ) -> Result<Option<Shipment>, String> {
+        match provider {
+            TrackingProvider::Track17 => {
+                let key = self.config.track17_api_key.as_deref().unwrap_or_default();
+                let mut entry = json!({ "number": number });
+                // 17TRACK carrier codes are numeric; names are auto-detected
+                if let Some(code) = carrier.and_then(|c| c.parse::<u64>().ok()) {
+                    entry["carrier"] = json!(code);
+                }
+                let registered = fetch_json(
+                    http_client()
+                        .post(format!("{TRACK17_API}/register"))
+                        .header("17token", key)
+                        .json(&json!([entry])),
+                )
+                .await?;
+                // Request-level failures (bad token, quota) carry data.errors
+                if registered["code"].as_i64() != Some(0) {
+                    return Err(non_empty(str_field(&registered, "/data/errors/0/message"))
+                        .unwrap_or_else(|| format!("error code {}", registered["code"])));
+                }
+                if let Some(message) =
+                    track17_rejection(&registered, Some(TRACK17_ALREADY_REGISTERED))
+                {
+                    return Err(message);
+                }
+                let info = fetch_json(
+                    http_client()
+                        .post(format!("{TRACK17_API}/gettrackinfo"))
+                        .header("17token", key)
+                        .json(&json!([{ "number": number }])),
+                )
+                .await?;
+                match parse_track17(&info) {
+                    Some(shipment) => Ok(Some(shipment)),
+                    // Just registered: 17TRACK has not queried the carrier yet
+                    None if track17_rejection(&info, None).is_some() => Ok(Some(Shipment {
+                        number: number.to_string(),
+                        carrier: None,
+                        status: "Registered".to_string(),
+                        estimated_delivery: None,
+                        events: Vec::new(),
+                    })),
+                    None => Ok(None),
+                }
+            }
+            TrackingProvider::AfterShip => {
+                let key = self.config.aftership_api_key.as_deref().unwrap_or_default();
+                let mut body = json!({ "tracking_number": number });
+                if let Some(slug) = carrier {
+                    body["slug"] = json!(slug.to_lowercase());
+                }
+                let created = fetch_json(
+                    http_client()
+                        .post(format!("{AFTERSHIP_API}/trackings"))
+                        .header("as-api-key", key)
+                        .json(&body),
+                )
+                .await?;
+                let code = created.pointer("/meta/code").and_then(|c| c.as_i64());
+                let body = match code {
+                    Some(200) | Some(201) => created,
+                    Some(AFTERSHIP_ALREADY_EXISTS) => {
+                        fetch_json(
+                            http_client()
+                                .get(format!("{AFTERSHIP_API}/trackings"))
+                                .header("as-api-key", key)
+                                .query(&[("tracking_numbers", number)]),
+                        )
+                        .await?
+                    }
+                    _ => {
+                        return Err(non_empty(str_field(&created, "/meta/message"))
+                            .unwrap_or_else(|| format!("unexpected response code {code:?}")))
+                    }
+                };
+                Ok(parse_aftership(&body))
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Tool for TrackPackageTool {
+    fn name(&self) -> &str {
+        "track_package"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "track_package".into(),
+            description: "Track a parcel by its tracking number. Returns the delivery status, estimated delivery date and latest carrier scan events. The carrier is detected automatically.".into(),
+            input_schema: schema_object(
+                json!({
+                    "tracking_number": {
+                        "type": "string",
+                        "description": "The parcel's tracking number"
+                    },
+                    "carrier": {
+                        "type": "string",
+                        "description": "Carrier code if detection picks the wrong one (AfterShip slug such as \"ups\" or \"dhl\", or a numeric 17TRACK carrier code)"
+                    }
+                }),
+                &["tracking_number"],
+            ),
+        }
+    }
+
+    async fn execute(&self, input: serde_json::Value) -> ToolResult {
+        let number = match input.get("tracking_number").and_then(|v| v.as_str()) {
+            Some(n) if !n.trim().is_empty() => n.trim().replace(' ', "").to_uppercase(),
+            _ => return ToolResult::error("Missing required parameter: tracking_number".into()),
+        };
+        if !valid_tracking_number(&number) {
+            return ToolResult::error(format!("Invalid tracking number: {number}"));
+        }
+        let carrier = input
+            .get("carrier")
+            .and_then(|v| v.as_str())
+            .map(str::trim)
+            .filter(|c| !c.is_empty());
+
+        let providers = self.providers();
+        if providers.is_empty() {
+            return ToolResult::error(
+                "Package tracking is not configured: set package_tracking.track17_api_key or package_tracking.aftership_api_key".into(),
+            );
+        }
+        let mut failures = Vec::new();
+        for provider in providers {
+            match self.track(provider, &number, carrier).await {
+                Ok(Some(shipment)) => {
+                    return ToolResult::success(format_shipment(&shipment, provider))
+                }
+                Ok(None) => {
+                    return ToolResult::success(format!(
+                        "No tracking information found for {number} ({}).",
+                        provider.name()
+                    ))
+                }
+                Err(e) => {
+                    warn!("track_package: {} failed: {e}", provider.name());
+                    failures.push(format!("{}: {e}", provider.name()));
+                }
+            }
+        }
+        ToolResult::error(format!("Package tracking failed: {}", failures.join("; ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tool(provider: Option<&str>, track17: bool, aftership: bool) -> TrackPackageTool {
+        TrackPackageTool {
+            config: PackageTrackingConfig {
+                provider: provider.map(str::to_string),
+                track17_api_key: track17.then(|| "17-key".to_string()),
+                aftership_api_key: aftership.then(|| "as-key".to_string()),
+            },
+        }
+    }
+
+    #[test]
+    fn test_provider_order() {
+        assert!(tool(None, false, false).providers().is_empty());
+        assert_eq!(
+            tool(None, true, true).providers(),
+            vec![TrackingProvider::Track17, TrackingProvider::AfterShip]
+        );
+        assert_eq!(
+            tool(Some("aftership"), true, true).providers(),
+            vec![TrackingProvider::AfterShip]
+        );
+    }
+
+    #[test]
+    fn test_tracking_number_and_status() {
+        assert!(valid_tracking_number("1Z999AA10123456784"));
+        assert!(valid_tracking_number("RR123456789CN"));
+        assert!(!valid_tracking_number("1234"));
+        assert!(!valid_tracking_number("abc/../def"));
+        assert_eq!(humanize_status("OutForDelivery"), "Out for delivery");
+        assert_eq!(humanize_status("Delivered"), "Delivered");
+        assert_eq!(event_time("2026-10-16T09:12:00+02:00"), "2026-10-16 09:12");
+        assert_eq!(event_time("2026-10-16"), "2026-10-16");
+    }
+
+    #[test]
+    fn test_parse_track17() {
+        let body = json!({"code": 0, "data": {"accepted": [{
+            "number": "RR123456789CN",
+            "track_info": {
+                "latest_status": {"status": "InTransit"},
+                "time_metrics": {"estimated_delivery_date": {"from": "2026-10-18", "to": null}},
+                "tracking": {"providers": [{
+                    "provider": {"name": "China Post"},
+                    "events": [
+                        {"time_iso": "2026-10-15T20:01:00+08:00", "location": "Guangzhou", "description": "Departed from facility"},
+                        {"time_iso": "2026-10-14T08:30:00+08:00", "location": "", "description": "Accepted"}
+                    ]
+                }]}
+            }
+        }], "rejected": []}});
+        let shipment = parse_track17(&body).unwrap();
+        assert_eq!(shipment.carrier.as_deref(), Some("China Post"));
+        assert_eq!(shipment.status, "In transit");
+        assert_eq!(shipment.estimated_delivery.as_deref(), Some("2026-10-18"));
+        assert_eq!(
+            format_shipment(&shipment, TrackingProvider::Track17),
+            "Package RR123456789CN (China Post): In transit\nEstimated delivery: 2026-10-18\n\nLatest events:\n- 2026-10-15 20:01 Guangzhou: Departed from facility\n- 2026-10-14 08:30 Accepted\n\nSource: 17TRACK\n"
+        );
+
+        let pending = json!({"code": 0, "data": {"accepted": [], "rejected": [
+            {"number": "RR123456789CN", "error": {"code": -18019909, "message": "No tracking information at this time."}}
+        ]}});
+        assert!(parse_track17(&pending).is_none());
+        assert_eq!(
+            track17_rejection(&pending, Some(TRACK17_ALREADY_REGISTERED)).as_deref(),
+            Some("No tracking information at this time.")
+        );
+        let registered = json!({"code": 0, "data": {"accepted": [], "rejected": [
+            {"number": "RR123456789CN", "error": {"code": TRACK17_ALREADY_REGISTERED, "message": "already registered"}}
+        ]}});
+        assert!(track17_rejection(&registered, Some(TRACK17_ALREADY_REGISTERED)).is_none());
+    }
+
+    #[test]
+    fn test_parse_aftership() {
+        let body = json!({"meta": {"code": 200}, "data": {"trackings": [{
+            "tracking_number": "1Z999AA10123456784",
+            "slug": "ups",
+            "tag": "OutForDelivery",
+            "subtag_message": "",
+            "courier_estimated_delivery_date": {"estimated_delivery_date": "2026-10-16"},
+            "checkpoints": [
+                {"checkpoint_time": "2026-10-15T18:00:00-04:00", "city": "Louisville", "country_region": "USA", "message": "Arrived at facility"},
+                {"checkpoint_time": "2026-10-16T07:45:00-04:00", "location": "Springfield, MA", "message": "Out for delivery"}
+            ]
+        }]}});
+        let shipment = parse_aftership(&body).unwrap();
+        assert_eq!(shipment.status, "Out for delivery");
+        assert_eq!(shipment.carrier.as_deref(), Some("ups"));
+        assert_eq!(shipment.events[0].location, "Springfield, MA");
+        assert_eq!(shipment.events[1].location, "Louisville, USA");
+
+        let created = json!({"meta": {"code": 201}, "data": {
+            "tracking_number": "1Z999AA10123456784", "tag": "Pending", "checkpoints": []
+        }});
+        let shipment = parse_aftership(&created).unwrap();
+        assert_eq!(shipment.status, "Pending");
+        assert!(format_shipment(&shipment, TrackingProvider::AfterShip)
+            .contains("No tracking events yet"));
+        assert!(parse_aftership(&json!({"data": {"trackings": []}})).is_none());
+    }
+}
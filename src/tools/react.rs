@@ -0,0 +1,190 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde_json::json;
+
+use super::{authorize_chat_access, schema_object, Tool, ToolResult};
+use crate::channel::react_to_message;
+use crate::channel_adapter::ChannelRegistry;
+use crate::db::{call_blocking, Database};
+use crate::llm_types::ToolDefinition;
+
+/// How far back to look for the latest user message when no ID is given.
+const RECENT_MESSAGE_WINDOW: usize = 50;
+
+pub struct ReactToMessageTool {
+    registry: Arc<ChannelRegistry>,
+    db: Arc<Database>,
+}
+
+impl ReactToMessageTool {
+    pub fn new(registry: Arc<ChannelRegistry>, db: Arc<Database>) -> Self {
+        ReactToMessageTool { registry, db }
+    }
+
+    /// ID of the newest message in the chat that didn't come from the bot.
+    async fn latest_user_message_id(&self, chat_id: i64) -> Result<Option<String>, String> {
+        let messages = call_blocking(self.db.clone(), move |db| {
+            db.get_recent_messages(chat_id, RECENT_MESSAGE_WINDOW)
+        })
+        .await
+        .map_err(|e| format!("Failed to load recent messages: {e}"))?;
+        Ok(messages
+            .into_iter()
+            .rev()
+            .find(|m| !m.is_from_bot)
+            .map(|m| m.id))
+    }
+}
+
+#[async_trait]
+impl Tool for ReactToMessageTool {
+    fn name(&self) -> &str {
+        "react_to_message"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "react_to_message".into(),
+            description: "Add an emoji reaction to a message — a lightweight acknowledgement \
+                (e.g. 👀 while working, ✅ when done) instead of a reply. Reacts to the latest \
+                user message unless message_id is given. Supported on Telegram, Discord, Slack \
+                and Feishu; Telegram only accepts its own reaction set."
+                .into(),
+            input_schema: schema_object(
+                json!({
+                    "chat_id": {
+                        "type": "integer",
+                        "description": "The target chat ID"
+                    },
+                    "emoji": {
+                        "type": "string",
+                        "description": "The emoji to react with, e.g. \"👀\" or \"✅\""
+                    },
+                    "message_id": {
+                        "type": "string",
+                        "description": "Platform message ID to react to (default: the latest user message)"
+                    }
+                }),
+                &["chat_id", "emoji"],
+            ),
+        }
+    }
+
+    async fn execute(&self, input: serde_json::Value) -> ToolResult {
+        let chat_id = match input.get("chat_id").and_then(|v| v.as_i64()) {
+            Some(id) => id,
+            None => return ToolResult::error("Missing required parameter: chat_id".into()),
+        };
+        let emoji = input
+            .get("emoji")
+            .and_then(|v| v.as_str())
+            .map(str::trim)
+            .unwrap_or("");
+        if emoji.is_empty() {
+            return ToolResult::error("Missing required parameter: emoji".into());
+        }
+        if let Err(e) = authorize_chat_access(&input, chat_id) {
+            return ToolResult::error(e);
+        }
+
+        let message_id = match input
+            .get("message_id")
+            .and_then(|v| v.as_str())
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+        {
+            Some(id) => id.to_string(),
+            None => match self.latest_user_message_id(chat_id).await {
+                Ok(Some(id)) => id,
+                Ok(None) => {
+                    return ToolResult::error(format!(
+                        "No user message to react to in chat {chat_id}"
+                    ))
+                }
+                Err(e) => return ToolResult::error(e),
+            },
+        };
+
+        match react_to_message(&self.registry, self.db.clone(), chat_id, &message_id, emoji).await {
+            Ok(()) => ToolResult::success(format!("Reacted {emoji} to message {message_id}")),
+            Err(e) => ToolResult::error(e),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "web"))]
+mod tests {
+    use super::*;
+    use crate::db::StoredMessage;
+    use crate::web::WebAdapter;
+
+    fn test_db() -> (Arc<Database>, std::path::PathBuf) {
+        let dir = std::env::temp_dir().join(format!("rayclaw_react_{}", uuid::Uuid::new_v4()));
+        let db = Arc::new(Database::new(dir.to_str().unwrap()).unwrap());
+        (db, dir)
+    }
+
+    fn test_registry() -> Arc<ChannelRegistry> {
+        let mut registry = ChannelRegistry::new();
+        registry.register(Arc::new(WebAdapter));
+        Arc::new(registry)
+    }
+
+    fn auth(chat_id: i64) -> serde_json::Value {
+        json!({"caller_chat_id": chat_id, "control_chat_ids": []})
+    }
+
+    #[tokio::test]
+    async fn test_react_permission_denied() {
+        let (db, dir) = test_db();
+        let tool = ReactToMessageTool::new(test_registry(), db);
+        let result = tool
+            .execute(json!({"chat_id": 200, "emoji": "👀", "__rayclaw_auth": auth(100)}))
+            .await;
+        assert!(result.is_error);
+        assert!(result.content.contains("Permission denied"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_react_defaults_to_latest_user_message() {
+        let (db, dir) = test_db();
+        db.upsert_chat(999, Some("web-main"), "web").unwrap();
+        let tool = ReactToMessageTool::new(test_registry(), db.clone());
+
+        let result = tool
+            .execute(json!({"chat_id": 999, "emoji": "👀", "__rayclaw_auth": auth(999)}))
+            .await;
+        assert!(result.is_error);
+        assert!(result.content.contains("No user message"));
+
+        for (id, from_bot, ts) in [
+            ("m1", false, "2024-01-01T00:00:01Z"),
+            ("m2", true, "2024-01-01T00:00:02Z"),
+        ] {
+            db.store_message(&StoredMessage {
+                id: id.into(),
+                chat_id: 999,
+                sender_name: "alice".into(),
+                content: "hi".into(),
+                is_from_bot: from_bot,
+                timestamp: ts.into(),
+                thread_id: None,
+            })
+            .unwrap();
+        }
+        assert_eq!(
+            tool.latest_user_message_id(999).await.unwrap().as_deref(),
+            Some("m1")
+        );
+
+        // The web channel has no reactions
+        let result = tool
+            .execute(json!({"chat_id": 999, "emoji": "👀", "__rayclaw_auth": auth(999)}))
+            .await;
+        assert!(result.is_error);
+        assert!(result.content.contains("reactions not supported for web"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -99,7 +99,8 @@ impl Tool for ReadFileTool {
             .map(|(i, line)| format!("{:>6}\t{}", offset + i + 1, line))
             .collect();
 
-        ToolResult::success(selected.join("\n"))
+        // More lines follow past the requested window
+        ToolResult::success(selected.join("\n")).with_truncated(end < lines.len())
     }
 }
 
@@ -122,6 +123,7 @@ mod tests {
         assert!(result.content.contains("line5"));
         // Should have line numbers
         assert!(result.content.contains("1\t"));
+        assert!(!result.truncated);
 
         let _ = std::fs::remove_dir_all(&dir);
     }
@@ -142,6 +144,7 @@ mod tests {
         assert!(result.content.contains("b"));
         assert!(result.content.contains("c"));
         assert!(!result.content.contains("\ta\n") && !result.content.contains("\td"));
+        assert!(result.truncated);
 
         let _ = std::fs::remove_dir_all(&dir);
     }
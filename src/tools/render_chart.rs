@@ -0,0 +1,309 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde_json::json;
+
+use super::send_message::send_attachment_to_chat;
+use super::{
+    auth_context_from_input, authorize_chat_access, resolve_tool_working_dir, schema_object, Tool,
+    ToolResult,
+};
+use crate::channel::enforce_channel_policy;
+use crate::channel_adapter::ChannelRegistry;
+use crate::chart::{parse_csv, render_png, ChartKind, ChartSpec, Series, MAX_CHART_DIMENSION};
+use crate::config::WorkingDirIsolation;
+use crate::db::Database;
+use crate::image_utils::base64_encode;
+use crate::llm_types::ToolDefinition;
+
+const DEFAULT_WIDTH: u32 = 800;
+const DEFAULT_HEIGHT: u32 = 480;
+
+pub struct RenderChartTool {
+    registry: Arc<ChannelRegistry>,
+    db: Arc<Database>,
+    bot_username: String,
+    working_dir: PathBuf,
+    working_dir_isolation: WorkingDirIsolation,
+    font_path: Option<String>,
+}
+
+impl RenderChartTool {
+    pub fn new(
+        registry: Arc<ChannelRegistry>,
+        db: Arc<Database>,
+        bot_username: String,
+        working_dir: &str,
+        working_dir_isolation: WorkingDirIsolation,
+        font_path: Option<String>,
+    ) -> Self {
+        RenderChartTool {
+            registry,
+            db,
+            bot_username,
+            working_dir: PathBuf::from(working_dir),
+            working_dir_isolation,
+            font_path,
+        }
+    }
+}
+
+fn optional_str(input: &serde_json::Value, key: &str) -> Option<String> {
+    input
+        .get(key)
+        .and_then(|v| v.as_str())
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(str::to_string)
+}
+
+/// Build the chart spec from either `csv` or `labels` + `series`.
+fn spec_from_input(input: &serde_json::Value) -> Result<ChartSpec, String> {
+    let kind = ChartKind::parse(
+        input
+            .get("chart_type")
+            .and_then(|v| v.as_str())
+            .unwrap_or(""),
+    )?;
+    let (labels, series) = if let Some(csv) = optional_str(input, "csv") {
+        parse_csv(&csv)?
+    } else {
+        let labels = input
+            .get("labels")
+            .and_then(|v| v.as_array())
+            .ok_or("Provide csv, or labels and series")?
+            .iter()
+            .map(|l| match l {
+                serde_json::Value::String(s) => s.clone(),
+                other => other.to_string(),
+            })
+            .collect();
+        let series = input
+            .get("series")
+            .and_then(|v| v.as_array())
+            .ok_or("Provide csv, or labels and series")?
+            .iter()
+            .enumerate()
+            .map(|(i, s)| {
+                let values = s
+                    .get("values")
+                    .and_then(|v| v.as_array())
+                    .ok_or(format!("series[{i}] is missing values"))?
+                    .iter()
+                    .map(|v| {
+                        v.as_f64()
+                            .ok_or(format!("series[{i}] has a non-numeric value: {v}"))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(Series {
+                    name: optional_str(s, "name").unwrap_or_default(),
+                    values,
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        (labels, series)
+    };
+    Ok(ChartSpec {
+        kind,
+        title: optional_str(input, "title"),
+        x_label: optional_str(input, "x_label"),
+        y_label: optional_str(input, "y_label"),
+        labels,
+        series,
+    })
+}
+
+fn dimension(input: &serde_json::Value, key: &str, default: u32) -> u32 {
+    input
+        .get(key)
+        .and_then(|v| v.as_u64())
+        .map_or(default, |v| v.clamp(100, MAX_CHART_DIMENSION as u64) as u32)
+}
+
+#[async_trait]
+impl Tool for RenderChartTool {
+    fn name(&self) -> &str {
+        "render_chart"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "render_chart".into(),
+            description: "Render a line or bar chart as a PNG and send it to the chat as an \
+                image (e.g. for metric reports). Give the data either as csv (header row, label \
+                column first, one column per series) or as labels plus series. The PNG is also \
+                saved in the working directory and shown to you."
+                .into(),
+            input_schema: schema_object(
+                json!({
+                    "chat_id": {
+                        "type": "integer",
+                        "description": "The chat to send the chart to"
+                    },
+                    "chart_type": {
+                        "type": "string",
+                        "enum": ["line", "bar"],
+                        "description": "Chart type (default: line)"
+                    },
+                    "title": {
+                        "type": "string",
+                        "description": "Chart title"
+                    },
+                    "x_label": {
+                        "type": "string",
+                        "description": "X axis description"
+                    },
+                    "y_label": {
+                        "type": "string",
+                        "description": "Y axis description"
+                    },
+                    "csv": {
+                        "type": "string",
+                        "description": "Data as CSV, e.g. \"day,errors,warnings\\nMon,3,10\\nTue,1,7\""
+                    },
+                    "labels": {
+                        "type": "array",
+                        "items": {"type": "string"},
+                        "description": "X axis labels, one per data point (when not using csv)"
+                    },
+                    "series": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "name": {"type": "string"},
+                                "values": {"type": "array", "items": {"type": "number"}}
+                            },
+                            "required": ["values"]
+                        },
+                        "description": "Data series, each with one value per label (when not using csv)"
+                    },
+                    "width": {
+                        "type": "integer",
+                        "description": "Width in pixels (default: 800)"
+                    },
+                    "height": {
+                        "type": "integer",
+                        "description": "Height in pixels (default: 480)"
+                    },
+                    "caption": {
+                        "type": "string",
+                        "description": "Caption sent with the image"
+                    },
+                    "send": {
+                        "type": "boolean",
+                        "description": "Send the chart to the chat (default: true); false only saves it"
+                    }
+                }),
+                &["chat_id"],
+            ),
+        }
+    }
+
+    async fn execute(&self, input: serde_json::Value) -> ToolResult {
+        let chat_id = match input.get("chat_id").and_then(|v| v.as_i64()) {
+            Some(id) => id,
+            None => return ToolResult::error("Missing required parameter: chat_id".into()),
+        };
+        if let Err(e) = authorize_chat_access(&input, chat_id) {
+            return ToolResult::error(e);
+        }
+        let spec = match spec_from_input(&input) {
+            Ok(spec) => spec,
+            Err(e) => return ToolResult::error(e),
+        };
+        let send = input.get("send").and_then(|v| v.as_bool()).unwrap_or(true);
+        if send {
+            if let Err(e) =
+                enforce_channel_policy(&self.registry, self.db.clone(), &input, chat_id).await
+            {
+                return ToolResult::error(e);
+            }
+        }
+
+        let width = dimension(&input, "width", DEFAULT_WIDTH);
+        let height = dimension(&input, "height", DEFAULT_HEIGHT);
+        let font_path = self.font_path.clone();
+        let png = match tokio::task::spawn_blocking(move || {
+            render_png(&spec, width, height, font_path.as_deref())
+        })
+        .await
+        {
+            Ok(Ok(png)) => png,
+            Ok(Err(e)) => return ToolResult::error(e),
+            Err(e) => return ToolResult::error(format!("Chart rendering task failed: {e}")),
+        };
+
+        let dir = resolve_tool_working_dir(&self.working_dir, self.working_dir_isolation, &input)
+            .join("charts");
+        let path = dir.join(format!(
+            "chart-{}.png",
+            chrono::Utc::now().format("%Y%m%d-%H%M%S-%3f")
+        ));
+        if let Err(e) = std::fs::create_dir_all(&dir).and_then(|_| std::fs::write(&path, &png)) {
+            return ToolResult::error(format!("Failed to save chart: {e}"));
+        }
+
+        let mut message = format!("Chart saved to {}", path.display());
+        if send {
+            if let Err(e) = send_attachment_to_chat(
+                &self.registry,
+                self.db.clone(),
+                &self.bot_username,
+                chat_id,
+                auth_context_from_input(&input).and_then(|auth| auth.thread_for(chat_id)),
+                &path,
+                optional_str(&input, "caption").as_deref(),
+            )
+            .await
+            {
+                return ToolResult::error(format!("{message}, but sending it failed: {e}"));
+            }
+            message.push_str(" and sent to the chat.");
+        }
+        ToolResult::success(message).with_image("image/png", base64_encode(&png))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spec_from_input() {
+        let spec = spec_from_input(&json!({
+            "chart_type": "bar",
+            "title": " Errors ",
+            "labels": ["Mon", 2],
+            "series": [{"name": "api", "values": [1, 2.5]}, {"values": [0, 1]}]
+        }))
+        .unwrap();
+        assert_eq!(spec.kind, ChartKind::Bar);
+        assert_eq!(spec.title.as_deref(), Some("Errors"));
+        assert_eq!(spec.labels, vec!["Mon", "2"]);
+        assert_eq!(spec.series[0].values, vec![1.0, 2.5]);
+        assert_eq!(spec.series[1].name, "");
+
+        let spec = spec_from_input(&json!({"csv": "day,api\nMon,3"})).unwrap();
+        assert_eq!(spec.kind, ChartKind::Line);
+        assert_eq!(spec.series[0].name, "api");
+
+        assert!(spec_from_input(&json!({})).is_err());
+        assert!(
+            spec_from_input(&json!({"labels": ["a"], "series": [{"values": ["x"]}]}))
+                .unwrap_err()
+                .contains("non-numeric")
+        );
+    }
+
+    #[test]
+    fn test_dimension_clamped() {
+        assert_eq!(dimension(&json!({}), "width", 800), 800);
+        assert_eq!(dimension(&json!({"width": 10}), "width", 800), 100);
+        assert_eq!(
+            dimension(&json!({"width": 99999}), "width", 800),
+            MAX_CHART_DIMENSION
+        );
+    }
+}
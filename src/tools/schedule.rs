@@ -4,13 +4,13 @@ use std::sync::Arc;
 use async_trait::async_trait;
 use serde_json::json;
 
-use super::{authorize_chat_access, schema_object, Tool, ToolResult};
+use super::{auth_context_from_input, authorize_chat_access, schema_object, Tool, ToolResult};
 use crate::channel::enforce_channel_policy;
 use crate::channel_adapter::ChannelRegistry;
 use crate::db::{call_blocking, Database};
 use crate::llm_types::ToolDefinition;
 
-fn compute_next_run(cron_expr: &str, tz_name: &str) -> Result<String, String> {
+pub(crate) fn compute_next_run(cron_expr: &str, tz_name: &str) -> Result<String, String> {
     let tz: chrono_tz::Tz = tz_name
         .parse()
         .map_err(|_| format!("Invalid timezone: {tz_name}"))?;
@@ -134,13 +134,16 @@ impl Tool for ScheduleTaskTool {
             _ => return ToolResult::error("schedule_type must be 'cron' or 'once'".into()),
         };
 
+        // Run in the caller's forum topic when scheduling for its own chat
+        let thread_id = auth_context_from_input(&input).and_then(|auth| auth.thread_for(chat_id));
         let prompt_owned = prompt.to_string();
         let schedule_type_owned = schedule_type.to_string();
         let schedule_value_owned = schedule_value.to_string();
         let next_run_owned = next_run.clone();
         match call_blocking(self.db.clone(), move |db| {
-            db.create_scheduled_task(
+            db.create_scheduled_task_in_thread(
                 chat_id,
+                thread_id,
                 &prompt_owned,
                 &schedule_type_owned,
                 &schedule_value_owned,
@@ -887,6 +890,32 @@ mod tests {
         cleanup(&dir);
     }
 
+    #[tokio::test]
+    async fn test_schedule_task_keeps_caller_thread() {
+        let (db, dir) = test_db();
+        let tool = ScheduleTaskTool::new(test_registry(), db.clone(), "UTC".into());
+        for chat_id in [100, 200] {
+            let result = tool
+                .execute(json!({
+                    "chat_id": chat_id,
+                    "prompt": "say hi",
+                    "schedule_type": "once",
+                    "schedule_value": "2099-12-31T23:59:59+00:00",
+                    "__rayclaw_auth": {
+                        "caller_chat_id": 100,
+                        "caller_thread_id": 7,
+                        "control_chat_ids": [100]
+                    }
+                }))
+                .await;
+            assert!(!result.is_error, "{}", result.content);
+        }
+        assert_eq!(db.get_tasks_for_chat(100).unwrap()[0].thread_id, Some(7));
+        // Tasks for other chats run outside any topic
+        assert_eq!(db.get_tasks_for_chat(200).unwrap()[0].thread_id, None);
+        cleanup(&dir);
+    }
+
     #[tokio::test]
     async fn test_pause_task_allowed_for_control_chat_cross_chat() {
         let (db, dir) = test_db();
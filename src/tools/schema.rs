@@ -0,0 +1,264 @@
+//! Checks tool input against the tool's JSON schema before it runs.
+//!
+//! Covers the subset of JSON Schema the tool definitions use: `type`
+//! (including type lists), `properties`, `required`, `additionalProperties:
+//! false`, `enum`, `items`, `minimum`/`maximum`, `minLength`/`maxLength`,
+//! `minItems`/`maxItems` and `anyOf`/`oneOf`. Other keywords are ignored, so
+//! an unusual schema (e.g. from an MCP server) never blocks a call.
+
+use serde_json::Value;
+
+/// Keys the runtime injects into tool input (auth context, approval tokens).
+const INTERNAL_KEY_PREFIX: &str = "__rayclaw";
+
+/// Problems found in `input`, one line each (`path: problem`). Empty when
+/// the input matches.
+pub fn validate_input(schema: &Value, input: &Value) -> Vec<String> {
+    let mut errors = Vec::new();
+    validate(schema, input, "input", &mut errors);
+    errors
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_i64() || n.is_u64() => "integer",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn matches_type(expected: &str, value: &Value) -> bool {
+    match expected {
+        "integer" => match value {
+            Value::Number(n) => {
+                n.is_i64() || n.is_u64() || n.as_f64().is_some_and(|f| f.fract() == 0.0)
+            }
+            _ => false,
+        },
+        "number" => value.is_number(),
+        other => type_name(value) == other,
+    }
+}
+
+fn validate(schema: &Value, value: &Value, path: &str, errors: &mut Vec<String>) {
+    let Some(schema) = schema.as_object() else {
+        return;
+    };
+
+    if let Some(expected) = schema.get("type") {
+        let allowed: Vec<&str> = match expected {
+            Value::String(t) => vec![t.as_str()],
+            Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !allowed.is_empty() && !allowed.iter().any(|t| matches_type(t, value)) {
+            errors.push(format!(
+                "{path}: expected {}, got {}",
+                allowed.join(" or "),
+                type_name(value)
+            ));
+            return;
+        }
+    }
+
+    if let Some(options) = schema.get("enum").and_then(Value::as_array) {
+        if !options.contains(value) {
+            let listed: Vec<String> = options.iter().map(Value::to_string).collect();
+            errors.push(format!(
+                "{path}: {value} is not one of {}",
+                listed.join(", ")
+            ));
+            return;
+        }
+    }
+
+    for key in ["anyOf", "oneOf"] {
+        if let Some(variants) = schema.get(key).and_then(Value::as_array) {
+            let fits = variants.iter().any(|variant| {
+                let mut scratch = Vec::new();
+                validate(variant, value, path, &mut scratch);
+                scratch.is_empty()
+            });
+            if !variants.is_empty() && !fits {
+                errors.push(format!("{path}: does not match any allowed form"));
+                return;
+            }
+        }
+    }
+
+    match value {
+        Value::Object(map) => {
+            let properties = schema.get("properties").and_then(Value::as_object);
+            if let Some(required) = schema.get("required").and_then(Value::as_array) {
+                for name in required.iter().filter_map(Value::as_str) {
+                    if map.get(name).is_none_or(Value::is_null) {
+                        errors.push(format!("{path}: missing required property '{name}'"));
+                    }
+                }
+            }
+            let closed = schema.get("additionalProperties") == Some(&Value::Bool(false));
+            for (name, item) in map {
+                if name.starts_with(INTERNAL_KEY_PREFIX) {
+                    continue;
+                }
+                match properties.and_then(|p| p.get(name)) {
+                    // Models often send null for optional properties they skip
+                    Some(_) if item.is_null() => {}
+                    Some(property) => validate(property, item, &format!("{path}.{name}"), errors),
+                    None if closed => {
+                        errors.push(format!("{path}: unknown property '{name}'"));
+                    }
+                    None => {}
+                }
+            }
+        }
+        Value::Array(items) => {
+            check_count(
+                schema,
+                "minItems",
+                "maxItems",
+                items.len(),
+                "items",
+                path,
+                errors,
+            );
+            if let Some(item_schema) = schema.get("items") {
+                for (i, item) in items.iter().enumerate() {
+                    validate(item_schema, item, &format!("{path}[{i}]"), errors);
+                }
+            }
+        }
+        Value::String(s) => {
+            check_count(
+                schema,
+                "minLength",
+                "maxLength",
+                s.chars().count(),
+                "characters",
+                path,
+                errors,
+            );
+        }
+        Value::Number(n) => {
+            let n = n.as_f64().unwrap_or_default();
+            if let Some(min) = schema.get("minimum").and_then(Value::as_f64) {
+                if n < min {
+                    errors.push(format!("{path}: {n} is below the minimum of {min}"));
+                }
+            }
+            if let Some(max) = schema.get("maximum").and_then(Value::as_f64) {
+                if n > max {
+                    errors.push(format!("{path}: {n} is above the maximum of {max}"));
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn check_count(
+    schema: &serde_json::Map<String, Value>,
+    min_key: &str,
+    max_key: &str,
+    count: usize,
+    unit: &str,
+    path: &str,
+    errors: &mut Vec<String>,
+) {
+    let count = count as u64;
+    if let Some(min) = schema.get(min_key).and_then(Value::as_u64) {
+        if count < min {
+            errors.push(format!("{path}: needs at least {min} {unit}, got {count}"));
+        }
+    }
+    if let Some(max) = schema.get(max_key).and_then(Value::as_u64) {
+        if count > max {
+            errors.push(format!("{path}: allows at most {max} {unit}, got {count}"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": {"type": "string", "minLength": 1},
+                "limit": {"type": "integer", "minimum": 1, "maximum": 50},
+                "mode": {"type": "string", "enum": ["read", "write"]},
+                "tags": {"type": "array", "items": {"type": "string"}},
+                "options": {
+                    "type": "object",
+                    "properties": {"recursive": {"type": "boolean"}},
+                    "additionalProperties": false
+                }
+            },
+            "required": ["path"]
+        })
+    }
+
+    #[test]
+    fn test_valid_input_passes() {
+        let input = json!({
+            "path": "a.txt",
+            "limit": 5.0,
+            "mode": "read",
+            "tags": ["x"],
+            "options": {"recursive": true},
+            "extra": 1,
+            "__rayclaw_auth": {"caller_chat_id": 1}
+        });
+        assert!(validate_input(&schema(), &input).is_empty());
+        // Null stands for an omitted optional property
+        assert!(validate_input(&schema(), &json!({"path": "a", "limit": null})).is_empty());
+    }
+
+    #[test]
+    fn test_reports_each_problem_with_its_path() {
+        let input = json!({
+            "limit": "10",
+            "mode": "append",
+            "tags": ["x", 2],
+            "options": {"recursive": "yes", "depth": 3}
+        });
+        let errors = validate_input(&schema(), &input);
+        assert_eq!(
+            errors,
+            vec![
+                "input: missing required property 'path'",
+                "input.limit: expected integer, got string",
+                "input.mode: \"append\" is not one of \"read\", \"write\"",
+                "input.options: unknown property 'depth'",
+                "input.options.recursive: expected boolean, got string",
+                "input.tags[1]: expected string, got integer",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bounds_and_any_of() {
+        let errors = validate_input(&schema(), &json!({"path": "", "limit": 99}));
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].contains("maximum of 50"));
+        assert!(errors[1].contains("at least 1 characters"));
+
+        let schema = json!({"anyOf": [{"type": "string"}, {"type": "array"}]});
+        assert!(validate_input(&schema, &json!(["a"])).is_empty());
+        assert_eq!(validate_input(&schema, &json!(3)).len(), 1);
+    }
+
+    #[test]
+    fn test_ignores_unknown_keywords() {
+        let schema = json!({"$ref": "#/defs/x", "type": ["object", "null"]});
+        assert!(validate_input(&schema, &json!(null)).is_empty());
+        assert!(validate_input(&json!(true), &json!({"a": 1})).is_empty());
+    }
+}
@@ -0,0 +1,296 @@
+use async_trait::async_trait;
+use serde_json::json;
+use tracing::info;
+
+use crate::image_utils::{encode_image_for_llm, ImageOptions};
+use crate::llm_types::ToolDefinition;
+use crate::tools::command_runner::agent_browser_program;
+
+use super::{auth_context_from_input, schema_object, Tool, ToolResult};
+
+const DEFAULT_TIMEOUT_SECS: u64 = 45;
+const MAX_WAIT_MS: u64 = 30_000;
+const MIN_VIEWPORT: u64 = 320;
+const MAX_VIEWPORT: u64 = 3840;
+
+/// Renders a webpage in a throwaway headless browser session and returns
+/// the screenshot as an image. Unlike `browser`, no cookies or logins from
+/// the chat's browser profile are used, so the result is what a first-time
+/// visitor sees.
+pub struct ScreenshotUrlTool;
+
+/// Accept only absolute http(s) URLs; `file://`, `javascript:` and friends
+/// would let the browser read local files or run script.
+fn validate_url(url: &str) -> Result<String, String> {
+    let parsed = reqwest::Url::parse(url.trim()).map_err(|e| format!("Invalid URL: {e}"))?;
+    match parsed.scheme() {
+        "http" | "https" => Ok(parsed.to_string()),
+        other => Err(format!(
+            "Unsupported URL scheme '{other}': only http and https are allowed"
+        )),
+    }
+}
+
+fn viewport_dimension(input: &serde_json::Value, key: &str) -> Result<Option<u64>, String> {
+    match input.get(key).and_then(|v| v.as_u64()) {
+        None => Ok(None),
+        Some(v) if (MIN_VIEWPORT..=MAX_VIEWPORT).contains(&v) => Ok(Some(v)),
+        Some(v) => Err(format!(
+            "'{key}' must be between {MIN_VIEWPORT} and {MAX_VIEWPORT} pixels, got {v}"
+        )),
+    }
+}
+
+/// The agent-browser commands that produce the screenshot, in order.
+fn screenshot_commands(
+    url: &str,
+    viewport: Option<(u64, u64)>,
+    wait_ms: u64,
+    full_page: bool,
+    output: &str,
+) -> Vec<Vec<String>> {
+    let mut commands = Vec::new();
+    if let Some((width, height)) = viewport {
+        commands.push(vec![
+            "set".into(),
+            "viewport".into(),
+            width.to_string(),
+            height.to_string(),
+        ]);
+    }
+    commands.push(vec!["open".into(), url.into()]);
+    if wait_ms > 0 {
+        commands.push(vec!["wait".into(), wait_ms.to_string()]);
+    }
+    let mut shot = vec!["screenshot".to_string()];
+    if full_page {
+        shot.push("--full".into());
+    }
+    shot.push(output.into());
+    commands.push(shot);
+    commands
+}
+
+async fn run_browser_command(session: &str, command: &[String]) -> Result<(), String> {
+    let output = tokio::process::Command::new(agent_browser_program())
+        .arg("--session")
+        .arg(session)
+        .args(command)
+        .output()
+        .await
+        .map_err(|e| format!("Failed to execute agent-browser: {e}"))?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let detail = if stderr.trim().is_empty() {
+        stdout.trim().to_string()
+    } else {
+        stderr.trim().to_string()
+    };
+    Err(format!(
+        "`{}` failed (exit code {}): {detail}",
+        command.join(" "),
+        output.status.code().unwrap_or(-1)
+    ))
+}
+
+#[async_trait]
+impl Tool for ScreenshotUrlTool {
+    fn name(&self) -> &str {
+        "screenshot_url"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "screenshot_url".into(),
+            description: "Take a screenshot of a webpage and look at it. Opens the URL in a fresh headless browser (no saved cookies or logins), renders it, and returns a PNG image. Use this to see what a page looks like or to visually check a page in a scheduled monitor; use `browser` instead when you need to log in or interact first.".into(),
+            input_schema: schema_object(
+                json!({
+                    "url": {
+                        "type": "string",
+                        "description": "The http(s) URL to capture"
+                    },
+                    "full_page": {
+                        "type": "boolean",
+                        "description": "Capture the whole scrollable page instead of just the viewport (default: false)"
+                    },
+                    "width": {
+                        "type": "integer",
+                        "description": "Viewport width in pixels (320-3840, default: browser default)"
+                    },
+                    "height": {
+                        "type": "integer",
+                        "description": "Viewport height in pixels (320-3840, default: browser default)"
+                    },
+                    "wait_ms": {
+                        "type": "integer",
+                        "description": "Extra time to wait after the page loads, for late-rendering content (max 30000, default: 0)"
+                    },
+                    "timeout_secs": {
+                        "type": "integer",
+                        "description": "Overall timeout in seconds (default: 45)"
+                    }
+                }),
+                &["url"],
+            ),
+        }
+    }
+
+    async fn execute(&self, input: serde_json::Value) -> ToolResult {
+        let url = match input.get("url").and_then(|v| v.as_str()) {
+            Some(u) => u,
+            None => return ToolResult::error("Missing required parameter: url".into()),
+        };
+        let url = match validate_url(url) {
+            Ok(u) => u,
+            Err(e) => return ToolResult::error(e),
+        };
+        let chat_id = auth_context_from_input(&input).map(|auth| auth.caller_chat_id);
+        if let Err(e) = crate::politeness::before_request("screenshot_url", &url, chat_id).await {
+            return ToolResult::error(e);
+        }
+        let (width, height) = match (
+            viewport_dimension(&input, "width"),
+            viewport_dimension(&input, "height"),
+        ) {
+            (Ok(w), Ok(h)) => (w, h),
+            (Err(e), _) | (_, Err(e)) => return ToolResult::error(e),
+        };
+        let viewport = match (width, height) {
+            (None, None) => None,
+            (w, h) => Some((w.unwrap_or(1280), h.unwrap_or(800))),
+        };
+        let full_page = input
+            .get("full_page")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        let wait_ms = input
+            .get("wait_ms")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(0)
+            .min(MAX_WAIT_MS);
+        let timeout_secs = input
+            .get("timeout_secs")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(DEFAULT_TIMEOUT_SECS);
+
+        let id = uuid::Uuid::new_v4().simple().to_string();
+        let session = format!("rayclaw-screenshot-{id}");
+        let output = std::env::temp_dir().join(format!("rayclaw-screenshot-{id}.png"));
+        let output_str = output.to_string_lossy().to_string();
+        let commands = screenshot_commands(&url, viewport, wait_ms, full_page, &output_str);
+
+        info!("Capturing screenshot of {url}");
+        let result = tokio::time::timeout(std::time::Duration::from_secs(timeout_secs), async {
+            for command in &commands {
+                run_browser_command(&session, command).await?;
+            }
+            Ok::<(), String>(())
+        })
+        .await;
+        let _ = run_browser_command(&session, &["close".to_string()]).await;
+
+        let bytes = match result {
+            Ok(Ok(())) => std::fs::read(&output),
+            Ok(Err(e)) => {
+                let _ = std::fs::remove_file(&output);
+                return ToolResult::error(e).with_error_type("browser_error");
+            }
+            Err(_) => {
+                let _ = std::fs::remove_file(&output);
+                return ToolResult::error(format!(
+                    "Screenshot timed out after {timeout_secs} seconds"
+                ))
+                .with_error_type("timeout");
+            }
+        };
+        let _ = std::fs::remove_file(&output);
+
+        match bytes {
+            Ok(bytes) if !bytes.is_empty() => {
+                let (data, media_type) = encode_image_for_llm(bytes, ImageOptions::default()).await;
+                let scope = if full_page { "full page" } else { "viewport" };
+                ToolResult::success(format!("Screenshot of {url} ({scope})"))
+                    .with_image(media_type, data)
+            }
+            _ => ToolResult::error("agent-browser did not produce a screenshot".into())
+                .with_error_type("browser_error"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_url() {
+        assert_eq!(
+            validate_url(" https://example.com ").unwrap(),
+            "https://example.com/"
+        );
+        assert!(validate_url("http://example.com/a?b=1").is_ok());
+        assert!(validate_url("file:///etc/passwd")
+            .unwrap_err()
+            .contains("only http and https"));
+        assert!(validate_url("javascript:alert(1)").is_err());
+        assert!(validate_url("example.com").is_err());
+    }
+
+    #[test]
+    fn test_viewport_dimension_bounds() {
+        let input = json!({"width": 1024, "height": 100});
+        assert_eq!(viewport_dimension(&input, "width").unwrap(), Some(1024));
+        assert!(viewport_dimension(&input, "height")
+            .unwrap_err()
+            .contains("between"));
+        assert_eq!(viewport_dimension(&input, "missing").unwrap(), None);
+    }
+
+    #[test]
+    fn test_screenshot_commands() {
+        let cmds = screenshot_commands(
+            "https://example.com/",
+            Some((1024, 768)),
+            500,
+            true,
+            "/tmp/s.png",
+        );
+        assert_eq!(cmds[0], vec!["set", "viewport", "1024", "768"]);
+        assert_eq!(cmds[1], vec!["open", "https://example.com/"]);
+        assert_eq!(cmds[2], vec!["wait", "500"]);
+        assert_eq!(cmds[3], vec!["screenshot", "--full", "/tmp/s.png"]);
+
+        let cmds = screenshot_commands("https://example.com/", None, 0, false, "/tmp/s.png");
+        assert_eq!(cmds.len(), 2);
+        assert_eq!(cmds[1], vec!["screenshot", "/tmp/s.png"]);
+    }
+
+    #[test]
+    fn test_definition() {
+        let def = ScreenshotUrlTool.definition();
+        assert_eq!(def.name, "screenshot_url");
+        assert_eq!(def.input_schema["required"][0], "url");
+        assert!(def.input_schema["properties"]["full_page"].is_object());
+    }
+
+    #[tokio::test]
+    async fn test_rejects_bad_input_before_launching_browser() {
+        let result = ScreenshotUrlTool.execute(json!({})).await;
+        assert!(result.is_error);
+        assert!(result.content.contains("url"));
+
+        let result = ScreenshotUrlTool
+            .execute(json!({"url": "file:///etc/passwd"}))
+            .await;
+        assert!(result.is_error);
+
+        let result = ScreenshotUrlTool
+            .execute(json!({"url": "https://example.com", "width": 10}))
+            .await;
+        assert!(result.is_error);
+        assert!(result.content.contains("width"));
+    }
+}
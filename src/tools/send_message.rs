@@ -1,13 +1,13 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use async_trait::async_trait;
 use serde_json::json;
 use tracing::{info, warn};
 
-use super::{authorize_chat_access, schema_object, Tool, ToolResult};
+use super::{auth_context_from_input, authorize_chat_access, schema_object, Tool, ToolResult};
 use crate::channel::{
-    deliver_and_store_bot_message, enforce_channel_policy, get_required_chat_routing,
+    deliver_and_store_bot_message_in_thread, enforce_channel_policy, get_required_chat_routing,
 };
 use crate::channel_adapter::ChannelRegistry;
 use crate::db::{call_blocking, Database, StoredMessage};
@@ -27,27 +27,78 @@ impl SendMessageTool {
             bot_username,
         }
     }
+}
 
-    async fn store_bot_message(&self, chat_id: i64, content: String) -> Result<(), String> {
-        let msg = StoredMessage {
-            id: uuid::Uuid::new_v4().to_string(),
-            chat_id,
-            sender_name: self.bot_username.clone(),
-            content,
-            is_from_bot: true,
-            timestamp: chrono::Utc::now().to_rfc3339(),
-        };
-        call_blocking(self.db.clone(), move |db| db.store_message(&msg))
-            .await
-            .map_err(|e| format!("Failed to store sent message: {e}"))
-    }
-
-    async fn resolve_external_chat_id(&self, chat_id: i64) -> Result<String, String> {
-        let external = call_blocking(self.db.clone(), move |db| db.get_chat_external_id(chat_id))
-            .await
-            .map_err(|e| format!("Failed to resolve external chat id: {e}"))?;
-        Ok(external.unwrap_or_else(|| chat_id.to_string()))
-    }
+/// Send a local file to a chat through its channel adapter and record it as
+/// a bot message. Callers check access and channel policy first.
+pub(crate) async fn send_attachment_to_chat(
+    registry: &ChannelRegistry,
+    db: Arc<Database>,
+    bot_username: &str,
+    chat_id: i64,
+    thread_id: Option<i64>,
+    file_path: &Path,
+    caption: Option<&str>,
+) -> Result<(), String> {
+    let routing = get_required_chat_routing(registry, db.clone(), chat_id).await?;
+    info!(
+        "send_message attachment routing: chat_id={}, channel={}, path={}",
+        chat_id,
+        routing.channel_name,
+        file_path.display()
+    );
+
+    let adapter = registry.get(&routing.channel_name).ok_or_else(|| {
+        format!(
+            "No adapter registered for channel '{}'",
+            routing.channel_name
+        )
+    })?;
+
+    let external = call_blocking(db.clone(), move |db| db.get_chat_external_id(chat_id))
+        .await
+        .map_err(|e| format!("Failed to resolve external chat id: {e}"))?;
+    let external_chat_id = external.unwrap_or_else(|| chat_id.to_string());
+
+    let content = match adapter
+        .send_attachment_in_thread(&external_chat_id, thread_id, file_path, caption)
+        .await
+    {
+        Ok(content) => content,
+        Err(e) => {
+            warn!(
+                "send_message attachment delivery failed: chat_id={}, path={}, error={}",
+                chat_id,
+                file_path.display(),
+                e
+            );
+            return Err(e);
+        }
+    };
+    info!(
+        "send_message attachment sent: chat_id={}, path={}",
+        chat_id,
+        file_path.display()
+    );
+
+    let msg = StoredMessage {
+        id: uuid::Uuid::new_v4().to_string(),
+        chat_id,
+        sender_name: bot_username.to_string(),
+        content,
+        is_from_bot: true,
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        thread_id,
+    };
+    call_blocking(db, move |db| db.store_message(&msg))
+        .await
+        .map_err(|e| {
+            warn!(
+                "send_message store_bot_message failed: chat_id={}, error={}",
+                chat_id, e
+            );
+            format!("Failed to store sent message: {e}")
+        })
 }
 
 #[async_trait]
@@ -125,18 +176,9 @@ impl Tool for SendMessageTool {
         {
             return ToolResult::error(e);
         }
+        let thread_id = auth_context_from_input(&input).and_then(|auth| auth.thread_for(chat_id));
 
         if let Some(path) = attachment_path {
-            let routing =
-                match get_required_chat_routing(&self.registry, self.db.clone(), chat_id).await {
-                    Ok(v) => v,
-                    Err(e) => return ToolResult::error(e),
-                };
-            info!(
-                "send_message attachment routing: chat_id={}, channel={}, path={}",
-                chat_id, routing.channel_name, path
-            );
-
             let file_path = PathBuf::from(&path);
             if !file_path.is_file() {
                 warn!(
@@ -160,57 +202,27 @@ impl Tool for SendMessageTool {
                 }
             });
 
-            let adapter = match self.registry.get(&routing.channel_name) {
-                Some(a) => a,
-                None => {
-                    return ToolResult::error(format!(
-                        "No adapter registered for channel '{}'",
-                        routing.channel_name
-                    ))
-                }
-            };
-
-            let external_chat_id = match self.resolve_external_chat_id(chat_id).await {
-                Ok(v) => v,
-                Err(e) => return ToolResult::error(e),
-            };
-
-            let send_result = adapter
-                .send_attachment(&external_chat_id, &file_path, used_caption.as_deref())
-                .await;
-
-            match send_result {
-                Ok(content) => {
-                    info!(
-                        "send_message attachment sent: chat_id={}, path={}",
-                        chat_id,
-                        file_path.display()
-                    );
-                    if let Err(e) = self.store_bot_message(chat_id, content).await {
-                        warn!(
-                            "send_message store_bot_message failed: chat_id={}, error={}",
-                            chat_id, e
-                        );
-                        return ToolResult::error(e);
-                    }
-                    ToolResult::success("Attachment sent successfully.".into())
-                }
-                Err(e) => {
-                    warn!(
-                        "send_message attachment delivery failed: chat_id={}, path={}, error={}",
-                        chat_id,
-                        file_path.display(),
-                        e
-                    );
-                    ToolResult::error(e)
-                }
+            match send_attachment_to_chat(
+                &self.registry,
+                self.db.clone(),
+                &self.bot_username,
+                chat_id,
+                thread_id,
+                &file_path,
+                used_caption.as_deref(),
+            )
+            .await
+            {
+                Ok(()) => ToolResult::success("Attachment sent successfully.".into()),
+                Err(e) => ToolResult::error(e),
             }
         } else {
-            match deliver_and_store_bot_message(
+            match deliver_and_store_bot_message_in_thread(
                 &self.registry,
                 self.db.clone(),
                 &self.bot_username,
                 chat_id,
+                thread_id,
                 &text,
             )
             .await
@@ -317,6 +329,7 @@ mod tests {
                 bot_token: "123456:TEST_TOKEN".into(),
                 bot_username: "bot".into(),
                 allowed_groups: vec![],
+                streaming: Default::default(),
             },
         );
         registry.register(Arc::new(tg_adapter));
@@ -0,0 +1,285 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde_json::json;
+
+use super::{auth_context_from_input, authorize_chat_access, schema_object, Tool, ToolResult};
+use crate::config::Config;
+use crate::db::{call_blocking, ChatModelOverride, Database};
+use crate::llm_types::ToolDefinition;
+
+const MAX_MODEL_NAME_LEN: usize = 200;
+
+/// Check a requested override, including that `config` has credentials for
+/// its provider. The provider is lowercased; the model name is kept as given
+/// since some providers' names are case-sensitive.
+pub(crate) fn parse_model_override(
+    config: &Config,
+    provider: Option<&str>,
+    model: &str,
+) -> Result<ChatModelOverride, String> {
+    let model = model.trim();
+    if model.is_empty() {
+        return Err("Model name is empty.".into());
+    }
+    if model.len() > MAX_MODEL_NAME_LEN || model.chars().any(char::is_whitespace) {
+        return Err(format!("'{model}' is not a valid model name."));
+    }
+    let provider = match provider.map(str::trim).filter(|p| !p.is_empty()) {
+        Some(p) => {
+            let p = p.to_ascii_lowercase();
+            if !p
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+            {
+                return Err(format!("'{p}' is not a valid provider name."));
+            }
+            Some(p)
+        }
+        None => None,
+    };
+    config.with_model_override(provider.as_deref(), model)?;
+    Ok(ChatModelOverride {
+        provider,
+        model: model.to_string(),
+    })
+}
+
+/// "provider/model" for display, filling in the configured provider.
+pub(crate) fn describe_model(
+    config: &Config,
+    model_override: Option<&ChatModelOverride>,
+) -> String {
+    match model_override {
+        Some(o) => format!(
+            "{}/{}",
+            o.provider.as_deref().unwrap_or(&config.llm_provider),
+            o.model
+        ),
+        None => format!("{}/{}", config.llm_provider, config.model),
+    }
+}
+
+pub struct SetModelTool {
+    config: Config,
+    db: Arc<Database>,
+}
+
+impl SetModelTool {
+    pub fn new(config: &Config, db: Arc<Database>) -> Self {
+        SetModelTool {
+            config: config.clone(),
+            db,
+        }
+    }
+}
+
+#[async_trait]
+impl Tool for SetModelTool {
+    fn name(&self) -> &str {
+        "set_model"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "set_model".into(),
+            description: "Show or change the LLM a chat runs on. `set` stores a per-chat \
+                model (and optionally provider) that replaces the configured default from the \
+                next message; `clear` goes back to the default. Changing the model is only \
+                allowed from control chats. Another provider needs credentials in the config's \
+                `provider_credentials` (ollama, bedrock and openai-codex need no API key)."
+                .into(),
+            input_schema: schema_object(
+                json!({
+                    "chat_id": {
+                        "type": "integer",
+                        "description": "The chat ID"
+                    },
+                    "action": {
+                        "type": "string",
+                        "enum": ["get", "set", "clear"],
+                        "description": "get (default), set or clear"
+                    },
+                    "model": {
+                        "type": "string",
+                        "description": "Model name for `set`, e.g. claude-opus-4-1"
+                    },
+                    "provider": {
+                        "type": "string",
+                        "description": "Provider for `set` (e.g. anthropic, openai, bedrock); defaults to the configured one"
+                    }
+                }),
+                &["chat_id"],
+            ),
+        }
+    }
+
+    async fn execute(&self, input: serde_json::Value) -> ToolResult {
+        let chat_id = match input.get("chat_id").and_then(|v| v.as_i64()) {
+            Some(id) => id,
+            None => return ToolResult::error("Missing required parameter: chat_id".into()),
+        };
+        if let Err(e) = authorize_chat_access(&input, chat_id) {
+            return ToolResult::error(e);
+        }
+        let action = input
+            .get("action")
+            .and_then(|v| v.as_str())
+            .unwrap_or("get");
+        if action != "get" {
+            if let Some(auth) = auth_context_from_input(&input) {
+                if !auth.is_control_chat() {
+                    return ToolResult::error(
+                        "Permission denied: only control chats can change the model".into(),
+                    );
+                }
+            }
+        }
+
+        match action {
+            "get" => match call_blocking(self.db.clone(), move |db| {
+                db.get_chat_model_override(chat_id)
+            })
+            .await
+            {
+                Ok(Some(o)) => ToolResult::success(format!(
+                    "{} (override; default is {})",
+                    describe_model(&self.config, Some(&o)),
+                    describe_model(&self.config, None)
+                )),
+                Ok(None) => {
+                    ToolResult::success(format!("{} (default)", describe_model(&self.config, None)))
+                }
+                Err(e) => ToolResult::error(format!("Failed to load model override: {e}")),
+            },
+            "set" => {
+                let Some(model) = input.get("model").and_then(|v| v.as_str()) else {
+                    return ToolResult::error("Missing required parameter for set: model".into());
+                };
+                let provider = input.get("provider").and_then(|v| v.as_str());
+                let model_override = match parse_model_override(&self.config, provider, model) {
+                    Ok(o) => o,
+                    Err(e) => return ToolResult::error(e),
+                };
+                let described = describe_model(&self.config, Some(&model_override));
+                match call_blocking(self.db.clone(), move |db| {
+                    db.set_chat_model_override(chat_id, &model_override)
+                })
+                .await
+                {
+                    Ok(()) => ToolResult::success(format!(
+                        "Chat {chat_id} now uses {described} from the next message."
+                    )),
+                    Err(e) => ToolResult::error(format!("Failed to save model override: {e}")),
+                }
+            }
+            "clear" => match call_blocking(self.db.clone(), move |db| {
+                db.clear_chat_model_override(chat_id)
+            })
+            .await
+            {
+                Ok(true) => ToolResult::success(format!(
+                    "Model override cleared; chat {chat_id} is back on {}.",
+                    describe_model(&self.config, None)
+                )),
+                Ok(false) => ToolResult::success("No model override was set.".into()),
+                Err(e) => ToolResult::error(format!("Failed to clear model override: {e}")),
+            },
+            other => ToolResult::error(format!("Unknown action '{other}': use get, set or clear")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_db() -> (Arc<Database>, std::path::PathBuf) {
+        let dir = std::env::temp_dir().join(format!("rayclaw_set_model_{}", uuid::Uuid::new_v4()));
+        let db = Arc::new(Database::new(dir.to_str().unwrap()).unwrap());
+        (db, dir)
+    }
+
+    #[test]
+    fn test_parse_model_override() {
+        let config: Config = serde_yaml::from_str(
+            "api_key: key\nprovider_credentials:\n  openai:\n    api_key: sk\n",
+        )
+        .unwrap();
+        let parse = |provider, model| parse_model_override(&config, provider, model);
+        let o = parse(Some(" Anthropic "), " claude-opus-4-1 ").unwrap();
+        assert_eq!(o.provider.as_deref(), Some("anthropic"));
+        assert_eq!(o.model, "claude-opus-4-1");
+        let o = parse(Some(""), "openai/gpt-5-mini").unwrap();
+        assert!(o.provider.is_none());
+        assert_eq!(o.model, "openai/gpt-5-mini");
+        assert!(parse(None, "  ").is_err());
+        assert!(parse(None, "two words").is_err());
+        assert!(parse(Some("open ai"), "gpt-5").is_err());
+        // Other providers need credentials (or no API key at all)
+        assert!(parse(Some("openai"), "gpt-5").is_ok());
+        assert!(parse(Some("ollama"), "llama3").is_ok());
+        assert!(parse(Some("deepseek"), "deepseek-chat")
+            .unwrap_err()
+            .contains("No credentials"));
+        assert!(parse(Some("acme"), "m")
+            .unwrap_err()
+            .contains("Unknown provider"));
+    }
+
+    #[tokio::test]
+    async fn test_set_model_tool() {
+        let (db, dir) = test_db();
+        let config: Config = serde_yaml::from_str("api_key: key\n").unwrap();
+        let tool = SetModelTool::new(&config, db.clone());
+        let default = describe_model(&config, None);
+
+        let result = tool.execute(json!({"chat_id": 100})).await;
+        assert_eq!(result.content, format!("{default} (default)"));
+
+        let denied = tool
+            .execute(json!({
+                "chat_id": 100,
+                "action": "set",
+                "model": "claude-opus-4-1",
+                "__rayclaw_auth": {"caller_chat_id": 100, "control_chat_ids": [1]}
+            }))
+            .await;
+        assert!(denied.is_error);
+        assert!(denied.content.contains("only control chats"));
+
+        let result = tool
+            .execute(json!({
+                "chat_id": 100,
+                "action": "set",
+                "model": "claude-opus-4-1",
+                "provider": "anthropic",
+                "__rayclaw_auth": {"caller_chat_id": 1, "control_chat_ids": [1]}
+            }))
+            .await;
+        assert!(!result.is_error, "{}", result.content);
+        assert_eq!(
+            db.get_chat_model_override(100).unwrap(),
+            Some(ChatModelOverride {
+                provider: Some("anthropic".into()),
+                model: "claude-opus-4-1".into(),
+            })
+        );
+
+        let result = tool.execute(json!({"chat_id": 100})).await;
+        assert!(result
+            .content
+            .starts_with("anthropic/claude-opus-4-1 (override"));
+
+        let result = tool.execute(json!({"chat_id": 100, "action": "set"})).await;
+        assert!(result.is_error);
+
+        let result = tool
+            .execute(json!({"chat_id": 100, "action": "clear"}))
+            .await;
+        assert!(result.content.starts_with("Model override cleared"));
+        assert!(db.get_chat_model_override(100).unwrap().is_none());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,177 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde_json::json;
+
+use super::{authorize_chat_access, schema_object, Tool, ToolResult};
+use crate::db::{call_blocking, Database};
+use crate::llm_types::ToolDefinition;
+use crate::prompt_template::validate_standing_instructions;
+
+pub struct StandingInstructionsTool {
+    db: Arc<Database>,
+}
+
+impl StandingInstructionsTool {
+    pub fn new(db: Arc<Database>) -> Self {
+        StandingInstructionsTool { db }
+    }
+}
+
+#[async_trait]
+impl Tool for StandingInstructionsTool {
+    fn name(&self) -> &str {
+        "standing_instructions"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "standing_instructions".into(),
+            description: "Read, set or clear this chat's standing instructions: preferences \
+                that apply to every reply (e.g. \"always answer in Spanish\", \"keep replies \
+                under 100 words\"). They are added to your system prompt on every turn. Use \
+                `set` when the user asks for a lasting change in how you respond; `set` \
+                replaces the previous instructions, so include anything that should be kept."
+                .into(),
+            input_schema: schema_object(
+                json!({
+                    "chat_id": {
+                        "type": "integer",
+                        "description": "The chat ID"
+                    },
+                    "action": {
+                        "type": "string",
+                        "enum": ["get", "set", "clear"],
+                        "description": "get (default), set or clear"
+                    },
+                    "instructions": {
+                        "type": "string",
+                        "description": "The full instructions for `set` (max 2000 characters)"
+                    }
+                }),
+                &["chat_id"],
+            ),
+        }
+    }
+
+    async fn execute(&self, input: serde_json::Value) -> ToolResult {
+        let chat_id = match input.get("chat_id").and_then(|v| v.as_i64()) {
+            Some(id) => id,
+            None => return ToolResult::error("Missing required parameter: chat_id".into()),
+        };
+        if let Err(e) = authorize_chat_access(&input, chat_id) {
+            return ToolResult::error(e);
+        }
+
+        match input
+            .get("action")
+            .and_then(|v| v.as_str())
+            .unwrap_or("get")
+        {
+            "get" => match call_blocking(self.db.clone(), move |db| {
+                db.get_standing_instructions(chat_id)
+            })
+            .await
+            {
+                Ok(Some(text)) => ToolResult::success(text),
+                Ok(None) => ToolResult::success("No standing instructions set.".into()),
+                Err(e) => ToolResult::error(format!("Failed to load standing instructions: {e}")),
+            },
+            "set" => {
+                let text = match input
+                    .get("instructions")
+                    .and_then(|v| v.as_str())
+                    .map(validate_standing_instructions)
+                {
+                    Some(Ok(text)) => text,
+                    Some(Err(e)) => return ToolResult::error(e),
+                    None => {
+                        return ToolResult::error(
+                            "Missing required parameter for set: instructions".into(),
+                        )
+                    }
+                };
+                match call_blocking(self.db.clone(), move |db| {
+                    db.set_standing_instructions(chat_id, &text)
+                })
+                .await
+                {
+                    Ok(()) => ToolResult::success(
+                        "Standing instructions saved; they apply from the next message.".into(),
+                    ),
+                    Err(e) => {
+                        ToolResult::error(format!("Failed to save standing instructions: {e}"))
+                    }
+                }
+            }
+            "clear" => match call_blocking(self.db.clone(), move |db| {
+                db.clear_standing_instructions(chat_id)
+            })
+            .await
+            {
+                Ok(true) => ToolResult::success("Standing instructions cleared.".into()),
+                Ok(false) => ToolResult::success("No standing instructions were set.".into()),
+                Err(e) => ToolResult::error(format!("Failed to clear standing instructions: {e}")),
+            },
+            other => ToolResult::error(format!("Unknown action '{other}': use get, set or clear")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_db() -> (Arc<Database>, std::path::PathBuf) {
+        let dir =
+            std::env::temp_dir().join(format!("rayclaw_instructions_{}", uuid::Uuid::new_v4()));
+        let db = Arc::new(Database::new(dir.to_str().unwrap()).unwrap());
+        (db, dir)
+    }
+
+    #[tokio::test]
+    async fn test_standing_instructions_tool() {
+        let (db, dir) = test_db();
+        let tool = StandingInstructionsTool::new(db.clone());
+
+        let result = tool.execute(json!({"chat_id": 100})).await;
+        assert_eq!(result.content, "No standing instructions set.");
+
+        let result = tool
+            .execute(json!({
+                "chat_id": 100,
+                "action": "set",
+                "instructions": " Always answer in Spanish. "
+            }))
+            .await;
+        assert!(!result.is_error, "{}", result.content);
+        assert_eq!(
+            db.get_standing_instructions(100).unwrap().as_deref(),
+            Some("Always answer in Spanish.")
+        );
+
+        let result = tool.execute(json!({"chat_id": 100, "action": "set"})).await;
+        assert!(result.is_error);
+        let result = tool
+            .execute(json!({"chat_id": 100, "action": "set", "instructions": "  "}))
+            .await;
+        assert!(result.is_error);
+
+        let denied = tool
+            .execute(json!({
+                "chat_id": 100,
+                "action": "clear",
+                "__rayclaw_auth": {"caller_chat_id": 200, "control_chat_ids": []}
+            }))
+            .await;
+        assert!(denied.content.contains("Permission denied"));
+
+        let result = tool
+            .execute(json!({"chat_id": 100, "action": "clear"}))
+            .await;
+        assert_eq!(result.content, "Standing instructions cleared.");
+        assert!(db.get_standing_instructions(100).unwrap().is_none());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
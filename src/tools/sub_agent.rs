@@ -7,10 +7,11 @@ use super::{auth_context_from_input, schema_object, Tool, ToolRegistry, ToolResu
 use crate::config::Config;
 #[cfg(test)]
 use crate::config::WorkingDirIsolation;
-use crate::db::{call_blocking, Database};
+use crate::db::Database;
 use crate::llm_types::{
     ContentBlock, Message, MessageContent, ResponseContentBlock, ToolDefinition,
 };
+use crate::usage::LlmCallSource;
 
 const MAX_SUB_AGENT_ITERATIONS: usize = 10;
 
@@ -66,6 +67,7 @@ impl Tool for SubAgentTool {
         info!("Sub-agent starting task: {}", task);
 
         let llm = crate::llm::create_provider(&self.config);
+        let vision = llm.capabilities().vision;
         let tools = ToolRegistry::new_sub_agent(&self.config, self.db.clone());
         let tool_defs = tools.definitions().to_vec();
 
@@ -94,27 +96,20 @@ impl Tool for SubAgentTool {
             };
 
             if let Some(usage) = &response.usage {
-                let chat_id = auth_context.as_ref().map(|a| a.caller_chat_id).unwrap_or(0);
-                let caller_channel = auth_context
-                    .as_ref()
-                    .map(|a| a.caller_channel.clone())
-                    .unwrap_or_else(|| "sub_agent".to_string());
-                let provider = self.config.llm_provider.clone();
-                let model = self.config.model.clone();
-                let input_tokens = i64::from(usage.input_tokens);
-                let output_tokens = i64::from(usage.output_tokens);
-                let _ = call_blocking(self.db.clone(), move |db| {
-                    db.log_llm_usage(
-                        chat_id,
-                        &caller_channel,
-                        &provider,
-                        &model,
-                        input_tokens,
-                        output_tokens,
-                        "sub_agent",
-                    )
-                    .map(|_| ())
-                })
+                crate::usage::record_llm_call(
+                    self.db.clone(),
+                    &self.config,
+                    LlmCallSource {
+                        chat_id: auth_context.as_ref().map_or(0, |a| a.caller_chat_id),
+                        caller_channel: auth_context
+                            .as_ref()
+                            .map_or("sub_agent", |a| a.caller_channel.as_str()),
+                        provider: &self.config.llm_provider,
+                        model: &self.config.model,
+                        request_kind: "sub_agent",
+                    },
+                    usage,
+                )
                 .await;
             }
 
@@ -142,18 +137,7 @@ impl Tool for SubAgentTool {
                 let assistant_content: Vec<ContentBlock> = response
                     .content
                     .iter()
-                    .map(|block| match block {
-                        ResponseContentBlock::Text { text } => {
-                            ContentBlock::Text { text: text.clone() }
-                        }
-                        ResponseContentBlock::ToolUse { id, name, input } => {
-                            ContentBlock::ToolUse {
-                                id: id.clone(),
-                                name: name.clone(),
-                                input: input.clone(),
-                            }
-                        }
-                    })
+                    .filter_map(ResponseContentBlock::to_content_block)
                     .collect();
 
                 messages.push(Message {
@@ -162,18 +146,23 @@ impl Tool for SubAgentTool {
                 });
 
                 let mut tool_results = Vec::new();
+                let mut tool_images = Vec::new();
                 for block in &response.content {
-                    if let ResponseContentBlock::ToolUse { id, name, input } = block {
+                    if let ResponseContentBlock::ToolUse {
+                        id, name, input, ..
+                    } = block
+                    {
                         info!(
                             "Sub-agent executing tool: {} (iteration {})",
                             name,
                             iteration + 1
                         );
-                        let result = if let Some(ref auth) = auth_context {
+                        let mut result = if let Some(ref auth) = auth_context {
                             tools.execute_with_auth(name, input.clone(), auth).await
                         } else {
                             tools.execute(name, input.clone()).await
                         };
+                        tool_images.extend(result.take_image_blocks(vision));
                         tool_results.push(ContentBlock::ToolResult {
                             tool_use_id: id.clone(),
                             content: result.content,
@@ -181,6 +170,7 @@ impl Tool for SubAgentTool {
                         });
                     }
                 }
+                tool_results.extend(tool_images);
 
                 messages.push(Message {
                     role: "user".into(),
@@ -241,19 +231,35 @@ mod tests {
             control_chat_ids: vec![],
             max_session_messages: 40,
             compact_keep_recent: 20,
+            session_idle_expiry_hours: 0,
+            session_expiry_summary: true,
             discord_bot_token: None,
             discord_allowed_channels: vec![],
             show_thinking: false,
+            thinking: crate::config::ThinkingConfig::default(),
+            inference: crate::config::InferenceParams::default(),
+            chat_inference: std::collections::HashMap::new(),
+            openrouter: crate::config::OpenRouterConfig::default(),
+            secret_patterns: vec![],
+            news_search: crate::config::NewsSearchConfig::default(),
+            maps: crate::config::MapsConfig::default(),
+            package_tracking: crate::config::PackageTrackingConfig::default(),
+            flight_status: crate::config::FlightStatusConfig::default(),
+            outbound_http: crate::config::OutboundHttpConfig::default(),
+            browsing_profiles_key: None,
+            tool_output_budgets: std::collections::HashMap::new(),
             web_enabled: false,
             web_host: "127.0.0.1".into(),
             web_port: 3900,
             web_auth_token: None,
+            web_public_url: None,
             web_max_inflight_per_session: 2,
             web_max_requests_per_window: 8,
             web_rate_window_seconds: 10,
             web_run_history_limit: 512,
             web_session_idle_ttl_seconds: 300,
             model_prices: vec![],
+            model_limits: vec![],
             embedding_provider: None,
             embedding_api_key: None,
             embedding_base_url: None,
@@ -266,8 +272,43 @@ mod tests {
             aws_secret_access_key: None,
             aws_session_token: None,
             aws_profile: None,
+            aws_bedrock_inference_profile: None,
+            aws_bedrock_inference_profiles: std::collections::HashMap::new(),
+            aws_bedrock_latency: None,
+            aws_endpoint_url: None,
+            aws_use_fips_endpoint: false,
+            ollama_keep_alive: None,
+            email_gateway: None,
             soul_path: None,
+            system_prompt_template: None,
             skip_tool_approval: false,
+            tool_approval_timeout_secs: 300,
+            workspace_quota_mb: 0,
+            workspace_tmp_ttl_hours: 0,
+            workspace_cleanup_interval_mins: 60,
+            image_max_dimension: 1568,
+            image_jpeg_quality: 85,
+            chart_font_path: None,
+            tts_model: "tts-1".into(),
+            tts_voice: "alloy".into(),
+            stt_backend: "openai".into(),
+            whisper_cpp_binary: "whisper-cli".into(),
+            whisper_cpp_model: None,
+            max_concurrent_agent_runs: 8,
+            outage_queue_size: 50,
+            outage_queue_ttl_mins: 60,
+            message_debounce_ms: 0,
+            digest_dedup_days: 14,
+            llm_max_retries: 3,
+            llm_retry_max_delay_secs: 60,
+            llm_trace: false,
+            startup_preflight: true,
+            coordination_redis_url: None,
+            coordination_key_prefix: "rayclaw".into(),
+            cache_backend: "memory".into(),
+            queue_backend: "memory".into(),
+            response_cache_ttl_secs: 0,
+            provider_credentials: std::collections::HashMap::new(),
             skills_dir: None,
             channels: std::collections::HashMap::new(),
         }
@@ -307,7 +348,7 @@ mod tests {
         let config = test_config();
         let registry = ToolRegistry::new_sub_agent(&config, test_db());
         let defs = registry.definitions();
-        assert_eq!(defs.len(), 12);
+        assert_eq!(defs.len(), 21);
     }
 
     #[test]
@@ -325,9 +366,16 @@ mod tests {
         assert!(names.contains(&"glob"));
         assert!(names.contains(&"grep"));
         assert!(names.contains(&"web_search"));
+        assert!(names.contains(&"news_search"));
+        assert!(names.contains(&"wiki_lookup"));
+        assert!(names.contains(&"directions"));
+        assert!(names.contains(&"place_search"));
+        assert!(names.contains(&"track_package"));
+        assert!(names.contains(&"flight_status"));
         assert!(names.contains(&"web_fetch"));
         assert!(names.contains(&"read_memory"));
         assert!(names.contains(&"structured_memory_search"));
+        assert!(names.contains(&"read_tool_output"));
 
         // Should NOT include
         assert!(!names.contains(&"sub_agent"));
@@ -0,0 +1,311 @@
+//! Per-tool output budgets and the `read_tool_output` overflow tool.
+//!
+//! Output over a tool's token budget (`tool_output_budgets`, falling back
+//! to [`DEFAULT_TOOL_OUTPUT_BUDGETS`]) is cut to fit, and the full text is
+//! saved under `<data_dir>/runtime/tool_outputs/<handle>.txt` so the model
+//! can page through the rest with `read_tool_output` instead of re-running
+//! the tool.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use async_trait::async_trait;
+use serde_json::json;
+use tracing::warn;
+
+use crate::config::Config;
+use crate::llm_types::ToolDefinition;
+
+use super::{schema_object, Tool, ToolResult};
+
+/// Budgets (in estimated tokens) for tools whose output is routinely large.
+/// Tools not listed here or in `tool_output_budgets` are not limited.
+pub const DEFAULT_TOOL_OUTPUT_BUDGETS: &[(&str, usize)] = &[
+    ("bash", 2_000),
+    ("browser", 8_000),
+    ("web_fetch", 4_000),
+    ("acp_prompt", 8_000),
+];
+
+/// Tokens `read_tool_output` returns per call unless asked for fewer.
+const READ_CHUNK_TOKENS: usize = 4_000;
+
+/// Saved outputs older than this are deleted when a registry starts.
+const RETENTION: Duration = Duration::from_secs(3 * 24 * 60 * 60);
+
+pub fn tool_outputs_dir(config: &Config) -> PathBuf {
+    Path::new(&config.runtime_data_dir()).join("tool_outputs")
+}
+
+/// Byte index where `text` exceeds `budget` tokens, by the same estimate
+/// as [`crate::llm::estimate_text_tokens`]; `None` when it fits.
+fn budget_cutoff(text: &str, budget: usize) -> Option<usize> {
+    let (mut ascii, mut other) = (0usize, 0usize);
+    for (i, c) in text.char_indices() {
+        if c.is_ascii() {
+            ascii += 1;
+        } else {
+            other += 1;
+        }
+        if ascii.div_ceil(4) + other > budget {
+            return Some(i);
+        }
+    }
+    None
+}
+
+#[derive(Default)]
+pub struct OutputBudgets {
+    budgets: HashMap<String, usize>,
+    /// Where overflow is saved; without it output is only truncated
+    dir: Option<PathBuf>,
+}
+
+impl OutputBudgets {
+    pub fn from_config(config: &Config) -> Self {
+        let mut budgets: HashMap<String, usize> = DEFAULT_TOOL_OUTPUT_BUDGETS
+            .iter()
+            .map(|(tool, budget)| (tool.to_string(), *budget))
+            .collect();
+        budgets.extend(config.tool_output_budgets.clone());
+        // 0 lifts a default budget
+        budgets.retain(|_, budget| *budget > 0);
+
+        let dir = tool_outputs_dir(config);
+        prune_saved_outputs(&dir);
+        OutputBudgets {
+            budgets,
+            dir: Some(dir),
+        }
+    }
+
+    pub fn budget(&self, tool: &str) -> Option<usize> {
+        self.budgets.get(tool).copied()
+    }
+
+    /// Cut `result` to `tool`'s budget, saving the full text for
+    /// `read_tool_output`.
+    pub fn apply(&self, tool: &str, result: &mut ToolResult) {
+        let Some(budget) = self.budget(tool) else {
+            return;
+        };
+        let Some(cutoff) = budget_cutoff(&result.content, budget) else {
+            return;
+        };
+        let total = result.content.len();
+        let handle = self.dir.as_deref().and_then(|dir| {
+            let handle = uuid::Uuid::new_v4().simple().to_string()[..12].to_string();
+            let saved = std::fs::create_dir_all(dir)
+                .and_then(|_| std::fs::write(dir.join(format!("{handle}.txt")), &result.content));
+            match saved {
+                Ok(()) => Some(handle),
+                Err(e) => {
+                    warn!("Failed to save overflow of {tool} output: {e}");
+                    None
+                }
+            }
+        });
+
+        result.content.truncate(cutoff);
+        match handle {
+            Some(handle) => {
+                result.content.push_str(&format!(
+                    "\n\n[Output cut at ~{budget} tokens ({cutoff} of {total} bytes). The full output is saved: call read_tool_output with handle=\"{handle}\" and offset={cutoff} to read the rest.]"
+                ));
+                if !result
+                    .suggested_next_tools
+                    .iter()
+                    .any(|t| t == "read_tool_output")
+                {
+                    result
+                        .suggested_next_tools
+                        .push("read_tool_output".to_string());
+                }
+            }
+            None => result.content.push_str(&format!(
+                "\n\n[Output cut at ~{budget} tokens ({cutoff} of {total} bytes).]"
+            )),
+        }
+        result.truncated = true;
+    }
+}
+
+fn prune_saved_outputs(dir: &Path) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let now = SystemTime::now();
+    for entry in entries.flatten() {
+        let expired = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| now.duration_since(modified).ok())
+            .is_some_and(|age| age > RETENTION);
+        if expired {
+            let _ = std::fs::remove_file(entry.path());
+        }
+    }
+}
+
+pub struct ReadToolOutputTool {
+    dir: PathBuf,
+}
+
+impl ReadToolOutputTool {
+    pub fn new(config: &Config) -> Self {
+        ReadToolOutputTool {
+            dir: tool_outputs_dir(config),
+        }
+    }
+}
+
+#[async_trait]
+impl Tool for ReadToolOutputTool {
+    fn name(&self) -> &str {
+        "read_tool_output"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "read_tool_output".into(),
+            description: "Read more of a tool result that was cut to fit its output budget. Use the handle and offset given in the truncation notice; each call returns the next chunk and the offset to continue from.".into(),
+            input_schema: schema_object(
+                json!({
+                    "handle": {
+                        "type": "string",
+                        "description": "Handle from the truncation notice"
+                    },
+                    "offset": {
+                        "type": "integer",
+                        "minimum": 0,
+                        "description": "Byte offset to start reading from (default 0)"
+                    },
+                    "max_tokens": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "maximum": READ_CHUNK_TOKENS,
+                        "description": format!("Approximate size of the chunk to return (default {READ_CHUNK_TOKENS})")
+                    }
+                }),
+                &["handle"],
+            ),
+        }
+    }
+
+    async fn execute(&self, input: serde_json::Value) -> ToolResult {
+        let handle = input.get("handle").and_then(|v| v.as_str()).unwrap_or("");
+        if handle.is_empty() || !handle.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return ToolResult::error(format!("Invalid tool output handle: {handle:?}"));
+        }
+        let content = match std::fs::read_to_string(self.dir.join(format!("{handle}.txt"))) {
+            Ok(content) => content,
+            Err(_) => {
+                return ToolResult::error(format!(
+                    "No saved tool output with handle {handle} (outputs expire after a few days)"
+                ))
+            }
+        };
+        let offset = input.get("offset").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+        if offset >= content.len() {
+            return ToolResult::error(format!(
+                "Offset {offset} is past the end of the output ({} bytes)",
+                content.len()
+            ));
+        }
+        let start = crate::text::floor_char_boundary(&content, offset);
+        let max_tokens = input
+            .get("max_tokens")
+            .and_then(|v| v.as_u64())
+            .map_or(READ_CHUNK_TOKENS, |n| n as usize);
+
+        let rest = &content[start..];
+        match budget_cutoff(rest, max_tokens) {
+            Some(cut) => {
+                let next = start + cut;
+                ToolResult::success(format!(
+                    "{}\n\n[Bytes {start}..{next} of {}. Continue with offset={next}.]",
+                    &rest[..cut],
+                    content.len()
+                ))
+                .with_truncated(true)
+                .with_suggested_next_tools(&["read_tool_output"])
+            }
+            None => ToolResult::success(format!(
+                "{rest}\n\n[Bytes {start}..{} of {}: end of output.]",
+                content.len(),
+                content.len()
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn budgets(dir: &Path) -> OutputBudgets {
+        OutputBudgets {
+            budgets: HashMap::from([("bash".to_string(), 10)]),
+            dir: Some(dir.to_path_buf()),
+        }
+    }
+
+    #[test]
+    fn test_budget_cutoff_counts_estimated_tokens() {
+        assert_eq!(budget_cutoff("abcdefgh", 2), None);
+        assert_eq!(budget_cutoff("abcdefghi", 2), Some(8));
+        // Non-ASCII characters count a token each
+        assert_eq!(budget_cutoff("日本語", 2), Some(6));
+    }
+
+    #[tokio::test]
+    async fn test_overflow_is_saved_and_readable() {
+        let dir = std::env::temp_dir().join(format!("rayclaw_tool_out_{}", uuid::Uuid::new_v4()));
+        let full = "x".repeat(100);
+        let mut result = ToolResult::success(full.clone());
+        budgets(&dir).apply("bash", &mut result);
+
+        assert!(result.truncated);
+        assert!(result.content.starts_with(&"x".repeat(40)));
+        assert!(result.content.contains("offset=40"));
+        assert_eq!(result.suggested_next_tools, vec!["read_tool_output"]);
+        let handle = result
+            .content
+            .split("handle=\"")
+            .nth(1)
+            .and_then(|s| s.split('"').next())
+            .unwrap()
+            .to_string();
+
+        let tool = ReadToolOutputTool { dir: dir.clone() };
+        let chunk = tool
+            .execute(json!({"handle": handle, "offset": 40, "max_tokens": 10}))
+            .await;
+        assert!(!chunk.is_error);
+        assert!(chunk.content.contains("offset=80"));
+        let last = tool.execute(json!({"handle": handle, "offset": 80})).await;
+        assert!(last.content.starts_with(&"x".repeat(20)));
+        assert!(last.content.contains("end of output"));
+
+        // Unbudgeted tools and short output pass through untouched
+        let mut other = ToolResult::success(full.clone());
+        budgets(&dir).apply("read_file", &mut other);
+        assert_eq!(other.content, full);
+        assert!(!other.truncated);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_read_tool_output_rejects_bad_handles() {
+        let tool = ReadToolOutputTool {
+            dir: std::env::temp_dir(),
+        };
+        let result = tool.execute(json!({"handle": "../etc/passwd"})).await;
+        assert!(result.is_error);
+        let result = tool.execute(json!({"handle": "doesnotexist1"})).await;
+        assert!(result.is_error);
+    }
+}
@@ -0,0 +1,158 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde_json::json;
+
+use super::{auth_context_from_input, authorize_chat_access, schema_object, Tool, ToolResult};
+use crate::db::Database;
+use crate::llm_types::ToolDefinition;
+
+pub struct UsageReportTool {
+    db: Arc<Database>,
+}
+
+impl UsageReportTool {
+    pub fn new(db: Arc<Database>) -> Self {
+        UsageReportTool { db }
+    }
+}
+
+#[async_trait]
+impl Tool for UsageReportTool {
+    fn name(&self) -> &str {
+        "usage_report"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "usage_report".into(),
+            description: "Report LLM token usage and cost per UTC day. Includes input, \
+                output and prompt-cache tokens; cost is computed from `model_prices` and \
+                calls to unpriced models are counted separately. Defaults to the current \
+                chat over the last 7 days."
+                .into(),
+            input_schema: schema_object(
+                json!({
+                    "chat_id": {
+                        "type": "integer",
+                        "description": "Chat to report on (default: the current chat)"
+                    },
+                    "all_chats": {
+                        "type": "boolean",
+                        "description": "Report across all chats (control chats only)"
+                    },
+                    "days": {
+                        "type": "integer",
+                        "description": "Number of days to cover, including today (default: 7, max: 90)"
+                    }
+                }),
+                &[],
+            ),
+        }
+    }
+
+    async fn execute(&self, input: serde_json::Value) -> ToolResult {
+        let all_chats = input
+            .get("all_chats")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        let chat_id = if all_chats {
+            if let Some(auth) = auth_context_from_input(&input) {
+                if !auth.is_control_chat() {
+                    return ToolResult::error(format!(
+                        "Permission denied: chat {} cannot view usage for all chats",
+                        auth.caller_chat_id
+                    ));
+                }
+            }
+            None
+        } else {
+            match input.get("chat_id").and_then(|v| v.as_i64()) {
+                Some(id) => {
+                    if let Err(e) = authorize_chat_access(&input, id) {
+                        return ToolResult::error(e);
+                    }
+                    Some(id)
+                }
+                None => match auth_context_from_input(&input) {
+                    Some(auth) => Some(auth.caller_chat_id),
+                    None => return ToolResult::error("chat_id is required".to_string()),
+                },
+            }
+        };
+        let days = input
+            .get("days")
+            .and_then(|v| v.as_i64())
+            .unwrap_or(7)
+            .clamp(1, 90);
+
+        match crate::usage::build_cost_report(self.db.clone(), chat_id, days).await {
+            Ok(report) => ToolResult::success(report.to_string()),
+            Err(e) => ToolResult::error(format!("Failed to load usage: {e}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::LlmUsageEntry;
+
+    fn test_db() -> (Arc<Database>, std::path::PathBuf) {
+        let dir = std::env::temp_dir().join(format!("rayclaw_usage_{}", uuid::Uuid::new_v4()));
+        let db = Arc::new(Database::new(dir.to_str().unwrap()).unwrap());
+        (db, dir)
+    }
+
+    fn entry(chat_id: i64, cost_usd: Option<f64>) -> LlmUsageEntry {
+        LlmUsageEntry {
+            chat_id,
+            caller_channel: "telegram".into(),
+            provider: "anthropic".into(),
+            model: "claude-sonnet-4-5".into(),
+            input_tokens: 100,
+            output_tokens: 20,
+            cache_read_tokens: 50,
+            cost_usd,
+            request_kind: "agent_loop".into(),
+            ..LlmUsageEntry::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_usage_report_scoped_to_caller_chat() {
+        let (db, dir) = test_db();
+        db.record_llm_usage(&entry(100, Some(0.25))).unwrap();
+        db.record_llm_usage(&entry(100, None)).unwrap();
+        db.record_llm_usage(&entry(200, Some(1.0))).unwrap();
+        let tool = UsageReportTool::new(db);
+        let auth = json!({"caller_chat_id": 100, "control_chat_ids": []});
+
+        let result = tool.execute(json!({"__rayclaw_auth": auth})).await;
+        assert!(!result.is_error, "{}", result.content);
+        let body: serde_json::Value = serde_json::from_str(&result.content).unwrap();
+        assert_eq!(body["requests"], 2);
+        assert_eq!(body["cache_read_tokens"], 100);
+        assert_eq!(body["unpriced_requests"], 1);
+        assert!((body["cost_usd"].as_f64().unwrap() - 0.25).abs() < 1e-9);
+        assert_eq!(body["daily"].as_array().unwrap().len(), 1);
+
+        let denied = tool
+            .execute(json!({"chat_id": 200, "__rayclaw_auth": auth}))
+            .await;
+        assert!(denied.is_error);
+        let denied_all = tool
+            .execute(json!({"all_chats": true, "__rayclaw_auth": auth}))
+            .await;
+        assert!(denied_all.is_error);
+
+        let control = json!({"caller_chat_id": 100, "control_chat_ids": [100]});
+        let all = tool
+            .execute(json!({"all_chats": true, "__rayclaw_auth": control}))
+            .await;
+        let body: serde_json::Value = serde_json::from_str(&all.content).unwrap();
+        assert_eq!(body["requests"], 3);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
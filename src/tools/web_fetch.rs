@@ -1,26 +1,42 @@
-use std::sync::OnceLock;
+use std::sync::{Arc, OnceLock};
 
 use async_trait::async_trait;
+use reqwest::header::{HeaderMap, WWW_AUTHENTICATE};
+use reqwest::StatusCode;
 use serde_json::json;
 
 use super::web_html::{extract_primary_html, html_to_text};
-use super::{schema_object, Tool, ToolResult};
+use super::{auth_context_from_input, schema_object, Tool, ToolResult};
+use crate::browsing_profiles::{domain_allowed, BrowsingProfiles};
+use crate::db::BrowsingProfile;
 use crate::llm_types::ToolDefinition;
-use crate::text::floor_char_boundary;
+use crate::outbound_http::{self, OutboundClient};
 
-fn http_client() -> &'static reqwest::Client {
-    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+fn client_builder() -> reqwest::ClientBuilder {
+    outbound_http::client_builder("web_fetch", "RayClaw/1.0")
+        .timeout(std::time::Duration::from_secs(15))
+        .redirect(reqwest::redirect::Policy::limited(5))
+}
+
+fn http_client() -> &'static OutboundClient {
+    static CLIENT: OnceLock<OutboundClient> = OnceLock::new();
     CLIENT.get_or_init(|| {
-        reqwest::Client::builder()
-            .timeout(std::time::Duration::from_secs(15))
-            .redirect(reqwest::redirect::Policy::limited(5))
-            .user_agent("RayClaw/1.0")
+        client_builder()
             .build()
             .expect("failed to build HTTP client")
+            .into()
     })
 }
 
-pub struct WebFetchTool;
+pub struct WebFetchTool {
+    profiles: Arc<BrowsingProfiles>,
+}
+
+impl WebFetchTool {
+    pub fn new(profiles: Arc<BrowsingProfiles>) -> Self {
+        WebFetchTool { profiles }
+    }
+}
 
 #[async_trait]
 impl Tool for WebFetchTool {
@@ -32,13 +48,17 @@ impl Tool for WebFetchTool {
         ToolDefinition {
             name: "web_fetch".into(),
             description:
-                "Fetch a URL and return its text content (HTML parsed, scripts/styles removed). Max 20KB."
+                "Fetch a URL and return its text content (HTML parsed, scripts/styles removed). Long pages are cut to the tool output budget; read the rest with read_tool_output. For pages behind a login, pass a browsing profile (see browsing_profile)."
                     .into(),
             input_schema: schema_object(
                 json!({
                     "url": {
                         "type": "string",
                         "description": "The URL to fetch"
+                    },
+                    "profile": {
+                        "type": "string",
+                        "description": "Browsing profile whose cookies and login to use; the URL must be on one of its domains"
                     }
                 }),
                 &["url"],
@@ -51,8 +71,24 @@ impl Tool for WebFetchTool {
             Some(u) => u,
             None => return ToolResult::error("Missing required parameter: url".into()),
         };
+        let chat_id = auth_context_from_input(&input).map(|auth| auth.caller_chat_id);
+        if let Err(e) = crate::politeness::before_request("web_fetch", url, chat_id).await {
+            return ToolResult::error(e);
+        }
 
-        match fetch_url(url).await {
+        let fetched = match input.get("profile").and_then(|v| v.as_str()) {
+            Some(name) => {
+                let Some(auth) = auth_context_from_input(&input) else {
+                    return ToolResult::error("Browsing profiles need a chat context".into());
+                };
+                match self.profiles.get(auth.caller_chat_id, name).await {
+                    Ok(profile) => fetch_with_profile(url, &self.profiles, &profile).await,
+                    Err(e) => return ToolResult::error(e),
+                }
+            }
+            None => fetch_url(url).await,
+        };
+        match fetched {
             Ok(text) => ToolResult::success(text),
             Err(e) => ToolResult::error(format!("Failed to fetch URL: {e}")),
         }
@@ -65,64 +101,167 @@ async fn fetch_url(url: &str) -> Result<String, String> {
         .send()
         .await
         .map_err(|e| e.to_string())?;
+    page_text(resp).await
+}
+
+/// Whether a 401 response offers HTTP Basic authentication.
+fn offers_basic_auth(headers: &HeaderMap) -> bool {
+    headers.get_all(WWW_AUTHENTICATE).iter().any(|value| {
+        value
+            .to_str()
+            .is_ok_and(|v| v.trim_start().to_ascii_lowercase().starts_with("basic"))
+    })
+}
+
+/// Fetch with the profile's cookie jar, answering a Basic auth challenge
+/// with its login, and keep whatever cookies the site set.
+async fn fetch_with_profile(
+    url: &str,
+    profiles: &BrowsingProfiles,
+    profile: &BrowsingProfile,
+) -> Result<String, String> {
+    let host = reqwest::Url::parse(url)
+        .map_err(|e| e.to_string())?
+        .host_str()
+        .unwrap_or_default()
+        .to_string();
+    if !domain_allowed(&profile.domains, &host) {
+        return Err(format!(
+            "{host} is not one of profile '{}' domains ({})",
+            profile.name,
+            profile.domains.join(", ")
+        ));
+    }
+    let jar = Arc::new(profiles.cookie_jar(profile)?);
+    let client: OutboundClient = client_builder()
+        .cookie_provider(jar.clone())
+        .build()
+        .map_err(|e| e.to_string())?
+        .into();
+
+    let mut resp = client.get(url).send().await.map_err(|e| e.to_string())?;
+    if resp.status() == StatusCode::UNAUTHORIZED && offers_basic_auth(resp.headers()) {
+        if let Some(username) = &profile.username {
+            let password = profiles.password(profile)?;
+            resp = client
+                .get(url)
+                .basic_auth(username, password)
+                .send()
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    profiles.store_cookie_jar(profile, &jar).await?;
+    page_text(resp).await
+}
 
+async fn page_text(resp: reqwest::Response) -> Result<String, String> {
     if !resp.status().is_success() {
         return Err(format!("HTTP {}", resp.status()));
     }
 
     let body = resp.text().await.map_err(|e| e.to_string())?;
     let primary = extract_primary_html(&body);
-    let text = html_to_text(primary);
-
-    const MAX_BYTES: usize = 20_000;
-    if text.len() > MAX_BYTES {
-        let truncated = &text[..floor_char_boundary(&text, MAX_BYTES)];
-        Ok(format!("{truncated}\n\n[Truncated at 20KB]"))
-    } else {
-        Ok(text)
-    }
+    Ok(html_to_text(primary))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::db::Database;
     use serde_json::json;
 
+    fn test_tool() -> (WebFetchTool, std::path::PathBuf) {
+        let dir = std::env::temp_dir().join(format!("rayclaw_webfetch_{}", uuid::Uuid::new_v4()));
+        let db = Arc::new(Database::new(dir.to_str().unwrap()).unwrap());
+        let profiles = BrowsingProfiles::new(db, dir.to_str().unwrap(), None);
+        (WebFetchTool::new(Arc::new(profiles)), dir)
+    }
+
     #[test]
     fn test_web_fetch_definition() {
-        let tool = WebFetchTool;
+        let (tool, dir) = test_tool();
         assert_eq!(tool.name(), "web_fetch");
         let def = tool.definition();
         assert_eq!(def.name, "web_fetch");
-        assert!(def.description.contains("20KB"));
+        assert!(def.description.contains("read_tool_output"));
         assert!(def.input_schema["properties"]["url"].is_object());
+        assert!(def.input_schema["properties"]["profile"].is_object());
         let required = def.input_schema["required"].as_array().unwrap();
         assert!(required.iter().any(|v| v == "url"));
+        let _ = std::fs::remove_dir_all(&dir);
     }
 
     #[tokio::test]
     async fn test_web_fetch_missing_url() {
-        let tool = WebFetchTool;
+        let (tool, dir) = test_tool();
         let result = tool.execute(json!({})).await;
         assert!(result.is_error);
         assert!(result.content.contains("Missing required parameter: url"));
+        let _ = std::fs::remove_dir_all(&dir);
     }
 
     #[tokio::test]
     async fn test_web_fetch_null_url() {
-        let tool = WebFetchTool;
+        let (tool, dir) = test_tool();
         let result = tool.execute(json!({"url": null})).await;
         assert!(result.is_error);
         assert!(result.content.contains("Missing required parameter: url"));
+        let _ = std::fs::remove_dir_all(&dir);
     }
 
     #[tokio::test]
     async fn test_web_fetch_invalid_url() {
-        let tool = WebFetchTool;
+        let (tool, dir) = test_tool();
         let result = tool
             .execute(json!({"url": "https://this-domain-does-not-exist-12345.example"}))
             .await;
         assert!(result.is_error);
         assert!(result.content.contains("Failed to fetch URL"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_offers_basic_auth() {
+        let mut headers = HeaderMap::new();
+        assert!(!offers_basic_auth(&headers));
+        headers.append(WWW_AUTHENTICATE, "Bearer realm=\"api\"".parse().unwrap());
+        assert!(!offers_basic_auth(&headers));
+        headers.append(
+            WWW_AUTHENTICATE,
+            "Basic realm=\"intranet\"".parse().unwrap(),
+        );
+        assert!(offers_basic_auth(&headers));
+    }
+
+    #[tokio::test]
+    async fn test_web_fetch_profile_checks() {
+        let (tool, dir) = test_tool();
+        let auth = json!({"caller_chat_id": 100, "control_chat_ids": []});
+        let result = tool
+            .execute(json!({"url": "https://example.com", "profile": "work"}))
+            .await;
+        assert!(result.content.contains("chat context"));
+        let result = tool
+            .execute(
+                json!({"url": "https://example.com", "profile": "work", "__rayclaw_auth": auth}),
+            )
+            .await;
+        assert!(result.content.contains("No browsing profile named 'work'"));
+
+        tool.profiles
+            .save(100, "work", Some(vec!["example.com".into()]), None, None)
+            .await
+            .unwrap();
+        let result = tool
+            .execute(
+                json!({"url": "https://example.org/", "profile": "work", "__rayclaw_auth": auth}),
+            )
+            .await;
+        assert!(result.is_error);
+        assert!(result
+            .content
+            .contains("example.org is not one of profile 'work' domains"));
+        let _ = std::fs::remove_dir_all(&dir);
     }
 }
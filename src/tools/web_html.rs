@@ -1,20 +1,22 @@
-use crate::text::floor_char_boundary;
+use regex::Regex;
+use scraper::{ElementRef, Html, Selector};
+use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
+use std::sync::OnceLock;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SearchItem {
     pub title: String,
     pub url: String,
     pub snippet: String,
+    /// Publication date (`YYYY-MM-DD`) when the result page shows one
+    pub published: Option<String>,
 }
 
 fn strip_block(mut html: String, tag: &str) -> String {
     let open = format!("<{}", tag);
     let close = format!("</{}>", tag);
-    loop {
-        let Some(start) = find_case_insensitive(&html, &open, 0) else {
-            break;
-        };
+    while let Some(start) = find_case_insensitive(&html, &open, 0) {
         let Some(end) = find_case_insensitive(&html, &close, start) else {
             html.truncate(start);
             break;
@@ -107,101 +109,241 @@ pub fn collapse_whitespace(input: &str) -> String {
     compact
 }
 
-fn extract_attr(tag: &str, attr: &str) -> Option<String> {
-    let lower_tag = tag.to_ascii_lowercase();
-    let needle = format!("{}=", attr.to_ascii_lowercase());
-    let idx = lower_tag.find(&needle)?;
-    let raw = &tag[idx + needle.len()..];
-    let raw = raw.trim_start();
-    if raw.is_empty() {
-        return None;
-    }
-
-    let first = raw.chars().next()?;
-    if first == '"' || first == '\'' {
-        let quote = first;
-        let rest = &raw[1..];
-        let end = rest.find(quote)?;
-        return Some(rest[..end].to_string());
-    }
-
-    let end = raw
-        .find(|c: char| c.is_whitespace() || c == '>')
-        .unwrap_or(raw.len());
-    Some(raw[..end].to_string())
+/// One DuckDuckGo result page layout: how to find result titles (links)
+/// and their snippets.
+struct DdgLayout {
+    title: &'static str,
+    snippet: &'static str,
 }
 
-fn extract_snippet_near(html: &str, from: usize) -> String {
-    let from = floor_char_boundary(html, from.min(html.len()));
-    let window_end = floor_char_boundary(html, from.saturating_add(4000).min(html.len()));
-    let segment = &html[from..window_end];
-    let Some(class_idx) = find_case_insensitive(segment, "result__snippet", 0) else {
-        return String::new();
-    };
-
-    let tag_start = segment[..class_idx].rfind('<').unwrap_or(0);
-    let Some(tag_end_rel) = segment[tag_start..].find('>') else {
-        return String::new();
-    };
-    let tag_end = tag_start + tag_end_rel;
+/// Layouts seen on `html.duckduckgo.com` (current and older markup),
+/// `lite.duckduckgo.com`, and the script-rendered main site. Tried in
+/// order; the first that finds anything wins.
+const DDG_LAYOUTS: &[DdgLayout] = &[
+    DdgLayout {
+        title: "a.result__a",
+        snippet: ".result__snippet",
+    },
+    DdgLayout {
+        title: "a.result-link",
+        snippet: ".result-snippet",
+    },
+    DdgLayout {
+        title: r#"a[data-testid="result-title-a"]"#,
+        snippet: r#"[data-result="snippet"]"#,
+    },
+    DdgLayout {
+        title: "h2.result__title > a",
+        snippet: ".result__snippet",
+    },
+];
+
+/// Text that only appears on DuckDuckGo's bot check (CAPTCHA) page.
+const DDG_CHALLENGE_MARKERS: &[&str] = &[
+    "anomaly-modal",
+    "challenge-form",
+    "/anomaly.js",
+    "bots use DuckDuckGo too",
+];
+
+const DDG_NO_RESULTS_MARKERS: &[&str] = &["class=\"no-results\"", "No results found for"];
+
+/// Elements that carry a result's date, in any layout.
+const DDG_DATE_SELECTOR: &str = "time, .result__timestamp, .timestamp";
+
+/// What a DuckDuckGo response turned out to be.
+#[derive(Debug)]
+pub enum DdgPage {
+    Results(Vec<SearchItem>),
+    NoResults,
+    /// The bot check page instead of results
+    Challenge,
+    /// No known layout matched, e.g. after a markup change
+    Unrecognized,
+}
 
-    let closing = if segment[tag_start..].starts_with("<a") {
-        "</a>"
+pub fn parse_ddg_page(html: &str, max_results: usize) -> DdgPage {
+    if DDG_CHALLENGE_MARKERS.iter().any(|m| html.contains(m)) {
+        return DdgPage::Challenge;
+    }
+    let items = extract_ddg_results(html, max_results);
+    if !items.is_empty() {
+        DdgPage::Results(items)
+    } else if DDG_NO_RESULTS_MARKERS.iter().any(|m| html.contains(m)) {
+        DdgPage::NoResults
     } else {
-        "</div>"
-    };
-
-    let Some(close_rel) = find_case_insensitive(segment, closing, tag_end) else {
-        return String::new();
-    };
-
-    let inner = &segment[tag_end + 1..close_rel];
-    html_to_text(inner)
+        DdgPage::Unrecognized
+    }
 }
 
 pub fn extract_ddg_results(html: &str, max_results: usize) -> Vec<SearchItem> {
-    let mut results = Vec::new();
-    let mut pos = 0usize;
+    let document = Html::parse_document(html);
+    for layout in DDG_LAYOUTS {
+        let items = extract_with_layout(&document, layout, max_results);
+        if !items.is_empty() {
+            return items;
+        }
+    }
+    Vec::new()
+}
 
-    while results.len() < max_results {
-        let Some(a_start) = find_case_insensitive(html, "<a", pos) else {
-            break;
-        };
-        let Some(a_tag_end_rel) = html[a_start..].find('>') else {
+fn extract_with_layout(document: &Html, layout: &DdgLayout, max_results: usize) -> Vec<SearchItem> {
+    let (Ok(title_sel), Ok(snippet_sel)) = (
+        Selector::parse(layout.title),
+        Selector::parse(layout.snippet),
+    ) else {
+        return Vec::new();
+    };
+    let date_sel = Selector::parse(DDG_DATE_SELECTOR).ok();
+    let mut results = Vec::new();
+    for link in document.select(&title_sel) {
+        if results.len() >= max_results {
             break;
+        }
+        let container = result_container(link, &title_sel);
+        if is_ad(link, container) {
+            continue;
+        }
+        let Some(url) = link.value().attr("href").and_then(ddg_target_url) else {
+            continue;
         };
-        let a_tag_end = a_start + a_tag_end_rel;
-        let a_tag = &html[a_start..=a_tag_end];
-
-        let class = extract_attr(a_tag, "class").unwrap_or_default();
-        if !class.split_whitespace().any(|c| c == "result__a") {
-            pos = a_tag_end + 1;
+        let title = element_text(link);
+        if title.is_empty() {
             continue;
         }
+        let snippet = container
+            .select(&snippet_sel)
+            .next()
+            .or_else(|| sibling_snippet(container, &title_sel, &snippet_sel))
+            .map(element_text)
+            .unwrap_or_default();
+        let (published, snippet) = match result_date(container, date_sel.as_ref()) {
+            Some(date) => (Some(date), snippet),
+            None => match split_snippet_date(&snippet) {
+                Some((date, rest)) => (Some(date), rest.to_string()),
+                None => (None, snippet),
+            },
+        };
+        results.push(SearchItem {
+            title,
+            url,
+            snippet,
+            published,
+        });
+    }
+    results
+}
 
-        let Some(close_rel) = find_case_insensitive(html, "</a>", a_tag_end + 1) else {
+/// The largest ancestor of `link` holding no other result title.
+fn result_container<'a>(link: ElementRef<'a>, title_sel: &Selector) -> ElementRef<'a> {
+    let mut container = link;
+    for ancestor in link.ancestors().filter_map(ElementRef::wrap) {
+        if ancestor.select(title_sel).nth(1).is_some() {
             break;
-        };
+        }
+        container = ancestor;
+    }
+    container
+}
 
-        let href = extract_attr(a_tag, "href")
-            .map(|h| decode_html_entities(&h).into_owned())
-            .unwrap_or_default();
-        let title_html = &html[a_tag_end + 1..close_rel];
-        let title = html_to_text(title_html);
-        let snippet = extract_snippet_near(html, close_rel + 4);
-
-        if !href.is_empty() && !title.is_empty() {
-            results.push(SearchItem {
-                title,
-                url: href,
-                snippet,
-            });
+/// For table layouts (lite) where the snippet sits in a later row: the
+/// first snippet among the following siblings, up to the next result.
+fn sibling_snippet<'a>(
+    container: ElementRef<'a>,
+    title_sel: &Selector,
+    snippet_sel: &Selector,
+) -> Option<ElementRef<'a>> {
+    for sibling in container.next_siblings().filter_map(ElementRef::wrap) {
+        if sibling.select(title_sel).next().is_some() {
+            return None;
+        }
+        if snippet_sel.matches(&sibling) {
+            return Some(sibling);
+        }
+        if let Some(snippet) = sibling.select(snippet_sel).next() {
+            return Some(snippet);
         }
+    }
+    None
+}
 
-        pos = close_rel + 4;
+/// A date element in the result, or an ISO timestamp in its text (the
+/// HTML endpoint prints one after the display URL).
+fn result_date(container: ElementRef, date_sel: Option<&Selector>) -> Option<String> {
+    if let Some(date_sel) = date_sel {
+        for element in container.select(date_sel) {
+            let text = element
+                .value()
+                .attr("datetime")
+                .map(str::to_string)
+                .unwrap_or_else(|| element_text(element));
+            if let Some(date) = parse_result_date(&text) {
+                return Some(date);
+            }
+        }
     }
+    static ISO: OnceLock<Regex> = OnceLock::new();
+    let iso = ISO.get_or_init(|| Regex::new(r"\b(\d{4}-\d{2}-\d{2})T\d{2}:\d{2}").unwrap());
+    let text = container.text().collect::<String>();
+    iso.captures(&text)
+        .and_then(|caps| parse_result_date(&caps[1]))
+}
 
-    results
+/// A snippet that starts with its page's date (`Mar 3, 2024 · ...`): the
+/// date and the rest of the snippet.
+fn split_snippet_date(snippet: &str) -> Option<(String, &str)> {
+    ["·", "—", " - "].iter().find_map(|sep| {
+        let (head, rest) = snippet.split_once(sep)?;
+        if head.len() > 20 {
+            return None;
+        }
+        parse_result_date(head).map(|date| (date, rest.trim_start()))
+    })
+}
+
+/// `YYYY-MM-DD` for the date formats result pages use.
+fn parse_result_date(text: &str) -> Option<String> {
+    let text = text.trim();
+    if let Some(day) = text.get(..10) {
+        if let Ok(date) = chrono::NaiveDate::parse_from_str(day, "%Y-%m-%d") {
+            return Some(date.to_string());
+        }
+    }
+    ["%b %d, %Y", "%B %d, %Y", "%d %b %Y", "%d %B %Y"]
+        .iter()
+        .find_map(|format| chrono::NaiveDate::parse_from_str(text, format).ok())
+        .map(|date| date.to_string())
+}
+
+fn is_ad(link: ElementRef, container: ElementRef) -> bool {
+    let href = link.value().attr("href").unwrap_or_default();
+    href.contains("duckduckgo.com/y.js")
+        || container
+            .value()
+            .classes()
+            .any(|c| c.starts_with("result--ad") || c == "result-sponsored")
+}
+
+/// The destination of a result link, unwrapping DuckDuckGo's redirect
+/// (`//duckduckgo.com/l/?uddg=<encoded url>&rut=...`).
+fn ddg_target_url(href: &str) -> Option<String> {
+    let href = href.trim();
+    if let Some((_, query)) = href.split_once("/l/?") {
+        let target = query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("uddg="))
+            .and_then(|encoded| urlencoding::decode(encoded).ok())?;
+        return Some(target.into_owned());
+    }
+    if href.starts_with("http://") || href.starts_with("https://") {
+        return Some(href.to_string());
+    }
+    href.strip_prefix("//")
+        .map(|rest| format!("https://{rest}"))
+}
+
+fn element_text(element: ElementRef) -> String {
+    collapse_whitespace(&element.text().collect::<String>())
 }
 
 pub fn extract_primary_html(html: &str) -> &str {
@@ -246,6 +388,108 @@ mod tests {
         assert_eq!(items[0].snippet, "This is snippet.");
     }
 
+    fn ddg_fixture(name: &str) -> String {
+        let path = format!("{}/tests/fixtures/ddg/{name}", env!("CARGO_MANIFEST_DIR"));
+        std::fs::read_to_string(path).unwrap()
+    }
+
+    fn summarize(items: &[SearchItem]) -> Vec<String> {
+        items
+            .iter()
+            .map(|i| format!("{} | {} | {}", i.title, i.url, i.snippet))
+            .collect()
+    }
+
+    #[test]
+    fn test_ddg_html_fixture() {
+        let DdgPage::Results(items) = parse_ddg_page(&ddg_fixture("html_results.html"), 8) else {
+            panic!("expected results");
+        };
+        assert_eq!(
+            summarize(&items),
+            vec![
+                "Tokio - An asynchronous Rust runtime | https://tokio.rs/ | Tokio is an event-driven, non-blocking I/O platform for writing asynchronous applications with the Rust programming language.",
+                "Asynchronous Programming in Rust | https://rust-lang.github.io/async-book/ | This book aims to be a thorough guide to asynchronous programming in Rust, from beginner to advanced.",
+                "async_std - Rust & friends | https://docs.rs/async-std/latest/async_std/ | Async version of the Rust standard library.",
+            ]
+        );
+        assert_eq!(
+            extract_ddg_results(&ddg_fixture("html_results.html"), 2).len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_ddg_lite_fixture() {
+        let DdgPage::Results(items) = parse_ddg_page(&ddg_fixture("lite_results.html"), 8) else {
+            panic!("expected results");
+        };
+        assert_eq!(
+            summarize(&items),
+            vec![
+                "Tokio - An asynchronous Rust runtime | https://tokio.rs/ | Tokio is an event-driven, non-blocking I/O platform for writing asynchronous applications.",
+                "smol-rs/smol: A small and fast async runtime for Rust | https://github.com/smol-rs/smol | ",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ddg_result_dates() {
+        let DdgPage::Results(items) = parse_ddg_page(&ddg_fixture("html_results.html"), 8) else {
+            panic!("expected results");
+        };
+        let dates: Vec<_> = items.iter().map(|i| i.published.as_deref()).collect();
+        assert_eq!(dates, vec![Some("2024-11-20"), None, Some("2023-03-07")]);
+        assert_eq!(
+            items[2].snippet,
+            "Async version of the Rust standard library."
+        );
+
+        assert_eq!(
+            parse_result_date("2024-02-29T10:00:00"),
+            Some("2024-02-29".into())
+        );
+        assert_eq!(parse_result_date("5 March 2024"), Some("2024-03-05".into()));
+        assert_eq!(parse_result_date("yesterday"), None);
+        assert_eq!(split_snippet_date("No date - just text"), None);
+    }
+
+    #[test]
+    fn test_ddg_challenge_and_empty_pages() {
+        assert!(matches!(
+            parse_ddg_page(&ddg_fixture("captcha.html"), 8),
+            DdgPage::Challenge
+        ));
+        assert!(matches!(
+            parse_ddg_page(&ddg_fixture("no_results.html"), 8),
+            DdgPage::NoResults
+        ));
+        assert!(matches!(
+            parse_ddg_page("<html><body><p>Something else</p></body></html>", 8),
+            DdgPage::Unrecognized
+        ));
+    }
+
+    #[test]
+    fn test_ddg_script_layout_and_redirects() {
+        let html = r#"<article data-testid="result"><h2><a data-testid="result-title-a" href="https://example.org/page">Example</a></h2><div data-result="snippet">Snippet text</div></article>"#;
+        let items = extract_ddg_results(html, 8);
+        assert_eq!(
+            summarize(&items),
+            vec!["Example | https://example.org/page | Snippet text"]
+        );
+
+        assert_eq!(
+            ddg_target_url("/l/?kh=-1&uddg=https%3A%2F%2Fexample.com%2Fa%3Fb%3D1"),
+            Some("https://example.com/a?b=1".into())
+        );
+        assert_eq!(
+            ddg_target_url("//example.com/x"),
+            Some("https://example.com/x".into())
+        );
+        assert_eq!(ddg_target_url("/settings"), None);
+    }
+
     #[test]
     fn test_extract_primary_html_prefers_main() {
         let html = "<body>body</body><main>main section</main>";
@@ -1,25 +1,41 @@
-use std::sync::OnceLock;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
 
 use async_trait::async_trait;
 use serde_json::json;
+use tracing::warn;
 
-use super::web_html::extract_ddg_results;
-use super::{schema_object, Tool, ToolResult};
+use super::web_html::{parse_ddg_page, DdgPage, SearchItem};
+use super::{auth_context_from_input, schema_object, Tool, ToolResult};
+use crate::config::Config;
 use crate::llm_types::ToolDefinition;
+use crate::outbound_http::{self, OutboundClient};
+use crate::response_cache::ResponseCache;
 
-fn http_client() -> &'static reqwest::Client {
-    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+fn http_client() -> &'static OutboundClient {
+    static CLIENT: OnceLock<OutboundClient> = OnceLock::new();
     CLIENT.get_or_init(|| {
-        reqwest::Client::builder()
+        outbound_http::client_builder("web_search", "RayClaw/1.0")
             .timeout(std::time::Duration::from_secs(15))
             .redirect(reqwest::redirect::Policy::limited(5))
-            .user_agent("RayClaw/1.0")
             .build()
             .expect("failed to build HTTP client")
+            .into()
     })
 }
 
-pub struct WebSearchTool;
+pub struct WebSearchTool {
+    cache: ResponseCache,
+}
+
+impl WebSearchTool {
+    pub fn new(config: &Config) -> Self {
+        WebSearchTool {
+            cache: ResponseCache::from_config(config),
+        }
+    }
+}
 
 #[async_trait]
 impl Tool for WebSearchTool {
@@ -30,13 +46,21 @@ impl Tool for WebSearchTool {
     fn definition(&self) -> ToolDefinition {
         ToolDefinition {
             name: "web_search".into(),
-            description: "Search the web using DuckDuckGo. Returns titles, URLs, and snippets."
-                .into(),
+            description: "Search the web using DuckDuckGo. Returns titles, URLs, snippets and publication dates when known. Results already returned by an earlier search in the same request are listed by URL only.".into(),
             input_schema: schema_object(
                 json!({
                     "query": {
                         "type": "string",
                         "description": "The search query"
+                    },
+                    "time_range": {
+                        "type": "string",
+                        "enum": ["day", "week", "month", "year"],
+                        "description": "Only return pages published within this period"
+                    },
+                    "site": {
+                        "type": "string",
+                        "description": "Only return results from this domain, e.g. \"docs.rs\""
                     }
                 }),
                 &["query"],
@@ -49,49 +73,265 @@ impl Tool for WebSearchTool {
             Some(q) => q,
             None => return ToolResult::error("Missing required parameter: query".into()),
         };
+        let time_range = match input.get("time_range").and_then(|v| v.as_str()) {
+            None => None,
+            Some(range) => match ddg_time_filter(range) {
+                Some(filter) => Some(filter),
+                None => {
+                    return ToolResult::error(format!(
+                        "Invalid time_range: {range} (expected day, week, month or year)"
+                    ))
+                }
+            },
+        };
+        let query = match input.get("site").and_then(|v| v.as_str()) {
+            None => query.to_string(),
+            Some(site) => match site_filter(site) {
+                Some(site) => format!("{query} site:{site}"),
+                None => return ToolResult::error(format!("Invalid site: {site}")),
+            },
+        };
 
-        match search_ddg(query).await {
-            Ok(results) => {
-                if results.is_empty() {
-                    ToolResult::success("No results found.".into())
-                } else {
-                    ToolResult::success(results)
+        match self.cached_search(&query, time_range).await {
+            Ok(items) => {
+                if items.is_empty() {
+                    return ToolResult::success("No results found.".into());
                 }
+                let (fresh, repeated) = match auth_context_from_input(&input) {
+                    Some(auth) => dedup_for_run(auth.caller_chat_id, items),
+                    None => dedup_results(items, &mut HashSet::new()),
+                };
+                let mut output = format_results(&fresh);
+                if !repeated.is_empty() {
+                    output.push_str(&format!(
+                        "Already returned by an earlier search in this request ({}):\n",
+                        repeated.len()
+                    ));
+                    for item in &repeated {
+                        output.push_str(&format!("- {}\n", item.url));
+                    }
+                }
+                ToolResult::success(output).with_suggested_next_tools(&["web_fetch"])
+            }
+            Err(SearchError::Challenge) => {
+                ToolResult::error(DDG_CHALLENGE_ERROR.into()).with_error_type("search_blocked")
             }
-            Err(e) => ToolResult::error(format!("Search failed: {e}")),
+            Err(SearchError::Failed(e)) => ToolResult::error(format!("Search failed: {e}")),
+        }
+    }
+}
+
+impl WebSearchTool {
+    /// [`search_ddg`] through the response cache. Failures aren't cached.
+    async fn cached_search(
+        &self,
+        query: &str,
+        time_filter: Option<&str>,
+    ) -> Result<Vec<SearchItem>, SearchError> {
+        let key = format!("web_search:{}:{query}", time_filter.unwrap_or_default());
+        if let Some(items) = self
+            .cache
+            .get(&key)
+            .await
+            .and_then(|cached| serde_json::from_str(&cached).ok())
+        {
+            return Ok(items);
+        }
+        let items = search_ddg(query, time_filter).await?;
+        if let Ok(json) = serde_json::to_string(&items) {
+            self.cache.put(&key, &json).await;
         }
+        Ok(items)
     }
 }
 
-async fn search_ddg(query: &str) -> Result<String, String> {
+/// Result pages tried in order: the HTML endpoint, then the lighter
+/// lite endpoint, which is sometimes served when the other is blocked or
+/// has changed its markup.
+const DDG_ENDPOINTS: &[&str] = &[
+    "https://html.duckduckgo.com/html/",
+    "https://lite.duckduckgo.com/lite/",
+];
+
+const DDG_CHALLENGE_ERROR: &str = "DuckDuckGo answered with its bot check (CAPTCHA) page instead of results: this server's IP is being rate-limited. Wait a few minutes before searching again; meanwhile use web_fetch on URLs you already know.";
+
+enum SearchError {
+    Challenge,
+    Failed(String),
+}
+
+/// DuckDuckGo's `df` parameter for a `time_range`.
+fn ddg_time_filter(range: &str) -> Option<&'static str> {
+    match range {
+        "day" => Some("d"),
+        "week" => Some("w"),
+        "month" => Some("m"),
+        "year" => Some("y"),
+        _ => None,
+    }
+}
+
+/// The bare domain for a `site:` operator, accepting a pasted URL.
+fn site_filter(site: &str) -> Option<String> {
+    let site = site.trim();
+    let site = site
+        .strip_prefix("https://")
+        .or_else(|| site.strip_prefix("http://"))
+        .unwrap_or(site);
+    let domain = site
+        .split('/')
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let valid = domain.contains('.')
+        && domain
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-');
+    valid.then_some(domain)
+}
+
+async fn search_ddg(
+    query: &str,
+    time_filter: Option<&str>,
+) -> Result<Vec<SearchItem>, SearchError> {
     let encoded = urlencoding::encode(query);
-    let url = format!("https://html.duckduckgo.com/html/?q={encoded}");
+    let mut failures = Vec::new();
+    let mut challenged = false;
 
+    for endpoint in DDG_ENDPOINTS {
+        let mut url = format!("{endpoint}?q={encoded}");
+        if let Some(filter) = time_filter {
+            url.push_str(&format!("&df={filter}"));
+        }
+        let body = match fetch_page(&url).await {
+            Ok(body) => body,
+            Err(e) => {
+                failures.push(format!("{endpoint}: {e}"));
+                continue;
+            }
+        };
+        match parse_ddg_page(&body, 8) {
+            DdgPage::Results(items) => return Ok(items),
+            DdgPage::NoResults => return Ok(Vec::new()),
+            DdgPage::Challenge => {
+                warn!("web_search: DuckDuckGo bot check at {endpoint}");
+                challenged = true;
+            }
+            DdgPage::Unrecognized => {
+                warn!("web_search: unrecognized DuckDuckGo page layout at {endpoint}");
+                failures.push(format!("{endpoint}: unrecognized result page layout"));
+            }
+        }
+    }
+
+    if challenged {
+        Err(SearchError::Challenge)
+    } else {
+        Err(SearchError::Failed(failures.join("; ")))
+    }
+}
+
+async fn fetch_page(url: &str) -> Result<String, String> {
     let resp = http_client()
-        .get(&url)
+        .get(url)
         .send()
         .await
         .map_err(|e| e.to_string())?;
-
     if !resp.status().is_success() {
         return Err(format!("HTTP {}", resp.status()));
     }
+    resp.text().await.map_err(|e| e.to_string())
+}
+
+/// Results a chat has been given during its current agent run, so
+/// overlapping queries don't hand the model the same pages again. Cleared
+/// by [`end_run`]; entries left by runs that failed expire after
+/// [`SEEN_RESULTS_TTL`].
+struct SeenResults {
+    keys: HashSet<String>,
+    touched: Instant,
+}
+
+const SEEN_RESULTS_TTL: Duration = Duration::from_secs(60 * 60);
+
+fn seen_results() -> &'static Mutex<HashMap<i64, SeenResults>> {
+    static SEEN: OnceLock<Mutex<HashMap<i64, SeenResults>>> = OnceLock::new();
+    SEEN.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Forget the results shown to `chat_id` once its agent run finishes.
+pub fn end_run(chat_id: i64) {
+    seen_results()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(&chat_id);
+}
+
+fn dedup_for_run(chat_id: i64, items: Vec<SearchItem>) -> (Vec<SearchItem>, Vec<SearchItem>) {
+    let mut seen = seen_results().lock().unwrap_or_else(|e| e.into_inner());
+    seen.retain(|_, run| run.touched.elapsed() < SEEN_RESULTS_TTL);
+    let run = seen.entry(chat_id).or_insert_with(|| SeenResults {
+        keys: HashSet::new(),
+        touched: Instant::now(),
+    });
+    run.touched = Instant::now();
+    dedup_results(items, &mut run.keys)
+}
 
-    let body = resp.text().await.map_err(|e| e.to_string())?;
-    let items = extract_ddg_results(&body, 8);
+/// Split `items` into new results and ones matching a key in `seen` (the
+/// same page under a different URL form, or the same long title).
+fn dedup_results(
+    items: Vec<SearchItem>,
+    seen: &mut HashSet<String>,
+) -> (Vec<SearchItem>, Vec<SearchItem>) {
+    let (mut fresh, mut repeated) = (Vec::new(), Vec::new());
+    for item in items {
+        let keys = result_keys(&item);
+        if keys.iter().any(|key| seen.contains(key)) {
+            repeated.push(item);
+        } else {
+            seen.extend(keys);
+            fresh.push(item);
+        }
+    }
+    (fresh, repeated)
+}
+
+fn result_keys(item: &SearchItem) -> Vec<String> {
+    let url = crate::digest_dedup::normalize_link(&item.url).unwrap_or_else(|| item.url.clone());
+    let mut keys = vec![format!("url:{url}")];
+    let title: Vec<String> = item
+        .title
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    let title = title.join(" ");
+    // Short titles ("Home", "Documentation") are too generic to compare
+    if title.len() >= 24 {
+        keys.push(format!("title:{title}"));
+    }
+    keys
+}
 
+fn format_results(items: &[SearchItem]) -> String {
     let mut output = String::new();
     for (i, item) in items.iter().enumerate() {
+        let published = item
+            .published
+            .as_deref()
+            .map(|date| format!(" (published {date})"))
+            .unwrap_or_default();
         output.push_str(&format!(
-            "{}. {}\n   {}\n   {}\n\n",
+            "{}. {}{}\n   {}\n   {}\n\n",
             i + 1,
             item.title,
+            published,
             item.url,
             item.snippet
         ));
     }
-
-    Ok(output)
+    output
 }
 
 #[cfg(test)]
@@ -101,7 +341,9 @@ mod tests {
 
     #[test]
     fn test_web_search_definition() {
-        let tool = WebSearchTool;
+        let tool = WebSearchTool {
+            cache: ResponseCache::disabled(),
+        };
         assert_eq!(tool.name(), "web_search");
         let def = tool.definition();
         assert_eq!(def.name, "web_search");
@@ -111,9 +353,100 @@ mod tests {
         assert!(required.iter().any(|v| v == "query"));
     }
 
+    fn item(title: &str, url: &str) -> SearchItem {
+        SearchItem {
+            title: title.into(),
+            url: url.into(),
+            snippet: String::new(),
+            published: None,
+        }
+    }
+
+    #[test]
+    fn test_format_results() {
+        let items = vec![
+            SearchItem {
+                snippet: "An async runtime.".into(),
+                ..item("Tokio", "https://tokio.rs/")
+            },
+            SearchItem {
+                snippet: "Release notes.".into(),
+                published: Some("2024-11-20".into()),
+                ..item("Tokio 1.42", "https://tokio.rs/blog")
+            },
+        ];
+        assert_eq!(
+            format_results(&items),
+            "1. Tokio\n   https://tokio.rs/\n   An async runtime.\n\n\
+             2. Tokio 1.42 (published 2024-11-20)\n   https://tokio.rs/blog\n   Release notes.\n\n"
+        );
+    }
+
+    #[test]
+    fn test_dedup_results_across_queries() {
+        let mut seen = HashSet::new();
+        let (fresh, repeated) = dedup_results(
+            vec![
+                item("Tokio", "https://tokio.rs/"),
+                item(
+                    "Announcing the async runtime survey",
+                    "https://a.example/post",
+                ),
+            ],
+            &mut seen,
+        );
+        assert_eq!(fresh.len(), 2);
+        assert!(repeated.is_empty());
+
+        let (fresh, repeated) = dedup_results(
+            vec![
+                item("Tokio docs", "http://www.tokio.rs?utm_source=x"),
+                item(
+                    "Announcing the Async Runtime Survey!",
+                    "https://b.example/mirror",
+                ),
+                item("Tokio", "https://docs.rs/tokio"),
+            ],
+            &mut seen,
+        );
+        assert_eq!(
+            fresh.iter().map(|i| i.url.as_str()).collect::<Vec<_>>(),
+            vec!["https://docs.rs/tokio"]
+        );
+        assert_eq!(repeated.len(), 2);
+    }
+
+    #[test]
+    fn test_search_filters() {
+        assert_eq!(ddg_time_filter("week"), Some("w"));
+        assert_eq!(ddg_time_filter("decade"), None);
+        assert_eq!(site_filter("docs.rs"), Some("docs.rs".into()));
+        assert_eq!(
+            site_filter("https://GitHub.com/tokio-rs"),
+            Some("github.com".into())
+        );
+        assert_eq!(site_filter("not a site"), None);
+    }
+
+    #[tokio::test]
+    async fn test_web_search_invalid_filters() {
+        let tool = WebSearchTool {
+            cache: ResponseCache::disabled(),
+        };
+        let result = tool
+            .execute(json!({"query": "rust", "time_range": "decade"}))
+            .await;
+        assert!(result.is_error);
+        assert!(result.content.contains("Invalid time_range"));
+        let result = tool.execute(json!({"query": "rust", "site": "a b"})).await;
+        assert!(result.is_error);
+    }
+
     #[tokio::test]
     async fn test_web_search_missing_query() {
-        let tool = WebSearchTool;
+        let tool = WebSearchTool {
+            cache: ResponseCache::disabled(),
+        };
         let result = tool.execute(json!({})).await;
         assert!(result.is_error);
         assert!(result.content.contains("Missing required parameter: query"));
@@ -121,7 +454,9 @@ mod tests {
 
     #[tokio::test]
     async fn test_web_search_null_query() {
-        let tool = WebSearchTool;
+        let tool = WebSearchTool {
+            cache: ResponseCache::disabled(),
+        };
         let result = tool.execute(json!({"query": null})).await;
         assert!(result.is_error);
         assert!(result.content.contains("Missing required parameter: query"));
@@ -0,0 +1,658 @@
+//! `wiki_lookup`: an article's summary or full text from Wikipedia plus
+//! infobox-style facts from Wikidata, through the official MediaWiki and
+//! Wikibase APIs instead of scraping pages.
+//!
+//! The Wikipedia language is the `language` parameter if given, else the
+//! chat's locale as reported by its platform (see `Database::get_chat_locale`),
+//! else English.
+
+use std::collections::HashSet;
+use std::sync::{Arc, OnceLock};
+
+use async_trait::async_trait;
+use serde_json::json;
+use tracing::warn;
+
+use super::{auth_context_from_input, schema_object, Tool, ToolResult};
+use crate::db::{call_blocking, Database};
+use crate::llm_types::ToolDefinition;
+use crate::outbound_http::{self, OutboundClient};
+
+const DEFAULT_LANGUAGE: &str = "en";
+const INTRO_MAX_CHARS: usize = 4_000;
+const FULL_MAX_CHARS: usize = 16_000;
+/// Values listed per property; long series (e.g. past populations) are cut
+const MAX_VALUES_PER_FACT: usize = 3;
+
+/// Wikidata properties shown as facts, in output order.
+const FACT_PROPERTIES: &[(&str, &str)] = &[
+    ("P31", "instance of"),
+    ("P279", "subclass of"),
+    ("P17", "country"),
+    ("P30", "continent"),
+    ("P131", "located in"),
+    ("P36", "capital"),
+    ("P35", "head of state"),
+    ("P6", "head of government"),
+    ("P37", "official language"),
+    ("P38", "currency"),
+    ("P1082", "population"),
+    ("P2046", "area"),
+    ("P2044", "elevation"),
+    ("P625", "coordinates"),
+    ("P569", "date of birth"),
+    ("P19", "place of birth"),
+    ("P570", "date of death"),
+    ("P20", "place of death"),
+    ("P27", "citizenship"),
+    ("P106", "occupation"),
+    ("P69", "educated at"),
+    ("P50", "author"),
+    ("P57", "director"),
+    ("P175", "performer"),
+    ("P136", "genre"),
+    ("P495", "country of origin"),
+    ("P577", "publication date"),
+    ("P571", "inception"),
+    ("P576", "dissolved"),
+    ("P112", "founded by"),
+    ("P169", "chief executive"),
+    ("P159", "headquarters"),
+    ("P452", "industry"),
+    ("P1128", "employees"),
+    ("P856", "official website"),
+];
+
+fn http_client() -> &'static OutboundClient {
+    static CLIENT: OnceLock<OutboundClient> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        // Wikimedia asks API clients for a User-Agent with contact details
+        outbound_http::client_builder(
+            "wiki_lookup",
+            "RayClaw/1.0 (https://github.com/rayclaw/rayclaw)",
+        )
+        .timeout(std::time::Duration::from_secs(15))
+        .build()
+        .expect("failed to build HTTP client")
+        .into()
+    })
+}
+
+/// Wikipedia subdomain for a language tag: `pt-BR` -> `pt`, `zh-Hans` -> `zh`.
+/// Dialect wikis (`zh-yue`, `be-tarask`) and `simple` are kept whole.
+fn wiki_language(tag: &str) -> Option<String> {
+    let tag = tag.trim().replace('_', "-").to_ascii_lowercase();
+    if tag.is_empty()
+        || tag.len() > 16
+        || !tag.chars().all(|c| c.is_ascii_lowercase() || c == '-')
+        || tag.starts_with('-')
+    {
+        return None;
+    }
+    let mut parts = tag.splitn(2, '-');
+    let primary = parts.next().unwrap_or_default();
+    match parts.next() {
+        // Region or script subtags (`BR`, `Hans`) are not separate wikis
+        Some(sub) if sub.len() == 2 || sub.len() == 4 => Some(primary.to_string()),
+        _ => Some(tag),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct WikiPage {
+    title: String,
+    url: String,
+    description: Option<String>,
+    extract: String,
+    wikidata_id: Option<String>,
+    disambiguation: bool,
+}
+
+/// First page of an action=query response (formatversion=2), or None when
+/// the title does not exist.
+fn parse_page(body: &serde_json::Value) -> Option<WikiPage> {
+    let pages = body.pointer("/query/pages")?.as_array()?;
+    // Search generators return pages in ranking order via `index`
+    let page = pages
+        .iter()
+        .filter(|p| p.get("missing").is_none() && p.get("invalid").is_none())
+        .min_by_key(|p| p["index"].as_i64().unwrap_or(0))?;
+    let title = page["title"].as_str()?.to_string();
+    let props = &page["pageprops"];
+    Some(WikiPage {
+        url: page["fullurl"].as_str().unwrap_or_default().to_string(),
+        description: props["wikibase-shortdesc"]
+            .as_str()
+            .or_else(|| page["description"].as_str())
+            .map(str::to_string),
+        extract: page["extract"]
+            .as_str()
+            .unwrap_or_default()
+            .trim()
+            .to_string(),
+        wikidata_id: props["wikibase_item"].as_str().map(str::to_string),
+        disambiguation: props.get("disambiguation").is_some(),
+        title,
+    })
+}
+
+/// One claim's value, with entity IDs left for label lookup.
+#[derive(Debug, Clone, PartialEq)]
+enum FactValue {
+    Entity(String),
+    Quantity {
+        amount: String,
+        unit: Option<String>,
+    },
+    Text(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Fact {
+    label: &'static str,
+    values: Vec<FactValue>,
+}
+
+/// `+1952-03-11T00:00:00Z` at Wikidata's precision (9 year, 10 month, 11 day).
+fn format_wikidata_time(time: &str, precision: u64) -> Option<String> {
+    let bce = time.starts_with('-');
+    let date = time.trim_start_matches(['+', '-']);
+    let mut parts = date.split(['-', 'T']);
+    let year = parts.next()?.trim_start_matches('0');
+    let month = parts.next().unwrap_or("00");
+    let day = parts.next().unwrap_or("00");
+    let year = if year.is_empty() { "0" } else { year };
+    let formatted = match precision {
+        11.. if day != "00" => format!("{year}-{month}-{day}"),
+        10.. if month != "00" => format!("{year}-{month}"),
+        9.. => year.to_string(),
+        8 => format!("{year}s"),
+        _ => return None,
+    };
+    Some(if bce {
+        format!("{formatted} BCE")
+    } else {
+        formatted
+    })
+}
+
+fn parse_datavalue(value: &serde_json::Value) -> Option<FactValue> {
+    let data = &value["value"];
+    match value["type"].as_str()? {
+        "wikibase-entityid" => data["id"].as_str().map(|id| FactValue::Entity(id.into())),
+        "time" => format_wikidata_time(data["time"].as_str()?, data["precision"].as_u64()?)
+            .map(FactValue::Text),
+        "quantity" => {
+            let amount = data["amount"].as_str()?.trim_start_matches('+').to_string();
+            // Unitless quantities use the unit "1"
+            let unit = data["unit"]
+                .as_str()
+                .and_then(|u| u.rsplit('/').next())
+                .filter(|u| u.starts_with('Q'))
+                .map(str::to_string);
+            Some(FactValue::Quantity { amount, unit })
+        }
+        "globecoordinate" => Some(FactValue::Text(format!(
+            "{:.4}, {:.4}",
+            data["latitude"].as_f64()?,
+            data["longitude"].as_f64()?
+        ))),
+        "monolingualtext" => data["text"].as_str().map(|t| FactValue::Text(t.into())),
+        "string" => data.as_str().map(|s| FactValue::Text(s.into())),
+        _ => None,
+    }
+}
+
+/// Facts from a wbgetentities response. Preferred-rank statements win over
+/// normal ones; deprecated statements are dropped.
+fn parse_claims(body: &serde_json::Value, entity_id: &str) -> Vec<Fact> {
+    let claims = &body["entities"][entity_id]["claims"];
+    let mut facts = Vec::new();
+    for (property, label) in FACT_PROPERTIES {
+        let Some(statements) = claims[*property].as_array() else {
+            continue;
+        };
+        let has_preferred = statements.iter().any(|s| s["rank"] == "preferred");
+        let values: Vec<FactValue> = statements
+            .iter()
+            .filter(|s| match s["rank"].as_str() {
+                Some("preferred") => true,
+                Some("normal") => !has_preferred,
+                _ => false,
+            })
+            .filter_map(|s| parse_datavalue(&s["mainsnak"]["datavalue"]))
+            .take(MAX_VALUES_PER_FACT)
+            .collect();
+        if !values.is_empty() {
+            facts.push(Fact { label, values });
+        }
+    }
+    facts
+}
+
+/// Entity IDs (values and units) whose labels are needed to print `facts`.
+fn referenced_entities(facts: &[Fact]) -> Vec<String> {
+    let mut seen = HashSet::new();
+    facts
+        .iter()
+        .flat_map(|f| &f.values)
+        .filter_map(|v| match v {
+            FactValue::Entity(id) => Some(id),
+            FactValue::Quantity { unit: Some(id), .. } => Some(id),
+            _ => None,
+        })
+        .filter(|id| seen.insert(id.as_str()))
+        .cloned()
+        .collect()
+}
+
+/// Label per entity from a wbgetentities props=labels response, in `language`
+/// with an English fallback.
+fn parse_labels(
+    body: &serde_json::Value,
+    language: &str,
+) -> std::collections::HashMap<String, String> {
+    let Some(entities) = body["entities"].as_object() else {
+        return Default::default();
+    };
+    entities
+        .iter()
+        .filter_map(|(id, entity)| {
+            let labels = &entity["labels"];
+            let label = labels[language]["value"]
+                .as_str()
+                .or_else(|| labels[DEFAULT_LANGUAGE]["value"].as_str())?;
+            Some((id.clone(), label.to_string()))
+        })
+        .collect()
+}
+
+fn format_facts(facts: &[Fact], labels: &std::collections::HashMap<String, String>) -> String {
+    let label_of = |id: &String| labels.get(id).cloned().unwrap_or_else(|| id.clone());
+    let mut output = String::new();
+    for fact in facts {
+        let values: Vec<String> = fact
+            .values
+            .iter()
+            .map(|v| match v {
+                FactValue::Entity(id) => label_of(id),
+                FactValue::Quantity { amount, unit } => match unit {
+                    Some(unit) => format!("{amount} {}", label_of(unit)),
+                    None => amount.clone(),
+                },
+                FactValue::Text(text) => text.clone(),
+            })
+            .collect();
+        output.push_str(&format!("- {}: {}\n", fact.label, values.join("; ")));
+    }
+    output
+}
+
+fn truncate_extract(extract: &str, max_chars: usize) -> String {
+    if extract.len() <= max_chars {
+        return extract.to_string();
+    }
+    let end = crate::text::floor_char_boundary(extract, max_chars);
+    format!("{}\n[... article truncated ...]", extract[..end].trim_end())
+}
+
+fn format_page(page: &WikiPage, language: &str, full: bool, facts: Option<&str>) -> String {
+    let mut output = format!("{} ({language}.wikipedia.org)\n", page.title);
+    if let Some(description) = &page.description {
+        output.push_str(&format!("{description}\n"));
+    }
+    if !page.url.is_empty() {
+        output.push_str(&format!("{}\n", page.url));
+    }
+    if page.disambiguation {
+        output.push_str(
+            "\nThis is a disambiguation page; look up one of the specific meanings below.\n",
+        );
+    }
+    if !page.extract.is_empty() {
+        let max_chars = if full {
+            FULL_MAX_CHARS
+        } else {
+            INTRO_MAX_CHARS
+        };
+        output.push_str(&format!(
+            "\n{}\n",
+            truncate_extract(&page.extract, max_chars)
+        ));
+    }
+    if let (Some(facts), Some(id)) = (facts, &page.wikidata_id) {
+        if !facts.is_empty() {
+            output.push_str(&format!("\nFacts (Wikidata {id}):\n{facts}"));
+        }
+    }
+    output
+}
+
+async fn fetch_json(request: reqwest::RequestBuilder) -> Result<serde_json::Value, String> {
+    let response = request.send().await.map_err(|e| e.to_string())?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(format!("HTTP {status}"));
+    }
+    let body: serde_json::Value = response.json().await.map_err(|e| e.to_string())?;
+    if let Some(info) = body.pointer("/error/info").and_then(|v| v.as_str()) {
+        return Err(info.to_string());
+    }
+    Ok(body)
+}
+
+/// The article titled `query`, or the best search match for it.
+async fn find_page(language: &str, query: &str, full: bool) -> Result<Option<WikiPage>, String> {
+    let endpoint = format!("https://{language}.wikipedia.org/w/api.php");
+    let mut params = vec![
+        ("action", "query"),
+        ("format", "json"),
+        ("formatversion", "2"),
+        ("redirects", "1"),
+        ("prop", "extracts|pageprops|info"),
+        ("inprop", "url"),
+        ("ppprop", "wikibase_item|wikibase-shortdesc|disambiguation"),
+        ("explaintext", "1"),
+    ];
+    if !full {
+        params.push(("exintro", "1"));
+    }
+
+    let exact = fetch_json(
+        http_client()
+            .get(&endpoint)
+            .query(&params)
+            .query(&[("titles", query)]),
+    )
+    .await?;
+    if let Some(page) = parse_page(&exact) {
+        return Ok(Some(page));
+    }
+    let search = fetch_json(http_client().get(&endpoint).query(&params).query(&[
+        ("generator", "search"),
+        ("gsrsearch", query),
+        ("gsrlimit", "1"),
+    ]))
+    .await?;
+    Ok(parse_page(&search))
+}
+
+/// Facts for a Wikidata item, formatted with labels in `language`.
+async fn fetch_facts(entity_id: &str, language: &str) -> Result<String, String> {
+    const ENDPOINT: &str = "https://www.wikidata.org/w/api.php";
+    let claims = fetch_json(http_client().get(ENDPOINT).query(&[
+        ("action", "wbgetentities"),
+        ("format", "json"),
+        ("ids", entity_id),
+        ("props", "claims"),
+    ]))
+    .await?;
+    let facts = parse_claims(&claims, entity_id);
+    let mut labels = std::collections::HashMap::new();
+    // wbgetentities takes at most 50 IDs per request
+    for ids in referenced_entities(&facts).chunks(50) {
+        let body = fetch_json(http_client().get(ENDPOINT).query(&[
+            ("action", "wbgetentities"),
+            ("format", "json"),
+            ("ids", &ids.join("|")),
+            ("props", "labels"),
+            ("languages", &format!("{language}|{DEFAULT_LANGUAGE}")),
+        ]))
+        .await?;
+        labels.extend(parse_labels(&body, language));
+    }
+    Ok(format_facts(&facts, &labels))
+}
+
+pub struct WikiLookupTool {
+    db: Arc<Database>,
+}
+
+impl WikiLookupTool {
+    pub fn new(db: Arc<Database>) -> Self {
+        WikiLookupTool { db }
+    }
+
+    /// The calling chat's locale, if its platform reported one.
+    async fn chat_language(&self, input: &serde_json::Value) -> Option<String> {
+        let chat_id = auth_context_from_input(input)?.caller_chat_id;
+        let locale = call_blocking(self.db.clone(), move |db| db.get_chat_locale(chat_id))
+            .await
+            .ok()
+            .flatten()?;
+        wiki_language(&locale)
+    }
+}
+
+#[async_trait]
+impl Tool for WikiLookupTool {
+    fn name(&self) -> &str {
+        "wiki_lookup"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "wiki_lookup".into(),
+            description: "Look up a topic on Wikipedia and Wikidata. Returns the article's summary (or full text), its URL and key facts such as dates, places, population or founders. Use it for quick factual grounding on well-known people, places, organizations and concepts; use web_search for recent events. The article language defaults to the chat's language.".into(),
+            input_schema: schema_object(
+                json!({
+                    "query": {
+                        "type": "string",
+                        "description": "Article title or search terms, e.g. \"Ada Lovelace\""
+                    },
+                    "language": {
+                        "type": "string",
+                        "description": "Wikipedia language code such as \"en\", \"de\" or \"ja\" (default: the chat's language, else English)"
+                    },
+                    "full": {
+                        "type": "boolean",
+                        "description": "Return the whole article text instead of the introduction (default false)"
+                    },
+                    "facts": {
+                        "type": "boolean",
+                        "description": "Include Wikidata facts (default true)"
+                    }
+                }),
+                &["query"],
+            ),
+        }
+    }
+
+    async fn execute(&self, input: serde_json::Value) -> ToolResult {
+        let query = match input.get("query").and_then(|v| v.as_str()) {
+            Some(q) if !q.trim().is_empty() => q.trim(),
+            _ => return ToolResult::error("Missing required parameter: query".into()),
+        };
+        let language = match input.get("language").and_then(|v| v.as_str()) {
+            Some(tag) => match wiki_language(tag) {
+                Some(language) => language,
+                None => return ToolResult::error(format!("Invalid language code: {tag}")),
+            },
+            None => self
+                .chat_language(&input)
+                .await
+                .unwrap_or_else(|| DEFAULT_LANGUAGE.to_string()),
+        };
+        let full = input.get("full").and_then(|v| v.as_bool()).unwrap_or(false);
+        let with_facts = input.get("facts").and_then(|v| v.as_bool()).unwrap_or(true);
+
+        let page = match find_page(&language, query, full).await {
+            Ok(Some(page)) => page,
+            Ok(None) => {
+                return ToolResult::success(format!(
+                    "No {language}.wikipedia.org article found for \"{query}\"."
+                ))
+            }
+            Err(e) => return ToolResult::error(format!("Wikipedia lookup failed: {e}")),
+        };
+
+        // Facts are a bonus; the article alone still answers the lookup
+        let facts = match (&page.wikidata_id, with_facts) {
+            (Some(id), true) => match fetch_facts(id, &language).await {
+                Ok(facts) => Some(facts),
+                Err(e) => {
+                    warn!("wiki_lookup: Wikidata facts for {id} failed: {e}");
+                    None
+                }
+            },
+            _ => None,
+        };
+        ToolResult::success(format_page(&page, &language, full, facts.as_deref()))
+            .with_suggested_next_tools(&["web_fetch"])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wiki_language() {
+        assert_eq!(wiki_language("en").as_deref(), Some("en"));
+        assert_eq!(wiki_language("pt-BR").as_deref(), Some("pt"));
+        assert_eq!(wiki_language("zh_Hans").as_deref(), Some("zh"));
+        assert_eq!(wiki_language("zh-yue").as_deref(), Some("zh-yue"));
+        assert_eq!(wiki_language("be-tarask").as_deref(), Some("be-tarask"));
+        assert_eq!(wiki_language("simple").as_deref(), Some("simple"));
+        assert_eq!(wiki_language("evil.com/x"), None);
+        assert_eq!(wiki_language(""), None);
+    }
+
+    #[tokio::test]
+    async fn test_chat_language_from_locale() {
+        let dir = std::env::temp_dir().join(format!("rayclaw_wiki_{}", uuid::Uuid::new_v4()));
+        let db = Arc::new(Database::new(dir.to_str().unwrap()).unwrap());
+        db.set_chat_locale(42, "de-AT").unwrap();
+        let tool = WikiLookupTool::new(db);
+
+        let input = json!({"__rayclaw_auth": {"caller_chat_id": 42}});
+        assert_eq!(tool.chat_language(&input).await.as_deref(), Some("de"));
+        let input = json!({"__rayclaw_auth": {"caller_chat_id": 7}});
+        assert_eq!(tool.chat_language(&input).await, None);
+        assert_eq!(tool.chat_language(&json!({})).await, None);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_parse_page() {
+        let body = json!({
+            "query": {
+                "redirects": [{"from": "Lovelace", "to": "Ada Lovelace"}],
+                "pages": [{
+                    "pageid": 1,
+                    "title": "Ada Lovelace",
+                    "fullurl": "https://en.wikipedia.org/wiki/Ada_Lovelace",
+                    "extract": "Augusta Ada King, Countess of Lovelace, was an English mathematician.\n",
+                    "pageprops": {
+                        "wikibase-shortdesc": "English mathematician (1815–1852)",
+                        "wikibase_item": "Q7259"
+                    }
+                }]
+            }
+        });
+        let page = parse_page(&body).unwrap();
+        assert_eq!(page.title, "Ada Lovelace");
+        assert_eq!(page.wikidata_id.as_deref(), Some("Q7259"));
+        assert_eq!(
+            page.description.as_deref(),
+            Some("English mathematician (1815–1852)")
+        );
+        assert!(page.extract.ends_with("mathematician."));
+        assert!(!page.disambiguation);
+
+        let missing = json!({"query": {"pages": [{"title": "Xyzzy", "missing": true}]}});
+        assert_eq!(parse_page(&missing), None);
+
+        let search = json!({"query": {"pages": [
+            {"title": "Mercury (element)", "index": 2, "pageprops": {}},
+            {"title": "Mercury (planet)", "index": 1, "pageprops": {}}
+        ]}});
+        assert_eq!(parse_page(&search).unwrap().title, "Mercury (planet)");
+    }
+
+    #[test]
+    fn test_format_wikidata_time() {
+        assert_eq!(
+            format_wikidata_time("+1815-12-10T00:00:00Z", 11).as_deref(),
+            Some("1815-12-10")
+        );
+        assert_eq!(
+            format_wikidata_time("+1969-07-00T00:00:00Z", 11).as_deref(),
+            Some("1969-07")
+        );
+        assert_eq!(
+            format_wikidata_time("+2001-00-00T00:00:00Z", 9).as_deref(),
+            Some("2001")
+        );
+        assert_eq!(
+            format_wikidata_time("-0044-03-15T00:00:00Z", 11).as_deref(),
+            Some("44-03-15 BCE")
+        );
+        assert_eq!(format_wikidata_time("+1500-00-00T00:00:00Z", 7), None);
+    }
+
+    #[test]
+    fn test_parse_claims_and_format() {
+        let entity = |id: &str| json!({"type": "wikibase-entityid", "value": {"id": id}});
+        let body = json!({"entities": {"Q64": {"claims": {
+            "P1082": [
+                {"rank": "normal", "mainsnak": {"datavalue": {"type": "quantity",
+                    "value": {"amount": "+3400000", "unit": "1"}}}},
+                {"rank": "preferred", "mainsnak": {"datavalue": {"type": "quantity",
+                    "value": {"amount": "+3850809", "unit": "1"}}}}
+            ],
+            "P2046": [{"rank": "normal", "mainsnak": {"datavalue": {"type": "quantity",
+                "value": {"amount": "+891.3", "unit": "http://www.wikidata.org/entity/Q712226"}}}}],
+            "P17": [
+                {"rank": "normal", "mainsnak": {"datavalue": entity("Q183")}},
+                {"rank": "deprecated", "mainsnak": {"datavalue": entity("Q41304")}}
+            ],
+            "P31": [{"rank": "normal", "mainsnak": {"snaktype": "novalue"}}],
+            "P856": [{"rank": "normal", "mainsnak": {"datavalue": {"type": "string",
+                "value": "https://www.berlin.de/"}}}]
+        }}}});
+        let facts = parse_claims(&body, "Q64");
+        assert_eq!(
+            facts.iter().map(|f| f.label).collect::<Vec<_>>(),
+            vec!["country", "population", "area", "official website"]
+        );
+        assert_eq!(referenced_entities(&facts), vec!["Q183", "Q712226"]);
+
+        let labels = parse_labels(
+            &json!({"entities": {
+                "Q183": {"labels": {"de": {"value": "Deutschland"}, "en": {"value": "Germany"}}},
+                "Q712226": {"labels": {"en": {"value": "square kilometre"}}}
+            }}),
+            "de",
+        );
+        assert_eq!(
+            format_facts(&facts, &labels),
+            "- country: Deutschland\n- population: 3850809\n- area: 891.3 square kilometre\n- official website: https://www.berlin.de/\n"
+        );
+    }
+
+    #[test]
+    fn test_format_page() {
+        let page = WikiPage {
+            title: "Berlin".into(),
+            url: "https://de.wikipedia.org/wiki/Berlin".into(),
+            description: Some("Hauptstadt Deutschlands".into()),
+            extract: "Berlin ist die Hauptstadt.".into(),
+            wikidata_id: Some("Q64".into()),
+            disambiguation: false,
+        };
+        let output = format_page(&page, "de", false, Some("- country: Deutschland\n"));
+        assert_eq!(
+            output,
+            "Berlin (de.wikipedia.org)\nHauptstadt Deutschlands\nhttps://de.wikipedia.org/wiki/Berlin\n\nBerlin ist die Hauptstadt.\n\nFacts (Wikidata Q64):\n- country: Deutschland\n"
+        );
+
+        let long = WikiPage {
+            extract: "word ".repeat(2_000),
+            ..page
+        };
+        let output = format_page(&long, "de", false, None);
+        assert!(output.contains("[... article truncated ...]"));
+        assert!(!output.contains("Facts"));
+    }
+}
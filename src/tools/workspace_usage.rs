@@ -0,0 +1,190 @@
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use serde_json::json;
+
+use super::{auth_context_from_input, schema_object, Tool, ToolResult};
+use crate::config::WorkingDirIsolation;
+use crate::llm_types::ToolDefinition;
+use crate::workspace::{dir_usage, format_bytes, list_workspaces};
+
+pub struct WorkspaceUsageTool {
+    working_dir: PathBuf,
+    working_dir_isolation: WorkingDirIsolation,
+    quota_mb: u64,
+    tmp_ttl_hours: u64,
+}
+
+impl WorkspaceUsageTool {
+    pub fn new(
+        working_dir: &str,
+        working_dir_isolation: WorkingDirIsolation,
+        quota_mb: u64,
+        tmp_ttl_hours: u64,
+    ) -> Self {
+        Self {
+            working_dir: PathBuf::from(working_dir),
+            working_dir_isolation,
+            quota_mb,
+            tmp_ttl_hours,
+        }
+    }
+
+    fn quota_line(&self) -> String {
+        let quota = if self.quota_mb == 0 {
+            "unlimited".to_string()
+        } else {
+            format!("{} MB", self.quota_mb)
+        };
+        let ttl = if self.tmp_ttl_hours == 0 {
+            "never".to_string()
+        } else {
+            format!("after {}h", self.tmp_ttl_hours)
+        };
+        format!("Quota: {quota} per workspace; tmp/ and uploads expire: {ttl}")
+    }
+}
+
+#[async_trait]
+impl Tool for WorkspaceUsageTool {
+    fn name(&self) -> &str {
+        "workspace_usage"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "workspace_usage".into(),
+            description: "Report disk usage of the current chat's working directory and the configured quota. Files under tmp/ are treated as temporary and may be cleaned up automatically. Control chats can pass all=true to list every workspace.".into(),
+            input_schema: schema_object(
+                json!({
+                    "all": {
+                        "type": "boolean",
+                        "description": "List usage for all workspaces (control chats only)"
+                    }
+                }),
+                &[],
+            ),
+        }
+    }
+
+    async fn execute(&self, input: serde_json::Value) -> ToolResult {
+        let all = input.get("all").and_then(|v| v.as_bool()).unwrap_or(false);
+        if all {
+            let is_control = auth_context_from_input(&input)
+                .map(|a| a.is_control_chat())
+                .unwrap_or(true);
+            if !is_control {
+                return ToolResult::error(
+                    "Permission denied: only control chats can list all workspaces".into(),
+                );
+            }
+            let working_dir = self.working_dir.clone();
+            let rows = tokio::task::spawn_blocking(move || {
+                list_workspaces(&working_dir)
+                    .into_iter()
+                    .map(|(label, path, _)| (label, dir_usage(&path)))
+                    .collect::<Vec<_>>()
+            })
+            .await;
+            let mut rows = match rows {
+                Ok(rows) => rows,
+                Err(e) => return ToolResult::error(format!("Failed to scan workspaces: {e}")),
+            };
+            if rows.is_empty() {
+                return ToolResult::success(format!(
+                    "No workspaces found under {}.\n{}",
+                    self.working_dir.display(),
+                    self.quota_line()
+                ));
+            }
+            rows.sort_by_key(|(_, u)| std::cmp::Reverse(u.total_bytes));
+            let total: u64 = rows.iter().map(|(_, u)| u.total_bytes).sum();
+            let mut out = format!(
+                "Workspaces under {} (total {}):\n",
+                self.working_dir.display(),
+                format_bytes(total)
+            );
+            for (label, usage) in rows {
+                out.push_str(&format!(
+                    "- {label}: {} in {} file(s)\n",
+                    format_bytes(usage.total_bytes),
+                    usage.file_count
+                ));
+            }
+            out.push_str(&self.quota_line());
+            return ToolResult::success(out);
+        }
+
+        let dir =
+            super::resolve_tool_working_dir(&self.working_dir, self.working_dir_isolation, &input);
+        let scan_dir = dir.clone();
+        let usage = match tokio::task::spawn_blocking(move || dir_usage(&scan_dir)).await {
+            Ok(u) => u,
+            Err(e) => return ToolResult::error(format!("Failed to scan workspace: {e}")),
+        };
+        let mut out = format!(
+            "Workspace: {}\nUsage: {} in {} file(s)",
+            dir.display(),
+            format_bytes(usage.total_bytes),
+            usage.file_count
+        );
+        if self.quota_mb > 0 {
+            let quota_bytes = self.quota_mb * 1024 * 1024;
+            let pct = usage.total_bytes as f64 * 100.0 / quota_bytes as f64;
+            out.push_str(&format!(" ({pct:.0}% of quota)"));
+        }
+        out.push('\n');
+        out.push_str(&self.quota_line());
+        ToolResult::success(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("rayclaw_wsu_{}", uuid::Uuid::new_v4()))
+    }
+
+    #[tokio::test]
+    async fn test_workspace_usage_reports_chat_dir() {
+        let dir = test_dir();
+        let tool = WorkspaceUsageTool::new(dir.to_str().unwrap(), WorkingDirIsolation::Chat, 1, 0);
+        let chat_dir = dir.join("chat").join("telegram").join("42");
+        std::fs::create_dir_all(&chat_dir).unwrap();
+        std::fs::write(chat_dir.join("a.txt"), vec![b'x'; 2048]).unwrap();
+
+        let result = tool
+            .execute(json!({
+                "__rayclaw_auth": {
+                    "caller_channel": "telegram",
+                    "caller_chat_id": 42,
+                    "control_chat_ids": []
+                }
+            }))
+            .await;
+        assert!(!result.is_error);
+        assert!(result.content.contains("2.0 KB in 1 file(s)"));
+        assert!(result.content.contains("Quota: 1 MB"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_workspace_usage_all_requires_control_chat() {
+        let dir = test_dir();
+        let tool = WorkspaceUsageTool::new(dir.to_str().unwrap(), WorkingDirIsolation::Chat, 0, 0);
+        let result = tool
+            .execute(json!({
+                "all": true,
+                "__rayclaw_auth": {
+                    "caller_channel": "telegram",
+                    "caller_chat_id": 42,
+                    "control_chat_ids": []
+                }
+            }))
+            .await;
+        assert!(result.is_error);
+        assert!(result.content.contains("Permission denied"));
+    }
+}
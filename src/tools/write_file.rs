@@ -85,6 +85,7 @@ impl Tool for WriteFileTool {
         match tokio::fs::write(&resolved_path, content).await {
             Ok(()) => {
                 ToolResult::success(format!("Successfully wrote to {}", resolved_path.display()))
+                    .with_produced_file(resolved_path.display().to_string())
             }
             Err(e) => ToolResult::error(format!("Failed to write file: {e}")),
         }
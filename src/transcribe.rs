@@ -1,11 +1,104 @@
+//! Speech-to-text for voice notes and web voice mode, through the OpenAI
+//! Whisper API or a local whisper.cpp binary (`stt_backend`).
+
+use std::path::Path;
+use std::time::Duration;
+
 use reqwest::multipart;
+use tracing::error;
+
+use crate::config::Config;
+
+/// whisper.cpp runs on the CPU; a few minutes of audio can take a while.
+const WHISPER_CPP_TIMEOUT: Duration = Duration::from_secs(300);
 
-pub async fn transcribe_audio(api_key: &str, audio_bytes: &[u8]) -> Result<String, String> {
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SttSettings {
+    OpenAi { api_key: String },
+    WhisperCpp { binary: String, model: String },
+}
+
+impl SttSettings {
+    /// `None` when the configured backend can't run: the OpenAI backend with
+    /// no `openai_api_key`. Config validation makes sure `whisper_cpp` has a
+    /// model.
+    pub fn from_config(config: &Config) -> Option<Self> {
+        match config.stt_backend.as_str() {
+            "whisper_cpp" => Some(Self::WhisperCpp {
+                binary: config.whisper_cpp_binary.clone(),
+                model: config.whisper_cpp_model.clone()?,
+            }),
+            _ => {
+                let api_key = config
+                    .openai_api_key
+                    .as_deref()
+                    .map(str::trim)
+                    .filter(|k| !k.is_empty())?;
+                Some(Self::OpenAi {
+                    api_key: api_key.to_string(),
+                })
+            }
+        }
+    }
+}
+
+/// Transcribe `audio` with the configured backend.
+pub async fn transcribe(
+    settings: &SttSettings,
+    audio: &[u8],
+    mime: &str,
+) -> Result<String, String> {
+    let text = match settings {
+        SttSettings::OpenAi { api_key } => transcribe_audio_as(api_key, audio, mime).await?,
+        SttSettings::WhisperCpp { binary, model } => {
+            transcribe_whisper_cpp(binary, model, audio, mime).await?
+        }
+    };
+    Ok(text.trim().to_string())
+}
+
+/// The message text for a voice note: the transcript behind a `[voice]`
+/// marker so the agent knows it was spoken. A failed transcription is noted
+/// in its place.
+pub async fn voice_note_text(settings: &SttSettings, audio: &[u8], mime: &str) -> String {
+    match transcribe(settings, audio, mime).await {
+        Ok(text) => voice_annotation(&text),
+        Err(e) => {
+            error!("Voice transcription failed: {e}");
+            voice_annotation(&format!("[transcription failed: {e}]"))
+        }
+    }
+}
+
+fn voice_annotation(text: &str) -> String {
+    format!("[voice] {text}")
+}
+
+/// File extension Whisper should see for an audio MIME type; it detects the
+/// container from the file name.
+pub fn audio_file_extension(mime: &str) -> &'static str {
+    match mime.split(';').next().unwrap_or("").trim() {
+        "audio/webm" | "video/webm" => "webm",
+        "audio/mp4" | "audio/m4a" | "audio/x-m4a" => "m4a",
+        "audio/mpeg" | "audio/mp3" => "mp3",
+        "audio/wav" | "audio/x-wav" | "audio/wave" => "wav",
+        "audio/flac" => "flac",
+        _ => "ogg",
+    }
+}
+
+/// Transcribe audio in any container Whisper accepts, identified by MIME type.
+pub async fn transcribe_audio_as(
+    api_key: &str,
+    audio_bytes: &[u8],
+    mime: &str,
+) -> Result<String, String> {
     let client = reqwest::Client::new();
 
+    let mime = mime.split(';').next().unwrap_or("").trim();
     let part = multipart::Part::bytes(audio_bytes.to_vec())
-        .file_name("audio.ogg")
-        .mime_str("audio/ogg")
+        .file_name(format!("audio.{}", audio_file_extension(mime)))
+        .mime_str(if mime.is_empty() { "audio/ogg" } else { mime })
         .map_err(|e| e.to_string())?;
 
     let form = multipart::Form::new()
@@ -37,10 +130,144 @@ pub async fn transcribe_audio(api_key: &str, audio_bytes: &[u8]) -> Result<Strin
         .ok_or_else(|| "Whisper response missing 'text' field".into())
 }
 
+/// whisper.cpp only reads 16 kHz WAV reliably (voice notes are usually Opus),
+/// so the clip goes through ffmpeg first.
+async fn transcribe_whisper_cpp(
+    binary: &str,
+    model: &str,
+    audio: &[u8],
+    mime: &str,
+) -> Result<String, String> {
+    let dir = std::env::temp_dir().join(format!("rayclaw_stt_{}", uuid::Uuid::new_v4()));
+    tokio::fs::create_dir_all(&dir)
+        .await
+        .map_err(|e| format!("Failed to create {}: {e}", dir.display()))?;
+    let result = run_whisper_cpp(&dir, binary, model, audio, mime).await;
+    let _ = tokio::fs::remove_dir_all(&dir).await;
+    result
+}
+
+async fn run_whisper_cpp(
+    dir: &Path,
+    binary: &str,
+    model: &str,
+    audio: &[u8],
+    mime: &str,
+) -> Result<String, String> {
+    let input = dir.join(format!("input.{}", audio_file_extension(mime)));
+    let wav = dir.join("input.wav");
+    let out_base = dir.join("transcript");
+    tokio::fs::write(&input, audio)
+        .await
+        .map_err(|e| format!("Failed to write audio: {e}"))?;
+
+    let mut ffmpeg = tokio::process::Command::new("ffmpeg");
+    ffmpeg
+        .args(["-nostdin", "-loglevel", "error", "-y", "-i"])
+        .arg(&input)
+        .args(["-ar", "16000", "-ac", "1", "-c:a", "pcm_s16le"])
+        .arg(&wav);
+    run_with_timeout(ffmpeg, "ffmpeg").await?;
+
+    let mut whisper = tokio::process::Command::new(binary);
+    whisper
+        .arg("-m")
+        .arg(model)
+        .arg("-f")
+        .arg(&wav)
+        .args(["-nt", "-otxt", "-of"])
+        .arg(&out_base);
+    run_with_timeout(whisper, binary).await?;
+
+    let text = tokio::fs::read_to_string(out_base.with_extension("txt"))
+        .await
+        .map_err(|e| format!("whisper.cpp wrote no transcript: {e}"))?;
+    Ok(join_transcript_lines(&text))
+}
+
+async fn run_with_timeout(mut cmd: tokio::process::Command, name: &str) -> Result<(), String> {
+    cmd.stdin(std::process::Stdio::null()).kill_on_drop(true);
+    let output = tokio::time::timeout(WHISPER_CPP_TIMEOUT, cmd.output())
+        .await
+        .map_err(|_| format!("{name} timed out after {}s", WHISPER_CPP_TIMEOUT.as_secs()))?
+        .map_err(|e| format!("Failed to run {name}: {e}"))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!(
+            "{name} failed ({}): {}",
+            output.status,
+            stderr.trim()
+        ));
+    }
+    Ok(())
+}
+
+/// whisper.cpp writes one segment per line.
+fn join_transcript_lines(text: &str) -> String {
+    text.lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
 #[cfg(test)]
 mod tests {
+    use super::*;
+
     #[test]
     fn test_transcribe_module_exists() {
         // Basic smoke test that the module compiles
     }
+
+    #[test]
+    fn test_audio_file_extension() {
+        assert_eq!(audio_file_extension("audio/webm;codecs=opus"), "webm");
+        assert_eq!(audio_file_extension("audio/mp4"), "m4a");
+        assert_eq!(audio_file_extension("audio/wav"), "wav");
+        assert_eq!(audio_file_extension(""), "ogg");
+    }
+
+    #[test]
+    fn test_stt_settings_from_config() {
+        let mut config: Config =
+            serde_yaml::from_str("telegram_bot_token: tok\nbot_username: bot\napi_key: key\n")
+                .unwrap();
+        assert_eq!(SttSettings::from_config(&config), None);
+
+        config.openai_api_key = Some(" sk-test ".into());
+        assert_eq!(
+            SttSettings::from_config(&config),
+            Some(SttSettings::OpenAi {
+                api_key: "sk-test".into()
+            })
+        );
+
+        config.stt_backend = "whisper_cpp".into();
+        config.whisper_cpp_model = Some("/models/ggml-base.bin".into());
+        assert_eq!(
+            SttSettings::from_config(&config),
+            Some(SttSettings::WhisperCpp {
+                binary: "whisper-cli".into(),
+                model: "/models/ggml-base.bin".into()
+            })
+        );
+    }
+
+    #[test]
+    fn test_voice_annotation() {
+        assert_eq!(
+            voice_annotation("turn on the lights"),
+            "[voice] turn on the lights"
+        );
+    }
+
+    #[test]
+    fn test_join_transcript_lines() {
+        assert_eq!(
+            join_transcript_lines(" Hello there.\n\n How are you?\n"),
+            "Hello there. How are you?"
+        );
+        assert_eq!(join_transcript_lines("\n"), "");
+    }
 }
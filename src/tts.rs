@@ -0,0 +1,151 @@
+//! Text-to-speech via the OpenAI speech API, plus helpers for speaking a
+//! streamed reply sentence by sentence (used by the web voice mode).
+
+use crate::config::Config;
+
+#[derive(Debug, Clone)]
+pub struct TtsSettings {
+    pub api_key: String,
+    pub model: String,
+    pub voice: String,
+}
+
+impl TtsSettings {
+    /// TTS shares `openai_api_key` with Whisper transcription; `None` if unset.
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let api_key = config
+            .openai_api_key
+            .as_deref()
+            .map(str::trim)
+            .filter(|k| !k.is_empty())?;
+        Some(Self {
+            api_key: api_key.to_string(),
+            model: config.tts_model.clone(),
+            voice: config.tts_voice.clone(),
+        })
+    }
+}
+
+/// Synthesize `text` to MP3 audio.
+pub async fn synthesize_speech(settings: &TtsSettings, text: &str) -> Result<Vec<u8>, String> {
+    let client = reqwest::Client::new();
+    let resp = client
+        .post("https://api.openai.com/v1/audio/speech")
+        .header("Authorization", format!("Bearer {}", settings.api_key))
+        .json(&serde_json::json!({
+            "model": settings.model,
+            "voice": settings.voice,
+            "input": text,
+            "response_format": "mp3",
+        }))
+        .send()
+        .await
+        .map_err(|e| format!("TTS API request failed: {e}"))?;
+
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let body = resp.text().await.unwrap_or_default();
+        return Err(format!("TTS API error HTTP {status}: {body}"));
+    }
+
+    resp.bytes()
+        .await
+        .map(|b| b.to_vec())
+        .map_err(|e| format!("Failed to read TTS audio: {e}"))
+}
+
+/// Drop code blocks and markdown markers that would otherwise be read aloud.
+pub fn speakable_text(text: &str) -> String {
+    let mut in_code = false;
+    let mut lines = Vec::new();
+    for line in text.lines() {
+        if line.trim_start().starts_with("```") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            continue;
+        }
+        lines.push(
+            line.trim_start_matches(|c: char| c == '#' || c == '>' || c.is_whitespace())
+                .replace(['*', '`'], ""),
+        );
+    }
+    lines.join("\n").trim().to_string()
+}
+
+/// Accumulates streamed text deltas and yields complete sentences, so speech
+/// for the start of a reply can be generated while the rest is still streaming.
+#[derive(Debug, Default)]
+pub struct SentenceChunker {
+    buffer: String,
+}
+
+impl SentenceChunker {
+    /// Shortest sentence emitted on its own; shorter ones are merged forward
+    /// to avoid a burst of tiny audio clips.
+    const MIN_CHARS: usize = 24;
+
+    pub fn push(&mut self, delta: &str) -> Vec<String> {
+        self.buffer.push_str(delta);
+        let mut sentences = Vec::new();
+        let mut start = 0;
+        let mut chars = self.buffer.char_indices().peekable();
+        while let Some((i, c)) = chars.next() {
+            let end = i + c.len_utf8();
+            let boundary = match c {
+                '。' | '！' | '？' | '\n' => true,
+                '.' | '!' | '?' => chars.peek().is_some_and(|(_, next)| next.is_whitespace()),
+                _ => false,
+            };
+            if boundary && self.buffer[start..end].trim().chars().count() >= Self::MIN_CHARS {
+                sentences.push(self.buffer[start..end].trim().to_string());
+                start = end;
+            }
+        }
+        self.buffer.drain(..start);
+        sentences
+    }
+
+    /// Return whatever text is left once the stream has ended.
+    pub fn finish(&mut self) -> Option<String> {
+        let rest = std::mem::take(&mut self.buffer);
+        let rest = rest.trim();
+        (!rest.is_empty()).then(|| rest.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sentence_chunker_splits_on_boundaries() {
+        let mut chunker = SentenceChunker::default();
+        assert!(chunker.push("The build finished without").is_empty());
+        let out = chunker.push(" errors. Tests are running now! Version 1.5 is");
+        assert_eq!(out, vec!["The build finished without errors."]);
+        // Too short to stand alone, and "1.5" is not a sentence end
+        assert!(chunker.push(" out").is_empty());
+        assert_eq!(
+            chunker.finish().as_deref(),
+            Some("Tests are running now! Version 1.5 is out")
+        );
+        assert_eq!(chunker.finish(), None);
+    }
+
+    #[test]
+    fn test_sentence_chunker_cjk_punctuation() {
+        let mut chunker = SentenceChunker::default();
+        let out = chunker.push("今天的天气很好，我们一起去公园散步吧，顺便买点水果和咖啡。然后");
+        assert_eq!(out.len(), 1);
+        assert!(out[0].ends_with('。'));
+        assert_eq!(chunker.finish().as_deref(), Some("然后"));
+    }
+
+    #[test]
+    fn test_speakable_text_strips_markdown() {
+        let text = "## Result\n**Done**, see `main.rs`.\n```rust\nfn main() {}\n```";
+        assert_eq!(speakable_text(text), "Result\nDone, see main.rs.");
+    }
+}
@@ -23,8 +23,14 @@ pub async fn run_update(args: &[String]) -> anyhow::Result<()> {
         return Ok(());
     }
 
-    let (os_target, arch_target) = detect_platform()?;
-    let asset_name = format!("rayclaw-v{latest_version}-{arch_target}-{os_target}.tar.gz");
+    let target = detect_platform()?;
+    // Windows builds ship as zip archives
+    let archive_ext = if cfg!(target_os = "windows") {
+        "zip"
+    } else {
+        "tar.gz"
+    };
+    let asset_name = format!("rayclaw-v{latest_version}-{target}.{archive_ext}");
 
     let download_url = assets
         .iter()
@@ -55,27 +61,29 @@ pub async fn run_update(args: &[String]) -> anyhow::Result<()> {
     let _ = std::fs::remove_dir_all(&tmp_dir);
     std::fs::create_dir_all(&tmp_dir)?;
 
-    let tarball_path = tmp_dir.join(&asset_name);
-    download_file(&download_url, &tarball_path).await?;
+    let archive_path = tmp_dir.join(&asset_name);
+    download_file(&download_url, &archive_path).await?;
 
     println!("Extracting...");
+    // The tar bundled with Windows 10+ (bsdtar) also reads zip archives
     let status = std::process::Command::new("tar")
         .args([
-            "xzf",
-            &tarball_path.to_string_lossy(),
+            if archive_ext == "zip" { "xf" } else { "xzf" },
+            &archive_path.to_string_lossy(),
             "-C",
             &tmp_dir.to_string_lossy(),
         ])
         .status()?;
     if !status.success() {
         let _ = std::fs::remove_dir_all(&tmp_dir);
-        anyhow::bail!("Failed to extract tarball");
+        anyhow::bail!("Failed to extract {asset_name}");
     }
 
-    let new_binary = tmp_dir.join("rayclaw");
+    let binary_name = format!("rayclaw{}", std::env::consts::EXE_SUFFIX);
+    let new_binary = tmp_dir.join(&binary_name);
     if !new_binary.exists() {
         let _ = std::fs::remove_dir_all(&tmp_dir);
-        anyhow::bail!("Extracted archive does not contain 'rayclaw' binary");
+        anyhow::bail!("Extracted archive does not contain '{binary_name}' binary");
     }
 
     let current_exe = std::env::current_exe()?;
@@ -111,18 +119,41 @@ async fn fetch_latest_release() -> anyhow::Result<(String, Vec<serde_json::Value
     Ok((tag, assets))
 }
 
-fn detect_platform() -> anyhow::Result<(&'static str, &'static str)> {
-    let os = match std::env::consts::OS {
-        "linux" => "unknown-linux-gnu",
-        "macos" => "apple-darwin",
-        other => anyhow::bail!("Unsupported OS: {other}"),
-    };
-    let arch = match std::env::consts::ARCH {
+/// Target triple of the release asset for this binary. The C library is the
+/// one this binary was built against, so a musl build (Alpine containers)
+/// keeps updating to musl builds.
+fn detect_platform() -> anyhow::Result<String> {
+    release_target(
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        cfg!(target_env = "musl"),
+        cfg!(target_feature = "v7"),
+    )
+}
+
+/// `armv7` tells a 32-bit ARM build for ARMv7 apart from older ones.
+fn release_target(os: &str, arch: &str, musl: bool, armv7: bool) -> anyhow::Result<String> {
+    let arch = match arch {
         "x86_64" => "x86_64",
         "aarch64" => "aarch64",
+        // 32-bit Raspberry Pi OS and other hard-float ARMv7 systems
+        "arm" if armv7 => "armv7",
+        // ARMv6 (Raspberry Pi 1 and Zero) can't run the ARMv7 builds
+        "arm" => anyhow::bail!(
+            "No release builds for ARMv6; build from source with `cargo build --release`"
+        ),
         other => anyhow::bail!("Unsupported architecture: {other}"),
     };
-    Ok((os, arch))
+    let os = match (os, musl) {
+        ("linux", false) if arch == "armv7" => "unknown-linux-gnueabihf",
+        ("linux", true) if arch == "armv7" => "unknown-linux-musleabihf",
+        ("linux", false) => "unknown-linux-gnu",
+        ("linux", true) => "unknown-linux-musl",
+        ("macos", _) => "apple-darwin",
+        ("windows", _) => "pc-windows-msvc",
+        (other, _) => anyhow::bail!("Unsupported OS: {other}"),
+    };
+    Ok(format!("{arch}-{os}"))
 }
 
 async fn download_file(url: &str, dest: &Path) -> anyhow::Result<()> {
@@ -143,12 +174,15 @@ async fn download_file(url: &str, dest: &Path) -> anyhow::Result<()> {
 
 fn replace_binary(current: &Path, new_binary: &Path) -> anyhow::Result<()> {
     let backup = current.with_extension("bak");
+    // Windows can't delete a running executable, so the backup of the
+    // previous update may still be around
+    let _ = std::fs::remove_file(&backup);
 
-    // Rename current → .bak
+    // Rename current → .bak (allowed on Windows even while it runs)
     std::fs::rename(current, &backup).map_err(|e| {
         anyhow::anyhow!(
             "Cannot rename current binary (permission denied?): {e}\n\
-             Try: sudo rayclaw update"
+             Try: sudo rayclaw update (or an elevated prompt on Windows)"
         )
     })?;
 
@@ -171,3 +205,32 @@ fn replace_binary(current: &Path, new_binary: &Path) -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_release_target() {
+        let target = |os, arch, musl| release_target(os, arch, musl, true).unwrap();
+        assert_eq!(target("linux", "x86_64", false), "x86_64-unknown-linux-gnu");
+        assert_eq!(target("linux", "x86_64", true), "x86_64-unknown-linux-musl");
+        assert_eq!(
+            target("linux", "aarch64", true),
+            "aarch64-unknown-linux-musl"
+        );
+        assert_eq!(
+            target("linux", "arm", false),
+            "armv7-unknown-linux-gnueabihf"
+        );
+        assert_eq!(
+            target("linux", "arm", true),
+            "armv7-unknown-linux-musleabihf"
+        );
+        assert_eq!(target("macos", "aarch64", false), "aarch64-apple-darwin");
+        assert_eq!(target("windows", "x86_64", false), "x86_64-pc-windows-msvc");
+        assert!(release_target("linux", "arm", false, false).is_err());
+        assert!(release_target("linux", "riscv64", false, true).is_err());
+        assert!(release_target("freebsd", "x86_64", false, true).is_err());
+    }
+}
@@ -0,0 +1,403 @@
+//! Website change monitoring: fetches watched pages on their interval,
+//! compares the visible text (optionally narrowed by a CSS selector) with
+//! the last check and tells the chat what changed.
+
+use std::collections::HashSet;
+use std::sync::{Arc, OnceLock};
+
+use chrono::{DateTime, Utc};
+use scraper::{ElementRef, Html, Node, Selector};
+use tracing::{error, info};
+
+use crate::db::{call_blocking, UrlMonitor};
+use crate::notifications::{notify_chat, NotificationKind};
+use crate::outbound_http::{self, OutboundClient};
+use crate::runtime::AppState;
+use crate::text::floor_char_boundary;
+
+pub const DEFAULT_INTERVAL_MINS: i64 = 60;
+pub const MIN_INTERVAL_MINS: i64 = 5;
+pub const MAX_INTERVAL_MINS: i64 = 7 * 24 * 60;
+/// Stored content is capped so a huge page doesn't bloat the database.
+const MAX_CONTENT_BYTES: usize = 50_000;
+/// Lines shown per side (added/removed) in a change notification.
+const MAX_DIFF_LINES: usize = 10;
+const MAX_DIFF_LINE_CHARS: usize = 200;
+
+/// Elements whose text is never part of what a visitor reads.
+const SKIPPED_ELEMENTS: &[&str] = &["script", "style", "noscript", "template", "svg", "head"];
+/// Elements that start a new line in the extracted text.
+const BLOCK_ELEMENTS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "br",
+    "dd",
+    "div",
+    "dl",
+    "dt",
+    "figcaption",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "li",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "td",
+    "th",
+    "tr",
+    "ul",
+];
+
+fn http_client() -> &'static OutboundClient {
+    static CLIENT: OnceLock<OutboundClient> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        outbound_http::client_builder("url_monitor", "RayClaw/1.0")
+            .timeout(std::time::Duration::from_secs(20))
+            .redirect(reqwest::redirect::Policy::limited(5))
+            .build()
+            .expect("failed to build HTTP client")
+            .into()
+    })
+}
+
+/// Parse a CSS selector, with a readable error for the model.
+pub fn parse_selector(selector: &str) -> Result<Selector, String> {
+    Selector::parse(selector).map_err(|e| format!("Invalid CSS selector '{selector}': {e}"))
+}
+
+fn collect_text(element: ElementRef<'_>, out: &mut String) {
+    let name = element.value().name();
+    if SKIPPED_ELEMENTS.contains(&name) {
+        return;
+    }
+    let block = BLOCK_ELEMENTS.contains(&name);
+    if block {
+        out.push('\n');
+    }
+    for child in element.children() {
+        match child.value() {
+            Node::Text(text) => out.push_str(text),
+            Node::Element(_) => {
+                if let Some(child) = ElementRef::wrap(child) {
+                    collect_text(child, out);
+                }
+            }
+            _ => {}
+        }
+    }
+    if block {
+        out.push('\n');
+    }
+}
+
+/// One line per block of visible text, whitespace collapsed, blank lines
+/// dropped, so cosmetic markup changes don't register as content changes.
+fn normalize_lines(raw: &str) -> Vec<String> {
+    raw.lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect()
+}
+
+/// The text a monitor compares between checks: everything under the
+/// elements matching `selector`, or the page's `main`/`article`/`body`.
+pub fn extract_monitored_text(html: &str, selector: Option<&str>) -> Result<String, String> {
+    let document = Html::parse_document(html);
+    let mut raw = String::new();
+    match selector {
+        Some(selector) => {
+            let parsed = parse_selector(selector)?;
+            let mut matched = false;
+            for element in document.select(&parsed) {
+                matched = true;
+                collect_text(element, &mut raw);
+                raw.push('\n');
+            }
+            if !matched {
+                return Err(format!(
+                    "CSS selector '{selector}' matched nothing on the page"
+                ));
+            }
+        }
+        None => {
+            let root = ["main", "article", "body"]
+                .iter()
+                .filter_map(|tag| Selector::parse(tag).ok())
+                .find_map(|sel| document.select(&sel).next())
+                .unwrap_or_else(|| document.root_element());
+            collect_text(root, &mut raw);
+        }
+    }
+
+    let mut text = normalize_lines(&raw).join("\n");
+    if text.len() > MAX_CONTENT_BYTES {
+        text.truncate(floor_char_boundary(&text, MAX_CONTENT_BYTES));
+    }
+    Ok(text)
+}
+
+/// Fetch `url` and extract its monitored text.
+pub async fn fetch_monitored_content(url: &str, selector: Option<&str>) -> Result<String, String> {
+    crate::politeness::before_request("url_monitor", url, None).await?;
+    let resp = http_client()
+        .get(url)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("HTTP {}", resp.status()));
+    }
+    let body = resp.text().await.map_err(|e| e.to_string())?;
+    extract_monitored_text(&body, selector)
+}
+
+fn clip_line(line: &str) -> String {
+    if line.chars().count() > MAX_DIFF_LINE_CHARS {
+        let cut: String = line.chars().take(MAX_DIFF_LINE_CHARS).collect();
+        format!("{cut}…")
+    } else {
+        line.to_string()
+    }
+}
+
+/// Line-level summary of what changed: lines that disappeared (`-`) and
+/// lines that appeared (`+`), each side capped at a few lines.
+pub fn diff_summary(old: &str, new: &str) -> String {
+    let old_lines: HashSet<&str> = old.lines().collect();
+    let new_lines: HashSet<&str> = new.lines().collect();
+    let removed: Vec<&str> = old.lines().filter(|l| !new_lines.contains(l)).collect();
+    let added: Vec<&str> = new.lines().filter(|l| !old_lines.contains(l)).collect();
+
+    if removed.is_empty() && added.is_empty() {
+        return "Same lines, different order.".into();
+    }
+
+    let mut out = Vec::new();
+    for (prefix, lines) in [("-", &removed), ("+", &added)] {
+        for line in lines.iter().take(MAX_DIFF_LINES) {
+            out.push(format!("{prefix} {}", clip_line(line)));
+        }
+        if lines.len() > MAX_DIFF_LINES {
+            out.push(format!(
+                "{prefix} … {} more line(s)",
+                lines.len() - MAX_DIFF_LINES
+            ));
+        }
+    }
+    out.join("\n")
+}
+
+pub fn next_check_after(now: DateTime<Utc>, interval_mins: i64) -> String {
+    (now + chrono::Duration::minutes(interval_mins)).to_rfc3339()
+}
+
+/// Human-readable name of what a monitor watches.
+pub fn describe_target(url: &str, selector: Option<&str>) -> String {
+    match selector {
+        Some(selector) => format!("{url} ({selector})"),
+        None => url.to_string(),
+    }
+}
+
+/// Notification for a check, if one is due: a change against the stored
+/// content, or the first failure after a run of successful checks.
+fn notification_for(monitor: &UrlMonitor, result: &Result<String, String>) -> Option<String> {
+    let target = describe_target(&monitor.url, monitor.selector.as_deref());
+    match result {
+        Ok(content) => {
+            let previous = monitor.last_content.as_deref()?;
+            (previous != content).then(|| {
+                format!(
+                    "🔔 Change detected on {target} (monitor #{}):\n{}",
+                    monitor.id,
+                    diff_summary(previous, content)
+                )
+            })
+        }
+        Err(e) => monitor.last_error.is_none().then(|| {
+            format!(
+                "⚠️ Monitor #{} could not check {target}: {e}. It will keep retrying; you'll hear about the next change once it recovers.",
+                monitor.id
+            )
+        }),
+    }
+}
+
+/// Check every monitor that is due and notify chats about changes.
+pub async fn run_due_monitors(state: &Arc<AppState>) {
+    let now = Utc::now().to_rfc3339();
+    let monitors =
+        match call_blocking(state.db.clone(), move |db| db.get_due_url_monitors(&now)).await {
+            Ok(m) => m,
+            Err(e) => {
+                error!("URL monitor: failed to query due monitors: {e}");
+                return;
+            }
+        };
+
+    for monitor in monitors {
+        let result = fetch_monitored_content(&monitor.url, monitor.selector.as_deref()).await;
+        let notification = notification_for(&monitor, &result);
+        let changed = matches!(&result, Ok(content) if monitor.last_content.as_deref().is_some_and(|prev| prev != content));
+        if changed {
+            info!(
+                "URL monitor #{}: change detected on {}",
+                monitor.id, monitor.url
+            );
+        }
+
+        let checked_at = Utc::now();
+        let next_check = next_check_after(checked_at, monitor.interval_mins);
+        let id = monitor.id;
+        if let Err(e) = call_blocking(state.db.clone(), move |db| {
+            let (content, error) = match &result {
+                Ok(content) => (Some(content.as_str()), None),
+                Err(e) => (None, Some(e.as_str())),
+            };
+            db.record_url_monitor_check(
+                id,
+                &checked_at.to_rfc3339(),
+                &next_check,
+                content,
+                changed,
+                error,
+            )
+        })
+        .await
+        {
+            error!("URL monitor: failed to record check for #{id}: {e}");
+        }
+
+        if let Some(text) = notification {
+            let kind = if changed {
+                NotificationKind::UrlChange
+            } else {
+                NotificationKind::UrlMonitorError
+            };
+            if let Err(e) = notify_chat(state, monitor.chat_id, &text, kind).await {
+                error!(
+                    "URL monitor: failed to notify chat {}: {e}",
+                    monitor.chat_id
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE: &str = r#"<html><head><title>Shop</title><style>.x{}</style></head>
+        <body>
+          <nav>Home | Products</nav>
+          <main>
+            <h1>Widget</h1>
+            <p>The   best <b>widget</b> around.</p>
+            <div class="price">$10.00</div>
+            <script>var tracking = 1;</script>
+            <ul><li>Red</li><li>Blue</li></ul>
+          </main>
+        </body></html>"#;
+
+    fn monitor(last_content: Option<&str>, last_error: Option<&str>) -> UrlMonitor {
+        UrlMonitor {
+            id: 7,
+            chat_id: 100,
+            url: "https://example.com".into(),
+            selector: None,
+            interval_mins: 60,
+            status: "active".into(),
+            last_content: last_content.map(str::to_string),
+            last_error: last_error.map(str::to_string),
+            last_checked: None,
+            last_changed: None,
+            next_check: "2024-01-01T00:00:00+00:00".into(),
+            change_count: 0,
+            created_at: "2024-01-01T00:00:00+00:00".into(),
+        }
+    }
+
+    #[test]
+    fn test_extract_main_content_lines() {
+        let text = extract_monitored_text(PAGE, None).unwrap();
+        assert_eq!(text, "Widget\nThe best widget around.\n$10.00\nRed\nBlue");
+    }
+
+    #[test]
+    fn test_extract_with_selector() {
+        assert_eq!(
+            extract_monitored_text(PAGE, Some("div.price")).unwrap(),
+            "$10.00"
+        );
+        assert_eq!(
+            extract_monitored_text(PAGE, Some("li")).unwrap(),
+            "Red\nBlue"
+        );
+        let err = extract_monitored_text(PAGE, Some("#missing")).unwrap_err();
+        assert!(err.contains("matched nothing"));
+        let err = extract_monitored_text(PAGE, Some("div[")).unwrap_err();
+        assert!(err.contains("Invalid CSS selector"));
+    }
+
+    #[test]
+    fn test_extract_ignores_markup_only_changes() {
+        let restyled = PAGE.replace("<b>widget</b>", "<em>widget</em>");
+        assert_eq!(
+            extract_monitored_text(PAGE, None).unwrap(),
+            extract_monitored_text(&restyled, None).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_diff_summary() {
+        let diff = diff_summary("a\nb\nc", "a\nc\nd");
+        assert_eq!(diff, "- b\n+ d");
+        assert_eq!(diff_summary("a\nb", "b\na"), "Same lines, different order.");
+
+        let old: String = (0..20).map(|i| format!("old {i}\n")).collect();
+        let diff = diff_summary(&old, "new");
+        assert!(diff.contains("- old 9"));
+        assert!(!diff.contains("- old 10"));
+        assert!(diff.contains("… 10 more line(s)"));
+        assert!(diff.ends_with("+ new"));
+    }
+
+    #[test]
+    fn test_notification_for() {
+        let ok = |s: &str| Ok::<String, String>(s.to_string());
+        let changed = notification_for(&monitor(Some("$10"), None), &ok("$12")).unwrap();
+        assert!(changed.contains("monitor #7"));
+        assert!(changed.contains("- $10\n+ $12"));
+        assert!(notification_for(&monitor(Some("$10"), None), &ok("$10")).is_none());
+        // No baseline yet: nothing to compare against
+        assert!(notification_for(&monitor(None, None), &ok("$10")).is_none());
+
+        let failure = Err("HTTP 503".to_string());
+        let first = notification_for(&monitor(Some("$10"), None), &failure).unwrap();
+        assert!(first.contains("HTTP 503"));
+        assert!(notification_for(&monitor(Some("$10"), Some("HTTP 503")), &failure).is_none());
+    }
+
+    #[test]
+    fn test_next_check_after() {
+        let now = DateTime::parse_from_rfc3339("2024-01-01T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(next_check_after(now, 90), "2024-01-01T01:30:00+00:00");
+    }
+}
@@ -4,10 +4,47 @@ use chrono::SecondsFormat;
 
 use crate::config::Config;
 use crate::db::{
-    call_blocking, Database, LlmModelUsageSummary, LlmUsageSummary, MemoryObservabilitySummary,
+    call_blocking, Database, LlmCostSummary, LlmDailyUsage, LlmModelUsageSummary, LlmUsageEntry,
+    LlmUsageSummary, MemoryObservabilitySummary,
 };
+use crate::llm_types::Usage;
 
-fn fmt_int(v: i64) -> String {
+/// Where an LLM call was made, for the usage log.
+pub struct LlmCallSource<'a> {
+    pub chat_id: i64,
+    pub caller_channel: &'a str,
+    pub provider: &'a str,
+    pub model: &'a str,
+    pub request_kind: &'a str,
+}
+
+/// Record one LLM call in the usage log, priced from `model_prices`.
+pub async fn record_llm_call(
+    db: Arc<Database>,
+    config: &Config,
+    source: LlmCallSource<'_>,
+    usage: &Usage,
+) {
+    let entry = LlmUsageEntry {
+        chat_id: source.chat_id,
+        caller_channel: source.caller_channel.to_string(),
+        provider: source.provider.to_string(),
+        model: source.model.to_string(),
+        input_tokens: i64::from(usage.input_tokens),
+        output_tokens: i64::from(usage.output_tokens),
+        cache_read_tokens: i64::from(usage.cache_read_input_tokens),
+        cache_write_tokens: i64::from(usage.cache_creation_input_tokens),
+        cost_usd: config
+            .model_price(source.model)
+            .map(|price| price.usage_cost_usd(usage)),
+        request_kind: source.request_kind.to_string(),
+    };
+    if let Err(e) = call_blocking(db, move |db| db.record_llm_usage(&entry)).await {
+        tracing::warn!("Failed to record LLM usage: {e}");
+    }
+}
+
+pub(crate) fn fmt_int(v: i64) -> String {
     let neg = v < 0;
     let mut n = v.unsigned_abs();
     let mut parts = Vec::new();
@@ -37,6 +74,40 @@ fn fmt_summary_line(name: &str, s: &LlmUsageSummary) -> String {
     )
 }
 
+fn fmt_cost_line(name: &str, c: &LlmCostSummary) -> String {
+    let mut line = format!(
+        "{name:<8} ${:.4}  cache read {} / write {}",
+        c.cost_usd,
+        fmt_int(c.cache_read_tokens),
+        fmt_int(c.cache_write_tokens)
+    );
+    if c.unpriced_requests > 0 {
+        line.push_str(&format!(
+            "  (+{} unpriced req)",
+            fmt_int(c.unpriced_requests)
+        ));
+    }
+    line
+}
+
+fn format_daily_rows(rows: &[LlmDailyUsage]) -> Vec<String> {
+    if rows.is_empty() {
+        return vec!["    - (no data)".to_string()];
+    }
+
+    rows.iter()
+        .map(|row| {
+            format!(
+                "    {}  ${:.4}  tok={}  req={}",
+                row.day,
+                row.cost_usd,
+                fmt_int(row.total_tokens),
+                fmt_int(row.requests)
+            )
+        })
+        .collect()
+}
+
 fn format_model_rows(rows: &[LlmModelUsageSummary], max_rows: usize) -> Vec<String> {
     if rows.is_empty() {
         return vec!["    - (no data)".to_string()];
@@ -64,6 +135,7 @@ fn block_lines(
     all: &LlmUsageSummary,
     d24: &LlmUsageSummary,
     d7: &LlmUsageSummary,
+    costs: [&LlmCostSummary; 3],
     models_24h: &[LlmModelUsageSummary],
     models_7d: &[LlmModelUsageSummary],
 ) -> Vec<String> {
@@ -74,6 +146,11 @@ fn block_lines(
         format!("  🕓 {}", fmt_summary_line("Last 24h", d24)),
         format!("  📆 {}", fmt_summary_line("Last 7d", d7)),
         "".to_string(),
+        "  💵 Cost".to_string(),
+        format!("    {}", fmt_cost_line("All-time", costs[0])),
+        format!("    {}", fmt_cost_line("Last 24h", costs[1])),
+        format!("    {}", fmt_cost_line("Last 7d", costs[2])),
+        "".to_string(),
         "  🤖 Top models (24h)".to_string(),
     ];
     lines.extend(format_model_rows(models_24h, 4));
@@ -108,6 +185,71 @@ async fn query_by_model(
     .map_err(|e| e.to_string())
 }
 
+async fn query_cost(
+    db: Arc<Database>,
+    chat_id: Option<i64>,
+    since: Option<String>,
+) -> Result<LlmCostSummary, String> {
+    call_blocking(db, move |d| {
+        d.get_llm_cost_summary(chat_id, since.as_deref())
+    })
+    .await
+    .map_err(|e| e.to_string())
+}
+
+async fn query_daily(
+    db: Arc<Database>,
+    chat_id: Option<i64>,
+    since: String,
+) -> Result<Vec<LlmDailyUsage>, String> {
+    call_blocking(db, move |d| d.get_llm_usage_by_day(chat_id, &since))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Start of the UTC day `days - 1` days ago, so `days` = 7 covers today and
+/// the six days before it.
+fn day_window_start(now: chrono::DateTime<chrono::Utc>, days: i64) -> String {
+    let start = (now - chrono::Duration::days(days.max(1) - 1)).date_naive();
+    start
+        .and_hms_opt(0, 0, 0)
+        .unwrap_or_default()
+        .and_utc()
+        .to_rfc3339()
+}
+
+/// Per-day LLM usage and cost for one chat (or all chats) over the last
+/// `days` days.
+pub async fn build_cost_report(
+    db: Arc<Database>,
+    chat_id: Option<i64>,
+    days: i64,
+) -> Result<serde_json::Value, String> {
+    let since = day_window_start(chrono::Utc::now(), days);
+    let daily = query_daily(db.clone(), chat_id, since.clone()).await?;
+    let summary = query_summary(db.clone(), chat_id, Some(since.clone())).await?;
+    let cost = query_cost(db, chat_id, Some(since.clone())).await?;
+
+    Ok(serde_json::json!({
+        "chat_id": chat_id,
+        "since": since,
+        "requests": summary.requests,
+        "input_tokens": summary.input_tokens,
+        "output_tokens": summary.output_tokens,
+        "cache_read_tokens": cost.cache_read_tokens,
+        "cache_write_tokens": cost.cache_write_tokens,
+        "total_tokens": summary.total_tokens,
+        "cost_usd": cost.cost_usd,
+        "unpriced_requests": cost.unpriced_requests,
+        "daily": daily.iter().map(|d| serde_json::json!({
+            "day": d.day,
+            "requests": d.requests,
+            "total_tokens": d.total_tokens,
+            "cost_usd": d.cost_usd,
+        })).collect::<Vec<_>>(),
+    }))
+}
+
 async fn query_memory_summary(
     db: Arc<Database>,
     chat_id: Option<i64>,
@@ -129,8 +271,13 @@ pub async fn build_usage_report(
     let chat_all = query_summary(db.clone(), Some(chat_id), None).await?;
     let chat_24h = query_summary(db.clone(), Some(chat_id), Some(since_24h.clone())).await?;
     let chat_7d = query_summary(db.clone(), Some(chat_id), Some(since_7d.clone())).await?;
-    let chat_models_24h = query_by_model(db.clone(), Some(chat_id), Some(since_24h)).await?;
-    let chat_models_7d = query_by_model(db.clone(), Some(chat_id), Some(since_7d)).await?;
+    let chat_cost_all = query_cost(db.clone(), Some(chat_id), None).await?;
+    let chat_cost_24h = query_cost(db.clone(), Some(chat_id), Some(since_24h.clone())).await?;
+    let chat_cost_7d = query_cost(db.clone(), Some(chat_id), Some(since_7d.clone())).await?;
+    let chat_models_24h =
+        query_by_model(db.clone(), Some(chat_id), Some(since_24h.clone())).await?;
+    let chat_models_7d = query_by_model(db.clone(), Some(chat_id), Some(since_7d.clone())).await?;
+    let chat_daily = query_daily(db.clone(), Some(chat_id), day_window_start(now, 7)).await?;
 
     let global_all = query_summary(db.clone(), None, None).await?;
     let global_24h = query_summary(
@@ -145,6 +292,9 @@ pub async fn build_usage_report(
         Some((now - chrono::Duration::days(7)).to_rfc3339()),
     )
     .await?;
+    let global_cost_all = query_cost(db.clone(), None, None).await?;
+    let global_cost_24h = query_cost(db.clone(), None, Some(since_24h)).await?;
+    let global_cost_7d = query_cost(db.clone(), None, Some(since_7d)).await?;
     let global_models_24h = query_by_model(
         db.clone(),
         None,
@@ -174,9 +324,13 @@ pub async fn build_usage_report(
         &chat_all,
         &chat_24h,
         &chat_7d,
+        [&chat_cost_all, &chat_cost_24h, &chat_cost_7d],
         &chat_models_24h,
         &chat_models_7d,
     ));
+    lines.push("".to_string());
+    lines.push("  📅 Daily (7d, UTC)".to_string());
+    lines.extend(format_daily_rows(&chat_daily));
 
     lines.push("".to_string());
 
@@ -185,6 +339,7 @@ pub async fn build_usage_report(
         &global_all,
         &global_24h,
         &global_7d,
+        [&global_cost_all, &global_cost_24h, &global_cost_7d],
         &global_models_24h,
         &global_models_7d,
     ));
@@ -13,15 +13,16 @@ use include_dir::{include_dir, Dir};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use tokio::sync::{broadcast, Mutex};
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 use crate::agent_engine::{
     process_with_agent, process_with_agent_with_events, AgentEvent, AgentRequestContext,
 };
 use crate::channel::ConversationKind;
 use crate::channel::{deliver_and_store_bot_message, get_chat_routing, session_source_for_chat};
-use crate::channel_adapter::{ChannelAdapter, ChannelRegistry};
+use crate::channel_adapter::{ChannelAdapter, ChannelRegistry, TextFormat};
 use crate::config::{Config, WorkingDirIsolation};
+use crate::coordination::{Admission, SharedRateLimiter};
 use crate::db::{call_blocking, ChatSummary, StoredMessage};
 use crate::runtime::AppState;
 use crate::usage::build_usage_report;
@@ -36,6 +37,10 @@ impl ChannelAdapter for WebAdapter {
         "web"
     }
 
+    fn text_format(&self) -> TextFormat {
+        TextFormat::Markdown
+    }
+
     fn chat_type_routes(&self) -> Vec<(&str, ConversationKind)> {
         vec![("web", ConversationKind::Private)]
     }
@@ -60,6 +65,7 @@ struct WebState {
     run_hub: RunHub,
     session_hub: SessionHub,
     request_hub: RequestHub,
+    voice_hub: VoiceHub,
     limits: WebLimits,
 }
 
@@ -116,6 +122,16 @@ impl WebLimits {
 #[derive(Clone, Default)]
 struct RequestHub {
     sessions: Arc<Mutex<HashMap<String, SessionQuota>>>,
+    /// Limits shared across instances; the in-memory quotas are the
+    /// fallback when Redis can't be reached.
+    shared: Option<SharedRateLimiter>,
+}
+
+/// Where a request was admitted, so it is released in the same place.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Admitted {
+    Local,
+    Shared,
 }
 
 struct SessionQuota {
@@ -139,6 +155,39 @@ struct SessionLockEntry {
     last_touch: Instant,
 }
 
+/// In-progress voice turns: audio chunks are transcribed as they arrive and
+/// the transcript is sent to the agent when the turn is finished.
+#[derive(Clone, Default)]
+struct VoiceHub {
+    turns: Arc<Mutex<HashMap<String, VoiceTurn>>>,
+}
+
+struct VoiceTurn {
+    session_key: String,
+    sender_name: Option<String>,
+    /// Transcribed chunks keyed by client sequence number
+    transcript: Vec<(u64, String)>,
+    next_seq: u64,
+    last_touch: Instant,
+}
+
+impl VoiceTurn {
+    fn transcript_text(&self) -> String {
+        let mut parts = self.transcript.clone();
+        parts.sort_by_key(|(seq, _)| *seq);
+        parts
+            .into_iter()
+            .map(|(_, text)| text)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Abandoned voice turns are dropped after this long without a chunk.
+const VOICE_TURN_TTL: Duration = Duration::from_secs(600);
+/// Whisper rejects uploads above 25 MB.
+const MAX_VOICE_CHUNK_BYTES: usize = 25 * 1024 * 1024;
+
 #[derive(Clone)]
 struct RunChannel {
     sender: broadcast::Sender<RunEvent>,
@@ -258,11 +307,44 @@ impl SessionHub {
 }
 
 impl RequestHub {
+    fn new(shared: Option<SharedRateLimiter>) -> Self {
+        Self {
+            shared,
+            ..Self::default()
+        }
+    }
+
     async fn begin(
         &self,
         session_key: &str,
         limits: &WebLimits,
-    ) -> Result<(), (StatusCode, String)> {
+    ) -> Result<Admitted, (StatusCode, String)> {
+        if let Some(shared) = &self.shared {
+            match shared
+                .begin(
+                    &format!("web:{session_key}"),
+                    limits.max_inflight_per_session,
+                    limits.max_requests_per_window,
+                    limits.rate_window,
+                )
+                .await
+            {
+                Ok(Admission::Admitted) => return Ok(Admitted::Shared),
+                Ok(Admission::TooManyInflight) => {
+                    return Err((
+                        StatusCode::TOO_MANY_REQUESTS,
+                        "too many concurrent requests for session".into(),
+                    ))
+                }
+                Ok(Admission::RateLimited) => {
+                    return Err((
+                        StatusCode::TOO_MANY_REQUESTS,
+                        "rate limit exceeded for session".into(),
+                    ))
+                }
+                Err(e) => warn!("Shared rate limits unavailable, using this instance's: {e}"),
+            }
+        }
         let now = Instant::now();
         let mut guard = self.sessions.lock().await;
         let quota = guard.entry(session_key.to_string()).or_default();
@@ -291,10 +373,16 @@ impl RequestHub {
 
         quota.inflight += 1;
         quota.recent.push_back(now);
-        Ok(())
+        Ok(Admitted::Local)
     }
 
-    async fn end_with_limits(&self, session_key: &str, limits: &WebLimits) {
+    async fn end_with_limits(&self, session_key: &str, limits: &WebLimits, admitted: Admitted) {
+        if let (Some(shared), Admitted::Shared) = (&self.shared, admitted) {
+            if let Err(e) = shared.end(&format!("web:{session_key}")).await {
+                warn!("Failed to release shared rate limit for {session_key}: {e}");
+            }
+            return;
+        }
         let now = Instant::now();
         let mut guard = self.sessions.lock().await;
         if let Some(quota) = guard.get_mut(session_key) {
@@ -326,6 +414,14 @@ fn auth_token_from_headers(headers: &HeaderMap) -> Option<String> {
         .filter(|v| !v.is_empty())
 }
 
+/// Compare a caller's token in constant time.
+#[allow(deprecated)]
+pub(crate) fn token_matches(token: Option<&str>, expected: &str) -> bool {
+    token.is_some_and(|t| {
+        ring::constant_time::verify_slices_are_equal(t.as_bytes(), expected.as_bytes()).is_ok()
+    })
+}
+
 fn require_auth(
     headers: &HeaderMap,
     expected_token: Option<&str>,
@@ -400,11 +496,39 @@ struct RunStatusQuery {
     run_id: String,
 }
 
+#[derive(Debug, Deserialize)]
+struct VoiceStartRequest {
+    session_key: Option<String>,
+    sender_name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct VoiceChunkQuery {
+    voice_id: String,
+    /// Position of the chunk in the utterance; defaults to arrival order
+    seq: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct VoiceFinishRequest {
+    voice_id: String,
+    /// Stream the reply back as speech (default true)
+    speak: Option<bool>,
+}
+
 #[derive(Debug, Deserialize)]
 struct UsageQuery {
     session_key: Option<String>,
 }
 
+#[derive(Debug, Deserialize)]
+struct AnalyticsQuery {
+    session_key: Option<String>,
+    days: Option<u32>,
+    /// `json` (default) or `png` for the messages-per-day chart
+    format: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 struct MemoryObservabilityQuery {
     session_key: Option<String>,
@@ -492,6 +616,8 @@ fn json_to_yaml_value(v: &serde_json::Value) -> serde_yaml::Value {
 const CHANNEL_SECRET_FIELDS: &[(&str, &[&str])] = &[
     ("slack", &["bot_token", "app_token"]),
     ("feishu", &["app_secret"]),
+    ("teams", &["app_password"]),
+    ("webhook", &["token", "secret"]),
 ];
 
 fn config_path_for_save() -> Result<PathBuf, (StatusCode, String)> {
@@ -522,24 +648,40 @@ fn redact_config(config: &Config) -> serde_json::Value {
     if cfg.web_auth_token.is_some() {
         cfg.web_auth_token = Some("***".into());
     }
+    if let Some(gateway) = cfg.email_gateway.as_mut() {
+        gateway.token = "***".into();
+    }
 
     // Redact secrets in channels map using declarative list
     for (channel_name, secret_fields) in CHANNEL_SECRET_FIELDS {
         if let Some(channel_val) = cfg.channels.get_mut(*channel_name) {
-            if let Some(map) = channel_val.as_mapping_mut() {
-                for field in *secret_fields {
-                    let key = serde_yaml::Value::String((*field).to_string());
-                    if map.contains_key(&key) {
-                        map.insert(key, serde_yaml::Value::String("***".into()));
-                    }
-                }
-            }
+            redact_channel_secrets(channel_val, secret_fields);
         }
     }
 
     json!(cfg)
 }
 
+/// Mask `secret_fields` in a channel config, including inside nested lists
+/// such as Slack's per-workspace installs.
+fn redact_channel_secrets(value: &mut serde_yaml::Value, secret_fields: &[&str]) {
+    if let Some(map) = value.as_mapping_mut() {
+        for field in secret_fields {
+            let key = serde_yaml::Value::String((*field).to_string());
+            if map.contains_key(&key) {
+                map.insert(key, serde_yaml::Value::String("***".into()));
+            }
+        }
+        for (_, nested) in map.iter_mut() {
+            if let Some(items) = nested.as_sequence_mut() {
+                for item in items {
+                    redact_channel_secrets(item, secret_fields);
+                }
+            }
+        }
+    }
+}
+
 async fn index() -> impl IntoResponse {
     match WEB_ASSETS.get_file("index.html") {
         Some(file) => Html(String::from_utf8_lossy(file.contents()).to_string()).into_response(),
@@ -880,6 +1022,58 @@ async fn api_usage(
     })))
 }
 
+async fn api_analytics(
+    headers: HeaderMap,
+    State(state): State<WebState>,
+    Query(query): Query<AnalyticsQuery>,
+) -> Result<axum::response::Response, (StatusCode, String)> {
+    require_auth(&headers, state.auth_token.as_deref())?;
+
+    let days = query.days.unwrap_or(crate::analytics::DEFAULT_DAYS);
+    if !(1..=crate::analytics::MAX_DAYS).contains(&days) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("days must be between 1 and {}", crate::analytics::MAX_DAYS),
+        ));
+    }
+    let session_key = normalize_session_key(query.session_key.as_deref());
+    let chat_id = resolve_chat_id_for_session_key(&state, &session_key).await?;
+    let analytics = crate::analytics::build_chat_analytics(
+        state.app_state.db.clone(),
+        &state.app_state.config,
+        chat_id,
+        days,
+    )
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+    match query.format.as_deref().unwrap_or("json") {
+        "json" => Ok(Json(json!({
+            "ok": true,
+            "session_key": session_key,
+            "chat_id": chat_id,
+            "report": crate::analytics::format_report(&analytics),
+            "analytics": analytics,
+        }))
+        .into_response()),
+        "png" => {
+            let spec = crate::analytics::messages_chart(&analytics);
+            let font_path = state.app_state.config.chart_font_path.clone();
+            let png = tokio::task::spawn_blocking(move || {
+                crate::chart::render_png(&spec, 800, 480, font_path.as_deref())
+            })
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+            Ok(([("content-type", "image/png")], png).into_response())
+        }
+        other => Err((
+            StatusCode::BAD_REQUEST,
+            format!("unsupported format '{other}' (expected json or png)"),
+        )),
+    }
+}
+
 async fn api_memory_observability(
     headers: HeaderMap,
     State(state): State<WebState>,
@@ -983,21 +1177,24 @@ async fn api_send(
     require_auth(&headers, state.auth_token.as_deref())?;
     let start = Instant::now();
     let session_key = normalize_session_key(body.session_key.as_deref());
-    if let Err((status, msg)) = state.request_hub.begin(&session_key, &state.limits).await {
-        info!(
-            target: "web",
-            endpoint = "/api/send",
-            session_key = %session_key,
-            status = status.as_u16(),
-            reason = %msg,
-            "Request rejected by limiter"
-        );
-        return Err((status, msg));
-    }
+    let admitted = match state.request_hub.begin(&session_key, &state.limits).await {
+        Ok(admitted) => admitted,
+        Err((status, msg)) => {
+            info!(
+                target: "web",
+                endpoint = "/api/send",
+                session_key = %session_key,
+                status = status.as_u16(),
+                reason = %msg,
+                "Request rejected by limiter"
+            );
+            return Err((status, msg));
+        }
+    };
     let result = send_and_store_response(state.clone(), body).await;
     state
         .request_hub
-        .end_with_limits(&session_key, &state.limits)
+        .end_with_limits(&session_key, &state.limits, admitted)
         .await;
     info!(
         target: "web",
@@ -1024,18 +1221,52 @@ async fn api_send_stream(
     }
 
     let session_key = normalize_session_key(body.session_key.as_deref());
-    if let Err((status, msg)) = state.request_hub.begin(&session_key, &state.limits).await {
-        info!(
-            target: "web",
-            endpoint = "/api/send_stream",
-            session_key = %session_key,
-            status = status.as_u16(),
-            reason = %msg,
-            "Request rejected by limiter"
-        );
-        return Err((status, msg));
-    }
+    let admitted = match state.request_hub.begin(&session_key, &state.limits).await {
+        Ok(admitted) => admitted,
+        Err((status, msg)) => {
+            info!(
+                target: "web",
+                endpoint = "/api/send_stream",
+                session_key = %session_key,
+                status = status.as_u16(),
+                reason = %msg,
+                "Request rejected by limiter"
+            );
+            return Err((status, msg));
+        }
+    };
+
+    let run_id = start_stream_run(
+        state,
+        body,
+        session_key,
+        admitted,
+        "/api/send_stream",
+        start,
+        None,
+    )
+    .await;
+
+    Ok(Json(json!({
+        "ok": true,
+        "run_id": run_id,
+    })))
+}
 
+/// Start an agent run in the background and return its `run_id`; progress is
+/// published to the run hub for `/api/stream`. With `speech`, the reply is
+/// also synthesized sentence by sentence and published as `audio` events
+/// ahead of `done`. The caller must already hold the request-hub slot
+/// `admitted`, which the run releases.
+async fn start_stream_run(
+    state: WebState,
+    body: SendRequest,
+    session_key: String,
+    admitted: Admitted,
+    endpoint: &'static str,
+    start: Instant,
+    speech: Option<crate::tts::TtsSettings>,
+) -> String {
     let run_id = uuid::Uuid::new_v4().to_string();
     state.run_hub.create(&run_id).await;
     let state_for_task = state.clone();
@@ -1048,7 +1279,7 @@ async fn api_send_stream(
     let session_key_for_release = session_key.clone();
     info!(
         target: "web",
-        endpoint = "/api/send_stream",
+        endpoint = endpoint,
         session_key = %session_key,
         run_id = %run_id,
         latency_ms = start.elapsed().as_millis(),
@@ -1068,11 +1299,31 @@ async fn api_send_stream(
             )
             .await;
 
+        // Reply sentences flow from the event forwarder to the TTS task,
+        // which publishes them as ordered `audio` events.
+        let (sentence_tx, speech_task) = match speech {
+            Some(settings) => {
+                let (tx, rx) = tokio::sync::mpsc::unbounded_channel::<String>();
+                let task = tokio::spawn(speak_sentences(
+                    settings,
+                    rx,
+                    state_for_task.run_hub.clone(),
+                    run_id_for_task.clone(),
+                    limits.run_history_limit,
+                ));
+                (Some(tx), Some(task))
+            }
+            None => (None, None),
+        };
+
         let (evt_tx, mut evt_rx) = tokio::sync::mpsc::unbounded_channel::<AgentEvent>();
         let run_hub = state_for_task.run_hub.clone();
         let run_id_for_events = run_id_for_task.clone();
         let run_history_limit = limits.run_history_limit;
+        let forward_sentence_tx = sentence_tx.clone();
         let forward = tokio::spawn(async move {
+            let mut chunker = crate::tts::SentenceChunker::default();
+            let mut spoke = false;
             while let Some(evt) = evt_rx.recv().await {
                 match evt {
                     AgentEvent::Iteration { iteration } => {
@@ -1103,6 +1354,9 @@ async fn api_send_stream(
                         status_code,
                         bytes,
                         error_type,
+                        truncated,
+                        produced_files,
+                        suggested_next_tools,
                     } => {
                         run_hub
                             .publish(
@@ -1115,7 +1369,10 @@ async fn api_send_stream(
                                     "duration_ms": duration_ms,
                                     "status_code": status_code,
                                     "bytes": bytes,
-                                    "error_type": error_type
+                                    "error_type": error_type,
+                                    "truncated": truncated,
+                                    "produced_files": produced_files,
+                                    "suggested_next_tools": suggested_next_tools
                                 })
                                 .to_string(),
                                 run_history_limit,
@@ -1123,6 +1380,12 @@ async fn api_send_stream(
                             .await;
                     }
                     AgentEvent::TextDelta { delta } => {
+                        if let Some(tx) = &forward_sentence_tx {
+                            for sentence in chunker.push(&delta) {
+                                spoke = true;
+                                let _ = tx.send(sentence);
+                            }
+                        }
                         run_hub
                             .publish(
                                 &run_id_for_events,
@@ -1133,12 +1396,41 @@ async fn api_send_stream(
                             .await;
                     }
                     AgentEvent::FinalResponse { .. } => {}
+                    AgentEvent::Superseded => {
+                        run_hub
+                            .publish(
+                                &run_id_for_events,
+                                "status",
+                                json!({"message": "merged into a newer message"}).to_string(),
+                                run_history_limit,
+                            )
+                            .await;
+                    }
                 }
             }
+            if let (Some(tx), Some(rest)) = (&forward_sentence_tx, chunker.finish()) {
+                spoke = true;
+                let _ = tx.send(rest);
+            }
+            spoke
         });
 
-        match send_and_store_response_with_events(state_for_task.clone(), body, Some(&evt_tx)).await
-        {
+        let outcome =
+            send_and_store_response_with_events(state_for_task.clone(), body, Some(&evt_tx)).await;
+        drop(evt_tx);
+        let spoke = forward.await.unwrap_or(false);
+        if let (Some(tx), Ok(resp), false) = (&sentence_tx, &outcome, spoke) {
+            // Nothing was streamed (e.g. an ACP or command reply); speak the final text
+            if let Some(text) = resp.0.get("response").and_then(|v| v.as_str()) {
+                let _ = tx.send(text.to_string());
+            }
+        }
+        drop(sentence_tx);
+        if let Some(task) = speech_task {
+            let _ = task.await;
+        }
+
+        match outcome {
             Ok(resp) => {
                 let response_text = resp
                     .0
@@ -1169,15 +1461,13 @@ async fn api_send_stream(
                     .await;
             }
         }
-        drop(evt_tx);
-        let _ = forward.await;
         state_for_task
             .request_hub
-            .end_with_limits(&session_key_for_release, &limits)
+            .end_with_limits(&session_key_for_release, &limits, admitted)
             .await;
         info!(
             target: "web",
-            endpoint = "/api/send_stream",
+            endpoint = endpoint,
             session_key = %session_key_for_release,
             run_id = %run_id_for_task,
             latency_ms = run_start.elapsed().as_millis(),
@@ -1190,9 +1480,218 @@ async fn api_send_stream(
             .await;
     });
 
+    run_id
+}
+
+/// Synthesize queued reply sentences in order, publishing each as an `audio`
+/// event (base64 MP3) on the run.
+async fn speak_sentences(
+    settings: crate::tts::TtsSettings,
+    mut sentences: tokio::sync::mpsc::UnboundedReceiver<String>,
+    run_hub: RunHub,
+    run_id: String,
+    history_limit: usize,
+) {
+    use base64::Engine;
+
+    let mut seq: u64 = 0;
+    while let Some(sentence) = sentences.recv().await {
+        let text = crate::tts::speakable_text(&sentence);
+        if text.is_empty() {
+            continue;
+        }
+        match crate::tts::synthesize_speech(&settings, &text).await {
+            Ok(audio) => {
+                seq += 1;
+                run_hub
+                    .publish(
+                        &run_id,
+                        "audio",
+                        json!({
+                            "seq": seq,
+                            "format": "mp3",
+                            "text": text,
+                            "data": base64::engine::general_purpose::STANDARD.encode(audio),
+                        })
+                        .to_string(),
+                        history_limit,
+                    )
+                    .await;
+            }
+            Err(e) => {
+                error!("Voice reply synthesis failed: {e}");
+                run_hub
+                    .publish(
+                        &run_id,
+                        "status",
+                        json!({"message": format!("speech synthesis failed: {e}")}).to_string(),
+                        history_limit,
+                    )
+                    .await;
+            }
+        }
+    }
+}
+
+fn voice_api_key(state: &WebState) -> Result<String, (StatusCode, String)> {
+    state
+        .app_state
+        .config
+        .openai_api_key
+        .as_deref()
+        .map(str::trim)
+        .filter(|k| !k.is_empty())
+        .map(str::to_string)
+        .ok_or((
+            StatusCode::BAD_REQUEST,
+            "voice mode requires openai_api_key".into(),
+        ))
+}
+
+async fn api_voice_start(
+    headers: HeaderMap,
+    State(state): State<WebState>,
+    Json(body): Json<VoiceStartRequest>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    require_auth(&headers, state.auth_token.as_deref())?;
+    voice_api_key(&state)?;
+
+    let session_key = normalize_session_key(body.session_key.as_deref());
+    let voice_id = uuid::Uuid::new_v4().to_string();
+    let now = Instant::now();
+    let mut turns = state.voice_hub.turns.lock().await;
+    turns.retain(|_, turn| now.duration_since(turn.last_touch) < VOICE_TURN_TTL);
+    turns.insert(
+        voice_id.clone(),
+        VoiceTurn {
+            session_key: session_key.clone(),
+            sender_name: body.sender_name,
+            transcript: Vec::new(),
+            next_seq: 0,
+            last_touch: now,
+        },
+    );
+
+    Ok(Json(json!({
+        "ok": true,
+        "voice_id": voice_id,
+        "session_key": session_key,
+    })))
+}
+
+async fn api_voice_chunk(
+    headers: HeaderMap,
+    State(state): State<WebState>,
+    Query(query): Query<VoiceChunkQuery>,
+    audio: axum::body::Bytes,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    require_auth(&headers, state.auth_token.as_deref())?;
+    let api_key = voice_api_key(&state)?;
+    if audio.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "audio chunk is empty".into()));
+    }
+    if audio.len() > MAX_VOICE_CHUNK_BYTES {
+        return Err((
+            StatusCode::PAYLOAD_TOO_LARGE,
+            "audio chunk exceeds 25 MB".into(),
+        ));
+    }
+    if !state
+        .voice_hub
+        .turns
+        .lock()
+        .await
+        .contains_key(&query.voice_id)
+    {
+        return Err((StatusCode::NOT_FOUND, "voice turn not found".into()));
+    }
+
+    let mime = headers
+        .get("content-type")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("audio/webm")
+        .to_string();
+    let text = crate::transcribe::transcribe_audio_as(&api_key, &audio, &mime)
+        .await
+        .map_err(|e| (StatusCode::BAD_GATEWAY, e))?;
+    let text = text.trim().to_string();
+
+    let mut turns = state.voice_hub.turns.lock().await;
+    let Some(turn) = turns.get_mut(&query.voice_id) else {
+        return Err((StatusCode::NOT_FOUND, "voice turn not found".into()));
+    };
+    let seq = query.seq.unwrap_or(turn.next_seq);
+    turn.next_seq = turn.next_seq.max(seq + 1);
+    turn.last_touch = Instant::now();
+    if !text.is_empty() {
+        turn.transcript.push((seq, text.clone()));
+    }
+
+    Ok(Json(json!({
+        "ok": true,
+        "seq": seq,
+        "text": text,
+        "transcript": turn.transcript_text(),
+    })))
+}
+
+async fn api_voice_finish(
+    headers: HeaderMap,
+    State(state): State<WebState>,
+    Json(body): Json<VoiceFinishRequest>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    require_auth(&headers, state.auth_token.as_deref())?;
+    let start = Instant::now();
+
+    let Some(turn) = state.voice_hub.turns.lock().await.remove(&body.voice_id) else {
+        return Err((StatusCode::NOT_FOUND, "voice turn not found".into()));
+    };
+    let transcript = turn.transcript_text();
+    if transcript.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "no speech recognized".into()));
+    }
+
+    let session_key = turn.session_key;
+    let admitted = match state.request_hub.begin(&session_key, &state.limits).await {
+        Ok(admitted) => admitted,
+        Err((status, msg)) => {
+            info!(
+                target: "web",
+                endpoint = "/api/voice/finish",
+                session_key = %session_key,
+                status = status.as_u16(),
+                reason = %msg,
+                "Request rejected by limiter"
+            );
+            return Err((status, msg));
+        }
+    };
+
+    let speech = if body.speak.unwrap_or(true) {
+        crate::tts::TtsSettings::from_config(&state.app_state.config)
+    } else {
+        None
+    };
+    let send = SendRequest {
+        session_key: Some(session_key.clone()),
+        sender_name: turn.sender_name,
+        message: transcript.clone(),
+    };
+    let run_id = start_stream_run(
+        state,
+        send,
+        session_key,
+        admitted,
+        "/api/voice/finish",
+        start,
+        speech,
+    )
+    .await;
+
     Ok(Json(json!({
         "ok": true,
         "run_id": run_id,
+        "transcript": transcript,
     })))
 }
 
@@ -1372,6 +1871,7 @@ async fn send_and_store_response_with_events(
         content: text,
         is_from_bot: false,
         timestamp: chrono::Utc::now().to_rfc3339(),
+        thread_id: None,
     };
     call_blocking(state.app_state.db.clone(), move |db| {
         db.store_message(&user_msg)
@@ -1385,7 +1885,9 @@ async fn send_and_store_response_with_events(
             AgentRequestContext {
                 caller_channel: "web",
                 chat_id,
+                thread_id: None,
                 chat_type: "web",
+                denied_tools: &[],
             },
             None,
             None,
@@ -1399,7 +1901,9 @@ async fn send_and_store_response_with_events(
             AgentRequestContext {
                 caller_channel: "web",
                 chat_id,
+                thread_id: None,
                 chat_type: "web",
+                denied_tools: &[],
             },
             None,
             None,
@@ -1499,7 +2003,8 @@ pub async fn start_web_server(state: Arc<AppState>) {
         app_state: state.clone(),
         run_hub: RunHub::default(),
         session_hub: SessionHub::default(),
-        request_hub: RequestHub::default(),
+        request_hub: RequestHub::new(SharedRateLimiter::from_config(&state.config)),
+        voice_hub: VoiceHub::default(),
         limits,
     };
 
@@ -1570,56 +2075,264 @@ async fn favicon_file() -> impl IntoResponse {
 }
 
 // ---------------------------------------------------------------------------
-// ACP HTTP API — /api/acp/*
+// Teams — /api/teams/messages
 // ---------------------------------------------------------------------------
 
-/// Check ACP-specific API token (falls back to web_auth_token if acp_api_token is not set).
-fn require_acp_auth(headers: &HeaderMap, state: &WebState) -> Result<(), (StatusCode, String)> {
-    let token = state
-        .app_state
-        .acp_manager
-        .config
-        .acp_api_token
-        .as_deref()
-        .or(state.auth_token.as_deref());
-    require_auth(headers, token)
+/// Bot Framework messaging endpoint for the Teams channel. Requests carry a
+/// Bot Framework JWT instead of the web auth token.
+#[cfg(feature = "teams")]
+async fn api_teams_messages(
+    headers: HeaderMap,
+    State(state): State<WebState>,
+    body: String,
+) -> Result<StatusCode, (StatusCode, String)> {
+    use crate::channels::teams::{accept_activity, TeamsError};
+
+    let authorization = headers
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok());
+    match accept_activity(state.app_state.clone(), authorization, &body).await {
+        Ok(()) => Ok(StatusCode::OK),
+        Err(TeamsError::NotConfigured) => Err((
+            StatusCode::NOT_FOUND,
+            "Teams channel is not configured".into(),
+        )),
+        Err(TeamsError::Unauthorized(e)) => {
+            warn!("Teams: rejected activity: {e}");
+            Err((StatusCode::UNAUTHORIZED, "unauthorized".into()))
+        }
+        Err(TeamsError::Invalid(e)) => Err((StatusCode::BAD_REQUEST, e)),
+        Err(TeamsError::Unavailable(e)) => {
+            error!("Teams: {e}");
+            Err((StatusCode::SERVICE_UNAVAILABLE, e))
+        }
+    }
 }
 
-async fn api_acp_health(
+// ---------------------------------------------------------------------------
+// Webhook channel — /api/webhook/messages
+// ---------------------------------------------------------------------------
+
+/// `?token=` of the webhook and inbound email endpoints
+#[derive(Debug, Deserialize)]
+struct InboundTokenQuery {
+    token: Option<String>,
+}
+
+/// Inbound endpoint of the generic webhook channel. The channel token comes
+/// as a Bearer header or `?token=`, like the email gateway's.
+#[cfg(feature = "webhook")]
+async fn api_webhook_messages(
     headers: HeaderMap,
+    Query(query): Query<InboundTokenQuery>,
     State(state): State<WebState>,
-) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
-    require_acp_auth(&headers, &state)?;
-    let sessions = state.app_state.acp_manager.list_sessions().await;
-    let agents = state.app_state.acp_manager.available_agents();
-    Ok(Json(json!({
-        "ok": true,
-        "agents_configured": agents.len(),
-        "agents": agents,
-        "active_sessions": sessions.len(),
-    })))
+    body: String,
+) -> Result<(StatusCode, Json<serde_json::Value>), (StatusCode, String)> {
+    use crate::channels::webhook::{accept, WebhookError};
+
+    let token = auth_token_from_headers(&headers).or(query.token);
+    match accept(state.app_state.clone(), token.as_deref(), &body).await {
+        Ok(accepted) => Ok((
+            StatusCode::ACCEPTED,
+            Json(json!({
+                "ok": true,
+                "chat_id": accepted.chat_id,
+                "message_id": accepted.message_id,
+                "duplicate": accepted.duplicate,
+            })),
+        )),
+        Err(WebhookError::NotConfigured) => Err((
+            StatusCode::NOT_FOUND,
+            "webhook channel is not configured".into(),
+        )),
+        Err(WebhookError::Unauthorized) => Err((StatusCode::UNAUTHORIZED, "unauthorized".into())),
+        Err(WebhookError::Invalid(e)) => Err((StatusCode::BAD_REQUEST, e)),
+        Err(WebhookError::Internal(e)) => Err((StatusCode::INTERNAL_SERVER_ERROR, e)),
+    }
 }
 
-async fn api_acp_agents(
+// ---------------------------------------------------------------------------
+// Inbound email — /api/email/inbound
+// ---------------------------------------------------------------------------
+
+/// Webhook for forwarded emails (see `email_gateway`). Accepts JSON or
+/// urlencoded form payloads; the gateway token comes as a Bearer header or
+/// `?token=`, since many mail providers can only configure a URL.
+async fn api_email_inbound(
     headers: HeaderMap,
+    Query(query): Query<InboundTokenQuery>,
     State(state): State<WebState>,
+    body: String,
 ) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
-    require_acp_auth(&headers, &state)?;
-    let agents: Vec<serde_json::Value> = state
-        .app_state
-        .acp_manager
-        .available_agents()
-        .into_iter()
-        .map(|name| {
-            let cfg = state.app_state.acp_manager.agent_config(&name);
-            json!({
-                "name": name,
-                "mode": cfg.map(|c| c.mode.as_str()).unwrap_or("acp"),
-                "launch": cfg.map(|c| c.launch.as_str()).unwrap_or("unknown"),
-                "command": cfg.map(|c| c.command.as_str()).unwrap_or("unknown"),
-                "workspace": cfg.and_then(|c| c.workspace.as_deref()),
-            })
-        })
+    use crate::email_gateway::{accept, GatewayError, InboundEmail};
+
+    let Some(gateway) = state.app_state.config.email_gateway.as_ref() else {
+        return Err((StatusCode::NOT_FOUND, "email gateway is not enabled".into()));
+    };
+    let provided = auth_token_from_headers(&headers).or(query.token);
+    if !token_matches(provided.as_deref(), &gateway.token) {
+        return Err((StatusCode::UNAUTHORIZED, "unauthorized".into()));
+    }
+
+    let is_json = headers
+        .get("content-type")
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct.contains("json"));
+    let email = if is_json {
+        serde_json::from_str(&body)
+            .map_err(|e| e.to_string())
+            .and_then(|v| InboundEmail::from_json(&v))
+    } else {
+        InboundEmail::from_form(&body)
+    }
+    .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    match accept(&state.app_state, gateway, email).await {
+        Ok(accepted) => Ok(Json(json!({
+            "ok": true,
+            "chat_id": accepted.chat_id,
+            "task_id": accepted.task_id,
+            "next_run": accepted.next_run,
+            "recurring": accepted.recurring,
+        }))),
+        Err(GatewayError::NoRoute) => Err((
+            StatusCode::NOT_FOUND,
+            "no route for recipient address".into(),
+        )),
+        Err(GatewayError::SenderNotAllowed) => {
+            Err((StatusCode::FORBIDDEN, "sender not allowed".into()))
+        }
+        Err(GatewayError::Invalid(e)) => Err((StatusCode::BAD_REQUEST, e)),
+        Err(GatewayError::Internal(e)) => Err((StatusCode::INTERNAL_SERVER_ERROR, e)),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Task calendar feed — /api/tasks.ics
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Deserialize)]
+struct TasksIcsQuery {
+    token: Option<String>,
+    chat_id: Option<i64>,
+}
+
+/// iCal feed of upcoming task runs. A per-chat feed token (issued with
+/// `/calendar`) serves that chat's tasks; otherwise the web auth token is
+/// required and `chat_id` narrows the feed, all chats when omitted.
+async fn api_tasks_ics(
+    headers: HeaderMap,
+    Query(query): Query<TasksIcsQuery>,
+    State(state): State<WebState>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let feed_chat = match query.token.clone() {
+        Some(token) => call_blocking(state.app_state.db.clone(), move |db| {
+            db.get_chat_for_calendar_token(&token)
+        })
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?,
+        None => None,
+    };
+    let chat_id = match feed_chat {
+        Some(chat_id) => Some(chat_id),
+        None => {
+            // Calendar apps can't send headers, so the admin token may also
+            // come as ?token=
+            let provided = auth_token_from_headers(&headers).or(query.token);
+            if let Some(expected) = state.auth_token.as_deref() {
+                if !token_matches(provided.as_deref(), expected) {
+                    return Err((StatusCode::UNAUTHORIZED, "unauthorized".into()));
+                }
+            }
+            query.chat_id
+        }
+    };
+
+    let (name, tasks) = call_blocking(state.app_state.db.clone(), move |db| match chat_id {
+        Some(chat_id) => {
+            let title = db
+                .get_chat_title(chat_id)?
+                .unwrap_or_else(|| format!("chat {chat_id}"));
+            Ok((
+                format!("RayClaw tasks: {title}"),
+                db.get_tasks_for_chat(chat_id)?,
+            ))
+        }
+        None => {
+            let (tasks, _) = db.get_all_tasks(Some("active"), None, 1000, 0)?;
+            Ok(("RayClaw tasks".to_string(), tasks))
+        }
+    })
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let tz: chrono_tz::Tz = state
+        .app_state
+        .config
+        .timezone
+        .parse()
+        .unwrap_or(chrono_tz::Tz::UTC);
+    let body = crate::calendar::render_ics(&name, &tasks, tz, chrono::Utc::now());
+    Ok((
+        [
+            ("content-type", "text/calendar; charset=utf-8"),
+            ("cache-control", "no-cache"),
+        ],
+        body,
+    ))
+}
+
+// ---------------------------------------------------------------------------
+// ACP HTTP API — /api/acp/*
+// ---------------------------------------------------------------------------
+
+/// Check ACP-specific API token (falls back to web_auth_token if acp_api_token is not set).
+fn require_acp_auth(headers: &HeaderMap, state: &WebState) -> Result<(), (StatusCode, String)> {
+    let token = state
+        .app_state
+        .acp_manager
+        .config
+        .acp_api_token
+        .as_deref()
+        .or(state.auth_token.as_deref());
+    require_auth(headers, token)
+}
+
+async fn api_acp_health(
+    headers: HeaderMap,
+    State(state): State<WebState>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    require_acp_auth(&headers, &state)?;
+    let sessions = state.app_state.acp_manager.list_sessions().await;
+    let agents = state.app_state.acp_manager.available_agents();
+    Ok(Json(json!({
+        "ok": true,
+        "agents_configured": agents.len(),
+        "agents": agents,
+        "active_sessions": sessions.len(),
+    })))
+}
+
+async fn api_acp_agents(
+    headers: HeaderMap,
+    State(state): State<WebState>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    require_acp_auth(&headers, &state)?;
+    let agents: Vec<serde_json::Value> = state
+        .app_state
+        .acp_manager
+        .available_agents()
+        .into_iter()
+        .map(|name| {
+            let cfg = state.app_state.acp_manager.agent_config(&name);
+            json!({
+                "name": name,
+                "mode": cfg.map(|c| c.mode.as_str()).unwrap_or("acp"),
+                "launch": cfg.map(|c| c.launch.as_str()).unwrap_or("unknown"),
+                "command": cfg.map(|c| c.command.as_str()).unwrap_or("unknown"),
+                "workspace": cfg.and_then(|c| c.workspace.as_deref()),
+            })
+        })
         .collect();
     Ok(Json(json!({ "ok": true, "agents": agents })))
 }
@@ -1643,6 +2356,7 @@ async fn api_acp_list_sessions(
                 "status": format!("{:?}", s.status),
                 "created_at": s.created_at,
                 "idle_secs": s.idle_secs,
+                "usage": s.usage,
             })
         })
         .collect();
@@ -1682,6 +2396,8 @@ async fn api_acp_create_session(
 struct AcpPromptBody {
     message: String,
     timeout_secs: Option<u64>,
+    #[serde(default)]
+    include_thoughts: bool,
 }
 
 async fn api_acp_prompt(
@@ -1697,18 +2413,24 @@ async fn api_acp_prompt(
         .prompt(&session_id, &body.message, body.timeout_secs, None)
         .await
     {
-        Ok(result) => Ok(Json(json!({
-            "ok": true,
-            "completed": result.completed,
-            "messages": result.messages,
-            "tool_calls": result.tool_calls.iter().map(|tc| json!({
-                "name": tc.name,
-                "input": tc.input,
-            })).collect::<Vec<_>>(),
-            "files_changed": result.files_changed,
-            "duration_ms": result.duration_ms,
-            "context_reset": result.context_reset,
-        }))),
+        Ok(result) => {
+            let mut output = json!({
+                "ok": true,
+                "completed": result.completed,
+                "messages": result.messages,
+                "tool_calls": result.tool_calls.iter().map(|tc| json!({
+                    "name": tc.name,
+                    "input": tc.input,
+                })).collect::<Vec<_>>(),
+                "files_changed": result.files_changed,
+                "duration_ms": result.duration_ms,
+                "context_reset": result.context_reset,
+            });
+            if body.include_thoughts {
+                output["thoughts"] = json!(result.thoughts);
+            }
+            Ok(Json(output))
+        }
         Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e)),
     }
 }
@@ -1731,6 +2453,7 @@ async fn api_acp_prompt_stream(
     let sid = session_id.clone();
     let msg = body.message.clone();
     let timeout = body.timeout_secs;
+    let include_thoughts = body.include_thoughts;
 
     // Spawn the prompt in background so we can stream events
     let (result_tx, result_rx) = tokio::sync::oneshot::channel();
@@ -1757,6 +2480,24 @@ async fn api_acp_prompt_stream(
                 AcpProgressEvent::Thinking { text } => json!({
                     "type": "thinking", "text": text
                 }),
+                AcpProgressEvent::MessageChunk { text } => json!({
+                    "type": "message_chunk", "text": text
+                }),
+                AcpProgressEvent::Plan { entries } => json!({
+                    "type": "plan",
+                    "entries": entries.iter().map(|e| json!({
+                        "content": e.content, "status": e.status
+                    })).collect::<Vec<serde_json::Value>>(),
+                }),
+                AcpProgressEvent::TerminalOutput { terminal_id, text } => json!({
+                    "type": "terminal_output", "terminal_id": terminal_id, "text": text
+                }),
+                AcpProgressEvent::Queued { position } => json!({
+                    "type": "queued", "position": position
+                }),
+                AcpProgressEvent::Question { text } => json!({
+                    "type": "question", "text": text
+                }),
             };
             yield Ok(Event::default().event("progress").data(data.to_string()));
         }
@@ -1764,7 +2505,7 @@ async fn api_acp_prompt_stream(
         // Stream final result
         match result_rx.await {
             Ok(Ok(result)) => {
-                let data = json!({
+                let mut data = json!({
                     "ok": true,
                     "completed": result.completed,
                     "messages": result.messages,
@@ -1776,6 +2517,9 @@ async fn api_acp_prompt_stream(
                     "duration_ms": result.duration_ms,
                     "context_reset": result.context_reset,
                 });
+                if include_thoughts {
+                    data["thoughts"] = json!(result.thoughts);
+                }
                 yield Ok(Event::default().event("result").data(data.to_string()));
             }
             Ok(Err(e)) => {
@@ -1804,6 +2548,43 @@ async fn api_acp_end_session(
     }
 }
 
+async fn api_acp_cancel_prompt(
+    headers: HeaderMap,
+    State(state): State<WebState>,
+    Path(session_id): Path<String>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    require_acp_auth(&headers, &state)?;
+    match state.app_state.acp_manager.cancel_prompt(&session_id).await {
+        Ok(()) => Ok(Json(json!({ "ok": true }))),
+        Err(e) if e.contains("not found") => Err((StatusCode::NOT_FOUND, e)),
+        Err(e) => Err((StatusCode::CONFLICT, e)),
+    }
+}
+
+#[derive(Deserialize)]
+struct AcpAnswerBody {
+    answer: String,
+}
+
+async fn api_acp_answer(
+    headers: HeaderMap,
+    State(state): State<WebState>,
+    Path(session_id): Path<String>,
+    Json(body): Json<AcpAnswerBody>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    require_acp_auth(&headers, &state)?;
+    match state
+        .app_state
+        .acp_manager
+        .answer_question(&session_id, &body.answer)
+        .await
+    {
+        Ok(()) => Ok(Json(json!({ "ok": true }))),
+        Err(e) if e.contains("not found") => Err((StatusCode::NOT_FOUND, e)),
+        Err(e) => Err((StatusCode::CONFLICT, e)),
+    }
+}
+
 #[derive(Deserialize)]
 struct AcpSubmitJobBody {
     session_id: String,
@@ -2032,8 +2813,85 @@ async fn api_dashboard_db_stats(
     })))
 }
 
+#[derive(Debug, Deserialize)]
+struct DeliveriesQuery {
+    chat_id: Option<i64>,
+    status: Option<String>,
+    limit: Option<usize>,
+    offset: Option<usize>,
+}
+
+fn delivery_json(d: &crate::db::Delivery) -> serde_json::Value {
+    json!({
+        "id": d.id,
+        "chat_id": d.chat_id,
+        "channel": d.channel,
+        "content": d.content,
+        "status": d.status,
+        "error": d.error,
+        "retryable": d.retryable,
+        "attempts": d.attempts,
+        "created_at": d.created_at,
+        "updated_at": d.updated_at,
+    })
+}
+
+async fn api_deliveries(
+    headers: HeaderMap,
+    State(state): State<WebState>,
+    Query(query): Query<DeliveriesQuery>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    require_auth(&headers, state.auth_token.as_deref())?;
+    let chat_id = query.chat_id;
+    let status = query.status.clone();
+    let limit = query.limit.unwrap_or(50).min(200);
+    let offset = query.offset.unwrap_or(0);
+    let deliveries = call_blocking(state.app_state.db.clone(), move |db| {
+        db.get_deliveries(chat_id, status.as_deref(), limit, offset)
+    })
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(Json(json!({
+        "ok": true,
+        "limit": limit,
+        "offset": offset,
+        "deliveries": deliveries.iter().map(delivery_json).collect::<Vec<_>>(),
+    })))
+}
+
+#[derive(Debug, Deserialize)]
+struct RetryDeliveryBody {
+    /// Replacement text; the delivery is marked `edited` if it differs
+    text: Option<String>,
+}
+
+async fn api_retry_delivery(
+    headers: HeaderMap,
+    State(state): State<WebState>,
+    Path(delivery_id): Path<i64>,
+    body: Option<Json<RetryDeliveryBody>>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    require_auth(&headers, state.auth_token.as_deref())?;
+    let text = body.and_then(|Json(b)| b.text);
+    match crate::channel::retry_delivery(
+        &state.app_state.channel_registry,
+        state.app_state.db.clone(),
+        delivery_id,
+        text,
+    )
+    .await
+    {
+        Ok(delivery) => Ok(Json(json!({
+            "ok": delivery.status != "failed",
+            "delivery": delivery_json(&delivery),
+        }))),
+        Err(e) if e.contains("not found") => Err((StatusCode::NOT_FOUND, e)),
+        Err(e) => Err((StatusCode::CONFLICT, e)),
+    }
+}
+
 fn build_router(web_state: WebState) -> Router {
-    Router::new()
+    let router = Router::new()
         .route("/", get(index))
         .route("/assets/*file", get(asset_file))
         .route("/icon.svg", get(icon_file))
@@ -2044,10 +2902,18 @@ fn build_router(web_state: WebState) -> Router {
         .route("/api/sessions", get(api_sessions))
         .route("/api/history", get(api_history))
         .route("/api/usage", get(api_usage))
+        .route("/api/analytics", get(api_analytics))
         .route("/api/memory_observability", get(api_memory_observability))
         .route("/api/send", post(api_send))
         .route("/api/send_stream", post(api_send_stream))
         .route("/api/stream", get(api_stream))
+        .route("/api/voice/start", post(api_voice_start))
+        .route(
+            "/api/voice/chunk",
+            post(api_voice_chunk)
+                .layer(axum::extract::DefaultBodyLimit::max(MAX_VOICE_CHUNK_BYTES)),
+        )
+        .route("/api/voice/finish", post(api_voice_finish))
         .route("/api/run_status", get(api_run_status))
         .route("/api/reset", post(api_reset))
         .route("/api/delete_session", post(api_delete_session))
@@ -2063,6 +2929,11 @@ fn build_router(web_state: WebState) -> Router {
         )
         .route("/api/dashboard/memories", get(api_dashboard_memories))
         .route("/api/dashboard/db/stats", get(api_dashboard_db_stats))
+        // Outbound delivery receipts
+        .route("/api/deliveries", get(api_deliveries))
+        .route("/api/deliveries/:id/retry", post(api_retry_delivery))
+        .route("/api/email/inbound", post(api_email_inbound))
+        .route("/api/tasks.ics", get(api_tasks_ics))
         // ACP HTTP API
         .route("/api/acp/health", get(api_acp_health))
         .route("/api/acp/agents", get(api_acp_agents))
@@ -2071,14 +2942,20 @@ fn build_router(web_state: WebState) -> Router {
             get(api_acp_list_sessions).post(api_acp_create_session),
         )
         .route("/api/acp/sessions/:id/prompt", post(api_acp_prompt))
+        .route("/api/acp/sessions/:id/cancel", post(api_acp_cancel_prompt))
+        .route("/api/acp/sessions/:id/answer", post(api_acp_answer))
         .route(
             "/api/acp/sessions/:id/prompt/stream",
             post(api_acp_prompt_stream),
         )
         .route("/api/acp/sessions/:id", delete(api_acp_end_session))
         .route("/api/acp/jobs", post(api_acp_submit_job))
-        .route("/api/acp/jobs/:id", get(api_acp_job_status))
-        .with_state(web_state)
+        .route("/api/acp/jobs/:id", get(api_acp_job_status));
+    #[cfg(feature = "teams")]
+    let router = router.route("/api/teams/messages", post(api_teams_messages));
+    #[cfg(feature = "webhook")]
+    let router = router.route("/api/webhook/messages", post(api_webhook_messages));
+    router.with_state(web_state)
 }
 
 #[cfg(test)]
@@ -2096,6 +2973,14 @@ mod tests {
     use std::sync::atomic::{AtomicUsize, Ordering};
     use tower::ServiceExt;
 
+    #[test]
+    fn test_token_matches() {
+        assert!(token_matches(Some("s3cret"), "s3cret"));
+        assert!(!token_matches(Some("s3cre"), "s3cret"));
+        assert!(!token_matches(Some("S3cret"), "s3cret"));
+        assert!(!token_matches(None, "s3cret"));
+    }
+
     #[test]
     fn test_web_assets_embedded() {
         assert!(
@@ -2193,6 +3078,7 @@ mod tests {
                         id: "tool_1".into(),
                         name: "glob".into(),
                         input: json!({"pattern": "*.rs", "path": "."}),
+                        signature: None,
                     }],
                     stop_reason: Some("tool_use".into()),
                     usage: None,
@@ -2230,19 +3116,35 @@ mod tests {
             control_chat_ids: vec![],
             max_session_messages: 40,
             compact_keep_recent: 20,
+            session_idle_expiry_hours: 0,
+            session_expiry_summary: true,
             discord_bot_token: None,
             discord_allowed_channels: vec![],
             show_thinking: false,
+            thinking: crate::config::ThinkingConfig::default(),
+            inference: crate::config::InferenceParams::default(),
+            chat_inference: std::collections::HashMap::new(),
+            openrouter: crate::config::OpenRouterConfig::default(),
+            secret_patterns: vec![],
+            news_search: crate::config::NewsSearchConfig::default(),
+            maps: crate::config::MapsConfig::default(),
+            package_tracking: crate::config::PackageTrackingConfig::default(),
+            flight_status: crate::config::FlightStatusConfig::default(),
+            outbound_http: crate::config::OutboundHttpConfig::default(),
+            browsing_profiles_key: None,
+            tool_output_budgets: std::collections::HashMap::new(),
             web_enabled: true,
             web_host: "127.0.0.1".into(),
             web_port: 3900,
             web_auth_token: None,
+            web_public_url: None,
             web_max_inflight_per_session: 2,
             web_max_requests_per_window: 8,
             web_rate_window_seconds: 10,
             web_run_history_limit: 512,
             web_session_idle_ttl_seconds: 300,
             model_prices: vec![],
+            model_limits: vec![],
             embedding_provider: None,
             embedding_api_key: None,
             embedding_base_url: None,
@@ -2255,8 +3157,43 @@ mod tests {
             aws_secret_access_key: None,
             aws_session_token: None,
             aws_profile: None,
+            aws_bedrock_inference_profile: None,
+            aws_bedrock_inference_profiles: std::collections::HashMap::new(),
+            aws_bedrock_latency: None,
+            aws_endpoint_url: None,
+            aws_use_fips_endpoint: false,
+            ollama_keep_alive: None,
+            email_gateway: None,
             soul_path: None,
+            system_prompt_template: None,
             skip_tool_approval: false,
+            tool_approval_timeout_secs: 300,
+            workspace_quota_mb: 0,
+            workspace_tmp_ttl_hours: 0,
+            workspace_cleanup_interval_mins: 60,
+            image_max_dimension: 1568,
+            image_jpeg_quality: 85,
+            chart_font_path: None,
+            tts_model: "tts-1".into(),
+            tts_voice: "alloy".into(),
+            stt_backend: "openai".into(),
+            whisper_cpp_binary: "whisper-cli".into(),
+            whisper_cpp_model: None,
+            max_concurrent_agent_runs: 8,
+            outage_queue_size: 50,
+            outage_queue_ttl_mins: 60,
+            message_debounce_ms: 0,
+            digest_dedup_days: 14,
+            llm_max_retries: 3,
+            llm_retry_max_delay_secs: 60,
+            llm_trace: false,
+            startup_preflight: true,
+            coordination_redis_url: None,
+            coordination_key_prefix: "rayclaw".into(),
+            cache_backend: "memory".into(),
+            queue_backend: "memory".into(),
+            response_cache_ttl_secs: 0,
+            provider_credentials: std::collections::HashMap::new(),
             skills_dir: None,
             channels: std::collections::HashMap::new(),
             prompt_cache_ttl: "none".into(),
@@ -2281,7 +3218,15 @@ mod tests {
             embedding: None,
             tools: ToolRegistry::new(&cfg, channel_registry, db),
             acp_manager: std::sync::Arc::new(crate::acp::AcpManager::from_config_file("")),
+            tool_approvals: crate::approvals::ToolApprovals::new(std::time::Duration::from_secs(
+                300,
+            )),
             chat_locks: tokio::sync::Mutex::new(std::collections::HashMap::new()),
+            chat_debounce: tokio::sync::Mutex::new(std::collections::HashMap::new()),
+            chat_llms: tokio::sync::Mutex::new(std::collections::HashMap::new()),
+            inbound_queue: crate::inbound_queue::InboundQueue::new(0),
+            outage: crate::outage::OutageQueue::new(0, std::time::Duration::ZERO),
+            coordinator: crate::coordination::Coordinator::local(),
         };
         Arc::new(state)
     }
@@ -2298,6 +3243,7 @@ mod tests {
             run_hub: RunHub::default(),
             session_hub: SessionHub::default(),
             request_hub: RequestHub::default(),
+            voice_hub: VoiceHub::default(),
             limits,
         }
     }
@@ -2338,6 +3284,81 @@ mod tests {
         assert!(text.contains("event: done"));
     }
 
+    #[tokio::test]
+    async fn test_voice_start_requires_openai_key() {
+        let web_state = test_web_state(Box::new(DummyLlm), None, WebLimits::default());
+        let app = build_router(web_state);
+
+        let req = Request::builder()
+            .method("POST")
+            .uri("/api/voice/start")
+            .header("content-type", "application/json")
+            .body(Body::from(r#"{"session_key":"main"}"#))
+            .unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_voice_finish_unknown_turn() {
+        let web_state = test_web_state(Box::new(DummyLlm), None, WebLimits::default());
+        let app = build_router(web_state);
+
+        let req = Request::builder()
+            .method("POST")
+            .uri("/api/voice/finish")
+            .header("content-type", "application/json")
+            .body(Body::from(r#"{"voice_id":"missing"}"#))
+            .unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_voice_finish_orders_transcript_and_starts_run() {
+        let web_state = test_web_state(Box::new(DummyLlm), None, WebLimits::default());
+        web_state.voice_hub.turns.lock().await.insert(
+            "v1".into(),
+            VoiceTurn {
+                session_key: "main".into(),
+                sender_name: Some("u".into()),
+                transcript: vec![(1, "world".into()), (0, "hello".into())],
+                next_seq: 2,
+                last_touch: Instant::now(),
+            },
+        );
+        let app = build_router(web_state.clone());
+
+        let req = Request::builder()
+            .method("POST")
+            .uri("/api/voice/finish")
+            .header("content-type", "application/json")
+            .body(Body::from(r#"{"voice_id":"v1","speak":false}"#))
+            .unwrap();
+        let resp = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(v["transcript"], "hello world");
+        let run_id = v["run_id"].as_str().unwrap();
+        assert!(web_state.voice_hub.turns.lock().await.is_empty());
+
+        let req2 = Request::builder()
+            .method("GET")
+            .uri(format!("/api/stream?run_id={run_id}"))
+            .body(Body::empty())
+            .unwrap();
+        let resp2 = app.oneshot(req2).await.unwrap();
+        let bytes = axum::body::to_bytes(resp2.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let text = String::from_utf8_lossy(&bytes);
+        assert!(text.contains("event: done"));
+        assert!(!text.contains("event: audio"));
+    }
+
     #[tokio::test]
     async fn test_auth_failure_requires_header() {
         let web_state = test_web_state(
@@ -2568,6 +3589,28 @@ mod tests {
         assert_eq!(resp3.status(), StatusCode::OK);
     }
 
+    #[tokio::test]
+    async fn test_request_hub_falls_back_when_redis_is_down() {
+        let mut config: Config = serde_yaml::from_str(
+            "telegram_bot_token: tok\nbot_username: bot\napi_key: key\ncoordination_redis_url: redis://127.0.0.1:1/0\ncache_backend: redis\n",
+        )
+        .unwrap();
+        config.post_deserialize().unwrap();
+        let hub = RequestHub::new(SharedRateLimiter::from_config(&config));
+        assert!(hub.shared.is_some());
+        let limits = WebLimits {
+            max_inflight_per_session: 1,
+            ..WebLimits::default()
+        };
+
+        let admitted = hub.begin("main", &limits).await.unwrap();
+        assert_eq!(admitted, Admitted::Local);
+        let (status, _) = hub.begin("main", &limits).await.unwrap_err();
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+        hub.end_with_limits("main", &limits, admitted).await;
+        hub.begin("main", &limits).await.unwrap();
+    }
+
     #[tokio::test]
     async fn test_api_usage_returns_report() {
         let web_state = test_web_state(Box::new(DummyLlm), None, WebLimits::default());
@@ -2610,6 +3653,62 @@ mod tests {
         assert!(mem.unwrap().contains_key("total"));
     }
 
+    #[tokio::test]
+    async fn test_api_analytics() {
+        let web_state = test_web_state(Box::new(DummyLlm), None, WebLimits::default());
+        let db = web_state.app_state.db.clone();
+        call_blocking(db, |d| {
+            d.upsert_chat(123, Some("main"), "web")?;
+            d.log_tool_call(123, "bash", false, 50)?;
+            d.log_llm_usage(
+                123,
+                "web",
+                "anthropic",
+                "claude-test",
+                100,
+                20,
+                "agent_loop",
+            )?;
+            Ok(())
+        })
+        .await
+        .unwrap();
+        let app = build_router(web_state);
+
+        let req = Request::builder()
+            .method("GET")
+            .uri("/api/analytics?session_key=main&days=3")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(v["analytics"]["daily"].as_array().map(Vec::len), Some(3));
+        assert_eq!(v["analytics"]["tokens"]["total_tokens"], 120);
+        assert_eq!(v["analytics"]["tools"][0]["name"], "bash");
+        assert!(v["report"].as_str().unwrap().contains("Chat analytics"));
+
+        let req = Request::builder()
+            .method("GET")
+            .uri("/api/analytics?session_key=main&format=png")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()["content-type"], "image/png");
+
+        let req = Request::builder()
+            .method("GET")
+            .uri("/api/analytics?session_key=main&days=0")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
     #[tokio::test]
     async fn test_api_memory_observability_returns_series() {
         let web_state = test_web_state(Box::new(DummyLlm), None, WebLimits::default());
@@ -2721,6 +3820,85 @@ mod tests {
     // ACP API tests
     // -----------------------------------------------------------------------
 
+    #[tokio::test]
+    async fn test_deliveries_list_and_retry() {
+        let web_state = test_web_state(Box::new(DummyLlm), None, WebLimits::default());
+        let db = web_state.app_state.db.clone();
+        let id = db.create_delivery(42, "web", "hello").unwrap();
+        db.record_delivery_attempt(id, "failed", Some("timed out"), true, None)
+            .unwrap();
+        let app = build_router(web_state);
+
+        let resp = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/api/deliveries?status=failed")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(
+            &axum::body::to_bytes(resp.into_body(), 1 << 16)
+                .await
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(body["deliveries"][0]["id"], id);
+        assert_eq!(body["deliveries"][0]["retryable"], true);
+
+        let retry = |body: serde_json::Value| {
+            Request::builder()
+                .method("POST")
+                .uri(format!("/api/deliveries/{id}/retry"))
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+        let resp = app
+            .clone()
+            .oneshot(retry(json!({"text": "hello again"})))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(
+            &axum::body::to_bytes(resp.into_body(), 1 << 16)
+                .await
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(body["delivery"]["status"], "edited");
+        assert_eq!(body["delivery"]["content"], "hello again");
+        assert_eq!(body["delivery"]["attempts"], 2);
+
+        // Only failed deliveries can be retried
+        let resp = app.oneshot(retry(json!({}))).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::CONFLICT);
+    }
+
+    #[cfg(feature = "webhook")]
+    #[tokio::test]
+    async fn test_webhook_messages_not_configured() {
+        let web_state = test_web_state(Box::new(DummyLlm), None, WebLimits::default());
+        let app = build_router(web_state);
+        let resp = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/webhook/messages?token=tok")
+                    .header("content-type", "application/json")
+                    .body(Body::from(
+                        json!({"chat_id": "a", "text": "hi"}).to_string(),
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
     #[tokio::test]
     async fn test_acp_health_no_auth() {
         let web_state = test_web_state(Box::new(DummyLlm), None, WebLimits::default());
@@ -2864,6 +4042,35 @@ mod tests {
         assert_eq!(resp.status(), StatusCode::NOT_FOUND);
     }
 
+    #[tokio::test]
+    async fn test_acp_cancel_prompt_not_found() {
+        let web_state = test_web_state(Box::new(DummyLlm), None, WebLimits::default());
+        let app = build_router(web_state);
+
+        let req = Request::builder()
+            .method("POST")
+            .uri("/api/acp/sessions/nonexistent/cancel")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_acp_answer_not_found() {
+        let web_state = test_web_state(Box::new(DummyLlm), None, WebLimits::default());
+        let app = build_router(web_state);
+
+        let req = Request::builder()
+            .method("POST")
+            .uri("/api/acp/sessions/nonexistent/answer")
+            .header("content-type", "application/json")
+            .body(Body::from(r#"{"answer":"yes"}"#))
+            .unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
     #[tokio::test]
     async fn test_acp_job_status_not_found() {
         let web_state = test_web_state(Box::new(DummyLlm), None, WebLimits::default());
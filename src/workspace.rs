@@ -0,0 +1,311 @@
+//! Per-chat workspace accounting and cleanup.
+//!
+//! Workspaces live under `working_dir`:
+//! - `shared/` when `working_dir_isolation: shared`
+//! - `chat/<channel>/<chat>/` when `working_dir_isolation: chat`
+//! - `uploads/<channel>/<chat>/` for attachments saved by channel adapters
+//!
+//! "Temp files" are uploaded attachments and anything below a `tmp/` directory
+//! inside a workspace; they are removed once older than `workspace_tmp_ttl_hours`.
+//! Each workspace is then trimmed to `workspace_quota_mb` by deleting the
+//! least-recently-used files first.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WorkspaceUsage {
+    pub total_bytes: u64,
+    pub file_count: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CleanupReport {
+    pub files_removed: usize,
+    pub bytes_freed: u64,
+}
+
+impl CleanupReport {
+    fn merge(&mut self, other: CleanupReport) {
+        self.files_removed += other.files_removed;
+        self.bytes_freed += other.bytes_freed;
+    }
+}
+
+struct FileEntry {
+    path: PathBuf,
+    size: u64,
+    last_used: SystemTime,
+    is_temp: bool,
+}
+
+/// Recursively list regular files below `dir` without following symlinks.
+fn collect_files(dir: &Path, in_tmp: bool, out: &mut Vec<FileEntry>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        let path = entry.path();
+        if file_type.is_dir() {
+            let child_tmp = in_tmp || entry.file_name() == "tmp";
+            collect_files(&path, child_tmp, out);
+        } else if file_type.is_file() {
+            let Ok(meta) = entry.metadata() else {
+                continue;
+            };
+            let modified = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            // atime is often disabled (noatime); never treat it as older than mtime.
+            let last_used = meta.accessed().map(|a| a.max(modified)).unwrap_or(modified);
+            out.push(FileEntry {
+                path,
+                size: meta.len(),
+                last_used,
+                is_temp: in_tmp,
+            });
+        }
+    }
+}
+
+pub fn dir_usage(dir: &Path) -> WorkspaceUsage {
+    let mut files = Vec::new();
+    collect_files(dir, false, &mut files);
+    WorkspaceUsage {
+        total_bytes: files.iter().map(|f| f.size).sum(),
+        file_count: files.len(),
+    }
+}
+
+/// Enumerate every per-chat workspace (plus `shared/`) below `working_dir`.
+/// Returned paths are `(label, path, all_temp)`; upload dirs are entirely temp.
+pub fn list_workspaces(working_dir: &Path) -> Vec<(String, PathBuf, bool)> {
+    let mut out = Vec::new();
+    let shared = working_dir.join("shared");
+    if shared.is_dir() {
+        out.push(("shared".to_string(), shared, false));
+    }
+    for (root, all_temp) in [("chat", false), ("uploads", true)] {
+        let Ok(channels) = std::fs::read_dir(working_dir.join(root)) else {
+            continue;
+        };
+        for channel in channels.flatten() {
+            if !channel.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+                continue;
+            }
+            let Ok(chats) = std::fs::read_dir(channel.path()) else {
+                continue;
+            };
+            for chat in chats.flatten() {
+                if chat.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+                    let label = format!(
+                        "{root}/{}/{}",
+                        channel.file_name().to_string_lossy(),
+                        chat.file_name().to_string_lossy()
+                    );
+                    out.push((label, chat.path(), all_temp));
+                }
+            }
+        }
+    }
+    out
+}
+
+fn remove_file(entry: &FileEntry, report: &mut CleanupReport) -> bool {
+    match std::fs::remove_file(&entry.path) {
+        Ok(()) => {
+            report.files_removed += 1;
+            report.bytes_freed += entry.size;
+            true
+        }
+        Err(e) => {
+            tracing::warn!(
+                "Workspace cleaner: failed to remove '{}': {}",
+                entry.path.display(),
+                e
+            );
+            false
+        }
+    }
+}
+
+/// Remove empty directories below `dir` (but never `dir` itself).
+fn prune_empty_dirs(dir: &Path) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        if entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+            let path = entry.path();
+            prune_empty_dirs(&path);
+            let _ = std::fs::remove_dir(&path);
+        }
+    }
+}
+
+/// Apply the temp-file TTL and the size cap to a single workspace.
+/// `tmp_ttl` of `None` and `max_bytes` of 0 disable the respective pass.
+pub fn clean_workspace(
+    dir: &Path,
+    all_temp: bool,
+    tmp_ttl: Option<Duration>,
+    max_bytes: u64,
+    now: SystemTime,
+) -> CleanupReport {
+    let mut report = CleanupReport::default();
+    let mut files = Vec::new();
+    collect_files(dir, all_temp, &mut files);
+
+    if let Some(ttl) = tmp_ttl {
+        files.retain(|f| {
+            let expired = f.is_temp
+                && now
+                    .duration_since(f.last_used)
+                    .map(|age| age > ttl)
+                    .unwrap_or(false);
+            !(expired && remove_file(f, &mut report))
+        });
+    }
+
+    if max_bytes > 0 {
+        let mut total: u64 = files.iter().map(|f| f.size).sum();
+        if total > max_bytes {
+            files.sort_by_key(|f| f.last_used);
+            for f in &files {
+                if total <= max_bytes {
+                    break;
+                }
+                if remove_file(f, &mut report) {
+                    total = total.saturating_sub(f.size);
+                }
+            }
+        }
+    }
+
+    if report.files_removed > 0 {
+        prune_empty_dirs(dir);
+    }
+    report
+}
+
+/// Run one cleanup pass over every workspace below `working_dir`.
+pub fn clean_all_workspaces(
+    working_dir: &Path,
+    tmp_ttl: Option<Duration>,
+    max_bytes: u64,
+) -> CleanupReport {
+    let now = SystemTime::now();
+    let mut total = CleanupReport::default();
+    for (label, dir, all_temp) in list_workspaces(working_dir) {
+        let report = clean_workspace(&dir, all_temp, tmp_ttl, max_bytes, now);
+        if report.files_removed > 0 {
+            tracing::info!(
+                "Workspace cleaner: {} removed {} file(s), freed {} bytes",
+                label,
+                report.files_removed,
+                report.bytes_freed
+            );
+        }
+        total.merge(report);
+    }
+    total
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rayclaw_ws_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write(path: &Path, bytes: usize) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, vec![b'x'; bytes]).unwrap();
+    }
+
+    #[test]
+    fn test_dir_usage_counts_nested_files() {
+        let dir = temp_dir();
+        write(&dir.join("a.txt"), 10);
+        write(&dir.join("sub/b.txt"), 20);
+        let usage = dir_usage(&dir);
+        assert_eq!(usage.total_bytes, 30);
+        assert_eq!(usage.file_count, 2);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_list_workspaces() {
+        let dir = temp_dir();
+        std::fs::create_dir_all(dir.join("shared")).unwrap();
+        std::fs::create_dir_all(dir.join("chat/telegram/42")).unwrap();
+        std::fs::create_dir_all(dir.join("uploads/telegram/42")).unwrap();
+        let mut labels: Vec<_> = list_workspaces(&dir)
+            .into_iter()
+            .map(|(l, _, temp)| (l, temp))
+            .collect();
+        labels.sort();
+        assert_eq!(
+            labels,
+            vec![
+                ("chat/telegram/42".to_string(), false),
+                ("shared".to_string(), false),
+                ("uploads/telegram/42".to_string(), true),
+            ]
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_clean_workspace_ttl_only_touches_tmp() {
+        let dir = temp_dir();
+        write(&dir.join("keep.txt"), 5);
+        write(&dir.join("tmp/old.bin"), 5);
+        let later = SystemTime::now() + Duration::from_secs(3600);
+        let report = clean_workspace(&dir, false, Some(Duration::from_secs(60)), 0, later);
+        assert_eq!(report.files_removed, 1);
+        assert!(dir.join("keep.txt").exists());
+        assert!(!dir.join("tmp").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_clean_workspace_quota_removes_lru_first() {
+        let dir = temp_dir();
+        write(&dir.join("old.bin"), 60);
+        std::thread::sleep(Duration::from_millis(20));
+        write(&dir.join("new.bin"), 60);
+        let report = clean_workspace(&dir, false, None, 100, SystemTime::now());
+        assert_eq!(report.files_removed, 1);
+        assert_eq!(report.bytes_freed, 60);
+        assert!(!dir.join("old.bin").exists());
+        assert!(dir.join("new.bin").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(2048), "2.0 KB");
+        assert_eq!(format_bytes(5 * 1024 * 1024), "5.0 MB");
+    }
+}
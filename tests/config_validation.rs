@@ -26,19 +26,35 @@ fn minimal_config() -> Config {
         control_chat_ids: vec![],
         max_session_messages: 40,
         compact_keep_recent: 20,
+        session_idle_expiry_hours: 0,
+        session_expiry_summary: true,
         discord_bot_token: None,
         discord_allowed_channels: vec![],
         show_thinking: false,
+        thinking: rayclaw::config::ThinkingConfig::default(),
+        inference: rayclaw::config::InferenceParams::default(),
+        chat_inference: std::collections::HashMap::new(),
+        openrouter: rayclaw::config::OpenRouterConfig::default(),
+        secret_patterns: vec![],
+        news_search: rayclaw::config::NewsSearchConfig::default(),
+        maps: rayclaw::config::MapsConfig::default(),
+        package_tracking: rayclaw::config::PackageTrackingConfig::default(),
+        flight_status: rayclaw::config::FlightStatusConfig::default(),
+        outbound_http: rayclaw::config::OutboundHttpConfig::default(),
+        browsing_profiles_key: None,
+        tool_output_budgets: std::collections::HashMap::new(),
         web_enabled: false,
         web_host: "127.0.0.1".into(),
         web_port: 3900,
         web_auth_token: None,
+        web_public_url: None,
         web_max_inflight_per_session: 2,
         web_max_requests_per_window: 8,
         web_rate_window_seconds: 10,
         web_run_history_limit: 512,
         web_session_idle_ttl_seconds: 300,
         model_prices: vec![],
+        model_limits: vec![],
         embedding_provider: None,
         embedding_api_key: None,
         embedding_base_url: None,
@@ -47,12 +63,47 @@ fn minimal_config() -> Config {
         reflector_enabled: true,
         reflector_interval_mins: 15,
         soul_path: None,
+        system_prompt_template: None,
         skip_tool_approval: false,
+        tool_approval_timeout_secs: 300,
         aws_region: None,
         aws_access_key_id: None,
         aws_secret_access_key: None,
         aws_session_token: None,
         aws_profile: None,
+        aws_bedrock_inference_profile: None,
+        aws_bedrock_inference_profiles: std::collections::HashMap::new(),
+        aws_bedrock_latency: None,
+        aws_endpoint_url: None,
+        aws_use_fips_endpoint: false,
+        ollama_keep_alive: None,
+        email_gateway: None,
+        workspace_quota_mb: 0,
+        workspace_tmp_ttl_hours: 0,
+        workspace_cleanup_interval_mins: 60,
+        image_max_dimension: 1568,
+        image_jpeg_quality: 85,
+        chart_font_path: None,
+        tts_model: "tts-1".into(),
+        tts_voice: "alloy".into(),
+        stt_backend: "openai".into(),
+        whisper_cpp_binary: "whisper-cli".into(),
+        whisper_cpp_model: None,
+        max_concurrent_agent_runs: 8,
+        outage_queue_size: 50,
+        outage_queue_ttl_mins: 60,
+        message_debounce_ms: 0,
+        digest_dedup_days: 14,
+        llm_max_retries: 3,
+        llm_retry_max_delay_secs: 60,
+        llm_trace: false,
+        startup_preflight: true,
+        coordination_redis_url: None,
+        coordination_key_prefix: "rayclaw".into(),
+        cache_backend: "memory".into(),
+        queue_backend: "memory".into(),
+        response_cache_ttl_secs: 0,
+        provider_credentials: std::collections::HashMap::new(),
         skills_dir: None,
         channels: std::collections::HashMap::new(),
     }
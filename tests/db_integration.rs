@@ -29,6 +29,7 @@ fn test_message_full_lifecycle() {
             content: format!("chat1 message {i}"),
             is_from_bot: false,
             timestamp: format!("2024-01-01T00:00:{:02}Z", i),
+            thread_id: None,
         })
         .unwrap();
     }
@@ -40,6 +41,7 @@ fn test_message_full_lifecycle() {
             content: format!("chat2 message {i}"),
             is_from_bot: false,
             timestamp: format!("2024-01-01T00:00:{:02}Z", i),
+            thread_id: None,
         })
         .unwrap();
     }
@@ -73,32 +75,32 @@ fn test_session_lifecycle() {
     let (db, dir) = test_db();
 
     // No session initially
-    assert!(db.load_session(100).unwrap().is_none());
+    assert!(db.load_session(100, None).unwrap().is_none());
 
     // Save session
     let json1 = r#"[{"role":"user","content":"hello"}]"#;
-    db.save_session(100, json1).unwrap();
+    db.save_session(100, None, json1).unwrap();
 
     // Load and verify
-    let (loaded, ts1) = db.load_session(100).unwrap().unwrap();
+    let (loaded, ts1) = db.load_session(100, None).unwrap().unwrap();
     assert_eq!(loaded, json1);
     assert!(!ts1.is_empty());
 
     // Update session (upsert)
     std::thread::sleep(std::time::Duration::from_millis(10));
     let json2 = r#"[{"role":"user","content":"hello"},{"role":"assistant","content":"hi"}]"#;
-    db.save_session(100, json2).unwrap();
+    db.save_session(100, None, json2).unwrap();
 
-    let (loaded2, ts2) = db.load_session(100).unwrap().unwrap();
+    let (loaded2, ts2) = db.load_session(100, None).unwrap().unwrap();
     assert_eq!(loaded2, json2);
     assert!(ts2 >= ts1); // updated_at should be newer
 
     // Sessions are per-chat isolated
-    assert!(db.load_session(200).unwrap().is_none());
+    assert!(db.load_session(200, None).unwrap().is_none());
 
     // Delete
     assert!(db.delete_session(100).unwrap());
-    assert!(db.load_session(100).unwrap().is_none());
+    assert!(db.load_session(100, None).unwrap().is_none());
     assert!(!db.delete_session(100).unwrap()); // already gone
 
     cleanup(&dir);
@@ -264,13 +266,14 @@ fn test_catch_up_query_complex() {
             content: content.to_string(),
             is_from_bot: *is_bot,
             timestamp: ts.to_string(),
+            thread_id: None,
         })
         .unwrap();
     }
 
     // Should get bot message + everything after it
     let catchup = db
-        .get_messages_since_last_bot_response(100, 50, 50)
+        .get_messages_since_last_bot_response(100, None, 50, 50)
         .unwrap();
     assert!(catchup.len() >= 3); // at least bot msg + 3 after
     assert_eq!(catchup[0].id, "m3"); // starts with bot msg
@@ -300,13 +303,14 @@ fn test_new_user_messages_since() {
             content: content.to_string(),
             is_from_bot: *is_bot,
             timestamp: ts.to_string(),
+            thread_id: None,
         })
         .unwrap();
     }
 
     // Only non-bot messages after the cutoff
     let new_msgs = db
-        .get_new_user_messages_since(100, "2024-01-01T00:00:03Z")
+        .get_new_user_messages_since(100, None, "2024-01-01T00:00:03Z")
         .unwrap();
     // m3 is at exactly 03Z, but query is >, so only m5 (timestamp > 03Z and not bot)
     assert_eq!(new_msgs.len(), 1);
@@ -331,6 +335,7 @@ fn test_chat_and_messages_together() {
         content: "hello".into(),
         is_from_bot: false,
         timestamp: "2024-01-01T00:00:00Z".into(),
+        thread_id: None,
     })
     .unwrap();
 
@@ -15,6 +15,7 @@ fn test_auth_context_control_chat() {
         caller_channel: "telegram".into(),
         caller_chat_id: 100,
         control_chat_ids: vec![100, 200],
+        caller_thread_id: None,
     };
     assert!(auth.is_control_chat());
     assert!(auth.can_access_chat(999)); // control can access any chat
@@ -26,6 +27,7 @@ fn test_auth_context_regular_chat() {
         caller_channel: "telegram".into(),
         caller_chat_id: 300,
         control_chat_ids: vec![100, 200],
+        caller_thread_id: None,
     };
     assert!(!auth.is_control_chat());
     assert!(auth.can_access_chat(300)); // can access own chat
@@ -38,6 +40,7 @@ fn test_auth_context_empty_control_list() {
         caller_channel: "telegram".into(),
         caller_chat_id: 100,
         control_chat_ids: vec![],
+        caller_thread_id: None,
     };
     assert!(!auth.is_control_chat());
     assert!(auth.can_access_chat(100)); // can access own
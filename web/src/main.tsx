@@ -73,6 +73,9 @@ type ToolResultPayload = {
   bytes?: number
   status_code?: number
   error_type?: string
+  truncated?: boolean
+  produced_files?: string[]
+  suggested_next_tools?: string[]
 }
 
 type Appearance = 'dark' | 'light'
@@ -413,6 +416,8 @@ function ToolCallCard(props: ToolCallMessagePartProps) {
   const bytes = result.bytes
   const statusCode = result.status_code
   const errorType = result.error_type
+  const truncated = result.truncated === true
+  const producedFiles = Array.isArray(result.produced_files) ? result.produced_files : []
 
   return (
     <div className="rc-tool-card">
@@ -431,6 +436,8 @@ function ToolCallCard(props: ToolCallMessagePartProps) {
           {typeof bytes === 'number' ? <span>{bytes}b</span> : null}
           {typeof statusCode === 'number' ? <span>HTTP {statusCode}</span> : null}
           {typeof errorType === 'string' && errorType ? <span>{errorType}</span> : null}
+          {truncated ? <span>truncated</span> : null}
+          {producedFiles.length > 0 ? <span>files: {producedFiles.join(', ')}</span> : null}
         </div>
       ) : null}
       {output !== undefined ? <pre className="rc-tool-card-pre">{formatUnknown(output)}</pre> : null}
@@ -818,6 +825,9 @@ function App() {
                 bytes: payload.bytes ?? null,
                 status_code: payload.status_code ?? null,
                 error_type: payload.error_type ?? null,
+                truncated: payload.truncated ?? false,
+                produced_files: payload.produced_files ?? [],
+                suggested_next_tools: payload.suggested_next_tools ?? [],
               })
 
               toolState.set(payload.tool_use_id, {